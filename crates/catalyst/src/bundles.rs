@@ -0,0 +1,730 @@
+//! Builders spawning the component sets of common engine objects, so games don't have
+//! to know which components each of them needs:
+//!
+//! ```ignore
+//! let cube = asset_server.add_mesh("cube", primitives::cube(1.0));
+//! let material = asset_server.create_material("crate", |m| m.roughness(0.8));
+//! PropBundle::new(cube, material)
+//!     .with_transform(Transform::from_xyz(0.0, 5.0, 0.0))
+//!     .dynamic(10.0)
+//!     .spawn(world)?;
+//! ```
+//!
+//! `spawn` checks the builder's values first and spawns nothing when one is off.
+//! The tests below list the exact components each bundle adds.
+
+use catalyst_assets::{
+    MaterialDefinition, MeshDefinition,
+    assets::{Handle, MeshData},
+    material::MaterialData,
+};
+use catalyst_core::{
+    camera::Camera,
+    color::Color,
+    light::{DirectionalLight, PointLight},
+    movement::MovementIntent,
+    physics::{ColliderDefinition, ColliderShape, PhysicsBody, RigidBodyDefinition},
+    player::PlayerIndex,
+    transform::{GlobalTransform, Transform},
+};
+use flecs_ecs::prelude::*;
+use glam::{Quat, Vec3};
+use thiserror::Error;
+
+/// Why a bundle wasn't spawned.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum BundleError {
+    #[error("{0} transform is not finite or has a zero scale")]
+    NonFiniteTransform(&'static str),
+    #[error("{name} must be positive and finite, got {value}")]
+    NotPositive { name: &'static str, value: f32 },
+    #[error("{0}")]
+    Invalid(&'static str),
+}
+
+fn positive(name: &'static str, value: f32) -> Result<(), BundleError> {
+    if value > 0.0 && value.is_finite() {
+        Ok(())
+    } else {
+        Err(BundleError::NotPositive { name, value })
+    }
+}
+
+fn finite(name: &'static str, transform: &Transform) -> Result<(), BundleError> {
+    let finite = transform.translation.is_finite()
+        && transform.rotation.is_finite()
+        && transform.scale.is_finite();
+    // A zero scale collapses the entity, and its collider can't be built
+    if finite && transform.scale.cmpne(Vec3::ZERO).all() {
+        Ok(())
+    } else {
+        Err(BundleError::NonFiniteTransform(name))
+    }
+}
+
+fn validate_shape(shape: &ColliderShape) -> Result<(), BundleError> {
+    match shape {
+        ColliderShape::Box { hx, hy, hz } => {
+            positive("box half extent x", *hx)?;
+            positive("box half extent y", *hy)?;
+            positive("box half extent z", *hz)
+        }
+        ColliderShape::Sphere { radius } => positive("sphere radius", *radius),
+        ColliderShape::Capsule { radius, height } => {
+            positive("capsule radius", *radius)?;
+            positive("capsule height", *height)
+        }
+        ColliderShape::Convex { vertices } if vertices.len() < 4 => Err(BundleError::Invalid(
+            "a convex collider needs at least 4 vertices",
+        )),
+        ColliderShape::Mesh { indices, .. } if indices.is_empty() || indices.len() % 3 != 0 => Err(
+            BundleError::Invalid("a mesh collider needs whole triangles"),
+        ),
+        ColliderShape::Heightfield {
+            rows,
+            columns,
+            heights,
+            size,
+        } => {
+            positive("heightfield width", size.x)?;
+            positive("heightfield depth", size.y)?;
+            if *rows < 2 || *columns < 2 || heights.len() != (*rows * *columns) as usize {
+                return Err(BundleError::Invalid(
+                    "a heightfield needs rows * columns heights, at least 2 of each",
+                ));
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+// Body on the entity, collider on a child at `offset`, the way the physics plugin
+// expects them
+fn spawn_collider(world: &World, body: Entity, shape: ColliderShape, offset: Transform) {
+    world
+        .entity()
+        .child_of(body)
+        .set(offset)
+        .set(ColliderDefinition {
+            shape,
+            ..Default::default()
+        });
+}
+
+/// A mesh drawn with a material, optionally a physics body with a collider.
+///
+/// Adds Transform, GlobalTransform, MeshDefinition and MaterialDefinition. `dynamic` and
+/// `fixed` add a RigidBodyDefinition and a child entity with the ColliderDefinition,
+/// a unit box unless `collider` says otherwise (it matches `primitives::cube(1.0)`).
+#[derive(Clone, Debug)]
+pub struct PropBundle {
+    mesh: Handle<MeshData>,
+    material: Handle<MaterialData>,
+    transform: Transform,
+    body: Option<RigidBodyDefinition>,
+    collider: ColliderShape,
+}
+
+impl PropBundle {
+    pub fn new(mesh: Handle<MeshData>, material: Handle<MaterialData>) -> Self {
+        Self {
+            mesh,
+            material,
+            transform: Transform::default(),
+            body: None,
+            collider: ColliderDefinition::default().shape,
+        }
+    }
+
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    pub fn at(mut self, translation: Vec3) -> Self {
+        self.transform.translation = translation;
+        self
+    }
+
+    /// Moved by the physics simulation, `mass` kilograms on top of its collider's.
+    pub fn dynamic(mut self, mass: f32) -> Self {
+        self.body = Some(RigidBodyDefinition {
+            body_type: PhysicsBody::Dynamic,
+            mass: Some(mass),
+            ..Default::default()
+        });
+        self
+    }
+
+    /// Collides but never moves: floors, walls.
+    pub fn fixed(mut self) -> Self {
+        self.body = Some(RigidBodyDefinition {
+            body_type: PhysicsBody::Static,
+            ..Default::default()
+        });
+        self
+    }
+
+    /// Shape of the collider of a `dynamic` or `fixed` prop, scaled by the transform.
+    pub fn collider(mut self, shape: ColliderShape) -> Self {
+        self.collider = shape;
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), BundleError> {
+        finite("prop", &self.transform)?;
+        if let Some(body) = &self.body {
+            if let Some(mass) = body.mass {
+                positive("mass", mass)?;
+            }
+            validate_shape(&self.collider)?;
+        }
+        Ok(())
+    }
+
+    pub fn spawn<'a>(self, world: &'a World) -> Result<EntityView<'a>, BundleError> {
+        self.validate()?;
+        let entity = world
+            .entity()
+            .set(self.transform)
+            .set(GlobalTransform::default())
+            .set(MeshDefinition(self.mesh))
+            .set(MaterialDefinition(self.material));
+        if let Some(body) = self.body {
+            entity.set(body);
+            spawn_collider(world, entity.id(), self.collider, Transform::default());
+        }
+        Ok(entity)
+    }
+}
+
+/// A perspective camera. Adds Camera, Transform and GlobalTransform, and PlayerIndex
+/// with `player`.
+#[derive(Clone, Debug)]
+pub struct CameraBundle {
+    camera: Camera,
+    transform: Transform,
+    target: Option<Vec3>,
+    player: Option<PlayerIndex>,
+}
+
+impl CameraBundle {
+    /// `fov` is the vertical field of view in radians.
+    pub fn perspective(fov: f32) -> Self {
+        Self {
+            camera: Camera {
+                fov,
+                ..Default::default()
+            },
+            transform: Transform::default(),
+            target: None,
+            player: None,
+        }
+    }
+
+    pub fn at(mut self, translation: Vec3) -> Self {
+        self.transform.translation = translation;
+        self
+    }
+
+    /// Turned towards `target` when spawned, with +Y up.
+    pub fn looking_at(mut self, target: Vec3) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    pub fn clip_planes(mut self, near: f32, far: f32) -> Self {
+        self.camera.near = near;
+        self.camera.far = far;
+        self
+    }
+
+    /// The split-screen view of local player `index`.
+    pub fn player(mut self, index: u8) -> Self {
+        self.player = Some(PlayerIndex(index));
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), BundleError> {
+        finite("camera", &self.transform)?;
+        positive("field of view", self.camera.fov)?;
+        if self.camera.fov >= std::f32::consts::PI {
+            return Err(BundleError::Invalid(
+                "the field of view must be below 180 degrees",
+            ));
+        }
+        positive("near plane", self.camera.near)?;
+        if !(self.camera.far > self.camera.near) {
+            return Err(BundleError::Invalid(
+                "the far plane must be beyond the near plane",
+            ));
+        }
+        if let Some(target) = self.target {
+            let forward = target - self.transform.translation;
+            if !forward.is_finite() || forward.length_squared() < 1e-8 {
+                return Err(BundleError::Invalid(
+                    "the camera can't look at its own position",
+                ));
+            }
+            if forward.normalize().cross(Vec3::Y).length_squared() < 1e-8 {
+                return Err(BundleError::Invalid(
+                    "the camera can't look straight up or down",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn spawn<'a>(self, world: &'a World) -> Result<EntityView<'a>, BundleError> {
+        self.validate()?;
+        let transform = match self.target {
+            Some(target) => self.transform.looking_at(target, Vec3::Y),
+            None => self.transform,
+        };
+        let entity = world
+            .entity()
+            .set(self.camera)
+            .set(transform)
+            .set(GlobalTransform::default());
+        if let Some(player) = self.player {
+            entity.set(player);
+        }
+        Ok(entity)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum LightKind {
+    Point(PointLight),
+    Directional(DirectionalLight, Vec3),
+}
+
+/// A point or directional light. Adds the light, Transform and GlobalTransform.
+#[derive(Clone, Debug)]
+pub struct LightBundle {
+    light: LightKind,
+    transform: Transform,
+}
+
+impl LightBundle {
+    /// Lights everything within `range` of its position.
+    pub fn point(color: impl Into<Color>, intensity: f32, range: f32) -> Self {
+        Self {
+            light: LightKind::Point(PointLight {
+                color: color.into(),
+                intensity,
+                radius: range,
+            }),
+            transform: Transform::default(),
+        }
+    }
+
+    /// Shines along `direction` everywhere, like the sun.
+    pub fn directional(color: impl Into<Color>, intensity: f32, direction: Vec3) -> Self {
+        Self {
+            light: LightKind::Directional(
+                DirectionalLight {
+                    color: color.into(),
+                    intensity,
+                },
+                direction,
+            ),
+            transform: Transform::default(),
+        }
+    }
+
+    pub fn at(mut self, translation: Vec3) -> Self {
+        self.transform.translation = translation;
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), BundleError> {
+        finite("light", &self.transform)?;
+        let intensity = match self.light {
+            LightKind::Point(light) => {
+                positive("light range", light.radius)?;
+                light.intensity
+            }
+            LightKind::Directional(light, direction) => {
+                if !direction.is_finite() || direction.length_squared() < 1e-8 {
+                    return Err(BundleError::Invalid(
+                        "a directional light needs a direction",
+                    ));
+                }
+                light.intensity
+            }
+        };
+        if !(intensity >= 0.0 && intensity.is_finite()) {
+            return Err(BundleError::Invalid("light intensity must be 0 or more"));
+        }
+        Ok(())
+    }
+
+    pub fn spawn<'a>(self, world: &'a World) -> Result<EntityView<'a>, BundleError> {
+        self.validate()?;
+        let entity = world.entity().set(GlobalTransform::default());
+        match self.light {
+            LightKind::Point(light) => entity.set(light).set(self.transform),
+            // Directional lights shine along their forward (-Z) axis
+            LightKind::Directional(light, direction) => entity.set(light).set(Transform {
+                rotation: Quat::from_rotation_arc(Vec3::NEG_Z, direction.normalize()),
+                ..self.transform
+            }),
+        };
+        Ok(entity)
+    }
+}
+
+/// A character standing on its entity's position, moved by its MovementIntent (player
+/// input or a NavAgent writes it). Adds Transform, GlobalTransform, MovementIntent and
+/// a kinematic RigidBodyDefinition, which pushes dynamic bodies out of the way, with a
+/// child entity holding the capsule's ColliderDefinition. `with_mesh` adds a
+/// MeshDefinition and MaterialDefinition.
+#[derive(Clone, Debug)]
+pub struct CharacterBundle {
+    height: f32,
+    radius: f32,
+    transform: Transform,
+    mesh: Option<(Handle<MeshData>, Handle<MaterialData>)>,
+}
+
+impl CharacterBundle {
+    /// `height` from the feet to the top of the head, caps included.
+    pub fn capsule(height: f32, radius: f32) -> Self {
+        Self {
+            height,
+            radius,
+            transform: Transform::default(),
+            mesh: None,
+        }
+    }
+
+    pub fn at(mut self, translation: Vec3) -> Self {
+        self.transform.translation = translation;
+        self
+    }
+
+    /// Drawn with `mesh`, centered on the entity like the primitives are: usually
+    /// `primitives::capsule` of the same size, on a child to lift it to the collider.
+    pub fn with_mesh(mut self, mesh: Handle<MeshData>, material: Handle<MaterialData>) -> Self {
+        self.mesh = Some((mesh, material));
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), BundleError> {
+        finite("character", &self.transform)?;
+        positive("character radius", self.radius)?;
+        positive("character height", self.height)?;
+        if self.height <= self.radius * 2.0 {
+            return Err(BundleError::Invalid(
+                "a character must be taller than its width (height > 2 * radius)",
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn spawn<'a>(self, world: &'a World) -> Result<EntityView<'a>, BundleError> {
+        self.validate()?;
+        let entity = world
+            .entity()
+            .set(self.transform)
+            .set(GlobalTransform::default())
+            .set(MovementIntent::default())
+            .set(RigidBodyDefinition {
+                body_type: PhysicsBody::Kinematic,
+                gravity_scale: 0.0,
+                ..Default::default()
+            });
+
+        // The capsule's bottom at the entity's origin
+        let center = Transform::from_xyz(0.0, self.height * 0.5, 0.0);
+        let shape = ColliderShape::Capsule {
+            radius: self.radius,
+            height: self.height - self.radius * 2.0,
+        };
+        spawn_collider(world, entity.id(), shape, center);
+
+        if let Some((mesh, material)) = self.mesh {
+            world
+                .entity()
+                .child_of(entity)
+                .set(center)
+                .set(GlobalTransform::default())
+                .set(MeshDefinition(mesh))
+                .set(MaterialDefinition(material));
+        }
+        Ok(entity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Components of an entity, without the ChildOf and other pairs
+    fn components(entity: EntityView) -> Vec<Entity> {
+        let mut ids = Vec::new();
+        entity.each_component(|id| {
+            if !id.is_pair() {
+                ids.push(id.entity_view().id());
+            }
+        });
+        ids.sort();
+        ids
+    }
+
+    fn children(entity: EntityView) -> Vec<Entity> {
+        let mut children = Vec::new();
+        entity.each_child(|child| children.push(child.id()));
+        children
+    }
+
+    fn ids(mut ids: Vec<Entity>) -> Vec<Entity> {
+        ids.sort();
+        ids
+    }
+
+    fn prop() -> PropBundle {
+        PropBundle::new(Handle::new(), Handle::new())
+    }
+
+    #[test]
+    fn a_prop_without_a_body_has_no_collider() {
+        let world = World::new();
+        let prop = prop().spawn(&world).unwrap();
+        assert_eq!(
+            components(prop),
+            ids(vec![
+                world.component_id::<Transform>(),
+                world.component_id::<GlobalTransform>(),
+                world.component_id::<MeshDefinition>(),
+                world.component_id::<MaterialDefinition>(),
+            ])
+        );
+        assert!(children(prop).is_empty());
+    }
+
+    #[test]
+    fn a_dynamic_prop_adds_a_body_and_a_collider_child() {
+        let world = World::new();
+        let prop = prop()
+            .with_transform(Transform::from_xyz(0.0, 5.0, 0.0))
+            .dynamic(10.0)
+            .spawn(&world)
+            .unwrap();
+        assert_eq!(
+            components(prop),
+            ids(vec![
+                world.component_id::<Transform>(),
+                world.component_id::<GlobalTransform>(),
+                world.component_id::<MeshDefinition>(),
+                world.component_id::<MaterialDefinition>(),
+                world.component_id::<RigidBodyDefinition>(),
+            ])
+        );
+        assert!(prop.get::<&RigidBodyDefinition>(|body| {
+            matches!(body.body_type, PhysicsBody::Dynamic) && body.mass == Some(10.0)
+        }));
+        assert!(prop.get::<&Transform>(|t| t.translation == Vec3::new(0.0, 5.0, 0.0)));
+
+        let children = children(prop);
+        assert_eq!(children.len(), 1);
+        assert_eq!(
+            components(world.entity_from_id(children[0])),
+            ids(vec![
+                world.component_id::<Transform>(),
+                world.component_id::<ColliderDefinition>(),
+            ])
+        );
+    }
+
+    #[test]
+    fn a_fixed_prop_is_a_static_body_with_the_given_collider() {
+        let world = World::new();
+        let floor = prop()
+            .fixed()
+            .collider(ColliderShape::Box {
+                hx: 10.0,
+                hy: 0.1,
+                hz: 10.0,
+            })
+            .spawn(&world)
+            .unwrap();
+        assert!(
+            floor.get::<&RigidBodyDefinition>(|body| matches!(body.body_type, PhysicsBody::Static))
+        );
+        assert!(
+            world
+                .entity_from_id(children(floor)[0])
+                .get::<&ColliderDefinition>(
+                    |c| matches!(c.shape, ColliderShape::Box { hx, .. } if hx == 10.0)
+                )
+        );
+    }
+
+    #[test]
+    fn a_camera_looks_at_its_target() {
+        let world = World::new();
+        let camera = CameraBundle::perspective(60f32.to_radians())
+            .at(Vec3::new(0.0, 2.0, 5.0))
+            .looking_at(Vec3::ZERO)
+            .spawn(&world)
+            .unwrap();
+        assert_eq!(
+            components(camera),
+            ids(vec![
+                world.component_id::<Camera>(),
+                world.component_id::<Transform>(),
+                world.component_id::<GlobalTransform>(),
+            ])
+        );
+        assert!(camera.get::<&Transform>(|t| {
+            let forward = t.rotation * Vec3::NEG_Z;
+            forward.dot(Vec3::new(0.0, -2.0, -5.0).normalize()) > 0.999
+        }));
+    }
+
+    #[test]
+    fn a_player_camera_adds_player_index() {
+        let world = World::new();
+        let camera = CameraBundle::perspective(1.0)
+            .player(1)
+            .spawn(&world)
+            .unwrap();
+        assert_eq!(
+            components(camera),
+            ids(vec![
+                world.component_id::<Camera>(),
+                world.component_id::<Transform>(),
+                world.component_id::<GlobalTransform>(),
+                world.component_id::<PlayerIndex>(),
+            ])
+        );
+    }
+
+    #[test]
+    fn lights_have_their_range_and_direction() {
+        let world = World::new();
+        let point = LightBundle::point([1.0, 0.5, 0.2], 20.0, 8.0)
+            .at(Vec3::new(0.0, 3.0, 0.0))
+            .spawn(&world)
+            .unwrap();
+        assert_eq!(
+            components(point),
+            ids(vec![
+                world.component_id::<PointLight>(),
+                world.component_id::<Transform>(),
+                world.component_id::<GlobalTransform>(),
+            ])
+        );
+        assert!(point.get::<&PointLight>(|light| light.radius == 8.0 && light.intensity == 20.0));
+
+        let direction = Vec3::new(0.0, -1.0, -1.0);
+        let sun = LightBundle::directional([1.0, 1.0, 1.0], 5.0, direction)
+            .spawn(&world)
+            .unwrap();
+        assert_eq!(
+            components(sun),
+            ids(vec![
+                world.component_id::<DirectionalLight>(),
+                world.component_id::<Transform>(),
+                world.component_id::<GlobalTransform>(),
+            ])
+        );
+        assert!(sun.get::<&Transform>(|t| {
+            (t.rotation * Vec3::NEG_Z).dot(direction.normalize()) > 0.999
+        }));
+    }
+
+    #[test]
+    fn a_character_capsule_stands_on_its_origin() {
+        let world = World::new();
+        let character = CharacterBundle::capsule(1.8, 0.4).spawn(&world).unwrap();
+        assert_eq!(
+            components(character),
+            ids(vec![
+                world.component_id::<Transform>(),
+                world.component_id::<GlobalTransform>(),
+                world.component_id::<MovementIntent>(),
+                world.component_id::<RigidBodyDefinition>(),
+            ])
+        );
+        assert!(character.get::<&RigidBodyDefinition>(|body| {
+            matches!(body.body_type, PhysicsBody::Kinematic)
+        }));
+
+        let children = children(character);
+        assert_eq!(children.len(), 1);
+        assert!(
+            world
+                .entity_from_id(children[0])
+                .get::<(&Transform, &ColliderDefinition)>(|(t, c)| {
+                    (t.translation.y - 0.9).abs() < 1e-5
+                        && matches!(c.shape, ColliderShape::Capsule { radius, height }
+                        if radius == 0.4 && (height - 1.0).abs() < 1e-5)
+                })
+        );
+    }
+
+    #[test]
+    fn a_character_with_a_mesh_has_a_mesh_child() {
+        let world = World::new();
+        let character = CharacterBundle::capsule(1.8, 0.4)
+            .with_mesh(Handle::new(), Handle::new())
+            .spawn(&world)
+            .unwrap();
+        assert_eq!(children(character).len(), 2);
+    }
+
+    #[test]
+    fn invalid_values_are_refused_without_spawning_anything() {
+        let world = World::new();
+        let transform = world.component_id::<Transform>();
+        let before = world.count(transform);
+
+        assert!(matches!(
+            prop().dynamic(-1.0).spawn(&world),
+            Err(BundleError::NotPositive { name: "mass", .. })
+        ));
+        assert!(matches!(
+            prop().at(Vec3::new(f32::NAN, 0.0, 0.0)).spawn(&world),
+            Err(BundleError::NonFiniteTransform(_))
+        ));
+        assert!(
+            prop()
+                .fixed()
+                .collider(ColliderShape::Sphere { radius: 0.0 })
+                .spawn(&world)
+                .is_err()
+        );
+        assert!(CameraBundle::perspective(4.0).spawn(&world).is_err());
+        assert!(
+            CameraBundle::perspective(1.0)
+                .clip_planes(1.0, 0.5)
+                .spawn(&world)
+                .is_err()
+        );
+        // Looking at its own position
+        assert!(
+            CameraBundle::perspective(1.0)
+                .looking_at(Vec3::ZERO)
+                .spawn(&world)
+                .is_err()
+        );
+        assert!(
+            LightBundle::point([1.0, 1.0, 1.0], 10.0, 0.0)
+                .spawn(&world)
+                .is_err()
+        );
+        assert!(
+            LightBundle::directional([1.0, 1.0, 1.0], 5.0, Vec3::ZERO)
+                .spawn(&world)
+                .is_err()
+        );
+        // Shorter than its width
+        assert!(CharacterBundle::capsule(0.5, 0.4).spawn(&world).is_err());
+
+        assert_eq!(world.count(transform), before);
+    }
+}
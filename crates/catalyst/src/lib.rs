@@ -0,0 +1,152 @@
+//! Catalyst Engine facade. Games depend on this crate only:
+//!
+//! ```ignore
+//! use catalyst::prelude::*;
+//!
+//! fn main() {
+//!     let mut app = App::new();
+//!     app.add_plugins(DefaultPlugins);
+//!     run_catalyst_app(app);
+//! }
+//! ```
+
+use catalyst_core::{PluginGroup, PluginGroupBuilder};
+
+pub mod bundles;
+
+pub use catalyst_assets as assets;
+pub use catalyst_core as core;
+pub use catalyst_debug as debug;
+pub use catalyst_input as input;
+pub use catalyst_nav as nav;
+pub use catalyst_physics as physics;
+pub use catalyst_renderer as renderer;
+pub use catalyst_scene as scene;
+pub use catalyst_terrain as terrain;
+pub use catalyst_window as window;
+
+pub use flecs_ecs;
+pub use glam;
+
+/// Every engine plugin, in dependency order:
+/// Input, Window, Asset, Scene, Render, Physics, Nav, Terrain, Debug.
+///
+/// Members can be dropped or swapped before adding the group:
+/// `app.add_plugins(DefaultPlugins.without::<DebugPlugin>())`.
+pub struct DefaultPlugins;
+
+impl PluginGroup for DefaultPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        PluginGroupBuilder::default()
+            .add(input::InputPlugin)
+            .add(window::WindowPlugin)
+            .add(assets::AssetPlugin)
+            .add(scene::ScenePlugin)
+            .add(renderer::RenderPlugin)
+            .add(physics::PhysicsPlugin::default())
+            .add(nav::NavPlugin)
+            .add(terrain::TerrainPlugin)
+            // debug plugin must be last
+            .add(debug::DebugPlugin)
+    }
+}
+
+/// The types most games need. Everything else is reachable through the crate re-exports above.
+pub mod prelude {
+    pub use crate::{
+        DefaultPlugins,
+        bundles::{BundleError, CameraBundle, CharacterBundle, LightBundle, PropBundle},
+    };
+
+    pub use catalyst_core::{
+        App, AppExit, Plugin, PluginGroup, PluginGroupBuilder, PluginId,
+        activity::{ActivityRange, ActivitySource, ActivityState, Inactive, ReducedTick},
+        animation::{PropertyTrack, PropertyValue, Timeline, TimelineFinished},
+        bounds::Ray,
+        bulk::{BulkBudget, BulkSpawn, DespawnQueue, SpawnQueue},
+        camera::Camera,
+        camera_effects::{CameraEffects, CameraFollow},
+        color::Color,
+        debug_hud::DebugHud,
+        game_state::{
+            GameState, GameStateChange, GameStateChanged, GameStateId, GameStates,
+            STATE_GAMEPLAY, STATE_LOADING, STATE_MENU, run_in_states,
+        },
+        importance::{ImportanceBias, ImportanceSettings},
+        light::{DirectionalLight, PointLight},
+        logging::{EngineConfig, LogSettings},
+        movement::MovementIntent,
+        physics::{ColliderShape, CollisionFilter, CollisionLayers},
+        player::PlayerIndex,
+        raycast::{RayHit, RaycastBackend, RaycastFilter, raycast},
+        registry::ComponentRegistry,
+        replication::{
+            RemoteState, Replicated, ReplicationClock, ReplicationMode, ReplicationSettings,
+            apply_remote_state,
+        },
+        rooms::{Portal, Room},
+        scene_file::{load_scene_file, save_scene_file},
+        sequence::{Easing, Looping, Sequence, SequenceCancelled, SequenceFinished},
+        settings::{Settings, SettingsConfig, SettingsSection, rename_field},
+        smoothing::{SmoothedValue, exp_decay, smooth_damp},
+        stable_id::{StableId, StableIdCollision, StableRef, entity_by_stable_id, stable_id_of},
+        state_machine::{
+            State, StateChanged, StateContext, StateMachine, Transition, state_machine_system,
+        },
+        time::Time,
+        transform::{GlobalTransform, StaticTransform, Transform},
+    };
+
+    pub use catalyst_assets::{
+        AssetPlugin, AssetSource, LoadScene, MaterialDefinition, MeshDefinition, PrewarmScene,
+        asset_events::{AssetChanged, AssetEvent},
+        asset_server::AssetServer,
+        assets::{Handle, MeshData},
+        import::ImportSettings,
+        lod::{GenerateLods, LodLevelSettings, MeshLod},
+        lut::LutData,
+        material::{MaterialBuilder, MaterialData, MaterialSettings, MaterialWind},
+        primitives,
+        reload::{AssetDependencies, AssetReloaded, HotReload, SceneDiff},
+        scene::{SceneFilter, SceneLoadState, SceneReady},
+    };
+
+    pub use catalyst_input::{
+        InputPlugin,
+        context::{CTX_DEBUG, CTX_GAMEPLAY, CTX_UI, GameStateContexts},
+        logical::{ActionId, AxisId, ButtonPhase, InputMap},
+        physical::{InputState, MouseAxisId, MouseButtonId},
+        player::PlayerInputs,
+        processing::{AxisProcessor, AxisSettings, ResponseCurve},
+        rumble::{ImpactRumble, Rumble, RumbleRequest, RumbleSettings, impact_rumble},
+    };
+
+    pub use catalyst_renderer::{
+        AutoExposure, Autofocus, ClearColor, ColorGrading, ComputedVisibility, DebugViewMode, Decal, DepthOfField, DynamicResolution, EmitterMode, Exposure, ExposureState, FogSettings,
+        FrameGraph, FrustumCulled, FrustumCulling, GraphTextureDesc, OcclusionCullable, OcclusionCulling, Outlined, ParticleEmitter, ParticlePool, ParticleTier, PassStage, PickBackend, PickResult, Picking,
+        GradingVolume, MaterialProgram, MaterialPrograms, PlanarReflector, PortalCulling, ReflectionProbe, ReflectionProbes, RenderBucket, RenderLayers, RenderOrder, RenderPlugin, RenderSettings, Screenshots,
+        RendererCapabilities, SplitScreenLayout, SplitScreenSettings, SpotLight, StaticBatching,
+        StaticGeometry, TaaSettings, UploadSettings, WindSettings,
+        raycast_render, raycast_render_all,
+        render::DebugDraw3D,
+    };
+
+    pub use catalyst_debug::{DebugPlugin, GuiState};
+    pub use catalyst_nav::{
+        NavAgent, NavAgentStatus, NavMeshAffector, NavMeshData, NavMeshSettings, NavMeshState,
+        NavPlugin,
+    };
+    pub use catalyst_physics::PhysicsPlugin;
+    pub use catalyst_scene::{
+        ChunkGroup, NodeHookResult, SceneMembership, SceneNodes, ScenePlugin, SceneRoot,
+        StreamingVolume, WorldComposer,
+    };
+    pub use catalyst_terrain::{Terrain, TerrainHeights, TerrainPlugin};
+    pub use catalyst_window::{
+        FileDropSettings, FileDropped, FileHover, FramePacing, FrameStats, MainWindow,
+        WindowConfig, WindowFocus, WindowPlugin, run_catalyst_app,
+    };
+
+    pub use flecs_ecs::prelude::*;
+    pub use glam::{Mat4, Quat, Vec2, Vec3, Vec4};
+}
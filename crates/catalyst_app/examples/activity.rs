@@ -0,0 +1,157 @@
+// Activity sample: 10,000 dynamic boxes resting on a floor 500 units away, each with an
+// ActivityRange. Physics pushes every body's transform each frame, which keeps them all
+// awake and simulated; past the sleep radius they are force-slept and their colliders
+// leave the broad phase instead. Every two seconds the average "Physics Evaluation" time
+// and the counts per ActivityState are logged.
+//
+// Run with: cargo run -p catalyst_app --example activity
+//
+// Press T to move the camera over the field and back. Over the field the physics step
+// goes up to what the whole field costs; back home it should fall to almost nothing.
+
+use catalyst::{
+    core::{activity::ActivityStats, log, profiling},
+    prelude::*,
+};
+use catalyst_app::SamplePlugin;
+
+pub const ACTION_TOGGLE_FIELD: ActionId = ActionId(340);
+
+const BODIES: usize = 10_000;
+const SPACING: f32 = 2.0;
+const FIELD_CENTER: Vec3 = Vec3::new(0.0, 0.0, -500.0);
+const REPORT_INTERVAL: f32 = 2.0;
+
+#[derive(Component, Default)]
+pub struct ActivitySample {
+    over_field: bool,
+    home: Vec3,
+    physics_ms: f32,
+    frames: u32,
+    since_report: f32,
+}
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    register_activity_sample(&mut app);
+    app.add_plugin(SamplePlugin);
+    run_catalyst_app(app)
+}
+
+fn register_activity_sample(app: &mut App) {
+    app.register_singleton_default::<ActivitySample>();
+
+    app.world
+        .system_named::<&AssetServer>("activity_setup")
+        .kind(flecs::pipeline::OnStart)
+        .run(|iter| setup_activity(&iter.world()));
+
+    app.world
+        .system_named::<(&mut ActivitySample, &InputState)>("activity_toggle")
+        .kind(flecs::pipeline::OnUpdate)
+        .each_iter(|iter, _, (sample, input)| {
+            if !input.just_pressed(ACTION_TOGGLE_FIELD) {
+                return;
+            }
+            let world = iter.world();
+            let over_field = !sample.over_field;
+            world
+                .query::<&mut Transform>()
+                .with(Camera::id())
+                .build()
+                .each(|transform| {
+                    if over_field {
+                        sample.home = transform.translation;
+                        transform.translation = FIELD_CENTER + Vec3::new(0.0, 30.0, 40.0);
+                    } else {
+                        transform.translation = sample.home;
+                    }
+                });
+            sample.over_field = over_field;
+            log::info!(
+                "Camera {}",
+                if over_field {
+                    "over the field"
+                } else {
+                    "back home"
+                }
+            );
+        });
+
+    // Last frame's profile, so the step of the frame before this one
+    app.world
+        .system_named::<(&mut ActivitySample, &ActivityStats, &Time)>("activity_report")
+        .kind(flecs::pipeline::PostUpdate)
+        .each(|(sample, stats, time)| {
+            sample.physics_ms += profiling::last_frame()
+                .iter()
+                .filter(|(name, _)| *name == "Physics Evaluation")
+                .map(|(_, ms)| ms)
+                .sum::<f32>();
+            sample.frames += 1;
+            sample.since_report += time.delta_seconds();
+            if sample.since_report < REPORT_INTERVAL {
+                return;
+            }
+
+            log::info!(
+                "Physics Evaluation {:.2} ms average: {} active, {} reduced, {} asleep",
+                sample.physics_ms / sample.frames as f32,
+                stats.active,
+                stats.reduced,
+                stats.asleep
+            );
+            sample.physics_ms = 0.0;
+            sample.frames = 0;
+            sample.since_report = 0.0;
+        });
+}
+
+fn setup_activity(world: &World) {
+    profiling::set_enabled(true);
+
+    world.get::<&mut InputMap>(|input_map| {
+        input_map.bind_keyboard_button(winit::keyboard::KeyCode::KeyT as u16, ACTION_TOGGLE_FIELD);
+    });
+
+    world.get::<&AssetServer>(|asset_server| {
+        let cube = asset_server.add_mesh("activity_cube", primitives::cube(1.0));
+        let material = asset_server.create_material("activity_material", |m| {
+            m.base_color(Color::hsl(30.0, 0.6, 0.5)).roughness(0.8)
+        });
+
+        let columns = (BODIES as f32).sqrt().ceil() as usize;
+        let half_size = columns as f32 * SPACING * 0.5;
+
+        // The floor, static and without a range: it never sleeps anyway
+        let floor_size = (half_size + SPACING) * 2.0;
+        PropBundle::new(cube.clone(), material.clone())
+            .with_transform(Transform {
+                translation: Vec3::new(FIELD_CENTER.x, -0.5, FIELD_CENTER.z),
+                scale: Vec3::new(floor_size, 1.0, floor_size),
+                ..Default::default()
+            })
+            .fixed()
+            .spawn(world)
+            .expect("activity floor");
+
+        let range = ActivityRange {
+            disable_colliders: true,
+            ..Default::default()
+        };
+        for index in 0..BODIES {
+            let (row, column) = (index / columns, index % columns);
+            PropBundle::new(cube.clone(), material.clone())
+                .at(Vec3::new(
+                    FIELD_CENTER.x + column as f32 * SPACING - half_size,
+                    0.5,
+                    FIELD_CENTER.z + row as f32 * SPACING - half_size,
+                ))
+                .dynamic(1.0)
+                .spawn(world)
+                .expect("activity body")
+                .set(range);
+        }
+    });
+}
@@ -0,0 +1,107 @@
+// Camera effects sample: every camera gets CameraEffects, layered over whatever moves
+// it, so the fly-cam keeps working while the view shakes. A cube circles nearby for
+// the follow constraint to chase.
+//
+// Run with: cargo run -p catalyst_app --example camera_shake
+//
+// Space adds trauma, K kicks the camera like recoil, F toggles following the cube.
+// Pausing gameplay time (time.pause in the console) freezes the effects.
+
+use catalyst::prelude::*;
+use catalyst_app::SamplePlugin;
+
+pub const ACTION_SHAKE: ActionId = ActionId(310);
+pub const ACTION_KICK: ActionId = ActionId(311);
+pub const ACTION_FOLLOW: ActionId = ActionId(312);
+
+const TARGET_ID: StableId = StableId(0x5ca3_0001);
+const ORBIT_CENTER: Vec3 = Vec3::new(0.0, 1.0, -6.0);
+const ORBIT_RADIUS: f32 = 4.0;
+const ORBIT_SPEED: f32 = 0.8;
+
+/// The cube the cameras follow.
+#[derive(Component)]
+pub struct ShakeTarget;
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    register_camera_shake_sample(&mut app);
+    app.add_plugin(SamplePlugin);
+    run_catalyst_app(app)
+}
+
+fn register_camera_shake_sample(app: &mut App) {
+    app.world.component::<ShakeTarget>();
+
+    app.world
+        .system_named::<&AssetServer>("camera_shake_setup")
+        .kind(flecs::pipeline::OnStart)
+        .run(|iter| setup_camera_shake(&iter.world()));
+
+    // Cameras of scenes loaded later get theirs too
+    app.world
+        .system_named::<&Camera>("camera_shake_attach")
+        .without(CameraEffects::id())
+        .kind(flecs::pipeline::OnUpdate)
+        .each_entity(|entity, _| {
+            entity.set(CameraEffects::default());
+        });
+
+    app.world
+        .system_named::<(&mut Transform, &Time)>("camera_shake_orbit")
+        .with(ShakeTarget::id())
+        .kind(flecs::pipeline::OnUpdate)
+        .each(|(transform, time)| {
+            let angle = time.elapsed_seconds() * ORBIT_SPEED;
+            transform.translation =
+                ORBIT_CENTER + Vec3::new(angle.cos(), 0.0, angle.sin()) * ORBIT_RADIUS;
+            transform.rotation = Quat::from_rotation_y(-angle);
+        });
+
+    app.world
+        .system_named::<(&mut CameraEffects, &InputState)>("camera_shake_input")
+        .kind(flecs::pipeline::OnUpdate)
+        .each(|(effects, input)| {
+            if input.just_pressed(ACTION_SHAKE) {
+                effects.add_trauma(0.5);
+            }
+            if input.just_pressed(ACTION_KICK) {
+                // Back and up, like a rifle
+                effects.kick(Vec3::new(0.0, 0.0, 1.5), Vec3::new(1.2, 0.0, 0.0));
+            }
+            if input.just_pressed(ACTION_FOLLOW) {
+                effects.follow = match effects.follow {
+                    Some(_) => None,
+                    None => Some(CameraFollow {
+                        target: Some(StableRef::new(TARGET_ID)),
+                        ..Default::default()
+                    }),
+                };
+            }
+        });
+}
+
+fn setup_camera_shake(world: &World) {
+    world.get::<&mut InputMap>(|input_map| {
+        input_map.bind_keyboard_button(winit::keyboard::KeyCode::Space as u16, ACTION_SHAKE);
+        input_map.bind_keyboard_button(winit::keyboard::KeyCode::KeyK as u16, ACTION_KICK);
+        input_map.bind_keyboard_button(winit::keyboard::KeyCode::KeyF as u16, ACTION_FOLLOW);
+    });
+
+    world.get::<&AssetServer>(|asset_server| {
+        let cube = asset_server.add_mesh("camera_shake_cube", primitives::cube(1.0));
+        let material = asset_server.create_material("camera_shake_target", |m| {
+            m.base_color([0.9, 0.5, 0.1, 1.0]).roughness(0.4)
+        });
+
+        world
+            .entity_named("camera_shake_target")
+            .add(ShakeTarget)
+            .set(TARGET_ID)
+            .set(Transform::from_xyz(ORBIT_CENTER.x + ORBIT_RADIUS, ORBIT_CENTER.y, ORBIT_CENTER.z))
+            .set(GlobalTransform::default())
+            .set(MeshDefinition(cube))
+            .set(MaterialDefinition(material));
+    });
+}
@@ -0,0 +1,88 @@
+// Depth precision sample: two overlapping quads 500 m away, 5 cm apart, facing a camera
+// with a 0.1 m near plane. With the standard depth mapping the overlap z-fights in
+// shimmering stripes; with reversed-Z (infinite far plane, float depth) the nearer red
+// quad wins cleanly. Fly towards them to see the standard mapping settle up close.
+//
+// Run with: cargo run -p catalyst_app --example depth_precision
+//      and: cargo run -p catalyst_app --example depth_precision -- --reversed-z
+//
+// The depth mode is a renderer-wide setting read at startup, so it is set before the
+// first frame. Like any RenderSettings change it is kept in the settings file.
+
+use std::f32::consts::FRAC_PI_2;
+
+use catalyst::{core::log, prelude::*, renderer::DepthMode};
+use catalyst_app::SamplePlugin;
+
+const DISTANCE: f32 = 500.0;
+const GAP: f32 = 0.05;
+const QUAD_SIZE: f32 = 60.0;
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    register_depth_precision_sample(&mut app);
+    app.add_plugin(SamplePlugin);
+    run_catalyst_app(app)
+}
+
+fn register_depth_precision_sample(app: &mut App) {
+    let depth_mode = if std::env::args().any(|arg| arg == "--reversed-z") {
+        DepthMode::ReversedZ
+    } else {
+        DepthMode::Standard
+    };
+    app.world
+        .get::<&mut RenderSettings>(|settings| settings.depth_mode = depth_mode);
+    log::info!("Depth precision sample, depth mode {:?}", depth_mode);
+
+    app.world
+        .system_named::<&AssetServer>("depth_precision_setup")
+        .kind(flecs::pipeline::OnStart)
+        .run(|iter| setup_depth_precision(&iter.world()));
+}
+
+fn setup_depth_precision(world: &World) {
+    world.get::<&AssetServer>(|asset_server| {
+        let quad = asset_server.add_mesh("depth_precision_quad", primitives::plane(QUAD_SIZE));
+
+        // Unlit, so the stripes aren't mistaken for shading. Red is the nearer one
+        let quads = [
+            (
+                "depth_precision_near",
+                [0.9, 0.1, 0.1, 1.0],
+                -DISTANCE,
+                -QUAD_SIZE * 0.25,
+            ),
+            (
+                "depth_precision_far",
+                [0.1, 0.3, 0.9, 1.0],
+                -DISTANCE - GAP,
+                QUAD_SIZE * 0.25,
+            ),
+        ];
+        for (name, color, z, x) in quads {
+            let material = asset_server.create_material(name, |m| m.base_color(color).unlit());
+            world
+                .entity_named(name)
+                .set(Transform {
+                    translation: Vec3::new(x, 0.0, z),
+                    // The plane faces +Y; stand it up to face the camera
+                    rotation: Quat::from_rotation_x(FRAC_PI_2),
+                    scale: Vec3::ONE,
+                })
+                .set(GlobalTransform::default())
+                .set(MeshDefinition(quad.clone()))
+                .set(MaterialDefinition(material));
+        }
+    });
+
+    // Moved by the free camera; the far plane only matters to the standard mapping
+    CameraBundle::perspective(45f32.to_radians())
+        .at(Vec3::ZERO)
+        .looking_at(Vec3::new(0.0, 0.0, -DISTANCE))
+        .clip_planes(0.1, 1000.0)
+        .spawn(world)
+        .expect("depth precision camera")
+        .set_name("depth_precision_camera");
+}
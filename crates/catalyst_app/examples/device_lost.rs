@@ -0,0 +1,121 @@
+// Device lost sample: simulates losing the GPU device a few seconds in, and again on L.
+// The renderer drops everything on the GPU, creates a new device and uploads the
+// assets again from their CPU data. Once every resource is back, the mesh draw count
+// is compared with the one before the loss and the time it took is logged.
+//
+// Run with: cargo run -p catalyst_app --example device_lost
+//
+// Lower UploadSettings::bytes_per_frame to watch the meshes come back over several
+// frames, textured materials showing the default white texture until theirs is up.
+
+use catalyst::{
+    core::log,
+    prelude::*,
+    renderer::{DeviceRecovery, RenderContext, RenderStats},
+};
+use catalyst_app::SamplePlugin;
+
+pub const ACTION_LOSE_DEVICE: ActionId = ActionId(330);
+
+const FIRST_LOSS_AT: f32 = 3.0;
+const CUBES: usize = 8;
+
+#[derive(Component, Default)]
+pub struct DeviceLostSample {
+    simulated: bool,
+    // Mesh draws and time when the loss was simulated, until the recovery is checked
+    pending: Option<(usize, f32)>,
+    recoveries: u32,
+}
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    register_device_lost_sample(&mut app);
+    app.add_plugin(SamplePlugin);
+    run_catalyst_app(app)
+}
+
+fn register_device_lost_sample(app: &mut App) {
+    app.register_singleton_default::<DeviceLostSample>();
+
+    app.world
+        .system_named::<&AssetServer>("device_lost_setup")
+        .kind(flecs::pipeline::OnStart)
+        .run(|iter| setup_device_lost(&iter.world()));
+
+    app.world
+        .system_named::<(
+            &mut DeviceLostSample,
+            &RenderContext,
+            &RenderStats,
+            &InputState,
+            &Time,
+        )>("device_lost_trigger")
+        .kind(flecs::pipeline::OnUpdate)
+        .each(|(sample, context, stats, input, time)| {
+            let first = !sample.simulated && time.elapsed_seconds() > FIRST_LOSS_AT;
+            if (first || input.just_pressed(ACTION_LOSE_DEVICE)) && sample.pending.is_none() {
+                log::info!("Simulating a device loss");
+                context.simulate_device_loss();
+                sample.simulated = true;
+                sample.pending = Some((stats.mesh_draws, time.elapsed_seconds()));
+            }
+        });
+
+    // Checked in the frame after every resource is back, once the draws were recorded
+    app.world
+        .system_named::<(&mut DeviceLostSample, &DeviceRecovery, &RenderStats, &Time)>(
+            "device_lost_report",
+        )
+        .kind(flecs::pipeline::PostUpdate)
+        .each(|(sample, recovery, stats, time)| {
+            let Some((draws_before, lost_at)) = sample.pending else {
+                return;
+            };
+            if recovery.recoveries == sample.recoveries || recovery.regenerating > 0 {
+                return;
+            }
+            sample.recoveries = recovery.recoveries;
+            sample.pending = None;
+
+            log::info!(
+                "Recovered in {:.2}s ({:?})",
+                time.elapsed_seconds() - lost_at,
+                recovery.last_reason
+            );
+            if stats.mesh_draws == draws_before {
+                log::info!("ok: {} mesh draws, as before the loss", stats.mesh_draws);
+            } else {
+                log::error!(
+                    "FAILED: {} mesh draws, {} before the loss",
+                    stats.mesh_draws,
+                    draws_before
+                );
+            }
+        });
+}
+
+fn setup_device_lost(world: &World) {
+    world.get::<&mut InputMap>(|input_map| {
+        input_map.bind_keyboard_button(winit::keyboard::KeyCode::KeyL as u16, ACTION_LOSE_DEVICE);
+    });
+
+    world.get::<&AssetServer>(|asset_server| {
+        let cube = asset_server.add_mesh("device_lost_cube", primitives::cube(1.0));
+
+        for index in 0..CUBES {
+            let hue = index as f32 / CUBES as f32 * 360.0;
+            let material = asset_server
+                .create_material(&format!("device_lost_material_{}", index), |m| {
+                    m.base_color(Color::hsl(hue, 0.7, 0.5)).roughness(0.6)
+                });
+            world
+                .entity()
+                .set(Transform::from_xyz(index as f32 * 1.5 - 5.0, 0.5, -6.0))
+                .set(GlobalTransform::default())
+                .set(MeshDefinition(cube.clone()))
+                .set(MaterialDefinition(material));
+        }
+    });
+}
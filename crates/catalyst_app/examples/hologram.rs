@@ -0,0 +1,217 @@
+// Custom material program sample: a hologram of a capsule drawn by the game's own
+// shader (hologram.wgsl) next to the same capsule drawn by the PBR program. The
+// hologram is additive with scan lines scrolling up, and also shows up in the pond of
+// the reflections sample when both are enabled.
+//
+// Run with: cargo run -p catalyst_app --example hologram
+
+use catalyst::{
+    prelude::*,
+    renderer::{CustomMaterialProgram, CustomProgramContext, ProgramPasses, RenderContext, wgpu},
+};
+use catalyst_app::SamplePlugin;
+
+const HOLOGRAM_KEY: &str = "hologram";
+// Four floats of color, then time, scroll speed, line density and padding
+const UNIFORM_SIZE: u64 = 8 * 4;
+
+/// Look of the hologram, uploaded every frame.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct HologramSettings {
+    pub color: [f32; 4],
+    /// World units per second.
+    pub scroll_speed: f32,
+    /// Scan lines per world unit.
+    pub line_density: f32,
+}
+
+impl Default for HologramSettings {
+    fn default() -> Self {
+        Self {
+            color: [0.2, 0.8, 1.0, 0.6],
+            scroll_speed: 0.5,
+            line_density: 8.0,
+        }
+    }
+}
+
+pub struct HologramProgram {
+    pipeline: wgpu::RenderPipeline,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl HologramProgram {
+    pub fn new(ctx: &CustomProgramContext) -> Self {
+        let shader = ctx.create_shader_module(
+            "Hologram Shader",
+            &[("hologram.wgsl", include_str!("hologram.wgsl"))],
+            &["hologram.wgsl"],
+        );
+
+        let layout = ctx
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Hologram Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+        let buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Hologram Uniform"),
+            size: UNIFORM_SIZE,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Hologram Bind Group"),
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Hologram Pipeline Layout"),
+                bind_group_layouts: &[ctx.global_layout, ctx.mesh_layout, &layout],
+                push_constant_ranges: &[],
+            });
+
+        // Added on top of the scene, depth tested but not written
+        let additive = wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent::OVER,
+        };
+        let pipeline = ctx
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Hologram Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[ctx.vertex_layout()],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &ctx.color_targets(Some(additive), false),
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: Some(wgpu::Face::Back),
+                    ..Default::default()
+                },
+                depth_stencil: Some(ctx.depth_stencil(false)),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            });
+
+        Self {
+            pipeline,
+            buffer,
+            bind_group,
+        }
+    }
+
+    pub fn write(&self, queue: &wgpu::Queue, settings: &HologramSettings, time: f32) {
+        let values = [
+            settings.color[0],
+            settings.color[1],
+            settings.color[2],
+            settings.color[3],
+            time,
+            settings.scroll_speed,
+            settings.line_density,
+            0.0,
+        ];
+        let bytes: Vec<u8> = values
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect();
+        queue.write_buffer(&self.buffer, 0, &bytes);
+    }
+}
+
+impl CustomMaterialProgram for HologramProgram {
+    fn passes(&self) -> ProgramPasses {
+        ProgramPasses::REFLECTIONS
+    }
+
+    fn bind<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(2, &self.bind_group, &[]);
+    }
+}
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    register_hologram_sample(&mut app);
+    app.add_plugin(SamplePlugin);
+    run_catalyst_app(app)
+}
+
+fn register_hologram_sample(app: &mut App) {
+    app.register_singleton_default::<HologramSettings>();
+    app.world.get::<&mut MaterialPrograms>(|programs| {
+        programs.register(HOLOGRAM_KEY, HologramProgram::new);
+    });
+
+    app.world
+        .system_named::<&AssetServer>("hologram_setup")
+        .kind(flecs::pipeline::OnStart)
+        .run(|iter| setup_hologram(&iter.world()));
+
+    app.world
+        .system_named::<(&RenderContext, &HologramSettings, &Time)>("hologram_uniform")
+        .kind(flecs::pipeline::OnUpdate)
+        .each(|(context, settings, time)| {
+            if let Some(program) = context.custom_programs.get::<HologramProgram>(HOLOGRAM_KEY) {
+                program.write(&context.queue, settings, time.elapsed_seconds());
+            }
+        });
+}
+
+fn setup_hologram(world: &World) {
+    world.get::<&AssetServer>(|asset_server| {
+        let capsule =
+            asset_server.add_mesh("hologram_capsule", primitives::capsule(0.5, 1.0, 24, 12));
+
+        let material = asset_server.create_material("hologram_solid", |m| {
+            m.base_color([0.6, 0.6, 0.65, 1.0]).roughness(0.4)
+        });
+        world
+            .entity_named("hologram_solid")
+            .set(Transform::from_xyz(-1.5, 1.0, -4.0))
+            .set(GlobalTransform::default())
+            .set(MeshDefinition(capsule.clone()))
+            .set(MaterialDefinition(material));
+
+        // Same mesh, drawn by the program registered under HOLOGRAM_KEY
+        world
+            .entity_named("hologram_projection")
+            .set(Transform::from_xyz(1.5, 1.0, -4.0))
+            .set(GlobalTransform::default())
+            .set(MeshDefinition(capsule))
+            .set(MaterialProgram::new(HOLOGRAM_KEY));
+    });
+}
@@ -0,0 +1,49 @@
+// ========================================================================
+//  HOLOGRAM (custom material program of the --hologram sample)
+//  Unlit and additive: scan lines scrolling up the mesh in world space.
+// ========================================================================
+
+#include "common/camera.wgsl"
+#include "common/mesh.wgsl"
+
+// Must match HologramUniform in hologram.rs
+struct Hologram {
+    color: vec4<f32>,
+    time: f32,
+    scroll_speed: f32,
+    line_density: f32,
+    padding: f32,
+};
+
+// Groups 0 and 1 are bound by the renderer, group 2 is the program's
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(1) @binding(0) var<uniform> mesh: MeshUniform;
+@group(2) @binding(0) var<uniform> hologram: Hologram;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_y: f32,
+};
+
+@vertex
+fn vs_main(
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+) -> VertexOutput {
+    var out: VertexOutput;
+    let world_pos = mesh.model * vec4<f32>(position, 1.0);
+    out.clip_position = camera.view_proj * world_pos;
+    out.world_y = world_pos.y;
+    return out;
+}
+
+// Only the color target is written, the velocity and luminance of what is
+// behind the hologram stay
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let phase = (in.world_y - hologram.time * hologram.scroll_speed) * hologram.line_density;
+    let lines = 0.25 + 0.75 * smoothstep(0.4, 0.6, fract(phase));
+    let strength = lines * hologram.color.a;
+    return vec4<f32>(hologram.color.rgb * strength * camera.exposure, 1.0);
+}
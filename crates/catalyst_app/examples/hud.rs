@@ -0,0 +1,110 @@
+// Debug HUD sample: a frame time graph, the camera position and the progress of a fake
+// loading job in the HUD corner, with the debug windows closed. The job's bar goes away
+// a few frames after it stops being updated.
+//
+// Run with: cargo run -p catalyst_app --example hud
+//
+// H shows and hides the HUD, C moves it to the next corner and J starts the job again.
+// The hud, hud.corner and hud.font_size console commands do the same.
+
+use catalyst::{
+    core::{debug_hud::DebugHud, log},
+    debug::{HudCorner, HudSettings},
+    prelude::*,
+};
+use catalyst_app::SamplePlugin;
+
+pub const ACTION_TOGGLE_HUD: ActionId = ActionId(362);
+pub const ACTION_NEXT_CORNER: ActionId = ActionId(363);
+pub const ACTION_RESTART_JOB: ActionId = ActionId(364);
+
+const JOB_SECONDS: f32 = 8.0;
+
+/// Progress of the fake job, None once it is done.
+#[derive(Component)]
+pub struct HudSample {
+    job: Option<f32>,
+}
+
+impl Default for HudSample {
+    fn default() -> Self {
+        Self { job: Some(0.0) }
+    }
+}
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    register_hud_sample(&mut app);
+    app.add_plugin(SamplePlugin);
+    run_catalyst_app(app)
+}
+
+fn register_hud_sample(app: &mut App) {
+    app.register_singleton_default::<HudSample>();
+
+    app.world
+        .system_named::<&AssetServer>("hud_setup")
+        .kind(flecs::pipeline::OnStart)
+        .run(|iter| setup_hud(&iter.world()));
+
+    app.world
+        .system_named::<(&mut HudSettings, &mut HudSample, &InputState)>("hud_keys")
+        .kind(flecs::pipeline::OnUpdate)
+        .each(|(settings, sample, input)| {
+            if input.just_pressed(ACTION_TOGGLE_HUD) {
+                settings.enabled = !settings.enabled;
+            }
+            if input.just_pressed(ACTION_NEXT_CORNER) {
+                let index = HudCorner::ALL
+                    .iter()
+                    .position(|corner| *corner == settings.corner)
+                    .unwrap_or(0);
+                settings.corner = HudCorner::ALL[(index + 1) % HudCorner::ALL.len()];
+                log::info!("HUD in the {} corner", settings.corner.name());
+            }
+            if input.just_pressed(ACTION_RESTART_JOB) {
+                sample.job = Some(0.0);
+            }
+        });
+
+    app.world
+        .system_named::<(&mut HudSample, &Time)>("hud_values")
+        .kind(flecs::pipeline::OnUpdate)
+        .each(|(sample, time)| {
+            let delta = time.delta_seconds();
+            DebugHud::plot("frame ms", delta * 1000.0);
+            if delta > 0.0 {
+                DebugHud::watch("fps", format!("{:.0}", 1.0 / delta));
+            }
+
+            // Not refreshed once done, so the bar expires on its own
+            if let Some(elapsed) = sample.job.as_mut() {
+                *elapsed += delta;
+                DebugHud::progress("loading job", *elapsed / JOB_SECONDS);
+                if *elapsed >= JOB_SECONDS {
+                    sample.job = None;
+                }
+            }
+        });
+
+    app.world
+        .system_named::<&GlobalTransform>("hud_camera")
+        .with(Camera::id())
+        .kind(flecs::pipeline::OnUpdate)
+        .each(|global| {
+            let position = global.0.w_axis.truncate();
+            DebugHud::watch(
+                "camera",
+                format!("{:.1} {:.1} {:.1}", position.x, position.y, position.z),
+            );
+        });
+}
+
+fn setup_hud(world: &World) {
+    world.get::<&mut InputMap>(|input_map| {
+        input_map.bind_keyboard_button(winit::keyboard::KeyCode::KeyH as u16, ACTION_TOGGLE_HUD);
+        input_map.bind_keyboard_button(winit::keyboard::KeyCode::KeyC as u16, ACTION_NEXT_CORNER);
+        input_map.bind_keyboard_button(winit::keyboard::KeyCode::KeyJ as u16, ACTION_RESTART_JOB);
+    });
+}
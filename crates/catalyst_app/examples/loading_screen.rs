@@ -0,0 +1,121 @@
+// Loading screen sample: the current level stays playable while the next one is
+// read and uploaded in the background, then the two are swapped in one frame.
+//
+// Run with: cargo run -p catalyst_app --example loading_screen
+//
+// Press N to warm up the next level.
+
+use catalyst::{assets::scene::scene_load_state, core::log, prelude::*, scene::despawn_scene};
+use catalyst_app::SamplePlugin;
+
+pub const ACTION_NEXT_LEVEL: ActionId = ActionId(300);
+
+const LEVELS: &[&str] = &[
+    "assets/scene1.glb",
+    "assets/scene2.glb",
+    "assets/scene3.glb",
+];
+
+#[derive(Component, Default)]
+pub struct LevelSwap {
+    current: Option<Entity>,
+    next: Option<Entity>,
+    next_index: usize,
+    // Longest frame while the next level was warming, shows whether the uploads hitched
+    worst_frame: f32,
+}
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    register_loading_screen_sample(&mut app);
+    app.add_plugin(SamplePlugin);
+    run_catalyst_app(app)
+}
+
+fn register_loading_screen_sample(app: &mut App) {
+    app.register_singleton_default::<LevelSwap>();
+
+    app.world
+        .system_named::<&AssetServer>("loading_screen_setup")
+        .kind(flecs::pipeline::OnStart)
+        .run(|iter| {
+            let world = iter.world();
+
+            world.get::<&mut InputMap>(|input_map| {
+                input_map
+                    .bind_keyboard_button(winit::keyboard::KeyCode::KeyN as u16, ACTION_NEXT_LEVEL);
+            });
+
+            let first = spawn_level(&world, 0, false);
+            world.get::<&mut LevelSwap>(|swap| {
+                swap.current = Some(first);
+                swap.next_index = 1;
+            });
+        });
+
+    // Runs after "Spawn Scenes", so the new level appears in the same frame the old one goes away
+    app.world
+        .system_named::<(&mut LevelSwap, &InputState, &Time)>("loading_screen_swap")
+        .kind(flecs::pipeline::OnUpdate)
+        .run(|iter| update_level_swap(&iter.world()));
+}
+
+fn update_level_swap(world: &World) {
+    let next_pressed = world.get::<&InputState>(|input| input.just_pressed(ACTION_NEXT_LEVEL));
+    let dt = world.get::<&Time>(|time| time.delta_seconds());
+
+    world.get::<&mut LevelSwap>(|swap| {
+        if swap.next.is_none() && next_pressed {
+            let path = LEVELS[swap.next_index % LEVELS.len()];
+            log::info!("Warming up {} ...", path);
+
+            swap.next = Some(spawn_level(world, swap.next_index, true));
+            swap.next_index += 1;
+            swap.worst_frame = 0.0;
+            return;
+        }
+
+        let Some(next) = swap.next else {
+            return;
+        };
+        swap.worst_frame = swap.worst_frame.max(dt);
+
+        if scene_load_state(world.entity_from_id(next)) != SceneLoadState::Ready {
+            return;
+        }
+
+        // Deleting the level root deletes its spawned nodes, over several frames for a
+        // large level. The level's asset entities stay loaded; unloading them is not part
+        // of this sample.
+        if let Some(current) = swap.current.take() {
+            despawn_scene(world.entity_from_id(current));
+        }
+        swap.current = Some(next);
+        swap.next = None;
+
+        log::info!(
+            "Level swapped, longest frame while warming: {:.1} ms",
+            swap.worst_frame * 1000.0
+        );
+    });
+}
+
+fn spawn_level(world: &World, index: usize, prewarm: bool) -> Entity {
+    let level = world
+        .entity()
+        .set(AssetSource {
+            path: LEVELS[index % LEVELS.len()].to_string(),
+        })
+        .add(LoadScene)
+        .set(Transform::default())
+        .set(GlobalTransform::default());
+
+    if prewarm {
+        level.add(PrewarmScene).set(SceneRoot {
+            spawn_when_ready: true,
+        });
+    }
+
+    level.id()
+}
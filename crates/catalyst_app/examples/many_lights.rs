@@ -0,0 +1,87 @@
+// Clustered lighting stress sample: 500 small point lights circling over a large
+// floor. With uniform lighting (downlevel adapters or RenderSettings::uniform_lighting)
+// only 4 of them light the scene. Lights per cluster are shown in the Rendering window.
+//
+// Run with: cargo run -p catalyst_app --example many_lights
+
+use catalyst::prelude::*;
+use catalyst_app::SamplePlugin;
+
+const LIGHT_COUNT: usize = 500;
+const FLOOR_SIZE: f32 = 80.0;
+const LIGHT_HEIGHT: f32 = 0.6;
+const LIGHT_RADIUS: f32 = 4.0;
+const LIGHT_INTENSITY: f32 = 4.0;
+
+/// Circles the light around `center`, one turn every TAU / `speed` seconds.
+#[derive(Component)]
+pub struct Orbit {
+    center: Vec3,
+    radius: f32,
+    speed: f32,
+    phase: f32,
+}
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    register_many_lights_sample(&mut app);
+    app.add_plugin(SamplePlugin);
+    run_catalyst_app(app)
+}
+
+fn register_many_lights_sample(app: &mut App) {
+    app.world.component::<Orbit>();
+
+    app.world
+        .system_named::<&AssetServer>("many_lights_setup")
+        .kind(flecs::pipeline::OnStart)
+        .run(|iter| setup_many_lights(&iter.world()));
+
+    app.world
+        .system_named::<(&Orbit, &mut Transform, &Time)>("many_lights_update")
+        .kind(flecs::pipeline::OnUpdate)
+        .each(|(orbit, transform, time)| {
+            let angle = orbit.phase + time.elapsed_seconds() * orbit.speed;
+            transform.translation =
+                orbit.center + Vec3::new(angle.cos(), 0.0, angle.sin()) * orbit.radius;
+        });
+}
+
+fn setup_many_lights(world: &World) {
+    world.get::<&AssetServer>(|asset_server| {
+        let mesh = asset_server.add_mesh("many_lights_floor", primitives::plane(FLOOR_SIZE));
+        let material = asset_server.create_material("many_lights_floor_material", |m| {
+            m.base_color([0.6, 0.6, 0.6, 1.0]).roughness(0.6)
+        });
+
+        PropBundle::new(mesh, material)
+            .spawn(world)
+            .expect("many lights floor")
+            .set_name("many_lights_floor");
+    });
+
+    // Golden angle spiral, so the lights cover the floor evenly without a random generator
+    let golden_angle = std::f32::consts::PI * (3.0 - 5.0_f32.sqrt());
+    for index in 0..LIGHT_COUNT {
+        let t = index as f32 / LIGHT_COUNT as f32;
+        let angle = index as f32 * golden_angle;
+        let distance = t.sqrt() * FLOOR_SIZE * 0.45;
+        let center = Vec3::new(angle.cos() * distance, LIGHT_HEIGHT, angle.sin() * distance);
+
+        LightBundle::point(
+            Color::hsl(t * 7.0 * 360.0, 1.0, 0.5),
+            LIGHT_INTENSITY,
+            LIGHT_RADIUS,
+        )
+        .at(center)
+        .spawn(world)
+        .expect("many lights light")
+        .set(Orbit {
+            center,
+            radius: 1.0 + (index % 3) as f32,
+            speed: 0.5 + (index % 5) as f32 * 0.25,
+            phase: angle,
+        });
+    }
+}
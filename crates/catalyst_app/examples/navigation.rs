@@ -0,0 +1,306 @@
+// Click-to-move navigation sample: a floor with a few walls and a capsule agent.
+// Left click on the floor to walk there; the navmesh and the agent's path are drawn
+// while the debug GUI is open (Tab), where the Navigation window also triggers rebakes.
+//
+// Every click leaves a decal and a particle burst where the ray hit, and a few hundred
+// stain decals are scattered over the floor and walls.
+//
+// Run with: cargo run -p catalyst_app --example navigation
+
+use catalyst::{
+    assets::material::{TextureData, TextureFormat, TextureType},
+    core::log,
+    prelude::*,
+    window::MainWindow,
+};
+use catalyst_app::SamplePlugin;
+use winit::window::CursorGrabMode;
+
+pub const ACTION_NAV_CLICK: ActionId = ActionId(20);
+
+const FLOOR_SIZE: f32 = 30.0;
+const AGENT_RADIUS: f32 = 0.35;
+const AGENT_HEIGHT: f32 = 1.0;
+
+// (center x, center z, half width, half depth) of the walls
+const WALLS: [(f32, f32, f32, f32); 4] = [
+    (-4.0, 0.0, 0.5, 6.0),
+    (4.0, 3.0, 0.5, 6.0),
+    (0.0, -8.0, 6.0, 0.5),
+    (9.0, -4.0, 3.0, 0.5),
+];
+const WALL_HEIGHT: f32 = 1.5;
+
+const MARK_LIFETIME: f32 = 8.0;
+const STAIN_COUNT: usize = 300;
+const DECAL_TEXTURE_SIZE: u32 = 128;
+
+/// The entity the sample moves around.
+#[derive(Component)]
+pub struct NavigationSampleAgent;
+
+/// Decal and burst left by a click, removed after MARK_LIFETIME.
+#[derive(Component, Default)]
+pub struct ClickMark {
+    age: f32,
+}
+
+#[derive(Component, Default)]
+pub struct NavigationDecals {
+    marker: Option<Handle<TextureData>>,
+}
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    register_navigation_sample(&mut app);
+    app.add_plugin(SamplePlugin);
+    run_catalyst_app(app)
+}
+
+fn register_navigation_sample(app: &mut App) {
+    app.world.component::<NavigationSampleAgent>();
+    app.world.component::<ClickMark>();
+    app.register_singleton_default::<NavigationDecals>();
+
+    app.world
+        .system_named::<&AssetServer>("navigation_setup")
+        .kind(flecs::pipeline::OnStart)
+        .run(|iter| setup_navigation(&iter.world()));
+
+    let agents = app
+        .world
+        .query::<&mut NavAgent>()
+        .with(NavigationSampleAgent)
+        .build();
+    let cameras = app
+        .world
+        .query::<(&Camera, &GlobalTransform)>()
+        .with(PlayerIndex::id())
+        .build();
+
+    app.world
+        .system_named::<(&InputState, &MainWindow, &NavMeshState)>("navigation_click")
+        .kind(flecs::pipeline::OnUpdate)
+        .each_iter(move |iter, _, (input, window, state)| {
+            if !input.just_pressed(ACTION_NAV_CLICK) {
+                return;
+            }
+
+            let size = window.0.inner_size();
+            let viewport = Vec2::new(size.width as f32, size.height as f32);
+            let cursor = Vec2::new(input.mouse_position.0, input.mouse_position.1);
+
+            let mut ray = None;
+            cameras.each(|(camera, transform)| {
+                ray = Some(camera.screen_ray(transform, cursor, viewport));
+            });
+            let Some(ray) = ray else {
+                return;
+            };
+
+            let world = iter.world();
+            let hit = state
+                .handle()
+                .try_get_entity(&world)
+                .and_then(|entity| entity.try_get::<&NavMeshData>(|navmesh| navmesh.raycast(&ray)))
+                .flatten();
+
+            match hit {
+                Some(point) => {
+                    agents.each(|agent| agent.set_target(point));
+                    spawn_click_mark(&world, point);
+                }
+                None => log::info!("Clicked off the navmesh"),
+            }
+        });
+
+    // Marks fade out over their last second
+    app.world
+        .system_named::<(&mut ClickMark, &Time)>("navigation_click_marks")
+        .kind(flecs::pipeline::OnUpdate)
+        .each_entity(|entity, (mark, time)| {
+            mark.age += time.delta_seconds();
+            if mark.age >= MARK_LIFETIME {
+                entity.destruct();
+                return;
+            }
+            let fade = (MARK_LIFETIME - mark.age).min(1.0);
+            entity.try_get::<&mut Decal>(|decal| decal.color[3] = fade);
+        });
+}
+
+fn spawn_click_mark(world: &World, point: Vec3) {
+    let marker = world.get::<&NavigationDecals>(|decals| decals.marker.clone());
+    let mut transform = Transform::from_xyz(point.x, point.y, point.z);
+
+    world
+        .entity()
+        .set(ClickMark::default())
+        .set(transform)
+        .set(GlobalTransform::default())
+        .set(Decal {
+            texture: marker,
+            size: Vec2::splat(1.5),
+            depth: 1.0,
+            color: [0.3, 1.0, 0.5, 1.0],
+            // Above the stains
+            priority: 1,
+            ..Default::default()
+        });
+
+    transform.translation.y += 0.05;
+    world
+        .entity()
+        .set(ClickMark::default())
+        .set(transform)
+        .set(GlobalTransform::default())
+        .set(ParticleEmitter {
+            cone_angle: 0.8,
+            speed: (1.5, 3.0),
+            lifetime: (0.4, 0.8),
+            gravity: Vec3::new(0.0, -6.0, 0.0),
+            start_color: [0.6, 1.0, 0.6, 1.0],
+            end_color: [0.2, 0.8, 0.3, 0.0],
+            start_size: 0.12,
+            end_size: 0.02,
+            ..ParticleEmitter::burst(32)
+        });
+}
+
+fn setup_navigation(world: &World) {
+    // Clicking needs a free cursor
+    world.get::<&MainWindow>(|window| {
+        let _ = window.0.set_cursor_grab(CursorGrabMode::None);
+        window.0.set_cursor_visible(true);
+    });
+
+    world.get::<&mut InputMap>(|input_map| {
+        input_map.bind_mouse_button(MouseButtonId::Left, ACTION_NAV_CLICK);
+    });
+
+    world.get::<&AssetServer>(|asset_server| {
+        let floor = asset_server.add_mesh("navigation_floor", primitives::plane(FLOOR_SIZE));
+        let wall = asset_server.add_mesh("navigation_wall", primitives::cube(1.0));
+        let agent = asset_server.add_mesh(
+            "navigation_agent",
+            primitives::capsule(AGENT_RADIUS, AGENT_HEIGHT, 16, 8),
+        );
+
+        let material = |name: &str, color: [f32; 4]| {
+            asset_server.create_material(name, |m| m.base_color(color).roughness(0.7))
+        };
+        let floor_material = material("navigation_floor_material", [0.5, 0.5, 0.5, 1.0]);
+        let wall_material = material("navigation_wall_material", [0.7, 0.4, 0.3, 1.0]);
+        let agent_material = material("navigation_agent_material", [0.2, 0.5, 0.9, 1.0]);
+
+        let marker = asset_server.add_texture(decal_texture("navigation_marker", |r| {
+            // Ring
+            (1.0 - (r - 0.75).abs() * 8.0).clamp(0.0, 1.0)
+        }));
+        world.get::<&mut NavigationDecals>(|decals| decals.marker = marker.ok());
+
+        PropBundle::new(floor, floor_material)
+            .spawn(world)
+            .expect("navigation floor")
+            .set_name("navigation_floor")
+            .add(NavMeshAffector);
+
+        for (index, (x, z, half_width, half_depth)) in WALLS.into_iter().enumerate() {
+            let mut transform = Transform::from_xyz(x, WALL_HEIGHT * 0.5, z);
+            transform.scale = Vec3::new(half_width * 2.0, WALL_HEIGHT, half_depth * 2.0);
+
+            PropBundle::new(wall.clone(), wall_material.clone())
+                .with_transform(transform)
+                .spawn(world)
+                .expect("navigation wall")
+                .set_name(&format!("navigation_wall_{}", index))
+                .add(NavMeshAffector);
+        }
+
+        world
+            .entity_named("navigation_agent")
+            .add(NavigationSampleAgent)
+            .set(NavAgent::new(AGENT_RADIUS, 3.0))
+            .set(MovementIntent::default())
+            .set(Transform::from_xyz(-8.0, AGENT_HEIGHT * 0.5 + AGENT_RADIUS, 4.0))
+            .set(GlobalTransform::default())
+            .set(MeshDefinition(agent))
+            .set(MaterialDefinition(agent_material));
+    });
+
+    spawn_stains(world);
+
+    // Fixed overhead camera, so mouse look doesn't move it
+    CameraBundle::perspective(45f32.to_radians())
+        .at(Vec3::new(0.0, 22.0, 16.0))
+        .looking_at(Vec3::ZERO)
+        .player(0)
+        .spawn(world)
+        .expect("navigation camera")
+        .set_name("navigation_camera");
+}
+
+// Stains of random size, tint and rotation; the ones overlapping a wall show the angle
+// fade, and overlapping ones are ordered by their priority
+fn spawn_stains(world: &World) {
+    let stain = world.get::<&AssetServer>(|asset_server| {
+        asset_server
+            .add_texture(decal_texture("navigation_stain", |r| 1.0 - r * r))
+            .ok()
+    });
+
+    let mut seed = 0x2545_f491u32;
+    let mut random = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        (seed >> 8) as f32 / (1u32 << 24) as f32
+    };
+
+    for index in 0..STAIN_COUNT {
+        let x = (random() - 0.5) * FLOOR_SIZE * 0.9;
+        let z = (random() - 0.5) * FLOOR_SIZE * 0.9;
+        let size = 0.5 + random() * 1.5;
+        let rotation = Quat::from_rotation_y(random() * std::f32::consts::TAU);
+        let mut transform = Transform::from_rotation(rotation);
+        transform.translation = Vec3::new(x, 0.0, z);
+
+        world
+            .entity_named(&format!("navigation_stain_{}", index))
+            .set(transform)
+            .set(GlobalTransform::default())
+            .set(Decal {
+                texture: stain.clone(),
+                size: Vec2::splat(size),
+                depth: 1.0,
+                color: [0.2 + random() * 0.3, 0.15 + random() * 0.2, 0.1, 0.8],
+                priority: -((random() * 4.0) as i32),
+                ..Default::default()
+            });
+    }
+}
+
+// White texture with the alpha given by `alpha(distance from center)`, 1 at the edge
+fn decal_texture(name: &str, alpha: impl Fn(f32) -> f32) -> TextureData {
+    let size = DECAL_TEXTURE_SIZE;
+    let mut pixels = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let u = (x as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+            let v = (y as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+            let r = (u * u + v * v).sqrt();
+            let a = if r > 1.0 { 0.0 } else { alpha(r) };
+            pixels.extend([255, 255, 255, (a * 255.0) as u8]);
+        }
+    }
+
+    TextureData {
+        name: name.to_string(),
+        pixels: TextureType::LDR(pixels),
+        width: size,
+        height: size,
+        format: TextureFormat::Rgba8UnormSrgb,
+        mips: true,
+    }
+}
@@ -0,0 +1,140 @@
+// Occlusion culling sample: a 2M triangle statue, tagged OcclusionCullable, standing
+// behind a wall. From the front the wall hides it and its draw is skipped; walking
+// around the wall (WASD) brings it back a few frames after its box shows. Every two
+// seconds the GPU time of the main pass and the skipped draws are logged.
+//
+// Run with: cargo run -p catalyst_app --example occlusion
+//
+// O turns occlusion culling on and off, to compare the main pass time. T moves the
+// camera to the front of the wall, a jump that throws the results in flight away.
+
+use catalyst::{core::log, prelude::*, renderer::RenderStats};
+use catalyst_app::SamplePlugin;
+
+pub const ACTION_TOGGLE_OCCLUSION: ActionId = ActionId(360);
+pub const ACTION_TELEPORT: ActionId = ActionId(361);
+
+// Far enough from the default scene to be seen on its own
+const ORIGIN: Vec3 = Vec3::new(-60.0, 0.0, 0.0);
+const REPORT_INTERVAL: f32 = 2.0;
+
+#[derive(Component, Default)]
+pub struct OcclusionSample {
+    since_report: f32,
+}
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    register_occlusion_sample(&mut app);
+    app.add_plugin(SamplePlugin);
+    run_catalyst_app(app)
+}
+
+fn register_occlusion_sample(app: &mut App) {
+    app.register_singleton_default::<OcclusionSample>();
+
+    app.world
+        .system_named::<&AssetServer>("occlusion_setup")
+        .kind(flecs::pipeline::OnStart)
+        .run(|iter| setup_occlusion(&iter.world()));
+
+    app.world
+        .system_named::<(&mut OcclusionCulling, &InputState)>("occlusion_toggle")
+        .kind(flecs::pipeline::OnUpdate)
+        .each_iter(|iter, _, (culling, input)| {
+            if input.just_pressed(ACTION_TOGGLE_OCCLUSION) {
+                culling.enabled = !culling.enabled;
+                log::info!(
+                    "Occlusion culling {}",
+                    if culling.enabled { "on" } else { "off" }
+                );
+            }
+            if input.just_pressed(ACTION_TELEPORT) {
+                iter.world()
+                    .query::<&mut Transform>()
+                    .with(Camera::id())
+                    .build()
+                    .each(|transform| {
+                        *transform = Transform::from_xyz(ORIGIN.x, 2.0, ORIGIN.z + 12.0)
+                            .looking_at(ORIGIN + Vec3::Y * 2.0, Vec3::Y);
+                    });
+            }
+        });
+
+    app.world
+        .system_named::<(&mut OcclusionSample, &OcclusionCulling, &RenderStats, &Time)>(
+            "occlusion_report",
+        )
+        .kind(flecs::pipeline::PostUpdate)
+        .each(|(sample, culling, stats, time)| {
+            sample.since_report += time.delta_seconds();
+            if sample.since_report < REPORT_INTERVAL {
+                return;
+            }
+            sample.since_report = 0.0;
+
+            let main_pass_ms = stats
+                .pass_timings
+                .iter()
+                .find(|(name, _)| *name == "Main Render Pass")
+                .map(|(_, ms)| format!("{:.3} ms", ms))
+                .unwrap_or_else(|| "not timed".to_string());
+            log::info!(
+                "Main pass: {}, {} of {} tested hidden, {} draws skipped",
+                main_pass_ms,
+                culling.occluded_entities(),
+                culling.tested_entities(),
+                stats.occlusion_skipped_draws
+            );
+        });
+}
+
+fn setup_occlusion(world: &World) {
+    world.get::<&mut InputMap>(|input_map| {
+        input_map.bind_keyboard_button(
+            winit::keyboard::KeyCode::KeyO as u16,
+            ACTION_TOGGLE_OCCLUSION,
+        );
+        input_map.bind_keyboard_button(winit::keyboard::KeyCode::KeyT as u16, ACTION_TELEPORT);
+    });
+
+    world.get::<&AssetServer>(|asset_server| {
+        let cube = asset_server.add_mesh("occlusion_cube", primitives::cube(1.0));
+        let wall_material = asset_server.create_material("occlusion_wall", |m| {
+            m.base_color([0.6, 0.55, 0.5, 1.0]).roughness(0.9)
+        });
+
+        PropBundle::new(cube.clone(), wall_material.clone())
+            .with_transform(Transform {
+                translation: ORIGIN + Vec3::new(0.0, 3.0, 0.0),
+                scale: Vec3::new(10.0, 6.0, 0.5),
+                ..Default::default()
+            })
+            .spawn(world)
+            .expect("occlusion wall")
+            .set_name("occlusion_wall");
+        PropBundle::new(cube, wall_material)
+            .with_transform(Transform {
+                translation: ORIGIN + Vec3::new(0.0, -0.25, -4.0),
+                scale: Vec3::new(30.0, 0.5, 30.0),
+                ..Default::default()
+            })
+            .spawn(world)
+            .expect("occlusion floor")
+            .set_name("occlusion_floor");
+
+        // About 2M triangles: (2 * 500 + 1) rows of 1000 quads
+        let statue =
+            asset_server.add_mesh("occlusion_statue", primitives::capsule(1.0, 2.0, 1000, 500));
+        let marble = asset_server.create_material("occlusion_marble", |m| {
+            m.base_color([0.9, 0.9, 0.95, 1.0]).roughness(0.3)
+        });
+        PropBundle::new(statue, marble)
+            .at(ORIGIN + Vec3::new(0.0, 2.0, -4.0))
+            .spawn(world)
+            .expect("occlusion statue")
+            .set_name("occlusion_statue")
+            .add(OcclusionCullable);
+    });
+}
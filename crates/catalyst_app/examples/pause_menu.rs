@@ -0,0 +1,250 @@
+// Pause menu sample: a short loading state, then crates drop onto a floor every second
+// next to a spinning cube and a bobbing light. Escape pushes the menu state: gameplay
+// time, the physics steps and the light's timeline stop, the spinning and dropping
+// systems are disabled, the cursor is freed and input switches to the UI context.
+// The menu resumes (Escape again), toggles vsync or quits; resuming puts the cursor
+// grab and input contexts back as they were. Escape during loading pauses over it.
+//
+// Run with: cargo run -p catalyst_app --example pause_menu
+
+use catalyst::{
+    core::log,
+    debug::{egui, register_game_ui},
+    prelude::*,
+};
+use catalyst_app::SamplePlugin;
+
+pub const ACTION_PAUSE_MENU: ActionId = ActionId(367);
+
+const LOADING_SECONDS: f32 = 1.5;
+const DROP_SECONDS: f32 = 1.0;
+const MAX_CRATES: usize = 20;
+const SPIN_SPEED: f32 = 1.5;
+
+#[derive(Component)]
+struct Spinner;
+
+/// Loading progress and the crates dropped so far, oldest first.
+#[derive(Component, Default)]
+pub struct PauseMenuSample {
+    loading: f32,
+    until_drop: f32,
+    crates: Vec<Entity>,
+    crate_mesh: Option<(Handle<MeshData>, Handle<MaterialData>)>,
+}
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    register_pause_menu_sample(&mut app);
+    app.add_plugin(SamplePlugin);
+    run_catalyst_app(app)
+}
+
+fn register_pause_menu_sample(app: &mut App) {
+    app.register_singleton_default::<PauseMenuSample>();
+    register_game_ui(app, pause_menu_ui);
+
+    app.world
+        .system_named::<&AssetServer>("pause_menu_setup")
+        .kind(flecs::pipeline::OnStart)
+        .run(|iter| setup_pause_menu(&iter.world()));
+
+    // In every state: Escape opens the menu over gameplay or loading, and closes it
+    app.world
+        .system_named::<(&InputState, &mut GameStates)>("pause_menu_toggle")
+        .kind(flecs::pipeline::OnUpdate)
+        .each(|(input, states)| {
+            if !input.just_pressed(ACTION_PAUSE_MENU) || states.has_pending() {
+                return;
+            }
+            if states.current() == STATE_MENU {
+                states.pop();
+            } else {
+                states.push(STATE_MENU);
+            }
+        });
+
+    // Real time, since loading pauses gameplay time
+    app.world
+        .system_named::<(&Time, &mut GameStates, &mut PauseMenuSample)>("pause_menu_loading")
+        .kind(flecs::pipeline::OnUpdate)
+        .each(|(time, states, sample)| {
+            if states.current() != STATE_LOADING || states.has_pending() {
+                return;
+            }
+            sample.loading += time.delta_seconds();
+            if sample.loading >= LOADING_SECONDS {
+                states.pop();
+            }
+        });
+
+    let spin = app
+        .world
+        .system_named::<(&mut Transform, &Time)>("pause_menu_spin")
+        .with(Spinner)
+        .kind(flecs::pipeline::OnUpdate)
+        .each(|(transform, time)| {
+            if time.is_paused() {
+                log::error!("pause_menu_spin ran while gameplay time was paused");
+            }
+            let angle = SPIN_SPEED * time.scaled_delta().as_secs_f32();
+            transform.rotation *= Quat::from_rotation_y(angle);
+        });
+    run_in_states(&app.world, spin.id(), &[STATE_GAMEPLAY]);
+
+    let drop = app
+        .world
+        .system_named::<(&Time, &mut PauseMenuSample)>("pause_menu_drop")
+        .kind(flecs::pipeline::OnUpdate)
+        .each_iter(|iter, _, (time, sample)| {
+            sample.until_drop -= time.scaled_delta().as_secs_f32();
+            if sample.until_drop > 0.0 {
+                return;
+            }
+            sample.until_drop = DROP_SECONDS;
+            drop_crate(&iter.world(), sample);
+        });
+    run_in_states(&app.world, drop.id(), &[STATE_GAMEPLAY]);
+}
+
+fn setup_pause_menu(world: &World) {
+    world.get::<&mut InputMap>(|input_map| {
+        let escape = winit::keyboard::KeyCode::Escape as u16;
+        input_map.bind_keyboard_button(escape, ACTION_PAUSE_MENU);
+        input_map.bind_keyboard_button_with_context(escape, ACTION_PAUSE_MENU, CTX_UI);
+    });
+
+    world.get::<&AssetServer>(|asset_server| {
+        let cube = asset_server.add_mesh("pause_menu_cube", primitives::cube(1.0));
+        let floor = asset_server.create_material("pause_menu_floor", |m| {
+            m.base_color([0.4, 0.4, 0.45, 1.0]).roughness(0.9)
+        });
+        let spinner = asset_server.create_material("pause_menu_spinner", |m| {
+            m.base_color([0.9, 0.5, 0.2, 1.0]).roughness(0.4)
+        });
+        let crates = asset_server.create_material("pause_menu_crate", |m| {
+            m.base_color([0.6, 0.45, 0.3, 1.0]).roughness(0.7)
+        });
+
+        PropBundle::new(cube.clone(), floor)
+            .with_transform(Transform {
+                translation: Vec3::new(0.0, -0.5, -8.0),
+                scale: Vec3::new(12.0, 1.0, 12.0),
+                ..Default::default()
+            })
+            .fixed()
+            .collider(ColliderShape::Box {
+                hx: 0.5,
+                hy: 0.5,
+                hz: 0.5,
+            })
+            .spawn(world)
+            .expect("pause menu floor")
+            .set_name("pause_menu_floor");
+        PropBundle::new(cube.clone(), spinner)
+            .at(Vec3::new(-3.0, 1.0, -6.0))
+            .spawn(world)
+            .expect("pause menu spinner")
+            .set_name("pause_menu_spinner")
+            .add(Spinner);
+
+        world.get::<&mut PauseMenuSample>(|sample| {
+            sample.crate_mesh = Some((cube, crates));
+        });
+    });
+
+    // Freezes with gameplay time like any timeline that isn't unscaled
+    world
+        .entity_named("pause_menu_light")
+        .set(Transform::from_xyz(2.0, 2.0, -6.0))
+        .set(GlobalTransform::default())
+        .set(PointLight {
+            color: Color::linear_rgb(1.0, 0.9, 0.7),
+            intensity: 20.0,
+            radius: 8.0,
+        })
+        .set(
+            Timeline::new().looping(Looping::PingPong).track(
+                PropertyTrack::new("Transform", "translation")
+                    .easing(Easing::EaseInOut)
+                    .key(0.0, Vec3::new(2.0, 1.0, -6.0))
+                    .key(2.0, Vec3::new(2.0, 4.0, -6.0)),
+            ),
+        );
+
+    // Pushed over gameplay; the loading system pops it
+    world.get::<&mut GameStates>(|states| states.push(STATE_LOADING));
+}
+
+fn drop_crate(world: &World, sample: &mut PauseMenuSample) {
+    let Some((mesh, material)) = sample.crate_mesh.clone() else {
+        return;
+    };
+    if sample.crates.len() >= MAX_CRATES {
+        let oldest = world.entity_from_id(sample.crates.remove(0));
+        if oldest.is_alive() {
+            oldest.destruct();
+        }
+    }
+    let x = (sample.crates.len() % 5) as f32 * 0.3 - 0.6;
+    let spawned = PropBundle::new(mesh, material)
+        .with_transform(Transform {
+            translation: Vec3::new(x, 6.0, -8.0),
+            rotation: Quat::from_rotation_z(0.3),
+            scale: Vec3::splat(0.6),
+        })
+        .dynamic(1.0)
+        .collider(ColliderShape::Box {
+            hx: 0.5,
+            hy: 0.5,
+            hz: 0.5,
+        })
+        .spawn(world);
+    match spawned {
+        Ok(entity) => sample.crates.push(entity.id()),
+        Err(error) => log::warn!("Can't drop a crate: {}", error),
+    }
+}
+
+fn pause_menu_ui(ctx: &egui::Context, world: &WorldRef) -> bool {
+    let (current, covered) =
+        world.get::<&GameStates>(|states| (states.current(), states.contains(STATE_LOADING)));
+
+    if current == STATE_LOADING {
+        egui::Area::new(egui::Id::new("pause_menu_loading"))
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .interactable(false)
+            .show(ctx, |ui| {
+                ui.heading("Loading...");
+            });
+        return true;
+    }
+    if current != STATE_MENU {
+        return false;
+    }
+
+    egui::Window::new("Paused")
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            if covered {
+                ui.label("Loading is paused");
+            }
+            if ui.button("Resume").clicked() {
+                world.get::<&mut GameStates>(|states| {
+                    if !states.has_pending() {
+                        states.pop();
+                    }
+                });
+            }
+            world.get::<&mut RenderSettings>(|settings| {
+                ui.checkbox(&mut settings.vsync, "VSync");
+            });
+            if ui.button("Quit").clicked() {
+                world.get::<&mut AppExit>(|exit| exit.request(0));
+            }
+        });
+    true
+}
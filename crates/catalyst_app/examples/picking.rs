@@ -0,0 +1,157 @@
+// Picking sample: a row of shapes in front of the camera. Left click on one to select
+// it; the entity under the cursor is read back from the GPU a frame or two later,
+// outlined and logged, and clicking the background clears the selection. Right click
+// casts a ray against the render geometry instead, logs the triangle and UV it hit and
+// leaves a decal on the surface. F12 saves a screenshot to screenshots/.
+//
+// Run with: cargo run -p catalyst_app --example picking
+
+use catalyst::{core::log, prelude::*, window::MainWindow};
+use catalyst_app::SamplePlugin;
+use winit::window::CursorGrabMode;
+
+pub const ACTION_PICK: ActionId = ActionId(365);
+pub const ACTION_SCREENSHOT: ActionId = ActionId(366);
+pub const ACTION_PICK_SURFACE: ActionId = ActionId(368);
+
+const ORIGIN: Vec3 = Vec3::new(0.0, 1.0, -6.0);
+const SPACING: f32 = 2.5;
+
+/// The entity the last click hit, and how many screenshots were taken.
+#[derive(Component, Default)]
+pub struct PickingSample {
+    selected: Option<Entity>,
+    screenshots: u32,
+}
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    register_picking_sample(&mut app);
+    app.add_plugin(SamplePlugin);
+    run_catalyst_app(app)
+}
+
+fn register_picking_sample(app: &mut App) {
+    app.register_singleton_default::<PickingSample>();
+
+    app.world
+        .system_named::<&AssetServer>("picking_setup")
+        .kind(flecs::pipeline::OnStart)
+        .run(|iter| setup_picking(&iter.world()));
+
+    app.world
+        .system_named::<(
+            &InputState,
+            &mut Picking,
+            &mut Screenshots,
+            &mut PickingSample,
+        )>("picking_click")
+        .kind(flecs::pipeline::OnUpdate)
+        .each(|(input, picking, screenshots, sample)| {
+            let cursor = Vec2::new(input.mouse_position.0, input.mouse_position.1);
+            if input.just_pressed(ACTION_PICK) {
+                picking.pick(cursor);
+            }
+            if input.just_pressed(ACTION_PICK_SURFACE) {
+                picking.pick_with(cursor, PickBackend::Raycast(RaycastBackend::Render));
+            }
+            if input.just_pressed(ACTION_SCREENSHOT) {
+                screenshots.request(format!("screenshots/picking_{}.png", sample.screenshots));
+                sample.screenshots += 1;
+            }
+        });
+
+    app.world
+        .system_named::<(&mut Picking, &mut PickingSample)>("picking_select")
+        .kind(flecs::pipeline::PostUpdate)
+        .each_iter(|iter, _, (picking, sample)| {
+            let world = iter.world();
+            for result in picking.take_results() {
+                let hit = match result.hit {
+                    Ok(hit) => hit,
+                    Err(error) => {
+                        log::warn!("Pick at {} failed: {}", result.position, error);
+                        continue;
+                    }
+                };
+
+                if let Some(previous) = sample.selected.take() {
+                    let previous = world.entity_from_id(previous);
+                    if previous.is_alive() {
+                        previous.remove(Outlined::id());
+                    }
+                }
+                if let Some(surface) = &result.surface {
+                    mark_surface(&world, surface);
+                }
+                match hit {
+                    Some(entity) => {
+                        let entity = world.entity_from_id(entity);
+                        entity.set(Outlined::default());
+                        sample.selected = Some(entity.id());
+                        log::info!("Picked '{}' (frame {})", entity.name(), result.frame);
+                    }
+                    None => log::info!("Nothing under the cursor (frame {})", result.frame),
+                }
+            }
+        });
+}
+
+// A small decal where a right click hit, lined up with the surface's texture
+fn mark_surface(world: &World, surface: &RayHit) {
+    log::info!(
+        "Hit triangle {:?} at uv {:?}, {:.2} away",
+        surface.triangle,
+        surface.uv,
+        surface.distance
+    );
+    world
+        .entity()
+        .set(Decal::transform_at(surface, 0.0))
+        .set(GlobalTransform::default())
+        .set(Decal {
+            size: Vec2::new(0.3, 0.15),
+            depth: 0.2,
+            color: [1.0, 0.9, 0.2, 1.0],
+            ..Default::default()
+        });
+}
+
+fn setup_picking(world: &World) {
+    // Clicking needs a free cursor
+    world.get::<&MainWindow>(|window| {
+        let _ = window.0.set_cursor_grab(CursorGrabMode::None);
+        window.0.set_cursor_visible(true);
+    });
+
+    world.get::<&mut InputMap>(|input_map| {
+        input_map.bind_mouse_button(MouseButtonId::Left, ACTION_PICK);
+        input_map.bind_mouse_button(MouseButtonId::Right, ACTION_PICK_SURFACE);
+        input_map.bind_keyboard_button(winit::keyboard::KeyCode::F12 as u16, ACTION_SCREENSHOT);
+    });
+
+    world.get::<&AssetServer>(|asset_server| {
+        let shapes = [
+            ("picking_cube", primitives::cube(1.2)),
+            ("picking_capsule", primitives::capsule(0.5, 1.0, 24, 8)),
+            ("picking_grid", primitives::grid(1.5, 4)),
+            ("picking_plane", primitives::plane(1.5)),
+        ];
+        let count = shapes.len();
+        for (index, (name, mesh)) in shapes.into_iter().enumerate() {
+            let mesh = asset_server.add_mesh(name, mesh);
+            let hue = index as f32 / count as f32;
+            let material = asset_server.create_material(&format!("{}_material", name), |m| {
+                m.base_color([0.3 + 0.7 * hue, 0.5, 1.0 - 0.7 * hue, 1.0])
+                    .roughness(0.5)
+            });
+            let offset = (index as f32 - (count - 1) as f32 / 2.0) * SPACING;
+            PropBundle::new(mesh, material)
+                .at(ORIGIN + Vec3::X * offset)
+                .spawn(world)
+                .expect("picking shape")
+                .set_name(name);
+        }
+    });
+}
@@ -0,0 +1,172 @@
+// Reflection probe sample: a closed room with differently colored walls and three
+// metal cubes of increasing roughness, with a ReflectionProbe matching the room. The
+// cubes reflect the walls around them instead of only the constant ambient; the probe
+// count, memory and capture timings are in the Rendering window.
+//
+// Run with: cargo run -p catalyst_app --example reflection_probes
+//
+// C captures the probe again. P makes it dynamic, capturing the lamp circling the room
+// every 10 frames, and static again.
+
+use catalyst::{core::log, prelude::*};
+use catalyst_app::SamplePlugin;
+
+pub const ACTION_CAPTURE_PROBE: ActionId = ActionId(350);
+pub const ACTION_TOGGLE_DYNAMIC: ActionId = ActionId(351);
+
+// Far enough from the default scene to be seen on its own
+const ORIGIN: Vec3 = Vec3::new(0.0, 0.0, 60.0);
+const ROOM_HALF_EXTENTS: Vec3 = Vec3::new(8.0, 3.0, 8.0);
+const WALL_THICKNESS: f32 = 0.2;
+
+/// The lamp circling the room.
+#[derive(Component)]
+pub struct ProbeLamp;
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    register_reflection_probes_sample(&mut app);
+    app.add_plugin(SamplePlugin);
+    run_catalyst_app(app)
+}
+
+fn register_reflection_probes_sample(app: &mut App) {
+    app.world.component::<ProbeLamp>();
+
+    app.world
+        .system_named::<&AssetServer>("reflection_probes_setup")
+        .kind(flecs::pipeline::OnStart)
+        .run(|iter| setup_reflection_probes(&iter.world()));
+
+    app.world
+        .system_named::<(&mut ReflectionProbe, &InputState)>("reflection_probes_input")
+        .kind(flecs::pipeline::OnUpdate)
+        .each(|(probe, input)| {
+            if input.just_pressed(ACTION_CAPTURE_PROBE) {
+                probe.request_capture = true;
+            }
+            if input.just_pressed(ACTION_TOGGLE_DYNAMIC) {
+                probe.dynamic = !probe.dynamic;
+                log::info!(
+                    "Reflection probe {}",
+                    if probe.dynamic { "dynamic" } else { "static" }
+                );
+            }
+        });
+
+    app.world
+        .system_named::<(&mut Transform, &Time)>("reflection_probes_lamp")
+        .with(ProbeLamp::id())
+        .kind(flecs::pipeline::OnUpdate)
+        .each(|(transform, time)| {
+            let angle = time.elapsed_seconds() * 0.5;
+            transform.translation = ORIGIN
+                + Vec3::new(
+                    angle.cos() * ROOM_HALF_EXTENTS.x * 0.7,
+                    ROOM_HALF_EXTENTS.y * 1.5,
+                    angle.sin() * ROOM_HALF_EXTENTS.z * 0.7,
+                );
+        });
+}
+
+fn setup_reflection_probes(world: &World) {
+    world.get::<&mut InputMap>(|input_map| {
+        input_map.bind_keyboard_button(winit::keyboard::KeyCode::KeyC as u16, ACTION_CAPTURE_PROBE);
+        input_map
+            .bind_keyboard_button(winit::keyboard::KeyCode::KeyP as u16, ACTION_TOGGLE_DYNAMIC);
+    });
+
+    world.get::<&AssetServer>(|asset_server| {
+        let cube = asset_server.add_mesh("reflection_probes_cube", primitives::cube(1.0));
+        let center = ORIGIN + Vec3::Y * ROOM_HALF_EXTENTS.y;
+        let size = ROOM_HALF_EXTENTS * 2.0;
+
+        // Floor, ceiling and walls, each its own color so the reflections can be told apart
+        let walls = [
+            ("floor", Vec3::new(0.0, -1.0, 0.0), [0.5, 0.5, 0.5, 1.0]),
+            ("ceiling", Vec3::new(0.0, 1.0, 0.0), [0.9, 0.9, 0.85, 1.0]),
+            ("wall_x", Vec3::new(1.0, 0.0, 0.0), [0.8, 0.2, 0.15, 1.0]),
+            (
+                "wall_neg_x",
+                Vec3::new(-1.0, 0.0, 0.0),
+                [0.15, 0.6, 0.2, 1.0],
+            ),
+            ("wall_z", Vec3::new(0.0, 0.0, 1.0), [0.2, 0.3, 0.8, 1.0]),
+            (
+                "wall_neg_z",
+                Vec3::new(0.0, 0.0, -1.0),
+                [0.85, 0.75, 0.2, 1.0],
+            ),
+        ];
+        for (name, side, color) in walls {
+            let material = asset_server
+                .create_material(&format!("reflection_probes_{name}"), |m| {
+                    m.base_color(color).roughness(0.9)
+                });
+            // Flat along the side's axis, as large as the room along the others
+            let scale = Vec3::select(
+                side.abs().cmpgt(Vec3::ZERO),
+                Vec3::splat(WALL_THICKNESS),
+                size,
+            );
+            world
+                .entity_named(&format!("reflection_probes_{name}"))
+                .set(Transform {
+                    translation: center + side * (ROOM_HALF_EXTENTS + WALL_THICKNESS * 0.5),
+                    scale,
+                    ..Default::default()
+                })
+                .set(GlobalTransform::default())
+                .set(MeshDefinition(cube.clone()))
+                .set(MaterialDefinition(material));
+        }
+
+        // Polished, brushed and rough metal
+        for (index, roughness) in [0.05, 0.35, 0.7].into_iter().enumerate() {
+            let material =
+                asset_server.create_material(&format!("reflection_probes_metal_{index}"), |m| {
+                    m.base_color([0.95, 0.95, 0.95, 1.0])
+                        .metallic(1.0)
+                        .roughness(roughness)
+                });
+            world
+                .entity_named(&format!("reflection_probes_metal_{index}"))
+                .set(Transform {
+                    translation: ORIGIN + Vec3::new((index as f32 - 1.0) * 4.0, 1.0, 0.0),
+                    scale: Vec3::splat(2.0),
+                    ..Default::default()
+                })
+                .set(GlobalTransform::default())
+                .set(MeshDefinition(cube.clone()))
+                .set(MaterialDefinition(material));
+        }
+
+        let lamp = asset_server.create_material("reflection_probes_lamp", |m| {
+            m.base_color([1.0, 0.9, 0.7, 1.0])
+                .emissive([1.0, 0.9, 0.7], 4.0)
+        });
+        world
+            .entity_named("reflection_probes_lamp")
+            .add(ProbeLamp)
+            .set(Transform {
+                scale: Vec3::splat(0.5),
+                ..Default::default()
+            })
+            .set(GlobalTransform::default())
+            .set(MeshDefinition(cube.clone()))
+            .set(MaterialDefinition(lamp));
+
+        // Captured from the middle of the room, its box the room's inside
+        world
+            .entity_named("reflection_probes_probe")
+            .set(Transform::from_xyz(center.x, center.y, center.z))
+            .set(GlobalTransform::default())
+            .set(ReflectionProbe {
+                half_extents: ROOM_HALF_EXTENTS,
+                blend_distance: 0.5,
+                update_interval: 10,
+                ..Default::default()
+            });
+    });
+}
@@ -0,0 +1,159 @@
+// Planar reflection sample: a pond with rippled water and a wall mirror, with a few
+// spinning cubes standing around them. The water redraws its reflection every frame,
+// the mirror every other frame; both passes show up in the GPU timings of the
+// Rendering window.
+//
+// Run with: cargo run -p catalyst_app --example reflections
+
+use catalyst::{
+    assets::material::{TextureData, TextureFormat, TextureType},
+    prelude::*,
+};
+use catalyst_app::SamplePlugin;
+
+const POND_SIZE: f32 = 24.0;
+const CUBE_COUNT: usize = 8;
+const RIPPLE_TEXTURE_SIZE: u32 = 128;
+
+/// Turns the entity around Y at `speed` radians per second.
+#[derive(Component)]
+pub struct Spin {
+    speed: f32,
+}
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    register_reflections_sample(&mut app);
+    app.add_plugin(SamplePlugin);
+    run_catalyst_app(app)
+}
+
+fn register_reflections_sample(app: &mut App) {
+    app.world.component::<Spin>();
+
+    app.world
+        .system_named::<&AssetServer>("reflections_setup")
+        .kind(flecs::pipeline::OnStart)
+        .run(|iter| setup_reflections(&iter.world()));
+
+    app.world
+        .system_named::<(&Spin, &mut Transform, &Time)>("reflections_spin")
+        .kind(flecs::pipeline::OnUpdate)
+        .each(|(spin, transform, time)| {
+            transform.rotate_y(spin.speed * time.delta_seconds());
+        });
+}
+
+fn setup_reflections(world: &World) {
+    world.get::<&AssetServer>(|asset_server| {
+        let ripples = asset_server.add_texture(ripple_texture()).ok();
+
+        // The water's own material shows through where the reflection is faint
+        let water = asset_server.create_material("reflections_water", |m| {
+            m.base_color([0.02, 0.08, 0.12, 1.0]).roughness(0.1)
+        });
+        world
+            .entity_named("reflections_pond")
+            .set(Transform::default())
+            .set(GlobalTransform::default())
+            .set(MeshDefinition(asset_server.add_mesh(
+                "reflections_pond",
+                primitives::plane(POND_SIZE),
+            )))
+            .set(MaterialDefinition(water))
+            .set(PlanarReflector {
+                normal_map: ripples,
+                tint: [0.8, 0.9, 1.0, 1.0],
+                fresnel: 0.1,
+                ..Default::default()
+            });
+
+        // Upright mirror at the far end, the plane's +Y turned to face +Z
+        let frame = asset_server.create_material("reflections_mirror", |m| {
+            m.base_color([0.05, 0.05, 0.05, 1.0]).roughness(0.2)
+        });
+        world
+            .entity_named("reflections_mirror")
+            .set(Transform {
+                translation: Vec3::new(0.0, 3.0, -POND_SIZE * 0.5),
+                rotation: Quat::from_rotation_x(std::f32::consts::FRAC_PI_2),
+                ..Default::default()
+            })
+            .set(GlobalTransform::default())
+            .set(MeshDefinition(
+                asset_server.add_mesh("reflections_mirror", primitives::plane(6.0)),
+            ))
+            .set(MaterialDefinition(frame))
+            .set(PlanarReflector {
+                fresnel: 1.0,
+                distortion: 0.0,
+                update_interval: 2,
+                ..Default::default()
+            });
+
+        let cube = asset_server.add_mesh("reflections_cube", primitives::cube(1.5));
+        for index in 0..CUBE_COUNT {
+            let t = index as f32 / CUBE_COUNT as f32;
+            let angle = t * std::f32::consts::TAU;
+            let material = asset_server.create_material(&format!("reflections_cube_{index}"), |m| {
+                m.base_color([0.5 + 0.5 * angle.cos(), 0.5 + 0.5 * angle.sin(), 0.6, 1.0])
+                    .roughness(0.5)
+            });
+
+            // Half of them stand in the water, the rest float above it
+            let height = if index % 2 == 0 { 0.4 } else { 2.5 };
+            world
+                .entity()
+                .set(Transform::from_xyz(
+                    angle.cos() * POND_SIZE * 0.3,
+                    height,
+                    angle.sin() * POND_SIZE * 0.3,
+                ))
+                .set(GlobalTransform::default())
+                .set(MeshDefinition(cube.clone()))
+                .set(MaterialDefinition(material))
+                .set(Spin { speed: 0.3 + t });
+        }
+    });
+}
+
+// Tangent-space normals of a few overlapping circular waves, tiling seamlessly
+fn ripple_texture() -> TextureData {
+    let size = RIPPLE_TEXTURE_SIZE;
+    let waves = [(0.25, 0.3, 3.0), (0.7, 0.6, 5.0), (0.4, 0.85, 4.0)];
+
+    let mut pixels = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let (u, v) = (x as f32 / size as f32, y as f32 / size as f32);
+            let mut slope = Vec2::ZERO;
+            for (cx, cy, frequency) in waves {
+                // Nearest copy of the wave center, so the pattern wraps at the edges
+                let mut offset = Vec2::new(u - cx, v - cy);
+                offset -= offset.round();
+                let distance = offset.length().max(1e-4);
+                let phase = distance * frequency * std::f32::consts::TAU;
+                slope += offset / distance * phase.cos() * 0.3;
+            }
+
+            let normal = Vec3::new(-slope.x, -slope.y, 1.0).normalize();
+            pixels.extend([
+                ((normal.x * 0.5 + 0.5) * 255.0) as u8,
+                ((normal.y * 0.5 + 0.5) * 255.0) as u8,
+                ((normal.z * 0.5 + 0.5) * 255.0) as u8,
+                255,
+            ]);
+        }
+    }
+
+    TextureData {
+        name: "reflections_ripples".to_string(),
+        pixels: TextureType::LDR(pixels),
+        width: size,
+        height: size,
+        // Normals are data, not color
+        format: TextureFormat::Rgba8Unorm,
+        mips: true,
+    }
+}
@@ -0,0 +1,213 @@
+// Portal culling sample: three rooms in a row, joined by doorways in the walls between
+// them, each filled with crates. The camera stands in the first room and turns around
+// slowly: while the doorway is out of view only the first room is drawn, and the mesh
+// draw count (logged every second, also in the Rendering window) drops.
+//
+// Run with: cargo run -p catalyst_app --example rooms
+
+use catalyst::{
+    core::{
+        log,
+        rooms::{Portal, Room},
+    },
+    prelude::*,
+    renderer::{PortalCulling, RenderStats},
+};
+use catalyst_app::SamplePlugin;
+
+// Far enough from the default scene that none of it shows through the doorways
+const ORIGIN_X: f32 = 60.0;
+const ROOM_COUNT: usize = 3;
+const ROOM_WIDTH: f32 = 10.0;
+const ROOM_DEPTH: f32 = 10.0;
+const WALL_HEIGHT: f32 = 4.0;
+const WALL_THICKNESS: f32 = 0.4;
+const DOOR_WIDTH: f32 = 2.0;
+const DOOR_HEIGHT: f32 = 2.5;
+const CRATES_PER_SIDE: usize = 4;
+const TURN_SPEED: f32 = 0.3;
+
+/// Seconds until the next draw count report.
+#[derive(Component, Default)]
+pub struct RoomsReport {
+    next: f32,
+}
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    register_rooms_sample(&mut app);
+    app.add_plugin(SamplePlugin);
+    run_catalyst_app(app)
+}
+
+fn register_rooms_sample(app: &mut App) {
+    app.register_singleton_default::<RoomsReport>();
+
+    app.world
+        .system_named::<&AssetServer>("rooms_setup")
+        .kind(flecs::pipeline::OnStart)
+        .run(|iter| setup_rooms(&iter.world()));
+
+    // Every camera stands in the first room, facing the doorway half of the time
+    app.world
+        .system_named::<(&Camera, &mut Transform, &Time)>("rooms_camera")
+        .kind(flecs::pipeline::OnUpdate)
+        .each(|(_, transform, time)| {
+            let angle = time.elapsed_seconds() * TURN_SPEED;
+            let eye = Vec3::new(ORIGIN_X, 1.7, ROOM_DEPTH * 0.3);
+            let direction = Vec3::new(angle.sin(), 0.0, -angle.cos());
+            *transform =
+                Transform::from_xyz(eye.x, eye.y, eye.z).looking_at(eye + direction, Vec3::Y);
+        });
+
+    app.world
+        .system_named::<(&RenderStats, &PortalCulling, &mut RoomsReport, &Time)>(
+            "rooms_report",
+        )
+        .kind(flecs::pipeline::PostUpdate)
+        .each(|(stats, culling, report, time)| {
+            if time.elapsed_seconds() < report.next {
+                return;
+            }
+            report.next = time.elapsed_seconds() + 1.0;
+
+            log::info!(
+                "Mesh draws: {}, rooms visible: {} of {}, {} entities culled",
+                stats.mesh_draws,
+                culling.visible_rooms(),
+                culling.room_count(),
+                culling.culled_entities()
+            );
+        });
+}
+
+fn setup_rooms(world: &World) {
+    world.get::<&AssetServer>(|asset_server| {
+        let cube = asset_server.add_mesh("rooms_cube", primitives::cube(1.0));
+        let wall = asset_server.create_material("rooms_wall", |m| {
+            m.base_color([0.7, 0.68, 0.62, 1.0]).roughness(0.9)
+        });
+        let crate_colors = [
+            [0.8, 0.3, 0.2, 1.0],
+            [0.2, 0.6, 0.3, 1.0],
+            [0.2, 0.4, 0.8, 1.0],
+        ];
+
+        let block = |name: &str, center: Vec3, size: Vec3, material: &Handle<MaterialData>| {
+            world
+                .entity_named(name)
+                .set(Transform {
+                    translation: center,
+                    scale: size,
+                    ..Default::default()
+                })
+                .set(GlobalTransform::default())
+                .set(MeshDefinition(cube.clone()))
+                .set(MaterialDefinition(material.clone()));
+        };
+
+        let mut rooms = Vec::new();
+        for index in 0..ROOM_COUNT {
+            let z = -(index as f32) * ROOM_DEPTH;
+
+            // The volume stops short of the walls between rooms: those are seen from
+            // both sides, so they stay outside every room and are never culled
+            let room = world
+                .entity_named(&format!("rooms_room_{index}"))
+                .set(Transform::from_xyz(ORIGIN_X, WALL_HEIGHT * 0.5, z))
+                .set(GlobalTransform::default())
+                .set(Room {
+                    half_extents: Vec3::new(
+                        ROOM_WIDTH * 0.5,
+                        WALL_HEIGHT * 0.5 + 0.5,
+                        (ROOM_DEPTH - WALL_THICKNESS) * 0.5 - 0.1,
+                    ),
+                });
+            rooms.push(room.id());
+
+            // Floor and side walls belong to the room
+            block(
+                &format!("rooms_floor_{index}"),
+                Vec3::new(ORIGIN_X, -0.1, z),
+                Vec3::new(ROOM_WIDTH, 0.2, ROOM_DEPTH),
+                &wall,
+            );
+            for (side, name) in [(-1.0, "left"), (1.0, "right")] {
+                let x = ORIGIN_X + side * (ROOM_WIDTH - WALL_THICKNESS) * 0.5;
+                block(
+                    &format!("rooms_side_{index}_{name}"),
+                    Vec3::new(x, WALL_HEIGHT * 0.5, z),
+                    Vec3::new(WALL_THICKNESS, WALL_HEIGHT, ROOM_DEPTH),
+                    &wall,
+                );
+            }
+
+            let material = asset_server.create_material(&format!("rooms_crate_{index}"), |m| {
+                m.base_color(crate_colors[index % crate_colors.len()]).roughness(0.6)
+            });
+            let spacing = (ROOM_WIDTH - 3.0) / (CRATES_PER_SIDE - 1) as f32;
+            for row in 0..CRATES_PER_SIDE {
+                for column in 0..CRATES_PER_SIDE {
+                    // Clear of the walkway between the doorways
+                    let x = -(ROOM_WIDTH - 3.0) * 0.5 + column as f32 * spacing;
+                    if x.abs() < DOOR_WIDTH {
+                        continue;
+                    }
+                    let offset_z = -(ROOM_DEPTH - 3.0) * 0.5 + row as f32 * spacing;
+                    block(
+                        &format!("rooms_crate_{index}_{row}_{column}"),
+                        Vec3::new(ORIGIN_X + x, 0.4, z + offset_z),
+                        Vec3::splat(0.8),
+                        &material,
+                    );
+                }
+            }
+        }
+
+        // End walls, and walls with a doorway between neighbouring rooms
+        let front = ROOM_DEPTH * 0.5;
+        let back = front - ROOM_COUNT as f32 * ROOM_DEPTH;
+        block(
+            "rooms_wall_front",
+            Vec3::new(ORIGIN_X, WALL_HEIGHT * 0.5, front),
+            Vec3::new(ROOM_WIDTH, WALL_HEIGHT, WALL_THICKNESS),
+            &wall,
+        );
+        block(
+            "rooms_wall_back",
+            Vec3::new(ORIGIN_X, WALL_HEIGHT * 0.5, back),
+            Vec3::new(ROOM_WIDTH, WALL_HEIGHT, WALL_THICKNESS),
+            &wall,
+        );
+
+        for (index, pair) in rooms.windows(2).enumerate() {
+            let z = front - (index + 1) as f32 * ROOM_DEPTH;
+            let side_width = (ROOM_WIDTH - DOOR_WIDTH) * 0.5;
+            for (side, name) in [(-1.0, "left"), (1.0, "right")] {
+                let x = ORIGIN_X + side * (DOOR_WIDTH + side_width) * 0.5;
+                block(
+                    &format!("rooms_wall_{index}_{name}"),
+                    Vec3::new(x, WALL_HEIGHT * 0.5, z),
+                    Vec3::new(side_width, WALL_HEIGHT, WALL_THICKNESS),
+                    &wall,
+                );
+            }
+            block(
+                &format!("rooms_wall_{index}_lintel"),
+                Vec3::new(ORIGIN_X, (WALL_HEIGHT + DOOR_HEIGHT) * 0.5, z),
+                Vec3::new(DOOR_WIDTH, WALL_HEIGHT - DOOR_HEIGHT, WALL_THICKNESS),
+                &wall,
+            );
+
+            world
+                .entity_named(&format!("rooms_door_{index}"))
+                .set(Transform::from_xyz(ORIGIN_X, DOOR_HEIGHT * 0.5, z))
+                .set(GlobalTransform::default())
+                .set(Portal {
+                    half_extents: Vec2::new(DOOR_WIDTH * 0.5, DOOR_HEIGHT * 0.5),
+                    ..Portal::new(pair[0], pair[1])
+                });
+        }
+    });
+}
@@ -0,0 +1,245 @@
+// Scripted sequence sample: a lamp flickers, then a door slides open, all from one
+// Sequence on the door. Meanwhile a few small sequences check ordering, parallel
+// branches joining, cancellation mid-wait and freezing while gameplay time is paused
+// (the sample pauses it for half a second). The sample exits with 0 once the door is
+// open and every check passed, 1 otherwise.
+//
+// Run with: cargo run -p catalyst_app --example sequences
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use catalyst::{core::log, prelude::*};
+use catalyst_app::SamplePlugin;
+
+// Gameplay time is paused while the sample clock is in this range (seconds)
+const PAUSE_START: f32 = 0.3;
+const PAUSE_END: f32 = 0.8;
+
+const DOOR_CLOSED: Vec3 = Vec3::new(0.0, 1.25, -4.0);
+const DOOR_LIFT: f32 = 2.4;
+const DOOR_SCALE: Vec3 = Vec3::new(1.5, 2.5, 0.2);
+const LAMP: PointLight = PointLight {
+    color: Color::linear_rgb(1.0, 0.8, 0.5),
+    intensity: 20.0,
+    radius: 8.0,
+};
+
+/// Emitted on the door when it finished opening.
+#[derive(Component)]
+pub struct DoorOpened;
+
+// Real seconds since the first frame, summed from Time::delta like the sequences see it
+type Clock = Arc<Mutex<f32>>;
+// Labels in the order the checks reached them, with the clock at that point
+type Log = Arc<Mutex<Vec<(&'static str, f32)>>>;
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    register_sequences_sample(&mut app);
+    app.add_plugin(SamplePlugin);
+    run_catalyst_app(app)
+}
+
+fn register_sequences_sample(app: &mut App) {
+    app.world.component::<DoorOpened>();
+
+    let clock = Clock::default();
+    let log = Log::default();
+
+    // Before "Advance Sequences" (OnUpdate), so every sequence sees this frame's pause
+    let pause_clock = clock.clone();
+    app.world
+        .system_named::<&mut Time>("sequences_pause")
+        .kind(flecs::pipeline::PreUpdate)
+        .each(move |time| {
+            let mut clock = pause_clock.lock().unwrap();
+            *clock += time.delta_seconds();
+            time.set_paused((PAUSE_START..PAUSE_END).contains(&*clock));
+        });
+
+    app.world
+        .system_named::<&AssetServer>("sequences_setup")
+        .kind(flecs::pipeline::OnStart)
+        .run(move |iter| setup_sequences(&iter.world(), &clock, &log));
+}
+
+fn setup_sequences(world: &World, clock: &Clock, log: &Log) {
+    let lamp = world
+        .entity_named("sequences_lamp")
+        .set(Transform::from_xyz(0.0, 3.0, -2.0))
+        .set(GlobalTransform::default())
+        .set(LAMP);
+
+    let door = world
+        .entity_named("sequences_door")
+        .set(door_transform(0.0))
+        .set(GlobalTransform::default());
+    world.get::<&AssetServer>(|asset_server| {
+        let material = asset_server.create_material("sequences_door", |m| {
+            m.base_color([0.45, 0.3, 0.2, 1.0]).roughness(0.7)
+        });
+        door.set(MeshDefinition(
+            asset_server.add_mesh("sequences_door", primitives::cube(1.0)),
+        ))
+        .set(MaterialDefinition(material));
+    });
+    door.observe::<DoorOpened>(|| log::info!("Door open"));
+
+    let dim = PointLight {
+        intensity: 2.0,
+        ..LAMP
+    };
+    let (check_log, check_clock) = (log.clone(), clock.clone());
+    door.set(
+        Sequence::new()
+            .wait_seconds(1.0)
+            .repeat(
+                4,
+                Sequence::new()
+                    .set(lamp, dim)
+                    .wait_seconds(0.08)
+                    .set(lamp, LAMP)
+                    .wait_seconds(0.15),
+            )
+            .wait_seconds(0.5)
+            .tween(Duration::from_secs_f32(1.5), |door, t| {
+                // Eased, so the door starts and stops softly
+                door.set(door_transform(t * t * (3.0 - 2.0 * t)));
+            })
+            .emit(DoorOpened)
+            .wait_seconds(0.5)
+            .run(move |door| finish(door, &check_log, &check_clock)),
+    );
+
+    // Ordering, and a parallel step ending with its slowest branch
+    world.entity_named("sequences_order").set(
+        Sequence::new()
+            .run(record(log, clock, "start"))
+            .parallel([
+                Sequence::new()
+                    .wait_seconds(0.2)
+                    .run(record(log, clock, "long")),
+                Sequence::new()
+                    .wait_seconds(0.1)
+                    .run(record(log, clock, "short")),
+            ])
+            .run(record(log, clock, "joined")),
+    );
+
+    // Cancelled halfway through its wait, so "victim" must never be logged
+    let victim = world.entity_named("sequences_victim").set(
+        Sequence::new()
+            .wait_seconds(0.25)
+            .run(record(log, clock, "victim")),
+    );
+    let (cancel_log, cancel_clock) = (log.clone(), clock.clone());
+    victim.observe::<SequenceCancelled>(move || push(&cancel_log, &cancel_clock, "cancelled"));
+    let victim = victim.id();
+    world.entity_named("sequences_canceller").set(
+        Sequence::new()
+            .wait_seconds(0.1)
+            .run(move |entity| Sequence::cancel(entity.world().entity_from_id(victim))),
+    );
+
+    // Both wait half a second; only the unscaled one keeps going through the pause
+    world.entity_named("sequences_scaled").set(
+        Sequence::new()
+            .wait_seconds(0.5)
+            .run(record(log, clock, "scaled")),
+    );
+    world.entity_named("sequences_unscaled").set(
+        Sequence::new()
+            .unscaled()
+            .wait_seconds(0.5)
+            .run(record(log, clock, "unscaled")),
+    );
+}
+
+fn door_transform(open: f32) -> Transform {
+    Transform {
+        translation: DOOR_CLOSED + Vec3::Y * DOOR_LIFT * open,
+        scale: DOOR_SCALE,
+        ..Default::default()
+    }
+}
+
+fn record(
+    log: &Log,
+    clock: &Clock,
+    label: &'static str,
+) -> impl FnMut(EntityView) + Send + Sync + 'static {
+    let (log, clock) = (log.clone(), clock.clone());
+    move |_| push(&log, &clock, label)
+}
+
+fn push(log: &Log, clock: &Clock, label: &'static str) {
+    let now = *clock.lock().unwrap();
+    log.lock().unwrap().push((label, now));
+}
+
+fn finish(door: EntityView, log: &Log, clock: &Clock) {
+    let log = log.lock().unwrap();
+    for (label, at) in log.iter() {
+        log::info!("{:>9} at {:.3}s", label, at);
+    }
+
+    let code = match check(&log) {
+        Ok(()) => {
+            log::info!(
+                "Sequences done in {:.2}s, all checks passed",
+                *clock.lock().unwrap()
+            );
+            0
+        }
+        Err(e) => {
+            log::error!("Sequence check failed: {}", e);
+            1
+        }
+    };
+    door.world().get::<&mut AppExit>(|exit| exit.request(code));
+}
+
+fn check(log: &[(&'static str, f32)]) -> Result<(), String> {
+    let at = |label: &str| {
+        log.iter()
+            .position(|(logged, _)| *logged == label)
+            .map(|index| (index, log[index].1))
+    };
+    let expect = |label: &str| at(label).ok_or(format!("'{}' never ran", label));
+
+    let order = ["start", "short", "long", "joined"]
+        .map(expect)
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+    if !order.windows(2).all(|pair| pair[0].0 < pair[1].0) {
+        return Err("steps ran out of order".to_string());
+    }
+    if order[3].1 != order[2].1 {
+        return Err("the parallel step didn't end with its last branch".to_string());
+    }
+
+    expect("cancelled")?;
+    if at("victim").is_some() {
+        return Err("a cancelled sequence kept running".to_string());
+    }
+
+    let (_, unscaled) = expect("unscaled")?;
+    let (_, scaled) = expect("scaled")?;
+    if !(PAUSE_START..PAUSE_END).contains(&unscaled) {
+        return Err(format!(
+            "unscaled wait ended at {:.3}s, not during the pause",
+            unscaled
+        ));
+    }
+    if scaled < PAUSE_END {
+        return Err(format!(
+            "scaled wait ended at {:.3}s, before the pause was over",
+            scaled
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,158 @@
+// Split-screen sample: two capsule characters on a shared ground plane,
+// each with its own camera and input device.
+//
+// Run with: cargo run -p catalyst_app --example split_screen
+//
+// Player 0 uses keyboard (WASD) + mouse, player 1 the first gamepad that sends input
+// (left stick to move, right stick to turn).
+
+use catalyst::{
+    input::physical::GamepadAxisId,
+    prelude::*,
+};
+
+use catalyst_app::{
+    ACTION_MOVE_BACKWARD, ACTION_MOVE_FORWARD, ACTION_MOVE_LEFT, ACTION_MOVE_RIGHT, AXIS_LOOK_X,
+    AXIS_MOUSE_X, SamplePlugin,
+};
+
+pub const AXIS_MOVE_X: AxisId = AxisId(200);
+pub const AXIS_MOVE_Y: AxisId = AxisId(201);
+
+const CAPSULE_RADIUS: f32 = 0.4;
+const CAPSULE_HEIGHT: f32 = 1.0;
+
+// Tag for the entities moved by the per-player controller
+#[derive(Component)]
+pub struct SplitScreenCharacter;
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    register_split_screen_sample(&mut app);
+    app.add_plugin(SamplePlugin);
+    run_catalyst_app(app)
+}
+
+fn register_split_screen_sample(app: &mut App) {
+    app.world.set(PlayerInputs::new(2));
+    app.world.set(SplitScreenSettings {
+        layout: SplitScreenLayout::Vertical,
+    });
+
+    app.world
+        .system_named::<&AssetServer>("split_screen_setup")
+        .kind(flecs::pipeline::OnStart)
+        .run(|iter| {
+            setup_split_screen_scene(&iter.world());
+        });
+
+    app.world
+        .system_named::<(&mut Transform, &PlayerIndex, &PlayerInputs, &Time)>(
+            "split_screen_character_movement",
+        )
+        .with(SplitScreenCharacter)
+        .kind(flecs::pipeline::OnUpdate)
+        .each(|(transform, player, players, time)| {
+            let Some(input) = players.state(*player) else {
+                return;
+            };
+
+            let dt = time.delta_seconds();
+            let speed = 5.0;
+            let turn_speed = 3.0;
+
+            // Keyboard actions and gamepad sticks both feed the same movement vector
+            let mut forward = input.axis(AXIS_MOVE_Y);
+            let mut strafe = input.axis(AXIS_MOVE_X);
+            if input.held(ACTION_MOVE_FORWARD) {
+                forward += 1.0;
+            }
+            if input.held(ACTION_MOVE_BACKWARD) {
+                forward -= 1.0;
+            }
+            if input.held(ACTION_MOVE_RIGHT) {
+                strafe += 1.0;
+            }
+            if input.held(ACTION_MOVE_LEFT) {
+                strafe -= 1.0;
+            }
+
+            let yaw = -input.axis(AXIS_LOOK_X) * turn_speed * dt - input.axis(AXIS_MOUSE_X);
+            transform.rotation = Quat::from_rotation_y(yaw) * transform.rotation;
+
+            let move_dir = transform.rotation * Vec3::new(strafe, 0.0, -forward);
+            if move_dir.length_squared() > 0.0 {
+                transform.translation += move_dir.clamp_length_max(1.0) * speed * dt;
+            }
+        });
+}
+
+fn setup_split_screen_scene(world: &World) {
+    world.get::<&mut InputMap>(|input_map| {
+        input_map
+            .bind_gamepad_axis(GamepadAxisId::LeftStickX as u16, AXIS_MOVE_X, 1.0)
+            .bind_gamepad_axis(GamepadAxisId::LeftStickY as u16, AXIS_MOVE_Y, 1.0)
+            .bind_gamepad_axis(GamepadAxisId::RightStickX as u16, AXIS_LOOK_X, 1.0);
+    });
+    // Slow turns near the center of the stick, without drifting when it rests
+    world.get::<&mut AxisSettings>(|settings| {
+        settings.set_default(
+            AxisProcessor::new(AXIS_LOOK_X)
+                .deadzone(0.15)
+                .curve(ResponseCurve::Power(2.0))
+                .smoothing(0.05),
+        );
+    });
+
+    world.get::<&AssetServer>(|asset_server| {
+        let capsule = asset_server.add_mesh(
+            "split_screen_capsule",
+            primitives::capsule(CAPSULE_RADIUS, CAPSULE_HEIGHT, 24, 8),
+        );
+        let ground = asset_server.add_mesh("split_screen_ground", primitives::plane(40.0));
+
+        let material = |name: &str, base_color: [f32; 4]| {
+            asset_server.create_material(name, |m| m.base_color(base_color))
+        };
+
+        let ground_material = material("split_screen_ground_material", [0.4, 0.4, 0.4, 1.0]);
+        let player_materials = [
+            material("split_screen_player_0", [0.9, 0.2, 0.2, 1.0]),
+            material("split_screen_player_1", [0.2, 0.4, 0.9, 1.0]),
+        ];
+
+        PropBundle::new(ground, ground_material)
+            .spawn(world)
+            .expect("split screen ground")
+            .set_name("split_screen_ground");
+
+        for (index, player_material) in player_materials.into_iter().enumerate() {
+            let player = PlayerIndex(index as u8);
+            let start_x = if index == 0 { -2.0 } else { 2.0 };
+
+            let character = world
+                .entity_named(&format!("player_{}", index))
+                .add(SplitScreenCharacter)
+                .set(player)
+                .set(Transform::from_xyz(
+                    start_x,
+                    CAPSULE_HEIGHT * 0.5 + CAPSULE_RADIUS,
+                    0.0,
+                ))
+                .set(GlobalTransform::default())
+                .set(MeshDefinition(capsule.clone()))
+                .set(MaterialDefinition(player_material));
+
+            // Third person camera, behind and above the character
+            CameraBundle::perspective(45f32.to_radians())
+                .at(Vec3::new(0.0, 1.5, 5.0))
+                .looking_at(Vec3::new(0.0, 0.5, 0.0))
+                .player(index as u8)
+                .spawn(world)
+                .expect("split screen camera")
+                .child_of(character)
+                .set_name(&format!("player_{}_camera", index));
+        }
+    });
+}
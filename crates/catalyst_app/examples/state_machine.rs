@@ -0,0 +1,149 @@
+// State machine sample: an enemy cube idles until the camera comes within reach, then
+// chases it through MovementIntent and attacks once close. Chase and Attack are
+// sub-states of Combat, whose transition back to Idle fires from either when the
+// camera gets away. Every change of state is logged from a StateChanged observer.
+//
+// Run with: cargo run -p catalyst_app --example state_machine
+//
+// Fly towards the cube to get its attention. There is no animation player yet, so the
+// attack is shown by the cube pulsing in its Attack state.
+
+use catalyst::{core::log, prelude::*};
+use catalyst_app::SamplePlugin;
+
+const ENEMY_START: Vec3 = Vec3::new(0.0, 0.5, -12.0);
+const SPOT_RADIUS: f32 = 10.0;
+const ATTACK_RADIUS: f32 = 2.5;
+// Further than SPOT_RADIUS, so the enemy doesn't flip between Idle and Chase at the edge
+const LOSE_RADIUS: f32 = 16.0;
+const CHASE_SPEED: f32 = 3.0;
+const PULSE_SPEED: f32 = 8.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EnemyState {
+    Idle,
+    Combat,
+    Chase,
+    Attack,
+}
+
+/// Where the enemies look for the player: the first camera, copied every frame.
+#[derive(Component, Default)]
+pub struct PlayerPosition(pub Vec3);
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    register_state_machine_sample(&mut app);
+    app.add_plugin(SamplePlugin);
+    run_catalyst_app(app)
+}
+
+fn register_state_machine_sample(app: &mut App) {
+    app.register_singleton_default::<PlayerPosition>();
+    state_machine_system::<EnemyState>(&mut app.world);
+
+    // Before the state machines (OnUpdate), so the guards see this frame's camera
+    app.world
+        .system_named::<(&Camera, &GlobalTransform, &mut PlayerPosition)>("state_machine_player")
+        .kind(flecs::pipeline::PreUpdate)
+        .each(|(_, global, player)| {
+            player.0 = global.0.w_axis.truncate();
+        });
+
+    app.world
+        .system_named::<&AssetServer>("state_machine_setup")
+        .kind(flecs::pipeline::OnStart)
+        .run(|iter| setup_state_machine(&iter.world()));
+}
+
+// Horizontal distance from the enemy to the player
+fn player_distance(entity: EntityView) -> f32 {
+    let player = entity.world().get::<&PlayerPosition>(|player| player.0);
+    let position = entity
+        .try_get::<&GlobalTransform>(|global| global.0.w_axis.truncate())
+        .unwrap_or(ENEMY_START);
+    (player - position).with_y(0.0).length()
+}
+
+fn enemy_machine() -> StateMachine<EnemyState> {
+    use EnemyState::*;
+
+    StateMachine::new(Idle)
+        .state(State::new(Idle))
+        .state(State::new(Combat).on_exit(|ctx| {
+            ctx.entity.set(MovementIntent::default());
+        }))
+        .state(State::new(Chase).child_of(Combat).on_update(|ctx| {
+            let player = ctx.entity.world().get::<&PlayerPosition>(|player| player.0);
+            let Some(position) = ctx
+                .entity
+                .try_get::<&GlobalTransform>(|global| global.0.w_axis.truncate())
+            else {
+                return;
+            };
+            let direction = (player - position).with_y(0.0).normalize_or_zero();
+            ctx.entity.set(MovementIntent {
+                velocity: direction * CHASE_SPEED,
+            });
+        }))
+        .state(
+            State::new(Attack)
+                .child_of(Combat)
+                .on_enter(|ctx| {
+                    ctx.entity.set(MovementIntent::default());
+                })
+                .on_update(|ctx| {
+                    let pulse = 1.0 + (ctx.time_in_state * PULSE_SPEED).sin().abs() * 0.3;
+                    ctx.entity.try_get::<&mut Transform>(|transform| {
+                        transform.scale = Vec3::splat(pulse)
+                    });
+                })
+                .on_exit(|ctx| {
+                    ctx.entity
+                        .try_get::<&mut Transform>(|transform| transform.scale = Vec3::ONE);
+                }),
+        )
+        .transition(
+            Transition::new(Idle, Chase).when(|ctx| player_distance(ctx.entity) < SPOT_RADIUS),
+        )
+        .transition(
+            Transition::new(Chase, Attack).when(|ctx| player_distance(ctx.entity) < ATTACK_RADIUS),
+        )
+        .transition(
+            Transition::new(Attack, Chase)
+                .when(|ctx| player_distance(ctx.entity) > ATTACK_RADIUS * 1.5),
+        )
+        // Checked first: losing the player ends the fight from any combat state
+        .transition(
+            Transition::new(Combat, Idle)
+                .when(|ctx| player_distance(ctx.entity) > LOSE_RADIUS)
+                .priority(1),
+        )
+}
+
+fn setup_state_machine(world: &World) {
+    world.get::<&AssetServer>(|asset_server| {
+        let cube = asset_server.add_mesh("state_machine_enemy", primitives::cube(1.0));
+        let material = asset_server.create_material("state_machine_enemy", |m| {
+            m.base_color([0.8, 0.15, 0.1, 1.0]).roughness(0.5)
+        });
+
+        let enemy = world
+            .entity_named("state_machine_enemy")
+            .set(Transform::from_xyz(
+                ENEMY_START.x,
+                ENEMY_START.y,
+                ENEMY_START.z,
+            ))
+            .set(GlobalTransform::default())
+            .set(MovementIntent::default())
+            .set(MeshDefinition(cube))
+            .set(MaterialDefinition(material))
+            .set(enemy_machine());
+
+        enemy.observe_payload::<&StateChanged<EnemyState>>(|changed| {
+            log::info!("Enemy: {:?} -> {:?}", changed.from, changed.to);
+        });
+    });
+}
@@ -0,0 +1,129 @@
+// Static batching sample: 500 cubes tagged StaticGeometry, sharing one material, merged
+// into a single batch and drawn with one call. The mesh draw count and the batch
+// totals are logged every second (also in the Rendering window).
+//
+// Run with: cargo run -p catalyst_app --example static_batching
+//
+// B turns batching off and on, so the two can be compared. M moves one cube, which
+// dissolves the batch; it is merged again on the next frame.
+
+use catalyst::{core::log, prelude::*, renderer::RenderStats};
+use catalyst_app::SamplePlugin;
+
+pub const ACTION_TOGGLE_BATCHING: ActionId = ActionId(320);
+pub const ACTION_MOVE_CUBE: ActionId = ActionId(321);
+
+// Far enough from the default scene to be seen on its own
+const ORIGIN: Vec3 = Vec3::new(-60.0, 0.0, 0.0);
+const CUBES_PER_SIDE: usize = 25;
+const CUBE_COUNT: usize = 500;
+const SPACING: f32 = 1.5;
+
+/// Seconds until the next report.
+#[derive(Component, Default)]
+pub struct StaticBatchingReport {
+    next: f32,
+}
+
+/// The cube M moves.
+#[derive(Component)]
+pub struct MovableCube;
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    register_static_batching_sample(&mut app);
+    app.add_plugin(SamplePlugin);
+    run_catalyst_app(app)
+}
+
+fn register_static_batching_sample(app: &mut App) {
+    app.register_singleton_default::<StaticBatchingReport>();
+    app.world.component::<MovableCube>();
+
+    app.world
+        .system_named::<&AssetServer>("static_batching_setup")
+        .kind(flecs::pipeline::OnStart)
+        .run(|iter| setup_static_batching(&iter.world()));
+
+    app.world
+        .system_named::<(&InputState, &mut StaticBatching)>("static_batching_toggle")
+        .kind(flecs::pipeline::OnUpdate)
+        .each(|(input, batching)| {
+            if input.just_pressed(ACTION_TOGGLE_BATCHING) {
+                batching.enabled = !batching.enabled;
+                log::info!(
+                    "Static batching {}",
+                    if batching.enabled { "on" } else { "off" }
+                );
+            }
+        });
+
+    app.world
+        .system_named::<(&mut Transform, &InputState)>("static_batching_move")
+        .with(MovableCube::id())
+        .kind(flecs::pipeline::OnUpdate)
+        .each(|(transform, input)| {
+            if input.just_pressed(ACTION_MOVE_CUBE) {
+                transform.translation.y += 0.5;
+            }
+        });
+
+    app.world
+        .system_named::<(&RenderStats, &mut StaticBatchingReport, &Time)>("static_batching_report")
+        .kind(flecs::pipeline::PostUpdate)
+        .each(|(stats, report, time)| {
+            if time.elapsed_seconds() < report.next {
+                return;
+            }
+            report.next = time.elapsed_seconds() + 1.0;
+
+            let batching = &stats.static_batching;
+            log::info!(
+                "Mesh draws: {}, {} batches of {} entities, {} draws saved, {:.1} MB",
+                stats.mesh_draws,
+                batching.batches,
+                batching.batched_entities,
+                batching.draws_saved,
+                batching.bytes as f64 / (1024.0 * 1024.0)
+            );
+        });
+}
+
+fn setup_static_batching(world: &World) {
+    world.get::<&mut InputMap>(|input_map| {
+        input_map.bind_keyboard_button(
+            winit::keyboard::KeyCode::KeyB as u16,
+            ACTION_TOGGLE_BATCHING,
+        );
+        input_map.bind_keyboard_button(winit::keyboard::KeyCode::KeyM as u16, ACTION_MOVE_CUBE);
+    });
+
+    world.get::<&AssetServer>(|asset_server| {
+        let cube = asset_server.add_mesh("static_batching_cube", primitives::cube(0.8));
+        let material = asset_server.create_material("static_batching_material", |m| {
+            m.base_color([0.6, 0.55, 0.45, 1.0]).roughness(0.8)
+        });
+
+        for index in 0..CUBE_COUNT {
+            let (row, column) = (index / CUBES_PER_SIDE, index % CUBES_PER_SIDE);
+            // Turned and stacked a little, so the baked transforms show
+            let height = 0.4 + (index % 3) as f32 * 0.3;
+            let cube = world
+                .entity()
+                .add(StaticGeometry)
+                .set(Transform {
+                    translation: ORIGIN
+                        + Vec3::new(column as f32 * SPACING, height, row as f32 * SPACING),
+                    rotation: Quat::from_rotation_y(index as f32 * 0.35),
+                    ..Default::default()
+                })
+                .set(GlobalTransform::default())
+                .set(MeshDefinition(cube.clone()))
+                .set(MaterialDefinition(material.clone()));
+            if index == 0 {
+                cube.add(MovableCube);
+            }
+        }
+    });
+}
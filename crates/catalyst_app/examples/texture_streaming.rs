@@ -0,0 +1,146 @@
+// Texture streaming stress sample: 40 large procedural textures on panels spread
+// over a wide area, about 850 MB with full mip chains. The camera flies a loop over
+// them and the streamer keeps the allocation inside the default 512 MB budget,
+// streaming in the panels ahead and shrinking the ones left behind. Residency per
+// texture is shown in the Textures debug window.
+//
+// Run with: cargo run --release -p catalyst_app --example texture_streaming
+
+use catalyst::{
+    assets::material::{TextureData, TextureFormat, TextureType},
+    core::log,
+    prelude::*,
+    renderer::TextureStreamingStats,
+};
+use catalyst_app::SamplePlugin;
+
+const TEXTURE_COUNT: usize = 40;
+const TEXTURE_SIZE: u32 = 2048;
+const PANEL_SIZE: f32 = 6.0;
+const RING_RADIUS: f32 = 60.0;
+const FLIGHT_HEIGHT: f32 = 3.0;
+const FLIGHT_SPEED: f32 = 0.08;
+
+/// Seconds until the next budget report.
+#[derive(Component, Default)]
+pub struct StreamingReport {
+    next: f32,
+}
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    register_texture_streaming_sample(&mut app);
+    app.add_plugin(SamplePlugin);
+    run_catalyst_app(app)
+}
+
+fn register_texture_streaming_sample(app: &mut App) {
+    app.register_singleton_default::<StreamingReport>();
+
+    app.world
+        .system_named::<&AssetServer>("texture_streaming_setup")
+        .kind(flecs::pipeline::OnStart)
+        .run(|iter| setup_texture_streaming(&iter.world()));
+
+    // Flies every camera around the ring, looking a little ahead
+    app.world
+        .system_named::<(&Camera, &mut Transform, &Time)>("texture_streaming_flight")
+        .kind(flecs::pipeline::OnUpdate)
+        .each(|(_, transform, time)| {
+            let angle = time.elapsed_seconds() * FLIGHT_SPEED;
+            let radius = RING_RADIUS + (angle * 3.0).sin() * PANEL_SIZE * 2.0;
+            let position = Vec3::new(angle.cos() * radius, FLIGHT_HEIGHT, angle.sin() * radius);
+            let ahead = angle + 0.15;
+            let target = Vec3::new(ahead.cos() * RING_RADIUS, FLIGHT_HEIGHT, ahead.sin() * RING_RADIUS);
+
+            *transform = Transform::from_xyz(position.x, position.y, position.z)
+                .looking_at(target, Vec3::Y);
+        });
+
+    app.world
+        .system_named::<(&TextureStreamingStats, &mut StreamingReport, &Time)>(
+            "texture_streaming_report",
+        )
+        .kind(flecs::pipeline::PostUpdate)
+        .each(|(stats, report, time)| {
+            if time.elapsed_seconds() < report.next {
+                return;
+            }
+            report.next = time.elapsed_seconds() + 1.0;
+
+            let megabytes = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+            if stats.allocated_bytes > stats.budget_bytes {
+                log::warn!(
+                    "Texture memory over budget: {:.1} / {:.1} MB",
+                    megabytes(stats.allocated_bytes),
+                    megabytes(stats.budget_bytes)
+                );
+            } else {
+                log::info!(
+                    "Texture memory: {:.1} / {:.1} MB, {} evictions",
+                    megabytes(stats.allocated_bytes),
+                    megabytes(stats.budget_bytes),
+                    stats.evictions
+                );
+            }
+        });
+}
+
+fn setup_texture_streaming(world: &World) {
+    world.get::<&AssetServer>(|asset_server| {
+        let mesh = asset_server.add_mesh("streaming_panel", primitives::cube(PANEL_SIZE));
+
+        for index in 0..TEXTURE_COUNT {
+            let texture = match asset_server.add_texture(panel_texture(index)) {
+                Ok(texture) => texture,
+                Err(e) => {
+                    log::error!("Streaming sample texture: {}", e);
+                    continue;
+                }
+            };
+            let material = asset_server.create_material(
+                &format!("streaming_panel_material_{}", index),
+                |m| m.diffuse_texture(texture).roughness(0.8),
+            );
+
+            let angle = index as f32 / TEXTURE_COUNT as f32 * std::f32::consts::TAU;
+            let side = if index % 2 == 0 { 1.0 } else { -1.0 };
+            let radius = RING_RADIUS + side * PANEL_SIZE * 1.5;
+            world
+                .entity()
+                .set(Transform::from_xyz(angle.cos() * radius, FLIGHT_HEIGHT, angle.sin() * radius))
+                .set(GlobalTransform::default())
+                .set(MeshDefinition(mesh.clone()))
+                .set(MaterialDefinition(material));
+        }
+    });
+}
+
+// Checkerboard with fine lines, so a missing top mip is easy to spot up close
+fn panel_texture(index: usize) -> TextureData {
+    let tint = [
+        (index * 53 % 256) as u8,
+        (index * 97 % 256) as u8,
+        (index * 151 % 256) as u8,
+    ];
+    let mut pixels = Vec::with_capacity((TEXTURE_SIZE * TEXTURE_SIZE * 4) as usize);
+    for y in 0..TEXTURE_SIZE {
+        for x in 0..TEXTURE_SIZE {
+            let checker = ((x / 128) + (y / 128)) % 2 == 0;
+            let line = x % 16 == 0 || y % 16 == 0;
+            let shade = if line { 40 } else if checker { 255 } else { 150 };
+            pixels.extend(tint.iter().map(|channel| (*channel as u32 * shade / 255) as u8));
+            pixels.push(255);
+        }
+    }
+
+    TextureData {
+        name: format!("streaming_panel_{}", index),
+        pixels: TextureType::LDR(pixels),
+        width: TEXTURE_SIZE,
+        height: TEXTURE_SIZE,
+        format: TextureFormat::Rgba8UnormSrgb,
+        mips: true,
+    }
+}
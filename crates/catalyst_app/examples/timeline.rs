@@ -0,0 +1,170 @@
+// Timeline sample: one Timeline on a lamp fades its PointLight down while it tints the
+// material of the lampshade, a child entity. The light is an engine component and the
+// tint a game one, both animated by name through the component registry; the shade
+// copies its tint into its material. Open the debug UI's Animation window to scrub it.
+// Half a second after the timeline finished the sample checks both ends and exits
+// with 0, 1 if either wasn't reached.
+//
+// Run with: cargo run -p catalyst_app --example timeline
+
+use catalyst::{core::log, prelude::*};
+use catalyst_app::SamplePlugin;
+use serde::{Deserialize, Serialize};
+
+const FADE_SECONDS: f32 = 3.0;
+const LAMP: PointLight = PointLight {
+    color: Color::linear_rgb(1.0, 0.85, 0.6),
+    intensity: 30.0,
+    radius: 10.0,
+};
+const WARM: Color = Color::linear_rgb(1.0, 0.75, 0.4);
+const COLD: Color = Color::linear_rgb(0.1, 0.2, 0.9);
+
+/// Color and glow of the lampshade's material, animated by the timeline.
+#[derive(Component, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShadeTint {
+    pub color: Color,
+    pub glow: f32,
+}
+
+impl Default for ShadeTint {
+    fn default() -> Self {
+        Self {
+            color: WARM,
+            glow: 2.0,
+        }
+    }
+}
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    register_timeline_sample(&mut app);
+    app.add_plugin(SamplePlugin);
+    run_catalyst_app(app)
+}
+
+fn register_timeline_sample(app: &mut App) {
+    app.register_component::<ShadeTint>();
+
+    // The timeline sets the tint; the material follows
+    app.world
+        .observer::<flecs::OnSet, (&ShadeTint, &MaterialDefinition)>()
+        .each_entity(|entity, (tint, definition)| {
+            let world = entity.world();
+            let Some(material) = definition.0.try_get_entity(&world) else {
+                return;
+            };
+            let Some(mut data) = material.try_get::<&MaterialData>(|data| data.clone()) else {
+                return;
+            };
+            data.settings.base_color = tint.color;
+            data.settings.emissive = tint.color;
+            data.settings.emissive_strength = tint.glow;
+            material.set(data);
+        });
+
+    app.world
+        .system_named::<&AssetServer>("timeline_setup")
+        .kind(flecs::pipeline::OnStart)
+        .run(|iter| setup_timeline(&iter.world()));
+}
+
+fn setup_timeline(world: &World) {
+    let lamp = world
+        .entity_named("timeline_lamp")
+        .set(Transform::from_xyz(0.0, 2.5, -3.0))
+        .set(GlobalTransform::default())
+        .set(LAMP);
+
+    world.get::<&AssetServer>(|asset_server| {
+        let material = asset_server.create_material("timeline_shade", |m| {
+            m.base_color(WARM).emissive(WARM, 2.0).roughness(0.6)
+        });
+        world
+            .entity_named("Shade")
+            .child_of(lamp)
+            .set(Transform {
+                translation: Vec3::new(0.0, 0.3, 0.0),
+                scale: Vec3::new(0.6, 0.4, 0.6),
+                ..Default::default()
+            })
+            .set(GlobalTransform::default())
+            .set(MeshDefinition(
+                asset_server.add_mesh("timeline_shade", primitives::cube(1.0)),
+            ))
+            .set(MaterialDefinition(material))
+            .set(ShadeTint::default());
+    });
+
+    lamp.set(
+        Timeline::new()
+            .track(
+                PropertyTrack::new("PointLight", "intensity")
+                    .easing(Easing::EaseIn)
+                    .key(0.0, LAMP.intensity)
+                    .key(FADE_SECONDS, 0.0),
+            )
+            .track(
+                PropertyTrack::new("ShadeTint", "color")
+                    .on_child("Shade")
+                    .key(0.0, WARM)
+                    .key(FADE_SECONDS, COLD),
+            )
+            .track(
+                PropertyTrack::new("ShadeTint", "glow")
+                    .on_child("Shade")
+                    .easing(Easing::EaseOut)
+                    .key(0.0, 2.0)
+                    .key(FADE_SECONDS, 0.2),
+            ),
+    );
+
+    lamp.observe::<TimelineFinished>(|| {
+        log::info!("Timeline finished");
+    });
+    // Checked a little later, once the last values are set and the material rebuilt
+    lamp.set(Sequence::new().wait_seconds(FADE_SECONDS + 0.5).run(finish));
+}
+
+fn finish(lamp: EntityView) {
+    let world = lamp.world();
+    let intensity = lamp.get::<&PointLight>(|light| light.intensity);
+    let mut shade = None;
+    lamp.each_child(|child| {
+        shade = child.try_get::<(&ShadeTint, &MaterialDefinition)>(|(tint, definition)| {
+            let material = definition.0.try_get_entity(&world).and_then(|material| {
+                material.try_get::<&MaterialData>(|data| data.settings.base_color)
+            });
+            (tint.color, material)
+        });
+    });
+
+    let errors = lamp.get::<&Timeline>(|timeline| {
+        timeline
+            .errors()
+            .map(|(track, error)| format!("{}.{}: {}", track.component, track.path, error))
+            .collect::<Vec<_>>()
+    });
+
+    let code = match shade {
+        _ if !errors.is_empty() => {
+            log::error!("Timeline tracks failed: {:?}", errors);
+            1
+        }
+        Some((tint, Some(material))) if intensity == 0.0 && tint == COLD && material == COLD => {
+            log::info!("Light faded out and the shade turned blue, all checks passed");
+            0
+        }
+        shade => {
+            log::error!(
+                "Timeline didn't reach its end: intensity {}, shade (tint, material) {:?}",
+                intensity,
+                shade
+            );
+            1
+        }
+    };
+    world.get::<&mut AppExit>(|exit| exit.request(code));
+}
@@ -0,0 +1,80 @@
+// Dynamic mesh sample: a grid whose vertices are moved by a sine wave every frame
+// through Handle::modify. The GPU buffers are rewritten in place, nothing is reallocated.
+//
+// Run with: cargo run -p catalyst_app --example wave_plane
+
+use catalyst::prelude::*;
+use catalyst_app::SamplePlugin;
+
+const SIZE: f32 = 10.0;
+const SUBDIVISIONS: u32 = 64;
+const AMPLITUDE: f32 = 0.3;
+const WAVE_LENGTH: f32 = 2.5;
+const SPEED: f32 = 2.0;
+
+#[derive(Component)]
+pub struct WavePlane {
+    mesh: Handle<MeshData>,
+}
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    register_wave_plane_sample(&mut app);
+    app.add_plugin(SamplePlugin);
+    run_catalyst_app(app)
+}
+
+fn register_wave_plane_sample(app: &mut App) {
+    // Set by the setup system; the update system doesn't match until then
+    app.world
+        .component::<WavePlane>()
+        .add_trait::<flecs::Singleton>();
+
+    app.world
+        .system_named::<&AssetServer>("wave_plane_setup")
+        .kind(flecs::pipeline::OnStart)
+        .run(|iter| setup_wave_plane(&iter.world()));
+
+    app.world
+        .system_named::<(&WavePlane, &Time)>("wave_plane_update")
+        .kind(flecs::pipeline::OnUpdate)
+        .run(|iter| update_wave_plane(&iter.world()));
+}
+
+fn setup_wave_plane(world: &World) {
+    world.get::<&AssetServer>(|asset_server| {
+        let mesh = asset_server.add_mesh("wave_plane", primitives::grid(SIZE, SUBDIVISIONS));
+        let material = asset_server.create_material("wave_plane_material", |m| {
+            m.base_color([0.1, 0.4, 0.8, 1.0]).roughness(0.3)
+        });
+
+        world
+            .entity_named("wave_plane")
+            .set(Transform::default())
+            .set(GlobalTransform::default())
+            .set(MeshDefinition(mesh.clone()))
+            .set(MaterialDefinition(material));
+
+        world.set(WavePlane { mesh });
+    });
+}
+
+fn update_wave_plane(world: &World) {
+    let time = world.get::<&Time>(|time| time.elapsed_seconds());
+    let mesh = world.get::<&WavePlane>(|wave| wave.mesh.clone());
+    let k = std::f32::consts::TAU / WAVE_LENGTH;
+
+    // False until the mesh has been flushed into the world, just try again next frame
+    mesh.modify(world, |data| {
+        for vertex in &mut data.vertices {
+            let phase = k * vertex.position[0] + time * SPEED;
+            vertex.position[1] = AMPLITUDE * phase.sin();
+
+            // Normal of y = A sin(kx + t): (-dy/dx, 1, 0) normalized
+            let slope = AMPLITUDE * k * phase.cos();
+            let normal = Vec3::new(-slope, 1.0, 0.0).normalize();
+            vertex.normal = normal.to_array();
+        }
+    });
+}
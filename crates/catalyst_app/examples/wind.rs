@@ -0,0 +1,84 @@
+// Wind sample: a field of 2000 grass tufts sharing one mesh and one material with wind,
+// and a row of capsule "shrubs" whose wind weights come from their height. Gusts roll
+// through the field as a wave; the wind stops with the game clock. Tune the wind in the
+// Rendering window's "Wind" section and a material's sway in the material editor.
+//
+// Run with: cargo run -p catalyst_app --example wind
+
+use catalyst::prelude::*;
+use catalyst_app::SamplePlugin;
+
+const TUFT_COUNT: usize = 2000;
+const SPACING: f32 = 0.35;
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    register_wind_sample(&mut app);
+    app.add_plugin(SamplePlugin);
+    run_catalyst_app(app)
+}
+
+fn register_wind_sample(app: &mut App) {
+    app.world
+        .system_named::<&AssetServer>("wind_setup")
+        .kind(flecs::pipeline::OnStart)
+        .run(|iter| setup_wind(&iter.world()));
+}
+
+fn setup_wind(world: &World) {
+    world.get::<&AssetServer>(|asset_server| {
+        let grass = asset_server.create_material("wind_grass", |m| {
+            m.base_color([0.25, 0.55, 0.15, 1.0])
+                .roughness(0.8)
+                .double_sided()
+                .wind(MaterialWind::default())
+        });
+        let shrub = asset_server.create_material("wind_shrub", |m| {
+            m.base_color([0.15, 0.4, 0.12, 1.0])
+                .roughness(0.9)
+                .wind(MaterialWind {
+                    strength: 0.15,
+                    frequency: 0.4,
+                    branch: 0.08,
+                    flutter: 0.03,
+                })
+        });
+
+        // One mesh for the whole field: the sway differs per tuft through its position
+        let tuft = asset_server.add_mesh("wind_grass_tuft", primitives::grass_tuft(0.3, 0.6, 3));
+        let columns = (TUFT_COUNT as f32).sqrt().ceil() as usize;
+        let offset = columns as f32 * SPACING * 0.5;
+        for index in 0..TUFT_COUNT {
+            let (row, column) = (index / columns, index % columns);
+            // Jittered a little, and turned, so the rows don't show
+            let jitter = ((index * 7919) % 100) as f32 / 100.0 - 0.5;
+            world
+                .entity()
+                .set(Transform {
+                    translation: Vec3::new(
+                        column as f32 * SPACING - offset + jitter * SPACING * 0.5,
+                        0.0,
+                        row as f32 * SPACING - offset - 6.0,
+                    ),
+                    rotation: Quat::from_rotation_y(jitter * std::f32::consts::PI),
+                    scale: Vec3::splat(1.0 + jitter * 0.4),
+                })
+                .set(GlobalTransform::default())
+                .set(MeshDefinition(tuft.clone()))
+                .set(MaterialDefinition(grass.clone()));
+        }
+
+        let mut capsule = primitives::capsule(0.5, 1.5, 12, 6);
+        capsule.wind_weights_from_height();
+        let capsule = asset_server.add_mesh("wind_shrub", capsule);
+        for index in 0..5 {
+            world
+                .entity()
+                .set(Transform::from_xyz(index as f32 * 2.0 - 4.0, 0.75, 2.0))
+                .set(GlobalTransform::default())
+                .set(MeshDefinition(capsule.clone()))
+                .set(MaterialDefinition(shrub.clone()));
+        }
+    });
+}
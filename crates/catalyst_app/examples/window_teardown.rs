@@ -0,0 +1,36 @@
+// Teardown sample: removes the MainWindow while the renderer is still alive, then exits.
+// The surface holds its own Arc of the window, so this has to shut down cleanly; debug
+// builds also assert the surface never draws to a window that isn't the MainWindow.
+//
+// Run with: cargo run -p catalyst_app --example window_teardown
+
+use catalyst::{core::log, prelude::*, window::MainWindow};
+use catalyst_app::SamplePlugin;
+
+const FRAMES_BEFORE_DROP: u32 = 120;
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    register_window_teardown_sample(&mut app);
+    app.add_plugin(SamplePlugin);
+    run_catalyst_app(app)
+}
+
+fn register_window_teardown_sample(app: &mut App) {
+    let mut frames = 0;
+
+    app.world
+        .system_named::<&mut AppExit>("window_teardown")
+        .kind(flecs::pipeline::OnUpdate)
+        .each_iter(move |iter, _, exit| {
+            frames += 1;
+            if frames != FRAMES_BEFORE_DROP {
+                return;
+            }
+
+            log::info!("Dropping the MainWindow before the renderer");
+            iter.world().remove(MainWindow::id());
+            exit.request(0);
+        });
+}
@@ -0,0 +1,126 @@
+// World composition sample: a 3x3 grid of chunks, each a glTF scene streamed in by a
+// StreamingVolume when the camera comes near and out again once it has left, with its
+// meshes, materials and textures. The camera flies a figure eight over the grid; the
+// logged asset and GPU memory peaks stop growing after the first loop.
+//
+// Run with: cargo run -p catalyst_app --example world_composition
+
+use catalyst::{
+    assets::asset_events::AssetLookup,
+    core::log,
+    prelude::*,
+    renderer::{RenderStats, TextureStreamingStats},
+};
+use catalyst_app::SamplePlugin;
+
+const GRID_SIZE: i32 = 3;
+const CHUNK_SPACING: f32 = 40.0;
+const LOAD_DISTANCE: f32 = 35.0;
+const UNLOAD_DISTANCE: f32 = 50.0;
+const FLIGHT_HEIGHT: f32 = 8.0;
+const FLIGHT_SPEED: f32 = 0.1;
+
+/// Peaks since startup, and seconds until the next report.
+#[derive(Component, Default)]
+pub struct CompositionReport {
+    next: f32,
+    peak_assets: usize,
+    peak_geometry: u64,
+    peak_textures: u64,
+}
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    register_world_composition_sample(&mut app);
+    app.add_plugin(SamplePlugin);
+    run_catalyst_app(app)
+}
+
+fn register_world_composition_sample(app: &mut App) {
+    app.register_singleton_default::<CompositionReport>();
+
+    app.world
+        .system_named::<&WorldComposer>("world_composition_setup")
+        .kind(flecs::pipeline::OnStart)
+        .run(|iter| setup_world_composition(&iter.world()));
+
+    // Flies every camera over the grid, through the center and past every edge
+    app.world
+        .system_named::<(&Camera, &mut Transform, &Time)>("world_composition_flight")
+        .kind(flecs::pipeline::OnUpdate)
+        .each(|(_, transform, time)| {
+            let extent = CHUNK_SPACING * 1.2;
+            let flight_position = |angle: f32| {
+                Vec3::new(
+                    angle.sin() * extent,
+                    FLIGHT_HEIGHT,
+                    (angle * 2.0).sin() * extent * 0.5,
+                )
+            };
+            let angle = time.elapsed_seconds() * FLIGHT_SPEED;
+            let position = flight_position(angle);
+            let target = flight_position(angle + 0.1) - Vec3::Y * FLIGHT_HEIGHT * 0.5;
+
+            *transform =
+                Transform::from_xyz(position.x, position.y, position.z).looking_at(target, Vec3::Y);
+        });
+
+    app.world
+        .system_named::<(
+            &WorldComposer,
+            &AssetLookup,
+            &RenderStats,
+            &TextureStreamingStats,
+            &mut CompositionReport,
+            &Time,
+        )>("world_composition_report")
+        .kind(flecs::pipeline::PostUpdate)
+        .each(|(composer, lookup, render, textures, report, time)| {
+            if time.elapsed_seconds() < report.next {
+                return;
+            }
+            report.next = time.elapsed_seconds() + 1.0;
+
+            let assets = lookup.map.len();
+            let geometry = render.geometry_pool.used_bytes;
+            report.peak_assets = report.peak_assets.max(assets);
+            report.peak_geometry = report.peak_geometry.max(geometry);
+            report.peak_textures = report.peak_textures.max(textures.allocated_bytes);
+
+            let megabytes = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+            log::info!(
+                "{} chunks loaded, {} assets (peak {}), geometry {:.1} MB (peak {:.1}), textures {:.1} MB (peak {:.1})",
+                composer.chunks().count(),
+                assets,
+                report.peak_assets,
+                megabytes(geometry),
+                megabytes(report.peak_geometry),
+                megabytes(textures.allocated_bytes),
+                megabytes(report.peak_textures)
+            );
+        });
+}
+
+fn setup_world_composition(world: &World) {
+    for z in 0..GRID_SIZE {
+        for x in 0..GRID_SIZE {
+            let offset = |index: i32| (index - GRID_SIZE / 2) as f32 * CHUNK_SPACING;
+            let index = z * GRID_SIZE + x;
+            // A few different scenes, so neighbouring chunks don't look alike
+            let path = format!("assets/scene{}.glb", index % 3 + 1);
+
+            // The volume is measured from before its first propagation
+            let transform = Transform::from_xyz(offset(x), 0.0, offset(z));
+            world
+                .entity_named(&format!("world_chunk_{x}_{z}"))
+                .set(transform)
+                .set(GlobalTransform(transform.compute_matrix()))
+                .set(
+                    StreamingVolume::new(format!("chunk_{x}_{z}"), path)
+                        .distances(LOAD_DISTANCE, UNLOAD_DISTANCE),
+                )
+                .set(ChunkGroup("grid".to_string()));
+        }
+    }
+}
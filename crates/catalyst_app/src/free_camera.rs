@@ -0,0 +1,85 @@
+// Free-flying camera for the default sample: WASD to move, mouse to look.
+// Player cameras (PlayerIndex) are driven by the split-screen sample instead. Both
+// systems only run in the gameplay state, so a menu doesn't turn the camera.
+
+use catalyst::prelude::*;
+
+use crate::{
+    ACTION_MOVE_BACKWARD, ACTION_MOVE_FORWARD, ACTION_MOVE_LEFT, ACTION_MOVE_RIGHT, AXIS_MOUSE_X,
+    AXIS_MOUSE_Y,
+};
+
+pub fn register_free_camera(app: &mut App) {
+    let movement = app
+        .world
+        .system_named::<(&mut Transform, &Time, &InputState)>("movement_system")
+        .with(Camera::id())
+        .without(PlayerIndex::id()) // player cameras are driven per player
+        .kind(flecs::pipeline::OnUpdate)
+        .each(|(transform, time, input)| {
+            let speed = 5.0 * time.delta_seconds();
+
+            let go_forward = input
+                .actions
+                .get(&ACTION_MOVE_FORWARD)
+                .map(|a| a.phase.contains(ButtonPhase::HELD))
+                .unwrap_or(false);
+
+            let go_backward = input
+                .actions
+                .get(&ACTION_MOVE_BACKWARD)
+                .map(|a| a.phase.contains(ButtonPhase::HELD))
+                .unwrap_or(false);
+
+            let go_left = input
+                .actions
+                .get(&ACTION_MOVE_LEFT)
+                .map(|a| a.phase.contains(ButtonPhase::HELD))
+                .unwrap_or(false);
+
+            let go_right = input
+                .actions
+                .get(&ACTION_MOVE_RIGHT)
+                .map(|a| a.phase.contains(ButtonPhase::HELD))
+                .unwrap_or(false);
+
+            let forward = transform.rotation * -Vec3::Z;
+            let right = transform.rotation * Vec3::X;
+            let up = Vec3::Y; // Global Up
+
+            if go_forward {
+                transform.translation += forward * speed;
+            }
+
+            if go_backward {
+                transform.translation -= forward * speed;
+            }
+
+            if go_left {
+                transform.translation -= right * speed;
+            }
+
+            if go_right {
+                transform.translation += right * speed;
+            }
+        });
+    run_in_states(&app.world, movement.id(), &[STATE_GAMEPLAY]);
+
+    let look = app
+        .world
+        .system_named::<(&mut Transform, &InputState)>("camera_movement_system")
+        .with(Camera::id())
+        .without(PlayerIndex::id())
+        .kind(flecs::pipeline::OnUpdate)
+        .each(|(transform, input)| {
+            // Mouse motion, with the sensitivity, curve and smoothing of AxisSettings
+            let yaw = -input.axis(AXIS_MOUSE_X);
+            let pitch = -input.axis(AXIS_MOUSE_Y); // Convert to quaternions 
+            let yaw_q = Quat::from_rotation_y(yaw);
+            let pitch_q = Quat::from_rotation_x(pitch);
+
+            transform.rotation = yaw_q * transform.rotation;
+            transform.rotation = transform.rotation * pitch_q;
+        });
+    run_in_states(&app.world, look.id(), &[STATE_GAMEPLAY]);
+}
@@ -0,0 +1,132 @@
+//! Shared setup for the sample app and the feature demos under `examples/`: the
+//! init script, WASD and mouse bindings, the skybox, the free camera and the pickup
+//! scene hook. Each demo registers its own systems and then adds [`SamplePlugin`].
+
+use catalyst::{
+    core::log,
+    debug::{ACTION_ENABLE_DEBUG, ACTION_REDO, ACTION_UNDO, ACTION_UNDO_MODIFIER},
+    prelude::*,
+};
+use flecs_ecs::addons::stats;
+
+mod free_camera;
+mod pickups;
+
+pub const ACTION_MOVE_FORWARD: ActionId = ActionId(1);
+pub const ACTION_MOVE_BACKWARD: ActionId = ActionId(2);
+pub const ACTION_MOVE_LEFT: ActionId = ActionId(3);
+pub const ACTION_MOVE_RIGHT: ActionId = ActionId(4);
+
+pub const AXIS_LOOK_X: AxisId = AxisId(100);
+pub const AXIS_LOOK_Y: AxisId = AxisId(101);
+// Mouse motion, in radians per frame after AxisSettings
+pub const AXIS_MOUSE_X: AxisId = AxisId(102);
+pub const AXIS_MOUSE_Y: AxisId = AxisId(103);
+
+/// The default sample scene. Goes after `DefaultPlugins` and after a demo's own
+/// systems, so the demo's OnStart systems run before `setup_scene`.
+pub struct SamplePlugin;
+
+impl Plugin for SamplePlugin {
+    fn build(&self, app: &mut App) {
+        app.world.script().build_from_file("scripts/init.flecs");
+
+        app.world
+            .system::<&mut AssetServer>()
+            .kind(flecs::pipeline::OnStart)
+            .run(|iter| setup_scene(&iter.world()));
+
+        free_camera::register_free_camera(app);
+        pickups::register_pickups(app);
+
+        app.world.import::<stats::Stats>();
+        app.world.set(flecs::rest::Rest::default());
+    }
+
+    fn dependencies(&self) -> Vec<PluginId> {
+        vec![PluginId::of::<InputPlugin>(), PluginId::of::<AssetPlugin>()]
+    }
+}
+
+// /// -------------------------------------------------------------------
+// /// SYSTEM: Setup Scene
+// /// -------------------------------------------------------------------
+fn setup_scene(world: &World) {
+    world.get::<&AssetServer>(|asset_server| {
+        log::info!("Requesting Mesh Load...");
+
+    // world.script().build_from_file("scripts/init.flecs");
+
+
+       let cubemap_handle = asset_server.load_cubemap("assets/skybox_puresky_4k.exr");
+
+        // let scene_handle = asset_server.load_scene("assets/simple15.glb");
+        // world
+        //     .entity()
+        //     .set(SceneRoot(scene_handle.clone()))
+        //     .set(Transform::from_xyz(0.0, 0.0, 0.0))
+        //     .set(GlobalTransform::default());
+
+        world.get::<&mut InputState>(|input_state| {
+            input_state.push_context(CTX_GAMEPLAY);
+        });
+
+        world.get::<&mut InputMap>(|input_map| {
+            input_map
+                .bind_keyboard_button(winit::keyboard::KeyCode::KeyW as u16, ACTION_MOVE_FORWARD);
+            input_map
+                .bind_keyboard_button(winit::keyboard::KeyCode::KeyS as u16, ACTION_MOVE_BACKWARD);
+            input_map.bind_keyboard_button(winit::keyboard::KeyCode::KeyA as u16, ACTION_MOVE_LEFT);
+            input_map
+                .bind_keyboard_button(winit::keyboard::KeyCode::KeyD as u16, ACTION_MOVE_RIGHT);
+
+            input_map
+                .bind_keyboard_button(winit::keyboard::KeyCode::Tab as u16, ACTION_ENABLE_DEBUG);
+            input_map.bind_keyboard_button_with_context(
+                winit::keyboard::KeyCode::Tab as u16,
+                ACTION_ENABLE_DEBUG,
+                CTX_DEBUG,
+            );
+
+            // Ctrl+Z and Ctrl+Y undo and redo the debug tooling's edits
+            for (key, action) in [
+                (winit::keyboard::KeyCode::KeyZ, ACTION_UNDO),
+                (winit::keyboard::KeyCode::KeyY, ACTION_REDO),
+                (winit::keyboard::KeyCode::ControlLeft, ACTION_UNDO_MODIFIER),
+                (winit::keyboard::KeyCode::ControlRight, ACTION_UNDO_MODIFIER),
+            ] {
+                input_map.bind_keyboard_button_with_context(key as u16, action, CTX_DEBUG);
+            }
+
+            input_map
+                .bind_mouse_axis(MouseAxisId::X, AXIS_MOUSE_X, 1.0)
+                .bind_mouse_axis(MouseAxisId::Y, AXIS_MOUSE_Y, 1.0);
+        });
+
+        // Unless the settings file has the player's own
+        world.get::<&mut AxisSettings>(|settings| {
+            for axis in [AXIS_MOUSE_X, AXIS_MOUSE_Y] {
+                settings.set_default(
+                    AxisProcessor::new(axis)
+                        .sensitivity(0.002)
+                        .per_frame(true),
+                );
+            }
+        });
+
+        // world
+        //     .entity()
+        //     .set(SceneRoot(scene_handle))
+        //     .set(Transform::from_xyz(10.0, 0.0, 0.0))
+        //     .set(GlobalTransform(Mat4::default().mul_scalar(10.0)));
+
+        // world
+        //     .entity()
+        //     .set_name("camera")
+        //     .set(Camera::default())
+        //     .set(
+        //         Transform::from_xyz(0.0, 2.0, 5.0) // Up 2, Back 5
+        //             .looking_at(Vec3::ZERO, Vec3::Y),
+        //     );
+    });
+}
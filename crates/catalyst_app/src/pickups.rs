@@ -0,0 +1,50 @@
+// Scene hook sample: nodes tagged in Blender with the custom property
+// pickup = "health" (or "ammo") become Pickup entities when the scene spawns.
+// Pickups are gameplay markers, so their placeholder mesh is not drawn.
+//
+// Always registered; tag a node in any of the sample scenes to try it. Pickup is a
+// registered component, so it shows up in the inspector and in saved scene files.
+
+use catalyst::{assets::scene::SceneNode, core::log, prelude::*};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PickupKind {
+    #[default]
+    Health,
+    Ammo,
+}
+
+#[derive(Component, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct Pickup {
+    pub kind: PickupKind,
+}
+
+pub fn register_pickups(app: &mut App) {
+    app.register_component::<Pickup>();
+
+    ScenePlugin::register_node_hook(&app.world, pickup_from_extras);
+}
+
+fn pickup_from_extras(node: &SceneNode, entity: EntityView) -> NodeHookResult {
+    let Some(tag) = node
+        .extras
+        .as_ref()
+        .and_then(|extras| extras.get("pickup"))
+        .and_then(|pickup| pickup.as_str())
+    else {
+        return NodeHookResult::Continue;
+    };
+
+    let kind = match tag {
+        "health" => PickupKind::Health,
+        "ammo" => PickupKind::Ammo,
+        _ => {
+            log::warn!("Node '{}' has an unknown pickup '{}'", node.name, tag);
+            return NodeHookResult::Continue;
+        }
+    };
+
+    entity.set(Pickup { kind });
+    NodeHookResult::SkipMeshAndMaterial
+}
@@ -4,7 +4,16 @@ use catalyst_core::Source;
 use flecs_ecs::prelude::*;
 use uuid::Uuid;
 
-use crate::{AssetReceiver, LoadScene, Loading, asset_server::AssetWorkerMessage};
+use crate::{
+    AssetReceiver, LoadScene, Loading, PrewarmAsset, PrewarmScene,
+    asset_server::{AssetServer, AssetWorkerMessage},
+    material::{MaterialData, TextureData},
+    reload::{
+        AssetDependencies, AssetReloaded, HotReload, material_textures, reload_scene,
+        texture_reloaded,
+    },
+    scene::SceneData,
+};
 
 #[derive(Component, Default)]
 pub struct AssetLookup {
@@ -26,6 +35,30 @@ pub struct MaterialAsset;
 #[derive(Component)]
 pub struct MeshAsset;
 
+#[derive(Component)]
+pub struct LutAsset;
+
+/// A change made to an asset in place, see AssetChanged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AssetEvent {
+    /// `Handle<MeshData>::modify` edited the mesh with this id.
+    MeshModified { id: Uuid },
+}
+
+/// Emitted on the AssetServer's entity for every AssetEvent, so whatever is built from
+/// an asset can follow it: the renderer rewrites a modified mesh's GPU buffers, a
+/// collider or BVH made from it is rebuilt. Observe it with
+/// `world.entity_from::<AssetServer>().observe_payload::<&AssetChanged>(...)`.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct AssetChanged(pub AssetEvent);
+
+/// Emits `event` to the observers of AssetChanged.
+pub fn emit_asset_event(world: &World, event: AssetEvent) {
+    world
+        .entity_from::<AssetServer>()
+        .emit(&AssetChanged(event));
+}
+
 impl AssetLookup {
     pub fn entity(&mut self, id: Uuid, world: &World) -> Entity {
         *self.map.entry(id).or_insert_with(|| {
@@ -37,26 +70,38 @@ impl AssetLookup {
 
 pub fn register_flush_system(world: &World) {
     world
-        .system::<(&mut AssetReceiver, &mut AssetLookup)>()
+        .system::<(&mut AssetReceiver, &mut AssetLookup, &mut AssetDependencies, &HotReload)>()
         .kind(flecs::pipeline::OnUpdate)
         .write(AssetReceiver::id())
         .write(AssetLookup::id())
         .run(|mut iter| {
+            catalyst_core::profile_scope!("Asset Flush");
             let world = iter.world();
 
             while iter.next() {
                 let mut receivers = iter.field_mut::<AssetReceiver>(0);
                 let mut lookups = iter.field_mut::<AssetLookup>(1);
+                let mut dependencies = iter.field_mut::<AssetDependencies>(2);
+                let respawn_scenes = iter.field::<HotReload>(3)[0].respawn_scenes;
 
-                if let (Some(receiver), Some(lookup)) = (receivers.get_mut(0), lookups.get_mut(0)) {
+                if let (Some(receiver), Some(lookup), Some(dependencies)) =
+                    (receivers.get_mut(0), lookups.get_mut(0), dependencies.get_mut(0))
+                {
                     while let Ok(msg) = receiver.0.try_recv() {
                         // Handle message...while let Ok(msg) = receiver.0.try_recv() {
                         match msg {
                             AssetWorkerMessage::TextureLoaded { id, path, data } => {
-                                println!("  [AssetPlugin] Offloaded Texture: {:?}", path);
-
-                                let entity = lookup.entity(id, &world);
-                                world.entity_from_id(entity).set_name(&path).set(data);
+                                log::debug!("Offloaded Texture: {:?}", path);
+
+                                let entity = world.entity_from_id(lookup.entity(id, &world));
+                                let reloaded = entity.has(TextureData::id());
+                                entity
+                                    .set_name(&path)
+                                    .add((AssetType, TextureAsset))
+                                    .set(data);
+                                if reloaded {
+                                    texture_reloaded(&world, dependencies, entity.id());
+                                }
                             }
                             AssetWorkerMessage::SceneLoaded {
                                 entity,
@@ -65,49 +110,117 @@ pub fn register_flush_system(world: &World) {
                                 textures: loaded_textures,
                                 materials: loaded_materials,
                                 meshes: loaded_meshes,
+                                prewarm,
                             } => {
-                                println!("  [AssetPlugin] Offloaded Scene: {:?}", path);
+                                log::debug!("Offloaded Scene: {:?}", path);
 
                                 // let entity = lookup.entity(id, &world);
-                                let scene_entity = world
-                                    .entity_from_id(entity)
+                                let scene_entity = world.entity_from_id(entity);
+                                // Deleted while loading (an unloaded chunk): nothing uses it
+                                if !scene_entity.is_alive() {
+                                    log::debug!("Scene '{}' was deleted while loading", path);
+                                    continue;
+                                }
+                                // Loaded again by HotReload: only what changed is updated
+                                if scene_entity.has(SceneData::id()) {
+                                    reload_scene(
+                                        &world,
+                                        lookup,
+                                        dependencies,
+                                        scene_entity,
+                                        (scene, loaded_textures, loaded_materials, loaded_meshes),
+                                        respawn_scenes,
+                                    );
+                                    continue;
+                                }
+                                if prewarm {
+                                    scene_entity.add(PrewarmScene);
+                                }
+                                scene_entity
                                     .set(scene)
                                     .add((AssetType, SceneAsset))
                                     .remove(Loading)
                                     .remove(LoadScene);
 
+                                let mut children = Vec::new();
+                                // The tags go on before the data, so the renderer's OnSet
+                                // observers already see them and leave the upload to the queue
+                                let mut asset_entity = |id: Uuid| {
+                                    let asset = world
+                                        .entity_from_id(lookup.entity(id, &world))
+                                        .add((Source, scene_entity));
+                                    if prewarm {
+                                        asset.add(PrewarmAsset);
+                                    }
+                                    children.push(asset.id());
+                                    asset
+                                };
+
                                 // 1. Unpack & Store Textures
                                 for (handle, data) in loaded_textures {
-                                    let entity = lookup.entity(handle.id, &world);
-                                    world
-                                        .entity_from_id(entity)
-                                        .add((Source, scene_entity))
+                                    asset_entity(handle.id)
                                         .set(data)
                                         .add((AssetType, TextureAsset));
                                 }
 
                                 // 2. Unpack & Store Materials
+                                let mut material_textures_of = Vec::new();
                                 for (handle, data) in loaded_materials {
-                                    let entity = lookup.entity(handle.id, &world);
-                                    world
-                                        .entity_from_id(entity)
-                                        .add((Source, scene_entity))
-                                        .add((AssetType, MaterialAsset))
-                                        .set(data);
+                                    let material = asset_entity(handle.id);
+                                    material_textures_of
+                                        .push((material.id(), material_textures(&data).collect::<Vec<_>>()));
+                                    material.add((AssetType, MaterialAsset)).set(data);
                                 }
 
                                 // 3. Unpack & Store Meshes
                                 for (handle, data) in loaded_meshes {
-                                    let entity = lookup.entity(handle.id, &world);
-                                    
-                                    world
-                                        .entity_from_id(entity)
-                                        .add((Source, scene_entity))
+                                    asset_entity(handle.id)
                                         .add((AssetType, MeshAsset))
                                         .set(data);
                                 }
 
-                                println!("✅ Scene '{}' fully unpacked and ready.", path);
+                                for (material, textures) in material_textures_of {
+                                    let textures: Vec<Entity> = textures
+                                        .into_iter()
+                                        .map(|id| lookup.entity(id, &world))
+                                        .collect();
+                                    dependencies.set_children(material, textures);
+                                }
+                                dependencies.set_children(scene_entity.id(), children);
+
+                                log::info!("Scene '{}' fully unpacked and ready.", path);
+                            }
+                            AssetWorkerMessage::MeshAdded { id, name, data } => {
+                                let entity = lookup.entity(id, &world);
+                                world
+                                    .entity_from_id(entity)
+                                    .set_name(&name)
+                                    .add((AssetType, MeshAsset))
+                                    .set(data);
+                            }
+                            AssetWorkerMessage::MaterialAdded { id, name, data } => {
+                                let entity = world.entity_from_id(lookup.entity(id, &world));
+                                let textures: Vec<Entity> = material_textures(&data)
+                                    .map(|id| lookup.entity(id, &world))
+                                    .collect();
+                                dependencies.set_children(entity.id(), textures);
+                                // A material file loaded again, the OnSet rebuilds its bind group
+                                let reloaded = entity.has(MaterialData::id());
+                                entity
+                                    .set_name(&name)
+                                    .add((AssetType, MaterialAsset))
+                                    .set(data);
+                                if reloaded {
+                                    entity.emit(&AssetReloaded);
+                                }
+                            }
+                            AssetWorkerMessage::LutLoaded { id, path, data } => {
+                                let entity = lookup.entity(id, &world);
+                                world
+                                    .entity_from_id(entity)
+                                    .set_name(&path)
+                                    .add((AssetType, LutAsset))
+                                    .set(data);
                             }
                         }
                     }
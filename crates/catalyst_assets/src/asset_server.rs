@@ -1,19 +1,35 @@
-use std::result;
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicBool, Ordering},
+};
 
+use catalyst_core::rayon::{self, prelude::*};
 use flecs_ecs::{core::Entity, macros::Component};
 use tokio::sync::mpsc::UnboundedSender;
 use uuid::Uuid;
 
 use crate::{
     assets::{EntityHandle, Handle, MeshData},
-    material::{MaterialData, TextureData, TextureFormat, TextureType},
-    scene::SceneData,
+    cache::{AssetCache, cache_key},
+    import::ImportSettings,
+    io::{AssetIoError, AssetMounts, AssetReader, normalize_path},
+    lod::{GenerateLods, generate_lods},
+    lut::{LutData, LutError},
+    material::{
+        MaterialBuilder, MaterialData, MaterialFile, TextureData, TextureDataError, TextureFormat,
+        TextureType,
+    },
+    postprocess::{AssetMetadata, AssetPostprocessors},
+    reload::{ReloadTarget, WatchedFile},
+    scene::{SceneData, SceneFilter},
 };
 use tokio::runtime::Handle as TokioHandle;
 
 mod exr_parser;
 mod gltf_parser;
 
+pub use gltf_parser::GltfPayload;
+
 // Internal Message (Heavy - Used only inside the plugin)
 pub enum AssetWorkerMessage {
     TextureLoaded {
@@ -30,55 +46,356 @@ pub enum AssetWorkerMessage {
         textures: Vec<(Handle<TextureData>, TextureData)>,
         materials: Vec<(Handle<MaterialData>, MaterialData)>,
         meshes: Vec<(Handle<MeshData>, MeshData)>,
+        // GPU uploads go through the renderer's budgeted queue
+        prewarm: bool,
+    },
+    MeshAdded {
+        id: Uuid,
+        name: String,
+        data: MeshData,
+    },
+    MaterialAdded {
+        id: Uuid,
+        name: String,
+        data: MaterialData,
+    },
+    LutLoaded {
+        id: Uuid,
+        path: String,
+        data: LutData,
     },
 }
 
+/// How `AssetServer::load_scene_with_options` imports a scene.
+#[derive(Clone, Debug, Default)]
+pub struct SceneLoadOptions {
+    /// Upload GPU resources through the budgeted queue, see `load_scene_prewarmed`.
+    pub prewarm: bool,
+    /// Generate a LOD chain for every mesh before the scene is handed over.
+    /// Wins over the `lods` of the import settings.
+    pub lods: Option<GenerateLods>,
+    /// Used instead of the scene's `.meta` import settings file.
+    pub import: Option<ImportSettings>,
+    /// Only the matching nodes and what they use, see `load_scene_filtered`.
+    pub filter: Option<SceneFilter>,
+}
+
 #[derive(Component, Clone)]
 pub struct AssetServer {
     event_sender: UnboundedSender<AssetWorkerMessage>,
     // The "Ticket" to the Async World
     io_handle: TokioHandle,
+    // Where asset bytes come from (loose files + mounted packs)
+    mounts: Arc<AssetMounts>,
+    write_import_settings: Arc<AtomicBool>,
+    postprocessors: Arc<AssetPostprocessors>,
+    cache: Arc<AssetCache>,
+    // Files loaded so far and how to load them again, see HotReload
+    watched: Arc<Mutex<Vec<WatchedFile>>>,
 }
 
 impl AssetServer {
-    pub fn new(event_sender: UnboundedSender<AssetWorkerMessage>, io_handle: TokioHandle) -> Self {
+    pub fn new(
+        event_sender: UnboundedSender<AssetWorkerMessage>,
+        io_handle: TokioHandle,
+        mounts: AssetMounts,
+    ) -> Self {
         Self {
             event_sender,
             io_handle,
+            mounts: Arc::new(mounts),
+            write_import_settings: Arc::new(AtomicBool::new(false)),
+            postprocessors: Arc::new(AssetPostprocessors::default()),
+            cache: Arc::new(AssetCache::default()),
+            watched: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
+    /// Transforms run on loaded data before it reaches the world, see
+    /// `AssetPlugin::register_postprocessor`.
+    pub fn postprocessors(&self) -> &AssetPostprocessors {
+        &self.postprocessors
+    }
+
+    /// Parsed textures kept on disk between runs, in DEFAULT_CACHE_DIR unless moved
+    /// with `AssetCache::set_dir`.
+    pub fn cache(&self) -> &AssetCache {
+        &self.cache
+    }
+
+    /// Writes the effective import settings of every texture and scene loaded from now
+    /// on to its `.meta` file, defaults included, so artists can see what can be set.
+    /// Off by default: shipped builds shouldn't write next to their assets.
+    pub fn set_write_import_settings(&self, enabled: bool) {
+        self.write_import_settings.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Mounts a pack file. Assets in it are found by the same paths as loose files,
+    /// loose files still take priority so they can override packed content.
+    pub fn mount_pack(&self, pack_path: &str) -> Result<(), AssetIoError> {
+        self.mounts.mount_pack(pack_path)
+    }
+
+    pub fn reader(&self) -> &dyn AssetReader {
+        self.mounts.as_ref()
+    }
+
+    /// Registers a mesh built in code (e.g. a primitive) as an asset.
+    /// It shows up in the world on the next flush, like any loaded mesh.
+    pub fn add_mesh(&self, name: &str, data: MeshData) -> Handle<MeshData> {
+        let handle = Handle::<MeshData>::new();
+        let _ = self.event_sender.send(AssetWorkerMessage::MeshAdded {
+            id: handle.id,
+            name: name.to_owned(),
+            data,
+        });
+        handle
+    }
+
+    /// Registers a material built in code as an asset.
+    pub fn add_material(&self, name: &str, data: MaterialData) -> Handle<MaterialData> {
+        let handle = Handle::<MaterialData>::new();
+        let _ = self.event_sender.send(AssetWorkerMessage::MaterialAdded {
+            id: handle.id,
+            name: name.to_owned(),
+            data,
+        });
+        handle
+    }
+
+    /// Registers a material described with a MaterialBuilder, e.g.
+    /// `create_material("red", |m| m.base_color([1.0, 0.2, 0.2, 1.0]).roughness(0.9))`.
+    /// The handle can be used right away; the GPU material is built once the renderer is up.
+    pub fn create_material(
+        &self,
+        name: &str,
+        build: impl FnOnce(MaterialBuilder) -> MaterialBuilder,
+    ) -> Handle<MaterialData> {
+        self.add_material(name, build(MaterialData::builder()).build())
+    }
+
+    /// Loads a material file (see MaterialFile), e.g. one saved by the material editor.
+    /// Its textures are loaded like `load_texture`; missing ones are left out with a
+    /// warning, so the material still shows up.
+    pub fn load_material(&self, path: &str) -> Handle<MaterialData> {
+        let handle = Handle::<MaterialData>::new();
+        self.watch(path, ReloadTarget::Material { id: handle.id });
+        self.spawn_material_load(path, handle.id);
+        handle
+    }
+
+    fn spawn_material_load(&self, path: &str, id: Uuid) {
+        let path = path.to_owned();
+        let server = self.clone();
+
+        self.io_handle.spawn(async move {
+            let path_clone = path.clone();
+            let mounts = server.mounts.clone();
+            let load_result = tokio::task::spawn_blocking(move || {
+                let bytes = mounts.read(&path_clone).map_err(|e| e.to_string())?;
+                let text = String::from_utf8(bytes).map_err(|e| e.to_string())?;
+                MaterialFile::from_ron(&text).map_err(|e| e.to_string())
+            })
+            .await;
+
+            let file = match load_result {
+                Ok(Ok(file)) => file,
+                Ok(Err(e)) => {
+                    log::error!("Failed to load material '{}': {}", path, e);
+                    return;
+                }
+                Err(_) => {
+                    log::error!("Failed to load material: {}", path);
+                    return;
+                }
+            };
+
+            let texture = |slot: &Option<String>| {
+                let texture_path = slot.as_deref()?;
+                if !server.mounts.exists(texture_path) {
+                    log::warn!("Material '{}': texture '{}' not found", path, texture_path);
+                    return None;
+                }
+                // Shared with other materials, and kept when this one is reloaded
+                Some(
+                    server
+                        .loaded_texture(texture_path)
+                        .unwrap_or_else(|| server.load_texture(texture_path)),
+                )
+            };
+            let mut data = MaterialData {
+                settings: file.settings,
+                diffuse_texture: texture(&file.diffuse_texture),
+                normal_texture: texture(&file.normal_texture),
+                metallic_roughness_texture: texture(&file.metallic_roughness_texture),
+                occlusion_texture: texture(&file.occlusion_texture),
+            };
+            let metadata = AssetMetadata::new(&path, &ImportSettings::default());
+            if let Err(e) = server.postprocessors.run(&mut data, &metadata) {
+                log::error!("Failed to load material '{}': {}", path, e);
+                return;
+            }
+
+            let _ = server.event_sender.send(AssetWorkerMessage::MaterialAdded {
+                id,
+                name: path,
+                data,
+            });
+        });
+    }
+
+    /// Writes a material file as a loose file, for `load_material`.
+    pub fn save_material(&self, path: &str, file: &MaterialFile) -> Result<(), AssetIoError> {
+        self.mounts.write(path, file.to_ron().as_bytes())
+    }
+
+    /// Registers a texture built in code. Textures whose pixels don't match their size
+    /// and format (including zero-sized ones) are rejected.
+    pub fn add_texture(&self, data: TextureData) -> Result<Handle<TextureData>, TextureDataError> {
+        data.validate()?;
+
+        let handle = Handle::<TextureData>::new();
+        let _ = self.event_sender.send(AssetWorkerMessage::TextureLoaded {
+            id: handle.id,
+            path: data.name.clone(),
+            data,
+        });
+        Ok(handle)
+    }
+
     pub fn load_texture(&self, path: &str) -> Handle<TextureData> {
+        self.spawn_texture_load(path, None)
+    }
+
+    /// Loads a texture with `settings` instead of its `.meta` import settings file.
+    pub fn load_texture_with_settings(
+        &self,
+        path: &str,
+        settings: ImportSettings,
+    ) -> Handle<TextureData> {
+        self.spawn_texture_load(path, Some(settings))
+    }
+
+    fn spawn_texture_load(
+        &self,
+        path: &str,
+        overrides: Option<ImportSettings>,
+    ) -> Handle<TextureData> {
         let handle = Handle::<TextureData>::new();
-        let id = handle.id;
+        self.watch(
+            path,
+            ReloadTarget::Texture {
+                id: handle.id,
+                overrides: overrides.clone(),
+            },
+        );
+        self.load_texture_into(path, handle.id, overrides);
+        handle
+    }
+
+    fn load_texture_into(&self, path: &str, id: Uuid, overrides: Option<ImportSettings>) {
         let path = path.to_owned();
         let sender = self.event_sender.clone();
+        let mounts = self.mounts.clone();
+        let write_settings = self.write_import_settings.load(Ordering::Relaxed);
+        let postprocessors = self.postprocessors.clone();
+        let cache = self.cache.clone();
 
         self.io_handle.spawn(async move {
             let path_clone = path.clone();
+            let blocking_cache = cache.clone();
 
             // Blocking load via 'image' crate
             let load_result = tokio::task::spawn_blocking(move || {
-                // Open file
-                let img = image::open(&path_clone).map_err(|e| e.to_string())?;
-                // Convert to RGBA8 (Standard for GPU)
-                let rgba = img.to_rgba8();
-
-                Ok::<TextureData, String>(TextureData {
-                    name: path_clone.clone(),
-                    width: rgba.width(),
-                    height: rgba.height(),
-                    pixels: TextureType::LDR(rgba.into_raw()),
-                    format: TextureFormat::Rgba8Unorm, // Use sRGB for colors!
-                })
+                let settings =
+                    import_settings(&path_clone, &mounts, overrides.as_ref(), write_settings);
+                let key = mounts
+                    .modified(&path_clone)
+                    .map(|modified| cache_key(&path_clone, modified, &settings, "texture"));
+
+                let (mut data, to_store) = cached_texture(&blocking_cache, &path_clone, key, || {
+                    // Read file
+                    let bytes = mounts.read(&path_clone).map_err(|e| e.to_string())?;
+                    let img = image::load_from_memory(&bytes).map_err(|e| e.to_string())?;
+                    // Convert to RGBA8 (Standard for GPU)
+                    let rgba = settings.texture.fit(img).to_rgba8();
+
+                    Ok(TextureData {
+                        name: path_clone.clone(),
+                        width: rgba.width(),
+                        height: rgba.height(),
+                        pixels: TextureType::LDR(rgba.into_raw()),
+                        // Linear unless the settings say sRGB
+                        format: settings.texture.format(TextureFormat::Rgba8Unorm),
+                        mips: settings.texture.mips,
+                    })
+                })?;
+                postprocessors
+                    .run(&mut data, &AssetMetadata::new(&path_clone, &settings))
+                    .map_err(|e| e.to_string())?;
+                Ok::<_, String>((data, to_store))
             })
             .await;
 
             match load_result {
+                Ok(Ok((data, to_store))) => {
+                    let _ = sender.send(AssetWorkerMessage::TextureLoaded {
+                        id,
+                        path: path.clone(),
+                        data,
+                    });
+                    store_in_cache(cache, path, to_store);
+                }
+                Ok(Err(e)) => log::error!("Failed to load texture '{}': {}", path, e),
+                Err(_) => log::error!("Failed to load texture: {}", path),
+            }
+        });
+    }
+
+    /// Registers a LUT built in code, e.g. with `LutData::from_fn`. LUTs without one entry
+    /// per cell are rejected.
+    pub fn add_lut(&self, data: LutData) -> Result<Handle<LutData>, LutError> {
+        data.validate()?;
+
+        let handle = Handle::<LutData>::new();
+        let _ = self.event_sender.send(AssetWorkerMessage::LutLoaded {
+            id: handle.id,
+            path: data.name.clone(),
+            data,
+        });
+        Ok(handle)
+    }
+
+    /// Loads a color grading LUT from a `.cube` file, or from a strip image otherwise
+    /// (see the `lut` module).
+    pub fn load_lut(&self, path: &str) -> Handle<LutData> {
+        let handle = Handle::<LutData>::new();
+        let id = handle.id;
+        let path = path.to_owned();
+        let sender = self.event_sender.clone();
+        let mounts = self.mounts.clone();
+
+        self.io_handle.spawn(async move {
+            let path_clone = path.clone();
+
+            let result = tokio::task::spawn_blocking(move || {
+                let bytes = mounts.read(&path_clone).map_err(|e| e.to_string())?;
+                if path_clone.to_lowercase().ends_with(".cube") {
+                    let text = String::from_utf8(bytes).map_err(|e| e.to_string())?;
+                    LutData::parse_cube(&path_clone, &text).map_err(|e| e.to_string())
+                } else {
+                    let img = image::load_from_memory(&bytes).map_err(|e| e.to_string())?;
+                    LutData::from_strip(&path_clone, &img.to_rgba8()).map_err(|e| e.to_string())
+                }
+            })
+            .await;
+
+            match result {
                 Ok(Ok(data)) => {
-                    let _ = sender.send(AssetWorkerMessage::TextureLoaded { id, path, data });
+                    let _ = sender.send(AssetWorkerMessage::LutLoaded { id, path, data });
                 }
-                _ => eprintln!("Failed to load texture: {}", path),
+                Ok(Err(e)) => log::error!("Failed to load LUT '{}': {}", path, e),
+                Err(_) => log::error!("Failed to load LUT: {}", path),
             }
         });
 
@@ -90,29 +407,53 @@ impl AssetServer {
         let id = handle.id;
         let path = path.to_owned();
         let sender = self.event_sender.clone();
+        let mounts = self.mounts.clone();
+        let postprocessors = self.postprocessors.clone();
+        let cache = self.cache.clone();
 
         self.io_handle.spawn(async move {
             let path_clone = path.clone();
+            let blocking_cache = cache.clone();
 
-            let result =
-                tokio::task::spawn_blocking(move || exr_parser::parse_exr(&path_clone, 1024)).await; // 2048 for 8K image
+            let result = tokio::task::spawn_blocking(move || {
+                // Cubemaps have no import settings
+                let settings = ImportSettings::default();
+                let face_size = 1024; // 2048 for 8K image
+                let key = mounts.modified(&path_clone).map(|modified| {
+                    cache_key(&path_clone, modified, &settings, &format!("cubemap {}", face_size))
+                });
+
+                // The slowest import there is, so the first to get cached
+                let (mut data, to_store) = cached_texture(&blocking_cache, &path_clone, key, || {
+                    let bytes = mounts.read(&path_clone).map_err(|e| e.to_string())?;
+                    let (pixels, width, height) =
+                        exr_parser::parse_exr(bytes, face_size).map_err(|e| e.to_string())?;
+                    Ok(TextureData {
+                        name: "".to_string(),
+                        pixels: TextureType::HDR(pixels),
+                        width,
+                        height,
+                        format: TextureFormat::Rgba32Float,
+                        mips: false,
+                    })
+                })?;
+                let metadata = AssetMetadata::new(&path_clone, &settings);
+                postprocessors.run(&mut data, &metadata).map_err(|e| e.to_string())?;
+                Ok::<_, String>((data, to_store))
+            })
+            .await;
 
             match result {
-                Ok(Ok((pixels, width, height))) => {
+                Ok(Ok((data, to_store))) => {
                     let _ = sender.send(AssetWorkerMessage::TextureLoaded {
                         id,
-                        path,
-                        data: TextureData {
-                            name: "".to_string(),
-                            pixels: TextureType::HDR(pixels),
-                            width,
-                            height,
-                            format: TextureFormat::Rgba32Float,
-                        },
+                        path: path.clone(),
+                        data,
                     });
+                    store_in_cache(cache, path, to_store);
                 }
-                Err(e) => eprintln!("Exr Task Error: {:?}", e),
-                Ok(Err(e)) => eprintln!("Failed to parse Exr '{}': {}", path, e),
+                Err(e) => log::error!("Exr Task Error: {:?}", e),
+                Ok(Err(e)) => log::error!("Failed to parse Exr '{}': {}", path, e),
             };
         });
 
@@ -120,35 +461,277 @@ impl AssetServer {
     }
 
     pub fn load_scene(&self, path: &str, entity: Entity) -> EntityHandle<SceneData> {
-        let handle = EntityHandle::<SceneData>::new(entity);
-        // let id = handle.id;
+        self.load_scene_with_options(path, entity, SceneLoadOptions::default())
+    }
+
+    /// Loads a scene without hitching the frames around it: once the CPU data arrives,
+    /// its textures, materials and meshes are uploaded a few per frame, and the scene
+    /// entity gets SceneReady when all of them are on the GPU (see `EntityHandle::load_state`).
+    /// Needs the RenderPlugin; without it the scene never becomes ready.
+    pub fn load_scene_prewarmed(&self, path: &str, entity: Entity) -> EntityHandle<SceneData> {
+        self.load_scene_with_options(
+            path,
+            entity,
+            SceneLoadOptions {
+                prewarm: true,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Loads the nodes of a scene `filter` matches, with their subtrees, and only the
+    /// meshes, materials and textures they use: the rest of the file is never decoded
+    /// or uploaded. Assets shared by several kept nodes are loaded once. Handy to pick
+    /// one variant, or a few props out of a library file, e.g.
+    /// `load_scene_filtered("props.glb", entity, SceneFilter::names(["Tree_*"]))`.
+    pub fn load_scene_filtered(
+        &self,
+        path: &str,
+        entity: Entity,
+        filter: SceneFilter,
+    ) -> EntityHandle<SceneData> {
+        self.load_scene_with_options(
+            path,
+            entity,
+            SceneLoadOptions {
+                filter: Some(filter),
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn load_scene_with_options(
+        &self,
+        path: &str,
+        entity: Entity,
+        options: SceneLoadOptions,
+    ) -> EntityHandle<SceneData> {
+        self.watch(
+            path,
+            ReloadTarget::Scene {
+                entity,
+                options: options.clone(),
+            },
+        );
+        self.spawn_scene_load(path, entity, options);
+        EntityHandle::new(entity)
+    }
+
+    fn spawn_scene_load(&self, path: &str, entity: Entity, options: SceneLoadOptions) {
+        let SceneLoadOptions {
+            prewarm,
+            lods,
+            import,
+            filter,
+        } = options;
         let path = path.to_owned();
         let sender = self.event_sender.clone();
+        let mounts = self.mounts.clone();
+        let write_settings = self.write_import_settings.load(Ordering::Relaxed);
+        let postprocessors = self.postprocessors.clone();
 
         // Spawn background task
         self.io_handle.spawn(async move {
             let path_clone = path.clone();
             // Run blocking parser
-            let result =
-                tokio::task::spawn_blocking(move || gltf_parser::parse_gltf(&path_clone)).await;
+            let result = tokio::task::spawn_blocking(move || {
+                let settings =
+                    import_settings(&path_clone, &mounts, import.as_ref(), write_settings);
+                let (mut scene, mut textures, mut materials, mut meshes) = gltf_parser::parse_gltf(
+                    &path_clone,
+                    mounts.as_ref(),
+                    &settings,
+                    filter.as_ref(),
+                )?;
+                // Before the LODs, so they are built from the processed meshes
+                postprocessors
+                    .run_scene(
+                        &mut scene,
+                        &mut textures,
+                        &mut materials,
+                        &mut meshes,
+                        &AssetMetadata::new(&path_clone, &settings),
+                    )
+                    .map_err(|e| e.to_string())?;
+                Ok::<_, String>(((scene, textures, materials, meshes), settings.mesh.lods))
+            })
+            .await;
 
             match result {
-                Ok(Ok(payload)) => {
-                    // Send the "Big Payload" back to main thread
-                    let _ = sender.send(AssetWorkerMessage::SceneLoaded {
-                        entity,
-                        path,
-                        scene: payload.0,
-                        textures: payload.1,
-                        materials: payload.2,
-                        meshes: payload.3,
-                    });
+                Ok(Ok((payload, settings_lods))) => {
+                    let scene_path = path.clone();
+                    let send = move |(scene, textures, materials, meshes): gltf_parser::GltfPayload| {
+                        // Send the "Big Payload" back to main thread
+                        let _ = sender.send(AssetWorkerMessage::SceneLoaded {
+                            entity,
+                            path,
+                            scene,
+                            textures,
+                            materials,
+                            meshes,
+                            prewarm,
+                        });
+                    };
+
+                    match lods.or(settings_lods) {
+                        // CPU heavy, so it runs on the compute pool rather than an IO thread
+                        Some(settings) => rayon::spawn(move || {
+                            catalyst_core::profile_scope!("Scene LODs");
+                            send(add_scene_lods(&scene_path, payload, &settings));
+                        }),
+                        None => send(payload),
+                    }
                 }
-                Err(e) => eprintln!("GLTF Task Error: {:?}", e),
-                Ok(Err(e)) => eprintln!("Failed to parse GLTF '{}': {}", path, e),
+                Err(e) => log::error!("GLTF Task Error: {:?}", e),
+                Ok(Err(e)) => log::error!("Failed to parse GLTF '{}': {}", path, e),
             }
         });
+    }
 
-        handle
+    /// Loads every watched file whose modification time changed since it was last
+    /// loaded again, into the asset it was loaded into. Returns how many were. The
+    /// HotReload system calls it every `poll_interval` while enabled.
+    pub fn reload_changed(&self) -> usize {
+        let mut reloads = Vec::new();
+        for file in self.watched.lock().unwrap().iter_mut() {
+            let modified = self.mounts.modified(&file.path);
+            if modified.is_none() || modified == file.modified {
+                continue;
+            }
+            file.modified = modified;
+            reloads.push((file.path.clone(), file.target.clone()));
+        }
+
+        for (path, target) in &reloads {
+            log::info!("Reloading '{}'", path);
+            match target.clone() {
+                ReloadTarget::Texture { id, overrides } => {
+                    self.load_texture_into(path, id, overrides)
+                }
+                ReloadTarget::Material { id } => self.spawn_material_load(path, id),
+                ReloadTarget::Scene { entity, options } => {
+                    self.spawn_scene_load(path, entity, options)
+                }
+            }
+        }
+        reloads.len()
+    }
+
+    /// Stops watching the file of a scene entity, e.g. once it was deleted.
+    pub fn forget_scene(&self, entity: Entity) {
+        self.watched.lock().unwrap().retain(
+            |file| !matches!(file.target, ReloadTarget::Scene { entity: e, .. } if e == entity),
+        );
     }
+
+    // Remembers where an asset was loaded from. Only loose files can change; a scene
+    // entity loaded again replaces its entry
+    fn watch(&self, path: &str, target: ReloadTarget) {
+        let mut watched = self.watched.lock().unwrap();
+        if let ReloadTarget::Scene { entity, .. } = target {
+            watched.retain(
+                |file| !matches!(file.target, ReloadTarget::Scene { entity: e, .. } if e == entity),
+            );
+        }
+        watched.push(WatchedFile {
+            path: normalize_path(path),
+            modified: self.mounts.modified(path),
+            target,
+        });
+    }
+
+    // The texture `load_texture` already loaded from `path`, if any
+    fn loaded_texture(&self, path: &str) -> Option<Handle<TextureData>> {
+        let path = normalize_path(path);
+        self.watched
+            .lock()
+            .unwrap()
+            .iter()
+            .find_map(|file| match &file.target {
+                ReloadTarget::Texture {
+                    id,
+                    overrides: None,
+                } if file.path == path => Some(Handle::from_id(*id)),
+                _ => None,
+            })
+    }
+}
+
+/// Parses the glTF scene at `path` from `reader` on this thread, as scene loads do before
+/// the postprocessors run. Nothing is sent to the world.
+pub fn parse_scene(
+    path: &str,
+    reader: &dyn AssetReader,
+    settings: &ImportSettings,
+    filter: Option<&SceneFilter>,
+) -> Result<GltfPayload, String> {
+    gltf_parser::parse_gltf(path, reader, settings, filter)
+}
+
+// The cached parse of `path` under `key`, or a fresh one from `parse`. A fresh parse
+// comes back with a copy for the cache, stored once the load is done
+fn cached_texture(
+    cache: &AssetCache,
+    path: &str,
+    key: Option<u64>,
+    parse: impl FnOnce() -> Result<TextureData, String>,
+) -> Result<(TextureData, Option<(u64, TextureData)>), String> {
+    if let Some(data) = key.and_then(|key| cache.load_texture(path, key)) {
+        return Ok((data, None));
+    }
+    let data = parse()?;
+    let to_store = key.map(|key| (key, data.clone()));
+    Ok((data, to_store))
+}
+
+// Writes a fresh parse to the cache on the IO pool, after the asset was sent, so the
+// write doesn't delay the first load
+fn store_in_cache(cache: Arc<AssetCache>, path: String, to_store: Option<(u64, TextureData)>) {
+    if let Some((key, data)) = to_store {
+        tokio::task::spawn_blocking(move || cache.store_texture(&path, key, &data));
+    }
+}
+
+// The import settings of `path`, written back to its .meta file if asked to
+fn import_settings(
+    path: &str,
+    mounts: &AssetMounts,
+    overrides: Option<&ImportSettings>,
+    write: bool,
+) -> ImportSettings {
+    let settings = ImportSettings::resolve(path, mounts, overrides);
+    if write {
+        if let Err(error) = settings.write(path, mounts) {
+            log::warn!("Can't write import settings of '{}': {}", path, error);
+        }
+    }
+    settings
+}
+
+// Simplifies every mesh of the scene, appending the reduced meshes to its payload
+fn add_scene_lods(
+    path: &str,
+    (mut scene, textures, materials, mut meshes): gltf_parser::GltfPayload,
+    settings: &GenerateLods,
+) -> gltf_parser::GltfPayload {
+    let chains: Vec<_> = meshes
+        .par_iter()
+        .enumerate()
+        .map(|(index, (_, data))| generate_lods(&format!("{} mesh {}", path, index), data, settings))
+        .collect();
+
+    scene.mesh_lods = chains
+        .into_iter()
+        .map(|chain| {
+            chain
+                .into_iter()
+                .map(|(level, data)| {
+                    meshes.push((level.mesh.clone(), data));
+                    level
+                })
+                .collect()
+        })
+        .collect();
+
+    (scene, textures, materials, meshes)
 }
@@ -1,4 +1,3 @@
-use exr::prelude::read_first_rgba_layer_from_file;
 use glam::Vec3;
 
 type ExtPayload = (Vec<f32>, u32, u32);
@@ -9,27 +8,36 @@ struct HdrBuffer {
 }
 
 pub fn parse_exr(
-    path: &str,
+    bytes: Vec<u8>,
     face_size: u32, /* e.g., 1024 or 2048 */
 ) -> Result<ExtPayload, exr::error::Error> {
-    let image = read_first_rgba_layer_from_file(
-        path,
-        |resolution, _| {
-            let width = resolution.width();
-            let height = resolution.height();
-            HdrBuffer {
-                width, // Store width so we can use it later
-                data: vec![0.0; width * height * 4],
-            }
-        },
-        |buffer, position, (r, g, b, a): (f32, f32, f32, f32)| {
-            let idx = (position.y() * buffer.width + position.x()) * 4;
-            buffer.data[idx] = r;
-            buffer.data[idx + 1] = g;
-            buffer.data[idx + 2] = b;
-            buffer.data[idx + 3] = a;
-        },
-    )?;
+    // exr's prelude shadows `Result`, so keep it scoped to this function
+    use exr::prelude::*;
+
+    // Same as read_first_rgba_layer_from_file, but from memory so the EXR can come from a pack
+    let image = read()
+        .no_deep_data()
+        .largest_resolution_level()
+        .rgba_channels(
+            |resolution, _| {
+                let width = resolution.width();
+                let height = resolution.height();
+                HdrBuffer {
+                    width, // Store width so we can use it later
+                    data: vec![0.0; width * height * 4],
+                }
+            },
+            |buffer, position, (r, g, b, a): (f32, f32, f32, f32)| {
+                let idx = (position.y() * buffer.width + position.x()) * 4;
+                buffer.data[idx] = r;
+                buffer.data[idx + 1] = g;
+                buffer.data[idx + 2] = b;
+                buffer.data[idx + 3] = a;
+            },
+        )
+        .first_valid_layer()
+        .all_attributes()
+        .from_buffered(std::io::Cursor::new(bytes))?;
 
     // Extract the data from the returned image structure
     // The type structure is: Image { layer_data: Layer { channel_data: Pixels { pixels: T } } }
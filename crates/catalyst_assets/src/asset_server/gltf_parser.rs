@@ -1,72 +1,160 @@
-use std::{collections::HashMap, path::Path};
+use std::collections::HashMap;
 
 use catalyst_core::{
     camera::{self, Camera},
     transform::Transform,
 };
-use glam::Quat;
+use glam::{Mat4, Quat, Vec3};
 
 use crate::{
     assets::{Handle, MeshData, Vertex},
-    material::{MaterialData, MaterialSettings, TextureData, TextureFormat},
+    import::ImportSettings,
+    io::{AssetReader, resolve_relative},
+    material::{MaterialData, MaterialSettings, MaterialWind, TextureData, TextureFormat},
     physics::PhysicsExtras,
-    scene::SceneData,
+    scene::{SceneData, SceneFilter},
 };
 
-type GltfPayload = (
+/// A parsed scene with the data of its assets, as handed to the postprocessors.
+pub type GltfPayload = (
     SceneData,
     Vec<(Handle<TextureData>, TextureData)>,
     Vec<(Handle<MaterialData>, MaterialData)>,
     Vec<(Handle<MeshData>, MeshData)>,
 );
 
-pub fn parse_gltf(path: &str) -> Result<GltfPayload, String> {
-    let base_path = Path::new(path).parent().unwrap_or(Path::new("./"));
-
+pub fn parse_gltf(
+    path: &str,
+    reader: &dyn AssetReader,
+    settings: &ImportSettings,
+    filter: Option<&SceneFilter>,
+) -> Result<GltfPayload, String> {
     // A. Load Document & Buffers
-    let (document, buffers, images) = gltf::import(path).map_err(|e| e.to_string())?;
+    // We go through the AssetReader instead of gltf::import so scenes can live inside packs
+    let bytes = reader.read(path).map_err(|e| e.to_string())?;
+    let gltf::Gltf { document, mut blob } =
+        gltf::Gltf::from_slice(&bytes).map_err(|e| e.to_string())?;
+    let buffers = load_buffers(&document, &mut blob, path, reader)?;
+
+    // --- STEP 0: WHAT TO LOAD ---
+    // Decided before decoding anything, so what a filter drops costs nothing
+    let nodes: Vec<_> = document.nodes().collect();
+    let mut parents = vec![None; nodes.len()];
+    for node in &nodes {
+        for child in node.children() {
+            parents[child.index()].get_or_insert(node.index());
+        }
+    }
+    let node_extras: Vec<Option<serde_json::Value>> = nodes
+        .iter()
+        .map(|node| {
+            node.extras()
+                .as_ref()
+                .and_then(|extras| serde_json::from_str(extras.get()).ok())
+        })
+        .collect();
+
+    let kept_nodes: Vec<bool> = match filter {
+        None => vec![true; nodes.len()],
+        Some(filter) => {
+            let matched: Vec<bool> = nodes
+                .iter()
+                .map(|node| {
+                    filter.matches(
+                        node.name().unwrap_or("Node"),
+                        node_extras[node.index()].as_ref(),
+                    )
+                })
+                .collect();
+            // Kept with a matching ancestor; bounded so a cycle can't hang the load
+            (0..nodes.len())
+                .map(|index| ancestors(index, &parents).any(|ancestor| matched[ancestor]))
+                .collect()
+        }
+    };
+    if let Some(filter) = filter {
+        if !kept_nodes.contains(&true) {
+            log::warn!(
+                "Scene filter '{}' matches no node of '{}'",
+                filter.key(),
+                path
+            );
+        }
+    }
+
+    // A kept node pulls in its mesh, the mesh pulls in its materials and they pull in
+    // their textures; without a filter everything is loaded, used or not
+    let mut kept_meshes = vec![filter.is_none(); document.meshes().len()];
+    for node in nodes.iter().filter(|node| kept_nodes[node.index()]) {
+        if let Some(mesh) = node.mesh() {
+            kept_meshes[mesh.index()] = true;
+        }
+    }
+    let mut kept_materials = vec![filter.is_none(); document.materials().len()];
+    for mesh in document.meshes().filter(|mesh| kept_meshes[mesh.index()]) {
+        for primitive in mesh.primitives() {
+            if let Some(index) = primitive.material().index() {
+                kept_materials[index] = true;
+            }
+        }
+    }
+    let mut kept_images = vec![filter.is_none(); document.images().len()];
+    for material in document
+        .materials()
+        .filter(|material| material.index().is_some_and(|index| kept_materials[index]))
+    {
+        for image in material_images(&material) {
+            kept_images[image] = true;
+        }
+    }
 
     // --- STEP 1: TEXTURES ---
     let mut texture_artifacts = Vec::new();
-    let mut texture_map = Vec::new(); // Maps GLTF Image Index -> Our Handle
+    let mut texture_map = Vec::new();
+    let mut image_map = vec![None; kept_images.len()]; // Maps GLTF Image Index -> Our index
 
     for image in document.images() {
-        match image.source() {
+        if !kept_images[image.index()] {
+            continue;
+        }
+        let name = image.name().unwrap_or("GLTF Image");
+
+        let image_data = match image.source() {
             gltf::image::Source::View { view, .. } => {
                 let buffer = &buffers[view.buffer().index()];
 
-                let name = image.name().unwrap_or("GLTF Image");
-
                 let start = view.offset();
                 let end = start + view.length();
-                let image_data = &buffer[start..end];
-
-                // Decode image data using the `image` crate
-                let img = image::load_from_memory(image_data)
-                    .map_err(|e| format!("Failed to decode image: {}", e))?;
-
-                let img = img.to_rgba8();
-
-                let width = img.width();
-                let height = img.height();
-                let pixels = img.into_raw(); // Get raw pixel data
-
-                // Create our TextureData
-                let image = TextureData {
-                    name: name.to_string(),
-                    width,
-                    height,
-                    pixels: crate::material::TextureType::LDR(pixels),
-                    format: TextureFormat::Rgba8Unorm,
-                };
-
-                // Store the texture data
-                let handle = Handle::<TextureData>::new();
-                texture_artifacts.push((handle.clone(), image));
-                texture_map.push(handle);
+                buffer[start..end].to_vec()
             }
-            gltf::image::Source::Uri { .. } => {}
-        }
+            gltf::image::Source::Uri { uri, .. } => load_uri(uri, path, reader)?,
+        };
+
+        // Decode image data using the `image` crate
+        let img = image::load_from_memory(&image_data)
+            .map_err(|e| format!("Failed to decode image: {}", e))?;
+
+        let img = settings.texture.fit(img).to_rgba8();
+
+        let width = img.width();
+        let height = img.height();
+        let pixels = img.into_raw(); // Get raw pixel data
+
+        // Create our TextureData
+        let image = TextureData {
+            name: name.to_string(),
+            width,
+            height,
+            pixels: crate::material::TextureType::LDR(pixels),
+            format: TextureFormat::Rgba8Unorm,
+            mips: settings.texture.mips,
+        };
+
+        // Store the texture data
+        let handle = Handle::<TextureData>::new();
+        image_map[image.index()] = Some(texture_map.len());
+        texture_artifacts.push((handle.clone(), image));
+        texture_map.push(handle);
 
         // let converted_pixels = match image.format {
         //     // CASE A: It's already RGBA (Good!)
@@ -109,41 +197,55 @@ pub fn parse_gltf(path: &str) -> Result<GltfPayload, String> {
     // --- STEP 2: MATERIALS ---
     let mut material_artifacts = Vec::new();
     let mut material_map = Vec::new();
+    let mut material_index_map = vec![None; kept_materials.len()];
 
     for mat in document.materials() {
+        let Some(index) = mat.index().filter(|&index| kept_materials[index]) else {
+            continue;
+        };
         let pbr = mat.pbr_metallic_roughness();
 
         // 1. Resolve Texture Handle
-        let diffuse_handle = pbr.base_color_texture().map(|info| {
-            let idx = info.texture().source().index();
+        let diffuse_handle = pbr.base_color_texture().and_then(|info| {
+            let idx = image_map[info.texture().source().index()]?;
             texture_artifacts[idx].1.format = TextureFormat::Rgba8UnormSrgb;
-            texture_map[idx].clone() // <--- The Link!
+            Some(texture_map[idx].clone()) // <--- The Link!
         });
 
-        let roughness_handle = pbr.metallic_roughness_texture().map(|info| {
-            let idx = info.texture().source().index();
+        let roughness_handle = pbr.metallic_roughness_texture().and_then(|info| {
+            let idx = image_map[info.texture().source().index()]?;
             texture_artifacts[idx].1.format = TextureFormat::Rgba8Unorm;
-            texture_map[idx].clone() // <--- The Link!
+            Some(texture_map[idx].clone()) // <--- The Link!
         });
 
-        let normal_handle = mat.normal_texture().map(|info| {
-            let idx = info.texture().source().index();
+        let normal_handle = mat.normal_texture().and_then(|info| {
+            let idx = image_map[info.texture().source().index()]?;
             texture_artifacts[idx].1.format = TextureFormat::Rgba8Unorm;
-            texture_map[idx].clone() // <--- The Link!
+            Some(texture_map[idx].clone()) // <--- The Link!
         });
 
-        let occlusion_handle = mat.occlusion_texture().map(|info| {
-            let idx = info.texture().source().index();
+        let occlusion_handle = mat.occlusion_texture().and_then(|info| {
+            let idx = image_map[info.texture().source().index()]?;
             texture_artifacts[idx].1.format = TextureFormat::Rgba8Unorm;
-            texture_map[idx].clone() // <--- The Link!
+            Some(texture_map[idx].clone()) // <--- The Link!
         });
 
         // 2. Build Material Data
         let mat_data = MaterialData {
             settings: MaterialSettings {
-                base_color: pbr.base_color_factor(),
+                base_color: pbr.base_color_factor().into(),
                 roughness: pbr.roughness_factor(),
                 metallic: pbr.metallic_factor(),
+                alpha_cutoff: match mat.alpha_mode() {
+                    // glTF default cutoff is 0.5
+                    gltf::material::AlphaMode::Mask => Some(mat.alpha_cutoff().unwrap_or(0.5)),
+                    _ => None,
+                },
+                double_sided: mat.double_sided(),
+                emissive: mat.emissive_factor().into(),
+                emissive_strength: mat.emissive_strength().unwrap_or(1.0),
+                unlit: mat.unlit(),
+                wind: material_wind(&mat),
             },
             diffuse_texture: diffuse_handle,
             // For now, we skip Normal/Metallic maps to keep it simple.
@@ -154,15 +256,26 @@ pub fn parse_gltf(path: &str) -> Result<GltfPayload, String> {
         };
 
         let handle = Handle::<MaterialData>::new();
+        material_index_map[index] = Some(material_map.len());
         material_artifacts.push((handle.clone(), mat_data));
         material_map.push(handle);
     }
 
+    // The material slots picked the color spaces; a forced one replaces them
+    for (_, texture) in &mut texture_artifacts {
+        texture.format = settings.texture.format(texture.format);
+    }
+
     // --- STEP 3: MESHES ---
     let mut mesh_artifacts = Vec::new();
-    let mut mesh_map = Vec::new(); // Maps GLTF Mesh Index -> Our Handle
+    let mut mesh_map = Vec::new();
+    let mut mesh_index_map = vec![None; kept_meshes.len()]; // GLTF Mesh -> its first primitive
 
     for mesh in document.meshes() {
+        if !kept_meshes[mesh.index()] {
+            continue;
+        }
+        mesh_index_map[mesh.index()] = Some(mesh_map.len());
         for primitive in mesh.primitives() {
             let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
 
@@ -184,6 +297,11 @@ pub fn parse_gltf(path: &str) -> Result<GltfPayload, String> {
                 .map(|read| read.into_f32().collect())
                 .unwrap_or_else(|| vec![[0.0, 0.0]; positions.len()]);
 
+            // Wind weights from the red channel of the vertex colors, else from the height
+            let wind_weights: Option<Vec<f32>> = reader
+                .read_colors(0)
+                .map(|read| read.into_rgba_f32().map(|color| color[0]).collect());
+
             // Extract Indices
             let indices: Vec<u32> = reader
                 .read_indices()
@@ -194,13 +312,37 @@ pub fn parse_gltf(path: &str) -> Result<GltfPayload, String> {
             let mut vertices = Vec::new();
             for i in 0..positions.len() {
                 vertices.push(Vertex {
-                    position: positions[i],
+                    position: positions[i].map(|value| value * settings.mesh.scale),
                     normal: normals[i],
                     uv: uvs[i],
+                    wind_weight: wind_weights.as_ref().map_or(0.0, |weights| weights[i]),
                 });
             }
 
-            let mesh_data = MeshData { vertices, indices };
+            // Bad exports: NaN positions, degenerate or out of range triangles
+            let mut mesh_data = MeshData::new(vertices, indices);
+            if wind_weights.is_none() {
+                mesh_data.wind_weights_from_height();
+            }
+            let report = mesh_data.repair();
+            let label = format!(
+                "{} mesh {} ({}) primitive {}",
+                path,
+                mesh.index(),
+                mesh.name().unwrap_or("unnamed"),
+                primitive.index()
+            );
+            if !report.is_clean() {
+                log::warn!("{} was repaired: {}", label, report);
+            } else if report.non_manifold_edges > 0 {
+                log::warn!("{} is not manifold: {}", label, report);
+            } else {
+                log::debug!("{}: {}", label, report);
+            }
+            mesh_data.repair_report = Some(report);
+            if settings.mesh.generate_tangents {
+                mesh_data.generate_tangents();
+            }
             let handle = Handle::<MeshData>::new();
 
             mesh_artifacts.push((handle.clone(), mesh_data));
@@ -215,18 +357,45 @@ pub fn parse_gltf(path: &str) -> Result<GltfPayload, String> {
     // --- STEP 4: NODES (The Hierarchy) ---
     let mut scene_nodes = Vec::new();
 
-    for node in document.nodes() {
-        // Position/Rotation/Scale
-        let (t, r, s) = node.transform().decomposed();
+    // Position/Rotation/Scale
+    let transforms: Vec<Transform> = nodes
+        .iter()
+        .map(|node| {
+            let (t, r, s) = node.transform().decomposed();
+            // Scaling the translations too keeps the scene's layout with its meshes
+            Transform {
+                translation: Vec3::from(t) * settings.mesh.scale,
+                rotation: Quat::from_array(r),
+                scale: s.into(),
+            }
+        })
+        .collect();
+
+    let mut node_map = vec![None; nodes.len()]; // Maps GLTF Node Index -> Our index
+    for (new_index, index) in (0..nodes.len())
+        .filter(|&index| kept_nodes[index])
+        .enumerate()
+    {
+        node_map[index] = Some(new_index);
+    }
 
-        let transform = Transform {
-            translation: t.into(),
-            rotation: Quat::from_array(r),
-            scale: s.into(),
-        };
+    for node in nodes.iter().filter(|node| kept_nodes[node.index()]) {
+        let mut transform = transforms[node.index()];
+        // The top of a kept subtree lost its parents, it keeps their placement
+        if let Some(parent) = parents[node.index()].filter(|&parent| !kept_nodes[parent]) {
+            let world = ancestors(parent, &parents).fold(Mat4::IDENTITY, |world, ancestor| {
+                transforms[ancestor].compute_matrix() * world
+            }) * transform.compute_matrix();
+            let (scale, rotation, translation) = world.to_scale_rotation_translation();
+            transform = Transform {
+                translation,
+                rotation,
+                scale,
+            };
+        }
 
         // Link to Mesh
-        let mesh_index = node.mesh().map(|m| m.index());
+        let mesh_index = node.mesh().and_then(|m| mesh_index_map[m.index()]);
 
         // Link to Material
         // In GLTF, materials are assigned to Mesh Primitives, not Nodes directly.
@@ -234,12 +403,15 @@ pub fn parse_gltf(path: &str) -> Result<GltfPayload, String> {
         let material_index = node
             .mesh()
             .and_then(|m| m.primitives().next())
-            .and_then(|p| p.material().index());
+            .and_then(|p| p.material().index())
+            .and_then(|index| material_index_map[index]);
 
         let camera_index = node.camera().map(|cam| cam.index());
 
         // physics
-        let physics = if let Some(extras) = node.extras() {
+        let physics = if !settings.mesh.physics {
+            None
+        } else if let Some(extras) = node.extras() {
             if let Ok(json) = serde_json::from_str::<PhysicsExtras>(extras.get()) {
                 Some(json)
             } else {
@@ -249,14 +421,20 @@ pub fn parse_gltf(path: &str) -> Result<GltfPayload, String> {
             None
         };
 
+        let extras = node_extras[node.index()].clone();
+
         scene_nodes.push(crate::scene::SceneNode {
             name: node.name().unwrap_or("Node").to_string(),
             transform,
             mesh_index,
             material_index,
             camera_index,
-            children: node.children().map(|c| c.index()).collect(),
+            children: node
+                .children()
+                .filter_map(|c| node_map[c.index()])
+                .collect(),
             physics,
+            extras,
         });
     }
 
@@ -269,7 +447,8 @@ pub fn parse_gltf(path: &str) -> Result<GltfPayload, String> {
                 fov: perspective.yfov(),
                 aspect_ratio: perspective.aspect_ratio().unwrap_or(1f32),
                 near: perspective.znear(),
-                far: perspective.zfar().unwrap_or(1f32),
+                // glTF omits zfar for infinite projections; fall back to the engine default
+                far: perspective.zfar().unwrap_or(Camera::default().far),
             },
         })
         .collect();
@@ -281,6 +460,8 @@ pub fn parse_gltf(path: &str) -> Result<GltfPayload, String> {
         meshes: mesh_map,
         camera: cameras,
         physics_materials: HashMap::new(),
+        mesh_lods: Vec::new(),
+        filter: filter.map(|filter| filter.key().to_string()),
     };
 
     Ok((
@@ -290,3 +471,82 @@ pub fn parse_gltf(path: &str) -> Result<GltfPayload, String> {
         mesh_artifacts,
     ))
 }
+
+// `index`, then its parents up to the root. Stops after as many steps as there are
+// nodes, in case the parents loop
+fn ancestors(index: usize, parents: &[Option<usize>]) -> impl Iterator<Item = usize> + '_ {
+    std::iter::successors(Some(index), |&node| parents[node]).take(parents.len())
+}
+
+// Images a material samples, by glTF image index
+// Extras `"wind": true` or `"wind": { "strength": .. }`, or "_wind" in the name
+fn material_wind(material: &gltf::Material) -> Option<MaterialWind> {
+    let extras = material
+        .extras()
+        .as_ref()
+        .and_then(|extras| serde_json::from_str::<serde_json::Value>(extras.get()).ok());
+    match extras.as_ref().and_then(|extras| extras.get("wind")) {
+        Some(serde_json::Value::Bool(wind)) => return wind.then(MaterialWind::default),
+        Some(wind @ serde_json::Value::Object(_)) => {
+            return serde_json::from_value(wind.clone()).ok();
+        }
+        _ => {}
+    }
+    material
+        .name()
+        .is_some_and(|name| name.to_lowercase().contains("_wind"))
+        .then(MaterialWind::default)
+}
+
+fn material_images(material: &gltf::Material) -> Vec<usize> {
+    let pbr = material.pbr_metallic_roughness();
+    [
+        pbr.base_color_texture().map(|info| info.texture()),
+        pbr.metallic_roughness_texture().map(|info| info.texture()),
+        material.normal_texture().map(|info| info.texture()),
+        material.occlusion_texture().map(|info| info.texture()),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|texture| texture.source().index())
+    .collect()
+}
+
+// Resolves every glTF buffer to bytes: the GLB binary chunk, an embedded
+// base64 data URI, or an external .bin file next to the scene.
+fn load_buffers(
+    document: &gltf::Document,
+    blob: &mut Option<Vec<u8>>,
+    path: &str,
+    reader: &dyn AssetReader,
+) -> Result<Vec<Vec<u8>>, String> {
+    document
+        .buffers()
+        .map(|buffer| {
+            let mut data = match buffer.source() {
+                gltf::buffer::Source::Bin => blob.take().ok_or("GLB binary chunk missing")?,
+                gltf::buffer::Source::Uri(uri) => load_uri(uri, path, reader)?,
+            };
+
+            if data.len() < buffer.length() {
+                return Err(format!("Buffer {} is truncated", buffer.index()));
+            }
+            // Buffers may be padded to 4 bytes
+            data.truncate(buffer.length());
+            Ok(data)
+        })
+        .collect()
+}
+
+fn load_uri(uri: &str, path: &str, reader: &dyn AssetReader) -> Result<Vec<u8>, String> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        let (_, encoded) = data
+            .split_once(";base64,")
+            .ok_or_else(|| format!("Unsupported data URI in '{}'", path))?;
+        base64::decode(encoded).map_err(|e| e.to_string())
+    } else {
+        reader
+            .read(&resolve_relative(path, uri))
+            .map_err(|e| e.to_string())
+    }
+}
@@ -1,9 +1,13 @@
 use std::{cmp::Ordering, marker::PhantomData};
+use catalyst_core::bounds::Aabb;
 use flecs_ecs::prelude::*;
 use std::hash::{Hash, Hasher};
 use uuid::Uuid;
 
-use crate::asset_events::AssetLookup;
+use crate::{
+    asset_events::{AssetEvent, AssetLookup, emit_asset_event},
+    mesh_repair::MeshRepairReport,
+};
 
 #[derive(Debug)]
 pub struct EntityHandle<T> {
@@ -107,15 +111,212 @@ impl<T> PartialOrd for Handle<T> {
     }
 }
 
+impl Handle<MeshData> {
+    /// Edits the mesh in place. Bounds are recomputed afterwards, and
+    /// `AssetEvent::MeshModified` is emitted, so the renderer updates the GPU buffers and
+    /// every entity drawing this mesh picks it up without being respawned.
+    /// Returns false while the mesh hasn't arrived in the world yet.
+    pub fn modify(&self, world: &World, f: impl FnOnce(&mut MeshData)) -> bool {
+        let Some(entity) = self.try_get_entity(world) else {
+            return false;
+        };
+
+        let modified = entity
+            .try_get::<&mut MeshData>(|data| {
+                f(data);
+                data.aabb = MeshData::compute_aabb(&data.vertices);
+            })
+            .is_some();
+
+        if modified {
+            emit_asset_event(world, AssetEvent::MeshModified { id: self.id });
+        }
+        modified
+    }
+}
+
 #[derive(Debug)]
 pub struct Vertex {
     pub position: [f32; 3], // Flat lists are easier for generic loaders
     pub normal: [f32; 3],
     pub uv: [f32; 2],
+    /// How far the vertex sways with materials that have wind, from 0 (rooted) to 1.
+    /// Read from the red channel of glTF vertex colors, see `MeshData::wind_weights_from_height`
+    /// for meshes without them.
+    pub wind_weight: f32,
 }
 
 #[derive(Component, Debug)]
 pub struct MeshData {
     pub vertices: Vec<Vertex>,
     pub indices: Vec<u32>,
-}
\ No newline at end of file
+    // Local-space bounds, computed once at creation so consumers
+    // (culling, picking, LOD) don't have to walk the vertex list again
+    pub aabb: Aabb,
+    /// What the importer repaired, see `MeshData::repair`. None for meshes built in code.
+    pub repair_report: Option<MeshRepairReport>,
+    /// One per vertex when generated (`MeshData::generate_tangents`), else empty: the
+    /// direction of +U along the surface, with the bitangent's sign in w.
+    pub tangents: Vec<[f32; 4]>,
+}
+
+impl MeshData {
+    pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>) -> Self {
+        let aabb = Self::compute_aabb(&vertices);
+        Self {
+            vertices,
+            indices,
+            aabb,
+            repair_report: None,
+            tangents: Vec::new(),
+        }
+    }
+
+    /// Sets every vertex's wind weight from its height in the mesh: 0 at the bottom, 1 at
+    /// the top, squared so the base stays put while the tips bend.
+    pub fn wind_weights_from_height(&mut self) {
+        let (bottom, top) = (self.aabb.min.y, self.aabb.max.y);
+        let height = top - bottom;
+        for vertex in &mut self.vertices {
+            let t = if height > 0.0 {
+                ((vertex.position[1] - bottom) / height).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            vertex.wind_weight = t * t;
+        }
+    }
+
+    /// Fills `tangents` from the UVs, averaging the triangles around each vertex and
+    /// making the result orthogonal to its normal. Vertices without UV area get any
+    /// direction orthogonal to the normal. Edits made afterwards don't update them.
+    pub fn generate_tangents(&mut self) {
+        use glam::{Vec2, Vec3};
+
+        let count = self.vertices.len();
+        let mut tangents = vec![Vec3::ZERO; count];
+        let mut bitangents = vec![Vec3::ZERO; count];
+        for triangle in self.indices.chunks_exact(3) {
+            let corners = [triangle[0], triangle[1], triangle[2]].map(|index| index as usize);
+            if corners.iter().any(|&index| index >= count) {
+                continue;
+            }
+            let [a, b, c] = corners.map(|index| &self.vertices[index]);
+            let edge1 = Vec3::from_array(b.position) - Vec3::from_array(a.position);
+            let edge2 = Vec3::from_array(c.position) - Vec3::from_array(a.position);
+            let delta1 = Vec2::from_array(b.uv) - Vec2::from_array(a.uv);
+            let delta2 = Vec2::from_array(c.uv) - Vec2::from_array(a.uv);
+            let determinant = delta1.x * delta2.y - delta2.x * delta1.y;
+            if determinant.abs() <= f32::EPSILON {
+                continue;
+            }
+            let tangent = (edge1 * delta2.y - edge2 * delta1.y) / determinant;
+            let bitangent = (edge2 * delta1.x - edge1 * delta2.x) / determinant;
+            for index in corners {
+                tangents[index] += tangent;
+                bitangents[index] += bitangent;
+            }
+        }
+
+        self.tangents = self
+            .vertices
+            .iter()
+            .zip(tangents.into_iter().zip(bitangents))
+            .map(|(vertex, (tangent, bitangent))| {
+                let normal = Vec3::from_array(vertex.normal).normalize_or(Vec3::Y);
+                let tangent = (tangent - normal * normal.dot(tangent))
+                    .try_normalize()
+                    .unwrap_or_else(|| normal.any_orthonormal_vector());
+                let sign = if normal.cross(tangent).dot(bitangent) < 0.0 {
+                    -1.0
+                } else {
+                    1.0
+                };
+                tangent.extend(sign).to_array()
+            })
+            .collect();
+    }
+
+    /// Bounds of the vertex positions. An empty mesh gets a zero-sized box at the origin.
+    pub fn compute_aabb(vertices: &[Vertex]) -> Aabb {
+        Aabb::from_points(vertices.iter().map(|v| glam::Vec3::from_array(v.position)))
+            .unwrap_or_default()
+    }
+}
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{asset_events::AssetChanged, asset_server::AssetServer, primitives};
+
+    #[test]
+    fn modify_emits_mesh_modified_with_the_handle_id() {
+        let world = World::new();
+        world
+            .component::<AssetLookup>()
+            .add_trait::<flecs::Singleton>();
+        world.set(AssetLookup::default());
+
+        let handle = Handle::<MeshData>::new();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = events.clone();
+        world
+            .entity_from::<AssetServer>()
+            .observe_payload::<&AssetChanged>(move |AssetChanged(event)| {
+                seen.lock().unwrap().push(*event);
+            });
+
+        // Not in the world yet
+        assert!(!handle.modify(&world, |_| {}));
+        assert!(events.lock().unwrap().is_empty());
+
+        let entity = world.get::<&mut AssetLookup>(|lookup| lookup.entity(handle.id, &world));
+        world.entity_from_id(entity).set(primitives::plane(1.0));
+        assert!(handle.modify(&world, |mesh| {
+            for vertex in &mut mesh.vertices {
+                vertex.position[1] = 2.0;
+            }
+        }));
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![AssetEvent::MeshModified { id: handle.id }]
+        );
+        // Bounds follow the edit
+        let aabb = world
+            .entity_from_id(entity)
+            .get::<&MeshData>(|mesh| mesh.aabb);
+        assert_eq!((aabb.min.y, aabb.max.y), (2.0, 2.0));
+    }
+
+    #[test]
+    fn generated_tangents_follow_u_with_the_bitangent_sign() {
+        let vertex = |position: [f32; 3], uv: [f32; 2]| Vertex {
+            position,
+            normal: [0.0, 1.0, 0.0],
+            uv,
+            wind_weight: 0.0,
+        };
+        // Facing up, U along +X and V along +Z: the bitangent is -(normal x tangent)
+        let mut mesh = MeshData::new(
+            vec![
+                vertex([0.0, 0.0, 0.0], [0.0, 0.0]),
+                vertex([1.0, 0.0, 0.0], [1.0, 0.0]),
+                vertex([0.0, 0.0, 1.0], [0.0, 1.0]),
+                // Not in any triangle
+                vertex([5.0, 0.0, 5.0], [0.0, 0.0]),
+            ],
+            vec![0, 1, 2],
+        );
+        assert!(mesh.tangents.is_empty());
+
+        mesh.generate_tangents();
+        assert_eq!(mesh.tangents.len(), 4);
+        for tangent in &mesh.tangents[..3] {
+            assert_eq!(*tangent, [1.0, 0.0, 0.0, -1.0]);
+        }
+        let unused = glam::Vec4::from_array(mesh.tangents[3]);
+        assert!((unused.truncate().length() - 1.0).abs() < 1e-5);
+        assert!(unused.truncate().dot(glam::Vec3::Y).abs() < 1e-5);
+    }
+}
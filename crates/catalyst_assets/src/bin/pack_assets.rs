@@ -0,0 +1,37 @@
+// Builds a Catalyst pack file from a directory of loose assets.
+//
+// Usage:
+//   cargo run -p catalyst_assets --bin pack_assets -- <source_dir> <output.pak> [path_prefix]
+//
+// path_prefix defaults to the source directory name, so packing "assets"
+// keeps paths like "assets/simple15.glb" valid inside the pack.
+
+use std::path::Path;
+
+use catalyst_assets::io::pack::write_pack;
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    if args.len() < 3 {
+        eprintln!("Usage: pack_assets <source_dir> <output.pak> [path_prefix]");
+        std::process::exit(1);
+    }
+
+    let source_dir = Path::new(&args[1]);
+    let output = Path::new(&args[2]);
+    let prefix = args.get(3).cloned().unwrap_or_else(|| {
+        source_dir
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    });
+
+    match write_pack(source_dir, &prefix, output) {
+        Ok(count) => println!("Packed {} files into {:?}", count, output),
+        Err(e) => {
+            eprintln!("Failed to build pack: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
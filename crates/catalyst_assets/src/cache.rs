@@ -0,0 +1,463 @@
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        RwLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    import::ImportSettings,
+    io::normalize_path,
+    material::{TextureData, TextureFormat, TextureType},
+};
+
+// Cache file layout (all integers little endian):
+//
+// [magic "CCHE"] [version: u32] [key: u64]
+// [name_len: u32] [name: utf8 bytes] [width: u32] [height: u32]
+// [format: u8] [mips: u8] [pixel type: u8, 0 = LDR u8, 1 = HDR f32]
+// [pixel_count: u64] [pixels ...]
+//
+// One file per source path, named after a hash of the path. The key covers
+// everything the parse depends on, a file with another key is stale.
+const CACHE_MAGIC: &[u8; 4] = b"CCHE";
+/// Bumped whenever the layout or the parsers' output changes, so old files miss.
+pub const CACHE_VERSION: u32 = 1;
+
+/// Default cache folder, relative to the working directory.
+pub const DEFAULT_CACHE_DIR: &str = ".asset_cache";
+
+/// Counters since startup (or the last clear), for the debug UI.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AssetCacheStats {
+    pub hits: u64,
+    /// Loads that had to parse: no file, a stale one, or a broken one.
+    pub misses: u64,
+    /// Cache files that couldn't be read back and were deleted.
+    pub invalid: u64,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+}
+
+/// Parsed textures kept on disk between runs, so unchanged sources (EXR cubemaps above
+/// all) aren't decoded again. Loads look their key up first and fall back to a full
+/// parse on any mismatch; a file that can't be read back is deleted, never trusted.
+///
+/// What is cached is the parser's output, before postprocessors, which run on every
+/// load. Sources without a modification time (packed assets) aren't cached.
+pub struct AssetCache {
+    dir: RwLock<Option<PathBuf>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    invalid: AtomicU64,
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+}
+
+impl Default for AssetCache {
+    fn default() -> Self {
+        Self::new(Some(PathBuf::from(DEFAULT_CACHE_DIR)))
+    }
+}
+
+impl AssetCache {
+    /// A cache in `dir`, created on the first write; None disables it.
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        Self {
+            dir: RwLock::new(dir),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            invalid: AtomicU64::new(0),
+            bytes_read: AtomicU64::new(0),
+            bytes_written: AtomicU64::new(0),
+        }
+    }
+
+    /// Moves the cache (files already written stay where they are), None disables it.
+    pub fn set_dir(&self, dir: Option<PathBuf>) {
+        *self.dir.write().expect("asset cache poisoned") = dir;
+    }
+
+    pub fn dir(&self) -> Option<PathBuf> {
+        self.dir.read().expect("asset cache poisoned").clone()
+    }
+
+    pub fn stats(&self) -> AssetCacheStats {
+        AssetCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            invalid: self.invalid.load(Ordering::Relaxed),
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+        }
+    }
+
+    /// The cached texture parsed from `path` under `key`, counting a hit or a miss.
+    /// Always None while the cache is disabled.
+    pub fn load_texture(&self, path: &str, key: u64) -> Option<TextureData> {
+        let file = self.file(path)?;
+        let result = self.read_texture(&file, key);
+        match result {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        result
+    }
+
+    /// Writes `data`, parsed from `path`, under `key`. Failures are only logged: the
+    /// next run parses again.
+    pub fn store_texture(&self, path: &str, key: u64, data: &TextureData) {
+        let Some(file) = self.file(path) else {
+            return;
+        };
+        let bytes = encode_texture(key, data);
+        let result = file
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| write_atomically(&file, &bytes));
+        match result {
+            Ok(()) => {
+                self.bytes_written.fetch_add(bytes.len() as u64, Ordering::Relaxed);
+            }
+            Err(error) => log::warn!("Can't cache '{}': {}", path, error),
+        }
+    }
+
+    /// Deletes every cache file and resets the counters. Returns how many were deleted.
+    pub fn clear(&self) -> std::io::Result<usize> {
+        let mut deleted = 0;
+        if let Some(dir) = self.dir() {
+            match std::fs::read_dir(&dir) {
+                Ok(entries) => {
+                    for entry in entries {
+                        let path = entry?.path();
+                        if path.extension().is_some_and(|extension| extension == "cache") {
+                            std::fs::remove_file(&path)?;
+                            deleted += 1;
+                        }
+                    }
+                }
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+                Err(error) => return Err(error),
+            }
+        }
+        for counter in [
+            &self.hits,
+            &self.misses,
+            &self.invalid,
+            &self.bytes_read,
+            &self.bytes_written,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+        Ok(deleted)
+    }
+
+    fn file(&self, path: &str) -> Option<PathBuf> {
+        let dir = self.dir()?;
+        let name = fnv1a(FNV_OFFSET, normalize_path(path).as_bytes());
+        Some(dir.join(format!("{:016x}.cache", name)))
+    }
+
+    fn read_texture(&self, file: &Path, key: u64) -> Option<TextureData> {
+        let bytes = std::fs::read(file).ok()?;
+        match decode_texture(&bytes) {
+            Ok((file_key, _)) if file_key != key => None, // Stale, overwritten by the next store
+            Ok((_, data)) => {
+                self.bytes_read.fetch_add(bytes.len() as u64, Ordering::Relaxed);
+                Some(data)
+            }
+            Err(error) => {
+                log::warn!("Deleting broken cache file {:?}: {}", file, error);
+                self.invalid.fetch_add(1, Ordering::Relaxed);
+                let _ = std::fs::remove_file(file);
+                None
+            }
+        }
+    }
+}
+
+/// Cache key of a parse of `path`: the source's modification time, the import settings,
+/// CACHE_VERSION and `parameters` (anything else the parser is given, e.g. a face size).
+/// Stable across runs and builds.
+pub fn cache_key(
+    path: &str,
+    modified: SystemTime,
+    settings: &ImportSettings,
+    parameters: &str,
+) -> u64 {
+    let modified = modified
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_nanos())
+        .unwrap_or(0);
+    let settings = serde_json::to_string(settings).expect("import settings serialize");
+
+    let mut hash = fnv1a(FNV_OFFSET, &CACHE_VERSION.to_le_bytes());
+    hash = fnv1a(hash, normalize_path(path).as_bytes());
+    hash = fnv1a(hash, &modified.to_le_bytes());
+    hash = fnv1a(hash, settings.as_bytes());
+    fnv1a(hash, parameters.as_bytes())
+}
+
+// FNV-1a: unlike std's DefaultHasher, the same on every build
+pub(crate) const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+pub(crate) fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+// Written next to the target and renamed over it, so a crash mid-write can't leave
+// a truncated file with a valid header
+fn write_atomically(file: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let temporary = file.with_extension("tmp");
+    std::fs::File::create(&temporary)?.write_all(bytes)?;
+    std::fs::rename(&temporary, file)
+}
+
+fn encode_texture(key: u64, data: &TextureData) -> Vec<u8> {
+    let pixel_bytes = match &data.pixels {
+        TextureType::LDR(pixels) => pixels.len(),
+        TextureType::HDR(pixels) => pixels.len() * 4,
+    };
+    let mut out = Vec::with_capacity(48 + data.name.len() + pixel_bytes);
+    out.extend_from_slice(CACHE_MAGIC);
+    out.extend_from_slice(&CACHE_VERSION.to_le_bytes());
+    out.extend_from_slice(&key.to_le_bytes());
+    out.extend_from_slice(&(data.name.len() as u32).to_le_bytes());
+    out.extend_from_slice(data.name.as_bytes());
+    out.extend_from_slice(&data.width.to_le_bytes());
+    out.extend_from_slice(&data.height.to_le_bytes());
+    out.push(match data.format {
+        TextureFormat::Rgba8Unorm => 0,
+        TextureFormat::Rgba8UnormSrgb => 1,
+        TextureFormat::Rgba32Float => 2,
+        TextureFormat::Gray8 => 3,
+    });
+    out.push(data.mips as u8);
+    match &data.pixels {
+        TextureType::LDR(pixels) => {
+            out.push(0);
+            out.extend_from_slice(&(pixels.len() as u64).to_le_bytes());
+            out.extend_from_slice(pixels);
+        }
+        TextureType::HDR(pixels) => {
+            out.push(1);
+            out.extend_from_slice(&(pixels.len() as u64).to_le_bytes());
+            for value in pixels {
+                out.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+    }
+    out
+}
+
+fn decode_texture(mut bytes: &[u8]) -> Result<(u64, TextureData), String> {
+    let reader = &mut bytes;
+    let mut magic = [0u8; 4];
+    read_exact(reader, &mut magic)?;
+    if &magic != CACHE_MAGIC {
+        return Err("bad magic".to_string());
+    }
+    let version = read_u32(reader)?;
+    if version != CACHE_VERSION {
+        return Err(format!("version {}, expected {}", version, CACHE_VERSION));
+    }
+    let key = read_u64(reader)?;
+
+    let name_len = read_u32(reader)? as usize;
+    let name = String::from_utf8(read_vec(reader, name_len)?).map_err(|_| "non utf8 name")?;
+    let width = read_u32(reader)?;
+    let height = read_u32(reader)?;
+    let format = match read_u8(reader)? {
+        0 => TextureFormat::Rgba8Unorm,
+        1 => TextureFormat::Rgba8UnormSrgb,
+        2 => TextureFormat::Rgba32Float,
+        3 => TextureFormat::Gray8,
+        other => return Err(format!("unknown format {}", other)),
+    };
+    let mips = read_u8(reader)? != 0;
+    let pixel_type = read_u8(reader)?;
+    let count = read_u64(reader)? as usize;
+    let pixels = match pixel_type {
+        0 => TextureType::LDR(read_vec(reader, count)?),
+        1 => {
+            let bytes = read_vec(reader, count.checked_mul(4).ok_or("pixel count overflow")?)?;
+            TextureType::HDR(
+                bytes
+                    .chunks_exact(4)
+                    .map(|value| f32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+                    .collect(),
+            )
+        }
+        other => return Err(format!("unknown pixel type {}", other)),
+    };
+    if !reader.is_empty() {
+        return Err("trailing bytes".to_string());
+    }
+
+    let data = TextureData {
+        name,
+        pixels,
+        width,
+        height,
+        format,
+        mips,
+    };
+    // A file that decodes but doesn't add up is as broken as one that doesn't decode
+    data.validate().map_err(|error| error.to_string())?;
+    Ok((key, data))
+}
+
+fn read_exact(reader: &mut &[u8], buf: &mut [u8]) -> Result<(), String> {
+    reader.read_exact(buf).map_err(|_| "truncated".to_string())
+}
+
+fn read_vec(reader: &mut &[u8], len: usize) -> Result<Vec<u8>, String> {
+    // Checked before allocating, a corrupt length could be anything
+    if len > reader.len() {
+        return Err("truncated".to_string());
+    }
+    let (head, tail) = reader.split_at(len);
+    *reader = tail;
+    Ok(head.to_vec())
+}
+
+fn read_u8(reader: &mut &[u8]) -> Result<u8, String> {
+    let mut buf = [0u8; 1];
+    read_exact(reader, &mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u32(reader: &mut &[u8]) -> Result<u32, String> {
+    let mut buf = [0u8; 4];
+    read_exact(reader, &mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(reader: &mut &[u8]) -> Result<u64, String> {
+    let mut buf = [0u8; 8];
+    read_exact(reader, &mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    const PATH: &str = "textures/brick.png";
+
+    fn texture() -> TextureData {
+        TextureData {
+            name: PATH.to_string(),
+            width: 2,
+            height: 1,
+            pixels: TextureType::LDR(vec![255, 0, 0, 255, 0, 0, 255, 255]),
+            format: TextureFormat::Rgba8UnormSrgb,
+            mips: true,
+        }
+    }
+
+    fn key() -> u64 {
+        let modified = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        cache_key(PATH, modified, &ImportSettings::default(), "texture")
+    }
+
+    // An empty cache in its own temporary folder, since tests run in parallel
+    fn temp_cache(name: &str) -> (AssetCache, PathBuf) {
+        let dir = std::env::temp_dir().join(format!(
+            "catalyst_asset_cache_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let cache = AssetCache::new(Some(dir.clone()));
+        let _ = cache.clear();
+        (cache, dir)
+    }
+
+    fn cache_files(dir: &Path) -> Vec<PathBuf> {
+        std::fs::read_dir(dir)
+            .map(|entries| entries.flatten().map(|entry| entry.path()).collect())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn keys_follow_the_source_time_and_the_settings() {
+        let modified = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let settings = ImportSettings::default();
+        let key = cache_key(PATH, modified, &settings, "texture");
+        assert_eq!(key, cache_key(PATH, modified, &settings, "texture"));
+
+        let touched = modified + Duration::from_secs(1);
+        assert_ne!(key, cache_key(PATH, touched, &settings, "texture"));
+
+        let mut changed = ImportSettings::default();
+        changed.texture.mips = !changed.texture.mips;
+        assert_ne!(key, cache_key(PATH, modified, &changed, "texture"));
+    }
+
+    #[test]
+    fn a_stored_texture_reads_back_the_same() {
+        let (cache, dir) = temp_cache("round_trip");
+        assert!(cache.load_texture(PATH, key()).is_none());
+
+        cache.store_texture(PATH, key(), &texture());
+        let data = cache
+            .load_texture(PATH, key())
+            .expect("stored texture missed");
+        let expected = texture();
+        match (&data.pixels, &expected.pixels) {
+            (TextureType::LDR(a), TextureType::LDR(b)) => assert_eq!(a, b),
+            _ => panic!("pixels changed type"),
+        }
+        assert_eq!(data.name, expected.name);
+        assert_eq!((data.width, data.height), (expected.width, expected.height));
+        assert_eq!(
+            format!("{:?}", data.format),
+            format!("{:?}", expected.format)
+        );
+        assert_eq!(data.mips, expected.mips);
+
+        // A stale key misses
+        assert!(cache.load_texture(PATH, key() ^ 1).is_none());
+        assert_eq!(cache_files(&dir).len(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn a_truncated_file_is_deleted_and_counted_as_invalid() {
+        let (cache, dir) = temp_cache("truncated");
+        cache.store_texture(PATH, key(), &texture());
+        let file = cache_files(&dir).pop().expect("nothing stored");
+        let bytes = std::fs::read(&file).unwrap();
+        std::fs::write(&file, &bytes[..bytes.len() / 2]).unwrap();
+
+        assert!(cache.load_texture(PATH, key()).is_none());
+        assert!(!file.exists());
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.invalid), (0, 1, 1));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn clear_empties_the_folder_and_resets_the_counters() {
+        let (cache, dir) = temp_cache("clear");
+        cache.store_texture(PATH, key(), &texture());
+        assert!(cache.load_texture(PATH, key()).is_some());
+        assert_eq!(cache.stats().hits, 1);
+
+        assert_eq!(cache.clear().unwrap(), 1);
+        assert!(cache_files(&dir).is_empty());
+        assert_eq!(cache.stats().hits, 0);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,436 @@
+//! Cleanup of asset entities nothing uses anymore.
+//!
+//! Deleting a scene asset entity deletes the textures, materials and meshes it loaded
+//! (the entities with `(Source, scene)`), except those another asset still uses, e.g. a
+//! texture picked by a material made at runtime. They go through the DespawnQueue, so a
+//! large scene doesn't make a frame spike, and their OnRemove observers free the GPU
+//! resources like for any deleted asset. Spawned instances of the scene should be gone
+//! first: their meshes and materials go with the scene.
+//!
+//! Every `AssetGc::check_interval` seconds, AssetLookup is also checked for entries whose
+//! entity was deleted some other way, or holds another type of asset than its AssetType
+//! pair says; those are dropped and logged.
+
+use std::collections::HashSet;
+
+use catalyst_core::{
+    bulk::{DespawnGroups, DespawnQueue},
+    console::ConsoleCommands,
+    time::Time,
+};
+use flecs_ecs::prelude::*;
+
+use crate::{
+    asset_events::{AssetLookup, AssetType, LutAsset, MaterialAsset, MeshAsset, TextureAsset},
+    assets::MeshData,
+    lut::LutData,
+    material::{MaterialData, TextureData},
+    reload::AssetDependencies,
+};
+
+/// The asset garbage collection counters, in the singleton of the same name, shown in
+/// the Textures debug window and by the `assets.gc` console command.
+#[derive(Component, Clone, Debug)]
+pub struct AssetGc {
+    /// Seconds between two checks of AssetLookup; 0 checks every frame.
+    pub check_interval: f32,
+    /// Asset entities deleted with the last scene using them, since startup.
+    pub orphans_collected: usize,
+    /// AssetLookup entries dropped by the checks, since startup.
+    pub dangling_pruned: usize,
+    since_check: f32,
+}
+
+impl Default for AssetGc {
+    fn default() -> Self {
+        Self {
+            check_interval: 10.0,
+            orphans_collected: 0,
+            dangling_pruned: 0,
+            since_check: 0.0,
+        }
+    }
+}
+
+impl AssetGc {
+    // True once `check_interval` seconds went by since the last time it was
+    fn check_due(&mut self, dt: f32) -> bool {
+        self.since_check += dt;
+        if self.since_check < self.check_interval {
+            return false;
+        }
+        self.since_check = 0.0;
+        true
+    }
+}
+
+/// Drops the AssetLookup entries whose entity was deleted, or holds asset data without
+/// the matching AssetType pair, and returns how many. Entities without data yet are
+/// kept: they are assets still loading, or only referenced so far.
+pub fn check_asset_lookup(world: &World, lookup: &mut AssetLookup) -> usize {
+    let before = lookup.map.len();
+    lookup.map.retain(|id, &mut entity| {
+        let view = world.entity_from_id(entity);
+        let problem = if !view.is_alive() {
+            "was deleted"
+        } else if !has_expected_type(view) {
+            "doesn't have the AssetType of its data"
+        } else {
+            return true;
+        };
+        log::warn!(
+            "Asset {} ({:?}) {}, dropped from AssetLookup",
+            id,
+            entity,
+            problem
+        );
+        false
+    });
+    before - lookup.map.len()
+}
+
+fn has_expected_type(entity: EntityView) -> bool {
+    if entity.has(TextureData::id()) {
+        entity.has((AssetType, TextureAsset))
+    } else if entity.has(MaterialData::id()) {
+        entity.has((AssetType, MaterialAsset))
+    } else if entity.has(MeshData::id()) {
+        entity.has((AssetType, MeshAsset))
+    } else if entity.has(LutData::id()) {
+        entity.has((AssetType, LutAsset))
+    } else {
+        true
+    }
+}
+
+/// Forgets a deleted scene and queues the deletion of the assets only it used, see the
+/// module docs.
+pub(crate) fn collect_scene_orphans(world: &World, scene: Entity) {
+    let orphans =
+        world.get::<&mut AssetDependencies>(|dependencies| dependencies.remove_orphans(scene));
+    if orphans.is_empty() {
+        return;
+    }
+
+    let collected: HashSet<Entity> = orphans.iter().copied().collect();
+    world.get::<&mut AssetLookup>(|lookup| {
+        lookup.map.retain(|_, entity| !collected.contains(entity));
+    });
+    let job = DespawnGroups::new(world, orphans);
+    world.get::<&mut DespawnQueue>(|queue| {
+        queue.push(job);
+    });
+    world.get::<&mut AssetGc>(|gc| gc.orphans_collected += collected.len());
+    log::debug!(
+        "Scene {:?} deleted, {} unused assets queued for deletion",
+        scene,
+        collected.len()
+    );
+}
+
+pub(crate) fn register_asset_gc(world: &World) {
+    world
+        .system_named::<(&mut AssetLookup, &mut AssetGc, &Time)>("Check Asset Lookup")
+        .kind(flecs::pipeline::OnUpdate)
+        .each_iter(|iter, _, (lookup, gc, time)| {
+            if gc.check_due(time.delta_seconds()) {
+                gc.dangling_pruned += check_asset_lookup(&iter.world(), lookup);
+            }
+        });
+}
+
+pub(crate) fn register_gc_commands(commands: &mut ConsoleCommands) {
+    commands.register(
+        "assets.gc",
+        "Check AssetLookup now and show the asset garbage collection counters",
+        |world, _| {
+            let pruned = world.get::<&mut AssetLookup>(|lookup| check_asset_lookup(world, lookup));
+            Ok(world.get::<&mut AssetGc>(|gc| {
+                gc.dangling_pruned += pruned;
+                format!(
+                    "Dropped {} entries; since startup: {} orphans collected, {} entries dropped",
+                    pruned, gc.orphans_collected, gc.dangling_pruned
+                )
+            }))
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Cursor,
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        time::Duration,
+    };
+
+    use catalyst_core::App;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        AssetPlugin, AssetSource, LoadScene, asset_server::AssetServer, primitives,
+        scene::SceneData,
+    };
+
+    const CYCLES: usize = 100;
+
+    fn pad(bytes: &mut Vec<u8>, with: u8) {
+        while bytes.len() % 4 != 0 {
+            bytes.push(with);
+        }
+    }
+
+    // One textured triangle
+    fn crate_glb() -> Vec<u8> {
+        let mut bin = Vec::new();
+        for value in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+            bin.extend_from_slice(&value.to_le_bytes());
+        }
+        for index in [0u32, 1, 2] {
+            bin.extend_from_slice(&index.to_le_bytes());
+        }
+        let image = image::RgbaImage::from_pixel(1, 1, image::Rgba([150, 110, 60, 255]));
+        let mut png = Vec::new();
+        image::DynamicImage::ImageRgba8(image)
+            .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+            .expect("png encodes");
+        let image_offset = bin.len();
+        bin.extend_from_slice(&png);
+        pad(&mut bin, 0);
+
+        let document = json!({
+            "asset": { "version": "2.0" },
+            "scene": 0,
+            "scenes": [{ "nodes": [0] }],
+            "nodes": [{ "name": "Crate", "mesh": 0 }],
+            "meshes": [{
+                "name": "Crate",
+                "primitives": [{ "attributes": { "POSITION": 0 }, "indices": 1, "material": 0 }],
+            }],
+            "materials": [{
+                "name": "Wood",
+                "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } },
+            }],
+            "textures": [{ "source": 0 }],
+            "images": [{ "name": "wood", "bufferView": 2, "mimeType": "image/png" }],
+            "accessors": [
+                { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                  "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0] },
+                { "bufferView": 1, "componentType": 5125, "count": 3, "type": "SCALAR" },
+            ],
+            "bufferViews": [
+                { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+                { "buffer": 0, "byteOffset": 36, "byteLength": 12 },
+                { "buffer": 0, "byteOffset": image_offset, "byteLength": png.len() },
+            ],
+            "buffers": [{ "byteLength": bin.len() }],
+        });
+
+        let mut json = serde_json::to_vec(&document).expect("json serializes");
+        pad(&mut json, b' ');
+
+        let length = 12 + 8 + json.len() + 8 + bin.len();
+        let mut glb = Vec::with_capacity(length);
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&(length as u32).to_le_bytes());
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"JSON");
+        glb.extend_from_slice(&json);
+        glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"BIN\0");
+        glb.extend_from_slice(&bin);
+        glb
+    }
+
+    // Runs frames until `done` or about two seconds went by; loads finish on the IO pool
+    fn update_until(app: &mut App, done: impl Fn(&App) -> bool) -> bool {
+        for _ in 0..200 {
+            app.update();
+            if done(app) {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    fn count<T: ComponentId>(world: &World) -> usize {
+        world.query::<()>().with(T::id()).build().count() as usize
+    }
+
+    // Asset entities of each type, AssetLookup entries and assets in AssetDependencies
+    fn counts(app: &App) -> [usize; 5] {
+        [
+            count::<TextureData>(&app.world),
+            count::<MaterialData>(&app.world),
+            count::<MeshData>(&app.world),
+            app.world.get::<&AssetLookup>(|lookup| lookup.map.len()),
+            app.world
+                .get::<&AssetDependencies>(|dependencies| dependencies.tracked()),
+        ]
+    }
+
+    fn load_scene(app: &mut App, path: &str) -> Option<Entity> {
+        let scene = app
+            .world
+            .entity()
+            .set(AssetSource {
+                path: path.to_string(),
+            })
+            .add(LoadScene)
+            .id();
+        let loaded = update_until(app, |app| {
+            app.world.entity_from_id(scene).has(SceneData::id())
+        });
+        loaded.then_some(scene)
+    }
+
+    // Deletes the scene and runs frames until the DespawnQueue applied the collection
+    fn unload_scene(app: &mut App, scene: Entity) -> bool {
+        app.world.entity_from_id(scene).destruct();
+        update_until(app, |app| {
+            app.world.get::<&DespawnQueue>(|queue| queue.is_empty())
+        })
+    }
+
+    // Writes the GLB to its own temporary file, since tests run in parallel
+    fn temp_glb(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!(
+            "catalyst_asset_gc_{}_{}.glb",
+            name,
+            std::process::id()
+        ));
+        std::fs::write(&path, crate_glb()).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    fn gc_app() -> App {
+        let mut app = App::new();
+        app.add_plugin(AssetPlugin);
+        // Every frame, so healthy entries are seen to be left alone too
+        app.world.get::<&mut AssetGc>(|gc| gc.check_interval = 0.0);
+        app
+    }
+
+    #[test]
+    fn loading_and_unloading_a_scene_leaves_nothing_behind() {
+        let path = temp_glb("cycles");
+        let mut app = gc_app();
+        let removed_textures = Arc::new(AtomicUsize::new(0));
+        let removed = removed_textures.clone();
+        app.world
+            .observer::<flecs::OnRemove, &TextureData>()
+            .each(move |_| {
+                removed.fetch_add(1, Ordering::Relaxed);
+            });
+
+        let before = counts(&app);
+        let mut loaded_counts = Vec::new();
+        for cycle in 0..CYCLES {
+            let scene = load_scene(&mut app, &path)
+                .unwrap_or_else(|| panic!("the scene loads, cycle {}", cycle));
+            loaded_counts.push(counts(&app));
+            assert!(unload_scene(&mut app, scene), "cycle {}", cycle);
+            // The entity counts, lookup and dependencies are back where they started
+            assert_eq!(counts(&app), before, "cycle {}", cycle);
+        }
+        let _ = std::fs::remove_file(&path);
+
+        // Every load makes the same entities
+        assert_eq!(loaded_counts[0][..3], [1, 1, 1]);
+        assert!(loaded_counts.windows(2).all(|pair| pair[0] == pair[1]));
+        // The OnRemove observers run for the collected textures
+        assert_eq!(removed_textures.load(Ordering::Relaxed), CYCLES);
+        let (collected, pruned) = app
+            .world
+            .get::<&AssetGc>(|gc| (gc.orphans_collected, gc.dangling_pruned));
+        // Each unload collects the mesh, material and texture
+        assert_eq!(collected, CYCLES * 3);
+        assert_eq!(pruned, 0);
+    }
+
+    #[test]
+    fn a_texture_another_material_uses_outlives_its_scene() {
+        let path = temp_glb("shared");
+        let mut app = gc_app();
+        let scene = load_scene(&mut app, &path).expect("the scene loads");
+        let _ = std::fs::remove_file(&path);
+
+        let texture = app
+            .world
+            .entity_from_id(scene)
+            .get::<&SceneData>(|data| data.textures[0].clone());
+        let painted = app.world.get::<&AssetServer>(|server| {
+            server.create_material("painted", |m| m.diffuse_texture(texture.clone()))
+        });
+        let material = app
+            .world
+            .get::<&mut AssetLookup>(|lookup| lookup.entity(painted.id, &app.world));
+        assert!(update_until(&mut app, |app| {
+            app.world.entity_from_id(material).has(MaterialData::id())
+        }));
+        assert!(unload_scene(&mut app, scene));
+
+        let texture_entity = app
+            .world
+            .get::<&AssetLookup>(|lookup| lookup.map.get(&texture.id).copied())
+            .expect("the texture is still looked up");
+        assert!(
+            app.world
+                .entity_from_id(texture_entity)
+                .has(TextureData::id())
+        );
+        // The scene's own mesh and material are collected
+        assert_eq!(counts(&app)[..3], [1, 1, 0]);
+    }
+
+    #[test]
+    fn the_integrity_pass_drops_broken_entries_and_keeps_healthy_ones() {
+        let path = temp_glb("integrity");
+        let mut app = gc_app();
+        let scene = load_scene(&mut app, &path).expect("the scene loads");
+        let _ = std::fs::remove_file(&path);
+        let texture = app
+            .world
+            .entity_from_id(scene)
+            .get::<&SceneData>(|data| data.textures[0].clone());
+        let painted = app
+            .world
+            .get::<&AssetServer>(|server| server.create_material("painted", |m| m));
+        let material = app
+            .world
+            .get::<&mut AssetLookup>(|lookup| lookup.entity(painted.id, &app.world));
+        let mesh = app
+            .world
+            .get::<&AssetServer>(|server| server.add_mesh("cube", primitives::cube(1.0)));
+        let mesh_entity = app
+            .world
+            .get::<&mut AssetLookup>(|lookup| lookup.entity(mesh.id, &app.world));
+        assert!(update_until(&mut app, |app| {
+            app.world.entity_from_id(mesh_entity).has(MeshData::id())
+                && app.world.entity_from_id(material).has(MaterialData::id())
+        }));
+
+        // A texture deleted elsewhere, and a mesh tagged as a texture
+        let texture_entity = app
+            .world
+            .get::<&AssetLookup>(|lookup| lookup.map[&texture.id]);
+        app.world.entity_from_id(texture_entity).destruct();
+        app.world
+            .entity_from_id(mesh_entity)
+            .add((AssetType, TextureAsset));
+        app.update();
+
+        let pruned = app.world.get::<&AssetGc>(|gc| gc.dangling_pruned);
+        assert_eq!(pruned, 2);
+        let kept = app.world.get::<&AssetLookup>(|lookup| {
+            [texture.id, mesh.id, painted.id].map(|id| lookup.map.contains_key(&id))
+        });
+        assert_eq!(kept, [false, false, true]);
+    }
+}
@@ -0,0 +1,338 @@
+use image::{DynamicImage, imageops::FilterType};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    io::{AssetIoError, AssetMounts, AssetReader},
+    lod::GenerateLods,
+    material::TextureFormat,
+};
+
+/// Per-asset import options. A loader looks for them in a sidecar file named after the
+/// source plus `.meta` (`textures/brick.png.meta`), holding RON like
+/// `(texture: (color_space: linear, max_size: Some(512)))`; missing fields and missing
+/// files keep the defaults. Settings passed to the load call replace the file.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImportSettings {
+    /// For a texture, or every image of a scene.
+    pub texture: TextureImportSettings,
+    /// For every mesh of a scene.
+    pub mesh: MeshImportSettings,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorSpace {
+    /// Decided by the use: sRGB for glTF base color maps, linear for everything else.
+    #[default]
+    Auto,
+    Srgb,
+    Linear,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextureCompression {
+    #[default]
+    None,
+    /// BC block compression, on adapters with `TEXTURE_COMPRESSION_BC`. Only kept in the
+    /// settings (and so the cache key) for now: the renderer has no BC encoder yet and
+    /// uploads every texture uncompressed.
+    Bc,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextureImportSettings {
+    pub color_space: ColorSpace,
+    /// Build a mip chain on upload (which also lets the texture stream). Off for UI
+    /// images and lookup tables that are always drawn at their size.
+    pub mips: bool,
+    /// Larger images are scaled down on import, keeping their aspect ratio.
+    pub max_size: Option<u32>,
+    pub compression: TextureCompression,
+}
+
+impl Default for TextureImportSettings {
+    fn default() -> Self {
+        Self {
+            color_space: ColorSpace::Auto,
+            mips: true,
+            max_size: None,
+            compression: TextureCompression::None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MeshImportSettings {
+    /// Applied to the vertex positions, e.g. 0.01 for a scene modelled in centimeters.
+    /// LODs are cached by the scaled mesh, so changing it regenerates them.
+    pub scale: f32,
+    /// LOD chain generated for every mesh. A GenerateLods on the scene entity wins.
+    pub lods: Option<GenerateLods>,
+    /// Fill `MeshData::tangents` from the UVs. Off by default: the renderer's normal
+    /// mapping builds its own frame in the shader.
+    pub generate_tangents: bool,
+    /// Read the physics extras of the nodes. Off imports the scene as visuals only.
+    pub physics: bool,
+}
+
+impl Default for MeshImportSettings {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            lods: None,
+            generate_tangents: false,
+            physics: true,
+        }
+    }
+}
+
+impl TextureImportSettings {
+    /// `format` unless the color space is forced.
+    pub fn format(&self, format: TextureFormat) -> TextureFormat {
+        match (self.color_space, format) {
+            (ColorSpace::Srgb, TextureFormat::Rgba8Unorm) => TextureFormat::Rgba8UnormSrgb,
+            (ColorSpace::Linear, TextureFormat::Rgba8UnormSrgb) => TextureFormat::Rgba8Unorm,
+            _ => format,
+        }
+    }
+
+    /// Scales `image` down to `max_size` if it is larger.
+    pub fn fit(&self, image: DynamicImage) -> DynamicImage {
+        match self.max_size {
+            Some(max_size) if image.width().max(image.height()) > max_size.max(1) => {
+                image.resize(max_size.max(1), max_size.max(1), FilterType::Triangle)
+            }
+            _ => image,
+        }
+    }
+}
+
+/// Path of the import settings of `path`.
+pub fn meta_path(path: &str) -> String {
+    format!("{}.meta", path)
+}
+
+impl ImportSettings {
+    /// The settings in the `.meta` file next to `path`, or the defaults. A file that
+    /// doesn't parse is reported and ignored.
+    pub fn read(path: &str, reader: &dyn AssetReader) -> Self {
+        let meta_path = meta_path(path);
+        match reader.read(&meta_path) {
+            Ok(bytes) => ron::de::from_bytes(&bytes).unwrap_or_else(|error| {
+                log::warn!("Ignoring import settings '{}': {}", meta_path, error);
+                Self::default()
+            }),
+            Err(AssetIoError::NotFound(_)) => Self::default(),
+            Err(error) => {
+                log::warn!("Ignoring import settings '{}': {}", meta_path, error);
+                Self::default()
+            }
+        }
+    }
+
+    /// `overrides` if given, else the settings file of `path`.
+    pub fn resolve(path: &str, reader: &dyn AssetReader, overrides: Option<&Self>) -> Self {
+        match overrides {
+            Some(settings) => settings.clone(),
+            None => Self::read(path, reader),
+        }
+    }
+
+    /// Writes the settings next to `path` as loose files, unless the file already holds
+    /// exactly them, so every option shows up for editing.
+    pub fn write(&self, path: &str, mounts: &AssetMounts) -> Result<(), AssetIoError> {
+        let meta_path = meta_path(path);
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .expect("import settings serialize");
+        if mounts
+            .read(&meta_path)
+            .is_ok_and(|bytes| bytes == text.as_bytes())
+        {
+            return Ok(());
+        }
+        mounts.write(&meta_path, text.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::PathBuf,
+        time::{Duration, SystemTime},
+    };
+
+    use super::*;
+    use crate::io::FileAssetReader;
+
+    const PATH: &str = "textures/brick.png";
+
+    // Loose files in their own folder, since tests run in parallel
+    fn temp_mounts(name: &str) -> (PathBuf, AssetMounts) {
+        let dir = std::env::temp_dir().join(format!(
+            "catalyst_import_settings_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let mounts = AssetMounts::new(Some(FileAssetReader::new(&dir)));
+        (dir, mounts)
+    }
+
+    #[test]
+    fn missing_or_broken_files_give_the_defaults() {
+        let (dir, mounts) = temp_mounts("missing");
+
+        let settings = ImportSettings::read(PATH, &mounts);
+        assert_eq!(settings.texture.color_space, ColorSpace::Auto);
+        assert!(settings.texture.mips);
+        assert_eq!(settings.texture.max_size, None);
+        assert_eq!(settings.texture.compression, TextureCompression::None);
+        assert_eq!(settings.mesh.scale, 1.0);
+        assert!(settings.mesh.lods.is_none());
+        assert!(!settings.mesh.generate_tangents);
+        assert!(settings.mesh.physics);
+
+        mounts
+            .write(&meta_path(PATH), b"(texture: (mips: maybe))")
+            .unwrap();
+        let settings = ImportSettings::read(PATH, &mounts);
+        assert!(settings.texture.mips);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn partial_files_keep_the_defaults_of_missing_fields() {
+        let (dir, mounts) = temp_mounts("partial");
+        mounts
+            .write(
+                &meta_path(PATH),
+                b"(texture: (color_space: linear, max_size: Some(512)), mesh: (generate_tangents: true))",
+            )
+            .unwrap();
+
+        let settings = ImportSettings::read(PATH, &mounts);
+        assert_eq!(settings.texture.color_space, ColorSpace::Linear);
+        assert_eq!(settings.texture.max_size, Some(512));
+        assert!(settings.texture.mips);
+        assert_eq!(settings.texture.compression, TextureCompression::None);
+        assert!(settings.mesh.generate_tangents);
+        assert_eq!(settings.mesh.scale, 1.0);
+        assert!(settings.mesh.physics);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn overrides_win_over_the_file() {
+        let (dir, mounts) = temp_mounts("overrides");
+        mounts
+            .write(
+                &meta_path(PATH),
+                b"(texture: (compression: bc, mips: false))",
+            )
+            .unwrap();
+
+        let from_file = ImportSettings::resolve(PATH, &mounts, None);
+        assert_eq!(from_file.texture.compression, TextureCompression::Bc);
+        assert!(!from_file.texture.mips);
+
+        // The whole override, not merged with the file
+        let mut overrides = ImportSettings::default();
+        overrides.texture.max_size = Some(64);
+        let resolved = ImportSettings::resolve(PATH, &mounts, Some(&overrides));
+        assert_eq!(resolved.texture.max_size, Some(64));
+        assert_eq!(resolved.texture.compression, TextureCompression::None);
+        assert!(resolved.texture.mips);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn format_follows_a_forced_color_space() {
+        let mut settings = TextureImportSettings::default();
+        for format in [TextureFormat::Rgba8Unorm, TextureFormat::Rgba8UnormSrgb] {
+            assert_eq!(settings.format(format), format);
+        }
+
+        settings.color_space = ColorSpace::Srgb;
+        assert_eq!(
+            settings.format(TextureFormat::Rgba8Unorm),
+            TextureFormat::Rgba8UnormSrgb
+        );
+        assert_eq!(
+            settings.format(TextureFormat::Rgba8UnormSrgb),
+            TextureFormat::Rgba8UnormSrgb
+        );
+
+        settings.color_space = ColorSpace::Linear;
+        assert_eq!(
+            settings.format(TextureFormat::Rgba8UnormSrgb),
+            TextureFormat::Rgba8Unorm
+        );
+        // Only 8-bit color has both
+        assert_eq!(settings.format(TextureFormat::Gray8), TextureFormat::Gray8);
+        assert_eq!(
+            settings.format(TextureFormat::Rgba32Float),
+            TextureFormat::Rgba32Float
+        );
+    }
+
+    #[test]
+    fn fit_scales_large_images_down_keeping_the_aspect_ratio() {
+        let image = || DynamicImage::new_rgba8(1024, 512);
+        let mut settings = TextureImportSettings::default();
+
+        let fitted = settings.fit(image());
+        assert_eq!((fitted.width(), fitted.height()), (1024, 512));
+
+        settings.max_size = Some(256);
+        let fitted = settings.fit(image());
+        assert_eq!((fitted.width(), fitted.height()), (256, 128));
+
+        settings.max_size = Some(2048);
+        let fitted = settings.fit(image());
+        assert_eq!((fitted.width(), fitted.height()), (1024, 512));
+    }
+
+    #[test]
+    fn write_skips_an_identical_file() {
+        let (dir, mounts) = temp_mounts("write");
+        let file = dir.join(meta_path(PATH));
+        let mut settings = ImportSettings::default();
+        settings.mesh.scale = 0.01;
+
+        settings.write(PATH, &mounts).unwrap();
+        assert_eq!(ImportSettings::read(PATH, &mounts).mesh.scale, 0.01);
+
+        // Backdated, so a rewrite would show in the modification time
+        let backdated = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let set_modified = |time| {
+            std::fs::File::options()
+                .write(true)
+                .open(&file)
+                .unwrap()
+                .set_modified(time)
+                .unwrap()
+        };
+        set_modified(backdated);
+        let modified = || std::fs::metadata(&file).unwrap().modified().unwrap();
+
+        settings.write(PATH, &mounts).unwrap();
+        assert_eq!(modified(), backdated);
+
+        settings.texture.compression = TextureCompression::Bc;
+        settings.write(PATH, &mounts).unwrap();
+        assert_ne!(modified(), backdated);
+        let written = ImportSettings::read(PATH, &mounts);
+        assert_eq!(written.texture.compression, TextureCompression::Bc);
+        assert_eq!(written.mesh.scale, 0.01);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,275 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::RwLock,
+    time::SystemTime,
+};
+
+use thiserror::Error;
+
+pub mod pack;
+
+pub use pack::PackAssetReader;
+
+#[derive(Error, Debug)]
+pub enum AssetIoError {
+    #[error("asset not found: {0}")]
+    NotFound(String),
+    #[error("io error while reading '{path}': {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("invalid pack file '{0}': {1}")]
+    InvalidPack(String, String),
+    #[error("can't write '{0}': no loose files are mounted")]
+    ReadOnly(String),
+}
+
+/// Somewhere asset bytes can come from (a directory, a pack file, ...).
+/// All loaders go through this instead of touching std::fs directly.
+pub trait AssetReader: Send + Sync {
+    fn read(&self, path: &str) -> Result<Vec<u8>, AssetIoError>;
+
+    fn exists(&self, path: &str) -> bool;
+
+    /// When the asset last changed, for the asset cache. None where that isn't known
+    /// (packs), which keeps the asset out of the cache.
+    fn modified(&self, _path: &str) -> Option<SystemTime> {
+        None
+    }
+}
+
+/// Loose files on disk, resolved relative to `root`.
+pub struct FileAssetReader {
+    root: PathBuf,
+}
+
+impl FileAssetReader {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl AssetReader for FileAssetReader {
+    fn read(&self, path: &str) -> Result<Vec<u8>, AssetIoError> {
+        let full_path = self.root.join(path);
+        std::fs::read(&full_path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => AssetIoError::NotFound(path.to_string()),
+            _ => AssetIoError::Io {
+                path: path.to_string(),
+                source: e,
+            },
+        })
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.root.join(path).is_file()
+    }
+
+    fn modified(&self, path: &str) -> Option<SystemTime> {
+        std::fs::metadata(self.root.join(path)).ok()?.modified().ok()
+    }
+}
+
+impl FileAssetReader {
+    /// Writes a file under `root`, creating its folder. Only tools and import settings
+    /// write assets; loaders never do.
+    pub fn write(&self, path: &str, bytes: &[u8]) -> Result<(), AssetIoError> {
+        let full_path = self.root.join(path);
+        let io_error = |source| AssetIoError::Io {
+            path: path.to_string(),
+            source,
+        };
+        if let Some(dir) = full_path.parent() {
+            std::fs::create_dir_all(dir).map_err(io_error)?;
+        }
+        std::fs::write(&full_path, bytes).map_err(io_error)
+    }
+}
+
+/// The set of mounted sources an AssetServer reads from.
+///
+/// Lookup order:
+/// 1. Loose files (so a file next to the executable can patch/mod a packed asset)
+/// 2. Packs, most recently mounted first (so a patch pack overrides the base pack)
+pub struct AssetMounts {
+    loose: Option<FileAssetReader>,
+    packs: RwLock<Vec<PackAssetReader>>,
+}
+
+impl Default for AssetMounts {
+    fn default() -> Self {
+        // Same behavior as before packs existed: paths are relative to the working directory
+        Self::new(Some(FileAssetReader::new(".")))
+    }
+}
+
+impl AssetMounts {
+    pub fn new(loose: Option<FileAssetReader>) -> Self {
+        Self {
+            loose,
+            packs: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Opens a pack file and mounts it above all previously mounted packs.
+    pub fn mount_pack(&self, pack_path: impl AsRef<Path>) -> Result<(), AssetIoError> {
+        let pack = PackAssetReader::open(pack_path)?;
+        self.packs.write().unwrap().push(pack);
+        Ok(())
+    }
+
+    /// Writes a loose file; packs are read-only.
+    pub fn write(&self, path: &str, bytes: &[u8]) -> Result<(), AssetIoError> {
+        let path = normalize_path(path);
+        match &self.loose {
+            Some(loose) => loose.write(&path, bytes),
+            None => Err(AssetIoError::ReadOnly(path)),
+        }
+    }
+}
+
+impl AssetReader for AssetMounts {
+    fn read(&self, path: &str) -> Result<Vec<u8>, AssetIoError> {
+        let path = normalize_path(path);
+
+        if let Some(loose) = &self.loose {
+            match loose.read(&path) {
+                Err(AssetIoError::NotFound(_)) => {}
+                result => return result,
+            }
+        }
+
+        for pack in self.packs.read().unwrap().iter().rev() {
+            if pack.exists(&path) {
+                return pack.read(&path);
+            }
+        }
+
+        Err(AssetIoError::NotFound(path))
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        let path = normalize_path(path);
+
+        self.loose.as_ref().is_some_and(|l| l.exists(&path))
+            || self.packs.read().unwrap().iter().any(|p| p.exists(&path))
+    }
+
+    // Only loose files have one; packed assets are already what ships
+    fn modified(&self, path: &str) -> Option<SystemTime> {
+        let path = normalize_path(path);
+        self.loose.as_ref().and_then(|l| l.modified(&path))
+    }
+}
+
+/// Pack entries are stored with forward slashes and without a leading "./",
+/// so "./assets\\box.glb" and "assets/box.glb" resolve to the same entry.
+pub fn normalize_path(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut path = path.as_str();
+    while let Some(stripped) = path.strip_prefix("./") {
+        path = stripped;
+    }
+    path.to_string()
+}
+
+/// Resolves a URI referenced from inside an asset (e.g. a glTF buffer) against the asset's folder.
+pub fn resolve_relative(base_path: &str, uri: &str) -> String {
+    let base = Path::new(base_path).parent().unwrap_or(Path::new(""));
+    normalize_path(&base.join(uri).to_string_lossy())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::pack::write_pack;
+
+    // Packs built from the (path, bytes) lists, in their own folder since tests run in parallel
+    fn temp_packs(name: &str, packs: &[&[(&str, &[u8])]]) -> (PathBuf, Vec<PathBuf>) {
+        let dir = std::env::temp_dir().join(format!(
+            "catalyst_asset_mounts_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let paths = packs
+            .iter()
+            .enumerate()
+            .map(|(i, files)| {
+                let source = dir.join(format!("source_{}", i));
+                for (path, bytes) in *files {
+                    let path = source.join(path);
+                    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                    std::fs::write(path, bytes).unwrap();
+                }
+                let pack = dir.join(format!("{}.cpak", i));
+                write_pack(&source, "assets", &pack).unwrap();
+                pack
+            })
+            .collect();
+        (dir, paths)
+    }
+
+    #[test]
+    fn loose_files_override_packed_ones() {
+        let (dir, packs) = temp_packs(
+            "loose",
+            &[&[("box.glb", b"packed box"), ("wood.png", b"packed wood")]],
+        );
+        let loose = dir.join("loose");
+        std::fs::create_dir_all(loose.join("assets")).unwrap();
+        std::fs::write(loose.join("assets/box.glb"), b"loose box").unwrap();
+
+        let mounts = AssetMounts::new(Some(FileAssetReader::new(&loose)));
+        mounts.mount_pack(&packs[0]).unwrap();
+
+        assert_eq!(mounts.read("assets/box.glb").unwrap(), b"loose box");
+        assert!(mounts.modified("assets/box.glb").is_some());
+        assert_eq!(mounts.read("./assets/wood.png").unwrap(), b"packed wood");
+        assert!(mounts.modified("assets/wood.png").is_none());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn later_packs_override_earlier_ones() {
+        let (dir, packs) = temp_packs(
+            "patch",
+            &[
+                &[("box.glb", b"base box"), ("wood.png", b"base wood")],
+                &[("box.glb", b"patched box")],
+            ],
+        );
+        let mounts = AssetMounts::new(None);
+        for pack in &packs {
+            mounts.mount_pack(pack).unwrap();
+        }
+
+        assert_eq!(mounts.read("assets/box.glb").unwrap(), b"patched box");
+        assert_eq!(mounts.read("assets/wood.png").unwrap(), b"base wood");
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn missing_assets_are_not_found_and_packs_are_read_only() {
+        let (dir, packs) = temp_packs("missing", &[&[("box.glb", b"box")]]);
+        let mounts = AssetMounts::new(None);
+        mounts.mount_pack(&packs[0]).unwrap();
+
+        assert!(!mounts.exists("assets/nope.glb"));
+        assert!(matches!(
+            mounts.read("assets/nope.glb"),
+            Err(AssetIoError::NotFound(_))
+        ));
+        assert!(matches!(
+            mounts.write("assets/box.glb", b"new box"),
+            Err(AssetIoError::ReadOnly(_))
+        ));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
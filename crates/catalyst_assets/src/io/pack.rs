@@ -0,0 +1,381 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use crate::io::{AssetIoError, AssetReader, normalize_path};
+
+// Pack layout (all integers little endian):
+//
+// [magic "CPAK"] [version: u32] [entry_count: u32]
+// entry_count x [path_len: u16] [path: utf8 bytes] [offset: u64] [size: u64]
+// [file data ...]
+//
+// Offsets are absolute from the start of the file.
+const PACK_MAGIC: &[u8; 4] = b"CPAK";
+const PACK_VERSION: u32 = 1;
+const HEADER_SIZE: u64 = 4 + 4 + 4;
+// path_len + offset + size, not counting the path itself
+const MIN_ENTRY_SIZE: u64 = 2 + 8 + 8;
+
+#[derive(Clone, Copy, Debug)]
+struct PackEntry {
+    offset: u64,
+    size: u64,
+}
+
+/// Reads assets out of a single pack file. The index is loaded once on open,
+/// file contents are read on demand.
+pub struct PackAssetReader {
+    name: String,
+    file: Mutex<File>,
+    index: HashMap<String, PackEntry>,
+}
+
+impl PackAssetReader {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, AssetIoError> {
+        let path = path.as_ref();
+        let name = path.to_string_lossy().to_string();
+        let io_err = |e| AssetIoError::Io {
+            path: name.clone(),
+            source: e,
+        };
+
+        let mut file = File::open(path).map_err(io_err)?;
+        let file_len = file.metadata().map_err(io_err)?.len();
+
+        let mut magic = [0u8; 4];
+        file.read_exact(&mut magic).map_err(io_err)?;
+        if &magic != PACK_MAGIC {
+            return Err(AssetIoError::InvalidPack(name, "bad magic".to_string()));
+        }
+
+        let version = read_u32(&mut file).map_err(io_err)?;
+        if version != PACK_VERSION {
+            return Err(AssetIoError::InvalidPack(
+                name,
+                format!("unsupported version {}", version),
+            ));
+        }
+
+        let entry_count = read_u32(&mut file).map_err(io_err)?;
+        // The counts and sizes come from the file, check them before allocating anything
+        if HEADER_SIZE + entry_count as u64 * MIN_ENTRY_SIZE > file_len {
+            return Err(AssetIoError::InvalidPack(
+                name,
+                format!("{} entries don't fit in {} bytes", entry_count, file_len),
+            ));
+        }
+        let mut index = HashMap::with_capacity(entry_count as usize);
+
+        for _ in 0..entry_count {
+            let path_len = read_u16(&mut file).map_err(io_err)?;
+            let mut path_bytes = vec![0u8; path_len as usize];
+            file.read_exact(&mut path_bytes).map_err(io_err)?;
+            let entry_path = String::from_utf8(path_bytes)
+                .map_err(|_| AssetIoError::InvalidPack(name.clone(), "non utf8 path".to_string()))?;
+
+            let offset = read_u64(&mut file).map_err(io_err)?;
+            let size = read_u64(&mut file).map_err(io_err)?;
+            if offset.checked_add(size).is_none_or(|end| end > file_len) {
+                return Err(AssetIoError::InvalidPack(
+                    name,
+                    format!(
+                        "'{}' ({} bytes at {}) is past the end of the file",
+                        entry_path, size, offset
+                    ),
+                ));
+            }
+
+            index.insert(entry_path, PackEntry { offset, size });
+        }
+
+        log::info!(
+            "  [AssetPlugin] Mounted pack '{}' ({} entries)",
+            name,
+            index.len()
+        );
+
+        Ok(Self {
+            name,
+            file: Mutex::new(file),
+            index,
+        })
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &str> {
+        self.index.keys().map(|k| k.as_str())
+    }
+}
+
+impl AssetReader for PackAssetReader {
+    fn read(&self, path: &str) -> Result<Vec<u8>, AssetIoError> {
+        let path = normalize_path(path);
+        let entry = *self
+            .index
+            .get(&path)
+            .ok_or_else(|| AssetIoError::NotFound(path.clone()))?;
+
+        let io_err = |e| AssetIoError::Io {
+            path: format!("{}:{}", self.name, path),
+            source: e,
+        };
+
+        let mut file = self.file.lock().unwrap();
+        file.seek(SeekFrom::Start(entry.offset)).map_err(io_err)?;
+
+        let mut data = vec![0u8; entry.size as usize];
+        file.read_exact(&mut data).map_err(io_err)?;
+
+        Ok(data)
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.index.contains_key(&normalize_path(path))
+    }
+}
+
+/// Packs every file under `source_dir` into `output`.
+/// Entry paths are stored relative to `path_prefix` joined with the path inside `source_dir`,
+/// e.g. packing "./assets" with prefix "assets" keeps `load_scene("assets/box.glb")` working.
+/// Fails with `InvalidInput` when an entry path is longer than the format can store.
+pub fn write_pack(
+    source_dir: impl AsRef<Path>,
+    path_prefix: &str,
+    output: impl AsRef<Path>,
+) -> std::io::Result<usize> {
+    let source_dir = source_dir.as_ref();
+
+    let mut files = Vec::new();
+    collect_files(source_dir, &mut files)?;
+    files.sort();
+
+    let entries: Vec<(String, Vec<u8>)> = files
+        .iter()
+        .map(|file| {
+            let relative = file.strip_prefix(source_dir).unwrap();
+            let entry_path = normalize_path(&Path::new(path_prefix).join(relative).to_string_lossy());
+            std::fs::read(file).map(|data| (entry_path, data))
+        })
+        .collect::<Result<_, _>>()?;
+
+    let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidInput, message);
+    let entry_count = u32::try_from(entries.len())
+        .map_err(|_| invalid(format!("{} files don't fit in one pack", entries.len())))?;
+    let path_lens = entries
+        .iter()
+        .map(|(path, _)| {
+            u16::try_from(path.len())
+                .map_err(|_| invalid(format!("'{}' is longer than {} bytes", path, u16::MAX)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Header size must be known up front so the data offsets can be absolute
+    let header_size = HEADER_SIZE
+        + entries
+            .iter()
+            .map(|(path, _)| MIN_ENTRY_SIZE + path.len() as u64)
+            .sum::<u64>();
+
+    let mut out = std::io::BufWriter::new(File::create(output)?);
+    out.write_all(PACK_MAGIC)?;
+    out.write_all(&PACK_VERSION.to_le_bytes())?;
+    out.write_all(&entry_count.to_le_bytes())?;
+
+    let mut offset = header_size;
+    for ((path, data), path_len) in entries.iter().zip(path_lens) {
+        out.write_all(&path_len.to_le_bytes())?;
+        out.write_all(path.as_bytes())?;
+        out.write_all(&offset.to_le_bytes())?;
+        out.write_all(&(data.len() as u64).to_le_bytes())?;
+        offset += data.len() as u64;
+    }
+
+    for (_, data) in &entries {
+        out.write_all(data)?;
+    }
+    out.flush()?;
+
+    Ok(entries.len())
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<std::path::PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn read_u16(reader: &mut impl Read) -> std::io::Result<u16> {
+    let mut buf = [0u8; 2];
+    reader.read_exact(&mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+fn read_u32(reader: &mut impl Read) -> std::io::Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(reader: &mut impl Read) -> std::io::Result<u64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    const FILES: [(&str, &[u8]); 3] = [
+        ("box.glb", b"glTF box"),
+        ("textures/wood.png", &[0x89, b'P', b'N', b'G', 0, 0xff]),
+        ("empty.txt", b""),
+    ];
+
+    // A source folder and an output path of their own, since tests run in parallel
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("catalyst_pack_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_sources(dir: &Path) -> PathBuf {
+        let source = dir.join("assets");
+        for (path, bytes) in FILES {
+            let path = source.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, bytes).unwrap();
+        }
+        source
+    }
+
+    fn packed(name: &str) -> (PathBuf, PathBuf) {
+        let dir = temp_dir(name);
+        let source = write_sources(&dir);
+        let pack = dir.join("assets.cpak");
+        assert_eq!(write_pack(&source, "assets", &pack).unwrap(), FILES.len());
+        (dir, pack)
+    }
+
+    fn open_error(pack: &Path) -> AssetIoError {
+        match PackAssetReader::open(pack) {
+            Ok(_) => panic!("{} opened", pack.display()),
+            Err(error) => error,
+        }
+    }
+
+    #[test]
+    fn packed_files_read_back_byte_for_byte() {
+        let (dir, pack) = packed("round_trip");
+        let reader = PackAssetReader::open(&pack).unwrap();
+
+        let mut entries: Vec<_> = reader.entries().collect();
+        entries.sort();
+        assert_eq!(
+            entries,
+            [
+                "assets/box.glb",
+                "assets/empty.txt",
+                "assets/textures/wood.png"
+            ]
+        );
+        for (path, bytes) in FILES {
+            let path = format!("assets/{}", path);
+            assert!(reader.exists(&path));
+            assert_eq!(reader.read(&path).unwrap(), bytes, "{}", path);
+        }
+        // Paths are normalized the same way as on write
+        assert_eq!(
+            reader.read(".\\assets\\textures\\wood.png").unwrap(),
+            FILES[1].1
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn a_missing_entry_is_not_found() {
+        let (dir, pack) = packed("missing");
+        let reader = PackAssetReader::open(&pack).unwrap();
+
+        assert!(!reader.exists("assets/nope.glb"));
+        assert!(matches!(
+            reader.read("assets/nope.glb"),
+            Err(AssetIoError::NotFound(path)) if path == "assets/nope.glb"
+        ));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn a_truncated_pack_is_rejected_on_open() {
+        let (dir, pack) = packed("truncated");
+        let len = std::fs::metadata(&pack).unwrap().len();
+        File::options()
+            .write(true)
+            .open(&pack)
+            .unwrap()
+            .set_len(len - 1)
+            .unwrap();
+
+        assert!(matches!(open_error(&pack), AssetIoError::InvalidPack(..)));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn entries_past_the_end_of_the_file_are_rejected_on_open() {
+        let dir = temp_dir("corrupt");
+        let header = |entry_count: u32| {
+            let mut bytes = PACK_MAGIC.to_vec();
+            bytes.extend(PACK_VERSION.to_le_bytes());
+            bytes.extend(entry_count.to_le_bytes());
+            bytes
+        };
+
+        // A size that would allocate most of the address space
+        let huge = dir.join("huge.cpak");
+        let mut bytes = header(1);
+        bytes.extend(1u16.to_le_bytes());
+        bytes.push(b'a');
+        bytes.extend(0u64.to_le_bytes());
+        bytes.extend((u64::MAX - 1).to_le_bytes());
+        std::fs::write(&huge, bytes).unwrap();
+        assert!(matches!(open_error(&huge), AssetIoError::InvalidPack(..)));
+
+        // More entries than the file could hold
+        let count = dir.join("count.cpak");
+        std::fs::write(&count, header(u32::MAX)).unwrap();
+        assert!(matches!(open_error(&count), AssetIoError::InvalidPack(..)));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn paths_too_long_for_the_format_fail_to_pack() {
+        let dir = temp_dir("long_path");
+        let pack = dir.join("assets.cpak");
+        let prefix = "a".repeat(u16::MAX as usize);
+        std::fs::create_dir_all(dir.join("assets")).unwrap();
+        std::fs::write(dir.join("assets/box.glb"), b"box").unwrap();
+
+        let error = write_pack(dir.join("assets"), &prefix, &pack).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
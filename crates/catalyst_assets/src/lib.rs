@@ -1,19 +1,33 @@
-use catalyst_core::{App, IoTaskPool, Plugin};
+use catalyst_core::{App, IoTaskPool, Plugin, console::ConsoleCommands, time::Time};
 use flecs_ecs::prelude::*;
 use tokio::sync::mpsc::{UnboundedReceiver, unbounded_channel};
 
 use crate::{
     asset_events::{AssetLookup, AssetType, register_flush_system},
-    asset_server::{AssetServer, AssetWorkerMessage}, scene::{SceneData},
+    asset_server::{AssetServer, AssetWorkerMessage, SceneLoadOptions}, io::AssetMounts, lod::GenerateLods, lut::LutData, scene::{SceneData, SceneFilter},
+    gc::{AssetGc, collect_scene_orphans, register_asset_gc, register_gc_commands},
+    postprocess::{AssetMetadata, PostprocessError, PostprocessedAsset},
+    reload::{AssetDependencies, HotReload},
 };
 
 pub mod asset_events;
 pub mod asset_server;
 pub mod assets;
+pub mod cache;
 mod components;
+pub mod gc;
+pub mod import;
+pub mod io;
+pub mod lod;
+pub mod lut;
 pub mod material;
+pub mod mesh_repair;
 pub mod physics;
+pub mod postprocess;
+pub mod primitives;
+pub mod reload;
 pub mod scene;
+pub mod simplify;
 
 pub use components::{MaterialDefinition, MeshDefinition};
 
@@ -32,14 +46,42 @@ pub struct LoadSkybox;
 #[derive(Component)]
 pub struct LoadScene;
 
+/// Tag next to LoadScene: build the scene's GPU resources over several frames
+/// through the renderer's upload queue. The scene gets SceneReady once they all exist.
+#[derive(Component)]
+pub struct PrewarmScene;
+
 #[derive(Component)]
 pub struct Loading; // Tag: "I am currently busy, don't touch me"
 
+// Tag on the textures/materials/meshes of a prewarmed scene: uploaded by the
+// renderer's budgeted queue instead of as soon as the data lands
+#[derive(Component)]
+pub struct PrewarmAsset;
+
 #[derive(Component)]
 pub struct AssetError(pub String); 
 
 pub struct AssetPlugin;
 
+impl AssetPlugin {
+    /// Runs `processor` on every T (TextureData, MeshData, MaterialData or SceneData)
+    /// loaded from now on, on the loading thread, after the ones registered before it.
+    /// An error aborts the load. The parts of a scene go through the processors of
+    /// their type before the scene does. Needs the AssetPlugin to be built.
+    pub fn register_postprocessor<T: PostprocessedAsset>(
+        app: &mut App,
+        processor: impl Fn(&mut T, &AssetMetadata) -> Result<(), PostprocessError>
+        + Send
+        + Sync
+        + 'static,
+    ) {
+        app.world.get::<&AssetServer>(|server| {
+            server.postprocessors().register(processor);
+        });
+    }
+}
+
 impl Plugin for AssetPlugin {
     fn build(&self, app: &mut App) {
         app.world
@@ -48,16 +90,29 @@ impl Plugin for AssetPlugin {
 
         app.world.component::<AssetSource>();
         app.world.component::<LoadScene>();
+        app.world.component::<PrewarmScene>();
+        app.world.component::<GenerateLods>();
+        app.world.component::<SceneFilter>();
+        app.world.component::<LutData>();
 
-        let io_handle = app.world.get::<&IoTaskPool>(|t| t.0.clone());
+        let Some(io_handle) = app.world.try_get::<&IoTaskPool>(|t| t.0.clone()) else {
+            panic!("AssetPlugin requires the IoTaskPool singleton; create the app with App::new()");
+        };
         // 2. Create the internal communication channel
         let (tx, rx) = unbounded_channel::<AssetWorkerMessage>();
 
         // 3. Create and Insert the AssetServer (Public API)
-        let server = AssetServer::new(tx, io_handle);
+        let server = AssetServer::new(tx, io_handle, AssetMounts::default());
         app.register_singleton(server);
         app.register_singleton_default::<AssetLookup>();
         app.register_singleton(AssetReceiver(rx));
+        app.register_settings::<HotReload>("hot_reload");
+        app.register_singleton_default::<AssetDependencies>();
+        app.register_singleton_default::<AssetGc>();
+        app.world.get::<&mut ConsoleCommands>(|commands| {
+            register_cache_commands(commands);
+            register_gc_commands(commands);
+        });
 
         app.world
             .system_named::<(&AssetSource, &AssetServer)>("load_assets")
@@ -66,14 +121,89 @@ impl Plugin for AssetPlugin {
             .without(SceneData::id())
             .kind(flecs::pipeline::OnUpdate)
             .each_entity(|entity, (source, assets)| {
-                assets.load_scene(&source.path, entity.id());
+                let options = SceneLoadOptions {
+                    prewarm: entity.has(PrewarmScene),
+                    lods: entity.try_get::<&GenerateLods>(|lods| lods.clone()),
+                    filter: entity.try_get::<&SceneFilter>(|filter| filter.clone()),
+                    ..Default::default()
+                };
+                assets.load_scene_with_options(&source.path, entity.id(), options);
                 entity.add(Loading);
             });
 
+        app.world
+            .system_named::<(&AssetServer, &HotReload, &mut AssetDependencies, &Time)>(
+                "Watch Asset Files",
+            )
+            .kind(flecs::pipeline::OnUpdate)
+            .each(|(server, hot_reload, dependencies, time)| {
+                if hot_reload.enabled
+                    && dependencies.poll_due(time.delta_seconds(), hot_reload.poll_interval)
+                {
+                    server.reload_changed();
+                }
+            });
+
+        // A deleted scene isn't loaded again, whatever happens to its file, and the
+        // assets only it used go with it
+        app.world
+            .observer::<flecs::OnRemove, (&SceneData, &AssetServer)>()
+            .term_at(1)
+            .filter()
+            .each_entity(|entity, (_, server)| {
+                server.forget_scene(entity.id());
+                collect_scene_orphans(&entity.world(), entity.id());
+            });
+
         register_flush_system(&app.world);
+        register_asset_gc(&app.world);
     }
 }
 
+fn register_cache_commands(commands: &mut ConsoleCommands) {
+    commands
+        .register("assets.cache", "Show the asset cache counters", |world, _| {
+            Ok(world.get::<&AssetServer>(|server| {
+                let cache = server.cache();
+                let stats = cache.stats();
+                match cache.dir() {
+                    Some(dir) => format!(
+                        "{:?}: {} hits, {} misses, {} invalid, {} bytes read, {} bytes written",
+                        dir,
+                        stats.hits,
+                        stats.misses,
+                        stats.invalid,
+                        stats.bytes_read,
+                        stats.bytes_written
+                    ),
+                    None => "Asset cache disabled".to_string(),
+                }
+            }))
+        })
+        .register(
+            "assets.clear_cache",
+            "Delete the cached asset files; the next loads parse again",
+            |world, _| {
+                world.get::<&AssetServer>(|server| {
+                    server
+                        .cache()
+                        .clear()
+                        .map(|deleted| format!("Deleted {} cache files", deleted))
+                        .map_err(|e| e.to_string())
+                })
+            },
+        )
+        .register(
+            "assets.reload",
+            "Load again every watched asset file that changed on disk",
+            |world, _| {
+                Ok(world.get::<&AssetServer>(|server| {
+                    format!("Reloading {} files", server.reload_changed())
+                }))
+            },
+        );
+}
+
 // Internal wrapper to hold the receiver
 #[derive(Component)]
 struct AssetReceiver(UnboundedReceiver<AssetWorkerMessage>);
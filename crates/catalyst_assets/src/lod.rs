@@ -0,0 +1,345 @@
+use std::{
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use catalyst_core::rayon::prelude::*;
+use flecs_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    assets::{Handle, MeshData, Vertex},
+    simplify::simplify,
+};
+
+// Part of the cache key: bump when the simplifier's output changes
+const SIMPLIFIER_VERSION: u32 = 1;
+const CACHE_MAGIC: &[u8; 4] = b"CLD2";
+
+/// One level of a generated LOD chain.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LodLevelSettings {
+    /// Fraction of the source triangles to keep (0.5 = half).
+    pub triangle_ratio: f32,
+    /// The level is drawn once the mesh's bounding sphere covers less than this
+    /// fraction of the screen height.
+    pub screen_size: f32,
+}
+
+/// Import setting: put it next to LoadScene to generate a LOD chain for every mesh of
+/// the scene. Simplification runs on the compute pool after parsing; spawned nodes
+/// get a MeshLod for their mesh.
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
+pub struct GenerateLods {
+    /// Most detailed first.
+    pub levels: Vec<LodLevelSettings>,
+    /// Where simplified meshes are cached, keyed by the source mesh and the level
+    /// settings, so later runs skip the work. None always simplifies.
+    pub cache_dir: Option<PathBuf>,
+}
+
+impl Default for GenerateLods {
+    fn default() -> Self {
+        Self {
+            levels: vec![
+                LodLevelSettings { triangle_ratio: 0.5, screen_size: 0.3 },
+                LodLevelSettings { triangle_ratio: 0.25, screen_size: 0.15 },
+                LodLevelSettings { triangle_ratio: 0.1, screen_size: 0.06 },
+            ],
+            cache_dir: Some(PathBuf::from("cache/lods")),
+        }
+    }
+}
+
+/// Reduced versions of the entity's MeshDefinition mesh. The renderer draws the
+/// least detailed level whose `screen_size` the mesh is still below.
+#[derive(Component, Clone, Debug)]
+pub struct MeshLod {
+    /// Most detailed first.
+    pub levels: Vec<MeshLodLevel>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MeshLodLevel {
+    pub mesh: Handle<MeshData>,
+    pub screen_size: f32,
+    pub triangles: usize,
+    /// Simplification error relative to the mesh size, see SimplifiedMesh::error.
+    pub error: f32,
+}
+
+/// Builds the LOD chain of `mesh`. Levels that come out empty, or no smaller than
+/// the level before, are dropped. `name` only labels the quality report in the log.
+pub fn generate_lods(
+    name: &str,
+    mesh: &MeshData,
+    settings: &GenerateLods,
+) -> Vec<(MeshLodLevel, MeshData)> {
+    let source_triangles = mesh.indices.len() / 3;
+    let source_hash = hash_mesh(mesh);
+
+    let generated: Vec<(LodLevelSettings, MeshData, f32)> = settings
+        .levels
+        .par_iter()
+        .map(|level| {
+            catalyst_core::profile_scope!("Generate LOD Level");
+            let cache_path = settings
+                .cache_dir
+                .as_ref()
+                .map(|dir| dir.join(cache_file_name(source_hash, level)));
+
+            if let Some(cached) = cache_path.as_deref().and_then(read_cached) {
+                return (*level, cached.0, cached.1);
+            }
+
+            let target = (source_triangles as f32 * level.triangle_ratio.clamp(0.0, 1.0)) as usize;
+            let simplified = simplify(mesh, target);
+
+            if let Some(path) = &cache_path {
+                if let Err(error) = write_cached(path, &simplified.mesh, simplified.error) {
+                    log::warn!("Can't cache LOD of '{}' at {}: {}", name, path.display(), error);
+                }
+            }
+            (*level, simplified.mesh, simplified.error)
+        })
+        .collect();
+
+    let mut levels = Vec::with_capacity(generated.len());
+    let mut report = Vec::with_capacity(generated.len());
+    let mut previous_triangles = source_triangles;
+
+    for (settings, data, error) in generated {
+        let triangles = data.indices.len() / 3;
+        if triangles == 0 || triangles >= previous_triangles {
+            report.push(format!("{:.0}% dropped ({} tris)", settings.triangle_ratio * 100.0, triangles));
+            continue;
+        }
+        previous_triangles = triangles;
+
+        report.push(format!("{} tris (error {:.4})", triangles, error));
+        levels.push((
+            MeshLodLevel {
+                mesh: Handle::new(),
+                screen_size: settings.screen_size,
+                triangles,
+                error,
+            },
+            data,
+        ));
+    }
+
+    log::info!(
+        "LODs of '{}' ({} tris): {}",
+        name,
+        source_triangles,
+        report.join(", ")
+    );
+    levels
+}
+
+// FNV-1a over the mesh contents: stable across runs and compiler versions, unlike
+// std's default hasher, so it can key files on disk
+fn hash_mesh(mesh: &MeshData) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut feed = |bytes: &[u8]| {
+        for byte in bytes {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    };
+
+    for vertex in &mesh.vertices {
+        let values = vertex.position.iter().chain(&vertex.normal).chain(&vertex.uv);
+        for value in values.chain([&vertex.wind_weight]) {
+            feed(&value.to_le_bytes());
+        }
+    }
+    for index in &mesh.indices {
+        feed(&index.to_le_bytes());
+    }
+    hash
+}
+
+fn cache_file_name(source_hash: u64, level: &LodLevelSettings) -> String {
+    format!(
+        "{:016x}_{:08x}_v{}.lod",
+        source_hash,
+        level.triangle_ratio.to_bits(),
+        SIMPLIFIER_VERSION
+    )
+}
+
+// Missing or unreadable entries are simply regenerated
+fn read_cached(path: &Path) -> Option<(MeshData, f32)> {
+    let bytes = std::fs::read(path).ok()?;
+    let mut reader = bytes.as_slice();
+
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic).ok()?;
+    if &magic != CACHE_MAGIC {
+        return None;
+    }
+
+    let error = read_f32(&mut reader)?;
+    let vertex_count = read_u32(&mut reader)? as usize;
+    let index_count = read_u32(&mut reader)? as usize;
+
+    let mut vertices = Vec::with_capacity(vertex_count);
+    for _ in 0..vertex_count {
+        let mut values = [0.0f32; 9];
+        for value in &mut values {
+            *value = read_f32(&mut reader)?;
+        }
+        vertices.push(Vertex {
+            position: [values[0], values[1], values[2]],
+            normal: [values[3], values[4], values[5]],
+            uv: [values[6], values[7]],
+            wind_weight: values[8],
+        });
+    }
+
+    let mut indices = Vec::with_capacity(index_count);
+    for _ in 0..index_count {
+        indices.push(read_u32(&mut reader)?);
+    }
+
+    Some((MeshData::new(vertices, indices), error))
+}
+
+fn write_cached(path: &Path, mesh: &MeshData, error: f32) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let mut bytes = Vec::with_capacity(16 + mesh.vertices.len() * 36 + mesh.indices.len() * 4);
+    bytes.write_all(CACHE_MAGIC)?;
+    bytes.write_all(&error.to_le_bytes())?;
+    bytes.write_all(&(mesh.vertices.len() as u32).to_le_bytes())?;
+    bytes.write_all(&(mesh.indices.len() as u32).to_le_bytes())?;
+    for vertex in &mesh.vertices {
+        let values = vertex.position.iter().chain(&vertex.normal).chain(&vertex.uv);
+        for value in values.chain([&vertex.wind_weight]) {
+            bytes.write_all(&value.to_le_bytes())?;
+        }
+    }
+    for index in &mesh.indices {
+        bytes.write_all(&index.to_le_bytes())?;
+    }
+
+    std::fs::write(path, bytes)
+}
+
+fn read_u32(reader: &mut &[u8]) -> Option<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes).ok()?;
+    Some(u32::from_le_bytes(bytes))
+}
+
+fn read_f32(reader: &mut &[u8]) -> Option<f32> {
+    read_u32(reader).map(f32::from_bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives;
+
+    fn level(triangle_ratio: f32) -> LodLevelSettings {
+        LodLevelSettings {
+            triangle_ratio,
+            screen_size: triangle_ratio * 0.5,
+        }
+    }
+
+    fn uncached(levels: Vec<LodLevelSettings>) -> GenerateLods {
+        GenerateLods {
+            levels,
+            cache_dir: None,
+        }
+    }
+
+    #[test]
+    fn levels_keep_their_share_of_the_triangles() {
+        // 16x16 cells, 512 triangles. Flat, so only the border limits the collapses
+        let mesh = primitives::grid(4.0, 16);
+        let lods = generate_lods("grid", &mesh, &uncached(vec![level(0.5), level(0.25)]));
+
+        assert_eq!(lods.len(), 2);
+        for ((lod, data), target) in lods.iter().zip([256, 128]) {
+            assert_eq!(lod.triangles, data.indices.len() / 3);
+            // A collapse removes two triangles, so it can stop one under
+            assert!(
+                lod.triangles <= target && lod.triangles + 1 >= target,
+                "{} triangles for a target of {}",
+                lod.triangles,
+                target
+            );
+            assert!(lod.error < 1e-4);
+        }
+        assert_eq!(lods[0].0.screen_size, 0.25);
+        assert_eq!(lods[1].0.screen_size, 0.125);
+    }
+
+    #[test]
+    fn levels_that_reduce_nothing_are_dropped() {
+        // Asks for more than the level before it got
+        let mesh = primitives::grid(4.0, 16);
+        let lods = generate_lods(
+            "grid",
+            &mesh,
+            &uncached(vec![level(0.5), level(1.0), level(0.25)]),
+        );
+        let triangles: Vec<usize> = lods.iter().map(|(lod, _)| lod.triangles).collect();
+        assert_eq!(triangles.len(), 2, "{:?}", triangles);
+        assert!(triangles[0] > triangles[1]);
+
+        // All border: nothing can collapse
+        let quad = primitives::plane(1.0);
+        assert!(generate_lods("quad", &quad, &uncached(vec![level(0.5), level(0.1)])).is_empty());
+    }
+
+    #[test]
+    fn cache_files_are_named_by_mesh_and_ratio() {
+        let mesh = primitives::grid(4.0, 8);
+        let hash = hash_mesh(&mesh);
+
+        let name = cache_file_name(hash, &level(0.5));
+        assert_eq!(name, cache_file_name(hash, &level(0.5)));
+        assert_ne!(name, cache_file_name(hash, &level(0.25)));
+        // Only when the level is drawn changes nothing simplified
+        let later = LodLevelSettings {
+            screen_size: 0.01,
+            ..level(0.5)
+        };
+        assert_eq!(name, cache_file_name(hash, &later));
+
+        let other = primitives::grid(2.0, 8);
+        assert_ne!(name, cache_file_name(hash_mesh(&other), &level(0.5)));
+    }
+
+    #[test]
+    fn cached_levels_are_read_back() {
+        let dir = std::env::temp_dir().join(format!("catalyst_lods_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mesh = primitives::grid(4.0, 8);
+        let settings = GenerateLods {
+            levels: vec![level(0.5), level(0.25)],
+            cache_dir: Some(dir.clone()),
+        };
+
+        let generated = generate_lods("grid", &mesh, &settings);
+        for level in &settings.levels {
+            assert!(dir.join(cache_file_name(hash_mesh(&mesh), level)).is_file());
+        }
+
+        let cached = generate_lods("grid", &mesh, &settings);
+        assert_eq!(cached.len(), generated.len());
+        for ((cached, cached_data), (generated, generated_data)) in cached.iter().zip(&generated) {
+            assert_eq!(cached.triangles, generated.triangles);
+            assert_eq!(cached.error, generated.error);
+            assert_eq!(cached_data.indices, generated_data.indices);
+        }
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,313 @@
+//! 3D color lookup tables, for the renderer's color grading.
+//!
+//! A LUT maps every display color (sRGB encoded, as seen on screen) to a graded one.
+//! It is loaded from a `.cube` file, as written by Resolve, Photoshop and most grading
+//! tools, or from a strip image: `size` squares of `size` x `size` pixels side by side,
+//! blue growing from square to square, red to the right and green downwards in each
+//! square (the layout of Unreal's neutral LUT). Grade a screenshot of the game together
+//! with `identity.png` from `assets/luts`, and the identity strip becomes the LUT.
+
+use flecs_ecs::prelude::*;
+use thiserror::Error;
+
+/// Entries per axis of the LUTs the engine ships and generates.
+pub const LUT_SIZE: u32 = 32;
+
+/// Smallest and largest LUT sizes accepted.
+pub const LUT_SIZES: std::ops::RangeInclusive<u32> = 2..=64;
+
+#[derive(Error, Debug)]
+pub enum LutError {
+    #[error("LUT size {0} is outside 2 to 64")]
+    Size(u32),
+    #[error("a LUT strip is N squares of N x N side by side, {width}x{height} isn't")]
+    NotAStrip { width: u32, height: u32 },
+    #[error("line {line}: {message}")]
+    Cube { line: usize, message: String },
+    #[error("LUT '{name}' should have {expected} entries, it has {actual}")]
+    WrongEntryCount {
+        name: String,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct LutData {
+    pub name: String,
+    /// Entries per axis.
+    pub size: u32,
+    /// Output color of every entry, red changing fastest, then green, then blue (the
+    /// order of .cube files).
+    pub entries: Vec<[f32; 3]>,
+}
+
+impl LutData {
+    /// The LUT that changes nothing.
+    pub fn identity(size: u32) -> Self {
+        Self::from_fn("identity", size, |color| color)
+    }
+
+    /// A LUT whose entries are `grade` of their input color, e.g. to build looks in code.
+    pub fn from_fn(name: &str, size: u32, grade: impl Fn([f32; 3]) -> [f32; 3]) -> Self {
+        let size = size.clamp(*LUT_SIZES.start(), *LUT_SIZES.end());
+        let step = 1.0 / (size - 1) as f32;
+        let mut entries = Vec::with_capacity((size * size * size) as usize);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    entries.push(grade([r as f32 * step, g as f32 * step, b as f32 * step]));
+                }
+            }
+        }
+        Self {
+            name: name.to_string(),
+            size,
+            entries,
+        }
+    }
+
+    /// Reads a strip image, see the module docs.
+    pub fn from_strip(name: &str, image: &image::RgbaImage) -> Result<Self, LutError> {
+        let (width, height) = image.dimensions();
+        if width != height * height {
+            return Err(LutError::NotAStrip { width, height });
+        }
+        let size = height;
+        if !LUT_SIZES.contains(&size) {
+            return Err(LutError::Size(size));
+        }
+
+        let mut entries = Vec::with_capacity((size * size * size) as usize);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    let pixel = image.get_pixel(b * size + r, g);
+                    entries.push([
+                        pixel[0] as f32 / 255.0,
+                        pixel[1] as f32 / 255.0,
+                        pixel[2] as f32 / 255.0,
+                    ]);
+                }
+            }
+        }
+        Ok(Self {
+            name: name.to_string(),
+            size,
+            entries,
+        })
+    }
+
+    /// Writes the LUT as a strip image, see the module docs.
+    pub fn to_strip(&self) -> image::RgbaImage {
+        let size = self.size;
+        image::RgbaImage::from_fn(size * size, size, |x, y| {
+            let [r, g, b] = self.entry(x % size, y, x / size);
+            let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+            image::Rgba([channel(r), channel(g), channel(b), 255])
+        })
+    }
+
+    /// Parses a .cube file: `LUT_3D_SIZE`, optional `TITLE`, `DOMAIN_MIN` and
+    /// `DOMAIN_MAX`, then one `r g b` line per entry. 1D LUTs aren't supported.
+    pub fn parse_cube(name: &str, text: &str) -> Result<Self, LutError> {
+        let mut size = None;
+        let mut domain_min = [0.0f32; 3];
+        let mut domain_max = [1.0f32; 3];
+        let mut entries = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let error = |message: String| LutError::Cube {
+                line: index + 1,
+                message,
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut words = line.split_whitespace();
+            let keyword = words.next().unwrap_or_default();
+            let numbers = |words: std::str::SplitWhitespace| -> Result<[f32; 3], LutError> {
+                let values: Vec<f32> = words
+                    .map(|word| word.parse::<f32>())
+                    .collect::<Result<_, _>>()
+                    .map_err(|e| error(e.to_string()))?;
+                values
+                    .try_into()
+                    .map_err(|_| error("expected three numbers".to_string()))
+            };
+
+            match keyword {
+                "TITLE" => {}
+                "LUT_1D_SIZE" => return Err(error("1D LUTs aren't supported".to_string())),
+                "LUT_3D_SIZE" => {
+                    let value = words
+                        .next()
+                        .and_then(|word| word.parse::<u32>().ok())
+                        .ok_or_else(|| error("LUT_3D_SIZE needs a number".to_string()))?;
+                    if !LUT_SIZES.contains(&value) {
+                        return Err(LutError::Size(value));
+                    }
+                    size = Some(value);
+                }
+                "DOMAIN_MIN" => domain_min = numbers(words)?,
+                "DOMAIN_MAX" => domain_max = numbers(words)?,
+                _ if keyword.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.') => {
+                    let mut color = numbers(line.split_whitespace())?;
+                    // Stored normalized, so the GPU path doesn't care about the domain
+                    for channel in 0..3 {
+                        let range = domain_max[channel] - domain_min[channel];
+                        if range > 0.0 {
+                            color[channel] = (color[channel] - domain_min[channel]) / range;
+                        }
+                    }
+                    entries.push(color);
+                }
+                // Other keywords (LUT_IN_VIDEO_RANGE, ...) don't change the table
+                _ => {}
+            }
+        }
+
+        let size = size.ok_or_else(|| LutError::Cube {
+            line: 0,
+            message: "no LUT_3D_SIZE".to_string(),
+        })?;
+        let lut = Self {
+            name: name.to_string(),
+            size,
+            entries,
+        };
+        lut.validate()?;
+        Ok(lut)
+    }
+
+    /// Checks that there is one entry per cell, e.g. before adding a LUT built in code.
+    pub fn validate(&self) -> Result<(), LutError> {
+        if !LUT_SIZES.contains(&self.size) {
+            return Err(LutError::Size(self.size));
+        }
+        let expected = (self.size * self.size * self.size) as usize;
+        if self.entries.len() != expected {
+            return Err(LutError::WrongEntryCount {
+                name: self.name.clone(),
+                expected,
+                actual: self.entries.len(),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn entry(&self, r: u32, g: u32, b: u32) -> [f32; 3] {
+        let size = self.size;
+        self.entries[((b * size + g) * size + r) as usize]
+    }
+
+    /// The graded `color` (components between 0 and 1), interpolated between the eight
+    /// nearest entries the way the GPU samples the LUT.
+    pub fn sample(&self, color: [f32; 3]) -> [f32; 3] {
+        let last = (self.size - 1) as f32;
+        let mut low = [0u32; 3];
+        let mut high = [0u32; 3];
+        let mut t = [0.0f32; 3];
+        for channel in 0..3 {
+            let position = color[channel].clamp(0.0, 1.0) * last;
+            low[channel] = position.floor() as u32;
+            high[channel] = (low[channel] + 1).min(self.size - 1);
+            t[channel] = position - low[channel] as f32;
+        }
+
+        let lerp = |a: [f32; 3], b: [f32; 3], t: f32| {
+            [
+                a[0] + (b[0] - a[0]) * t,
+                a[1] + (b[1] - a[1]) * t,
+                a[2] + (b[2] - a[2]) * t,
+            ]
+        };
+        let corner = |r: bool, g: bool, b: bool| {
+            self.entry(
+                if r { high[0] } else { low[0] },
+                if g { high[1] } else { low[1] },
+                if b { high[2] } else { low[2] },
+            )
+        };
+
+        let along_r = |g, b| lerp(corner(false, g, b), corner(true, g, b), t[0]);
+        let along_g = |b| lerp(along_r(false, b), along_r(true, b), t[1]);
+        lerp(along_g(false), along_g(true), t[2])
+    }
+
+    /// The entries as RGBA, blue slices first, for a 3D texture.
+    pub fn to_rgba_f32(&self) -> Vec<f32> {
+        self.entries
+            .iter()
+            .flat_map(|[r, g, b]| [*r, *g, *b, 1.0])
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLD_CUBE: &str = include_str!("../../../assets/luts/cold.cube");
+
+    #[test]
+    fn the_shipped_cube_lut_parses() {
+        let cold = LutData::parse_cube("cold.cube", COLD_CUBE).unwrap();
+        assert_eq!(cold.size, 17);
+        assert!(cold.validate().is_ok());
+    }
+
+    #[test]
+    fn cube_entries_are_normalized_to_the_domain() {
+        let scaled = LutData::parse_cube(
+            "scaled",
+            "LUT_3D_SIZE 2\nDOMAIN_MAX 2 2 2\n0 0 0\n2 0 0\n0 2 0\n2 2 0\n0 0 2\n2 0 2\n0 2 2\n2 2 2\n",
+        )
+        .unwrap();
+        assert_eq!(scaled.entry(1, 1, 1), [1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn broken_cube_luts_are_rejected() {
+        assert!(LutData::parse_cube("short", "LUT_3D_SIZE 2\n0 0 0\n1 1 1\n").is_err());
+        assert!(LutData::parse_cube("1d", "LUT_1D_SIZE 4\n").is_err());
+    }
+
+    #[test]
+    fn a_lut_survives_a_round_trip_through_a_strip() {
+        let identity = LutData::identity(LUT_SIZE);
+        let strip = identity.to_strip();
+        // N squares of N x N side by side
+        assert_eq!(strip.dimensions(), (LUT_SIZE * LUT_SIZE, LUT_SIZE));
+
+        let round_trip = LutData::from_strip("round trip", &strip).unwrap();
+        for (a, b) in round_trip.entries.iter().zip(&identity.entries) {
+            for c in 0..3 {
+                assert!((a[c] - b[c]).abs() <= 0.5 / 255.0);
+            }
+        }
+    }
+
+    #[test]
+    fn sampling_the_identity_lut_returns_the_input() {
+        let identity = LutData::identity(LUT_SIZE);
+        for color in [
+            [0.0, 0.0, 0.0],
+            [1.0, 1.0, 1.0],
+            [0.3, 0.52, 0.91],
+            [0.77, 0.01, 0.5],
+        ] {
+            let graded = identity.sample(color);
+            for c in 0..3 {
+                assert!(
+                    (graded[c] - color[c]).abs() < 1e-5,
+                    "{:?} -> {:?}",
+                    color,
+                    graded
+                );
+            }
+        }
+    }
+}
@@ -1,7 +1,11 @@
+use catalyst_core::color::Color;
 use flecs_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
 use crate::assets::Handle;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum TextureFormat {
     Rgba8Unorm, // Standard 32-bit color (0-255)
     Rgba8UnormSrgb, // Standard 32-bit color (0-255)
@@ -22,21 +26,108 @@ pub struct TextureData {
     pub width: u32,
     pub height: u32,
     pub format: TextureFormat, // e.g., Rgba8Unorm
+    /// Build a mip chain on upload, see `TextureImportSettings::mips`.
+    pub mips: bool,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Error, Debug)]
+pub enum TextureDataError {
+    #[error("texture '{0}' has zero size")]
+    ZeroSized(String),
+    #[error("texture '{name}' should have {expected} values for its size and format, it has {actual}")]
+    WrongPixelCount {
+        name: String,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl TextureData {
+    /// Checks that the pixels match the size and format, e.g. before adding a texture built in code.
+    pub fn validate(&self) -> Result<(), TextureDataError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(TextureDataError::ZeroSized(self.name.clone()));
+        }
+
+        let channels = match self.format {
+            TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb | TextureFormat::Rgba32Float => 4,
+            TextureFormat::Gray8 => 1,
+        };
+        let expected = self.width as usize * self.height as usize * channels;
+        let actual = match &self.pixels {
+            TextureType::LDR(pixels) => pixels.len(),
+            TextureType::HDR(pixels) => pixels.len(),
+        };
+
+        if expected != actual {
+            return Err(TextureDataError::WrongPixelCount {
+                name: self.name.clone(),
+                expected,
+                actual,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct MaterialSettings {
-    pub base_color: [f32; 4],
+    pub base_color: Color,
     pub roughness: f32,
     pub metallic: f32,
+    /// Alpha-tested (glTF MASK): fragments with alpha below the cutoff are discarded.
+    pub alpha_cutoff: Option<f32>,
+    /// Render back faces too (no culling).
+    pub double_sided: bool,
+    /// Emitted light, added on top of the shading. Alpha is ignored.
+    pub emissive: Color,
+    /// Scales `emissive` (KHR_materials_emissive_strength). Can go past 1.0 for bloom.
+    pub emissive_strength: f32,
+    /// KHR_materials_unlit: base color times texture (plus emission), no lighting.
+    pub unlit: bool,
+    /// Sways the vertices in the wind, scaled by their `Vertex::wind_weight`.
+    pub wind: Option<MaterialWind>,
 }
 
 impl Default for MaterialSettings {
     fn default() -> Self {
         Self {
-            base_color: [1.0, 1.0, 1.0, 1.0],
+            base_color: Color::WHITE,
             roughness: 0.5,
             metallic: 0.0,
+            alpha_cutoff: None,
+            double_sided: false,
+            emissive: Color::BLACK,
+            emissive_strength: 1.0,
+            unlit: false,
+            wind: None,
+        }
+    }
+}
+
+/// How a material moves in the wind set by the renderer's WindSettings. Distances are
+/// in meters at full wind strength and a vertex weight of 1.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaterialWind {
+    /// How far the whole plant bends along the wind.
+    pub strength: f32,
+    /// Sways per second of the bend and the branches.
+    pub frequency: f32,
+    /// Sideways sway of branches, out of step with their neighbours.
+    pub branch: f32,
+    /// Fast flutter of leaf and blade tips along their normal.
+    pub flutter: f32,
+}
+
+impl Default for MaterialWind {
+    fn default() -> Self {
+        Self {
+            strength: 0.3,
+            frequency: 0.6,
+            branch: 0.05,
+            flutter: 0.02,
         }
     }
 }
@@ -60,4 +151,144 @@ impl Default for MaterialData {
             occlusion_texture: None,
         }
     }
-}
\ No newline at end of file
+}
+impl MaterialData {
+    pub fn builder() -> MaterialBuilder {
+        MaterialBuilder::default()
+    }
+
+    /// Flat color that ignores lighting.
+    pub fn unlit_color(color: impl Into<Color>) -> Self {
+        Self::builder().base_color(color).unlit().build()
+    }
+
+    /// Lit material showing `texture` as its base color.
+    pub fn textured(texture: Handle<TextureData>) -> Self {
+        Self::builder().diffuse_texture(texture).build()
+    }
+}
+
+/// Builds a MaterialData from code, see `AssetServer::create_material`.
+/// Values are brought into their valid range by `build`.
+#[derive(Clone, Debug, Default)]
+pub struct MaterialBuilder {
+    data: MaterialData,
+}
+
+impl MaterialBuilder {
+    /// Arrays are taken as linear RGBA; use `Color::srgb` or `Color::hex` for picked colors.
+    pub fn base_color(mut self, color: impl Into<Color>) -> Self {
+        self.data.settings.base_color = color.into();
+        self
+    }
+
+    pub fn roughness(mut self, roughness: f32) -> Self {
+        self.data.settings.roughness = roughness;
+        self
+    }
+
+    pub fn metallic(mut self, metallic: f32) -> Self {
+        self.data.settings.metallic = metallic;
+        self
+    }
+
+    /// Emission (arrays are linear RGB) times `strength`, which can go past 1.0 for bloom.
+    pub fn emissive(mut self, color: impl Into<Color>, strength: f32) -> Self {
+        self.data.settings.emissive = color.into();
+        self.data.settings.emissive_strength = strength;
+        self
+    }
+
+    pub fn alpha_cutoff(mut self, cutoff: f32) -> Self {
+        self.data.settings.alpha_cutoff = Some(cutoff);
+        self
+    }
+
+    pub fn double_sided(mut self) -> Self {
+        self.data.settings.double_sided = true;
+        self
+    }
+
+    pub fn unlit(mut self) -> Self {
+        self.data.settings.unlit = true;
+        self
+    }
+
+    pub fn wind(mut self, wind: MaterialWind) -> Self {
+        self.data.settings.wind = Some(wind);
+        self
+    }
+
+    pub fn diffuse_texture(mut self, texture: Handle<TextureData>) -> Self {
+        self.data.diffuse_texture = Some(texture);
+        self
+    }
+
+    pub fn normal_texture(mut self, texture: Handle<TextureData>) -> Self {
+        self.data.normal_texture = Some(texture);
+        self
+    }
+
+    pub fn metallic_roughness_texture(mut self, texture: Handle<TextureData>) -> Self {
+        self.data.metallic_roughness_texture = Some(texture);
+        self
+    }
+
+    pub fn occlusion_texture(mut self, texture: Handle<TextureData>) -> Self {
+        self.data.occlusion_texture = Some(texture);
+        self
+    }
+
+    pub fn build(mut self) -> MaterialData {
+        let settings = &mut self.data.settings;
+        settings.base_color = settings
+            .base_color
+            .to_linear()
+            .map(|channel| sanitize(channel, 0.0, 1.0))
+            .into();
+        settings.roughness = sanitize(settings.roughness, 0.0, 1.0);
+        settings.metallic = sanitize(settings.metallic, 0.0, 1.0);
+        settings.alpha_cutoff = settings.alpha_cutoff.map(|cutoff| sanitize(cutoff, 0.0, 1.0));
+        settings.emissive = settings
+            .emissive
+            .to_linear_rgb()
+            .map(|channel| sanitize(channel, 0.0, f32::MAX))
+            .into();
+        settings.emissive_strength = sanitize(settings.emissive_strength, 0.0, f32::MAX);
+        if let Some(wind) = &mut settings.wind {
+            wind.strength = sanitize(wind.strength, 0.0, f32::MAX);
+            wind.frequency = sanitize(wind.frequency, 0.0, f32::MAX);
+            wind.branch = sanitize(wind.branch, 0.0, f32::MAX);
+            wind.flutter = sanitize(wind.flutter, 0.0, f32::MAX);
+        }
+        self.data
+    }
+}
+
+// NaN would poison the shading, so it becomes the lower bound
+fn sanitize(value: f32, min: f32, max: f32) -> f32 {
+    if value.is_nan() { min } else { value.clamp(min, max) }
+}
+
+/// A material as saved by the material editor, in a `.material.ron` file, and read
+/// back by `AssetServer::load_material`. Textures are referenced by asset path.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaterialFile {
+    pub settings: MaterialSettings,
+    pub diffuse_texture: Option<String>,
+    pub normal_texture: Option<String>,
+    pub metallic_roughness_texture: Option<String>,
+    pub occlusion_texture: Option<String>,
+}
+
+impl MaterialFile {
+    pub fn to_ron(&self) -> String {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .expect("material file serialize")
+    }
+
+    pub fn from_ron(text: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::from_str(text)
+    }
+}
@@ -0,0 +1,391 @@
+use std::{collections::HashMap, fmt};
+
+use glam::Vec3;
+
+use crate::assets::MeshData;
+
+/// Most vertices a mesh can have and still be drawn with 16-bit indices.
+pub const MAX_U16_VERTICES: usize = u16::MAX as usize + 1;
+
+/// Width of a mesh's indices on the GPU. MeshData always keeps u32 indices; the
+/// renderer narrows them when the mesh is small enough, halving the index bandwidth.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum IndexFormat {
+    U16,
+    #[default]
+    U32,
+}
+
+impl IndexFormat {
+    /// U16 below 65536 vertices, U32 from there on.
+    pub fn for_vertex_count(vertices: usize) -> Self {
+        if vertices < MAX_U16_VERTICES {
+            IndexFormat::U16
+        } else {
+            IndexFormat::U32
+        }
+    }
+
+    /// Bytes per index.
+    pub fn size(self) -> usize {
+        match self {
+            IndexFormat::U16 => 2,
+            IndexFormat::U32 => 4,
+        }
+    }
+}
+
+impl fmt::Display for IndexFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            IndexFormat::U16 => "u16",
+            IndexFormat::U32 => "u32",
+        })
+    }
+}
+
+/// What `MeshData::repair` found and fixed in a mesh. Imported meshes keep theirs in
+/// `MeshData::repair_report`, for the import log and the debug UI.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MeshRepairReport {
+    /// Vertices with a NaN or infinite position, removed with their triangles.
+    pub removed_vertices: usize,
+    /// Vertices whose NaN or infinite normal or UV was reset.
+    pub fixed_attributes: usize,
+    /// Triangles using a removed vertex, an index past the vertex list or cut short
+    /// at the end of the index list.
+    pub invalid_triangles: usize,
+    /// Triangles repeating an index or with no area.
+    pub degenerate_triangles: usize,
+    /// Edges shared by more than two triangles. Left as they are: they draw fine but
+    /// break tools that walk the surface (e.g. simplification).
+    pub non_manifold_edges: usize,
+    /// Triangles left.
+    pub triangles: usize,
+    pub index_format: IndexFormat,
+}
+
+impl MeshRepairReport {
+    /// Nothing was removed or changed.
+    pub fn is_clean(&self) -> bool {
+        self.removed_vertices == 0
+            && self.fixed_attributes == 0
+            && self.invalid_triangles == 0
+            && self.degenerate_triangles == 0
+    }
+
+    pub fn removed_triangles(&self) -> usize {
+        self.invalid_triangles + self.degenerate_triangles
+    }
+}
+
+impl fmt::Display for MeshRepairReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} triangles, {} indices",
+            self.triangles, self.index_format
+        )?;
+        if self.removed_vertices > 0 {
+            write!(f, ", {} non-finite vertices removed", self.removed_vertices)?;
+        }
+        if self.fixed_attributes > 0 {
+            write!(f, ", {} normals/UVs reset", self.fixed_attributes)?;
+        }
+        if self.invalid_triangles > 0 {
+            write!(f, ", {} invalid triangles removed", self.invalid_triangles)?;
+        }
+        if self.degenerate_triangles > 0 {
+            write!(
+                f,
+                ", {} degenerate triangles removed",
+                self.degenerate_triangles
+            )?;
+        }
+        if self.non_manifold_edges > 0 {
+            write!(f, ", {} non-manifold edges", self.non_manifold_edges)?;
+        }
+        Ok(())
+    }
+}
+
+impl MeshData {
+    /// Fixes what would otherwise draw garbage or fail GPU validation: vertices with a
+    /// NaN or infinite position are removed (indices are remapped, their triangles
+    /// dropped), non-finite normals and UVs are reset, and triangles that are out of
+    /// range, repeat an index or have no area are removed. Bounds are recomputed.
+    pub fn repair(&mut self) -> MeshRepairReport {
+        let mut report = MeshRepairReport::default();
+
+        // 1. Vertices, remembering where each one moved to
+        let mut remap = vec![None; self.vertices.len()];
+        let mut kept = 0;
+        for (index, mut vertex) in std::mem::take(&mut self.vertices).into_iter().enumerate() {
+            if !vertex.position.iter().all(|value| value.is_finite()) {
+                report.removed_vertices += 1;
+                continue;
+            }
+            let mut fixed = false;
+            if !vertex.normal.iter().all(|value| value.is_finite()) {
+                vertex.normal = [0.0, 1.0, 0.0];
+                fixed = true;
+            }
+            if !vertex.uv.iter().all(|value| value.is_finite()) {
+                vertex.uv = [0.0, 0.0];
+                fixed = true;
+            }
+            if !vertex.wind_weight.is_finite() {
+                vertex.wind_weight = 0.0;
+                fixed = true;
+            }
+            report.fixed_attributes += fixed as usize;
+            remap[index] = Some(kept);
+            self.vertices.push(vertex);
+            kept += 1;
+        }
+
+        // 2. Triangles
+        let triangles = self.indices.chunks(3);
+        let mut indices = Vec::with_capacity(self.indices.len());
+        for triangle in triangles {
+            let corners = match triangle {
+                [a, b, c] => [*a, *b, *c].map(|index| remap.get(index as usize).copied().flatten()),
+                _ => [None; 3],
+            };
+            let [Some(a), Some(b), Some(c)] = corners else {
+                report.invalid_triangles += 1;
+                continue;
+            };
+            if a == b || b == c || a == c || zero_area(self, [a, b, c]) {
+                report.degenerate_triangles += 1;
+                continue;
+            }
+            indices.extend([a, b, c]);
+        }
+        self.indices = indices;
+
+        report.non_manifold_edges = non_manifold_edges(&self.indices);
+        report.triangles = self.indices.len() / 3;
+        report.index_format = self.index_format();
+        self.aabb = MeshData::compute_aabb(&self.vertices);
+        report
+    }
+
+    /// Index width the renderer uploads this mesh with, from its vertex count.
+    pub fn index_format(&self) -> IndexFormat {
+        IndexFormat::for_vertex_count(self.vertices.len())
+    }
+}
+
+// No area relative to the triangle's size, so tiny valid triangles are kept
+fn zero_area(mesh: &MeshData, [a, b, c]: [u32; 3]) -> bool {
+    let position = |index: u32| Vec3::from_array(mesh.vertices[index as usize].position);
+    let (ab, ac) = (position(b) - position(a), position(c) - position(a));
+    let longest = ab.length_squared().max(ac.length_squared());
+    ab.cross(ac).length_squared() <= longest * longest * 1e-12
+}
+
+fn non_manifold_edges(indices: &[u32]) -> usize {
+    let mut edges: HashMap<(u32, u32), u32> = HashMap::new();
+    for triangle in indices.chunks_exact(3) {
+        for (a, b) in [
+            (triangle[0], triangle[1]),
+            (triangle[1], triangle[2]),
+            (triangle[2], triangle[0]),
+        ] {
+            *edges.entry((a.min(b), a.max(b))).or_default() += 1;
+        }
+    }
+    edges.values().filter(|&&count| count > 2).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use catalyst_core::logging::{LogSettings, init_logging};
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        asset_server::parse_scene,
+        assets::Vertex,
+        import::ImportSettings,
+        io::{AssetIoError, AssetReader},
+    };
+
+    const PATH: &str = "corrupted.glb";
+
+    struct MemoryReader(Vec<u8>);
+
+    impl AssetReader for MemoryReader {
+        fn read(&self, _path: &str) -> Result<Vec<u8>, AssetIoError> {
+            Ok(self.0.clone())
+        }
+
+        fn exists(&self, _path: &str) -> bool {
+            true
+        }
+    }
+
+    fn pad(bytes: &mut Vec<u8>, with: u8) {
+        while bytes.len() % 4 != 0 {
+            bytes.push(with);
+        }
+    }
+
+    // A unit quad (vertices 0-3) plus the kind of garbage broken exporters write
+    fn corrupted_glb() -> Vec<u8> {
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [f32::NAN, 0.0, 0.0],
+            [0.0, 0.0, 1.0],
+            [1.0, 0.0, 1.0],
+            [2.0, 0.0, 1.0],
+        ];
+        let indices: [u32; 18] = [
+            0, 1, 2, 0, 2, 3, // the quad
+            4, 0, 1, // uses the NaN vertex
+            0, 0, 1, // repeats an index
+            5, 6, 7, // three points on a line
+            0, 1, 99, // past the vertex list
+        ];
+
+        let mut bin = Vec::new();
+        for value in positions.iter().flatten() {
+            bin.extend_from_slice(&value.to_le_bytes());
+        }
+        let positions_length = bin.len();
+        for index in indices {
+            bin.extend_from_slice(&index.to_le_bytes());
+        }
+
+        let document = json!({
+            "asset": { "version": "2.0" },
+            "scene": 0,
+            "scenes": [{ "nodes": [0] }],
+            "nodes": [{ "name": "Broken", "mesh": 0 }],
+            "meshes": [{
+                "name": "Broken",
+                "primitives": [{ "attributes": { "POSITION": 0 }, "indices": 1 }],
+            }],
+            "accessors": [
+                // The exporter skipped the NaN when it wrote the bounds
+                { "bufferView": 0, "componentType": 5126, "count": positions.len(), "type": "VEC3",
+                  "min": [0.0, 0.0, 0.0], "max": [2.0, 1.0, 1.0] },
+                { "bufferView": 1, "componentType": 5125, "count": indices.len(), "type": "SCALAR" },
+            ],
+            "bufferViews": [
+                { "buffer": 0, "byteOffset": 0, "byteLength": positions_length },
+                { "buffer": 0, "byteOffset": positions_length, "byteLength": indices.len() * 4 },
+            ],
+            "buffers": [{ "byteLength": bin.len() }],
+        });
+
+        let mut json = serde_json::to_vec(&document).expect("json serializes");
+        pad(&mut json, b' ');
+
+        let length = 12 + 8 + json.len() + 8 + bin.len();
+        let mut glb = Vec::with_capacity(length);
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&(length as u32).to_le_bytes());
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"JSON");
+        glb.extend_from_slice(&json);
+        glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"BIN\0");
+        glb.extend_from_slice(&bin);
+        glb
+    }
+
+    fn vertex(x: f32, y: f32, z: f32) -> Vertex {
+        Vertex {
+            position: [x, y, z],
+            normal: [0.0, 0.0, 1.0],
+            uv: [0.0, 0.0],
+            wind_weight: 0.0,
+        }
+    }
+
+    #[test]
+    fn a_corrupted_mesh_comes_out_drawable() {
+        let log = init_logging(&LogSettings::default());
+
+        let reader = MemoryReader(corrupted_glb());
+        let (_, _, _, meshes) = parse_scene(PATH, &reader, &ImportSettings::default(), None)
+            .expect("the corrupted GLB still parses");
+        assert_eq!(meshes.len(), 1);
+        let (_, mesh) = &meshes[0];
+
+        // The NaN vertex is removed, only the quad's two triangles are left
+        assert_eq!(mesh.vertices.len(), 7);
+        assert!(
+            mesh.vertices
+                .iter()
+                .all(|vertex| vertex.position.iter().all(|value| value.is_finite()))
+        );
+        assert_eq!(mesh.indices, [0, 1, 2, 0, 2, 3]);
+        assert!(
+            mesh.indices
+                .iter()
+                .all(|&index| (index as usize) < mesh.vertices.len())
+        );
+        // The bounds are recomputed without the NaN
+        assert_eq!(mesh.aabb.min, Vec3::ZERO);
+        assert_eq!(mesh.aabb.max, Vec3::new(2.0, 1.0, 1.0));
+
+        let report = mesh.repair_report.expect("the mesh was repaired");
+        assert_eq!(report.removed_vertices, 1);
+        assert_eq!(report.invalid_triangles, 2);
+        assert_eq!(report.degenerate_triangles, 2);
+        assert_eq!(report.triangles, 2);
+        assert!(!report.is_clean());
+        assert_eq!(report.index_format, IndexFormat::U16);
+        assert_eq!(mesh.index_format(), IndexFormat::U16);
+
+        let warnings: Vec<String> = log
+            .since(0)
+            .into_iter()
+            .filter(|line| line.level == log::Level::Warn && line.message.contains(PATH))
+            .map(|line| line.message)
+            .collect();
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert!(warnings[0].contains("Broken"));
+        assert!(warnings[0].contains("1 non-finite vertices removed"));
+    }
+
+    #[test]
+    fn non_manifold_edges_are_reported_and_left_alone() {
+        // A fin: three triangles on the edge 0-1
+        let mut fin = MeshData::new(
+            vec![
+                vertex(0.0, 0.0, 0.0),
+                vertex(1.0, 0.0, 0.0),
+                vertex(0.5, 1.0, 0.0),
+                vertex(0.5, -1.0, 0.0),
+                vertex(0.5, 0.0, 1.0),
+            ],
+            vec![0, 1, 2, 1, 0, 3, 0, 1, 4],
+        );
+        let report = fin.repair();
+        assert_eq!(report.non_manifold_edges, 1);
+        assert!(report.is_clean());
+        assert_eq!(fin.indices.len(), 9);
+    }
+
+    #[test]
+    fn the_index_format_follows_the_vertex_count() {
+        let grid = |count: usize| {
+            MeshData::new(
+                (0..count).map(|i| vertex(i as f32, 0.0, 0.0)).collect(),
+                Vec::new(),
+            )
+        };
+        assert_eq!(grid(MAX_U16_VERTICES - 1).index_format(), IndexFormat::U16);
+        assert_eq!(grid(MAX_U16_VERTICES).index_format(), IndexFormat::U32);
+        assert_eq!(IndexFormat::U16.size(), 2);
+        assert_eq!(IndexFormat::U32.size(), 4);
+    }
+}
@@ -1,4 +1,6 @@
-#[derive(serde::Deserialize, Clone, Debug)]
+use catalyst_core::physics::{CollisionLayerError, CollisionLayers};
+
+#[derive(serde::Deserialize, Clone, Debug, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum PhysicsBody {
     Static,
@@ -19,7 +21,7 @@ impl Into<catalyst_core::physics::PhysicsBody> for PhysicsBody {
     }
 }
 
-#[derive(serde::Deserialize, Clone, Debug)]
+#[derive(serde::Deserialize, Clone, Debug, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum PhysicsShape {
     Box,
@@ -31,12 +33,32 @@ pub enum PhysicsShape {
     Unknown,
 }
 
-#[derive(serde::Deserialize, Clone, Debug)]
+/// `physics_layer` / `physics_mask` extra: raw bits, or layer names resolved
+/// through CollisionLayers (`"player"`, `["world", "enemy"]`).
+#[derive(serde::Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum LayerExtra {
+    Bits(u32),
+    Name(String),
+    Names(Vec<String>),
+}
+
+impl LayerExtra {
+    pub fn resolve(&self, layers: &CollisionLayers) -> Result<u32, CollisionLayerError> {
+        match self {
+            LayerExtra::Bits(bits) => Ok(*bits),
+            LayerExtra::Name(name) => layers.layer(name),
+            LayerExtra::Names(names) => layers.layers(names.iter().map(String::as_str)),
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Clone, Debug, PartialEq)]
 pub struct PhysicsExtras {
     pub physics_body: Option<PhysicsBody>,
     pub physics_shape: Option<PhysicsShape>,
-    pub physics_layer: Option<u32>,
-    pub physics_mask: Option<u32>,
+    pub physics_layer: Option<LayerExtra>,
+    pub physics_mask: Option<LayerExtra>,
     pub physics_is_trigger: Option<bool>,
     pub physics_mass: Option<f32>,
     pub physics_gravity_scale: Option<f32>,
@@ -0,0 +1,313 @@
+use std::sync::{Arc, RwLock};
+
+use glam::Vec3;
+use thiserror::Error;
+
+use crate::{
+    assets::{Handle, MeshData},
+    import::ImportSettings,
+    material::{MaterialData, TextureData},
+    scene::SceneData,
+};
+
+/// What a postprocessor knows about the asset it is given.
+#[derive(Clone, Debug)]
+pub struct AssetMetadata {
+    /// Path the asset was loaded from; for the parts of a scene, the scene's.
+    pub path: String,
+    /// Which part of the scene the asset is ("mesh 3", "texture 0"), None for the
+    /// loaded asset itself.
+    pub label: Option<String>,
+    /// Import settings the asset was loaded with.
+    pub settings: ImportSettings,
+}
+
+impl AssetMetadata {
+    pub fn new(path: &str, settings: &ImportSettings) -> Self {
+        Self {
+            path: path.to_owned(),
+            label: None,
+            settings: settings.clone(),
+        }
+    }
+
+    /// Metadata of a part of the same scene.
+    pub fn part(&self, label: String) -> Self {
+        Self {
+            label: Some(label),
+            ..self.clone()
+        }
+    }
+}
+
+/// Returned by a postprocessor to abort the load, which then fails like a parse error.
+#[derive(Error, Debug)]
+#[error("{0}")]
+pub struct PostprocessError(pub String);
+
+impl PostprocessError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self(message.into())
+    }
+}
+
+/// A transform of freshly parsed asset data, see `AssetPlugin::register_postprocessor`.
+pub type Postprocessor<T> =
+    Arc<dyn Fn(&mut T, &AssetMetadata) -> Result<(), PostprocessError> + Send + Sync>;
+
+/// The registered postprocessors of every asset type, shared by the load tasks.
+///
+/// They run on the worker threads after parsing, before the data is sent to the world,
+/// in registration order. They only see the data: the ECS isn't reachable from there.
+/// Assets added from code (`AssetServer::add_mesh` and the like) aren't postprocessed.
+#[derive(Default)]
+pub struct AssetPostprocessors {
+    textures: RwLock<Vec<Postprocessor<TextureData>>>,
+    meshes: RwLock<Vec<Postprocessor<MeshData>>>,
+    materials: RwLock<Vec<Postprocessor<MaterialData>>>,
+    scenes: RwLock<Vec<Postprocessor<SceneData>>>,
+}
+
+/// Asset types that can be postprocessed.
+pub trait PostprocessedAsset: Sized + 'static {
+    fn postprocessors(registry: &AssetPostprocessors) -> &RwLock<Vec<Postprocessor<Self>>>;
+}
+
+impl PostprocessedAsset for TextureData {
+    fn postprocessors(registry: &AssetPostprocessors) -> &RwLock<Vec<Postprocessor<Self>>> {
+        &registry.textures
+    }
+}
+
+impl PostprocessedAsset for MeshData {
+    fn postprocessors(registry: &AssetPostprocessors) -> &RwLock<Vec<Postprocessor<Self>>> {
+        &registry.meshes
+    }
+}
+
+impl PostprocessedAsset for MaterialData {
+    fn postprocessors(registry: &AssetPostprocessors) -> &RwLock<Vec<Postprocessor<Self>>> {
+        &registry.materials
+    }
+}
+
+impl PostprocessedAsset for SceneData {
+    fn postprocessors(registry: &AssetPostprocessors) -> &RwLock<Vec<Postprocessor<Self>>> {
+        &registry.scenes
+    }
+}
+
+impl AssetPostprocessors {
+    /// Appends `processor` to those of T. Loads already running may miss it.
+    pub fn register<T: PostprocessedAsset>(
+        &self,
+        processor: impl Fn(&mut T, &AssetMetadata) -> Result<(), PostprocessError>
+        + Send
+        + Sync
+        + 'static,
+    ) {
+        T::postprocessors(self)
+            .write()
+            .expect("postprocessors poisoned")
+            .push(Arc::new(processor));
+    }
+
+    pub fn count<T: PostprocessedAsset>(&self) -> usize {
+        T::postprocessors(self).read().expect("postprocessors poisoned").len()
+    }
+
+    /// Runs the postprocessors of T on `data` in registration order, stopping at the
+    /// first error.
+    pub fn run<T: PostprocessedAsset>(
+        &self,
+        data: &mut T,
+        metadata: &AssetMetadata,
+    ) -> Result<(), PostprocessError> {
+        // Cloned out so a slow processor doesn't hold the lock against registration
+        let processors = T::postprocessors(self)
+            .read()
+            .expect("postprocessors poisoned")
+            .clone();
+        for processor in &processors {
+            processor(data, metadata)?;
+        }
+        Ok(())
+    }
+
+    /// Runs every postprocessor on the parts of a scene, then on the scene itself.
+    /// Errors name the part that failed.
+    pub fn run_scene(
+        &self,
+        scene: &mut SceneData,
+        textures: &mut [(Handle<TextureData>, TextureData)],
+        materials: &mut [(Handle<MaterialData>, MaterialData)],
+        meshes: &mut [(Handle<MeshData>, MeshData)],
+        metadata: &AssetMetadata,
+    ) -> Result<(), PostprocessError> {
+        let failed = |label: &str, error: PostprocessError| {
+            PostprocessError(format!("{}: {}", label, error))
+        };
+        for (index, (_, data)) in textures.iter_mut().enumerate() {
+            let label = format!("texture {}", index);
+            self.run(data, &metadata.part(label.clone()))
+                .map_err(|error| failed(&label, error))?;
+        }
+        for (index, (_, data)) in materials.iter_mut().enumerate() {
+            let label = format!("material {}", index);
+            self.run(data, &metadata.part(label.clone()))
+                .map_err(|error| failed(&label, error))?;
+        }
+        for (index, (_, data)) in meshes.iter_mut().enumerate() {
+            let label = format!("mesh {}", index);
+            self.run(data, &metadata.part(label.clone()))
+                .map_err(|error| failed(&label, error))?;
+        }
+        self.run(scene, metadata)
+    }
+}
+
+/// Mesh postprocessor recomputing smooth normals when every normal of the mesh is zero,
+/// as some exporters write them. Normals are the area weighted average of the faces
+/// around each vertex. Register it with
+/// `AssetPlugin::register_postprocessor(app, recompute_degenerate_normals)`.
+pub fn recompute_degenerate_normals(
+    mesh: &mut MeshData,
+    _metadata: &AssetMetadata,
+) -> Result<(), PostprocessError> {
+    let degenerate = mesh
+        .vertices
+        .iter()
+        .all(|vertex| Vec3::from_array(vertex.normal) == Vec3::ZERO);
+    if !degenerate || mesh.vertices.is_empty() {
+        return Ok(());
+    }
+
+    let mut normals = vec![Vec3::ZERO; mesh.vertices.len()];
+    for triangle in mesh.indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|index| index as usize);
+        if a.max(b).max(c) >= normals.len() {
+            return Err(PostprocessError::new(format!(
+                "index {} out of {} vertices",
+                a.max(b).max(c),
+                normals.len()
+            )));
+        }
+        let position = |index: usize| Vec3::from_array(mesh.vertices[index].position);
+        // Not normalized: the cross product's length is twice the face's area
+        let face = (position(b) - position(a)).cross(position(c) - position(a));
+        normals[a] += face;
+        normals[b] += face;
+        normals[c] += face;
+    }
+
+    for (vertex, normal) in mesh.vertices.iter_mut().zip(normals) {
+        // Vertices of no (or only zero-area) faces get the parser's default
+        vertex.normal = normal.try_normalize().unwrap_or(Vec3::Y).to_array();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+    use crate::{
+        assets::Vertex,
+        material::{TextureFormat, TextureType},
+    };
+
+    fn texture() -> TextureData {
+        TextureData {
+            name: "brick".to_string(),
+            width: 1,
+            height: 1,
+            pixels: TextureType::LDR(vec![255, 255, 255, 255]),
+            format: TextureFormat::Rgba8Unorm,
+            mips: false,
+        }
+    }
+
+    // A unit quad in the XZ plane facing up, with the given normal on every vertex
+    fn quad(normal: [f32; 3]) -> MeshData {
+        let vertex = |position: [f32; 3]| Vertex {
+            position,
+            normal,
+            uv: [0.0; 2],
+            wind_weight: 0.0,
+        };
+        MeshData::new(
+            vec![
+                vertex([0.0, 0.0, 0.0]),
+                vertex([0.0, 0.0, 1.0]),
+                vertex([1.0, 0.0, 1.0]),
+                vertex([1.0, 0.0, 0.0]),
+            ],
+            vec![0, 1, 2, 0, 2, 3],
+        )
+    }
+
+    fn texture_metadata() -> AssetMetadata {
+        AssetMetadata::new("textures/brick.png", &ImportSettings::default())
+    }
+
+    #[test]
+    fn postprocessors_run_in_registration_order() {
+        let processors = AssetPostprocessors::default();
+        for step in ["first", "second", "third"] {
+            processors.register::<TextureData>(move |texture, _| {
+                texture.name.push_str(&format!(" {}", step));
+                Ok(())
+            });
+        }
+        let mut data = texture();
+        processors.run(&mut data, &texture_metadata()).unwrap();
+        assert_eq!(data.name, "brick first second third");
+    }
+
+    #[test]
+    fn an_error_stops_the_chain() {
+        let processors = AssetPostprocessors::default();
+        processors.register::<TextureData>(|texture, metadata| {
+            if metadata.path.ends_with(".png") && texture.width < 4 {
+                return Err(PostprocessError::new("too small"));
+            }
+            Ok(())
+        });
+        let ran_after = Arc::new(AtomicBool::new(false));
+        let flag = ran_after.clone();
+        processors.register::<TextureData>(move |_, _| {
+            flag.store(true, Ordering::Relaxed);
+            Ok(())
+        });
+
+        assert!(processors.run(&mut texture(), &texture_metadata()).is_err());
+        assert!(!ran_after.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn zero_normals_are_rebuilt_and_others_kept() {
+        let processors = AssetPostprocessors::default();
+        processors.register::<MeshData>(recompute_degenerate_normals);
+        let metadata = AssetMetadata::new("scenes/level.gltf", &ImportSettings::default());
+
+        // Both triangles of the quad face +Y
+        for (source, expected) in [
+            ([0.0; 3], [0.0, 1.0, 0.0]),
+            ([1.0, 0.0, 0.0], [1.0, 0.0, 0.0]),
+        ] {
+            let mut mesh = quad(source);
+            processors.run(&mut mesh, &metadata).unwrap();
+            for vertex in &mesh.vertices {
+                for (a, b) in vertex.normal.iter().zip(expected) {
+                    assert!(
+                        (a - b).abs() <= 1e-5,
+                        "{:?}: got {:?}",
+                        source,
+                        vertex.normal
+                    );
+                }
+            }
+        }
+    }
+}
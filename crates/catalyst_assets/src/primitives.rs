@@ -0,0 +1,182 @@
+use std::f32::consts::PI;
+
+use crate::assets::{MeshData, Vertex};
+
+/// Y-up capsule centered at the origin.
+///
+/// `height` is the length of the cylindrical section only (same convention as
+/// ColliderShape::Capsule), so the full mesh is `height + 2 * radius` tall.
+pub fn capsule(radius: f32, height: f32, segments: u32, rings: u32) -> MeshData {
+    let segments = segments.max(3);
+    let rings = rings.max(1);
+    let half_height = height * 0.5;
+
+    // Rows go from the top pole down to the bottom pole. Each hemisphere has
+    // rings + 1 rows, and the two equator rows are stitched into the cylinder.
+    let rows_per_cap = rings + 1;
+    let total_rows = rows_per_cap * 2;
+
+    let mut vertices = Vec::with_capacity((total_rows * (segments + 1)) as usize);
+
+    for row in 0..total_rows {
+        let (phi, offset) = if row < rows_per_cap {
+            (row as f32 / rings as f32 * PI * 0.5, half_height)
+        } else {
+            let bottom_row = row - rows_per_cap;
+            (
+                PI * 0.5 + bottom_row as f32 / rings as f32 * PI * 0.5,
+                -half_height,
+            )
+        };
+
+        for segment in 0..=segments {
+            let theta = segment as f32 / segments as f32 * PI * 2.0;
+            let normal = [phi.sin() * theta.sin(), phi.cos(), phi.sin() * theta.cos()];
+
+            vertices.push(Vertex {
+                position: [
+                    normal[0] * radius,
+                    normal[1] * radius + offset,
+                    normal[2] * radius,
+                ],
+                normal,
+                uv: [
+                    segment as f32 / segments as f32,
+                    row as f32 / (total_rows - 1) as f32,
+                ],
+                wind_weight: 0.0,
+            });
+        }
+    }
+
+    let stride = segments + 1;
+    let mut indices = Vec::with_capacity(((total_rows - 1) * segments * 6) as usize);
+
+    for row in 0..total_rows - 1 {
+        for segment in 0..segments {
+            let a = row * stride + segment;
+            let b = a + stride;
+            let c = a + 1;
+            let d = b + 1;
+
+            indices.extend_from_slice(&[a, b, c, c, b, d]);
+        }
+    }
+
+    MeshData::new(vertices, indices)
+}
+
+/// Flat square on the XZ plane facing +Y, centered at the origin, split into
+/// `subdivisions` x `subdivisions` quads. Useful as a base for deformed surfaces.
+pub fn grid(size: f32, subdivisions: u32) -> MeshData {
+    let cells = subdivisions.max(1);
+    let row = cells + 1;
+    let h = size * 0.5;
+
+    let mut vertices = Vec::with_capacity((row * row) as usize);
+    for j in 0..row {
+        for i in 0..row {
+            let u = i as f32 / cells as f32;
+            let v = j as f32 / cells as f32;
+            vertices.push(Vertex {
+                position: [u * size - h, 0.0, v * size - h],
+                normal: [0.0, 1.0, 0.0],
+                uv: [u, v],
+                wind_weight: 0.0,
+            });
+        }
+    }
+
+    let mut indices = Vec::with_capacity((cells * cells * 6) as usize);
+    for j in 0..cells {
+        for i in 0..cells {
+            let a = j * row + i;
+            let b = a + 1;
+            let c = a + row;
+            let d = c + 1;
+            indices.extend_from_slice(&[a, c, b, b, c, d]);
+        }
+    }
+
+    MeshData::new(vertices, indices)
+}
+
+/// Flat square on the XZ plane facing +Y, centered at the origin.
+pub fn plane(size: f32) -> MeshData {
+    let h = size * 0.5;
+    let corners = [[-h, -h], [h, -h], [h, h], [-h, h]];
+
+    let vertices = corners
+        .iter()
+        .map(|[x, z]| Vertex {
+            position: [*x, 0.0, *z],
+            normal: [0.0, 1.0, 0.0],
+            uv: [(x / size) + 0.5, (z / size) + 0.5],
+            wind_weight: 0.0,
+        })
+        .collect();
+
+    MeshData::new(vertices, vec![0, 2, 1, 0, 3, 2])
+}
+
+/// Axis-aligned cube centered at the origin with flat shaded faces (4 vertices each).
+pub fn cube(size: f32) -> MeshData {
+    let h = size * 0.5;
+    // Normal, then the two in-face axes (u, v) so that u x v = normal
+    let faces = [
+        ([1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+        ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+        ([0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]),
+        ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ([0.0, 0.0, -1.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ];
+
+    let mut vertices = Vec::with_capacity(24);
+    let mut indices = Vec::with_capacity(36);
+    for (normal, u, v) in faces {
+        let base = vertices.len() as u32;
+        for (su, sv) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+            let position = std::array::from_fn(|axis| (normal[axis] + u[axis] * su + v[axis] * sv) * h);
+            vertices.push(Vertex {
+                position,
+                normal,
+                uv: [su * 0.5 + 0.5, 0.5 - sv * 0.5],
+                wind_weight: 0.0,
+            });
+        }
+        indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+
+    MeshData::new(vertices, indices)
+}
+
+/// Grass tuft: `cards` upright quads crossed around the Y axis, standing on the origin,
+/// with both faces so it shows from every side. Wind weights go from 0 at the ground to
+/// 1 at the tips, for a material with wind; UVs have v = 0 at the top.
+pub fn grass_tuft(width: f32, height: f32, cards: u32) -> MeshData {
+    let cards = cards.max(1);
+    let h = width * 0.5;
+    let mut vertices = Vec::with_capacity(cards as usize * 8);
+    let mut indices = Vec::with_capacity(cards as usize * 12);
+    for card in 0..cards {
+        let angle = card as f32 / cards as f32 * PI;
+        let (sin, cos) = angle.sin_cos();
+        for side in [1.0f32, -1.0] {
+            let base = vertices.len() as u32;
+            let normal = [sin * side, 0.0, cos * side];
+            for (u, v) in [(0.0, 1.0), (1.0, 1.0), (1.0, 0.0), (0.0, 0.0)] {
+                let x = (u * 2.0 - 1.0) * h * side;
+                vertices.push(Vertex {
+                    position: [x * cos, (1.0 - v) * height, -x * sin],
+                    normal,
+                    uv: [u, v],
+                    wind_weight: (1.0 - v) * (1.0 - v),
+                });
+            }
+            indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+    }
+
+    MeshData::new(vertices, indices)
+}
@@ -0,0 +1,646 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::SystemTime,
+};
+
+use catalyst_core::Source;
+use flecs_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    PrewarmAsset,
+    asset_events::{AssetLookup, AssetType, MaterialAsset, MeshAsset, TextureAsset},
+    asset_server::{GltfPayload, SceneLoadOptions},
+    assets::{Handle, MeshData},
+    cache::{FNV_OFFSET, fnv1a},
+    import::ImportSettings,
+    material::{MaterialData, TextureData, TextureType},
+    scene::{SceneData, SceneNode},
+};
+
+/// Hot reloading of loose asset files, in the singleton of the same name and the
+/// "hot_reload" settings section. Off by default: shipped builds don't look at their
+/// files.
+///
+/// Textures, material files and glTF scenes loaded through the AssetServer are looked
+/// at every `poll_interval` seconds, and one whose modification time changed is loaded
+/// again into the same asset, so handles stay valid and the renderer updates the GPU
+/// resources through the same OnSet observers as a first load. Packed assets never
+/// change. The `assets.reload` console command checks right away, even when this is off.
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HotReload {
+    pub enabled: bool,
+    /// Seconds between two looks at the files.
+    pub poll_interval: f32,
+    /// Respawns every node of a reloaded scene instead of updating the spawned ones
+    /// in place.
+    pub respawn_scenes: bool,
+}
+
+impl Default for HotReload {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            poll_interval: 0.5,
+            respawn_scenes: false,
+        }
+    }
+}
+
+/// Emitted on an asset entity after a reload changed it: a texture, material or mesh
+/// updated in place, or a scene, which also gets a SceneDiff.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct AssetReloaded;
+
+/// Left on a scene entity by a reload, for the ScenePlugin to bring the spawned nodes
+/// up to date; it removes the component once done.
+///
+/// The scene's meshes, materials and textures are matched with the previous ones by
+/// their position in the file: those that changed are updated in place, keeping their
+/// entity and handle, ones past the old count are added and the rest removed, which
+/// frees their GPU resources through the usual OnRemove observers.
+#[derive(Component, Clone, Debug, Default)]
+pub struct SceneDiff {
+    /// Nodes whose transform or physics changed, by index into `SceneData::nodes`.
+    pub changed_nodes: Vec<usize>,
+    /// Nodes were added, removed, renamed, moved to another parent or given another
+    /// mesh, material, camera or extras: they can't be matched up with the spawned
+    /// entities, so the scene is spawned again. Also set with `HotReload::respawn_scenes`.
+    pub respawn: bool,
+    pub added: Vec<Entity>,
+    pub changed: Vec<Entity>,
+    pub removed: Vec<Entity>,
+}
+
+/// Which asset uses which, in the singleton of the same name: a scene uses its meshes,
+/// materials and textures, a material its textures. Recorded as assets arrive, so a
+/// reload knows what else to refresh.
+#[derive(Component, Default)]
+pub struct AssetDependencies {
+    children: HashMap<Entity, Vec<Entity>>,
+    parents: HashMap<Entity, Vec<Entity>>,
+    since_poll: f32,
+}
+
+impl AssetDependencies {
+    /// The assets `asset` uses directly.
+    pub fn children(&self, asset: Entity) -> &[Entity] {
+        self.children.get(&asset).map_or(&[], Vec::as_slice)
+    }
+
+    /// The assets using `asset` directly.
+    pub fn parents(&self, asset: Entity) -> &[Entity] {
+        self.parents.get(&asset).map_or(&[], Vec::as_slice)
+    }
+
+    /// Every asset using `asset`, directly or through others, nearest first.
+    pub fn dependents(&self, asset: Entity) -> Vec<Entity> {
+        let mut found = Vec::new();
+        let mut seen = HashSet::from([asset]);
+        let mut next = 0;
+        let mut current = asset;
+        loop {
+            for &parent in self.parents(current) {
+                if seen.insert(parent) {
+                    found.push(parent);
+                }
+            }
+            let Some(&parent) = found.get(next) else {
+                return found;
+            };
+            current = parent;
+            next += 1;
+        }
+    }
+
+    /// Replaces what `parent` uses.
+    pub fn set_children(&mut self, parent: Entity, children: impl IntoIterator<Item = Entity>) {
+        self.remove_children(parent);
+        let mut list: Vec<Entity> = Vec::new();
+        for child in children {
+            if !list.contains(&child) {
+                list.push(child);
+            }
+        }
+        for &child in &list {
+            self.parents.entry(child).or_default().push(parent);
+        }
+        if !list.is_empty() {
+            self.children.insert(parent, list);
+        }
+    }
+
+    /// Forgets `asset`, on both sides.
+    pub fn remove(&mut self, asset: Entity) {
+        self.remove_children(asset);
+        for parent in self.parents.remove(&asset).unwrap_or_default() {
+            if let Some(children) = self.children.get_mut(&parent) {
+                children.retain(|&child| child != asset);
+            }
+        }
+    }
+
+    /// Forgets `asset`, then every asset it used that nothing else uses anymore, directly
+    /// or through others, and returns those.
+    pub fn remove_orphans(&mut self, asset: Entity) -> Vec<Entity> {
+        let mut candidates = self.children(asset).to_vec();
+        self.remove(asset);
+        let mut orphans = Vec::new();
+        let mut seen = HashSet::new();
+        // A texture is looked at again once the material using it was removed
+        while let Some(candidate) = candidates.pop() {
+            if !self.parents(candidate).is_empty() || !seen.insert(candidate) {
+                continue;
+            }
+            candidates.extend_from_slice(self.children(candidate));
+            self.remove(candidate);
+            orphans.push(candidate);
+        }
+        orphans
+    }
+
+    /// How many assets use or are used by others.
+    pub fn tracked(&self) -> usize {
+        self.children
+            .keys()
+            .chain(self.parents.keys())
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    fn remove_children(&mut self, parent: Entity) {
+        for child in self.children.remove(&parent).unwrap_or_default() {
+            if let Some(parents) = self.parents.get_mut(&child) {
+                parents.retain(|&p| p != parent);
+                if parents.is_empty() {
+                    self.parents.remove(&child);
+                }
+            }
+        }
+    }
+
+    // True once `poll_interval` seconds went by since the last time it was
+    pub(crate) fn poll_due(&mut self, dt: f32, poll_interval: f32) -> bool {
+        self.since_poll += dt;
+        if self.since_poll < poll_interval {
+            return false;
+        }
+        self.since_poll = 0.0;
+        true
+    }
+}
+
+/// A file the AssetServer loaded, and how to load it again.
+pub(crate) struct WatchedFile {
+    pub path: String,
+    pub modified: Option<SystemTime>,
+    pub target: ReloadTarget,
+}
+
+#[derive(Clone)]
+pub(crate) enum ReloadTarget {
+    Texture {
+        id: Uuid,
+        overrides: Option<ImportSettings>,
+    },
+    Material {
+        id: Uuid,
+    },
+    Scene {
+        entity: Entity,
+        options: SceneLoadOptions,
+    },
+}
+
+/// The indices of the nodes whose transform or physics differ, or None when the nodes
+/// can't be matched up one to one (see `SceneDiff::respawn`).
+pub fn diff_nodes(old: &[SceneNode], new: &[SceneNode]) -> Option<Vec<usize>> {
+    if old.len() != new.len() {
+        return None;
+    }
+    let mut changed = Vec::new();
+    for (index, (old, new)) in old.iter().zip(new).enumerate() {
+        let physics_kind = |node: &SceneNode| {
+            node.physics.as_ref().map(|physics| {
+                (
+                    physics.physics_body.clone(),
+                    physics.physics_shape.is_some(),
+                )
+            })
+        };
+        let same_structure = old.name == new.name
+            && old.children == new.children
+            && old.mesh_index == new.mesh_index
+            && old.material_index == new.material_index
+            && old.camera_index == new.camera_index
+            && old.extras == new.extras
+            && physics_kind(old) == physics_kind(new);
+        if !same_structure {
+            return None;
+        }
+        if old.transform != new.transform || old.physics != new.physics {
+            changed.push(index);
+        }
+    }
+    Some(changed)
+}
+
+// Every mesh handle of the scene in the order its payload lists them: the meshes,
+// then their LOD levels
+fn payload_meshes(scene: &SceneData) -> Vec<Uuid> {
+    scene
+        .meshes
+        .iter()
+        .map(|mesh| mesh.id)
+        .chain(scene.mesh_lods.iter().flatten().map(|level| level.mesh.id))
+        .collect()
+}
+
+// New handle -> the previous handle at the same position, so reloaded sub-assets land
+// on the entities of the ones they replace
+fn position_remap(old: &SceneData, new: &SceneData) -> HashMap<Uuid, Uuid> {
+    let pairs = |old: Vec<Uuid>, new: Vec<Uuid>| new.into_iter().zip(old).collect::<Vec<_>>();
+    let ids = |handles: &[Handle<TextureData>]| handles.iter().map(|h| h.id).collect();
+    let materials = |handles: &[Handle<MaterialData>]| handles.iter().map(|h| h.id).collect();
+    pairs(ids(&old.textures), ids(&new.textures))
+        .into_iter()
+        .chain(pairs(materials(&old.materials), materials(&new.materials)))
+        .chain(pairs(payload_meshes(old), payload_meshes(new)))
+        .collect()
+}
+
+fn remap<T>(handle: &mut Handle<T>, map: &HashMap<Uuid, Uuid>) {
+    if let Some(&id) = map.get(&handle.id) {
+        *handle = Handle::from_id(id);
+    }
+}
+
+fn remap_material(material: &mut MaterialData, map: &HashMap<Uuid, Uuid>) {
+    for slot in [
+        &mut material.diffuse_texture,
+        &mut material.normal_texture,
+        &mut material.metallic_roughness_texture,
+        &mut material.occlusion_texture,
+    ] {
+        if let Some(handle) = slot {
+            remap(handle, map);
+        }
+    }
+}
+
+pub(crate) fn material_textures(material: &MaterialData) -> impl Iterator<Item = Uuid> + '_ {
+    [
+        &material.diffuse_texture,
+        &material.normal_texture,
+        &material.metallic_roughness_texture,
+        &material.occlusion_texture,
+    ]
+    .into_iter()
+    .flatten()
+    .map(|handle| handle.id)
+}
+
+fn texture_fingerprint(data: &TextureData) -> u64 {
+    let mut hash = fnv1a(FNV_OFFSET, data.name.as_bytes());
+    hash = fnv1a(hash, format!("{:?} {}", data.format, data.mips).as_bytes());
+    hash = fnv1a(hash, &data.width.to_le_bytes());
+    hash = fnv1a(hash, &data.height.to_le_bytes());
+    match &data.pixels {
+        TextureType::LDR(pixels) => fnv1a(hash, pixels),
+        TextureType::HDR(pixels) => pixels
+            .iter()
+            .fold(hash, |hash, value| fnv1a(hash, &value.to_le_bytes())),
+    }
+}
+
+fn material_fingerprint(data: &MaterialData) -> u64 {
+    fnv1a(FNV_OFFSET, format!("{:?}", data).as_bytes())
+}
+
+fn mesh_fingerprint(data: &MeshData) -> u64 {
+    let hash = data.vertices.iter().fold(FNV_OFFSET, |hash, vertex| {
+        let values = vertex
+            .position
+            .iter()
+            .chain(&vertex.normal)
+            .chain(&vertex.uv)
+            .chain([&vertex.wind_weight]);
+        values.fold(hash, |hash, value| fnv1a(hash, &value.to_le_bytes()))
+    });
+    data.indices
+        .iter()
+        .fold(hash, |hash, index| fnv1a(hash, &index.to_le_bytes()))
+}
+
+/// A texture already in the world was loaded again: materials using it rebuild their
+/// bind groups, since theirs point at the replaced GPU texture.
+pub(crate) fn texture_reloaded(world: &World, dependencies: &AssetDependencies, texture: Entity) {
+    let texture = world.entity_from_id(texture);
+    texture.emit(&AssetReloaded);
+    for &parent in dependencies.parents(texture.id()) {
+        let parent = world.entity_from_id(parent);
+        if parent.is_alive() && parent.has(MaterialData::id()) {
+            parent.modified(MaterialData::id());
+        }
+    }
+}
+
+// The entity a sub-asset of a reloaded scene goes on: the one it had when `changed`
+// says its content differs, a new one when it is new, None when nothing changed
+fn reloaded_entity<'a>(
+    world: &'a World,
+    lookup: &mut AssetLookup,
+    scene: EntityView,
+    diff: &mut SceneDiff,
+    id: Uuid,
+    changed: impl FnOnce(EntityView) -> bool,
+) -> Option<EntityView<'a>> {
+    let existing = lookup
+        .map
+        .get(&id)
+        .map(|&entity| world.entity_from_id(entity));
+    match existing.filter(|entity| entity.is_alive()) {
+        Some(entity) if !changed(entity) => None,
+        Some(entity) => {
+            // Uploaded right away like any edit, not through the prewarm queue
+            entity.remove(PrewarmAsset);
+            diff.changed.push(entity.id());
+            Some(entity)
+        }
+        None => {
+            let entity = world
+                .entity_from_id(lookup.entity(id, world))
+                .add((Source, scene.id()));
+            diff.added.push(entity.id());
+            Some(entity)
+        }
+    }
+}
+
+/// Applies a scene loaded again into `scene`, which already has the previous SceneData:
+/// sub-assets are matched up by position and only the changed ones are set again, then
+/// the node differences go into a SceneDiff.
+pub(crate) fn reload_scene(
+    world: &World,
+    lookup: &mut AssetLookup,
+    dependencies: &mut AssetDependencies,
+    scene: EntityView,
+    (mut data, textures, materials, meshes): GltfPayload,
+    respawn: bool,
+) {
+    let previous = scene.get::<&SceneData>(|data| data.clone());
+    let map = position_remap(&previous, &data);
+    data.textures.iter_mut().for_each(|h| remap(h, &map));
+    data.materials.iter_mut().for_each(|h| remap(h, &map));
+    data.meshes.iter_mut().for_each(|h| remap(h, &map));
+    for level in data.mesh_lods.iter_mut().flatten() {
+        remap(&mut level.mesh, &map);
+    }
+
+    let mut diff = SceneDiff::default();
+    for (mut handle, texture) in textures {
+        remap(&mut handle, &map);
+        let fingerprint = texture_fingerprint(&texture);
+        let changed =
+            |e: EntityView| e.try_get::<&TextureData>(texture_fingerprint) != Some(fingerprint);
+        if let Some(entity) = reloaded_entity(world, lookup, scene, &mut diff, handle.id, changed) {
+            entity.set(texture).add((AssetType, TextureAsset));
+        }
+    }
+    for (mut handle, mut material) in materials {
+        remap(&mut handle, &map);
+        remap_material(&mut material, &map);
+        let fingerprint = material_fingerprint(&material);
+        let changed =
+            |e: EntityView| e.try_get::<&MaterialData>(material_fingerprint) != Some(fingerprint);
+        if let Some(entity) = reloaded_entity(world, lookup, scene, &mut diff, handle.id, changed) {
+            let textures: Vec<Entity> = material_textures(&material)
+                .map(|id| lookup.entity(id, world))
+                .collect();
+            dependencies.set_children(entity.id(), textures);
+            entity.add((AssetType, MaterialAsset)).set(material);
+        }
+    }
+    for (mut handle, mesh) in meshes {
+        remap(&mut handle, &map);
+        let fingerprint = mesh_fingerprint(&mesh);
+        let changed = |e: EntityView| e.try_get::<&MeshData>(mesh_fingerprint) != Some(fingerprint);
+        if let Some(entity) = reloaded_entity(world, lookup, scene, &mut diff, handle.id, changed) {
+            entity.add((AssetType, MeshAsset)).set(mesh);
+        }
+    }
+
+    // What the new file doesn't have anymore goes, GPU resources with it
+    let old_ids = previous
+        .textures
+        .iter()
+        .map(|h| h.id)
+        .chain(previous.materials.iter().map(|h| h.id))
+        .chain(payload_meshes(&previous));
+    let kept: HashSet<Uuid> = data
+        .textures
+        .iter()
+        .map(|h| h.id)
+        .chain(data.materials.iter().map(|h| h.id))
+        .chain(payload_meshes(&data))
+        .collect();
+    for id in old_ids.collect::<Vec<_>>() {
+        if kept.contains(&id) {
+            continue;
+        }
+        if let Some(entity) = lookup.map.remove(&id) {
+            dependencies.remove(entity);
+            let entity = world.entity_from_id(entity);
+            if entity.is_alive() {
+                diff.removed.push(entity.id());
+                entity.destruct();
+            }
+        }
+    }
+
+    let children: Vec<Entity> = kept
+        .iter()
+        .filter_map(|id| lookup.map.get(id).copied())
+        .collect();
+    dependencies.set_children(scene.id(), children);
+
+    match diff_nodes(&previous.nodes, &data.nodes) {
+        Some(changed) if !respawn => diff.changed_nodes = changed,
+        _ => diff.respawn = true,
+    }
+    log::info!(
+        "Scene '{}' reloaded: {} assets added, {} changed, {} removed, {}",
+        scene.name(),
+        diff.added.len(),
+        diff.changed.len(),
+        diff.removed.len(),
+        if diff.respawn {
+            "respawning".to_string()
+        } else {
+            format!("{} nodes changed", diff.changed_nodes.len())
+        }
+    );
+
+    for &entity in &diff.changed {
+        world.entity_from_id(entity).emit(&AssetReloaded);
+    }
+    scene.set(data).set(diff);
+    scene.emit(&AssetReloaded);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::Path,
+        sync::{
+            Arc,
+            atomic::{AtomicU32, Ordering},
+        },
+        time::Duration,
+    };
+
+    use catalyst_core::{App, color::Color, transform::Transform};
+    use glam::Vec3;
+
+    use super::*;
+    use crate::{
+        AssetPlugin,
+        asset_server::AssetServer,
+        material::{MaterialFile, MaterialSettings},
+    };
+
+    // Runs frames until `done` or about two seconds went by; loads finish on the IO pool
+    fn update_until(app: &mut App, done: impl Fn(&App) -> bool) -> bool {
+        for _ in 0..200 {
+            app.update();
+            if done(app) {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    fn write_material(path: &Path, base_color: Color, modified: SystemTime) {
+        let file = MaterialFile {
+            settings: MaterialSettings {
+                base_color,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        std::fs::write(path, file.to_ron()).unwrap();
+        // File systems with coarse timestamps would see the same time twice
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_modified(modified))
+            .unwrap();
+    }
+
+    fn base_color(app: &App, entity: Entity) -> Option<Color> {
+        app.world
+            .entity_from_id(entity)
+            .try_get::<&MaterialData>(|material| material.settings.base_color)
+    }
+
+    fn node(name: &str, translation: Vec3) -> SceneNode {
+        SceneNode {
+            name: name.to_string(),
+            transform: Transform {
+                translation,
+                ..Default::default()
+            },
+            mesh_index: Some(0),
+            material_index: Some(0),
+            camera_index: None,
+            children: Vec::new(),
+            physics: None,
+            extras: None,
+        }
+    }
+
+    #[test]
+    fn a_rewritten_material_file_is_loaded_again_into_the_same_entity() {
+        let dir = std::env::temp_dir().join(format!("catalyst_hot_reload_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("crate.material.ron");
+        let red = Color::linear_rgb(1.0, 0.0, 0.0);
+        let blue = Color::linear_rgb(0.0, 0.0, 1.0);
+        let start = SystemTime::now() - Duration::from_secs(60);
+        write_material(&path, red, start);
+
+        let mut app = App::new();
+        app.add_plugin(AssetPlugin);
+        let handle = app
+            .world
+            .get::<&AssetServer>(|server| server.load_material(path.to_str().unwrap()));
+        let entity = app
+            .world
+            .get::<&mut AssetLookup>(|lookup| lookup.entity(handle.id, &app.world));
+        let reloads = Arc::new(AtomicU32::new(0));
+        let counter = reloads.clone();
+        app.world
+            .entity_from_id(entity)
+            .observe::<AssetReloaded>(move || {
+                counter.fetch_add(1, Ordering::Relaxed);
+            });
+        assert!(update_until(&mut app, |app| base_color(app, entity) == Some(red)));
+
+        // An unchanged file isn't loaded again
+        let reloaded = app
+            .world
+            .get::<&AssetServer>(|server| server.reload_changed());
+        assert_eq!(reloaded, 0);
+        assert_eq!(reloads.load(Ordering::Relaxed), 0);
+
+        write_material(&path, blue, start + Duration::from_secs(30));
+        let reloaded = app
+            .world
+            .get::<&AssetServer>(|server| server.reload_changed());
+        assert_eq!(reloaded, 1);
+        assert!(update_until(&mut app, |app| base_color(app, entity) == Some(blue)));
+        assert_eq!(reloads.load(Ordering::Relaxed), 1);
+        // The handle still points at the same entity
+        let entities = app
+            .world
+            .get::<&AssetLookup>(|lookup| lookup.map.get(&handle.id).copied());
+        assert_eq!(entities, Some(entity));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn moved_nodes_change_while_renamed_or_removed_ones_respawn() {
+        let old = vec![node("Crate", Vec3::ZERO), node("Barrel", Vec3::X)];
+        let moved = vec![node("Crate", Vec3::ZERO), node("Barrel", Vec3::Y)];
+        let renamed = vec![node("Crate", Vec3::ZERO), node("Keg", Vec3::X)];
+        assert_eq!(diff_nodes(&old, &old), Some(Vec::new()));
+        assert_eq!(diff_nodes(&old, &moved), Some(vec![1]));
+        assert_eq!(diff_nodes(&old, &renamed), None);
+        assert_eq!(diff_nodes(&old, &old[..1]), None);
+    }
+
+    #[test]
+    fn dependencies_find_direct_and_indirect_users_and_forget_removed_assets() {
+        // scene -> material -> texture
+        let world = World::new();
+        let [scene, material, texture] = [(); 3].map(|_| world.entity().id());
+        let mut dependencies = AssetDependencies::default();
+        dependencies.set_children(scene, [material, texture]);
+        dependencies.set_children(material, [texture, texture]);
+        // Children are listed once
+        assert_eq!(dependencies.children(material), [texture]);
+
+        // In any order
+        let dependents = dependencies.dependents(texture);
+        assert_eq!(dependents.len(), 2);
+        assert!(dependents.contains(&scene) && dependents.contains(&material));
+
+        dependencies.remove(material);
+        assert_eq!(dependencies.parents(texture), [scene]);
+        assert_eq!(dependencies.children(scene), [texture]);
+    }
+}
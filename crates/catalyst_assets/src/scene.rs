@@ -1,9 +1,15 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt, sync::Arc};
 
 use flecs_ecs::prelude::*;
 use catalyst_core::{camera::Camera, physics::PhysicsMaterialDefinition, transform::Transform};
 
-use crate::{assets::{Handle, MeshData}, material::{MaterialData, TextureData}, physics::PhysicsExtras};
+use crate::{
+    LoadScene, Loading, PrewarmScene,
+    assets::{EntityHandle, Handle, MeshData},
+    lod::MeshLodLevel,
+    material::{MaterialData, TextureData},
+    physics::PhysicsExtras,
+};
 
 #[derive(Component, Clone, Debug)]
 pub struct SceneData {
@@ -11,10 +17,94 @@ pub struct SceneData {
     pub materials: Vec<Handle<MaterialData>>,
     pub textures: Vec<Handle<TextureData>>,
     pub physics_materials: HashMap<String, PhysicsMaterialDefinition>,
+    /// Generated LOD chain of each mesh, same order as `meshes`; empty unless the
+    /// scene was loaded with GenerateLods.
+    pub mesh_lods: Vec<Vec<MeshLodLevel>>,
     
     // The Nodes (Entities)
     pub nodes: Vec<SceneNode>, 
-    pub camera: Vec<Camera>
+    pub camera: Vec<Camera>,
+    /// Key of the SceneFilter the scene was loaded with, None when every node was.
+    /// Anything cached from the scene should put it in its `cache_key` parameters,
+    /// so two variants of the same file don't share entries.
+    pub filter: Option<String>,
+}
+
+type NodePredicate = dyn Fn(&str, Option<&serde_json::Value>) -> bool + Send + Sync;
+
+/// Which nodes of a glTF file to load, see `AssetServer::load_scene_filtered`. Put it
+/// next to LoadScene to filter a scene loaded from the world.
+///
+/// It is evaluated on every node before anything is decoded. A node that matches is
+/// loaded with its whole subtree, its parents' transforms baked into its own; the other
+/// nodes are dropped, and so are the meshes, materials and textures no kept node uses.
+#[derive(Component, Clone)]
+pub struct SceneFilter {
+    key: String,
+    predicate: Arc<NodePredicate>,
+}
+
+impl SceneFilter {
+    /// Nodes named like one of `patterns`, where `*` stands for any run of characters:
+    /// `["Tree_*", "Rock_A"]`.
+    pub fn names<S: Into<String>>(patterns: impl IntoIterator<Item = S>) -> Self {
+        let patterns: Vec<String> = patterns.into_iter().map(Into::into).collect();
+        Self {
+            key: format!("names {}", patterns.join(",")),
+            predicate: Arc::new(move |name, _| {
+                patterns
+                    .iter()
+                    .any(|pattern| matches_pattern(pattern, name))
+            }),
+        }
+    }
+
+    /// Nodes `predicate` accepts, from their name and glTF extras. `key` stands for the
+    /// predicate in `SceneData::filter`, so different predicates need different keys.
+    pub fn predicate(
+        key: &str,
+        predicate: impl Fn(&str, Option<&serde_json::Value>) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            key: format!("predicate {}", key),
+            predicate: Arc::new(predicate),
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn matches(&self, name: &str, extras: Option<&serde_json::Value>) -> bool {
+        (self.predicate)(name, extras)
+    }
+}
+
+impl fmt::Debug for SceneFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SceneFilter").field(&self.key).finish()
+    }
+}
+
+/// Whether `name` matches `pattern`, where `*` matches any run of characters
+/// (none included) and everything else only itself.
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let Some((first, rest)) = pattern.split_once('*') else {
+        return pattern == name;
+    };
+    let Some(mut remaining) = name.strip_prefix(first) else {
+        return false;
+    };
+
+    let mut parts: Vec<&str> = rest.split('*').collect();
+    let last = parts.pop().unwrap_or("");
+    for part in parts {
+        match remaining.find(part) {
+            Some(at) => remaining = &remaining[at + part.len()..],
+            None => return false,
+        }
+    }
+    remaining.ends_with(last)
 }
 
 #[derive(Clone, Debug)]
@@ -25,6 +115,270 @@ pub struct SceneNode {
     pub material_index: Option<usize>,
     pub camera_index: Option<usize>,
     pub children: Vec<usize>,
-    pub physics: Option<PhysicsExtras>
+    pub physics: Option<PhysicsExtras>,
+    /// The node's glTF extras (Blender custom properties), for scene node hooks.
+    pub extras: Option<serde_json::Value>,
+}
+
+
+/// Added to a prewarmed scene once every texture, material and mesh it uses is on the GPU.
+/// Observe OnAdd to react to it, or poll `EntityHandle::load_state`.
+#[derive(Component)]
+pub struct SceneReady;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SceneLoadState {
+    /// No load was requested for the entity.
+    NotLoaded,
+    /// The file is still being read and parsed.
+    Loading,
+    /// CPU data arrived, GPU resources are still being uploaded (prewarmed scenes only).
+    Warming,
+    /// Safe to spawn: nothing is left to upload in the frame its entities appear.
+    Ready,
 }
 
+/// Load state of a scene entity (the one carrying AssetSource + LoadScene).
+pub fn scene_load_state(entity: EntityView) -> SceneLoadState {
+    if entity.has(SceneData::id()) {
+        if entity.has(PrewarmScene) && !entity.has(SceneReady) {
+            SceneLoadState::Warming
+        } else {
+            SceneLoadState::Ready
+        }
+    } else if entity.has(LoadScene) || entity.has(Loading) {
+        SceneLoadState::Loading
+    } else {
+        SceneLoadState::NotLoaded
+    }
+}
+
+impl EntityHandle<SceneData> {
+    pub fn load_state(&self, world: &World) -> SceneLoadState {
+        scene_load_state(world.entity_from_id(self.entity))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use glam::Vec3;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        asset_server::{GltfPayload, parse_scene},
+        import::ImportSettings,
+        io::{AssetIoError, AssetReader},
+    };
+
+    struct MemoryReader(Vec<u8>);
+
+    impl AssetReader for MemoryReader {
+        fn read(&self, _path: &str) -> Result<Vec<u8>, AssetIoError> {
+            Ok(self.0.clone())
+        }
+
+        fn exists(&self, _path: &str) -> bool {
+            true
+        }
+    }
+
+    fn png(color: [u8; 4]) -> Vec<u8> {
+        let image = image::RgbaImage::from_pixel(1, 1, image::Rgba(color));
+        let mut bytes = Vec::new();
+        image::DynamicImage::ImageRgba8(image)
+            .write_to(&mut Cursor::new(&mut bytes), image::ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    fn pad(bytes: &mut Vec<u8>, with: u8) {
+        while bytes.len() % 4 != 0 {
+            bytes.push(with);
+        }
+    }
+
+    // "Tree_A" (with a "Leaves" child), "Tree_B" and "Rock_A" under a "Library" root,
+    // each with its own mesh, material and color texture, all sharing one normal map.
+    // One triangle shared by every mesh, then an image per texture
+    fn props_glb() -> Vec<u8> {
+        let mut bin = Vec::new();
+        for value in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+            bin.extend_from_slice(&value.to_le_bytes());
+        }
+        for index in [0u32, 1, 2] {
+            bin.extend_from_slice(&index.to_le_bytes());
+        }
+
+        let images = [
+            ("bark_a", [120, 80, 40, 255]),
+            ("bark_b", [100, 70, 30, 255]),
+            ("rock", [128, 128, 128, 255]),
+            ("normal", [128, 128, 255, 255]),
+        ];
+        let mut views = vec![
+            json!({ "buffer": 0, "byteOffset": 0, "byteLength": 36 }),
+            json!({ "buffer": 0, "byteOffset": 36, "byteLength": 12 }),
+        ];
+        let mut image_json = Vec::new();
+        for (name, color) in images {
+            let bytes = png(color);
+            image_json
+                .push(json!({ "name": name, "bufferView": views.len(), "mimeType": "image/png" }));
+            views.push(json!({ "buffer": 0, "byteOffset": bin.len(), "byteLength": bytes.len() }));
+            bin.extend_from_slice(&bytes);
+            pad(&mut bin, 0);
+        }
+
+        let mesh = |name: &str, material: usize| {
+            json!({
+                "name": name,
+                "primitives": [{ "attributes": { "POSITION": 0 }, "indices": 1, "material": material }],
+            })
+        };
+        let material = |name: &str, image: usize| {
+            json!({
+                "name": name,
+                "pbrMetallicRoughness": { "baseColorTexture": { "index": image } },
+                "normalTexture": { "index": 3 },
+            })
+        };
+        let document = json!({
+            "asset": { "version": "2.0" },
+            "scene": 0,
+            "scenes": [{ "nodes": [0] }],
+            "nodes": [
+                { "name": "Library", "translation": [10.0, 0.0, 0.0], "children": [1, 2, 3] },
+                { "name": "Tree_A", "mesh": 0, "translation": [1.0, 0.0, 0.0], "children": [4], "extras": { "kind": "tree" } },
+                { "name": "Tree_B", "mesh": 1, "extras": { "kind": "tree" } },
+                { "name": "Rock_A", "mesh": 2, "extras": { "kind": "rock" } },
+                { "name": "Leaves", "mesh": 0, "translation": [0.0, 2.0, 0.0] },
+            ],
+            "meshes": [mesh("Tree_A", 0), mesh("Tree_B", 1), mesh("Rock_A", 2)],
+            "materials": [material("bark_a", 0), material("bark_b", 1), material("rock", 2)],
+            "textures": [{ "source": 0 }, { "source": 1 }, { "source": 2 }, { "source": 3 }],
+            "images": image_json,
+            "accessors": [
+                { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                  "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0] },
+                { "bufferView": 1, "componentType": 5125, "count": 3, "type": "SCALAR" },
+            ],
+            "bufferViews": views,
+            "buffers": [{ "byteLength": bin.len() }],
+        });
+
+        let mut json = serde_json::to_vec(&document).unwrap();
+        pad(&mut json, b' ');
+
+        let length = 12 + 8 + json.len() + 8 + bin.len();
+        let mut glb = Vec::with_capacity(length);
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&(length as u32).to_le_bytes());
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"JSON");
+        glb.extend_from_slice(&json);
+        glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"BIN\0");
+        glb.extend_from_slice(&bin);
+        glb
+    }
+
+    fn parse(filter: Option<&SceneFilter>) -> GltfPayload {
+        let reader = MemoryReader(props_glb());
+        parse_scene("props.glb", &reader, &ImportSettings::default(), filter).unwrap()
+    }
+
+    #[test]
+    fn patterns_match_with_a_wildcard_and_exactly_without() {
+        assert!(matches_pattern("Tree_*", "Tree_A"));
+        assert!(matches_pattern("*_A", "Rock_A"));
+        assert!(matches_pattern("T*e*_B", "Tree_B"));
+        assert!(!matches_pattern("Tree_*", "Rock_A"));
+        assert!(!matches_pattern("Tree", "Tree_A"));
+    }
+
+    #[test]
+    fn without_a_filter_everything_is_loaded() {
+        let (scene, textures, materials, meshes) = parse(None);
+        assert_eq!(scene.nodes.len(), 5);
+        assert_eq!(meshes.len(), 3);
+        assert_eq!(materials.len(), 3);
+        assert_eq!(textures.len(), 4);
+        assert!(scene.filter.is_none());
+    }
+
+    #[test]
+    fn a_name_filter_keeps_the_matching_nodes_and_what_they_use() {
+        let trees = SceneFilter::names(["Tree_*"]);
+        let (scene, textures, materials, meshes) = parse(Some(&trees));
+        let names: Vec<&str> = scene.nodes.iter().map(|node| node.name.as_str()).collect();
+        assert_eq!(names, ["Tree_A", "Tree_B", "Leaves"]);
+        assert_eq!((meshes.len(), scene.meshes.len()), (2, 2));
+        assert_eq!((materials.len(), scene.materials.len()), (2, 2));
+
+        // The shared normal map is loaded once, and both materials point to it
+        let mut texture_names: Vec<&str> = textures.iter().map(|(_, t)| t.name.as_str()).collect();
+        texture_names.sort();
+        assert_eq!(texture_names, ["bark_a", "bark_b", "normal"]);
+        for (_, material) in &materials {
+            assert_eq!(material.normal_texture.as_ref(), Some(&textures[2].0));
+            assert!(material.diffuse_texture.is_some());
+        }
+        assert_eq!(scene.filter.as_deref(), Some(trees.key()));
+    }
+
+    #[test]
+    fn filtered_nodes_are_renumbered_and_keep_their_placement() {
+        let (scene, _, _, _) = parse(Some(&SceneFilter::names(["Tree_*"])));
+        // Leaves shares Tree_A's mesh
+        assert_eq!(scene.nodes[0].mesh_index, scene.nodes[2].mesh_index);
+        assert_ne!(scene.nodes[0].mesh_index, scene.nodes[1].mesh_index);
+        for node in &scene.nodes {
+            assert!(
+                node.mesh_index
+                    .is_some_and(|index| index < scene.meshes.len())
+            );
+            assert!(
+                node.material_index
+                    .is_some_and(|index| index < scene.materials.len())
+            );
+        }
+        assert_eq!(scene.nodes[0].children, [2]);
+        assert!(scene.nodes[1].children.is_empty());
+
+        // Kept roots take the dropped Library's placement, children stay local
+        let translations = [
+            Vec3::new(11.0, 0.0, 0.0),
+            Vec3::new(10.0, 0.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+        ];
+        for (node, expected) in scene.nodes.iter().zip(translations) {
+            assert!(node.transform.translation.distance(expected) < 1e-5);
+        }
+    }
+
+    #[test]
+    fn a_predicate_filters_on_the_extras() {
+        let rocks = SceneFilter::predicate("kind rock", |_, extras| {
+            extras.and_then(|extras| extras.get("kind")) == Some(&json!("rock"))
+        });
+        let (scene, textures, materials, meshes) = parse(Some(&rocks));
+        assert_eq!(scene.nodes.len(), 1);
+        assert_eq!(scene.nodes[0].name, "Rock_A");
+        assert_eq!((meshes.len(), materials.len(), textures.len()), (1, 1, 2));
+        assert_ne!(
+            scene.filter.as_deref(),
+            Some(SceneFilter::names(["Tree_*"]).key())
+        );
+    }
+
+    #[test]
+    fn a_filter_matching_nothing_loads_nothing() {
+        let (scene, textures, materials, meshes) = parse(Some(&SceneFilter::names(["Bush_*"])));
+        assert!(scene.nodes.is_empty());
+        assert!(meshes.is_empty() && materials.is_empty() && textures.is_empty());
+    }
+}
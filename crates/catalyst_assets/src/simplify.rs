@@ -0,0 +1,352 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+use glam::{DVec3, Vec3};
+
+use crate::assets::{MeshData, Vertex};
+
+/// Result of `simplify`: the reduced mesh and how far it strays from the source.
+pub struct SimplifiedMesh {
+    pub mesh: MeshData,
+    /// Largest distance a collapse moved the surface, relative to the mesh's bounding
+    /// box diagonal (0.01 = 1% of the mesh size).
+    pub error: f32,
+}
+
+/// Reduces `mesh` towards `target_triangles` with quadric error metric edge collapses.
+///
+/// Vertices collapse onto one of their neighbours, so positions, normals and UVs are
+/// all taken from the source. Vertices on an open border are never moved, and since
+/// UV and normal seams are split vertices, seam edges count as borders and survive
+/// intact. The result can have more triangles than asked for when nothing else
+/// can be collapsed without moving a border or folding a triangle over.
+pub fn simplify(mesh: &MeshData, target_triangles: usize) -> SimplifiedMesh {
+    let mut state = Simplifier::new(mesh);
+    let error = state.run(target_triangles);
+    let diagonal = mesh.aabb.size().length().max(f32::EPSILON);
+
+    SimplifiedMesh {
+        mesh: state.into_mesh(mesh),
+        error: error / diagonal,
+    }
+}
+
+// Symmetric 4x4 quadric, upper triangle
+#[derive(Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    #[rustfmt::skip]
+    fn from_plane(normal: DVec3, distance: f64) -> Self {
+        let (a, b, c, d) = (normal.x, normal.y, normal.z, distance);
+        Self([
+            a * a, a * b, a * c, a * d,
+            b * b, b * c, b * d,
+            c * c, c * d,
+            d * d,
+        ])
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (value, other) in self.0.iter_mut().zip(other.0) {
+            *value += other;
+        }
+    }
+
+    // Sum of squared distances from `p` to the planes in the quadric
+    #[rustfmt::skip]
+    fn error(&self, p: DVec3) -> f64 {
+        let q = &self.0;
+        let (x, y, z) = (p.x, p.y, p.z);
+        (q[0] * x * x + 2.0 * q[1] * x * y + 2.0 * q[2] * x * z + 2.0 * q[3] * x
+            + q[4] * y * y + 2.0 * q[5] * y * z + 2.0 * q[6] * y
+            + q[7] * z * z + 2.0 * q[8] * z
+            + q[9])
+            .max(0.0)
+    }
+}
+
+// Cheapest known collapse of `from`, valid while `from`'s version is unchanged
+struct Candidate {
+    cost: f64,
+    from: u32,
+    to: u32,
+    version: u32,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost == other.cost
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    // Reversed, so the BinaryHeap pops the cheapest collapse first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+struct Simplifier {
+    positions: Vec<DVec3>,
+    triangles: Vec<[u32; 3]>,
+    triangle_alive: Vec<bool>,
+    live_triangles: usize,
+    // Triangles around each vertex; may list dead ones, which are skipped
+    vertex_triangles: Vec<Vec<usize>>,
+    quadrics: Vec<Quadric>,
+    locked: Vec<bool>,
+    removed: Vec<bool>,
+    versions: Vec<u32>,
+    heap: BinaryHeap<Candidate>,
+}
+
+impl Simplifier {
+    fn new(mesh: &MeshData) -> Self {
+        let positions: Vec<DVec3> = mesh
+            .vertices
+            .iter()
+            .map(|vertex| Vec3::from_array(vertex.position).as_dvec3())
+            .collect();
+        let vertex_count = positions.len();
+
+        let triangles: Vec<[u32; 3]> = mesh
+            .indices
+            .chunks_exact(3)
+            .map(|chunk| [chunk[0], chunk[1], chunk[2]])
+            .filter(|t| t.iter().all(|&i| (i as usize) < vertex_count))
+            .filter(|t| t[0] != t[1] && t[1] != t[2] && t[0] != t[2])
+            .collect();
+
+        let mut vertex_triangles = vec![Vec::new(); vertex_count];
+        let mut quadrics = vec![Quadric::default(); vertex_count];
+        for (index, triangle) in triangles.iter().enumerate() {
+            let [a, b, c] = triangle.map(|i| positions[i as usize]);
+            let normal = (b - a).cross(c - a).normalize_or_zero();
+            let plane = Quadric::from_plane(normal, -normal.dot(a));
+            for &vertex in triangle {
+                vertex_triangles[vertex as usize].push(index);
+                quadrics[vertex as usize].add(&plane);
+            }
+        }
+
+        // An edge used by a single triangle is a border, or a seam between split vertices
+        let mut edge_uses: HashMap<(u32, u32), u32> = HashMap::new();
+        for triangle in &triangles {
+            for (a, b) in edges(triangle) {
+                *edge_uses.entry((a.min(b), a.max(b))).or_default() += 1;
+            }
+        }
+        let mut locked = vec![false; vertex_count];
+        for (&(a, b), &uses) in &edge_uses {
+            if uses != 2 {
+                locked[a as usize] = true;
+                locked[b as usize] = true;
+            }
+        }
+
+        let live_triangles = triangles.len();
+        let mut simplifier = Self {
+            positions,
+            triangle_alive: vec![true; triangles.len()],
+            triangles,
+            live_triangles,
+            vertex_triangles,
+            quadrics,
+            locked,
+            removed: vec![false; vertex_count],
+            versions: vec![0; vertex_count],
+            heap: BinaryHeap::new(),
+        };
+
+        for vertex in 0..vertex_count as u32 {
+            simplifier.push_candidate(vertex);
+        }
+        simplifier
+    }
+
+    // Returns the largest distance a collapse moved the surface
+    fn run(&mut self, target_triangles: usize) -> f32 {
+        let mut max_error = 0.0f64;
+
+        while self.live_triangles > target_triangles {
+            let Some(candidate) = self.heap.pop() else {
+                break;
+            };
+            let from = candidate.from as usize;
+            if self.removed[from] || self.versions[from] != candidate.version {
+                continue;
+            }
+
+            // The neighbourhood may have changed in a way that forbids it now
+            if !self.can_collapse(candidate.from, candidate.to) {
+                self.versions[from] += 1;
+                continue;
+            }
+
+            self.collapse(candidate.from, candidate.to);
+            max_error = max_error.max(candidate.cost);
+        }
+
+        max_error.sqrt() as f32
+    }
+
+    fn live_triangles_of(&self, vertex: u32) -> impl Iterator<Item = usize> + '_ {
+        self.vertex_triangles[vertex as usize]
+            .iter()
+            .copied()
+            .filter(|&triangle| self.triangle_alive[triangle])
+    }
+
+    fn neighbours(&self, vertex: u32) -> HashSet<u32> {
+        self.live_triangles_of(vertex)
+            .flat_map(|triangle| self.triangles[triangle])
+            .filter(|&other| other != vertex)
+            .collect()
+    }
+
+    fn push_candidate(&mut self, vertex: u32) {
+        let index = vertex as usize;
+        if self.locked[index] || self.removed[index] {
+            return;
+        }
+
+        let best = self
+            .neighbours(vertex)
+            .into_iter()
+            .filter(|&to| self.can_collapse(vertex, to))
+            .map(|to| {
+                let mut quadric = self.quadrics[index];
+                quadric.add(&self.quadrics[to as usize]);
+                (quadric.error(self.positions[to as usize]), to)
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+
+        if let Some((cost, to)) = best {
+            self.heap.push(Candidate {
+                cost,
+                from: vertex,
+                to,
+                version: self.versions[index],
+            });
+        }
+    }
+
+    fn can_collapse(&self, from: u32, to: u32) -> bool {
+        if self.locked[from as usize] || self.removed[to as usize] {
+            return false;
+        }
+
+        // Link condition: the edge's triangles must be the only ones sharing both
+        // neighbourhoods, otherwise the collapse pinches the surface
+        let shared_triangles = self
+            .live_triangles_of(from)
+            .filter(|&triangle| self.triangles[triangle].contains(&to))
+            .count();
+        if shared_triangles == 0 {
+            return false;
+        }
+        let from_neighbours = self.neighbours(from);
+        let shared_neighbours = self
+            .neighbours(to)
+            .intersection(&from_neighbours)
+            .count();
+        if shared_neighbours != shared_triangles {
+            return false;
+        }
+
+        // No remaining triangle may flip or collapse to a sliver
+        let target = self.positions[to as usize];
+        self.live_triangles_of(from)
+            .filter(|&triangle| !self.triangles[triangle].contains(&to))
+            .all(|triangle| {
+                let corners = self.triangles[triangle].map(|i| self.positions[i as usize]);
+                let moved = self.triangles[triangle]
+                    .map(|i| if i == from { target } else { self.positions[i as usize] });
+                let before = (corners[1] - corners[0]).cross(corners[2] - corners[0]);
+                let after = (moved[1] - moved[0]).cross(moved[2] - moved[0]);
+                let after_length = after.length();
+                after_length > before.length() * 1e-3
+                    && before.normalize_or_zero().dot(after / after_length) > 0.2
+            })
+    }
+
+    fn collapse(&mut self, from: u32, to: u32) {
+        let triangles: Vec<usize> = self.live_triangles_of(from).collect();
+        for triangle in triangles {
+            if self.triangles[triangle].contains(&to) {
+                self.triangle_alive[triangle] = false;
+                self.live_triangles -= 1;
+            } else {
+                for corner in &mut self.triangles[triangle] {
+                    if *corner == from {
+                        *corner = to;
+                    }
+                }
+                self.vertex_triangles[to as usize].push(triangle);
+            }
+        }
+
+        let quadric = self.quadrics[from as usize];
+        self.quadrics[to as usize].add(&quadric);
+        self.removed[from as usize] = true;
+        self.vertex_triangles[from as usize].clear();
+
+        // Every collapse cost that involved this neighbourhood is stale now
+        let mut touched = self.neighbours(to);
+        touched.insert(to);
+        for &vertex in &touched {
+            self.versions[vertex as usize] += 1;
+        }
+        for vertex in touched {
+            self.push_candidate(vertex);
+        }
+    }
+
+    // Live triangles with their vertices renumbered, unused vertices dropped
+    fn into_mesh(self, source: &MeshData) -> MeshData {
+        let mut remap = vec![u32::MAX; source.vertices.len()];
+        let mut vertices = Vec::new();
+        let mut indices = Vec::with_capacity(self.live_triangles * 3);
+
+        for (triangle, alive) in self.triangles.iter().zip(&self.triangle_alive) {
+            if !alive {
+                continue;
+            }
+            for &vertex in triangle {
+                let slot = &mut remap[vertex as usize];
+                if *slot == u32::MAX {
+                    let original = &source.vertices[vertex as usize];
+                    *slot = vertices.len() as u32;
+                    vertices.push(Vertex {
+                        position: original.position,
+                        normal: original.normal,
+                        uv: original.uv,
+                        wind_weight: original.wind_weight,
+                    });
+                }
+                indices.push(*slot);
+            }
+        }
+
+        MeshData::new(vertices, indices)
+    }
+}
+
+fn edges(triangle: &[u32; 3]) -> [(u32, u32); 3] {
+    [
+        (triangle[0], triangle[1]),
+        (triangle[1], triangle[2]),
+        (triangle[2], triangle[0]),
+    ]
+}
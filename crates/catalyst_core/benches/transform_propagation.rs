@@ -0,0 +1,167 @@
+//! Times GlobalTransform propagation on a synthetic 50k node hierarchy.
+//!
+//! Run with: cargo bench -p catalyst_core --bench transform_propagation
+//!
+//! Compares a full serial pass, a full parallel pass and the incremental (dirty subtree)
+//! pass with 1% of the nodes moving every frame. The propagation tests check that the
+//! incremental result matches a full recompute.
+//!
+//! Then times 1k moving entities next to 100k static ones, with and without the
+//! StaticTransform marker, and next to 10k marked ones, which should cost about the
+//! same as 100k.
+
+use std::time::{Duration, Instant};
+
+use catalyst_core::{
+    propagation::{TransformPropagation, TransformTree, transform_propagation_system},
+    transform::{GlobalTransform, StaticTransform, Transform},
+};
+use criterion::{Criterion, criterion_group, criterion_main};
+use flecs_ecs::prelude::*;
+use glam::{Quat, Vec3};
+
+const NODES: usize = 50_000;
+// Direct children of the root, i.e. parallel jobs
+const BRANCHES: usize = 64;
+const FANOUT: usize = 4;
+// One node in MOVING_EVERY gets a new Transform each frame
+const MOVING_EVERY: usize = 100;
+const STATIC: usize = 100_000;
+const DYNAMIC: usize = 1_000;
+
+fn propagation(c: &mut Criterion) {
+    let world = World::new();
+    transform_propagation_system(&world);
+
+    let entities = build_hierarchy(&world);
+    world.progress();
+    println!(
+        "{} nodes, {} jobs",
+        world.get::<&TransformTree>(|tree| tree.len()),
+        world.get::<&TransformTree>(|tree| tree.job_count())
+    );
+
+    let mut group = c.benchmark_group("transform_propagation");
+    for (name, parallel, incremental) in [
+        ("serial", false, false),
+        ("parallel", true, false),
+        ("incremental", true, true),
+    ] {
+        set_mode(&world, parallel, incremental);
+        let mut frame = 0;
+        // Only the propagation is timed, not moving the nodes
+        group.bench_function(name, |b| {
+            b.iter_custom(|frames| {
+                let mut total = Duration::ZERO;
+                for _ in 0..frames {
+                    move_nodes(&world, &entities, frame);
+                    frame += 1;
+
+                    let start = Instant::now();
+                    world.progress();
+                    total += start.elapsed();
+                }
+                total
+            })
+        });
+    }
+    group.finish();
+}
+
+// Root -> BRANCHES children -> the rest as FANOUT-ary trees under those
+fn build_hierarchy(world: &World) -> Vec<Entity> {
+    let mut entities: Vec<Entity> = Vec::with_capacity(NODES);
+
+    for index in 0..NODES {
+        let local = Transform {
+            rotation: Quat::from_rotation_y(0.1),
+            ..Transform::from_xyz(1.0, 0.0, 0.0)
+        };
+        let entity = world.entity().set(local).set(GlobalTransform::default());
+
+        if index > 0 {
+            let parent = if index <= BRANCHES {
+                0
+            } else {
+                (index - BRANCHES - 1) / FANOUT + 1
+            };
+            entity.child_of(entities[parent]);
+        }
+        entities.push(entity.id());
+    }
+
+    entities
+}
+
+fn set_mode(world: &World, parallel: bool, incremental: bool) {
+    world.get::<&mut TransformPropagation>(|settings| {
+        settings.parallel = parallel;
+        settings.incremental = incremental;
+    });
+}
+
+// Only the incremental pass has anything to skip, the full ones recompute regardless
+fn move_nodes(world: &World, entities: &[Entity], frame: usize) {
+    for (index, &entity) in entities.iter().enumerate() {
+        if (index + frame) % MOVING_EVERY == 0 {
+            world
+                .entity_from_id(entity)
+                .get::<&mut Transform>(|transform| transform.rotate_y(0.01));
+        }
+    }
+}
+
+fn static_transforms(c: &mut Criterion) {
+    let mut group = c.benchmark_group("static_transforms");
+    for (name, statics, marked) in [
+        ("100k marked", STATIC, true),
+        ("100k unmarked", STATIC, false),
+        ("10k marked", STATIC / 10, true),
+    ] {
+        let world = World::new();
+        transform_propagation_system(&world);
+        world.get::<&mut TransformPropagation>(|settings| settings.check_static = false);
+
+        for index in 0..statics {
+            let entity = world
+                .entity()
+                .set(Transform::from_xyz(index as f32, 0.0, 0.0))
+                .set(GlobalTransform::default());
+            if marked {
+                entity.add(StaticTransform);
+            }
+        }
+        let dynamic: Vec<Entity> = (0..DYNAMIC)
+            .map(|index| {
+                world
+                    .entity()
+                    .set(Transform::from_xyz(-(index as f32), 0.0, 0.0))
+                    .set(GlobalTransform::default())
+                    .id()
+            })
+            .collect();
+        world.progress();
+
+        group.bench_function(name, |b| {
+            b.iter_custom(|frames| {
+                let mut total = Duration::ZERO;
+                for _ in 0..frames {
+                    for &entity in &dynamic {
+                        world
+                            .entity_from_id(entity)
+                            .get::<&mut Transform>(|transform| transform.translation += Vec3::Z);
+                    }
+
+                    let start = Instant::now();
+                    world.progress();
+                    total += start.elapsed();
+                }
+                total
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, propagation, static_transforms);
+criterion_main!(benches);
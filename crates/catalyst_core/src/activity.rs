@@ -0,0 +1,333 @@
+use flecs_ecs::prelude::*;
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    camera::Camera,
+    importance::{ImportanceBias, effective_distance},
+    transform::GlobalTransform,
+};
+
+/// Opts an entity into activity culling: how far from the nearest camera or
+/// ActivitySource it keeps updating every frame, at a reduced rate, or not at all.
+///
+/// Distances are in world units and grow with the entity's largest scale axis, so
+/// something scaled up twice stays active twice as far out, roughly as long as it
+/// covers the same part of the screen. An ImportanceBias counts as being that much
+/// closer. Leaving a state takes `hysteresis` more distance
+/// than entering it, so entities on a boundary don't flip every frame.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ActivityRange {
+    /// Within this distance the entity is Active.
+    pub full_rate: f32,
+    /// Past this distance it is Asleep; in between it is Reduced.
+    pub sleep: f32,
+    pub hysteresis: f32,
+    /// In the Reduced state, frames between two updates (see ReducedTick).
+    pub reduced_interval: u32,
+    /// Asleep physics bodies also take their colliders out of the broad phase. Nothing
+    /// collides with them until they wake up.
+    pub disable_colliders: bool,
+}
+
+impl Default for ActivityRange {
+    fn default() -> Self {
+        Self {
+            full_rate: 40.0,
+            sleep: 120.0,
+            hysteresis: 5.0,
+            reduced_interval: 4,
+            disable_colliders: false,
+        }
+    }
+}
+
+impl ActivityRange {
+    /// The state at `distance` (already divided by the entity's scale) for an entity
+    /// currently in `current`.
+    pub fn classify(&self, distance: f32, current: ActivityState) -> ActivityState {
+        let full_rate = match current {
+            ActivityState::Active => self.full_rate + self.hysteresis,
+            _ => self.full_rate,
+        };
+        let sleep = match current {
+            ActivityState::Asleep => self.sleep,
+            _ => self.sleep + self.hysteresis,
+        };
+
+        if distance <= full_rate {
+            ActivityState::Active
+        } else if distance <= sleep {
+            ActivityState::Reduced
+        } else {
+            ActivityState::Asleep
+        }
+    }
+}
+
+/// Set on every entity with an ActivityRange by "Update Activity" (PreUpdate). Systems
+/// that only care about Active entities can filter on the Inactive tag instead of
+/// reading this.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ActivityState {
+    #[default]
+    Active,
+    Reduced,
+    Asleep,
+}
+
+/// Tag: activity is measured from this entity too (a listener, a remote player).
+/// Cameras always count.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct ActivitySource;
+
+/// Tag: on entities whose ActivityState is Reduced or Asleep, so opt-in systems can skip
+/// them cheaply with `.without(Inactive)`.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct Inactive;
+
+/// Runs an entity's logic at the rate its ActivityState allows. Keep one per entity and
+/// call `advance` every frame with the frame's delta:
+///
+/// ```ignore
+/// if let Some(dt) = tick.advance(*state, range, time.scaled_delta().as_secs_f32()) {
+///     wander(transform, dt);
+/// }
+/// ```
+///
+/// Active entities run every frame. Reduced ones run every `reduced_interval` frames
+/// with the time accumulated since their last run; start them with `staggered` so they
+/// don't all land on the same frame. Asleep ones never run and the time passes without
+/// them.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct ReducedTick {
+    frame: u32,
+    accumulated: f32,
+}
+
+impl ReducedTick {
+    /// Starts at `offset` frames into the interval, e.g. an entity index, to spread
+    /// reduced updates over the frames.
+    pub fn staggered(offset: u32) -> Self {
+        Self {
+            frame: offset,
+            accumulated: 0.0,
+        }
+    }
+
+    /// The delta to run with this frame, or None to skip it.
+    pub fn advance(
+        &mut self,
+        state: ActivityState,
+        range: &ActivityRange,
+        delta: f32,
+    ) -> Option<f32> {
+        match state {
+            ActivityState::Active => {
+                // Whatever was left from the Reduced state goes in with this frame
+                let delta = self.accumulated + delta;
+                self.accumulated = 0.0;
+                self.frame = 0;
+                Some(delta)
+            }
+            ActivityState::Reduced => {
+                self.accumulated += delta;
+                self.frame = self.frame.wrapping_add(1);
+                if self.frame % range.reduced_interval.max(1) != 0 {
+                    return None;
+                }
+                let delta = self.accumulated;
+                self.accumulated = 0.0;
+                Some(delta)
+            }
+            ActivityState::Asleep => {
+                self.accumulated = 0.0;
+                None
+            }
+        }
+    }
+}
+
+/// Entities per ActivityState this frame, for the debug overlay.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct ActivityStats {
+    pub active: usize,
+    pub reduced: usize,
+    pub asleep: usize,
+}
+
+pub fn activity_system(world: &mut World) {
+    world.component::<ActivityState>();
+    world.component::<ActivitySource>();
+    world.component::<Inactive>();
+    world
+        .component::<ActivityStats>()
+        .add_trait::<flecs::Singleton>();
+    world.set(ActivityStats::default());
+
+    let cameras = world.query::<&GlobalTransform>().with(Camera::id()).build();
+    let sources = world
+        .query::<&GlobalTransform>()
+        .with(ActivitySource::id())
+        .build();
+    let ranged = world
+        .query::<(
+            &ActivityRange,
+            &GlobalTransform,
+            Option<&ActivityState>,
+            Option<&ImportanceBias>,
+        )>()
+        .build();
+
+    // PreUpdate, so gameplay in OnUpdate sees the states for this frame. Positions are
+    // last frame's, which is close enough at these distances.
+    world
+        .system_named::<&mut ActivityStats>("Update Activity")
+        .kind(flecs::pipeline::PreUpdate)
+        .each(move |stats| {
+            let mut origins: Vec<Vec3> = Vec::new();
+            cameras.each(|global| origins.push(global.0.w_axis.truncate()));
+            sources.each(|global| origins.push(global.0.w_axis.truncate()));
+
+            *stats = ActivityStats::default();
+            ranged.each_entity(|entity, (range, global, current, importance)| {
+                let previous = current.copied();
+                let current = previous.unwrap_or_default();
+                // Nothing to measure from (no camera yet): everything stays active
+                let state = if origins.is_empty() {
+                    ActivityState::Active
+                } else {
+                    let position = global.0.w_axis.truncate();
+                    let nearest = origins
+                        .iter()
+                        .map(|origin| origin.distance_squared(position))
+                        .fold(f32::INFINITY, f32::min)
+                        .sqrt();
+                    let nearest = effective_distance(importance, nearest);
+                    let (scale, _, _) = global.0.to_scale_rotation_translation();
+                    range.classify(nearest / scale.max_element().max(1e-3), current)
+                };
+
+                match state {
+                    ActivityState::Active => stats.active += 1,
+                    ActivityState::Reduced => stats.reduced += 1,
+                    ActivityState::Asleep => stats.asleep += 1,
+                }
+
+                // Only on change, so the table moves and observers fire once per transition
+                if previous == Some(state) {
+                    return;
+                }
+                entity.set(state);
+                if state == ActivityState::Active {
+                    entity.remove(Inactive);
+                } else {
+                    entity.add(Inactive);
+                }
+            });
+        });
+
+    world
+        .observer_named::<flecs::OnRemove, &ActivityRange>("Clear Activity")
+        .each_entity(|entity, _| {
+            entity.remove(ActivityState::id());
+            entity.remove(Inactive);
+            entity.remove(ReducedTick::id());
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ActivityState::*, *};
+
+    const DT: f32 = 1.0 / 60.0;
+
+    fn range() -> ActivityRange {
+        ActivityRange {
+            full_rate: 10.0,
+            sleep: 50.0,
+            hysteresis: 2.0,
+            reduced_interval: 4,
+            disable_colliders: false,
+        }
+    }
+
+    fn assert_ran(delta: Option<f32>, expected: f32) {
+        let delta = delta.expect("didn't run");
+        assert!(
+            (delta - expected).abs() < 1e-4,
+            "ran with {}, expected {}",
+            delta,
+            expected
+        );
+    }
+
+    #[test]
+    fn classifies_by_distance_from_any_state() {
+        let range = range();
+        assert_eq!(range.classify(5.0, Asleep), Active);
+        assert_eq!(range.classify(30.0, Active), Reduced);
+        assert_eq!(range.classify(80.0, Active), Asleep);
+    }
+
+    #[test]
+    fn changing_state_takes_the_hysteresis() {
+        let range = range();
+        assert_eq!(range.classify(11.0, Active), Active);
+        assert_eq!(range.classify(11.0, Reduced), Reduced);
+        assert_eq!(range.classify(12.5, Active), Reduced);
+        assert_eq!(range.classify(51.0, Reduced), Reduced);
+        assert_eq!(range.classify(51.0, Asleep), Asleep);
+        assert_eq!(range.classify(49.0, Asleep), Reduced);
+    }
+
+    #[test]
+    fn jitter_within_the_hysteresis_doesnt_flip() {
+        let range = range();
+        // Back and forth over the full rate boundary
+        for step in 0..100 {
+            let distance = 10.0 + (step as f32 * 0.7).sin() * 1.5;
+            assert_eq!(range.classify(distance, Active), Active);
+        }
+    }
+
+    #[test]
+    fn reduced_ticks_run_every_interval_with_the_accumulated_time() {
+        let range = range();
+        let mut tick = ReducedTick::default();
+        assert_ran(tick.advance(Active, &range, DT), DT);
+
+        let runs: Vec<Option<f32>> = (0..8).map(|_| tick.advance(Reduced, &range, DT)).collect();
+        assert_eq!(runs.iter().flatten().count(), 2);
+        for run in runs.into_iter().flatten() {
+            assert_ran(Some(run), DT * 4.0);
+        }
+
+        // Back to Active flushes what was accumulated
+        tick.advance(Reduced, &range, DT);
+        assert_ran(tick.advance(Active, &range, DT), DT * 2.0);
+    }
+
+    #[test]
+    fn asleep_never_runs_and_drops_its_time() {
+        let range = range();
+        let mut tick = ReducedTick::default();
+        for _ in 0..10 {
+            assert_eq!(tick.advance(Asleep, &range, DT), None);
+        }
+        assert_ran(tick.advance(Active, &range, DT), DT);
+    }
+
+    #[test]
+    fn staggered_ticks_run_on_different_frames() {
+        let range = range();
+        let first_runs: Vec<Option<usize>> = (0..4)
+            .map(|offset| {
+                let mut tick = ReducedTick::staggered(offset);
+                (0..4).position(|_| tick.advance(Reduced, &range, DT).is_some())
+            })
+            .collect();
+        assert_eq!(first_runs, [Some(3), Some(2), Some(1), Some(0)]);
+    }
+}
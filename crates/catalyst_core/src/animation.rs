@@ -0,0 +1,717 @@
+use flecs_ecs::prelude::*;
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    color::Color,
+    registry::{ComponentRegistration, ComponentRegistry},
+    sequence::{Easing, Looping},
+    time::Time,
+};
+
+/// A value a track can animate. The field it targets must hold the same kind: a
+/// number, a `[x, y, z]` array or a color (`[r, g, b, a]`, linear).
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PropertyValue {
+    F32(f32),
+    Vec3(Vec3),
+    Color(Color),
+}
+
+impl PropertyValue {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PropertyValue::F32(_) => "f32",
+            PropertyValue::Vec3(_) => "Vec3",
+            PropertyValue::Color(_) => "Color",
+        }
+    }
+
+    /// Straight line from `self` to `other`; colors blend per channel in linear space.
+    /// Values of different kinds don't blend, `self` is kept.
+    pub fn lerp(&self, other: &PropertyValue, t: f32) -> PropertyValue {
+        match (self, other) {
+            (PropertyValue::F32(a), PropertyValue::F32(b)) => PropertyValue::F32(a + (b - a) * t),
+            (PropertyValue::Vec3(a), PropertyValue::Vec3(b)) => PropertyValue::Vec3(a.lerp(*b, t)),
+            (PropertyValue::Color(a), PropertyValue::Color(b)) => {
+                let channel = |a: f32, b: f32| a + (b - a) * t;
+                PropertyValue::Color(Color::linear_rgba(
+                    channel(a.r, b.r),
+                    channel(a.g, b.g),
+                    channel(a.b, b.b),
+                    channel(a.a, b.a),
+                ))
+            }
+            _ => *self,
+        }
+    }
+
+    // Checks that the field currently holds this kind of value, so a track never
+    // changes the shape of a component
+    fn matches(&self, field: &Value) -> bool {
+        let numbers = |count: &[usize]| {
+            field.as_array().is_some_and(|values| {
+                count.contains(&values.len()) && values.iter().all(Value::is_number)
+            })
+        };
+        match self {
+            PropertyValue::F32(_) => field.is_number(),
+            PropertyValue::Vec3(_) => numbers(&[3]),
+            PropertyValue::Color(_) => numbers(&[3, 4]),
+        }
+    }
+
+    fn to_json(self) -> Value {
+        match self {
+            PropertyValue::F32(value) => serde_json::json!(value),
+            PropertyValue::Vec3(value) => serde_json::json!([value.x, value.y, value.z]),
+            PropertyValue::Color(value) => serde_json::json!([value.r, value.g, value.b, value.a]),
+        }
+    }
+}
+
+impl From<f32> for PropertyValue {
+    fn from(value: f32) -> Self {
+        PropertyValue::F32(value)
+    }
+}
+
+// Untyped float literals are f64, so `.key(1.0, 40.0)` needs this
+impl From<f64> for PropertyValue {
+    fn from(value: f64) -> Self {
+        PropertyValue::F32(value as f32)
+    }
+}
+
+impl From<Vec3> for PropertyValue {
+    fn from(value: Vec3) -> Self {
+        PropertyValue::Vec3(value)
+    }
+}
+
+impl From<Color> for PropertyValue {
+    fn from(value: Color) -> Self {
+        PropertyValue::Color(value)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Keyframe {
+    /// Seconds from the start of the timeline.
+    pub time: f32,
+    pub value: PropertyValue,
+}
+
+/// Keyframes for one field of one registered component, e.g. `PointLight` /
+/// `intensity`. Fields are found by name through the ComponentRegistry, so any
+/// component registered with `app.register_component` can be animated, including
+/// ones the engine wasn't compiled against.
+///
+/// `path` is the field's name in the component's serialized form; nested fields are
+/// separated by dots (`settings.base_color`) and array elements are numbers
+/// (`points.2`). A path that doesn't resolve (e.g. a renamed field) logs a warning
+/// naming the entity and path, and the track does nothing until it resolves again.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PropertyTrack {
+    /// Name of a direct child of the timeline's entity to animate; the timeline's
+    /// own entity when None.
+    pub child: Option<String>,
+    /// Registered name of the component, e.g. "PointLight".
+    pub component: String,
+    pub path: String,
+    /// Applied between every two keyframes.
+    pub easing: Easing,
+    keyframes: Vec<Keyframe>,
+    #[serde(skip)]
+    error: Option<String>,
+}
+
+impl PropertyTrack {
+    pub fn new(component: impl Into<String>, path: impl Into<String>) -> Self {
+        Self {
+            component: component.into(),
+            path: path.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Animates the direct child of the timeline's entity with this name instead.
+    pub fn on_child(mut self, name: impl Into<String>) -> Self {
+        self.child = Some(name.into());
+        self
+    }
+
+    pub fn easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    /// Adds a keyframe, kept sorted by time. Every keyframe of a track holds the same
+    /// kind of value: one of another kind is ignored with a warning.
+    pub fn key(mut self, time: f32, value: impl Into<PropertyValue>) -> Self {
+        let value = value.into();
+        if let Some(first) = self.keyframes.first() {
+            if first.value.kind() != value.kind() {
+                log::warn!(
+                    "Track {}.{} animates a {}, its {} keyframe at {}s is ignored",
+                    self.component,
+                    self.path,
+                    first.value.kind(),
+                    value.kind(),
+                    time
+                );
+                return self;
+            }
+        }
+        let index = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.time <= time);
+        self.keyframes.insert(index, Keyframe { time, value });
+        self
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    /// Time of the last keyframe.
+    pub fn end(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+    }
+
+    /// Why the track couldn't be applied the last time it was played, if it couldn't.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Value at `time`: the first keyframe's before it, the last one's after it and
+    /// eased between the two around it otherwise. None without keyframes.
+    pub fn sample(&self, time: f32) -> Option<PropertyValue> {
+        let next = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.time <= time);
+        let Some(after) = self.keyframes.get(next) else {
+            return self.keyframes.last().map(|keyframe| keyframe.value);
+        };
+        let Some(before) = next.checked_sub(1).map(|index| &self.keyframes[index]) else {
+            return Some(after.value);
+        };
+
+        let t = (time - before.time) / (after.time - before.time);
+        Some(before.value.lerp(&after.value, self.easing.apply(t)))
+    }
+}
+
+/// Emitted on an entity when its Timeline played to the end and stopped. Looping
+/// timelines never finish.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct TimelineFinished;
+
+/// Property tracks played together on one clock, advanced by "Play Timelines" in
+/// OnUpdate. The tracks animate the entity the Timeline is on, or its named
+/// children, so one timeline can dim a light while it tints a mesh.
+///
+/// ```ignore
+/// lamp.set(
+///     Timeline::new()
+///         .track(PropertyTrack::new("PointLight", "intensity").key(0.0, 40.0).key(2.0, 0.0))
+///         .track(
+///             PropertyTrack::new("Tint", "color")
+///                 .on_child("Shade")
+///                 .key(0.0, Color::WHITE)
+///                 .key(2.0, Color::hex("#3050ff").unwrap()),
+///         )
+///         .looping(Looping::PingPong),
+/// );
+/// ```
+///
+/// Like sequences, timelines follow gameplay time and freeze while it is paused,
+/// unless `unscaled`. Components are written through the registry, deferred like any
+/// set from a system, so their OnSet observers run. A stopped timeline leaves the
+/// fields alone, except right after `seek` (e.g. scrubbing in the debug UI).
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Timeline {
+    pub tracks: Vec<PropertyTrack>,
+    /// Length in seconds; the last keyframe of any track when None.
+    pub duration: Option<f32>,
+    pub looping: Looping,
+    /// Playback rate, 1.0 for normal speed.
+    pub speed: f32,
+    pub playing: bool,
+    pub unscaled: bool,
+    // Seconds played, kept within one loop cycle
+    clock: f32,
+    #[serde(skip)]
+    seeked: bool,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self {
+            tracks: Vec::new(),
+            duration: None,
+            looping: Looping::Once,
+            speed: 1.0,
+            playing: true,
+            unscaled: false,
+            clock: 0.0,
+            seeked: true,
+        }
+    }
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn track(mut self, track: PropertyTrack) -> Self {
+        self.tracks.push(track);
+        self
+    }
+
+    pub fn looping(mut self, looping: Looping) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    pub fn with_duration(mut self, seconds: f32) -> Self {
+        self.duration = Some(seconds.max(0.0));
+        self
+    }
+
+    /// Runs on real time: ignores the time scale and keeps going while gameplay is
+    /// paused.
+    pub fn unscaled(mut self) -> Self {
+        self.unscaled = true;
+        self
+    }
+
+    /// Starts stopped at the beginning, for `play` later.
+    pub fn paused(mut self) -> Self {
+        self.playing = false;
+        self
+    }
+
+    pub fn play(&mut self) {
+        if self.looping == Looping::Once && self.clock >= self.length() {
+            self.clock = 0.0;
+        }
+        self.playing = true;
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    /// Length in seconds, see `duration`.
+    pub fn length(&self) -> f32 {
+        self.duration.unwrap_or_else(|| {
+            self.tracks
+                .iter()
+                .map(PropertyTrack::end)
+                .fold(0.0, f32::max)
+        })
+    }
+
+    /// Where playback is, in seconds from 0 to `length`. Ping-pong timelines come
+    /// back down on the way back.
+    pub fn position(&self) -> f32 {
+        let length = self.length();
+        self.looping.phase(self.clock, length) * length
+    }
+
+    /// Jumps to `seconds` into the timeline. The tracks are applied on the next
+    /// update even while the timeline is stopped.
+    pub fn seek(&mut self, seconds: f32) {
+        self.clock = seconds.clamp(0.0, self.length());
+        self.seeked = true;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.looping == Looping::Once && self.clock >= self.length()
+    }
+
+    /// Tracks that couldn't be applied, with the reason.
+    pub fn errors(&self) -> impl Iterator<Item = (&PropertyTrack, &str)> {
+        self.tracks
+            .iter()
+            .filter_map(|track| track.error().map(|error| (track, error)))
+    }
+
+    // Moves the clock by `dt` seconds. Returns true when a Once timeline reached its end
+    fn advance(&mut self, dt: f32) -> bool {
+        let length = self.length();
+        self.clock += dt * self.speed.max(0.0);
+        if self.looping == Looping::Once {
+            if self.clock >= length {
+                self.clock = length;
+                return true;
+            }
+        } else {
+            let period = self.looping.period(length);
+            if period > 0.0 {
+                self.clock %= period;
+            }
+        }
+        false
+    }
+
+    // Writes every track's value at the current position. Tracks on the same
+    // component of the same entity are written together, with one set
+    fn apply(&mut self, entity: EntityView, registry: &ComponentRegistry) {
+        let position = self.position();
+        let mut targets: Vec<(Entity, &ComponentRegistration, Value)> = Vec::new();
+        let mut results = Vec::new();
+
+        for (index, track) in self.tracks.iter().enumerate() {
+            let Some(value) = track.sample(position) else {
+                continue;
+            };
+            let result = resolve_target(entity, track).and_then(|target| {
+                let registration = registry
+                    .get(&track.component)
+                    .filter(|registration| !registration.is_tag())
+                    .ok_or_else(|| {
+                        format!(
+                            "no component with data is registered as '{}'",
+                            track.component
+                        )
+                    })?;
+
+                let slot = match targets.iter().position(|(id, existing, _)| {
+                    *id == target.id() && existing.id() == registration.id()
+                }) {
+                    Some(slot) => slot,
+                    None => {
+                        let current = registration
+                            .serialize(&target)
+                            .ok_or_else(|| format!("it has no {}", track.component))??;
+                        targets.push((target.id(), registration, current));
+                        targets.len() - 1
+                    }
+                };
+                write_field(&mut targets[slot].2, &track.path, value)
+            });
+            results.push((index, result.err()));
+        }
+
+        let world = entity.world();
+        for (target, registration, value) in targets {
+            if let Err(error) = registration.deserialize(&world.entity_from_id(target), value) {
+                log::warn!(
+                    "Timeline on '{}' couldn't set {}: {}",
+                    entity.name(),
+                    registration.name(),
+                    error
+                );
+            }
+        }
+
+        // Warns once when a track starts failing, not every frame
+        for (index, error) in results {
+            let track = &mut self.tracks[index];
+            if let Some(error) = &error {
+                if track.error.as_ref() != Some(error) {
+                    log::warn!(
+                        "Timeline on '{}': track {}.{}{} is skipped, {}",
+                        entity.name(),
+                        track.component,
+                        track.path,
+                        track
+                            .child
+                            .as_ref()
+                            .map(|child| format!(" of child '{}'", child))
+                            .unwrap_or_default(),
+                        error
+                    );
+                }
+            }
+            track.error = error;
+        }
+    }
+}
+
+fn resolve_target<'a>(
+    entity: EntityView<'a>,
+    track: &PropertyTrack,
+) -> Result<EntityView<'a>, String> {
+    let Some(name) = &track.child else {
+        return Ok(entity);
+    };
+    let mut found = None;
+    entity.each_child(|child| {
+        if found.is_none() && child.name() == *name {
+            found = Some(child.id());
+        }
+    });
+    found
+        .map(|child| entity.world().entity_from_id(child))
+        .ok_or_else(|| format!("there is no child named '{}'", name))
+}
+
+// Replaces the field at `path` in a serialized component, checking that it exists and
+// holds the same kind of value
+fn write_field(component: &mut Value, path: &str, value: PropertyValue) -> Result<(), String> {
+    let mut field = component;
+    for segment in path.split('.') {
+        let next = match field {
+            Value::Object(fields) => fields.get_mut(segment),
+            Value::Array(values) => segment
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| values.get_mut(index)),
+            _ => None,
+        };
+        field = next.ok_or_else(|| format!("field '{}' not found", path))?;
+    }
+
+    if !value.matches(field) {
+        return Err(format!("field '{}' is not a {}", path, value.kind()));
+    }
+    *field = value.to_json();
+    Ok(())
+}
+
+pub fn timeline_system(world: &mut World) {
+    world.component::<TimelineFinished>();
+
+    world
+        .system_named::<(&mut Timeline, &Time, &ComponentRegistry)>("Play Timelines")
+        .kind(flecs::pipeline::OnUpdate)
+        .each_entity(|entity, (timeline, time, registry)| {
+            let mut finished = false;
+            if timeline.playing && (timeline.unscaled || !time.is_paused()) {
+                let dt = if timeline.unscaled {
+                    time.delta()
+                } else {
+                    time.scaled_delta()
+                };
+                finished = timeline.advance(dt.as_secs_f32());
+            } else if !timeline.seeked {
+                return;
+            }
+
+            timeline.seeked = false;
+            timeline.apply(entity, registry);
+
+            if finished {
+                timeline.playing = false;
+                entity.emit(&TimelineFinished);
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicU32, Ordering},
+        },
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::{App, light::PointLight, logging::LogBuffer};
+
+    /// Stands in for a game's material tint: any registered component works.
+    #[derive(Component, Clone, Default, Serialize, Deserialize)]
+    #[serde(default)]
+    struct Tint {
+        color: Color,
+        offset: Vec3,
+    }
+
+    const FRAME: Duration = Duration::from_millis(100);
+
+    struct Lamp {
+        app: App,
+        lamp: Entity,
+        shade: Entity,
+        finished: Arc<AtomicU32>,
+    }
+
+    impl Lamp {
+        /// A lamp dimming over a second while the shade on its child turns blue.
+        fn new(extra_track: Option<PropertyTrack>) -> Self {
+            let mut app = App::new();
+            app.register_component::<Tint>();
+
+            let lamp = app.world.entity_named("Lamp").set(PointLight {
+                intensity: 40.0,
+                ..Default::default()
+            });
+            let shade = app
+                .world
+                .entity_named("Shade")
+                .child_of(lamp)
+                .set(Tint::default())
+                .id();
+
+            let finished = Arc::new(AtomicU32::new(0));
+            let counter = finished.clone();
+            lamp.observe::<TimelineFinished>(move || {
+                counter.fetch_add(1, Ordering::Relaxed);
+            });
+
+            let mut timeline = Timeline::new()
+                .track(
+                    PropertyTrack::new("PointLight", "intensity")
+                        .key(0.0, 40.0)
+                        .key(1.0, 0.0),
+                )
+                .track(
+                    PropertyTrack::new("Tint", "color")
+                        .on_child("Shade")
+                        .key(0.0, Color::WHITE)
+                        .key(1.0, Color::BLUE),
+                );
+            if let Some(track) = extra_track {
+                timeline = timeline.track(track);
+            }
+            let lamp = lamp.set(timeline).id();
+
+            Self {
+                app,
+                lamp,
+                shade,
+                finished,
+            }
+        }
+
+        fn step(&mut self, frames: usize) {
+            for _ in 0..frames {
+                self.app
+                    .world
+                    .get::<&mut Time>(|time| time.set_delta(FRAME));
+                self.app.update();
+            }
+        }
+
+        fn intensity(&self) -> f32 {
+            self.app
+                .world
+                .entity_from_id(self.lamp)
+                .get::<&PointLight>(|light| light.intensity)
+        }
+
+        fn tint(&self) -> Tint {
+            self.app
+                .world
+                .entity_from_id(self.shade)
+                .get::<&Tint>(|tint| tint.clone())
+        }
+
+        fn timeline<R>(&self, f: impl FnOnce(&mut Timeline) -> R) -> R {
+            self.app
+                .world
+                .entity_from_id(self.lamp)
+                .get::<&mut Timeline>(f)
+        }
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn tracks_hold_their_end_values_outside_the_keyframes() {
+        let track = PropertyTrack::new("PointLight", "intensity")
+            .key(1.0, 10.0)
+            .key(3.0, 0.0);
+        assert_eq!(track.sample(0.0), Some(PropertyValue::F32(10.0)));
+        assert_eq!(track.sample(5.0), Some(PropertyValue::F32(0.0)));
+        assert_eq!(track.sample(2.0), Some(PropertyValue::F32(5.0)));
+
+        let eased = track.easing(Easing::EaseIn);
+        assert!(matches!(eased.sample(2.0), Some(PropertyValue::F32(value)) if close(value, 8.75)));
+    }
+
+    #[test]
+    fn a_keyframe_of_another_kind_is_ignored() {
+        let mixed = PropertyTrack::new("Tint", "color")
+            .key(0.0, Color::WHITE)
+            .key(1.0, 2.0);
+        assert_eq!(mixed.keyframes().len(), 1);
+    }
+
+    #[test]
+    fn looping_phases_wrap_and_reverse() {
+        assert!(close(Looping::PingPong.phase(1.5, 1.0), 0.5));
+        assert!(close(Looping::Loop.phase(1.25, 1.0), 0.25));
+    }
+
+    #[test]
+    fn halfway_through_every_field_is_halfway() {
+        let mut lamp = Lamp::new(None);
+        lamp.step(5);
+        assert!(close(lamp.intensity(), 20.0));
+        let tint = lamp.tint();
+        assert!(close(tint.color.r, 0.5) && close(tint.color.b, 1.0));
+        // Fields without a track keep their values
+        assert_eq!(tint.offset, Vec3::ZERO);
+    }
+
+    #[test]
+    fn a_renamed_field_logs_one_warning_and_the_other_tracks_keep_working() {
+        // Renamed from "colour"
+        let mut lamp = Lamp::new(Some(
+            PropertyTrack::new("Tint", "colour")
+                .on_child("Shade")
+                .key(0.0, Color::RED),
+        ));
+        let log = lamp.app.world.get::<&LogBuffer>(|log| log.clone());
+        lamp.step(5);
+        assert!(close(lamp.intensity(), 20.0));
+
+        let warnings: Vec<String> = log
+            .since(0)
+            .into_iter()
+            .filter(|line| line.level == log::Level::Warn && line.message.contains("colour"))
+            .map(|line| line.message)
+            .collect();
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert!(warnings[0].contains("Lamp") && warnings[0].contains("Tint.colour"));
+        assert_eq!(lamp.timeline(|timeline| timeline.errors().count()), 1);
+    }
+
+    #[test]
+    fn a_timeline_that_plays_once_ends_on_the_last_keyframes() {
+        let mut lamp = Lamp::new(None);
+        lamp.step(15);
+        assert_eq!(lamp.intensity(), 0.0);
+        assert_eq!(lamp.tint().color, Color::BLUE);
+        assert_eq!(lamp.finished.load(Ordering::Relaxed), 1);
+        assert!(lamp.timeline(|timeline| !timeline.playing && timeline.is_finished()));
+    }
+
+    #[test]
+    fn seeking_a_stopped_timeline_applies_it_without_playing_it() {
+        let mut lamp = Lamp::new(None);
+        lamp.step(15);
+        lamp.timeline(|timeline| timeline.seek(0.25));
+        lamp.step(1);
+        assert!(close(lamp.intensity(), 30.0));
+        assert!(lamp.timeline(|timeline| !timeline.playing));
+    }
+
+    #[test]
+    fn a_ping_pong_timeline_plays_back_and_freezes_with_paused_time() {
+        let mut lamp = Lamp::new(None);
+        lamp.timeline(|timeline| timeline.looping = Looping::PingPong);
+        lamp.step(13);
+        assert!(close(lamp.intensity(), 12.0));
+
+        lamp.app
+            .world
+            .get::<&mut Time>(|time| time.set_paused(true));
+        lamp.step(5);
+        assert!(close(lamp.intensity(), 12.0));
+        // Looping timelines don't finish
+        assert_eq!(lamp.finished.load(Ordering::Relaxed), 0);
+    }
+}
@@ -0,0 +1,244 @@
+use glam::{Mat4, Vec3};
+
+/// Axis-aligned bounding box in the local space of whatever it was computed from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self {
+            min: Vec3::ZERO,
+            max: Vec3::ZERO,
+        }
+    }
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    /// Builds the tightest box around a set of points.
+    /// Returns None for an empty set (there is nothing to bound).
+    pub fn from_points<I: IntoIterator<Item = Vec3>>(points: I) -> Option<Self> {
+        let mut iter = points.into_iter();
+        let first = iter.next()?;
+
+        let (min, max) = iter.fold((first, first), |(min, max), p| (min.min(p), max.max(p)));
+        Some(Self { min, max })
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn half_extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn corners(&self) -> [Vec3; 8] {
+        let (min, max) = (self.min, self.max);
+        [
+            Vec3::new(min.x, min.y, min.z),
+            Vec3::new(max.x, min.y, min.z),
+            Vec3::new(max.x, max.y, min.z),
+            Vec3::new(min.x, max.y, min.z),
+            Vec3::new(min.x, min.y, max.z),
+            Vec3::new(max.x, min.y, max.z),
+            Vec3::new(max.x, max.y, max.z),
+            Vec3::new(min.x, max.y, max.z),
+        ]
+    }
+
+    /// Returns the box enclosing this box after applying `matrix`.
+    /// We transform all 8 corners and re-fit, so rotations and negative scales
+    /// (which swap min/max on an axis) are handled correctly.
+    pub fn transform(&self, matrix: &Mat4) -> Aabb {
+        let corners = self.corners().map(|c| matrix.transform_point3(c));
+        // 8 corners are never empty
+        Aabb::from_points(corners).unwrap()
+    }
+
+    /// Returns the smallest box containing both boxes.
+    pub fn merge(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+}
+
+// Barycentric slack at triangle edges, see Ray::intersect_triangle
+const EDGE_TOLERANCE: f32 = 1e-5;
+
+/// Half-line from `origin` along `direction` (normalized), e.g. a picking ray.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+impl Ray {
+    pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Self {
+            origin,
+            direction: direction.normalize_or_zero(),
+        }
+    }
+
+    pub fn at(&self, distance: f32) -> Vec3 {
+        self.origin + self.direction * distance
+    }
+
+    /// Distance along the ray to where it enters the box, 0 from inside. None if it
+    /// misses or the box is behind it.
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        // Slabs; a zero direction component divides to +-inf, which the min/max absorb
+        let inv = self.direction.recip();
+        let t1 = (aabb.min - self.origin) * inv;
+        let t2 = (aabb.max - self.origin) * inv;
+        let near = t1.min(t2).max_element().max(0.0);
+        let far = t1.max(t2).min_element();
+        (near <= far).then_some(near)
+    }
+
+    /// Distance to the triangle along the ray (Moller-Trumbore), both faces count.
+    /// Edges get a hair of tolerance, so a ray exactly on the edge two triangles share
+    /// can't slip between them.
+    pub fn intersect_triangle(&self, a: Vec3, b: Vec3, c: Vec3) -> Option<f32> {
+        let edge1 = b - a;
+        let edge2 = c - a;
+        let p = self.direction.cross(edge2);
+        let det = edge1.dot(p);
+        if det.abs() < f32::EPSILON {
+            return None; // Parallel to the triangle
+        }
+
+        let inv_det = 1.0 / det;
+        let t = self.origin - a;
+        let u = t.dot(p) * inv_det;
+        if !(-EDGE_TOLERANCE..=1.0 + EDGE_TOLERANCE).contains(&u) {
+            return None;
+        }
+
+        let q = t.cross(edge1);
+        let v = self.direction.dot(q) * inv_det;
+        if v < -EDGE_TOLERANCE || u + v > 1.0 + EDGE_TOLERANCE {
+            return None;
+        }
+
+        let distance = edge2.dot(q) * inv_det;
+        (distance >= 0.0).then_some(distance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use glam::{Mat4, Quat, Vec3};
+
+    use super::Aabb;
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!(a.abs_diff_eq(b, 1e-5), "{} != {}", a, b);
+    }
+
+    #[test]
+    fn from_points_fits_the_points() {
+        let aabb = Aabb::from_points([
+            Vec3::new(1.0, -2.0, 3.0),
+            Vec3::new(-1.0, 4.0, 0.0),
+            Vec3::new(0.5, 0.0, -5.0),
+        ])
+        .unwrap();
+        assert_eq!(aabb.min, Vec3::new(-1.0, -2.0, -5.0));
+        assert_eq!(aabb.max, Vec3::new(1.0, 4.0, 3.0));
+    }
+
+    #[test]
+    fn from_points_of_one_point_is_flat() {
+        let point = Vec3::new(2.0, 3.0, 4.0);
+        let aabb = Aabb::from_points([point]).unwrap();
+        assert_eq!(aabb, Aabb::new(point, point));
+        assert_eq!(aabb.size(), Vec3::ZERO);
+    }
+
+    #[test]
+    fn from_no_points_is_none() {
+        assert_eq!(Aabb::from_points(std::iter::empty()), None);
+    }
+
+    #[test]
+    fn transform_rotated_and_scaled() {
+        let aabb = Aabb::new(Vec3::new(-1.0, -2.0, -3.0), Vec3::new(1.0, 2.0, 3.0));
+        // A quarter turn around Y swaps X and Z, then everything doubles and moves up
+        let matrix = Mat4::from_scale_rotation_translation(
+            Vec3::splat(2.0),
+            Quat::from_rotation_y(FRAC_PI_2),
+            Vec3::new(0.0, 10.0, 0.0),
+        );
+        let transformed = aabb.transform(&matrix);
+        assert_near(transformed.min, Vec3::new(-6.0, 6.0, -2.0));
+        assert_near(transformed.max, Vec3::new(6.0, 14.0, 2.0));
+    }
+
+    #[test]
+    fn transform_with_negative_scale_keeps_min_below_max() {
+        let aabb = Aabb::new(Vec3::new(1.0, 0.0, 0.0), Vec3::new(2.0, 1.0, 1.0));
+        let transformed = aabb.transform(&Mat4::from_scale(Vec3::new(-1.0, 1.0, 1.0)));
+        assert_near(transformed.min, Vec3::new(-2.0, 0.0, 0.0));
+        assert_near(transformed.max, Vec3::new(-1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn transform_by_a_rotation_grows_to_fit_the_corners() {
+        let aabb = Aabb::new(Vec3::splat(-1.0), Vec3::splat(1.0));
+        let transformed = aabb.transform(&Mat4::from_rotation_y(FRAC_PI_2 / 2.0));
+        let half = 2.0_f32.sqrt();
+        assert_near(transformed.min, Vec3::new(-half, -1.0, -half));
+        assert_near(transformed.max, Vec3::new(half, 1.0, half));
+    }
+
+    #[test]
+    fn merge_contains_both() {
+        let a = Aabb::new(Vec3::ZERO, Vec3::ONE);
+        let b = Aabb::new(Vec3::new(-1.0, 0.5, 2.0), Vec3::new(0.5, 0.75, 3.0));
+        let merged = a.merge(&b);
+        assert_eq!(merged.min, Vec3::new(-1.0, 0.0, 0.0));
+        assert_eq!(merged.max, Vec3::new(1.0, 1.0, 3.0));
+        assert_eq!(merged, b.merge(&a));
+    }
+
+    #[test]
+    fn merge_with_a_box_inside_changes_nothing() {
+        let outer = Aabb::new(Vec3::splat(-2.0), Vec3::splat(2.0));
+        let inner = Aabb::new(Vec3::splat(-1.0), Vec3::splat(1.0));
+        assert_eq!(outer.merge(&inner), outer);
+    }
+
+    #[test]
+    fn merge_of_an_empty_point_set_is_skipped_by_from_points() {
+        // Meshes without vertices have no bounds, so only the others are merged
+        let boxes: Vec<Aabb> = [vec![], vec![Vec3::ONE], vec![Vec3::ZERO, Vec3::splat(-1.0)]]
+            .into_iter()
+            .filter_map(Aabb::from_points)
+            .collect();
+        let merged = boxes
+            .iter()
+            .skip(1)
+            .fold(boxes[0], |acc, aabb| acc.merge(aabb));
+        assert_eq!(merged, Aabb::new(Vec3::splat(-1.0), Vec3::ONE));
+    }
+}
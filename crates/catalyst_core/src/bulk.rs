@@ -0,0 +1,555 @@
+//! Spawning and despawning thousands of entities without a frame spike.
+//!
+//! Jobs pushed to the SpawnQueue and DespawnQueue singletons are applied a group at a
+//! time, despawns first, until the frame's BulkBudget is spent. A group (a prop with its
+//! colliders, say) is always applied whole, within one frame, so nothing is seen half
+//! built or half removed; a frame goes over its budget by at most one group, and at least
+//! one group is applied every frame so every job ends. Scenes with more nodes than
+//! `BulkBudget::threshold` spawn and unload through these queues (see catalyst_scene).
+
+use std::{
+    collections::{HashSet, VecDeque},
+    ops::{Deref, DerefMut},
+    time::{Duration, Instant},
+};
+
+use flecs_ecs::prelude::*;
+
+/// How much queued spawn and despawn work a frame takes on.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct BulkBudget {
+    /// Time spent on the queues per frame, both together.
+    pub milliseconds: f32,
+    /// Optional cap on the entities made or removed per frame, on top of the time.
+    pub max_entities: Option<usize>,
+    /// Scenes with more entities than this are spawned and unloaded through the queues,
+    /// smaller ones at once.
+    pub threshold: usize,
+}
+
+impl Default for BulkBudget {
+    fn default() -> Self {
+        Self {
+            milliseconds: 2.0,
+            max_entities: None,
+            threshold: 500,
+        }
+    }
+}
+
+/// Work that makes or removes entities a group at a time, see the module docs.
+pub trait BulkJob: Send + Sync {
+    /// Entities the whole job makes or removes, for its progress.
+    fn total(&self) -> usize;
+
+    /// Applies the next group and returns how many entities it made or removed.
+    /// Not called again once `is_done`.
+    fn step(&mut self, world: &World) -> usize;
+
+    fn is_done(&self) -> bool;
+
+    /// Called once, right after the last group.
+    fn finish(&mut self, _world: &World) {}
+}
+
+/// A job in a SpawnQueue or DespawnQueue.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BulkId(u64);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BulkProgress {
+    pub done: usize,
+    pub total: usize,
+}
+
+impl BulkProgress {
+    /// Between 0 and 1.
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            (self.done as f32 / self.total as f32).min(1.0)
+        }
+    }
+}
+
+/// What a queue applied in the last frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BulkFrame {
+    pub entities: usize,
+    pub milliseconds: f32,
+}
+
+struct QueuedJob {
+    id: BulkId,
+    job: Box<dyn BulkJob>,
+    done: usize,
+}
+
+/// Jobs of one queue, applied in the order they were pushed.
+#[derive(Default)]
+pub struct BulkJobs {
+    next_id: u64,
+    jobs: VecDeque<QueuedJob>,
+    last_frame: BulkFrame,
+}
+
+impl BulkJobs {
+    pub fn push(&mut self, job: impl BulkJob + 'static) -> BulkId {
+        let id = BulkId(self.next_id);
+        self.next_id += 1;
+        self.jobs.push_back(QueuedJob {
+            id,
+            job: Box::new(job),
+            done: 0,
+        });
+        id
+    }
+
+    /// Progress of a queued job; None once it is finished, or if it was cancelled.
+    /// Jobs are taken out of the queue while the queue system applies them, so this is
+    /// also None from inside a job.
+    pub fn progress(&self, id: BulkId) -> Option<BulkProgress> {
+        self.jobs
+            .iter()
+            .find(|queued| queued.id == id)
+            .map(|queued| BulkProgress {
+                done: queued.done,
+                total: queued.job.total(),
+            })
+    }
+
+    /// Drops a job without finishing it. What it already applied stays.
+    pub fn cancel(&mut self, id: BulkId) -> bool {
+        let before = self.jobs.len();
+        self.jobs.retain(|queued| queued.id != id);
+        self.jobs.len() != before
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    /// Entities still to be made or removed by every queued job.
+    pub fn remaining(&self) -> usize {
+        self.jobs
+            .iter()
+            .map(|queued| queued.job.total().saturating_sub(queued.done))
+            .sum()
+    }
+
+    pub fn last_frame(&self) -> BulkFrame {
+        self.last_frame
+    }
+
+    // Puts back the jobs left after a frame, ahead of the ones pushed meanwhile
+    fn restore(&mut self, mut jobs: VecDeque<QueuedJob>, frame: BulkFrame) {
+        jobs.append(&mut self.jobs);
+        self.jobs = jobs;
+        self.last_frame = frame;
+    }
+}
+
+/// Singleton of jobs making entities, e.g. BulkSpawn or a large scene.
+#[derive(Component, Default)]
+pub struct SpawnQueue(BulkJobs);
+
+/// Singleton of jobs removing entities, e.g. DespawnGroups. Applied before the spawns.
+#[derive(Component, Default)]
+pub struct DespawnQueue(BulkJobs);
+
+impl Deref for SpawnQueue {
+    type Target = BulkJobs;
+
+    fn deref(&self) -> &BulkJobs {
+        &self.0
+    }
+}
+
+impl DerefMut for SpawnQueue {
+    fn deref_mut(&mut self) -> &mut BulkJobs {
+        &mut self.0
+    }
+}
+
+impl Deref for DespawnQueue {
+    type Target = BulkJobs;
+
+    fn deref(&self) -> &BulkJobs {
+        &self.0
+    }
+}
+
+impl DerefMut for DespawnQueue {
+    fn deref_mut(&mut self) -> &mut BulkJobs {
+        &mut self.0
+    }
+}
+
+/// Calls `spawn(world, index)` for every index below `count`, `group` calls at a time
+/// (one by default). Each call is counted as one entity.
+pub struct BulkSpawn<F> {
+    count: usize,
+    next: usize,
+    group: usize,
+    spawn: F,
+}
+
+impl<F: FnMut(&World, usize) + Send + Sync> BulkSpawn<F> {
+    pub fn new(count: usize, spawn: F) -> Self {
+        Self {
+            count,
+            next: 0,
+            group: 1,
+            spawn,
+        }
+    }
+
+    /// Makes every `group` consecutive indices one group, spawned in the same frame.
+    pub fn grouped(mut self, group: usize) -> Self {
+        self.group = group.max(1);
+        self
+    }
+}
+
+impl<F: FnMut(&World, usize) + Send + Sync> BulkJob for BulkSpawn<F> {
+    fn total(&self) -> usize {
+        self.count
+    }
+
+    fn step(&mut self, world: &World) -> usize {
+        let end = (self.next + self.group).min(self.count);
+        for index in self.next..end {
+            (self.spawn)(world, index);
+        }
+        let spawned = end - self.next;
+        self.next = end;
+        spawned
+    }
+
+    fn is_done(&self) -> bool {
+        self.next >= self.count
+    }
+}
+
+/// Deletes entities in order, each with everything under it as one group. Entities
+/// already gone when their turn comes are skipped.
+pub struct DespawnGroups {
+    groups: VecDeque<Entity>,
+    total: usize,
+}
+
+impl DespawnGroups {
+    pub fn new(world: &World, groups: impl IntoIterator<Item = Entity>) -> Self {
+        let groups: VecDeque<Entity> = groups.into_iter().collect();
+        let roots: HashSet<Entity> = groups.iter().copied().collect();
+        // An entity listed under another group's entity counts for its own group only
+        let total = groups
+            .iter()
+            .map(|&entity| {
+                let entity = world.entity_from_id(entity);
+                if entity.is_alive() {
+                    subtree_size(entity, &roots)
+                } else {
+                    0
+                }
+            })
+            .sum();
+        Self { groups, total }
+    }
+
+    /// `root` and everything under it, split where `keep_whole` is false: such an
+    /// entity's children go first (as groups of their own, split the same way), then the
+    /// entity alone. Entities for which it is true are deleted with their subtree in one
+    /// frame, e.g. a prop with its collider children.
+    pub fn hierarchy(root: EntityView, keep_whole: impl Fn(EntityView) -> bool) -> Self {
+        fn collect(
+            entity: EntityView,
+            keep_whole: &dyn Fn(EntityView) -> bool,
+            groups: &mut Vec<Entity>,
+        ) {
+            if !keep_whole(entity) {
+                entity.each_child(|child| collect(child, keep_whole, groups));
+            }
+            groups.push(entity.id());
+        }
+
+        let mut groups = Vec::new();
+        collect(root, &keep_whole, &mut groups);
+        Self::new(&root.world(), groups)
+    }
+}
+
+impl BulkJob for DespawnGroups {
+    fn total(&self) -> usize {
+        self.total
+    }
+
+    fn step(&mut self, world: &World) -> usize {
+        let Some(entity) = self.groups.pop_front() else {
+            return 0;
+        };
+        let entity = world.entity_from_id(entity);
+        if !entity.is_alive() {
+            return 0;
+        }
+        let removed = subtree_size(entity, &HashSet::new());
+        entity.destruct();
+        removed
+    }
+
+    fn is_done(&self) -> bool {
+        self.groups.is_empty()
+    }
+}
+
+// The entity and its descendants, not descending into `except`
+fn subtree_size(entity: EntityView, except: &HashSet<Entity>) -> usize {
+    let mut size = 1;
+    entity.each_child(|child| {
+        if !except.contains(&child.id()) {
+            size += subtree_size(child, except);
+        }
+    });
+    size
+}
+
+// Time and entities left in the current frame, shared by both queues
+struct FrameBudget {
+    start: Instant,
+    limit: Duration,
+    max_entities: Option<usize>,
+    entities: usize,
+    groups: usize,
+}
+
+impl FrameBudget {
+    fn new(budget: &BulkBudget) -> Self {
+        Self {
+            start: Instant::now(),
+            limit: Duration::from_secs_f32(budget.milliseconds.max(0.0) / 1000.0),
+            max_entities: budget.max_entities,
+            entities: 0,
+            groups: 0,
+        }
+    }
+
+    fn spent(&self) -> bool {
+        self.groups > 0
+            && (self.start.elapsed() >= self.limit
+                || self.max_entities.is_some_and(|max| self.entities >= max))
+    }
+
+    fn apply(&mut self, world: &World, jobs: &mut VecDeque<QueuedJob>) -> BulkFrame {
+        let start = Instant::now();
+        let entities = self.entities;
+
+        while let Some(queued) = jobs.front_mut() {
+            if !queued.job.is_done() {
+                if self.spent() {
+                    break;
+                }
+                let applied = queued.job.step(world);
+                queued.done += applied;
+                self.entities += applied;
+                self.groups += 1;
+            }
+            if queued.job.is_done() {
+                queued.job.finish(world);
+                jobs.pop_front();
+            }
+        }
+
+        BulkFrame {
+            entities: self.entities - entities,
+            milliseconds: start.elapsed().as_secs_f32() * 1000.0,
+        }
+    }
+}
+
+/// Applies the queues every frame. Immediate, so entities are made and removed while
+/// the budget is measured rather than when the frame's commands are merged.
+pub fn bulk_queue_system(world: &mut World) {
+    world
+        .system_named::<()>("bulk_queues")
+        .kind(flecs::pipeline::OnUpdate)
+        .immediate(true)
+        .run(|iter| {
+            let world = iter.world();
+            let budget = world.get::<&BulkBudget>(|budget| *budget);
+            let mut frame = FrameBudget::new(&budget);
+
+            // Taken out while applied, so jobs and hooks may push new jobs
+            let mut despawns =
+                world.get::<&mut DespawnQueue>(|queue| std::mem::take(&mut queue.jobs));
+            let applied = frame.apply(&world, &mut despawns);
+            world.get::<&mut DespawnQueue>(|queue| queue.restore(despawns, applied));
+
+            let mut spawns = world.get::<&mut SpawnQueue>(|queue| std::mem::take(&mut queue.jobs));
+            let applied = frame.apply(&world, &mut spawns);
+            world.get::<&mut SpawnQueue>(|queue| queue.restore(spawns, applied));
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::App;
+
+    const MAX_FRAMES: usize = 10_000;
+
+    #[derive(Component)]
+    struct Spawned;
+
+    #[derive(Component)]
+    struct Part;
+
+    #[derive(Component)]
+    struct Crate;
+
+    fn app(budget: BulkBudget) -> App {
+        let app = App::new();
+        app.world.component::<Spawned>();
+        app.world.component::<Part>();
+        app.world.component::<Crate>();
+        app.world.set(budget);
+        app
+    }
+
+    fn count<T: ComponentId>(world: &World) -> usize {
+        world.query::<()>().with(T::id()).build().count() as usize
+    }
+
+    // Spawns `count` entities through the queue, checking that the progress only grows.
+    // Returns the frames it took and the longest one
+    fn spawn_budgeted(app: &mut App, count: usize) -> (usize, f32) {
+        let id = app.world.get::<&mut SpawnQueue>(|queue| {
+            queue.push(BulkSpawn::new(count, |world, _| {
+                world.entity().add(Spawned);
+            }))
+        });
+        let mut frames = 0;
+        let mut worst_ms: f32 = 0.0;
+        let mut last_fraction = 0.0;
+        while frames < MAX_FRAMES {
+            app.update();
+            frames += 1;
+            let (frame, progress) = app
+                .world
+                .get::<&SpawnQueue>(|queue| (queue.last_frame(), queue.progress(id)));
+            worst_ms = worst_ms.max(frame.milliseconds);
+            let Some(progress) = progress else {
+                break;
+            };
+            assert!(progress.fraction() >= last_fraction, "progress went back");
+            last_fraction = progress.fraction();
+        }
+        (frames, worst_ms)
+    }
+
+    #[test]
+    fn a_spawn_is_spread_over_several_frames() {
+        let mut app = app(BulkBudget::default());
+        let (frames, _) = spawn_budgeted(&mut app, 20_000);
+        assert!(frames > 1);
+        assert_eq!(count::<Spawned>(&app.world), 20_000);
+        assert!(
+            app.world
+                .get::<&SpawnQueue>(|queue| queue.is_empty() && queue.remaining() == 0)
+        );
+    }
+
+    // Depends on the machine's timers and load, so it only runs on request:
+    // cargo test -p catalyst_core -- --ignored
+    #[test]
+    #[ignore]
+    fn a_spawn_stays_within_its_time_budget() {
+        const BUDGET_MS: f32 = 2.0;
+        // Room for the one group a frame may start before noticing its time is up
+        const TOLERANCE_MS: f32 = 0.5;
+        let mut app = app(BulkBudget {
+            milliseconds: BUDGET_MS,
+            ..Default::default()
+        });
+        let (_, worst_ms) = spawn_budgeted(&mut app, 20_000);
+        assert!(
+            worst_ms <= BUDGET_MS + TOLERANCE_MS,
+            "worst frame {} ms",
+            worst_ms
+        );
+    }
+
+    #[test]
+    fn groups_are_never_split_across_frames() {
+        // Groups of eight under a cap of 100 entities a frame
+        let mut app = app(BulkBudget {
+            milliseconds: 1000.0,
+            max_entities: Some(100),
+            ..Default::default()
+        });
+        app.world.get::<&mut SpawnQueue>(|queue| {
+            queue.push(
+                BulkSpawn::new(800, |world, _| {
+                    world.entity().add(Part);
+                })
+                .grouped(8),
+            );
+        });
+        for _ in 0..MAX_FRAMES {
+            app.update();
+            let frame = app.world.get::<&SpawnQueue>(|queue| queue.last_frame());
+            assert_eq!(frame.entities % 8, 0);
+            // The cap stops a frame within one group
+            assert!(frame.entities < 100 + 8);
+            if app.world.get::<&SpawnQueue>(|queue| queue.is_empty()) {
+                break;
+            }
+        }
+        assert_eq!(count::<Part>(&app.world), 800);
+    }
+
+    #[test]
+    fn a_hierarchy_despawns_whole_groups_at_a_time() {
+        let mut app = app(BulkBudget {
+            milliseconds: 1000.0,
+            max_entities: Some(50),
+            ..Default::default()
+        });
+        // Fifty crates of twenty children
+        let root = app.world.entity().id();
+        let mut crates = Vec::new();
+        for _ in 0..50 {
+            let crate_entity = app.world.entity().child_of(root).add(Crate);
+            for _ in 0..20 {
+                app.world.entity().child_of(crate_entity);
+            }
+            crates.push(crate_entity.id());
+        }
+        let job =
+            DespawnGroups::hierarchy(app.world.entity_from_id(root), |entity| entity.has(Crate));
+        let id = app.world.get::<&mut DespawnQueue>(|queue| queue.push(job));
+        let total = app
+            .world
+            .get::<&DespawnQueue>(|queue| queue.progress(id).map(|progress| progress.total));
+        // Every crate, part and the root
+        assert_eq!(total, Some(50 * 21 + 1));
+
+        let mut frames = 0;
+        while frames < MAX_FRAMES && app.world.get::<&DespawnQueue>(|queue| !queue.is_empty()) {
+            app.update();
+            frames += 1;
+            // A crate is never seen with some of its parts gone
+            for &crate_entity in &crates {
+                let crate_entity = app.world.entity_from_id(crate_entity);
+                if crate_entity.is_alive() {
+                    let mut children = 0;
+                    crate_entity.each_child(|_| children += 1);
+                    assert_eq!(children, 20);
+                }
+            }
+        }
+        assert!(frames > 1);
+        assert!(!app.world.entity_from_id(root).is_alive());
+        assert_eq!(count::<Crate>(&app.world), 0);
+    }
+}
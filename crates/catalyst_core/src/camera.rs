@@ -1,7 +1,11 @@
 use flecs_ecs::macros::Component;
-use glam::Mat4;
+use glam::{Mat4, Vec2, Vec3, Vec4};
+use serde::{Deserialize, Serialize};
 
-#[derive(Component, Clone, Debug)]
+use crate::bounds::Ray;
+
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Camera {
     pub fov: f32,
     pub aspect_ratio: f32,
@@ -26,4 +30,34 @@ impl Camera {
         // Perspective projection (things get smaller as they move away)
         Mat4::perspective_rh(self.fov, self.aspect_ratio, self.near, self.far)
     }
+
+    /// Reversed-Z projection with an infinite far plane.
+    /// Near maps to depth 1.0 and infinity to 0.0, which spreads float precision
+    /// evenly over distance. `far` is ignored.
+    pub fn compute_reversed_z_projection_matrix(&self) -> Mat4 {
+        Mat4::perspective_infinite_reverse_rh(self.fov, self.aspect_ratio, self.near)
+    }
+
+    /// Picking ray through `cursor` (pixels from the top left of the viewport).
+    /// The aspect ratio comes from `viewport_size`, like the renderer does, not from `aspect_ratio`.
+    pub fn screen_ray(&self, camera_transform: &Mat4, cursor: Vec2, viewport_size: Vec2) -> Ray {
+        let size = viewport_size.max(Vec2::ONE);
+        let ndc = Vec2::new(cursor.x / size.x * 2.0 - 1.0, 1.0 - cursor.y / size.y * 2.0);
+
+        let projection = Camera {
+            aspect_ratio: size.x / size.y,
+            ..self.clone()
+        }
+        .compute_projection_matrix();
+        let inverse_view_proj = (projection * camera_transform.inverse()).inverse();
+
+        let unproject = |depth: f32| {
+            let point = inverse_view_proj * Vec4::new(ndc.x, ndc.y, depth, 1.0);
+            point.truncate() / point.w
+        };
+        let near = unproject(0.0);
+        let far = unproject(1.0);
+
+        Ray::new(camera_transform.transform_point3(Vec3::ZERO), far - near)
+    }
 }
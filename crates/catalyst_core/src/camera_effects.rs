@@ -0,0 +1,291 @@
+use flecs_ecs::prelude::*;
+use glam::{EulerRot, Mat4, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    smoothing::exp_decay,
+    stable_id::{StableIdRegistry, StableRef},
+    time::Time,
+    transform::{GlobalTransform, Transform},
+};
+
+// Kick springs are integrated in steps no longer than this, so stiff springs stay
+// stable through long frames
+const MAX_SPRING_STEP: f32 = 1.0 / 120.0;
+
+/// Shake, kicks and smooth follow for a camera, layered over its Transform by the
+/// renderer without writing to it, so whatever drives the camera (fly-cam, gameplay)
+/// and the effects don't fight.
+///
+/// The view is the camera's GlobalTransform, moved to the follow position and turned
+/// towards the target when `follow` is set, then offset in its own space by the shake
+/// and the kick springs. Everything advances with scaled gameplay time, so pausing
+/// freezes the effects where they are.
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraEffects {
+    /// Shake amount from 0 to 1, raised by `add_trauma`. The shake grows with its
+    /// square, so small hits stay subtle.
+    pub trauma: f32,
+    /// Trauma lost per second.
+    pub trauma_decay: f32,
+    /// Largest shake offset along each axis at full trauma, in meters.
+    pub max_shake_offset: f32,
+    /// Largest shake angle around each axis at full trauma, in radians.
+    pub max_shake_angle: f32,
+    /// How fast the shake noise changes, in cycles per second.
+    pub shake_frequency: f32,
+    /// Pull of the kick springs back to rest; higher recovers faster.
+    pub kick_stiffness: f32,
+    /// Damping of the kick springs; 2 * sqrt(stiffness) settles without overshoot.
+    pub kick_damping: f32,
+    pub follow: Option<CameraFollow>,
+    #[serde(skip)]
+    state: EffectsState,
+}
+
+/// Keeps the camera at `offset` from a target entity, easing towards it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraFollow {
+    /// The entity to follow, which needs a StableId. Nothing is followed while None or
+    /// unresolved.
+    pub target: Option<StableRef>,
+    /// Camera position relative to the target, in world space.
+    pub offset: Vec3,
+    /// How quickly the camera catches up, as an `exp_decay` rate per second. 0 doesn't
+    /// move it.
+    pub stiffness: f32,
+    /// Turn the camera towards the target, with the same easing.
+    pub look_at: bool,
+}
+
+impl Default for CameraFollow {
+    fn default() -> Self {
+        Self {
+            target: None,
+            offset: Vec3::new(0.0, 3.0, 6.0),
+            stiffness: 4.0,
+            look_at: true,
+        }
+    }
+}
+
+// Runtime state, not saved
+#[derive(Clone, Debug, Default)]
+struct EffectsState {
+    noise_time: f32,
+    kick_offset: Vec3,
+    kick_velocity: Vec3,
+    // Pitch, yaw, roll in radians
+    kick_angles: Vec3,
+    kick_angular_velocity: Vec3,
+    follow_position: Option<Vec3>,
+    follow_rotation: Option<Quat>,
+    // This frame's offsets in camera space, from shake and kicks
+    offset: Vec3,
+    rotation: Quat,
+}
+
+impl Default for CameraEffects {
+    fn default() -> Self {
+        Self {
+            trauma: 0.0,
+            trauma_decay: 0.8,
+            max_shake_offset: 0.15,
+            max_shake_angle: 3.0f32.to_radians(),
+            shake_frequency: 15.0,
+            kick_stiffness: 150.0,
+            kick_damping: 18.0,
+            follow: None,
+            state: EffectsState::default(),
+        }
+    }
+}
+
+impl CameraEffects {
+    /// Effects with a default CameraFollow of `target`.
+    pub fn following(target: StableRef) -> Self {
+        Self {
+            follow: Some(CameraFollow {
+                target: Some(target),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Adds to the trauma, e.g. 0.3 for a nearby hit and 1.0 for an explosion. Capped at 1.
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0.0, 1.0);
+    }
+
+    /// Kicks the camera, e.g. for recoil: `velocity` in meters per second and
+    /// `angular_velocity` as pitch, yaw, roll in radians per second, both in camera
+    /// space. The kick springs bring it back.
+    pub fn kick(&mut self, velocity: Vec3, angular_velocity: Vec3) {
+        self.state.kick_velocity += velocity;
+        self.state.kick_angular_velocity += angular_velocity;
+    }
+
+    /// Current kick displacement in camera space, in meters.
+    pub fn kick_offset(&self) -> Vec3 {
+        self.state.kick_offset
+    }
+
+    /// Current kick rotation as pitch, yaw, roll in radians.
+    pub fn kick_angles(&self) -> Vec3 {
+        self.state.kick_angles
+    }
+
+    /// True while a kick hasn't settled yet.
+    pub fn is_kicking(&self) -> bool {
+        let state = &self.state;
+        state.kick_offset.length_squared() > 1e-8
+            || state.kick_velocity.length_squared() > 1e-8
+            || state.kick_angles.length_squared() > 1e-8
+            || state.kick_angular_velocity.length_squared() > 1e-8
+    }
+
+    /// Stops the shake and the kicks and forgets the follow position.
+    pub fn reset(&mut self) {
+        self.trauma = 0.0;
+        self.state = EffectsState::default();
+    }
+
+    /// The transform to render the camera with, from the camera's GlobalTransform.
+    pub fn apply(&self, camera_transform: Mat4) -> Mat4 {
+        let (scale, mut rotation, mut translation) =
+            camera_transform.to_scale_rotation_translation();
+        if let Some(position) = self.state.follow_position {
+            translation = position;
+        }
+        if let Some(look) = self.state.follow_rotation {
+            rotation = look;
+        }
+        Mat4::from_scale_rotation_translation(
+            scale,
+            rotation * self.state.rotation,
+            translation + rotation * self.state.offset,
+        )
+    }
+
+    // Advances everything by `dt` seconds of gameplay time. `camera` is the authored
+    // GlobalTransform, `target` the followed entity's position if it resolved.
+    fn advance(&mut self, dt: f32, camera: Mat4, target: Option<Vec3>) {
+        self.trauma = (self.trauma - self.trauma_decay * dt).max(0.0);
+        self.state.noise_time += dt * self.shake_frequency;
+        self.advance_kicks(dt);
+        self.advance_follow(dt, camera, target);
+
+        let shake = self.trauma * self.trauma;
+        let time = self.state.noise_time;
+        let noise = |seed: u32| perlin(time, seed) * shake;
+        let shake_offset = Vec3::new(noise(0), noise(1), noise(2)) * self.max_shake_offset;
+        let shake_angles = Vec3::new(noise(3), noise(4), noise(5)) * self.max_shake_angle;
+
+        let angles = shake_angles + self.state.kick_angles;
+        self.state.offset = shake_offset + self.state.kick_offset;
+        self.state.rotation = Quat::from_euler(EulerRot::YXZ, angles.y, angles.x, angles.z);
+    }
+
+    // Spring-damper towards zero, in small steps
+    fn advance_kicks(&mut self, dt: f32) {
+        let steps = (dt / MAX_SPRING_STEP).ceil().max(1.0);
+        let step = dt / steps;
+        let (stiffness, damping) = (self.kick_stiffness, self.kick_damping);
+        let state = &mut self.state;
+        for _ in 0..steps as u32 {
+            state.kick_velocity +=
+                (-stiffness * state.kick_offset - damping * state.kick_velocity) * step;
+            state.kick_offset += state.kick_velocity * step;
+            state.kick_angular_velocity +=
+                (-stiffness * state.kick_angles - damping * state.kick_angular_velocity) * step;
+            state.kick_angles += state.kick_angular_velocity * step;
+        }
+        if !self.is_kicking() {
+            self.state.kick_offset = Vec3::ZERO;
+            self.state.kick_velocity = Vec3::ZERO;
+            self.state.kick_angles = Vec3::ZERO;
+            self.state.kick_angular_velocity = Vec3::ZERO;
+        }
+    }
+
+    fn advance_follow(&mut self, dt: f32, camera: Mat4, target: Option<Vec3>) {
+        let (Some(follow), Some(target)) = (&self.follow, target) else {
+            self.state.follow_position = None;
+            self.state.follow_rotation = None;
+            return;
+        };
+        let (_, camera_rotation, camera_position) = camera.to_scale_rotation_translation();
+
+        let goal = target + follow.offset;
+        let position = self.state.follow_position.unwrap_or(camera_position);
+        let position = exp_decay(position, goal, follow.stiffness, dt);
+        self.state.follow_position = Some(position);
+
+        self.state.follow_rotation =
+            if follow.look_at && (target - position).length_squared() > 1e-6 {
+                let look = Transform::from_xyz(position.x, position.y, position.z)
+                    .looking_at(target, Vec3::Y)
+                    .rotation;
+                let rotation = self.state.follow_rotation.unwrap_or(camera_rotation);
+                Some(exp_decay(rotation, look, follow.stiffness, dt))
+            } else {
+                None
+            };
+    }
+}
+
+// 1D gradient noise in about -1..1, one independent channel per seed
+fn perlin(x: f32, seed: u32) -> f32 {
+    let cell = x.floor();
+    let t = x - cell;
+    let gradient = |i: i32| {
+        let hash = hash32((i as u32) ^ seed.wrapping_mul(0x9e37_79b9));
+        hash as f32 / u32::MAX as f32 * 2.0 - 1.0
+    };
+    let a = gradient(cell as i32) * t;
+    let b = gradient(cell as i32 + 1) * (t - 1.0);
+    let fade = t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    // A 1D gradient noise peaks at 0.5
+    (a + (b - a) * fade) * 2.0
+}
+
+fn hash32(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb_352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846c_a68b);
+    x ^= x >> 16;
+    x
+}
+
+pub fn camera_effects_system(world: &mut World) {
+    // After "transform_propagation_system" (also PostUpdate, registered first), so the
+    // camera and the followed target are where they are drawn this frame
+    world
+        .system_named::<(&mut CameraEffects, &GlobalTransform, &Time)>("Camera Effects")
+        .kind(flecs::pipeline::PostUpdate)
+        .each_entity(|entity, (effects, camera, time)| {
+            if time.is_paused() {
+                return;
+            }
+            let dt = time.scaled_delta().as_secs_f32();
+            let world = entity.world();
+            let target = effects
+                .follow
+                .as_ref()
+                .and_then(|follow| follow.target)
+                .and_then(|target| {
+                    world.try_get::<&StableIdRegistry>(|registry| registry.entity(target.id))
+                })
+                .flatten()
+                .map(|target| world.entity_from_id(target))
+                .filter(|target| target.is_alive())
+                .and_then(|target| {
+                    target.try_get::<&GlobalTransform>(|global| global.0.w_axis.truncate())
+                });
+            effects.advance(dt, camera.0, target);
+        });
+}
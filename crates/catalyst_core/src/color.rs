@@ -0,0 +1,406 @@
+use glam::{Vec3, Vec4};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// An RGBA color, stored as linear RGB with straight (not premultiplied) alpha, the
+/// way shaders and GPU uniforms want it.
+///
+/// Colors picked in an editor or written as hex codes are sRGB: build those with
+/// `srgb`, `srgb_u8`, `hsl` or `hex`, which convert once. Plain arrays and vectors
+/// convert as linear values (`From<[f32; 4]>`), like the `[f32; 4]` fields this type
+/// replaced.
+///
+/// Serialized as a linear `[r, g, b, a]` array; three values read as opaque.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(from = "ColorRepr", into = "[f32; 4]")]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorError {
+    #[error("expected 3, 4, 6 or 8 hex digits, found {0}")]
+    InvalidLength(usize),
+    #[error("'{0}' is not a hex digit")]
+    InvalidDigit(char),
+}
+
+impl Color {
+    pub const WHITE: Color = Color::linear_rgb(1.0, 1.0, 1.0);
+    pub const BLACK: Color = Color::linear_rgb(0.0, 0.0, 0.0);
+    pub const TRANSPARENT: Color = Color::linear_rgba(0.0, 0.0, 0.0, 0.0);
+    pub const RED: Color = Color::linear_rgb(1.0, 0.0, 0.0);
+    pub const GREEN: Color = Color::linear_rgb(0.0, 1.0, 0.0);
+    pub const BLUE: Color = Color::linear_rgb(0.0, 0.0, 1.0);
+    pub const YELLOW: Color = Color::linear_rgb(1.0, 1.0, 0.0);
+    pub const CYAN: Color = Color::linear_rgb(0.0, 1.0, 1.0);
+    pub const MAGENTA: Color = Color::linear_rgb(1.0, 0.0, 1.0);
+    /// sRGB 50% gray (#808080).
+    pub const GRAY: Color = Color::linear_rgb(0.2158605, 0.2158605, 0.2158605);
+    /// sRGB #FF8000.
+    pub const ORANGE: Color = Color::linear_rgb(1.0, 0.2158605, 0.0);
+
+    pub const fn linear_rgb(r: f32, g: f32, b: f32) -> Self {
+        Self::linear_rgba(r, g, b, 1.0)
+    }
+
+    pub const fn linear_rgba(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// From sRGB channels in 0..1, e.g. from a color picker.
+    pub fn srgb(r: f32, g: f32, b: f32) -> Self {
+        Self::srgba(r, g, b, 1.0)
+    }
+
+    /// From sRGB channels in 0..1. Alpha is linear either way.
+    pub fn srgba(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self::linear_rgba(srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a)
+    }
+
+    pub fn srgb_u8(r: u8, g: u8, b: u8) -> Self {
+        Self::srgba_u8(r, g, b, 255)
+    }
+
+    pub fn srgba_u8(r: u8, g: u8, b: u8, a: u8) -> Self {
+        let channel = |value: u8| value as f32 / 255.0;
+        Self::srgba(channel(r), channel(g), channel(b), channel(a))
+    }
+
+    /// From hue in degrees (any value, wrapped to 0..360), saturation and lightness in
+    /// 0..1, in sRGB space like color pickers show them.
+    pub fn hsl(hue: f32, saturation: f32, lightness: f32) -> Self {
+        Self::hsla(hue, saturation, lightness, 1.0)
+    }
+
+    pub fn hsla(hue: f32, saturation: f32, lightness: f32, alpha: f32) -> Self {
+        let hue = hue.rem_euclid(360.0);
+        let saturation = saturation.clamp(0.0, 1.0);
+        let lightness = lightness.clamp(0.0, 1.0);
+        let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+        let channel = |offset: f32| {
+            let k = (offset + hue / 30.0) % 12.0;
+            lightness - chroma * 0.5 * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0)
+        };
+        Self::srgba(channel(0.0), channel(8.0), channel(4.0), alpha)
+    }
+
+    /// From an sRGB hex code: "#RGB", "#RGBA", "#RRGGBB" or "#RRGGBBAA", the '#' optional.
+    pub fn hex(code: &str) -> Result<Self, ColorError> {
+        let digits = code.strip_prefix('#').unwrap_or(code);
+        let values = digits
+            .chars()
+            .map(|digit| {
+                digit
+                    .to_digit(16)
+                    .map(|value| value as u8)
+                    .ok_or(ColorError::InvalidDigit(digit))
+            })
+            .collect::<Result<Vec<u8>, _>>()?;
+        match values.as_slice() {
+            // Short forms repeat each digit: #F80 is #FF8800
+            [r, g, b] => Ok(Self::srgb_u8(r * 17, g * 17, b * 17)),
+            [r, g, b, a] => Ok(Self::srgba_u8(r * 17, g * 17, b * 17, a * 17)),
+            [r1, r0, g1, g0, b1, b0] => Ok(Self::srgb_u8(
+                (r1 << 4) | r0,
+                (g1 << 4) | g0,
+                (b1 << 4) | b0,
+            )),
+            [r1, r0, g1, g0, b1, b0, a1, a0] => Ok(Self::srgba_u8(
+                (r1 << 4) | r0,
+                (g1 << 4) | g0,
+                (b1 << 4) | b0,
+                (a1 << 4) | a0,
+            )),
+            _ => Err(ColorError::InvalidLength(values.len())),
+        }
+    }
+
+    pub const fn with_alpha(self, a: f32) -> Self {
+        Self { a, ..self }
+    }
+
+    /// Linear RGBA with straight alpha, for GPU uniforms.
+    pub const fn to_linear(self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    /// Linear RGB, alpha dropped (lights, emission).
+    pub const fn to_linear_rgb(self) -> [f32; 3] {
+        [self.r, self.g, self.b]
+    }
+
+    /// sRGB channels in 0..1 (values past 1 stay past 1) with linear alpha.
+    pub fn to_srgba(self) -> [f32; 4] {
+        [
+            linear_to_srgb(self.r),
+            linear_to_srgb(self.g),
+            linear_to_srgb(self.b),
+            self.a,
+        ]
+    }
+
+    /// sRGB bytes, clamped to 0..1 first.
+    pub fn to_srgba_u8(self) -> [u8; 4] {
+        self.to_srgba()
+            .map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8)
+    }
+
+    /// "#RRGGBBAA" in sRGB, or "#RRGGBB" when opaque.
+    pub fn to_hex(self) -> String {
+        let [r, g, b, a] = self.to_srgba_u8();
+        if a == 255 {
+            format!("#{:02X}{:02X}{:02X}", r, g, b)
+        } else {
+            format!("#{:02X}{:02X}{:02X}{:02X}", r, g, b, a)
+        }
+    }
+
+    /// Hue in degrees (0..360), saturation, lightness and alpha, from the sRGB values.
+    /// Grays have hue and saturation 0.
+    pub fn to_hsla(self) -> [f32; 4] {
+        let [r, g, b, a] = self.to_srgba();
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let lightness = (max + min) * 0.5;
+        let chroma = max - min;
+        if chroma <= f32::EPSILON {
+            return [0.0, 0.0, lightness, a];
+        }
+        let saturation = chroma / (1.0 - (2.0 * lightness - 1.0).abs());
+        let hue = if max == r {
+            ((g - b) / chroma).rem_euclid(6.0)
+        } else if max == g {
+            (b - r) / chroma + 2.0
+        } else {
+            (r - g) / chroma + 4.0
+        } * 60.0;
+        [hue, saturation, lightness, a]
+    }
+
+    /// Linear RGB multiplied by alpha, for premultiplied blending.
+    pub fn to_premultiplied(self) -> [f32; 4] {
+        [self.r * self.a, self.g * self.a, self.b * self.a, self.a]
+    }
+
+    /// From linear premultiplied RGBA. Fully transparent colors come back black.
+    pub fn from_premultiplied([r, g, b, a]: [f32; 4]) -> Self {
+        if a <= 0.0 {
+            return Self::TRANSPARENT;
+        }
+        Self::linear_rgba(r / a, g / a, b / a, a)
+    }
+
+    /// Blends in linear space: 0 is `self`, 1 is `other`.
+    pub fn lerp(self, other: Color, t: f32) -> Self {
+        Vec4::from(self).lerp(Vec4::from(other), t).into()
+    }
+}
+
+impl Default for Color {
+    fn default() -> Self {
+        Self::WHITE
+    }
+}
+
+/// sRGB transfer function, one channel: encoded 0..1 to linear.
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Inverse of `srgb_to_linear`.
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// Older files store colors as plain arrays, lights without alpha
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColorRepr {
+    Rgb([f32; 3]),
+    Rgba([f32; 4]),
+}
+
+impl From<ColorRepr> for Color {
+    fn from(repr: ColorRepr) -> Self {
+        match repr {
+            ColorRepr::Rgb(rgb) => rgb.into(),
+            ColorRepr::Rgba(rgba) => rgba.into(),
+        }
+    }
+}
+
+impl From<[f32; 4]> for Color {
+    fn from([r, g, b, a]: [f32; 4]) -> Self {
+        Self::linear_rgba(r, g, b, a)
+    }
+}
+
+impl From<[f32; 3]> for Color {
+    fn from([r, g, b]: [f32; 3]) -> Self {
+        Self::linear_rgb(r, g, b)
+    }
+}
+
+impl From<Vec4> for Color {
+    fn from(value: Vec4) -> Self {
+        value.to_array().into()
+    }
+}
+
+impl From<Vec3> for Color {
+    fn from(value: Vec3) -> Self {
+        value.to_array().into()
+    }
+}
+
+impl From<Color> for [f32; 4] {
+    fn from(color: Color) -> Self {
+        color.to_linear()
+    }
+}
+
+impl From<Color> for Vec4 {
+    fn from(color: Color) -> Self {
+        Vec4::from_array(color.to_linear())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} is not {}", a, b);
+    }
+
+    fn assert_rgb(color: Color, rgb: [f32; 3]) {
+        for (a, b) in color.to_linear_rgb().into_iter().zip(rgb) {
+            assert_close(a, b);
+        }
+    }
+
+    // Reference points of the sRGB curve (IEC 61966-2-1)
+    const REFERENCES: [(f32, f32); 5] = [
+        (0.0, 0.0),
+        (0.04045, 0.0031308),
+        (0.5, 0.2140411),
+        (0.7353569, 0.5),
+        (1.0, 1.0),
+    ];
+
+    #[test]
+    fn srgb_transfer_matches_the_reference_values() {
+        for (srgb, linear) in REFERENCES {
+            assert_close(srgb_to_linear(srgb), linear);
+            assert_close(linear_to_srgb(linear), srgb);
+        }
+    }
+
+    #[test]
+    fn every_8_bit_srgb_value_round_trips() {
+        for value in 0..=255u8 {
+            assert_eq!(
+                Color::srgb_u8(value, value, value).to_srgba_u8(),
+                [value, value, value, 255]
+            );
+        }
+        assert_close(Color::srgb_u8(128, 128, 128).r, Color::GRAY.r);
+    }
+
+    #[test]
+    fn alpha_is_not_gamma_corrected() {
+        assert_close(Color::srgba(0.5, 0.5, 0.5, 0.5).a, 0.5);
+    }
+
+    #[test]
+    fn hsl_primaries() {
+        assert_rgb(Color::hsl(0.0, 1.0, 0.5), [1.0, 0.0, 0.0]);
+        assert_rgb(Color::hsl(120.0, 1.0, 0.5), [0.0, 1.0, 0.0]);
+        assert_rgb(Color::hsl(240.0, 1.0, 0.5), [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn hsl_hue_wraps_around() {
+        assert_eq!(Color::hsl(360.0, 1.0, 0.5), Color::hsl(0.0, 1.0, 0.5));
+        assert_eq!(Color::hsl(-120.0, 1.0, 0.5), Color::hsl(240.0, 1.0, 0.5));
+        assert_eq!(Color::hsl(480.0, 1.0, 0.5), Color::hsl(120.0, 1.0, 0.5));
+    }
+
+    #[test]
+    fn hsl_grays_and_extremes() {
+        for hue in [0.0, 77.0, 200.0, 359.0] {
+            assert_eq!(Color::hsl(hue, 0.0, 0.5), Color::srgb(0.5, 0.5, 0.5));
+        }
+        assert_rgb(Color::hsl(200.0, 1.0, 0.0), [0.0; 3]);
+        assert_rgb(Color::hsl(200.0, 1.0, 1.0), [1.0; 3]);
+        assert_eq!(Color::srgb(0.3, 0.3, 0.3).to_hsla()[..2], [0.0, 0.0]);
+    }
+
+    #[test]
+    fn hsl_round_trips() {
+        let [hue, saturation, lightness, _] = Color::hsl(30.0, 0.8, 0.4).to_hsla();
+        assert_close(hue, 30.0);
+        assert_close(saturation, 0.8);
+        assert_close(lightness, 0.4);
+    }
+
+    #[test]
+    fn hex_codes_parse_in_all_four_forms() {
+        assert_rgb(
+            Color::hex("#FF8000").unwrap(),
+            Color::ORANGE.to_linear_rgb(),
+        );
+        assert_eq!(Color::hex("f80"), Color::hex("#FF8800"));
+        assert_eq!(
+            Color::hex("#ff800080").map(Color::to_srgba_u8),
+            Ok([255, 128, 0, 128])
+        );
+        assert_eq!(
+            Color::hex("#F808").map(Color::to_srgba_u8),
+            Ok([255, 136, 0, 136])
+        );
+        assert_eq!(
+            Color::hex("#3A7BD5").map(Color::to_hex),
+            Ok("#3A7BD5".to_string())
+        );
+    }
+
+    #[test]
+    fn bad_hex_codes_are_errors() {
+        assert_eq!(Color::hex("#12345"), Err(ColorError::InvalidLength(5)));
+        assert_eq!(Color::hex("#12G456"), Err(ColorError::InvalidDigit('G')));
+    }
+
+    #[test]
+    fn premultiplied_alpha_round_trips() {
+        let color = Color::linear_rgba(0.8, 0.4, 0.2, 0.5);
+        assert_eq!(color.to_premultiplied(), [0.4, 0.2, 0.1, 0.5]);
+        assert_eq!(Color::from_premultiplied(color.to_premultiplied()), color);
+        assert_eq!(Color::from_premultiplied([0.0; 4]), Color::TRANSPARENT);
+    }
+
+    #[test]
+    fn saved_as_a_linear_array() {
+        let color = Color::linear_rgba(0.8, 0.4, 0.2, 0.5);
+        let saved = serde_json::to_string(&color).unwrap();
+        assert_eq!(saved, "[0.8,0.4,0.2,0.5]");
+        assert_eq!(serde_json::from_str::<Color>(&saved).unwrap(), color);
+        // Light colors saved without alpha
+        assert_eq!(
+            serde_json::from_str::<Color>("[1.0, 0.8, 0.5]").unwrap(),
+            Color::linear_rgb(1.0, 0.8, 0.5)
+        );
+    }
+}
@@ -0,0 +1,59 @@
+use std::collections::BTreeMap;
+
+use flecs_ecs::prelude::*;
+
+/// Runs a console command. Gets the arguments after the command name and
+/// returns the text to print, or an error message.
+pub type ConsoleHandler = fn(&WorldRef, &[&str]) -> Result<String, String>;
+
+#[derive(Clone, Copy)]
+pub struct ConsoleCommand {
+    pub help: &'static str,
+    pub handler: ConsoleHandler,
+}
+
+/// Registry of debug console commands. Plugins register theirs at build time;
+/// the debug console (or anything else) runs them with `run_command`.
+#[derive(Component, Default)]
+pub struct ConsoleCommands {
+    commands: BTreeMap<&'static str, ConsoleCommand>,
+}
+
+impl ConsoleCommands {
+    /// Names are dotted by owner, e.g. "input.record". Registering a name again replaces it.
+    pub fn register(
+        &mut self,
+        name: &'static str,
+        help: &'static str,
+        handler: ConsoleHandler,
+    ) -> &mut Self {
+        self.commands.insert(name, ConsoleCommand { help, handler });
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<ConsoleCommand> {
+        self.commands.get(name).copied()
+    }
+
+    /// Commands sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &ConsoleCommand)> {
+        self.commands.iter().map(|(name, command)| (*name, command))
+    }
+}
+
+/// Parses `line` as `name arg1 arg2 ...` and runs the matching command.
+/// The handler is copied out first, so it is free to access any singleton (including ConsoleCommands).
+pub fn run_command(world: &WorldRef, line: &str) -> Result<String, String> {
+    let mut parts = line.split_whitespace();
+    let Some(name) = parts.next() else {
+        return Ok(String::new());
+    };
+    let args: Vec<&str> = parts.collect();
+
+    let command = world
+        .try_get::<&ConsoleCommands>(|commands| commands.get(name))
+        .flatten()
+        .ok_or_else(|| format!("Unknown command '{}'. Type 'help' for a list.", name))?;
+
+    (command.handler)(world, &args)
+}
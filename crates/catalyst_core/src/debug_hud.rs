@@ -0,0 +1,247 @@
+//! Live numbers for a small overlay drawn over the game.
+//!
+//! Any system, on any thread, can call `DebugHud::watch("player.speed", speed)`,
+//! `DebugHud::plot("frame time", ms)` or `DebugHud::progress("loading", done)`. The calls
+//! land in one shared buffer, drawn once a frame by the debug plugin whether or not its
+//! windows are open (see HudSettings there). A widget that isn't refreshed for a few
+//! frames is dropped, so values of systems that stopped don't linger.
+
+use std::{collections::VecDeque, fmt::Display, sync::Mutex};
+
+/// Samples a plot keeps, the newest last.
+pub const PLOT_SAMPLES: usize = 120;
+
+static BUFFER: Mutex<HudBuffer> = Mutex::new(HudBuffer {
+    frame: 0,
+    widgets: Vec::new(),
+});
+
+struct HudBuffer {
+    frame: u64,
+    // In the order they were first seen, so the overlay doesn't reshuffle
+    widgets: Vec<HudWidget>,
+}
+
+impl HudBuffer {
+    // The widget called `name`, made by `new` if it doesn't exist or is of another kind
+    fn widget(
+        &mut self,
+        name: &str,
+        same_kind: impl Fn(&HudValue) -> bool,
+        new: impl FnOnce() -> HudValue,
+    ) -> &mut HudValue {
+        let frame = self.frame;
+        let index = match self.widgets.iter().position(|widget| widget.name == name) {
+            Some(index) => index,
+            None => {
+                self.widgets.push(HudWidget {
+                    name: name.to_string(),
+                    value: new(),
+                    last_frame: frame,
+                });
+                return &mut self.widgets.last_mut().expect("just pushed").value;
+            }
+        };
+
+        let widget = &mut self.widgets[index];
+        widget.last_frame = frame;
+        if !same_kind(&widget.value) {
+            widget.value = new();
+        }
+        &mut widget.value
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum HudValue {
+    Text(String),
+    /// The last PLOT_SAMPLES values, oldest first.
+    Plot(VecDeque<f32>),
+    /// Between 0 and 1.
+    Progress(f32),
+}
+
+#[derive(Clone, Debug)]
+pub struct HudWidget {
+    pub name: String,
+    pub value: HudValue,
+    // Frame the widget was last refreshed in
+    last_frame: u64,
+}
+
+/// The shared HUD buffer. Every call takes a short lock, fine for a few dozen values a
+/// frame, not for one per entity.
+pub struct DebugHud;
+
+impl DebugHud {
+    /// Shows `name: value` until it stops being refreshed.
+    pub fn watch(name: &str, value: impl Display) {
+        let text = value.to_string();
+        let mut buffer = BUFFER.lock().expect("debug hud poisoned");
+        let value = buffer.widget(
+            name,
+            |value| matches!(value, HudValue::Text(_)),
+            || HudValue::Text(String::new()),
+        );
+        *value = HudValue::Text(text);
+    }
+
+    /// Adds `value` to the graph `name`. Call it once a frame for a time series.
+    pub fn plot(name: &str, value: f32) {
+        let mut buffer = BUFFER.lock().expect("debug hud poisoned");
+        let HudValue::Plot(samples) = buffer.widget(
+            name,
+            |value| matches!(value, HudValue::Plot(_)),
+            || HudValue::Plot(VecDeque::with_capacity(PLOT_SAMPLES)),
+        ) else {
+            unreachable!("plot widgets hold plots");
+        };
+        if samples.len() == PLOT_SAMPLES {
+            samples.pop_front();
+        }
+        samples.push_back(value);
+    }
+
+    /// Shows a progress bar at `fraction` (clamped to 0..1), e.g. of a loading job.
+    pub fn progress(name: &str, fraction: f32) {
+        let fraction = if fraction.is_nan() {
+            0.0
+        } else {
+            fraction.clamp(0.0, 1.0)
+        };
+        let mut buffer = BUFFER.lock().expect("debug hud poisoned");
+        let value = buffer.widget(
+            name,
+            |value| matches!(value, HudValue::Progress(_)),
+            || HudValue::Progress(0.0),
+        );
+        *value = HudValue::Progress(fraction);
+    }
+
+    /// Drops the widget `name` right away.
+    pub fn remove(name: &str) {
+        let mut buffer = BUFFER.lock().expect("debug hud poisoned");
+        buffer.widgets.retain(|widget| widget.name != name);
+    }
+
+    pub fn clear() {
+        BUFFER.lock().expect("debug hud poisoned").widgets.clear();
+    }
+
+    /// Ends the frame: drops the widgets not refreshed in the last `expire_frames`
+    /// frames (this one included) and returns the others, to be drawn. Called once a
+    /// frame by whatever draws the HUD.
+    pub fn end_frame(expire_frames: u64) -> Vec<HudWidget> {
+        let mut buffer = BUFFER.lock().expect("debug hud poisoned");
+        let frame = buffer.frame;
+        buffer
+            .widgets
+            .retain(|widget| frame - widget.last_frame < expire_frames.max(1));
+        buffer.frame += 1;
+        buffer.widgets.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::MutexGuard;
+
+    use super::*;
+
+    const EXPIRE_FRAMES: u64 = 3;
+
+    // The buffer is global, so the tests take turns with it
+    static TESTS: Mutex<()> = Mutex::new(());
+
+    fn empty_hud() -> MutexGuard<'static, ()> {
+        let guard = TESTS
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        DebugHud::clear();
+        guard
+    }
+
+    fn find<'a>(widgets: &'a [HudWidget], name: &str) -> Option<&'a HudValue> {
+        widgets
+            .iter()
+            .find(|widget| widget.name == name)
+            .map(|widget| &widget.value)
+    }
+
+    #[test]
+    fn widgets_keep_the_order_they_were_first_seen_in() {
+        let _hud = empty_hud();
+        DebugHud::watch("player.speed", 4.5);
+        DebugHud::progress("loading", 1.5);
+        for sample in 0..PLOT_SAMPLES + 10 {
+            DebugHud::plot("frame ms", sample as f32);
+        }
+        DebugHud::watch("player.speed", "5.0 m/s");
+
+        let widgets = DebugHud::end_frame(EXPIRE_FRAMES);
+        let names: Vec<&str> = widgets.iter().map(|widget| widget.name.as_str()).collect();
+        assert_eq!(names, ["player.speed", "loading", "frame ms"]);
+        // A watch shows its last value
+        assert!(
+            matches!(find(&widgets, "player.speed"), Some(HudValue::Text(text)) if text == "5.0 m/s")
+        );
+        // Progress is clamped
+        assert!(
+            matches!(find(&widgets, "loading"), Some(HudValue::Progress(fraction)) if *fraction == 1.0)
+        );
+        // A plot keeps its last samples, oldest first
+        let Some(HudValue::Plot(samples)) = find(&widgets, "frame ms") else {
+            panic!("frame ms isn't a plot");
+        };
+        assert_eq!(samples.len(), PLOT_SAMPLES);
+        assert_eq!(samples.front(), Some(&10.0));
+        assert_eq!(samples.back(), Some(&(PLOT_SAMPLES as f32 + 9.0)));
+    }
+
+    #[test]
+    fn widgets_left_alone_expire() {
+        let _hud = empty_hud();
+        DebugHud::watch("player.speed", 4.5);
+        DebugHud::progress("loading", 0.5);
+        DebugHud::plot("frame ms", 1.0);
+        assert_eq!(DebugHud::end_frame(EXPIRE_FRAMES).len(), 3);
+
+        // Only the plot is refreshed from now on
+        for _ in 1..EXPIRE_FRAMES {
+            DebugHud::plot("frame ms", 1.0);
+            assert_eq!(DebugHud::end_frame(EXPIRE_FRAMES).len(), 3);
+        }
+        DebugHud::plot("frame ms", 1.0);
+        let widgets = DebugHud::end_frame(EXPIRE_FRAMES);
+        assert_eq!(widgets.len(), 1);
+        assert_eq!(widgets[0].name, "frame ms");
+    }
+
+    #[test]
+    fn a_widget_called_with_another_kind_is_replaced() {
+        let _hud = empty_hud();
+        DebugHud::plot("frame ms", 1.0);
+        DebugHud::end_frame(EXPIRE_FRAMES);
+        DebugHud::watch("frame ms", "paused");
+        let widgets = DebugHud::end_frame(EXPIRE_FRAMES);
+        assert!(matches!(
+            find(&widgets, "frame ms"),
+            Some(HudValue::Text(_))
+        ));
+    }
+
+    #[test]
+    fn remove_and_clear_drop_widgets_right_away() {
+        let _hud = empty_hud();
+        DebugHud::watch("frame ms", "paused");
+        DebugHud::end_frame(EXPIRE_FRAMES);
+        DebugHud::remove("frame ms");
+        DebugHud::watch("kept", 1);
+        let widgets = DebugHud::end_frame(EXPIRE_FRAMES);
+        assert_eq!(widgets.len(), 1);
+        assert_eq!(widgets[0].name, "kept");
+
+        DebugHud::clear();
+        assert!(DebugHud::end_frame(EXPIRE_FRAMES).is_empty());
+    }
+}
@@ -0,0 +1,591 @@
+//! A stack of game states (gameplay, menus, loading screens) on top of the frame loop.
+//!
+//! The top of the stack is the current state. Pushing a state covers the one below
+//! without leaving it, e.g. a pause menu over gameplay; popping goes back to it exactly
+//! as it was. Each state says whether gameplay time runs while it is current, and
+//! systems can be limited to some states with `run_in_states`:
+//!
+//! ```ignore
+//! let system = world
+//!     .system_named::<(&mut Enemy, &Time)>("Move Enemies")
+//!     .each(|(enemy, time)| enemy.advance(time.scaled_delta()));
+//! run_in_states(world, system.id(), &[STATE_GAMEPLAY]);
+//!
+//! // From a system, e.g. on Escape
+//! world.get::<&mut GameStates>(|states| states.push(STATE_MENU));
+//! ```
+//!
+//! The input plugin switches input contexts per state and the window plugin grabs or
+//! frees the cursor, both through GameStateChanged.
+
+use std::collections::VecDeque;
+
+use flecs_ecs::prelude::*;
+
+use crate::{
+    console::ConsoleCommands,
+    time::{PhysicsTime, Time},
+};
+
+/// Identifies a game state, like ContextId does an input context. Games add their own
+/// past the built-in ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GameStateId(pub u32);
+
+pub const STATE_GAMEPLAY: GameStateId = GameStateId(1);
+pub const STATE_MENU: GameStateId = GameStateId(2);
+pub const STATE_LOADING: GameStateId = GameStateId(3);
+
+type Hook = Box<dyn FnMut(&World) + Send + Sync>;
+
+/// A state the GameStates stack can hold, with its hooks.
+pub struct GameState {
+    id: GameStateId,
+    name: String,
+    pauses_time: bool,
+    shows_cursor: bool,
+    on_enter: Option<Hook>,
+    on_exit: Option<Hook>,
+}
+
+impl GameState {
+    /// A state where gameplay time runs and the cursor is grabbed, like gameplay.
+    pub fn new(id: GameStateId, name: impl Into<String>) -> Self {
+        Self {
+            id,
+            name: name.into(),
+            pauses_time: false,
+            shows_cursor: false,
+            on_enter: None,
+            on_exit: None,
+        }
+    }
+
+    /// Pauses gameplay time and physics while this state is current, e.g. menus.
+    pub fn pauses_time(mut self) -> Self {
+        self.pauses_time = true;
+        self
+    }
+
+    /// Frees and shows the cursor while this state is current.
+    pub fn shows_cursor(mut self) -> Self {
+        self.shows_cursor = true;
+        self
+    }
+
+    /// Called when the state is pushed or replaces another, after time is paused or
+    /// resumed for it.
+    pub fn on_enter(mut self, hook: impl FnMut(&World) + Send + Sync + 'static) -> Self {
+        self.on_enter = Some(Box::new(hook));
+        self
+    }
+
+    /// Called when the state is popped or replaced. Not when another is pushed over it.
+    pub fn on_exit(mut self, hook: impl FnMut(&World) + Send + Sync + 'static) -> Self {
+        self.on_exit = Some(Box::new(hook));
+        self
+    }
+
+    pub fn id(&self) -> GameStateId {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_time_paused(&self) -> bool {
+        self.pauses_time
+    }
+
+    pub fn is_cursor_shown(&self) -> bool {
+        self.shows_cursor
+    }
+}
+
+/// How the stack changed in a GameStateChanged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameStateChange {
+    Pushed,
+    Popped,
+    Replaced,
+}
+
+/// Emitted on the GameStates singleton entity after every change of the stack, once
+/// the hooks ran:
+///
+/// ```ignore
+/// world
+///     .entity_from::<GameStates>()
+///     .observe_payload::<&GameStateChanged>(|changed| {
+///         log::info!("{:?} -> {:?}", changed.from, changed.to);
+///     });
+/// ```
+#[derive(Component, Clone, Copy, Debug)]
+pub struct GameStateChanged {
+    pub change: GameStateChange,
+    /// The state that was current.
+    pub from: GameStateId,
+    /// The state that is current now.
+    pub to: GameStateId,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Request {
+    Push(GameStateId),
+    Pop,
+    Replace(GameStateId),
+}
+
+// What a push paused, so the pop puts it back as it was
+#[derive(Clone, Copy, Debug)]
+struct SavedTime {
+    time_paused: bool,
+    physics_paused: bool,
+}
+
+/// The game state stack, in the singleton of the same name. It starts with
+/// STATE_GAMEPLAY, the bottom state is never popped.
+///
+/// `push`, `pop` and `replace` are queued and applied in the order they were made at
+/// the end of the frame, after every system ran, so the next frame starts in the new
+/// state: its systems are already enabled or disabled for it. Entering a state
+/// pauses or resumes gameplay time (`Time::set_paused`) and the fixed physics steps
+/// (`PhysicsTime::set_paused`) for it; popping it restores both as they were before the
+/// push, so a pause from the console survives a trip through the menu.
+#[derive(Component)]
+pub struct GameStates {
+    states: Vec<GameState>,
+    stack: Vec<(GameStateId, SavedTime)>,
+    requests: VecDeque<Request>,
+    // Systems and the states they run in
+    gated: Vec<(Entity, Vec<GameStateId>)>,
+}
+
+impl Default for GameStates {
+    fn default() -> Self {
+        Self {
+            states: vec![
+                GameState::new(STATE_GAMEPLAY, "gameplay"),
+                GameState::new(STATE_MENU, "menu")
+                    .pauses_time()
+                    .shows_cursor(),
+                GameState::new(STATE_LOADING, "loading").pauses_time(),
+            ],
+            stack: vec![(
+                STATE_GAMEPLAY,
+                SavedTime {
+                    time_paused: false,
+                    physics_paused: false,
+                },
+            )],
+            requests: VecDeque::new(),
+            gated: Vec::new(),
+        }
+    }
+}
+
+impl GameStates {
+    /// Adds a state, or replaces the one with the same id, hooks included.
+    pub fn define(&mut self, state: GameState) {
+        match self.states.iter_mut().find(|s| s.id == state.id) {
+            Some(existing) => *existing = state,
+            None => self.states.push(state),
+        }
+    }
+
+    pub fn state(&self, id: GameStateId) -> Option<&GameState> {
+        self.states.iter().find(|state| state.id == id)
+    }
+
+    /// Covers the current state with `id`.
+    pub fn push(&mut self, id: GameStateId) {
+        self.requests.push_back(Request::Push(id));
+    }
+
+    /// Leaves the current state for the one it covers.
+    pub fn pop(&mut self) {
+        self.requests.push_back(Request::Pop);
+    }
+
+    /// Leaves the current state for `id`, at the same depth, e.g. loading to gameplay.
+    pub fn replace(&mut self, id: GameStateId) {
+        self.requests.push_back(Request::Replace(id));
+    }
+
+    pub fn current(&self) -> GameStateId {
+        self.stack[self.stack.len() - 1].0
+    }
+
+    /// The stack, bottom first.
+    pub fn stack(&self) -> impl Iterator<Item = GameStateId> + '_ {
+        self.stack.iter().map(|(id, _)| *id)
+    }
+
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// `id` is somewhere on the stack, current or covered.
+    pub fn contains(&self, id: GameStateId) -> bool {
+        self.stack.iter().any(|(state, _)| *state == id)
+    }
+
+    /// Changes are queued that the next frame applies.
+    pub fn has_pending(&self) -> bool {
+        !self.requests.is_empty()
+    }
+
+    fn name_of(&self, id: GameStateId) -> String {
+        self.state(id)
+            .map_or_else(|| format!("state {}", id.0), |state| state.name.clone())
+    }
+
+    // Systems to enable and disable for the current state
+    fn gated_systems(&self) -> Vec<(Entity, bool)> {
+        let current = self.current();
+        self.gated
+            .iter()
+            .map(|(system, states)| (*system, states.contains(&current)))
+            .collect()
+    }
+}
+
+/// Runs `system` only while the current game state is one of `states`; it is disabled
+/// the rest of the time, so it sees no frames at all, not even zero deltas.
+pub fn run_in_states(world: &World, system: impl Into<Entity>, states: &[GameStateId]) {
+    let system = system.into();
+    let enabled = world.get::<&mut GameStates>(|game_states| {
+        game_states.gated.retain(|(entity, _)| *entity != system);
+        game_states.gated.push((system, states.to_vec()));
+        states.contains(&game_states.current())
+    });
+    set_system_enabled(world.entity_from_id(system), enabled);
+}
+
+fn set_system_enabled(system: EntityView, enabled: bool) {
+    if enabled {
+        system.enable_self();
+    } else {
+        system.disable_self();
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum HookKind {
+    Enter,
+    Exit,
+}
+
+// Hooks run without the singleton borrowed, so they can change the stack themselves
+fn call_hook(world: &World, id: GameStateId, kind: HookKind) {
+    let hook = world.get::<&mut GameStates>(|game_states| {
+        let state = game_states.states.iter_mut().find(|s| s.id == id)?;
+        match kind {
+            HookKind::Enter => state.on_enter.take(),
+            HookKind::Exit => state.on_exit.take(),
+        }
+    });
+    let Some(mut hook) = hook else {
+        return;
+    };
+    hook(world);
+    world.get::<&mut GameStates>(|game_states| {
+        if let Some(state) = game_states.states.iter_mut().find(|s| s.id == id) {
+            let slot = match kind {
+                HookKind::Enter => &mut state.on_enter,
+                HookKind::Exit => &mut state.on_exit,
+            };
+            // Unless the hook redefined its own state
+            if slot.is_none() {
+                *slot = Some(hook);
+            }
+        }
+    });
+}
+
+fn apply_time(world: &World, id: GameStateId) {
+    let paused = world.get::<&GameStates>(|game_states| {
+        game_states.state(id).is_some_and(|state| state.pauses_time)
+    });
+    world.get::<&mut Time>(|time| time.set_paused(paused));
+    world.get::<&mut PhysicsTime>(|time| time.set_paused(paused));
+}
+
+fn saved_time(world: &World) -> SavedTime {
+    SavedTime {
+        time_paused: world.get::<&Time>(|time| time.is_paused()),
+        physics_paused: world.get::<&PhysicsTime>(|time| time.is_paused()),
+    }
+}
+
+/// Applies the queued changes of the GameStates stack, running hooks and emitting
+/// GameStateChanged for each. `App::update` calls it after every frame; call it
+/// yourself to apply changes made outside of one.
+pub fn apply_game_states(world: &World) {
+    while let Some(request) = world.get::<&mut GameStates>(|states| states.requests.pop_front()) {
+        let from = world.get::<&GameStates>(|states| states.current());
+        let change = match request {
+            Request::Push(to) => {
+                let saved = saved_time(world);
+                world.get::<&mut GameStates>(|states| states.stack.push((to, saved)));
+                apply_time(world, to);
+                call_hook(world, to, HookKind::Enter);
+                GameStateChanged {
+                    change: GameStateChange::Pushed,
+                    from,
+                    to,
+                }
+            }
+            Request::Pop => {
+                let popped = world.get::<&mut GameStates>(|states| {
+                    if states.stack.len() > 1 {
+                        states.stack.pop()
+                    } else {
+                        None
+                    }
+                });
+                let Some((_, saved)) = popped else {
+                    log::warn!("Can't pop the last game state");
+                    continue;
+                };
+                call_hook(world, from, HookKind::Exit);
+                world.get::<&mut Time>(|time| time.set_paused(saved.time_paused));
+                world.get::<&mut PhysicsTime>(|time| time.set_paused(saved.physics_paused));
+                GameStateChanged {
+                    change: GameStateChange::Popped,
+                    from,
+                    to: world.get::<&GameStates>(|states| states.current()),
+                }
+            }
+            Request::Replace(to) => {
+                call_hook(world, from, HookKind::Exit);
+                world.get::<&mut GameStates>(|states| {
+                    let top = states.stack.len() - 1;
+                    states.stack[top].0 = to;
+                });
+                apply_time(world, to);
+                call_hook(world, to, HookKind::Enter);
+                GameStateChanged {
+                    change: GameStateChange::Replaced,
+                    from,
+                    to,
+                }
+            }
+        };
+
+        let gated = world.get::<&GameStates>(|states| {
+            log::info!(
+                "Game state {:?}: {} -> {}",
+                change.change,
+                states.name_of(change.from),
+                states.name_of(change.to)
+            );
+            states.gated_systems()
+        });
+        for (system, enabled) in gated {
+            let system = world.entity_from_id(system);
+            if system.is_alive() {
+                set_system_enabled(system, enabled);
+            }
+        }
+
+        world.entity_from::<GameStates>().emit(&change);
+    }
+}
+
+pub(crate) fn register_game_state_commands(commands: &mut ConsoleCommands) {
+    commands.register(
+        "state.stack",
+        "List the game states, bottom first",
+        |world, _| {
+            Ok(world.get::<&GameStates>(|states| {
+                states
+                    .stack()
+                    .map(|id| states.name_of(id))
+                    .collect::<Vec<_>>()
+                    .join(" > ")
+            }))
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::App;
+
+    const FRAME: Duration = Duration::from_millis(16);
+
+    type Log = Arc<Mutex<Vec<String>>>;
+
+    // Gameplay, loading and menu with their enter and exit hooks and GameStateChanged
+    // recorded, in that order
+    fn states_app() -> (App, Log, Log) {
+        let app = App::new();
+        let hooks: Log = Arc::default();
+        app.world.get::<&mut GameStates>(|states| {
+            for (state, name, pauses) in [
+                (STATE_GAMEPLAY, "gameplay", false),
+                (STATE_LOADING, "loading", true),
+                (STATE_MENU, "menu", true),
+            ] {
+                let (enter, exit) = (hooks.clone(), hooks.clone());
+                let mut state = GameState::new(state, name)
+                    .on_enter(move |_| enter.lock().unwrap().push(format!("enter {}", name)))
+                    .on_exit(move |_| exit.lock().unwrap().push(format!("exit {}", name)));
+                if pauses {
+                    state = state.pauses_time();
+                }
+                states.define(state);
+            }
+        });
+
+        let changes: Log = Arc::default();
+        let changed = changes.clone();
+        app.world
+            .entity_from::<GameStates>()
+            .observe_payload::<&GameStateChanged>(move |change| {
+                changed.lock().unwrap().push(format!(
+                    "{:?} {} {}",
+                    change.change, change.from.0, change.to.0
+                ));
+            });
+        (app, hooks, changes)
+    }
+
+    fn frame(app: &mut App) {
+        app.world.get::<&mut Time>(|time| {
+            time.update();
+            time.set_delta(FRAME);
+        });
+        app.update();
+    }
+
+    fn take(log: &Log) -> Vec<String> {
+        std::mem::take(&mut *log.lock().unwrap())
+    }
+
+    fn stack(app: &App) -> Vec<u32> {
+        app.world
+            .get::<&GameStates>(|states| states.stack().map(|id| id.0).collect())
+    }
+
+    fn paused(app: &App) -> (bool, bool) {
+        (
+            app.world.get::<&Time>(|time| time.is_paused()),
+            app.world.get::<&PhysicsTime>(|time| time.is_paused()),
+        )
+    }
+
+    #[test]
+    fn pushed_states_pause_time_until_they_are_popped() {
+        let (mut app, hooks, changes) = states_app();
+
+        // What a gameplay-only system and an everywhere system saw, in scaled seconds
+        let gameplay_deltas: Arc<Mutex<Vec<f32>>> = Arc::default();
+        let everywhere_deltas: Arc<Mutex<Vec<f32>>> = Arc::default();
+        let seen = gameplay_deltas.clone();
+        let gameplay = app
+            .world
+            .system_named::<&Time>("Gameplay Only")
+            .each(move |time| seen.lock().unwrap().push(time.scaled_delta().as_secs_f32()));
+        run_in_states(&app.world, gameplay.id(), &[STATE_GAMEPLAY]);
+        let seen = everywhere_deltas.clone();
+        app.world
+            .system_named::<&Time>("Everywhere")
+            .each(move |time| seen.lock().unwrap().push(time.scaled_delta().as_secs_f32()));
+
+        frame(&mut app);
+        assert_eq!(stack(&app), [STATE_GAMEPLAY.0]);
+        assert_eq!(paused(&app), (false, false));
+        assert_eq!(*gameplay_deltas.lock().unwrap(), [FRAME.as_secs_f32()]);
+
+        // A pause menu over loading. Changes wait for the end of the frame
+        app.world.get::<&mut GameStates>(|states| {
+            states.push(STATE_LOADING);
+            states.push(STATE_MENU);
+        });
+        assert_eq!(stack(&app), [STATE_GAMEPLAY.0]);
+        frame(&mut app);
+        gameplay_deltas.lock().unwrap().clear();
+        everywhere_deltas.lock().unwrap().clear();
+        for _ in 0..5 {
+            frame(&mut app);
+        }
+        assert_eq!(
+            stack(&app),
+            [STATE_GAMEPLAY.0, STATE_LOADING.0, STATE_MENU.0]
+        );
+        // Without exiting the covered states
+        assert_eq!(take(&hooks), ["enter loading", "enter menu"]);
+        assert_eq!(take(&changes), ["Pushed 1 3", "Pushed 3 2"]);
+        assert_eq!(paused(&app), (true, true));
+        // The gameplay system doesn't run, the others only see zero scaled deltas
+        assert!(gameplay_deltas.lock().unwrap().is_empty());
+        assert_eq!(*everywhere_deltas.lock().unwrap(), [0.0; 5]);
+
+        app.world.get::<&mut GameStates>(|states| states.pop());
+        frame(&mut app);
+        assert_eq!(stack(&app), [STATE_GAMEPLAY.0, STATE_LOADING.0]);
+        assert_eq!(paused(&app), (true, true));
+        assert!(gameplay_deltas.lock().unwrap().is_empty());
+
+        app.world.get::<&mut GameStates>(|states| states.pop());
+        frame(&mut app);
+        assert_eq!(stack(&app), [STATE_GAMEPLAY.0]);
+        assert_eq!(paused(&app), (false, false));
+        assert_eq!(take(&hooks), ["exit menu", "exit loading"]);
+        assert_eq!(take(&changes), ["Popped 2 3", "Popped 3 1"]);
+
+        // The gameplay system runs again the next frame, never having seen a zero delta
+        frame(&mut app);
+        let deltas = gameplay_deltas.lock().unwrap();
+        assert_eq!(deltas.len(), 1);
+        assert!(deltas[0] > 0.0);
+    }
+
+    #[test]
+    fn popping_restores_time_as_it_was_before_the_push() {
+        let (mut app, _, _) = states_app();
+        frame(&mut app);
+
+        // Paused from the console, then through the menu
+        app.world.get::<&mut Time>(|time| time.set_paused(true));
+        app.world
+            .get::<&mut GameStates>(|states| states.push(STATE_MENU));
+        frame(&mut app);
+        app.world.get::<&mut GameStates>(|states| states.pop());
+        frame(&mut app);
+        assert_eq!(paused(&app), (true, false));
+    }
+
+    #[test]
+    fn replace_swaps_the_current_state_at_the_same_depth() {
+        let (mut app, hooks, changes) = states_app();
+        frame(&mut app);
+        take(&hooks);
+        take(&changes);
+
+        app.world
+            .get::<&mut GameStates>(|states| states.replace(STATE_LOADING));
+        frame(&mut app);
+        assert_eq!(stack(&app), [STATE_LOADING.0]);
+        assert_eq!(paused(&app), (true, true));
+        // Exiting the old state before entering the new one
+        assert_eq!(take(&hooks), ["exit gameplay", "enter loading"]);
+        assert_eq!(take(&changes), ["Replaced 1 3"]);
+
+        // The bottom state is never popped, and the refused pop is dropped
+        app.world.get::<&mut GameStates>(|states| states.pop());
+        frame(&mut app);
+        assert_eq!(stack(&app), [STATE_LOADING.0]);
+        assert!(take(&changes).is_empty());
+        assert!(app.world.get::<&GameStates>(|states| !states.has_pending()));
+    }
+}
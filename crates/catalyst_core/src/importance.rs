@@ -0,0 +1,205 @@
+use flecs_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::time::Time;
+
+/// Closest an importance bias can make an entity count as, in world units. Entities
+/// really closer than this keep their distance.
+pub const MIN_EFFECTIVE_DISTANCE: f32 = 1.0;
+
+/// How much more detail and attention an entity deserves than its distance alone earns
+/// it: LOD selection, texture streaming and activity culling all measure it as if it
+/// were `total()` world units closer to the camera. The player's weapon or a nearby NPC
+/// gets a positive bias, background clutter a negative one.
+///
+/// `bias` is set by gameplay and saved with the scene. `boost` is raised by the
+/// automatic sources (see ImportanceSettings) and decays back to zero; they only raise
+/// entities that have an ImportanceBias.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImportanceBias {
+    pub bias: f32,
+    #[serde(skip)]
+    pub boost: f32,
+}
+
+impl ImportanceBias {
+    pub fn new(bias: f32) -> Self {
+        Self { bias, boost: 0.0 }
+    }
+
+    /// The bias and the current boost, in world units. Zero when either isn't finite.
+    pub fn total(&self) -> f32 {
+        let total = self.bias + self.boost;
+        if total.is_finite() { total } else { 0.0 }
+    }
+
+    /// Raises the boost to at least `boost`, e.g. every frame a source still applies.
+    pub fn raise(&mut self, boost: f32) {
+        if boost.is_finite() {
+            self.boost = self.boost.max(boost);
+        }
+    }
+
+    /// The distance consumers use instead of `distance`. However big the bias, it never
+    /// gets below MIN_EFFECTIVE_DISTANCE (or the real distance, when that is smaller), so
+    /// derived screen sizes stay finite and detail levels stay within what exists.
+    pub fn effective_distance(&self, distance: f32) -> f32 {
+        (distance - self.total()).max(distance.min(MIN_EFFECTIVE_DISTANCE))
+    }
+}
+
+/// `distance` adjusted by an optional ImportanceBias, for queries with
+/// `Option<&ImportanceBias>`.
+pub fn effective_distance(importance: Option<&ImportanceBias>, distance: f32) -> f32 {
+    match importance {
+        Some(importance) => importance.effective_distance(distance),
+        None => distance,
+    }
+}
+
+/// Automatic importance sources. Boosts are in world units, like ImportanceBias.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct ImportanceSettings {
+    /// For entities drawn near the center of a view (set by the renderer).
+    pub screen_center_boost: f32,
+    /// How near: distance from the view's center in normalized device coordinates,
+    /// 1.0 being the edge of the screen.
+    pub screen_center_radius: f32,
+    /// For entities touching a player's body (an entity with a PlayerIndex), set by the
+    /// physics.
+    pub collision_boost: f32,
+    /// World units per second a boost falls back by once its source stops.
+    pub decay: f32,
+}
+
+impl Default for ImportanceSettings {
+    fn default() -> Self {
+        Self {
+            screen_center_boost: 15.0,
+            screen_center_radius: 0.3,
+            collision_boost: 25.0,
+            decay: 10.0,
+        }
+    }
+}
+
+pub fn importance_system(world: &mut World) {
+    world
+        .component::<ImportanceSettings>()
+        .add_trait::<flecs::Singleton>();
+    world.set(ImportanceSettings::default());
+
+    // PreUpdate, before "Update Activity": sources raise boosts later in the frame, so
+    // a source that still applies keeps its entity boosted without a gap
+    world
+        .system_named::<(&mut ImportanceBias, &ImportanceSettings, &Time)>("Decay Importance")
+        .kind(flecs::pipeline::PreUpdate)
+        .each(|(importance, settings, time)| {
+            if importance.boost > 0.0 {
+                let decay = settings.decay.max(0.0) * time.delta_seconds();
+                importance.boost = (importance.boost - decay).max(0.0);
+            } else {
+                importance.boost = 0.0;
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        App,
+        activity::{ActivityRange, ActivityState},
+        camera::Camera,
+        transform::{GlobalTransform, Transform},
+    };
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn a_bias_moves_an_entity_closer_or_further() {
+        assert!(close(
+            ImportanceBias::new(30.0).effective_distance(100.0),
+            70.0
+        ));
+        assert!(close(
+            ImportanceBias::new(-20.0).effective_distance(10.0),
+            30.0
+        ));
+        // Entities without a bias keep their distance
+        assert_eq!(effective_distance(None, 42.0), 42.0);
+    }
+
+    #[test]
+    fn however_big_the_bias_never_closer_than_the_minimum() {
+        let important = ImportanceBias::new(30.0);
+        assert_eq!(important.effective_distance(10.0), MIN_EFFECTIVE_DISTANCE);
+        assert_eq!(
+            ImportanceBias::new(1e30).effective_distance(1e6),
+            MIN_EFFECTIVE_DISTANCE
+        );
+        // Something already closer keeps its distance
+        assert!(close(important.effective_distance(0.25), 0.25));
+    }
+
+    #[test]
+    fn a_bias_that_isnt_finite_counts_as_none() {
+        assert_eq!(ImportanceBias::new(f32::NAN).effective_distance(50.0), 50.0);
+        assert_eq!(
+            ImportanceBias::new(f32::INFINITY).effective_distance(50.0),
+            50.0
+        );
+    }
+
+    #[test]
+    fn sources_raise_the_boost_to_the_highest_on_top_of_the_bias() {
+        let mut raised = ImportanceBias::new(5.0);
+        raised.raise(10.0);
+        raised.raise(4.0);
+        assert_eq!(raised.boost, 10.0);
+        assert_eq!(raised.total(), 15.0);
+    }
+
+    #[test]
+    fn activity_culling_counts_biased_entities_as_closer() {
+        let mut app = App::new();
+        // A camera at the origin
+        app.world
+            .entity()
+            .set(Camera::default())
+            .set(Transform::default())
+            .set(GlobalTransform::default());
+        let spawn_ranged = |app: &App, x: f32, importance: Option<ImportanceBias>| {
+            let entity = app
+                .world
+                .entity()
+                .set(ActivityRange::default())
+                .set(Transform::from_xyz(x, 0.0, 0.0))
+                .set(GlobalTransform::default());
+            if let Some(importance) = importance {
+                entity.set(importance);
+            }
+            entity.id()
+        };
+        let plain = spawn_ranged(&app, 100.0, None);
+        let biased = spawn_ranged(&app, 100.0, Some(ImportanceBias::new(70.0)));
+        let huge = spawn_ranged(&app, 500.0, Some(ImportanceBias::new(f32::MAX)));
+        let broken = spawn_ranged(&app, 100.0, Some(ImportanceBias::new(f32::NAN)));
+        app.update();
+        app.update();
+
+        let state = |entity: Entity| {
+            app.world
+                .entity_from_id(entity)
+                .get::<&ActivityState>(|state| *state)
+        };
+        assert_eq!(state(plain), ActivityState::Reduced);
+        assert_eq!(state(biased), ActivityState::Active);
+        // Without overflowing
+        assert_eq!(state(huge), ActivityState::Active);
+        assert_eq!(state(broken), ActivityState::Reduced);
+    }
+}
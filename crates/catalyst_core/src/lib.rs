@@ -1,20 +1,74 @@
 use flecs_ecs::prelude::*;
+pub use log;
 pub use rayon;
 pub use tokio;
 
+pub mod activity;
+pub mod animation;
+pub mod bounds;
+pub mod bulk;
 pub mod camera;
+pub mod camera_effects;
+pub mod color;
+pub mod console;
+pub mod debug_hud;
+pub mod game_state;
+pub mod importance;
 pub mod input;
+pub mod light;
+pub mod logging;
+pub mod movement;
 pub mod time;
 pub mod transform;
 pub mod pipeline;
 pub mod physics;
+pub mod player;
+pub mod plugin;
+pub mod profiling;
+pub mod propagation;
+pub mod raycast;
+pub mod registry;
+pub mod replication;
+pub mod rooms;
+pub mod scene_file;
+pub mod sequence;
+pub mod settings;
+pub mod smoothing;
+pub mod stable_id;
+pub mod state_diff;
+pub mod state_machine;
 
 pub use input::*;
+pub use plugin::{PluginError, PluginGroup, PluginGroupBuilder, PluginId};
 
 use crate::{
-    pipeline::define_pipeline_stages, time::{PhysicsTime, Time}, transform::{
-        GlobalTransform, ReflectQuat, ReflectVec3, ReflectVec4, Transform, transform_propagation_system
-    }
+    activity::{ActivityRange, activity_system},
+    animation::{Timeline, timeline_system},
+    bulk::{BulkBudget, DespawnQueue, SpawnQueue, bulk_queue_system},
+    camera::Camera,
+    camera_effects::{CameraEffects, camera_effects_system},
+    console::ConsoleCommands,
+    game_state::{GameStateChanged, GameStates, register_game_state_commands},
+    importance::{ImportanceBias, importance_system},
+    light::{DirectionalLight, PointLight},
+    logging::{EngineConfig, LogBuffer, init_logging, register_log_commands},
+    movement::{MovementIntent, movement_intent_system},
+    physics::{ColliderDefinition, PhysicsMaterialDefinition, RigidBodyDefinition},
+    pipeline::define_pipeline_stages,
+    profiling::register_profile_commands,
+    player::PlayerIndex,
+    propagation::transform_propagation_system,
+    raycast::Raycasters,
+    registry::ComponentRegistry,
+    replication::replication_system,
+    rooms::Room,
+    scene_file::register_scene_file_commands,
+    sequence::sequence_system,
+    settings::{Settings, register_settings_commands},
+    stable_id::stable_id_system,
+    state_diff::{StateDiff, register_state_diff_commands},
+    time::{PhysicsTime, Time, register_time_commands},
+    transform::{GlobalTransform, ReflectQuat, ReflectVec3, ReflectVec4, Transform},
 };
 
 #[derive(Component, Clone)]
@@ -22,8 +76,14 @@ pub struct IoTaskPool(pub tokio::runtime::Handle);
 
 /// The Plugin Trait
 /// Every module (Renderer, Physics, Window) must implement this.
-pub trait Plugin {
+pub trait Plugin: 'static {
     fn build(&self, app: &mut App);
+
+    /// Plugins that must be added before this one. Checked by `add_plugin`,
+    /// so a wrong order fails at startup instead of inside a system.
+    fn dependencies(&self) -> Vec<PluginId> {
+        Vec::new()
+    }
 }
 
 /// The Engine Application
@@ -32,13 +92,38 @@ pub struct App {
     pub world: World,
     pub running: bool,
     pub io_runtime: tokio::runtime::Runtime,
+    // Plugins added so far, in order
+    plugins: Vec<PluginId>,
 }
 
 #[derive(Component)]
 pub struct Source;
 
+/// Set by any system to shut the app down cleanly after the current frame.
+/// The runner exits the event loop and the process returns `code`.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct AppExit {
+    pub requested: bool,
+    pub code: i32,
+}
+
+impl AppExit {
+    pub fn request(&mut self, code: i32) {
+        self.requested = true;
+        self.code = code;
+    }
+}
+
 impl App {
     pub fn new() -> Self {
+        Self::with_config(EngineConfig::default())
+    }
+
+    /// Like `new`, with options that must be applied before anything else runs (e.g. logging).
+    pub fn with_config(config: EngineConfig) -> Self {
+        // 0. Logging first, so everything below can report
+        let log_buffer = init_logging(&config.log);
+
         // 1. Initialize Rayon (Global Compute Pool)
         // Rayon initializes itself globally the first time you use it.
         // But we can configure it manually if we want to reserve threads.
@@ -78,6 +163,54 @@ impl App {
             .add_trait::<flecs::Singleton>();
         world.set(PhysicsTime::default());
 
+        world
+            .component::<GameStates>()
+            .add_trait::<flecs::Singleton>();
+        world.set(GameStates::default());
+        world.component::<GameStateChanged>();
+
+        world
+            .component::<AppExit>()
+            .add_trait::<flecs::Singleton>();
+        world.set(AppExit::default());
+
+        world
+            .component::<ConsoleCommands>()
+            .add_trait::<flecs::Singleton>();
+        let mut commands = ConsoleCommands::default();
+        register_log_commands(&mut commands);
+        register_scene_file_commands(&mut commands);
+        register_profile_commands(&mut commands);
+        register_state_diff_commands(&mut commands);
+        register_time_commands(&mut commands);
+        register_game_state_commands(&mut commands);
+        register_settings_commands(&mut commands);
+        if std::env::var_os("CATALYST_PROFILE").is_some() {
+            profiling::set_enabled(true);
+        }
+        world.set(commands);
+
+        // Backends are registered by the plugins that own the geometry
+        world
+            .component::<Raycasters>()
+            .add_trait::<flecs::Singleton>();
+        world.set(Raycasters::default());
+
+        // The debug console reads the log from here
+        world
+            .component::<LogBuffer>()
+            .add_trait::<flecs::Singleton>();
+        world.set(log_buffer);
+        // Read before any plugin registers its sections
+        world
+            .component::<Settings>()
+            .add_trait::<flecs::Singleton>();
+        world.set(Settings::load(&config.settings));
+        world
+            .component::<EngineConfig>()
+            .add_trait::<flecs::Singleton>();
+        world.set(config);
+
         define_pipeline_stages(&mut world);
 
         let vec3_id = world.component_id::<ReflectVec3>();
@@ -98,28 +231,139 @@ impl App {
             .member(vec4_id, "z_axis")
             .member(vec4_id, "w_axis");
 
+        world
+            .component::<ComponentRegistry>()
+            .add_trait::<flecs::Singleton>();
+        world.set(ComponentRegistry::default());
+
+        world
+            .component::<StateDiff>()
+            .add_trait::<flecs::Singleton>();
+        world.set(StateDiff::default());
+
+        world
+            .component::<BulkBudget>()
+            .add_trait::<flecs::Singleton>();
+        world.set(BulkBudget::default());
+        world
+            .component::<SpawnQueue>()
+            .add_trait::<flecs::Singleton>();
+        world.set(SpawnQueue::default());
+        world
+            .component::<DespawnQueue>()
+            .add_trait::<flecs::Singleton>();
+        world.set(DespawnQueue::default());
+
         let mut app = Self {
             world,
             running: true,
             io_runtime,
+            plugins: Vec::new(),
         };
 
+        // Core components for the inspector and scene files; plugins register their own
+        app.register_component::<Transform>()
+            .register_component::<Camera>()
+            .register_component::<CameraEffects>()
+            .register_component::<PointLight>()
+            .register_component::<DirectionalLight>()
+            .register_component::<PlayerIndex>()
+            .register_component::<MovementIntent>()
+            .register_component::<RigidBodyDefinition>()
+            .register_component::<ColliderDefinition>()
+            .register_component::<PhysicsMaterialDefinition>()
+            .register_component::<Room>()
+            .register_component::<ActivityRange>()
+            .register_component::<ImportanceBias>()
+            .register_component::<Timeline>();
+
+        movement_intent_system(&mut app.world);
+        replication_system(&mut app.world);
         transform_propagation_system(&mut app.world);
+        camera_effects_system(&mut app.world);
+        sequence_system(&mut app.world);
+        timeline_system(&mut app.world);
+        stable_id_system(&mut app.world);
+        importance_system(&mut app.world);
+        activity_system(&mut app.world);
+        bulk_queue_system(&mut app.world);
 
         app
     }
 
+    /// Adds a plugin. Panics with a readable message if its dependencies are missing.
     pub fn add_plugin<P: Plugin>(&mut self, plugin: P) -> &mut Self {
-        plugin.build(self);
+        if let Err(e) = self.try_add_plugin(plugin) {
+            panic!("Failed to add plugin: {}", e);
+        }
         self
     }
 
+    pub fn try_add_plugin<P: Plugin>(&mut self, plugin: P) -> Result<&mut Self, PluginError> {
+        self.try_add_boxed_plugin(PluginId::of::<P>(), &plugin)?;
+        Ok(self)
+    }
+
+    /// Adds every plugin of a group in order, e.g. `app.add_plugins(DefaultPlugins)`.
+    pub fn add_plugins<G: PluginGroup>(&mut self, group: G) -> &mut Self {
+        if let Err(e) = group.build().finish(self) {
+            panic!("Failed to add plugins: {}", e);
+        }
+        self
+    }
+
+    pub fn has_plugin<P: Plugin>(&self) -> bool {
+        self.plugins.contains(&PluginId::of::<P>())
+    }
+
+    pub(crate) fn try_add_boxed_plugin(
+        &mut self,
+        id: PluginId,
+        plugin: &dyn Plugin,
+    ) -> Result<(), PluginError> {
+        if self.plugins.contains(&id) {
+            return Err(PluginError::AlreadyAdded(id.name));
+        }
+
+        if let Some(missing) = plugin
+            .dependencies()
+            .into_iter()
+            .find(|dependency| !self.plugins.contains(dependency))
+        {
+            return Err(PluginError::MissingDependency {
+                plugin: id.name,
+                dependency: missing.name,
+            });
+        }
+
+        plugin.build(self);
+        self.plugins.push(id);
+        Ok(())
+    }
+
     pub fn update(&mut self) {
         if !self.running {
             return;
         }
 
         self.world.progress();
+        // Outside the frame, so the next one runs with the systems of the new state
+        game_state::apply_game_states(&self.world);
+        profiling::end_frame();
+        state_diff::end_frame(&self.world);
+
+        let exiting = self.world.get::<&AppExit>(|exit| exit.requested);
+        // Changes still waiting for their save delay are written before exiting
+        settings::end_frame(&self.world, exiting);
+        if exiting {
+            self.running = false;
+        }
+    }
+
+    /// Exit code requested through AppExit, if any.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.world
+            .get::<&AppExit>(|exit| exit.requested.then_some(exit.code))
     }
 
     pub fn register_singleton<T: ComponentId + DataComponent + ComponentType<Struct>>(&mut self, component: T) -> &mut Self {
@@ -137,6 +381,6 @@ impl App {
     }
 
     pub fn startup(&mut self) {
-        println!("App Startup");
+        log::info!("App Startup");
     }
 }
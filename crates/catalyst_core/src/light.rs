@@ -0,0 +1,40 @@
+use flecs_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::color::Color;
+
+/// Omni light at the entity's GlobalTransform position.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PointLight {
+    pub color: Color, // Alpha is ignored
+    pub intensity: f32,
+    pub radius: f32, // Range of influence, also the gizmo size
+}
+
+impl Default for PointLight {
+    fn default() -> Self {
+        Self {
+            color: Color::WHITE,
+            intensity: 10.0,
+            radius: 5.0,
+        }
+    }
+}
+
+/// Sun-like light shining along the entity's forward (-Z) axis. Position is ignored.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DirectionalLight {
+    pub color: Color, // Alpha is ignored
+    pub intensity: f32,
+}
+
+impl Default for DirectionalLight {
+    fn default() -> Self {
+        Self {
+            color: Color::WHITE,
+            intensity: 5.0,
+        }
+    }
+}
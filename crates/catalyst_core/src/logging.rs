@@ -0,0 +1,268 @@
+use std::{
+    collections::VecDeque,
+    io::Write,
+    sync::{Arc, Mutex, OnceLock, RwLock},
+    time::{Duration, Instant},
+};
+
+use flecs_ecs::prelude::*;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use crate::{console::ConsoleCommands, settings::SettingsConfig};
+
+/// Checked before LogSettings::filter. RUST_LOG is used when this one isn't set.
+pub const LOG_ENV: &str = "CATALYST_LOG";
+
+/// Engine options that have to be known before the World exists. See App::with_config.
+#[derive(Component, Clone, Debug, Default)]
+pub struct EngineConfig {
+    pub log: LogSettings,
+    pub settings: SettingsConfig,
+}
+
+#[derive(Clone, Debug)]
+pub struct LogSettings {
+    /// RUST_LOG-style filter: a default level plus per-module overrides,
+    /// e.g. "info,catalyst_renderer=debug,wgpu_core=warn".
+    pub filter: String,
+    /// Lines kept in memory for the debug console.
+    pub buffer_lines: usize,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        Self {
+            // wgpu is chatty at info
+            filter: "info,wgpu_core=warn,wgpu_hal=warn,naga=warn".to_string(),
+            buffer_lines: 1000,
+        }
+    }
+}
+
+/// A default level plus per-module levels. The longest matching module prefix wins.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogFilter {
+    pub default: LevelFilter,
+    pub modules: Vec<(String, LevelFilter)>,
+}
+
+impl LogFilter {
+    /// Parses "level,module=level,...". Unknown levels are an error, a bare module name means trace.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut filter = Self {
+            default: LevelFilter::Info,
+            modules: Vec::new(),
+        };
+
+        for part in spec.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            match part.split_once('=') {
+                Some((module, level)) => {
+                    let level = level
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("unknown log level '{}'", level.trim()))?;
+                    filter.modules.push((module.trim().to_string(), level));
+                }
+                None => match part.parse() {
+                    Ok(level) => filter.default = level,
+                    Err(_) => filter.modules.push((part.to_string(), LevelFilter::Trace)),
+                },
+            }
+        }
+
+        // Longest first, so the first match is the most specific one
+        filter
+            .modules
+            .sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()));
+        Ok(filter)
+    }
+
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .find(|(module, _)| {
+                target == module
+                    || target
+                        .strip_prefix(module.as_str())
+                        .is_some_and(|rest| rest.starts_with("::"))
+            })
+            .map(|(_, level)| *level)
+            .unwrap_or(self.default)
+    }
+
+    /// The most verbose level anything can be logged at.
+    pub fn max_level(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, LevelFilter::max)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LogLine {
+    /// Increases by one per line, for readers that only want what is new.
+    pub sequence: u64,
+    /// Time since logging was initialized.
+    pub time: Duration,
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+struct LogRing {
+    lines: VecDeque<LogLine>,
+    capacity: usize,
+    next_sequence: u64,
+}
+
+/// The last LogSettings::buffer_lines log lines. Cheap to clone, every clone shares the lines.
+#[derive(Component, Clone)]
+pub struct LogBuffer {
+    ring: Arc<Mutex<LogRing>>,
+}
+
+impl LogBuffer {
+    fn new(capacity: usize) -> Self {
+        Self {
+            ring: Arc::new(Mutex::new(LogRing {
+                lines: VecDeque::with_capacity(capacity),
+                capacity,
+                next_sequence: 0,
+            })),
+        }
+    }
+
+    fn push(&self, time: Duration, level: Level, target: &str, message: String) {
+        let Ok(mut ring) = self.ring.lock() else {
+            return;
+        };
+        let sequence = ring.next_sequence;
+        ring.next_sequence += 1;
+
+        if ring.lines.len() >= ring.capacity {
+            ring.lines.pop_front();
+        }
+        ring.lines.push_back(LogLine {
+            sequence,
+            time,
+            level,
+            target: target.to_string(),
+            message,
+        });
+    }
+
+    /// Lines with a sequence number of at least `sequence`, oldest first.
+    /// Lines already dropped from the ring are skipped.
+    pub fn since(&self, sequence: u64) -> Vec<LogLine> {
+        self.ring
+            .lock()
+            .map(|ring| {
+                ring.lines
+                    .iter()
+                    .filter(|line| line.sequence >= sequence)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+struct EngineLogger {
+    filter: RwLock<LogFilter>,
+    buffer: LogBuffer,
+    start: Instant,
+}
+
+impl Log for EngineLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.filter
+            .read()
+            .map(|filter| metadata.level() <= filter.level_for(metadata.target()))
+            .unwrap_or(false)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let time = self.start.elapsed();
+        let message = record.args().to_string();
+        let _ = writeln!(
+            std::io::stderr().lock(),
+            "[{:>9.3}s {:<5} {}] {}",
+            time.as_secs_f64(),
+            record.level(),
+            record.target(),
+            message
+        );
+        self.buffer
+            .push(time, record.level(), record.target(), message);
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stderr().flush();
+    }
+}
+
+static LOGGER: OnceLock<&'static EngineLogger> = OnceLock::new();
+
+/// Installs the engine logger as the `log` backend, with the filter from
+/// CATALYST_LOG, RUST_LOG or `settings` (first one set wins).
+/// Only the first call installs anything; later calls return the same buffer.
+pub fn init_logging(settings: &LogSettings) -> LogBuffer {
+    let logger = LOGGER.get_or_init(|| {
+        let spec = std::env::var(LOG_ENV)
+            .or_else(|_| std::env::var("RUST_LOG"))
+            .unwrap_or_else(|_| settings.filter.clone());
+
+        let (filter, error) = match LogFilter::parse(&spec) {
+            Ok(filter) => (filter, None),
+            Err(error) => (
+                LogFilter::parse(&LogSettings::default().filter).unwrap(),
+                Some(error),
+            ),
+        };
+
+        let logger: &'static EngineLogger = Box::leak(Box::new(EngineLogger {
+            filter: RwLock::new(filter.clone()),
+            buffer: LogBuffer::new(settings.buffer_lines.max(1)),
+            start: Instant::now(),
+        }));
+
+        // Fails if the game installed its own logger first; then that one gets the output
+        if log::set_logger(logger).is_ok() {
+            log::set_max_level(filter.max_level());
+        }
+        if let Some(error) = error {
+            log::warn!("Ignoring log filter '{}': {}", spec, error);
+        }
+        logger
+    });
+
+    logger.buffer.clone()
+}
+
+/// Replaces the filter at runtime.
+pub fn set_log_filter(filter: LogFilter) {
+    if let Some(logger) = LOGGER.get() {
+        log::set_max_level(filter.max_level());
+        if let Ok(mut current) = logger.filter.write() {
+            *current = filter;
+        }
+    }
+}
+
+pub fn register_log_commands(commands: &mut ConsoleCommands) {
+    commands.register(
+        "log.filter",
+        "log.filter <spec> - e.g. 'info,catalyst_renderer=trace'",
+        |_, args| {
+            let spec = args.first().ok_or("usage: log.filter <spec>")?;
+            let filter = LogFilter::parse(spec)?;
+            set_log_filter(filter);
+            Ok(format!("Log filter set to '{}'", spec))
+        },
+    );
+}
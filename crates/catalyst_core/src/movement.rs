@@ -0,0 +1,57 @@
+use flecs_ecs::prelude::*;
+use glam::{Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    physics::{PhysicsBody, RigidBodyDefinition},
+    replication::Replicated,
+    time::Time,
+    transform::Transform,
+};
+
+/// Where a character wants to go this frame, as a world-space velocity. Written by
+/// player input or AI (e.g. a NavAgent), consumed by whatever moves the character.
+///
+/// Entities without a RigidBodyDefinition, or with a kinematic one, are moved by "Apply
+/// Movement Intent": translated along the horizontal part and turned to face it. Vertical movement is left
+/// to the caller. Replicated entities are moved by their authority instead.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MovementIntent {
+    pub velocity: Vec3,
+}
+
+impl MovementIntent {
+    pub fn stop(&mut self) {
+        self.velocity = Vec3::ZERO;
+    }
+}
+
+// Before transform propagation (both PostUpdate, this one is registered first),
+// so intents written during OnUpdate show up in this frame's GlobalTransform
+pub fn movement_intent_system(world: &mut World) {
+    world
+        .system_named::<(
+            &MovementIntent,
+            &mut Transform,
+            Option<&RigidBodyDefinition>,
+            &Time,
+        )>("Apply Movement Intent")
+        .without(Replicated::id())
+        .kind(flecs::pipeline::PostUpdate)
+        .each(|(intent, transform, body, time)| {
+            // Dynamic bodies are moved by the simulation, kinematic ones follow their Transform
+            if body.is_some_and(|body| !matches!(body.body_type, PhysicsBody::Kinematic)) {
+                return;
+            }
+
+            let horizontal = Vec3::new(intent.velocity.x, 0.0, intent.velocity.z);
+            if horizontal.length_squared() < 1e-6 {
+                return;
+            }
+
+            transform.translation += horizontal * time.delta_seconds();
+            // Forward is -Z
+            transform.rotation = Quat::from_rotation_y(f32::atan2(-horizontal.x, -horizontal.z));
+        });
+}
@@ -1,8 +1,13 @@
 use flecs_ecs::macros::Component;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 use crate::transform::Transform;
 
-#[derive(Debug, Clone, Copy)]
+/// Rapier collision groups are 32 bits wide.
+pub const MAX_COLLISION_LAYERS: usize = 32;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum PhysicsBody {
     Static,
     Dynamic,
@@ -10,16 +15,25 @@ pub enum PhysicsBody {
     Unknown,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ColliderShape {
     Box { hx: f32, hy: f32, hz: f32 },
     Sphere { radius: f32 },
     Capsule { radius: f32, height: f32 },
     Convex { vertices: Vec<glam::Vec3> },
     Mesh { vertices: Vec<glam::Vec3>, indices: Vec<u32> },
+    /// Grid of heights centered on the collider, spanning `size` (X, Z). Row-major:
+    /// `heights[row * columns + column]`, rows along +Z and columns along +X.
+    Heightfield {
+        rows: u32,
+        columns: u32,
+        heights: Vec<f32>,
+        size: glam::Vec2,
+    },
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct RigidBodyDefinition {
     pub body_type: PhysicsBody,
     pub mass: Option<f32>,
@@ -28,7 +42,8 @@ pub struct RigidBodyDefinition {
     pub angular_damping: f32,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ColliderDefinition {
     pub shape: ColliderShape,
     pub is_trigger: bool,
@@ -37,14 +52,372 @@ pub struct ColliderDefinition {
     pub mask: u32,
 }
 
-#[derive(Component, Debug, Clone)]
+// Same fallbacks as glTF physics extras without the optional fields
+impl Default for RigidBodyDefinition {
+    fn default() -> Self {
+        Self {
+            body_type: PhysicsBody::Dynamic,
+            mass: None,
+            gravity_scale: 1.0,
+            linear_damping: 0.0,
+            angular_damping: 0.0,
+        }
+    }
+}
+
+impl Default for ColliderDefinition {
+    fn default() -> Self {
+        Self {
+            shape: ColliderShape::Box {
+                hx: 0.5,
+                hy: 0.5,
+                hz: 0.5,
+            },
+            is_trigger: false,
+            offset: Transform::default(),
+            layer: 0,
+            mask: u32::MAX,
+        }
+    }
+}
+
+impl ColliderDefinition {
+    /// Applies a filter built from layer names (see `CollisionFilter`).
+    pub fn with_filter(mut self, filter: CollisionFilterDefinition) -> Self {
+        self.layer = filter.layer;
+        self.mask = filter.mask;
+        self
+    }
+}
+
+/// Tag for a collider whose ColliderDefinition shape was changed after it was created:
+/// the next physics prepare rebuilds the collider's shape and removes the tag.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct ColliderShapeChanged;
+
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct PhysicsMaterialDefinition {
     pub friction: f32,
     pub restitution: f32,
 }
 
+impl Default for PhysicsMaterialDefinition {
+    fn default() -> Self {
+        Self {
+            friction: 0.5,
+            restitution: 0.0,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CollisionFilterDefinition {
     pub layer: u32,
     pub mask: u32,
 }
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum CollisionLayerError {
+    #[error("unknown collision layer '{0}'")]
+    Unknown(String),
+    #[error("collision layer '{0}' is already registered")]
+    Duplicate(String),
+    #[error("all 32 collision layers are in use")]
+    Full,
+    #[error("bits {0:#010x} are not registered collision layers")]
+    Unregistered(u32),
+}
+
+/// Named collision layers and which of them interact.
+///
+/// Layer N is bit N of `ColliderDefinition::layer`/`mask`. Layers interact with every
+/// layer (registered or not) until told otherwise, matching a `mask` of `u32::MAX`.
+/// Registered once at startup through the PhysicsPlugin, and stored as a singleton so
+/// scene loading can resolve layer names from glTF extras.
+#[derive(Component, Debug, Clone, Default)]
+pub struct CollisionLayers {
+    names: Vec<String>,
+    // Row per registered layer: the bits of the layers it collides with
+    matrix: Vec<u32>,
+}
+
+impl CollisionLayers {
+    /// Adds a layer and returns its bit. Panics on a duplicate name or when all
+    /// 32 layers are taken; use `register_layer` to handle those.
+    pub fn with_layer(mut self, name: &str) -> Self {
+        if let Err(error) = self.register_layer(name) {
+            panic!("{}", error);
+        }
+        self
+    }
+
+    /// Stops two layers from colliding. Panics if either name is unknown.
+    pub fn without_interaction(mut self, a: &str, b: &str) -> Self {
+        if let Err(error) = self.set_interaction(a, b, false) {
+            panic!("{}", error);
+        }
+        self
+    }
+
+    pub fn register_layer(&mut self, name: &str) -> Result<u32, CollisionLayerError> {
+        if self.names.iter().any(|existing| existing == name) {
+            return Err(CollisionLayerError::Duplicate(name.to_owned()));
+        }
+        if self.names.len() == MAX_COLLISION_LAYERS {
+            return Err(CollisionLayerError::Full);
+        }
+
+        self.names.push(name.to_owned());
+        self.matrix.push(u32::MAX);
+        Ok(1 << (self.names.len() - 1))
+    }
+
+    /// Bit of a registered layer.
+    pub fn layer(&self, name: &str) -> Result<u32, CollisionLayerError> {
+        self.names
+            .iter()
+            .position(|existing| existing == name)
+            .map(|index| 1 << index)
+            .ok_or_else(|| CollisionLayerError::Unknown(name.to_owned()))
+    }
+
+    /// Bits of several layers, or'ed together.
+    pub fn layers<'a>(
+        &self,
+        names: impl IntoIterator<Item = &'a str>,
+    ) -> Result<u32, CollisionLayerError> {
+        names
+            .into_iter()
+            .try_fold(0, |bits, name| Ok(bits | self.layer(name)?))
+    }
+
+    /// Sets whether two layers collide, in both directions.
+    pub fn set_interaction(
+        &mut self,
+        a: &str,
+        b: &str,
+        enabled: bool,
+    ) -> Result<(), CollisionLayerError> {
+        let (a_bit, b_bit) = (self.layer(a)?, self.layer(b)?);
+        let (a_index, b_index) = (a_bit.trailing_zeros() as usize, b_bit.trailing_zeros() as usize);
+
+        if enabled {
+            self.matrix[a_index] |= b_bit;
+            self.matrix[b_index] |= a_bit;
+        } else {
+            self.matrix[a_index] &= !b_bit;
+            self.matrix[b_index] &= !a_bit;
+        }
+        Ok(())
+    }
+
+    pub fn interacts(&self, a: &str, b: &str) -> Result<bool, CollisionLayerError> {
+        let (a_bit, b_bit) = (self.layer(a)?, self.layer(b)?);
+        Ok(self.matrix[a_bit.trailing_zeros() as usize] & b_bit != 0)
+    }
+
+    /// Mask for a collider on `layer`: every layer that one of its layers collides with.
+    /// Anything not made of registered layers collides with everything.
+    pub fn interaction_mask(&self, layer: u32) -> u32 {
+        if layer == 0 || self.unregistered_bits(layer) != 0 {
+            return u32::MAX;
+        }
+
+        (0..self.names.len())
+            .filter(|index| layer & (1 << index) != 0)
+            .fold(0, |mask, index| mask | self.matrix[index])
+    }
+
+    /// Checks that `bits` only uses registered layers. Without any registered
+    /// layers every value is accepted, so numeric layers keep working.
+    pub fn validate(&self, bits: u32) -> Result<(), CollisionLayerError> {
+        match self.unregistered_bits(bits) {
+            0 => Ok(()),
+            _ if self.names.is_empty() => Ok(()),
+            unregistered => Err(CollisionLayerError::Unregistered(unregistered)),
+        }
+    }
+
+    fn unregistered_bits(&self, bits: u32) -> u32 {
+        let registered = if self.names.len() == MAX_COLLISION_LAYERS {
+            u32::MAX
+        } else {
+            (1 << self.names.len()) - 1
+        };
+        bits & !registered
+    }
+
+    /// Registered layers with their bits, in registration order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u32)> {
+        self.names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| (name.as_str(), 1 << index))
+    }
+
+    /// The interaction matrix as text, 'x' where two layers collide.
+    pub fn format_matrix(&self) -> String {
+        if self.names.is_empty() {
+            return "No collision layers registered".to_string();
+        }
+
+        let width = self.names.iter().map(|name| name.len()).max().unwrap_or(0);
+        let mut out = format!("{:width$}", "");
+        for index in 0..self.names.len() {
+            out.push_str(&format!(" {:>2}", index));
+        }
+
+        for (row, name) in self.names.iter().enumerate() {
+            out.push_str(&format!("\n{:width$}", name));
+            for column in 0..self.names.len() {
+                let mark = if self.matrix[row] & (1 << column) != 0 { "x" } else { "." };
+                out.push_str(&format!(" {:>2}", mark));
+            }
+            out.push_str(&format!("  ({})", row));
+        }
+        out
+    }
+}
+
+/// Builds a layer/mask pair from layer names:
+/// `CollisionFilter::new().belongs_to("player").collides_with(["world", "enemy"])`.
+/// Without `collides_with`, the mask comes from the CollisionLayers interaction matrix.
+#[derive(Debug, Clone, Default)]
+pub struct CollisionFilter {
+    belongs_to: Vec<String>,
+    collides_with: Option<Vec<String>>,
+}
+
+impl CollisionFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn belongs_to(mut self, layer: &str) -> Self {
+        self.belongs_to.push(layer.to_owned());
+        self
+    }
+
+    pub fn collides_with<'a>(mut self, layers: impl IntoIterator<Item = &'a str>) -> Self {
+        self.collides_with
+            .get_or_insert_with(Vec::new)
+            .extend(layers.into_iter().map(str::to_owned));
+        self
+    }
+
+    pub fn resolve(
+        &self,
+        layers: &CollisionLayers,
+    ) -> Result<CollisionFilterDefinition, CollisionLayerError> {
+        let layer = layers.layers(self.belongs_to.iter().map(String::as_str))?;
+        let mask = match &self.collides_with {
+            Some(names) => layers.layers(names.iter().map(String::as_str))?,
+            None => layers.interaction_mask(layer),
+        };
+        Ok(CollisionFilterDefinition { layer, mask })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Rapier's rule: each side's layer has to be in the other side's mask
+    fn collide(a: &CollisionFilterDefinition, b: &CollisionFilterDefinition) -> bool {
+        a.layer & b.mask != 0 && b.layer & a.mask != 0
+    }
+
+    fn layers() -> CollisionLayers {
+        CollisionLayers::default()
+            .with_layer("world")
+            .with_layer("player")
+            .with_layer("enemy")
+    }
+
+    #[test]
+    fn default_layers_collide_with_everything() {
+        let collider = ColliderDefinition::default();
+        assert_eq!((collider.layer, collider.mask), (0, u32::MAX));
+
+        // Nothing registered: any bits are accepted and collide with everything
+        let none = CollisionLayers::default();
+        assert_eq!(none.validate(0b1010), Ok(()));
+        assert_eq!(none.interaction_mask(0b1010), u32::MAX);
+        let filter = CollisionFilter::new().resolve(&none).unwrap();
+        assert_eq!((filter.layer, filter.mask), (0, u32::MAX));
+
+        // New layers collide with every layer until told otherwise
+        let layers = layers();
+        assert_eq!(layers.layer("enemy"), Ok(0b100));
+        for a in ["world", "player", "enemy"] {
+            for b in ["world", "player", "enemy"] {
+                assert_eq!(layers.interacts(a, b), Ok(true));
+            }
+        }
+        assert_eq!(layers.interaction_mask(0b010), u32::MAX);
+        assert_eq!(
+            layers.validate(0b1000),
+            Err(CollisionLayerError::Unregistered(0b1000))
+        );
+    }
+
+    #[test]
+    fn interactions_are_symmetric() {
+        let mut layers = layers().without_interaction("player", "enemy");
+        assert_eq!(layers.interacts("player", "enemy"), Ok(false));
+        assert_eq!(layers.interacts("enemy", "player"), Ok(false));
+        assert_eq!(layers.interacts("player", "world"), Ok(true));
+
+        let filter = |name| {
+            CollisionFilter::new()
+                .belongs_to(name)
+                .resolve(&layers)
+                .unwrap()
+        };
+        let (world, player, enemy) = (filter("world"), filter("player"), filter("enemy"));
+        assert_eq!(player.mask & enemy.layer, 0);
+        assert_eq!(enemy.mask & player.layer, 0);
+        assert!(!collide(&player, &enemy));
+        assert!(collide(&player, &world));
+        assert!(collide(&enemy, &world));
+
+        layers.set_interaction("enemy", "player", true).unwrap();
+        assert_eq!(layers.interacts("player", "enemy"), Ok(true));
+        assert_eq!(layers.interacts("enemy", "player"), Ok(true));
+    }
+
+    #[test]
+    fn a_one_sided_mask_stops_the_pair() {
+        let layers = layers();
+        // The player only collides with the world; the enemy still accepts the player
+        let player = CollisionFilter::new()
+            .belongs_to("player")
+            .collides_with(["world"])
+            .resolve(&layers)
+            .unwrap();
+        let enemy = CollisionFilter::new()
+            .belongs_to("enemy")
+            .resolve(&layers)
+            .unwrap();
+        let world = CollisionFilter::new()
+            .belongs_to("world")
+            .resolve(&layers)
+            .unwrap();
+
+        assert_eq!(player.mask, 0b001);
+        assert_ne!(enemy.mask & player.layer, 0);
+        assert!(!collide(&player, &enemy));
+        assert!(!collide(&enemy, &player));
+        assert!(collide(&player, &world));
+
+        assert_eq!(
+            CollisionFilter::new()
+                .belongs_to("player")
+                .collides_with(["ghost"])
+                .resolve(&layers)
+                .unwrap_err(),
+            CollisionLayerError::Unknown("ghost".to_string())
+        );
+    }
+}
@@ -1,5 +1,12 @@
 use flecs_ecs::prelude::*;
 
+use crate::{profiling, state_diff};
+
+/// Per-frame GPU preparation (acquiring the surface texture, uploads) ahead of the
+/// render phases. Skipped together with them while rendering is disabled.
+#[derive(Component)]
+pub struct PhaseRenderPrepare;
+
 #[derive(Component)]
 pub struct PhaseRender3D;
 
@@ -26,6 +33,10 @@ pub struct PhysicsStep;
 pub struct PhysicsSync;
 
 pub fn define_pipeline_stages(world: &mut World) {
+    world
+        .component::<PhaseRenderPrepare>()
+        .add(flecs::Phase)
+        .depends_on(flecs::pipeline::PreStore);
     world
         .component::<PhaseRender3D>()
         .add(flecs::Phase)
@@ -65,4 +76,158 @@ pub fn define_pipeline_stages(world: &mut World) {
         .component::<PhysicsSync>()
         .add(PhysicsPipelinePhase)
         .depends_on(PhysicsStep);
+
+    register_phase_scopes(world);
+}
+
+// A system at the start of every phase switches the open phase span, so the phase
+// timings shown in the debug overlay and the spans in a trace are the same numbers.
+// Fine-grained state diffs snapshot here too. Registered before any other system,
+// which puts each one first in its phase.
+fn register_phase_scopes(world: &World) {
+    fn marker(world: &World, name: &'static str, phase: impl IntoEntity) {
+        world
+            .system_named::<()>(&format!("Profile {}", name))
+            .kind(phase)
+            .run(move |iter| {
+                profiling::begin_phase(name);
+                state_diff::phase_boundary(&iter.world(), name);
+            });
+    }
+
+    marker(world, "OnLoad", flecs::pipeline::OnLoad);
+    marker(world, "PostLoad", flecs::pipeline::PostLoad);
+    marker(world, "PreUpdate", flecs::pipeline::PreUpdate);
+    marker(world, "OnUpdate", flecs::pipeline::OnUpdate);
+    marker(world, "OnValidate", flecs::pipeline::OnValidate);
+    marker(world, "PostUpdate", flecs::pipeline::PostUpdate);
+    marker(world, "PreStore", flecs::pipeline::PreStore);
+    marker(world, "RenderPrepare", PhaseRenderPrepare);
+    marker(world, "Render3D", PhaseRender3D);
+    marker(world, "RenderGUI", PhaseRenderGUI);
+    marker(world, "Present", PhasePresent);
+}
+
+/// Turns every render phase on or off, e.g. while the window is minimized. Systems in
+/// PhaseRenderGUI and PhasePresent follow PhaseRender3D through DependsOn.
+pub fn set_rendering_enabled(world: &World, enabled: bool) {
+    for phase in [
+        world.component_id::<PhaseRenderPrepare>(),
+        world.component_id::<PhaseRender3D>(),
+    ] {
+        let phase = world.entity_from_id(phase);
+        if enabled {
+            phase.enable_self();
+        } else {
+            phase.disable_self();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use glam::Mat4;
+
+    use super::*;
+    use crate::{
+        App,
+        transform::{GlobalTransform, Transform},
+    };
+
+    // Entities whose GlobalTransform PhaseRenderPrepare records
+    #[derive(Component)]
+    struct Watched;
+
+    type Seen = Arc<Mutex<Vec<(Entity, Mat4)>>>;
+
+    // What renderers see when they create and write mesh instances
+    fn recording_app() -> (App, Seen) {
+        let mut app = App::new();
+        let seen: Seen = Arc::default();
+        let record = seen.clone();
+        app.world
+            .system_named::<&GlobalTransform>("record_globals")
+            .with(Watched::id())
+            .kind(PhaseRenderPrepare)
+            .each_entity(move |entity, global| {
+                record.lock().unwrap().push((entity.id(), global.0));
+            });
+        app.update();
+        (app, seen)
+    }
+
+    fn first_seen(seen: &Seen, entity: Entity) -> Option<Mat4> {
+        seen.lock()
+            .unwrap()
+            .iter()
+            .find(|(seen, _)| *seen == entity)
+            .map(|(_, global)| *global)
+    }
+
+    #[test]
+    fn render_prepare_sees_the_transform_of_entities_spawned_that_frame() {
+        let (mut app, seen) = recording_app();
+        let before = Transform::from_xyz(4.0, 5.0, 6.0);
+        let spawned = app
+            .world
+            .entity()
+            .set(before)
+            .set(GlobalTransform::default())
+            .add(Watched)
+            .id();
+        let during = Transform::from_xyz(-3.0, 0.5, 2.0);
+        let in_update: Arc<Mutex<Option<Entity>>> = Arc::default();
+        let spawn = in_update.clone();
+        app.world
+            .system_named::<()>("spawn_in_update")
+            .kind(flecs::pipeline::OnUpdate)
+            .run(move |iter| {
+                let world = iter.world();
+                let mut spawn = spawn.lock().unwrap();
+                if spawn.is_none() {
+                    let entity = world
+                        .entity()
+                        .set(during)
+                        .set(GlobalTransform::default())
+                        .add(Watched);
+                    *spawn = Some(entity.id());
+                }
+            });
+        app.update();
+
+        assert_eq!(first_seen(&seen, spawned), Some(before.compute_matrix()));
+        let in_update = in_update.lock().unwrap().expect("spawned in OnUpdate");
+        assert_eq!(first_seen(&seen, in_update), Some(during.compute_matrix()));
+    }
+
+    #[test]
+    fn a_new_child_of_a_parent_moved_that_frame_follows_it() {
+        let (mut app, seen) = recording_app();
+        let parent_at = |x: f32| Transform::from_xyz(x, 0.0, 0.0);
+        let parent = app
+            .world
+            .entity()
+            .set(parent_at(0.0))
+            .set(GlobalTransform::default())
+            .id();
+        app.update();
+
+        let local = Transform::from_xyz(0.0, 1.0, 0.0);
+        let child = app
+            .world
+            .entity()
+            .set(local)
+            .set(GlobalTransform::default())
+            .add(Watched)
+            .child_of(parent)
+            .id();
+        app.world.entity_from_id(parent).set(parent_at(10.0));
+        app.update();
+        assert_eq!(
+            first_seen(&seen, child),
+            Some(parent_at(10.0).compute_matrix() * local.compute_matrix())
+        );
+    }
 }
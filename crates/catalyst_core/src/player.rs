@@ -0,0 +1,9 @@
+use flecs_ecs::macros::Component;
+use serde::{Deserialize, Serialize};
+
+/// Identifies which local player an entity belongs to (camera, character, ...).
+/// Player 0 is the keyboard + mouse player by default.
+#[derive(
+    Component, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize, Deserialize,
+)]
+pub struct PlayerIndex(pub u8);
@@ -0,0 +1,106 @@
+use std::any::TypeId;
+
+use thiserror::Error;
+
+use crate::{App, Plugin};
+
+/// Identifies a plugin type, for dependency checks and group edits.
+#[derive(Clone, Copy, Debug)]
+pub struct PluginId {
+    pub type_id: TypeId,
+    pub name: &'static str,
+}
+
+impl PluginId {
+    pub fn of<P: Plugin>() -> Self {
+        Self {
+            type_id: TypeId::of::<P>(),
+            name: std::any::type_name::<P>(),
+        }
+    }
+}
+
+impl PartialEq for PluginId {
+    fn eq(&self, other: &Self) -> bool {
+        self.type_id == other.type_id
+    }
+}
+
+impl Eq for PluginId {}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PluginError {
+    #[error("{plugin} requires {dependency} to be added before it")]
+    MissingDependency {
+        plugin: &'static str,
+        dependency: &'static str,
+    },
+    #[error("{0} was added twice")]
+    AlreadyAdded(&'static str),
+}
+
+/// A set of plugins added together, in order. Members can be removed or replaced
+/// before the group is added: `app.add_plugins(DefaultPlugins.without::<DebugPlugin>())`.
+pub trait PluginGroup: Sized {
+    fn build(self) -> PluginGroupBuilder;
+
+    fn without<P: Plugin>(self) -> PluginGroupBuilder {
+        self.build().without::<P>()
+    }
+
+    fn set<P: Plugin>(self, plugin: P) -> PluginGroupBuilder {
+        self.build().set(plugin)
+    }
+
+    fn add<P: Plugin>(self, plugin: P) -> PluginGroupBuilder {
+        self.build().add(plugin)
+    }
+}
+
+#[derive(Default)]
+pub struct PluginGroupBuilder {
+    plugins: Vec<(PluginId, Box<dyn Plugin>)>,
+}
+
+impl PluginGroupBuilder {
+    /// Appends a plugin to the end of the group.
+    pub fn add<P: Plugin>(mut self, plugin: P) -> Self {
+        self.plugins.push((PluginId::of::<P>(), Box::new(plugin)));
+        self
+    }
+
+    /// Drops a member. Plugins that depend on it will report the missing dependency when added.
+    pub fn without<P: Plugin>(mut self) -> Self {
+        let id = PluginId::of::<P>();
+        self.plugins.retain(|(member, _)| *member != id);
+        self
+    }
+
+    /// Replaces the member of the same type in place (keeping its position), or appends it.
+    pub fn set<P: Plugin>(mut self, plugin: P) -> Self {
+        let id = PluginId::of::<P>();
+        match self.plugins.iter_mut().find(|(member, _)| *member == id) {
+            Some(member) => member.1 = Box::new(plugin),
+            None => self.plugins.push((id, Box::new(plugin))),
+        }
+        self
+    }
+
+    pub fn contains<P: Plugin>(&self) -> bool {
+        let id = PluginId::of::<P>();
+        self.plugins.iter().any(|(member, _)| *member == id)
+    }
+
+    pub(crate) fn finish(self, app: &mut App) -> Result<(), PluginError> {
+        for (id, plugin) in self.plugins {
+            app.try_add_boxed_plugin(id, plugin.as_ref())?;
+        }
+        Ok(())
+    }
+}
+
+impl PluginGroup for PluginGroupBuilder {
+    fn build(self) -> PluginGroupBuilder {
+        self
+    }
+}
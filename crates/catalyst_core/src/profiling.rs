@@ -0,0 +1,446 @@
+//! Hierarchical CPU profiling scopes.
+//!
+//! `profile_scope!("name")` records a span from the macro to the end of the enclosing
+//! block. Every thread writes its spans into a ring buffer of its own, so recording
+//! never takes a lock; the buffers are only read when a capture is dumped. While
+//! recording is off a scope costs a single relaxed load and branch.
+//!
+//! Nesting isn't stored: spans on one thread nest by their timestamps, which is also
+//! how chrome://tracing and Perfetto stack them.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    path::Path,
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+    },
+    time::Instant,
+};
+
+use serde_json::{Value, json};
+
+use crate::console::ConsoleCommands;
+
+/// Spans kept per thread. At a few hundred spans a frame that's a couple of seconds.
+const RING_CAPACITY: usize = 1 << 15;
+
+/// Name of the span around a whole frame. Captures are cut at these.
+pub const FRAME_SCOPE: &str = "Frame";
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static EPOCH: OnceLock<Instant> = OnceLock::new();
+static BUFFERS: Mutex<Vec<Arc<ThreadBuffer>>> = Mutex::new(Vec::new());
+static NAMES: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+static LAST_FRAME: Mutex<Vec<(&'static str, f32)>> = Mutex::new(Vec::new());
+
+thread_local! {
+    static BUFFER: Arc<ThreadBuffer> = ThreadBuffer::register();
+    static NAME_IDS: RefCell<HashMap<(usize, usize), u32>> = RefCell::new(HashMap::new());
+    static OPEN_PHASE: Cell<Option<(&'static str, u64)>> = const { Cell::new(None) };
+    static FRAME_PHASES: RefCell<Vec<(&'static str, f32)>> = const { RefCell::new(Vec::new()) };
+}
+
+pub fn set_enabled(enabled: bool) {
+    if enabled {
+        EPOCH.get_or_init(Instant::now);
+    }
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+#[inline]
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Records a span from here to the end of the enclosing block.
+///
+/// ```ignore
+/// profile_scope!("Upload Prewarmed Assets");
+/// ```
+#[macro_export]
+macro_rules! profile_scope {
+    ($name:expr) => {
+        let _profile_scope = $crate::profiling::ProfileScope::new($name);
+    };
+}
+
+/// Guard behind `profile_scope!`. Writes its span when dropped.
+pub struct ProfileScope {
+    active: Option<(&'static str, u64)>,
+}
+
+impl ProfileScope {
+    #[inline]
+    pub fn new(name: &'static str) -> Self {
+        if !is_enabled() {
+            return Self { active: None };
+        }
+        Self {
+            active: Some((name, now())),
+        }
+    }
+}
+
+impl Drop for ProfileScope {
+    #[inline]
+    fn drop(&mut self) {
+        if let Some((name, start)) = self.active {
+            record(name, start, now());
+        }
+    }
+}
+
+/// Closes the open phase span on this thread and opens one for `name`. The pipeline
+/// calls this at the start of every phase, so the phases tile the frame.
+pub fn begin_phase(name: &'static str) {
+    end_phase();
+    if is_enabled() {
+        OPEN_PHASE.with(|phase| phase.set(Some((name, now()))));
+    }
+}
+
+/// Closes the open phase span, if any.
+pub fn end_phase() {
+    let Some((name, start)) = OPEN_PHASE.with(|phase| phase.take()) else {
+        return;
+    };
+    let end = now();
+    record(name, start, end);
+    FRAME_PHASES.with(|phases| {
+        phases
+            .borrow_mut()
+            .push((name, (end - start) as f32 / 1_000_000.0));
+    });
+}
+
+/// Ends the frame's phase spans and publishes their durations for `last_frame`.
+pub fn end_frame() {
+    end_phase();
+    let phases = FRAME_PHASES.with(|phases| std::mem::take(&mut *phases.borrow_mut()));
+    if !phases.is_empty() {
+        *LAST_FRAME.lock().unwrap() = phases;
+    }
+}
+
+/// Milliseconds spent in each phase of the last recorded frame, in pipeline order.
+/// Taken from the same spans a capture exports.
+pub fn last_frame() -> Vec<(&'static str, f32)> {
+    LAST_FRAME.lock().unwrap().clone()
+}
+
+#[derive(Clone, Debug)]
+pub struct Span {
+    pub name: &'static str,
+    pub thread: u32,
+    pub thread_name: Arc<str>,
+    /// Nanoseconds since recording was first enabled.
+    pub start_ns: u64,
+    pub duration_ns: u64,
+}
+
+/// Copies the spans of the last `frames` frames out of every thread's buffer, oldest
+/// first. Everything still buffered is returned if no frame spans were recorded.
+pub fn capture(frames: usize) -> Vec<Span> {
+    let buffers = BUFFERS.lock().unwrap().clone();
+    let names = NAMES.lock().unwrap().clone();
+
+    let mut spans: Vec<Span> = buffers
+        .iter()
+        .flat_map(|buffer| buffer.read(&names))
+        .collect();
+    spans.sort_by_key(|span| span.start_ns);
+
+    let mut frame_starts: Vec<u64> = spans
+        .iter()
+        .filter(|span| span.name == FRAME_SCOPE)
+        .map(|span| span.start_ns)
+        .collect();
+    frame_starts.reverse();
+    if let Some(&from) = frame_starts.get(frames.max(1) - 1).or(frame_starts.last()) {
+        spans.retain(|span| span.start_ns + span.duration_ns >= from);
+    }
+
+    spans
+}
+
+/// Writes spans as a chrome://tracing (Trace Event Format) JSON file. Perfetto opens
+/// it as well.
+pub fn write_chrome_trace(spans: &[Span], path: impl AsRef<Path>) -> std::io::Result<()> {
+    let mut events: Vec<Value> = Vec::with_capacity(spans.len() + 8);
+
+    let mut threads: Vec<(u32, &str)> = spans
+        .iter()
+        .map(|span| (span.thread, &*span.thread_name))
+        .collect();
+    threads.sort();
+    threads.dedup();
+    for (tid, name) in threads {
+        events.push(json!({
+            "name": "thread_name",
+            "ph": "M",
+            "pid": 1,
+            "tid": tid,
+            "args": { "name": name },
+        }));
+    }
+
+    for span in spans {
+        events.push(json!({
+            "name": span.name,
+            "cat": "catalyst",
+            "ph": "X",
+            "pid": 1,
+            "tid": span.thread,
+            "ts": span.start_ns as f64 / 1000.0,
+            "dur": span.duration_ns as f64 / 1000.0,
+        }));
+    }
+
+    let trace = json!({ "traceEvents": events, "displayTimeUnit": "ms" });
+    std::fs::write(path, serde_json::to_vec(&trace)?)
+}
+
+pub fn register_profile_commands(commands: &mut ConsoleCommands) {
+    commands.register("profile.start", "profile.start - record profiling scopes", |_, _| {
+        set_enabled(true);
+        Ok("Profiling started".to_string())
+    });
+    commands.register("profile.stop", "profile.stop - stop recording", |_, _| {
+        set_enabled(false);
+        Ok("Profiling stopped".to_string())
+    });
+    commands.register(
+        "profile.dump",
+        "profile.dump [frames] [path] - write the last frames (default 120) as a chrome trace",
+        |_, args| {
+            let frames = match args.first() {
+                Some(frames) => frames
+                    .parse::<usize>()
+                    .map_err(|_| format!("'{}' is not a frame count", frames))?,
+                None => 120,
+            };
+            let path = args.get(1).copied().unwrap_or("trace.json");
+
+            let spans = capture(frames);
+            if spans.is_empty() {
+                return Err("Nothing recorded, run profile.start first".to_string());
+            }
+            write_chrome_trace(&spans, path).map_err(|e| e.to_string())?;
+            Ok(format!("Wrote {} spans to {}", spans.len(), path))
+        },
+    );
+}
+
+fn now() -> u64 {
+    EPOCH.get_or_init(Instant::now).elapsed().as_nanos() as u64
+}
+
+fn record(name: &'static str, start: u64, end: u64) {
+    let name = intern(name);
+    BUFFER.with(|buffer| buffer.push(name, start, end));
+}
+
+// Names are stored as indices so a slot is three atomics. Each thread caches the
+// lookup; the global table is only locked the first time a thread sees a name.
+fn intern(name: &'static str) -> u32 {
+    let key = (name.as_ptr() as usize, name.len());
+    NAME_IDS.with(|ids| {
+        *ids.borrow_mut().entry(key).or_insert_with(|| {
+            let mut names = NAMES.lock().unwrap();
+            match names.iter().position(|known| *known == name) {
+                Some(index) => index as u32,
+                None => {
+                    names.push(name);
+                    (names.len() - 1) as u32
+                }
+            }
+        })
+    })
+}
+
+#[derive(Default)]
+struct Slot {
+    name: AtomicU32,
+    start: AtomicU64,
+    end: AtomicU64,
+}
+
+/// Single writer (the owning thread), any number of readers. A reader can see a slot
+/// the writer is overwriting; those are detected through `written` and dropped.
+struct ThreadBuffer {
+    id: u32,
+    name: Arc<str>,
+    slots: Box<[Slot]>,
+    written: AtomicU64,
+}
+
+impl ThreadBuffer {
+    fn register() -> Arc<Self> {
+        let mut buffers = BUFFERS.lock().unwrap();
+        let id = buffers.len() as u32;
+        let thread = std::thread::current();
+        let name = match thread.name() {
+            Some(name) => name.into(),
+            None => format!("thread {}", id).into(),
+        };
+
+        let buffer = Arc::new(Self {
+            id,
+            name,
+            slots: (0..RING_CAPACITY).map(|_| Slot::default()).collect(),
+            written: AtomicU64::new(0),
+        });
+        buffers.push(buffer.clone());
+        buffer
+    }
+
+    fn push(&self, name: u32, start: u64, end: u64) {
+        let index = self.written.load(Ordering::Relaxed);
+        let slot = &self.slots[index as usize % RING_CAPACITY];
+        slot.name.store(name, Ordering::Relaxed);
+        slot.start.store(start, Ordering::Relaxed);
+        slot.end.store(end, Ordering::Relaxed);
+        self.written.store(index + 1, Ordering::Release);
+    }
+
+    fn read(&self, names: &[&'static str]) -> Vec<Span> {
+        let written = self.written.load(Ordering::Acquire);
+        let first = written.saturating_sub(RING_CAPACITY as u64);
+
+        let copied: Vec<(u64, u32, u64, u64)> = (first..written)
+            .map(|index| {
+                let slot = &self.slots[index as usize % RING_CAPACITY];
+                (
+                    index,
+                    slot.name.load(Ordering::Relaxed),
+                    slot.start.load(Ordering::Relaxed),
+                    slot.end.load(Ordering::Relaxed),
+                )
+            })
+            .collect();
+
+        // Slots the writer lapped while they were being copied may be torn
+        let still_valid = self
+            .written
+            .load(Ordering::Acquire)
+            .saturating_sub(RING_CAPACITY as u64);
+
+        copied
+            .into_iter()
+            .filter(|(index, ..)| *index >= still_valid)
+            .filter_map(|(_, name, start, end)| {
+                Some(Span {
+                    name: *names.get(name as usize)?,
+                    thread: self.id,
+                    thread_name: self.name.clone(),
+                    start_ns: start,
+                    duration_ns: end.saturating_sub(start),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Not registered in BUFFERS, so captures in other tests don't see it
+    fn buffer() -> ThreadBuffer {
+        ThreadBuffer {
+            id: 3,
+            name: "worker".into(),
+            slots: (0..RING_CAPACITY).map(|_| Slot::default()).collect(),
+            written: AtomicU64::new(0),
+        }
+    }
+
+    #[test]
+    fn ring_buffer_keeps_the_newest_spans_when_it_wraps() {
+        let buffer = buffer();
+        let names = ["even", "odd"];
+        for index in 0..10u64 {
+            buffer.push((index % 2) as u32, index * 10, index * 10 + 5);
+        }
+        let spans = buffer.read(&names);
+        assert_eq!(spans.len(), 10);
+        assert_eq!(
+            (spans[1].name, spans[1].start_ns, spans[1].duration_ns),
+            ("odd", 10, 5)
+        );
+        assert_eq!((spans[1].thread, &*spans[1].thread_name), (3, "worker"));
+
+        // Past capacity the oldest slots are overwritten
+        let total = RING_CAPACITY as u64 + 100;
+        for index in 10..total {
+            buffer.push((index % 2) as u32, index * 10, index * 10 + 5);
+        }
+        let spans = buffer.read(&names);
+        assert_eq!(spans.len(), RING_CAPACITY);
+        assert_eq!(spans[0].start_ns, 100 * 10);
+        assert_eq!(spans.last().unwrap().start_ns, (total - 1) * 10);
+        assert!(
+            spans
+                .windows(2)
+                .all(|pair| pair[0].start_ns < pair[1].start_ns)
+        );
+        assert_eq!(spans[0].name, "even");
+    }
+
+    #[test]
+    fn spans_with_unknown_names_are_skipped() {
+        let buffer = buffer();
+        buffer.push(0, 0, 1);
+        buffer.push(5, 1, 2);
+        let spans = buffer.read(&["known"]);
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].name, "known");
+    }
+
+    #[test]
+    fn chrome_traces_hold_complete_events_in_microseconds() {
+        let span = |name, thread: u32, thread_name: &str, start_ns, duration_ns| Span {
+            name,
+            thread,
+            thread_name: thread_name.into(),
+            start_ns,
+            duration_ns,
+        };
+        let spans = [
+            span(FRAME_SCOPE, 0, "main", 1_500, 2_000_000),
+            span("Physics", 0, "main", 2_000, 500_250),
+            span("Decode", 4, "io", 3_000, 1_000),
+        ];
+        let path = std::env::temp_dir().join(format!("catalyst_trace_{}.json", std::process::id()));
+
+        write_chrome_trace(&spans, &path).unwrap();
+        let trace: Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(trace["displayTimeUnit"], "ms");
+        let events = trace["traceEvents"].as_array().unwrap();
+
+        // One thread name per thread first
+        let metadata: Vec<_> = events.iter().filter(|event| event["ph"] == "M").collect();
+        assert_eq!(metadata.len(), 2);
+        assert_eq!(metadata[0]["tid"], 0);
+        assert_eq!(metadata[0]["args"]["name"], "main");
+        assert_eq!(metadata[1]["tid"], 4);
+        assert_eq!(metadata[1]["args"]["name"], "io");
+
+        let complete: Vec<_> = events.iter().filter(|event| event["ph"] == "X").collect();
+        assert_eq!(complete.len(), 3);
+        for (event, span) in complete.iter().zip(&spans) {
+            assert_eq!(event["name"], span.name);
+            assert_eq!(event["pid"], 1);
+            assert_eq!(event["tid"], span.thread);
+        }
+        assert_eq!(complete[0]["ts"].as_f64(), Some(1.5));
+        assert_eq!(complete[0]["dur"].as_f64(), Some(2000.0));
+        assert_eq!(complete[1]["ts"].as_f64(), Some(2.0));
+        assert_eq!(complete[1]["dur"].as_f64(), Some(500.25));
+        assert_eq!(complete[2]["tid"], 4);
+    }
+}
@@ -0,0 +1,725 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+    time::Instant,
+};
+
+use flecs_ecs::prelude::*;
+use glam::Mat4;
+use rayon::prelude::*;
+
+use crate::transform::{GlobalTransform, StaticTransform, Transform};
+
+/// How GlobalTransform is recomputed. Read every frame, so it can be changed at runtime.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct TransformPropagation {
+    /// Subtrees under the roots are computed as parallel jobs on the rayon pool.
+    pub parallel: bool,
+    /// Only subtrees where a Transform changed (or the hierarchy did) are recomputed.
+    /// GlobalTransforms written directly, without going through Transform, are not corrected.
+    pub incremental: bool,
+    /// Compares every StaticTransform entity's Transform with the one it was spawned
+    /// with and warns about the ones that changed (then follows them). It walks all
+    /// the static entities the marker lets propagation skip, so it is only on by
+    /// default in debug builds.
+    pub check_static: bool,
+}
+
+impl Default for TransformPropagation {
+    fn default() -> Self {
+        Self {
+            parallel: true,
+            incremental: true,
+            check_static: cfg!(debug_assertions),
+        }
+    }
+}
+
+struct TransformNode {
+    entity: Entity,
+    parent: Option<u32>,
+    // Job this node belongs to, None for roots
+    job: Option<u32>,
+    local: Transform,
+    global: Mat4,
+    dirty: bool,
+    // Recomputed this frame, needs to be written back
+    updated: bool,
+}
+
+struct PropagationJob {
+    root: u32,
+    range: Range<usize>,
+    dirty: bool,
+}
+
+/// The transform hierarchy flattened depth-first, so every subtree is a contiguous
+/// range of nodes and its parents always come before it. Each direct child of a root
+/// is one job; jobs share no nodes and run independently of each other.
+///
+/// Rebuilt only when the hierarchy changes (Transform/GlobalTransform/ChildOf added
+/// or removed); otherwise only the local transforms are refreshed every frame, and only
+/// those of entities without a StaticTransform.
+#[derive(Component, Default)]
+pub struct TransformTree {
+    nodes: Vec<TransformNode>,
+    index: HashMap<Entity, u32>,
+    jobs: Vec<PropagationJob>,
+    // The jobs under each root, by the root's node index
+    root_jobs: HashMap<u32, Range<u32>>,
+    // Marked by refresh_locals for the next propagation
+    dirty_roots: Vec<u32>,
+    dirty_jobs: Vec<u32>,
+    // Node ranges the last propagation visited, to write back
+    written: Vec<Range<usize>>,
+    structure_changed: bool,
+    // Recompute everything on the next propagation, after a rebuild
+    needs_full: bool,
+    updated_last_frame: usize,
+    refreshed_last_frame: usize,
+    last_frame_ms: f32,
+    // StaticTransform entities already warned about, to warn once each
+    moved_static: HashSet<Entity>,
+}
+
+impl TransformTree {
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Number of jobs, i.e. subtrees that can run in parallel.
+    pub fn job_count(&self) -> usize {
+        self.jobs.len()
+    }
+
+    /// GlobalTransforms recomputed by the last propagation.
+    pub fn updated_last_frame(&self) -> usize {
+        self.updated_last_frame
+    }
+
+    /// Local transforms compared by the last propagation: every node after a rebuild,
+    /// otherwise the ones without a StaticTransform (and the static ones as well under
+    /// `TransformPropagation::check_static`).
+    pub fn refreshed_last_frame(&self) -> usize {
+        self.refreshed_last_frame
+    }
+
+    /// Time the last propagation took, refresh and write-back included.
+    pub fn last_frame_ms(&self) -> f32 {
+        self.last_frame_ms
+    }
+
+    /// StaticTransform entities found moving by `TransformPropagation::check_static`.
+    pub fn moved_static_count(&self) -> usize {
+        self.moved_static.len()
+    }
+
+    /// Forces a full rebuild (and recompute) on the next propagation.
+    pub fn mark_structure_changed(&mut self) {
+        self.structure_changed = true;
+    }
+
+    fn rebuild(&mut self, members: &Query<&Transform>) {
+        let mut locals = HashMap::new();
+        let mut children: HashMap<Entity, Vec<Entity>> = HashMap::new();
+        let mut roots = Vec::new();
+
+        members.each_entity(|entity, local| {
+            locals.insert(entity.id(), *local);
+
+            // Parents outside the hierarchy (no Transform) are ignored, like the world origin
+            match entity
+                .parent()
+                .filter(|parent| parent.has(Transform::id()) && parent.has(GlobalTransform::id()))
+            {
+                Some(parent) => children.entry(parent.id()).or_default().push(entity.id()),
+                None => roots.push(entity.id()),
+            }
+        });
+
+        self.nodes.clear();
+        self.index.clear();
+        self.jobs.clear();
+        self.root_jobs.clear();
+
+        let mut stack = Vec::new();
+        for root in roots {
+            let root_index = self.push_node(root, None, None, locals[&root]);
+            let first_job = self.jobs.len() as u32;
+
+            for &child in children.get(&root).into_iter().flatten() {
+                let job = self.jobs.len() as u32;
+                self.jobs.push(PropagationJob {
+                    root: root_index,
+                    range: self.nodes.len()..self.nodes.len(),
+                    dirty: true,
+                });
+
+                // Depth-first, so the whole subtree lands in one contiguous range
+                stack.push((child, root_index));
+                while let Some((entity, parent)) = stack.pop() {
+                    let index = self.push_node(entity, Some(parent), Some(job), locals[&entity]);
+                    for &grandchild in children.get(&entity).into_iter().flatten().rev() {
+                        stack.push((grandchild, index));
+                    }
+                }
+
+                self.jobs[job as usize].range.end = self.nodes.len();
+            }
+            if self.jobs.len() as u32 > first_job {
+                self.root_jobs
+                    .insert(root_index, first_job..self.jobs.len() as u32);
+            }
+        }
+
+        self.structure_changed = false;
+        self.needs_full = true;
+        self.refreshed_last_frame = self.nodes.len();
+    }
+
+    fn push_node(
+        &mut self,
+        entity: Entity,
+        parent: Option<u32>,
+        job: Option<u32>,
+        local: Transform,
+    ) -> u32 {
+        let index = self.nodes.len() as u32;
+        self.nodes.push(TransformNode {
+            entity,
+            parent,
+            job,
+            local,
+            global: Mat4::IDENTITY,
+            dirty: true,
+            updated: false,
+        });
+        self.index.insert(entity, index);
+        index
+    }
+
+    // Marks nodes whose Transform changed since the last frame, and their job. Returns
+    // the entities that did.
+    fn refresh_locals(&mut self, members: &Query<&Transform>) -> Vec<Entity> {
+        let mut changed = Vec::new();
+        members.each_entity(|entity, local| {
+            self.refreshed_last_frame += 1;
+            let Some(&index) = self.index.get(&entity.id()) else {
+                return;
+            };
+            let node = &mut self.nodes[index as usize];
+            if node.local == *local {
+                return;
+            }
+
+            node.local = *local;
+            let was_dirty = std::mem::replace(&mut node.dirty, true);
+            match node.job {
+                Some(job) => {
+                    let job_state = &mut self.jobs[job as usize];
+                    if !std::mem::replace(&mut job_state.dirty, true) {
+                        self.dirty_jobs.push(job);
+                    }
+                }
+                None if !was_dirty => self.dirty_roots.push(index),
+                None => {}
+            }
+            changed.push(entity.id());
+        });
+        changed
+    }
+
+    // Refreshes the static nodes too, warning once about each that moved
+    fn check_static(&mut self, world: &World, statics: &Query<&Transform>) {
+        for entity in self.refresh_locals(statics) {
+            if self.moved_static.insert(entity) {
+                log::warn!(
+                    "{} ({:?}) has a StaticTransform but its Transform changed; remove the \
+                     marker before moving it",
+                    world.entity_from_id(entity).name(),
+                    entity
+                );
+            }
+        }
+    }
+
+    /// Recomputes the global matrices. Returns how many were updated.
+    pub fn propagate(&mut self, settings: TransformPropagation) -> usize {
+        crate::profile_scope!("Transform Propagation");
+        let full = !settings.incremental || self.needs_full;
+        self.needs_full = false;
+        self.written.clear();
+
+        // Only the roots and jobs refresh_locals marked are visited, so a frame costs
+        // what moved rather than the size of the tree
+        let (roots, mut jobs): (Vec<u32>, Vec<u32>) = if full {
+            self.dirty_roots.clear();
+            self.dirty_jobs.clear();
+            self.written.push(0..self.nodes.len());
+            let roots = (0..self.nodes.len() as u32)
+                .filter(|&index| self.nodes[index as usize].parent.is_none())
+                .collect();
+            (roots, (0..self.jobs.len() as u32).collect())
+        } else {
+            (
+                std::mem::take(&mut self.dirty_roots),
+                std::mem::take(&mut self.dirty_jobs),
+            )
+        };
+
+        // Roots first, serially: there are few of them and every job starts from one
+        let mut updated = 0;
+        for &root in &roots {
+            let node = &mut self.nodes[root as usize];
+            node.updated = true;
+            node.global = node.local.compute_matrix();
+            node.dirty = false;
+            updated += 1;
+            if !full {
+                self.written.push(root as usize..root as usize + 1);
+                // A moved root moves all of its subtrees
+                jobs.extend(self.root_jobs.get(&root).cloned().into_iter().flatten());
+            }
+        }
+        jobs.sort_unstable();
+        jobs.dedup();
+
+        let bases: Vec<(Mat4, bool)> = jobs
+            .iter()
+            .map(|&job| {
+                let root = &self.nodes[self.jobs[job as usize].root as usize];
+                (root.global, root.updated)
+            })
+            .collect();
+
+        // Jobs in node order, each with its root's matrix
+        let mut work = Vec::new();
+        let mut rest: &mut [TransformNode] = &mut self.nodes;
+        let mut consumed = 0;
+        for (&job, (base, base_updated)) in jobs.iter().zip(bases) {
+            let job = &mut self.jobs[job as usize];
+            job.dirty = false;
+            if !full {
+                self.written.push(job.range.clone());
+            }
+
+            let (_, tail) = std::mem::take(&mut rest).split_at_mut(job.range.start - consumed);
+            let (nodes, tail) = tail.split_at_mut(job.range.len());
+            work.push((nodes, job.range.start, base, base_updated));
+            rest = tail;
+            consumed = job.range.end;
+        }
+
+        let run = |(nodes, offset, base, base_updated): (&mut [TransformNode], usize, Mat4, bool)| {
+            crate::profile_scope!("Propagate Subtree");
+            propagate_subtree(nodes, offset, base, base_updated, full)
+        };
+        updated += if settings.parallel {
+            work.into_par_iter().map(run).sum::<usize>()
+        } else {
+            work.into_iter().map(run).sum::<usize>()
+        };
+
+        self.updated_last_frame = updated;
+        updated
+    }
+
+    fn write_back(&mut self, world: &World) {
+        for range in self.written.drain(..) {
+            for node in self.nodes[range].iter_mut().filter(|node| node.updated) {
+                node.updated = false;
+                world
+                    .entity_from_id(node.entity)
+                    .try_get::<&mut GlobalTransform>(|global| global.0 = node.global);
+            }
+        }
+    }
+}
+
+// `offset` is the index of nodes[0] in the whole tree. Parents come before their
+// children, so a parent is either earlier in the slice or the job's root (`base`).
+fn propagate_subtree(
+    nodes: &mut [TransformNode],
+    offset: usize,
+    base: Mat4,
+    base_updated: bool,
+    full: bool,
+) -> usize {
+    let mut updated = 0;
+
+    for i in 0..nodes.len() {
+        let (parent_global, parent_updated) = match nodes[i].parent {
+            Some(parent) if parent as usize >= offset => {
+                let parent = &nodes[parent as usize - offset];
+                (parent.global, parent.updated)
+            }
+            _ => (base, base_updated),
+        };
+
+        let node = &mut nodes[i];
+        node.updated = full || node.dirty || parent_updated;
+        if node.updated {
+            node.global = parent_global * node.local.compute_matrix();
+            node.dirty = false;
+            updated += 1;
+        }
+    }
+
+    updated
+}
+
+fn mark_structure_changed(world: &World) {
+    world.try_get::<&mut TransformTree>(|tree| tree.mark_structure_changed());
+}
+
+pub fn transform_propagation_system(world: &World) {
+    world
+        .component::<TransformPropagation>()
+        .add_trait::<flecs::Singleton>()
+        .set(TransformPropagation::default());
+
+    world
+        .component::<TransformTree>()
+        .add_trait::<flecs::Singleton>()
+        .set(TransformTree {
+            structure_changed: true,
+            ..Default::default()
+        });
+
+    // Anything that adds or removes a node, or moves it to another parent. A new
+    // StaticTransform too: its last change may not have been picked up yet
+    world
+        .observer::<flecs::OnAdd, ()>()
+        .with(StaticTransform::id())
+        .each_entity(|entity, _| mark_structure_changed(&entity.world()));
+    world
+        .observer::<flecs::OnAdd, ()>()
+        .with(Transform::id())
+        .each_entity(|entity, _| mark_structure_changed(&entity.world()));
+    world
+        .observer::<flecs::OnRemove, ()>()
+        .with(Transform::id())
+        .each_entity(|entity, _| mark_structure_changed(&entity.world()));
+    world
+        .observer::<flecs::OnAdd, ()>()
+        .with(GlobalTransform::id())
+        .each_entity(|entity, _| mark_structure_changed(&entity.world()));
+    world
+        .observer::<flecs::OnRemove, ()>()
+        .with(GlobalTransform::id())
+        .each_entity(|entity, _| mark_structure_changed(&entity.world()));
+    world
+        .observer::<flecs::OnAdd, ()>()
+        .with((flecs::ChildOf, flecs::Wildcard))
+        .each_entity(|entity, _| mark_structure_changed(&entity.world()));
+    world
+        .observer::<flecs::OnRemove, ()>()
+        .with((flecs::ChildOf, flecs::Wildcard))
+        .each_entity(|entity, _| mark_structure_changed(&entity.world()));
+
+    let members = world
+        .query::<&Transform>()
+        .with(GlobalTransform::id())
+        .set_cached()
+        .build();
+    let dynamic = world
+        .query::<&Transform>()
+        .with(GlobalTransform::id())
+        .without(StaticTransform::id())
+        .set_cached()
+        .build();
+    let statics = world
+        .query::<&Transform>()
+        .with(GlobalTransform::id())
+        .with(StaticTransform::id())
+        .set_cached()
+        .build();
+
+    world
+        .system_named::<(&TransformPropagation, &mut TransformTree)>("transform_propagation_system")
+        .kind(flecs::pipeline::PostUpdate)
+        .run(move |iter| propagate_transforms(&iter.world(), &members, &dynamic, &statics));
+}
+
+// `members` is every node, `dynamic` and `statics` the ones without and with a
+// StaticTransform
+fn propagate_transforms(
+    world: &World,
+    members: &Query<&Transform>,
+    dynamic: &Query<&Transform>,
+    statics: &Query<&Transform>,
+) {
+    let settings = world.get::<&TransformPropagation>(|settings| *settings);
+    let started = Instant::now();
+
+    world.get::<&mut TransformTree>(|tree| {
+        tree.refreshed_last_frame = 0;
+        if tree.structure_changed {
+            tree.rebuild(members);
+        } else {
+            tree.refresh_locals(dynamic);
+            if settings.check_static {
+                tree.check_static(world, statics);
+            }
+        }
+
+        tree.propagate(settings);
+        tree.write_back(world);
+        tree.last_frame_ms = started.elapsed().as_secs_f32() * 1000.0;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Quat, Vec3};
+
+    use super::*;
+
+    fn world() -> World {
+        let world = World::new();
+        transform_propagation_system(&world);
+        world
+    }
+
+    fn spawn(world: &World, local: Transform, parent: Option<Entity>) -> Entity {
+        let entity = world.entity().set(local).set(GlobalTransform::default());
+        if let Some(parent) = parent {
+            entity.child_of(parent);
+        }
+        entity.id()
+    }
+
+    // Root -> `branches` children -> the rest as `fanout`-ary trees under those
+    fn build_hierarchy(world: &World, nodes: usize, branches: usize, fanout: usize) -> Vec<Entity> {
+        let mut entities: Vec<Entity> = Vec::with_capacity(nodes);
+        for index in 0..nodes {
+            let local = Transform {
+                rotation: Quat::from_rotation_y(0.1),
+                ..Transform::from_xyz(1.0, 0.0, 0.0)
+            };
+            let parent = match index {
+                0 => None,
+                index if index <= branches => Some(entities[0]),
+                index => Some(entities[(index - branches - 1) / fanout + 1]),
+            };
+            entities.push(spawn(world, local, parent));
+        }
+        entities
+    }
+
+    fn translation(world: &World, entity: Entity) -> Vec3 {
+        world
+            .entity_from_id(entity)
+            .get::<&GlobalTransform>(|global| global.0.w_axis.truncate())
+    }
+
+    fn move_by(world: &World, entity: Entity, offset: Vec3) {
+        world
+            .entity_from_id(entity)
+            .get::<&mut Transform>(|transform| transform.translation += offset);
+    }
+
+    fn updated(world: &World) -> usize {
+        world.get::<&TransformTree>(|tree| tree.updated_last_frame())
+    }
+
+    fn set_mode(world: &World, parallel: bool, incremental: bool) {
+        world.get::<&mut TransformPropagation>(|settings| {
+            settings.parallel = parallel;
+            settings.incremental = incremental;
+        });
+    }
+
+    #[test]
+    fn unchanged_frames_update_nothing() {
+        let world = world();
+        let entities = build_hierarchy(&world, 500, 8, 4);
+        world.progress();
+        assert_eq!(updated(&world), entities.len());
+
+        world.progress();
+        assert_eq!(updated(&world), 0);
+    }
+
+    #[test]
+    fn only_the_moved_subtree_is_updated() {
+        let world = world();
+        let root = spawn(&world, Transform::default(), None);
+        let branch = spawn(&world, Transform::from_xyz(1.0, 0.0, 0.0), Some(root));
+        let child = spawn(&world, Transform::from_xyz(1.0, 0.0, 0.0), Some(branch));
+        let leaf = spawn(&world, Transform::from_xyz(1.0, 0.0, 0.0), Some(child));
+        let sibling = spawn(&world, Transform::from_xyz(0.0, 0.0, 5.0), Some(root));
+        world.progress();
+
+        move_by(&world, leaf, Vec3::Y);
+        world.progress();
+        assert_eq!(updated(&world), 1);
+        assert_eq!(translation(&world, leaf), Vec3::new(3.0, 1.0, 0.0));
+
+        move_by(&world, branch, Vec3::Z);
+        world.progress();
+        assert_eq!(updated(&world), 3);
+        assert_eq!(translation(&world, leaf), Vec3::new(3.0, 1.0, 1.0));
+        assert_eq!(translation(&world, sibling), Vec3::new(0.0, 0.0, 5.0));
+
+        // A moved root moves everything under it
+        move_by(&world, root, Vec3::X);
+        world.progress();
+        assert_eq!(updated(&world), 5);
+        assert_eq!(translation(&world, sibling), Vec3::new(1.0, 0.0, 5.0));
+    }
+
+    #[test]
+    fn a_reparented_node_follows_its_new_parent() {
+        let world = world();
+        let a = spawn(&world, Transform::from_xyz(10.0, 0.0, 0.0), None);
+        let b = spawn(&world, Transform::from_xyz(0.0, 10.0, 0.0), None);
+        let child = spawn(&world, Transform::from_xyz(1.0, 0.0, 0.0), Some(a));
+        world.progress();
+        assert_eq!(translation(&world, child), Vec3::new(11.0, 0.0, 0.0));
+
+        world.entity_from_id(child).child_of(b);
+        world.progress();
+        assert_eq!(translation(&world, child), Vec3::new(1.0, 10.0, 0.0));
+    }
+
+    #[test]
+    fn a_deep_hierarchy_propagates_to_the_bottom() {
+        const DEPTH: usize = 10_000;
+        let world = world();
+        let mut chain = vec![spawn(&world, Transform::default(), None)];
+        for _ in 1..DEPTH {
+            let parent = *chain.last().unwrap();
+            chain.push(spawn(
+                &world,
+                Transform::from_xyz(1.0, 0.0, 0.0),
+                Some(parent),
+            ));
+        }
+        world.progress();
+        let bottom = *chain.last().unwrap();
+        assert_eq!(
+            translation(&world, bottom),
+            Vec3::new((DEPTH - 1) as f32, 0.0, 0.0)
+        );
+
+        move_by(&world, chain[0], Vec3::Y);
+        world.progress();
+        assert_eq!(updated(&world), DEPTH);
+        assert_eq!(
+            translation(&world, bottom),
+            Vec3::new((DEPTH - 1) as f32, 1.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn incremental_matches_a_full_recompute() {
+        let world = world();
+        let entities = build_hierarchy(&world, 5_000, 64, 4);
+        world.progress();
+
+        // One node in 100 gets a new Transform each frame
+        for frame in 0..20 {
+            for (index, &entity) in entities.iter().enumerate() {
+                if (index + frame) % 100 == 0 {
+                    world
+                        .entity_from_id(entity)
+                        .get::<&mut Transform>(|transform| transform.rotate_y(0.01));
+                }
+            }
+            world.progress();
+            assert!(updated(&world) < entities.len());
+        }
+
+        let globals = |world: &World| -> Vec<Mat4> {
+            entities
+                .iter()
+                .map(|&entity| {
+                    world
+                        .entity_from_id(entity)
+                        .get::<&GlobalTransform>(|global| global.0)
+                })
+                .collect()
+        };
+        let incremental = globals(&world);
+        set_mode(&world, false, false);
+        world.progress();
+        assert_eq!(updated(&world), entities.len());
+        let full = globals(&world);
+
+        for (a, b) in incremental.iter().zip(&full) {
+            assert!(a.abs_diff_eq(*b, 1e-5));
+        }
+    }
+
+    // Static entities next to moving roots, and moving parents with two children each
+    fn static_scene(world: &World) -> (Vec<Entity>, Vec<Entity>, Vec<Entity>) {
+        world.get::<&mut TransformPropagation>(|settings| settings.check_static = false);
+        let statics = (0..1_000)
+            .map(|index| {
+                let entity = spawn(world, Transform::from_xyz(index as f32, 0.0, 0.0), None);
+                world.entity_from_id(entity).add(StaticTransform);
+                entity
+            })
+            .collect();
+        let moving: Vec<Entity> = (0..50)
+            .map(|index| spawn(world, Transform::from_xyz(-(index as f32), 0.0, 0.0), None))
+            .collect();
+        let children = moving[..10]
+            .iter()
+            .flat_map(|&parent| {
+                [0, 1].map(|_| spawn(world, Transform::from_xyz(0.0, 1.0, 0.0), Some(parent)))
+            })
+            .collect();
+        world.progress();
+        (statics, moving, children)
+    }
+
+    #[test]
+    fn static_entities_are_not_compared() {
+        let world = world();
+        let (statics, moving, children) = static_scene(&world);
+
+        for &entity in moving.iter().chain(&children) {
+            move_by(&world, entity, Vec3::Z);
+        }
+        world.progress();
+        let dynamic = moving.len() + children.len();
+        assert_eq!(
+            world.get::<&TransformTree>(|tree| tree.refreshed_last_frame()),
+            dynamic
+        );
+        assert_eq!(updated(&world), dynamic);
+
+        // Children are recomputed when only their parents moved
+        for &entity in &moving {
+            move_by(&world, entity, Vec3::Z);
+        }
+        world.progress();
+        assert_eq!(updated(&world), dynamic);
+        assert_eq!(translation(&world, children[0]), Vec3::new(0.0, 1.0, 3.0));
+        assert_eq!(translation(&world, statics[42]), Vec3::new(42.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn check_static_reports_a_moved_static_entity_once_and_follows_it() {
+        let world = world();
+        let (statics, _, _) = static_scene(&world);
+        world.get::<&mut TransformPropagation>(|settings| settings.check_static = true);
+
+        let moved = statics[42];
+        for _ in 0..3 {
+            move_by(&world, moved, Vec3::Z);
+            world.progress();
+        }
+        assert_eq!(
+            world.get::<&TransformTree>(|tree| tree.moved_static_count()),
+            1
+        );
+        assert_eq!(translation(&world, moved), Vec3::new(42.0, 0.0, 3.0));
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+
+use flecs_ecs::prelude::*;
+use glam::{Vec2, Vec3};
+
+use crate::bounds::Ray;
+
+/// What a ray is tested against. Each is answered by the plugin that owns the
+/// geometry, see `Raycasters`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RaycastBackend {
+    /// Colliders (catalyst_physics). Entities without one are not hit.
+    #[default]
+    Physics,
+    /// The triangles of drawn meshes (catalyst_renderer), with their UVs.
+    Render,
+}
+
+/// Which entities a ray may hit.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RaycastFilter {
+    /// Hits further along the ray are ignored.
+    pub max_distance: f32,
+    /// Never hit, e.g. the entity casting the ray.
+    pub exclude: Vec<Entity>,
+}
+
+impl Default for RaycastFilter {
+    fn default() -> Self {
+        Self {
+            max_distance: f32::INFINITY,
+            exclude: Vec::new(),
+        }
+    }
+}
+
+impl RaycastFilter {
+    pub fn max_distance(mut self, max_distance: f32) -> Self {
+        self.max_distance = max_distance;
+        self
+    }
+
+    pub fn exclude(mut self, entity: Entity) -> Self {
+        self.exclude.push(entity);
+        self
+    }
+
+    pub fn allows(&self, entity: Entity, distance: f32) -> bool {
+        distance <= self.max_distance && !self.exclude.contains(&entity)
+    }
+}
+
+/// The nearest thing a ray hit.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RayHit {
+    pub entity: Entity,
+    /// Along the ray, in world units.
+    pub distance: f32,
+    /// World space.
+    pub position: Vec3,
+    /// World space surface normal, facing back along the ray.
+    pub normal: Vec3,
+    /// Index of the triangle in the mesh's index list (index / 3). Render hits only.
+    pub triangle: Option<u32>,
+    /// The mesh's UV at the hit. Render hits only.
+    pub uv: Option<Vec2>,
+    /// World space direction of increasing U along the surface, perpendicular to the
+    /// normal. Render hits only, None where the triangle's UVs are degenerate.
+    pub tangent: Option<Vec3>,
+}
+
+/// Casts a ray for a backend. Copied out of `Raycasters` before it runs, so it is free
+/// to access any singleton.
+pub type Raycaster = fn(&WorldRef, &Ray, &RaycastFilter) -> Option<RayHit>;
+
+/// Registry of raycast backends, in the singleton of the same name. Plugins register
+/// theirs at build time; `raycast` runs them.
+#[derive(Component, Default)]
+pub struct Raycasters {
+    backends: BTreeMap<RaycastBackend, Raycaster>,
+}
+
+impl Raycasters {
+    /// Registering a backend again replaces it.
+    pub fn register(&mut self, backend: RaycastBackend, raycaster: Raycaster) -> &mut Self {
+        self.backends.insert(backend, raycaster);
+        self
+    }
+
+    pub fn get(&self, backend: RaycastBackend) -> Option<Raycaster> {
+        self.backends.get(&backend).copied()
+    }
+}
+
+/// The nearest hit of `ray` with `backend`. None if nothing is hit, or if no plugin
+/// answers for the backend (e.g. Physics without the PhysicsPlugin).
+pub fn raycast(
+    world: &WorldRef,
+    ray: &Ray,
+    filter: &RaycastFilter,
+    backend: RaycastBackend,
+) -> Option<RayHit> {
+    let raycaster = world
+        .try_get::<&Raycasters>(|raycasters| raycasters.get(backend))
+        .flatten()?;
+    raycaster(world, ray, filter)
+}
@@ -0,0 +1,250 @@
+use std::{any::Any, collections::BTreeMap, sync::Arc};
+
+use flecs_ecs::prelude::*;
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::Value;
+
+use crate::App;
+
+/// Components the registry can serialize, edit and construct. Implemented for every
+/// Component that is Serialize + Deserialize + Default + Clone; register it with
+/// `app.register_component::<T>()`.
+pub trait RegisteredComponent:
+    ComponentId + DataComponent + ComponentType<Struct> + Serialize + DeserializeOwned + Default + Clone
+{
+}
+
+impl<T> RegisteredComponent for T where
+    T: ComponentId
+        + DataComponent
+        + ComponentType<Struct>
+        + Serialize
+        + DeserializeOwned
+        + Default
+        + Clone
+{
+}
+
+// Edits the component of the entity through a UI of whatever type the editor was
+// registered for (egui::Ui for the debug inspector). Returns the new value when it changed.
+type ComponentEditor = Arc<dyn Fn(&EntityView, &mut dyn Any) -> Option<Value> + Send + Sync>;
+
+/// What the registry knows about one component, for tools that only have its name.
+#[derive(Clone)]
+pub struct ComponentRegistration {
+    name: &'static str,
+    id: Entity,
+    // None for tags, which are saved as null and added back on load
+    data: Option<DataFunctions>,
+    editor: Option<ComponentEditor>,
+}
+
+#[derive(Clone, Copy)]
+struct DataFunctions {
+    serialize: fn(&EntityView) -> Option<Result<Value, String>>,
+    deserialize: fn(&EntityView, Value) -> Result<(), String>,
+    insert_default: fn(&EntityView),
+    default_value: fn() -> Result<Value, String>,
+}
+
+impl ComponentRegistration {
+    /// Type name without its module path, also the key in scene files.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn id(&self) -> Entity {
+        self.id
+    }
+
+    pub fn is_tag(&self) -> bool {
+        self.data.is_none()
+    }
+
+    pub fn has(&self, entity: &EntityView) -> bool {
+        entity.has(self.id)
+    }
+
+    pub fn remove(&self, entity: &EntityView) {
+        entity.remove(self.id);
+    }
+
+    /// Adds the component with its Default value, replacing it if the entity already has it.
+    pub fn insert_default(&self, entity: &EntityView) {
+        match &self.data {
+            Some(data) => (data.insert_default)(entity),
+            None => {
+                entity.add(self.id);
+            }
+        }
+    }
+
+    /// The component's Default value, serialized; null for tags.
+    pub fn default_value(&self) -> Result<Value, String> {
+        match &self.data {
+            Some(data) => (data.default_value)(),
+            None => Ok(Value::Null),
+        }
+    }
+
+    /// None when the entity doesn't have the component.
+    pub fn serialize(&self, entity: &EntityView) -> Option<Result<Value, String>> {
+        match &self.data {
+            Some(data) => (data.serialize)(entity),
+            None => self.has(entity).then_some(Ok(Value::Null)),
+        }
+    }
+
+    /// Sets the component from `value`. Fields missing from `value` keep their Default.
+    pub fn deserialize(&self, entity: &EntityView, value: Value) -> Result<(), String> {
+        match &self.data {
+            Some(data) => (data.deserialize)(entity, value),
+            None => {
+                entity.add(self.id);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn has_editor(&self) -> bool {
+        self.editor.is_some()
+    }
+
+    /// Runs the editor registered with `ComponentRegistry::set_editor`. False when there
+    /// is none, it was registered for another UI type, or nothing changed.
+    pub fn edit(&self, entity: &EntityView, ui: &mut dyn Any) -> bool {
+        self.edit_value(entity, ui).is_some()
+    }
+
+    /// Like `edit`, but returns the component's new value, serialized, when it changed.
+    /// Inside a system the set is deferred, so serializing the entity would still give
+    /// the value from before the edit.
+    pub fn edit_value(&self, entity: &EntityView, ui: &mut dyn Any) -> Option<Value> {
+        self.editor.as_ref().and_then(|editor| editor(entity, ui))
+    }
+}
+
+/// Components known by name at runtime, so the inspector and scene files can handle
+/// game components they weren't compiled against. Engine plugins register theirs at
+/// build time; games call `app.register_component::<T>()` for their own.
+#[derive(Component, Default)]
+pub struct ComponentRegistry {
+    components: BTreeMap<&'static str, ComponentRegistration>,
+}
+
+impl ComponentRegistry {
+    pub fn register<T: RegisteredComponent>(&mut self, world: &World) -> &mut Self {
+        self.insert(ComponentRegistration {
+            name: short_type_name::<T>(),
+            id: world.component_id::<T>(),
+            data: Some(DataFunctions {
+                serialize: serialize_component::<T>,
+                deserialize: deserialize_component::<T>,
+                insert_default: insert_default_component::<T>,
+                default_value: default_component_value::<T>,
+            }),
+            editor: None,
+        })
+    }
+
+    /// Tags (zero-sized components) have no data: they are saved as null and can't be edited.
+    pub fn register_tag<T: ComponentId>(&mut self, world: &World) -> &mut Self {
+        self.insert(ComponentRegistration {
+            name: short_type_name::<T>(),
+            id: world.component_id::<T>(),
+            data: None,
+            editor: None,
+        })
+    }
+
+    /// Editor for a registered component, drawn with a `U` (e.g. egui::Ui). It edits a
+    /// copy, which is set back only when the editor returns true, so OnSet observers run.
+    pub fn set_editor<T: RegisteredComponent, U: 'static>(&mut self, editor: fn(&mut U, &mut T) -> bool) {
+        let Some(registration) = self.components.get_mut(short_type_name::<T>()) else {
+            log::warn!(
+                "No component registered as '{}', its editor is ignored",
+                short_type_name::<T>()
+            );
+            return;
+        };
+
+        registration.editor = Some(Arc::new(move |entity, ui| {
+            let ui = ui.downcast_mut::<U>()?;
+            let mut value = entity.try_get::<&T>(|value| value.clone())?;
+            if !editor(ui, &mut value) {
+                return None;
+            }
+            let serialized = serde_json::to_value(&value);
+            entity.set(value);
+            // Only fails for components that can't be saved either
+            serialized.ok()
+        }));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ComponentRegistration> {
+        self.components.get(name)
+    }
+
+    /// Registrations sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = &ComponentRegistration> {
+        self.components.values()
+    }
+
+    fn insert(&mut self, registration: ComponentRegistration) -> &mut Self {
+        if let Some(previous) = self.components.get(registration.name) {
+            if previous.id != registration.id {
+                log::warn!(
+                    "Two components are named '{}', the last one registered replaces the other",
+                    registration.name
+                );
+            }
+        }
+        self.components.insert(registration.name, registration);
+        self
+    }
+}
+
+impl App {
+    /// Makes `T` visible to the inspector and saved in scene files.
+    pub fn register_component<T: RegisteredComponent>(&mut self) -> &mut Self {
+        let world = &self.world;
+        world.get::<&mut ComponentRegistry>(|registry| {
+            registry.register::<T>(world);
+        });
+        self
+    }
+
+    pub fn register_tag<T: ComponentId>(&mut self) -> &mut Self {
+        let world = &self.world;
+        world.get::<&mut ComponentRegistry>(|registry| {
+            registry.register_tag::<T>(world);
+        });
+        self
+    }
+}
+
+fn short_type_name<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    // Generic arguments can contain paths too, so only look before the first '<'
+    let path_end = name.find('<').unwrap_or(name.len());
+    let start = name[..path_end].rfind("::").map_or(0, |index| index + 2);
+    &name[start..]
+}
+
+fn serialize_component<T: RegisteredComponent>(entity: &EntityView) -> Option<Result<Value, String>> {
+    entity.try_get::<&T>(|value| serde_json::to_value(value).map_err(|error| error.to_string()))
+}
+
+fn deserialize_component<T: RegisteredComponent>(entity: &EntityView, value: Value) -> Result<(), String> {
+    let value: T = serde_json::from_value(value).map_err(|error| error.to_string())?;
+    entity.set(value);
+    Ok(())
+}
+
+fn insert_default_component<T: RegisteredComponent>(entity: &EntityView) {
+    entity.set(T::default());
+}
+
+fn default_component_value<T: RegisteredComponent>() -> Result<Value, String> {
+    serde_json::to_value(T::default()).map_err(|error| error.to_string())
+}
@@ -0,0 +1,619 @@
+//! Entities whose Transform comes from a remote authority (a server, another peer)
+//! instead of the local simulation.
+//!
+//! The network layer hands each received state to `apply_remote_state`, which buffers
+//! it on the entity's Replicated component. Every frame "Interpolate Replicated" shows
+//! the entity as it was `ReplicationSettings::render_delay` seconds ago on the remote
+//! clock: between the two buffered states around that time while they last, and
+//! carried on along the last velocity (dead reckoning) for at most `max_extrapolation`
+//! seconds when the next state is late or lost. Physics turns replicated bodies
+//! kinematic, so the local simulation doesn't fight the authority.
+
+use std::collections::VecDeque;
+
+use flecs_ecs::prelude::*;
+use glam::{Quat, Vec3};
+
+use crate::{smoothing::exp_decay, time::Time, transform::Transform};
+
+/// One state of a replicated entity, as the authority sent it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RemoteState {
+    pub position: Vec3,
+    pub rotation: Quat,
+    /// World units per second.
+    pub velocity: Vec3,
+    /// When the state was taken, in seconds on the authority's clock.
+    pub timestamp: f64,
+}
+
+/// What a replicated entity showed this frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReplicationMode {
+    /// No state old enough to show yet: the entity holds its first one.
+    #[default]
+    Waiting,
+    /// Between two buffered states.
+    Interpolating,
+    /// Past the last state, moving along its velocity.
+    Extrapolating,
+    /// Past the last state for longer than `max_extrapolation`: stopped where dead
+    /// reckoning left it until the next state comes.
+    Stalled,
+}
+
+/// Marks an entity whose Transform is driven by `apply_remote_state` rather than
+/// local simulation, and buffers its states. Movement intents and physics leave its
+/// Transform alone; a dynamic body on it is simulated as kinematic.
+#[derive(Component, Clone, Debug, Default)]
+pub struct Replicated {
+    // Sorted by timestamp, without duplicates
+    snapshots: VecDeque<RemoteState>,
+    mode: ReplicationMode,
+    // Timestamp of the state sampled from last
+    base: f64,
+    velocity: Vec3,
+    // Position shown last frame, and what is still added to smooth out a correction
+    rendered: Option<Vec3>,
+    correction: Vec3,
+    late: u32,
+}
+
+impl Replicated {
+    pub fn mode(&self) -> ReplicationMode {
+        self.mode
+    }
+
+    /// The velocity the entity moved with this frame, e.g. for animation.
+    pub fn velocity(&self) -> Vec3 {
+        self.velocity
+    }
+
+    /// States buffered for the frames to come (and the one being left).
+    pub fn buffered(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    /// States dropped for arriving after the time they describe was already shown.
+    pub fn late_states(&self) -> u32 {
+        self.late
+    }
+
+    /// Adds a state in timestamp order. Returns false if it came too late to be shown:
+    /// older than the state being interpolated from, or already buffered.
+    pub fn push(&mut self, state: RemoteState, render_time: f64, max_states: usize) -> bool {
+        let index = self
+            .snapshots
+            .partition_point(|buffered| buffered.timestamp < state.timestamp);
+        let duplicate = self
+            .snapshots
+            .get(index)
+            .is_some_and(|buffered| buffered.timestamp == state.timestamp);
+        // Before the first state while it's already behind the render time: the
+        // frames it would have filled have been drawn
+        let shown = index == 0
+            && self
+                .snapshots
+                .front()
+                .is_some_and(|first| first.timestamp <= render_time);
+        if duplicate || shown {
+            self.late += 1;
+            return false;
+        }
+        self.snapshots.insert(index, state);
+        while self.snapshots.len() > max_states.max(2) {
+            self.snapshots.pop_front();
+        }
+        true
+    }
+
+    /// Position and rotation at `render_time`, or None before the first state. Drops
+    /// the states no longer needed and sets the mode and velocity.
+    pub fn sample(&mut self, render_time: f64, max_extrapolation: f32) -> Option<(Vec3, Quat)> {
+        while self.snapshots.len() >= 2 && self.snapshots[1].timestamp <= render_time {
+            self.snapshots.pop_front();
+        }
+
+        let first = *self.snapshots.front()?;
+        self.base = first.timestamp;
+        if first.timestamp > render_time {
+            self.mode = ReplicationMode::Waiting;
+            self.velocity = Vec3::ZERO;
+            return Some((first.position, first.rotation));
+        }
+
+        if let Some(next) = self.snapshots.get(1) {
+            self.mode = ReplicationMode::Interpolating;
+            let span = (next.timestamp - first.timestamp) as f32;
+            let t = ((render_time - first.timestamp) as f32 / span).clamp(0.0, 1.0);
+            self.velocity = first.velocity.lerp(next.velocity, t);
+            return Some((
+                hermite(&first, next, span, t),
+                first.rotation.slerp(next.rotation, t),
+            ));
+        }
+
+        let ahead = (render_time - first.timestamp) as f32;
+        if ahead > max_extrapolation {
+            self.mode = ReplicationMode::Stalled;
+            self.velocity = Vec3::ZERO;
+        } else {
+            self.mode = ReplicationMode::Extrapolating;
+            self.velocity = first.velocity;
+        }
+        Some((
+            first.position + first.velocity * ahead.min(max_extrapolation),
+            first.rotation,
+        ))
+    }
+}
+
+// Cubic through both positions with both velocities as tangents: exact for constant
+// acceleration, and the speed doesn't jump at each state like a straight line's does
+fn hermite(from: &RemoteState, to: &RemoteState, span: f32, t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    from.position * (2.0 * t3 - 3.0 * t2 + 1.0)
+        + from.velocity * span * (t3 - 2.0 * t2 + t)
+        + to.position * (-2.0 * t3 + 3.0 * t2)
+        + to.velocity * span * (t3 - t2)
+}
+
+/// How replicated entities are shown.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ReplicationSettings {
+    /// Seconds behind the authority's clock the entities are shown. Covers the
+    /// variation in latency and a lost state or two; longer is smoother but later.
+    pub render_delay: f32,
+    /// Longest dead reckoning past the last state, in seconds.
+    pub max_extrapolation: f32,
+    /// Time constant, in seconds, over which the jump is smoothed out when a state
+    /// arrives that disagrees with what dead reckoning showed.
+    pub correction_time: f32,
+    /// Corrections longer than this are applied at once: a teleport, not a drift.
+    pub snap_distance: f32,
+    /// States buffered per entity at most; the oldest go first.
+    pub max_states: usize,
+}
+
+impl Default for ReplicationSettings {
+    fn default() -> Self {
+        Self {
+            render_delay: 0.1,
+            max_extrapolation: 0.25,
+            correction_time: 0.1,
+            snap_distance: 4.0,
+            max_states: 32,
+        }
+    }
+}
+
+// How much faster than real time the clock may run to catch up with the authority
+const CLOCK_CATCH_UP: f64 = 0.1;
+
+/// The authority's clock as seen locally: set from the first state received, then run
+/// on the frame's real delta and pulled towards the newest timestamp when it lags.
+/// It never goes back, so neither do the entities.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct ReplicationClock {
+    time: Option<f64>,
+    newest: Option<f64>,
+}
+
+impl ReplicationClock {
+    /// The authority's time now, None until a state was received.
+    pub fn time(&self) -> Option<f64> {
+        self.time
+    }
+
+    /// Time entities are shown at.
+    pub fn render_time(&self, settings: &ReplicationSettings) -> f64 {
+        self.time.unwrap_or(f64::NEG_INFINITY) - settings.render_delay as f64
+    }
+
+    /// A state with this timestamp was received.
+    pub fn observe(&mut self, timestamp: f64) {
+        self.newest = Some(
+            self.newest
+                .map_or(timestamp, |newest| newest.max(timestamp)),
+        );
+        if self.time.is_none() {
+            self.time = Some(timestamp);
+        }
+    }
+
+    /// Moves on by a frame of `dt` seconds. Lagging by more than `render_delay` (a
+    /// hitch, a long frame) jumps to the newest timestamp; less is caught up gradually.
+    pub fn advance(&mut self, dt: f32, render_delay: f32) {
+        let (Some(time), Some(newest)) = (self.time, self.newest) else {
+            return;
+        };
+        let dt = dt as f64;
+        let mut time = time + dt;
+        let lag = newest - time;
+        if lag > render_delay as f64 {
+            time = newest;
+        } else if lag > 0.0 {
+            time += lag.min(dt * CLOCK_CATCH_UP);
+        }
+        self.time = Some(time);
+    }
+}
+
+/// Buffers a state of `entity` received from the authority, adding Replicated if it
+/// has none. States may arrive in any order; the ones that come too late to be shown
+/// are dropped and counted (see Replicated::late_states).
+///
+/// Give entities their Replicated when spawning them if their states can arrive
+/// inside a system: until the deferred add lands, each state replaces the last.
+pub fn apply_remote_state(
+    entity: EntityView,
+    position: Vec3,
+    rotation: Quat,
+    velocity: Vec3,
+    timestamp: f64,
+) {
+    let world = entity.world();
+    let settings = world.get::<&ReplicationSettings>(|settings| *settings);
+    let render_time = world.get::<&mut ReplicationClock>(|clock| {
+        clock.observe(timestamp);
+        clock.render_time(&settings)
+    });
+    let state = RemoteState {
+        position,
+        rotation,
+        velocity,
+        timestamp,
+    };
+    let buffered = entity.try_get::<&mut Replicated>(|replicated| {
+        replicated.push(state, render_time, settings.max_states);
+    });
+    if buffered.is_none() {
+        let mut replicated = Replicated::default();
+        replicated.push(state, render_time, settings.max_states);
+        entity.set(replicated);
+    }
+}
+
+// Before transform propagation (both PostUpdate, these are registered first), so the
+// states show up in this frame's GlobalTransform
+pub fn replication_system(world: &mut World) {
+    world
+        .component::<ReplicationSettings>()
+        .add_trait::<flecs::Singleton>();
+    world.set(ReplicationSettings::default());
+    world
+        .component::<ReplicationClock>()
+        .add_trait::<flecs::Singleton>();
+    world.set(ReplicationClock::default());
+    world.component::<Replicated>();
+
+    world
+        .system_named::<(&mut ReplicationClock, &ReplicationSettings, &Time)>(
+            "Advance Replication Clock",
+        )
+        .kind(flecs::pipeline::PostUpdate)
+        .each(|(clock, settings, time)| {
+            // Real time: the authority doesn't pause with local gameplay
+            clock.advance(time.delta_seconds(), settings.render_delay);
+        });
+
+    world
+        .system_named::<(
+            &mut Replicated,
+            &mut Transform,
+            &ReplicationSettings,
+            &ReplicationClock,
+            &Time,
+        )>("Interpolate Replicated")
+        .kind(flecs::pipeline::PostUpdate)
+        .each(|(replicated, transform, settings, clock, time)| {
+            let was_reckoning = matches!(
+                replicated.mode,
+                ReplicationMode::Extrapolating | ReplicationMode::Stalled
+            );
+            let base = replicated.base;
+            let Some((position, rotation)) =
+                replicated.sample(clock.render_time(settings), settings.max_extrapolation)
+            else {
+                return;
+            };
+
+            // A state arrived during dead reckoning: start from where the entity was
+            // shown and let the difference fade
+            let corrected = was_reckoning
+                && (replicated.mode == ReplicationMode::Interpolating || replicated.base != base);
+            if let Some(rendered) = replicated.rendered.filter(|_| corrected) {
+                replicated.correction = rendered - position;
+            }
+            if replicated.correction.length() > settings.snap_distance {
+                replicated.correction = Vec3::ZERO;
+            }
+            replicated.correction = exp_decay(
+                replicated.correction,
+                Vec3::ZERO,
+                1.0 / settings.correction_time.max(1e-4),
+                time.delta_seconds(),
+            );
+
+            let shown = position + replicated.correction;
+            replicated.rendered = Some(shown);
+            transform.translation = shown;
+            transform.rotation = rotation;
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ops::Range, time::Duration};
+
+    use super::*;
+    use crate::{App, transform::GlobalTransform};
+
+    // The authority's clock starts long before ours
+    const CLOCK_OFFSET: f64 = 1000.0;
+    const SEND_INTERVAL: f64 = 0.05;
+    const DURATION: f64 = 10.0;
+    const OUTAGE: Range<f64> = 4.0..4.4;
+    // Uneven frames, 30 to 144 fps
+    const FRAMES: [f32; 5] = [1.0 / 60.0, 1.0 / 30.0, 1.0 / 144.0, 1.0 / 90.0, 1.0 / 60.0];
+
+    // Forwards along X at a varying speed, swaying along Z
+    fn truth(time: f64) -> (Vec3, Vec3) {
+        let t = time as f32;
+        let position = Vec3::new(4.0 * t + 0.5 * t.sin(), 0.0, (0.7 * t).sin());
+        let velocity = Vec3::new(4.0 + 0.5 * t.cos(), 0.0, 0.7 * (0.7 * t).cos());
+        (position, velocity)
+    }
+
+    // Latency and loss of a network, deterministic
+    struct SimulatedLink {
+        seed: u64,
+        latency: Range<f64>,
+        loss: f64,
+        in_flight: Vec<(f64, RemoteState)>,
+    }
+
+    impl SimulatedLink {
+        fn random(&mut self) -> f64 {
+            // xorshift64
+            self.seed ^= self.seed << 13;
+            self.seed ^= self.seed >> 7;
+            self.seed ^= self.seed << 17;
+            (self.seed >> 11) as f64 / (1u64 << 53) as f64
+        }
+
+        fn send(&mut self, now: f64, state: RemoteState) {
+            if self.random() < self.loss {
+                return;
+            }
+            let latency =
+                self.latency.start + self.random() * (self.latency.end - self.latency.start);
+            self.in_flight.push((now + latency, state));
+        }
+
+        // In the order they arrive, which isn't the order they were sent in
+        fn receive(&mut self, now: f64) -> Vec<RemoteState> {
+            self.in_flight.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let arrived = self
+                .in_flight
+                .partition_point(|(arrival, _)| *arrival <= now);
+            self.in_flight
+                .drain(..arrived)
+                .map(|(_, state)| state)
+                .collect()
+        }
+    }
+
+    struct Frame {
+        // Local time
+        time: f64,
+        shown: Vec3,
+        error: f32,
+        mode: ReplicationMode,
+    }
+
+    struct Run {
+        frames: Vec<Frame>,
+        late: u32,
+        // How far behind the authority the clock ended up
+        clock_lag: f64,
+    }
+
+    fn simulate(render_delay: f32) -> Run {
+        let mut app = App::new();
+        app.world
+            .get::<&mut ReplicationSettings>(|settings| settings.render_delay = render_delay);
+        let entity = app
+            .world
+            .entity()
+            .set(Transform::default())
+            .set(GlobalTransform::default())
+            .set(Replicated::default())
+            .id();
+
+        let mut link = SimulatedLink {
+            seed: 0x9e37_79b9_7f4a_7c15,
+            latency: 0.04..0.12,
+            loss: 0.1,
+            in_flight: Vec::new(),
+        };
+        let mut frames = Vec::new();
+        let mut now = 0.0;
+        let mut next_send = 0.0;
+        let mut index = 0;
+        while now < DURATION {
+            let dt = FRAMES[index % FRAMES.len()];
+            index += 1;
+            now += dt as f64;
+
+            while next_send <= now {
+                if !OUTAGE.contains(&next_send) {
+                    let (position, velocity) = truth(next_send);
+                    let state = RemoteState {
+                        position,
+                        rotation: Quat::IDENTITY,
+                        velocity,
+                        timestamp: next_send + CLOCK_OFFSET,
+                    };
+                    link.send(next_send, state);
+                }
+                next_send += SEND_INTERVAL;
+            }
+            for state in link.receive(now) {
+                apply_remote_state(
+                    app.world.entity_from_id(entity),
+                    state.position,
+                    state.rotation,
+                    state.velocity,
+                    state.timestamp,
+                );
+            }
+
+            app.world
+                .get::<&mut Time>(|time| time.set_delta(Duration::from_secs_f32(dt)));
+            app.update();
+
+            let render_time = app.world.get::<&ReplicationSettings>(|settings| {
+                app.world
+                    .get::<&ReplicationClock>(|clock| clock.render_time(settings))
+            });
+            let (shown, mode) = app
+                .world
+                .entity_from_id(entity)
+                .get::<(&Transform, &Replicated)>(|(transform, replicated)| {
+                    (transform.translation, replicated.mode())
+                });
+            frames.push(Frame {
+                time: now,
+                shown,
+                error: shown.distance(truth(render_time - CLOCK_OFFSET).0),
+                mode,
+            });
+        }
+
+        let late = app
+            .world
+            .entity_from_id(entity)
+            .get::<&Replicated>(|replicated| replicated.late_states());
+        let clock = app.world.get::<&ReplicationClock>(|clock| *clock);
+        Run {
+            frames,
+            late,
+            clock_lag: now + CLOCK_OFFSET - clock.time().unwrap_or(0.0),
+        }
+    }
+
+    fn reckoning(mode: ReplicationMode) -> bool {
+        matches!(
+            mode,
+            ReplicationMode::Extrapolating | ReplicationMode::Stalled
+        )
+    }
+
+    fn simulate_default() -> Run {
+        simulate(ReplicationSettings::default().render_delay)
+    }
+
+    #[test]
+    fn the_entity_never_moves_backwards_and_stays_near_the_truth() {
+        let run = simulate_default();
+        let frames = &run.frames;
+        let backwards = frames
+            .windows(2)
+            .filter(|pair| pair[1].shown.x < pair[0].shown.x - 1e-5)
+            .count();
+        assert_eq!(backwards, 0);
+
+        // Against the true position at the render time
+        let mut last_reckoning = f64::NEG_INFINITY;
+        let mut interpolation_error: f32 = 0.0;
+        let mut extrapolation_error: f32 = 0.0;
+        let mut max_error: f32 = 0.0;
+        for frame in frames.iter().filter(|frame| frame.time > 1.0) {
+            if reckoning(frame.mode) {
+                last_reckoning = frame.time;
+            }
+            match frame.mode {
+                ReplicationMode::Interpolating if frame.time > last_reckoning + 1.0 => {
+                    interpolation_error = interpolation_error.max(frame.error);
+                }
+                ReplicationMode::Extrapolating => {
+                    extrapolation_error = extrapolation_error.max(frame.error);
+                }
+                _ => {}
+            }
+            max_error = max_error.max(frame.error);
+        }
+        assert!(interpolation_error < 0.01, "{:.4}", interpolation_error);
+        // Dead reckoning along the velocity
+        assert!(extrapolation_error < 0.05, "{:.4}", extrapolation_error);
+        // Through the outage
+        assert!(max_error < 1.5, "{:.3}", max_error);
+    }
+
+    #[test]
+    fn an_outage_is_dead_reckoned_then_stalls_and_is_caught_up_smoothly() {
+        let run = simulate_default();
+        let frames = &run.frames;
+        let count =
+            |mode: ReplicationMode| frames.iter().filter(|frame| frame.mode == mode).count();
+        assert!(count(ReplicationMode::Extrapolating) > 0);
+        // Stops at max_extrapolation
+        assert!(count(ReplicationMode::Stalled) > 0);
+
+        // A stalled entity holds still
+        let stalled: Vec<Vec3> = frames
+            .iter()
+            .filter(|frame| frame.mode == ReplicationMode::Stalled)
+            .map(|frame| frame.shown)
+            .collect();
+        assert!(stalled.windows(2).all(|pair| pair[0] == pair[1]));
+
+        // The error left at the stall fades over several frames instead of being jumped
+        let stall = frames
+            .iter()
+            .position(|frame| frame.mode == ReplicationMode::Stalled)
+            .unwrap();
+        let resumed = stall
+            + frames[stall..]
+                .iter()
+                .position(|frame| frame.mode == ReplicationMode::Interpolating)
+                .expect("states come back");
+        let errors: Vec<f32> = frames[resumed - 1..]
+            .iter()
+            .take(10)
+            .map(|frame| frame.error)
+            .collect();
+        assert!(errors[1] > 0.5 * errors[0], "{:?}", errors);
+        assert!(
+            errors[1..].windows(2).all(|pair| pair[1] <= pair[0] + 1e-4),
+            "{:?}",
+            errors
+        );
+    }
+
+    #[test]
+    fn with_the_default_delay_no_state_is_late_and_the_clock_settles() {
+        let run = simulate_default();
+        assert_eq!(run.late, 0);
+        // On the authority's time less the latency
+        assert!(
+            (0.0..0.13).contains(&run.clock_lag),
+            "{:.3} s",
+            run.clock_lag
+        );
+    }
+
+    #[test]
+    fn a_delay_shorter_than_the_jitter_drops_late_states_and_stays_bounded() {
+        let short = simulate(0.01);
+        let error = short
+            .frames
+            .iter()
+            .filter(|frame| frame.time > 1.0)
+            .fold(0.0f32, |error, frame| error.max(frame.error));
+        assert!(short.late > 0);
+        assert!(error < 1.5, "{:.3}", error);
+    }
+}
@@ -0,0 +1,38 @@
+use flecs_ecs::prelude::*;
+use glam::{Vec2, Vec3};
+use serde::{Deserialize, Serialize};
+
+/// An interior space for portal culling: a box of `half_extents` around the entity,
+/// placed by its GlobalTransform (scale included). Rooms only see each other through
+/// Portals; what lies in a room no camera can see into is not drawn.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Room {
+    pub half_extents: Vec3,
+}
+
+// A unit box, sized by the transform's scale like glTF box colliders
+impl Default for Room {
+    fn default() -> Self {
+        Self {
+            half_extents: Vec3::splat(0.5),
+        }
+    }
+}
+
+/// An opening between two Room entities (a door, a window): a rectangle of
+/// `half_extents` in the entity's local XY plane, seen from either side.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct Portal {
+    pub rooms: [Entity; 2],
+    pub half_extents: Vec2,
+}
+
+impl Portal {
+    pub fn new(a: impl Into<Entity>, b: impl Into<Entity>) -> Self {
+        Self {
+            rooms: [a.into(), b.into()],
+            half_extents: Vec2::splat(0.5),
+        }
+    }
+}
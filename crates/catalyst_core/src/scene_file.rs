@@ -0,0 +1,204 @@
+use std::{collections::HashMap, path::Path};
+
+use flecs_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use crate::{
+    console::ConsoleCommands,
+    registry::ComponentRegistry,
+    stable_id::{StableId, StableIdRegistry, remap_stable_refs, stable_id_of},
+    transform::{GlobalTransform, Transform},
+};
+
+/// Entities saved by `save_scene`: every registered component they have, by name.
+/// Components that aren't registered (asset handles, renderer state) are not saved.
+/// Entities referencing each other do so through StableRefs to the saved `id`s.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct SceneFile {
+    pub entities: Vec<SceneFileEntity>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct SceneFileEntity {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    /// Index of the parent in `entities`, when the parent was saved too.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<usize>,
+    /// The entity's StableId when it was saved. Loading gives every instance new ids
+    /// and remaps StableRefs to these onto them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<StableId>,
+    pub components: Map<String, Value>,
+}
+
+#[derive(Error, Debug)]
+pub enum SceneFileError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid scene file: {0}")]
+    Format(#[from] serde_json::Error),
+}
+
+/// Every entity with at least one registered component, parents before their children.
+pub fn save_scene(world: &World) -> SceneFile {
+    let mut entities: Vec<(Entity, Map<String, Value>)> = Vec::new();
+    let mut index: HashMap<Entity, usize> = HashMap::new();
+
+    world.get::<&ComponentRegistry>(|registry| {
+        for registration in registry.iter() {
+            world
+                .query::<()>()
+                .with(registration.id())
+                .build()
+                .each_entity(|entity, _| {
+                    let value = match registration.serialize(&entity) {
+                        Some(Ok(value)) => value,
+                        Some(Err(error)) => {
+                            log::warn!(
+                                "Can't save {} of '{}': {}",
+                                registration.name(),
+                                entity.name(),
+                                error
+                            );
+                            return;
+                        }
+                        None => return,
+                    };
+
+                    let slot = *index.entry(entity.id()).or_insert_with(|| {
+                        entities.push((entity.id(), Map::new()));
+                        entities.len() - 1
+                    });
+                    entities[slot].1.insert(registration.name().to_owned(), value);
+                });
+        }
+    });
+
+    // Parents first, so loading can create them before their children
+    let depth = |entity: Entity| {
+        let mut depth = 0;
+        let mut current = world.entity_from_id(entity).parent();
+        while let Some(parent) = current {
+            depth += 1;
+            current = parent.parent();
+        }
+        depth
+    };
+    entities.sort_by_cached_key(|(entity, _)| depth(*entity));
+    let index: HashMap<Entity, usize> = entities
+        .iter()
+        .enumerate()
+        .map(|(slot, (entity, _))| (*entity, slot))
+        .collect();
+
+    SceneFile {
+        entities: entities
+            .into_iter()
+            .map(|(entity, components)| {
+                let view = world.entity_from_id(entity);
+                SceneFileEntity {
+                    name: view.name(),
+                    parent: view.parent().and_then(|parent| index.get(&parent.id()).copied()),
+                    id: stable_id_of(&view),
+                    components,
+                }
+            })
+            .collect(),
+    }
+}
+
+/// Spawns the entities of `scene` under a new entity, which is returned; destroying it
+/// removes the whole scene again. Unknown components are skipped with a warning.
+///
+/// Every load is a new instance: its entities get StableIds from `source` (e.g. the
+/// file path), their index and the number of instances of `source` loaded before, and
+/// StableRefs between them are remapped to those, so each instance references its own
+/// entities.
+pub fn load_scene<'a>(world: &'a World, scene: &SceneFile, source: &str) -> EntityView<'a> {
+    let root = world.entity();
+    let mut spawned: Vec<EntityView> = Vec::with_capacity(scene.entities.len());
+
+    let instance = world.get::<&mut StableIdRegistry>(|registry| registry.next_instance(source));
+    let ids: Vec<StableId> = (0..scene.entities.len())
+        .map(|index| StableId::for_scene_node(source, instance, index))
+        .collect();
+    let remap: HashMap<StableId, StableId> = scene
+        .entities
+        .iter()
+        .zip(&ids)
+        .filter_map(|(saved, id)| Some((saved.id?, *id)))
+        .collect();
+
+    world.get::<&ComponentRegistry>(|registry| {
+        for (saved, id) in scene.entities.iter().zip(&ids) {
+            let parent = saved
+                .parent
+                .and_then(|parent| spawned.get(parent).copied())
+                .unwrap_or(root);
+            let entity = world.entity().child_of(parent).set(*id);
+            if !saved.name.is_empty() {
+                entity.set_name(&saved.name);
+            }
+
+            for (name, value) in &saved.components {
+                let Some(registration) = registry.get(name) else {
+                    log::warn!("Unknown component '{}' in scene file, skipped", name);
+                    continue;
+                };
+                let mut value = value.clone();
+                remap_stable_refs(&mut value, &remap);
+                if let Err(error) = registration.deserialize(&entity, value) {
+                    log::warn!("Can't load {} of '{}': {}", name, saved.name, error);
+                }
+            }
+
+            // Derived, so it isn't saved
+            if entity.has(Transform::id()) && !entity.has(GlobalTransform::id()) {
+                entity.set(GlobalTransform::default());
+            }
+
+            spawned.push(entity);
+        }
+    });
+
+    root
+}
+
+pub fn save_scene_file(world: &World, path: impl AsRef<Path>) -> Result<usize, SceneFileError> {
+    let scene = save_scene(world);
+    std::fs::write(path, serde_json::to_string_pretty(&scene)?)?;
+    Ok(scene.entities.len())
+}
+
+pub fn load_scene_file<'a>(
+    world: &'a World,
+    path: impl AsRef<Path>,
+) -> Result<EntityView<'a>, SceneFileError> {
+    let path = path.as_ref();
+    let scene: SceneFile = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    Ok(load_scene(world, &scene, &path.to_string_lossy()))
+}
+
+pub(crate) fn register_scene_file_commands(commands: &mut ConsoleCommands) {
+    commands.register(
+        "scene.save",
+        "Save registered components of every entity: scene.save <path>",
+        |world, args| {
+            let path = args.first().ok_or("Usage: scene.save <path>")?;
+            let count = save_scene_file(world, path).map_err(|error| error.to_string())?;
+            Ok(format!("Saved {} entities to {}", count, path))
+        },
+    );
+    commands.register(
+        "scene.load",
+        "Spawn the entities of a saved scene: scene.load <path>",
+        |world, args| {
+            let path = args.first().ok_or("Usage: scene.load <path>")?;
+            let root = load_scene_file(world, path).map_err(|error| error.to_string())?;
+            Ok(format!("Loaded {} under entity {}", path, root.id()))
+        },
+    );
+}
@@ -0,0 +1,419 @@
+use std::time::Duration;
+
+use flecs_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::time::Time;
+
+type Action = Box<dyn FnMut(EntityView) + Send + Sync>;
+type Condition = Box<dyn FnMut(EntityView) -> bool + Send + Sync>;
+type TweenUpdate = Box<dyn FnMut(EntityView, f32) + Send + Sync>;
+
+/// A scripted chain of steps on an entity ("wait 2 seconds, open the door, then
+/// spawn enemies"), advanced every frame by "Advance Sequences" in OnUpdate, before
+/// transform propagation. When the last step is done the component is removed and
+/// `SequenceFinished` is emitted on the entity; `Sequence::cancel` stops it early.
+///
+/// ```ignore
+/// entity.set(
+///     Sequence::new()
+///         .wait_seconds(2.0)
+///         .tween(Duration::from_secs(1), move |door, t| { /* move the door */ })
+///         .emit(DoorOpened),
+/// );
+/// ```
+///
+/// Steps run on the main thread, inside the system, so closures get the owning entity
+/// and change the world the way systems do: `set`, `add` and `destroy` are deferred
+/// until the end of the phase. Use `entity.world()` to reach other entities.
+///
+/// Waits follow gameplay time (`Time::scaled_delta`) and the whole sequence freezes
+/// while it is paused; `unscaled` sequences run on real time (e.g. UI). Time left over
+/// when a step finishes carries into the next one, so chained waits don't drift with
+/// the frame rate.
+///
+/// An entity runs one Sequence; put more on child entities or into `parallel`.
+#[derive(Component, Default)]
+pub struct Sequence {
+    steps: Vec<Step>,
+    cursor: usize,
+    unscaled: bool,
+}
+
+/// Emitted on an entity when its Sequence ran its last step.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct SequenceFinished;
+
+/// Emitted on an entity when `Sequence::cancel` stopped its Sequence.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct SequenceCancelled;
+
+/// How a tween's progress maps to the value it passes on. Also used by timeline
+/// tracks between two keyframes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Easing {
+    #[default]
+    Linear,
+    /// Starts slow (cubic).
+    EaseIn,
+    /// Ends slow (cubic).
+    EaseOut,
+    /// Starts and ends slow (cubic).
+    EaseInOut,
+    SmoothStep,
+}
+
+impl Easing {
+    /// Eased value of `t` in 0..1; 0 and 1 map to themselves.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+            Easing::SmoothStep => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// What a tween or timeline does when it reaches its end.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Looping {
+    /// Stops at the end.
+    #[default]
+    Once,
+    /// Jumps back to the start.
+    Loop,
+    /// Plays backwards to the start, then forwards again.
+    PingPong,
+}
+
+impl Looping {
+    /// Progress from 0 to 1 at `time` seconds into playback of something `duration`
+    /// seconds long. Anything of zero length is always at its end.
+    pub fn phase(self, time: f32, duration: f32) -> f32 {
+        if duration <= 0.0 {
+            return 1.0;
+        }
+        let cycles = time.max(0.0) / duration;
+        match self {
+            Looping::Once => cycles.min(1.0),
+            Looping::Loop => cycles.fract(),
+            Looping::PingPong => {
+                let cycle = cycles % 2.0;
+                if cycle > 1.0 { 2.0 - cycle } else { cycle }
+            }
+        }
+    }
+
+    /// Length of one full cycle, after which playback looks the same again.
+    pub(crate) fn period(self, duration: f32) -> f32 {
+        match self {
+            Looping::PingPong => duration * 2.0,
+            Looping::Once | Looping::Loop => duration,
+        }
+    }
+}
+
+enum Step {
+    Wait {
+        duration: Duration,
+        elapsed: Duration,
+    },
+    WaitUntil(Condition),
+    Run(Action),
+    Tween {
+        duration: Duration,
+        elapsed: Duration,
+        easing: Easing,
+        looping: Looping,
+        update: TweenUpdate,
+    },
+    Parallel(Vec<Sequence>),
+    Repeat {
+        times: Option<u32>,
+        done: u32,
+        body: Sequence,
+    },
+}
+
+impl Sequence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs on real time: ignores the time scale and keeps going while gameplay is
+    /// paused. Applies to the steps of nested sequences too.
+    pub fn unscaled(mut self) -> Self {
+        self.unscaled = true;
+        self
+    }
+
+    pub fn wait(self, duration: Duration) -> Self {
+        self.step(Step::Wait {
+            duration,
+            elapsed: Duration::ZERO,
+        })
+    }
+
+    pub fn wait_seconds(self, seconds: f32) -> Self {
+        self.wait(Duration::from_secs_f32(seconds.max(0.0)))
+    }
+
+    /// Waits until `condition` returns true, checking it once per frame (the first
+    /// time in the frame the previous step finished).
+    pub fn wait_until(
+        self,
+        condition: impl FnMut(EntityView) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.step(Step::WaitUntil(Box::new(condition)))
+    }
+
+    /// Runs `action` once and moves on in the same frame.
+    pub fn run(self, action: impl FnMut(EntityView) + Send + Sync + 'static) -> Self {
+        self.step(Step::Run(Box::new(action)))
+    }
+
+    /// Sets `value` on `target` (deferred, like any set from a system).
+    pub fn set<T>(self, target: impl Into<Entity>, value: T) -> Self
+    where
+        T: ComponentId + DataComponent + ComponentType<Struct> + Clone + Send + Sync,
+    {
+        let target = target.into();
+        self.run(move |entity| {
+            entity.world().entity_from_id(target).set(value.clone());
+        })
+    }
+
+    /// Emits `event` on the sequence's entity, for observers registered with
+    /// `entity.observe`.
+    pub fn emit<T>(self, event: T) -> Self
+    where
+        T: ComponentId + Send + Sync,
+    {
+        self.run(move |entity| entity.emit(&event))
+    }
+
+    /// Calls `update` every frame for `duration` with the progress from 0 to 1,
+    /// ending with exactly 1.
+    pub fn tween(
+        self,
+        duration: Duration,
+        update: impl FnMut(EntityView, f32) + Send + Sync + 'static,
+    ) -> Self {
+        self.tween_with(duration, Easing::Linear, Looping::Once, update)
+    }
+
+    /// Like `tween`, with the progress passed through `easing`. A tween that loops
+    /// or ping-pongs never ends; cancel the sequence to stop it.
+    pub fn tween_with(
+        self,
+        duration: Duration,
+        easing: Easing,
+        looping: Looping,
+        update: impl FnMut(EntityView, f32) + Send + Sync + 'static,
+    ) -> Self {
+        self.step(Step::Tween {
+            duration,
+            elapsed: Duration::ZERO,
+            easing,
+            looping,
+            update: Box::new(update),
+        })
+    }
+
+    /// Runs the branches side by side; the step ends when the last of them does.
+    pub fn parallel(self, branches: impl IntoIterator<Item = Sequence>) -> Self {
+        self.step(Step::Parallel(branches.into_iter().collect()))
+    }
+
+    /// Runs `body` `times` times in a row.
+    pub fn repeat(self, times: u32, body: Sequence) -> Self {
+        self.step(Step::Repeat {
+            times: Some(times),
+            done: 0,
+            body,
+        })
+    }
+
+    /// Runs `body` over and over until the sequence is cancelled. A body that takes
+    /// no time runs once per frame.
+    pub fn repeat_forever(self, body: Sequence) -> Self {
+        self.step(Step::Repeat {
+            times: None,
+            done: 0,
+            body,
+        })
+    }
+
+    /// Stops the entity's Sequence where it is and emits `SequenceCancelled`. Does
+    /// nothing if the entity has none.
+    pub fn cancel(entity: EntityView) {
+        if entity.has(Sequence::id()) {
+            entity.remove(Sequence::id());
+            entity.emit(&SequenceCancelled);
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.cursor >= self.steps.len()
+    }
+
+    fn step(mut self, step: Step) -> Self {
+        self.steps.push(step);
+        self
+    }
+
+    fn reset(&mut self) {
+        self.cursor = 0;
+        for step in &mut self.steps {
+            step.reset();
+        }
+    }
+
+    // Runs steps until one has to wait for a later frame. Returns true once the last
+    // step is done; the time steps used up is taken out of `dt`
+    fn advance(&mut self, entity: EntityView, dt: &mut Duration) -> bool {
+        while let Some(step) = self.steps.get_mut(self.cursor) {
+            if !step.advance(entity, dt) {
+                return false;
+            }
+            self.cursor += 1;
+        }
+        true
+    }
+}
+
+impl Step {
+    fn reset(&mut self) {
+        match self {
+            Step::Wait { elapsed, .. } | Step::Tween { elapsed, .. } => *elapsed = Duration::ZERO,
+            Step::Parallel(branches) => branches.iter_mut().for_each(Sequence::reset),
+            Step::Repeat { done, body, .. } => {
+                *done = 0;
+                body.reset();
+            }
+            Step::WaitUntil(_) | Step::Run(_) => {}
+        }
+    }
+
+    fn advance(&mut self, entity: EntityView, dt: &mut Duration) -> bool {
+        match self {
+            Step::Wait { duration, elapsed } => consume(*duration, elapsed, dt),
+            Step::WaitUntil(condition) => condition(entity),
+            Step::Run(action) => {
+                action(entity);
+                true
+            }
+            Step::Tween {
+                duration,
+                elapsed,
+                easing,
+                looping: Looping::Once,
+                update,
+            } => {
+                let done = consume(*duration, elapsed, dt);
+                let progress = if done {
+                    1.0
+                } else {
+                    elapsed.as_secs_f32() / duration.as_secs_f32()
+                };
+                update(entity, easing.apply(progress));
+                done
+            }
+            Step::Tween {
+                duration,
+                elapsed,
+                easing,
+                looping,
+                update,
+            } => {
+                // Takes the whole frame; kept within one cycle so it doesn't lose precision
+                let period = Duration::from_secs_f32(looping.period(duration.as_secs_f32()));
+                *elapsed += std::mem::take(dt);
+                if !period.is_zero() {
+                    *elapsed =
+                        Duration::from_nanos((elapsed.as_nanos() % period.as_nanos()) as u64);
+                }
+                let progress = looping.phase(elapsed.as_secs_f32(), duration.as_secs_f32());
+                update(entity, easing.apply(progress));
+                false
+            }
+            Step::Parallel(branches) => {
+                // Every branch gets the whole frame; what's left afterwards is what the
+                // slowest one left
+                let mut left = *dt;
+                let mut finished = true;
+                for branch in branches.iter_mut().filter(|branch| !branch.is_finished()) {
+                    let mut branch_dt = *dt;
+                    if branch.advance(entity, &mut branch_dt) {
+                        left = left.min(branch_dt);
+                    } else {
+                        finished = false;
+                    }
+                }
+                *dt = if finished { left } else { Duration::ZERO };
+                finished
+            }
+            Step::Repeat { times, done, body } => loop {
+                if times.is_some_and(|times| *done >= times) {
+                    return true;
+                }
+                let before = *dt;
+                if !body.advance(entity, dt) {
+                    return false;
+                }
+                *done += 1;
+                body.reset();
+                // Endlessly repeating a body that took no time would never return
+                if *dt == before && times.is_none() {
+                    return false;
+                }
+            },
+        }
+    }
+}
+
+// Moves `elapsed` toward `duration` by as much of `dt` as it needs
+fn consume(duration: Duration, elapsed: &mut Duration, dt: &mut Duration) -> bool {
+    let needed = duration.saturating_sub(*elapsed);
+    if *dt >= needed {
+        *elapsed = duration;
+        *dt -= needed;
+        true
+    } else {
+        *elapsed += *dt;
+        *dt = Duration::ZERO;
+        false
+    }
+}
+
+pub fn sequence_system(world: &mut World) {
+    world.component::<SequenceFinished>();
+    world.component::<SequenceCancelled>();
+
+    world
+        .system_named::<(&mut Sequence, &Time)>("Advance Sequences")
+        .kind(flecs::pipeline::OnUpdate)
+        .each_entity(|entity, (sequence, time)| {
+            let mut dt = if sequence.unscaled {
+                time.delta()
+            } else if time.is_paused() {
+                return;
+            } else {
+                time.scaled_delta()
+            };
+
+            if sequence.advance(entity, &mut dt) {
+                entity.remove(Sequence::id());
+                entity.emit(&SequenceFinished);
+            }
+        });
+}
@@ -0,0 +1,704 @@
+//! User settings that survive a restart: graphics options, window behavior, a game's
+//! own audio volumes or difficulty.
+//!
+//! Plugins register their settings singletons with `app.register_settings::<T>(name)`
+//! instead of `register_singleton_default`. All sections live in one RON file, read
+//! when the App is created: a section in the file replaces the Default of its
+//! singleton when it is registered. Changes made after the first frame are written
+//! back once nothing changed for `SettingsConfig::save_delay`, when the app exits, or
+//! right away with `Settings::save` (`settings.save` in the console).
+//!
+//! A section that can't be read, say a field of the wrong type, falls back to its
+//! Default with a warning; the other sections still load. Fields the section doesn't
+//! know are ignored, and fields missing from the file keep their Default when the
+//! type is `#[serde(default)]`. A section that renamed or reshaped fields bumps its
+//! version and registers a migration from the old one.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use flecs_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{App, console::ConsoleCommands, registry::RegisteredComponent};
+
+/// Path of the settings file, used instead of SettingsConfig's. Checked when the App is
+/// created.
+pub const SETTINGS_ENV: &str = "CATALYST_SETTINGS";
+
+pub const SETTINGS_FILE: &str = "settings.ron";
+
+/// Where settings are read from and when they are written. Part of EngineConfig.
+#[derive(Clone, Debug)]
+pub struct SettingsConfig {
+    /// Directory of the game under the platform's config directory, see
+    /// `default_settings_path`.
+    pub app_name: String,
+    /// File used instead of the one in the config directory. CATALYST_SETTINGS wins
+    /// over it.
+    pub path: Option<PathBuf>,
+    /// Off: nothing is read or written, every section keeps its Default. For tools
+    /// and tests that mustn't touch the player's file.
+    pub persist: bool,
+    /// Time without changes before they are written.
+    pub save_delay: Duration,
+}
+
+impl Default for SettingsConfig {
+    fn default() -> Self {
+        Self {
+            app_name: "catalyst".to_string(),
+            path: None,
+            persist: true,
+            save_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+impl SettingsConfig {
+    /// The file these settings use, None when they aren't persisted or there is no
+    /// config directory.
+    pub fn resolve_path(&self) -> Option<PathBuf> {
+        if !self.persist {
+            return None;
+        }
+        if let Some(path) = std::env::var_os(SETTINGS_ENV) {
+            return Some(path.into());
+        }
+        self.path
+            .clone()
+            .or_else(|| default_settings_path(&self.app_name))
+    }
+}
+
+/// `<config dir>/<app_name>/settings.ron`, the config directory being %APPDATA% on
+/// Windows, ~/Library/Application Support on macOS and $XDG_CONFIG_HOME (or ~/.config)
+/// elsewhere. None when the variables it comes from aren't set.
+pub fn default_settings_path(app_name: &str) -> Option<PathBuf> {
+    let home = || std::env::var_os("HOME").map(PathBuf::from);
+    let dir = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library/Application Support"))
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| home().map(|home| home.join(".config")))
+    }?;
+    Some(dir.join(app_name).join(SETTINGS_FILE))
+}
+
+/// Brings the values of a section saved at one version to the next.
+pub type SettingsMigration = fn(&mut Map<String, Value>);
+
+/// Name, version and migrations of a settings section. A plain name is version 0
+/// without migrations:
+///
+/// ```ignore
+/// app.register_settings::<AudioSettings>("audio");
+/// // Version 1 renamed `volume` to `master_volume`
+/// app.register_settings::<AudioSettings>(
+///     SettingsSection::new("audio")
+///         .version(1)
+///         .migration(0, |values| rename_field(values, "volume", "master_volume")),
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct SettingsSection {
+    name: String,
+    version: u32,
+    // By the version they migrate from
+    migrations: BTreeMap<u32, SettingsMigration>,
+}
+
+impl SettingsSection {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            version: 0,
+            migrations: BTreeMap::new(),
+        }
+    }
+
+    pub fn version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Runs `migrate` on sections saved at version `from`, before the migration from
+    /// `from + 1` and so on up to the current version.
+    pub fn migration(mut self, from: u32, migrate: SettingsMigration) -> Self {
+        self.migrations.insert(from, migrate);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl From<&str> for SettingsSection {
+    fn from(name: &str) -> Self {
+        Self::new(name)
+    }
+}
+
+/// Moves the field `from` to `to`, for migrations. Nothing happens when `from` isn't
+/// there; an existing `to` is replaced.
+pub fn rename_field(values: &mut Map<String, Value>, from: &str, to: &str) {
+    if let Some(value) = values.remove(from) {
+        values.insert(to.to_string(), value);
+    }
+}
+
+// One section as it is written to the file
+#[derive(Serialize, Deserialize)]
+struct SectionFile {
+    version: u32,
+    values: Value,
+}
+
+struct Registration {
+    section: SettingsSection,
+    serialize: fn(&World) -> Result<Value, String>,
+    deserialize: fn(&World, Value) -> Result<(), String>,
+    // Values as last written (or at the first frame), and as seen at the last check
+    saved: Option<Value>,
+    seen: Option<Value>,
+}
+
+/// The registered settings sections and the file they persist to. A singleton, created
+/// by the App.
+#[derive(Component, Default)]
+pub struct Settings {
+    path: Option<PathBuf>,
+    save_delay: Duration,
+    // Sections read from the file, by name. The ones no plugin registered are written
+    // back as they were.
+    file: BTreeMap<String, Value>,
+    sections: Vec<Registration>,
+    // When the last change was seen, while it isn't written yet
+    changed_at: Option<Instant>,
+}
+
+impl Settings {
+    /// Reads the file of `config`. A file that isn't valid RON is kept next to it as
+    /// `settings.ron.corrupt` and every section starts from its Default.
+    pub fn load(config: &SettingsConfig) -> Self {
+        let path = config.resolve_path();
+        let mut settings = Self {
+            path,
+            save_delay: config.save_delay,
+            ..Default::default()
+        };
+        let Some(path) = &settings.path else {
+            return settings;
+        };
+
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return settings,
+            Err(e) => {
+                log::warn!("Can't read settings from {}: {}", path.display(), e);
+                return settings;
+            }
+        };
+        match ron::from_str::<BTreeMap<String, Value>>(&text) {
+            Ok(file) => {
+                log::info!("Settings loaded from {}", path.display());
+                settings.file = file;
+            }
+            Err(e) => {
+                let backup = path.with_extension("ron.corrupt");
+                log::warn!(
+                    "Settings file {} is unreadable ({}), using defaults; it is kept as {}",
+                    path.display(),
+                    e,
+                    backup.display()
+                );
+                if let Err(e) = std::fs::rename(path, &backup) {
+                    log::warn!("Can't keep the settings file: {}", e);
+                }
+            }
+        }
+        settings
+    }
+
+    /// The file settings are written to, None when they aren't persisted.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Names of the registered sections, in registration order.
+    pub fn sections(&self) -> impl Iterator<Item = &str> {
+        self.sections
+            .iter()
+            .map(|registration| registration.section.name())
+    }
+
+    /// The current values of section `name`, as JSON.
+    pub fn serialize(&self, world: &World, name: &str) -> Option<Result<Value, String>> {
+        let registration = self.find(name)?;
+        Some((registration.serialize)(world))
+    }
+
+    /// Sets the singleton of section `name` from `values`; fields missing from them
+    /// keep their Default.
+    pub fn deserialize(&self, world: &World, name: &str, values: Value) -> Result<(), String> {
+        let registration = self
+            .find(name)
+            .ok_or_else(|| format!("no settings section '{}'", name))?;
+        (registration.deserialize)(world, values)
+    }
+
+    /// Whether something changed since the settings were last written.
+    pub fn has_unsaved_changes(&self) -> bool {
+        self.changed_at.is_some()
+    }
+
+    /// Writes every section now.
+    pub fn save(&mut self, world: &World) -> Result<(), String> {
+        let Some(path) = self.path.clone() else {
+            return Err("settings aren't persisted".to_string());
+        };
+
+        let mut file = self.file.clone();
+        for registration in &mut self.sections {
+            let values = match (registration.serialize)(world) {
+                Ok(values) => values,
+                Err(e) => {
+                    log::warn!(
+                        "Can't save settings section '{}': {}",
+                        registration.section.name,
+                        e
+                    );
+                    continue;
+                }
+            };
+            let section = SectionFile {
+                version: registration.section.version,
+                values: values.clone(),
+            };
+            let section = serde_json::to_value(section).map_err(|e| e.to_string())?;
+            file.insert(registration.section.name.clone(), section);
+            registration.saved = Some(values.clone());
+            registration.seen = Some(values);
+        }
+
+        let text = ron::ser::to_string_pretty(&file, ron::ser::PrettyConfig::default())
+            .map_err(|e| e.to_string())?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        // Through a temporary file, so a crash mid-write leaves the old settings
+        let temporary = path.with_extension("ron.tmp");
+        std::fs::write(&temporary, text)
+            .and_then(|_| std::fs::rename(&temporary, &path))
+            .map_err(|e| format!("can't write {}: {}", path.display(), e))?;
+
+        self.file = file;
+        self.changed_at = None;
+        log::debug!("Settings saved to {}", path.display());
+        Ok(())
+    }
+
+    fn find(&self, name: &str) -> Option<&Registration> {
+        self.sections
+            .iter()
+            .find(|registration| registration.section.name == name)
+    }
+
+    // The file's values of `section`, migrated to its version. None when the file
+    // doesn't have it or it can't be read.
+    fn read_section(&self, section: &SettingsSection) -> Option<Value> {
+        let stored = self.file.get(&section.name)?;
+        let stored = match serde_json::from_value::<SectionFile>(stored.clone()) {
+            Ok(stored) => stored,
+            Err(e) => {
+                log::warn!(
+                    "Settings section '{}' is malformed ({}), using its defaults",
+                    section.name,
+                    e
+                );
+                return None;
+            }
+        };
+
+        let Value::Object(mut values) = stored.values else {
+            log::warn!(
+                "Settings section '{}' isn't a map of fields, using its defaults",
+                section.name
+            );
+            return None;
+        };
+        if stored.version > section.version {
+            log::warn!(
+                "Settings section '{}' was saved by a newer version ({} > {}), reading it anyway",
+                section.name,
+                stored.version,
+                section.version
+            );
+        }
+        if stored.version < section.version {
+            for (_, migrate) in section.migrations.range(stored.version..section.version) {
+                migrate(&mut values);
+            }
+        }
+        Some(Value::Object(values))
+    }
+
+    fn register<T: RegisteredComponent>(&mut self, section: SettingsSection) -> T {
+        let value = match self.read_section(&section) {
+            Some(values) => serde_json::from_value(values).unwrap_or_else(|e| {
+                log::warn!(
+                    "Settings section '{}' has invalid values ({}), using its defaults",
+                    section.name,
+                    e
+                );
+                T::default()
+            }),
+            None => T::default(),
+        };
+
+        let registration = Registration {
+            section,
+            serialize: serialize_settings::<T>,
+            deserialize: deserialize_settings::<T>,
+            saved: None,
+            seen: None,
+        };
+        match self
+            .sections
+            .iter_mut()
+            .find(|existing| existing.section.name == registration.section.name)
+        {
+            Some(existing) => {
+                log::warn!(
+                    "Settings section '{}' is registered twice, the last one replaces the other",
+                    registration.section.name
+                );
+                *existing = registration;
+            }
+            None => self.sections.push(registration),
+        }
+        value
+    }
+
+    // Once per frame: notes what changed and writes it when it settled
+    fn check(&mut self, world: &World, flush: bool) {
+        if self.path.is_none() {
+            return;
+        }
+
+        let now = Instant::now();
+        let mut dirty = false;
+        for registration in &mut self.sections {
+            let Ok(values) = (registration.serialize)(world) else {
+                continue;
+            };
+            // What the first frame starts with isn't a change: the file's values, or
+            // what the game set up in code
+            let saved = registration.saved.get_or_insert_with(|| values.clone());
+            dirty |= *saved != values;
+            if registration.seen.as_ref() != Some(&values) {
+                registration.seen = Some(values);
+                self.changed_at = Some(now);
+            }
+        }
+
+        if !dirty {
+            self.changed_at = None;
+            return;
+        }
+        let settled = self
+            .changed_at
+            .is_none_or(|changed_at| now - changed_at >= self.save_delay);
+        if settled || flush {
+            if let Err(e) = self.save(world) {
+                log::warn!("Can't save settings: {}", e);
+                // Not again every frame
+                self.changed_at = Some(now);
+            }
+        }
+    }
+}
+
+impl App {
+    /// Registers `T` as a singleton whose value persists in the settings file under
+    /// `section`, starting from the file's values when it has them. Use it instead of
+    /// `register_singleton_default`.
+    pub fn register_settings<T: RegisteredComponent>(
+        &mut self,
+        section: impl Into<SettingsSection>,
+    ) -> &mut Self {
+        let section = section.into();
+        let value = self
+            .world
+            .get::<&mut Settings>(|settings| settings.register::<T>(section));
+        self.register_singleton(value)
+    }
+}
+
+/// Called by App::update after every frame, and with `flush` when it exits.
+pub(crate) fn end_frame(world: &World, flush: bool) {
+    world.get::<&mut Settings>(|settings| settings.check(world, flush));
+}
+
+pub(crate) fn register_settings_commands(commands: &mut ConsoleCommands) {
+    commands.register(
+        "settings.save",
+        "Write the settings file now",
+        |world, _| {
+            world.get::<&mut Settings>(|settings| {
+                settings.save(world)?;
+                Ok(format!(
+                    "Settings saved to {}",
+                    settings.path().map(Path::display).unwrap()
+                ))
+            })
+        },
+    );
+    commands.register(
+        "settings.list",
+        "List the settings sections and where they are saved",
+        |world, _| {
+            world.get::<&Settings>(|settings| {
+                let path = match settings.path() {
+                    Some(path) => path.display().to_string(),
+                    None => "not persisted".to_string(),
+                };
+                let sections: Vec<_> = settings.sections().collect();
+                Ok(format!("{} ({})", sections.join(", "), path))
+            })
+        },
+    );
+}
+
+fn serialize_settings<T: RegisteredComponent>(world: &World) -> Result<Value, String> {
+    world.get::<&T>(|value| serde_json::to_value(value).map_err(|e| e.to_string()))
+}
+
+fn deserialize_settings<T: RegisteredComponent>(
+    world: &World,
+    values: Value,
+) -> Result<(), String> {
+    let value: T = serde_json::from_value(values).map_err(|e| e.to_string())?;
+    world.set(value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AppExit, logging::EngineConfig};
+
+    #[derive(Component, Clone, Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(default)]
+    struct AudioSettings {
+        master_volume: f32,
+        muted: bool,
+    }
+
+    impl Default for AudioSettings {
+        fn default() -> Self {
+            Self {
+                master_volume: 0.8,
+                muted: false,
+            }
+        }
+    }
+
+    #[derive(Component, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+    #[serde(default)]
+    struct GameplaySettings {
+        difficulty: u32,
+        subtitles: bool,
+    }
+
+    // Version 1 renamed `volume` to `master_volume`
+    fn audio_section() -> SettingsSection {
+        SettingsSection::new("audio")
+            .version(1)
+            .migration(0, |values| rename_field(values, "volume", "master_volume"))
+    }
+
+    fn new_app(path: &Path, save_delay: Duration) -> App {
+        let mut app = App::with_config(EngineConfig {
+            settings: SettingsConfig {
+                path: Some(path.to_path_buf()),
+                save_delay,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        app.register_settings::<AudioSettings>(audio_section())
+            .register_settings::<GameplaySettings>("gameplay");
+        app
+    }
+
+    fn audio(app: &App) -> AudioSettings {
+        app.world.get::<&AudioSettings>(|audio| audio.clone())
+    }
+
+    fn gameplay(app: &App) -> GameplaySettings {
+        app.world
+            .get::<&GameplaySettings>(|gameplay| gameplay.clone())
+    }
+
+    // A settings file in its own temporary folder, since tests run in parallel. Returns
+    // the folder, to remove at the end, and the file
+    fn temp_settings(name: &str) -> (PathBuf, PathBuf) {
+        let dir =
+            std::env::temp_dir().join(format!("catalyst_settings_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.ron");
+        (dir, path)
+    }
+
+    #[test]
+    fn changes_are_written_and_loaded_by_the_next_app() {
+        let (dir, path) = temp_settings("saving");
+        let mut app = new_app(&path, Duration::ZERO);
+        // Without a file every section starts from its defaults
+        assert_eq!(audio(&app), AudioSettings::default());
+        assert_eq!(gameplay(&app), GameplaySettings::default());
+
+        // What the first frame starts with isn't written
+        app.world
+            .get::<&mut GameplaySettings>(|gameplay| gameplay.difficulty = 2);
+        app.update();
+        assert!(!path.exists());
+
+        app.world.get::<&mut AudioSettings>(|audio| {
+            audio.master_volume = 0.25;
+            audio.muted = true;
+        });
+        app.update();
+        assert!(path.exists());
+        drop(app);
+
+        let app = new_app(&path, Duration::ZERO);
+        assert_eq!(
+            audio(&app),
+            AudioSettings {
+                master_volume: 0.25,
+                muted: true,
+            }
+        );
+        assert_eq!(gameplay(&app).difficulty, 2);
+        drop(app);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn a_change_is_written_after_its_delay_or_when_the_app_exits() {
+        let (dir, path) = temp_settings("debounced");
+        std::fs::write(
+            &path,
+            r#"{"gameplay": {"version": 0, "values": {"difficulty": 1}}}"#,
+        )
+        .unwrap();
+        let mut app = new_app(&path, Duration::from_secs(3600));
+        app.update();
+        app.world
+            .get::<&mut GameplaySettings>(|gameplay| gameplay.subtitles = true);
+        app.update();
+        app.update();
+        assert!(
+            app.world
+                .get::<&Settings>(|settings| settings.has_unsaved_changes())
+        );
+        assert!(
+            !std::fs::read_to_string(&path)
+                .unwrap()
+                .contains("subtitles\": true")
+        );
+
+        app.world.get::<&mut AppExit>(|exit| exit.request(0));
+        app.update();
+        drop(app);
+        assert!(gameplay(&new_app(&path, Duration::ZERO)).subtitles);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn unknown_fields_are_ignored_and_unknown_sections_written_back() {
+        let (dir, path) = temp_settings("unknown");
+        std::fs::write(
+            &path,
+            r#"{
+                "audio": {"version": 1, "values": {"master_volume": 0.5, "reverb": 2.0}},
+                "legacy": {"version": 3, "values": {"bloom": true}},
+            }"#,
+        )
+        .unwrap();
+        let app = new_app(&path, Duration::ZERO);
+        assert_eq!(audio(&app).master_volume, 0.5);
+        // A section missing from the file keeps its defaults
+        assert_eq!(gameplay(&app), GameplaySettings::default());
+
+        app.world
+            .get::<&mut Settings>(|settings| settings.save(&app.world))
+            .unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.contains("legacy") && text.contains("bloom"));
+        drop(app);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn a_field_renamed_since_the_file_was_saved_is_migrated() {
+        let (dir, path) = temp_settings("migration");
+        std::fs::write(
+            &path,
+            r#"{"audio": {"version": 0, "values": {"volume": 0.125, "muted": true}}}"#,
+        )
+        .unwrap();
+        let app = new_app(&path, Duration::ZERO);
+        assert_eq!(
+            audio(&app),
+            AudioSettings {
+                master_volume: 0.125,
+                muted: true,
+            }
+        );
+        drop(app);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn a_bad_value_only_costs_its_own_section() {
+        let (dir, path) = temp_settings("bad_value");
+        std::fs::write(
+            &path,
+            r#"{
+                "audio": {"version": 1, "values": {"master_volume": "loud"}},
+                "gameplay": {"version": 0, "values": {"difficulty": 3}},
+            }"#,
+        )
+        .unwrap();
+        let app = new_app(&path, Duration::ZERO);
+        assert_eq!(audio(&app), AudioSettings::default());
+        assert_eq!(gameplay(&app).difficulty, 3);
+        drop(app);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn an_unreadable_file_leaves_the_defaults_and_is_kept_aside() {
+        let (dir, path) = temp_settings("unreadable");
+        std::fs::write(&path, "{ not ron").unwrap();
+        let app = new_app(&path, Duration::ZERO);
+        assert_eq!(audio(&app), AudioSettings::default());
+        assert_eq!(gameplay(&app), GameplaySettings::default());
+        assert!(dir.join("settings.ron.corrupt").exists());
+        drop(app);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
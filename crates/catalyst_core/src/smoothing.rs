@@ -0,0 +1,460 @@
+use glam::{Quat, Vec3};
+
+// Smooth times shorter than this are clamped, so a zero doesn't divide by zero
+const MIN_SMOOTH_TIME: f32 = 1e-4;
+
+/// Share of the remaining distance covered in `dt` seconds when decaying
+/// exponentially at `rate` per second: `1 - exp(-rate * dt)`. Blending by it gives
+/// the same result for one long frame as for several short ones, which
+/// `lerp(a, b, rate * dt)` doesn't.
+pub fn decay_blend(rate: f32, dt: f32) -> f32 {
+    1.0 - (-rate * dt).exp()
+}
+
+/// The decay rate that halves the remaining distance every `half_life` seconds.
+pub fn rate_from_half_life(half_life: f32) -> f32 {
+    std::f32::consts::LN_2 / half_life.max(MIN_SMOOTH_TIME)
+}
+
+/// Moves `current` towards `target`, leaving `exp(-rate * dt)` of the distance.
+/// Frame rate independent; quaternions turn along the shortest arc.
+pub fn exp_decay<T: Smooth>(current: T, target: T, rate: f32, dt: f32) -> T {
+    current.blend(target, decay_blend(rate, dt))
+}
+
+/// Critically damped spring from `current` towards `target`, with the semantics of
+/// Unity's SmoothDamp: `velocity` is carried between calls, `smooth_time` is roughly
+/// the time to reach the target, speed is capped at `max_speed` (`f32::INFINITY` for
+/// none) and the value stops on the target at the end of a step that would pass it.
+/// Unlike Unity's, the spring is solved exactly, so ten steps of 0.1 s land where a
+/// thousand of 1 ms do, as long as neither the speed cap nor that stop kicks in.
+/// Rotations go the shorter way around.
+pub fn smooth_damp<T: Smooth>(
+    current: T,
+    target: T,
+    velocity: &mut T::Velocity,
+    smooth_time: f32,
+    max_speed: f32,
+    dt: f32,
+) -> T {
+    T::smooth_damp(current, target, velocity, smooth_time, max_speed, dt)
+}
+
+/// Values `exp_decay` and `smooth_damp` work on.
+pub trait Smooth: Copy {
+    /// Rate of change kept by `smooth_damp`. For rotations, an angular velocity in
+    /// radians per second.
+    type Velocity: Copy + Default;
+
+    /// Moves `blend` (0 to 1) of the way to `target`.
+    fn blend(self, target: Self, blend: f32) -> Self;
+
+    fn smooth_damp(
+        current: Self,
+        target: Self,
+        velocity: &mut Self::Velocity,
+        smooth_time: f32,
+        max_speed: f32,
+        dt: f32,
+    ) -> Self;
+}
+
+impl Smooth for f32 {
+    type Velocity = f32;
+
+    fn blend(self, target: Self, blend: f32) -> Self {
+        self + (target - self) * blend
+    }
+
+    fn smooth_damp(
+        current: f32,
+        target: f32,
+        velocity: &mut f32,
+        smooth_time: f32,
+        max_speed: f32,
+        dt: f32,
+    ) -> f32 {
+        if dt <= 0.0 {
+            return current;
+        }
+        let smooth_time = smooth_time.max(MIN_SMOOTH_TIME);
+        let omega = 2.0 / smooth_time;
+        let max_change = max_speed * smooth_time;
+        let change = (current - target).clamp(-max_change, max_change);
+        let goal = current - change;
+
+        // Closed form of x'' = -omega^2 x - 2 omega x' over dt
+        let decay = (-omega * dt).exp();
+        let temp = (*velocity + omega * change) * dt;
+        *velocity = (*velocity - omega * temp) * decay;
+        let output = goal + (change + temp) * decay;
+
+        if (target - current) * (output - target) > 0.0 {
+            *velocity = 0.0;
+            return target;
+        }
+        output
+    }
+}
+
+impl Smooth for Vec3 {
+    type Velocity = Vec3;
+
+    fn blend(self, target: Self, blend: f32) -> Self {
+        self.lerp(target, blend)
+    }
+
+    fn smooth_damp(
+        current: Vec3,
+        target: Vec3,
+        velocity: &mut Vec3,
+        smooth_time: f32,
+        max_speed: f32,
+        dt: f32,
+    ) -> Vec3 {
+        if dt <= 0.0 {
+            return current;
+        }
+        let smooth_time = smooth_time.max(MIN_SMOOTH_TIME);
+        let omega = 2.0 / smooth_time;
+        let change = (current - target).clamp_length_max(max_speed * smooth_time);
+        let goal = current - change;
+
+        let decay = (-omega * dt).exp();
+        let temp = (*velocity + omega * change) * dt;
+        *velocity = (*velocity - omega * temp) * decay;
+        let output = goal + (change + temp) * decay;
+
+        if (target - current).dot(output - target) > 0.0 {
+            *velocity = Vec3::ZERO;
+            return target;
+        }
+        output
+    }
+}
+
+// Rotations are smoothed as the rotation vector from the target to the current
+// rotation, which the spring takes to zero; the velocity is that vector's rate of
+// change, in world space.
+impl Smooth for Quat {
+    type Velocity = Vec3;
+
+    fn blend(self, target: Self, blend: f32) -> Self {
+        self.slerp(target, blend)
+    }
+
+    fn smooth_damp(
+        current: Quat,
+        target: Quat,
+        velocity: &mut Vec3,
+        smooth_time: f32,
+        max_speed: f32,
+        dt: f32,
+    ) -> Quat {
+        let mut error = current * target.inverse();
+        // The shorter way around
+        if error.w < 0.0 {
+            error = -error;
+        }
+        let error = Vec3::smooth_damp(
+            error.to_scaled_axis(),
+            Vec3::ZERO,
+            velocity,
+            smooth_time,
+            max_speed,
+            dt,
+        );
+        (Quat::from_scaled_axis(error) * target).normalize()
+    }
+}
+
+/// A value following its target with `smooth_damp`, e.g. a camera distance or a UI
+/// element's position: set `target` whenever it changes and call `update` every frame.
+#[derive(Clone, Copy, Debug)]
+pub struct SmoothedValue<T: Smooth> {
+    pub target: T,
+    /// Roughly the time to reach the target, in seconds.
+    pub smooth_time: f32,
+    /// Speed cap, `f32::INFINITY` for none. Radians per second for rotations.
+    pub max_speed: f32,
+    current: T,
+    velocity: T::Velocity,
+}
+
+impl<T: Smooth> SmoothedValue<T> {
+    /// Starts at rest on `value`.
+    pub fn new(value: T, smooth_time: f32) -> Self {
+        Self {
+            target: value,
+            smooth_time,
+            max_speed: f32::INFINITY,
+            current: value,
+            velocity: T::Velocity::default(),
+        }
+    }
+
+    pub fn with_max_speed(mut self, max_speed: f32) -> Self {
+        self.max_speed = max_speed;
+        self
+    }
+
+    pub fn value(&self) -> T {
+        self.current
+    }
+
+    pub fn velocity(&self) -> T::Velocity {
+        self.velocity
+    }
+
+    /// Jumps to `value` and stops there, e.g. after a teleport.
+    pub fn snap(&mut self, value: T) {
+        self.target = value;
+        self.current = value;
+        self.velocity = T::Velocity::default();
+    }
+
+    /// Advances by `dt` seconds and returns the new value.
+    pub fn update(&mut self, dt: f32) -> T {
+        self.current = T::smooth_damp(
+            self.current,
+            self.target,
+            &mut self.velocity,
+            self.smooth_time,
+            self.max_speed,
+            dt,
+        );
+        self.current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::*;
+
+    const CASES: usize = 300;
+    // Relative to the distance covered
+    const EPSILON: f32 = 1e-4;
+
+    // Small deterministic generator, so a failure can be reproduced
+    struct Cases(u64);
+
+    impl Cases {
+        fn next(&mut self) -> f32 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.0 >> 40) as f32 / (1u64 << 24) as f32
+        }
+
+        fn range(&mut self, min: f32, max: f32) -> f32 {
+            min + (max - min) * self.next()
+        }
+
+        fn vec3(&mut self, extent: f32) -> Vec3 {
+            Vec3::new(
+                self.range(-extent, extent),
+                self.range(-extent, extent),
+                self.range(-extent, extent),
+            )
+        }
+
+        fn rotation(&mut self, max_angle: f32) -> Quat {
+            let axis = self.vec3(1.0).try_normalize().unwrap_or(Vec3::Y);
+            Quat::from_axis_angle(axis, self.range(-max_angle, max_angle))
+        }
+    }
+
+    // Angle between two rotations, accurate for small angles too
+    fn angle(a: Quat, b: Quat) -> f32 {
+        let difference = a * b.inverse();
+        2.0 * difference.xyz().length().atan2(difference.w.abs())
+    }
+
+    // Steps one second in `steps` frames
+    fn second<T>(steps: u32, mut step: impl FnMut(f32) -> T) -> T {
+        let dt = 1.0 / steps as f32;
+        let mut value = step(dt);
+        for _ in 1..steps {
+            value = step(dt);
+        }
+        value
+    }
+
+    // Starting velocities point away from the target in these tests: the path never
+    // passes it, where smooth_damp stops on the target at the end of the frame it
+    // passed it
+
+    #[test]
+    fn smooth_damp_f32_is_frame_rate_independent() {
+        let mut cases = Cases(0x5eed);
+        for case in 0..CASES {
+            let smooth_time = cases.range(0.05, 1.0);
+            let (start, target) = (cases.range(-50.0, 50.0), cases.range(-50.0, 50.0));
+            let velocity = cases.range(-20.0, 20.0).abs() * (start - target).signum();
+            let run = |steps| {
+                let (mut value, mut v) = (start, velocity);
+                second(steps, |dt| {
+                    value = smooth_damp(value, target, &mut v, smooth_time, f32::INFINITY, dt);
+                    value
+                })
+            };
+            let scale = (start - target).abs().max(1.0);
+            assert!(
+                (run(10) - run(1000)).abs() <= EPSILON * scale,
+                "case {}",
+                case
+            );
+        }
+    }
+
+    #[test]
+    fn smooth_damp_vec3_is_frame_rate_independent() {
+        let mut cases = Cases(0x5eed);
+        for case in 0..CASES {
+            let smooth_time = cases.range(0.05, 1.0);
+            let (start, target, velocity) = (cases.vec3(50.0), cases.vec3(50.0), cases.vec3(20.0));
+            let velocity = velocity * velocity.dot(start - target).signum();
+            let run = |steps| {
+                let (mut value, mut v) = (start, velocity);
+                second(steps, |dt| {
+                    value = smooth_damp(value, target, &mut v, smooth_time, f32::INFINITY, dt);
+                    value
+                })
+            };
+            let scale = start.distance(target).max(1.0);
+            assert!(
+                run(10).distance(run(1000)) <= EPSILON * scale,
+                "case {}",
+                case
+            );
+        }
+    }
+
+    #[test]
+    fn smooth_damp_quat_is_frame_rate_independent() {
+        let mut cases = Cases(0x5eed);
+        for case in 0..CASES {
+            let smooth_time = cases.range(0.05, 1.0);
+            // Within 2 radians, so the velocity can't carry the rotation past half a
+            // turn, where the shorter way around flips
+            let start = cases.rotation(3.0);
+            let target = cases.rotation(2.0) * start;
+            let velocity = cases.vec3(2.0);
+            let error = (start * target.inverse()).to_scaled_axis();
+            let velocity = velocity * velocity.dot(error).signum();
+            let run = |steps| {
+                let (mut value, mut v) = (start, velocity);
+                second(steps, |dt| {
+                    value = smooth_damp(value, target, &mut v, smooth_time, f32::INFINITY, dt);
+                    value
+                })
+            };
+            assert!(angle(run(10), run(1000)) <= EPSILON * PI, "case {}", case);
+        }
+    }
+
+    #[test]
+    fn exp_decay_is_frame_rate_independent() {
+        let mut cases = Cases(0x5eed);
+        for case in 0..CASES {
+            let rate = cases.range(0.1, 20.0);
+            let start = cases.rotation(3.0);
+            let target = cases.rotation(2.0) * start;
+            let run = |steps| {
+                let (mut value, mut rotation) = (start.x, start);
+                second(steps, |dt| {
+                    value = exp_decay(value, target.x, rate, dt);
+                    rotation = exp_decay(rotation, target, rate, dt);
+                    (value, rotation)
+                })
+            };
+            let ((value_a, rotation_a), (value_b, rotation_b)) = (run(10), run(1000));
+            assert!((value_a - value_b).abs() <= EPSILON, "case {}", case);
+            assert!(
+                angle(rotation_a, rotation_b) <= EPSILON * PI,
+                "case {}",
+                case
+            );
+        }
+    }
+
+    #[test]
+    fn smoothed_value_is_frame_rate_independent() {
+        let mut cases = Cases(0x5eed);
+        for case in 0..CASES {
+            let smooth_time = cases.range(0.05, 1.0);
+            let target = cases.vec3(50.0);
+            let run = |steps| {
+                let mut smoothed = SmoothedValue::new(Vec3::ZERO, smooth_time);
+                smoothed.target = target;
+                second(steps, |dt| smoothed.update(dt))
+            };
+            let scale = target.length().max(1.0);
+            assert!(
+                run(10).distance(run(1000)) <= EPSILON * scale,
+                "case {}",
+                case
+            );
+        }
+    }
+
+    #[test]
+    fn smooth_damp_settles_without_overshooting() {
+        let mut smoothed = SmoothedValue::new(0.0f32, 0.25);
+        smoothed.target = 10.0;
+        for _ in 0..60 {
+            assert!(smoothed.update(1.0 / 60.0) <= 10.0);
+        }
+        assert!((smoothed.value() - 10.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn smooth_damp_stops_on_the_target_when_it_would_pass_it() {
+        let mut velocity = 100.0;
+        let mut value = 0.0f32;
+        for _ in 0..120 {
+            value = smooth_damp(value, 1.0, &mut velocity, 0.5, f32::INFINITY, 1.0 / 60.0);
+            assert!(value <= 1.0);
+        }
+        assert_eq!(value, 1.0);
+        assert_eq!(velocity, 0.0);
+    }
+
+    #[test]
+    fn max_speed_caps_how_fast_smooth_damp_moves() {
+        let mut smoothed = SmoothedValue::new(Vec3::ZERO, 0.1).with_max_speed(2.0);
+        smoothed.target = Vec3::new(100.0, 0.0, 0.0);
+        let mut previous = smoothed.value();
+        for _ in 0..60 {
+            let value = smoothed.update(1.0 / 60.0);
+            assert!(value.distance(previous) * 60.0 <= 2.0 + 1e-3);
+            previous = value;
+        }
+        assert!(smoothed.value().x > 1.5);
+    }
+
+    #[test]
+    fn exp_decay_halves_the_distance_every_half_life() {
+        let rotation = exp_decay(
+            Quat::IDENTITY,
+            Quat::from_rotation_y(2.0),
+            rate_from_half_life(0.5),
+            0.5,
+        );
+        assert!((angle(rotation, Quat::IDENTITY) - 1.0).abs() < 1e-4);
+        assert!((exp_decay(0.0, 8.0, rate_from_half_life(2.0), 4.0) - 6.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn snap_jumps_to_the_value_and_stops() {
+        let mut smoothed = SmoothedValue::new(Quat::IDENTITY, 0.2);
+        smoothed.target = Quat::from_rotation_x(1.0);
+        smoothed.update(0.1);
+        smoothed.snap(Quat::from_rotation_z(0.5));
+        assert_eq!(smoothed.value(), Quat::from_rotation_z(0.5));
+        assert_eq!(smoothed.velocity(), Vec3::ZERO);
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+
+use flecs_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Identity of an entity that is the same in every session and on every machine, for
+/// networking and save games: runtime Entity ids depend on spawn order.
+///
+/// Entities spawned from glTF scenes and scene files get one derived from the scene, the
+/// node and how many times that scene was spawned before (`StableId::for_scene_node`), so
+/// peers spawning the same scenes in the same order agree on them. Gameplay entities
+/// spawned at runtime get one by setting it. The StableIdRegistry maps ids to entities
+/// and back; despawning the entity (or removing the component) frees its id.
+#[derive(
+    Component, Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct StableId(pub u64);
+
+impl StableId {
+    /// Id of node `node` in the `instance`th spawn of the scene loaded from `source`:
+    /// an FNV-1a hash of the three, which doesn't change between builds or platforms.
+    pub fn for_scene_node(source: &str, instance: u32, node: usize) -> Self {
+        let mut hash = Fnv1a::default();
+        hash.write(source.as_bytes());
+        hash.write(&instance.to_le_bytes());
+        hash.write(&(node as u64).to_le_bytes());
+        Self(hash.0)
+    }
+}
+
+/// A reference from a component to another entity that survives saving: the target's
+/// StableId rather than its Entity. Use it (usually as `Option<StableRef>`) in
+/// registered components that point at other entities, e.g. the door a trigger opens.
+///
+/// When a scene file is loaded, references between entities of the same file are
+/// remapped to the new instance's ids, so every instance points at its own entities.
+/// References to entities outside the file are kept as they are.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct StableRef {
+    // The name marks references in saved JSON, see remap_stable_refs
+    #[serde(rename = "stable_ref")]
+    pub id: StableId,
+}
+
+impl StableRef {
+    pub fn new(id: StableId) -> Self {
+        Self { id }
+    }
+
+    /// A reference to `entity`; None when it has no StableId.
+    pub fn to(entity: &EntityView) -> Option<Self> {
+        stable_id_of(entity).map(Self::new)
+    }
+
+    /// The entity currently holding the id, if any.
+    pub fn resolve<'a>(&self, world: &'a World) -> Option<EntityView<'a>> {
+        entity_by_stable_id(world, self.id)
+    }
+}
+
+/// Emitted on an entity that was given a StableId already held by another entity.
+/// The id stays with `owner`, the entity keeps its component but can't be found by it.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct StableIdCollision {
+    pub id: StableId,
+    pub owner: Entity,
+}
+
+/// Singleton mapping every StableId in the world to its entity and back, kept up to
+/// date by observers on StableId.
+#[derive(Component, Default)]
+pub struct StableIdRegistry {
+    entities: HashMap<StableId, Entity>,
+    ids: HashMap<Entity, StableId>,
+    // Spawns so far per scene source, the `instance` of StableId::for_scene_node
+    instances: HashMap<String, u32>,
+}
+
+impl StableIdRegistry {
+    pub fn entity(&self, id: StableId) -> Option<Entity> {
+        self.entities.get(&id).copied()
+    }
+
+    pub fn stable_id(&self, entity: Entity) -> Option<StableId> {
+        self.ids.get(&entity).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Counts a spawn of the scene loaded from `source` and returns its instance
+    /// number, starting at 0.
+    pub fn next_instance(&mut self, source: &str) -> u32 {
+        let count = self.instances.entry(source.to_owned()).or_default();
+        let instance = *count;
+        *count += 1;
+        instance
+    }
+
+    // Err with the current owner when the id is taken by another entity
+    fn insert(&mut self, entity: Entity, id: StableId) -> Result<(), Entity> {
+        match self.entities.get(&id) {
+            Some(&owner) if owner != entity => return Err(owner),
+            _ => {}
+        }
+        // Set again with a new id: the old one is free again
+        if let Some(previous) = self.ids.insert(entity, id) {
+            if previous != id {
+                self.entities.remove(&previous);
+            }
+        }
+        self.entities.insert(id, entity);
+        Ok(())
+    }
+
+    fn remove(&mut self, entity: Entity) {
+        if let Some(id) = self.ids.remove(&entity) {
+            self.entities.remove(&id);
+        }
+    }
+}
+
+/// The entity holding `id`, if any.
+pub fn entity_by_stable_id(world: &World, id: StableId) -> Option<EntityView<'_>> {
+    let entity = world.get::<&StableIdRegistry>(|registry| registry.entity(id))?;
+    Some(world.entity_from_id(entity))
+}
+
+pub fn stable_id_of(entity: &EntityView) -> Option<StableId> {
+    entity.try_get::<&StableId>(|id| *id)
+}
+
+/// Rewrites every StableRef in `value` (a serialized component) whose id is a key of
+/// `remap`. Used by scene loading to point references at the new instance.
+pub fn remap_stable_refs(value: &mut Value, remap: &HashMap<StableId, StableId>) {
+    match value {
+        Value::Object(fields) => {
+            if fields.len() == 1 {
+                if let Some(id) = fields.get_mut("stable_ref") {
+                    if let Some(new_id) = id.as_u64().and_then(|old| remap.get(&StableId(old))) {
+                        *id = Value::from(new_id.0);
+                    }
+                    return;
+                }
+            }
+            for field in fields.values_mut() {
+                remap_stable_refs(field, remap);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                remap_stable_refs(item, remap);
+            }
+        }
+        _ => {}
+    }
+}
+
+pub fn stable_id_system(world: &mut World) {
+    world
+        .component::<StableIdRegistry>()
+        .add_trait::<flecs::Singleton>();
+    world.set(StableIdRegistry::default());
+    world.component::<StableIdCollision>();
+
+    world
+        .observer::<flecs::OnSet, (&StableId, &mut StableIdRegistry)>()
+        .term_at(1)
+        .filter()
+        .each_entity(|entity, (id, registry)| {
+            if let Err(owner) = registry.insert(entity.id(), *id) {
+                log::error!(
+                    "StableId {} of '{}' is already held by {}",
+                    id.0,
+                    entity.name(),
+                    owner
+                );
+                // Not in the registry under its old id either
+                registry.remove(entity.id());
+                entity.emit(&StableIdCollision { id: *id, owner });
+            }
+        });
+
+    world
+        .observer::<flecs::OnRemove, (&StableId, &mut StableIdRegistry)>()
+        .term_at(1)
+        .filter()
+        .each_entity(|entity, (_, registry)| {
+            registry.remove(entity.id());
+        });
+}
+
+#[derive(Clone, Copy)]
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        App,
+        scene_file::{load_scene, save_scene},
+    };
+
+    #[derive(Component, Clone, Default, Serialize, Deserialize)]
+    #[serde(default)]
+    struct Door {
+        open: bool,
+    }
+
+    #[derive(Component, Clone, Default, Serialize, Deserialize)]
+    #[serde(default)]
+    struct Trigger {
+        opens: Option<StableRef>,
+    }
+
+    #[test]
+    fn each_scene_instance_references_its_own_entities() {
+        let mut app = App::new();
+        app.register_component::<Door>()
+            .register_component::<Trigger>();
+        let world = &app.world;
+
+        // The authored level; it stays alive, so a reference that wasn't remapped
+        // resolves to this door
+        let door = world
+            .entity_named("Door")
+            .set(Door::default())
+            .set(StableId(1));
+        world
+            .entity_named("Trigger")
+            .set(Trigger {
+                opens: StableRef::to(&door),
+            })
+            .set(StableId(2));
+
+        let scene = save_scene(world);
+        let mut instance_ids = Vec::new();
+        for _ in 0..2 {
+            let root = load_scene(world, &scene, "level.json");
+            let mut opens = None;
+            root.each_child(|child| {
+                instance_ids.extend(stable_id_of(&child));
+                if let Some(Some(target)) = child.try_get::<&Trigger>(|trigger| trigger.opens) {
+                    opens = Some(target);
+                }
+            });
+
+            let door = opens
+                .and_then(|target| target.resolve(world))
+                .expect("the trigger's reference doesn't resolve");
+            assert_eq!(door.parent().map(|parent| parent.id()), Some(root.id()));
+        }
+
+        // The two instances don't share any StableId
+        instance_ids.sort();
+        instance_ids.dedup();
+        assert_eq!(instance_ids.len(), 4);
+    }
+}
@@ -0,0 +1,515 @@
+//! Frame-to-frame diffs of component values, for finding out what changed an entity
+//! when nothing should have.
+//!
+//! `diff.start` snapshots the tracked components of the matching entities at the end
+//! of every frame and reports each value that differs from the previous snapshot.
+//! In fine-grained mode a snapshot is also taken at every phase boundary, so each
+//! change is reported with the phase it happened in. Values are read through the
+//! component registry (as JSON), which makes any registered component trackable.
+//!
+//! Snapshotting walks every matching entity several times a frame, so nothing runs
+//! until a session is started.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use flecs_ecs::prelude::*;
+use serde_json::{Value, json};
+
+use crate::{
+    console::ConsoleCommands,
+    registry::{ComponentRegistration, ComponentRegistry},
+    transform::GlobalTransform,
+};
+
+/// Tracked when `diff.start` isn't given a component list.
+pub const DEFAULT_COMPONENTS: &[&str] = &["Transform", "GlobalTransform"];
+
+/// Changes printed to the console per snapshot; the file gets all of them.
+const CONSOLE_LINES: usize = 50;
+
+/// Name of the stretch between the end of a frame and its first phase: fixed physics
+/// steps and window events.
+const BETWEEN_FRAMES: &str = "Between Frames";
+
+// Lets the phase markers skip the singleton lookup while no session is running
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static FINE_GRAINED: AtomicBool = AtomicBool::new(false);
+
+/// Which entities a session looks at.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EntityFilter {
+    All,
+    Id(u64),
+    /// Entity name, with `*` matching any run of characters.
+    Name(String),
+}
+
+impl EntityFilter {
+    /// "*" or "all", "#<id>", or a name pattern.
+    pub fn parse(text: &str) -> Self {
+        match text {
+            "*" | "all" => Self::All,
+            _ => match text.strip_prefix('#').and_then(|id| id.parse().ok()) {
+                Some(id) => Self::Id(id),
+                None => Self::Name(text.to_string()),
+            },
+        }
+    }
+
+    pub fn matches(&self, entity: &EntityView) -> bool {
+        match self {
+            Self::All => true,
+            Self::Id(id) => *entity.id() == *id,
+            Self::Name(pattern) => glob_match(pattern, &entity.name()),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct StateDiffSettings {
+    pub filter: EntityFilter,
+    /// Component names: registered components, plus GlobalTransform.
+    pub components: Vec<String>,
+    /// Snapshot at every phase boundary instead of once per frame.
+    pub fine_grained: bool,
+    /// Numbers closer than this count as unchanged.
+    pub epsilon: f64,
+    /// Every change is also appended here.
+    pub file: Option<String>,
+}
+
+impl Default for StateDiffSettings {
+    fn default() -> Self {
+        Self {
+            filter: EntityFilter::All,
+            components: DEFAULT_COMPONENTS.iter().map(|name| name.to_string()).collect(),
+            fine_grained: false,
+            epsilon: 1e-5,
+            file: None,
+        }
+    }
+}
+
+/// One reported difference.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValueChange {
+    pub frame: u64,
+    /// Phase the change happened in, or "Frame" when not fine-grained.
+    pub phase: &'static str,
+    pub entity: String,
+    /// Component and field, e.g. "Transform.translation[1]".
+    pub path: String,
+    /// None when the component was added or removed.
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+impl std::fmt::Display for ValueChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[frame {} | {}] {} {}: ", self.frame, self.phase, self.entity, self.path)?;
+        match (&self.before, &self.after) {
+            (Some(before), Some(after)) => write!(f, "{} -> {}", before, after),
+            (None, Some(after)) => write!(f, "added {}", after),
+            (Some(before), None) => write!(f, "removed (was {})", before),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Reader {
+    Registered(ComponentRegistration),
+    // Not a registered component (it is derived, never saved), read directly
+    GlobalTransform,
+}
+
+#[derive(Clone)]
+struct Tracker {
+    name: &'static str,
+    id: Entity,
+    reader: Reader,
+}
+
+impl Tracker {
+    fn read(&self, entity: &EntityView) -> Option<Value> {
+        match &self.reader {
+            Reader::Registered(registration) => registration.serialize(entity)?.ok(),
+            Reader::GlobalTransform => entity.try_get::<&GlobalTransform>(|global| {
+                json!({
+                    "x_axis": global.0.x_axis.to_array(),
+                    "y_axis": global.0.y_axis.to_array(),
+                    "z_axis": global.0.z_axis.to_array(),
+                    "w_axis": global.0.w_axis.to_array(),
+                })
+            }),
+        }
+    }
+}
+
+type Snapshot = BTreeMap<(u64, &'static str), Value>;
+
+struct Session {
+    settings: StateDiffSettings,
+    trackers: Vec<Tracker>,
+    file: Option<BufWriter<File>>,
+    /// None until the first snapshot, which only sets the baseline.
+    previous: Option<Snapshot>,
+    // Phase entered at the last boundary, the one the next diff is attributed to
+    phase: &'static str,
+    frame: u64,
+    changes: u64,
+}
+
+/// The running diff session, if any. Started and stopped with the `diff.*` commands.
+#[derive(Component, Default)]
+pub struct StateDiff {
+    session: Option<Session>,
+}
+
+impl StateDiff {
+    pub fn is_running(&self) -> bool {
+        self.session.is_some()
+    }
+
+    /// Starts a session, replacing a running one. Fails for component names that
+    /// aren't registered or a file that can't be created.
+    pub fn start(&mut self, world: &World, settings: StateDiffSettings) -> Result<(), String> {
+        let trackers = world.get::<&ComponentRegistry>(|registry| {
+            settings
+                .components
+                .iter()
+                .map(|name| tracker(world, registry, name))
+                .collect::<Result<Vec<_>, _>>()
+        })?;
+
+        let file = match &settings.file {
+            Some(path) => Some(BufWriter::new(
+                File::create(path).map_err(|error| format!("Can't create {}: {}", path, error))?,
+            )),
+            None => None,
+        };
+
+        FINE_GRAINED.store(settings.fine_grained, Ordering::Relaxed);
+        ACTIVE.store(true, Ordering::Relaxed);
+        self.session = Some(Session {
+            settings,
+            trackers,
+            file,
+            previous: None,
+            phase: BETWEEN_FRAMES,
+            frame: 0,
+            changes: 0,
+        });
+        Ok(())
+    }
+
+    /// Ends the session and returns how many changes it reported.
+    pub fn stop(&mut self) -> Option<u64> {
+        ACTIVE.store(false, Ordering::Relaxed);
+        let mut session = self.session.take()?;
+        if let Some(file) = &mut session.file {
+            let _ = file.flush();
+        }
+        Some(session.changes)
+    }
+
+    // Diffs the state against the last snapshot, attributing the changes to the phase
+    // that just ended, and enters `next_phase`
+    fn boundary(&mut self, world: &World, next_phase: &'static str) {
+        let Some(session) = &mut self.session else {
+            return;
+        };
+
+        let current = snapshot(world, &session.settings.filter, &session.trackers);
+        if let Some(previous) = &session.previous {
+            let phase = if session.settings.fine_grained {
+                session.phase
+            } else {
+                "Frame"
+            };
+            let changes = diff_snapshots(
+                world,
+                previous,
+                &current,
+                session.settings.epsilon,
+                session.frame,
+                phase,
+            );
+            session.report(&changes);
+        }
+        session.previous = Some(current);
+        session.phase = next_phase;
+    }
+}
+
+impl Session {
+    fn report(&mut self, changes: &[ValueChange]) {
+        self.changes += changes.len() as u64;
+        for change in changes.iter().take(CONSOLE_LINES) {
+            log::info!(target: "state_diff", "{}", change);
+        }
+        if changes.len() > CONSOLE_LINES {
+            log::info!(
+                target: "state_diff",
+                "... and {} more changes this frame",
+                changes.len() - CONSOLE_LINES
+            );
+        }
+
+        if let Some(file) = &mut self.file {
+            for change in changes {
+                if let Err(error) = writeln!(file, "{}", change) {
+                    log::error!("State diff file: {}, no longer writing it", error);
+                    self.file = None;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Called by the phase markers at the start of every phase. Only does anything in a
+/// fine-grained session.
+pub fn phase_boundary(world: &World, phase: &'static str) {
+    if !FINE_GRAINED.load(Ordering::Relaxed) || !ACTIVE.load(Ordering::Relaxed) {
+        return;
+    }
+    world.get::<&mut StateDiff>(|diff| diff.boundary(world, phase));
+}
+
+/// Called by App::update after the frame's systems ran.
+pub fn end_frame(world: &World) {
+    if !ACTIVE.load(Ordering::Relaxed) {
+        return;
+    }
+    world.get::<&mut StateDiff>(|diff| {
+        diff.boundary(world, BETWEEN_FRAMES);
+        if let Some(session) = &mut diff.session {
+            session.frame += 1;
+        }
+    });
+}
+
+fn tracker(world: &World, registry: &ComponentRegistry, name: &str) -> Result<Tracker, String> {
+    if name == "GlobalTransform" {
+        return Ok(Tracker {
+            name: "GlobalTransform",
+            id: world.component_id::<GlobalTransform>(),
+            reader: Reader::GlobalTransform,
+        });
+    }
+
+    match registry.get(name) {
+        Some(registration) if !registration.is_tag() => Ok(Tracker {
+            name: registration.name(),
+            id: registration.id(),
+            reader: Reader::Registered(registration.clone()),
+        }),
+        Some(_) => Err(format!("'{}' is a tag, it has no values to diff", name)),
+        None => Err(format!("No component registered as '{}'", name)),
+    }
+}
+
+fn snapshot(world: &World, filter: &EntityFilter, trackers: &[Tracker]) -> Snapshot {
+    let mut snapshot = Snapshot::new();
+    for tracker in trackers {
+        if let EntityFilter::Id(id) = filter {
+            let entity = world.entity_from_id(*id);
+            if !entity.is_alive() {
+                continue;
+            }
+            if let Some(value) = tracker.read(&entity) {
+                snapshot.insert((*id, tracker.name), value);
+            }
+            continue;
+        }
+
+        world
+            .query::<()>()
+            .with(tracker.id)
+            .build()
+            .each_entity(|entity, _| {
+                if !filter.matches(&entity) {
+                    return;
+                }
+                if let Some(value) = tracker.read(&entity) {
+                    snapshot.insert((*entity.id(), tracker.name), value);
+                }
+            });
+    }
+    snapshot
+}
+
+fn diff_snapshots(
+    world: &World,
+    previous: &Snapshot,
+    current: &Snapshot,
+    epsilon: f64,
+    frame: u64,
+    phase: &'static str,
+) -> Vec<ValueChange> {
+    let mut changes = Vec::new();
+    let mut push = |key: &(u64, &'static str),
+                    path: String,
+                    before: Option<&Value>,
+                    after: Option<&Value>| {
+        changes.push(ValueChange {
+            frame,
+            phase,
+            entity: entity_label(world, key.0),
+            path,
+            before: before.cloned(),
+            after: after.cloned(),
+        });
+    };
+
+    for (key, after) in current {
+        match previous.get(key) {
+            Some(before) => {
+                let mut fields = Vec::new();
+                diff_values(key.1.to_string(), before, after, epsilon, &mut fields);
+                for (path, before, after) in fields {
+                    push(key, path, Some(before), Some(after));
+                }
+            }
+            None => push(key, key.1.to_string(), None, Some(after)),
+        }
+    }
+    for (key, before) in previous {
+        if !current.contains_key(key) {
+            push(key, key.1.to_string(), Some(before), None);
+        }
+    }
+
+    changes
+}
+
+/// Collects the leaves of `before` and `after` that differ, with their paths below
+/// `path`. Numbers closer than `epsilon` are equal; a structural change (another
+/// type, a different array length) is reported for the whole value.
+pub fn diff_values<'a>(
+    path: String,
+    before: &'a Value,
+    after: &'a Value,
+    epsilon: f64,
+    out: &mut Vec<(String, &'a Value, &'a Value)>,
+) {
+    match (before, after) {
+        (Value::Number(a), Value::Number(b)) => {
+            let (a, b) = (a.as_f64().unwrap_or(f64::NAN), b.as_f64().unwrap_or(f64::NAN));
+            // NaN never equals itself, but NaN staying NaN is not a change
+            if !((a - b).abs() <= epsilon || (a.is_nan() && b.is_nan())) {
+                out.push((path, before, after));
+            }
+        }
+        (Value::Array(a), Value::Array(b)) if a.len() == b.len() => {
+            for (index, (a, b)) in a.iter().zip(b).enumerate() {
+                diff_values(format!("{}[{}]", path, index), a, b, epsilon, out);
+            }
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            for (key, a_value) in a {
+                match b.get(key) {
+                    Some(b_value) => {
+                        diff_values(format!("{}.{}", path, key), a_value, b_value, epsilon, out)
+                    }
+                    None => out.push((format!("{}.{}", path, key), a_value, &Value::Null)),
+                }
+            }
+            for (key, b_value) in b {
+                if !a.contains_key(key) {
+                    out.push((format!("{}.{}", path, key), &Value::Null, b_value));
+                }
+            }
+        }
+        _ => {
+            if before != after {
+                out.push((path, before, after));
+            }
+        }
+    }
+}
+
+fn entity_label(world: &World, id: u64) -> String {
+    let entity = world.entity_from_id(id);
+    if !entity.is_alive() {
+        return format!("#{}", id);
+    }
+    let name = entity.name();
+    if name.is_empty() {
+        format!("#{}", id)
+    } else {
+        format!("{} (#{})", name, id)
+    }
+}
+
+// '*' matches any run of characters, everything else itself
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+pub fn register_state_diff_commands(commands: &mut ConsoleCommands) {
+    commands.register(
+        "diff.start",
+        "diff.start [filter] [fine] [components=A,B] [eps=1e-5] [file=path] - report component changes every frame. filter: * (default), #id or a name with * wildcards",
+        |world, args| {
+            let mut settings = StateDiffSettings::default();
+            for arg in args {
+                match arg.split_once('=') {
+                    Some(("components", names)) => {
+                        settings.components = names
+                            .split(',')
+                            .filter(|name| !name.is_empty())
+                            .map(str::to_string)
+                            .collect();
+                    }
+                    Some(("eps", epsilon)) => {
+                        settings.epsilon = epsilon
+                            .parse()
+                            .map_err(|_| format!("'{}' is not a number", epsilon))?;
+                    }
+                    Some(("file", path)) => settings.file = Some(path.to_string()),
+                    Some((key, _)) => return Err(format!("Unknown option '{}'", key)),
+                    None if *arg == "fine" => settings.fine_grained = true,
+                    None => settings.filter = EntityFilter::parse(arg),
+                }
+            }
+
+            let description = format!(
+                "Diffing {} of {:?}{}",
+                settings.components.join(", "),
+                settings.filter,
+                if settings.fine_grained { " per phase" } else { "" }
+            );
+            world.get::<&mut StateDiff>(|diff| diff.start(world, settings))?;
+            Ok(description)
+        },
+    );
+    commands.register("diff.stop", "diff.stop - end the diff session", |world, _| {
+        match world.get::<&mut StateDiff>(|diff| diff.stop()) {
+            Some(changes) => Ok(format!("Diff stopped, {} changes reported", changes)),
+            None => Err("No diff session is running".to_string()),
+        }
+    });
+}
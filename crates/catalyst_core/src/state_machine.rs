@@ -0,0 +1,617 @@
+use std::fmt::Debug;
+
+use flecs_ecs::prelude::*;
+
+use crate::time::Time;
+
+// Immediate transitions followed in one tick at most, so a cycle of them can't hang
+// the frame
+const MAX_IMMEDIATE_CHAIN: usize = 8;
+
+type Callback<S> = Box<dyn FnMut(&StateContext<'_, S>) + Send + Sync>;
+type Guard<S> = Box<dyn FnMut(&StateContext<'_, S>) -> bool + Send + Sync>;
+
+/// What identifies a state, usually a fieldless enum.
+pub trait StateId: Copy + PartialEq + Debug + Send + Sync + 'static {}
+
+impl<T> StateId for T where T: Copy + PartialEq + Debug + Send + Sync + 'static {}
+
+/// Passed to state callbacks and transition guards.
+pub struct StateContext<'a, S> {
+    /// The entity running the machine. Changes through it are deferred like any
+    /// system's; use `entity.world()` to reach other entities.
+    pub entity: EntityView<'a>,
+    /// The state the callback belongs to, or the `from` state of a guard.
+    pub state: S,
+    /// Seconds since `state` was entered.
+    pub time_in_state: f32,
+    /// This frame's delta in seconds, as the machine sees it (scaled unless unscaled).
+    pub dt: f32,
+}
+
+/// A state of a StateMachine, with its callbacks. States can be nested with
+/// `child_of`: while a child is active its parents are too, entered before it and
+/// exited after it.
+pub struct State<S: StateId> {
+    id: S,
+    parent: Option<S>,
+    initial: Option<S>,
+    on_enter: Option<Callback<S>>,
+    on_update: Option<Callback<S>>,
+    on_exit: Option<Callback<S>>,
+}
+
+impl<S: StateId> State<S> {
+    pub fn new(id: S) -> Self {
+        Self {
+            id,
+            parent: None,
+            initial: None,
+            on_enter: None,
+            on_update: None,
+            on_exit: None,
+        }
+    }
+
+    /// Makes this a sub-state of `parent`.
+    pub fn child_of(mut self, parent: S) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+
+    /// The child entered when a transition targets this state. Defaults to the first
+    /// child declared.
+    pub fn initial(mut self, child: S) -> Self {
+        self.initial = Some(child);
+        self
+    }
+
+    pub fn on_enter(
+        mut self,
+        callback: impl FnMut(&StateContext<'_, S>) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_enter = Some(Box::new(callback));
+        self
+    }
+
+    /// Called every frame the state is active, parents before children.
+    pub fn on_update(
+        mut self,
+        callback: impl FnMut(&StateContext<'_, S>) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_update = Some(Box::new(callback));
+        self
+    }
+
+    pub fn on_exit(
+        mut self,
+        callback: impl FnMut(&StateContext<'_, S>) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_exit = Some(Box::new(callback));
+        self
+    }
+}
+
+/// A move from one state to another, taken when its guard passes. `from` can be a
+/// parent state: the transition then applies in any of its sub-states.
+pub struct Transition<S: StateId> {
+    from: S,
+    to: S,
+    guard: Option<Guard<S>>,
+    priority: i32,
+    immediate: bool,
+}
+
+impl<S: StateId> Transition<S> {
+    /// A transition without a guard, taken the first frame it is checked.
+    pub fn new(from: S, to: S) -> Self {
+        Self {
+            from,
+            to,
+            guard: None,
+            priority: 0,
+            immediate: false,
+        }
+    }
+
+    /// Only taken while `guard` returns true.
+    pub fn when(
+        mut self,
+        guard: impl FnMut(&StateContext<'_, S>) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.guard = Some(Box::new(guard));
+        self
+    }
+
+    /// Checked before transitions of lower priority (default 0); equal priorities go
+    /// in the order they were added.
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// May be taken in the same frame as another transition, right after it.
+    pub fn immediate(mut self) -> Self {
+        self.immediate = true;
+        self
+    }
+}
+
+/// Emitted on the entity after every change of state, with the innermost states.
+/// `from` is None when the machine starts.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct StateChanged<S: StateId> {
+    pub from: Option<S>,
+    pub to: S,
+}
+
+/// A hierarchical state machine on an entity, ticked every frame in OnUpdate by the
+/// system `state_machine_system::<S>` registers.
+///
+/// ```ignore
+/// entity.set(
+///     StateMachine::new(Enemy::Idle)
+///         .state(State::new(Enemy::Idle))
+///         .state(State::new(Enemy::Combat))
+///         .state(State::new(Enemy::Chase).child_of(Enemy::Combat).on_update(|ctx| { /* move */ }))
+///         .state(State::new(Enemy::Attack).child_of(Enemy::Combat))
+///         .transition(Transition::new(Enemy::Idle, Enemy::Chase).when(|ctx| player_near(ctx.entity)))
+///         .transition(Transition::new(Enemy::Combat, Enemy::Idle).when(|ctx| player_gone(ctx.entity))),
+/// );
+/// ```
+///
+/// Each tick adds the frame time to the active states, then checks the transitions:
+/// in priority order, then the order they were added, the first whose `from` is active
+/// and whose guard passes is taken. States are exited innermost first up to the parent
+/// shared with the target, then entered outermost first down to the target (and its
+/// initial children), and `StateChanged` is emitted. Only transitions marked
+/// `immediate` may follow in the same tick. Last, `on_update` runs for the active
+/// states, outermost first. The first tick enters the initial state.
+///
+/// Like sequences, machines freeze while gameplay time is paused unless `unscaled`.
+#[derive(Component)]
+pub struct StateMachine<S: StateId> {
+    initial: S,
+    states: Vec<State<S>>,
+    transitions: Vec<Transition<S>>,
+    // Outermost to innermost, with the seconds spent in each
+    active: Vec<(S, f32)>,
+    requested: Option<S>,
+    unscaled: bool,
+}
+
+enum Hook {
+    Enter,
+    Update,
+    Exit,
+}
+
+impl<S: StateId> StateMachine<S> {
+    /// A machine that starts in `initial` (or its initial children) on its first tick.
+    pub fn new(initial: S) -> Self {
+        Self {
+            initial,
+            states: Vec::new(),
+            transitions: Vec::new(),
+            active: Vec::new(),
+            requested: None,
+            unscaled: false,
+        }
+    }
+
+    /// Adds a state. States used without being added have no callbacks and no parent.
+    pub fn state(mut self, state: State<S>) -> Self {
+        self.states.push(state);
+        self
+    }
+
+    pub fn transition(mut self, transition: Transition<S>) -> Self {
+        self.transitions.push(transition);
+        // Stable, so equal priorities keep the order they were added in
+        self.transitions
+            .sort_by_key(|transition| std::cmp::Reverse(transition.priority));
+        self
+    }
+
+    /// Runs on real time: ignores the time scale and keeps going while gameplay is paused.
+    pub fn unscaled(mut self) -> Self {
+        self.unscaled = true;
+        self
+    }
+
+    /// The innermost active state, None before the first tick.
+    pub fn current(&self) -> Option<S> {
+        self.active.last().map(|(state, _)| *state)
+    }
+
+    /// True if `state` is active, as the innermost state or one of its parents.
+    pub fn is_in(&self, state: S) -> bool {
+        self.active.iter().any(|(active, _)| *active == state)
+    }
+
+    /// The active states, outermost first.
+    pub fn active_states(&self) -> impl Iterator<Item = S> + '_ {
+        self.active.iter().map(|(state, _)| *state)
+    }
+
+    /// Seconds since the innermost state was entered.
+    pub fn time_in_state(&self) -> f32 {
+        self.active.last().map_or(0.0, |(_, time)| *time)
+    }
+
+    /// Moves to `state` on the next tick, before any guard is checked, e.g. from an
+    /// event another system handles.
+    pub fn request(&mut self, state: S) {
+        self.requested = Some(state);
+    }
+
+    fn node(&self, state: S) -> Option<&State<S>> {
+        self.states.iter().find(|node| node.id == state)
+    }
+
+    fn initial_child(&self, state: S) -> Option<S> {
+        self.node(state).and_then(|node| node.initial).or_else(|| {
+            self.states
+                .iter()
+                .find(|node| node.parent == Some(state))
+                .map(|node| node.id)
+        })
+    }
+
+    // Outermost parent down to the innermost initial child of `state`. Bounded by the
+    // state count, so a parent cycle can't loop forever
+    fn path_to(&self, state: S) -> (Vec<S>, usize) {
+        let mut path = vec![state];
+        while let Some(parent) = self.node(path[path.len() - 1]).and_then(|node| node.parent) {
+            if path.len() > self.states.len() {
+                break;
+            }
+            path.push(parent);
+        }
+        path.reverse();
+        let depth = path.len() - 1;
+        while let Some(child) = self.initial_child(path[path.len() - 1]) {
+            if path.len() > self.states.len() {
+                break;
+            }
+            path.push(child);
+        }
+        (path, depth)
+    }
+
+    fn call(&mut self, hook: Hook, level: usize, entity: EntityView, dt: f32) {
+        let (state, time_in_state) = self.active[level];
+        let Some(node) = self.states.iter_mut().find(|node| node.id == state) else {
+            return;
+        };
+        let callback = match hook {
+            Hook::Enter => &mut node.on_enter,
+            Hook::Update => &mut node.on_update,
+            Hook::Exit => &mut node.on_exit,
+        };
+        if let Some(callback) = callback {
+            callback(&StateContext {
+                entity,
+                state,
+                time_in_state,
+                dt,
+            });
+        }
+    }
+
+    fn change(&mut self, to: S, entity: EntityView, dt: f32) {
+        let from = self.current();
+        let (target, depth) = self.path_to(to);
+        let shared = self
+            .active
+            .iter()
+            .zip(&target)
+            .take_while(|((active, _), target)| active == *target)
+            .count()
+            // A transition to an active state leaves and re-enters it
+            .min(depth);
+
+        while self.active.len() > shared {
+            self.call(Hook::Exit, self.active.len() - 1, entity, dt);
+            self.active.pop();
+        }
+        for state in &target[shared..] {
+            self.active.push((*state, 0.0));
+            self.call(Hook::Enter, self.active.len() - 1, entity, dt);
+        }
+
+        entity.emit(&StateChanged {
+            from,
+            to: target[target.len() - 1],
+        });
+    }
+
+    // Index of the first transition to take, if any
+    fn next_transition(
+        &mut self,
+        entity: EntityView,
+        dt: f32,
+        immediate_only: bool,
+    ) -> Option<usize> {
+        for (index, transition) in self.transitions.iter_mut().enumerate() {
+            if immediate_only && !transition.immediate {
+                continue;
+            }
+            let Some(&(state, time_in_state)) = self
+                .active
+                .iter()
+                .find(|(active, _)| *active == transition.from)
+            else {
+                continue;
+            };
+            let passes = match &mut transition.guard {
+                Some(guard) => guard(&StateContext {
+                    entity,
+                    state,
+                    time_in_state,
+                    dt,
+                }),
+                None => true,
+            };
+            if passes {
+                return Some(index);
+            }
+        }
+        None
+    }
+
+    fn tick(&mut self, entity: EntityView, dt: f32) {
+        for (_, time) in &mut self.active {
+            *time += dt;
+        }
+
+        let mut changed = false;
+        if self.active.is_empty() {
+            self.change(self.initial, entity, dt);
+            changed = true;
+        }
+        if let Some(state) = self.requested.take() {
+            self.change(state, entity, dt);
+            changed = true;
+        }
+
+        // After a change only immediate transitions are checked
+        let mut chained = 0;
+        while let Some(index) = self.next_transition(entity, dt, changed) {
+            if changed {
+                if chained == MAX_IMMEDIATE_CHAIN {
+                    log::warn!(
+                        "State machine on {} took {} immediate transitions in one frame, stopping at {:?}",
+                        entity.name(),
+                        MAX_IMMEDIATE_CHAIN,
+                        self.current()
+                    );
+                    break;
+                }
+                chained += 1;
+            }
+            self.change(self.transitions[index].to, entity, dt);
+            changed = true;
+        }
+
+        for level in 0..self.active.len() {
+            self.call(Hook::Update, level, entity, dt);
+        }
+    }
+}
+
+/// Ticks every `StateMachine<S>` in OnUpdate, after "Advance Sequences". Call once per
+/// state type.
+pub fn state_machine_system<S: StateId>(world: &mut World) {
+    world.component::<StateChanged<S>>();
+
+    world
+        .system_named::<(&mut StateMachine<S>, &Time)>(&format!(
+            "Advance State Machines ({})",
+            std::any::type_name::<S>()
+        ))
+        .kind(flecs::pipeline::OnUpdate)
+        .each_entity(|entity, (machine, time)| {
+            let dt = if machine.unscaled {
+                time.delta()
+            } else if time.is_paused() {
+                return;
+            } else {
+                time.scaled_delta()
+            };
+            machine.tick(entity, dt.as_secs_f32());
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicBool, AtomicU32, Ordering},
+        },
+        time::Duration,
+    };
+
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Enemy {
+        Idle,
+        Combat,
+        Chase,
+        Attack,
+        Flee,
+    }
+
+    type Log = Arc<Mutex<Vec<String>>>;
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.component::<Time>().add_trait::<flecs::Singleton>();
+        let mut time = Time::default();
+        time.set_delta(Duration::from_millis(16));
+        world.set(time);
+        state_machine_system::<Enemy>(&mut world);
+        world
+    }
+
+    fn logged(log: &Log, state: Enemy) -> State<Enemy> {
+        let (enter, update, exit) = (log.clone(), log.clone(), log.clone());
+        State::new(state)
+            .on_enter(move |ctx| enter.lock().unwrap().push(format!("enter {:?}", ctx.state)))
+            .on_update(move |ctx| {
+                update
+                    .lock()
+                    .unwrap()
+                    .push(format!("update {:?}", ctx.state))
+            })
+            .on_exit(move |ctx| exit.lock().unwrap().push(format!("exit {:?}", ctx.state)))
+    }
+
+    fn flag(
+        flag: &Arc<AtomicBool>,
+    ) -> impl Fn(&StateContext<'_, Enemy>) -> bool + Send + Sync + 'static {
+        let flag = flag.clone();
+        move |_| flag.load(Ordering::Relaxed)
+    }
+
+    fn take(log: &Log) -> Vec<String> {
+        std::mem::take(&mut *log.lock().unwrap())
+    }
+
+    fn current(entity: EntityView) -> Option<Enemy> {
+        entity.get::<&StateMachine<Enemy>>(|machine| machine.current())
+    }
+
+    #[test]
+    fn transitions_run_in_priority_then_declared_order() {
+        let world = world();
+        let log = Log::default();
+        let spotted = Arc::new(AtomicBool::new(false));
+        let close = Arc::new(AtomicBool::new(false));
+        let lost = Arc::new(AtomicBool::new(false));
+        let scared = Arc::new(AtomicBool::new(false));
+
+        let enemy = world.entity_named("enemy").set(
+            StateMachine::new(Enemy::Idle)
+                .state(logged(&log, Enemy::Idle))
+                .state(logged(&log, Enemy::Combat))
+                .state(logged(&log, Enemy::Chase).child_of(Enemy::Combat))
+                .state(logged(&log, Enemy::Attack).child_of(Enemy::Combat))
+                .state(logged(&log, Enemy::Flee))
+                .transition(Transition::new(Enemy::Idle, Enemy::Chase).when(flag(&spotted)))
+                // Declared after, so only checked once the first one fails
+                .transition(Transition::new(Enemy::Idle, Enemy::Flee).when(flag(&spotted)))
+                .transition(Transition::new(Enemy::Chase, Enemy::Attack).when(flag(&close)))
+                .transition(Transition::new(Enemy::Combat, Enemy::Idle).when(flag(&lost)))
+                // Declared last but checked first
+                .transition(
+                    Transition::new(Enemy::Combat, Enemy::Flee)
+                        .when(flag(&scared))
+                        .priority(1),
+                ),
+        );
+        let changes = Arc::new(AtomicU32::new(0));
+        let counter = changes.clone();
+        enemy.observe_payload::<&StateChanged<Enemy>>(move |_| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+
+        world.progress();
+        assert_eq!(take(&log), ["enter Idle", "update Idle"]);
+        assert_eq!(changes.load(Ordering::Relaxed), 1);
+
+        // Both Idle transitions pass: the one declared first wins, exits come before
+        // enters and entering Chase enters its parent first. Chase -> Attack passes too,
+        // but waits for the next frame
+        spotted.store(true, Ordering::Relaxed);
+        close.store(true, Ordering::Relaxed);
+        world.progress();
+        assert_eq!(
+            take(&log),
+            [
+                "exit Idle",
+                "enter Combat",
+                "enter Chase",
+                "update Combat",
+                "update Chase"
+            ]
+        );
+        assert_eq!(current(enemy), Some(Enemy::Chase));
+
+        // A sibling transition keeps the parent
+        world.progress();
+        assert_eq!(
+            take(&log),
+            [
+                "exit Chase",
+                "enter Attack",
+                "update Combat",
+                "update Attack"
+            ]
+        );
+
+        // The parent's transition with the higher priority beats the one declared
+        // earlier, and leaves all its sub-states
+        lost.store(true, Ordering::Relaxed);
+        scared.store(true, Ordering::Relaxed);
+        world.progress();
+        assert_eq!(
+            take(&log),
+            ["exit Attack", "exit Combat", "enter Flee", "update Flee"]
+        );
+        assert_eq!(changes.load(Ordering::Relaxed), 4);
+    }
+
+    #[test]
+    fn paused_time_freezes_the_machine() {
+        let world = world();
+        let log = Log::default();
+        let enemy = world.entity().set(
+            StateMachine::new(Enemy::Flee)
+                .state(logged(&log, Enemy::Idle))
+                .state(logged(&log, Enemy::Flee)),
+        );
+        world.progress();
+        take(&log);
+
+        world.get::<&mut Time>(|time| time.set_paused(true));
+        enemy.get::<&mut StateMachine<Enemy>>(|machine| machine.request(Enemy::Idle));
+        world.progress();
+        assert!(take(&log).is_empty());
+
+        // The request is taken once time runs again
+        world.get::<&mut Time>(|time| time.set_paused(false));
+        world.progress();
+        assert_eq!(take(&log), ["exit Flee", "enter Idle", "update Idle"]);
+    }
+
+    #[test]
+    fn immediate_transitions_chain_in_one_frame() {
+        let world = world();
+        let log = Log::default();
+        let chained = world.entity().set(
+            StateMachine::new(Enemy::Idle)
+                .state(logged(&log, Enemy::Idle))
+                .state(logged(&log, Enemy::Chase))
+                .state(logged(&log, Enemy::Attack))
+                .transition(Transition::new(Enemy::Idle, Enemy::Chase).immediate())
+                .transition(Transition::new(Enemy::Chase, Enemy::Attack).immediate()),
+        );
+        world.progress();
+        assert_eq!(current(chained), Some(Enemy::Attack));
+        // Only the last state of the chain updates
+        assert_eq!(
+            take(&log),
+            [
+                "enter Idle",
+                "exit Idle",
+                "enter Chase",
+                "exit Chase",
+                "enter Attack",
+                "update Attack"
+            ]
+        );
+    }
+}
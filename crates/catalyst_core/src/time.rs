@@ -1,12 +1,17 @@
 use std::time::{Duration, Instant};
 
-use flecs_ecs::macros::Component;
+use flecs_ecs::{core::WorldGet, macros::Component};
+
+use crate::console::ConsoleCommands;
 
 #[derive(Component)]
 pub struct Time {
     startup: Instant,
     last_update: Instant,
     delta: Duration,
+    max_delta: Duration,
+    scale: f32,
+    paused: bool,
 }
 
 impl Default for Time {
@@ -15,6 +20,9 @@ impl Default for Time {
             startup: Instant::now(),
             last_update: Instant::now(),
             delta: Duration::ZERO,
+            max_delta: Duration::from_millis(250),
+            scale: 1.0,
+            paused: false,
         }
     }
 }
@@ -23,15 +31,70 @@ impl Time {
     /// Called by the engine loop once per frame
     pub fn update(&mut self) {
         let now = Instant::now();
-        self.delta = now - self.last_update;
+        self.delta = (now - self.last_update).min(self.max_delta);
         self.last_update = now;
     }
 
+    /// Starts the next frame's delta from now, so time spent paused (e.g. while the
+    /// window was minimized) doesn't show up as one long frame.
+    pub fn reset_delta(&mut self) {
+        self.last_update = Instant::now();
+    }
+
+    /// Longest delta a frame reports, whatever the real time since the previous one.
+    /// Defaults to 250ms.
+    pub fn set_max_delta(&mut self, max_delta: Duration) {
+        self.max_delta = max_delta;
+    }
+
+    pub fn max_delta(&self) -> Duration {
+        self.max_delta
+    }
+
+    /// Replaces this frame's delta, e.g. with a recorded one during input replay.
+    /// Call after `update`.
+    pub fn set_delta(&mut self, delta: Duration) {
+        self.delta = delta;
+    }
+
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
     /// Returns time in seconds since last frame (e.g., 0.016 for 60fps)
     pub fn delta_seconds(&self) -> f32 {
         self.delta.as_secs_f32()
     }
 
+    /// Gameplay time: the frame's delta times the time scale, zero while paused.
+    /// Sequences and state machines follow it unless they are unscaled; `delta` stays
+    /// real time.
+    pub fn scaled_delta(&self) -> Duration {
+        if self.paused {
+            Duration::ZERO
+        } else {
+            self.delta.mul_f32(self.scale)
+        }
+    }
+
+    /// Pauses gameplay time, e.g. for a pause menu. Frames keep running.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Speed of gameplay time, 1.0 for real time. Negative scales count as 0.
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale.max(0.0);
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
     /// Returns total time since app started
     pub fn elapsed_seconds(&self) -> f32 {
         self.startup.elapsed().as_secs_f32()
@@ -42,15 +105,68 @@ impl Time {
 pub struct PhysicsTime {
     pub accumulator: f32,
     pub fixed_dt: f32,
+    paused: bool,
+    scale: f32,
+    requested_steps: u32,
 }
 
 impl Default for PhysicsTime {
     fn default() -> Self {
-        Self { accumulator: 0.0, fixed_dt: 1.0 / 60.0 }
+        Self {
+            accumulator: 0.0,
+            fixed_dt: 1.0 / 60.0,
+            paused: false,
+            scale: 1.0,
+            requested_steps: 0,
+        }
     }
 }
 
 impl PhysicsTime {
+    /// Adds a frame's time, times the slow-motion scale. Nothing accumulates while
+    /// paused, so resuming doesn't burst through the steps the pause would have owed.
+    pub fn accumulate(&mut self, dt: f32) {
+        if !self.paused {
+            self.accumulator += dt * self.scale;
+        }
+    }
+
+    /// Fixed steps to run now: what the accumulator holds, at most `max_steps`, plus
+    /// the ones asked for with `request_step`.
+    pub fn take_steps(&mut self, max_steps: u32) -> u32 {
+        let mut steps = 0;
+        while steps < max_steps && self.accumulator >= self.fixed_dt {
+            self.accumulator -= self.fixed_dt;
+            steps += 1;
+        }
+        steps + std::mem::take(&mut self.requested_steps)
+    }
+
+    /// Stops the fixed steps while frames keep running, e.g. to inspect a physics
+    /// explosion. `request_step` still steps.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Runs one more fixed step of `fixed_dt` next frame, paused or not.
+    pub fn request_step(&mut self) {
+        self.requested_steps += 1;
+    }
+
+    /// Slow motion: fewer fixed steps per second, each still `fixed_dt` long, so the
+    /// simulation behaves the same. 1.0 for real time, negative scales count as 0.
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale.max(0.0);
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
     pub fn should_step(&mut self, dt: f32) -> bool {
         self.accumulator += dt;
         if self.accumulator >= self.fixed_dt {
@@ -62,3 +178,83 @@ impl PhysicsTime {
     }
 }
 
+
+pub fn register_time_commands(commands: &mut ConsoleCommands) {
+    commands.register("time.pause", "Pause or resume gameplay time", |world, _| {
+        let paused = world.get::<&mut Time>(|time| {
+            time.set_paused(!time.is_paused());
+            time.is_paused()
+        });
+        Ok(if paused {
+            "Gameplay time paused".to_string()
+        } else {
+            "Gameplay time resumed".to_string()
+        })
+    });
+    commands.register(
+        "time.scale",
+        "time.scale <scale> - speed of gameplay time, 1 for real time",
+        |world, args| {
+            let arg = args.first().ok_or("usage: time.scale <scale>")?;
+            let scale = match arg.parse::<f32>() {
+                Ok(scale) if scale >= 0.0 => scale,
+                _ => return Err(format!("'{}' isn't a time scale", arg)),
+            };
+            world.get::<&mut Time>(|time| time.set_scale(scale));
+            Ok(format!("Gameplay time scale set to {}", scale))
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXED_DT: f32 = 1.0 / 50.0;
+
+    fn physics_time() -> PhysicsTime {
+        PhysicsTime {
+            fixed_dt: FIXED_DT,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn running_the_accumulator_steps() {
+        let mut time = physics_time();
+        time.accumulate(FIXED_DT * 2.5);
+        assert_eq!(time.take_steps(4), 2);
+    }
+
+    #[test]
+    fn paused_nothing_steps_or_accumulates() {
+        let mut time = physics_time();
+        time.set_paused(true);
+        time.accumulate(10.0);
+        assert_eq!(time.take_steps(4), 0);
+        assert!(time.accumulator < FIXED_DT);
+
+        // A requested step runs once while paused
+        time.request_step();
+        assert_eq!(time.take_steps(4), 1);
+        assert_eq!(time.take_steps(4), 0);
+
+        // Resuming after a long pause doesn't burst
+        time.set_paused(false);
+        time.accumulate(FIXED_DT);
+        assert_eq!(time.take_steps(4), 1);
+    }
+
+    #[test]
+    fn at_quarter_speed_a_quarter_of_the_steps_run() {
+        let mut time = physics_time();
+        time.set_scale(0.25);
+        let steps: u32 = (0..8)
+            .map(|_| {
+                time.accumulate(FIXED_DT);
+                time.take_steps(4)
+            })
+            .sum();
+        assert_eq!(steps, 2);
+    }
+}
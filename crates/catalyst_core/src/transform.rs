@@ -1,5 +1,6 @@
 use flecs_ecs::prelude::*;
 use glam::{Mat4, Quat, Vec3};
+use serde::{Deserialize, Serialize};
 
 #[derive(Component)]
 #[flecs(meta)]
@@ -22,7 +23,8 @@ pub struct ReflectVec4 {
 #[flecs(meta)]
 pub struct ReflectQuat { x: f32, y: f32, z: f32, w: f32 }
 
-#[derive(Component, Clone, Copy, Debug)]
+#[derive(Component, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Transform {
     pub translation: glam::Vec3,
     pub rotation: glam::Quat,
@@ -115,18 +117,12 @@ impl GlobalTransform {
     }
 }
 
-pub fn transform_propagation_system(world: &World) {
-    world
-        .system_named::<(&Transform, &mut GlobalTransform, Option<&GlobalTransform>)>("transform_propagation_system")
-        .kind(flecs::pipeline::PostUpdate)
-        .cascade()
-        .term_at(2)
-        .parent()
-        .each(|(local, global, parent_global)| {
-            if let Some(parent_global) = parent_global {
-                global.0 = parent_global.0 * local.compute_matrix();
-            } else {
-                global.0 = local.compute_matrix();
-            }
-        });
-}
+/// Promises that the entity never moves: its Transform, and those of its parents, stay
+/// as they were when it was spawned. Transform propagation doesn't look at it again
+/// after computing its GlobalTransform, nor do the other per-frame transform consumers
+/// (mesh uniforms, physics poses), so a level's static scenery costs nothing per frame.
+///
+/// Remove the marker before moving the entity. `TransformPropagation::check_static`
+/// (on in debug builds) warns about entities that moved anyway.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct StaticTransform;
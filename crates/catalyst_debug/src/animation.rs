@@ -0,0 +1,57 @@
+use catalyst_core::animation::Timeline;
+use flecs_ecs::prelude::*;
+
+/// Every entity with a Timeline: where it is, a slider to scrub it and the tracks that
+/// couldn't be applied. Scrubbing pauses the timeline, so it stays where it was left.
+pub fn animation_window(ctx: &egui::Context, world: &WorldRef, timelines: &[(Entity, String)]) {
+    egui::Window::new("Animation")
+        .default_open(false)
+        .show(ctx, |ui| {
+            if timelines.is_empty() {
+                ui.label("No entity has a Timeline.");
+                return;
+            }
+
+            for (entity, label) in timelines {
+                world
+                    .entity_from_id(*entity)
+                    .try_get::<&mut Timeline>(|timeline| {
+                        ui.separator();
+                        ui.horizontal(|ui| {
+                            let toggle = if timeline.playing { "Pause" } else { "Play" };
+                            if ui.button(toggle).clicked() {
+                                if timeline.playing {
+                                    timeline.pause();
+                                } else {
+                                    timeline.play();
+                                }
+                            }
+                            ui.label(format!(
+                                "{} ({} tracks, {:?})",
+                                label,
+                                timeline.tracks.len(),
+                                timeline.looping
+                            ));
+                        });
+
+                        let length = timeline.length();
+                        let mut position = timeline.position();
+                        let slider =
+                            egui::Slider::new(&mut position, 0.0..=length.max(f32::EPSILON))
+                                .suffix(" s")
+                                .text(format!("of {:.2} s", length));
+                        if ui.add(slider).changed() {
+                            timeline.pause();
+                            timeline.seek(position);
+                        }
+
+                        for (track, error) in timeline.errors() {
+                            ui.colored_label(
+                                egui::Color32::LIGHT_RED,
+                                format!("{}.{}: {}", track.component, track.path, error),
+                            );
+                        }
+                    });
+            }
+        });
+}
@@ -0,0 +1,107 @@
+use catalyst_core::{
+    console::{ConsoleCommands, run_command},
+    logging::LogBuffer,
+};
+use flecs_ecs::prelude::*;
+
+// Oldest lines are dropped past this
+const MAX_HISTORY: usize = 200;
+
+#[derive(Component, Default)]
+pub struct ConsoleState {
+    input: String,
+    history: Vec<ConsoleLine>,
+    // Next LogBuffer line to copy into the history
+    next_log_line: u64,
+}
+
+struct ConsoleLine {
+    text: String,
+    is_error: bool,
+}
+
+impl ConsoleState {
+    fn push(&mut self, text: String, is_error: bool) {
+        self.history.push(ConsoleLine { text, is_error });
+        if self.history.len() > MAX_HISTORY {
+            self.history.remove(0);
+        }
+    }
+
+    fn pull_log(&mut self, buffer: &LogBuffer) {
+        for line in buffer.since(self.next_log_line) {
+            self.next_log_line = line.sequence + 1;
+            self.push(
+                format!("[{:<5} {}] {}", line.level, line.target, line.message),
+                line.level <= log::Level::Warn,
+            );
+        }
+    }
+}
+
+pub fn register_builtin_commands(commands: &mut ConsoleCommands) {
+    commands.register("help", "List all commands", |world, _| {
+        Ok(world.get::<&ConsoleCommands>(|commands| {
+            commands
+                .iter()
+                .map(|(name, command)| format!("{:<16} {}", name, command.help))
+                .collect::<Vec<_>>()
+                .join("\n")
+        }))
+    });
+}
+
+pub fn console_window(ctx: &egui::Context, world: &WorldRef) {
+    let mut submitted = None;
+    let log_buffer = world.try_get::<&LogBuffer>(|buffer| buffer.clone());
+
+    world.get::<&mut ConsoleState>(|console| {
+        if let Some(buffer) = &log_buffer {
+            console.pull_log(buffer);
+        }
+
+        egui::Window::new("Console")
+            .default_height(240.0)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical()
+                    .max_height(200.0)
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        for line in &console.history {
+                            let text = egui::RichText::new(&line.text).monospace();
+                            if line.is_error {
+                                ui.label(text.color(egui::Color32::LIGHT_RED));
+                            } else {
+                                ui.label(text);
+                            }
+                        }
+                    });
+
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut console.input)
+                        .hint_text("help")
+                        .desired_width(f32::INFINITY),
+                );
+
+                if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    submitted = Some(std::mem::take(&mut console.input));
+                    response.request_focus();
+                }
+            });
+    });
+
+    // Run outside the borrow above, commands may touch any singleton
+    let Some(line) = submitted.filter(|line| !line.trim().is_empty()) else {
+        return;
+    };
+    let result = run_command(world, &line);
+
+    world.get::<&mut ConsoleState>(|console| {
+        console.push(format!("> {}", line), false);
+        match result {
+            Ok(output) if output.is_empty() => {}
+            Ok(output) => console.push(output, false),
+            Err(error) => console.push(error, true),
+        }
+    });
+}
@@ -0,0 +1,71 @@
+use catalyst_renderer::{DynamicResolutionState, RenderSettings, RenderStats};
+use flecs_ecs::prelude::*;
+
+use crate::exposure::plot_line;
+
+const GPU_TIME_COLOR: egui::Color32 = egui::Color32::from_rgb(230, 120, 90);
+const SCALE_COLOR: egui::Color32 = egui::Color32::from_rgb(120, 220, 140);
+
+/// Controller settings, the current render scale and a plot of the smoothed GPU frame
+/// time against the scale, followed by the last scale changes. `size` is the window's.
+pub fn dynamic_resolution_section(ui: &mut egui::Ui, world: &WorldRef, size: (u32, u32)) {
+    world.get::<&mut RenderSettings>(|settings| {
+        let resolution = &mut settings.dynamic_resolution;
+        ui.checkbox(&mut resolution.enabled, "Enabled");
+        ui.add(
+            egui::Slider::new(&mut resolution.target_frame_ms, 4.0..=50.0).text("Target GPU ms"),
+        );
+
+        let mut manual = resolution.manual_scale.is_some();
+        ui.checkbox(&mut manual, "Manual scale");
+        match (manual, resolution.manual_scale) {
+            (true, None) => resolution.manual_scale = Some(resolution.max_scale),
+            (false, Some(_)) => resolution.manual_scale = None,
+            _ => {}
+        }
+        if let Some(scale) = &mut resolution.manual_scale {
+            ui.add(egui::Slider::new(scale, 0.5..=1.0).text("Scale"));
+        }
+    });
+
+    let target_ms = world.get::<&RenderSettings>(|settings| settings.dynamic_resolution.target_frame_ms);
+    world.get::<&DynamicResolutionState>(|state| {
+        let (width, height) = size;
+        let (render_width, render_height) = state.render_size(width, height);
+        ui.label(format!(
+            "Scale {:.2}, {}x{} of {}x{}",
+            state.scale, render_width, render_height, width, height
+        ));
+        match state.gpu_ms {
+            Some(gpu_ms) => ui.label(format!("GPU {:.2} ms (target {:.1} ms)", gpu_ms, target_ms)),
+            None => ui.label("No GPU timings (timestamp queries unsupported?)"),
+        };
+
+        let (rect, _) = ui.allocate_exact_size(
+            egui::vec2(ui.available_width().max(200.0), 60.0),
+            egui::Sense::hover(),
+        );
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2.0, egui::Color32::from_gray(24));
+
+        let gpu_ms: Vec<f32> = state.history.iter().map(|(gpu_ms, _)| *gpu_ms).collect();
+        let scales: Vec<f32> = state.history.iter().map(|(_, scale)| *scale).collect();
+        painter.add(plot_line(rect, &gpu_ms, GPU_TIME_COLOR));
+        painter.add(plot_line(rect, &scales, SCALE_COLOR));
+        ui.horizontal(|ui| {
+            ui.colored_label(GPU_TIME_COLOR, "GPU ms");
+            ui.colored_label(SCALE_COLOR, "scale");
+        });
+
+        for decision in state.decisions.iter().rev() {
+            ui.label(format!(
+                "{:.1} s: {:.2} -> {:.2} at {:.2} ms",
+                decision.time, decision.from, decision.to, decision.gpu_ms
+            ));
+        }
+    });
+
+    world.get::<&RenderStats>(|stats| {
+        ui.label(format!("Last frame drawn at {:.2}", stats.render_scale));
+    });
+}
@@ -0,0 +1,488 @@
+use catalyst_core::{
+    App,
+    camera::Camera,
+    camera_effects::CameraEffects,
+    color::Color,
+    light::{DirectionalLight, PointLight},
+    registry::{ComponentRegistry, RegisteredComponent},
+    transform::Transform,
+};
+use catalyst_renderer::{
+    Autofocus, DepthOfField, ReflectionProbe, SpotLight, depth_of_field::MAX_BLUR_RADIUS,
+    spot_light::MAX_SPOT_ANGLE,
+};
+use glam::{EulerRot, Quat, Vec3};
+use serde_json::Value;
+
+/// Inspector editor for a registered component. Components without one are edited
+/// field by field through their serialized form.
+pub fn register_editor<T: RegisteredComponent>(app: &mut App, editor: fn(&mut egui::Ui, &mut T) -> bool) {
+    app.world.get::<&mut ComponentRegistry>(|registry| {
+        registry.set_editor::<T, egui::Ui>(editor);
+    });
+}
+
+pub(crate) fn register_engine_editors(app: &mut App) {
+    register_editor(app, transform_editor);
+    register_editor(app, point_light_editor);
+    register_editor(app, directional_light_editor);
+    register_editor(app, spot_light_editor);
+    register_editor(app, camera_editor);
+    register_editor(app, camera_effects_editor);
+    register_editor(app, depth_of_field_editor);
+    register_editor(app, reflection_probe_editor);
+}
+
+fn transform_editor(ui: &mut egui::Ui, transform: &mut Transform) -> bool {
+    let mut changed = false;
+    changed |= vec3_row(ui, "Translation", transform.translation.as_mut(), 0.05);
+
+    // Edited as XYZ euler angles in degrees, written back only when touched so
+    // the quaternion doesn't drift while nobody edits it
+    let (x, y, z) = transform.rotation.to_euler(EulerRot::XYZ);
+    let mut degrees = [x.to_degrees(), y.to_degrees(), z.to_degrees()];
+    if vec3_row(ui, "Rotation", &mut degrees, 0.5) {
+        transform.rotation = Quat::from_euler(
+            EulerRot::XYZ,
+            degrees[0].to_radians(),
+            degrees[1].to_radians(),
+            degrees[2].to_radians(),
+        );
+        changed = true;
+    }
+
+    changed |= vec3_row(ui, "Scale", transform.scale.as_mut(), 0.01);
+    changed
+}
+
+fn point_light_editor(ui: &mut egui::Ui, light: &mut PointLight) -> bool {
+    let mut changed = color_row(ui, &mut light.color);
+    changed |= ui
+        .add(
+            egui::DragValue::new(&mut light.intensity)
+                .speed(0.1)
+                .range(0.0..=1000.0)
+                .prefix("Intensity: "),
+        )
+        .changed();
+    changed |= ui
+        .add(
+            egui::DragValue::new(&mut light.radius)
+                .speed(0.05)
+                .range(0.0..=1000.0)
+                .prefix("Radius: "),
+        )
+        .changed();
+    changed
+}
+
+fn directional_light_editor(ui: &mut egui::Ui, light: &mut DirectionalLight) -> bool {
+    let mut changed = color_row(ui, &mut light.color);
+    changed |= ui
+        .add(
+            egui::DragValue::new(&mut light.intensity)
+                .speed(0.1)
+                .range(0.0..=1000.0)
+                .prefix("Intensity: "),
+        )
+        .changed();
+    changed
+}
+
+// Cone angles are edited in degrees, the gobo is kept as it is
+fn spot_light_editor(ui: &mut egui::Ui, light: &mut SpotLight) -> bool {
+    let mut changed = color_row(ui, &mut light.color);
+    changed |= ui
+        .add(
+            egui::DragValue::new(&mut light.intensity)
+                .speed(0.1)
+                .range(0.0..=1000.0)
+                .prefix("Intensity: "),
+        )
+        .changed();
+    changed |= ui
+        .add(
+            egui::DragValue::new(&mut light.range)
+                .speed(0.05)
+                .range(0.0..=1000.0)
+                .prefix("Range: "),
+        )
+        .changed();
+
+    let max_degrees = MAX_SPOT_ANGLE.to_degrees();
+    let mut outer = light.outer_angle.to_degrees();
+    if ui
+        .add(egui::Slider::new(&mut outer, 1.0..=max_degrees).text("Outer angle"))
+        .changed()
+    {
+        light.outer_angle = outer.to_radians();
+        changed = true;
+    }
+    let mut inner = light.inner_angle.to_degrees();
+    if ui
+        .add(egui::Slider::new(&mut inner, 0.0..=outer).text("Inner angle"))
+        .changed()
+    {
+        light.inner_angle = inner.to_radians();
+        changed = true;
+    }
+    ui.label(if light.gobo.is_some() { "Gobo: set" } else { "Gobo: none" });
+    changed
+}
+
+fn reflection_probe_editor(ui: &mut egui::Ui, probe: &mut ReflectionProbe) -> bool {
+    let mut changed = vec3_row(ui, "Half extents", probe.half_extents.as_mut(), 0.05);
+    changed |= ui
+        .add(
+            egui::DragValue::new(&mut probe.blend_distance)
+                .speed(0.05)
+                .range(0.0..=100.0)
+                .prefix("Blend distance: "),
+        )
+        .changed();
+    changed |= ui
+        .add(
+            egui::DragValue::new(&mut probe.resolution)
+                .speed(4.0)
+                .range(16..=2048)
+                .prefix("Resolution: "),
+        )
+        .changed();
+    changed |= ui
+        .add(
+            egui::DragValue::new(&mut probe.near)
+                .speed(0.01)
+                .range(0.001..=10.0)
+                .prefix("Near: "),
+        )
+        .changed();
+    changed |= ui
+        .add(
+            egui::DragValue::new(&mut probe.far)
+                .speed(1.0)
+                .range(1.0..=10000.0)
+                .prefix("Far: "),
+        )
+        .changed();
+    changed |= ui.checkbox(&mut probe.dynamic, "Dynamic").changed();
+    if probe.dynamic {
+        changed |= ui
+            .add(
+                egui::DragValue::new(&mut probe.update_interval)
+                    .speed(1.0)
+                    .range(1..=600)
+                    .prefix("Every frames: "),
+            )
+            .changed();
+    }
+    if ui.button("Capture now").clicked() {
+        probe.request_capture = true;
+        changed = true;
+    }
+    changed
+}
+
+fn camera_editor(ui: &mut egui::Ui, camera: &mut Camera) -> bool {
+    let mut fov = camera.fov.to_degrees();
+    let mut changed = ui
+        .add(egui::Slider::new(&mut fov, 10.0..=150.0).text("FOV"))
+        .changed();
+    if changed {
+        camera.fov = fov.to_radians();
+    }
+    changed |= ui
+        .add(
+            egui::DragValue::new(&mut camera.near)
+                .speed(0.01)
+                .range(0.001..=10.0)
+                .prefix("Near: "),
+        )
+        .changed();
+    changed |= ui
+        .add(
+            egui::DragValue::new(&mut camera.far)
+                .speed(1.0)
+                .range(1.0..=100000.0)
+                .prefix("Far: "),
+        )
+        .changed();
+    changed
+}
+
+// Shows the live trauma and kick state, with buttons to try the effects
+fn camera_effects_editor(ui: &mut egui::Ui, effects: &mut CameraEffects) -> bool {
+    let mut changed = ui
+        .add(egui::Slider::new(&mut effects.trauma, 0.0..=1.0).text("Trauma"))
+        .changed();
+    changed |= ui
+        .add(
+            egui::DragValue::new(&mut effects.trauma_decay)
+                .speed(0.05)
+                .range(0.0..=10.0)
+                .prefix("Decay: "),
+        )
+        .changed();
+    changed |= ui
+        .add(
+            egui::DragValue::new(&mut effects.max_shake_offset)
+                .speed(0.01)
+                .range(0.0..=5.0)
+                .prefix("Max offset: "),
+        )
+        .changed();
+    let mut angle = effects.max_shake_angle.to_degrees();
+    if ui
+        .add(egui::Slider::new(&mut angle, 0.0..=45.0).text("Max angle"))
+        .changed()
+    {
+        effects.max_shake_angle = angle.to_radians();
+        changed = true;
+    }
+    changed |= ui
+        .add(
+            egui::DragValue::new(&mut effects.shake_frequency)
+                .speed(0.1)
+                .range(0.0..=100.0)
+                .prefix("Frequency: "),
+        )
+        .changed();
+    changed |= ui
+        .add(
+            egui::DragValue::new(&mut effects.kick_stiffness)
+                .speed(1.0)
+                .range(0.0..=2000.0)
+                .prefix("Kick stiffness: "),
+        )
+        .changed();
+    changed |= ui
+        .add(
+            egui::DragValue::new(&mut effects.kick_damping)
+                .speed(0.1)
+                .range(0.0..=200.0)
+                .prefix("Kick damping: "),
+        )
+        .changed();
+
+    let offset = effects.kick_offset();
+    let angles = effects.kick_angles();
+    ui.label(format!(
+        "Kick: {}, offset ({:.3}, {:.3}, {:.3}) m, angles ({:.2}, {:.2}, {:.2})°",
+        if effects.is_kicking() { "active" } else { "at rest" },
+        offset.x,
+        offset.y,
+        offset.z,
+        angles.x.to_degrees(),
+        angles.y.to_degrees(),
+        angles.z.to_degrees()
+    ));
+    ui.horizontal(|ui| {
+        if ui.button("Add trauma").clicked() {
+            effects.add_trauma(0.5);
+            changed = true;
+        }
+        if ui.button("Kick").clicked() {
+            effects.kick(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.5, 0.0, 0.0));
+            changed = true;
+        }
+    });
+
+    if let Some(follow) = &mut effects.follow {
+        ui.label(match follow.target {
+            Some(target) => format!("Following StableId {}", target.id.0),
+            None => "Following: no target".to_string(),
+        });
+        changed |= vec3_row(ui, "Offset", follow.offset.as_mut(), 0.05);
+        changed |= ui
+            .add(
+                egui::DragValue::new(&mut follow.stiffness)
+                    .speed(0.05)
+                    .range(0.0..=50.0)
+                    .prefix("Stiffness: "),
+            )
+            .changed();
+        changed |= ui.checkbox(&mut follow.look_at, "Look at target").changed();
+    }
+    changed
+}
+
+// Hand written for the autofocus toggle, which the generic editor can't add or remove
+fn depth_of_field_editor(ui: &mut egui::Ui, dof: &mut DepthOfField) -> bool {
+    let mut changed = ui
+        .add(
+            egui::DragValue::new(&mut dof.focus_distance)
+                .speed(0.05)
+                .range(0.01..=10000.0)
+                .prefix("Focus: "),
+        )
+        .changed();
+    changed |= ui
+        .add(
+            egui::Slider::new(&mut dof.f_stop, 0.7..=22.0)
+                .logarithmic(true)
+                .text("f-stop"),
+        )
+        .changed();
+    changed |= ui
+        .add(egui::Slider::new(&mut dof.max_blur_radius, 0.0..=MAX_BLUR_RADIUS).text("Max blur"))
+        .changed();
+
+    let mut autofocus = dof.autofocus.is_some();
+    if ui.checkbox(&mut autofocus, "Autofocus").changed() {
+        dof.autofocus = autofocus.then(Autofocus::default);
+        changed = true;
+    }
+    if let Some(autofocus) = &mut dof.autofocus {
+        ui.indent("autofocus", |ui| {
+            changed |= ui
+                .add(
+                    egui::DragValue::new(&mut autofocus.speed)
+                        .speed(0.1)
+                        .range(0.0..=100.0)
+                        .prefix("Speed: "),
+                )
+                .changed();
+            changed |= ui
+                .add(
+                    egui::DragValue::new(&mut autofocus.min_distance)
+                        .speed(0.05)
+                        .range(0.01..=10000.0)
+                        .prefix("Min: "),
+                )
+                .changed();
+            changed |= ui
+                .add(
+                    egui::DragValue::new(&mut autofocus.max_distance)
+                        .speed(0.5)
+                        .range(0.01..=10000.0)
+                        .prefix("Max: "),
+                )
+                .changed();
+        });
+    }
+    changed
+}
+
+fn vec3_row(ui: &mut egui::Ui, label: &str, value: &mut [f32; 3], speed: f32) -> bool {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        let mut changed = false;
+        for component in value.iter_mut() {
+            changed |= ui.add(egui::DragValue::new(component).speed(speed)).changed();
+        }
+        changed
+    })
+    .inner
+}
+
+fn color_row(ui: &mut egui::Ui, color: &mut Color) -> bool {
+    ui.horizontal(|ui| {
+        ui.label("Color");
+        color_button(ui, color, false)
+    })
+    .inner
+}
+
+/// egui's color button for a Color. egui edits linear values and shows them as sRGB, so
+/// colors round-trip unchanged; only an edit writes back. Without `alpha` it is kept.
+pub(crate) fn color_button(ui: &mut egui::Ui, color: &mut Color, alpha: bool) -> bool {
+    if alpha {
+        let mut rgba = color.to_linear();
+        let changed = ui.color_edit_button_rgba_unmultiplied(&mut rgba).changed();
+        if changed {
+            *color = rgba.into();
+        }
+        changed
+    } else {
+        let mut rgb = color.to_linear_rgb();
+        let changed = ui.color_edit_button_rgb(&mut rgb).changed();
+        if changed {
+            let [r, g, b] = rgb;
+            *color = Color::linear_rgba(r, g, b, color.a);
+        }
+        changed
+    }
+}
+
+/// Generic editor over a component's serialized form: numbers, bools and strings are
+/// editable, objects and arrays are walked. Returns true when anything changed.
+pub(crate) fn json_editor(ui: &mut egui::Ui, label: &str, value: &mut Value) -> bool {
+    match value {
+        Value::Null => {
+            ui.label(format!("{}: -", label));
+            false
+        }
+        Value::Bool(flag) => ui.checkbox(flag, label).changed(),
+        Value::Number(_) => {
+            ui.horizontal(|ui| {
+                ui.label(label);
+                number_editor(ui, value)
+            })
+            .inner
+        }
+        Value::String(text) => {
+            ui.horizontal(|ui| {
+                ui.label(label);
+                ui.text_edit_singleline(text).changed()
+            })
+            .inner
+        }
+        // Short number arrays (vectors, colors) on one row
+        Value::Array(items) if items.len() <= 4 && items.iter().all(Value::is_number) => {
+            ui.horizontal(|ui| {
+                ui.label(label);
+                let mut changed = false;
+                for item in items.iter_mut() {
+                    changed |= number_editor(ui, item);
+                }
+                changed
+            })
+            .inner
+        }
+        Value::Array(items) => {
+            let mut changed = false;
+            egui::CollapsingHeader::new(format!("{} [{}]", label, items.len()))
+                .id_salt(label)
+                .show(ui, |ui| {
+                    for (index, item) in items.iter_mut().enumerate() {
+                        changed |= json_editor(ui, &index.to_string(), item);
+                    }
+                });
+            changed
+        }
+        Value::Object(_) => {
+            ui.label(label);
+            ui.indent(label, |ui| json_fields_editor(ui, value)).inner
+        }
+    }
+}
+
+/// Edits the fields of an object one per row, or the value itself when it isn't one.
+pub(crate) fn json_fields_editor(ui: &mut egui::Ui, value: &mut Value) -> bool {
+    let Value::Object(fields) = value else {
+        return json_editor(ui, "Value", value);
+    };
+
+    let mut changed = false;
+    for (name, field) in fields.iter_mut() {
+        changed |= json_editor(ui, name, field);
+    }
+    changed
+}
+
+// Integers stay integers, so they deserialize back into integer fields
+fn number_editor(ui: &mut egui::Ui, value: &mut Value) -> bool {
+    if let Some(mut integer) = value.as_i64() {
+        let changed = ui.add(egui::DragValue::new(&mut integer)).changed();
+        if changed {
+            *value = Value::from(integer);
+        }
+        changed
+    } else {
+        let mut float = value.as_f64().unwrap_or_default();
+        let changed = ui.add(egui::DragValue::new(&mut float).speed(0.01)).changed();
+        if changed {
+            *value = Value::from(float);
+        }
+        changed
+    }
+}
@@ -0,0 +1,75 @@
+use catalyst_renderer::ExposureState;
+use flecs_ecs::prelude::*;
+
+const LUMINANCE_COLOR: egui::Color32 = egui::Color32::from_rgb(230, 200, 80);
+const EXPOSURE_COLOR: egui::Color32 = egui::Color32::from_rgb(100, 180, 240);
+
+/// Current values and a plot of the last frames for every camera with an Exposure.
+/// Both curves are drawn in stops (log2), each scaled to its own range.
+pub fn exposure_section(ui: &mut egui::Ui, world: &WorldRef) {
+    world.get::<&ExposureState>(|state| {
+        for (camera, exposure) in state.iter() {
+            let name = world.entity_from_id(camera).name();
+            ui.label(format!(
+                "{}: exposure {:.3}, average luminance {}",
+                name,
+                exposure.exposure,
+                exposure
+                    .average_luminance
+                    .map(|luminance| format!("{:.3}", luminance))
+                    .unwrap_or_else(|| "-".to_string()),
+            ));
+
+            let (rect, _) = ui.allocate_exact_size(
+                egui::vec2(ui.available_width().max(200.0), 60.0),
+                egui::Sense::hover(),
+            );
+            let painter = ui.painter_at(rect);
+            painter.rect_filled(rect, 2.0, egui::Color32::from_gray(24));
+
+            let luminance: Vec<f32> = exposure
+                .history
+                .iter()
+                .map(|(luminance, _)| luminance.max(f32::EPSILON).log2())
+                .collect();
+            let exposures: Vec<f32> = exposure
+                .history
+                .iter()
+                .map(|(_, exposure)| exposure.max(f32::EPSILON).log2())
+                .collect();
+
+            painter.add(plot_line(rect, &luminance, LUMINANCE_COLOR));
+            painter.add(plot_line(rect, &exposures, EXPOSURE_COLOR));
+        }
+
+        if state.iter().next().is_none() {
+            ui.label("No camera has an Exposure component.");
+        } else {
+            ui.horizontal(|ui| {
+                ui.colored_label(LUMINANCE_COLOR, "average luminance");
+                ui.colored_label(EXPOSURE_COLOR, "exposure");
+            });
+        }
+    });
+}
+
+pub(crate) fn plot_line(rect: egui::Rect, values: &[f32], color: egui::Color32) -> egui::Shape {
+    let (min, max) = values
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(min, max), value| (min.min(*value), max.max(*value)));
+    // Flat curves sit in the middle instead of dividing by zero
+    let range = (max - min).max(0.5);
+    let mid = (min + max) * 0.5;
+
+    let step = rect.width() / (values.len().max(2) - 1) as f32;
+    let points = values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| {
+            let t = 0.5 + (value - mid) / range * 0.9;
+            egui::pos2(rect.left() + i as f32 * step, rect.bottom() - t * rect.height())
+        })
+        .collect();
+
+    egui::Shape::line(points, egui::Stroke::new(1.5, color))
+}
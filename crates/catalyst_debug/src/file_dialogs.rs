@@ -0,0 +1,191 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use catalyst_core::App;
+use flecs_ecs::prelude::*;
+
+/// Names a file dialog asked for with `FileDialogs`, and the answer to it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FileDialogRequestId(pub u64);
+
+/// Event: the user closed a file dialog. Emitted on the FileDialogs singleton entity by
+/// "Deliver File Dialog Results" at the start of the frame after it closed:
+///
+/// ```ignore
+/// world
+///     .entity_from::<FileDialogs>()
+///     .observe_payload_entity::<&FileDialogResult>(move |_, result| {
+///         if Some(result.request_id) == pending {
+///             // ...
+///         }
+///     });
+/// ```
+///
+/// Every request gets exactly one result. Check `request_id` against the one `pick_*`
+/// returned: other dialogs, yours included, may still be open or answer first.
+#[derive(Component, Clone, Debug)]
+pub struct FileDialogResult {
+    pub request_id: FileDialogRequestId,
+    /// None when the dialog was cancelled, or the crate was built without the
+    /// `file-dialogs` feature.
+    pub path: Option<PathBuf>,
+}
+
+impl FileDialogResult {
+    pub fn cancelled(&self) -> bool {
+        self.path.is_none()
+    }
+}
+
+/// A file type the dialog offers, e.g. `FileFilter::new("Scenes", &["glb", "gltf"])`.
+#[derive(Clone, Debug)]
+pub struct FileFilter {
+    pub name: String,
+    /// Without the dot.
+    pub extensions: Vec<String>,
+}
+
+impl FileFilter {
+    pub fn new(name: &str, extensions: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            extensions: extensions
+                .iter()
+                .map(|extension| extension.to_string())
+                .collect(),
+        }
+    }
+}
+
+enum DialogKind {
+    OpenFile,
+    SaveFile(String),
+    Folder,
+}
+
+/// Native open, save and folder dialogs (rfd) that never block the frame. Each `pick_*`
+/// opens one and returns at once with its request id; the answer comes back as a
+/// FileDialogResult event once the user closes it, while the window keeps pumping
+/// events and drawing frames. Several dialogs can be pending at once.
+///
+/// The dialogs are created on the calling thread, the main one for systems (which
+/// macOS requires), and waited for on a thread of their own. Without the `file-dialogs`
+/// feature, for headless builds, every request is answered as cancelled.
+#[derive(Component, Default)]
+pub struct FileDialogs {
+    next_id: u64,
+    pending: usize,
+    results: Arc<Mutex<Vec<FileDialogResult>>>,
+}
+
+impl FileDialogs {
+    /// Asks for an existing file of one of the `filters`' types, any file without filters.
+    pub fn pick_open_file(&mut self, filters: &[FileFilter]) -> FileDialogRequestId {
+        self.open(DialogKind::OpenFile, filters)
+    }
+
+    /// Asks where to save a file, suggesting `default_name`.
+    pub fn pick_save_file(
+        &mut self,
+        default_name: &str,
+        filters: &[FileFilter],
+    ) -> FileDialogRequestId {
+        self.open(DialogKind::SaveFile(default_name.to_string()), filters)
+    }
+
+    pub fn pick_folder(&mut self) -> FileDialogRequestId {
+        self.open(DialogKind::Folder, &[])
+    }
+
+    /// Dialogs still open, or answered since the last delivery.
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+
+    fn open(&mut self, kind: DialogKind, filters: &[FileFilter]) -> FileDialogRequestId {
+        let request_id = FileDialogRequestId(self.next_id);
+        self.next_id += 1;
+        self.pending += 1;
+        spawn_dialog(request_id, kind, filters, self.results.clone());
+        request_id
+    }
+
+    fn take_results(&mut self) -> Vec<FileDialogResult> {
+        let results = std::mem::take(&mut *self.results.lock().unwrap());
+        self.pending -= results.len();
+        results
+    }
+}
+
+#[cfg(feature = "file-dialogs")]
+fn spawn_dialog(
+    request_id: FileDialogRequestId,
+    kind: DialogKind,
+    filters: &[FileFilter],
+    results: Arc<Mutex<Vec<FileDialogResult>>>,
+) {
+    let mut dialog = rfd::AsyncFileDialog::new();
+    for filter in filters {
+        dialog = dialog.add_filter(&filter.name, &filter.extensions[..]);
+    }
+    // Shown by now; the futures only wait for the user
+    let answer: std::pin::Pin<Box<dyn Future<Output = Option<PathBuf>> + Send>> = match kind {
+        DialogKind::OpenFile => {
+            let file = dialog.pick_file();
+            Box::pin(async move { file.await.map(|file| file.path().to_path_buf()) })
+        }
+        DialogKind::SaveFile(default_name) => {
+            let file = dialog.set_file_name(default_name).save_file();
+            Box::pin(async move { file.await.map(|file| file.path().to_path_buf()) })
+        }
+        DialogKind::Folder => {
+            let folder = dialog.pick_folder();
+            Box::pin(async move { folder.await.map(|folder| folder.path().to_path_buf()) })
+        }
+    };
+    std::thread::spawn(move || {
+        let path = pollster::block_on(answer);
+        results
+            .lock()
+            .unwrap()
+            .push(FileDialogResult { request_id, path });
+    });
+}
+
+#[cfg(not(feature = "file-dialogs"))]
+fn spawn_dialog(
+    request_id: FileDialogRequestId,
+    _kind: DialogKind,
+    _filters: &[FileFilter],
+    results: Arc<Mutex<Vec<FileDialogResult>>>,
+) {
+    log::warn!("File dialogs are unavailable: catalyst_debug was built without `file-dialogs`");
+    results.lock().unwrap().push(FileDialogResult {
+        request_id,
+        path: None,
+    });
+}
+
+pub(crate) fn register_file_dialogs(app: &mut App) {
+    app.register_singleton_default::<FileDialogs>();
+
+    // Taken out first, so observers can open the next dialog
+    app.world
+        .system_named::<()>("Deliver File Dialog Results")
+        .kind(flecs::pipeline::PreUpdate)
+        .run(|iter| {
+            let world = iter.world();
+            let results = world.get::<&mut FileDialogs>(|dialogs| {
+                if dialogs.pending == 0 {
+                    Vec::new()
+                } else {
+                    dialogs.take_results()
+                }
+            });
+            for result in results {
+                world.entity_from::<FileDialogs>().emit(&result);
+            }
+        });
+}
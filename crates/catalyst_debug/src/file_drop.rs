@@ -0,0 +1,152 @@
+use std::{
+    path::Path,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use catalyst_assets::{
+    AssetSource, LoadScene, asset_server::AssetServer, assets::Handle, material::TextureData,
+};
+use catalyst_core::{
+    App,
+    camera::Camera,
+    transform::{GlobalTransform, Transform},
+};
+use catalyst_window::{FileDropSettings, FileDropped, FileHover, MainWindow};
+use flecs_ecs::prelude::*;
+use glam::Vec3;
+
+// How far in front of the camera dropped and opened scenes are placed
+const DROP_DISTANCE: f32 = 5.0;
+
+const IMAGE_EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "bmp", "tga"];
+
+/// The texture shown in the Debug Texture window, e.g. the last image dropped on the
+/// window. None shows the first one registered.
+#[derive(Component, Clone, Debug, Default)]
+pub struct DebugTextureView {
+    pub texture: Option<Handle<TextureData>>,
+}
+
+// Default handler for files dropped on the window, off with FileDropSettings
+pub(crate) fn register_file_drop_handler(app: &mut App) {
+    app.register_singleton_default::<DebugTextureView>();
+
+    let cameras = app
+        .world
+        .query::<&GlobalTransform>()
+        .with(Camera::id())
+        .build();
+
+    app.world
+        .entity_from::<MainWindow>()
+        .observe_payload_entity::<&FileDropped>(move |window, dropped| {
+            let world = window.world();
+            if !world.get::<&FileDropSettings>(|settings| settings.default_handler) {
+                return;
+            }
+
+            let path = dropped.path.to_string_lossy().to_string();
+            let extension = dropped
+                .path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .map(|extension| extension.to_ascii_lowercase());
+
+            match extension.as_deref() {
+                Some("glb" | "gltf") => {
+                    spawn_scene(&world, &cameras, &dropped.path, "dropped");
+                    log::info!("Loading dropped scene '{}'", path);
+                }
+                Some(extension) if IMAGE_EXTENSIONS.contains(&extension) => {
+                    let texture = world.get::<&AssetServer>(|server| server.load_texture(&path));
+                    world.get::<&mut DebugTextureView>(|view| view.texture = Some(texture));
+                    log::info!(
+                        "Loading dropped texture '{}' into the Debug Texture window",
+                        path
+                    );
+                }
+                _ => log::warn!(
+                    "Can't load dropped file '{}': only .glb, .gltf and {} are handled",
+                    path,
+                    IMAGE_EXTENSIONS
+                        .map(|extension| format!(".{}", extension))
+                        .join(", ")
+                ),
+            }
+        });
+}
+
+/// Loads the scene at `path` in front of the camera, named `<prefix>_<file stem>_<n>`.
+pub(crate) fn spawn_scene(
+    world: &WorldRef,
+    cameras: &Query<&GlobalTransform>,
+    path: &Path,
+    prefix: &str,
+) {
+    let mut focus = Vec3::ZERO;
+    cameras.each(|global| {
+        focus = global.0.w_axis.truncate() - global.0.z_axis.truncate() * DROP_DISTANCE;
+    });
+    world
+        .entity_named(&scene_name(path, prefix))
+        .set(AssetSource {
+            path: path.to_string_lossy().to_string(),
+        })
+        .add(LoadScene)
+        .set(Transform::from_xyz(focus.x, focus.y, focus.z))
+        .set(GlobalTransform::default());
+}
+
+// Numbered, so the same file can be loaded more than once
+fn scene_name(path: &Path, prefix: &str) -> String {
+    static LOADED: AtomicU32 = AtomicU32::new(0);
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    format!(
+        "{}_{}_{}",
+        prefix,
+        stem,
+        LOADED.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// A thin border around the window and the file names while files are dragged over it.
+/// Returns whether there is anything to draw.
+pub(crate) fn file_hover_overlay(ctx: &egui::Context, world: &WorldRef) -> bool {
+    let paths = world.get::<&FileHover>(|hover| hover.paths.clone());
+    if paths.is_empty() {
+        return false;
+    }
+
+    let painter = ctx.layer_painter(egui::LayerId::new(
+        egui::Order::Foreground,
+        egui::Id::new("file_hover"),
+    ));
+    let screen = ctx.content_rect().shrink(2.0);
+    let accent = egui::Color32::from_rgba_unmultiplied(120, 180, 255, 180);
+    painter.rect_stroke(
+        screen,
+        4.0,
+        egui::Stroke::new(3.0, accent),
+        egui::StrokeKind::Inside,
+    );
+
+    let names: Vec<String> = paths
+        .iter()
+        .map(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default()
+        })
+        .collect();
+    painter.text(
+        screen.center_bottom() - egui::vec2(0.0, 24.0),
+        egui::Align2::CENTER_BOTTOM,
+        format!("Drop to load {}", names.join(", ")),
+        egui::FontId::proportional(16.0),
+        accent,
+    );
+    true
+}
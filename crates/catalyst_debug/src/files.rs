@@ -0,0 +1,109 @@
+use std::path::Path;
+
+use catalyst_core::{App, camera::Camera, transform::GlobalTransform};
+use catalyst_renderer::Screenshots;
+use flecs_ecs::prelude::*;
+
+use crate::{
+    file_dialogs::{FileDialogRequestId, FileDialogResult, FileDialogs, FileFilter},
+    file_drop::spawn_scene,
+};
+
+/// The dialogs the Files section is waiting for, and what came of the last one.
+#[derive(Component, Default)]
+pub(crate) struct FilesState {
+    open_scene: Option<FileDialogRequestId>,
+    screenshot: Option<FileDialogRequestId>,
+    status: String,
+}
+
+pub(crate) fn register_files(app: &mut App) {
+    app.register_singleton_default::<FilesState>();
+
+    let cameras = app
+        .world
+        .query::<&GlobalTransform>()
+        .with(Camera::id())
+        .build();
+
+    app.world
+        .entity_from::<FileDialogs>()
+        .observe_payload_entity::<&FileDialogResult>(move |dialogs, result| {
+            let world = dialogs.world();
+            let (open_scene, screenshot) = world.get::<&mut FilesState>(|state| {
+                let open_scene = state.open_scene == Some(result.request_id);
+                let screenshot = state.screenshot == Some(result.request_id);
+                if open_scene {
+                    state.open_scene = None;
+                }
+                if screenshot {
+                    state.screenshot = None;
+                }
+                (open_scene, screenshot)
+            });
+            if !open_scene && !screenshot {
+                return;
+            }
+
+            let status = match &result.path {
+                None => "Cancelled".to_string(),
+                Some(path) if open_scene => {
+                    let path = relative_to_working_dir(path);
+                    spawn_scene(&world, &cameras, Path::new(&path), "opened");
+                    format!("Loading scene '{}'", path)
+                }
+                Some(path) => {
+                    let path = relative_to_working_dir(path);
+                    world.get::<&mut Screenshots>(|screenshots| screenshots.request(&path));
+                    format!("Saving the next frame to {}", path)
+                }
+            };
+            world.get::<&mut FilesState>(|state| state.status = status);
+        });
+}
+
+/// "Open scene…" and a screenshot "Save as…", both through the native file dialogs.
+pub(crate) fn files_section(ui: &mut egui::Ui, world: &WorldRef) {
+    world.get::<&mut FilesState>(|state| {
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(state.open_scene.is_none(), egui::Button::new("Open scene…"))
+                .clicked()
+            {
+                let filters = [FileFilter::new("glTF scenes", &["glb", "gltf"])];
+                state.open_scene =
+                    Some(world.get::<&mut FileDialogs>(|dialogs| dialogs.pick_open_file(&filters)));
+            }
+            if ui
+                .add_enabled(
+                    state.screenshot.is_none(),
+                    egui::Button::new("Screenshot: Save as…"),
+                )
+                .clicked()
+            {
+                let filters = [FileFilter::new("PNG images", &["png"])];
+                state.screenshot = Some(world.get::<&mut FileDialogs>(|dialogs| {
+                    dialogs.pick_save_file("screenshot.png", &filters)
+                }));
+            }
+        });
+        if state.open_scene.is_some() || state.screenshot.is_some() {
+            ui.label("Waiting for the file dialog");
+        } else if !state.status.is_empty() {
+            ui.label(&state.status);
+        }
+    });
+}
+
+/// `path` relative to the working directory when it is inside it, the way asset paths
+/// are usually written, and as is otherwise.
+pub(crate) fn relative_to_working_dir(path: &Path) -> String {
+    let relative = std::env::current_dir()
+        .ok()
+        .and_then(|dir| path.strip_prefix(dir).ok().map(Path::to_path_buf));
+    relative
+        .as_deref()
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
@@ -0,0 +1,25 @@
+use catalyst_renderer::FogSettings;
+use flecs_ecs::prelude::*;
+
+use crate::editors::color_button;
+
+/// Edits the FogSettings singleton in place.
+pub fn fog_section(ui: &mut egui::Ui, world: &WorldRef) {
+    world.get::<&mut FogSettings>(|fog| {
+        ui.checkbox(&mut fog.enabled, "Enabled");
+        ui.horizontal(|ui| {
+            color_button(ui, &mut fog.color, false);
+            ui.label("Color");
+        });
+        ui.add(
+            egui::Slider::new(&mut fog.density, 0.0..=0.5)
+                .logarithmic(true)
+                .text("Density"),
+        );
+        ui.add(egui::Slider::new(&mut fog.height_falloff, 0.0..=2.0).text("Height falloff"));
+        ui.add(egui::Slider::new(&mut fog.base_height, -50.0..=50.0).text("Base height"));
+        ui.add(egui::Slider::new(&mut fog.start_distance, 0.0..=100.0).text("Start distance"));
+        ui.add(egui::Slider::new(&mut fog.max_opacity, 0.0..=1.0).text("Max opacity"));
+        ui.add(egui::Slider::new(&mut fog.sun_anisotropy, -0.9..=0.9).text("Sun anisotropy"));
+    });
+}
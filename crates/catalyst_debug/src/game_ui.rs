@@ -0,0 +1,29 @@
+use catalyst_core::App;
+use flecs_ecs::prelude::*;
+
+/// Draws game UI with egui; returns whether it drew anything this frame.
+pub type GameUiFn = fn(&egui::Context, &WorldRef) -> bool;
+
+/// UI drawn by the game itself (menus, prompts) through the debug layer's egui pass,
+/// with the debug windows open or not. Add to it with `register_game_ui`.
+#[derive(Component, Default)]
+pub struct GameUi {
+    windows: Vec<GameUiFn>,
+}
+
+/// Draws `ui` every frame over the game, debug windows open or not. It should check
+/// itself whether it has anything to show, e.g. only while a menu state is current.
+pub fn register_game_ui(app: &mut App, ui: GameUiFn) {
+    app.world
+        .get::<&mut GameUi>(|game_ui| game_ui.windows.push(ui));
+}
+
+// Copied out first, so the windows can reach any singleton
+pub(crate) fn game_ui_overlay(ctx: &egui::Context, world: &WorldRef) -> bool {
+    let windows = world.get::<&GameUi>(|game_ui| game_ui.windows.clone());
+    let mut drawn = false;
+    for window in windows {
+        drawn |= window(ctx, world);
+    }
+    drawn
+}
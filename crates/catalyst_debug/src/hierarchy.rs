@@ -0,0 +1,58 @@
+use catalyst_core::transform::GlobalTransform;
+use catalyst_renderer::render::DebugDraw3D;
+use flecs_ecs::prelude::*;
+use glam::{Vec3, Vec4};
+
+const TRIAD_SIZE: f32 = 0.1;
+const LINK_COLOR: Vec4 = Vec4::new(1.0, 1.0, 1.0, 1.0);
+
+/// On an entity whose node hierarchy is drawn: a line from every node to each of its
+/// children and a small axis triad (X red, Y green, Z blue) at every node. Meant for
+/// rigs (every joint of a glTF skeleton is a node) and nested scenes. Toggled from the
+/// inspector; drawn with the debug GUI closed too.
+#[derive(Component)]
+pub struct ShowHierarchy;
+
+pub fn debug_hierarchy_system(app: &mut catalyst_core::App) {
+    app.world.component::<ShowHierarchy>();
+
+    app.world
+        .system_named::<(&GlobalTransform, &mut DebugDraw3D)>("debug_hierarchy")
+        .with(ShowHierarchy)
+        .kind(flecs::pipeline::OnUpdate)
+        .each_entity(|entity, (global, debug)| {
+            draw_node(entity, global, debug);
+        });
+}
+
+/// Nodes below `entity` that have a GlobalTransform, all the way down.
+pub fn hierarchy_size(entity: EntityView) -> usize {
+    let mut count = 0;
+    entity.each_child(|child| {
+        if child.has(GlobalTransform::id()) {
+            count += 1 + hierarchy_size(child);
+        }
+    });
+    count
+}
+
+fn draw_node(node: EntityView, global: &GlobalTransform, debug: &mut DebugDraw3D) {
+    let origin = global.transform_point3(Vec3::ZERO);
+    for (axis, color) in [
+        (global.x_axis, Vec4::new(1.0, 0.2, 0.2, 1.0)),
+        (global.y_axis, Vec4::new(0.2, 1.0, 0.2, 1.0)),
+        (global.z_axis, Vec4::new(0.3, 0.5, 1.0, 1.0)),
+    ] {
+        let direction = axis.truncate().normalize_or_zero();
+        debug.push_line(origin, origin + direction * TRIAD_SIZE, color);
+    }
+
+    // Children without a GlobalTransform aren't placed, nor is anything below them
+    node.each_child(|child| {
+        let Some(child_global) = child.try_get::<&GlobalTransform>(|global| *global) else {
+            return;
+        };
+        debug.push_line(origin, child_global.transform_point3(Vec3::ZERO), LINK_COLOR);
+        draw_node(child, &child_global, debug);
+    });
+}
@@ -0,0 +1,200 @@
+use catalyst_core::{
+    console::ConsoleCommands,
+    debug_hud::{DebugHud, HudValue},
+};
+use flecs_ecs::prelude::*;
+
+const PLOT_SIZE: egui::Vec2 = egui::vec2(160.0, 32.0);
+
+/// Screen corner the HUD sits in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HudCorner {
+    #[default]
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl HudCorner {
+    pub const ALL: [HudCorner; 4] = [
+        HudCorner::TopLeft,
+        HudCorner::TopRight,
+        HudCorner::BottomLeft,
+        HudCorner::BottomRight,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HudCorner::TopLeft => "top-left",
+            HudCorner::TopRight => "top-right",
+            HudCorner::BottomLeft => "bottom-left",
+            HudCorner::BottomRight => "bottom-right",
+        }
+    }
+
+    fn anchor(self) -> (egui::Align2, egui::Vec2) {
+        const MARGIN: f32 = 8.0;
+        match self {
+            HudCorner::TopLeft => (egui::Align2::LEFT_TOP, egui::vec2(MARGIN, MARGIN)),
+            HudCorner::TopRight => (egui::Align2::RIGHT_TOP, egui::vec2(-MARGIN, MARGIN)),
+            HudCorner::BottomLeft => (egui::Align2::LEFT_BOTTOM, egui::vec2(MARGIN, -MARGIN)),
+            HudCorner::BottomRight => (egui::Align2::RIGHT_BOTTOM, egui::vec2(-MARGIN, -MARGIN)),
+        }
+    }
+}
+
+/// How the DebugHud widgets are drawn. They show with the debug windows closed too,
+/// and never take the mouse. Also set with the hud.* console commands.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct HudSettings {
+    pub enabled: bool,
+    pub corner: HudCorner,
+    pub font_size: f32,
+    /// A widget not refreshed for this many frames is dropped.
+    pub expire_frames: u64,
+}
+
+impl Default for HudSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            corner: HudCorner::TopLeft,
+            font_size: 13.0,
+            expire_frames: 10,
+        }
+    }
+}
+
+/// Draws the DebugHud widgets in their corner and starts the HUD's next frame.
+/// Returns whether there is anything to draw.
+pub(crate) fn hud_overlay(ctx: &egui::Context, world: &WorldRef) -> bool {
+    let settings = world.get::<&HudSettings>(|settings| *settings);
+    let widgets = DebugHud::end_frame(settings.expire_frames);
+    if !settings.enabled || widgets.is_empty() {
+        return false;
+    }
+
+    let font = egui::FontId::monospace(settings.font_size);
+    let text_color = egui::Color32::from_gray(230);
+    let (align, offset) = settings.corner.anchor();
+
+    egui::Area::new(egui::Id::new("debug_hud"))
+        .anchor(align, offset)
+        .order(egui::Order::Foreground)
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::new()
+                .fill(egui::Color32::from_black_alpha(150))
+                .corner_radius(4.0)
+                .inner_margin(6.0)
+                .show(ui, |ui| {
+                    for widget in &widgets {
+                        let label = |text: String| {
+                            egui::RichText::new(text)
+                                .font(font.clone())
+                                .color(text_color)
+                        };
+                        match &widget.value {
+                            HudValue::Text(text) => {
+                                ui.label(label(format!("{}: {}", widget.name, text)));
+                            }
+                            HudValue::Plot(samples) => {
+                                let latest = samples.back().copied().unwrap_or_default();
+                                ui.label(label(format!("{}: {:.2}", widget.name, latest)));
+                                plot(ui, samples.iter().copied());
+                            }
+                            HudValue::Progress(fraction) => {
+                                ui.label(label(widget.name.clone()));
+                                ui.add(
+                                    egui::ProgressBar::new(*fraction)
+                                        .desired_width(PLOT_SIZE.x)
+                                        .show_percentage(),
+                                );
+                            }
+                        }
+                    }
+                });
+        });
+    true
+}
+
+// A line through the samples, scaled to their range
+fn plot(ui: &mut egui::Ui, samples: impl ExactSizeIterator<Item = f32> + Clone) {
+    let (rect, _) = ui.allocate_exact_size(PLOT_SIZE, egui::Sense::hover());
+    let painter = ui.painter();
+    painter.rect_filled(rect, 2.0, egui::Color32::from_black_alpha(100));
+
+    let count = samples.len();
+    if count < 2 {
+        return;
+    }
+    let (min, max) = samples
+        .clone()
+        .fold((f32::MAX, f32::MIN), |(min, max), value| {
+            (min.min(value), max.max(value))
+        });
+    let range = (max - min).max(f32::EPSILON);
+
+    let step = rect.width() / (catalyst_core::debug_hud::PLOT_SAMPLES - 1) as f32;
+    // Right aligned, so the newest sample is always at the same spot
+    let start = rect.right() - step * (count - 1) as f32;
+    let points = samples
+        .enumerate()
+        .map(|(index, value)| {
+            egui::pos2(
+                start + step * index as f32,
+                rect.bottom() - (value - min) / range * rect.height(),
+            )
+        })
+        .collect();
+    painter.add(egui::Shape::line(
+        points,
+        egui::Stroke::new(1.0, egui::Color32::LIGHT_GREEN),
+    ));
+}
+
+pub(crate) fn register_hud_commands(commands: &mut ConsoleCommands) {
+    commands.register(
+        "hud",
+        "hud <on|off> - show or hide the debug HUD",
+        |world, args| {
+            let enabled = match args.first().copied() {
+                Some("on") => true,
+                Some("off") => false,
+                _ => return Err("usage: hud <on|off>".to_string()),
+            };
+            world.get::<&mut HudSettings>(|settings| settings.enabled = enabled);
+            Ok(format!(
+                "Debug HUD {}",
+                if enabled { "shown" } else { "hidden" }
+            ))
+        },
+    );
+    commands.register(
+        "hud.corner",
+        "hud.corner <top-left|top-right|bottom-left|bottom-right> - move the debug HUD",
+        |world, args| {
+            let arg = args.first().ok_or("usage: hud.corner <corner>")?;
+            let corner = HudCorner::ALL
+                .into_iter()
+                .find(|corner| corner.name() == *arg)
+                .ok_or_else(|| format!("'{}' isn't a corner", arg))?;
+            world.get::<&mut HudSettings>(|settings| settings.corner = corner);
+            Ok(format!("Debug HUD moved to the {} corner", corner.name()))
+        },
+    );
+    commands.register(
+        "hud.font_size",
+        "hud.font_size <points> - text size of the debug HUD",
+        |world, args| {
+            let arg = args.first().ok_or("usage: hud.font_size <points>")?;
+            let size = match arg.parse::<f32>() {
+                Ok(size) if (4.0..=64.0).contains(&size) => size,
+                _ => return Err(format!("'{}' isn't a font size between 4 and 64", arg)),
+            };
+            world.get::<&mut HudSettings>(|settings| settings.font_size = size);
+            Ok(format!("Debug HUD font size set to {}", size))
+        },
+    );
+}
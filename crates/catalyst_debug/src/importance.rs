@@ -0,0 +1,80 @@
+use catalyst_core::{importance::ImportanceBias, transform::GlobalTransform};
+use catalyst_renderer::{
+    mesh::{AssetMesh, MeshBounds},
+    render::DebugDraw3D,
+};
+use flecs_ecs::prelude::*;
+use glam::{Vec3, Vec4};
+
+use crate::GuiState;
+
+const UNIMPORTANT_COLOR: Vec4 = Vec4::new(0.4, 0.4, 0.4, 1.0);
+const LOW_COLOR: Vec4 = Vec4::new(0.2, 0.4, 1.0, 1.0);
+const HIGH_COLOR: Vec4 = Vec4::new(1.0, 0.2, 0.1, 1.0);
+
+/// Boxes around every entity with an ImportanceBias, from blue (no bias) to red (at
+/// `full_scale` world units and above). Entities with a negative bias are grey.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ImportanceDebug {
+    pub draw: bool,
+    pub full_scale: f32,
+}
+
+impl Default for ImportanceDebug {
+    fn default() -> Self {
+        Self {
+            draw: false,
+            full_scale: 30.0,
+        }
+    }
+}
+
+impl ImportanceDebug {
+    pub fn color(&self, importance: f32) -> Vec4 {
+        if importance < 0.0 {
+            return UNIMPORTANT_COLOR;
+        }
+        LOW_COLOR.lerp(
+            HIGH_COLOR,
+            (importance / self.full_scale.max(f32::EPSILON)).min(1.0),
+        )
+    }
+}
+
+pub fn debug_importance_system(app: &mut catalyst_core::App) {
+    app.register_singleton_default::<ImportanceDebug>();
+
+    app.world
+        .system_named::<(
+            &ImportanceBias,
+            &GlobalTransform,
+            &ImportanceDebug,
+            &GuiState,
+            &mut DebugDraw3D,
+        )>("debug_importance")
+        .kind(flecs::pipeline::OnUpdate)
+        .each_entity(|entity, (importance, global, settings, gui_state, debug)| {
+            if !gui_state.enabled || !settings.draw {
+                return;
+            }
+
+            // The mesh's bounds, or a small box around entities without one
+            let bounds = entity
+                .target(AssetMesh, 0)
+                .and_then(|mesh| mesh.try_get::<&MeshBounds>(|bounds| bounds.0));
+            let (center, half_extents) = match bounds {
+                Some(bounds) => (bounds.center(), bounds.half_extents()),
+                None => (Vec3::ZERO, Vec3::splat(0.25)),
+            };
+            let corner = |x: f32, y: f32, z: f32| {
+                global.transform_point3(center + half_extents * Vec3::new(x, y, z))
+            };
+
+            let color = settings.color(importance.total());
+            for (x, y) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+                debug.push_line(corner(x, y, -1.0), corner(x, y, 1.0), color);
+                debug.push_line(corner(x, -1.0, y), corner(x, 1.0, y), color);
+                debug.push_line(corner(-1.0, x, y), corner(1.0, x, y), color);
+            }
+        });
+}
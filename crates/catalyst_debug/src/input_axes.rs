@@ -0,0 +1,155 @@
+use catalyst_input::{
+    physical::InputState,
+    processing::{AxisProcessor, AxisSettings, ResponseCurve},
+};
+use flecs_ecs::prelude::*;
+
+const CURVE_COLOR: egui::Color32 = egui::Color32::from_rgb(100, 180, 240);
+const RAW_COLOR: egui::Color32 = egui::Color32::from_rgb(230, 200, 80);
+
+// Inputs the curve is drawn over: a stick's deflection, or a mouse speed in pixels
+// per second
+const STICK_RANGE: f32 = 1.0;
+const PER_FRAME_RANGE: f32 = 2000.0;
+
+/// Edits the AxisSettings singleton in place, with each processor's curve and the
+/// axis's raw and processed values this frame.
+pub fn input_axes_section(ui: &mut egui::Ui, world: &WorldRef) {
+    world.get::<&InputState>(|input| {
+        world.get::<&mut AxisSettings>(|settings| {
+            if settings.processors.is_empty() {
+                ui.label("No axis is processed.");
+            }
+            for processor in &mut settings.processors {
+                let raw = input.raw_axis(processor.axis);
+                let processed = input.axis(processor.axis);
+                egui::CollapsingHeader::new(format!("Axis {}", processor.axis.0))
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        ui.label(format!("Raw {:.4}, processed {:.4}", raw, processed));
+                        processor_editor(ui, processor);
+                        curve_plot(ui, processor, raw);
+                    });
+            }
+        });
+    });
+}
+
+fn processor_editor(ui: &mut egui::Ui, processor: &mut AxisProcessor) {
+    let deadzone_range = if processor.per_frame {
+        0.0..=200.0
+    } else {
+        0.0..=0.5
+    };
+    ui.add(egui::Slider::new(&mut processor.deadzone, deadzone_range).text("Deadzone"));
+    ui.add(
+        egui::Slider::new(&mut processor.sensitivity, 0.0001..=10.0)
+            .logarithmic(true)
+            .text("Sensitivity"),
+    );
+    ui.add(egui::Slider::new(&mut processor.smoothing, 0.0..=0.5).text("Smoothing (s)"));
+    ui.checkbox(&mut processor.invert, "Invert");
+    ui.checkbox(&mut processor.per_frame, "Per frame (mouse)");
+
+    let name = match processor.curve {
+        ResponseCurve::Linear => "Linear",
+        ResponseCurve::Power(_) => "Power",
+        ResponseCurve::Points(_) => "Points",
+    };
+    egui::ComboBox::from_id_salt(("axis curve", processor.axis.0))
+        .selected_text(name)
+        .show_ui(ui, |ui| {
+            if ui.selectable_label(name == "Linear", "Linear").clicked() {
+                processor.curve = ResponseCurve::Linear;
+            }
+            if ui.selectable_label(name == "Power", "Power").clicked() && name != "Power" {
+                processor.curve = ResponseCurve::Power(2.0);
+            }
+            if ui.selectable_label(name == "Points", "Points").clicked() && name != "Points" {
+                processor.curve = ResponseCurve::Points(vec![[0.5, 0.25], [1.0, 1.0]]);
+            }
+        });
+    match &mut processor.curve {
+        ResponseCurve::Linear => {}
+        ResponseCurve::Power(exponent) => {
+            ui.add(egui::Slider::new(exponent, 0.2..=5.0).text("Exponent"));
+        }
+        ResponseCurve::Points(points) => {
+            for (index, point) in points.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{}:", index));
+                    ui.add(
+                        egui::DragValue::new(&mut point[0])
+                            .speed(0.01)
+                            .prefix("in "),
+                    );
+                    ui.add(
+                        egui::DragValue::new(&mut point[1])
+                            .speed(0.01)
+                            .prefix("out "),
+                    );
+                });
+            }
+            ui.horizontal(|ui| {
+                if ui.button("Add point").clicked() {
+                    let last = points.last().copied().unwrap_or([0.0, 0.0]);
+                    points.push([last[0] + 0.25, last[1] + 0.25]);
+                }
+                if points.len() > 1 && ui.button("Remove point").clicked() {
+                    points.pop();
+                }
+            });
+        }
+    }
+}
+
+// Output magnitude over the input range, with the raw input marked on it. Per-frame
+// axes are plotted against speed, whose raw value isn't known here
+fn curve_plot(ui: &mut egui::Ui, processor: &AxisProcessor, raw: f32) {
+    let range = if processor.per_frame {
+        PER_FRAME_RANGE
+    } else {
+        STICK_RANGE
+    };
+    let samples: Vec<(f32, f32)> = (0..=64)
+        .map(|index| {
+            let input = index as f32 / 64.0 * range;
+            (input, processor.shape(input).abs())
+        })
+        .collect();
+    let top = samples
+        .iter()
+        .fold(f32::EPSILON, |top, (_, output)| top.max(*output));
+
+    let (rect, _) = ui.allocate_exact_size(
+        egui::vec2(ui.available_width().max(200.0), 100.0),
+        egui::Sense::hover(),
+    );
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, egui::Color32::from_gray(24));
+    let at = |input: f32, output: f32| {
+        egui::pos2(
+            rect.left() + input / range * rect.width(),
+            rect.bottom() - output / top * rect.height() * 0.95,
+        )
+    };
+    let points = samples
+        .iter()
+        .map(|(input, output)| at(*input, *output))
+        .collect();
+    painter.add(egui::Shape::line(
+        points,
+        egui::Stroke::new(1.5, CURVE_COLOR),
+    ));
+
+    if !processor.per_frame {
+        let input = raw.abs().min(range);
+        painter.circle_filled(at(input, processor.shape(input).abs()), 3.0, RAW_COLOR);
+    }
+    let input = if processor.per_frame {
+        "Speed 0 to 2000 per second"
+    } else {
+        "Input 0 to 1"
+    };
+    ui.label(format!("{}, output 0 to {:.4}", input, top));
+}
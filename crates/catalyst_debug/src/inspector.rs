@@ -0,0 +1,316 @@
+use catalyst_assets::{MaterialDefinition, lod::MeshLod, material::MaterialData};
+use catalyst_core::{
+    light::PointLight,
+    registry::{ComponentRegistration, ComponentRegistry},
+    transform::{GlobalTransform, Transform},
+};
+use catalyst_renderer::{Outlined, ParticleEmitter, ParticlePool, lod::current_lod_level};
+use flecs_ecs::prelude::*;
+use serde_json::Value;
+
+use crate::{
+    editors::json_fields_editor,
+    hierarchy::{ShowHierarchy, hierarchy_size},
+    lights::Selected,
+    materials::material_settings_editor,
+    undo::{CoalesceKey, EditCommand, UndoStack},
+};
+
+/// On the selected entity when its Outlined was added by the selection, so deselecting
+/// leaves outlines set by gameplay alone.
+#[derive(Component)]
+struct SelectionOutline;
+
+/// Entity picker plus editing of every registered component (see ComponentRegistry) on the
+/// selected entity, through its registered editor or field by field when it has none.
+/// Material edits are set on the material asset, which rebuilds its GPU material.
+/// Particle emitters show their live particle count, meshes with a MeshLod their LOD levels,
+/// entities with children the size of their hierarchy and a toggle to draw it.
+/// Every edit is recorded on the UndoStack.
+pub fn inspector_window(
+    ctx: &egui::Context,
+    world: &WorldRef,
+    entities: &[(Entity, String)],
+    selected: Option<Entity>,
+) {
+    egui::Window::new("Inspector").show(ctx, |ui| {
+        if ui.button("New Light").clicked() {
+            let transform = Transform::from_xyz(0.0, 2.0, 0.0);
+            let light = world
+                .entity()
+                .set(transform)
+                .set(GlobalTransform::default())
+                .set(PointLight::default());
+            select(world, selected, light.id());
+
+            // Undone by removing the components; the empty entity stays for redo
+            let light_value = serde_json::to_value(PointLight::default()).ok();
+            let transform_value = serde_json::to_value(transform).ok();
+            world.get::<&mut UndoStack>(|stack| {
+                stack.begin_group("New light", None);
+                stack.record("", added(&light, "Transform", transform_value));
+                stack.record("", added(&light, "PointLight", light_value));
+                stack.end_group();
+            });
+        }
+
+        ui.separator();
+
+        egui::ScrollArea::vertical()
+            .max_height(200.0)
+            .show(ui, |ui| {
+                for (entity, name) in entities {
+                    if ui
+                        .selectable_label(selected == Some(*entity), name)
+                        .clicked()
+                    {
+                        select(world, selected, *entity);
+                    }
+                }
+            });
+
+        let Some(selected) = selected else {
+            return;
+        };
+
+        ui.separator();
+
+        let entity = world.entity_from_id(selected);
+        ui.heading(entity_label(&entity));
+
+        let registrations: Vec<ComponentRegistration> =
+            world.get::<&ComponentRegistry>(|registry| registry.iter().cloned().collect());
+
+        for registration in registrations.iter().filter(|r| r.has(&entity)) {
+            let mut remove = false;
+            egui::CollapsingHeader::new(registration.name())
+                .default_open(true)
+                .show(ui, |ui| {
+                    component_editor(ui, world, &entity, registration);
+                    remove = ui.button("Remove").clicked();
+                });
+            if remove {
+                let old = registration.serialize(&entity).and_then(Result::ok);
+                registration.remove(&entity);
+                let label = format!(
+                    "Remove {} from {}",
+                    registration.name(),
+                    entity_label(&entity)
+                );
+                let command = EditCommand::component(entity.id(), registration.name(), old, None);
+                world.get::<&mut UndoStack>(|stack| stack.record(label, command));
+            }
+        }
+        ui.separator();
+
+        if let Some((live, max, on_gpu, paused)) =
+            entity.try_get::<(&ParticleEmitter, &ParticlePool)>(|(emitter, pool)| {
+                (pool.len(), emitter.max_particles, pool.is_on_gpu(), pool.is_paused())
+            })
+        {
+            ui.label(format!(
+                "Particles: {}{} / {}{}",
+                if on_gpu { "up to " } else { "" },
+                live,
+                max,
+                if paused { " (paused, not visible)" } else { "" }
+            ));
+            ui.separator();
+        }
+
+        let nodes = hierarchy_size(entity);
+        if nodes > 0 {
+            let mut show = entity.has(ShowHierarchy);
+            ui.label(format!("Nodes below: {}", nodes));
+            if ui.checkbox(&mut show, "Draw hierarchy").changed() {
+                if show {
+                    entity.add(ShowHierarchy);
+                } else {
+                    entity.remove(ShowHierarchy);
+                }
+            }
+            ui.separator();
+        }
+
+        if let Some(lod) = entity.try_get::<&MeshLod>(|lod| lod.clone()) {
+            lod_section(ui, &entity, &lod);
+            ui.separator();
+        }
+
+        if let Some(definition) = entity.try_get::<&MaterialDefinition>(|d| d.clone()) {
+            material_editor(ui, world, &definition);
+            ui.separator();
+        }
+
+        ui.menu_button("Add Component", |ui| {
+            for registration in &registrations {
+                ui.add_enabled_ui(!registration.has(&entity), |ui| {
+                    if ui.button(registration.name()).clicked() {
+                        add_component(world, &entity, registration);
+                        ui.close();
+                    }
+                });
+            }
+        });
+    });
+}
+
+// Quality report of the generated levels, with the one drawn right now highlighted
+fn lod_section(ui: &mut egui::Ui, entity: &EntityView, lod: &MeshLod) {
+    let current = current_lod_level(*entity, lod);
+    egui::CollapsingHeader::new("LOD")
+        .default_open(true)
+        .show(ui, |ui| {
+            ui.label(if current.is_none() { "> Full detail" } else { "  Full detail" });
+            for (index, level) in lod.levels.iter().enumerate() {
+                ui.label(format!(
+                    "{} {}: {} tris, error {:.4}, below {:.0}% of the screen",
+                    if current == Some(index) { ">" } else { " " },
+                    index + 1,
+                    level.triangles,
+                    level.error,
+                    level.screen_size * 100.0
+                ));
+            }
+        });
+}
+
+fn component_editor(
+    ui: &mut egui::Ui,
+    world: &WorldRef,
+    entity: &EntityView,
+    registration: &ComponentRegistration,
+) {
+    if registration.is_tag() {
+        ui.label("Tag");
+        return;
+    }
+    if registration.has_editor() {
+        let old = registration.serialize(entity).and_then(Result::ok);
+        if let Some(new) = registration.edit_value(entity, ui) {
+            record_edit(world, entity, registration, old, new);
+        }
+        return;
+    }
+
+    let mut value = match registration.serialize(entity) {
+        Some(Ok(value)) => value,
+        Some(Err(error)) => {
+            ui.label(format!("Can't be shown: {}", error));
+            return;
+        }
+        None => return,
+    };
+    let old = value.clone();
+    if json_fields_editor(ui, &mut value) {
+        match registration.deserialize(entity, value.clone()) {
+            Ok(()) => record_edit(world, entity, registration, Some(old), value),
+            Err(error) => log::warn!("Can't apply the {} edit: {}", registration.name(), error),
+        }
+    }
+}
+
+// Coalesced per component, so dragging a value is one entry
+fn record_edit(
+    world: &WorldRef,
+    entity: &EntityView,
+    registration: &ComponentRegistration,
+    old: Option<Value>,
+    new: Value,
+) {
+    let command = EditCommand::component(entity.id(), registration.name(), old, Some(new))
+        .coalescing(CoalesceKey::new((entity.id(), registration.name())));
+    world.get::<&mut UndoStack>(|stack| {
+        stack.record(
+            format!("Edit {} of {}", registration.name(), entity_label(entity)),
+            command,
+        );
+    });
+}
+
+// The component goes in with the Transform it needs, undone together
+fn add_component(world: &WorldRef, entity: &EntityView, registration: &ComponentRegistration) {
+    let add_transform = !entity.has(Transform::id());
+    ensure_transform(entity);
+    registration.insert_default(entity);
+
+    let label = format!("Add {} to {}", registration.name(), entity_label(entity));
+    let transform = serde_json::to_value(Transform::default()).ok();
+    let component = registration.default_value().ok();
+    world.get::<&mut UndoStack>(|stack| {
+        stack.begin_group(label, None);
+        if add_transform {
+            stack.record("", added(entity, "Transform", transform));
+        }
+        stack.record("", added(entity, registration.name(), component));
+        stack.end_group();
+    });
+}
+
+fn added(entity: &EntityView, component: &'static str, value: Option<Value>) -> EditCommand {
+    EditCommand::component(entity.id(), component, None, value)
+}
+
+// Edits a copy and sets it back only when something changed, so the
+// MaterialData OnSet observer rebuilds the bind group and pipeline key once
+fn material_editor(ui: &mut egui::Ui, world: &WorldRef, definition: &MaterialDefinition) {
+    let Some(material_entity) = definition.0.try_get_entity(world) else {
+        ui.label("Material: not loaded");
+        return;
+    };
+    let Some(mut data) = material_entity.try_get::<&MaterialData>(|data| data.clone()) else {
+        ui.label("Material: not loaded");
+        return;
+    };
+
+    ui.label(format!("Material: {}", entity_label(&material_entity)));
+    let old = data.clone();
+    if material_settings_editor(ui, &mut data.settings) {
+        material_entity.set(data.clone());
+        let command = EditCommand::material(material_entity.id(), old, data)
+            .coalescing(CoalesceKey::new(material_entity.id()));
+        world.get::<&mut UndoStack>(|stack| {
+            stack.record(
+                format!("Edit material {}", entity_label(&material_entity)),
+                command,
+            );
+        });
+    }
+}
+
+pub fn entity_label(entity: &EntityView) -> String {
+    let name = entity.name();
+    if name.is_empty() {
+        format!("Entity {}", entity.id())
+    } else {
+        name
+    }
+}
+
+// The selection is outlined, in Outlined's default style unless it has an outline already
+fn select(world: &WorldRef, previous: Option<Entity>, entity: Entity) {
+    if let Some(previous) = previous {
+        let previous = world.entity_from_id(previous);
+        previous.remove(Selected);
+        if previous.has(SelectionOutline) {
+            previous.remove(SelectionOutline).remove(Outlined::id());
+        }
+    }
+
+    let entity = world.entity_from_id(entity);
+    entity.add(Selected);
+    if !entity.has(Outlined::id()) {
+        entity.set(Outlined::default()).add(SelectionOutline);
+    }
+}
+
+// Most registered components (lights, cameras, emitters, bodies) are placed by the
+// entity's GlobalTransform
+fn ensure_transform(entity: &EntityView) {
+    if !entity.has(Transform::id()) {
+        entity.set(Transform::default());
+    }
+    if !entity.has(GlobalTransform::id()) {
+        entity.set(GlobalTransform::default());
+    }
+}
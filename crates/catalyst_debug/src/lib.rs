@@ -1,4 +1,9 @@
+use catalyst_assets::{
+    assets::MeshData,
+    material::{MaterialData, TextureData},
+};
 use catalyst_input::{
+    InputPlugin,
     context::{CTX_DEBUG, CTX_GAMEPLAY},
     logical::ActionId,
     physical::InputState,
@@ -6,21 +11,98 @@ use catalyst_input::{
 use flecs_ecs::prelude::*;
 use winit::window::CursorGrabMode;
 
-use catalyst_core::{App, Plugin, SystemEvents, pipeline::PhaseRenderGUI};
-use catalyst_renderer::{GpuTexture, RenderContext, RenderTarget};
-use catalyst_window::MainWindow;
+use catalyst_core::{
+    App, Plugin, PluginId, SystemEvents, activity::ActivityStats, animation::Timeline,
+    console::ConsoleCommands, pipeline::PhaseRenderGUI, transform::Transform,
+};
+use catalyst_nav::NavPlugin;
+use catalyst_physics::PhysicsPlugin;
+use catalyst_renderer::{
+    DebugViewMode, DeviceRecovery, FrameGraph, FrustumCulling, GpuTexture, OcclusionCulling, PassStage, PortalCulling, ReflectionProbes, RenderContext, RenderPlugin, RenderSettings,
+    RenderStats, TaaSettings, TextureResidency,
+};
+use catalyst_window::{FrameStats, MainWindow, WindowFocus, WindowPlugin};
 use egui_wgpu::ScreenDescriptor;
-use wgpu::CommandEncoderDescriptor;
 
 use crate::{
-    egui_state::EguiState, greed::debug_greed_system, physics::debug_collider_render_system,
+    animation::animation_window,
+    console::{ConsoleState, console_window, register_builtin_commands},
+    editors::register_engine_editors,
+    egui_state::EguiState,
+    dynamic_resolution::dynamic_resolution_section,
+    exposure::exposure_section,
+    file_dialogs::register_file_dialogs,
+    file_drop::{file_hover_overlay, register_file_drop_handler},
+    files::{files_section, register_files},
+    fog::fog_section,
+    game_ui::{GameUi, game_ui_overlay},
+    greed::debug_greed_system,
+    hierarchy::debug_hierarchy_system,
+    hud::{hud_overlay, register_hud_commands},
+    importance::debug_importance_system,
+    input_axes::input_axes_section,
+    inspector::{entity_label, inspector_window},
+    lights::debug_light_gizmo_system,
+    materials::{material_editor_window, register_material_editor},
+    meshes::{MeshRow, meshes_window},
+    navigation::{debug_navigation_system, navigation_window},
+    physics::{debug_collider_render_system, physics_section},
+    profiler::profiler_section,
+    rooms::debug_room_system,
+    settings::settings_window,
+    textures::{TextureRow, textures_window},
+    undo::history_window,
+    wind::wind_section,
 };
 
+pub use editors::register_editor;
+pub use egui;
+pub use file_dialogs::{FileDialogRequestId, FileDialogResult, FileDialogs, FileFilter};
+pub use file_drop::DebugTextureView;
+pub use game_ui::{GameUiFn, register_game_ui};
+pub use hierarchy::ShowHierarchy;
+pub use hud::{HudCorner, HudSettings};
+pub use importance::ImportanceDebug;
+pub use lights::Selected;
+pub use navigation::NavigationDebug;
+pub use rooms::RoomDebug;
+pub use undo::{CoalesceKey, Edit, EditCommand, UndoEntry, UndoStack};
+
+mod animation;
+mod console;
+mod dynamic_resolution;
+mod editors;
 mod egui_state;
+mod exposure;
+mod file_dialogs;
+mod file_drop;
+mod files;
+mod fog;
+mod game_ui;
 mod greed;
+mod hierarchy;
+mod hud;
+mod importance;
+mod input_axes;
+mod inspector;
+mod lights;
+mod materials;
+mod meshes;
+mod navigation;
 mod physics;
+mod profiler;
+mod rooms;
+mod settings;
+mod textures;
+mod undo;
+mod wind;
 
 pub const ACTION_ENABLE_DEBUG: ActionId = ActionId(201);
+/// Undo and redo (see UndoStack), while ACTION_UNDO_MODIFIER is held: bind them to Z, Y
+/// and both Control keys in CTX_DEBUG for Ctrl+Z and Ctrl+Y.
+pub const ACTION_UNDO: ActionId = ActionId(202);
+pub const ACTION_REDO: ActionId = ActionId(203);
+pub const ACTION_UNDO_MODIFIER: ActionId = ActionId(204);
 
 #[derive(Component, Clone, Copy, Debug, Hash)]
 pub struct DebugTexture(pub egui::epaint::TextureId);
@@ -39,9 +121,28 @@ impl Plugin for DebugPlugin {
             .add_trait::<flecs::Singleton>();
 
         app.register_singleton_default::<GuiState>();
+        app.register_singleton_default::<ConsoleState>();
+        app.register_singleton_default::<HudSettings>();
+        app.register_singleton_default::<GameUi>();
+        app.register_singleton_default::<UndoStack>();
+
+        app.world.get::<&mut ConsoleCommands>(|commands| {
+            register_builtin_commands(commands);
+            register_hud_commands(commands);
+        });
 
         debug_collider_render_system(app);
         debug_greed_system(app);
+        debug_hierarchy_system(app);
+        debug_importance_system(app);
+        debug_light_gizmo_system(app);
+        debug_navigation_system(app);
+        debug_room_system(app);
+        register_engine_editors(app);
+        register_file_drop_handler(app);
+        register_file_dialogs(app);
+        register_files(app);
+        register_material_editor(app);
 
         app.world
             .system_named::<(&mut GuiState, &mut InputState)>("debug_inputs")
@@ -56,7 +157,7 @@ impl Plugin for DebugPlugin {
                     {
                         if input.just_pressed(ACTION_ENABLE_DEBUG) {
                             gui_state.enabled = !gui_state.enabled;
-                            println!("Debug GUI Enabled: {}", gui_state.enabled);
+                            log::info!("Debug GUI Enabled: {}", gui_state.enabled);
 
                             if gui_state.enabled {
                                 input.set_context(CTX_DEBUG);
@@ -68,6 +169,28 @@ impl Plugin for DebugPlugin {
                 }
             });
 
+        app.world
+            .system_named::<(&InputState, &GuiState)>("debug_undo_inputs")
+            .kind(flecs::pipeline::OnUpdate)
+            .each_iter(|iter, _, (input, gui_state)| {
+                if !gui_state.enabled || !input.held(ACTION_UNDO_MODIFIER) {
+                    return;
+                }
+                // Ctrl+Z in a text field undoes the typing instead
+                let world = iter.world();
+                let typing = world
+                    .try_get::<&EguiState>(|egui_state| egui_state.context.wants_keyboard_input())
+                    .unwrap_or(false);
+                if typing {
+                    return;
+                }
+                if input.just_pressed(ACTION_UNDO) {
+                    UndoStack::undo(&world);
+                } else if input.just_pressed(ACTION_REDO) {
+                    UndoStack::redo(&world);
+                }
+            });
+
         app.world
             .system::<(&RenderContext, &MainWindow)>()
             .kind(flecs::pipeline::OnUpdate)
@@ -82,7 +205,7 @@ impl Plugin for DebugPlugin {
                     if let (Some(context), Some(window)) =
                         (context_field.get(0), window_field.get(0))
                     {
-                        println!("Context is ready. Initializing EguiState...");
+                        log::debug!("Render context ready, initializing EguiState");
 
                         let egui_state = EguiState::new(&context, &window.0);
 
@@ -91,6 +214,20 @@ impl Plugin for DebugPlugin {
                 }
             });
 
+        // A new device after a device loss: the egui renderer and the textures registered
+        // with it belong to the old one
+        app.world
+            .observer::<flecs::OnSet, &RenderContext>()
+            .each_iter(|iter, _, _| {
+                iter.world().remove(EguiState::id());
+            });
+
+        app.world
+            .observer::<flecs::OnRemove, &GpuTexture>()
+            .each_entity(|entity, _| {
+                entity.remove(DebugTexture::id());
+            });
+
         app.world
             .observer::<flecs::OnSet, (&GpuTexture, &mut EguiState, &RenderContext)>()
             .each_entity(|entity, (texture, egui_state, context)| {
@@ -111,37 +248,87 @@ impl Plugin for DebugPlugin {
             .set_cached()
             .build();
 
+        let streamed_textures = app
+            .world
+            .query_named::<&TextureResidency>("streamed_textures")
+            .set_cached()
+            .build();
+
+        let material_assets = app
+            .world
+            .query_named::<&MaterialData>("material_assets")
+            .set_cached()
+            .build();
+
+        let texture_assets = app
+            .world
+            .query_named::<&TextureData>("texture_assets")
+            .set_cached()
+            .build();
+
+        let mesh_assets = app
+            .world
+            .query_named::<&MeshData>("mesh_assets")
+            .set_cached()
+            .build();
+
+        let inspectable_entities = app
+            .world
+            .query_named::<&Transform>("inspectable_entities")
+            .set_cached()
+            .build();
+
+        let timeline_entities = app
+            .world
+            .query_named::<&Timeline>("timeline_entities")
+            .set_cached()
+            .build();
+
+        let selected_entities = app
+            .world
+            .query_named::<()>("selected_entities")
+            .with(Selected)
+            .set_cached()
+            .build();
+
         app.world
             .system_named::<(
                 &mut EguiState,
                 &SystemEvents,
                 &MainWindow,
-                &RenderTarget,
+                &mut FrameGraph,
                 &RenderContext,
                 &GuiState,
+                &mut TaaSettings,
             )>("render_debug_ui")
             .kind(PhaseRenderGUI)
             .run(move |mut iter| {
+                catalyst_core::profile_scope!("Debug UI");
+                let world = iter.world();
+
                 while iter.next() {
                     let mut egui_state_field = iter.field_mut::<EguiState>(0);
                     let system_events_field = iter.field::<SystemEvents>(1);
                     let window_field = iter.field::<MainWindow>(2);
-                    let target_field = iter.field::<RenderTarget>(3);
+                    let mut graph_field = iter.field_mut::<FrameGraph>(3);
                     let context_field = iter.field::<RenderContext>(4);
                     let gui_state_field = iter.field::<GuiState>(5);
+                    let mut taa_field = iter.field_mut::<TaaSettings>(6);
 
                     if let (
                         Some(egui_state),
                         Some(window),
-                        Some(target),
+                        Some(graph),
                         Some(context),
                         Some(gui_state),
+                        Some(taa),
                     ) = (
                         egui_state_field.get_mut(0),
                         window_field.get(0),
-                        target_field.get(0),
+                        graph_field.get_mut(0),
                         context_field.get(0),
                         gui_state_field.get(0),
+                        taa_field.get_mut(0),
                     ) {
                         for event in &system_events_field[0].buffer {
                             let _ = egui_state.state.on_window_event(&window.0, event);
@@ -151,48 +338,354 @@ impl Plugin for DebugPlugin {
                         egui_state.context.begin_pass(raw_input);
                         let ctx = &egui_state.context;
 
-                        if !gui_state.enabled {
+                        // Drawn over the game too, so dropping files is discoverable
+                        let hovering = file_hover_overlay(ctx, &world);
+                        let hud = hud_overlay(ctx, &world);
+                        let game_ui = game_ui_overlay(ctx, &world);
+                        if !gui_state.enabled && !hovering && !hud && !game_ui {
                             return;
                         }
 
-                        window.0.set_cursor_grab(CursorGrabMode::None).unwrap();
-                        window.0.set_cursor_visible(true);
+                        if gui_state.enabled {
+                            window.0.set_cursor_grab(CursorGrabMode::None).unwrap();
+                            window.0.set_cursor_visible(true);
 
-                        let mut valid_textures = Vec::new();
+                            let mut valid_textures = Vec::new();
 
-                        textures_to_debug.each_entity(|entity, texture| {
-                            valid_textures.push((entity.name(), texture.clone()));
-                        });
+                            textures_to_debug.each_entity(|entity, texture| {
+                                valid_textures.push((entity.name(), texture.clone()));
+                            });
 
-                        egui::Window::new("Debug Texture").show(ctx, |ui| {
-                            ui.heading("Debug Texture Window");
+                            // Set by dropping an image on the window; None until it's on the GPU
+                            let viewed = world
+                                .get::<&DebugTextureView>(|view| view.texture.clone())
+                                .and_then(|texture| texture.try_get_entity(&world))
+                                .and_then(|entity| {
+                                    entity.try_get::<&DebugTexture>(|texture| (entity.name(), *texture))
+                                });
 
-                            if valid_textures.is_empty() {
-                                ui.label("No textures found with registered Egui IDs.");
-                                return;
-                            }
+                            egui::Window::new("Debug Texture").show(ctx, |ui| {
+                                ui.heading("Debug Texture Window");
+
+                                if let Some((name, texture)) = &viewed {
+                                    ui.label(name);
+                                    ui.image((texture.0, egui::vec2(512.0, 512.0)));
+                                    return;
+                                }
+
+                                if valid_textures.is_empty() {
+                                    ui.label("No textures found with registered Egui IDs.");
+                                    return;
+                                }
+
+                                if let Some(t) = valid_textures.get(1) {
+                                    ui.image((t.1.0, egui::vec2(512.0, 512.0)));
+                                }
+                            });
+
+                            egui::Window::new("Rendering").show(ctx, |ui| {
+                                ui.checkbox(&mut taa.enabled, "Temporal AA");
+                                ui.add_enabled(
+                                    taa.enabled,
+                                    egui::Slider::new(&mut taa.blend_factor, 0.01..=1.0)
+                                        .text("Blend factor"),
+                                );
+                                ui.add_enabled(
+                                    taa.enabled,
+                                    egui::Slider::new(&mut taa.jitter_scale, 0.0..=2.0)
+                                        .text("Jitter scale"),
+                                );
+
+                                ui.separator();
+                                world.get::<&RenderStats>(|stats| {
+                                    ui.label(format!(
+                                        "Pipelines: {} ({} hits, {} misses)",
+                                        stats.pipeline_cache_size,
+                                        stats.pipeline_cache_hits,
+                                        stats.pipeline_cache_misses
+                                    ));
+                                    ui.label(format!(
+                                        "Skipped draws (missing GPU resources): {}",
+                                        stats.missing_resource_skips
+                                    ));
+                                    ui.label(format!(
+                                        "Mesh draws: {} ({} skipped by occlusion)",
+                                        stats.mesh_draws, stats.occlusion_skipped_draws
+                                    ));
+                                    ui.label(format!(
+                                        "Mesh uniform writes: {} ({:.1} KB)",
+                                        stats.mesh_uniform_writes,
+                                        stats.mesh_uniform_bytes as f64 / 1024.0
+                                    ));
+                                    ui.label(format!("Particles drawn: {}", stats.particles_drawn));
+                                    ui.label(format!(
+                                        "GPU particle emitters: {} ({} spawned)",
+                                        stats.gpu_particle_emitters, stats.gpu_particles_spawned
+                                    ));
+                                    ui.label(format!("Decals drawn: {}", stats.decals_drawn));
+                                    ui.label(format!(
+                                        "Planar reflections drawn: {}",
+                                        stats.reflections_drawn
+                                    ));
+                                    world.get::<&ReflectionProbes>(|probes| {
+                                        ui.label(format!(
+                                            "Reflection probes: {} of {} ({:.1} MB), {} captures, last {:.2} ms CPU / {:.2} ms GPU",
+                                            probes.active_probes(),
+                                            probes.probe_count(),
+                                            probes.memory_bytes() as f64 / (1024.0 * 1024.0),
+                                            probes.captures(),
+                                            probes.capture_ms(),
+                                            probes.capture_gpu_ms()
+                                        ));
+                                    });
+                                    ui.label(format!(
+                                        "Point lights: {} ({:.1} per occupied cluster)",
+                                        stats.point_lights, stats.lights_per_cluster
+                                    ));
+                                    ui.label(format!(
+                                        "Spot lights: {} ({} with a gobo)",
+                                        stats.spot_lights, stats.gobos
+                                    ));
+                                    ui.label(format!(
+                                        "Frame graph: {} passes, {} pooled textures ({:.1} MB, {} aliased)",
+                                        stats.graph_passes,
+                                        stats.pool_textures,
+                                        stats.pool_memory_bytes as f64 / (1024.0 * 1024.0),
+                                        stats.aliased_textures
+                                    ));
+                                    let geometry = &stats.geometry_pool;
+                                    ui.label(format!(
+                                        "Geometry pool: {} meshes in {} blocks ({} of 16-bit indices), {:.1} of {:.1} MB ({:.0}%), {} free ranges",
+                                        geometry.allocations,
+                                        geometry.blocks,
+                                        geometry.u16_blocks,
+                                        geometry.used_bytes as f64 / (1024.0 * 1024.0),
+                                        geometry.capacity_bytes as f64 / (1024.0 * 1024.0),
+                                        geometry.utilization() * 100.0,
+                                        geometry.free_ranges
+                                    ));
+                                    let batching = &stats.static_batching;
+                                    ui.label(format!(
+                                        "Static batches: {} ({} entities, {} draws saved, {:.1} MB)",
+                                        batching.batches,
+                                        batching.batched_entities,
+                                        batching.draws_saved,
+                                        batching.bytes as f64 / (1024.0 * 1024.0)
+                                    ));
+                                    world.get::<&DeviceRecovery>(|recovery| {
+                                        ui.horizontal(|ui| {
+                                            ui.label(format!(
+                                                "Device losses recovered: {} ({} resources regenerating)",
+                                                recovery.recoveries, recovery.regenerating
+                                            ));
+                                            if ui.button("Simulate loss").clicked() {
+                                                context.simulate_device_loss();
+                                            }
+                                        });
+                                    });
+                                    ui.label(format!(
+                                        "Recording: {:.3} ms ({} passes on worker threads)",
+                                        stats.recording_ms, stats.parallel_passes
+                                    ));
+                                    for (pass, milliseconds) in &stats.pass_timings {
+                                        ui.label(format!("  {}: {:.3} ms", pass, milliseconds));
+                                    }
+                                });
+                                world.get::<&mut PortalCulling>(|culling| {
+                                    ui.checkbox(&mut culling.enabled, "Portal culling");
+                                    ui.label(format!(
+                                        "Rooms: {} of {} visible, {} entities culled",
+                                        culling.visible_rooms(),
+                                        culling.room_count(),
+                                        culling.culled_entities()
+                                    ));
+                                });
+                                let culling_ms = world.get::<&FrameStats>(|stats| stats.culling_ms);
+                                world.get::<&mut FrustumCulling>(|culling| {
+                                    ui.checkbox(&mut culling.enabled, "Frustum culling");
+                                    ui.checkbox(&mut culling.parallel, "Parallel culling");
+                                    ui.label(format!(
+                                        "Meshes: {} culled of {}, {} moved, {:.3} ms",
+                                        culling.culled_entities(),
+                                        culling.tested_entities(),
+                                        culling.resnapshotted_entities(),
+                                        culling_ms
+                                    ));
+                                });
+                                world.get::<&mut OcclusionCulling>(|culling| {
+                                    ui.checkbox(&mut culling.enabled, "Occlusion culling");
+                                    ui.label(format!(
+                                        "Occlusion: {} hidden of {} tested, {} flushes",
+                                        culling.occluded_entities(),
+                                        culling.tested_entities(),
+                                        culling.flushes()
+                                    ));
+                                });
+                                world.get::<&mut RoomDebug>(|settings| {
+                                    ui.checkbox(&mut settings.draw_rooms, "Draw rooms and portals");
+                                });
+                                world.get::<&mut ImportanceDebug>(|settings| {
+                                    ui.checkbox(&mut settings.draw, "Draw importance");
+                                    if settings.draw {
+                                        ui.add(
+                                            egui::Slider::new(&mut settings.full_scale, 1.0..=100.0)
+                                                .text("Red at importance"),
+                                        );
+                                    }
+                                });
+                                world.get::<&mut RenderSettings>(|settings| {
+                                    ui.checkbox(&mut settings.serial_recording, "Serial pass recording");
+                                });
+                                world.get::<&mut DebugViewMode>(|mode| {
+                                    egui::ComboBox::from_label("Debug view")
+                                        .selected_text(mode.name())
+                                        .show_ui(ui, |ui| {
+                                            for option in DebugViewMode::ALL {
+                                                ui.selectable_value(mode, option, option.name());
+                                            }
+                                        });
+                                });
+                                world.get::<&WindowFocus>(|focus| {
+                                    ui.label(format!(
+                                        "Window: {} ({})",
+                                        focus.pacing(),
+                                        if focus.focused { "focused" } else { "unfocused" }
+                                    ));
+                                });
+                                world.get::<&mut FrameStats>(|stats| {
+                                    let target = match stats.target_fps {
+                                        Some(fps) => format!("{:.0} fps cap", fps),
+                                        None => "uncapped".to_string(),
+                                    };
+                                    ui.label(format!(
+                                        "Frame interval: {:.2} ms, {:.2} ms average ({}), {} missed",
+                                        stats.interval_ms,
+                                        stats.average_interval_ms(),
+                                        target,
+                                        stats.missed_frames
+                                    ));
+                                    if ui.button("Reset frame stats").clicked() {
+                                        stats.reset();
+                                    }
+                                });
+                                world.get::<&FrameStats>(|stats| {
+                                    ui.label(format!(
+                                        "Transforms: {} refreshed, {} updated, {:.3} ms",
+                                        stats.transforms_refreshed,
+                                        stats.transforms_updated,
+                                        stats.transform_ms
+                                    ));
+                                });
+                                world.get::<&ActivityStats>(|activity| {
+                                    ui.label(format!(
+                                        "Activity: {} active, {} reduced, {} asleep",
+                                        activity.active, activity.reduced, activity.asleep
+                                    ));
+                                });
+
+                                ui.separator();
+                                egui::CollapsingHeader::new("Files").show(ui, |ui| {
+                                    files_section(ui, &world);
+                                });
+                                egui::CollapsingHeader::new("Exposure").show(ui, |ui| {
+                                    exposure_section(ui, &world);
+                                });
+                                egui::CollapsingHeader::new("Fog").show(ui, |ui| {
+                                    fog_section(ui, &world);
+                                });
+                                egui::CollapsingHeader::new("Wind").show(ui, |ui| {
+                                    wind_section(ui, &world);
+                                });
+                                egui::CollapsingHeader::new("Dynamic Resolution").show(ui, |ui| {
+                                    dynamic_resolution_section(
+                                        ui,
+                                        &world,
+                                        (context.config.width, context.config.height),
+                                    );
+                                });
+                                egui::CollapsingHeader::new("Physics").show(ui, |ui| {
+                                    physics_section(ui, &world);
+                                });
+                                egui::CollapsingHeader::new("Input Axes").show(ui, |ui| {
+                                    input_axes_section(ui, &world);
+                                });
+                                egui::CollapsingHeader::new("Profiler").show(ui, profiler_section);
+                            });
+
+                            let mut entities = Vec::new();
+                            inspectable_entities.each_entity(|entity, _| {
+                                entities.push((entity.id(), entity_label(&entity)));
+                            });
+
+                            let mut selected = None;
+                            selected_entities.each_entity(|entity, _| {
+                                selected = Some(entity.id());
+                            });
 
-                            if let Some(t) = valid_textures.get(1) {
-                                ui.image((t.1.0, egui::vec2(512.0, 512.0)));
+                            let mut texture_rows = Vec::new();
+                            streamed_textures.each_entity(|entity, residency| {
+                                texture_rows.push(TextureRow {
+                                    name: entity.name(),
+                                    resident_mips: residency.resident_mips(),
+                                    mip_count: residency.mip_count(),
+                                    allocated_bytes: residency.allocated_bytes(),
+                                    screen_pixels: residency.screen_pixels(),
+                                    streamed: residency.is_streamed(),
+                                });
+                            });
+
+                            let mut materials = Vec::new();
+                            material_assets.each_entity(|entity, _| {
+                                materials.push((entity.id(), entity_label(&entity)));
+                            });
+                            materials.sort_by(|a, b| a.1.cmp(&b.1));
+
+                            let mut textures = Vec::new();
+                            texture_assets.each_entity(|entity, texture| {
+                                textures.push((entity.id(), texture.name.clone()));
+                            });
+
+                            let mut mesh_rows = Vec::new();
+                            mesh_assets.each_entity(|entity, mesh| {
+                                mesh_rows.push(MeshRow {
+                                    name: entity_label(&entity),
+                                    vertices: mesh.vertices.len(),
+                                    triangles: mesh.indices.len() / 3,
+                                    index_format: mesh.index_format(),
+                                    repair: mesh.repair_report,
+                                });
+                            });
+
+                            let mut timelines = Vec::new();
+                            timeline_entities.each_entity(|entity, _| {
+                                timelines.push((entity.id(), entity_label(&entity)));
+                            });
+
+                            inspector_window(ctx, &world, &entities, selected);
+                            textures_window(ctx, &world, texture_rows);
+                            meshes_window(ctx, mesh_rows);
+                            material_editor_window(ctx, &world, &materials, &textures);
+                            console_window(ctx, &world);
+                            navigation_window(ctx, &world);
+                            animation_window(ctx, &world, &timelines);
+                            settings_window(ctx, &world);
+                            history_window(ctx, &world);
+
+                            // A drag or a typed value ends here, so its edits are one entry
+                            let editing = ctx.input(|input| input.pointer.any_down())
+                                || ctx.memory(|memory| memory.focused().is_some());
+                            if !editing {
+                                world.get::<&mut UndoStack>(|stack| stack.seal());
                             }
-                        });
+                        }
 
                         // 6. Render
-                        let view = match &target.view {
-                            Some(v) => v,
-                            None => return,
-                        };
+                        // The pass ends on frames without a backbuffer too: text copied in it
+                        // still goes to the clipboard, and the font atlas still gets its updates
                         let full_output = egui_state.context.end_pass();
                         egui_state
                             .state
                             .handle_platform_output(&window.0, full_output.platform_output);
-                        let paint_jobs = egui_state
-                            .context
-                            .tessellate(full_output.shapes, egui_state.context.pixels_per_point());
-                        let screen_descriptor = ScreenDescriptor {
-                            size_in_pixels: [context.config.width, context.config.height],
-                            pixels_per_point: window.0.scale_factor() as f32,
-                        };
                         for (id, delta) in &full_output.textures_delta.set {
                             egui_state.renderer.update_texture(
                                 &context.device,
@@ -201,46 +694,82 @@ impl Plugin for DebugPlugin {
                                 delta,
                             );
                         }
-                        let mut encoder =
-                            context
-                                .device
-                                .create_command_encoder(&CommandEncoderDescriptor {
-                                    label: Some("Debug UI"),
-                                });
-                        egui_state.renderer.update_buffers(
-                            &context.device,
-                            &context.queue,
-                            &mut encoder,
-                            &paint_jobs,
-                            &screen_descriptor,
-                        );
-                        {
-                            let pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                                label: Some("Egui Pass"),
-                                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                                    view: view,
-                                    resolve_target: None,
-                                    depth_slice: None,
-                                    ops: wgpu::Operations {
-                                        load: wgpu::LoadOp::Load,
-                                        store: wgpu::StoreOp::Store,
-                                    },
-                                })],
-                                depth_stencil_attachment: None,
-                                timestamp_writes: None,
-                                occlusion_query_set: None,
+                        let freed_textures = full_output.textures_delta.free;
+                        let Some(backbuffer) = graph.backbuffer() else {
+                            for id in &freed_textures {
+                                egui_state.renderer.free_texture(id);
+                            }
+                            return;
+                        };
+                        let paint_jobs = egui_state
+                            .context
+                            .tessellate(full_output.shapes, egui_state.context.pixels_per_point());
+                        let screen_descriptor = ScreenDescriptor {
+                            size_in_pixels: [context.config.width, context.config.height],
+                            pixels_per_point: window.0.scale_factor() as f32,
+                        };
+
+                        graph
+                            .add_pass("Egui", PassStage::Ui)
+                            .read(backbuffer)
+                            .write(backbuffer)
+                            .record(move |pass| {
+                                let view = pass.view(backbuffer);
+                                let timestamp_writes = pass.timestamp_writes();
+                                let context = &*pass.context;
+
+                                pass.world.get::<&mut EguiState>(|egui_state| {
+                                    egui_state.renderer.update_buffers(
+                                        &context.device,
+                                        &context.queue,
+                                        pass.encoder,
+                                        &paint_jobs,
+                                        &screen_descriptor,
+                                    );
+                                    {
+                                        let render_pass =
+                                            pass.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                                                label: Some("Egui Pass"),
+                                                color_attachments: &[Some(
+                                                    wgpu::RenderPassColorAttachment {
+                                                        view,
+                                                        resolve_target: None,
+                                                        depth_slice: None,
+                                                        ops: wgpu::Operations {
+                                                            load: wgpu::LoadOp::Load,
+                                                            store: wgpu::StoreOp::Store,
+                                                        },
+                                                    },
+                                                )],
+                                                depth_stencil_attachment: None,
+                                                timestamp_writes,
+                                                occlusion_query_set: None,
+                                            });
+                                        let mut render_pass = render_pass.forget_lifetime();
+                                        egui_state.renderer.render(
+                                            &mut render_pass,
+                                            &paint_jobs,
+                                            &screen_descriptor,
+                                        );
+                                    }
+                                    for id in &freed_textures {
+                                        egui_state.renderer.free_texture(id);
+                                    }
+                                });
                             });
-                            let mut pass = pass.forget_lifetime();
-                            egui_state
-                                .renderer
-                                .render(&mut pass, &paint_jobs, &screen_descriptor);
-                        }
-                        for id in &full_output.textures_delta.free {
-                            egui_state.renderer.free_texture(id);
-                        }
-                        context.queue.submit(std::iter::once(encoder.finish()));
                     }
                 }
             });
     }
+
+    // Debug draws colliders and the render/input state of everything else, so it goes last
+    fn dependencies(&self) -> Vec<PluginId> {
+        vec![
+            PluginId::of::<InputPlugin>(),
+            PluginId::of::<WindowPlugin>(),
+            PluginId::of::<RenderPlugin>(),
+            PluginId::of::<PhysicsPlugin>(),
+            PluginId::of::<NavPlugin>(),
+        ]
+    }
 }
@@ -0,0 +1,122 @@
+use catalyst_core::{
+    color::Color,
+    light::{DirectionalLight, PointLight},
+    transform::GlobalTransform,
+};
+use catalyst_renderer::{SpotLight, render::DebugDraw3D};
+use flecs_ecs::prelude::*;
+use glam::{Vec3, Vec4};
+
+use crate::GuiState;
+
+const CIRCLE_SEGMENTS: usize = 32;
+const ARROW_LENGTH: f32 = 2.0;
+const ARROW_HEAD: f32 = 0.4;
+const CONE_EDGES: usize = 8;
+
+/// Tag for the entity picked in the inspector. Its gizmos stay visible with the debug GUI closed.
+#[derive(Component)]
+pub struct Selected;
+
+pub fn debug_light_gizmo_system(app: &mut catalyst_core::App) {
+    app.world
+        .system_named::<(&PointLight, &GlobalTransform, &GuiState, &mut DebugDraw3D)>(
+            "debug_point_light_gizmos",
+        )
+        .kind(flecs::pipeline::OnUpdate)
+        .each_entity(|entity, (light, global, gui_state, debug)| {
+            if !gui_state.enabled && !entity.has(Selected) {
+                return;
+            }
+
+            let center = global.transform_point3(Vec3::ZERO);
+            let color = light_color(light.color);
+
+            // Wire sphere: one circle per axis plane
+            draw_circle(debug, center, Vec3::X, Vec3::Y, light.radius, color);
+            draw_circle(debug, center, Vec3::X, Vec3::Z, light.radius, color);
+            draw_circle(debug, center, Vec3::Y, Vec3::Z, light.radius, color);
+        });
+
+    app.world
+        .system_named::<(&DirectionalLight, &GlobalTransform, &GuiState, &mut DebugDraw3D)>(
+            "debug_directional_light_gizmos",
+        )
+        .kind(flecs::pipeline::OnUpdate)
+        .each_entity(|entity, (light, global, gui_state, debug)| {
+            if !gui_state.enabled && !entity.has(Selected) {
+                return;
+            }
+
+            let origin = global.transform_point3(Vec3::ZERO);
+            let forward = -global.z_axis.truncate().normalize_or_zero();
+            let side = global.x_axis.truncate().normalize_or_zero();
+            let up = global.y_axis.truncate().normalize_or_zero();
+
+            draw_arrow(debug, origin, forward, side, up, light_color(light.color));
+        });
+
+    app.world
+        .system_named::<(&SpotLight, &GlobalTransform, &GuiState, &mut DebugDraw3D)>(
+            "debug_spot_light_gizmos",
+        )
+        .kind(flecs::pipeline::OnUpdate)
+        .each_entity(|entity, (light, global, gui_state, debug)| {
+            if !gui_state.enabled && !entity.has(Selected) {
+                return;
+            }
+
+            let apex = global.transform_point3(Vec3::ZERO);
+            let forward = -global.z_axis.truncate().normalize_or_zero();
+            let side = global.x_axis.truncate().normalize_or_zero();
+            let up = global.y_axis.truncate().normalize_or_zero();
+            let color = light_color(light.color);
+
+            // Outer cone out to the range, inner cone as a circle at the same distance
+            let (inner, outer) = light.clamped_angles();
+            let center = apex + forward * light.range * outer.cos();
+            let radius = light.range * outer.sin();
+            draw_circle(debug, center, side, up, radius, color);
+            for i in 0..CONE_EDGES {
+                let angle = i as f32 / CONE_EDGES as f32 * std::f32::consts::TAU;
+                let rim = center + (side * angle.cos() + up * angle.sin()) * radius;
+                debug.push_line(apex, rim, color);
+            }
+
+            let inner_center = apex + forward * light.range * inner.cos();
+            let inner_color = Vec4::new(color.x * 0.5, color.y * 0.5, color.z * 0.5, 1.0);
+            draw_circle(debug, inner_center, side, up, light.range * inner.sin(), inner_color);
+        });
+}
+
+fn light_color(color: Color) -> Vec4 {
+    color.with_alpha(1.0).into()
+}
+
+fn draw_circle(debug: &mut DebugDraw3D, center: Vec3, a: Vec3, b: Vec3, radius: f32, color: Vec4) {
+    let point = |i: usize| {
+        let angle = i as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::TAU;
+        center + (a * angle.cos() + b * angle.sin()) * radius
+    };
+
+    for i in 0..CIRCLE_SEGMENTS {
+        debug.push_line(point(i), point(i + 1), color);
+    }
+}
+
+fn draw_arrow(
+    debug: &mut DebugDraw3D,
+    origin: Vec3,
+    direction: Vec3,
+    side: Vec3,
+    up: Vec3,
+    color: Vec4,
+) {
+    let tip = origin + direction * ARROW_LENGTH;
+    let base = tip - direction * ARROW_HEAD;
+
+    debug.push_line(origin, tip, color);
+    for offset in [side, -side, up, -up] {
+        debug.push_line(tip, base + offset * ARROW_HEAD * 0.5, color);
+    }
+}
@@ -0,0 +1,446 @@
+use std::{collections::HashMap, path::Path};
+
+use catalyst_assets::{
+    asset_events::AssetLookup,
+    asset_server::AssetServer,
+    assets::Handle,
+    material::{MaterialData, MaterialFile, MaterialSettings, MaterialWind, TextureData},
+};
+use catalyst_core::App;
+use catalyst_renderer::{MaterialPreview, material_preview::PREVIEW_SIZE};
+use flecs_ecs::prelude::*;
+
+use crate::{
+    DebugTexture,
+    editors::color_button,
+    file_dialogs::{FileDialogRequestId, FileDialogResult, FileDialogs, FileFilter},
+    files::relative_to_working_dir,
+    inspector::entity_label,
+    undo::{CoalesceKey, EditCommand, UndoStack},
+};
+
+/// The Materials window between frames.
+#[derive(Component, Default)]
+pub struct MaterialEditorState {
+    search: String,
+    selected: Option<Entity>,
+    // The material as it was when selected, restored by Revert
+    snapshot: Option<MaterialData>,
+    save_path: String,
+    status: String,
+    // The "Save material…" dialog, and whether it answered with a path to save to
+    save_dialog: Option<FileDialogRequestId>,
+    save_picked: bool,
+}
+
+// A loaded texture that can go in a material slot
+struct TextureChoice {
+    handle: Handle<TextureData>,
+    label: String,
+    // Asset path, None for textures that came out of a scene file
+    path: Option<String>,
+}
+
+enum EditorAction {
+    Select(Entity),
+    Apply(MaterialData),
+    Revert,
+    Save(MaterialData),
+}
+
+/// Searchable list of the loaded materials and an editor for the selected one, with a
+/// preview sphere (see MaterialPreview). Edits are set on the material asset, so the
+/// scene updates live; Revert goes back to the material as it was when selected, and
+/// Save writes it as a `.material.ron` file for `AssetServer::load_material`, to the
+/// path typed in or one picked with "Save material…". Edits and reverts are recorded on
+/// the UndoStack.
+///
+/// `materials` are (material entity, label), `textures` (texture entity, TextureData name).
+pub fn material_editor_window(
+    ctx: &egui::Context,
+    world: &WorldRef,
+    materials: &[(Entity, String)],
+    textures: &[(Entity, String)],
+) {
+    let selected = world
+        .get::<&MaterialEditorState>(|state| state.selected)
+        .filter(|entity| world.entity_from_id(*entity).is_alive());
+    let data = selected
+        .and_then(|entity| world.entity_from_id(entity).try_get::<&MaterialData>(|data| data.clone()));
+    let preview = world
+        .get::<&MaterialPreview>(|preview| preview.target)
+        .and_then(|target| world.entity_from_id(target).try_get::<&DebugTexture>(|texture| texture.0));
+    let choices = match data {
+        Some(_) => texture_choices(world, textures),
+        None => Vec::new(),
+    };
+
+    let mut open = false;
+    let mut actions = Vec::new();
+    world.get::<&mut MaterialEditorState>(|state| {
+        egui::Window::new("Materials")
+            .default_open(false)
+            .show(ctx, |ui| {
+                open = true;
+                ui.add(egui::TextEdit::singleline(&mut state.search).hint_text("Search"));
+                let search = state.search.to_lowercase();
+                egui::ScrollArea::vertical()
+                    .id_salt("material_list")
+                    .max_height(150.0)
+                    .show(ui, |ui| {
+                        let matches = materials
+                            .iter()
+                            .filter(|(_, label)| label.to_lowercase().contains(&search));
+                        for (entity, label) in matches {
+                            if ui.selectable_label(selected == Some(*entity), label).clicked() {
+                                actions.push(EditorAction::Select(*entity));
+                            }
+                        }
+                    });
+
+                let Some(mut data) = data.clone() else {
+                    return;
+                };
+                ui.separator();
+                match preview {
+                    Some(texture) => {
+                        ui.image((texture, egui::vec2(PREVIEW_SIZE as f32, PREVIEW_SIZE as f32)));
+                    }
+                    None => {
+                        ui.label("Preview: waiting for the GPU material");
+                    }
+                }
+
+                let mut changed = material_settings_editor(ui, &mut data.settings);
+                ui.separator();
+                for (label, slot) in [
+                    ("Base color texture", &mut data.diffuse_texture),
+                    ("Metallic/roughness texture", &mut data.metallic_roughness_texture),
+                    ("Normal texture", &mut data.normal_texture),
+                    ("Occlusion texture", &mut data.occlusion_texture),
+                ] {
+                    changed |= texture_slot(ui, label, slot, &choices);
+                }
+
+                ui.separator();
+                if ui
+                    .add_enabled(state.snapshot.is_some(), egui::Button::new("Revert"))
+                    .on_hover_text("Back to the material as it was when selected")
+                    .clicked()
+                {
+                    actions.push(EditorAction::Revert);
+                }
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut state.save_path);
+                    if ui.button("Save to file").clicked() {
+                        actions.push(EditorAction::Save(data.clone()));
+                    }
+                });
+                if ui
+                    .add_enabled(state.save_dialog.is_none(), egui::Button::new("Save material…"))
+                    .clicked()
+                {
+                    let name = Path::new(&state.save_path)
+                        .file_name()
+                        .map(|name| name.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    let filters = [FileFilter::new("Materials", &["ron"])];
+                    state.save_dialog = Some(
+                        world.get::<&mut FileDialogs>(|dialogs| dialogs.pick_save_file(&name, &filters)),
+                    );
+                    state.status = "Waiting for the file dialog".to_string();
+                }
+                // Saved once the dialog answered, with the material as it is by then
+                if std::mem::take(&mut state.save_picked) {
+                    actions.push(EditorAction::Save(data.clone()));
+                }
+                if !state.status.is_empty() {
+                    ui.label(&state.status);
+                }
+
+                if changed {
+                    actions.push(EditorAction::Apply(data));
+                }
+            });
+    });
+
+    // Only rendered while someone looks at it
+    world.get::<&mut MaterialPreview>(|preview| {
+        preview.material = selected.filter(|_| open);
+    });
+
+    // Applied outside the borrow above: setting MaterialData runs the renderer's observers
+    for action in actions {
+        match action {
+            EditorAction::Select(entity) => select_material(world, entity),
+            EditorAction::Apply(new) => {
+                if let (Some(entity), Some(old)) = (selected, data.clone()) {
+                    set_material(world, entity, old, new, true);
+                }
+            }
+            EditorAction::Revert => {
+                let snapshot = world.get::<&MaterialEditorState>(|state| state.snapshot.clone());
+                if let (Some(entity), Some(old), Some(snapshot)) =
+                    (selected, data.clone(), snapshot)
+                {
+                    set_material(world, entity, old, snapshot, false);
+                }
+            }
+            EditorAction::Save(data) => {
+                let status = save_material(world, &data, &choices);
+                world.get::<&mut MaterialEditorState>(|state| state.status = status);
+            }
+        }
+    }
+}
+
+/// Base color, metallic/roughness, emission, alpha mode, sidedness, unlit and wind.
+/// Returns true when anything changed.
+pub(crate) fn material_settings_editor(ui: &mut egui::Ui, settings: &mut MaterialSettings) -> bool {
+    let mut changed = false;
+
+    changed |= ui
+        .checkbox(&mut settings.unlit, "Unlit (KHR_materials_unlit)")
+        .changed();
+    ui.horizontal(|ui| {
+        ui.label("Base color");
+        changed |= color_button(ui, &mut settings.base_color, true);
+    });
+    ui.add_enabled_ui(!settings.unlit, |ui| {
+        changed |= ui
+            .add(egui::Slider::new(&mut settings.roughness, 0.0..=1.0).text("Roughness"))
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut settings.metallic, 0.0..=1.0).text("Metallic"))
+            .changed();
+    });
+    ui.horizontal(|ui| {
+        ui.label("Emissive");
+        changed |= color_button(ui, &mut settings.emissive, false);
+    });
+    changed |= ui
+        .add(
+            egui::DragValue::new(&mut settings.emissive_strength)
+                .speed(0.1)
+                .range(0.0..=100.0)
+                .prefix("Emissive strength: "),
+        )
+        .changed();
+
+    let mut masked = settings.alpha_cutoff.is_some();
+    ui.horizontal(|ui| {
+        ui.label("Alpha");
+        changed |= ui.radio_value(&mut masked, false, "Opaque").changed();
+        changed |= ui.radio_value(&mut masked, true, "Mask").changed();
+    });
+    match (masked, settings.alpha_cutoff) {
+        (true, None) => settings.alpha_cutoff = Some(0.5),
+        (false, Some(_)) => settings.alpha_cutoff = None,
+        _ => {}
+    }
+    if let Some(cutoff) = &mut settings.alpha_cutoff {
+        changed |= ui
+            .add(egui::Slider::new(cutoff, 0.0..=1.0).text("Alpha cutoff"))
+            .changed();
+    }
+    changed |= ui
+        .checkbox(&mut settings.double_sided, "Double sided")
+        .changed();
+
+    let mut wind = settings.wind.is_some();
+    changed |= ui.checkbox(&mut wind, "Wind").changed();
+    match (wind, settings.wind) {
+        (true, None) => settings.wind = Some(MaterialWind::default()),
+        (false, Some(_)) => settings.wind = None,
+        _ => {}
+    }
+    if let Some(wind) = &mut settings.wind {
+        changed |= ui
+            .add(egui::Slider::new(&mut wind.strength, 0.0..=2.0).text("Bend"))
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut wind.frequency, 0.0..=3.0).text("Frequency"))
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut wind.branch, 0.0..=0.5).text("Branch sway"))
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut wind.flutter, 0.0..=0.2).text("Flutter"))
+            .changed();
+    }
+
+    changed
+}
+
+// Handles of the loaded textures, through one pass over the asset lookup
+fn texture_choices(world: &WorldRef, textures: &[(Entity, String)]) -> Vec<TextureChoice> {
+    let entities: HashMap<Entity, &str> = textures
+        .iter()
+        .map(|(entity, name)| (*entity, name.as_str()))
+        .collect();
+
+    let mut choices: Vec<TextureChoice> = world
+        .try_get::<&AssetLookup>(|lookup| {
+            lookup
+                .map
+                .iter()
+                .filter_map(|(id, entity)| {
+                    let texture_name = entities.get(entity)?;
+                    // File loads are named by their path, scene textures aren't named
+                    let entity_name = world.entity_from_id(*entity).name();
+                    let path = (!entity_name.is_empty()).then_some(entity_name);
+                    Some(TextureChoice {
+                        handle: Handle::from_id(*id),
+                        label: path.clone().unwrap_or_else(|| format!("{} (scene)", texture_name)),
+                        path,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    choices.sort_by(|a, b| a.label.cmp(&b.label));
+    choices
+}
+
+fn texture_slot(
+    ui: &mut egui::Ui,
+    label: &str,
+    slot: &mut Option<Handle<TextureData>>,
+    choices: &[TextureChoice],
+) -> bool {
+    let current = slot
+        .as_ref()
+        .map(|handle| choices.iter().find(|choice| choice.handle == *handle));
+    let text = match current {
+        None => "None",
+        Some(Some(choice)) => choice.label.as_str(),
+        Some(None) => "(not loaded)",
+    };
+
+    let mut changed = false;
+    egui::ComboBox::from_label(label)
+        .selected_text(text)
+        .show_ui(ui, |ui| {
+            if ui.selectable_label(slot.is_none(), "None").clicked() && slot.is_some() {
+                *slot = None;
+                changed = true;
+            }
+            for choice in choices {
+                let is_current = slot.as_ref() == Some(&choice.handle);
+                if ui.selectable_label(is_current, &choice.label).clicked() && !is_current {
+                    *slot = Some(choice.handle.clone());
+                    changed = true;
+                }
+            }
+        });
+    changed
+}
+
+// Sets the material on its asset and records it for undo. Edits coalesce with the
+// inspector's edits of the same material, so a drag is one entry
+fn set_material(
+    world: &WorldRef,
+    entity: Entity,
+    old: MaterialData,
+    new: MaterialData,
+    edit: bool,
+) {
+    let material = world.entity_from_id(entity);
+    material.set(new.clone());
+
+    let mut command = EditCommand::material(entity, old, new);
+    let label = if edit {
+        command = command.coalescing(CoalesceKey::new(entity));
+        format!("Edit material {}", entity_label(&material))
+    } else {
+        format!("Revert material {}", entity_label(&material))
+    };
+    world.get::<&mut UndoStack>(|stack| stack.record(label, command));
+}
+
+/// Registers the editor's state, and takes the path "Save material…" picked.
+pub(crate) fn register_material_editor(app: &mut App) {
+    app.register_singleton_default::<MaterialEditorState>();
+
+    app.world
+        .entity_from::<FileDialogs>()
+        .observe_payload_entity::<&FileDialogResult>(|dialogs, result| {
+            dialogs.world().get::<&mut MaterialEditorState>(|state| {
+                if state.save_dialog != Some(result.request_id) {
+                    return;
+                }
+                state.save_dialog = None;
+                match &result.path {
+                    Some(path) => {
+                        state.save_path = relative_to_working_dir(path);
+                        state.save_picked = true;
+                    }
+                    None => state.status = "Not saved: cancelled".to_string(),
+                }
+            });
+        });
+}
+
+fn select_material(world: &WorldRef, entity: Entity) {
+    let material = world.entity_from_id(entity);
+    let snapshot = material.try_get::<&MaterialData>(|data| data.clone());
+    let save_path = default_save_path(&material.name());
+    world.get::<&mut MaterialEditorState>(|state| {
+        state.selected = Some(entity);
+        state.snapshot = snapshot;
+        state.save_path = save_path;
+        state.status.clear();
+    });
+}
+
+// Material files keep their path, everything else goes to materials/<name>
+fn default_save_path(name: &str) -> String {
+    if name.ends_with(".material.ron") {
+        return name.to_string();
+    }
+    let file_name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    let file_name = if file_name.is_empty() { "material" } else { &file_name };
+    format!("materials/{}.material.ron", file_name)
+}
+
+// Writes the file and returns the status line
+fn save_material(world: &WorldRef, data: &MaterialData, choices: &[TextureChoice]) -> String {
+    let path = world.get::<&MaterialEditorState>(|state| state.save_path.clone());
+    let mut skipped = Vec::new();
+    let mut texture_path = |label: &str, slot: &Option<Handle<TextureData>>| {
+        let handle = slot.as_ref()?;
+        let path = choices
+            .iter()
+            .find(|choice| choice.handle == *handle)
+            .and_then(|choice| choice.path.clone());
+        if path.is_none() {
+            skipped.push(label.to_string());
+        }
+        path
+    };
+    let file = MaterialFile {
+        settings: data.settings.clone(),
+        diffuse_texture: texture_path("base color", &data.diffuse_texture),
+        normal_texture: texture_path("normal", &data.normal_texture),
+        metallic_roughness_texture: texture_path(
+            "metallic/roughness",
+            &data.metallic_roughness_texture,
+        ),
+        occlusion_texture: texture_path("occlusion", &data.occlusion_texture),
+    };
+
+    let result = world.get::<&AssetServer>(|server| server.save_material(&path, &file));
+    match result {
+        Ok(()) if skipped.is_empty() => format!("Saved to {}", path),
+        // Scene textures have no file of their own to point at
+        Ok(()) => format!(
+            "Saved to {}, without the {} texture (from a scene)",
+            path,
+            skipped.join(", ")
+        ),
+        Err(error) => format!("Not saved: {}", error),
+    }
+}
@@ -0,0 +1,79 @@
+use catalyst_assets::mesh_repair::{IndexFormat, MeshRepairReport};
+
+/// One mesh asset, as listed in the Meshes window.
+pub struct MeshRow {
+    pub name: String,
+    pub vertices: usize,
+    pub triangles: usize,
+    pub index_format: IndexFormat,
+    /// Set on imported meshes, see MeshData::repair.
+    pub repair: Option<MeshRepairReport>,
+}
+
+/// Every mesh asset with its size and index width, and what the importer had to fix in
+/// it. Repaired meshes come first.
+pub fn meshes_window(ctx: &egui::Context, mut rows: Vec<MeshRow>) {
+    egui::Window::new("Meshes")
+        .default_open(false)
+        .show(ctx, |ui| {
+            let repaired = rows
+                .iter()
+                .filter(|row| row.repair.is_some_and(|report| !report.is_clean()))
+                .count();
+            let narrow = rows
+                .iter()
+                .filter(|row| row.index_format == IndexFormat::U16)
+                .count();
+            ui.label(format!(
+                "{} meshes, {} with 16-bit indices, {} repaired on import",
+                rows.len(),
+                narrow,
+                repaired
+            ));
+
+            ui.separator();
+            rows.sort_by_key(|row| {
+                (
+                    row.repair.is_none_or(|report| report.is_clean()),
+                    row.name.clone(),
+                )
+            });
+            egui::ScrollArea::vertical()
+                .max_height(300.0)
+                .show(ui, |ui| {
+                    egui::Grid::new("mesh_assets").striped(true).show(ui, |ui| {
+                        ui.strong("Mesh");
+                        ui.strong("Vertices");
+                        ui.strong("Triangles");
+                        ui.strong("Indices");
+                        ui.strong("Import");
+                        ui.end_row();
+
+                        for row in &rows {
+                            ui.label(&row.name);
+                            ui.label(row.vertices.to_string());
+                            ui.label(row.triangles.to_string());
+                            ui.label(row.index_format.to_string());
+                            match row.repair {
+                                Some(report) if !report.is_clean() => {
+                                    ui.colored_label(egui::Color32::YELLOW, report.to_string());
+                                }
+                                Some(report) if report.non_manifold_edges > 0 => {
+                                    ui.label(format!(
+                                        "{} non-manifold edges",
+                                        report.non_manifold_edges
+                                    ));
+                                }
+                                Some(_) => {
+                                    ui.label("clean");
+                                }
+                                None => {
+                                    ui.label("-");
+                                }
+                            }
+                            ui.end_row();
+                        }
+                    });
+                });
+        });
+}
@@ -0,0 +1,111 @@
+use catalyst_core::transform::GlobalTransform;
+use catalyst_nav::{NavAgent, NavMeshData, NavMeshState};
+use catalyst_renderer::render::DebugDraw3D;
+use flecs_ecs::prelude::*;
+use glam::{Vec3, Vec4};
+
+use crate::GuiState;
+
+// Lifted off the surface so the lines don't z-fight with the ground
+const LIFT: Vec3 = Vec3::new(0.0, 0.05, 0.0);
+const POLYGON_COLOR: Vec4 = Vec4::new(0.2, 0.6, 1.0, 1.0);
+const PORTAL_COLOR: Vec4 = Vec4::new(0.2, 1.0, 0.6, 1.0);
+const PATH_COLOR: Vec4 = Vec4::new(1.0, 0.8, 0.2, 1.0);
+
+/// What the navigation gizmos draw while the debug GUI is open.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct NavigationDebug {
+    pub draw_mesh: bool,
+    pub draw_paths: bool,
+}
+
+impl Default for NavigationDebug {
+    fn default() -> Self {
+        Self {
+            draw_mesh: true,
+            draw_paths: true,
+        }
+    }
+}
+
+pub fn debug_navigation_system(app: &mut catalyst_core::App) {
+    app.register_singleton_default::<NavigationDebug>();
+
+    app.world
+        .system_named::<(&NavMeshState, &NavigationDebug, &GuiState, &mut DebugDraw3D)>(
+            "debug_navmesh",
+        )
+        .kind(flecs::pipeline::OnUpdate)
+        .each_iter(|iter, _, (state, settings, gui_state, debug)| {
+            if !gui_state.enabled || !settings.draw_mesh {
+                return;
+            }
+
+            let world = iter.world();
+            let Some(entity) = state.handle().try_get_entity(&world) else {
+                return;
+            };
+            entity.try_get::<&NavMeshData>(|navmesh| {
+                for polygon in &navmesh.polygons {
+                    let count = polygon.vertices.len();
+                    for i in 0..count {
+                        let start = polygon.vertices[i] + LIFT;
+                        let end = polygon.vertices[(i + 1) % count] + LIFT;
+                        debug.push_line(start, end, POLYGON_COLOR);
+                    }
+                    for link in &polygon.links {
+                        let [a, b] = link.portal;
+                        debug.push_line(a + LIFT * 2.0, b + LIFT * 2.0, PORTAL_COLOR);
+                    }
+                }
+            });
+        });
+
+    app.world
+        .system_named::<(&NavAgent, &GlobalTransform, &NavigationDebug, &GuiState, &mut DebugDraw3D)>(
+            "debug_nav_agent_paths",
+        )
+        .kind(flecs::pipeline::OnUpdate)
+        .each(|(agent, global, settings, gui_state, debug)| {
+            if !gui_state.enabled || !settings.draw_paths {
+                return;
+            }
+
+            let mut previous = global.transform_point3(Vec3::ZERO);
+            for &corner in agent.path() {
+                debug.push_line(previous + LIFT, corner + LIFT, PATH_COLOR);
+                previous = corner;
+            }
+        });
+}
+
+pub fn navigation_window(ctx: &egui::Context, world: &WorldRef) {
+    egui::Window::new("Navigation")
+        .default_open(false)
+        .show(ctx, |ui| {
+            world.get::<&mut NavMeshState>(|state| {
+                match state.progress() {
+                    Some(progress) => {
+                        ui.add(egui::ProgressBar::new(progress).text("Baking"));
+                    }
+                    None => {
+                        ui.label(format!("Generation {}", state.generation()));
+                    }
+                }
+                if let Some(error) = state.last_error() {
+                    ui.colored_label(egui::Color32::LIGHT_RED, error);
+                }
+
+                ui.checkbox(&mut state.auto_rebake, "Rebake on geometry changes");
+                if ui.button("Rebake").clicked() {
+                    state.request_rebake();
+                }
+            });
+
+            ui.separator();
+            world.get::<&mut NavigationDebug>(|settings| {
+                ui.checkbox(&mut settings.draw_mesh, "Draw navmesh");
+                ui.checkbox(&mut settings.draw_paths, "Draw agent paths");
+            });
+        });
+}
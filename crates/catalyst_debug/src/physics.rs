@@ -1,5 +1,9 @@
-use catalyst_core::{physics::ColliderDefinition, transform::GlobalTransform};
-use catalyst_physics::{PhysicsWorld, prepare::PhysicsHandle};
+use catalyst_core::{physics::ColliderDefinition, time::PhysicsTime, transform::GlobalTransform};
+use catalyst_physics::{
+    PhysicsBodyAdded, PhysicsWorld,
+    debugger::{PhysicsDebugger, restore_snapshot},
+    prepare::PhysicsHandle,
+};
 use catalyst_renderer::render::DebugDraw3D;
 use flecs_ecs::prelude::*;
 use glam::Vec3;
@@ -42,11 +46,100 @@ pub fn debug_collider_render_system(app: &mut catalyst_core::App) {
                                 color,
                             );
                         }
+                        // Terrain sized: far too many lines to draw every frame
+                        catalyst_core::physics::ColliderShape::Heightfield { .. } => {}
                         _ => todo!(),
                     }
                 }
             }
         });
+
+    // Drawn from the bodies, so it stays live while physics is paused
+    app.world
+        .system_named::<(
+            &PhysicsHandle,
+            &PhysicsWorld,
+            &PhysicsDebugger,
+            &mut DebugDraw3D,
+        )>("debug_velocity_render")
+        .kind(flecs::pipeline::OnUpdate)
+        .with(PhysicsBodyAdded)
+        .each(|(handle, physics, debugger, debug)| {
+            if !debugger.draw_velocities {
+                return;
+            }
+            let Some(body) = handle.body.and_then(|body| physics.bodies.get(body)) else {
+                return;
+            };
+            if body.is_dynamic() && !body.is_sleeping() {
+                let position = body.position().translation;
+                let color = glam::vec4(1.0, 0.9, 0.0, 1.0);
+                debug.push_line(position, position + body.linvel(), color);
+            }
+        });
+}
+
+/// Pause, single steps and slow motion of the physics steps, and one snapshot to go
+/// back to while tweaking parameters.
+pub fn physics_section(ui: &mut egui::Ui, world: &WorldRef) {
+    world.get::<&mut PhysicsTime>(|time| {
+        ui.horizontal(|ui| {
+            let toggle = if time.is_paused() { "Resume" } else { "Pause" };
+            if ui.button(toggle).clicked() {
+                time.set_paused(!time.is_paused());
+            }
+            if ui.button("Step").clicked() {
+                time.request_step();
+            }
+            ui.label(format!("{:.4} s per step", time.fixed_dt));
+        });
+        let mut scale = time.scale();
+        if ui
+            .add(egui::Slider::new(&mut scale, 0.05..=1.0).text("Speed"))
+            .changed()
+        {
+            time.set_scale(scale);
+        }
+    });
+
+    world.get::<&PhysicsWorld>(|physics| {
+        let sleeping = physics
+            .bodies
+            .iter()
+            .filter(|(_, body)| body.is_sleeping())
+            .count();
+        ui.label(format!(
+            "{} bodies ({} sleeping), {} colliders",
+            physics.bodies.len(),
+            sleeping,
+            physics.colliders.len()
+        ));
+    });
+
+    let mut restore = None;
+    world.get::<&mut PhysicsDebugger>(|debugger| {
+        ui.checkbox(&mut debugger.draw_velocities, "Draw velocities");
+        ui.horizontal(|ui| {
+            if ui.button("Snapshot").clicked() {
+                match world.get::<&PhysicsWorld>(|physics| physics.snapshot()) {
+                    Ok(snapshot) => debugger.snapshot = Some(snapshot),
+                    Err(error) => log::error!("Can't snapshot physics: {}", error),
+                }
+            }
+            if let Some(snapshot) = &debugger.snapshot {
+                if ui.button("Restore").clicked() {
+                    restore = Some(snapshot.clone());
+                }
+                ui.label(format!("{:.1} KB", snapshot.len() as f64 / 1024.0));
+            }
+        });
+    });
+    // Outside the debugger's borrow: restoring moves the entities
+    if let Some(snapshot) = restore {
+        if let Err(error) = restore_snapshot(world, &snapshot) {
+            log::error!("Can't restore physics: {}", error);
+        }
+    }
 }
 
 fn draw_box(
@@ -0,0 +1,33 @@
+use catalyst_core::profiling;
+
+const DUMP_FRAMES: usize = 120;
+const DUMP_PATH: &str = "trace.json";
+
+/// Recording toggle, the phase timings of the last frame and a button writing the
+/// last frames as a chrome trace. Same data as the profile.* console commands.
+pub fn profiler_section(ui: &mut egui::Ui) {
+    let mut enabled = profiling::is_enabled();
+    if ui.checkbox(&mut enabled, "Record").changed() {
+        profiling::set_enabled(enabled);
+    }
+
+    let phases = profiling::last_frame();
+    if enabled {
+        let total: f32 = phases.iter().map(|(_, milliseconds)| milliseconds).sum();
+        ui.label(format!("Frame: {:.3} ms", total));
+        for (phase, milliseconds) in &phases {
+            ui.label(format!("  {}: {:.3} ms", phase, milliseconds));
+        }
+    }
+
+    if ui
+        .button(format!("Dump last {} frames to {}", DUMP_FRAMES, DUMP_PATH))
+        .clicked()
+    {
+        let spans = profiling::capture(DUMP_FRAMES);
+        match profiling::write_chrome_trace(&spans, DUMP_PATH) {
+            Ok(()) => log::info!("Wrote {} spans to {}", spans.len(), DUMP_PATH),
+            Err(e) => log::error!("Could not write {}: {}", DUMP_PATH, e),
+        }
+    }
+}
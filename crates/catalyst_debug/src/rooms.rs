@@ -0,0 +1,80 @@
+use catalyst_core::{
+    rooms::{Portal, Room},
+    transform::GlobalTransform,
+};
+use catalyst_renderer::{PortalCulling, render::DebugDraw3D};
+use flecs_ecs::prelude::*;
+use glam::{Vec3, Vec4};
+
+use crate::GuiState;
+
+const VISIBLE_ROOM_COLOR: Vec4 = Vec4::new(0.2, 1.0, 0.3, 1.0);
+const HIDDEN_ROOM_COLOR: Vec4 = Vec4::new(0.6, 0.2, 0.2, 1.0);
+const PORTAL_COLOR: Vec4 = Vec4::new(1.0, 0.8, 0.2, 1.0);
+
+/// What the portal culling gizmos draw while the debug GUI is open.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct RoomDebug {
+    pub draw_rooms: bool,
+}
+
+impl Default for RoomDebug {
+    fn default() -> Self {
+        Self { draw_rooms: true }
+    }
+}
+
+pub fn debug_room_system(app: &mut catalyst_core::App) {
+    app.register_singleton_default::<RoomDebug>();
+
+    // Room boxes, the ones a camera saw into last frame highlighted
+    app.world
+        .system_named::<(
+            &Room,
+            &GlobalTransform,
+            &PortalCulling,
+            &RoomDebug,
+            &GuiState,
+            &mut DebugDraw3D,
+        )>("debug_rooms")
+        .kind(flecs::pipeline::OnUpdate)
+        .each_entity(|entity, (room, global, culling, settings, gui_state, debug)| {
+            if !gui_state.enabled || !settings.draw_rooms {
+                return;
+            }
+
+            let color = if culling.is_visible(entity.id()) {
+                VISIBLE_ROOM_COLOR
+            } else {
+                HIDDEN_ROOM_COLOR
+            };
+            let corner = |x: f32, y: f32, z: f32| {
+                global.transform_point3(room.half_extents * Vec3::new(x, y, z))
+            };
+
+            for (x, y) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+                debug.push_line(corner(x, y, -1.0), corner(x, y, 1.0), color);
+                debug.push_line(corner(x, -1.0, y), corner(x, 1.0, y), color);
+                debug.push_line(corner(-1.0, x, y), corner(1.0, x, y), color);
+            }
+        });
+
+    app.world
+        .system_named::<(&Portal, &GlobalTransform, &RoomDebug, &GuiState, &mut DebugDraw3D)>(
+            "debug_portals",
+        )
+        .kind(flecs::pipeline::OnUpdate)
+        .each(|(portal, global, settings, gui_state, debug)| {
+            if !gui_state.enabled || !settings.draw_rooms {
+                return;
+            }
+
+            let (x, y) = (portal.half_extents.x, portal.half_extents.y);
+            let corners = [(-x, -y), (x, -y), (x, y), (-x, y)]
+                .map(|(x, y)| global.transform_point3(Vec3::new(x, y, 0.0)));
+            for i in 0..corners.len() {
+                debug.push_line(corners[i], corners[(i + 1) % corners.len()], PORTAL_COLOR);
+            }
+            debug.push_line(corners[0], corners[2], PORTAL_COLOR);
+        });
+}
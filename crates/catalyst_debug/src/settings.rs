@@ -0,0 +1,53 @@
+use catalyst_core::settings::Settings;
+use flecs_ecs::prelude::*;
+
+use crate::editors::json_fields_editor;
+
+/// Every registered settings section, edited through its serialized form, with the
+/// file they are saved to and a button writing it right away.
+pub fn settings_window(ctx: &egui::Context, world: &WorldRef) {
+    egui::Window::new("Settings")
+        .default_open(false)
+        .show(ctx, |ui| {
+            world.get::<&mut Settings>(|settings| {
+                ui.horizontal(|ui| {
+                    match settings.path() {
+                        Some(path) => ui.label(path.display().to_string()),
+                        None => ui.label("Not persisted"),
+                    };
+                    if settings.has_unsaved_changes() {
+                        ui.label("(unsaved)");
+                    }
+                });
+                if ui
+                    .add_enabled(settings.path().is_some(), egui::Button::new("Save now"))
+                    .clicked()
+                {
+                    if let Err(error) = settings.save(world) {
+                        log::warn!("Can't save settings: {}", error);
+                    }
+                }
+
+                let sections: Vec<String> = settings.sections().map(str::to_string).collect();
+                for name in sections {
+                    egui::CollapsingHeader::new(&name)
+                        .id_salt(("settings", &name))
+                        .show(ui, |ui| {
+                            let mut values = match settings.serialize(world, &name) {
+                                Some(Ok(values)) => values,
+                                Some(Err(error)) => {
+                                    ui.label(format!("Can't be shown: {}", error));
+                                    return;
+                                }
+                                None => return,
+                            };
+                            if json_fields_editor(ui, &mut values) {
+                                if let Err(error) = settings.deserialize(world, &name, values) {
+                                    log::warn!("Can't apply the {} edit: {}", name, error);
+                                }
+                            }
+                        });
+                }
+            });
+        });
+}
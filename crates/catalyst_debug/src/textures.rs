@@ -0,0 +1,80 @@
+use catalyst_assets::{asset_server::AssetServer, gc::AssetGc};
+use catalyst_renderer::TextureStreamingStats;
+use flecs_ecs::prelude::*;
+
+/// One texture with a mip chain, as listed in the Textures window.
+pub struct TextureRow {
+    pub name: String,
+    pub resident_mips: u32,
+    pub mip_count: u32,
+    pub allocated_bytes: u64,
+    pub screen_pixels: f32,
+    pub streamed: bool,
+}
+
+const MEGABYTE: f64 = 1024.0 * 1024.0;
+
+/// Streaming totals against the budget, the asset cache and garbage collection
+/// counters, then resident vs total mips per texture, biggest allocations first.
+pub fn textures_window(ctx: &egui::Context, world: &WorldRef, mut rows: Vec<TextureRow>) {
+    egui::Window::new("Textures")
+        .default_open(false)
+        .show(ctx, |ui| {
+            world.get::<&TextureStreamingStats>(|stats| {
+                ui.label(format!(
+                    "VRAM: {:.1} / {:.1} MB",
+                    stats.allocated_bytes as f64 / MEGABYTE,
+                    stats.budget_bytes as f64 / MEGABYTE
+                ));
+                ui.label(format!(
+                    "{} textures, {} streamed, {} levels streamed in last frame, {} evictions",
+                    stats.textures,
+                    stats.streamed_textures,
+                    stats.levels_streamed_in,
+                    stats.evictions
+                ));
+            });
+            world.get::<&AssetServer>(|server| {
+                let stats = server.cache().stats();
+                ui.label(format!(
+                    "Asset cache: {} hits, {} misses, {} invalid, {:.1} MB read, {:.1} MB written",
+                    stats.hits,
+                    stats.misses,
+                    stats.invalid,
+                    stats.bytes_read as f64 / MEGABYTE,
+                    stats.bytes_written as f64 / MEGABYTE
+                ));
+            });
+            world.get::<&AssetGc>(|gc| {
+                ui.label(format!(
+                    "Asset GC: {} orphaned assets collected, {} dangling lookup entries pruned",
+                    gc.orphans_collected, gc.dangling_pruned
+                ));
+            });
+
+            ui.separator();
+            rows.sort_by(|a, b| b.allocated_bytes.cmp(&a.allocated_bytes));
+            egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                egui::Grid::new("texture_residency").striped(true).show(ui, |ui| {
+                    ui.strong("Texture");
+                    ui.strong("Mips");
+                    ui.strong("MB");
+                    ui.strong("On screen");
+                    ui.end_row();
+
+                    for row in &rows {
+                        ui.label(&row.name);
+                        ui.label(format!(
+                            "{} / {}{}",
+                            row.resident_mips,
+                            row.mip_count,
+                            if row.streamed { "" } else { " (fixed)" }
+                        ));
+                        ui.label(format!("{:.2}", row.allocated_bytes as f64 / MEGABYTE));
+                        ui.label(format!("{:.0} px", row.screen_pixels));
+                        ui.end_row();
+                    }
+                });
+            });
+        });
+}
@@ -0,0 +1,706 @@
+use std::{
+    collections::VecDeque,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use catalyst_assets::material::MaterialData;
+use catalyst_core::registry::{ComponentRegistration, ComponentRegistry};
+use flecs_ecs::prelude::*;
+use serde_json::Value;
+
+/// Edits recorded with the same key merge into one undo entry until the stack is
+/// sealed, e.g. every frame of a slider drag. See `EditCommand::coalescing`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CoalesceKey(u64);
+
+impl CoalesceKey {
+    pub fn new(key: impl Hash) -> Self {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        Self(hasher.finish())
+    }
+}
+
+/// What an edit changed, with the value before and after it.
+#[derive(Clone, Debug)]
+pub enum Edit {
+    /// A component registered with ComponentRegistry, by name, in its serialized form;
+    /// None when the entity doesn't have it, so adding and removing are edits too.
+    Component {
+        entity: Entity,
+        component: &'static str,
+        old: Option<Value>,
+        new: Option<Value>,
+    },
+    /// The MaterialData of a material asset's entity.
+    Material {
+        material: Entity,
+        old: MaterialData,
+        new: MaterialData,
+    },
+}
+
+/// One edit the debug tooling made to the world, recorded on the UndoStack.
+#[derive(Clone, Debug)]
+pub struct EditCommand {
+    pub edit: Edit,
+    key: Option<CoalesceKey>,
+    // Rough memory use, for UndoStack::max_bytes
+    bytes: usize,
+}
+
+impl EditCommand {
+    pub fn component(
+        entity: Entity,
+        component: &'static str,
+        old: Option<Value>,
+        new: Option<Value>,
+    ) -> Self {
+        Self::new(Edit::Component {
+            entity,
+            component,
+            old,
+            new,
+        })
+    }
+
+    pub fn material(material: Entity, old: MaterialData, new: MaterialData) -> Self {
+        Self::new(Edit::Material { material, old, new })
+    }
+
+    fn new(edit: Edit) -> Self {
+        let mut command = Self {
+            edit,
+            key: None,
+            bytes: 0,
+        };
+        command.bytes = command.estimate_bytes();
+        command
+    }
+
+    fn estimate_bytes(&self) -> usize {
+        match &self.edit {
+            Edit::Component { old, new, .. } => [old, new]
+                .into_iter()
+                .flatten()
+                .map(|value| value.to_string().len())
+                .sum(),
+            Edit::Material { .. } => 2 * std::mem::size_of::<MaterialData>(),
+        }
+    }
+
+    /// Merges the command into the last entry when that one was recorded with the same
+    /// key and the stack wasn't sealed since: the entry keeps its first old value.
+    pub fn coalescing(mut self, key: CoalesceKey) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    // Whether both change the same thing, so one's new value can replace the other's
+    fn same_target(&self, other: &EditCommand) -> bool {
+        match (&self.edit, &other.edit) {
+            (
+                Edit::Component {
+                    entity, component, ..
+                },
+                Edit::Component {
+                    entity: other_entity,
+                    component: other_component,
+                    ..
+                },
+            ) => entity == other_entity && component == other_component,
+            (
+                Edit::Material { material, .. },
+                Edit::Material {
+                    material: other, ..
+                },
+            ) => material == other,
+            _ => false,
+        }
+    }
+
+    fn take_new_value(&mut self, other: EditCommand) {
+        match (&mut self.edit, other.edit) {
+            (Edit::Component { new, .. }, Edit::Component { new: other_new, .. }) => {
+                *new = other_new;
+            }
+            (Edit::Material { new, .. }, Edit::Material { new: other_new, .. }) => {
+                *new = other_new;
+            }
+            _ => {}
+        }
+        self.bytes = self.estimate_bytes();
+    }
+
+    // Sets the value from before the edit (undo) or after it (redo). Err when the
+    // target is gone
+    fn apply(&self, world: &World, undo: bool) -> Result<(), String> {
+        match &self.edit {
+            Edit::Component {
+                entity,
+                component,
+                old,
+                new,
+            } => {
+                let target = world.entity_from_id(*entity);
+                if !target.is_alive() {
+                    return Err(format!("entity {} was despawned", entity));
+                }
+                let registration: Option<ComponentRegistration> =
+                    world.get::<&ComponentRegistry>(|registry| registry.get(component).cloned());
+                let Some(registration) = registration else {
+                    return Err(format!("no component is registered as '{}'", component));
+                };
+                match if undo { old } else { new } {
+                    Some(value) => registration.deserialize(&target, value.clone()),
+                    None => {
+                        registration.remove(&target);
+                        Ok(())
+                    }
+                }
+            }
+            Edit::Material { material, old, new } => {
+                let target = world.entity_from_id(*material);
+                if !target.is_alive() {
+                    return Err(format!("material {} was unloaded", material));
+                }
+                target.set(if undo { old.clone() } else { new.clone() });
+                Ok(())
+            }
+        }
+    }
+}
+
+/// One line of the history: a command, or a group of them undone together.
+#[derive(Clone, Debug)]
+pub struct UndoEntry {
+    pub label: String,
+    pub commands: Vec<EditCommand>,
+    key: Option<CoalesceKey>,
+    bytes: usize,
+}
+
+impl UndoEntry {
+    // A command for an existing target only updates its new value
+    fn merge(&mut self, command: EditCommand) {
+        match self
+            .commands
+            .iter_mut()
+            .find(|existing| existing.same_target(&command))
+        {
+            Some(existing) => {
+                self.bytes -= existing.bytes;
+                existing.take_new_value(command);
+                self.bytes += existing.bytes;
+            }
+            None => {
+                self.bytes += command.bytes;
+                self.commands.push(command);
+            }
+        }
+    }
+}
+
+// A group between begin_group and end_group
+struct PendingGroup {
+    depth: usize,
+    entry: UndoEntry,
+}
+
+/// History of the edits the debug tooling (inspector, Materials window) made to the
+/// world, undone with Ctrl+Z and redone with Ctrl+Y in the debug input context (see
+/// ACTION_UNDO), or from the History window.
+///
+/// Tools record an EditCommand per change with `record`; changes of several targets
+/// that belong together go between `begin_group` and `end_group` and are undone as one.
+/// Coalescing commands (see `EditCommand::coalescing`) merge into the last entry until
+/// `seal`, which the debug UI calls once the pointer is released and no field has
+/// keyboard focus, so a slider drag is one entry. Undoing a command whose target is
+/// gone (a despawned entity, an unloaded material) skips it with a warning.
+///
+/// The oldest entries are dropped past `max_entries`, or while the history takes more
+/// than `max_bytes`.
+#[derive(Component)]
+pub struct UndoStack {
+    pub max_entries: usize,
+    /// Rough: the size of the serialized values.
+    pub max_bytes: usize,
+    undo: VecDeque<UndoEntry>,
+    redo: Vec<UndoEntry>,
+    group: Option<PendingGroup>,
+    sealed: bool,
+    bytes: usize,
+}
+
+impl Default for UndoStack {
+    fn default() -> Self {
+        Self {
+            max_entries: 100,
+            max_bytes: 8 * 1024 * 1024,
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            group: None,
+            sealed: true,
+            bytes: 0,
+        }
+    }
+}
+
+impl UndoStack {
+    /// Records an edit that was just made, under the group's label inside a group.
+    /// Clears the redo history.
+    pub fn record(&mut self, label: impl Into<String>, command: EditCommand) {
+        self.redo.clear();
+        if let Some(group) = &mut self.group {
+            group.entry.merge(command);
+            return;
+        }
+
+        let key = command.key;
+        let mut entry = UndoEntry {
+            label: label.into(),
+            commands: Vec::new(),
+            key,
+            bytes: 0,
+        };
+        entry.merge(command);
+        self.push(entry);
+    }
+
+    /// Starts a group: what is recorded until the matching `end_group` is one entry.
+    /// Groups can nest, the outermost one gives the label. With a key, the group
+    /// coalesces with the last entry like a command does.
+    pub fn begin_group(&mut self, label: impl Into<String>, key: Option<CoalesceKey>) {
+        match &mut self.group {
+            Some(group) => group.depth += 1,
+            None => {
+                self.group = Some(PendingGroup {
+                    depth: 1,
+                    entry: UndoEntry {
+                        label: label.into(),
+                        commands: Vec::new(),
+                        key,
+                        bytes: 0,
+                    },
+                });
+            }
+        }
+    }
+
+    pub fn end_group(&mut self) {
+        let Some(group) = &mut self.group else {
+            log::warn!("UndoStack::end_group without a group");
+            return;
+        };
+        group.depth -= 1;
+        if group.depth > 0 {
+            return;
+        }
+        let entry = self.group.take().unwrap().entry;
+        if !entry.commands.is_empty() {
+            self.push(entry);
+        }
+    }
+
+    /// Ends coalescing: the next command starts an entry of its own.
+    pub fn seal(&mut self) {
+        self.sealed = true;
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Entries that can be undone, oldest first.
+    pub fn undo_entries(&self) -> impl DoubleEndedIterator<Item = &UndoEntry> {
+        self.undo.iter()
+    }
+
+    /// Entries that can be redone, the next one to redo last.
+    pub fn redo_entries(&self) -> impl DoubleEndedIterator<Item = &UndoEntry> {
+        self.redo.iter()
+    }
+
+    /// Rough memory the history takes, see `max_bytes`.
+    pub fn bytes(&self) -> usize {
+        self.bytes + self.redo.iter().map(|entry| entry.bytes).sum::<usize>()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.bytes = 0;
+        self.sealed = true;
+    }
+
+    /// Undoes the last entry. False when there was nothing to undo.
+    pub fn undo(world: &World) -> bool {
+        let entry = world.get::<&mut UndoStack>(|stack| {
+            stack.sealed = true;
+            let entry = stack.undo.pop_back()?;
+            stack.bytes -= entry.bytes;
+            Some(entry)
+        });
+        let Some(entry) = entry else {
+            return false;
+        };
+
+        // Outside the borrow: setting components runs their observers. Last change first
+        for command in entry.commands.iter().rev() {
+            if let Err(error) = command.apply(world, true) {
+                log::warn!("Can't undo part of '{}': {}", entry.label, error);
+            }
+        }
+        world.get::<&mut UndoStack>(|stack| stack.redo.push(entry));
+        true
+    }
+
+    /// Redoes the last entry undone. False when there was nothing to redo.
+    pub fn redo(world: &World) -> bool {
+        let entry = world.get::<&mut UndoStack>(|stack| {
+            stack.sealed = true;
+            stack.redo.pop()
+        });
+        let Some(entry) = entry else {
+            return false;
+        };
+
+        for command in &entry.commands {
+            if let Err(error) = command.apply(world, false) {
+                log::warn!("Can't redo part of '{}': {}", entry.label, error);
+            }
+        }
+        world.get::<&mut UndoStack>(|stack| {
+            stack.bytes += entry.bytes;
+            stack.undo.push_back(entry);
+        });
+        true
+    }
+
+    fn push(&mut self, entry: UndoEntry) {
+        let coalesces = !self.sealed
+            && entry.key.is_some()
+            && self.undo.back().is_some_and(|last| last.key == entry.key);
+        self.sealed = false;
+
+        if coalesces {
+            let last = self.undo.back_mut().unwrap();
+            self.bytes -= last.bytes;
+            for command in entry.commands {
+                last.merge(command);
+            }
+            self.bytes += last.bytes;
+        } else {
+            self.bytes += entry.bytes;
+            self.undo.push_back(entry);
+        }
+
+        // The newest entry always stays, however large
+        while self.undo.len() > self.max_entries.max(1)
+            || (self.bytes > self.max_bytes && self.undo.len() > 1)
+        {
+            let oldest = self.undo.pop_front().unwrap();
+            self.bytes -= oldest.bytes;
+        }
+    }
+}
+
+/// The UndoStack's entries, newest first, with the undone ones that can still be
+/// redone above them, greyed out.
+pub fn history_window(ctx: &egui::Context, world: &WorldRef) {
+    let mut undo = false;
+    let mut redo = false;
+    egui::Window::new("History")
+        .default_open(false)
+        .show(ctx, |ui| {
+            world.get::<&mut UndoStack>(|stack| {
+                ui.horizontal(|ui| {
+                    undo = ui
+                        .add_enabled(stack.can_undo(), egui::Button::new("Undo"))
+                        .on_hover_text("Ctrl+Z")
+                        .clicked();
+                    redo = ui
+                        .add_enabled(stack.can_redo(), egui::Button::new("Redo"))
+                        .on_hover_text("Ctrl+Y")
+                        .clicked();
+                    if ui.button("Clear").clicked() {
+                        stack.clear();
+                    }
+                });
+                ui.label(format!(
+                    "{} of {} entries, {:.1} KB",
+                    stack.undo.len(),
+                    stack.max_entries,
+                    stack.bytes() as f64 / 1024.0
+                ));
+                ui.separator();
+
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for entry in stack.redo_entries() {
+                            ui.weak(entry_label(entry));
+                        }
+                        for entry in stack.undo_entries().rev() {
+                            ui.label(entry_label(entry));
+                        }
+                    });
+            });
+        });
+
+    // Outside the borrow above: undoing takes the stack itself
+    if undo {
+        UndoStack::undo(world);
+    }
+    if redo {
+        UndoStack::redo(world);
+    }
+}
+
+fn entry_label(entry: &UndoEntry) -> String {
+    match entry.commands.len() {
+        1 => entry.label.clone(),
+        changes => format!("{} ({} changes)", entry.label, changes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use catalyst_core::{App, transform::Transform};
+
+    use super::*;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.register_singleton_default::<UndoStack>();
+        app
+    }
+
+    fn translation_x(world: &World, entity: Entity) -> f32 {
+        world
+            .entity_from_id(entity)
+            .get::<&Transform>(|transform| transform.translation.x)
+    }
+
+    fn entries(world: &World) -> usize {
+        world.get::<&UndoStack>(|stack| stack.undo_entries().count())
+    }
+
+    // What the inspector does for one frame of an edit: set the component, record it
+    fn move_to(world: &World, entity: Entity, x: f32, coalesce: bool) {
+        let view = world.entity_from_id(entity);
+        let old = view.get::<&Transform>(|transform| transform.clone());
+        let mut new = old.clone();
+        new.translation.x = x;
+        view.set(new.clone());
+
+        let mut command = EditCommand::component(
+            entity,
+            "Transform",
+            serde_json::to_value(old).ok(),
+            serde_json::to_value(new).ok(),
+        );
+        if coalesce {
+            command = command.coalescing(CoalesceKey::new((entity, "Transform")));
+        }
+        world.get::<&mut UndoStack>(|stack| stack.record("Move", command));
+    }
+
+    fn move_selection(world: &World, selection: &[Entity], frames: usize) {
+        for frame in 1..=frames {
+            world.get::<&mut UndoStack>(|stack| {
+                stack.begin_group("Move 5 entities", Some(CoalesceKey::new("gizmo")));
+            });
+            for (index, entity) in selection.iter().enumerate() {
+                move_to(world, *entity, index as f32 + frame as f32, false);
+            }
+            world.get::<&mut UndoStack>(|stack| stack.end_group());
+        }
+        world.get::<&mut UndoStack>(|stack| stack.seal());
+    }
+
+    fn spawn_selection(world: &World) -> Vec<Entity> {
+        (0..5)
+            .map(|index| {
+                world
+                    .entity()
+                    .set(Transform::from_xyz(index as f32, 0.0, 0.0))
+                    .id()
+            })
+            .collect()
+    }
+
+    fn entry(key: Option<CoalesceKey>, command: EditCommand) -> UndoEntry {
+        UndoEntry {
+            label: "Edit".to_string(),
+            bytes: command.bytes,
+            commands: vec![command],
+            key,
+        }
+    }
+
+    #[test]
+    fn push_coalesces_entries_with_the_same_key_until_sealed() {
+        let world = World::new();
+        let material = world.entity().id();
+        let key = CoalesceKey::new("slider");
+        let edit = |from: f32, to: f32| {
+            let mut old = MaterialData::default();
+            old.settings.roughness = from;
+            let mut new = MaterialData::default();
+            new.settings.roughness = to;
+            EditCommand::material(material, old, new).coalescing(key)
+        };
+
+        let mut stack = UndoStack::default();
+        stack.push(entry(Some(key), edit(0.0, 0.1)));
+        stack.push(entry(Some(key), edit(0.1, 0.2)));
+        stack.push(entry(Some(key), edit(0.2, 0.3)));
+        assert_eq!(stack.undo_entries().count(), 1);
+        let Edit::Material { old, new, .. } =
+            &stack.undo_entries().next().unwrap().commands[0].edit
+        else {
+            panic!("expected a material edit");
+        };
+        assert_eq!((old.settings.roughness, new.settings.roughness), (0.0, 0.3));
+
+        stack.seal();
+        stack.push(entry(Some(key), edit(0.3, 0.4)));
+        assert_eq!(stack.undo_entries().count(), 2);
+
+        // A different key, or none, never merges
+        stack.push(entry(Some(CoalesceKey::new("other")), edit(0.4, 0.5)));
+        stack.push(entry(None, edit(0.5, 0.6)));
+        stack.push(entry(None, edit(0.6, 0.7)));
+        assert_eq!(stack.undo_entries().count(), 5);
+    }
+
+    #[test]
+    fn drags_coalesce_and_undo_to_where_they_started() {
+        let app = app();
+        let world = &app.world;
+        let cube = world.entity().set(Transform::default()).id();
+
+        for frame in 1..=30 {
+            move_to(world, cube, frame as f32 * 0.1, true);
+        }
+        world.get::<&mut UndoStack>(|stack| stack.seal());
+        for frame in 1..=30 {
+            move_to(world, cube, 3.0 + frame as f32 * 0.1, true);
+        }
+        world.get::<&mut UndoStack>(|stack| stack.seal());
+        assert_eq!(entries(world), 2);
+
+        assert!(UndoStack::undo(world));
+        assert!((translation_x(world, cube) - 3.0).abs() < 1e-5);
+        assert!(UndoStack::undo(world));
+        assert_eq!(translation_x(world, cube), 0.0);
+        assert!(!UndoStack::undo(world));
+
+        assert!(UndoStack::redo(world));
+        assert!((translation_x(world, cube) - 3.0).abs() < 1e-5);
+
+        // A new edit after an undo drops what could be redone
+        move_to(world, cube, 10.0, false);
+        assert!(world.get::<&UndoStack>(|stack| !stack.can_redo()));
+    }
+
+    #[test]
+    fn a_group_undoes_and_redoes_together() {
+        let app = app();
+        let world = &app.world;
+        let selection = spawn_selection(world);
+
+        move_selection(world, &selection, 10);
+        let group = world.get::<&UndoStack>(|stack| {
+            stack
+                .undo_entries()
+                .last()
+                .map(|entry| (entry.label.clone(), entry.commands.len()))
+        });
+        assert_eq!(group, Some(("Move 5 entities".to_string(), 5)));
+        assert_eq!(entries(world), 1);
+
+        assert!(UndoStack::undo(world));
+        for (index, entity) in selection.iter().enumerate() {
+            assert_eq!(translation_x(world, *entity), index as f32);
+        }
+        assert!(UndoStack::redo(world));
+        for (index, entity) in selection.iter().enumerate() {
+            assert_eq!(translation_x(world, *entity), index as f32 + 10.0);
+        }
+    }
+
+    #[test]
+    fn apply_on_a_despawned_entity_is_an_error() {
+        let app = app();
+        let world = &app.world;
+        let cube = world.entity().set(Transform::default()).id();
+        let command = EditCommand::component(
+            cube,
+            "Transform",
+            serde_json::to_value(Transform::default()).ok(),
+            serde_json::to_value(Transform::from_xyz(1.0, 0.0, 0.0)).ok(),
+        );
+        assert!(command.apply(world, false).is_ok());
+        assert_eq!(translation_x(world, cube), 1.0);
+
+        world.entity_from_id(cube).destruct();
+        assert!(command.apply(world, true).is_err());
+        assert!(command.apply(world, false).is_err());
+    }
+
+    #[test]
+    fn undo_skips_a_despawned_entity_and_restores_the_rest_of_its_group() {
+        let app = app();
+        let world = &app.world;
+        let selection = spawn_selection(world);
+        move_selection(world, &selection, 10);
+
+        world.entity_from_id(selection[2]).destruct();
+        assert!(UndoStack::undo(world));
+        for (index, entity) in selection.iter().enumerate() {
+            if index != 2 {
+                assert_eq!(translation_x(world, *entity), index as f32);
+            }
+        }
+        assert!(UndoStack::redo(world));
+        for (index, entity) in selection.iter().enumerate() {
+            if index != 2 {
+                assert_eq!(translation_x(world, *entity), index as f32 + 10.0);
+            }
+        }
+    }
+
+    #[test]
+    fn history_is_bounded_by_entries_then_bytes() {
+        let app = app();
+        let world = &app.world;
+        let cube = world.entity().set(Transform::default()).id();
+
+        world.get::<&mut UndoStack>(|stack| stack.max_entries = 3);
+        for index in 0..5 {
+            move_to(world, cube, index as f32, false);
+        }
+        assert_eq!(entries(world), 3);
+        UndoStack::undo(world);
+        assert_eq!(translation_x(world, cube), 3.0);
+
+        world.get::<&mut UndoStack>(|stack| {
+            stack.clear();
+            stack.max_entries = 100;
+            stack.max_bytes = 1024;
+        });
+        for index in 0..50 {
+            move_to(world, cube, index as f32, false);
+        }
+        let (count, bytes) =
+            world.get::<&UndoStack>(|stack| (stack.undo_entries().count(), stack.bytes()));
+        assert!(count < 50);
+        assert!(bytes <= 1024);
+    }
+}
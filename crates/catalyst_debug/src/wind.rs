@@ -0,0 +1,29 @@
+use catalyst_renderer::WindSettings;
+use flecs_ecs::prelude::*;
+
+/// Edits the WindSettings singleton in place.
+pub fn wind_section(ui: &mut egui::Ui, world: &WorldRef) {
+    world.get::<&mut WindSettings>(|wind| {
+        ui.checkbox(&mut wind.enabled, "Enabled");
+        // The direction as a heading is easier to drag than its two components
+        let [x, z] = wind.direction;
+        let mut heading = z.atan2(x).to_degrees();
+        if ui
+            .add(egui::Slider::new(&mut heading, -180.0..=180.0).text("Heading (degrees)"))
+            .changed()
+        {
+            let radians = heading.to_radians();
+            wind.direction = [radians.cos(), radians.sin()];
+        }
+        ui.add(egui::Slider::new(&mut wind.strength, 0.0..=4.0).text("Strength"));
+        ui.add(egui::Slider::new(&mut wind.gust_strength, 0.0..=2.0).text("Gust strength"));
+        ui.add(egui::Slider::new(&mut wind.gust_frequency, 0.0..=1.0).text("Gusts per second"));
+        let current = wind.wind();
+        ui.label(format!(
+            "Now: {:.2} ({:.2}, {:.2})",
+            current.length(),
+            current.x,
+            current.y
+        ));
+    });
+}
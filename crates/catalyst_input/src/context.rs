@@ -1,3 +1,13 @@
+use std::collections::HashMap;
+
+use catalyst_core::game_state::{
+    GameStateChange, GameStateChanged, GameStateId, GameStates, STATE_GAMEPLAY, STATE_LOADING,
+    STATE_MENU,
+};
+use flecs_ecs::prelude::*;
+
+use crate::{physical::InputState, player::PlayerInputs};
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
 pub struct ContextId(pub u32);
 
@@ -6,4 +16,161 @@ pub const CTX_UI: ContextId = ContextId(2);
 pub const CTX_VEHICLE: ContextId = ContextId(3);
 pub const CTX_DEBUG: ContextId = ContextId(4);
 
+/// The input context of each game state, in the singleton of the same name. Entering a
+/// state makes its context the only active one, for InputState and every player;
+/// popping a pushed state puts back the contexts that were active before the push,
+/// whatever they were. States without a context leave the contexts alone.
+#[derive(Component, Debug)]
+pub struct GameStateContexts {
+    contexts: HashMap<GameStateId, ContextId>,
+    // Active contexts before each push, InputState first, then each player
+    saved: Vec<Vec<Vec<ContextId>>>,
+}
+
+impl Default for GameStateContexts {
+    fn default() -> Self {
+        Self {
+            contexts: HashMap::from([
+                (STATE_GAMEPLAY, CTX_GAMEPLAY),
+                (STATE_MENU, CTX_UI),
+                (STATE_LOADING, CTX_UI),
+            ]),
+            saved: Vec::new(),
+        }
+    }
+}
+
+impl GameStateContexts {
+    pub fn set(&mut self, state: GameStateId, context: ContextId) {
+        self.contexts.insert(state, context);
+    }
+
+    /// Leaves the contexts alone when `state` is entered.
+    pub fn clear(&mut self, state: GameStateId) {
+        self.contexts.remove(&state);
+    }
+
+    pub fn get(&self, state: GameStateId) -> Option<ContextId> {
+        self.contexts.get(&state).copied()
+    }
+}
+
+fn enter(input: &mut InputState, players: &mut PlayerInputs, context: ContextId) {
+    input.set_context(context);
+    for player in &mut players.players {
+        player.set_context(context);
+    }
+}
+
+fn follow_game_state(world: &World, changed: &GameStateChanged) {
+    let (context, saved) = world.get::<&mut GameStateContexts>(|contexts| {
+        let saved = match changed.change {
+            GameStateChange::Popped => contexts.saved.pop(),
+            _ => None,
+        };
+        (contexts.get(changed.to), saved)
+    });
+
+    world.get::<&mut InputState>(|input| {
+        world.get::<&mut PlayerInputs>(|players| match changed.change {
+            GameStateChange::Pushed => {
+                let mut active = vec![input.active_contexts.clone()];
+                active.extend(players.players.iter().map(|p| p.active_contexts.clone()));
+                world.get::<&mut GameStateContexts>(|contexts| contexts.saved.push(active));
+                if let Some(context) = context {
+                    enter(input, players, context);
+                }
+            }
+            GameStateChange::Popped => {
+                let Some(saved) = saved else {
+                    return;
+                };
+                let mut saved = saved.into_iter();
+                if let Some(active) = saved.next() {
+                    input.active_contexts = active;
+                }
+                // Players added during the push keep what they have
+                for (player, active) in players.players.iter_mut().zip(saved) {
+                    player.active_contexts = active;
+                }
+            }
+            GameStateChange::Replaced => {
+                if let Some(context) = context {
+                    enter(input, players, context);
+                }
+            }
+        })
+    });
+}
+
+pub(crate) fn register_game_state_contexts(world: &World) {
+    world
+        .entity_from::<GameStates>()
+        .observe_payload_entity::<&GameStateChanged>(|entity, changed| {
+            follow_game_state(&entity.world(), changed);
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use catalyst_core::{App, game_state::GameState};
+
+    use super::*;
+    use crate::InputPlugin;
+
+    const STATE_CUTSCENE: GameStateId = GameStateId(100);
+    const CTX_LOADING: ContextId = ContextId(50);
+
+    // InputState's contexts, then each player's
+    fn contexts(app: &App) -> Vec<Vec<ContextId>> {
+        let mut all = vec![
+            app.world
+                .get::<&InputState>(|input| input.active_contexts.clone()),
+        ];
+        app.world.get::<&PlayerInputs>(|players| {
+            all.extend(players.players.iter().map(|p| p.active_contexts.clone()));
+        });
+        all
+    }
+
+    fn change_state(app: &mut App, change: impl FnOnce(&mut GameStates)) {
+        app.world.get::<&mut GameStates>(change);
+        app.update();
+    }
+
+    #[test]
+    fn input_contexts_follow_the_game_state_stack() {
+        // Two players, with gameplay and vehicle active
+        let mut app = App::new();
+        app.add_plugin(InputPlugin);
+        app.world.set(PlayerInputs::new(2));
+        app.world.get::<&mut InputState>(|input| {
+            input.push_context(CTX_GAMEPLAY);
+            input.push_context(CTX_VEHICLE);
+        });
+        app.world.get::<&mut GameStateContexts>(|contexts| {
+            contexts.set(STATE_LOADING, CTX_LOADING);
+            contexts.clear(STATE_CUTSCENE);
+        });
+        app.world.get::<&mut GameStates>(|states| {
+            states.define(GameState::new(STATE_CUTSCENE, "cutscene"));
+        });
+        let before = contexts(&app);
+
+        // Each state makes its context the only active one, for every player too
+        change_state(&mut app, |states| states.push(STATE_MENU));
+        assert_eq!(contexts(&app), vec![vec![CTX_UI]; 3]);
+        change_state(&mut app, |states| states.push(STATE_LOADING));
+        assert_eq!(contexts(&app), vec![vec![CTX_LOADING]; 3]);
+        change_state(&mut app, |states| states.pop());
+        assert_eq!(contexts(&app), vec![vec![CTX_UI]; 3]);
+
+        // Popping the menu restores the contexts active before it exactly
+        change_state(&mut app, |states| states.pop());
+        assert_eq!(contexts(&app), before);
 
+        // A state without a context leaves them alone
+        change_state(&mut app, |states| states.replace(STATE_CUTSCENE));
+        assert_eq!(contexts(&app), before);
+    }
+}
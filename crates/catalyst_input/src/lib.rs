@@ -1,14 +1,27 @@
 use catalyst_core::{App, Plugin};
 use flecs_ecs::prelude::*;
 
+use catalyst_core::console::ConsoleCommands;
+
 use crate::{
+    context::{GameStateContexts, register_game_state_contexts},
     logical::{InputMap, register_sys_input_map},
     physical::{InputState, register_input_systems},
+    player::PlayerInputs,
+    processing::AxisSettings,
+    replay::{InputReplay, register_replay_commands},
+    rumble::{Rumble, RumbleSettings, register_rumble, register_rumble_commands},
+    timeline::{FixedInput, InputTimeline},
 };
 
 pub mod logical;
 pub mod physical;
 pub mod context;
+pub mod player;
+pub mod processing;
+pub mod replay;
+pub mod rumble;
+pub mod timeline;
 
 pub struct InputPlugin;
 
@@ -16,9 +29,24 @@ impl Plugin for InputPlugin {
     fn build(&self, app: &mut catalyst_core::App) {
         app.register_singleton_default::<InputState>();
         app.register_singleton_default::<InputMap>();
+        app.register_singleton_default::<PlayerInputs>();
+        app.register_singleton_default::<InputReplay>();
+        app.register_singleton_default::<InputTimeline>();
+        app.register_singleton_default::<FixedInput>();
+        app.register_singleton_default::<Rumble>();
+        app.register_singleton_default::<GameStateContexts>();
+        app.register_settings::<RumbleSettings>("rumble");
+        app.register_settings::<AxisSettings>("axes");
+
+        app.world.get::<&mut ConsoleCommands>(|commands| {
+            register_replay_commands(commands);
+            register_rumble_commands(commands);
+        });
 
         register_input_systems(app);
         register_sys_input_map(app);
+        register_rumble(&app.world);
+        register_game_state_contexts(&app.world);
     }
 }
 
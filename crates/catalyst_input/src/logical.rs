@@ -1,13 +1,16 @@
 use crate::{
     context::{CTX_GAMEPLAY, ContextId},
-    physical::{InputState, PhysicalInputId},
+    physical::{DeviceKind, InputState, MouseAxisId, MouseButtonId, PhysicalInputId},
+    player::PlayerInputs,
+    processing::AxisSettings,
 };
-use catalyst_core::App;
+use catalyst_core::{App, time::Time};
 use flecs_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
 pub struct ActionId(pub u32);
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct AxisId(pub u32);
 
 #[derive(Clone, Debug)]
@@ -38,9 +41,14 @@ pub struct ActionState {
     pub phase: ButtonPhase,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct AxisState {
+    /// After the axis's processor in AxisSettings, if it has one.
     pub value: f32,
+    /// The bindings' sum, before processing.
+    pub raw: f32,
+    // The processor's smoothing, carried between frames
+    pub(crate) smoothed: Option<f32>,
 }
 
 #[derive(Component, Default, Clone, Debug)]
@@ -61,7 +69,7 @@ impl InputMap {
     ) -> &mut Self {
         self.bindings.push(InputBinding {
             physical: PhysicalInputId {
-                device: crate::physical::DeviceKind::Keyboard(key_code),
+                device: DeviceKind::Keyboard(key_code),
             },
             kind: BindingKind::Button { action },
             context,
@@ -69,72 +77,150 @@ impl InputMap {
 
         self
     }
+
+    pub fn bind_gamepad_button(&mut self, button: u16, action: ActionId) -> &mut Self {
+        self.bindings.push(InputBinding {
+            physical: PhysicalInputId {
+                device: DeviceKind::GamepadButton(button),
+            },
+            kind: BindingKind::Button { action },
+            context: CTX_GAMEPLAY,
+        });
+
+        self
+    }
+
+    pub fn bind_mouse_button(&mut self, button: MouseButtonId, action: ActionId) -> &mut Self {
+        self.bindings.push(InputBinding {
+            physical: PhysicalInputId {
+                device: DeviceKind::MouseButton(button),
+            },
+            kind: BindingKind::Button { action },
+            context: CTX_GAMEPLAY,
+        });
+
+        self
+    }
+
+    pub fn bind_gamepad_axis(&mut self, gamepad_axis: u16, axis: AxisId, scale: f32) -> &mut Self {
+        self.bindings.push(InputBinding {
+            physical: PhysicalInputId {
+                device: DeviceKind::GamepadAxis(gamepad_axis),
+            },
+            kind: BindingKind::Axis { axis, scale },
+            context: CTX_GAMEPLAY,
+        });
+
+        self
+    }
+
+    /// Binds the mouse's motion during the frame, in pixels; see
+    /// `AxisProcessor::per_frame` to process it as a speed.
+    pub fn bind_mouse_axis(
+        &mut self,
+        mouse_axis: MouseAxisId,
+        axis: AxisId,
+        scale: f32,
+    ) -> &mut Self {
+        self.bindings.push(InputBinding {
+            physical: PhysicalInputId {
+                device: DeviceKind::MouseAxis(mouse_axis as u16),
+            },
+            kind: BindingKind::Axis { axis, scale },
+            context: CTX_GAMEPLAY,
+        });
+
+        self
+    }
 }
 
 pub fn register_sys_input_map(app: &mut App) {
     app.world
-        .system_named::<(&InputMap, &mut InputState)>("sys_input_map")
+        .system_named::<(
+            &InputMap,
+            &mut InputState,
+            &mut PlayerInputs,
+            &AxisSettings,
+            &Time,
+        )>("sys_input_map")
         .kind(flecs::pipeline::OnUpdate)
         .run(|mut iter| {
             while iter.next() {
                 let input_map = &iter.field::<&InputMap>(0)[0];
+                let axis_settings = &iter.field::<&AxisSettings>(3)[0];
+                let dt = iter.field::<&Time>(4)[0].delta_seconds();
                 let input_state = &mut iter.field_mut::<&InputState>(1)[0];
+                apply_bindings(input_map, input_state);
+                axis_settings.process(input_state, dt);
 
-                // Reset logical state
-                for (_, action) in input_state.actions.iter_mut() {
-                    let was_held = action.phase.contains(ButtonPhase::HELD);
-                    action.phase = if was_held {
-                        ButtonPhase::HELD
-                    } else {
-                        ButtonPhase::NONE
-                    };
-                }
-                for (_, axis) in input_state.axes.iter_mut() {
-                    axis.value = 0.0;
+                // Local multiplayer: same bindings, resolved against each player's own devices
+                let player_inputs = &mut iter.field_mut::<&PlayerInputs>(2)[0];
+                for player_state in player_inputs.players.iter_mut() {
+                    apply_bindings(input_map, player_state);
+                    axis_settings.process(player_state, dt);
                 }
+            }
+        });
+}
 
-                // Apply bindings
-                for binding in &input_map.bindings {
-                    if !input_state.active_contexts.contains(&binding.context) {
-                        continue;
-                    }
+pub(crate) fn apply_bindings(input_map: &InputMap, input_state: &mut InputState) {
+    // Reset logical state
+    for (_, action) in input_state.actions.iter_mut() {
+        let was_held = action.phase.contains(ButtonPhase::HELD);
+        action.phase = if was_held {
+            ButtonPhase::HELD
+        } else {
+            ButtonPhase::NONE
+        };
+    }
+    for (_, axis) in input_state.axes.iter_mut() {
+        axis.value = 0.0;
+    }
+
+    // Apply bindings
+    for binding in &input_map.bindings {
+        if !input_state.active_contexts.contains(&binding.context) {
+            continue;
+        }
 
-                    match binding.kind {
-                        BindingKind::Button { action } => {
-                            let pressed = *input_state
-                                .physical_buttons
-                                .get(&binding.physical)
-                                .unwrap_or(&false);
-                            let entry = input_state.actions.entry(action).or_insert(ActionState {
-                                phase: ButtonPhase::NONE,
-                            });
-                            if pressed {
-                                if !entry.phase.contains(ButtonPhase::HELD) {
-                                    entry.phase |= ButtonPhase::PRESSED | ButtonPhase::HELD;
-                                } else {
-                                    entry.phase |= ButtonPhase::HELD;
-                                }
-                            } else {
-                                if entry.phase.contains(ButtonPhase::HELD) {
-                                    entry.phase &= !ButtonPhase::HELD;
-                                    entry.phase |= ButtonPhase::RELEASED;
-                                }
-                            }
-                        }
-                        BindingKind::Axis { axis, scale } => {
-                            let value = input_state
-                                .physical_axes
-                                .get(&binding.physical)
-                                .copied()
-                                .unwrap_or(0.0);
-                            let entry = input_state
-                                .axes
-                                .entry(axis)
-                                .or_insert(AxisState { value: 0.0 });
-                            entry.value += value * scale;
-                        }
+        match binding.kind {
+            BindingKind::Button { action } => {
+                let pressed = *input_state
+                    .physical_buttons
+                    .get(&binding.physical)
+                    .unwrap_or(&false);
+                let entry = input_state.actions.entry(action).or_insert(ActionState {
+                    phase: ButtonPhase::NONE,
+                });
+                if pressed {
+                    if !entry.phase.contains(ButtonPhase::HELD) {
+                        entry.phase |= ButtonPhase::PRESSED | ButtonPhase::HELD;
+                    } else {
+                        entry.phase |= ButtonPhase::HELD;
+                    }
+                } else {
+                    if entry.phase.contains(ButtonPhase::HELD) {
+                        entry.phase &= !ButtonPhase::HELD;
+                        entry.phase |= ButtonPhase::RELEASED;
                     }
                 }
             }
-        });
+            BindingKind::Axis { axis, scale } => {
+                let value = match binding.physical.device {
+                    DeviceKind::MouseAxis(mouse_axis) => input_state.mouse_axis(mouse_axis),
+                    _ => input_state
+                        .physical_axes
+                        .get(&binding.physical)
+                        .copied()
+                        .unwrap_or(0.0),
+                };
+                let entry = input_state.axes.entry(axis).or_default();
+                entry.value += value * scale;
+            }
+        }
+    }
+
+    for (_, axis) in input_state.axes.iter_mut() {
+        axis.raw = axis.value;
+    }
 }
@@ -1,10 +1,11 @@
 use catalyst_core::App;
 use flecs_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-use crate::{context::ContextId, logical::{ActionId, ActionState, AxisId, AxisState}};
+use crate::{context::ContextId, logical::{ActionId, ActionState, AxisId, AxisState}, player::PlayerInputs};
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 #[repr(u16)]
 pub enum MouseButtonId {
     Left = 0,
@@ -16,15 +17,58 @@ pub enum MouseButtonId {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[repr(u16)]
+pub enum GamepadButtonId {
+    South = 0,
+    East = 1,
+    North = 2,
+    West = 3,
+    LeftTrigger = 4,
+    LeftTrigger2 = 5,
+    RightTrigger = 6,
+    RightTrigger2 = 7,
+    Select = 8,
+    Start = 9,
+    Mode = 10,
+    LeftThumb = 11,
+    RightThumb = 12,
+    DPadUp = 13,
+    DPadDown = 14,
+    DPadLeft = 15,
+    DPadRight = 16,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[repr(u16)]
+pub enum GamepadAxisId {
+    LeftStickX = 0,
+    LeftStickY = 1,
+    RightStickX = 2,
+    RightStickY = 3,
+    LeftZ = 4,
+    RightZ = 5,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[repr(u16)]
+pub enum MouseAxisId {
+    X = 0,
+    Y = 1,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum DeviceKind {
     Keyboard(u16),
     MouseButton(MouseButtonId),
-    MouseAxis,
-    GamepadButton,
-    GamepadAxis,
+    /// The mouse's motion (`mouse_delta`), by MouseAxisId.
+    MouseAxis(u16),
+    // Gamepad inputs don't carry the pad id: each player's InputState only
+    // ever receives events from the pads routed to that player.
+    GamepadButton(u16),
+    GamepadAxis(u16),
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct PhysicalInputId {
     pub device: DeviceKind,
 }
@@ -65,12 +109,36 @@ impl InputState {
             false
         }
     }
+
+    pub fn held(&self, action: ActionId) -> bool {
+        self.actions
+            .get(&action)
+            .map(|a| a.phase.contains(crate::logical::ButtonPhase::HELD))
+            .unwrap_or(false)
+    }
+
+    pub fn axis(&self, axis: AxisId) -> f32 {
+        self.axes.get(&axis).map(|a| a.value).unwrap_or(0.0)
+    }
+
+    /// The axis as the bindings summed it, before AxisSettings processed it.
+    pub fn raw_axis(&self, axis: AxisId) -> f32 {
+        self.axes.get(&axis).map(|a| a.raw).unwrap_or(0.0)
+    }
+
+    pub(crate) fn mouse_axis(&self, mouse_axis: u16) -> f32 {
+        match mouse_axis {
+            0 => self.mouse_delta.0,
+            1 => self.mouse_delta.1,
+            _ => 0.0,
+        }
+    }
 }
 
 
 pub fn register_input_systems(app: &App) {
     app.world
-        .system::<&mut InputState>()
+        .system::<(&mut InputState, &mut PlayerInputs)>()
         .kind(flecs::pipeline::OnStore)
         .run(|mut iter| {
             while iter.next() {
@@ -78,6 +146,11 @@ pub fn register_input_systems(app: &App) {
                 let input_state = input_state.get_mut(0).unwrap();
 
                 input_state.mouse_delta = (0.0, 0.0);
+
+                let mut player_inputs = iter.field_mut::<&mut PlayerInputs>(1);
+                for player_state in player_inputs.get_mut(0).unwrap().players.iter_mut() {
+                    player_state.mouse_delta = (0.0, 0.0);
+                }
             }
         });
 }
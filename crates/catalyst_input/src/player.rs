@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+
+use catalyst_core::player::PlayerIndex;
+use flecs_ecs::prelude::*;
+
+use crate::{context::CTX_GAMEPLAY, physical::InputState};
+
+/// A physical source of input events. Devices are routed to players as a whole,
+/// so one player can't accidentally read another player's gamepad.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum InputDevice {
+    KeyboardMouse,
+    Gamepad(usize),
+}
+
+/// Per-player input for local multiplayer.
+///
+/// The singleton InputState keeps receiving everything (menus, debug tools, single
+/// player games). PlayerInputs additionally partitions the same events by device,
+/// and InputMap bindings are resolved into each player's own InputState.
+#[derive(Component, Default, Debug)]
+pub struct PlayerInputs {
+    pub players: Vec<InputState>,
+    pub assignments: HashMap<InputDevice, PlayerIndex>,
+    /// Give newly seen gamepads to the first player that has no device yet.
+    pub auto_assign_gamepads: bool,
+}
+
+impl PlayerInputs {
+    /// Creates `count` players, each starting in the gameplay context.
+    /// Keyboard + mouse goes to player 0, gamepads are assigned as they show up.
+    pub fn new(count: usize) -> Self {
+        let mut inputs = Self {
+            players: Vec::new(),
+            assignments: HashMap::new(),
+            auto_assign_gamepads: true,
+        };
+        inputs.set_player_count(count);
+        if count > 0 {
+            inputs.assign(InputDevice::KeyboardMouse, PlayerIndex(0));
+        }
+        inputs
+    }
+
+    pub fn set_player_count(&mut self, count: usize) {
+        self.players.resize_with(count, || {
+            let mut state = InputState::default();
+            state.push_context(CTX_GAMEPLAY);
+            state
+        });
+        self.assignments
+            .retain(|_, player| (player.0 as usize) < count);
+    }
+
+    pub fn assign(&mut self, device: InputDevice, player: PlayerIndex) {
+        self.assignments.insert(device, player);
+    }
+
+    pub fn player_count(&self) -> usize {
+        self.players.len()
+    }
+
+    pub fn state(&self, player: PlayerIndex) -> Option<&InputState> {
+        self.players.get(player.0 as usize)
+    }
+
+    pub fn state_mut(&mut self, player: PlayerIndex) -> Option<&mut InputState> {
+        self.players.get_mut(player.0 as usize)
+    }
+
+    /// Resolves which player a device belongs to, auto-assigning gamepads if enabled.
+    pub fn player_for(&mut self, device: InputDevice) -> Option<PlayerIndex> {
+        if let Some(player) = self.assignments.get(&device) {
+            return Some(*player);
+        }
+
+        if !(self.auto_assign_gamepads && matches!(device, InputDevice::Gamepad(_))) {
+            return None;
+        }
+
+        let free_player = (0..self.players.len() as u8)
+            .map(PlayerIndex)
+            .find(|p| !self.assignments.values().any(|assigned| assigned == p))?;
+
+        log::info!("{:?} assigned to player {}", device, free_player.0);
+        self.assign(device, free_player);
+        Some(free_player)
+    }
+
+    /// The InputState that should receive events from `device`, if it belongs to any player.
+    pub fn device_state_mut(&mut self, device: InputDevice) -> Option<&mut InputState> {
+        let player = self.player_for(device)?;
+        self.state_mut(player)
+    }
+}
@@ -0,0 +1,428 @@
+//! Per-axis processing between the bindings and the logical axes.
+//!
+//! `apply_bindings` sums the physical axes (and the mouse motion) into each logical
+//! axis; a processor in `AxisSettings` then reshapes the sum before gameplay reads
+//! `InputState::axis`. In order: deadzone, response curve, sensitivity, invert and
+//! smoothing. The unprocessed sum stays readable with `InputState::raw_axis`.
+
+use catalyst_core::smoothing::exp_decay;
+use flecs_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{logical::AxisId, physical::InputState};
+
+/// Maps the magnitude of an axis to the magnitude gameplay sees; the sign is kept.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum ResponseCurve {
+    #[default]
+    Linear,
+    /// `|x|^exponent`: above 1 small deflections get finer, below 1 coarser.
+    Power(f32),
+    /// Straight lines through `[input, output]` points, sorted by input, starting
+    /// from `[0, 0]`. Past the last point the last line goes on, so a curve drawn
+    /// for a stick's 0..1 still answers mouse speeds above it.
+    Points(Vec<[f32; 2]>),
+}
+
+impl ResponseCurve {
+    pub fn evaluate(&self, value: f32) -> f32 {
+        let magnitude = value.abs();
+        let shaped = match self {
+            ResponseCurve::Linear => magnitude,
+            ResponseCurve::Power(exponent) => magnitude.powf(*exponent),
+            ResponseCurve::Points(points) => {
+                // The segment containing the magnitude, or the last one past the end
+                let mut from = [0.0, 0.0];
+                let mut to = [1.0, 1.0];
+                for (index, point) in points.iter().enumerate() {
+                    from = if index == 0 {
+                        [0.0, 0.0]
+                    } else {
+                        points[index - 1]
+                    };
+                    to = *point;
+                    if magnitude <= point[0] {
+                        break;
+                    }
+                }
+                let width = to[0] - from[0];
+                if width <= f32::EPSILON {
+                    to[1]
+                } else {
+                    from[1] + (magnitude - from[0]) / width * (to[1] - from[1])
+                }
+            }
+        };
+        shaped.copysign(value)
+    }
+}
+
+/// How one logical axis is processed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AxisProcessor {
+    pub axis: AxisId,
+    /// Magnitudes below it read 0. On a stick the rest is stretched to start from 0;
+    /// on a per-frame axis it is a speed, and slower motion is dropped as jitter.
+    pub deadzone: f32,
+    pub curve: ResponseCurve,
+    /// Scales the curve's output.
+    pub sensitivity: f32,
+    pub invert: bool,
+    /// Time constant of the exponential smoothing, in seconds: the processed value
+    /// covers 63% of a change in this long, whatever the frame rate. 0 turns it off.
+    pub smoothing: f32,
+    /// The axis is a distance moved during the frame, like the mouse, rather than a
+    /// position like a stick. Deadzone, curve and smoothing then work on its speed
+    /// (per second), and the result is turned back into a distance for the frame.
+    pub per_frame: bool,
+}
+
+impl Default for AxisProcessor {
+    fn default() -> Self {
+        Self {
+            axis: AxisId(0),
+            deadzone: 0.0,
+            curve: ResponseCurve::Linear,
+            sensitivity: 1.0,
+            invert: false,
+            smoothing: 0.0,
+            per_frame: false,
+        }
+    }
+}
+
+impl AxisProcessor {
+    pub fn new(axis: AxisId) -> Self {
+        Self {
+            axis,
+            ..Default::default()
+        }
+    }
+
+    pub fn deadzone(mut self, deadzone: f32) -> Self {
+        self.deadzone = deadzone;
+        self
+    }
+
+    pub fn curve(mut self, curve: ResponseCurve) -> Self {
+        self.curve = curve;
+        self
+    }
+
+    pub fn sensitivity(mut self, sensitivity: f32) -> Self {
+        self.sensitivity = sensitivity;
+        self
+    }
+
+    pub fn invert(mut self, invert: bool) -> Self {
+        self.invert = invert;
+        self
+    }
+
+    pub fn smoothing(mut self, smoothing: f32) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+
+    pub fn per_frame(mut self, per_frame: bool) -> Self {
+        self.per_frame = per_frame;
+        self
+    }
+
+    /// Deadzone, curve, sensitivity and invert, without smoothing. Per-frame axes
+    /// take a speed.
+    pub fn shape(&self, value: f32) -> f32 {
+        let magnitude = value.abs();
+        if magnitude < self.deadzone {
+            return 0.0;
+        }
+        let magnitude = if self.per_frame || self.deadzone <= 0.0 {
+            magnitude
+        } else {
+            (magnitude - self.deadzone) / (1.0 - self.deadzone).max(f32::EPSILON)
+        };
+        let shaped = self.curve.evaluate(magnitude.copysign(value)) * self.sensitivity;
+        if self.invert { -shaped } else { shaped }
+    }
+
+    /// Processes one frame's summed value, `dt` seconds after the last. `smoothed`
+    /// carries the smoothing from frame to frame; None starts it on the first value.
+    pub fn process(&self, raw: f32, smoothed: &mut Option<f32>, dt: f32) -> f32 {
+        let target = if self.per_frame {
+            // Without time passing there is no speed; the motion waits for a real frame
+            if dt <= 0.0 {
+                return 0.0;
+            }
+            self.shape(raw / dt)
+        } else {
+            self.shape(raw)
+        };
+        let (value, distance) = match *smoothed {
+            Some(current) if self.smoothing > 0.0 => {
+                let value = exp_decay(current, target, 1.0 / self.smoothing, dt);
+                // What the decaying speed covered over the frame, rather than the end
+                // speed times dt, so a smoothed mouse turns as far at any frame rate
+                (value, target * dt + (current - value) * self.smoothing)
+            }
+            _ => (target, target * dt),
+        };
+        *smoothed = Some(value);
+        if self.per_frame { distance } else { value }
+    }
+}
+
+/// Player preferences for the logical axes, in the "axes" settings section, applied
+/// by "sys_input_map" each frame and by `begin_fixed_step` each fixed step. Axes
+/// without a processor pass through unchanged.
+#[derive(Component, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AxisSettings {
+    pub processors: Vec<AxisProcessor>,
+}
+
+impl AxisSettings {
+    pub fn get(&self, axis: AxisId) -> Option<&AxisProcessor> {
+        self.processors
+            .iter()
+            .find(|processor| processor.axis == axis)
+    }
+
+    pub fn get_mut(&mut self, axis: AxisId) -> Option<&mut AxisProcessor> {
+        self.processors
+            .iter_mut()
+            .find(|processor| processor.axis == axis)
+    }
+
+    /// Replaces the axis's processor.
+    pub fn set(&mut self, processor: AxisProcessor) -> &mut Self {
+        match self.get_mut(processor.axis) {
+            Some(existing) => *existing = processor,
+            None => self.processors.push(processor),
+        }
+        self
+    }
+
+    /// The game's defaults: adds the processor unless the axis has one, e.g. from
+    /// the settings file.
+    pub fn set_default(&mut self, processor: AxisProcessor) -> &mut Self {
+        if self.get(processor.axis).is_none() {
+            self.processors.push(processor);
+        }
+        self
+    }
+
+    /// Processes the axes `apply_bindings` just summed, `dt` seconds after the last
+    /// time this state was processed.
+    pub fn process(&self, state: &mut InputState, dt: f32) {
+        for processor in &self.processors {
+            let axis = state.axes.entry(processor.axis).or_default();
+            axis.value = processor.process(axis.raw, &mut axis.smoothed, dt);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::{
+        context::CTX_GAMEPLAY,
+        logical::InputMap,
+        physical::{DeviceKind, GamepadAxisId, MouseAxisId, PhysicalInputId},
+        timeline::{FixedInput, InputEvent, InputTimeline},
+    };
+
+    const AXIS_LOOK: AxisId = AxisId(1);
+    const AXIS_STICK: AxisId = AxisId(2);
+    const AXIS_PLAIN: AxisId = AxisId(3);
+
+    fn assert_close(a: f32, b: f32, tolerance: f32) {
+        assert!(
+            (a - b).abs() <= tolerance,
+            "{} is not within {} of {}",
+            a,
+            tolerance,
+            b
+        );
+    }
+
+    // Runs `processor` on `raw(dt)` with frames of `frames(index)` seconds, for
+    // `duration` seconds. Returns the smoothed state and the sum of the outputs.
+    fn run(
+        processor: &AxisProcessor,
+        duration: f32,
+        frames: impl Fn(usize) -> f32,
+        raw: impl Fn(f32) -> f32,
+    ) -> (f32, f32) {
+        let mut smoothed = Some(0.0);
+        let mut time = 0.0;
+        let mut total = 0.0;
+        let mut index = 0;
+        while time < duration - 1e-6 {
+            let dt = frames(index).min(duration - time);
+            time += dt;
+            total += processor.process(raw(dt), &mut smoothed, dt);
+            index += 1;
+        }
+        (smoothed.unwrap_or(0.0), total)
+    }
+
+    fn mouse() -> AxisProcessor {
+        AxisProcessor::new(AXIS_LOOK)
+            .sensitivity(0.002)
+            .per_frame(true)
+    }
+
+    #[test]
+    fn curves_keep_the_sign() {
+        for x in [-1.0, -0.3, 0.0, 0.5, 2.0] {
+            assert_eq!(ResponseCurve::Linear.evaluate(x), x);
+        }
+        let power = ResponseCurve::Power(2.0);
+        assert_close(power.evaluate(0.5), 0.25, 1e-6);
+        assert_close(power.evaluate(-0.5), -0.25, 1e-6);
+    }
+
+    #[test]
+    fn point_curves_interpolate_from_the_origin_and_go_on_past_the_last_point() {
+        let points = ResponseCurve::Points(vec![[0.5, 0.25], [1.0, 1.0]]);
+        assert_close(points.evaluate(0.25), 0.125, 1e-6);
+        assert_close(points.evaluate(0.75), 0.625, 1e-6);
+        assert_close(points.evaluate(-0.75), -0.625, 1e-6);
+        assert_close(points.evaluate(2.0), 2.5, 1e-6);
+        assert_eq!(ResponseCurve::Points(Vec::new()).evaluate(0.4), 0.4);
+    }
+
+    #[test]
+    fn deadzone_stretches_the_rest_to_the_full_range() {
+        let stick = AxisProcessor::new(AXIS_STICK).deadzone(0.2);
+        assert_eq!(stick.shape(0.15), 0.0);
+        assert_close(stick.shape(0.6), 0.5, 1e-6);
+        assert_close(stick.shape(-1.0), -1.0, 1e-6);
+    }
+
+    #[test]
+    fn sensitivity_scales_the_curve_and_invert_flips_it() {
+        let shaped = AxisProcessor::new(AXIS_STICK)
+            .curve(ResponseCurve::Power(2.0))
+            .sensitivity(3.0)
+            .invert(true);
+        assert_close(shaped.shape(0.5), -0.75, 1e-6);
+    }
+
+    #[test]
+    fn smoothing_reaches_the_same_value_at_any_frame_rate() {
+        let smoothing = AxisProcessor::new(AXIS_STICK).smoothing(0.25);
+        let expected = 1.0 - (-1.0f32 / 0.25).exp();
+        for fps in [30.0, 60.0, 144.0, 240.0] {
+            let (settled, _) = run(&smoothing, 1.0, |_| 1.0 / fps, |_| 1.0);
+            assert_close(settled, expected, 1e-4);
+        }
+        let uneven = [0.004, 0.031, 0.017, 0.009, 0.05, 0.012];
+        let (settled, _) = run(
+            &smoothing,
+            1.0,
+            |index| uneven[index % uneven.len()],
+            |_| 1.0,
+        );
+        assert_close(settled, expected, 1e-4);
+    }
+
+    #[test]
+    fn without_smoothing_the_value_follows_the_input_at_once() {
+        let (value, _) = run(&AxisProcessor::new(AXIS_STICK), 0.1, |_| 0.01, |_| 0.7);
+        assert_eq!(value, 0.7);
+    }
+
+    #[test]
+    fn mouse_motion_turns_the_same_at_any_frame_rate() {
+        // 600 pixels per second for one second
+        let speed = 600.0;
+        for fps in [30.0, 144.0] {
+            let (_, turn) = run(&mouse(), 1.0, |_| 1.0 / fps, |dt| speed * dt);
+            assert_close(turn, 1.2, 1e-4);
+        }
+
+        // The total turn is the integral of the smoothed speed
+        let smooth_mouse = mouse().smoothing(0.1);
+        let rate = 1.2 * (1.0 - (-1.0f32 / 0.1).exp());
+        let turn = 1.2 * (1.0 - 0.1 * (1.0 - (-10.0f32).exp()));
+        for fps in [30.0, 60.0, 144.0, 240.0] {
+            let (smoothed, total) = run(&smooth_mouse, 1.0, |_| 1.0 / fps, |dt| speed * dt);
+            assert_close(smoothed, rate, 1e-4);
+            assert_close(total, turn, 1e-4);
+        }
+    }
+
+    #[test]
+    fn mouse_motion_waits_for_a_frame_that_took_time() {
+        let mut smoothed = Some(0.3);
+        assert_eq!(mouse().process(10.0, &mut smoothed, 0.0), 0.0);
+        assert_eq!(smoothed, Some(0.3));
+    }
+
+    #[test]
+    fn set_default_leaves_an_axis_processor_alone() {
+        let mut settings = AxisSettings::default();
+        settings
+            .set(mouse())
+            .set_default(AxisProcessor::new(AXIS_LOOK).sensitivity(100.0))
+            .set_default(AxisProcessor::new(AXIS_STICK).deadzone(0.2));
+        assert_eq!(settings.get(AXIS_LOOK), Some(&mouse()));
+        assert_eq!(
+            settings.get(AXIS_STICK),
+            Some(&AxisProcessor::new(AXIS_STICK).deadzone(0.2))
+        );
+        assert_eq!(settings.get(AXIS_PLAIN), None);
+    }
+
+    #[test]
+    fn fixed_steps_read_processed_axes_and_raw_sums() {
+        let mut input_map = InputMap::default();
+        input_map
+            .bind_mouse_axis(MouseAxisId::X, AXIS_LOOK, 1.0)
+            .bind_gamepad_axis(GamepadAxisId::RightStickX as u16, AXIS_STICK, 1.0)
+            .bind_gamepad_axis(GamepadAxisId::LeftStickX as u16, AXIS_PLAIN, 1.0);
+        let mut settings = AxisSettings::default();
+        settings.set(mouse()).set(
+            AxisProcessor::new(AXIS_STICK)
+                .deadzone(0.2)
+                .curve(ResponseCurve::Power(2.0)),
+        );
+
+        let mut fixed = FixedInput::default();
+        fixed.input.push_context(CTX_GAMEPLAY);
+        let mut timeline = InputTimeline::default();
+        let start = Instant::now();
+        let gamepad_axis = |axis: GamepadAxisId, value: f32| InputEvent::Axis {
+            id: PhysicalInputId {
+                device: DeviceKind::GamepadAxis(axis as u16),
+            },
+            value,
+        };
+        for event in [
+            InputEvent::MouseMotion { delta: (12.0, 4.0) },
+            gamepad_axis(GamepadAxisId::RightStickX, 0.6),
+            gamepad_axis(GamepadAxisId::LeftStickX, 0.6),
+        ] {
+            timeline.push(start + Duration::from_millis(5), None, event);
+        }
+        let dt = 1.0 / 60.0;
+        timeline.advance_to(
+            start + Duration::from_secs_f32(dt),
+            &mut fixed,
+            &input_map,
+            &settings,
+            dt,
+        );
+
+        let input = &fixed.input;
+        assert_close(input.axis(AXIS_LOOK), 12.0 * 0.002, 1e-6);
+        assert_eq!(input.raw_axis(AXIS_LOOK), 12.0);
+        assert_close(input.axis(AXIS_STICK), 0.25, 1e-6);
+        assert_eq!(input.raw_axis(AXIS_STICK), 0.6);
+        assert_eq!(input.axis(AXIS_PLAIN), 0.6);
+    }
+}
@@ -0,0 +1,382 @@
+use std::{collections::HashMap, path::Path, time::Duration};
+
+use catalyst_core::{
+    console::ConsoleCommands,
+    time::{PhysicsTime, Time},
+};
+use flecs_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    physical::{InputState, PhysicalInputId},
+    player::PlayerInputs,
+};
+
+const RECORDING_VERSION: u32 = 1;
+
+/// Raw device state of one InputState. Logical actions and axes are not stored,
+/// they are rebuilt from this by the bindings like any other frame.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputSnapshot {
+    pub buttons: Vec<(PhysicalInputId, bool)>,
+    pub axes: Vec<(PhysicalInputId, f32)>,
+    pub mouse_position: (f32, f32),
+    pub mouse_delta: (f32, f32),
+}
+
+impl InputSnapshot {
+    pub fn capture(state: &InputState) -> Self {
+        Self {
+            buttons: state
+                .physical_buttons
+                .iter()
+                .map(|(id, pressed)| (*id, *pressed))
+                .collect(),
+            axes: state
+                .physical_axes
+                .iter()
+                .map(|(id, value)| (*id, *value))
+                .collect(),
+            mouse_position: state.mouse_position,
+            mouse_delta: state.mouse_delta,
+        }
+    }
+
+    /// Overwrites the device state, dropping whatever the real devices reported.
+    pub fn apply(&self, state: &mut InputState) {
+        state.physical_buttons = self.buttons.iter().copied().collect::<HashMap<_, _>>();
+        state.physical_axes = self.axes.iter().copied().collect::<HashMap<_, _>>();
+        state.mouse_position = self.mouse_position;
+        state.mouse_delta = self.mouse_delta;
+    }
+}
+
+/// Everything needed to re-run one frame: its delta time and the device state
+/// of the shared InputState and of every local player.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputFrame {
+    pub delta: Duration,
+    pub input: InputSnapshot,
+    pub players: Vec<InputSnapshot>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct InputRecording {
+    pub version: u32,
+    pub frames: Vec<InputFrame>,
+}
+
+impl InputRecording {
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ReplayError> {
+        let json = serde_json::to_string(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ReplayError> {
+        let recording: Self = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        if recording.version != RECORDING_VERSION {
+            return Err(ReplayError::Version(recording.version));
+        }
+        Ok(recording)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ReplayError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid recording: {0}")]
+    Format(#[from] serde_json::Error),
+    #[error("unsupported recording version {0}")]
+    Version(u32),
+    #[error("the recording is empty")]
+    Empty,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReplayMode {
+    #[default]
+    Idle,
+    Recording,
+    Replaying,
+}
+
+/// Input recording and replay.
+///
+/// While recording, every frame's device state and delta time are appended to
+/// `recording`. While replaying, they are fed back instead of the real devices, and
+/// Time uses the recorded deltas, so fixed-step physics runs the same steps again.
+/// Replays are only stable on the same machine and build, started from the same
+/// state as the recording (e.g. both from startup, after the same assets loaded).
+#[derive(Component, Default)]
+pub struct InputReplay {
+    mode: ReplayMode,
+    pub recording: InputRecording,
+    cursor: usize,
+    // Set when a recording/replay starts, so the first frame begins from a clean accumulator
+    reset_pending: bool,
+}
+
+impl InputReplay {
+    pub fn mode(&self) -> ReplayMode {
+        self.mode
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.recording.frames.len()
+    }
+
+    /// Frames replayed so far.
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Starts a fresh recording, dropping the frames held so far.
+    pub fn start_recording(&mut self) {
+        self.recording = InputRecording {
+            version: RECORDING_VERSION,
+            frames: Vec::new(),
+        };
+        self.mode = ReplayMode::Recording;
+        self.reset_pending = true;
+    }
+
+    /// Replays the held recording from its first frame.
+    pub fn start_replay(&mut self) -> Result<(), ReplayError> {
+        if self.recording.frames.is_empty() {
+            return Err(ReplayError::Empty);
+        }
+        self.cursor = 0;
+        self.mode = ReplayMode::Replaying;
+        self.reset_pending = true;
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        self.mode = ReplayMode::Idle;
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ReplayError> {
+        self.recording.save(path)
+    }
+
+    /// Loads a recording to replay. Stops whatever was running.
+    pub fn load(&mut self, path: impl AsRef<Path>) -> Result<(), ReplayError> {
+        self.recording = InputRecording::load(path)?;
+        self.mode = ReplayMode::Idle;
+        self.cursor = 0;
+        Ok(())
+    }
+}
+
+/// Called by the runner once per frame, after Time and the devices were updated and
+/// before physics runs. Records the frame, or replaces it with the next recorded one.
+pub fn begin_input_frame(world: &World) {
+    let Some((mode, reset)) = world.try_get::<&mut InputReplay>(|replay| {
+        (replay.mode, std::mem::take(&mut replay.reset_pending))
+    }) else {
+        return;
+    };
+
+    if reset {
+        world.get::<&mut PhysicsTime>(|physics_time| physics_time.accumulator = 0.0);
+    }
+
+    match mode {
+        ReplayMode::Idle => {}
+        ReplayMode::Recording => {
+            let frame = InputFrame {
+                delta: world.get::<&Time>(|time| time.delta()),
+                input: world.get::<&InputState>(InputSnapshot::capture),
+                players: world
+                    .try_get::<&PlayerInputs>(|players| {
+                        players.players.iter().map(InputSnapshot::capture).collect()
+                    })
+                    .unwrap_or_default(),
+            };
+            world.get::<&mut InputReplay>(|replay| replay.recording.frames.push(frame));
+        }
+        ReplayMode::Replaying => {
+            let frame = world.get::<&mut InputReplay>(|replay| {
+                let frame = replay.recording.frames.get(replay.cursor).cloned();
+                match frame {
+                    Some(_) => replay.cursor += 1,
+                    None => {
+                        log::info!("Input replay finished after {} frames", replay.cursor);
+                        replay.mode = ReplayMode::Idle;
+                    }
+                }
+                frame
+            });
+
+            let Some(frame) = frame else {
+                return;
+            };
+
+            world.get::<&mut Time>(|time| time.set_delta(frame.delta));
+            world.get::<&mut InputState>(|state| frame.input.apply(state));
+            world.try_get::<&mut PlayerInputs>(|players| {
+                for (state, snapshot) in players.players.iter_mut().zip(&frame.players) {
+                    snapshot.apply(state);
+                }
+            });
+        }
+    }
+}
+
+pub fn register_replay_commands(commands: &mut ConsoleCommands) {
+    commands
+        .register("input.record", "Start recording input", |world, _| {
+            world.get::<&mut InputReplay>(|replay| replay.start_recording());
+            Ok("Recording input".to_string())
+        })
+        .register(
+            "input.stop",
+            "Stop recording or replaying input",
+            |world, _| {
+                Ok(world.get::<&mut InputReplay>(|replay| {
+                    replay.stop();
+                    format!("Stopped ({} frames held)", replay.frame_count())
+                }))
+            },
+        )
+        .register(
+            "input.save",
+            "input.save <path> - write the held recording to a file",
+            |world, args| {
+                let path = args.first().ok_or("usage: input.save <path>")?;
+                world.get::<&InputReplay>(|replay| {
+                    replay
+                        .save(path)
+                        .map(|_| format!("Saved {} frames to {}", replay.frame_count(), path))
+                        .map_err(|e| e.to_string())
+                })
+            },
+        )
+        .register(
+            "input.load",
+            "input.load <path> - load a recording to replay",
+            |world, args| {
+                let path = args.first().ok_or("usage: input.load <path>")?;
+                world.get::<&mut InputReplay>(|replay| {
+                    replay
+                        .load(path)
+                        .map(|_| format!("Loaded {} frames from {}", replay.frame_count(), path))
+                        .map_err(|e| e.to_string())
+                })
+            },
+        )
+        .register(
+            "input.replay",
+            "input.replay [path] - replay the held recording, or load one first",
+            |world, args| {
+                world.get::<&mut InputReplay>(|replay| {
+                    if let Some(path) = args.first() {
+                        replay.load(path).map_err(|e| e.to_string())?;
+                    }
+                    replay.start_replay().map_err(|e| e.to_string())?;
+                    Ok(format!("Replaying {} frames", replay.frame_count()))
+                })
+            },
+        );
+}
+
+#[cfg(test)]
+mod tests {
+    use catalyst_core::{App, transform::Transform};
+
+    use super::*;
+    use crate::{InputPlugin, physical::DeviceKind};
+
+    const FRAMES: usize = 120;
+
+    const STICK_X: PhysicalInputId = PhysicalInputId {
+        device: DeviceKind::GamepadAxis(0),
+    };
+    const JUMP: PhysicalInputId = PhysicalInputId {
+        device: DeviceKind::Keyboard(57),
+    };
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugin(InputPlugin);
+        // Moves by the raw device state and the frame's delta, like a character would
+        app.world
+            .system_named::<(&mut Transform, &Time, &InputState)>("replay_test_movement")
+            .kind(flecs::pipeline::OnUpdate)
+            .each(|(transform, time, input)| {
+                let dt = time.delta_seconds();
+                let stick = input.physical_axes.get(&STICK_X).copied().unwrap_or(0.0);
+                transform.translation.x += stick * 4.0 * dt;
+                if input.physical_buttons.get(&JUMP).copied().unwrap_or(false) {
+                    transform.translation.y += 2.0 * dt;
+                }
+                transform.rotate_y(input.mouse_delta.0 * 0.01);
+            });
+        app
+    }
+
+    // The devices' state for a frame; a different seed stands for different real input
+    fn drive(world: &World, frame: usize, seed: f32) {
+        world.get::<&mut InputState>(|state| {
+            let t = frame as f32 * 0.1 + seed;
+            state.physical_axes.insert(STICK_X, t.sin());
+            state
+                .physical_buttons
+                .insert(JUMP, (frame + seed as usize) % 3 == 0);
+            state.mouse_delta = (t.cos() * 5.0, 0.0);
+        });
+    }
+
+    // One frame the way the window runner does it
+    fn run_frame(app: &mut App, frame: usize, seed: f32) {
+        app.world.get::<&mut Time>(|time| time.update());
+        drive(&app.world, frame, seed);
+        begin_input_frame(&app.world);
+        app.update();
+    }
+
+    #[test]
+    fn replaying_120_frames_ends_at_the_recorded_transform() {
+        let mut app = app();
+        let player = app.world.entity().set(Transform::default()).id();
+        let transform = |app: &App| app.world.entity_from_id(player).get::<&Transform>(|t| *t);
+
+        app.world
+            .get::<&mut InputReplay>(|replay| replay.start_recording());
+        for frame in 0..FRAMES {
+            run_frame(&mut app, frame, 0.0);
+            // Uneven frame times, which the replay mustn't depend on
+            std::thread::sleep(Duration::from_millis((frame % 4) as u64));
+        }
+        app.world.get::<&mut InputReplay>(|replay| replay.stop());
+        let recorded = transform(&app);
+        assert_eq!(
+            app.world.get::<&InputReplay>(|replay| replay.frame_count()),
+            FRAMES
+        );
+        assert_ne!(recorded, Transform::default());
+
+        app.world.entity_from_id(player).set(Transform::default());
+        app.world
+            .get::<&mut InputReplay>(|replay| replay.start_replay())
+            .unwrap();
+        for frame in 0..FRAMES {
+            run_frame(&mut app, frame, 7.0);
+        }
+        assert_eq!(
+            app.world.get::<&InputReplay>(|replay| replay.cursor()),
+            FRAMES
+        );
+        assert_eq!(transform(&app), recorded);
+
+        // The replay ends on the frame after the last recorded one
+        run_frame(&mut app, FRAMES, 7.0);
+        assert_eq!(
+            app.world.get::<&InputReplay>(|replay| replay.mode()),
+            ReplayMode::Idle
+        );
+    }
+}
@@ -0,0 +1,362 @@
+use std::time::Duration;
+
+use catalyst_core::{console::ConsoleCommands, player::PlayerIndex, time::Time};
+use flecs_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::player::{InputDevice, PlayerInputs};
+
+/// One rumble: how hard both motors of a gamepad shake, from 0 to 1, and for how long.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RumbleRequest {
+    /// The gamepad, as in `InputDevice::Gamepad`; None rumbles every gamepad.
+    pub gamepad: Option<usize>,
+    /// The low frequency motor, for heavy hits.
+    pub strong_motor: f32,
+    /// The high frequency motor, for light buzzes.
+    pub weak_motor: f32,
+    pub duration: Duration,
+}
+
+impl RumbleRequest {
+    pub fn new(
+        gamepad: Option<usize>,
+        strong_motor: f32,
+        weak_motor: f32,
+        duration: Duration,
+    ) -> Self {
+        Self {
+            gamepad,
+            strong_motor,
+            weak_motor,
+            duration,
+        }
+    }
+}
+
+/// Player preferences for force feedback, in the "rumble" settings section.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RumbleSettings {
+    /// Scales every rumble; 0 turns it off.
+    pub intensity: f32,
+}
+
+impl Default for RumbleSettings {
+    fn default() -> Self {
+        Self { intensity: 1.0 }
+    }
+}
+
+/// The rumbles playing, in the singleton of the same name. Submit them with `request`;
+/// the window runner sends `motors` to each gamepad that has force feedback after every
+/// frame.
+///
+/// Overlapping rumbles on the same gamepad don't add up: each motor runs at the
+/// strongest of them, until each one runs out. Everything stops while the game is
+/// paused (`Time::is_paused`) or the window is unfocused, and rumbles requested
+/// meanwhile are dropped rather than played later.
+#[derive(Component, Debug, Default)]
+pub struct Rumble {
+    // Each request with the time it has left
+    active: Vec<(RumbleRequest, Duration)>,
+    paused: bool,
+    unfocused: bool,
+}
+
+impl Rumble {
+    pub fn request(&mut self, request: RumbleRequest) {
+        if self.is_suspended() || request.duration.is_zero() {
+            return;
+        }
+        self.active.push((request, request.duration));
+    }
+
+    /// Stops every rumble.
+    pub fn stop_all(&mut self) {
+        self.active.clear();
+    }
+
+    /// Stops the rumbles of one gamepad, e.g. when it is disconnected. Rumbles for
+    /// every gamepad keep going on the others.
+    pub fn stop(&mut self, gamepad: usize) {
+        self.active
+            .retain(|(request, _)| request.gamepad != Some(gamepad));
+    }
+
+    /// Motor levels of `gamepad` from 0 to 1, strong then weak, scaled by `intensity`.
+    pub fn motors(&self, gamepad: usize, intensity: f32) -> (f32, f32) {
+        let (strong, weak) = self
+            .active
+            .iter()
+            .filter(|(request, _)| request.gamepad.is_none_or(|id| id == gamepad))
+            .fold((0.0f32, 0.0f32), |(strong, weak), (request, _)| {
+                (
+                    strong.max(request.strong_motor),
+                    weak.max(request.weak_motor),
+                )
+            });
+        let intensity = intensity.max(0.0);
+        (
+            (strong * intensity).clamp(0.0, 1.0),
+            (weak * intensity).clamp(0.0, 1.0),
+        )
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.active.is_empty()
+    }
+
+    /// Paused or unfocused: nothing rumbles.
+    pub fn is_suspended(&self) -> bool {
+        self.paused || self.unfocused
+    }
+
+    /// Called by the window runner when the window gains or loses focus.
+    pub fn set_window_focused(&mut self, focused: bool) {
+        self.unfocused = !focused;
+        if self.unfocused {
+            self.stop_all();
+        }
+    }
+
+    /// Counts `elapsed` off every rumble and drops the finished ones.
+    pub fn update(&mut self, elapsed: Duration, paused: bool) {
+        self.paused = paused;
+        if self.is_suspended() {
+            self.stop_all();
+            return;
+        }
+        self.active.retain_mut(|(_, left)| {
+            *left = left.saturating_sub(elapsed);
+            !left.is_zero()
+        });
+    }
+}
+
+/// Rumble when the entity takes a hit or lands, scaled by the impulse. The gamepads of
+/// the entity's PlayerIndex rumble, or every gamepad without one. Call `impact_rumble`
+/// with the impulse from collision or landing handlers.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ImpactRumble {
+    /// Motor levels at `full_impulse` and above.
+    pub strong_motor: f32,
+    pub weak_motor: f32,
+    pub duration: Duration,
+    /// Weaker impacts don't rumble, so resting and sliding contacts stay quiet.
+    pub min_impulse: f32,
+    pub full_impulse: f32,
+}
+
+impl Default for ImpactRumble {
+    fn default() -> Self {
+        Self {
+            strong_motor: 1.0,
+            weak_motor: 0.6,
+            duration: Duration::from_millis(150),
+            min_impulse: 2.0,
+            full_impulse: 20.0,
+        }
+    }
+}
+
+impl ImpactRumble {
+    /// The rumble for an impact of `impulse` on `gamepad`, if it is strong enough.
+    pub fn request(&self, gamepad: Option<usize>, impulse: f32) -> Option<RumbleRequest> {
+        if impulse.is_nan() || impulse < self.min_impulse {
+            return None;
+        }
+        let scale = (impulse / self.full_impulse.max(f32::EPSILON)).min(1.0);
+        Some(RumbleRequest::new(
+            gamepad,
+            self.strong_motor * scale,
+            self.weak_motor * scale,
+            self.duration,
+        ))
+    }
+}
+
+/// Rumbles for an impact of `impulse` on `entity`, when it has an ImpactRumble.
+pub fn impact_rumble(entity: EntityView, impulse: f32) {
+    let Some(impact) = entity.try_get::<&ImpactRumble>(|impact| *impact) else {
+        return;
+    };
+    let world = entity.world();
+    let gamepads = match entity.try_get::<&PlayerIndex>(|player| *player) {
+        Some(player) => world.get::<&PlayerInputs>(|inputs| player_gamepads(inputs, player)),
+        None => vec![None],
+    };
+    world.get::<&mut Rumble>(|rumble| {
+        for gamepad in gamepads {
+            if let Some(request) = impact.request(gamepad, impulse) {
+                rumble.request(request);
+            }
+        }
+    });
+}
+
+fn player_gamepads(inputs: &PlayerInputs, player: PlayerIndex) -> Vec<Option<usize>> {
+    inputs
+        .assignments
+        .iter()
+        .filter_map(|(device, assigned)| match device {
+            InputDevice::Gamepad(id) if *assigned == player => Some(Some(*id)),
+            _ => None,
+        })
+        .collect()
+}
+
+pub(crate) fn register_rumble(world: &World) {
+    // Real time: a slowed down game doesn't make rumbles last longer. PreUpdate, so a
+    // rumble requested during the frame plays for all of its duration
+    world
+        .system_named::<(&mut Rumble, &Time)>("Update Rumble")
+        .kind(flecs::pipeline::PreUpdate)
+        .each(|(rumble, time)| rumble.update(time.delta(), time.is_paused()));
+}
+
+pub(crate) fn register_rumble_commands(commands: &mut ConsoleCommands) {
+    commands.register(
+        "input.rumble_test",
+        "input.rumble_test <strong> <weak> <ms> [gamepad] - rumble the gamepads",
+        |world, args| {
+            let usage = "usage: input.rumble_test <strong> <weak> <ms> [gamepad]";
+            let [strong, weak, ms] =
+                [0, 1, 2].map(|i| args.get(i).and_then(|a| a.parse::<f32>().ok()));
+            let (Some(strong), Some(weak), Some(ms)) = (strong, weak, ms) else {
+                return Err(usage.to_string());
+            };
+            let gamepad = match args.get(3) {
+                Some(id) => Some(id.parse::<usize>().map_err(|_| usage.to_string())?),
+                None => None,
+            };
+            let request = RumbleRequest::new(
+                gamepad,
+                strong.clamp(0.0, 1.0),
+                weak.clamp(0.0, 1.0),
+                Duration::from_secs_f32(ms.max(0.0) / 1000.0),
+            );
+            world.get::<&mut Rumble>(|rumble| {
+                if rumble.is_suspended() {
+                    return Err("Rumble is suspended (paused or unfocused)".to_string());
+                }
+                rumble.request(request);
+                Ok(format!(
+                    "Rumbling {} at {:.2}/{:.2} for {} ms",
+                    gamepad.map_or("every gamepad".to_string(), |id| format!("gamepad {}", id)),
+                    request.strong_motor,
+                    request.weak_motor,
+                    ms
+                ))
+            })
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_millis(100);
+
+    fn assert_motors(rumble: &Rumble, gamepad: usize, intensity: f32, expected: (f32, f32)) {
+        let motors = rumble.motors(gamepad, intensity);
+        assert!(
+            (motors.0 - expected.0).abs() < 1e-5 && (motors.1 - expected.1).abs() < 1e-5,
+            "gamepad {} at {}: {:?}, expected {:?}",
+            gamepad,
+            intensity,
+            motors,
+            expected
+        );
+    }
+
+    fn full(gamepad: usize) -> RumbleRequest {
+        RumbleRequest::new(Some(gamepad), 1.0, 1.0, Duration::from_secs(1))
+    }
+
+    #[test]
+    fn overlapping_rumbles_take_the_strongest_level_until_each_ends() {
+        let mut rumble = Rumble::default();
+        rumble.request(RumbleRequest::new(
+            Some(0),
+            0.2,
+            0.7,
+            Duration::from_millis(500),
+        ));
+        rumble.request(RumbleRequest::new(
+            Some(0),
+            0.9,
+            0.1,
+            Duration::from_millis(200),
+        ));
+        assert_motors(&rumble, 0, 1.0, (0.9, 0.7));
+        // A rumble for one gamepad doesn't reach another
+        assert_motors(&rumble, 1, 1.0, (0.0, 0.0));
+
+        // The short one ends after its duration, the long one goes on
+        for _ in 0..2 {
+            rumble.update(FRAME, false);
+        }
+        assert_motors(&rumble, 0, 1.0, (0.2, 0.7));
+        for _ in 0..3 {
+            rumble.update(FRAME, false);
+        }
+        assert!(!rumble.is_active());
+        assert_motors(&rumble, 0, 1.0, (0.0, 0.0));
+    }
+
+    #[test]
+    fn a_rumble_without_a_gamepad_reaches_every_gamepad_scaled_by_the_intensity() {
+        let mut rumble = Rumble::default();
+        rumble.request(RumbleRequest::new(None, 0.8, 0.4, Duration::from_secs(1)));
+        assert_motors(&rumble, 0, 1.0, (0.8, 0.4));
+        assert_motors(&rumble, 3, 1.0, (0.8, 0.4));
+        // Within 0 to 1
+        assert_motors(&rumble, 0, 0.5, (0.4, 0.2));
+        assert_motors(&rumble, 0, 0.0, (0.0, 0.0));
+        assert_motors(&rumble, 0, 5.0, (1.0, 1.0));
+    }
+
+    #[test]
+    fn pausing_or_losing_focus_stops_every_rumble_and_drops_new_ones() {
+        let mut rumble = Rumble::default();
+        rumble.request(full(0));
+        rumble.update(FRAME, true);
+        rumble.request(full(0));
+        assert!(rumble.is_suspended() && !rumble.is_active());
+
+        rumble.update(FRAME, false);
+        rumble.request(full(0));
+        rumble.set_window_focused(false);
+        assert!(rumble.is_suspended() && !rumble.is_active());
+    }
+
+    #[test]
+    fn a_disconnected_gamepads_rumbles_stop_and_the_others_go_on() {
+        let mut rumble = Rumble::default();
+        rumble.request(full(0));
+        rumble.request(RumbleRequest::new(
+            Some(1),
+            0.5,
+            0.5,
+            Duration::from_secs(1),
+        ));
+        rumble.stop(0);
+        assert_motors(&rumble, 0, 1.0, (0.0, 0.0));
+        assert_motors(&rumble, 1, 1.0, (0.5, 0.5));
+    }
+
+    #[test]
+    fn impact_rumble_scales_with_the_impulse_and_ignores_weak_ones() {
+        let impact = ImpactRumble::default();
+        let half = impact.request(Some(0), impact.full_impulse * 0.5).unwrap();
+        assert!((half.strong_motor - impact.strong_motor * 0.5).abs() < 1e-5);
+        // Up to full_impulse
+        let hard = impact.request(Some(0), impact.full_impulse * 10.0).unwrap();
+        assert_eq!(hard.strong_motor, impact.strong_motor);
+
+        assert!(impact.request(Some(0), impact.min_impulse * 0.5).is_none());
+        assert!(impact.request(Some(0), f32::NAN).is_none());
+    }
+}
@@ -0,0 +1,399 @@
+use std::{collections::VecDeque, time::Instant};
+
+use catalyst_core::{player::PlayerIndex, time::PhysicsTime};
+use flecs_ecs::prelude::*;
+
+use crate::{
+    logical::{InputMap, apply_bindings},
+    physical::{InputState, PhysicalInputId},
+    player::PlayerInputs,
+    processing::AxisSettings,
+    replay::{InputReplay, ReplayMode},
+};
+
+/// One device change, as the runner receives it from the platform.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputEvent {
+    Button { id: PhysicalInputId, pressed: bool },
+    Axis { id: PhysicalInputId, value: f32 },
+    MouseMotion { delta: (f32, f32) },
+    CursorMoved { position: (f32, f32) },
+}
+
+impl InputEvent {
+    pub fn apply(&self, state: &mut InputState) {
+        match *self {
+            InputEvent::Button { id, pressed } => {
+                state.physical_buttons.insert(id, pressed);
+            }
+            InputEvent::Axis { id, value } => {
+                state.physical_axes.insert(id, value);
+            }
+            InputEvent::MouseMotion { delta } => {
+                state.mouse_delta.0 += delta.0;
+                state.mouse_delta.1 += delta.1;
+            }
+            InputEvent::CursorMoved { position } => {
+                state.mouse_position = position;
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TimedInputEvent {
+    pub at: Instant,
+    /// Player the device was routed to, if any.
+    pub player: Option<PlayerIndex>,
+    pub event: InputEvent,
+}
+
+/// Input events in arrival order, timestamped by the runner. Fixed steps take the
+/// events that happened before their end out of here, the rest waits for the next step.
+#[derive(Component, Default, Debug)]
+pub struct InputTimeline {
+    events: VecDeque<TimedInputEvent>,
+}
+
+impl InputTimeline {
+    pub fn push(&mut self, at: Instant, player: Option<PlayerIndex>, event: InputEvent) {
+        self.events.push_back(TimedInputEvent { at, player, event });
+    }
+
+    pub fn pending(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    /// Applies every event up to `step_end` to the step-local state and resolves the
+    /// bindings, so PRESSED and RELEASED land on the step the change happened in. The
+    /// axes are processed as `dt` seconds after the last step.
+    ///
+    /// A release of a button pressed earlier in the same step waits for the next step
+    /// (along with everything after it), so taps shorter than a step aren't lost.
+    pub fn advance_to(
+        &mut self,
+        step_end: Instant,
+        fixed: &mut FixedInput,
+        input_map: &InputMap,
+        axis_settings: &AxisSettings,
+        dt: f32,
+    ) {
+        fixed.begin_step();
+
+        let mut pressed_this_step = Vec::new();
+        while let Some(timed) = self.events.front().copied().filter(|timed| timed.at <= step_end) {
+            if let InputEvent::Button { id, pressed } = timed.event {
+                let button = (timed.player, id);
+                if !pressed && pressed_this_step.contains(&button) {
+                    break;
+                }
+                if pressed {
+                    pressed_this_step.push(button);
+                }
+            }
+            timed.event.apply(&mut fixed.input);
+            if let Some(state) = timed
+                .player
+                .and_then(|player| fixed.players.get_mut(player.0 as usize))
+            {
+                timed.event.apply(state);
+            }
+            self.events.pop_front();
+        }
+
+        fixed.resolve(input_map, axis_settings, dt);
+    }
+}
+
+/// Input as of the current fixed step, for gameplay systems in the physics phases.
+/// Render-rate systems keep reading InputState and PlayerInputs, which see the
+/// whole frame at once.
+///
+/// The physical state carries over from step to step; mouse deltas only cover the
+/// events of the step.
+#[derive(Component, Default, Debug)]
+pub struct FixedInput {
+    pub input: InputState,
+    pub players: Vec<InputState>,
+}
+
+impl FixedInput {
+    pub fn player(&self, player: PlayerIndex) -> Option<&InputState> {
+        self.players.get(player.0 as usize)
+    }
+
+    fn begin_step(&mut self) {
+        self.input.mouse_delta = (0.0, 0.0);
+        for state in &mut self.players {
+            state.mouse_delta = (0.0, 0.0);
+        }
+    }
+
+    fn resolve(&mut self, input_map: &InputMap, axis_settings: &AxisSettings, dt: f32) {
+        apply_bindings(input_map, &mut self.input);
+        axis_settings.process(&mut self.input, dt);
+        for state in &mut self.players {
+            apply_bindings(input_map, state);
+            axis_settings.process(state, dt);
+        }
+    }
+
+    // Contexts are switched on the frame-level states, follow them
+    fn sync_contexts(&mut self, input: &InputState, players: Option<&PlayerInputs>) {
+        self.input.active_contexts.clone_from(&input.active_contexts);
+
+        let Some(players) = players else {
+            return;
+        };
+        self.players
+            .resize_with(players.players.len(), InputState::default);
+        for (state, frame_state) in self.players.iter_mut().zip(&players.players) {
+            state.active_contexts.clone_from(&frame_state.active_contexts);
+        }
+    }
+
+    // During a replay the recorded frame state is all there is
+    fn copy_devices(&mut self, input: &InputState, players: Option<&PlayerInputs>) {
+        copy_device_state(&mut self.input, input);
+        for (state, frame_state) in self
+            .players
+            .iter_mut()
+            .zip(players.iter().flat_map(|players| &players.players))
+        {
+            copy_device_state(state, frame_state);
+        }
+    }
+}
+
+fn copy_device_state(target: &mut InputState, source: &InputState) {
+    target.physical_buttons.clone_from(&source.physical_buttons);
+    target.physical_axes.clone_from(&source.physical_axes);
+    target.mouse_position = source.mouse_position;
+    target.mouse_delta = source.mouse_delta;
+}
+
+/// Called by the runner before each fixed step, with the real time the step's
+/// simulation ends at. Builds FixedInput from the events up to then.
+///
+/// Replays only hold one snapshot per frame, so while replaying every step of a
+/// frame sees that snapshot (the first step gets its presses).
+pub fn begin_fixed_step(world: &World, step_end: Instant) {
+    let replaying = world
+        .try_get::<&InputReplay>(|replay| replay.mode() == ReplayMode::Replaying)
+        .unwrap_or(false);
+    // Smoothing steps along with the simulation
+    let dt = world
+        .try_get::<&PhysicsTime>(|time| time.fixed_dt)
+        .unwrap_or(0.0);
+    let axis_settings = world
+        .try_get::<&AxisSettings>(|settings| settings.clone())
+        .unwrap_or_default();
+
+    world.get::<&mut FixedInput>(|fixed| {
+        world.get::<&InputState>(|input| {
+            let sync = |fixed: &mut FixedInput, players: Option<&PlayerInputs>| {
+                fixed.sync_contexts(input, players);
+                if replaying {
+                    fixed.copy_devices(input, players);
+                }
+            };
+            if world.try_get::<&PlayerInputs>(|players| sync(fixed, Some(players))).is_none() {
+                sync(fixed, None);
+            }
+        });
+
+        world.get::<&mut InputTimeline>(|timeline| {
+            world.get::<&InputMap>(|input_map| {
+                if replaying {
+                    timeline.clear();
+                    fixed.resolve(input_map, &axis_settings, dt);
+                } else {
+                    timeline.advance_to(step_end, fixed, input_map, &axis_settings, dt);
+                }
+            });
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        context::CTX_GAMEPLAY,
+        logical::{ActionId, ButtonPhase},
+        physical::DeviceKind,
+    };
+
+    const ACTION_JUMP: ActionId = ActionId(1);
+    const KEY_SPACE: u16 = 62;
+    const FIXED_DT: f32 = 1.0 / 60.0;
+
+    const SPACE: PhysicalInputId = PhysicalInputId {
+        device: DeviceKind::Keyboard(KEY_SPACE),
+    };
+
+    fn space(pressed: bool) -> InputEvent {
+        InputEvent::Button { id: SPACE, pressed }
+    }
+
+    fn setup() -> (InputMap, FixedInput, InputTimeline) {
+        let mut input_map = InputMap::default();
+        input_map.bind_keyboard_button(KEY_SPACE, ACTION_JUMP);
+        let mut fixed = FixedInput::default();
+        fixed.input.push_context(CTX_GAMEPLAY);
+        (input_map, fixed, InputTimeline::default())
+    }
+
+    fn step_end(start: Instant, step: u32) -> Instant {
+        start + Duration::from_secs_f32(step as f32 * FIXED_DT)
+    }
+
+    fn advance(
+        timeline: &mut InputTimeline,
+        fixed: &mut FixedInput,
+        input_map: &InputMap,
+        step_end: Instant,
+    ) {
+        timeline.advance_to(
+            step_end,
+            fixed,
+            input_map,
+            &AxisSettings::default(),
+            FIXED_DT,
+        );
+    }
+
+    fn jump_phase(state: &InputState) -> ButtonPhase {
+        state
+            .actions
+            .get(&ACTION_JUMP)
+            .map_or(ButtonPhase::NONE, |action| action.phase.clone())
+    }
+
+    #[test]
+    fn a_tap_within_one_frame_reaches_the_steps_it_happened_in() {
+        let (input_map, mut fixed, mut timeline) = setup();
+
+        // One 40 ms frame running two steps: down 5 ms in (step 1), up 20 ms in (step 2)
+        let frame_start = Instant::now();
+        timeline.push(frame_start + Duration::from_millis(5), None, space(true));
+        timeline.push(frame_start + Duration::from_millis(20), None, space(false));
+
+        for (step, expected) in [(1, ButtonPhase::PRESSED), (2, ButtonPhase::RELEASED)] {
+            advance(
+                &mut timeline,
+                &mut fixed,
+                &input_map,
+                step_end(frame_start, step),
+            );
+            let phase = jump_phase(&fixed.input);
+            assert!(
+                phase.contains(expected.clone()),
+                "step {}: {:?}, expected {:?}",
+                step,
+                phase,
+                expected
+            );
+        }
+        assert_eq!(timeline.pending(), 0);
+    }
+
+    #[test]
+    fn an_event_on_a_step_boundary_belongs_to_the_step_ending_there() {
+        let (input_map, mut fixed, mut timeline) = setup();
+        let start = Instant::now();
+        let boundary = step_end(start, 1);
+        timeline.push(boundary, None, space(true));
+        timeline.push(
+            boundary + Duration::from_nanos(1),
+            None,
+            InputEvent::MouseMotion { delta: (3.0, 0.0) },
+        );
+
+        advance(&mut timeline, &mut fixed, &input_map, boundary);
+        assert!(fixed.input.just_pressed(ACTION_JUMP));
+        assert_eq!(fixed.input.mouse_delta, (0.0, 0.0));
+        assert_eq!(timeline.pending(), 1);
+
+        advance(&mut timeline, &mut fixed, &input_map, step_end(start, 2));
+        assert_eq!(fixed.input.mouse_delta, (3.0, 0.0));
+        assert_eq!(timeline.pending(), 0);
+    }
+
+    #[test]
+    fn an_empty_step_keeps_buttons_held_and_clears_the_mouse_delta() {
+        let (input_map, mut fixed, mut timeline) = setup();
+        let start = Instant::now();
+        timeline.push(start, None, space(true));
+        timeline.push(start, None, InputEvent::MouseMotion { delta: (2.0, -1.0) });
+        advance(&mut timeline, &mut fixed, &input_map, step_end(start, 1));
+        assert_eq!(fixed.input.mouse_delta, (2.0, -1.0));
+
+        advance(&mut timeline, &mut fixed, &input_map, step_end(start, 2));
+        let phase = jump_phase(&fixed.input);
+        assert!(phase.contains(ButtonPhase::HELD), "{:?}", phase);
+        assert!(!phase.contains(ButtonPhase::PRESSED), "{:?}", phase);
+        assert!(fixed.input.held(ACTION_JUMP));
+        assert_eq!(fixed.input.mouse_delta, (0.0, 0.0));
+    }
+
+    #[test]
+    fn a_tap_inside_one_step_releases_on_the_next_step() {
+        let (input_map, mut fixed, mut timeline) = setup();
+        let start = Instant::now();
+        timeline.push(start + Duration::from_millis(2), None, space(true));
+        timeline.push(start + Duration::from_millis(4), None, space(false));
+        timeline.push(
+            start + Duration::from_millis(6),
+            None,
+            InputEvent::MouseMotion { delta: (1.0, 1.0) },
+        );
+
+        advance(&mut timeline, &mut fixed, &input_map, step_end(start, 1));
+        let phase = jump_phase(&fixed.input);
+        assert!(phase.contains(ButtonPhase::PRESSED), "{:?}", phase);
+        assert!(!phase.contains(ButtonPhase::RELEASED), "{:?}", phase);
+        // The release and everything after it wait, in order
+        assert_eq!(timeline.pending(), 2);
+        assert_eq!(fixed.input.mouse_delta, (0.0, 0.0));
+
+        advance(&mut timeline, &mut fixed, &input_map, step_end(start, 2));
+        let phase = jump_phase(&fixed.input);
+        assert!(phase.contains(ButtonPhase::RELEASED), "{:?}", phase);
+        assert_eq!(fixed.input.mouse_delta, (1.0, 1.0));
+        assert_eq!(timeline.pending(), 0);
+    }
+
+    #[test]
+    fn routed_events_reach_the_player_and_the_combined_state() {
+        let (input_map, mut fixed, mut timeline) = setup();
+        for _ in 0..2 {
+            let mut state = InputState::default();
+            state.push_context(CTX_GAMEPLAY);
+            fixed.players.push(state);
+        }
+        let start = Instant::now();
+        timeline.push(start, Some(PlayerIndex(1)), space(true));
+
+        advance(&mut timeline, &mut fixed, &input_map, step_end(start, 1));
+        assert!(fixed.input.just_pressed(ACTION_JUMP));
+        assert!(
+            !fixed
+                .player(PlayerIndex(0))
+                .unwrap()
+                .just_pressed(ACTION_JUMP)
+        );
+        assert!(
+            fixed
+                .player(PlayerIndex(1))
+                .unwrap()
+                .just_pressed(ACTION_JUMP)
+        );
+    }
+}
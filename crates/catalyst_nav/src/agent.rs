@@ -0,0 +1,194 @@
+use catalyst_core::{movement::MovementIntent, transform::GlobalTransform};
+use flecs_ecs::prelude::*;
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::{NavMeshState, navmesh::NavMeshData};
+
+// Intermediate corners count as reached this close, seen from above
+const CORNER_DISTANCE: f32 = 0.1;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NavAgentStatus {
+    /// No target.
+    #[default]
+    Idle,
+    Moving,
+    /// Within `arrival_distance` of the target; the target is cleared.
+    Arrived,
+    /// No path to the target (or no navmesh yet). The target is kept and retried
+    /// whenever the navmesh is rebaked.
+    Unreachable,
+}
+
+/// Walks the entity to `target` over the navmesh by writing its MovementIntent.
+/// Paths are planned when the target changes or the navmesh is rebaked.
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NavAgent {
+    /// Should not exceed the navmesh's NavMeshSettings::agent_radius, which is the
+    /// clearance the mesh was baked with.
+    pub radius: f32,
+    pub speed: f32,
+    pub target: Option<Vec3>,
+    pub arrival_distance: f32,
+    // Runtime state, replanned after loading
+    #[serde(skip)]
+    status: NavAgentStatus,
+    // Corners left to walk, the next one first
+    #[serde(skip)]
+    path: Vec<Vec3>,
+    // What the path was planned for
+    #[serde(skip)]
+    planned_target: Option<Vec3>,
+    #[serde(skip)]
+    planned_generation: u32,
+    #[serde(skip)]
+    radius_reported: bool,
+}
+
+impl Default for NavAgent {
+    fn default() -> Self {
+        Self {
+            radius: 0.4,
+            speed: 3.0,
+            target: None,
+            arrival_distance: 0.2,
+            status: NavAgentStatus::Idle,
+            path: Vec::new(),
+            planned_target: None,
+            planned_generation: 0,
+            radius_reported: false,
+        }
+    }
+}
+
+impl NavAgent {
+    pub fn new(radius: f32, speed: f32) -> Self {
+        Self {
+            radius,
+            speed,
+            ..Default::default()
+        }
+    }
+
+    pub fn set_target(&mut self, target: Vec3) {
+        self.target = Some(target);
+    }
+
+    pub fn stop(&mut self) {
+        self.target = None;
+    }
+
+    pub fn status(&self) -> NavAgentStatus {
+        self.status
+    }
+
+    /// Corners still ahead, for debug drawing.
+    pub fn path(&self) -> &[Vec3] {
+        &self.path
+    }
+
+    fn steer(&mut self, position: Vec3, navmesh: &NavMeshData, generation: u32) -> Vec3 {
+        let Some(target) = self.target else {
+            self.path.clear();
+            self.planned_target = None;
+            if self.status != NavAgentStatus::Arrived {
+                self.status = NavAgentStatus::Idle;
+            }
+            return Vec3::ZERO;
+        };
+
+        if self.planned_target != Some(target) || self.planned_generation != generation {
+            self.planned_target = Some(target);
+            self.planned_generation = generation;
+
+            match navmesh.find_path(position, target) {
+                Some(path) => {
+                    // The first corner is where the agent stands
+                    self.path = path.into_iter().skip(1).collect();
+                    self.status = NavAgentStatus::Moving;
+                }
+                None => {
+                    self.path.clear();
+                    self.status = NavAgentStatus::Unreachable;
+                }
+            }
+        }
+
+        while let Some(&corner) = self.path.first() {
+            let last = self.path.len() == 1;
+            let reach = if last { self.arrival_distance } else { CORNER_DISTANCE };
+            if horizontal(corner - position).length() > reach {
+                break;
+            }
+            self.path.remove(0);
+        }
+
+        let Some(&corner) = self.path.first() else {
+            if self.status == NavAgentStatus::Moving {
+                self.status = NavAgentStatus::Arrived;
+                self.target = None;
+                self.planned_target = None;
+            }
+            return Vec3::ZERO;
+        };
+
+        horizontal(corner - position).normalize_or_zero() * self.speed
+    }
+}
+
+fn horizontal(v: Vec3) -> Vec3 {
+    Vec3::new(v.x, 0.0, v.z)
+}
+
+pub(crate) fn register_agent_systems(world: &World) {
+    let agents = world
+        .query::<(&mut NavAgent, &GlobalTransform, &mut MovementIntent)>()
+        .set_cached()
+        .build();
+
+    // Writes intents in OnUpdate; they are applied in PostUpdate
+    world
+        .system_named::<&NavMeshState>("Steer Nav Agents")
+        .kind(flecs::pipeline::OnUpdate)
+        .each_iter(move |iter, _, state| {
+            let world = iter.world();
+            let generation = state.generation();
+
+            let steered = state
+                .handle()
+                .try_get_entity(&world)
+                .and_then(|entity| {
+                    entity.try_get::<&NavMeshData>(|navmesh| steer_agents(&agents, navmesh, generation))
+                });
+            // Not baked yet: targets are unreachable until it is
+            if steered.is_none() {
+                steer_agents(&agents, &NavMeshData::default(), generation);
+            }
+        });
+}
+
+fn steer_agents(
+    agents: &Query<(&mut NavAgent, &GlobalTransform, &mut MovementIntent)>,
+    navmesh: &NavMeshData,
+    generation: u32,
+) {
+    agents.each_entity(|entity, (agent, transform, intent)| {
+        if !agent.radius_reported
+            && !navmesh.is_empty()
+            && agent.radius > navmesh.settings.agent_radius
+        {
+            agent.radius_reported = true;
+            log::warn!(
+                "NavAgent '{}' has radius {} but the navmesh was baked for {}, it may clip walls",
+                entity.name(),
+                agent.radius,
+                navmesh.settings.agent_radius
+            );
+        }
+
+        let position = transform.transform_point3(Vec3::ZERO);
+        intent.velocity = agent.steer(position, navmesh, generation);
+    });
+}
@@ -0,0 +1,399 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    },
+};
+
+use glam::{Vec2, Vec3};
+use thiserror::Error;
+
+use crate::navmesh::{NavLink, NavMeshData, NavMeshSettings, NavPolygon};
+
+// Keeps a bad cell size from allocating gigabytes
+const MAX_CELLS: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, Error)]
+pub enum BakeError {
+    #[error("no geometry to bake (tag static meshes with NavMeshAffector)")]
+    NoGeometry,
+    #[error("{width}x{depth} cells is too many, increase NavMeshSettings::cell_size")]
+    TooManyCells { width: usize, depth: usize },
+}
+
+/// Fraction of a bake done, shared between the bake job and the world.
+#[derive(Clone, Debug, Default)]
+pub struct BakeProgress(Arc<AtomicU32>);
+
+impl BakeProgress {
+    /// 0.0 to 1.0.
+    pub fn get(&self) -> f32 {
+        self.0.load(Ordering::Relaxed) as f32 / 1000.0
+    }
+
+    fn set(&self, fraction: f32) {
+        self.0
+            .store((fraction.clamp(0.0, 1.0) * 1000.0) as u32, Ordering::Relaxed);
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Cell {
+    height: f32,
+    walkable: bool,
+}
+
+/// Top surface of every cell, seen from above. A single layer: only the highest
+/// surface of a column is kept, so there is no walking under bridges or overhangs.
+struct Heightfield {
+    origin: Vec2, // World x, z of cell (0, 0)'s corner
+    cell_size: f32,
+    width: usize,
+    depth: usize,
+    cells: Vec<Option<Cell>>,
+}
+
+impl Heightfield {
+    fn index(&self, x: usize, z: usize) -> usize {
+        x + z * self.width
+    }
+
+    fn cell(&self, x: usize, z: usize) -> Option<Cell> {
+        self.cells[self.index(x, z)]
+    }
+
+    fn world(&self, x: usize, z: usize, height: f32) -> Vec3 {
+        Vec3::new(
+            self.origin.x + x as f32 * self.cell_size,
+            height,
+            self.origin.y + z as f32 * self.cell_size,
+        )
+    }
+
+    // The 4 direct neighbours that exist
+    fn neighbors(&self, x: usize, z: usize) -> impl Iterator<Item = (usize, usize)> + use<> {
+        let (width, depth) = (self.width, self.depth);
+        [(-1i32, 0i32), (1, 0), (0, -1), (0, 1)]
+            .into_iter()
+            .filter_map(move |(dx, dz)| {
+                let nx = x as i32 + dx;
+                let nz = z as i32 + dz;
+                (nx >= 0 && nz >= 0 && (nx as usize) < width && (nz as usize) < depth)
+                    .then_some((nx as usize, nz as usize))
+            })
+    }
+}
+
+/// Bakes world-space triangles into a navmesh. Runs for a while on big scenes, so it is
+/// meant for the compute pool; `progress` is updated as it goes.
+pub fn bake(
+    triangles: &[[Vec3; 3]],
+    settings: NavMeshSettings,
+    progress: &BakeProgress,
+) -> Result<NavMeshData, BakeError> {
+    progress.set(0.0);
+
+    let mut heightfield = rasterize(triangles, &settings)?;
+    progress.set(0.4);
+
+    erode(&mut heightfield, &settings);
+    progress.set(0.6);
+
+    let (regions, cell_regions) = grow_regions(&heightfield, &settings);
+    progress.set(0.9);
+
+    let polygons = build_polygons(&heightfield, &regions, &cell_regions, &settings);
+    progress.set(1.0);
+
+    Ok(NavMeshData { polygons, settings })
+}
+
+// 1. Voxelization: the top surface of every cell, sampled at the cell center
+fn rasterize(triangles: &[[Vec3; 3]], settings: &NavMeshSettings) -> Result<Heightfield, BakeError> {
+    let (min, max) = triangles
+        .iter()
+        .flatten()
+        .fold(None, |bounds: Option<(Vec3, Vec3)>, &p| match bounds {
+            Some((min, max)) => Some((min.min(p), max.max(p))),
+            None => Some((p, p)),
+        })
+        .ok_or(BakeError::NoGeometry)?;
+
+    let cell_size = settings.cell_size.max(0.01);
+    let width = ((max.x - min.x) / cell_size).ceil().max(1.0) as usize;
+    let depth = ((max.z - min.z) / cell_size).ceil().max(1.0) as usize;
+    if width.saturating_mul(depth) > MAX_CELLS {
+        return Err(BakeError::TooManyCells { width, depth });
+    }
+
+    let mut heightfield = Heightfield {
+        origin: Vec2::new(min.x, min.z),
+        cell_size,
+        width,
+        depth,
+        cells: vec![None; width * depth],
+    };
+
+    let min_normal_y = settings.max_slope_degrees.to_radians().cos();
+
+    for [a, b, c] in triangles {
+        let normal = (*b - *a).cross(*c - *a).normalize_or_zero();
+        if normal == Vec3::ZERO {
+            continue;
+        }
+        // Vertical faces have no area seen from above, the surfaces around them make the step
+        let walkable = normal.y.abs() >= min_normal_y;
+
+        let (a2, b2, c2) = (Vec2::new(a.x, a.z), Vec2::new(b.x, b.z), Vec2::new(c.x, c.z));
+        let area = (b2 - a2).perp_dot(c2 - a2);
+        if area.abs() <= f32::EPSILON {
+            continue;
+        }
+
+        let to_cell = |v: f32, origin: f32, count: usize| {
+            (((v - origin) / cell_size).floor().max(0.0) as usize).min(count - 1)
+        };
+        let x0 = to_cell(a.x.min(b.x).min(c.x), heightfield.origin.x, width);
+        let x1 = to_cell(a.x.max(b.x).max(c.x), heightfield.origin.x, width);
+        let z0 = to_cell(a.z.min(b.z).min(c.z), heightfield.origin.y, depth);
+        let z1 = to_cell(a.z.max(b.z).max(c.z), heightfield.origin.y, depth);
+
+        for z in z0..=z1 {
+            for x in x0..=x1 {
+                let center = heightfield.origin + (Vec2::new(x as f32, z as f32) + 0.5) * cell_size;
+
+                // Barycentric coordinates of the cell center
+                let w0 = (c2 - b2).perp_dot(center - b2) / area;
+                let w1 = (a2 - c2).perp_dot(center - c2) / area;
+                let w2 = 1.0 - w0 - w1;
+                if w0 < 0.0 || w1 < 0.0 || w2 < 0.0 {
+                    continue;
+                }
+
+                let height = a.y * w0 + b.y * w1 + c.y * w2;
+                let index = heightfield.index(x, z);
+                if heightfield.cells[index].is_none_or(|cell| height > cell.height) {
+                    heightfield.cells[index] = Some(Cell { height, walkable });
+                }
+            }
+        }
+    }
+
+    Ok(heightfield)
+}
+
+// 2. Erosion: cells closer than the agent radius to an edge (missing neighbour,
+// unwalkable neighbour or a step too high) are removed, by a breadth-first distance field
+fn erode(heightfield: &mut Heightfield, settings: &NavMeshSettings) {
+    let radius_cells = (settings.agent_radius / heightfield.cell_size).ceil() as u32;
+    if radius_cells == 0 {
+        return;
+    }
+
+    let connected = |heightfield: &Heightfield, x: usize, z: usize, nx: usize, nz: usize| {
+        match (heightfield.cell(x, z), heightfield.cell(nx, nz)) {
+            (Some(a), Some(b)) => b.walkable && (a.height - b.height).abs() <= settings.max_climb,
+            _ => false,
+        }
+    };
+
+    let mut distance = vec![u32::MAX; heightfield.cells.len()];
+    let mut queue = VecDeque::new();
+    for z in 0..heightfield.depth {
+        for x in 0..heightfield.width {
+            let index = heightfield.index(x, z);
+            if !heightfield.cells[index].is_some_and(|cell| cell.walkable) {
+                continue;
+            }
+
+            let at_edge = x == 0
+                || z == 0
+                || x == heightfield.width - 1
+                || z == heightfield.depth - 1
+                || heightfield
+                    .neighbors(x, z)
+                    .any(|(nx, nz)| !connected(heightfield, x, z, nx, nz));
+            if at_edge {
+                distance[index] = 1;
+                queue.push_back((x, z));
+            }
+        }
+    }
+
+    while let Some((x, z)) = queue.pop_front() {
+        let next = distance[heightfield.index(x, z)] + 1;
+        if next > radius_cells {
+            continue;
+        }
+        for (nx, nz) in heightfield.neighbors(x, z) {
+            let neighbor = heightfield.index(nx, nz);
+            if distance[neighbor] > next && connected(heightfield, x, z, nx, nz) {
+                distance[neighbor] = next;
+                queue.push_back((nx, nz));
+            }
+        }
+    }
+
+    for (cell, distance) in heightfield.cells.iter_mut().zip(distance) {
+        if let Some(cell) = cell
+            && distance <= radius_cells
+        {
+            cell.walkable = false;
+        }
+    }
+}
+
+struct Region {
+    x0: usize,
+    z0: usize,
+    x1: usize, // Exclusive
+    z1: usize, // Exclusive
+    height: f32,
+}
+
+// 3. Region growing: walkable cells are grouped greedily into rectangles of about the
+// same height, which become the polygons
+fn grow_regions(heightfield: &Heightfield, settings: &NavMeshSettings) -> (Vec<Region>, Vec<u32>) {
+    const NO_REGION: u32 = u32::MAX;
+
+    let max_cells = settings.max_polygon_cells.max(1) as usize;
+    let tolerance = settings.max_climb * 0.5;
+    let mut cell_regions = vec![NO_REGION; heightfield.cells.len()];
+    let mut regions = Vec::new();
+
+    for z in 0..heightfield.depth {
+        for x in 0..heightfield.width {
+            let Some(seed) = heightfield.cell(x, z) else {
+                continue;
+            };
+            if !seed.walkable || cell_regions[heightfield.index(x, z)] != NO_REGION {
+                continue;
+            }
+
+            let free = |cx: usize, cz: usize| {
+                cell_regions[heightfield.index(cx, cz)] == NO_REGION
+                    && heightfield.cell(cx, cz).is_some_and(|cell| {
+                        cell.walkable && (cell.height - seed.height).abs() <= tolerance
+                    })
+            };
+
+            let mut x1 = x + 1;
+            while x1 < heightfield.width && x1 - x < max_cells && free(x1, z) {
+                x1 += 1;
+            }
+            let mut z1 = z + 1;
+            while z1 < heightfield.depth && z1 - z < max_cells && (x..x1).all(|cx| free(cx, z1)) {
+                z1 += 1;
+            }
+
+            let mut height_sum = 0.0;
+            for cz in z..z1 {
+                for cx in x..x1 {
+                    cell_regions[heightfield.index(cx, cz)] = regions.len() as u32;
+                    height_sum += heightfield.cell(cx, cz).map_or(0.0, |cell| cell.height);
+                }
+            }
+
+            regions.push(Region {
+                x0: x,
+                z0: z,
+                x1,
+                z1,
+                height: height_sum / ((x1 - x) * (z1 - z)) as f32,
+            });
+        }
+    }
+
+    (regions, cell_regions)
+}
+
+// 4. Polygons and links. Each region walks its 4 sides; runs of cells facing the same
+// neighbouring region (within climbing height) become one portal
+fn build_polygons(
+    heightfield: &Heightfield,
+    regions: &[Region],
+    cell_regions: &[u32],
+    settings: &NavMeshSettings,
+) -> Vec<NavPolygon> {
+    let region_at = |x: i64, z: i64| -> Option<u32> {
+        if x < 0 || z < 0 || x as usize >= heightfield.width || z as usize >= heightfield.depth {
+            return None;
+        }
+        let region = cell_regions[heightfield.index(x as usize, z as usize)];
+        (region != u32::MAX).then_some(region)
+    };
+
+    regions
+        .iter()
+        .enumerate()
+        .map(|(index, region)| {
+            let h = region.height;
+            let vertices = vec![
+                heightfield.world(region.x0, region.z0, h),
+                heightfield.world(region.x1, region.z0, h),
+                heightfield.world(region.x1, region.z1, h),
+                heightfield.world(region.x0, region.z1, h),
+            ];
+            let center = vertices.iter().sum::<Vec3>() / 4.0;
+
+            let mut links = Vec::new();
+
+            // (edge along z, cell coordinate of the edge line, cells just outside, cells along)
+            let sides = [
+                (true, region.x0, region.x0 as i64 - 1, region.z0..region.z1),
+                (true, region.x1, region.x1 as i64, region.z0..region.z1),
+                (false, region.z0, region.z0 as i64 - 1, region.x0..region.x1),
+                (false, region.z1, region.z1 as i64, region.x0..region.x1),
+            ];
+
+            for (along_z, edge, outside, cells) in sides {
+                let point = |position: usize, y: f32| {
+                    if along_z {
+                        heightfield.world(edge, position, y)
+                    } else {
+                        heightfield.world(position, edge, y)
+                    }
+                };
+                // (neighbour, first cell, end cell exclusive)
+                let link = |(neighbor, first, end): (u32, usize, usize)| {
+                    let y = (h + regions[neighbor as usize].height) * 0.5;
+                    NavLink {
+                        polygon: neighbor,
+                        portal: [point(first, y), point(end, y)],
+                    }
+                };
+
+                let mut run: Option<(u32, usize, usize)> = None;
+                for position in cells {
+                    let (nx, nz) = if along_z {
+                        (outside, position as i64)
+                    } else {
+                        (position as i64, outside)
+                    };
+                    let neighbor = region_at(nx, nz).filter(|&neighbor| {
+                        neighbor != index as u32
+                            && (regions[neighbor as usize].height - h).abs() <= settings.max_climb
+                    });
+
+                    run = match (run, neighbor) {
+                        (Some((current, first, _)), Some(next)) if current == next => {
+                            Some((current, first, position + 1))
+                        }
+                        (previous, next) => {
+                            links.extend(previous.map(link));
+                            next.map(|next| (next, position, position + 1))
+                        }
+                    };
+                }
+                links.extend(run.map(link));
+            }
+
+            NavPolygon {
+                vertices,
+                center,
+                links,
+            }
+        })
+        .collect()
+}
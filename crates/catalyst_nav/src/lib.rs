@@ -0,0 +1,309 @@
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use catalyst_assets::{
+    AssetPlugin, MeshDefinition,
+    asset_events::AssetLookup,
+    assets::{Handle, MeshData},
+};
+use catalyst_core::{
+    App, Plugin, PluginId, console::ConsoleCommands, rayon, transform::GlobalTransform,
+};
+use flecs_ecs::prelude::*;
+use glam::Vec3;
+
+use crate::{
+    agent::register_agent_systems,
+    bake::{BakeError, BakeProgress, bake},
+};
+
+pub mod agent;
+pub mod bake;
+pub mod navmesh;
+mod path;
+
+pub use agent::{NavAgent, NavAgentStatus};
+pub use navmesh::{NavMeshData, NavMeshSettings};
+
+// Frames the affector geometry must stay unchanged before an automatic rebake starts,
+// so dragging an obstacle around doesn't start a bake every frame
+const SETTLE_FRAMES: u32 = 10;
+
+/// Tag for static geometry (MeshDefinition + GlobalTransform) the navmesh is baked from.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct NavMeshAffector;
+
+struct BakeTask {
+    progress: BakeProgress,
+    result: Arc<Mutex<Option<Result<NavMeshData, BakeError>>>>,
+    signature: u64,
+    started: Instant,
+}
+
+/// The navmesh and its baking. The baked NavMeshData lives on the asset entity of
+/// `handle()`, replaced (and `generation()` bumped) whenever a bake finishes.
+#[derive(Component)]
+pub struct NavMeshState {
+    handle: Handle<NavMeshData>,
+    generation: u32,
+    /// Rebake when affector geometry is added, removed, moved or replaced.
+    /// Without it the navmesh is baked once, then only on request_rebake.
+    pub auto_rebake: bool,
+    rebake_requested: bool,
+    // Geometry the current navmesh was baked from
+    baked_signature: Option<u64>,
+    // Geometry seen last frame, and for how many frames it hasn't changed
+    pending_signature: u64,
+    stable_frames: u32,
+    task: Option<BakeTask>,
+    last_error: Option<String>,
+}
+
+impl Default for NavMeshState {
+    fn default() -> Self {
+        Self {
+            handle: Handle::new(),
+            generation: 0,
+            auto_rebake: true,
+            rebake_requested: false,
+            baked_signature: None,
+            pending_signature: 0,
+            stable_frames: 0,
+            task: None,
+            last_error: None,
+        }
+    }
+}
+
+impl NavMeshState {
+    pub fn handle(&self) -> &Handle<NavMeshData> {
+        &self.handle
+    }
+
+    /// Bumped by every finished bake, 0 until the first one.
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// Progress of the running bake (0.0 to 1.0), None when idle.
+    pub fn progress(&self) -> Option<f32> {
+        self.task.as_ref().map(|task| task.progress.get())
+    }
+
+    pub fn is_baking(&self) -> bool {
+        self.task.is_some()
+    }
+
+    /// Bakes again as soon as the current bake (if any) is done.
+    pub fn request_rebake(&mut self) {
+        self.rebake_requested = true;
+    }
+
+    /// Why the last bake failed, cleared by the next successful one.
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+}
+
+/// Navigation meshes baked from NavMeshAffector geometry and NavAgents walking them.
+pub struct NavPlugin;
+
+impl Plugin for NavPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_singleton_default::<NavMeshSettings>();
+        app.register_singleton_default::<NavMeshState>();
+        app.register_component::<NavAgent>()
+            .register_tag::<NavMeshAffector>();
+        app.world
+            .get::<&mut ConsoleCommands>(|commands| register_nav_commands(commands));
+
+        register_bake_system(&app.world);
+        register_agent_systems(&app.world);
+    }
+
+    fn dependencies(&self) -> Vec<PluginId> {
+        vec![PluginId::of::<AssetPlugin>()]
+    }
+}
+
+fn register_nav_commands(commands: &mut ConsoleCommands) {
+    commands.register("nav.bake", "Rebake the navmesh", |world, _| {
+        world.get::<&mut NavMeshState>(|state| state.request_rebake());
+        Ok("NavMesh rebake requested".to_string())
+    });
+    commands.register("nav.info", "Print the navmesh state", |world, _| {
+        let (handle, generation, progress, error) = world.get::<&NavMeshState>(|state| {
+            (
+                state.handle().clone(),
+                state.generation(),
+                state.progress(),
+                state.last_error().map(str::to_owned),
+            )
+        });
+        let polygons = handle
+            .try_get_entity(world)
+            .and_then(|entity| entity.try_get::<&NavMeshData>(|navmesh| navmesh.polygons.len()))
+            .unwrap_or(0);
+
+        let mut info = format!("Generation {}, {} polygons", generation, polygons);
+        if let Some(progress) = progress {
+            info += &format!(", baking ({:.0}%)", progress * 100.0);
+        }
+        if let Some(error) = error {
+            info += &format!(", last bake failed: {}", error);
+        }
+        Ok(info)
+    });
+}
+
+// PostUpdate, after transform propagation, so affectors are seen where they are drawn
+fn register_bake_system(world: &World) {
+    let affectors = world
+        .query::<(&MeshDefinition, &GlobalTransform)>()
+        .with(NavMeshAffector)
+        .set_cached()
+        .build();
+
+    world
+        .system_named::<(&mut NavMeshState, &NavMeshSettings)>("Bake NavMesh")
+        .kind(flecs::pipeline::PostUpdate)
+        .each_iter(move |iter, _, (state, settings)| {
+            let world = iter.world();
+            finish_bake(&world, state);
+
+            let Some((signature, count)) = geometry_signature(&world, &affectors) else {
+                return; // Some affector mesh isn't loaded yet
+            };
+
+            if signature == state.pending_signature {
+                state.stable_frames = state.stable_frames.saturating_add(1);
+            } else {
+                state.pending_signature = signature;
+                state.stable_frames = 0;
+            }
+
+            let changed = (state.auto_rebake || state.baked_signature.is_none())
+                && state.baked_signature != Some(signature)
+                && state.stable_frames >= SETTLE_FRAMES;
+            if state.task.is_some() || !(changed || state.rebake_requested) {
+                return;
+            }
+            state.rebake_requested = false;
+
+            if count == 0 {
+                if state.baked_signature != Some(signature) {
+                    log::warn!("No NavMeshAffector geometry, nothing to bake");
+                    state.baked_signature = Some(signature);
+                }
+                return;
+            }
+
+            let triangles = collect_triangles(&world, &affectors);
+            state.task = Some(start_bake(triangles, *settings, signature));
+        });
+}
+
+// Hash of every affector's mesh and transform. None while a mesh isn't loaded.
+fn geometry_signature(
+    world: &WorldRef,
+    affectors: &Query<(&MeshDefinition, &GlobalTransform)>,
+) -> Option<(u64, usize)> {
+    let mut hasher = DefaultHasher::new();
+    let mut count = 0;
+    let mut loaded = true;
+
+    affectors.each_entity(|entity, (mesh, transform)| {
+        count += 1;
+        entity.id().hash(&mut hasher);
+        mesh.0.id.hash(&mut hasher);
+        transform.to_cols_array().map(f32::to_bits).hash(&mut hasher);
+
+        let vertex_count = mesh
+            .0
+            .try_get_entity(world)
+            .and_then(|asset| asset.try_get::<&MeshData>(|data| data.vertices.len()));
+        match vertex_count {
+            Some(vertex_count) => vertex_count.hash(&mut hasher),
+            None => loaded = false,
+        }
+    });
+
+    loaded.then(|| (hasher.finish(), count))
+}
+
+fn collect_triangles(
+    world: &WorldRef,
+    affectors: &Query<(&MeshDefinition, &GlobalTransform)>,
+) -> Vec<[Vec3; 3]> {
+    let mut triangles = Vec::new();
+    affectors.each(|(mesh, transform)| {
+        let Some(asset) = mesh.0.try_get_entity(world) else {
+            return;
+        };
+        asset.try_get::<&MeshData>(|data| {
+            let vertex = |index: u32| {
+                transform.transform_point3(Vec3::from_array(data.vertices[index as usize].position))
+            };
+            for triangle in data.indices.chunks_exact(3) {
+                triangles.push([vertex(triangle[0]), vertex(triangle[1]), vertex(triangle[2])]);
+            }
+        });
+    });
+    triangles
+}
+
+// Runs on the rayon compute pool, the result is picked up by finish_bake
+fn start_bake(triangles: Vec<[Vec3; 3]>, settings: NavMeshSettings, signature: u64) -> BakeTask {
+    log::info!("Baking navmesh from {} triangles", triangles.len());
+
+    let task = BakeTask {
+        progress: BakeProgress::default(),
+        result: Arc::new(Mutex::new(None)),
+        signature,
+        started: Instant::now(),
+    };
+
+    let progress = task.progress.clone();
+    let result = task.result.clone();
+    rayon::spawn(move || {
+        catalyst_core::profile_scope!("Navmesh Bake");
+        let baked = bake(&triangles, settings, &progress);
+        *result.lock().unwrap() = Some(baked);
+    });
+
+    task
+}
+
+fn finish_bake(world: &WorldRef, state: &mut NavMeshState) {
+    let finished = state
+        .task
+        .as_ref()
+        .and_then(|task| task.result.lock().unwrap().take());
+    let Some(result) = finished else {
+        return;
+    };
+    let task = state.task.take().unwrap();
+    state.baked_signature = Some(task.signature);
+
+    match result {
+        Ok(navmesh) => {
+            log::info!(
+                "Baked navmesh: {} polygons in {:.1} ms",
+                navmesh.polygons.len(),
+                task.started.elapsed().as_secs_f64() * 1000.0
+            );
+
+            let entity = world.get::<&mut AssetLookup>(|lookup| lookup.entity(state.handle.id, world));
+            world.entity_from_id(entity).set_name("navmesh").set(navmesh);
+            state.generation += 1;
+            state.last_error = None;
+        }
+        Err(error) => {
+            log::error!("NavMesh bake failed: {}", error);
+            state.last_error = Some(error.to_string());
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use catalyst_core::bounds::Ray;
+use flecs_ecs::prelude::*;
+use glam::{Vec2, Vec3};
+
+/// Bake parameters. Read when a bake starts, so edits apply to the next bake.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct NavMeshSettings {
+    /// Horizontal size of a heightfield cell. Smaller is more precise and slower to bake.
+    pub cell_size: f32,
+    /// The walkable area is shrunk by this much, so agents up to this radius clear walls.
+    pub agent_radius: f32,
+    /// Steeper surfaces are not walkable.
+    pub max_slope_degrees: f32,
+    /// Largest height step between neighbouring cells an agent can walk over.
+    pub max_climb: f32,
+    /// Polygons are at most this many cells across, which keeps A* costs meaningful.
+    pub max_polygon_cells: u32,
+}
+
+impl Default for NavMeshSettings {
+    fn default() -> Self {
+        Self {
+            cell_size: 0.25,
+            agent_radius: 0.4,
+            max_slope_degrees: 45.0,
+            max_climb: 0.3,
+            max_polygon_cells: 32,
+        }
+    }
+}
+
+/// Edge shared with a neighbouring polygon, the only way to walk into it.
+#[derive(Clone, Copy, Debug)]
+pub struct NavLink {
+    pub polygon: u32,
+    pub portal: [Vec3; 2],
+}
+
+/// Convex, flat walkable area.
+#[derive(Clone, Debug)]
+pub struct NavPolygon {
+    /// Outline, in order around the polygon.
+    pub vertices: Vec<Vec3>,
+    pub center: Vec3,
+    pub links: Vec<NavLink>,
+}
+
+impl NavPolygon {
+    /// Whether `point` is inside the outline, seen from above.
+    pub fn contains_xz(&self, point: Vec3) -> bool {
+        let p = Vec2::new(point.x, point.z);
+        let mut sign = 0.0f32;
+        for (i, a) in self.vertices.iter().enumerate() {
+            let b = self.vertices[(i + 1) % self.vertices.len()];
+            let edge = Vec2::new(b.x - a.x, b.z - a.z);
+            let to_point = p - Vec2::new(a.x, a.z);
+            let cross = edge.perp_dot(to_point);
+            if cross.abs() <= f32::EPSILON {
+                continue; // On the edge line
+            }
+            if sign != 0.0 && cross.signum() != sign {
+                return false;
+            }
+            sign = cross.signum();
+        }
+        true
+    }
+
+    /// Closest point of the polygon to `point`, seen from above, at the polygon's height.
+    pub fn clamp_xz(&self, point: Vec3) -> Vec3 {
+        if self.contains_xz(point) {
+            return Vec3::new(point.x, self.center.y, point.z);
+        }
+
+        let p = Vec2::new(point.x, point.z);
+        let mut closest = Vec2::new(self.center.x, self.center.z);
+        let mut closest_distance = f32::MAX;
+        for (i, a) in self.vertices.iter().enumerate() {
+            let b = self.vertices[(i + 1) % self.vertices.len()];
+            let (a, b) = (Vec2::new(a.x, a.z), Vec2::new(b.x, b.z));
+            let t = ((p - a).dot(b - a) / (b - a).length_squared().max(f32::EPSILON)).clamp(0.0, 1.0);
+            let candidate = a + (b - a) * t;
+            let distance = candidate.distance_squared(p);
+            if distance < closest_distance {
+                closest = candidate;
+                closest_distance = distance;
+            }
+        }
+        Vec3::new(closest.x, self.center.y, closest.y)
+    }
+}
+
+/// Baked navigation mesh: walkable polygons and how they connect. Lives on an asset
+/// entity, see NavMeshState::handle.
+#[derive(Component, Clone, Debug, Default)]
+pub struct NavMeshData {
+    pub polygons: Vec<NavPolygon>,
+    /// What it was baked with; agents wider than `agent_radius` may clip walls.
+    pub settings: NavMeshSettings,
+}
+
+impl NavMeshData {
+    pub fn is_empty(&self) -> bool {
+        self.polygons.is_empty()
+    }
+
+    /// The polygon under `point`: of those containing it seen from above, the one
+    /// closest in height. None when the point is outside the navmesh.
+    pub fn find_polygon(&self, point: Vec3) -> Option<u32> {
+        self.polygons
+            .iter()
+            .enumerate()
+            .filter(|(_, polygon)| polygon.contains_xz(point))
+            .min_by(|(_, a), (_, b)| {
+                (a.center.y - point.y)
+                    .abs()
+                    .total_cmp(&(b.center.y - point.y).abs())
+            })
+            .map(|(index, _)| index as u32)
+    }
+
+    /// Like find_polygon, but off-mesh points snap to the nearest polygon.
+    /// Returns the polygon and the point moved onto it. None only for an empty navmesh.
+    pub fn nearest_point(&self, point: Vec3) -> Option<(u32, Vec3)> {
+        if let Some(index) = self.find_polygon(point) {
+            let height = self.polygons[index as usize].center.y;
+            return Some((index, Vec3::new(point.x, height, point.z)));
+        }
+
+        self.polygons
+            .iter()
+            .enumerate()
+            .map(|(index, polygon)| (index as u32, polygon.clamp_xz(point)))
+            .min_by(|(_, a), (_, b)| a.distance_squared(point).total_cmp(&b.distance_squared(point)))
+    }
+
+    /// First hit of `ray` on the walkable surface, e.g. to turn a click into a target.
+    pub fn raycast(&self, ray: &Ray) -> Option<Vec3> {
+        self.polygons
+            .iter()
+            .flat_map(|polygon| {
+                // Fan triangulation, polygons are convex
+                let first = polygon.vertices[0];
+                polygon
+                    .vertices
+                    .windows(2)
+                    .skip(1)
+                    .filter_map(move |edge| ray.intersect_triangle(first, edge[0], edge[1]))
+            })
+            .min_by(f32::total_cmp)
+            .map(|distance| ray.at(distance))
+    }
+}
@@ -0,0 +1,327 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+};
+
+use glam::Vec3;
+
+use crate::navmesh::NavMeshData;
+
+#[derive(Clone, Copy, PartialEq)]
+struct OpenNode {
+    estimate: f32, // Cost so far + straight line to the goal
+    polygon: u32,
+}
+
+impl Eq for OpenNode {}
+
+// Reversed, so BinaryHeap pops the cheapest node first
+impl Ord for OpenNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl NavMeshData {
+    /// Shortest path from `start` to `end` as a list of corners, starting at `start`
+    /// and ending at `end`, both moved onto the navmesh. None when no polygon connects them.
+    pub fn find_path(&self, start: Vec3, end: Vec3) -> Option<Vec<Vec3>> {
+        let (start_polygon, start) = self.nearest_point(start)?;
+        let (end_polygon, end) = self.nearest_point(end)?;
+
+        let corridor = self.find_corridor(start_polygon, end_polygon, end)?;
+        Some(self.string_pull(&corridor, start, end))
+    }
+
+    // A* over polygons, costs between polygon centers. Returns the polygons walked through.
+    fn find_corridor(&self, start: u32, end: u32, goal: Vec3) -> Option<Vec<u32>> {
+        let mut open = BinaryHeap::new();
+        let mut cost: HashMap<u32, f32> = HashMap::new();
+        let mut came_from: HashMap<u32, u32> = HashMap::new();
+
+        cost.insert(start, 0.0);
+        open.push(OpenNode {
+            estimate: self.polygons[start as usize].center.distance(goal),
+            polygon: start,
+        });
+
+        while let Some(OpenNode { polygon, .. }) = open.pop() {
+            if polygon == end {
+                let mut corridor = vec![end];
+                while let Some(&previous) = came_from.get(corridor.last().unwrap()) {
+                    corridor.push(previous);
+                }
+                corridor.reverse();
+                return Some(corridor);
+            }
+
+            let current = &self.polygons[polygon as usize];
+            let current_cost = cost[&polygon];
+            for link in &current.links {
+                let next = &self.polygons[link.polygon as usize];
+                let next_cost = current_cost + current.center.distance(next.center);
+                if cost.get(&link.polygon).is_some_and(|&known| known <= next_cost) {
+                    continue;
+                }
+
+                cost.insert(link.polygon, next_cost);
+                came_from.insert(link.polygon, polygon);
+                open.push(OpenNode {
+                    estimate: next_cost + next.center.distance(goal),
+                    polygon: link.polygon,
+                });
+            }
+        }
+
+        None
+    }
+
+    // Funnel algorithm (simple stupid funnel): tightens the corridor's portals into the
+    // fewest corners that stay on the navmesh
+    fn string_pull(&self, corridor: &[u32], start: Vec3, end: Vec3) -> Vec<Vec3> {
+        // Portals as (left, right) seen walking through them, then the end as a closed portal
+        let mut portals = vec![(start, start)];
+        for pair in corridor.windows(2) {
+            let from = &self.polygons[pair[0] as usize];
+            let Some(link) = from.links.iter().find(|link| link.polygon == pair[1]) else {
+                continue;
+            };
+            let [a, b] = link.portal;
+            if triarea2(from.center, a, b) > 0.0 {
+                portals.push((a, b));
+            } else {
+                portals.push((b, a));
+            }
+        }
+        portals.push((end, end));
+
+        let mut path = vec![start];
+        let (mut apex, mut left, mut right) = (start, start, start);
+        let (mut apex_index, mut left_index, mut right_index) = (0, 0, 0);
+
+        let mut i = 1;
+        while i < portals.len() {
+            let (next_left, next_right) = portals[i];
+
+            // Tighten the right side
+            if triarea2(apex, right, next_right) <= 0.0 {
+                if same_xz(apex, right) || triarea2(apex, left, next_right) > 0.0 {
+                    right = next_right;
+                    right_index = i;
+                } else {
+                    // Right crossed over left: left is a corner, restart from it. Portals
+                    // that share the corner can make it the apex twice in a row
+                    apex = left;
+                    apex_index = left_index;
+                    push_corner(&mut path, apex);
+                    (left, right) = (apex, apex);
+                    (left_index, right_index) = (apex_index, apex_index);
+                    i = apex_index + 1;
+                    continue;
+                }
+            }
+
+            // Tighten the left side
+            if triarea2(apex, left, next_left) >= 0.0 {
+                if same_xz(apex, left) || triarea2(apex, right, next_left) < 0.0 {
+                    left = next_left;
+                    left_index = i;
+                } else {
+                    apex = right;
+                    apex_index = right_index;
+                    push_corner(&mut path, apex);
+                    (left, right) = (apex, apex);
+                    (left_index, right_index) = (apex_index, apex_index);
+                    i = apex_index + 1;
+                    continue;
+                }
+            }
+
+            i += 1;
+        }
+
+        push_corner(&mut path, end);
+        path
+    }
+}
+
+fn push_corner(path: &mut Vec<Vec3>, corner: Vec3) {
+    if !path.last().is_some_and(|&last| same_xz(last, corner)) {
+        path.push(corner);
+    }
+}
+
+// Twice the signed area of the triangle, seen from above (x, z)
+fn triarea2(a: Vec3, b: Vec3, c: Vec3) -> f32 {
+    let (ax, az) = (b.x - a.x, b.z - a.z);
+    let (bx, bz) = (c.x - a.x, c.z - a.z);
+    bx * az - ax * bz
+}
+
+fn same_xz(a: Vec3, b: Vec3) -> bool {
+    (a.x - b.x).abs() < 1e-4 && (a.z - b.z).abs() < 1e-4
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::navmesh::{NavLink, NavMeshSettings, NavPolygon};
+
+    // Unit squares on the ground, one per (x, z) cell, linked to their side neighbours
+    fn cells(cells: &[(i32, i32)]) -> NavMeshData {
+        let corner = |x: i32, z: i32| Vec3::new(x as f32, 0.0, z as f32);
+        let mut polygons: Vec<NavPolygon> = cells
+            .iter()
+            .map(|&(x, z)| NavPolygon {
+                vertices: vec![
+                    corner(x, z),
+                    corner(x + 1, z),
+                    corner(x + 1, z + 1),
+                    corner(x, z + 1),
+                ],
+                center: Vec3::new(x as f32 + 0.5, 0.0, z as f32 + 0.5),
+                links: Vec::new(),
+            })
+            .collect();
+
+        for (i, &(x, z)) in cells.iter().enumerate() {
+            for (j, &(other_x, other_z)) in cells.iter().enumerate() {
+                let portal = match (other_x - x, other_z - z) {
+                    (1, 0) => [corner(x + 1, z), corner(x + 1, z + 1)],
+                    (-1, 0) => [corner(x, z), corner(x, z + 1)],
+                    (0, 1) => [corner(x, z + 1), corner(x + 1, z + 1)],
+                    (0, -1) => [corner(x, z), corner(x + 1, z)],
+                    _ => continue,
+                };
+                polygons[i].links.push(NavLink {
+                    polygon: j as u32,
+                    portal,
+                });
+            }
+        }
+
+        NavMeshData {
+            polygons,
+            settings: NavMeshSettings::default(),
+        }
+    }
+
+    fn assert_path(path: &[Vec3], expected: &[Vec3]) {
+        assert_eq!(path.len(), expected.len(), "{:?}", path);
+        for (corner, expected) in path.iter().zip(expected) {
+            assert!(corner.distance(*expected) < 1e-4, "{:?}", path);
+        }
+    }
+
+    #[test]
+    fn straight_corridors_need_no_corners() {
+        let navmesh = cells(&[(0, 0), (1, 0), (2, 0), (3, 0), (4, 0)]);
+        let (start, end) = (Vec3::new(0.5, 0.0, 0.5), Vec3::new(4.5, 0.0, 0.2));
+
+        let path = navmesh.find_path(start, end).unwrap();
+        assert_path(&path, &[start, end]);
+    }
+
+    #[test]
+    fn paths_go_around_obstacles() {
+        // A 3x3 room with the middle cell blocked
+        let navmesh = cells(&[
+            (0, 0),
+            (1, 0),
+            (2, 0),
+            (0, 1),
+            (2, 1),
+            (0, 2),
+            (1, 2),
+            (2, 2),
+        ]);
+        let (start, end) = (Vec3::new(0.5, 0.0, 1.5), Vec3::new(2.5, 0.0, 1.5));
+
+        let path = navmesh.find_path(start, end).unwrap();
+        // Both ways round are as short; either hugs the blocked cell's corners
+        let below = [
+            start,
+            Vec3::new(1.0, 0.0, 1.0),
+            Vec3::new(2.0, 0.0, 1.0),
+            end,
+        ];
+        let above = [
+            start,
+            Vec3::new(1.0, 0.0, 2.0),
+            Vec3::new(2.0, 0.0, 2.0),
+            end,
+        ];
+        if path.iter().any(|corner| corner.z > 1.5) {
+            assert_path(&path, &above);
+        } else {
+            assert_path(&path, &below);
+        }
+
+        // Every step stays on the navmesh
+        for segment in path.windows(2) {
+            for step in 0..=10 {
+                let point = segment[0].lerp(segment[1], step as f32 / 10.0);
+                assert!(
+                    navmesh.find_polygon(point).is_some(),
+                    "{:?} is off the navmesh",
+                    point
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn unreachable_goals_have_no_path() {
+        // Two rooms with no link between them
+        let navmesh = cells(&[(0, 0), (1, 0), (3, 0), (4, 0)]);
+
+        assert!(
+            navmesh
+                .find_path(Vec3::new(0.5, 0.0, 0.5), Vec3::new(4.5, 0.0, 0.5))
+                .is_none()
+        );
+        assert!(
+            NavMeshData::default()
+                .find_path(Vec3::ZERO, Vec3::X)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn start_and_goal_in_one_polygon_go_straight() {
+        let navmesh = cells(&[(0, 0), (1, 0)]);
+        let (start, end) = (Vec3::new(0.1, 0.0, 0.1), Vec3::new(0.9, 0.0, 0.8));
+
+        assert_eq!(navmesh.find_corridor(0, 0, end), Some(vec![0]));
+        let path = navmesh.find_path(start, end).unwrap();
+        assert_path(&path, &[start, end]);
+
+        // Off-mesh ends are moved onto it first
+        let path = navmesh
+            .find_path(Vec3::new(-1.0, 2.0, 0.5), Vec3::new(0.5, 0.0, -3.0))
+            .unwrap();
+        assert_path(&path, &[Vec3::new(0.0, 0.0, 0.5), Vec3::new(0.5, 0.0, 0.0)]);
+    }
+
+    #[test]
+    fn funnel_turns_at_the_inner_corner_of_a_bend() {
+        // An L: along x, then along z
+        let navmesh = cells(&[(0, 0), (1, 0), (2, 0), (2, 1), (2, 2)]);
+        let (start, end) = (Vec3::new(0.5, 0.0, 0.5), Vec3::new(2.5, 0.0, 2.5));
+
+        let corridor = navmesh.find_corridor(0, 4, end).unwrap();
+        assert_eq!(corridor, vec![0, 1, 2, 3, 4]);
+        let path = navmesh.string_pull(&corridor, start, end);
+        assert_path(&path, &[start, Vec3::new(2.0, 0.0, 1.0), end]);
+
+        // The other way round turns at the same corner
+        let path = navmesh.string_pull(&[4, 3, 2, 1, 0], end, start);
+        assert_path(&path, &[end, Vec3::new(2.0, 0.0, 1.0), start]);
+    }
+}
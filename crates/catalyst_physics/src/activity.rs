@@ -0,0 +1,98 @@
+use catalyst_core::{
+    activity::{ActivityRange, ActivityState},
+    pipeline::PhysicsPrepare,
+};
+use flecs_ecs::prelude::*;
+use rapier3d::prelude::*;
+
+use crate::{PhysicsWorld, prepare::PhysicsHandle};
+
+/// Tag: a body put to sleep because its ActivityState is Asleep. It stays where it was
+/// and prepare_physic_bodies stops pushing its transform (which would wake it) until
+/// it comes back in range.
+#[derive(Component)]
+pub struct ForcedSleep;
+
+// After prepare_physics_system (same phase, registered first), so new bodies exist
+pub fn activity_sleep_system(app: &catalyst_core::App) {
+    app.world.component::<ForcedSleep>();
+
+    app.world
+        .system_named::<(
+            &ActivityState,
+            &ActivityRange,
+            &PhysicsHandle,
+            &mut PhysicsWorld,
+        )>("physics_activity_sleep")
+        .kind(PhysicsPrepare)
+        .each_entity(|entity, (state, range, handle, physics)| {
+            let Some(body_handle) = handle.body else {
+                return;
+            };
+            let asleep = *state == ActivityState::Asleep;
+            let forced = entity.has(ForcedSleep);
+
+            if asleep {
+                // Again every frame: contacts and joints may have woken it
+                if !forced || !body_sleeping(physics, body_handle) {
+                    put_to_sleep(physics, body_handle, range.disable_colliders);
+                }
+                if !forced {
+                    entity.add(ForcedSleep);
+                }
+            } else if forced {
+                wake(physics, body_handle);
+                entity.remove(ForcedSleep);
+            }
+        });
+
+    // Without a range the body is never put to sleep again, wake it for good
+    app.world
+        .system_named::<(&PhysicsHandle, &mut PhysicsWorld)>("physics_activity_release")
+        .with(ForcedSleep)
+        .without(ActivityRange::id())
+        .kind(PhysicsPrepare)
+        .each_entity(|entity, (handle, physics)| {
+            if let Some(body_handle) = handle.body {
+                wake(physics, body_handle);
+            }
+            entity.remove(ForcedSleep);
+        });
+}
+
+fn body_sleeping(physics: &PhysicsWorld, handle: RigidBodyHandle) -> bool {
+    physics
+        .bodies
+        .get(handle)
+        .is_none_or(|body| body.is_sleeping())
+}
+
+fn put_to_sleep(physics: &mut PhysicsWorld, handle: RigidBodyHandle, disable_colliders: bool) {
+    let Some(body) = physics.bodies.get_mut(handle) else {
+        return;
+    };
+    body.sleep();
+
+    // Disabled colliders leave the broad phase; the body keeps its pose
+    if disable_colliders {
+        for collider in body.colliders().to_vec() {
+            if let Some(collider) = physics.colliders.get_mut(collider) {
+                collider.set_enabled(false);
+            }
+        }
+    }
+}
+
+fn wake(physics: &mut PhysicsWorld, handle: RigidBodyHandle) {
+    let Some(body) = physics.bodies.get_mut(handle) else {
+        return;
+    };
+    body.wake_up(true);
+
+    // Whether or not the range disabled them, it may have changed since
+    for collider in body.colliders().to_vec() {
+        if let Some(collider) = physics.colliders.get_mut(collider) {
+            collider.set_enabled(true);
+        }
+    }
+}
@@ -0,0 +1,426 @@
+use std::{collections::HashMap, path::Path};
+
+use catalyst_core::{
+    console::ConsoleCommands,
+    time::PhysicsTime,
+    transform::{GlobalTransform, Transform},
+};
+use flecs_ecs::prelude::*;
+use glam::{Mat4, Vec3};
+use rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{PhysicsBodyAdded, PhysicsWorld, prepare::PhysicsHandle};
+
+/// The whole Rapier state at one step: bodies with their poses, velocities and sleep
+/// flags, colliders, joints, islands and contacts. Restoring it replays the simulation
+/// from there, as many times as needed while tweaking parameters.
+///
+/// Only meant for the world it was taken from: bodies are matched to entities by their
+/// handles, so bodies added or removed since then don't line up anymore.
+#[derive(Clone)]
+pub struct PhysicsSnapshot {
+    data: String,
+}
+
+impl PhysicsSnapshot {
+    /// Size of the serialized state, in bytes.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, &self.data)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Ok(Self {
+            data: std::fs::read_to_string(path)?,
+        })
+    }
+}
+
+// RON rather than JSON: Rapier keeps infinite values (e.g. the mass of fixed bodies)
+#[derive(Serialize)]
+struct StateRef<'a> {
+    gravity: Vec3,
+    integration_parameters: &'a IntegrationParameters,
+    islands: &'a IslandManager,
+    broad_phase: &'a BroadPhaseBvh,
+    narrow_phase: &'a NarrowPhase,
+    bodies: &'a RigidBodySet,
+    colliders: &'a ColliderSet,
+    impulse_joints: &'a ImpulseJointSet,
+    multibody_joints: &'a MultibodyJointSet,
+    ccd_solver: &'a CCDSolver,
+}
+
+#[derive(Deserialize)]
+struct State {
+    gravity: Vec3,
+    integration_parameters: IntegrationParameters,
+    islands: IslandManager,
+    broad_phase: BroadPhaseBvh,
+    narrow_phase: NarrowPhase,
+    bodies: RigidBodySet,
+    colliders: ColliderSet,
+    impulse_joints: ImpulseJointSet,
+    multibody_joints: MultibodyJointSet,
+    ccd_solver: CCDSolver,
+}
+
+impl PhysicsWorld {
+    pub fn snapshot(&self) -> Result<PhysicsSnapshot, String> {
+        let state = StateRef {
+            gravity: self.gravity,
+            integration_parameters: &self.integration_parameters,
+            islands: &self.islands,
+            broad_phase: &self.broad_phase,
+            narrow_phase: &self.narrow_phase,
+            bodies: &self.bodies,
+            colliders: &self.colliders,
+            impulse_joints: &self.impulse_joints,
+            multibody_joints: &self.multibody_joints,
+            ccd_solver: &self.ccd_solver,
+        };
+        let data = ron::to_string(&state).map_err(|e| e.to_string())?;
+        Ok(PhysicsSnapshot { data })
+    }
+
+    /// Puts the simulation back where `snapshot` was taken. Entity transforms follow at
+    /// the next step, or right away with `restore_snapshot`.
+    pub fn restore(&mut self, snapshot: &PhysicsSnapshot) -> Result<(), String> {
+        let state: State = ron::from_str(&snapshot.data).map_err(|e| e.to_string())?;
+        self.gravity = state.gravity;
+        self.integration_parameters = state.integration_parameters;
+        self.islands = state.islands;
+        self.broad_phase = state.broad_phase;
+        self.narrow_phase = state.narrow_phase;
+        self.bodies = state.bodies;
+        self.colliders = state.colliders;
+        self.impulse_joints = state.impulse_joints;
+        self.multibody_joints = state.multibody_joints;
+        self.ccd_solver = state.ccd_solver;
+        Ok(())
+    }
+}
+
+/// Debugger state kept between console commands and debug UI clicks.
+#[derive(Component, Default)]
+pub struct PhysicsDebugger {
+    /// Taken by `physics.snapshot` without a path, or the debug UI.
+    pub snapshot: Option<PhysicsSnapshot>,
+    /// Draw every dynamic body's linear velocity with the colliders.
+    pub draw_velocities: bool,
+}
+
+/// Restores `snapshot` and moves the bodies' entities to their restored poses right
+/// away, so they show where they were while physics is paused. Without this the next
+/// step would push their current transforms back into the bodies.
+pub fn restore_snapshot(world: &WorldRef, snapshot: &PhysicsSnapshot) -> Result<(), String> {
+    let poses: HashMap<RigidBodyHandle, Pose> = world.get::<&mut PhysicsWorld>(|physics| {
+        physics.restore(snapshot)?;
+        Ok::<_, String>(
+            physics
+                .bodies
+                .iter()
+                .map(|(handle, body)| (handle, *body.position()))
+                .collect(),
+        )
+    })?;
+
+    world
+        .query::<(&PhysicsHandle, &mut Transform, Option<&mut GlobalTransform>)>()
+        .with(PhysicsBodyAdded)
+        .build()
+        .each(|(handle, transform, global)| {
+            let Some(pose) = handle.body.and_then(|body| poses.get(&body)) else {
+                return;
+            };
+            transform.translation = pose.translation;
+            transform.rotation = pose.rotation.into();
+            // Bodies are roots: their global transform is the local one
+            if let Some(global) = global {
+                global.0 = Mat4::from_scale_rotation_translation(
+                    transform.scale,
+                    transform.rotation,
+                    transform.translation,
+                );
+            }
+        });
+    Ok(())
+}
+
+pub(crate) fn register_debugger_commands(commands: &mut ConsoleCommands) {
+    commands.register(
+        "physics.pause",
+        "Pause or resume the physics steps, the rest of the engine keeps running",
+        |world, _| {
+            let paused = world.get::<&mut PhysicsTime>(|time| {
+                time.set_paused(!time.is_paused());
+                time.is_paused()
+            });
+            Ok(if paused {
+                "Physics paused".to_string()
+            } else {
+                "Physics resumed".to_string()
+            })
+        },
+    );
+    commands.register(
+        "physics.step",
+        "physics.step [count] - run fixed steps now, paused or not",
+        |world, args| {
+            let count = match args.first() {
+                Some(arg) => arg
+                    .parse::<u32>()
+                    .map_err(|_| format!("'{}' isn't a step count", arg))?,
+                None => 1,
+            };
+            let fixed_dt = world.get::<&mut PhysicsTime>(|time| {
+                for _ in 0..count {
+                    time.request_step();
+                }
+                time.fixed_dt
+            });
+            Ok(format!("Stepping {} x {:.4} s", count, fixed_dt))
+        },
+    );
+    commands.register(
+        "physics.slowmo",
+        "physics.slowmo <scale> - speed of the physics steps, 1 for real time",
+        |world, args| {
+            let arg = args.first().ok_or("usage: physics.slowmo <scale>")?;
+            let scale = match arg.parse::<f32>() {
+                Ok(scale) if scale >= 0.0 => scale,
+                _ => return Err(format!("'{}' isn't a time scale", arg)),
+            };
+            world.get::<&mut PhysicsTime>(|time| time.set_scale(scale));
+            Ok(format!("Physics time scale set to {}", scale))
+        },
+    );
+    commands.register(
+        "physics.snapshot",
+        "physics.snapshot [path] - keep the physics state in memory, or write it to a file",
+        |world, args| {
+            let snapshot = world.get::<&PhysicsWorld>(|physics| physics.snapshot())?;
+            let size = snapshot.len();
+            match args.first() {
+                Some(path) => {
+                    snapshot.save(path).map_err(|e| e.to_string())?;
+                    Ok(format!(
+                        "Physics state written to {} ({} bytes)",
+                        path, size
+                    ))
+                }
+                None => {
+                    world.get::<&mut PhysicsDebugger>(|debugger| {
+                        debugger.snapshot = Some(snapshot);
+                    });
+                    Ok(format!("Physics state kept ({} bytes)", size))
+                }
+            }
+        },
+    );
+    commands.register(
+        "physics.restore",
+        "physics.restore [path] - go back to the kept physics state, or one from a file",
+        |world, args| {
+            let snapshot = match args.first() {
+                Some(path) => PhysicsSnapshot::load(path).map_err(|e| e.to_string())?,
+                None => world
+                    .get::<&PhysicsDebugger>(|debugger| debugger.snapshot.clone())
+                    .ok_or("no physics state kept yet, see physics.snapshot")?,
+            };
+            restore_snapshot(world, &snapshot)?;
+            Ok("Physics state restored".to_string())
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use catalyst_core::{App, physics::RigidBodyDefinition, pipeline::PhysicsPipeline};
+    use glam::Quat;
+
+    use super::*;
+    use crate::PhysicsPlugin;
+
+    const FIXED_DT: f32 = 1.0 / 50.0;
+
+    // The runner's loop: accumulate, run the steps due, then the frame
+    fn frame(app: &mut App, dt: f32) -> u32 {
+        let steps = app.world.get::<&mut PhysicsTime>(|time| {
+            time.accumulate(dt);
+            time.take_steps(4)
+        });
+        for _ in 0..steps {
+            app.world.run_pipeline_time(PhysicsPipeline, FIXED_DT);
+        }
+        app.update();
+        steps
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct BodyState {
+        translation: Vec3,
+        rotation: Quat,
+        linvel: Vec3,
+        angvel: Vec3,
+        sleeping: bool,
+    }
+
+    fn body_states(app: &App, handles: &[RigidBodyHandle]) -> Vec<BodyState> {
+        app.world.get::<&PhysicsWorld>(|physics| {
+            handles
+                .iter()
+                .filter_map(|handle| physics.bodies.get(*handle))
+                .map(|body| BodyState {
+                    translation: body.position().translation,
+                    rotation: body.position().rotation.into(),
+                    linvel: body.vels().linvel,
+                    angvel: body.vels().angvel,
+                    sleeping: body.is_sleeping(),
+                })
+                .collect()
+        })
+    }
+
+    fn physics_app(gravity: Vec3) -> App {
+        let mut app = App::new();
+        app.add_plugin(PhysicsPlugin::default());
+        app.world
+            .get::<&mut PhysicsTime>(|time| time.fixed_dt = FIXED_DT);
+        app.world
+            .get::<&mut PhysicsWorld>(|physics| physics.gravity = gravity);
+        app
+    }
+
+    // Spawns a body and runs a frame so it gets created
+    fn spawn_body(app: &mut App, transform: Transform, mass: f32) -> (Entity, RigidBodyHandle) {
+        let entity = app
+            .world
+            .entity()
+            .set(transform)
+            .set(GlobalTransform::default())
+            .set(RigidBodyDefinition {
+                mass: Some(mass),
+                ..Default::default()
+            })
+            .id();
+        frame(app, FIXED_DT);
+        let body = app
+            .world
+            .entity_from_id(entity)
+            .get::<&PhysicsHandle>(|handle| handle.body)
+            .expect("the body was created");
+        (entity, body)
+    }
+
+    #[test]
+    fn a_paused_simulation_only_moves_by_requested_steps() {
+        // A body sliding without gravity
+        let mut app = physics_app(Vec3::ZERO);
+        let (_, handle) = spawn_body(&mut app, Transform::default(), 1.0);
+        let velocity = Vec3::new(2.0, 0.0, 1.0);
+        app.world.get::<&mut PhysicsWorld>(|physics| {
+            physics.bodies[handle].set_linvel(velocity, true);
+        });
+        let position = |app: &App| body_states(app, &[handle])[0].translation;
+
+        app.world
+            .get::<&mut PhysicsTime>(|time| time.set_paused(true));
+        let start = position(&app);
+        let steps: u32 = (0..10).map(|_| frame(&mut app, 0.1)).sum();
+        assert_eq!(steps, 0);
+        assert_eq!(position(&app), start);
+
+        // A single step advances the body by exactly fixed_dt
+        app.world
+            .get::<&mut PhysicsTime>(|time| time.request_step());
+        assert_eq!(frame(&mut app, 0.1), 1);
+        let moved = position(&app) - start;
+        assert!((moved - velocity * FIXED_DT).length() < 1e-5);
+
+        // And then the simulation stays paused
+        assert_eq!(frame(&mut app, 0.1), 0);
+        assert_eq!(position(&app) - start, moved);
+    }
+
+    #[test]
+    fn restoring_a_snapshot_replays_the_same_steps() {
+        // Bodies falling and tumbling
+        let mut app = physics_app(Vec3::new(0.0, -9.81, 0.0));
+        let mut entities = Vec::new();
+        let mut handles = Vec::new();
+        for index in 0..3 {
+            let (entity, body) = spawn_body(
+                &mut app,
+                Transform::from_xyz(index as f32 * 2.0, 5.0, 0.0),
+                1.0 + index as f32,
+            );
+            app.world.get::<&mut PhysicsWorld>(|physics| {
+                physics.bodies[body].set_angvel(Vec3::new(1.0, index as f32, 0.5), true);
+            });
+            entities.push(entity);
+            handles.push(body);
+        }
+        for _ in 0..10 {
+            frame(&mut app, FIXED_DT);
+        }
+
+        let snapshot = app
+            .world
+            .get::<&PhysicsWorld>(|physics| physics.snapshot())
+            .expect("the state serializes");
+        let at_snapshot = body_states(&app, &handles);
+        assert_eq!(at_snapshot.len(), handles.len());
+        for _ in 0..20 {
+            frame(&mut app, FIXED_DT);
+        }
+        let after_twenty = body_states(&app, &handles);
+        assert_ne!(after_twenty, at_snapshot);
+
+        app.world
+            .get::<&mut PhysicsTime>(|time| time.set_paused(true));
+        let world: WorldRef = (&app.world).into();
+        restore_snapshot(&world, &snapshot).expect("the snapshot restores");
+        // Every position, velocity and sleep flag comes back
+        assert_eq!(body_states(&app, &handles), at_snapshot);
+        // And the entities move back right away
+        let translation = app
+            .world
+            .entity_from_id(entities[0])
+            .get::<&Transform>(|transform| transform.translation);
+        assert_eq!(translation, at_snapshot[0].translation);
+
+        // A restored, paused simulation stays put
+        frame(&mut app, FIXED_DT);
+        assert_eq!(body_states(&app, &handles), at_snapshot);
+
+        app.world
+            .get::<&mut PhysicsTime>(|time| time.set_paused(false));
+        for _ in 0..20 {
+            frame(&mut app, FIXED_DT);
+        }
+        assert_eq!(body_states(&app, &handles), after_twenty);
+
+        // Through a file, as physics.snapshot <path> does
+        let path = std::env::temp_dir().join(format!(
+            "catalyst_physics_snapshot_{}.ron",
+            std::process::id()
+        ));
+        let reloaded = snapshot
+            .save(&path)
+            .and_then(|_| PhysicsSnapshot::load(&path));
+        let _ = std::fs::remove_file(&path);
+        let world: WorldRef = (&app.world).into();
+        restore_snapshot(&world, &reloaded.expect("the file round trips"))
+            .expect("the file restores");
+        assert_eq!(body_states(&app, &handles), at_snapshot);
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::HashSet;
+
+use catalyst_core::{
+    importance::{ImportanceBias, ImportanceSettings},
+    pipeline::PhysicsSync,
+    player::PlayerIndex,
+};
+use flecs_ecs::prelude::*;
+use rapier3d::prelude::*;
+
+use crate::{PhysicsWorld, prepare::PhysicsHandle};
+
+// Boosts the bodies a player's body is touching after every step. Only entities with
+// an ImportanceBias and a body of their own are boosted; it decays once they separate.
+pub fn importance_contact_system(app: &catalyst_core::App) {
+    let players = app
+        .world
+        .query::<&PhysicsHandle>()
+        .with(PlayerIndex::id())
+        .build();
+    let important = app
+        .world
+        .query::<(&PhysicsHandle, &mut ImportanceBias)>()
+        .build();
+
+    app.world
+        .system_named::<(&PhysicsWorld, &ImportanceSettings)>("physics_importance_contacts")
+        .kind(PhysicsSync)
+        .each(move |(physics, settings)| {
+            let mut touched: HashSet<RigidBodyHandle> = HashSet::new();
+            players.each(|handle| {
+                let Some(body) = handle.body.and_then(|body| physics.bodies.get(body)) else {
+                    return;
+                };
+                for collider in body.colliders() {
+                    for pair in physics.narrow_phase.contact_pairs_with(*collider) {
+                        if !pair.has_any_active_contact {
+                            continue;
+                        }
+                        let other = if pair.collider1 == *collider {
+                            pair.collider2
+                        } else {
+                            pair.collider1
+                        };
+                        let body = physics
+                            .colliders
+                            .get(other)
+                            .and_then(|other| other.parent());
+                        touched.extend(body);
+                    }
+                }
+            });
+            if touched.is_empty() {
+                return;
+            }
+
+            important.each(|(handle, importance)| {
+                if handle.body.is_some_and(|body| touched.contains(&body)) {
+                    importance.raise(settings.collision_boost);
+                }
+            });
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use catalyst_core::{
+        App,
+        physics::{ColliderDefinition, ColliderShape, PhysicsBody, RigidBodyDefinition},
+        pipeline::PhysicsPipeline,
+        time::{PhysicsTime, Time},
+        transform::{GlobalTransform, Transform},
+    };
+
+    use super::*;
+    use crate::PhysicsPlugin;
+
+    const FIXED_DT: f32 = 1.0 / 50.0;
+
+    // One fixed step, then the frame with the same delta
+    fn frame(app: &mut App) {
+        app.world.run_pipeline_time(PhysicsPipeline, FIXED_DT);
+        app.world
+            .get::<&mut Time>(|time| time.set_delta(Duration::from_secs_f32(FIXED_DT)));
+        app.update();
+    }
+
+    fn boost(app: &App, entity: Entity) -> f32 {
+        app.world
+            .entity_from_id(entity)
+            .get::<&ImportanceBias>(|importance| importance.boost)
+    }
+
+    fn spawn_box(app: &App, x: f32) -> Entity {
+        let transform = Transform::from_xyz(x, 0.0, 0.0);
+        let body = app
+            .world
+            .entity()
+            .set(transform)
+            .set(GlobalTransform(transform.compute_matrix()))
+            .set(RigidBodyDefinition {
+                body_type: PhysicsBody::Static,
+                ..Default::default()
+            })
+            .set(ImportanceBias::default());
+        app.world
+            .entity()
+            .child_of(body)
+            .set(Transform::default())
+            .set(ColliderDefinition {
+                shape: ColliderShape::Box {
+                    hx: 2.0,
+                    hy: 0.5,
+                    hz: 2.0,
+                },
+                ..Default::default()
+            });
+        body.id()
+    }
+
+    #[test]
+    fn a_players_contact_boosts_a_body_until_it_leaves() {
+        let mut app = App::new();
+        app.add_plugin(PhysicsPlugin::default());
+        app.world
+            .get::<&mut PhysicsTime>(|time| time.fixed_dt = FIXED_DT);
+        let settings = app.world.get::<&ImportanceSettings>(|settings| *settings);
+
+        // A player's body falling on a box, another box out of reach
+        let touched = spawn_box(&app, 0.0);
+        let untouched = spawn_box(&app, 20.0);
+        // Global transforms set up front: bodies start from them, before propagation runs
+        let transform = Transform::from_xyz(0.0, 1.5, 0.0);
+        let player = app
+            .world
+            .entity()
+            .set(PlayerIndex(0))
+            .set(transform)
+            .set(GlobalTransform(transform.compute_matrix()))
+            .set(RigidBodyDefinition::default())
+            .id();
+        app.world
+            .entity()
+            .child_of(player)
+            .set(Transform::default())
+            .set(ColliderDefinition {
+                shape: ColliderShape::Sphere { radius: 0.5 },
+                ..Default::default()
+            });
+
+        for _ in 0..50 {
+            frame(&mut app);
+        }
+        let contact_boost = boost(&app, touched);
+        assert!(
+            contact_boost > settings.collision_boost - 1.0
+                && contact_boost <= settings.collision_boost,
+            "boost {}",
+            contact_boost
+        );
+        assert_eq!(boost(&app, untouched), 0.0);
+
+        // Without a player nothing raises it anymore, and it decays at the configured rate
+        app.world.entity_from_id(player).remove(PlayerIndex::id());
+        let frames = 10;
+        for _ in 0..frames {
+            frame(&mut app);
+        }
+        let expected = contact_boost - settings.decay * FIXED_DT * frames as f32;
+        assert!((boost(&app, touched) - expected).abs() < 1e-3);
+
+        // Down to zero and no further
+        let seconds = settings.collision_boost / settings.decay;
+        for _ in 0..(seconds / FIXED_DT) as u32 + 1 {
+            frame(&mut app);
+        }
+        assert_eq!(boost(&app, touched), 0.0);
+    }
+}
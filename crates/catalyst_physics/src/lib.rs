@@ -1,25 +1,76 @@
-use catalyst_core::Plugin;
+use catalyst_core::{
+    Plugin,
+    console::ConsoleCommands,
+    physics::CollisionLayers,
+    raycast::{RaycastBackend, Raycasters},
+};
 use flecs_ecs::prelude::*;
 use rapier3d::prelude::*;
 
-use crate::{prepare::prepare_physics_system, step::step_physics_system, sync::sync_physics_system};
+use crate::{
+    activity::activity_sleep_system,
+    debugger::{PhysicsDebugger, register_debugger_commands},
+    importance::importance_contact_system,
+    prepare::{prepare_physics_system, remove_physics_bodies},
+    raycast::physics_raycaster,
+    step::step_physics_system,
+    sync::sync_physics_system,
+};
 
+pub mod activity;
+pub mod debugger;
+mod importance;
 pub mod prepare;
+pub mod raycast;
 mod step;
 mod sync;
 
-pub struct PhysicsPlugin;
+/// Physics simulation (Rapier).
+///
+/// Collision layers are configured here, before the plugin is added:
+/// `DefaultPlugins.set(PhysicsPlugin::default().with_layers(layers))`.
+#[derive(Default)]
+pub struct PhysicsPlugin {
+    layers: CollisionLayers,
+}
+
+impl PhysicsPlugin {
+    pub fn with_layers(mut self, layers: CollisionLayers) -> Self {
+        self.layers = layers;
+        self
+    }
+}
 
 impl Plugin for PhysicsPlugin {
     fn build(&self, app: &mut catalyst_core::App) {
         app.register_singleton_default::<PhysicsWorld>();
+        app.register_singleton(self.layers.clone());
+        app.register_singleton_default::<PhysicsDebugger>();
+        app.world.get::<&mut ConsoleCommands>(|commands| {
+            register_physics_commands(commands);
+            register_debugger_commands(commands);
+        });
+        app.world.get::<&mut Raycasters>(|raycasters| {
+            raycasters.register(RaycastBackend::Physics, physics_raycaster);
+        });
 
         prepare_physics_system(&app);
+        remove_physics_bodies(&app);
+        activity_sleep_system(&app);
         step_physics_system(&app);
         sync_physics_system(&app);
+        importance_contact_system(&app);
     }
 }
 
+fn register_physics_commands(commands: &mut ConsoleCommands) {
+    commands.register(
+        "physics.layers",
+        "Print the collision layer interaction matrix",
+        |world, _| Ok(world.get::<&CollisionLayers>(|layers| layers.format_matrix())),
+    );
+}
+
 #[derive(Component)]
 pub struct PhysicsBodyAdded;
 
@@ -1,14 +1,18 @@
 use catalyst_core::{
-    physics::{ColliderDefinition, PhysicsBody, PhysicsMaterialDefinition, RigidBodyDefinition},
+    physics::{
+        ColliderDefinition, ColliderShape, ColliderShapeChanged, CollisionLayers, PhysicsBody,
+        PhysicsMaterialDefinition, RigidBodyDefinition,
+    },
     pipeline::PhysicsPrepare,
-    transform::{GlobalTransform, Transform},
+    replication::Replicated,
+    transform::{GlobalTransform, StaticTransform, Transform},
 };
 use flecs_ecs::prelude::*;
 use glam::Mat4;
 use nalgebra::{Isometry, Translation};
-use rapier3d::prelude::*;
+use rapier3d::{parry::utils::Array2, prelude::*};
 
-use crate::{PhysicsBodyAdded, PhysicsColliderAdded, PhysicsWorld};
+use crate::{PhysicsBodyAdded, PhysicsColliderAdded, PhysicsWorld, activity::ForcedSleep};
 
 #[derive(Component, Debug, Clone, Copy)]
 pub struct PhysicsHandle {
@@ -49,17 +53,27 @@ pub fn prepare_physics_system(app: &catalyst_core::App) {
                     b.set_angular_damping(rb_def.angular_damping);
                     b.set_gravity_scale(rb_def.gravity_scale, true);
 
-                    let iso = mat_to_iso(&transform.0);
-                    b.set_position(iso, true);
+                    // Becoming replicated (or no longer) switches the body's type
+                    let replicated = entity.has(Replicated::id());
+                    let rb_type = body_type(rb_def.body_type, replicated);
+                    if b.body_type() != rb_type {
+                        b.set_body_type(rb_type, true);
+                    }
+
+                    if replicated {
+                        // Moved to the pose over the step, so what it hits is pushed
+                        // with the authority's velocity
+                        b.set_next_kinematic_position(mat_to_iso(&transform.0));
+                    } else if !entity.has(ForcedSleep) && !entity.has(StaticTransform) {
+                        // Setting the pose wakes the body, and it hasn't moved while
+                        // asleep. A static entity keeps the pose its body was created with
+                        let iso = mat_to_iso(&transform.0);
+                        b.set_position(iso, true);
+                    }
                 }
             } else {
                 // Create new Rapier body
-                let rb_type = match rb_def.body_type {
-                    PhysicsBody::Dynamic => RigidBodyType::Dynamic,
-                    PhysicsBody::Static => RigidBodyType::Fixed,
-                    PhysicsBody::Kinematic => RigidBodyType::KinematicPositionBased,
-                    PhysicsBody::Unknown => RigidBodyType::Dynamic,
-                };
+                let rb_type = body_type(rb_def.body_type, entity.has(Replicated::id()));
 
                 let mut body = RigidBodyBuilder::new(rb_type)
                     .pose(Pose::from_mat4(transform.0))
@@ -92,6 +106,7 @@ pub fn prepare_physics_system(app: &catalyst_core::App) {
             &PhysicsHandle,
             Option<&GlobalTransform>,
             &mut PhysicsWorld,
+            &CollisionLayers,
         )>("prepare_physic_coliders")
         .kind(PhysicsPrepare)
         .term_at(4)
@@ -108,20 +123,30 @@ pub fn prepare_physics_system(app: &catalyst_core::App) {
                 parent_handle,
                 parent_transform,
                 physics,
+                layers,
             )| {
+                let global_scale = parent_transform
+                    .map(|s| s.to_scale_rotation_translation().0)
+                    .unwrap_or(Vec3::from_array([1f32, 1f32, 1f32]));
+
                 if let Some(handle) = collider_handle {
                     let collider = handle.collider.and_then(|c| physics.colliders.get_mut(c));
                     if let Some(c) = collider {
+                        if entity.has(ColliderShapeChanged) {
+                            c.set_shape(collider_shape(&col_def.shape, global_scale));
+                            entity.remove(ColliderShapeChanged);
+                        }
+
                         // Update material
                         if let Some(mat) = mat_def {
                             c.set_friction(mat.friction);
                             c.set_restitution(mat.restitution);
                         }
 
-                        // Update collision groups
+                        // Update collision groups (already reported when the collider was created)
                         let groups = InteractionGroups::new(
-                            Group::from_bits(col_def.layer).unwrap(),
-                            Group::from_bits(col_def.mask).unwrap(),
+                            Group::from_bits_truncate(col_def.layer),
+                            Group::from_bits_truncate(col_def.mask),
                             InteractionTestMode::default(),
                         );
                         c.set_collision_groups(groups);
@@ -131,38 +156,17 @@ pub fn prepare_physics_system(app: &catalyst_core::App) {
                         c.set_position_wrt_parent(iso);
                     }
                 } else {
-                    let global_scale = parent_transform
-                        .map(|s| s.to_scale_rotation_translation().0)
-                        .unwrap_or(Vec3::from_array([1f32, 1f32, 1f32]));
-
-                    // Build collider shape
-                    let builder = match &col_def.shape {
-                        catalyst_core::physics::ColliderShape::Box { hx, hy, hz } => {
-                            ColliderBuilder::cuboid(
-                                *hx * global_scale.x,
-                                *hy * global_scale.y,
-                                *hz * global_scale.z,
-                            )
-                        }
-                        catalyst_core::physics::ColliderShape::Sphere { radius } => {
-                            ColliderBuilder::ball(*radius)
-                        }
-                        catalyst_core::physics::ColliderShape::Capsule { radius, height } => {
-                            ColliderBuilder::capsule_y(*height * 0.5, *radius)
-                        }
-                        _ => todo!("Convex and Mesh shapes not supported"), // catalyst_core::physics::ColliderShape::Convex { vertices } => ColliderBuilder::convex_hull( &vertices.iter().map(|v| v.into()).collect::<Vec<_>>() ).unwrap(),
-                                                                            // catalyst_core::physics::ColliderShape::Mesh { vertices, indices } => ColliderBuilder::trimesh( vertices.iter().map(|v| v.into()).collect(), indices.chunks(3).map(|c| [c[0], c[1], c[2]]).collect(), ),
-                    };
+                    let builder =
+                        ColliderBuilder::new(collider_shape(&col_def.shape, global_scale));
+                    entity.remove(ColliderShapeChanged);
 
                     let iso = mat_to_iso(&local_transform.compute_matrix());
                     let mut collider = builder
-                        .collision_groups(InteractionGroups::new(
-                            Group::from_bits(col_def.layer).unwrap(),
-                            Group::from_bits(col_def.mask).unwrap(),
-                            InteractionTestMode::default(),
-                        ))
+                        .collision_groups(collision_groups(entity, col_def, layers))
                         .sensor(col_def.is_trigger)
                         .position(iso)
+                        // Lets raycasts answer with the entity, see raycast::collider_entity
+                        .user_data(*entity.id() as u128)
                         .build();
 
                     if let Some(mat) = mat_def {
@@ -187,7 +191,213 @@ pub fn prepare_physics_system(app: &catalyst_core::App) {
         );
 }
 
+// Replicated bodies follow their authority, only fixed ones stay fixed
+fn body_type(body: PhysicsBody, replicated: bool) -> RigidBodyType {
+    match body {
+        PhysicsBody::Static => RigidBodyType::Fixed,
+        _ if replicated => RigidBodyType::KinematicPositionBased,
+        PhysicsBody::Dynamic => RigidBodyType::Dynamic,
+        PhysicsBody::Kinematic => RigidBodyType::KinematicPositionBased,
+        PhysicsBody::Unknown => RigidBodyType::Dynamic,
+    }
+}
+
+/// Takes the Rapier body or collider of a despawned entity out of the PhysicsWorld.
+/// Runs per entity as it goes, so a level unloaded over several frames (see
+/// catalyst_core::bulk) keeps the simulation in step with what is left of it.
+pub fn remove_physics_bodies(app: &catalyst_core::App) {
+    app.world
+        .observer::<flecs::OnRemove, &PhysicsHandle>()
+        .each_entity(|entity, handle| {
+            let handle = *handle;
+            // Gone already while the world shuts down
+            entity.world().try_get::<&mut PhysicsWorld>(|physics| {
+                let PhysicsWorld {
+                    islands,
+                    bodies,
+                    colliders,
+                    impulse_joints,
+                    multibody_joints,
+                    ..
+                } = physics;
+
+                // A collider entity shares its parent's body, a body entity has no collider
+                // of its own. A collider whose body went first went with it.
+                let own_collider = handle
+                    .collider
+                    .filter(|collider| colliders.get(*collider).is_some());
+                match own_collider {
+                    Some(collider) => {
+                        colliders.remove(collider, islands, bodies, true);
+                    }
+                    None => {
+                        if let Some(body) = handle.body {
+                            bodies.remove(
+                                body,
+                                islands,
+                                colliders,
+                                impulse_joints,
+                                multibody_joints,
+                                true,
+                            );
+                        }
+                    }
+                }
+            });
+        });
+}
+
+fn collider_shape(shape: &ColliderShape, global_scale: Vec3) -> SharedShape {
+    match shape {
+        ColliderShape::Box { hx, hy, hz } => SharedShape::cuboid(
+            *hx * global_scale.x,
+            *hy * global_scale.y,
+            *hz * global_scale.z,
+        ),
+        ColliderShape::Sphere { radius } => SharedShape::ball(*radius),
+        ColliderShape::Capsule { radius, height } => SharedShape::capsule_y(*height * 0.5, *radius),
+        ColliderShape::Heightfield {
+            rows,
+            columns,
+            heights,
+            size,
+        } => {
+            let (rows, columns) = (*rows as usize, *columns as usize);
+            // Parry stores the grid column-major
+            let mut data = Vec::with_capacity(rows * columns);
+            for column in 0..columns {
+                for row in 0..rows {
+                    data.push(heights[row * columns + column]);
+                }
+            }
+            SharedShape::heightfield(
+                Array2::new(rows, columns, data),
+                Vec3::new(
+                    size.x * global_scale.x,
+                    global_scale.y,
+                    size.y * global_scale.z,
+                ),
+            )
+        }
+        _ => todo!("Convex and Mesh shapes not supported"), // catalyst_core::physics::ColliderShape::Convex { vertices } => ColliderBuilder::convex_hull( &vertices.iter().map(|v| v.into()).collect::<Vec<_>>() ).unwrap(),
+                                                            // catalyst_core::physics::ColliderShape::Mesh { vertices, indices } => ColliderBuilder::trimesh( vertices.iter().map(|v| v.into()).collect(), indices.chunks(3).map(|c| [c[0], c[1], c[2]]).collect(), ),
+    }
+}
+
+// Invalid layer values are reported and their unknown bits dropped, instead of panicking
+fn collision_groups(
+    entity: EntityView,
+    col_def: &ColliderDefinition,
+    layers: &CollisionLayers,
+) -> InteractionGroups {
+    let layer = col_def.layer;
+    let valid = layers.validate(layer).map_err(|e| e.to_string()).and_then(|_| {
+        Group::from_bits(layer).ok_or_else(|| "not a valid collision group".to_string())
+    });
+    if let Err(error) = valid {
+        log::warn!(
+            "Collider on entity '{}' ({:?}) has an invalid collision layer {:#010x}: {}",
+            entity.name(),
+            entity.id(),
+            layer,
+            error
+        );
+    }
+    let memberships = Group::from_bits_truncate(layer);
+
+    // Masks may name layers that are never registered (e.g. u32::MAX), only check the group
+    let filter = Group::from_bits(col_def.mask).unwrap_or_else(|| {
+        log::warn!(
+            "Collider on entity '{}' ({:?}) has an invalid collision mask {:#010x}",
+            entity.name(),
+            entity.id(),
+            col_def.mask
+        );
+        Group::from_bits_truncate(col_def.mask)
+    });
+
+    InteractionGroups::new(memberships, filter, InteractionTestMode::default())
+}
+
 fn mat_to_iso(gt: &Mat4) -> Pose3 {
     let (_, rotation, translation) = gt.to_scale_rotation_translation();
     Isometry::from_parts(Translation::from(translation), rotation.into()).into()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use catalyst_core::{
+        App, pipeline::PhysicsPipeline, replication::apply_remote_state, time::Time,
+    };
+    use glam::{Quat, Vec3};
+
+    use super::*;
+    use crate::PhysicsPlugin;
+
+    const FIXED_DT: f32 = 1.0 / 50.0;
+
+    // One fixed step, then the frame with the same delta
+    fn physics_frame(app: &mut App) {
+        app.world.run_pipeline_time(PhysicsPipeline, FIXED_DT);
+        app.world
+            .get::<&mut Time>(|time| time.set_delta(Duration::from_secs_f32(FIXED_DT)));
+        app.update();
+    }
+
+    fn rapier_body_type(app: &App, entity: Entity) -> Option<RigidBodyType> {
+        let handle = app
+            .world
+            .entity_from_id(entity)
+            .try_get::<&PhysicsHandle>(|handle| handle.body)??;
+        app.world
+            .get::<&PhysicsWorld>(|physics| physics.bodies.get(handle).map(|body| body.body_type()))
+    }
+
+    #[test]
+    fn a_replicated_dynamic_body_is_kinematic_until_it_no_longer_is() {
+        let mut app = App::new();
+        app.add_plugin(PhysicsPlugin::default());
+        let body = app
+            .world
+            .entity()
+            .set(Transform::from_xyz(0.0, 5.0, 0.0))
+            .set(GlobalTransform::default())
+            .set(RigidBodyDefinition {
+                body_type: PhysicsBody::Dynamic,
+                ..Default::default()
+            })
+            .id();
+        let height = |app: &App| {
+            app.world
+                .entity_from_id(body)
+                .get::<&Transform>(|transform| transform.translation.y)
+        };
+
+        // Physics leaves it where its authority puts it, ignoring gravity
+        for step in 0..30 {
+            apply_remote_state(
+                app.world.entity_from_id(body),
+                Vec3::new(0.0, 5.0, 0.0),
+                Quat::IDENTITY,
+                Vec3::ZERO,
+                step as f64 * FIXED_DT as f64,
+            );
+            physics_frame(&mut app);
+        }
+        assert_eq!(
+            rapier_body_type(&app, body),
+            Some(RigidBodyType::KinematicPositionBased)
+        );
+        assert!((height(&app) - 5.0).abs() < 1e-4, "{}", height(&app));
+
+        // Once no longer replicated it is dynamic again and falls
+        app.world.entity_from_id(body).remove(Replicated::id());
+        for _ in 0..30 {
+            physics_frame(&mut app);
+        }
+        assert_eq!(rapier_body_type(&app, body), Some(RigidBodyType::Dynamic));
+        assert!(height(&app) < 4.5, "{}", height(&app));
+    }
+}
@@ -0,0 +1,54 @@
+use catalyst_core::{
+    bounds::Ray,
+    raycast::{RayHit, RaycastFilter},
+};
+use flecs_ecs::prelude::*;
+use rapier3d::prelude::{Collider, ColliderHandle, QueryFilter, Ray as ColliderRay};
+
+use crate::PhysicsWorld;
+
+impl PhysicsWorld {
+    /// The nearest collider `ray` hits, sensors left out. A ray starting inside a
+    /// collider hits it at distance 0.
+    pub fn cast_ray(&self, ray: &Ray, filter: &RaycastFilter) -> Option<RayHit> {
+        let allowed = |_: ColliderHandle, collider: &Collider| {
+            !filter.exclude.contains(&collider_entity(collider))
+        };
+        let query = self.broad_phase.as_query_pipeline(
+            self.narrow_phase.query_dispatcher(),
+            &self.bodies,
+            &self.colliders,
+            QueryFilter::default().exclude_sensors().predicate(&allowed),
+        );
+        let (collider, intersection) = query.cast_ray_and_get_normal(
+            &ColliderRay::new(ray.origin, ray.direction),
+            filter.max_distance.min(f32::MAX),
+            true,
+        )?;
+
+        Some(RayHit {
+            entity: collider_entity(self.colliders.get(collider)?),
+            distance: intersection.time_of_impact,
+            position: ray.at(intersection.time_of_impact),
+            normal: intersection.normal,
+            triangle: None,
+            uv: None,
+            tangent: None,
+        })
+    }
+}
+
+/// The entity a collider was created for, see "prepare_physic_coliders".
+pub fn collider_entity(collider: &Collider) -> Entity {
+    Entity::new(collider.user_data as u64)
+}
+
+pub(crate) fn physics_raycaster(
+    world: &WorldRef,
+    ray: &Ray,
+    filter: &RaycastFilter,
+) -> Option<RayHit> {
+    world
+        .try_get::<&PhysicsWorld>(|physics| physics.cast_ray(ray, filter))
+        .flatten()
+}
@@ -1,4 +1,4 @@
-use catalyst_core::pipeline::PhysicsStep;
+use catalyst_core::{pipeline::PhysicsStep, time::PhysicsTime};
 use flecs_ecs::prelude::*;
 
 use crate::PhysicsWorld;
@@ -8,9 +8,13 @@ pub fn step_physics_system(app: &catalyst_core::App) {
         .system_named::<&mut PhysicsWorld>("physics_evaluation")
         .kind(PhysicsStep)
         .run(|mut iter| {
+            // One step simulates exactly one fixed_dt, whatever it was set to
+            let fixed_dt = iter.world().get::<&PhysicsTime>(|time| time.fixed_dt);
             while iter.next() {
                 let mut physics_comp = iter.field_mut::<PhysicsWorld>(0);
                 if let Some(physics) = physics_comp.get_mut(0) {
+                    catalyst_core::profile_scope!("Physics Evaluation");
+                    physics.integration_parameters.dt = fixed_dt;
                     physics.step();
                 }
             }
@@ -1,4 +1,4 @@
-use catalyst_core::{pipeline::PhysicsSync, transform::Transform};
+use catalyst_core::{pipeline::PhysicsSync, replication::Replicated, transform::Transform};
 use flecs_ecs::prelude::*;
 use rapier3d::prelude::*;
 
@@ -9,6 +9,8 @@ pub fn sync_physics_system(app: &catalyst_core::App) {
         .system_named::<(&mut Transform, &PhysicsHandle, &PhysicsWorld)>("physics_synchronization")
         .kind(PhysicsSync)
         .with(PhysicsBodyAdded)
+        // Their authority moves them, the body only follows
+        .without(Replicated::id())
         .each(|(transform, handle, physics)| {
             if let Some(body_handle) = handle.body {
                 if let Some(body) = physics.bodies.get(body_handle) {
@@ -0,0 +1,75 @@
+//! Times frustum culling of 100k meshes, serial against parallel.
+//!
+//! Run with: cargo bench -p catalyst_renderer --bench frustum_culling
+//!
+//! Fills a CullSnapshot with 100k meshes spread over a few materials, buckets and
+//! meshes, then culls them against two views on this thread and on the rayon pool. The
+//! frustum culling tests check that both give the same draw order.
+
+use catalyst_core::bounds::Aabb;
+use catalyst_renderer::{
+    RenderBucket,
+    frustum_culling::{CullSnapshot, DrawKey},
+    visibility::Frustum,
+};
+use criterion::{Criterion, criterion_group, criterion_main};
+use flecs_ecs::prelude::*;
+use glam::{Mat4, Vec3};
+
+const ENTITIES: u64 = 100_000;
+// Out of the way of real entity ids
+const FIRST_ID: u64 = 1 << 32;
+
+fn frustum_culling(c: &mut Criterion) {
+    let mut snapshot = fill();
+    // Two split-screen views looking down at the grid from opposite corners
+    let projection = Mat4::perspective_rh(60f32.to_radians(), 16.0 / 9.0, 0.1, 1000.0);
+    let frusta = [
+        Vec3::new(-200.0, 80.0, -200.0),
+        Vec3::new(200.0, 80.0, 200.0),
+    ]
+    .map(|eye| Frustum::from_view_proj(projection * Mat4::look_at_rh(eye, Vec3::ZERO, Vec3::Y)));
+    println!(
+        "{} meshes, {} threads",
+        ENTITIES,
+        rayon::current_num_threads()
+    );
+
+    let mut group = c.benchmark_group("frustum_culling");
+    for (name, parallel) in [("serial", false), ("parallel", true)] {
+        group.bench_function(name, |b| b.iter(|| snapshot.cull(&frusta, parallel)));
+    }
+    group.finish();
+}
+
+// A 316 x 316 grid of unit cubes around the origin
+fn fill() -> CullSnapshot {
+    let side = (ENTITIES as f64).sqrt().ceil() as u64;
+    let bounds = Aabb::new(Vec3::splat(-0.5), Vec3::splat(0.5));
+    let mut snapshot = CullSnapshot::default();
+    snapshot.begin();
+    for index in 0..ENTITIES {
+        let (row, column) = (index / side, index % side);
+        let model = Mat4::from_translation(Vec3::new(
+            column as f32 * 2.0 - side as f32,
+            0.0,
+            row as f32 * 2.0 - side as f32,
+        ));
+        let bucket = match index % 10 {
+            0 => RenderBucket::Transparent,
+            1 => RenderBucket::Overlay,
+            _ => RenderBucket::Opaque,
+        };
+        let key = DrawKey {
+            bucket,
+            material: Entity::new(FIRST_ID + ENTITIES + index % 7),
+            mesh: Entity::new(FIRST_ID + ENTITIES + 100 + index % 13),
+        };
+        snapshot.update(Entity::new(FIRST_ID + index), key, &model, &bounds);
+    }
+    snapshot.end();
+    snapshot
+}
+
+criterion_group!(benches, frustum_culling);
+criterion_main!(benches);
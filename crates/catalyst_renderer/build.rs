@@ -0,0 +1,509 @@
+//! Reflects the engine's WGSL into Rust at build time.
+//!
+//! Every program below is composed like `ComposedShader::compose` does at runtime,
+//! parsed and validated with naga, and written to `$OUT_DIR/reflected_shaders.rs`:
+//!
+//! - a `WGSL_<STRUCT>` StructLayout for every struct a uniform or storage buffer uses,
+//! - a module per program with a constant per binding index and a `GROUP_<N>` array of
+//!   layout entries per bind group, with the stages that use each binding.
+//!
+//! The build fails when a shader doesn't compile, or two shaders declare the same
+//! struct differently.
+
+use std::{collections::HashSet, fmt::Write as _, path::Path};
+
+use naga::{
+    AddressSpace, ArraySize, ImageClass, ImageDimension, Module, ScalarKind, ShaderStage,
+    StorageAccess, TypeInner, proc::Layouter, valid::ModuleInfo,
+};
+
+const SHADER_DIR: &str = "src/programs";
+
+struct Program {
+    /// Name of the generated module.
+    name: &'static str,
+    /// Composed in order, as by `create_shader_module`.
+    files: &'static [&'static str],
+    /// Entry points whose bindings are reflected; empty for all of them. Passes that
+    /// share a file but bind different things at the same slot are separate programs.
+    entry_points: &'static [&'static str],
+    /// Float textures read with textureLoad only, e.g. 32 bit float targets.
+    unfilterable: &'static [&'static str],
+    /// Uniform buffers bound with a dynamic offset.
+    dynamic_offset: &'static [&'static str],
+}
+
+impl Program {
+    const fn new(name: &'static str, files: &'static [&'static str]) -> Self {
+        Self {
+            name,
+            files,
+            entry_points: &[],
+            unfilterable: &[],
+            dynamic_offset: &[],
+        }
+    }
+
+    const fn entry_points(mut self, entry_points: &'static [&'static str]) -> Self {
+        self.entry_points = entry_points;
+        self
+    }
+
+    const fn unfilterable(mut self, textures: &'static [&'static str]) -> Self {
+        self.unfilterable = textures;
+        self
+    }
+
+    const fn dynamic_offset(mut self, buffers: &'static [&'static str]) -> Self {
+        self.dynamic_offset = buffers;
+        self
+    }
+}
+
+const PROGRAMS: &[Program] = &[
+    Program::new("pbr", &["shader.wgsl", "lights_clustered.wgsl"]),
+    Program::new(
+        "debug_view",
+        &["debug_view.wgsl", "debug_view_clusters.wgsl"],
+    )
+    .entry_points(&[
+        "vs_main",
+        "fs_count",
+        "fs_normal",
+        "fs_roughness",
+        "fs_metallic",
+        "fs_cluster_lights",
+    ]),
+    Program::new("debug_view_composite", &["debug_view.wgsl"])
+        .entry_points(&["vs_fullscreen", "fs_composite"])
+        .unfilterable(&["t_debug"]),
+    Program::new("decals", &["decals.wgsl"]).dynamic_offset(&["view"]),
+    Program::new("gpu_particles", &["gpu_particles.wgsl"]),
+    Program::new("gpu_particles_simulate", &["gpu_particles_simulate.wgsl"])
+        .entry_points(&["cs_simulate", "cs_spawn"]),
+    Program::new("gpu_particles_finish", &["gpu_particles_simulate.wgsl"])
+        .entry_points(&["cs_finish"]),
+    Program::new("lines", &["lines.wgsl"]),
+    Program::new("occlusion_proxy", &["occlusion_proxy.wgsl"]),
+    Program::new("outline_mask", &["outline.wgsl"]).entry_points(&["vs_mask", "fs_mask"]),
+    Program::new("outline_composite", &["outline.wgsl"])
+        .entry_points(&["vs_fullscreen", "fs_composite"]),
+    Program::new("particles", &["particles.wgsl"]),
+    Program::new("picking", &["picking.wgsl"]),
+    Program::new("reflection", &["reflection.wgsl"]).dynamic_offset(&["surface"]),
+    Program::new("color_grading", &["color_grading.wgsl"]),
+    Program::new("depth_of_field", &["depth_of_field.wgsl"]),
+    Program::new("dof_focus", &["dof_focus.wgsl"]),
+    Program::new("luminance", &["luminance.wgsl"]).unfilterable(&["t_luminance"]),
+    Program::new("material_preview", &["material_preview.wgsl"]),
+    Program::new("probe_prefilter", &["probe_prefilter.wgsl"]).dynamic_offset(&["params"]),
+    Program::new("taa", &["taa.wgsl"]),
+    Program::new("upscale", &["upscale.wgsl"]),
+];
+
+// A struct as laid out in WGSL: name, size, then (name, offset, size) per member
+#[derive(PartialEq)]
+struct ReflectedStruct {
+    name: String,
+    size: u32,
+    fields: Vec<(String, u32, u32)>,
+}
+
+struct Binding {
+    name: String,
+    group: u32,
+    binding: u32,
+    stages: Vec<&'static str>,
+    ty: String,
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed={}", SHADER_DIR);
+
+    // First declaration of each struct, with the program it came from
+    let mut structs: Vec<(ReflectedStruct, &str)> = Vec::new();
+    let mut modules = String::new();
+
+    for program in PROGRAMS {
+        let source = compose(program.files);
+        let module = naga::front::wgsl::parse_str(&source).unwrap_or_else(|error| {
+            panic!(
+                "{}: {}",
+                program.files.join(" + "),
+                error.emit_to_string(&source)
+            )
+        });
+        let info = naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        )
+        .validate(&module)
+        .unwrap_or_else(|error| {
+            panic!(
+                "{}: {}",
+                program.files.join(" + "),
+                error.emit_to_string(&source)
+            )
+        });
+        let mut layouter = Layouter::default();
+        layouter
+            .update(module.to_ctx())
+            .unwrap_or_else(|error| panic!("{}: {}", program.name, error));
+
+        for reflected in buffer_structs(&module, &layouter) {
+            match structs
+                .iter()
+                .find(|(known, _)| known.name == reflected.name)
+            {
+                Some((known, _)) if *known == reflected => {}
+                Some((_, first)) => panic!(
+                    "struct {} is declared differently in the {} and {} shaders, give one of \
+                     them another name or make them match",
+                    reflected.name, first, program.name
+                ),
+                None => structs.push((reflected, program.name)),
+            }
+        }
+
+        let bindings = bindings(program, &module, &info, &layouter);
+        write_program(&mut modules, program, &bindings);
+    }
+
+    let mut out = String::from("// Generated by build.rs from the WGSL in src/programs.\n");
+    for (reflected, _) in &structs {
+        write_struct(&mut out, reflected);
+    }
+    out.push_str(&modules);
+
+    let path = Path::new(&std::env::var("OUT_DIR").unwrap()).join("reflected_shaders.rs");
+    std::fs::write(&path, out).expect("write reflected_shaders.rs");
+}
+
+// Same expansion as ComposedShader::compose: each file once, in include order
+fn compose(entries: &[&str]) -> String {
+    fn expand(path: &str, source: &mut String, seen: &mut Vec<String>, stack: &mut Vec<String>) {
+        if seen.iter().any(|file| file == path) {
+            return;
+        }
+        let text = std::fs::read_to_string(Path::new(SHADER_DIR).join(path))
+            .unwrap_or_else(|error| panic!("can't read shader {}: {}", path, error));
+        seen.push(path.to_string());
+        stack.push(path.to_string());
+
+        for line in text.lines() {
+            let Some(include) = line.trim().strip_prefix("#include") else {
+                source.push_str(line);
+                source.push('\n');
+                continue;
+            };
+            let include = include
+                .trim()
+                .strip_prefix('"')
+                .and_then(|rest| rest.split_once('"'))
+                .map(|(include, _)| include)
+                .unwrap_or_else(|| panic!("{}: expected #include \"path\"", path));
+            if stack.iter().any(|file| file == include) {
+                panic!("{}: include cycle through {}", path, include);
+            }
+            expand(include, source, seen, stack);
+        }
+        stack.pop();
+    }
+
+    let mut source = String::new();
+    let (mut seen, mut stack) = (Vec::new(), Vec::new());
+    for entry in entries {
+        expand(entry, &mut source, &mut seen, &mut stack);
+    }
+    source
+}
+
+// Every named struct inside a uniform or storage buffer, nested ones included
+fn buffer_structs(module: &Module, layouter: &Layouter) -> Vec<ReflectedStruct> {
+    fn visit(
+        module: &Module,
+        layouter: &Layouter,
+        ty: naga::Handle<naga::Type>,
+        found: &mut Vec<ReflectedStruct>,
+    ) {
+        let ty_info = &module.types[ty];
+        match &ty_info.inner {
+            TypeInner::Array { base, .. } => visit(module, layouter, *base, found),
+            TypeInner::Struct { members, span } => {
+                let Some(name) = &ty_info.name else {
+                    return;
+                };
+                if found.iter().any(|known| &known.name == name) {
+                    return;
+                }
+                found.push(ReflectedStruct {
+                    name: name.clone(),
+                    size: *span,
+                    fields: members
+                        .iter()
+                        .map(|member| {
+                            (
+                                member.name.clone().unwrap_or_default(),
+                                member.offset,
+                                layouter[member.ty].size,
+                            )
+                        })
+                        .collect(),
+                });
+                for member in members {
+                    visit(module, layouter, member.ty, found);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut found = Vec::new();
+    for (_, global) in module.global_variables.iter() {
+        if matches!(
+            global.space,
+            AddressSpace::Uniform | AddressSpace::Storage { .. }
+        ) {
+            visit(module, layouter, global.ty, &mut found);
+        }
+    }
+    found
+}
+
+fn bindings(
+    program: &Program,
+    module: &Module,
+    info: &ModuleInfo,
+    layouter: &Layouter,
+) -> Vec<Binding> {
+    for entry_point in program.entry_points {
+        if !module.entry_points.iter().any(|e| e.name == *entry_point) {
+            panic!("{}: no entry point {}", program.name, entry_point);
+        }
+    }
+
+    let mut bindings = Vec::new();
+    for (handle, global) in module.global_variables.iter() {
+        let Some(resource) = &global.binding else {
+            continue;
+        };
+        let name = global.name.clone().unwrap_or_default();
+
+        let mut stages = Vec::new();
+        for (index, entry_point) in module.entry_points.iter().enumerate() {
+            let selected = program.entry_points.is_empty()
+                || program.entry_points.contains(&entry_point.name.as_str());
+            if !selected || info.get_entry_point(index)[handle].is_empty() {
+                continue;
+            }
+            let stage = stage_name(program, entry_point.stage);
+            if !stages.contains(&stage) {
+                stages.push(stage);
+            }
+        }
+        // Declared but unused by these entry points, like wgpu's automatic layouts
+        if stages.is_empty() {
+            continue;
+        }
+
+        bindings.push(Binding {
+            ty: binding_type(program, module, layouter, global, &name),
+            name,
+            group: resource.group,
+            binding: resource.binding,
+            stages,
+        });
+    }
+
+    bindings.sort_by_key(|b| (b.group, b.binding));
+    for pair in bindings.windows(2) {
+        if (pair[0].group, pair[0].binding) == (pair[1].group, pair[1].binding) {
+            panic!(
+                "{}: {} and {} are both at group {} binding {}, reflect their entry points as \
+                 separate programs",
+                program.name, pair[0].name, pair[1].name, pair[0].group, pair[0].binding
+            );
+        }
+    }
+    bindings
+}
+
+fn stage_name(program: &Program, stage: ShaderStage) -> &'static str {
+    if stage == ShaderStage::Vertex {
+        "VERTEX"
+    } else if stage == ShaderStage::Fragment {
+        "FRAGMENT"
+    } else if stage == ShaderStage::Compute {
+        "COMPUTE"
+    } else {
+        panic!("{}: unsupported shader stage {:?}", program.name, stage)
+    }
+}
+
+fn binding_type(
+    program: &Program,
+    module: &Module,
+    layouter: &Layouter,
+    global: &naga::GlobalVariable,
+    name: &str,
+) -> String {
+    let inner = &module.types[global.ty].inner;
+    // Runtime sized arrays have no fixed size to check against
+    let min_binding_size = match inner {
+        TypeInner::Array {
+            size: ArraySize::Dynamic,
+            ..
+        } => "None".to_string(),
+        _ => format!("wgpu::BufferSize::new({})", layouter[global.ty].size),
+    };
+
+    match global.space {
+        AddressSpace::Uniform => format!(
+            "wgpu::BindingType::Buffer {{ ty: wgpu::BufferBindingType::Uniform, \
+             has_dynamic_offset: {}, min_binding_size: {} }}",
+            program.dynamic_offset.contains(&name),
+            min_binding_size
+        ),
+        AddressSpace::Storage { access } => format!(
+            "wgpu::BindingType::Buffer {{ ty: wgpu::BufferBindingType::Storage {{ read_only: {} }}, \
+             has_dynamic_offset: {}, min_binding_size: {} }}",
+            !access.contains(StorageAccess::STORE),
+            program.dynamic_offset.contains(&name),
+            min_binding_size
+        ),
+        AddressSpace::Handle => match inner {
+            TypeInner::Sampler { comparison: true } => {
+                "wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison)".to_string()
+            }
+            TypeInner::Sampler { comparison: false } => {
+                "wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering)".to_string()
+            }
+            TypeInner::Image {
+                dim,
+                arrayed,
+                class,
+            } => {
+                let view_dimension = match (dim, arrayed) {
+                    (ImageDimension::D1, false) => "D1",
+                    (ImageDimension::D2, false) => "D2",
+                    (ImageDimension::D2, true) => "D2Array",
+                    (ImageDimension::D3, false) => "D3",
+                    (ImageDimension::Cube, false) => "Cube",
+                    (ImageDimension::Cube, true) => "CubeArray",
+                    _ => panic!(
+                        "{}: unsupported texture dimension of {}",
+                        program.name, name
+                    ),
+                };
+                let (sample_type, multisampled) = match class {
+                    ImageClass::Sampled { kind, multi } => {
+                        let sample_type = match kind {
+                            ScalarKind::Float => format!(
+                                "wgpu::TextureSampleType::Float {{ filterable: {} }}",
+                                !program.unfilterable.contains(&name)
+                            ),
+                            ScalarKind::Sint => "wgpu::TextureSampleType::Sint".to_string(),
+                            ScalarKind::Uint => "wgpu::TextureSampleType::Uint".to_string(),
+                            _ => panic!("{}: unsupported texel type of {}", program.name, name),
+                        };
+                        (sample_type, *multi)
+                    }
+                    ImageClass::Depth { multi } => {
+                        ("wgpu::TextureSampleType::Depth".to_string(), *multi)
+                    }
+                    _ => panic!("{}: {} is not a sampled texture", program.name, name),
+                };
+                format!(
+                    "wgpu::BindingType::Texture {{ multisampled: {}, view_dimension: \
+                     wgpu::TextureViewDimension::{}, sample_type: {} }}",
+                    multisampled, view_dimension, sample_type
+                )
+            }
+            _ => panic!("{}: unsupported binding type of {}", program.name, name),
+        },
+        _ => panic!("{}: unsupported address space of {}", program.name, name),
+    }
+}
+
+fn write_struct(out: &mut String, reflected: &ReflectedStruct) {
+    let _ = writeln!(
+        out,
+        "\npub const WGSL_{}: StructLayout = StructLayout {{\n    name: {:?},\n    size: {},\n    fields: &[",
+        screaming_snake(&reflected.name),
+        reflected.name,
+        reflected.size
+    );
+    for (name, offset, size) in &reflected.fields {
+        let _ = writeln!(
+            out,
+            "        FieldLayout::new({:?}, {}, {}),",
+            name, offset, size
+        );
+    }
+    out.push_str("    ],\n};\n");
+}
+
+fn write_program(out: &mut String, program: &Program, bindings: &[Binding]) {
+    let _ = writeln!(
+        out,
+        "\n/// Bindings of {}.\npub mod {} {{",
+        program.files.join(" with "),
+        program.name
+    );
+
+    let mut names = HashSet::new();
+    for binding in bindings {
+        let name = binding.name.to_uppercase();
+        if !names.insert(name.clone()) {
+            panic!("{}: two bindings are named {}", program.name, binding.name);
+        }
+        let _ = writeln!(
+            out,
+            "    /// Group {}.\n    pub const {}: u32 = {};",
+            binding.group, name, binding.binding
+        );
+    }
+
+    let mut groups: Vec<u32> = bindings.iter().map(|b| b.group).collect();
+    groups.dedup();
+    for group in groups {
+        let _ = writeln!(
+            out,
+            "    pub const GROUP_{}: &[wgpu::BindGroupLayoutEntry] = &[",
+            group
+        );
+        for binding in bindings.iter().filter(|b| b.group == group) {
+            let visibility = match binding.stages.as_slice() {
+                ["VERTEX", "FRAGMENT"] | ["FRAGMENT", "VERTEX"] => {
+                    "wgpu::ShaderStages::VERTEX_FRAGMENT".to_string()
+                }
+                [stage] => format!("wgpu::ShaderStages::{}", stage),
+                stages => stages
+                    .iter()
+                    .map(|stage| format!("wgpu::ShaderStages::{}", stage))
+                    .reduce(|all, stage| format!("{}.union({})", all, stage))
+                    .unwrap_or_default(),
+            };
+            let _ = writeln!(
+                out,
+                "        wgpu::BindGroupLayoutEntry {{ binding: {}, visibility: {}, ty: {}, count: None }},",
+                binding.binding, visibility, binding.ty
+            );
+        }
+        out.push_str("    ];\n");
+    }
+    out.push_str("}\n");
+}
+
+// MaterialUniforms -> MATERIAL_UNIFORMS
+fn screaming_snake(name: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            out.push('_');
+        }
+        out.push(c.to_ascii_uppercase());
+    }
+    out
+}
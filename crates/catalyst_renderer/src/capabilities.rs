@@ -0,0 +1,298 @@
+use std::fmt::Write;
+
+use flecs_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::clusters::LightingMode;
+
+/// Features the renderer cannot run without. Checked against the chosen adapter.
+pub const REQUIRED_FEATURES: wgpu::Features = wgpu::Features::empty();
+
+/// Features enabled when the adapter has them. Check RendererCapabilities before using one.
+pub const OPTIONAL_FEATURES: wgpu::Features = wgpu::Features::POLYGON_MODE_LINE
+    .union(wgpu::Features::TIMESTAMP_QUERY)
+    .union(wgpu::Features::TEXTURE_COMPRESSION_BC);
+
+// Storage buffers the GPU particle simulation binds at once
+const GPU_PARTICLE_STORAGE_BUFFERS: u32 = 4;
+
+/// Overrides the backend choice. Also settable with CATALYST_BACKEND=vulkan|dx12|metal|gl.
+pub const BACKEND_ENV: &str = "CATALYST_BACKEND";
+/// Forces an adapter by its index in the startup log. Also settable with CATALYST_ADAPTER=<index>.
+pub const ADAPTER_ENV: &str = "CATALYST_ADAPTER";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GraphicsBackend {
+    Vulkan,
+    Dx12,
+    Metal,
+    Gl,
+}
+
+impl GraphicsBackend {
+    pub fn backends(self) -> wgpu::Backends {
+        match self {
+            GraphicsBackend::Vulkan => wgpu::Backends::VULKAN,
+            GraphicsBackend::Dx12 => wgpu::Backends::DX12,
+            GraphicsBackend::Metal => wgpu::Backends::METAL,
+            GraphicsBackend::Gl => wgpu::Backends::GL,
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "vulkan" | "vk" => Some(GraphicsBackend::Vulkan),
+            "dx12" | "d3d12" => Some(GraphicsBackend::Dx12),
+            "metal" | "mtl" => Some(GraphicsBackend::Metal),
+            "gl" | "gles" | "opengl" => Some(GraphicsBackend::Gl),
+            _ => None,
+        }
+    }
+}
+
+/// Which GPU the renderer initializes on. Part of RenderSettings; the
+/// environment variables win over these so a build can be retargeted without recompiling.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdapterSelection {
+    pub backend: Option<GraphicsBackend>,
+    pub adapter_index: Option<usize>,
+}
+
+impl AdapterSelection {
+    pub fn with_env_overrides(mut self) -> Self {
+        if let Ok(value) = std::env::var(BACKEND_ENV) {
+            match GraphicsBackend::parse(&value) {
+                Some(backend) => self.backend = Some(backend),
+                None => log::warn!("Ignoring {}={}: unknown backend", BACKEND_ENV, value),
+            }
+        }
+
+        if let Ok(value) = std::env::var(ADAPTER_ENV) {
+            match value.trim().parse() {
+                Ok(index) => self.adapter_index = Some(index),
+                Err(_) => log::warn!("Ignoring {}={}: not an adapter index", ADAPTER_ENV, value),
+            }
+        }
+
+        self
+    }
+
+    pub fn backends(&self) -> wgpu::Backends {
+        self.backend
+            .map(GraphicsBackend::backends)
+            .unwrap_or(wgpu::Backends::PRIMARY | wgpu::Backends::GL)
+    }
+}
+
+/// What the renderer got from the GPU at startup. Features like wireframe, GPU timers
+/// and compressed textures branch on this instead of querying the adapter again.
+#[derive(Component, Clone, Debug)]
+pub struct RendererCapabilities {
+    pub adapter: wgpu::AdapterInfo,
+    /// Features enabled on the device (required plus whichever optional ones the adapter had).
+    pub features: wgpu::Features,
+    pub limits: wgpu::Limits,
+    /// What the adapter can do below the WebGPU baseline: compute shaders, indirect
+    /// draws, storage buffers in vertex shaders. Everything on Vulkan, DX12 and Metal.
+    pub downlevel: wgpu::DownlevelFlags,
+    pub surface_format: wgpu::TextureFormat,
+}
+
+impl RendererCapabilities {
+    pub fn has(&self, feature: wgpu::Features) -> bool {
+        self.features.contains(feature)
+    }
+
+    pub fn supports_wireframe(&self) -> bool {
+        self.has(wgpu::Features::POLYGON_MODE_LINE)
+    }
+
+    pub fn supports_timestamps(&self) -> bool {
+        self.has(wgpu::Features::TIMESTAMP_QUERY)
+    }
+
+    pub fn supports_bc_compression(&self) -> bool {
+        self.has(wgpu::Features::TEXTURE_COMPRESSION_BC)
+    }
+
+    /// Storage buffers in fragment shaders, needed for clustered lighting.
+    /// Without them (downlevel GL / WebGL2) only 4 point lights are drawn.
+    pub fn supports_clustered_lighting(&self) -> bool {
+        LightingMode::for_limits(&self.limits) == LightingMode::Clustered
+    }
+
+    /// Compute shaders, indirect dispatches and draws, and storage buffers in vertex
+    /// shaders, for the GPU particle tier. Without them (GL, WebGL2) GPU tier emitters
+    /// are simulated on the CPU. The indirect draws start at instance 0, so
+    /// INDIRECT_FIRST_INSTANCE isn't needed.
+    pub fn supports_gpu_particles(&self) -> bool {
+        self.downlevel.contains(
+            wgpu::DownlevelFlags::COMPUTE_SHADERS
+                | wgpu::DownlevelFlags::INDIRECT_EXECUTION
+                | wgpu::DownlevelFlags::VERTEX_STORAGE,
+        ) && self.limits.max_storage_buffers_per_shader_stage >= GPU_PARTICLE_STORAGE_BUFFERS
+    }
+}
+
+/// One enumerated adapter, as printed in the startup log and failure report.
+#[derive(Clone, Debug)]
+pub struct AdapterSummary {
+    pub index: usize,
+    pub info: wgpu::AdapterInfo,
+    pub features: wgpu::Features,
+    pub supports_surface: bool,
+}
+
+impl AdapterSummary {
+    pub fn describe(&self) -> String {
+        format!(
+            "[{}] {} ({:?}, {:?}, driver: {} {}){}",
+            self.index,
+            self.info.name,
+            self.info.backend,
+            self.info.device_type,
+            self.info.driver,
+            self.info.driver_info,
+            if self.supports_surface {
+                ""
+            } else {
+                " - cannot present to the window"
+            }
+        )
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum RendererInitError {
+    #[error("could not create a surface for the window: {0}")]
+    Surface(String),
+    #[error("no adapter can present to the window")]
+    NoCompatibleAdapter,
+    #[error("adapter index {index} requested, but only {count} adapter(s) are available")]
+    AdapterIndexOutOfRange { index: usize, count: usize },
+    #[error("adapter {index} cannot present to the window")]
+    AdapterIncompatible { index: usize },
+    #[error("adapter '{adapter}' is missing required features: {missing:?}")]
+    MissingFeatures {
+        adapter: String,
+        missing: wgpu::Features,
+    },
+    #[error("the surface reports no supported formats")]
+    NoSurfaceFormat,
+    #[error("could not create the device: {0}")]
+    RequestDevice(#[from] wgpu::RequestDeviceError),
+}
+
+/// Readable summary of what the GPU offered versus what the renderer needed.
+pub fn failure_report(
+    error: &RendererInitError,
+    selection: &AdapterSelection,
+    adapters: &[AdapterSummary],
+) -> String {
+    let mut report = String::new();
+    let _ = writeln!(report, "Catalyst Renderer failed to initialize: {}", error);
+    let _ = writeln!(report);
+    let _ = writeln!(
+        report,
+        "Requested backend: {}",
+        selection
+            .backend
+            .map(|b| format!("{:?}", b))
+            .unwrap_or_else(|| "any".to_string())
+    );
+    if let Some(index) = selection.adapter_index {
+        let _ = writeln!(report, "Requested adapter: {}", index);
+    }
+    let _ = writeln!(report, "Required features: {:?}", REQUIRED_FEATURES);
+    let _ = writeln!(report);
+
+    if adapters.is_empty() {
+        let _ = writeln!(report, "Available adapters: none");
+    } else {
+        let _ = writeln!(report, "Available adapters:");
+        for adapter in adapters {
+            let _ = writeln!(report, "  {}", adapter.describe());
+            let missing = REQUIRED_FEATURES.difference(adapter.features);
+            if !missing.is_empty() {
+                let _ = writeln!(report, "      missing features: {:?}", missing);
+            }
+        }
+    }
+
+    let _ = writeln!(report);
+    let _ = write!(
+        report,
+        "Try another backend with {}=vulkan|dx12|metal|gl or pick an adapter with {}=<index>.",
+        BACKEND_ENV, ADAPTER_ENV
+    );
+
+    report
+}
+
+/// Forced index first, otherwise the best surface-compatible adapter:
+/// discrete > integrated > virtual > other > software, earlier backends win ties.
+pub fn select_adapter(
+    adapters: &[AdapterSummary],
+    selection: &AdapterSelection,
+) -> Result<usize, RendererInitError> {
+    if let Some(index) = selection.adapter_index {
+        let adapter = adapters
+            .get(index)
+            .ok_or(RendererInitError::AdapterIndexOutOfRange {
+                index,
+                count: adapters.len(),
+            })?;
+
+        if !adapter.supports_surface {
+            return Err(RendererInitError::AdapterIncompatible { index });
+        }
+
+        return Ok(index);
+    }
+
+    adapters
+        .iter()
+        .filter(|adapter| adapter.supports_surface)
+        .min_by_key(|adapter| {
+            (
+                device_type_rank(adapter.info.device_type),
+                backend_rank(adapter.info.backend),
+            )
+        })
+        .map(|adapter| adapter.index)
+        .ok_or(RendererInitError::NoCompatibleAdapter)
+}
+
+/// Prefers an sRGB format so shader output is gamma corrected by the hardware.
+pub fn select_surface_format(
+    formats: &[wgpu::TextureFormat],
+) -> Result<wgpu::TextureFormat, RendererInitError> {
+    formats
+        .iter()
+        .copied()
+        .find(|format| format.is_srgb())
+        .or_else(|| formats.first().copied())
+        .ok_or(RendererInitError::NoSurfaceFormat)
+}
+
+fn device_type_rank(device_type: wgpu::DeviceType) -> u8 {
+    match device_type {
+        wgpu::DeviceType::DiscreteGpu => 0,
+        wgpu::DeviceType::IntegratedGpu => 1,
+        wgpu::DeviceType::VirtualGpu => 2,
+        wgpu::DeviceType::Other => 3,
+        wgpu::DeviceType::Cpu => 4,
+    }
+}
+
+fn backend_rank(backend: wgpu::Backend) -> u8 {
+    match backend {
+        wgpu::Backend::Vulkan => 0,
+        wgpu::Backend::Metal => 1,
+        wgpu::Backend::Dx12 => 2,
+        wgpu::Backend::Gl => 3,
+        _ => 4,
+    }
+}
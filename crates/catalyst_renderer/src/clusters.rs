@@ -0,0 +1,201 @@
+use glam::{Mat4, Vec2, Vec3};
+
+use crate::{
+    global_resources::GpuLight,
+    layout::{StructLayout, rust_layout},
+};
+
+/// Cluster grid of every view: screen tiles across, tiles down, depth slices.
+/// Slices are exponential in view depth, so near clusters stay small.
+pub const CLUSTER_DIMENSIONS: [u32; 3] = [16, 9, 24];
+pub const CLUSTER_COUNT: usize =
+    (CLUSTER_DIMENSIONS[0] * CLUSTER_DIMENSIONS[1] * CLUSTER_DIMENSIONS[2]) as usize;
+
+/// Point and spot lights uploaded per frame with clustered lighting. Lights past this are dropped.
+pub const MAX_LIGHTS: usize = 1024;
+
+/// Light indices per view over all clusters, room for an average of 32 lights per cluster.
+/// Clusters past the budget lose their lights.
+pub const MAX_LIGHT_INDICES: usize = CLUSTER_COUNT * 32;
+
+/// How point lights reach the PBR shader. Picked once at startup.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LightingMode {
+    /// Every light in a storage buffer; each fragment only visits the lights
+    /// assigned to its cluster.
+    Clustered,
+    /// The first 4 lights in the light uniform, for adapters without storage
+    /// buffers in fragment shaders (downlevel GL / WebGL2).
+    Uniform,
+}
+
+impl LightingMode {
+    // Lights, cluster ranges and light indices
+    const STORAGE_BUFFERS: u32 = 3;
+
+    pub fn for_limits(limits: &wgpu::Limits) -> Self {
+        if limits.max_storage_buffers_per_shader_stage >= Self::STORAGE_BUFFERS {
+            LightingMode::Clustered
+        } else {
+            LightingMode::Uniform
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ClusterUniform {
+    pub view: [[f32; 4]; 4],      // World -> view space
+    pub viewport: [f32; 4],       // x, y, width, height in pixels
+    pub dimensions: [u32; 4],     // Tiles x, tiles y, depth slices, unused
+    pub depth_slicing: [f32; 4],  // near, far, scale, bias: slice = log2(depth) * scale + bias
+}
+
+const _: () = assert!(std::mem::size_of::<ClusterUniform>() == 112);
+
+impl ClusterUniform {
+    pub const LAYOUT: StructLayout = rust_layout!(ClusterUniform {
+        view: [[f32; 4]; 4],
+        viewport: [f32; 4],
+        dimensions: [u32; 4],
+        depth_slicing: [f32; 4],
+    });
+}
+
+#[derive(Clone, Copy, Debug)]
+struct DepthSlicing {
+    near: f32,
+    far: f32,
+    scale: f32,
+    bias: f32,
+}
+
+impl DepthSlicing {
+    fn new(near: f32, far: f32) -> Self {
+        let slices = CLUSTER_DIMENSIONS[2] as f32;
+        let range = (far / near).log2().max(f32::EPSILON);
+        Self {
+            near,
+            far,
+            scale: slices / range,
+            bias: -slices * near.log2() / range,
+        }
+    }
+
+    fn slice(&self, depth: f32) -> u32 {
+        (depth.max(f32::EPSILON).log2() * self.scale + self.bias)
+            .floor()
+            .clamp(0.0, (CLUSTER_DIMENSIONS[2] - 1) as f32) as u32
+    }
+}
+
+/// Light lists of one view, rebuilt on the CPU every frame.
+#[derive(Default)]
+pub struct ClusterGrid {
+    /// (offset, count) into `indices` per cluster; x varies fastest, then y, then the slice.
+    pub ranges: Vec<[u32; 2]>,
+    pub indices: Vec<u32>,
+    lists: Vec<Vec<u32>>,
+}
+
+impl ClusterGrid {
+    /// Assigns `lights` to the clusters their bounding sphere touches (see
+    /// `GpuLight::bounding_sphere`, which only wraps a spot's cone). `view` and
+    /// `projection` are the camera's (unjittered); clusters cover `near..far` in view depth,
+    /// lights beyond `far` are skipped even with an infinite projection.
+    pub fn build(
+        &mut self,
+        lights: &[GpuLight],
+        view: Mat4,
+        projection: Mat4,
+        near: f32,
+        far: f32,
+    ) -> ClusterUniform {
+        let [tiles_x, tiles_y, _] = CLUSTER_DIMENSIONS;
+        let slicing = DepthSlicing::new(near, far);
+
+        self.lists.resize_with(CLUSTER_COUNT, Vec::new);
+        self.lists.iter_mut().for_each(Vec::clear);
+
+        for (index, light) in lights.iter().enumerate().take(MAX_LIGHTS) {
+            let (center, radius) = light.bounding_sphere();
+            let center = view.transform_point3(center);
+            let depth = -center.z;
+            if radius <= 0.0 || depth + radius < near || depth - radius > far {
+                continue;
+            }
+
+            let Some((min, max)) = screen_bounds(center, radius, near, projection) else {
+                continue;
+            };
+            let tile = |uv: f32, tiles: u32| ((uv * tiles as f32) as u32).min(tiles - 1);
+            let (x0, x1) = (tile(min.x, tiles_x), tile(max.x, tiles_x));
+            let (y0, y1) = (tile(min.y, tiles_y), tile(max.y, tiles_y));
+            let z0 = slicing.slice((depth - radius).max(near));
+            let z1 = slicing.slice((depth + radius).min(far));
+
+            for z in z0..=z1 {
+                for y in y0..=y1 {
+                    for x in x0..=x1 {
+                        self.lists[cluster_index(x, y, z)].push(index as u32);
+                    }
+                }
+            }
+        }
+
+        self.ranges.clear();
+        self.indices.clear();
+        for list in &self.lists {
+            let offset = self.indices.len();
+            let count = list.len().min(MAX_LIGHT_INDICES - offset);
+            self.indices.extend_from_slice(&list[..count]);
+            self.ranges.push([offset as u32, count as u32]);
+        }
+
+        ClusterUniform {
+            view: view.to_cols_array_2d(),
+            viewport: [0.0; 4], // Set by the caller, who knows the viewport
+            dimensions: [CLUSTER_DIMENSIONS[0], CLUSTER_DIMENSIONS[1], CLUSTER_DIMENSIONS[2], 0],
+            depth_slicing: [slicing.near, slicing.far, slicing.scale, slicing.bias],
+        }
+    }
+
+    /// Average light count of the clusters that have any lights.
+    pub fn average_lights_per_cluster(&self) -> f32 {
+        let occupied = self.ranges.iter().filter(|[_, count]| *count > 0).count();
+        if occupied == 0 {
+            return 0.0;
+        }
+        self.indices.len() as f32 / occupied as f32
+    }
+}
+
+fn cluster_index(x: u32, y: u32, z: u32) -> usize {
+    let [tiles_x, tiles_y, _] = CLUSTER_DIMENSIONS;
+    (x + tiles_x * (y + tiles_y * z)) as usize
+}
+
+// Screen rectangle (0..1, y down) covered by a view-space sphere, or None when it is
+// off screen. Projects the corners of its bounding box, pulled in front of the near
+// plane, which is conservative: a bit too large but never too small.
+fn screen_bounds(center: Vec3, radius: f32, near: f32, projection: Mat4) -> Option<(Vec2, Vec2)> {
+    let mut min = Vec2::splat(f32::MAX);
+    let mut max = Vec2::splat(f32::MIN);
+
+    for corner in 0..8 {
+        let sign = |bit: u32| if corner & bit == 0 { -radius } else { radius };
+        let mut point = center + Vec3::new(sign(1), sign(2), sign(4));
+        point.z = point.z.min(-near);
+
+        let clip = projection * point.extend(1.0);
+        let ndc = Vec2::new(clip.x, clip.y) / clip.w;
+        let uv = Vec2::new(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+        min = min.min(uv);
+        max = max.max(uv);
+    }
+
+    if max.x < 0.0 || max.y < 0.0 || min.x > 1.0 || min.y > 1.0 {
+        return None;
+    }
+    Some((min.clamp(Vec2::ZERO, Vec2::ONE), max.clamp(Vec2::ZERO, Vec2::ONE)))
+}
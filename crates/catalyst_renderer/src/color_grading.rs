@@ -0,0 +1,658 @@
+use catalyst_assets::{assets::Handle, lut::LutData};
+use catalyst_core::{
+    App, camera::Camera, pipeline::PhaseRenderPrepare, player::PlayerIndex,
+    transform::GlobalTransform,
+};
+use flecs_ecs::prelude::*;
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    clusters::LightingMode,
+    frame_graph::{FrameGraph, GraphTexture, PassStage},
+    layout::{StructLayout, rust_layout},
+    programs::{ColorGradingProgram, GpuProgram, GpuProgramRenderContext},
+    render::{RenderContext, collect_views},
+    texture::{DepthMode, GpuTexture},
+    viewport::Viewport,
+};
+
+// The grading pass has room for this many views, the split-screen maximum
+pub(crate) const MAX_VIEWS: usize = 4;
+
+/// Color grading of the whole game: the finished image, after tone mapping, TAA and
+/// depth of field, is looked up in `lut` (see catalyst_assets::lut) and mixed with the
+/// ungraded image by `intensity`. GradingVolumes override the LUT around the camera.
+///
+/// While no view uses a LUT, no pass is added. LUTs still loading count as neutral.
+#[derive(Component, Clone, Debug)]
+pub struct ColorGrading {
+    /// The default LUT, used outside every GradingVolume. None is neutral.
+    pub lut: Option<Handle<LutData>>,
+    /// 0 shows the ungraded image, 1 the graded one.
+    pub intensity: f32,
+}
+
+impl Default for ColorGrading {
+    fn default() -> Self {
+        Self {
+            lut: None,
+            intensity: 1.0,
+        }
+    }
+}
+
+/// A box where the camera sees the image through its own LUT instead of the
+/// ColorGrading default, e.g. a cold look in a cave. Outside the box the LUT fades
+/// out over `blend_distance`, so walking in blends from one look to the other.
+///
+/// The box is `half_extents` around the entity's position, scaled by its transform
+/// and aligned with the world axes. Each view blends at most two LUTs: of the volumes
+/// around the camera the smallest goes first, the next one and the default share
+/// what it leaves.
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GradingVolume {
+    /// None is neutral, e.g. to turn the default grading off indoors.
+    /// Not saved in scene files, asset handles only live as long as the session.
+    #[serde(skip)]
+    pub lut: Option<Handle<LutData>>,
+    pub half_extents: Vec3,
+    pub blend_distance: f32,
+}
+
+impl Default for GradingVolume {
+    fn default() -> Self {
+        Self {
+            lut: None,
+            half_extents: Vec3::splat(5.0),
+            blend_distance: 2.0,
+        }
+    }
+}
+
+impl GradingVolume {
+    pub fn new(lut: Handle<LutData>, half_extents: Vec3) -> Self {
+        Self {
+            lut: Some(lut),
+            half_extents,
+            ..Default::default()
+        }
+    }
+
+    /// The volume in world space.
+    pub fn world_box(&self, global: &GlobalTransform) -> GradingBox {
+        let (scale, _, center) = global.0.to_scale_rotation_translation();
+        GradingBox {
+            center,
+            half_extents: (self.half_extents * scale.abs()).max(Vec3::splat(0.01)),
+            blend_distance: self.blend_distance.max(0.0),
+        }
+    }
+}
+
+/// A GradingVolume in world space.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GradingBox {
+    pub center: Vec3,
+    pub half_extents: Vec3,
+    pub blend_distance: f32,
+}
+
+impl GradingBox {
+    /// 1 inside the box, fading to 0 at `blend_distance` outside it.
+    pub fn weight(&self, point: Vec3) -> f32 {
+        let distance = ((point - self.center).abs() - self.half_extents)
+            .max(Vec3::ZERO)
+            .length();
+        if distance <= 0.0 {
+            1.0
+        } else if self.blend_distance > 0.0 {
+            (1.0 - distance / self.blend_distance).max(0.0)
+        } else {
+            0.0
+        }
+    }
+
+    fn volume(&self) -> f32 {
+        self.half_extents.x * self.half_extents.y * self.half_extents.z
+    }
+}
+
+/// The two LUTs a view mixes: indices into the boxes given to `grading_blend`, None
+/// for the default. The primary one weighs `primary_weight`, the secondary the rest.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GradingBlend {
+    pub primary: Option<usize>,
+    pub secondary: Option<usize>,
+    pub primary_weight: f32,
+}
+
+/// Which LUTs a camera at `point` sees. Volumes go from the strongest to the weakest,
+/// smallest first among equals, each covering what the ones before left; the default
+/// covers the rest. The two largest shares are kept.
+pub fn grading_blend(point: Vec3, boxes: &[GradingBox]) -> GradingBlend {
+    let mut volumes: Vec<(usize, f32, f32)> = boxes
+        .iter()
+        .enumerate()
+        .map(|(index, grading_box)| (index, grading_box.weight(point), grading_box.volume()))
+        .filter(|(_, weight, _)| *weight > 0.0)
+        .collect();
+    volumes.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.2.total_cmp(&b.2)));
+
+    let mut left = 1.0;
+    let mut shares: Vec<(Option<usize>, f32)> = Vec::with_capacity(volumes.len() + 1);
+    for (index, weight, _) in volumes {
+        shares.push((Some(index), weight * left));
+        left *= 1.0 - weight;
+    }
+    shares.push((None, left));
+    // Stable, so a volume wins a tie with the default
+    shares.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let (primary, primary_share) = shares[0];
+    let (secondary, secondary_share) = shares.get(1).copied().unwrap_or((None, 0.0));
+    let total = primary_share + secondary_share;
+    GradingBlend {
+        primary,
+        secondary,
+        primary_weight: if total > 0.0 {
+            primary_share / total
+        } else {
+            1.0
+        },
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GradingViewUniform {
+    /// Entries per axis of the primary and secondary LUT.
+    pub lut_sizes: [f32; 2],
+    pub primary_weight: f32,
+    pub intensity: f32,
+}
+
+const _: () = assert!(std::mem::size_of::<GradingViewUniform>() == 16);
+
+impl GradingViewUniform {
+    pub const LAYOUT: StructLayout = rust_layout!(GradingViewUniform {
+        lut_sizes: [f32; 2],
+        primary_weight: f32,
+        intensity: f32,
+    });
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GradingUniform {
+    pub views: [GradingViewUniform; MAX_VIEWS],
+}
+
+impl GradingUniform {
+    pub const LAYOUT: StructLayout = rust_layout!(GradingUniform {
+        views: [GradingViewUniform; MAX_VIEWS],
+    });
+}
+
+/// What one view is graded with, None standing for a neutral LUT.
+#[derive(Clone)]
+pub struct ViewGrading {
+    pub primary: Option<GpuTexture>,
+    pub secondary: Option<GpuTexture>,
+    pub primary_weight: f32,
+    pub intensity: f32,
+}
+
+impl ViewGrading {
+    fn changes_image(&self) -> bool {
+        self.intensity > 0.0
+            && ((self.primary.is_some() && self.primary_weight > 0.0)
+                || (self.secondary.is_some() && self.primary_weight < 1.0))
+    }
+
+    fn uniform(&self, identity_size: u32) -> GradingViewUniform {
+        let size = |lut: &Option<GpuTexture>| {
+            lut.as_ref()
+                .map_or(identity_size, |lut| lut.texture.width()) as f32
+        };
+        GradingViewUniform {
+            lut_sizes: [size(&self.primary), size(&self.secondary)],
+            primary_weight: self.primary_weight,
+            intensity: self.intensity,
+        }
+    }
+}
+
+/// Each view's LUTs for this frame, in view order, set by "Prepare Color Grading".
+#[derive(Default)]
+pub struct ColorGradingState {
+    views: Vec<ViewGrading>,
+}
+
+impl ColorGradingState {
+    pub fn views(&self) -> &[ViewGrading] {
+        &self.views
+    }
+
+    /// Whether any view looks different graded, so the pass is worth adding.
+    pub fn is_active(&self) -> bool {
+        self.views.iter().any(ViewGrading::changes_image)
+    }
+
+    /// Writes this frame's settings, call before `add_color_grading_pass`.
+    pub(crate) fn write_views(&self, queue: &wgpu::Queue, program: &ColorGradingProgram) {
+        let mut uniform = GradingUniform {
+            views: [bytemuck::Zeroable::zeroed(); MAX_VIEWS],
+        };
+        for (slot, view) in uniform.views.iter_mut().zip(&self.views) {
+            *slot = view.uniform(program.identity_size());
+        }
+        program.write_uniform(queue, &uniform);
+    }
+}
+
+pub fn register_color_grading_systems(app: &mut App) {
+    app.register_singleton_default::<ColorGrading>();
+    register_lut_handlers(&app.world);
+
+    let volume_query = app
+        .world
+        .query::<(&GradingVolume, &GlobalTransform)>()
+        .set_cached()
+        .build();
+
+    let camera_query = app
+        .world
+        .query::<(&Camera, &GlobalTransform, Option<&PlayerIndex>)>()
+        .set_cached()
+        .build();
+
+    app.world
+        .system_named::<(&mut RenderContext, &ColorGrading)>("Prepare Color Grading")
+        .kind(PhaseRenderPrepare)
+        .run(move |iter| prepare_color_grading(&iter.world(), &volume_query, &camera_query));
+}
+
+// Uploads every LUT as a 3D texture, like textures
+fn register_lut_handlers(world: &World) {
+    world
+        .observer::<flecs::OnSet, (&LutData, &RenderContext)>()
+        .term_at(1)
+        .filter()
+        .each_entity(|entity, (lut, context)| {
+            entity.set(GpuTexture::from_lut(
+                &context.device,
+                &context.queue,
+                lut,
+                Some(&lut.name),
+            ));
+        });
+
+    world
+        .observer::<flecs::OnRemove, &LutData>()
+        .each_entity(|entity, _| {
+            entity.remove(GpuTexture::id());
+        });
+
+    // LUTs that arrived before the renderer was initialized, or while it was rebuilt
+    let luts_without_gpu = world.query::<&LutData>().without(GpuTexture::id()).build();
+
+    world
+        .observer::<flecs::OnSet, &RenderContext>()
+        .each(move |context| {
+            luts_without_gpu.each_entity(|entity, lut| {
+                entity.set(GpuTexture::from_lut(
+                    &context.device,
+                    &context.queue,
+                    lut,
+                    Some(&lut.name),
+                ));
+            });
+        });
+}
+
+// The LUT's 3D texture, None while it loads
+fn resolve_lut(world: &World, lut: &Option<Handle<LutData>>) -> Option<GpuTexture> {
+    lut.as_ref()?
+        .try_get_entity(world)?
+        .try_get::<&GpuTexture>(|texture| texture.clone())
+}
+
+// Blends the volumes around each view's camera with the default LUT
+fn prepare_color_grading(
+    world: &World,
+    volume_query: &Query<(&GradingVolume, &GlobalTransform)>,
+    camera_query: &Query<(&Camera, &GlobalTransform, Option<&PlayerIndex>)>,
+) {
+    let settings = world.get::<&ColorGrading>(|settings| settings.clone());
+    let default_lut = resolve_lut(world, &settings.lut);
+    let intensity = settings.intensity.clamp(0.0, 1.0);
+
+    let mut boxes = Vec::new();
+    let mut luts = Vec::new();
+    volume_query.each(|(volume, global)| {
+        boxes.push(volume.world_box(global));
+        luts.push(resolve_lut(world, &volume.lut));
+    });
+
+    // Same views, in the same order, as "Render Frame"
+    let views: Vec<ViewGrading> = collect_views(camera_query)
+        .into_iter()
+        .take(MAX_VIEWS)
+        .map(|(_, _, transform)| {
+            let blend = grading_blend(transform.transform_point3(Vec3::ZERO), &boxes);
+            let lut = |index: Option<usize>| match index {
+                Some(index) => luts[index].clone(),
+                None => default_lut.clone(),
+            };
+            ViewGrading {
+                primary: lut(blend.primary),
+                secondary: lut(blend.secondary),
+                primary_weight: blend.primary_weight,
+                intensity,
+            }
+        })
+        .collect();
+
+    world.get::<&mut RenderContext>(|context| context.color_grading.views = views);
+}
+
+/// Grades `input` into `output` with each view's LUTs. Call after `write_views`.
+pub(crate) fn add_color_grading_pass(
+    graph: &mut FrameGraph,
+    input: GraphTexture,
+    output: GraphTexture,
+    viewports: &[Viewport],
+) {
+    let viewports = viewports[..viewports.len().min(MAX_VIEWS)].to_vec();
+    graph
+        .add_pass("Color Grading", PassStage::PostProcess)
+        .read(input)
+        .write(output)
+        .record_parallel(move |pass| {
+            let timestamp_writes = pass.timestamp_writes();
+            let context = pass.context;
+            let program = &context.color_grading_program;
+            let source = program.source_bind_group(&context.device, pass.view(input));
+            let views: Vec<(wgpu::BindGroup, Viewport)> = context
+                .color_grading
+                .views()
+                .iter()
+                .zip(&viewports)
+                .map(|(view, viewport)| {
+                    let luts = program.lut_bind_group(
+                        &context.device,
+                        view.primary.as_ref(),
+                        view.secondary.as_ref(),
+                    );
+                    (luts, *viewport)
+                })
+                .collect();
+
+            let mut render_pass = pass.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Color Grading"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: pass.view(output),
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                timestamp_writes,
+                ..Default::default()
+            });
+            program.record(&mut render_pass, (&source, views.as_slice()));
+        });
+}
+
+/// Grades an RGBA8 image with `lut` on `device`, outside any frame: the same shader
+/// and LUT texture the renderer uses, for tools and for checking LUTs without a window.
+pub fn grade_image(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    lut: &LutData,
+    intensity: f32,
+) -> Vec<u8> {
+    let format = wgpu::TextureFormat::Rgba8Unorm;
+    let program = ColorGradingProgram::new(
+        &GpuProgramRenderContext {
+            device,
+            queue,
+            format,
+            depth_mode: DepthMode::Standard,
+            lighting_mode: LightingMode::Uniform,
+        },
+        &(),
+    );
+
+    let size = wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+    let texture = |label: &str, usage: wgpu::TextureUsages| {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        })
+    };
+    let source = texture(
+        "Grading Source",
+        wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+    );
+    let target = texture(
+        "Grading Target",
+        wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+    );
+    queue.write_texture(
+        wgpu::TexelCopyTextureInfo {
+            texture: &source,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        pixels,
+        wgpu::TexelCopyBufferLayout {
+            offset: 0,
+            bytes_per_row: Some(4 * width),
+            rows_per_image: Some(height),
+        },
+        size,
+    );
+
+    let view = ViewGrading {
+        primary: Some(GpuTexture::from_lut(device, queue, lut, Some(&lut.name))),
+        secondary: None,
+        primary_weight: 1.0,
+        intensity: intensity.clamp(0.0, 1.0),
+    };
+    let mut uniform = GradingUniform {
+        views: [bytemuck::Zeroable::zeroed(); MAX_VIEWS],
+    };
+    uniform.views[0] = view.uniform(program.identity_size());
+    program.write_uniform(queue, &uniform);
+
+    let source_view = source.create_view(&wgpu::TextureViewDescriptor::default());
+    let target_view = target.create_view(&wgpu::TextureViewDescriptor::default());
+    let source_bind_group = program.source_bind_group(device, &source_view);
+    let views = [(
+        program.lut_bind_group(device, view.primary.as_ref(), None),
+        Viewport::full(width, height),
+    )];
+
+    // Rows of a buffer copy are padded to COPY_BYTES_PER_ROW_ALIGNMENT
+    let row_bytes = 4 * width;
+    let padded_row_bytes =
+        row_bytes.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let readback = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Grading Readback"),
+        size: (padded_row_bytes * height) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Grade Image"),
+    });
+    {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Color Grading"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &target_view,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+        program.record(&mut render_pass, (&source_bind_group, views.as_slice()));
+    }
+    encoder.copy_texture_to_buffer(
+        wgpu::TexelCopyTextureInfo {
+            texture: &target,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::TexelCopyBufferInfo {
+            buffer: &readback,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row_bytes),
+                rows_per_image: Some(height),
+            },
+        },
+        size,
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    readback
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result.is_ok());
+        });
+    let mapped = loop {
+        let _ = device.poll(wgpu::PollType::Poll);
+        match receiver.try_recv() {
+            Ok(mapped) => break mapped,
+            Err(std::sync::mpsc::TryRecvError::Empty) => std::thread::yield_now(),
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => break false,
+        }
+    };
+    if !mapped {
+        return Vec::new();
+    }
+
+    let data = readback.slice(..).get_mapped_range();
+    let mut graded = Vec::with_capacity((row_bytes * height) as usize);
+    for row in data.chunks(padded_row_bytes as usize) {
+        graded.extend_from_slice(&row[..row_bytes as usize]);
+    }
+    graded
+}
+
+#[cfg(test)]
+mod tests {
+    use catalyst_assets::lut::LUT_SIZE;
+
+    use super::*;
+
+    fn room(x: f32) -> GradingBox {
+        GradingBox {
+            center: Vec3::new(x, 0.0, 0.0),
+            half_extents: Vec3::ONE,
+            blend_distance: 2.0,
+        }
+    }
+
+    fn warm(color: [f32; 3]) -> [f32; 3] {
+        [
+            (color[0] * 1.06 + 0.03).min(1.0),
+            (color[1] * 1.01 + 0.01).min(1.0),
+            color[2] * 0.86,
+        ]
+    }
+
+    // Largest difference of any color channel, in 1/255 steps
+    fn max_difference(a: &[u8], b: &[u8]) -> u8 {
+        assert_eq!(a.len(), b.len());
+        a.iter()
+            .zip(b)
+            .enumerate()
+            .filter(|(index, _)| index % 4 != 3)
+            .map(|(_, (a, b))| a.abs_diff(*b))
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn far_from_every_volume_only_the_default_is_used() {
+        let blend = grading_blend(Vec3::new(50.0, 0.0, 0.0), &[room(0.0)]);
+        assert_eq!(blend.primary, None);
+        assert_eq!(blend.primary_weight, 1.0);
+    }
+
+    #[test]
+    fn inside_nested_volumes_the_smallest_wins() {
+        let closet = GradingBox {
+            center: Vec3::ZERO,
+            half_extents: Vec3::splat(0.5),
+            blend_distance: 0.0,
+        };
+        let blend = grading_blend(Vec3::ZERO, &[room(0.0), closet]);
+        assert_eq!(blend.primary, Some(1));
+        assert_eq!(blend.primary_weight, 1.0);
+    }
+
+    #[test]
+    fn between_two_volumes_both_blend_by_their_shares() {
+        let blend = grading_blend(Vec3::new(1.5, 0.0, 0.0), &[room(0.0), room(3.0)]);
+        assert_eq!((blend.primary, blend.secondary), (Some(0), Some(1)));
+        assert!((blend.primary_weight - 0.8).abs() < 1e-4);
+    }
+
+    #[test]
+    fn halfway_through_the_blend_distance_the_default_gets_half() {
+        let blend = grading_blend(Vec3::new(2.0, 0.0, 0.0), &[room(0.0)]);
+        assert_eq!((blend.primary, blend.secondary), (Some(0), None));
+        assert!((blend.primary_weight - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn grading_on_the_gpu_follows_the_lut_and_intensity() {
+        let Some((device, queue)) = crate::headless_device() else {
+            return;
+        };
+        // Every entry of a 32^3 LUT, as a strip
+        let identity = LutData::identity(LUT_SIZE);
+        let strip = identity.to_strip();
+        let (width, height) = strip.dimensions();
+        let pixels = strip.into_raw();
+
+        // The identity LUT changes no channel by 1/255 or more
+        let graded = grade_image(&device, &queue, &pixels, width, height, &identity, 1.0);
+        assert_eq!(max_difference(&pixels, &graded), 0);
+
+        let warm = LutData::from_fn("warm", LUT_SIZE, warm);
+        let graded = grade_image(&device, &queue, &pixels, width, height, &warm, 1.0);
+        assert!(max_difference(&pixels, &graded) > 8);
+        let graded = grade_image(&device, &queue, &pixels, width, height, &warm, 0.0);
+        assert_eq!(max_difference(&pixels, &graded), 0);
+    }
+}
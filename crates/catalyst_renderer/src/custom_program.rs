@@ -0,0 +1,353 @@
+use std::{
+    any::Any,
+    collections::HashSet,
+    ops::Range,
+    sync::{Arc, Mutex},
+};
+
+use flecs_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    mesh::{GpuGeometry, MeshInstance, Vertex},
+    occlusion_culling::OcclusionCulled,
+    reflection::is_reflected,
+    render_order::RenderLayers,
+    shaders::{ShaderFiles, compose_shader_module},
+    texture::{DepthMode, TextureHelper},
+};
+
+/// Draws the entity's mesh with the custom program registered under this key in
+/// MaterialPrograms instead of PbrProgram; its MaterialDefinition, if any, is ignored.
+/// Entities naming a key nothing is registered under aren't drawn (with a warning).
+///
+/// Custom programs draw after the opaque PBR meshes of the main pass and ignore
+/// RenderOrder. Entities with a MaterialProgram aren't static batched.
+#[derive(Component, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MaterialProgram(pub String);
+
+impl MaterialProgram {
+    pub fn new(key: impl Into<String>) -> Self {
+        Self(key.into())
+    }
+}
+
+bitflags::bitflags! {
+    /// Scene passes a custom program's entities are drawn in, from
+    /// CustomMaterialProgram::passes. The main pass always draws them; the others are
+    /// the renderer's extra scene passes, which skip programs not listed. (There is no
+    /// depth prepass or shadow map pass yet; they will get flags here.)
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct ProgramPasses: u32 {
+        /// The main pass of every view.
+        const MAIN = 1 << 0;
+        /// Planar reflections (PlanarReflector), filtered by RenderLayers like PBR meshes.
+        const REFLECTIONS = 1 << 1;
+        /// Reflection probe captures.
+        const PROBE_CAPTURES = 1 << 2;
+    }
+}
+
+/// A game's own program for the meshes of entities with a MaterialProgram, created
+/// from MaterialPrograms whenever the renderer creates its programs (at startup and
+/// after a device loss).
+///
+/// The contract with the renderer, for every pass the program draws in:
+/// - `bind` sets the pipeline and the program's own bind groups, 2 and up. It is called
+///   once per pass and view, before the program's draws.
+/// - The renderer then sets group 0, the view's global group (`Camera` and
+///   `LightUniforms`, see common/camera.wgsl and common/lights.wgsl), and for every
+///   draw group 1, the entity's `MeshUniform` (common/mesh.wgsl), vertex buffer 0
+///   (`Vertex::desc`) and the index buffer, and draws the mesh's indexed triangles.
+/// - The pipeline writes the scene's three color targets and depth buffer, see
+///   CustomProgramContext::color_targets and depth_stencil.
+///
+/// Everything else (uniforms, textures, pipeline state) is the program's. Systems reach
+/// the program through `RenderContext::custom_programs` to update its buffers.
+pub trait CustomMaterialProgram: Any + Send + Sync {
+    /// Passes drawing this program's entities besides the main one.
+    fn passes(&self) -> ProgramPasses;
+
+    fn bind<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>);
+}
+
+/// What a custom program is created with: the device, the layouts of the groups the
+/// renderer binds and the formats its pipelines draw into.
+pub struct CustomProgramContext<'a> {
+    pub device: &'a wgpu::Device,
+    pub queue: &'a wgpu::Queue,
+    /// Group 0.
+    pub global_layout: &'a wgpu::BindGroupLayout,
+    /// Group 1.
+    pub mesh_layout: &'a wgpu::BindGroupLayout,
+    /// Of the scene color target.
+    pub format: wgpu::TextureFormat,
+    pub depth_mode: DepthMode,
+}
+
+impl CustomProgramContext<'_> {
+    /// Composes the shader from `files` (path, source) on top of the renderer's own, so
+    /// they can `#include "common/camera.wgsl"` and the other shared files. Panics with
+    /// the file and line when it doesn't compile, like the renderer's programs.
+    pub fn create_shader_module(
+        &self,
+        label: &str,
+        files: &[(&str, &str)],
+        entries: &[&str],
+    ) -> wgpu::ShaderModule {
+        let files = ShaderFiles::from_env().overlay(files.iter().copied());
+        compose_shader_module(self.device, label, &files, entries)
+    }
+
+    /// Layout of the mesh vertex buffer.
+    pub fn vertex_layout(&self) -> wgpu::VertexBufferLayout<'static> {
+        Vertex::desc()
+    }
+
+    /// The scene's color, velocity and luminance targets, in that order. Only the color
+    /// is blended with `blend`; velocity and luminance are written only with
+    /// `opaque`, blended surfaces keep those of what is behind them.
+    pub fn color_targets(
+        &self,
+        blend: Option<wgpu::BlendState>,
+        opaque: bool,
+    ) -> [Option<wgpu::ColorTargetState>; 3] {
+        let written = if opaque {
+            wgpu::ColorWrites::ALL
+        } else {
+            wgpu::ColorWrites::empty()
+        };
+        [
+            Some(wgpu::ColorTargetState {
+                format: self.format,
+                blend,
+                write_mask: wgpu::ColorWrites::ALL,
+            }),
+            Some(wgpu::ColorTargetState {
+                format: TextureHelper::VELOCITY_FORMAT,
+                blend: None,
+                write_mask: written,
+            }),
+            Some(wgpu::ColorTargetState {
+                format: TextureHelper::LUMINANCE_FORMAT,
+                blend: None,
+                write_mask: written,
+            }),
+        ]
+    }
+
+    /// The scene's depth buffer, tested like the PBR meshes.
+    pub fn depth_stencil(&self, depth_write_enabled: bool) -> wgpu::DepthStencilState {
+        wgpu::DepthStencilState {
+            format: TextureHelper::DEPTH_FORMAT,
+            depth_write_enabled,
+            depth_compare: self.depth_mode.compare(),
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }
+    }
+}
+
+type ProgramFactory =
+    Arc<dyn Fn(&CustomProgramContext) -> Box<dyn CustomMaterialProgram> + Send + Sync>;
+
+/// Custom programs by key. Register them before the first frame: "init renderer"
+/// creates them with the renderer's own programs, and again after a device loss.
+#[derive(Component, Clone, Default)]
+pub struct MaterialPrograms {
+    factories: Vec<(String, ProgramFactory)>,
+}
+
+impl MaterialPrograms {
+    /// Registers `new` under `key`, replacing the program registered under it before.
+    pub fn register<P: CustomMaterialProgram>(
+        &mut self,
+        key: impl Into<String>,
+        new: impl Fn(&CustomProgramContext) -> P + Send + Sync + 'static,
+    ) {
+        let key = key.into();
+        let factory: ProgramFactory = Arc::new(move |ctx| Box::new(new(ctx)));
+        match self
+            .factories
+            .iter_mut()
+            .find(|(existing, _)| *existing == key)
+        {
+            Some((_, existing)) => *existing = factory,
+            None => self.factories.push((key, factory)),
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.factories.iter().map(|(key, _)| key.as_str())
+    }
+
+    pub(crate) fn create(&self, ctx: &CustomProgramContext) -> CustomPrograms {
+        CustomPrograms {
+            programs: self
+                .factories
+                .iter()
+                .map(|(key, new)| (key.clone(), new(ctx)))
+                .collect(),
+            reported: Mutex::default(),
+        }
+    }
+}
+
+/// One mesh drawn by a custom program, collected like a PbrDraw so the pass can be
+/// recorded on any thread.
+#[derive(Clone)]
+pub struct CustomDraw {
+    // Index of the program in CustomPrograms
+    program: usize,
+    block: u32,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    index_format: wgpu::IndexFormat,
+    indices: Range<u32>,
+    base_vertex: i32,
+    instance_bind_group: wgpu::BindGroup,
+}
+
+/// The custom programs created from MaterialPrograms, in RenderContext.
+#[derive(Default)]
+pub struct CustomPrograms {
+    programs: Vec<(String, Box<dyn CustomMaterialProgram>)>,
+    // Keys entities asked for that nothing is registered under, warned about once
+    reported: Mutex<HashSet<String>>,
+}
+
+impl CustomPrograms {
+    pub fn get<P: CustomMaterialProgram>(&self, key: &str) -> Option<&P> {
+        let program = self.programs.iter().find(|(existing, _)| existing == key)?;
+        (program.1.as_ref() as &dyn Any).downcast_ref()
+    }
+
+    pub fn get_mut<P: CustomMaterialProgram>(&mut self, key: &str) -> Option<&mut P> {
+        let program = self
+            .programs
+            .iter_mut()
+            .find(|(existing, _)| existing == key)?;
+        (program.1.as_mut() as &mut dyn Any).downcast_mut()
+    }
+
+    pub fn len(&self) -> usize {
+        self.programs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.programs.is_empty()
+    }
+
+    fn index(&self, key: &str) -> Option<usize> {
+        let index = self
+            .programs
+            .iter()
+            .position(|(existing, _)| existing == key);
+        if index.is_none() && self.reported.lock().unwrap().insert(key.to_string()) {
+            log::warn!(
+                "Skipping draws with MaterialProgram '{}': no program is registered under it",
+                key
+            );
+        }
+        index
+    }
+
+    /// The draws of `pass` (MAIN, REFLECTIONS or PROBE_CAPTURES), grouped by program
+    /// and then by geometry block. `layers` filters them like PbrProgram::collect_draws:
+    /// only what a reflection drawing those layers shows, OcclusionCulled entities
+    /// skipped without.
+    pub fn collect_draws(
+        &self,
+        query: &Query<(&MeshInstance, &MaterialProgram)>,
+        pass: ProgramPasses,
+        layers: Option<RenderLayers>,
+    ) -> Arc<[CustomDraw]> {
+        let mut draws = Vec::new();
+        query.run(|mut iter| {
+            let world = iter.world();
+
+            while iter.next() {
+                if iter.count() == 0 {
+                    continue;
+                }
+                if layers.is_none() && iter.entity(0).has(OcclusionCulled) {
+                    continue;
+                }
+                let instances = iter.field::<MeshInstance>(0);
+                let programs = iter.field::<MaterialProgram>(1);
+                let mesh = world.entity_from_id(iter.pair(2).second_id().id());
+                if !mesh.is_alive() {
+                    continue;
+                }
+                let Some((
+                    block,
+                    vertex_buffer,
+                    index_buffer,
+                    index_format,
+                    (indices, base_vertex),
+                )) = mesh.try_get::<&GpuGeometry>(|geometry| {
+                    (
+                        geometry.pool_block_id,
+                        geometry.vertex_buffer.clone(),
+                        geometry.index_buffer.clone(),
+                        geometry.index_format,
+                        geometry.draw_range(),
+                    )
+                })
+                else {
+                    continue;
+                };
+
+                for i in iter.iter() {
+                    let Some(program) = self.index(&programs[i].0) else {
+                        continue;
+                    };
+                    let passes = self.programs[program].1.passes() | ProgramPasses::MAIN;
+                    if !passes.contains(pass)
+                        || layers.is_some_and(|layers| !is_reflected(iter.entity(i), layers))
+                    {
+                        continue;
+                    }
+                    draws.push(CustomDraw {
+                        program,
+                        block,
+                        vertex_buffer: vertex_buffer.clone(),
+                        index_buffer: index_buffer.clone(),
+                        index_format,
+                        indices: indices.clone(),
+                        base_vertex,
+                        instance_bind_group: instances[i].bind_group.clone(),
+                    });
+                }
+            }
+        });
+
+        draws.sort_by_key(|draw| (draw.program, draw.block));
+        draws.into()
+    }
+
+    /// Draws `draws` (from collect_draws) in a pass of the scene targets, one program
+    /// after the other, following the contract of CustomMaterialProgram.
+    pub fn record<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        global_bind_group: &'a wgpu::BindGroup,
+        draws: &'a [CustomDraw],
+    ) {
+        for group in draws.chunk_by(|a, b| a.program == b.program) {
+            self.programs[group[0].program].1.bind(render_pass);
+            render_pass.set_bind_group(0, global_bind_group, &[]);
+
+            let mut current_block = None;
+            for draw in group {
+                if current_block != Some(draw.block) {
+                    render_pass.set_vertex_buffer(0, draw.vertex_buffer.slice(..));
+                    render_pass.set_index_buffer(draw.index_buffer.slice(..), draw.index_format);
+                    current_block = Some(draw.block);
+                }
+                render_pass.set_bind_group(1, &draw.instance_bind_group, &[]);
+                render_pass.draw_indexed(draw.indices.clone(), draw.base_vertex, 0..1);
+            }
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use catalyst_core::console::ConsoleCommands;
+use flecs_ecs::prelude::*;
+use glam::Vec2;
+
+use crate::{
+    clusters::LightingMode,
+    frame_graph::{FrameGraph, GraphTexture, GraphTextureDesc, PassStage},
+    layout::{StructLayout, rust_layout},
+    programs::{GpuProgram, debug_view_program::DebugViewProgram, pbr_program::FrameDraws},
+    render::RenderContext,
+    texture::TextureHelper,
+    viewport::Viewport,
+};
+
+/// What the screen shows. Anything but Lit draws the scene's meshes again into a debug
+/// target and replaces the final image with it (outlines and the UI still go on top);
+/// the extra passes and their pipelines only exist while such a mode is on. Set from
+/// the debug UI's Rendering window or the render.debug_view console command.
+///
+/// The views cover the PBR draws: meshes with a MaterialProgram, particles, decals and
+/// planar reflections don't show in them.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum DebugViewMode {
+    #[default]
+    Lit,
+    /// Fragments shaded per pixel, opaque, transparent and overlay draws in their
+    /// order with depth testing. Black is nothing, red is `ramp_max`, white beyond.
+    Overdraw,
+    /// Every fragment rasterized per pixel, hidden or not.
+    DepthComplexity,
+    /// World-space vertex normals of opaque surfaces, normal maps left out.
+    Normals,
+    /// Roughness of opaque surfaces, texture times material factor.
+    Roughness,
+    /// Metallic of opaque surfaces, texture times material factor.
+    Metallic,
+    /// Lights assigned to the cluster of each visible opaque surface. Needs
+    /// clustered lighting, see LightingMode.
+    LightsPerCluster,
+}
+
+impl DebugViewMode {
+    pub const ALL: [DebugViewMode; 7] = [
+        DebugViewMode::Lit,
+        DebugViewMode::Overdraw,
+        DebugViewMode::DepthComplexity,
+        DebugViewMode::Normals,
+        DebugViewMode::Roughness,
+        DebugViewMode::Metallic,
+        DebugViewMode::LightsPerCluster,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DebugViewMode::Lit => "lit",
+            DebugViewMode::Overdraw => "overdraw",
+            DebugViewMode::DepthComplexity => "depth-complexity",
+            DebugViewMode::Normals => "normals",
+            DebugViewMode::Roughness => "roughness",
+            DebugViewMode::Metallic => "metallic",
+            DebugViewMode::LightsPerCluster => "lights-per-cluster",
+        }
+    }
+
+    /// The mode after this one in ALL, back to Lit after the last.
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|mode| *mode == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// Count at the hot end of the heat ramp, None for the modes showing a channel.
+    pub fn ramp_max(self) -> Option<f32> {
+        match self {
+            DebugViewMode::Overdraw | DebugViewMode::DepthComplexity => Some(8.0),
+            DebugViewMode::LightsPerCluster => Some(16.0),
+            DebugViewMode::Lit
+            | DebugViewMode::Normals
+            | DebugViewMode::Roughness
+            | DebugViewMode::Metallic => None,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DebugViewUniform {
+    pub uv_scale: [f32; 2],
+    pub ramp_max: f32,
+    /// 1 when the target holds counts shown through the heat ramp.
+    pub ramp: u32,
+}
+
+const _: () = assert!(std::mem::size_of::<DebugViewUniform>() == 16);
+
+impl DebugViewUniform {
+    pub const LAYOUT: StructLayout = rust_layout!(DebugViewUniform {
+        uv_scale: [f32; 2],
+        ramp_max: f32,
+        ramp: u32,
+    });
+
+    pub fn new(mode: DebugViewMode, uv_scale: Vec2) -> Self {
+        Self {
+            uv_scale: uv_scale.to_array(),
+            ramp_max: mode.ramp_max().unwrap_or(1.0),
+            ramp: mode.ramp_max().is_some() as u32,
+        }
+    }
+}
+
+/// Declares the debug view's passes for `mode`, called by "Render Frame" after the
+/// image is finished: the draws into the debug target (at the render scale, in
+/// `viewports`), then the composite replacing `backbuffer`.
+pub(crate) fn add_debug_view_passes(
+    graph: &mut FrameGraph,
+    context: &RenderContext,
+    mode: DebugViewMode,
+    draws: FrameDraws,
+    viewports: &[Viewport],
+    uv_scale: Vec2,
+    backbuffer: GraphTexture,
+) {
+    context
+        .debug_view_program
+        .write_uniform(&context.queue, &DebugViewUniform::new(mode, uv_scale));
+
+    let target = graph.create_texture(
+        "Debug View Target",
+        GraphTextureDesc::screen(&context.config, DebugViewProgram::target_format(mode)),
+    );
+    // Its own depth: overdraw counts what passes the test while the frame is drawn,
+    // not against the finished depth buffer
+    let depth = graph.create_texture(
+        "Debug View Depth",
+        GraphTextureDesc::screen(&context.config, TextureHelper::DEPTH_FORMAT),
+    );
+
+    let viewports = viewports.to_vec();
+    graph
+        .add_pass("Debug View Draws", PassStage::Scene)
+        .write(target)
+        .write(depth)
+        .record_parallel(move |pass| {
+            let timestamp_writes = pass.timestamp_writes();
+            let (view, depth) = (pass.view(target), pass.view(depth));
+            let context = pass.context;
+
+            let mut render_pass = pass.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Debug View Draws"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: depth,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(context.depth_mode.clear_value()),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes,
+                ..Default::default()
+            });
+
+            // The scene passes' order, so overdraw counts what they shade
+            for (index, (viewport, transparent)) in
+                viewports.iter().zip(&draws.transparent).enumerate()
+            {
+                viewport.apply(&mut render_pass);
+                let bind_group = &context.global_resources.view(index).bind_group;
+                for draws in [
+                    &draws.opaque,
+                    &draws.ordered_opaque,
+                    transparent,
+                    &draws.overlay,
+                ] {
+                    context
+                        .debug_view_program
+                        .record(&mut render_pass, (bind_group, mode, draws));
+                }
+            }
+        });
+
+    graph
+        .add_pass("Debug View Composite", PassStage::PostProcess)
+        .read(target)
+        .write(backbuffer)
+        .record_parallel(move |pass| {
+            let timestamp_writes = pass.timestamp_writes();
+            let (target, screen) = (pass.view(target), pass.view(backbuffer));
+            let context = pass.context;
+            let bind_group = context
+                .debug_view_program
+                .composite_bind_group(&context.device, target);
+
+            let mut render_pass = pass.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Debug View Composite"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: screen,
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                timestamp_writes,
+                ..Default::default()
+            });
+            context
+                .debug_view_program
+                .record_composite(&mut render_pass, &bind_group);
+        });
+}
+
+pub(crate) fn register_debug_view_commands(commands: &mut ConsoleCommands) {
+    commands.register(
+        "render.debug_view",
+        "render.debug_view [mode] - show a debug view (lit, overdraw, depth-complexity, normals, roughness, metallic, lights-per-cluster), the next one without a mode",
+        |world, args| {
+            // Without clustered lighting there are no clusters to count lights in
+            let unsupported = |mode: DebugViewMode| {
+                mode == DebugViewMode::LightsPerCluster
+                    && world.try_get::<&RenderContext>(|context| {
+                        context.global_resources.lighting_mode
+                    }) == Some(LightingMode::Uniform)
+            };
+
+            let mode = match args.first() {
+                Some(arg) => {
+                    let mode = DebugViewMode::ALL
+                        .into_iter()
+                        .find(|mode| mode.name() == *arg)
+                        .ok_or_else(|| format!("'{}' isn't a debug view", arg))?;
+                    if unsupported(mode) {
+                        return Err(format!("{} needs clustered lighting", mode.name()));
+                    }
+                    mode
+                }
+                None => {
+                    let mut mode = world.get::<&DebugViewMode>(|mode| mode.next());
+                    if unsupported(mode) {
+                        mode = mode.next();
+                    }
+                    mode
+                }
+            };
+
+            world.get::<&mut DebugViewMode>(|current| *current = mode);
+            Ok(format!("Debug view: {}", mode.name()))
+        },
+    );
+}
@@ -0,0 +1,233 @@
+use catalyst_assets::{assets::Handle, material::TextureData};
+use catalyst_core::{
+    App,
+    camera::Camera,
+    pipeline::PhaseRenderPrepare,
+    player::PlayerIndex,
+    raycast::RayHit,
+    transform::{GlobalTransform, Transform},
+};
+use flecs_ecs::prelude::*;
+use glam::{Mat3, Mat4, Quat, Vec2, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    programs::decal_program::{DecalBatch, DecalInstance},
+    render::{RenderContext, collect_views},
+    render_order::RenderLayers,
+    texture::GpuTexture,
+    visibility::ViewFrusta,
+};
+
+/// Projects a texture onto whatever opaque geometry is inside a box at the entity's
+/// GlobalTransform. The box is `size` wide on the local X and Z axes and `depth` tall
+/// on Y, centered on the entity; the texture is projected down the local -Y axis, so
+/// an unrotated decal lies flat on a floor.
+///
+/// Drawn after opaque geometry and before transparent geometry, alpha blended onto
+/// the scene color. Overlapping decals are drawn by `priority`, lower first.
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Decal {
+    /// Multiplied with the color. None projects the plain color.
+    /// Not saved in scene files, asset handles only live as long as the session.
+    #[serde(skip)]
+    pub texture: Option<Handle<TextureData>>,
+    pub size: Vec2,
+    /// Projection depth: how far above and below the entity surfaces are reached.
+    pub depth: f32,
+    pub color: [f32; 4],
+    /// Angles in radians between a surface's normal and the projection axis: fully
+    /// visible up to the first, faded out at the second. Keeps the decal from
+    /// smearing down walls it happens to overlap.
+    pub angle_fade: (f32, f32),
+    pub priority: i32,
+    /// Cameras whose RenderLayers don't share a layer with these don't draw the decal.
+    pub layers: RenderLayers,
+}
+
+impl Default for Decal {
+    fn default() -> Self {
+        Self {
+            texture: None,
+            size: Vec2::ONE,
+            depth: 1.0,
+            color: [1.0, 1.0, 1.0, 1.0],
+            angle_fade: (1.0, 1.4),
+            priority: 0,
+            layers: RenderLayers::ALL,
+        }
+    }
+}
+
+impl Decal {
+    pub fn new(texture: Handle<TextureData>, size: Vec2, depth: f32) -> Self {
+        Self {
+            texture: Some(texture),
+            size,
+            depth,
+            ..Default::default()
+        }
+    }
+
+    /// Where a decal goes to lie on the surface `hit`, e.g. of `raycast_render`: at the
+    /// hit, projecting into the surface along its normal, with the texture's X along the
+    /// surface's U direction so it lines up with the texture underneath. Then turned by
+    /// `spin` radians about the normal. A world transform; for a decal without a parent.
+    pub fn transform_at(hit: &RayHit, spin: f32) -> Transform {
+        let up = hit.normal.normalize_or(Vec3::Y);
+        let right = hit
+            .tangent
+            .map(|tangent| (tangent - up * up.dot(tangent)).normalize_or_zero())
+            .filter(|tangent| *tangent != Vec3::ZERO)
+            .unwrap_or_else(|| up.any_orthonormal_vector());
+        let basis = Quat::from_mat3(&Mat3::from_cols(right, up, right.cross(up)));
+        Transform {
+            translation: hit.position,
+            rotation: Quat::from_axis_angle(up, spin) * basis,
+            scale: Vec3::ONE,
+        }
+    }
+
+    /// The projection box in world space, unit box -> world.
+    pub fn box_transform(&self, global: &GlobalTransform) -> Mat4 {
+        global.0 * Mat4::from_scale(Vec3::new(self.size.x, self.depth, self.size.y))
+    }
+}
+
+pub fn register_decal_systems(app: &mut App) {
+    let decal_query = app
+        .world
+        .query::<(&Decal, &GlobalTransform)>()
+        .set_cached()
+        .build();
+
+    let camera_query = app
+        .world
+        .query::<(&Camera, &GlobalTransform, Option<&PlayerIndex>)>()
+        .set_cached()
+        .build();
+
+    app.world
+        .system_named::<(&mut RenderContext, &ViewFrusta)>("Prepare Decals")
+        .kind(PhaseRenderPrepare)
+        .run(move |iter| prepare_decals(&iter.world(), &decal_query, &camera_query));
+}
+
+// Gathers every visible decal into one instance list in draw order, batched by texture
+fn prepare_decals(
+    world: &World,
+    decal_query: &Query<(&Decal, &GlobalTransform)>,
+    camera_query: &Query<(&Camera, &GlobalTransform, Option<&PlayerIndex>)>,
+) {
+    // Same views, in the same order, as "Render Frame"
+    let view_layers: Vec<u32> = collect_views(camera_query)
+        .into_iter()
+        .map(|(camera, _, _)| {
+            world
+                .entity_from_id(camera)
+                .try_get::<&RenderLayers>(|layers| layers.0)
+                .unwrap_or(RenderLayers::ALL.0)
+        })
+        .collect();
+    let all_layers = view_layers.iter().fold(0, |all, layers| all | layers);
+
+    let mut decals: Vec<(i32, Option<Entity>, Entity, DecalInstance)> = Vec::new();
+    world.get::<&ViewFrusta>(|frusta| {
+        decal_query.each_entity(|entity, (decal, global)| {
+            if decal.layers.0 & all_layers == 0 || decal.color[3] <= 0.0 {
+                return;
+            }
+
+            let model = decal.box_transform(global);
+            if model.determinant().abs() <= f32::EPSILON {
+                return;
+            }
+
+            let center = model.transform_point3(Vec3::ZERO);
+            let radius = 0.5
+                * (model.x_axis.truncate().length()
+                    + model.y_axis.truncate().length()
+                    + model.z_axis.truncate().length());
+            if !frusta
+                .0
+                .iter()
+                .any(|view| view.frustum.intersects_sphere(center, radius))
+            {
+                return;
+            }
+
+            // Decals whose texture isn't loaded yet wait for it
+            let texture = match &decal.texture {
+                None => None,
+                Some(handle) => match handle.try_get_entity(world) {
+                    Some(texture) if texture.has(GpuTexture::id()) => Some(texture.id()),
+                    _ => return,
+                },
+            };
+
+            decals.push((
+                decal.priority,
+                texture,
+                entity.id(),
+                DecalInstance {
+                    model: model.to_cols_array_2d(),
+                    inv_model: model.inverse().to_cols_array_2d(),
+                    color: decal.color,
+                    angle_fade: [decal.angle_fade.0.cos(), decal.angle_fade.1.cos()],
+                    layers: decal.layers.0,
+                    _padding: 0,
+                },
+            ));
+        });
+    });
+
+    // Priority decides overlaps; within one priority, grouping by texture saves batches
+    decals.sort_by_key(|(priority, texture, entity, _)| {
+        (*priority, texture.map(|texture| *texture), **entity)
+    });
+
+    world.get::<&mut RenderContext>(|context| {
+        let mut instances = Vec::with_capacity(decals.len());
+        let mut batches: Vec<DecalBatch> = Vec::new();
+        let mut current: Option<Option<Entity>> = None;
+
+        for (_, texture, _, instance) in decals {
+            instances.push(instance);
+            let end = instances.len() as u32;
+
+            if current == Some(texture) {
+                if let Some(batch) = batches.last_mut() {
+                    batch.instances.end = end;
+                }
+                continue;
+            }
+            current = Some(texture);
+
+            let gpu_texture = texture
+                .and_then(|texture| {
+                    world
+                        .entity_from_id(texture)
+                        .try_get::<&GpuTexture>(|gpu_texture| gpu_texture.clone())
+                })
+                .unwrap_or_else(|| context.default_diffuse.clone());
+
+            batches.push(DecalBatch {
+                texture: context.decal_program.texture_bind_group(
+                    &context.device,
+                    &gpu_texture.view,
+                    &gpu_texture.sampler,
+                ),
+                instances: end - 1..end,
+            });
+        }
+
+        let RenderContext {
+            decal_program,
+            device,
+            queue,
+            ..
+        } = context;
+        decal_program.prepare(&instances, batches, view_layers, device, queue);
+    });
+}
@@ -0,0 +1,381 @@
+use catalyst_core::{camera::Camera, smoothing::decay_blend};
+use flecs_ecs::prelude::*;
+use glam::Mat4;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    frame_graph::{FrameGraph, GraphTexture, GraphTextureDesc, PassStage},
+    layout::{StructLayout, rust_layout},
+    programs::DepthOfFieldProgram,
+    texture::DepthMode,
+    viewport::Viewport,
+};
+
+// The DoF passes have room for this many views, the split-screen maximum
+pub(crate) const MAX_VIEWS: usize = 4;
+
+/// Widest blur radius in full resolution pixels; larger settings are clamped.
+pub const MAX_BLUR_RADIUS: f32 = 32.0;
+
+// Height of a full frame (35mm) sensor, which turns the field of view into a focal length
+const SENSOR_HEIGHT: f32 = 0.024;
+
+// Near and far fields at half resolution: color, and circle of confusion in alpha
+pub(crate) const FIELD_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Depth of field on a camera: what is nearer or further than `focus_distance` blurs,
+/// the more the wider the aperture (the lower `f_stop`). Follows a thin lens on a full
+/// frame sensor, with the focal length from the camera's field of view, so a wide
+/// angle camera keeps more in focus than a telephoto one at the same f-stop.
+///
+/// Blurred at half resolution after TAA, before outlines and the UI. Cameras without
+/// one cost nothing; while no drawn camera has one, no pass is added.
+#[derive(Component, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DepthOfField {
+    /// Distance in meters that is perfectly sharp. Where autofocus starts from.
+    pub focus_distance: f32,
+    /// Aperture as an f-number: f/1.4 is a shallow focus, f/16 keeps almost everything sharp.
+    pub f_stop: f32,
+    /// Largest blur radius in pixels, up to MAX_BLUR_RADIUS.
+    pub max_blur_radius: f32,
+    /// Focuses on what is at the center of the view instead of `focus_distance`.
+    pub autofocus: Option<Autofocus>,
+}
+
+impl Default for DepthOfField {
+    fn default() -> Self {
+        Self {
+            focus_distance: 10.0,
+            f_stop: 2.8,
+            max_blur_radius: 12.0,
+            autofocus: None,
+        }
+    }
+}
+
+impl DepthOfField {
+    pub fn new(focus_distance: f32, f_stop: f32) -> Self {
+        Self {
+            focus_distance,
+            f_stop,
+            ..Default::default()
+        }
+    }
+}
+
+/// Autofocus tuning. The depth at the center of the view is measured on the GPU every
+/// frame and the focus moves toward it, so it never lags behind a readback.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Autofocus {
+    /// How fast the focus follows (per second, exponential); higher is snappier.
+    pub speed: f32,
+    pub min_distance: f32,
+    /// Also where the focus goes while the center shows sky.
+    pub max_distance: f32,
+}
+
+impl Default for Autofocus {
+    fn default() -> Self {
+        Self {
+            speed: 4.0,
+            min_distance: 0.3,
+            max_distance: 100.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DofViewUniform {
+    /// Viewport x, y, width, height in pixels.
+    pub rect: [f32; 4],
+    pub focus_distance: f32,
+    /// Aperture diameter in meters.
+    pub aperture: f32,
+    pub focal_length: f32,
+    /// Sensor meters to circle of confusion radius in pixels.
+    pub pixels_per_meter: f32,
+    pub max_radius: f32,
+    pub near: f32,
+    pub far: f32,
+    pub reversed_z: u32,
+    pub autofocus: u32,
+    /// Weight of this frame's autofocus measurement, 1 snaps.
+    pub focus_blend: f32,
+    pub min_focus: f32,
+    pub max_focus: f32,
+    pub enabled: u32,
+    pub _padding0: u32,
+    pub _padding1: [u32; 2],
+}
+
+const _: () = assert!(std::mem::size_of::<DofViewUniform>() == 80);
+
+impl DofViewUniform {
+    pub const LAYOUT: StructLayout = rust_layout!(DofViewUniform {
+        rect: [f32; 4],
+        focus_distance: f32,
+        aperture: f32,
+        focal_length: f32,
+        pixels_per_meter: f32,
+        max_radius: f32,
+        near: f32,
+        far: f32,
+        reversed_z: u32,
+        autofocus: u32,
+        focus_blend: f32,
+        min_focus: f32,
+        max_focus: f32,
+        enabled: u32,
+        _padding0: u32,
+        _padding1: [u32; 2],
+    });
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DofUniform {
+    pub views: [DofViewUniform; MAX_VIEWS],
+}
+
+impl DofUniform {
+    pub const LAYOUT: StructLayout = rust_layout!(DofUniform {
+        views: [DofViewUniform; MAX_VIEWS],
+    });
+}
+
+/// Which camera each view's autofocus is tracking, so a view that switches cameras
+/// (or turns autofocus on) snaps to the new focus instead of racking over to it.
+#[derive(Default)]
+pub struct DepthOfFieldState {
+    tracked: [Option<Entity>; MAX_VIEWS],
+}
+
+impl DepthOfFieldState {
+    /// Forget the tracked cameras, e.g. while no view has depth of field, so the focus
+    /// snaps again once one does.
+    pub fn reset(&mut self) {
+        self.tracked = [None; MAX_VIEWS];
+    }
+
+    /// Writes the settings of this frame's views; views without DepthOfField pass the
+    /// image through. `views`, `settings` and `viewports` are in view order.
+    pub(crate) fn write_views(
+        &mut self,
+        queue: &wgpu::Queue,
+        program: &DepthOfFieldProgram,
+        views: &[(Entity, Camera, Mat4)],
+        settings: &[Option<DepthOfField>],
+        viewports: &[Viewport],
+        depth_mode: DepthMode,
+        dt: f32,
+    ) {
+        let mut uniform = DofUniform {
+            views: [bytemuck::Zeroable::zeroed(); MAX_VIEWS],
+        };
+
+        for (index, (((camera, cam, _), settings), viewport)) in views
+            .iter()
+            .zip(settings)
+            .zip(viewports)
+            .take(MAX_VIEWS)
+            .enumerate()
+        {
+            let Some(settings) = settings else {
+                self.tracked[index] = None;
+                continue;
+            };
+
+            let focal_length = 0.5 * SENSOR_HEIGHT / (cam.fov * 0.5).tan();
+            let autofocus = settings.autofocus.unwrap_or_default();
+            let focus_blend = if self.tracked[index] == Some(*camera) {
+                decay_blend(autofocus.speed, dt)
+            } else {
+                1.0
+            };
+            self.tracked[index] = settings.autofocus.map(|_| *camera);
+
+            uniform.views[index] = DofViewUniform {
+                rect: [viewport.x, viewport.y, viewport.width, viewport.height],
+                // In front of the lens nothing can be in focus
+                focus_distance: settings.focus_distance.max(focal_length * 2.0),
+                aperture: focal_length / settings.f_stop.max(0.5),
+                focal_length,
+                pixels_per_meter: viewport.height / SENSOR_HEIGHT * 0.5,
+                max_radius: settings.max_blur_radius.clamp(0.0, MAX_BLUR_RADIUS),
+                near: cam.near,
+                far: cam.far,
+                reversed_z: (depth_mode == DepthMode::ReversedZ) as u32,
+                autofocus: settings.autofocus.is_some() as u32,
+                focus_blend,
+                min_focus: autofocus.min_distance.max(focal_length * 2.0),
+                max_focus: autofocus.max_distance.max(autofocus.min_distance),
+                enabled: 1,
+                _padding0: 0,
+                _padding1: [0; 2],
+            };
+        }
+
+        program.write_uniform(queue, &uniform);
+    }
+}
+
+/// Blurs `input` by each view's depth of field into `output`: the focus is updated, the
+/// near and far fields are split off and blurred at half resolution, then composited
+/// over the sharp image. Call after `write_views`.
+pub(crate) fn add_depth_of_field_passes(
+    graph: &mut FrameGraph,
+    config: &wgpu::SurfaceConfiguration,
+    input: GraphTexture,
+    depth: GraphTexture,
+    output: GraphTexture,
+    viewports: &[Viewport],
+) {
+    let view_count = viewports.len().min(MAX_VIEWS);
+    let half = GraphTextureDesc {
+        width: config.width.div_ceil(2),
+        height: config.height.div_ceil(2),
+        ..GraphTextureDesc::screen(config, FIELD_FORMAT)
+    };
+    let near = graph.create_texture("DoF Near", half);
+    let far = graph.create_texture("DoF Far", half);
+    let near_blurred = graph.create_texture("DoF Near Blurred", half);
+    let far_blurred = graph.create_texture("DoF Far Blurred", half);
+
+    // Focus distance per view, kept on the GPU between frames
+    graph
+        .add_pass("DoF Focus", PassStage::PostProcess)
+        .read(depth)
+        .keep()
+        .record_parallel(move |pass| {
+            let timestamp_writes = pass.compute_timestamp_writes();
+            let context = pass.context;
+            let bind_group = context
+                .depth_of_field_program
+                .focus_bind_group(&context.device, pass.view(depth));
+            context.depth_of_field_program.dispatch_focus(
+                pass.encoder,
+                &bind_group,
+                view_count as u32,
+                timestamp_writes,
+            );
+        });
+
+    // Half resolution viewports for the field passes, grown to whole texels
+    let half_viewports: Vec<Viewport> = viewports
+        .iter()
+        .map(|viewport| {
+            let (x, y) = ((viewport.x * 0.5).floor(), (viewport.y * 0.5).floor());
+            Viewport {
+                x,
+                y,
+                width: ((viewport.x + viewport.width) * 0.5).ceil() - x,
+                height: ((viewport.y + viewport.height) * 0.5).ceil() - y,
+            }
+        })
+        .collect();
+
+    let viewports_split = half_viewports.clone();
+    graph
+        .add_pass("DoF Split", PassStage::PostProcess)
+        .read(input)
+        .read(depth)
+        .write(near)
+        .write(far)
+        .record_parallel(move |pass| {
+            let timestamp_writes = pass.timestamp_writes();
+            let context = pass.context;
+            let program = &context.depth_of_field_program;
+            let scene = program.scene_bind_group(&context.device, pass.view(input), pass.view(depth));
+
+            let mut render_pass = pass.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("DoF Split"),
+                color_attachments: &[
+                    field_attachment(pass.view(near)),
+                    field_attachment(pass.view(far)),
+                ],
+                timestamp_writes,
+                ..Default::default()
+            });
+            program.record_split(&mut render_pass, &scene, &viewports_split[..view_count]);
+        });
+
+    let viewports_blur = half_viewports;
+    graph
+        .add_pass("DoF Blur", PassStage::PostProcess)
+        .read(input)
+        .read(depth)
+        .read(near)
+        .read(far)
+        .write(near_blurred)
+        .write(far_blurred)
+        .record_parallel(move |pass| {
+            let timestamp_writes = pass.timestamp_writes();
+            let context = pass.context;
+            let program = &context.depth_of_field_program;
+            let scene = program.scene_bind_group(&context.device, pass.view(input), pass.view(depth));
+            let fields = program.fields_bind_group(&context.device, pass.view(near), pass.view(far));
+
+            let mut render_pass = pass.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("DoF Blur"),
+                color_attachments: &[
+                    field_attachment(pass.view(near_blurred)),
+                    field_attachment(pass.view(far_blurred)),
+                ],
+                timestamp_writes,
+                ..Default::default()
+            });
+            program.record_blur(&mut render_pass, &scene, &fields, &viewports_blur[..view_count]);
+        });
+
+    let viewports = viewports[..view_count].to_vec();
+    graph
+        .add_pass("DoF Composite", PassStage::PostProcess)
+        .read(input)
+        .read(depth)
+        .read(near_blurred)
+        .read(far_blurred)
+        .write(output)
+        .record_parallel(move |pass| {
+            let timestamp_writes = pass.timestamp_writes();
+            let context = pass.context;
+            let program = &context.depth_of_field_program;
+            let scene = program.scene_bind_group(&context.device, pass.view(input), pass.view(depth));
+            let fields = program.fields_bind_group(
+                &context.device,
+                pass.view(near_blurred),
+                pass.view(far_blurred),
+            );
+
+            let mut render_pass = pass.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("DoF Composite"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: pass.view(output),
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                timestamp_writes,
+                ..Default::default()
+            });
+            program.record_composite(&mut render_pass, &scene, &fields, &viewports);
+        });
+}
+
+// Every texel of a field is written by some view, clearing only covers the gaps
+fn field_attachment(view: &wgpu::TextureView) -> Option<wgpu::RenderPassColorAttachment<'_>> {
+    Some(wgpu::RenderPassColorAttachment {
+        view,
+        resolve_target: None,
+        depth_slice: None,
+        ops: wgpu::Operations {
+            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+            store: wgpu::StoreOp::Store,
+        },
+    })
+}
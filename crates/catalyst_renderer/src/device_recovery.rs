@@ -0,0 +1,183 @@
+use std::sync::{Arc, Mutex};
+
+use catalyst_assets::{
+    PrewarmAsset,
+    assets::MeshData,
+    material::{MaterialData, TextureData},
+};
+use catalyst_core::App;
+use catalyst_window::MainWindow;
+use flecs_ecs::prelude::*;
+
+use crate::{
+    frame_graph::FrameGraph,
+    material::{GpuMaterial, PendingGpuMaterial},
+    mesh::{GpuGeometry, MeshInstance},
+    render::{MaterialLayout, RenderContext, init_renderer},
+    streaming::TextureResidency,
+    texture::GpuTexture,
+};
+
+/// Device losses the renderer came back from (driver reset, GPU removed or hung).
+///
+/// A lost device takes every buffer, texture, pipeline and bind group with it. The frame
+/// after a loss drops them all along with the RenderContext, and the next one builds a
+/// new device, surface and pipelines from scratch. Textures and meshes go back up from
+/// their CPU data through the budgeted upload queue (see UploadSettings), materials are
+/// rebuilt right away with the default textures standing in for the ones still queued,
+/// and per-entity bind groups are created again on their next draw.
+#[derive(Component, Clone, Debug, Default)]
+pub struct DeviceRecovery {
+    /// Device losses recovered from since startup.
+    pub recoveries: u32,
+    /// wgpu's reason and message for the last loss.
+    pub last_reason: Option<String>,
+    /// Textures, meshes and materials still without their GPU resource since the last
+    /// recovery. Meshes are not drawn until theirs is back.
+    pub regenerating: usize,
+    // Torn down, the device is created again next frame
+    rebuild: bool,
+}
+
+// Tag: an asset whose GPU resource went with a lost device and hasn't been recreated yet.
+// Removed by init_gpu_texture, init_gpu_geometry and init_gpu_material.
+#[derive(Component)]
+pub(crate) struct Regenerating;
+
+/// Set from wgpu's device lost callback, which may run on any thread.
+#[derive(Clone, Default)]
+pub(crate) struct DeviceLost(Arc<Mutex<Option<String>>>);
+
+impl DeviceLost {
+    pub(crate) fn watch(device: &wgpu::Device) -> Self {
+        let lost = Self::default();
+
+        let signal = lost.clone();
+        device.set_device_lost_callback(move |reason, message| {
+            signal.report(format!("{:?}: {}", reason, message));
+        });
+
+        // Every call on a lost device fails until it is replaced. Anything else is a bug
+        // and stays fatal, like wgpu's default handler.
+        let signal = lost.clone();
+        device.on_uncaptured_error(Arc::new(move |error: wgpu::Error| {
+            if signal.is_lost() {
+                log::debug!("Ignored on the lost device: {}", error);
+                return;
+            }
+            log::error!("Handling wgpu errors as fatal by default");
+            panic!("wgpu error: {}\n", error);
+        }));
+
+        lost
+    }
+
+    fn report(&self, reason: String) {
+        let mut lost = self.0.lock().unwrap();
+        if lost.is_none() {
+            *lost = Some(reason);
+        }
+    }
+
+    fn is_lost(&self) -> bool {
+        self.0.lock().unwrap().is_some()
+    }
+
+    // Stays set: the old device keeps reporting errors until it is dropped
+    fn reason(&self) -> Option<String> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl RenderContext {
+    /// Handles the next frame as if the device had been lost, without touching the real
+    /// one: everything on the GPU is dropped and rebuilt. For testing the recovery path.
+    pub fn simulate_device_loss(&self) {
+        self.device_lost.report("simulated".to_string());
+    }
+}
+
+pub fn register_device_recovery(app: &mut App) {
+    app.register_singleton_default::<DeviceRecovery>();
+    app.world.component::<Regenerating>();
+
+    let textures = app.world.query::<&TextureData>().build();
+    let meshes = app.world.query::<&MeshData>().build();
+    let materials = app.world.query::<&MaterialData>().build();
+    // Including textures without TextureData, like the material preview target
+    let gpu_textures = app.world.query::<&GpuTexture>().build();
+    let mesh_instances = app.world.query::<&MeshInstance>().build();
+    let regenerating = app.world.query::<()>().with(Regenerating).build();
+
+    // Before "Setup Meshes in GPU" and the upload queue, so they see the new context
+    // the frame it is created
+    app.world
+        .system_named::<&mut DeviceRecovery>("Recover Lost Device")
+        .kind(flecs::pipeline::PreUpdate)
+        .write(RenderContext::id())
+        .each_iter(move |iter, _, recovery| {
+            let world = iter.world();
+
+            if recovery.rebuild {
+                // Waits for a window to draw to
+                let Some(created) =
+                    world.try_get::<&MainWindow>(|window| init_renderer(&world, window))
+                else {
+                    return;
+                };
+                recovery.rebuild = false;
+                if created {
+                    recovery.recoveries += 1;
+                    log::info!("GPU device recreated, regenerating resources");
+                }
+                return;
+            }
+
+            if recovery.recoveries > 0 {
+                let mut count = 0;
+                regenerating.each(|_| count += 1);
+                if recovery.regenerating > 0 && count == 0 {
+                    log::info!("Every GPU resource is back");
+                }
+                recovery.regenerating = count;
+            }
+
+            let Some(reason) = world
+                .try_get::<&RenderContext>(|context| context.device_lost.reason())
+                .flatten()
+            else {
+                return;
+            };
+            log::error!("GPU device lost ({}), recreating it", reason);
+
+            // Removals are deferred to the end of the system: the GpuGeometry observer
+            // still frees into the old pool, then the old context and its surface go
+            gpu_textures.each_entity(|entity, _| {
+                entity.remove(GpuTexture::id());
+                entity.remove(TextureResidency::id());
+            });
+            mesh_instances.each_entity(|entity, _| {
+                entity.remove(MeshInstance::id());
+            });
+            meshes.each_entity(|entity, _| {
+                entity.remove(GpuGeometry::id());
+                entity.add(PrewarmAsset).add(Regenerating);
+            });
+            textures.each_entity(|entity, _| {
+                entity.add(PrewarmAsset).add(Regenerating);
+            });
+            // Rebuilt with fallbacks when the new context is set, see init_gpu_material
+            materials.each_entity(|entity, _| {
+                entity.remove(GpuMaterial::id());
+                entity.remove(PendingGpuMaterial);
+                entity.add(Regenerating);
+            });
+
+            world.get::<&mut FrameGraph>(|graph| graph.release_gpu_resources());
+            world.remove(MaterialLayout::id());
+            world.remove(RenderContext::id());
+
+            recovery.last_reason = Some(reason);
+            recovery.rebuild = true;
+        });
+}
@@ -0,0 +1,211 @@
+use std::collections::VecDeque;
+
+use catalyst_core::{App, pipeline::PhaseRenderPrepare, time::Time};
+use flecs_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::render::{RenderSettings, RenderStats};
+
+/// Render scale bounds, whatever the settings say.
+pub const MIN_RENDER_SCALE: f32 = 0.5;
+pub const MAX_RENDER_SCALE: f32 = 1.0;
+
+// Scales are multiples of this, so the controller settles instead of drifting
+// and the TAA history is only reset for real changes
+const SCALE_STEP: f32 = 0.05;
+
+// GPU frame times kept for the debug plot, and scale changes for the log
+const HISTORY_SAMPLES: usize = 240;
+const DECISION_LOG: usize = 8;
+
+/// How the scaled scene is stretched to the screen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum UpscaleFilter {
+    #[default]
+    Bilinear,
+}
+
+/// Dynamic resolution options, part of RenderSettings. Unlike the rest of the
+/// settings they are read every frame.
+///
+/// The 3D scene is drawn at a fraction of the window size and stretched to it before
+/// the UI, which stays sharp. The controller follows the GPU frame time measured by
+/// the pass timestamps (adapters without timestamp queries keep the scale they have).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DynamicResolution {
+    pub enabled: bool,
+    /// GPU milliseconds per frame the controller aims for.
+    pub target_frame_ms: f32,
+    /// Range the controller moves in, within MIN_RENDER_SCALE..=MAX_RENDER_SCALE.
+    pub min_scale: f32,
+    pub max_scale: f32,
+    /// Fixed scale, used instead of the controller's (also while disabled).
+    pub manual_scale: Option<f32>,
+    /// Fraction of the target the frame time may be off before the scale moves:
+    /// down above `target * (1 + headroom)`, up below `target * (1 - headroom)`.
+    pub headroom: f32,
+    /// Consecutive timings out of the band needed for a change. Timings after a
+    /// change are ignored for as long, they may still be from the old scale.
+    pub patience: u32,
+    pub filter: UpscaleFilter,
+}
+
+impl Default for DynamicResolution {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target_frame_ms: 16.6,
+            min_scale: MIN_RENDER_SCALE,
+            max_scale: MAX_RENDER_SCALE,
+            manual_scale: None,
+            headroom: 0.1,
+            patience: 8,
+            filter: UpscaleFilter::Bilinear,
+        }
+    }
+}
+
+/// A change of the render scale, for the debug overlay.
+#[derive(Clone, Copy, Debug)]
+pub struct ScaleDecision {
+    /// Time::elapsed_seconds when it was made.
+    pub time: f32,
+    pub from: f32,
+    pub to: f32,
+    /// Smoothed GPU frame time that caused it.
+    pub gpu_ms: f32,
+}
+
+/// Render scale of this frame, kept by the "Dynamic Resolution" system.
+#[derive(Component, Debug)]
+pub struct DynamicResolutionState {
+    /// Fraction of the window size the scene is drawn at.
+    pub scale: f32,
+    /// Moving average of the GPU frame time, None until timings arrive.
+    pub gpu_ms: Option<f32>,
+    /// (GPU frame time, scale) per timing, oldest first.
+    pub history: VecDeque<(f32, f32)>,
+    /// Last scale changes, oldest first.
+    pub decisions: VecDeque<ScaleDecision>,
+    // Timing count already seen, RenderStats::gpu_timing_samples
+    seen_samples: u64,
+    // Consecutive timings above (positive) or below (negative) the band
+    streak: i32,
+    // Timings to skip after a change
+    cooldown: u32,
+}
+
+impl Default for DynamicResolutionState {
+    fn default() -> Self {
+        Self {
+            scale: MAX_RENDER_SCALE,
+            gpu_ms: None,
+            history: VecDeque::with_capacity(HISTORY_SAMPLES),
+            decisions: VecDeque::with_capacity(DECISION_LOG),
+            seen_samples: 0,
+            streak: 0,
+            cooldown: 0,
+        }
+    }
+}
+
+impl DynamicResolutionState {
+    /// Size the scene is drawn at in a `width` x `height` window, at least 1x1.
+    pub fn render_size(&self, width: u32, height: u32) -> (u32, u32) {
+        let scaled = |size: u32| ((size as f32 * self.scale).round() as u32).clamp(1, size.max(1));
+        (scaled(width), scaled(height))
+    }
+
+    fn update(&mut self, settings: &DynamicResolution, stats: &RenderStats, time: f32) {
+        if let Some(manual) = settings.manual_scale {
+            self.set_scale(quantize(manual.clamp(MIN_RENDER_SCALE, MAX_RENDER_SCALE)), time, 0);
+            return;
+        }
+        let min = settings.min_scale.clamp(MIN_RENDER_SCALE, MAX_RENDER_SCALE);
+        let max = settings.max_scale.clamp(min, MAX_RENDER_SCALE);
+        if !settings.enabled {
+            self.set_scale(MAX_RENDER_SCALE, time, 0);
+            return;
+        }
+
+        // One step per new timing; timings arrive a few frames late, not every frame
+        if stats.gpu_timing_samples == self.seen_samples {
+            return;
+        }
+        self.seen_samples = stats.gpu_timing_samples;
+        let gpu_ms = match self.gpu_ms {
+            Some(average) => average + (stats.gpu_frame_ms - average) * 0.25,
+            None => stats.gpu_frame_ms,
+        };
+        self.gpu_ms = Some(gpu_ms);
+        if self.history.len() == HISTORY_SAMPLES {
+            self.history.pop_front();
+        }
+        self.history.push_back((gpu_ms, self.scale));
+
+        if self.cooldown > 0 {
+            self.cooldown -= 1;
+            return;
+        }
+
+        let target = settings.target_frame_ms.max(0.1);
+        self.streak = if gpu_ms > target * (1.0 + settings.headroom) {
+            self.streak.max(0) + 1
+        } else if gpu_ms < target * (1.0 - settings.headroom) {
+            self.streak.min(0) - 1
+        } else {
+            0
+        };
+        let out_of_band = self.streak.unsigned_abs() >= settings.patience.max(1);
+
+        // GPU time grows with the pixel count, the square of the scale
+        let scale = if out_of_band {
+            let ideal = self.scale * (target / gpu_ms.max(0.01)).sqrt();
+            // At least one step, so a frame time just outside the band still moves it
+            let direction = self.streak.signum() as f32;
+            let change = (self.scale - ideal).abs().max(SCALE_STEP) * -direction;
+            quantize(self.scale + change)
+        } else {
+            quantize(self.scale)
+        };
+        self.set_scale(scale.clamp(min, max), time, settings.patience);
+    }
+
+    fn set_scale(&mut self, scale: f32, time: f32, cooldown: u32) {
+        if (scale - self.scale).abs() < f32::EPSILON {
+            return;
+        }
+
+        if self.decisions.len() == DECISION_LOG {
+            self.decisions.pop_front();
+        }
+        self.decisions.push_back(ScaleDecision {
+            time,
+            from: self.scale,
+            to: scale,
+            gpu_ms: self.gpu_ms.unwrap_or(0.0),
+        });
+        self.scale = scale;
+        self.streak = 0;
+        self.cooldown = cooldown;
+    }
+}
+
+fn quantize(scale: f32) -> f32 {
+    (scale / SCALE_STEP).round() * SCALE_STEP
+}
+
+pub fn register_dynamic_resolution_systems(app: &mut App) {
+    app.register_singleton_default::<DynamicResolutionState>();
+
+    // Before "Render Frame", which sizes this frame's viewports with the scale
+    app.world
+        .system_named::<(&mut DynamicResolutionState, &RenderSettings, &RenderStats, &Time)>(
+            "Dynamic Resolution",
+        )
+        .kind(PhaseRenderPrepare)
+        .each(|(state, settings, stats, time)| {
+            state.update(&settings.dynamic_resolution, stats, time.elapsed_seconds());
+        });
+}
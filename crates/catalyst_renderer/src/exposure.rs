@@ -0,0 +1,462 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use catalyst_core::{
+    App, camera::Camera, pipeline::PhaseRenderPrepare, smoothing::exp_decay, time::Time,
+    transform::GlobalTransform,
+};
+use flecs_ecs::prelude::*;
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    RenderContext,
+    layout::{StructLayout, rust_layout},
+    programs::luminance_program::LuminanceProgram,
+    readback::{ReadbackDelivery, ReadbackManager, ReadbackResult},
+    texture::TextureHelper,
+    viewport::Viewport,
+};
+
+// The luminance pass has room for this many views, the split-screen maximum
+const MAX_VIEWS: usize = 4;
+
+// Below this fraction of lit pixels a measurement is ignored (e.g. looking at the sky)
+const MIN_COVERAGE: f32 = 0.01;
+
+// Frames of (average luminance, exposure) kept per camera for the debug plot
+const HISTORY_LEN: usize = 240;
+
+/// Exposure of a camera, applied to lit geometry before tone mapping.
+/// Cameras without one use a multiplier of 1.
+#[derive(Component, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Exposure {
+    /// Fixed exposure multiplier. Always wins over `auto` when set.
+    pub manual: Option<f32>,
+    /// Adapts toward the measured scene luminance when `manual` is None.
+    pub auto: Option<AutoExposure>,
+}
+
+impl Exposure {
+    pub fn multiplier(multiplier: f32) -> Self {
+        Self {
+            manual: Some(multiplier),
+            auto: None,
+        }
+    }
+
+    /// Manual exposure from an EV100 value, as set on a physical camera.
+    pub fn from_ev100(ev100: f32) -> Self {
+        Self::multiplier(ev100_to_exposure(ev100))
+    }
+
+    pub fn automatic(auto: AutoExposure) -> Self {
+        Self {
+            manual: None,
+            auto: Some(auto),
+        }
+    }
+}
+
+/// Exposure multiplier for an EV100 value (saturation based sensitivity, 78% reflectance).
+pub fn ev100_to_exposure(ev100: f32) -> f32 {
+    1.0 / (1.2 * 2f32.powf(ev100))
+}
+
+/// Auto exposure tuning. Adaptation happens in stops (log2), so going from 1 to 2
+/// takes as long as going from 8 to 16.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoExposure {
+    /// Average scene luminance after exposure, 0.18 is middle grey.
+    pub target_luminance: f32,
+    pub min_exposure: f32,
+    pub max_exposure: f32,
+    /// Adaptation rate (per second) while exposure rises, e.g. walking into a dark room.
+    pub speed_up: f32,
+    /// Adaptation rate (per second) while exposure falls, e.g. stepping into sunlight.
+    pub speed_down: f32,
+    /// A camera moving further than this in one frame counts as a teleport and
+    /// snaps to the new exposure instead of adapting.
+    pub teleport_distance: f32,
+}
+
+impl Default for AutoExposure {
+    fn default() -> Self {
+        Self {
+            target_luminance: 0.18,
+            min_exposure: 0.05,
+            max_exposure: 20.0,
+            speed_up: 1.5,
+            speed_down: 3.0,
+            teleport_distance: 10.0,
+        }
+    }
+}
+
+/// Adaptation state of one camera.
+#[derive(Clone, Debug)]
+pub struct CameraExposure {
+    /// Exposure multiplier used this frame.
+    pub exposure: f32,
+    /// Last measured average scene luminance (before exposure). None until the first
+    /// measurement after a reset; measurements lag a frame or two behind rendering.
+    pub average_luminance: Option<f32>,
+    /// (average luminance, exposure) per frame, oldest first.
+    pub history: VecDeque<(f32, f32)>,
+    auto: bool,
+    // False until a measurement arrived since the last reset; the first one is snapped to
+    converged: bool,
+    eye: Vec3,
+}
+
+impl CameraExposure {
+    fn new(eye: Vec3) -> Self {
+        Self {
+            exposure: 1.0,
+            average_luminance: None,
+            history: VecDeque::with_capacity(HISTORY_LEN),
+            auto: false,
+            converged: false,
+            eye,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.average_luminance = None;
+        self.converged = false;
+    }
+
+    fn adapt(&mut self, exposure: &Exposure, eye: Vec3, dt: f32) {
+        if let Some(auto) = exposure.auto {
+            // Teleported: a gradual adaptation across the cut would look wrong
+            if self.eye.distance(eye) > auto.teleport_distance {
+                self.reset();
+            }
+        }
+        self.eye = eye;
+        self.auto = exposure.manual.is_none() && exposure.auto.is_some();
+
+        self.exposure = match (exposure.manual, exposure.auto, self.average_luminance) {
+            (Some(manual), _, _) => manual,
+            (None, Some(auto), Some(average)) => {
+                let target = (auto.target_luminance / average.max(f32::EPSILON))
+                    .clamp(auto.min_exposure, auto.max_exposure);
+
+                if self.converged {
+                    let (current, goal) = (self.exposure.log2(), target.log2());
+                    let speed = if goal > current {
+                        auto.speed_up
+                    } else {
+                        auto.speed_down
+                    };
+                    exp_decay(current, goal, speed, dt).exp2()
+                } else {
+                    self.converged = true;
+                    target
+                }
+            }
+            // Nothing measured yet, hold the current exposure
+            (None, Some(auto), None) => self.exposure.clamp(auto.min_exposure, auto.max_exposure),
+            (None, None, _) => 1.0,
+        };
+
+        if self.history.len() >= HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history
+            .push_back((self.average_luminance.unwrap_or(0.0), self.exposure));
+    }
+}
+
+/// Resolved exposure per camera, adapted every frame in PhaseRenderPrepare and used by Render Frame.
+#[derive(Component, Default)]
+pub struct ExposureState {
+    cameras: HashMap<Entity, CameraExposure>,
+    // Cameras drawn last frame; one that starts being drawn is reset
+    views: Vec<Entity>,
+}
+
+impl ExposureState {
+    /// Exposure multiplier of `camera` this frame.
+    pub fn exposure(&self, camera: Entity) -> f32 {
+        self.cameras
+            .get(&camera)
+            .map(|state| state.exposure)
+            .unwrap_or(1.0)
+    }
+
+    pub fn get(&self, camera: Entity) -> Option<&CameraExposure> {
+        self.cameras.get(&camera)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &CameraExposure)> {
+        self.cameras.iter().map(|(camera, state)| (*camera, state))
+    }
+
+    /// Forgets the adaptation of `camera`: the next measurement is applied at once.
+    /// Call after cuts the teleport check can't see, e.g. switching from a bright to a dark level.
+    pub fn reset(&mut self, camera: Entity) {
+        if let Some(state) = self.cameras.get_mut(&camera) {
+            state.reset();
+        }
+    }
+
+    pub fn reset_all(&mut self) {
+        self.cameras.values_mut().for_each(CameraExposure::reset);
+    }
+
+    /// Whether any camera needs luminance measurements.
+    pub fn needs_measurement(&self) -> bool {
+        self.cameras.values().any(|state| state.auto)
+    }
+
+    /// Tells which cameras are drawn this frame, in view order.
+    pub(crate) fn begin_views(&mut self, views: &[Entity]) {
+        for camera in views {
+            if !self.views.contains(camera) {
+                self.reset(*camera);
+            }
+        }
+        self.views.clear();
+        self.views.extend_from_slice(views);
+    }
+
+    fn record(&mut self, sample: LuminanceSample) {
+        if sample.coverage < MIN_COVERAGE {
+            return;
+        }
+        if let Some(state) = self.cameras.get_mut(&sample.camera) {
+            state.average_luminance = Some(sample.log_luminance.exp2());
+        }
+    }
+}
+
+/// A finished luminance measurement of one view.
+#[derive(Clone, Copy, Debug)]
+pub struct LuminanceSample {
+    pub camera: Entity,
+    /// Average log2 luminance over the lit pixels.
+    pub log_luminance: f32,
+    /// Fraction of the view covered by lit geometry.
+    pub coverage: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LuminanceViewsUniform {
+    pub rects: [[u32; 4]; MAX_VIEWS],
+    pub count: u32,
+    pub _padding0: u32,
+    pub _padding1: [u32; 2],
+}
+
+const _: () = assert!(std::mem::size_of::<LuminanceViewsUniform>() == 80);
+
+impl LuminanceViewsUniform {
+    pub const LAYOUT: StructLayout = rust_layout!(LuminanceViewsUniform {
+        rects: [[u32; 4]; MAX_VIEWS],
+        count: u32,
+        _padding0: u32,
+        _padding1: [u32; 2],
+    });
+}
+
+// Average log2 luminance and coverage per view, as written by luminance.wgsl
+const RESULTS_SIZE: wgpu::BufferAddress = (MAX_VIEWS * 2 * std::mem::size_of::<f32>()) as _;
+
+/// Luminance buffer written by the main pass, and the readback of its per-view average
+/// through the ReadbackManager. One measurement is in flight at a time; frames rendered
+/// meanwhile are not measured.
+pub struct ExposureMeter {
+    /// Third color target of the main pass: log2 luminance and coverage of lit geometry.
+    pub luminance: wgpu::TextureView,
+    bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+    results_buffer: wgpu::Buffer,
+    // Cameras of the measurement being read back, in view order
+    in_flight: Option<Vec<Entity>>,
+    // Set by the readback's callback
+    result: Arc<Mutex<Option<ReadbackResult>>>,
+}
+
+impl ExposureMeter {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        program: &LuminanceProgram,
+    ) -> Self {
+        let luminance = TextureHelper::create_color_target(
+            device,
+            config,
+            TextureHelper::LUMINANCE_FORMAT,
+            "Luminance Buffer",
+        );
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Luminance Views Buffer"),
+            contents: bytemuck::bytes_of(&LuminanceViewsUniform {
+                rects: [[0; 4]; MAX_VIEWS],
+                count: 0,
+                _padding0: 0,
+                _padding1: [0; 2],
+            }),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let results_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Luminance Results Buffer"),
+            size: RESULTS_SIZE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Luminance Bind Group"),
+            layout: &program.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&luminance),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: results_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        Self {
+            luminance,
+            bind_group,
+            uniform_buffer,
+            results_buffer,
+            in_flight: None,
+            result: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Records the measurement of this frame's views, unless the previous one is still being read back.
+    /// `cameras` and `viewports` are in view order.
+    pub fn measure(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        program: &LuminanceProgram,
+        readback: &mut ReadbackManager,
+        cameras: &[Entity],
+        viewports: &[Viewport],
+    ) {
+        if self.in_flight.is_some() {
+            return;
+        }
+
+        let count = cameras.len().min(viewports.len()).min(MAX_VIEWS);
+        if count == 0 {
+            return;
+        }
+
+        let mut uniform = LuminanceViewsUniform {
+            rects: [[0; 4]; MAX_VIEWS],
+            count: count as u32,
+            _padding0: 0,
+            _padding1: [0; 2],
+        };
+        for (rect, viewport) in uniform.rects.iter_mut().zip(viewports) {
+            *rect = [
+                viewport.x as u32,
+                viewport.y as u32,
+                viewport.width as u32,
+                viewport.height as u32,
+            ];
+        }
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+
+        program.dispatch(encoder, &self.bind_group, count as u32);
+        let result = self.result.clone();
+        readback.read_buffer(
+            device,
+            encoder,
+            &self.results_buffer,
+            0..RESULTS_SIZE,
+            ReadbackDelivery::callback(move |readback| {
+                *result.lock().unwrap() = Some(readback);
+            }),
+        );
+
+        self.in_flight = Some(cameras[..count].to_vec());
+    }
+
+    /// The measurement in flight, once the ReadbackManager has delivered it.
+    pub fn take_results(&mut self) -> Vec<LuminanceSample> {
+        if self.in_flight.is_none() {
+            return Vec::new();
+        }
+        let Some(result) = self.result.lock().unwrap().take() else {
+            return Vec::new();
+        };
+
+        let cameras = self.in_flight.take().unwrap_or_default();
+        match result {
+            Ok(readback) => cameras
+                .iter()
+                .zip(readback.to_vec::<[f32; 2]>())
+                .map(|(camera, result)| LuminanceSample {
+                    camera: *camera,
+                    log_luminance: result[0],
+                    coverage: result[1],
+                })
+                .collect(),
+            // Measured again next frame
+            Err(error) => {
+                log::debug!("Luminance readback failed: {}", error);
+                Vec::new()
+            }
+        }
+    }
+}
+
+pub fn register_exposure_systems(app: &mut App) {
+    app.world.component::<Exposure>();
+    app.register_singleton_default::<ExposureState>();
+
+    let cameras = app
+        .world
+        .query::<(&Exposure, &GlobalTransform)>()
+        .with(Camera::id())
+        .set_cached()
+        .build();
+
+    app.world
+        .system_named::<(&mut RenderContext, &mut ExposureState, &Time)>("Adapt Exposure")
+        .kind(PhaseRenderPrepare)
+        .each(move |(context, state, time)| {
+            for sample in context.exposure.take_results() {
+                state.record(sample);
+            }
+
+            let dt = time.delta().as_secs_f32();
+            let mut seen = HashSet::new();
+            cameras.each_entity(|camera, (exposure, global)| {
+                let eye = global.transform_point3(Vec3::ZERO);
+                state
+                    .cameras
+                    .entry(camera.id())
+                    .or_insert_with(|| CameraExposure::new(eye))
+                    .adapt(exposure, eye, dt);
+                seen.insert(camera.id());
+            });
+
+            // Cameras that lost their Exposure (or were deleted) go back to 1
+            state.cameras.retain(|camera, _| seen.contains(camera));
+        });
+}
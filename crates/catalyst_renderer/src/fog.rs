@@ -0,0 +1,234 @@
+use catalyst_core::color::Color;
+use flecs_ecs::prelude::*;
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::global_resources::LightUniforms;
+
+// Caps the height term, so a point far below the fog's base height doesn't overflow.
+// Must match FOG_MAX_EXPONENT in common/fog.wgsl
+const MAX_EXPONENT: f32 = 40.0;
+
+/// Distance and height fog over the whole scene, in the singleton of the same name.
+///
+/// The fog is densest at `base_height` and thins out exponentially above it, so valleys
+/// and low ground fill up first. How much of a surface it hides follows from the fog
+/// integrated along the ray from the camera, computed exactly per pixel rather than
+/// marched. What it shows instead is `color` lit by the sun, brighter looking towards
+/// the sun as `sun_anisotropy` goes up, plus the ambient light surfaces get.
+///
+/// Applied in the PBR shader before exposure and tone mapping, so fog reacts to auto
+/// exposure like the scene does. Unlit materials and planar reflection surfaces fade
+/// into it too; particles and decals aren't fogged.
+#[derive(Component, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FogSettings {
+    pub enabled: bool,
+    /// How much light the fog scatters towards the camera, per channel (linear).
+    pub color: Color,
+    /// Extinction per meter at `base_height`: 0.02 hides half of what is 35 m away.
+    pub density: f32,
+    /// How quickly the fog thins out above `base_height`, per meter. 0 is the same
+    /// density at every height.
+    pub height_falloff: f32,
+    pub base_height: f32,
+    /// Meters in front of the camera without fog.
+    pub start_distance: f32,
+    /// The most the fog hides anything, even at the horizon, from 0 to 1.
+    pub max_opacity: f32,
+    /// -1 to 1: 0 scatters the sun evenly, towards 1 the fog glows around the sun.
+    pub sun_anisotropy: f32,
+}
+
+impl Default for FogSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            color: Color::linear_rgb(0.6, 0.65, 0.7),
+            density: 0.02,
+            height_falloff: 0.2,
+            base_height: 0.0,
+            start_distance: 0.0,
+            max_opacity: 1.0,
+            sun_anisotropy: 0.5,
+        }
+    }
+}
+
+impl FogSettings {
+    /// How much of what is at `point` the fog hides when seen from `eye`, from 0 to 1.
+    /// Matches the shader, so gameplay can ask whether something is lost in the fog.
+    pub fn opacity(&self, eye: Vec3, point: Vec3) -> f32 {
+        if !self.enabled || self.density <= 0.0 {
+            return 0.0;
+        }
+        let falloff = self.height_falloff.max(0.0);
+        let start_distance = self.start_distance.max(0.0);
+        let to_point = point - eye;
+        let distance = to_point.length();
+        let length = distance - start_distance;
+        if length <= 0.0 {
+            return 0.0;
+        }
+        let direction = to_point / distance;
+        let start = eye + direction * start_distance;
+        let rise = direction.y * length;
+
+        // The density integrated along the ray, exponential in height
+        let density_at = |height: f32| {
+            (-falloff * (height - self.base_height))
+                .min(MAX_EXPONENT)
+                .exp()
+        };
+        let start_density = density_at(start.y);
+        let exponent = falloff * rise;
+        let optical_depth = if exponent.abs() > 1e-4 {
+            self.density * length * (start_density - density_at(start.y + rise)) / exponent
+        } else {
+            self.density * length * start_density
+        };
+        (1.0 - (-optical_depth).exp()).min(self.max_opacity.clamp(0.0, 1.0))
+    }
+
+    /// Writes the fog into a view's light uniforms; disabled fog has zero density.
+    pub(crate) fn write_uniforms(&self, uniforms: &mut LightUniforms) {
+        let [r, g, b] = self.color.to_linear_rgb();
+        let density = if self.enabled {
+            self.density.max(0.0)
+        } else {
+            0.0
+        };
+        uniforms.fog_color = [r, g, b, density];
+        uniforms.fog_params = [
+            self.height_falloff.max(0.0),
+            self.base_height,
+            self.start_distance.max(0.0),
+            self.max_opacity.clamp(0.0, 1.0),
+        ];
+        uniforms.fog_sun = [self.sun_anisotropy.clamp(-0.99, 0.99), 0.0, 0.0, 0.0];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEPS: usize = 20_000;
+    const EPSILON: f32 = 1e-3;
+
+    // Opacity by midpoint ray march, the way the fog's density is defined
+    fn marched(fog: &FogSettings, eye: Vec3, point: Vec3) -> f32 {
+        let distance = eye.distance(point);
+        let direction = (point - eye) / distance;
+        let step = distance / STEPS as f32;
+        let mut optical_depth = 0.0f64;
+        for i in 0..STEPS {
+            let t = (i as f32 + 0.5) * step;
+            if t < fog.start_distance {
+                continue;
+            }
+            let height = eye.y + direction.y * t;
+            let density = fog.density * (-fog.height_falloff * (height - fog.base_height)).exp();
+            optical_depth += (density * step) as f64;
+        }
+        (1.0 - (-optical_depth).exp()).min(fog.max_opacity as f64) as f32
+    }
+
+    fn fog() -> FogSettings {
+        FogSettings {
+            enabled: true,
+            density: 0.01,
+            height_falloff: 0.15,
+            base_height: 2.0,
+            ..Default::default()
+        }
+    }
+
+    const EYE: Vec3 = Vec3::new(0.0, 5.0, 0.0);
+
+    fn assert_matches_ray_march(fog: &FogSettings, eye: Vec3, point: Vec3) {
+        let analytic = fog.opacity(eye, point);
+        let reference = marched(fog, eye, point);
+        assert!(
+            (analytic - reference).abs() < EPSILON,
+            "{} -> {}: {:.4}, the ray march gives {:.4}",
+            eye,
+            point,
+            analytic,
+            reference
+        );
+    }
+
+    #[test]
+    fn the_integral_matches_a_ray_march() {
+        let fog = fog();
+        // Up, down, level and down past the base height
+        for point in [
+            Vec3::new(30.0, 40.0, -20.0),
+            Vec3::new(-10.0, -8.0, 35.0),
+            Vec3::new(0.0, 5.0, -60.0),
+            Vec3::new(50.0, -10.0, 0.0),
+        ] {
+            assert_matches_ray_march(&fog, EYE, point);
+        }
+        // Entirely below the base height
+        assert_matches_ray_march(&fog, Vec3::new(0.0, -6.0, 0.0), Vec3::new(40.0, -3.0, 10.0));
+    }
+
+    #[test]
+    fn nothing_is_fogged_before_the_start_distance() {
+        let started = FogSettings {
+            start_distance: 20.0,
+            ..fog()
+        };
+        assert_eq!(started.opacity(EYE, Vec3::new(0.0, 5.0, -19.0)), 0.0);
+        assert_matches_ray_march(&started, EYE, Vec3::new(0.0, 12.0, -50.0));
+    }
+
+    #[test]
+    fn disabled_fog_and_fog_without_density_hide_nothing() {
+        let far = Vec3::new(0.0, 0.0, -500.0);
+        let disabled = FogSettings {
+            enabled: false,
+            ..fog()
+        };
+        let clear = FogSettings {
+            density: 0.0,
+            ..fog()
+        };
+        assert_eq!(disabled.opacity(EYE, far), 0.0);
+        assert_eq!(clear.opacity(EYE, far), 0.0);
+    }
+
+    #[test]
+    fn without_height_falloff_the_fog_follows_beer_lambert() {
+        let uniform = FogSettings {
+            height_falloff: 0.0,
+            ..fog()
+        };
+        let expected = 1.0 - (-uniform.density * 50.0).exp();
+        assert!((uniform.opacity(EYE, Vec3::new(0.0, 30.0, -40.0)) - expected).abs() < 1e-5);
+    }
+
+    #[test]
+    fn max_opacity_caps_the_fog_at_the_horizon() {
+        let horizon = Vec3::new(0.0, 0.0, -5000.0);
+        let capped = FogSettings {
+            max_opacity: 0.6,
+            ..fog()
+        };
+        assert!(capped.opacity(EYE, horizon) <= 0.6);
+        assert!(fog().opacity(EYE, horizon) > 0.6);
+    }
+
+    #[test]
+    fn opacity_only_grows_with_distance() {
+        let fog = fog();
+        let mut previous = 0.0;
+        for step in 1..200 {
+            let opacity = fog.opacity(EYE, Vec3::new(0.0, 3.0, -(step as f32)));
+            assert!(opacity >= previous, "{} at {}", opacity, step);
+            previous = opacity;
+        }
+    }
+}
@@ -0,0 +1,1504 @@
+use std::{
+    cell::Cell,
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Instant,
+};
+
+use flecs_ecs::prelude::*;
+use rayon::prelude::*;
+use thiserror::Error;
+
+use crate::{RenderContext, render::RenderStats, viewport::Viewport};
+
+// Pool textures not used for this many frames are released
+const POOL_RETAIN_FRAMES: u64 = 3;
+
+// Passes timed per frame; any beyond run untimed
+const MAX_TIMED_PASSES: u32 = 32;
+
+/// A texture declared in the current frame's graph. Only valid until the graph executes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GraphTexture(usize);
+
+/// Size, format and usage of a texture the graph allocates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GraphTextureDesc {
+    pub width: u32,
+    pub height: u32,
+    pub format: wgpu::TextureFormat,
+    pub usage: wgpu::TextureUsages,
+}
+
+impl GraphTextureDesc {
+    /// A render target the size of the surface that later passes can sample.
+    pub fn screen(config: &wgpu::SurfaceConfiguration, format: wgpu::TextureFormat) -> Self {
+        Self::target(config.width, config.height, format)
+    }
+
+    /// A render target of the given size that later passes can sample.
+    pub fn target(width: u32, height: u32, format: wgpu::TextureFormat) -> Self {
+        Self {
+            width,
+            height,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        }
+    }
+
+    pub fn size_in_bytes(&self) -> u64 {
+        let texel = self.format.block_copy_size(None).unwrap_or(4) as u64;
+        self.width as u64 * self.height as u64 * texel
+    }
+}
+
+/// Coarse slot of a pass in the frame. Passes run stage by stage. Within a stage a pass
+/// runs after the passes it depends on through its textures, in declaration order
+/// otherwise (systems declare passes in pipeline order).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PassStage {
+    /// Draws into the scene targets: geometry, particles, debug lines.
+    Scene,
+    /// Reads the finished scene: exposure measurement, TAA resolve.
+    PostProcess,
+    /// Draws on top of the final image: debug UI, HUD.
+    Ui,
+}
+
+/// Targets of this frame's scene pass, for passes that draw into the 3D scene.
+#[derive(Clone, Debug)]
+pub struct SceneTargets {
+    pub color: GraphTexture,
+    pub velocity: GraphTexture,
+    pub luminance: GraphTexture,
+    pub depth: GraphTexture,
+    /// One per view, in the order of the GlobalResources views. With dynamic resolution
+    /// they only cover the scaled part of the targets, in their top left corner.
+    pub viewports: Vec<Viewport>,
+    /// The same views on the final image, at the window size. For passes drawing on
+    /// top of it after the scene was upscaled.
+    pub screen_viewports: Vec<Viewport>,
+}
+
+enum ResourceKind {
+    Imported(wgpu::TextureView),
+    Transient(GraphTextureDesc),
+    Persistent {
+        desc: GraphTextureDesc,
+        // No contents from previous frames
+        reset: bool,
+    },
+}
+
+struct Resource {
+    name: &'static str,
+    kind: ResourceKind,
+}
+
+// Passes record with the RenderContext; the tests record with a context of their own
+type RecordFn<C> = Box<dyn FnOnce(&mut PassContext<C>)>;
+type ParallelRecordFn<C> = Box<dyn FnOnce(&mut ParallelPassContext<C>) + Send>;
+
+enum Recorder<C> {
+    Serial(RecordFn<C>),
+    Parallel(ParallelRecordFn<C>),
+}
+
+struct Pass<C = RenderContext> {
+    name: &'static str,
+    stage: PassStage,
+    reads: Vec<GraphTexture>,
+    writes: Vec<GraphTexture>,
+    keep: bool,
+    record: Option<Recorder<C>>,
+}
+
+// What the recording needs from the context besides handing it to the passes
+trait RecordingContext: Sync {
+    fn device(&self) -> &wgpu::Device;
+}
+
+impl RecordingContext for RenderContext {
+    fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+}
+
+/// Passes of one stage that each need what another one of them writes, so none can
+/// run first. The frame runs them in declaration order instead.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("frame graph cycle {}", .0.join(" -> "))]
+pub struct PassCycle(pub Vec<&'static str>);
+
+/// Declares what a pass reads and writes. Nothing is added until `record` is called.
+#[must_use = "the pass is only added by record()"]
+pub struct PassBuilder<'g> {
+    graph: &'g mut FrameGraph,
+    pass: Pass,
+}
+
+impl PassBuilder<'_> {
+    /// The pass samples or loads `texture`. A pass that draws on top of what an earlier
+    /// pass wrote (LoadOp::Load) declares the texture as both read and written.
+    pub fn read(mut self, texture: GraphTexture) -> Self {
+        self.pass.reads.push(texture);
+        self
+    }
+
+    pub fn write(mut self, texture: GraphTexture) -> Self {
+        self.pass.writes.push(texture);
+        self
+    }
+
+    /// Never culled, for passes whose results aren't graph textures (buffers, readbacks).
+    pub fn keep(mut self) -> Self {
+        self.pass.keep = true;
+        self
+    }
+
+    /// Adds the pass; `record` runs when the graph executes, at the end of the frame.
+    pub fn record(mut self, record: impl FnOnce(&mut PassContext) + 'static) {
+        self.pass.record = Some(Recorder::Serial(Box::new(record)));
+        self.graph.passes.push(self.pass);
+    }
+
+    /// Adds a pass that may be recorded on a worker thread, into its own command
+    /// encoder, together with the parallel passes next to it in the execution order.
+    /// It gets no world and only shared access to the RenderContext: what it draws
+    /// has to be collected while the systems run (see PbrProgram::collect_draws).
+    pub fn record_parallel(
+        mut self,
+        record: impl FnOnce(&mut ParallelPassContext) + Send + 'static,
+    ) {
+        self.pass.record = Some(Recorder::Parallel(Box::new(record)));
+        self.graph.passes.push(self.pass);
+    }
+}
+
+/// What a pass records with. Views handed out by `view` borrow the graph rather than
+/// the context, so they can be fetched before `encoder` is borrowed for the pass.
+pub struct PassContext<'a, C = RenderContext> {
+    pub world: WorldRef<'a>,
+    pub context: &'a mut C,
+    pub encoder: &'a mut wgpu::CommandEncoder,
+    resources: PassResources<'a>,
+}
+
+impl<'a, C> PassContext<'a, C> {
+    /// The texture behind `texture`. Panics for a transient the pass didn't declare.
+    pub fn view(&self, texture: GraphTexture) -> &'a wgpu::TextureView {
+        self.resources.view(texture)
+    }
+
+    /// Timestamp writes timing the pass, for its render pass descriptor. None without
+    /// timestamp queries; only the first render or compute pass asking is timed.
+    pub fn timestamp_writes(&self) -> Option<wgpu::RenderPassTimestampWrites<'a>> {
+        self.resources.timestamp_writes()
+    }
+
+    pub fn compute_timestamp_writes(&self) -> Option<wgpu::ComputePassTimestampWrites<'a>> {
+        self.resources.compute_timestamp_writes()
+    }
+}
+
+/// What a `record_parallel` pass records with: the same as PassContext, minus the
+/// world, with the RenderContext shared between the threads recording.
+pub struct ParallelPassContext<'a, C = RenderContext> {
+    pub context: &'a C,
+    pub encoder: &'a mut wgpu::CommandEncoder,
+    resources: PassResources<'a>,
+}
+
+impl<'a, C> ParallelPassContext<'a, C> {
+    /// The texture behind `texture`. Panics for a transient the pass didn't declare.
+    pub fn view(&self, texture: GraphTexture) -> &'a wgpu::TextureView {
+        self.resources.view(texture)
+    }
+
+    pub fn timestamp_writes(&self) -> Option<wgpu::RenderPassTimestampWrites<'a>> {
+        self.resources.timestamp_writes()
+    }
+
+    pub fn compute_timestamp_writes(&self) -> Option<wgpu::ComputePassTimestampWrites<'a>> {
+        self.resources.compute_timestamp_writes()
+    }
+}
+
+// The graph's side of a pass context: texture views and the pass's timestamp slot
+struct PassResources<'a> {
+    views: &'a [Option<wgpu::TextureView>],
+    timestamps: Option<(&'a wgpu::QuerySet, u32)>,
+    timed: Cell<bool>,
+}
+
+impl<'a> PassResources<'a> {
+    fn view(&self, texture: GraphTexture) -> &'a wgpu::TextureView {
+        let views: &'a [Option<wgpu::TextureView>] = self.views;
+        views[texture.0]
+            .as_ref()
+            .expect("texture not declared by any pass of this frame")
+    }
+
+    fn timestamp_writes(&self) -> Option<wgpu::RenderPassTimestampWrites<'a>> {
+        let (query_set, slot) = self.take_timestamps()?;
+        Some(wgpu::RenderPassTimestampWrites {
+            query_set,
+            beginning_of_pass_write_index: Some(slot * 2),
+            end_of_pass_write_index: Some(slot * 2 + 1),
+        })
+    }
+
+    fn compute_timestamp_writes(&self) -> Option<wgpu::ComputePassTimestampWrites<'a>> {
+        let (query_set, slot) = self.take_timestamps()?;
+        Some(wgpu::ComputePassTimestampWrites {
+            query_set,
+            beginning_of_pass_write_index: Some(slot * 2),
+            end_of_pass_write_index: Some(slot * 2 + 1),
+        })
+    }
+
+    fn take_timestamps(&self) -> Option<(&'a wgpu::QuerySet, u32)> {
+        if self.timed.replace(true) {
+            return None;
+        }
+        self.timestamps
+    }
+}
+
+/// The passes of the current frame. Systems declare textures and add passes during the
+/// render phases; "Execute Frame Graph" culls passes nothing depends on, allocates
+/// transient textures from a pool (textures whose lifetimes don't overlap share
+/// memory) and records the passes. Runs of `record_parallel` passes are recorded on
+/// worker threads into encoders of their own; everything is submitted at once, in order.
+///
+/// ```ignore
+/// world.system_named::<&mut FrameGraph>("Outline Pass")
+///     .kind(PhaseRender3D)
+///     .each(|graph| {
+///         let Some(scene) = graph.scene_targets().cloned() else { return };
+///         graph.add_pass("Outline", PassStage::Scene)
+///             .read(scene.depth)
+///             .read(scene.color)
+///             .write(scene.color)
+///             .record(move |pass| { /* pass.encoder.begin_render_pass(..) */ });
+///     });
+/// ```
+#[derive(Component, Default)]
+pub struct FrameGraph {
+    resources: Vec<Resource>,
+    passes: Vec<Pass>,
+    backbuffer: Option<GraphTexture>,
+    scene: Option<SceneTargets>,
+    pool: TexturePool,
+    timer: Option<PassTimer>,
+    frame: u64,
+}
+
+impl FrameGraph {
+    /// The swapchain texture, None when no surface texture was acquired this frame.
+    pub fn backbuffer(&self) -> Option<GraphTexture> {
+        self.backbuffer
+    }
+
+    pub(crate) fn set_backbuffer(&mut self, view: wgpu::TextureView) {
+        self.backbuffer = Some(self.import_texture("Backbuffer", view));
+    }
+
+    /// Set by "Render Frame" once it has declared the scene targets.
+    pub fn scene_targets(&self) -> Option<&SceneTargets> {
+        self.scene.as_ref()
+    }
+
+    pub(crate) fn set_scene_targets(&mut self, scene: SceneTargets) {
+        self.scene = Some(scene);
+    }
+
+    /// A texture owned outside the graph. Passes writing it are never culled.
+    pub fn import_texture(&mut self, name: &'static str, view: wgpu::TextureView) -> GraphTexture {
+        self.add_resource(name, ResourceKind::Imported(view))
+    }
+
+    /// A texture that only lives while this frame's passes use it. Its memory may be
+    /// shared with other transients, so the first pass using it must clear it.
+    pub fn create_texture(&mut self, name: &'static str, desc: GraphTextureDesc) -> GraphTexture {
+        self.add_resource(name, ResourceKind::Transient(desc))
+    }
+
+    /// A texture kept across frames under `name` (history buffers, shadow maps).
+    /// It is released once no frame declares it for a few frames.
+    pub fn persistent_texture(&mut self, name: &'static str, desc: GraphTextureDesc) -> GraphTexture {
+        let existing = self.resources.iter().position(|resource| {
+            resource.name == name && matches!(resource.kind, ResourceKind::Persistent { .. })
+        });
+        if let Some(index) = existing {
+            return GraphTexture(index);
+        }
+
+        let reset = self
+            .pool
+            .persistent
+            .get(name)
+            .is_none_or(|texture| texture.desc != desc);
+        self.add_resource(name, ResourceKind::Persistent { desc, reset })
+    }
+
+    /// True when a persistent texture holds nothing from earlier frames: first use,
+    /// resized, or released after going unused.
+    pub fn was_reset(&self, texture: GraphTexture) -> bool {
+        matches!(
+            self.resources[texture.0].kind,
+            ResourceKind::Persistent { reset: true, .. }
+        )
+    }
+
+    pub fn add_pass(&mut self, name: &'static str, stage: PassStage) -> PassBuilder<'_> {
+        PassBuilder {
+            graph: self,
+            pass: Pass {
+                name,
+                stage,
+                reads: Vec::new(),
+                writes: Vec::new(),
+                keep: false,
+                record: None,
+            },
+        }
+    }
+
+    /// Drops everything created on the device (pooled textures, timestamp queries) and
+    /// anything declared this frame, after a device loss. Persistent textures come back
+    /// reset.
+    pub(crate) fn release_gpu_resources(&mut self) {
+        self.resources.clear();
+        self.passes.clear();
+        self.backbuffer = None;
+        self.scene = None;
+        self.pool = TexturePool::default();
+        self.timer = None;
+    }
+
+    fn add_resource(&mut self, name: &'static str, kind: ResourceKind) -> GraphTexture {
+        self.resources.push(Resource { name, kind });
+        GraphTexture(self.resources.len() - 1)
+    }
+
+    /// Records and submits this frame's passes, then clears the graph for the next one.
+    /// Without `parallel`, every pass is recorded on this thread into one encoder.
+    pub(crate) fn execute(
+        &mut self,
+        world: WorldRef,
+        context: &mut RenderContext,
+        timestamps: bool,
+        parallel: bool,
+        stats: &mut RenderStats,
+    ) {
+        if timestamps && self.timer.is_none() {
+            self.timer = Some(PassTimer::new(&context.device, &context.queue));
+        }
+        if let Some(timings) = self
+            .timer
+            .as_mut()
+            .and_then(|timer| timer.take_results(&context.device))
+        {
+            stats.gpu_frame_ms = timings.iter().map(|(_, ms)| ms).sum();
+            stats.gpu_timing_samples += 1;
+            stats.pass_timings = timings;
+        }
+
+        let mut passes = std::mem::take(&mut self.passes);
+        let resources = std::mem::take(&mut self.resources);
+        self.backbuffer = None;
+        self.scene = None;
+        self.frame += 1;
+
+        let order = resolve_order(&passes, &resources).unwrap_or_else(|cycle| {
+            log::error!("  [FrameGraph] {}, running it in declaration order", cycle);
+            cull(stage_order(&passes), &passes, &resources)
+        });
+        let (views, aliased) =
+            self.pool
+                .allocate(&context.device, &passes, &order, &resources, self.frame);
+        self.pool.release_unused(self.frame);
+
+        stats.graph_passes = order.len();
+        stats.pool_textures = self.pool.len();
+        stats.pool_memory_bytes = self.pool.memory_bytes();
+        stats.aliased_textures = aliased;
+
+        if order.is_empty() {
+            return;
+        }
+
+        let started = Instant::now();
+        let query_set = self.timer.as_ref().map(|timer| &timer.query_set);
+        let Recording {
+            mut command_buffers,
+            mut encoder,
+            timed,
+            parallel_passes,
+        } = record_passes(
+            &mut passes,
+            &order,
+            &views,
+            query_set,
+            parallel,
+            world,
+            context,
+        );
+
+        if let Some(timer) = &mut self.timer {
+            timer.resolve(&mut encoder, timed);
+        }
+        command_buffers.push(encoder.finish());
+        stats.recording_ms = started.elapsed().as_secs_f32() * 1000.0;
+        stats.parallel_passes = parallel_passes;
+
+        context.queue.submit(command_buffers);
+        if let Some(timer) = &mut self.timer {
+            timer.request_readback();
+        }
+    }
+}
+
+struct Recording {
+    // In submission order, without `encoder`
+    command_buffers: Vec<wgpu::CommandBuffer>,
+    // The last serial passes' encoder, still open for the timestamp resolve
+    encoder: wgpu::CommandEncoder,
+    // Pass names by timestamp slot; None where a pass never used its slot
+    timed: Vec<Option<&'static str>>,
+    parallel_passes: usize,
+}
+
+// Records the passes in `order`. Without `parallel`, every pass is recorded on this
+// thread into one encoder; with it, runs of parallel passes are recorded on worker
+// threads into encoders of their own. Submitting the buffers in order gives the same
+// commands either way.
+fn record_passes<C: RecordingContext>(
+    passes: &mut [Pass<C>],
+    order: &[usize],
+    views: &[Option<wgpu::TextureView>],
+    query_set: Option<&wgpu::QuerySet>,
+    parallel: bool,
+    world: WorldRef,
+    context: &mut C,
+) -> Recording {
+    let new_encoder = |device: &wgpu::Device| {
+        device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Frame Graph Encoder"),
+        })
+    };
+    let mut encoder = new_encoder(context.device());
+    let mut command_buffers = Vec::new();
+    let mut timed: Vec<Option<&'static str>> = Vec::new();
+    let mut parallel_passes = 0;
+
+    let mut position = 0;
+    while position < order.len() {
+        // Consecutive parallel passes, one encoder each; a lone one isn't worth the threads
+        let run = if parallel {
+            order[position..]
+                .iter()
+                .take_while(|&&index| matches!(passes[index].record, Some(Recorder::Parallel(_))))
+                .count()
+        } else {
+            0
+        };
+        if run > 1 {
+            // What was recorded so far runs first
+            let recorded = std::mem::replace(&mut encoder, new_encoder(context.device()));
+            command_buffers.push(recorded.finish());
+
+            let jobs: Vec<_> = order[position..position + run]
+                .iter()
+                .filter_map(|&index| {
+                    let pass = &mut passes[index];
+                    let Some(Recorder::Parallel(record)) = pass.record.take() else {
+                        return None;
+                    };
+                    let slot = (query_set.is_some() && timed.len() < MAX_TIMED_PASSES as usize)
+                        .then(|| {
+                            timed.push(Some(pass.name));
+                            timed.len() as u32 - 1
+                        });
+                    Some((pass.name, record, slot))
+                })
+                .collect();
+            parallel_passes += jobs.len();
+
+            let shared: &C = context;
+            let recorded: Vec<_> =
+                jobs.into_par_iter()
+                    .map(|(name, record, slot)| {
+                        let _pass_scope = catalyst_core::profiling::ProfileScope::new(name);
+                        let mut encoder = shared.device().create_command_encoder(
+                            &wgpu::CommandEncoderDescriptor { label: Some(name) },
+                        );
+                        let mut pass_context = ParallelPassContext {
+                            context: shared,
+                            encoder: &mut encoder,
+                            resources: PassResources {
+                                views,
+                                timestamps: query_set.zip(slot),
+                                timed: Cell::new(false),
+                            },
+                        };
+                        record(&mut pass_context);
+                        let used = pass_context.resources.timed.get();
+                        (encoder.finish(), slot.filter(|_| !used))
+                    })
+                    .collect();
+
+            // collect keeps the passes' order
+            for (command_buffer, unused_slot) in recorded {
+                command_buffers.push(command_buffer);
+                if let Some(slot) = unused_slot {
+                    timed[slot as usize] = None;
+                }
+            }
+            position += run;
+            continue;
+        }
+
+        let pass = &mut passes[order[position]];
+        position += 1;
+        let Some(record) = pass.record.take() else {
+            continue;
+        };
+        let _pass_scope = catalyst_core::profiling::ProfileScope::new(pass.name);
+
+        let timestamps = query_set
+            .filter(|_| timed.len() < MAX_TIMED_PASSES as usize)
+            .map(|query_set| (query_set, timed.len() as u32));
+        let resources = PassResources {
+            views,
+            timestamps,
+            timed: Cell::new(false),
+        };
+        let used = match record {
+            Recorder::Serial(record) => {
+                let mut pass_context = PassContext {
+                    world,
+                    context: &mut *context,
+                    encoder: &mut encoder,
+                    resources,
+                };
+                record(&mut pass_context);
+                pass_context.resources.timed.get()
+            }
+            Recorder::Parallel(record) => {
+                let mut pass_context = ParallelPassContext {
+                    context: &*context,
+                    encoder: &mut encoder,
+                    resources,
+                };
+                record(&mut pass_context);
+                pass_context.resources.timed.get()
+            }
+        };
+
+        if used && timestamps.is_some() {
+            timed.push(Some(pass.name));
+        }
+    }
+
+    Recording {
+        command_buffers,
+        encoder,
+        timed,
+        parallel_passes,
+    }
+}
+
+// Live passes in execution order, see PassStage
+fn resolve_order(passes: &[Pass], resources: &[Resource]) -> Result<Vec<usize>, PassCycle> {
+    let mut order = Vec::with_capacity(passes.len());
+    for stage in stage_order(passes).chunk_by(|&a, &b| passes[a].stage == passes[b].stage) {
+        order.extend(sort_stage(passes, resources, stage)?);
+    }
+    Ok(cull(order, passes, resources))
+}
+
+// Passes by stage, declaration order kept within one
+fn stage_order(passes: &[Pass]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..passes.len()).collect();
+    order.sort_by_key(|&index| passes[index].stage);
+    order
+}
+
+// Orders one stage's passes. A pass waits for the earlier declared passes sharing a
+// texture with it, when either of them writes it. A pass reading a transient nothing
+// wrote before it waits for the passes writing it later on instead, since a transient
+// has no contents until then.
+fn sort_stage(
+    passes: &[Pass],
+    resources: &[Resource],
+    stage: &[usize],
+) -> Result<Vec<usize>, PassCycle> {
+    // Positions in `stage` each pass waits for
+    let mut waits_for: Vec<Vec<usize>> = vec![Vec::new(); stage.len()];
+    // Per texture, the passes that used it so far and whether they wrote it
+    let mut accesses: HashMap<usize, Vec<(usize, bool)>> = HashMap::new();
+    // Per transient, the passes that read it before anything wrote it
+    let mut early_reads: HashMap<usize, Vec<usize>> = HashMap::new();
+
+    for (position, &index) in stage.iter().enumerate() {
+        let pass = &passes[index];
+        let used = pass
+            .reads
+            .iter()
+            .map(|texture| (texture.0, pass.writes.contains(texture)))
+            .chain(
+                pass.writes
+                    .iter()
+                    .filter(|texture| !pass.reads.contains(texture))
+                    .map(|texture| (texture.0, true)),
+            );
+        for (texture, writes) in used {
+            let previous = accesses.entry(texture).or_default();
+            let transient = matches!(resources[texture].kind, ResourceKind::Transient(_));
+            if !writes && transient && !previous.iter().any(|&(_, wrote)| wrote) {
+                early_reads.entry(texture).or_default().push(position);
+                continue;
+            }
+            for &(earlier, wrote) in previous.iter() {
+                if earlier != position && (writes || wrote) {
+                    waits_for[position].push(earlier);
+                }
+            }
+            if writes {
+                for &reader in early_reads.get(&texture).into_iter().flatten() {
+                    waits_for[reader].push(position);
+                }
+            }
+            previous.push((position, writes));
+        }
+    }
+
+    // Kahn's algorithm, taking the first declared of the passes that are ready
+    let mut waiting: Vec<usize> = waits_for.iter().map(Vec::len).collect();
+    let mut unblocks: Vec<Vec<usize>> = vec![Vec::new(); stage.len()];
+    for (position, earlier) in waits_for.iter().enumerate() {
+        for &earlier in earlier {
+            unblocks[earlier].push(position);
+        }
+    }
+    let mut ready: BinaryHeap<Reverse<usize>> = (0..stage.len())
+        .filter(|&position| waiting[position] == 0)
+        .map(Reverse)
+        .collect();
+    let mut order = Vec::with_capacity(stage.len());
+    while let Some(Reverse(position)) = ready.pop() {
+        order.push(stage[position]);
+        for &next in &unblocks[position] {
+            waiting[next] -= 1;
+            if waiting[next] == 0 {
+                ready.push(Reverse(next));
+            }
+        }
+    }
+    if order.len() == stage.len() {
+        return Ok(order);
+    }
+
+    // Every pass left waits for another one left: walk back until one repeats
+    let first = (0..stage.len()).find(|&position| waiting[position] > 0);
+    let mut path = vec![first.expect("some pass is left")];
+    loop {
+        let current = *path.last().unwrap();
+        let earlier = *waits_for[current]
+            .iter()
+            .find(|&&earlier| waiting[earlier] > 0)
+            .expect("a waiting pass waits for another waiting one");
+        if let Some(start) = path.iter().position(|&position| position == earlier) {
+            let mut cycle: Vec<_> = path[start..]
+                .iter()
+                .rev()
+                .map(|&position| passes[stage[position]].name)
+                .collect();
+            cycle.push(cycle[0]);
+            return Err(PassCycle(cycle));
+        }
+        path.push(earlier);
+    }
+}
+
+// Culled back to front, a pass lives if it must run anyway, writes something that
+// outlives the frame, or writes a transient that a live pass reads.
+fn cull(mut order: Vec<usize>, passes: &[Pass], resources: &[Resource]) -> Vec<usize> {
+    let mut needed = vec![false; resources.len()];
+    let mut live = vec![false; passes.len()];
+    for &index in order.iter().rev() {
+        let pass = &passes[index];
+        live[index] = pass.keep
+            || pass.writes.iter().any(|texture| {
+                needed[texture.0] || !matches!(resources[texture.0].kind, ResourceKind::Transient(_))
+            });
+        if live[index] {
+            for texture in &pass.reads {
+                needed[texture.0] = true;
+            }
+        }
+    }
+
+    order.retain(|&index| live[index]);
+    order
+}
+
+struct PooledTexture {
+    desc: GraphTextureDesc,
+    view: wgpu::TextureView,
+    last_used: u64,
+}
+
+impl PooledTexture {
+    fn new(device: &wgpu::Device, name: &str, desc: GraphTextureDesc, frame: u64) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(name),
+            size: wgpu::Extent3d {
+                width: desc.width.max(1),
+                height: desc.height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: desc.format,
+            usage: desc.usage,
+            view_formats: &[],
+        });
+
+        Self {
+            desc,
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            last_used: frame,
+        }
+    }
+}
+
+#[derive(Default)]
+struct TexturePool {
+    transient: Vec<PooledTexture>,
+    persistent: HashMap<&'static str, PooledTexture>,
+}
+
+impl TexturePool {
+    // Views of every resource used this frame, and how many transients share memory
+    // with another one
+    fn allocate(
+        &mut self,
+        device: &wgpu::Device,
+        passes: &[Pass],
+        order: &[usize],
+        resources: &[Resource],
+        frame: u64,
+    ) -> (Vec<Option<wgpu::TextureView>>, usize) {
+        let mut views = vec![None; resources.len()];
+        let mut aliased = 0;
+
+        let pooled: Vec<_> = self.transient.iter().map(|texture| texture.desc).collect();
+        let mut used = vec![false; pooled.len()];
+        for (index, desc, slot) in assign_transients(passes, order, resources, &pooled) {
+            if slot == self.transient.len() {
+                let name = resources[index].name;
+                self.transient.push(PooledTexture::new(device, name, desc, frame));
+                used.push(false);
+            }
+            if std::mem::replace(&mut used[slot], true) {
+                aliased += 1;
+            }
+
+            let texture = &mut self.transient[slot];
+            texture.last_used = frame;
+            views[index] = Some(texture.view.clone());
+        }
+
+        for (index, resource) in resources.iter().enumerate() {
+            match &resource.kind {
+                ResourceKind::Imported(view) => views[index] = Some(view.clone()),
+                ResourceKind::Persistent { desc, .. } => {
+                    let texture = self
+                        .persistent
+                        .entry(resource.name)
+                        .or_insert_with(|| PooledTexture::new(device, resource.name, *desc, frame));
+                    if texture.desc != *desc {
+                        *texture = PooledTexture::new(device, resource.name, *desc, frame);
+                    }
+                    texture.last_used = frame;
+                    views[index] = Some(texture.view.clone());
+                }
+                ResourceKind::Transient(_) => {}
+            }
+        }
+
+        (views, aliased)
+    }
+
+    fn release_unused(&mut self, frame: u64) {
+        let kept = |texture: &PooledTexture| frame - texture.last_used < POOL_RETAIN_FRAMES;
+        self.transient.retain(kept);
+        self.persistent.retain(|_, texture| kept(texture));
+    }
+
+    fn len(&self) -> usize {
+        self.transient.len() + self.persistent.len()
+    }
+
+    fn memory_bytes(&self) -> u64 {
+        self.transient
+            .iter()
+            .chain(self.persistent.values())
+            .map(|texture| texture.desc.size_in_bytes())
+            .sum()
+    }
+}
+
+// The pool texture each transient used this frame gets: an index into `pooled` (the
+// descs of the pool's textures), or the next index past it for a new texture. Transients
+// go by first use and take a texture of their desc that no earlier transient still
+// uses, so textures whose lifetimes don't overlap share memory.
+fn assign_transients(
+    passes: &[Pass],
+    order: &[usize],
+    resources: &[Resource],
+    pooled: &[GraphTextureDesc],
+) -> Vec<(usize, GraphTextureDesc, usize)> {
+    // First and last position in `order` of every resource
+    let mut lifetimes: Vec<Option<(usize, usize)>> = vec![None; resources.len()];
+    for (position, &index) in order.iter().enumerate() {
+        let pass = &passes[index];
+        for texture in pass.reads.iter().chain(&pass.writes) {
+            let lifetime = &mut lifetimes[texture.0];
+            *lifetime = Some(lifetime.map_or((position, position), |(first, _)| (first, position)));
+        }
+    }
+
+    let mut transients: Vec<(usize, GraphTextureDesc, usize, usize)> = resources
+        .iter()
+        .enumerate()
+        .filter_map(|(index, resource)| match (&resource.kind, lifetimes[index]) {
+            (ResourceKind::Transient(desc), Some((first, last))) => Some((index, *desc, first, last)),
+            _ => None,
+        })
+        .collect();
+    transients.sort_by_key(|(_, _, first, _)| *first);
+
+    // Every slot's desc, and the position in `order` after which it is free again
+    let mut slots: Vec<(GraphTextureDesc, Option<usize>)> =
+        pooled.iter().map(|desc| (*desc, None)).collect();
+    transients
+        .into_iter()
+        .map(|(index, desc, first, last)| {
+            let free = slots.iter().position(|(slot_desc, busy_until)| {
+                *slot_desc == desc && busy_until.is_none_or(|until| until < first)
+            });
+            let slot = free.unwrap_or_else(|| {
+                slots.push((desc, None));
+                slots.len() - 1
+            });
+            slots[slot].1 = Some(last);
+            (index, desc, slot)
+        })
+        .collect()
+}
+
+// GPU time of the passes that used their timestamp writes, read back like the
+// exposure measurement: copied after the frame, mapped without blocking
+struct PassTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    // Nanoseconds per timestamp tick
+    period: f32,
+    // Names of the passes being read back, in query order; None for unused slots
+    in_flight: Option<Vec<Option<&'static str>>>,
+    copy_recorded: bool,
+    mapped: Arc<AtomicBool>,
+}
+
+impl PassTimer {
+    fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let size = MAX_TIMED_PASSES as u64 * 2 * std::mem::size_of::<u64>() as u64;
+
+        Self {
+            query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("Pass Timestamps"),
+                ty: wgpu::QueryType::Timestamp,
+                count: MAX_TIMED_PASSES * 2,
+            }),
+            resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Pass Timestamps Resolve"),
+                size,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            readback_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Pass Timestamps Readback"),
+                size,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            period: queue.get_timestamp_period(),
+            in_flight: None,
+            copy_recorded: false,
+            mapped: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    // Skipped while the previous frame's timings are still being read back
+    fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder, passes: Vec<Option<&'static str>>) {
+        if passes.iter().all(Option::is_none) || self.in_flight.is_some() {
+            return;
+        }
+
+        let queries = passes.len() as u32 * 2;
+        encoder.resolve_query_set(&self.query_set, 0..queries, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            queries as u64 * std::mem::size_of::<u64>() as u64,
+        );
+
+        self.in_flight = Some(passes);
+        self.copy_recorded = true;
+    }
+
+    fn request_readback(&mut self) {
+        if !std::mem::take(&mut self.copy_recorded) {
+            return;
+        }
+
+        let mapped = self.mapped.clone();
+        self.readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                if result.is_ok() {
+                    mapped.store(true, Ordering::Release);
+                }
+            });
+    }
+
+    // Milliseconds per pass, once the GPU has finished the frame that measured them
+    fn take_results(&mut self, device: &wgpu::Device) -> Option<Vec<(&'static str, f32)>> {
+        if self.in_flight.is_none() || self.copy_recorded {
+            return None;
+        }
+
+        let _ = device.poll(wgpu::PollType::Poll);
+        if !self.mapped.swap(false, Ordering::Acquire) {
+            return None;
+        }
+
+        let passes = self.in_flight.take().unwrap_or_default();
+        let timings = {
+            let data = self.readback_buffer.slice(..).get_mapped_range();
+            let ticks: &[u64] = bytemuck::cast_slice(&data);
+            passes
+                .iter()
+                .zip(ticks.chunks_exact(2))
+                .filter_map(|(name, pair)| {
+                    let nanoseconds = pair[1].saturating_sub(pair[0]) as f64 * self.period as f64;
+                    Some(((*name)?, (nanoseconds / 1_000_000.0) as f32))
+                })
+                .collect()
+        };
+        self.readback_buffer.unmap();
+
+        Some(timings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn desc() -> GraphTextureDesc {
+        GraphTextureDesc::target(64, 64, wgpu::TextureFormat::Rgba8Unorm)
+    }
+
+    fn transient(name: &'static str) -> Resource {
+        Resource {
+            name,
+            kind: ResourceKind::Transient(desc()),
+        }
+    }
+
+    fn persistent(name: &'static str) -> Resource {
+        Resource {
+            name,
+            kind: ResourceKind::Persistent {
+                desc: desc(),
+                reset: false,
+            },
+        }
+    }
+
+    fn names(passes: &[Pass], order: &[usize]) -> Vec<&'static str> {
+        order.iter().map(|&index| passes[index].name).collect()
+    }
+
+    #[test]
+    fn passes_nothing_reads_from_are_culled() {
+        let resources = [transient("Unread"), transient("Lit"), persistent("Output")];
+        let mut readback = pass("Readback", PassStage::PostProcess, &[], &[]);
+        readback.keep = true;
+        let passes = [
+            pass("Orphan", PassStage::Scene, &[], &[0]),
+            pass("Lighting", PassStage::Scene, &[], &[1]),
+            pass("Tonemap", PassStage::PostProcess, &[1], &[2]),
+            readback,
+            // Its transient is only read by a pass that is culled itself
+            pass("Unused Blur", PassStage::PostProcess, &[0], &[0]),
+        ];
+
+        let order = resolve_order(&passes, &resources).unwrap();
+        assert_eq!(names(&passes, &order), ["Lighting", "Tonemap", "Readback"]);
+    }
+
+    #[test]
+    fn passes_run_after_the_passes_they_depend_on() {
+        let resources = [
+            transient("Hdr"),
+            persistent("Output"),
+            persistent("History"),
+        ];
+        let passes = [
+            pass("Hud", PassStage::Ui, &[1], &[1]),
+            // Declared before the pass writing its input
+            pass("Tonemap", PassStage::Scene, &[0], &[1]),
+            pass("Picking", PassStage::Scene, &[], &[2]),
+            pass("Scene", PassStage::Scene, &[], &[0]),
+        ];
+
+        let order = resolve_order(&passes, &resources).unwrap();
+        assert_eq!(
+            names(&passes, &order),
+            ["Picking", "Scene", "Tonemap", "Hud"]
+        );
+    }
+
+    #[test]
+    fn persistent_textures_are_read_before_a_later_pass_rewrites_them() {
+        let resources = [persistent("History"), persistent("Output")];
+        let passes = [
+            pass("Resolve", PassStage::PostProcess, &[0], &[1]),
+            pass("History Copy", PassStage::PostProcess, &[1], &[0]),
+        ];
+
+        let order = resolve_order(&passes, &resources).unwrap();
+        assert_eq!(names(&passes, &order), ["Resolve", "History Copy"]);
+    }
+
+    #[test]
+    fn passes_needing_each_others_output_are_a_cycle() {
+        let resources = [transient("A"), transient("B"), persistent("Output")];
+        let passes = [
+            pass("Blur X", PassStage::PostProcess, &[0], &[1]),
+            pass("Blur Y", PassStage::PostProcess, &[1], &[0, 2]),
+        ];
+
+        let cycle = resolve_order(&passes, &resources).unwrap_err();
+        assert_eq!(cycle, PassCycle(vec!["Blur Y", "Blur X", "Blur Y"]));
+        assert_eq!(
+            cycle.to_string(),
+            "frame graph cycle Blur Y -> Blur X -> Blur Y"
+        );
+        // What the frame falls back to
+        let fallback = cull(stage_order(&passes), &passes, &resources);
+        assert_eq!(names(&passes, &fallback), ["Blur X", "Blur Y"]);
+    }
+
+    #[test]
+    fn transients_whose_lifetimes_dont_overlap_share_a_texture() {
+        let resources = [
+            transient("Half"),
+            transient("Quarter"),
+            transient("Half Again"),
+            persistent("Output"),
+        ];
+        let passes = [
+            pass("Down 1", PassStage::PostProcess, &[], &[0]),
+            pass("Down 2", PassStage::PostProcess, &[0], &[1]),
+            pass("Up 1", PassStage::PostProcess, &[1], &[2]),
+            pass("Up 2", PassStage::PostProcess, &[2], &[3]),
+        ];
+        let order = resolve_order(&passes, &resources).unwrap();
+
+        let slots = assign_transients(&passes, &order, &resources, &[]);
+        let slot_of = |texture| {
+            slots
+                .iter()
+                .find(|(index, ..)| *index == texture)
+                .unwrap()
+                .2
+        };
+        assert_eq!(slots.len(), 3);
+        // "Half" is done once "Down 2" has read it
+        assert_eq!(slot_of(2), slot_of(0));
+        assert_ne!(slot_of(1), slot_of(0));
+
+        // Next frame the pool already has both textures
+        let pooled = [desc(), desc()];
+        let slots = assign_transients(&passes, &order, &resources, &pooled);
+        assert!(slots.iter().all(|(_, _, slot)| *slot < pooled.len()));
+    }
+
+    #[test]
+    fn transients_whose_lifetimes_overlap_get_their_own_texture() {
+        let resources = [
+            transient("Near"),
+            transient("Far"),
+            Resource {
+                name: "Small",
+                kind: ResourceKind::Transient(GraphTextureDesc::target(
+                    32,
+                    32,
+                    wgpu::TextureFormat::Rgba8Unorm,
+                )),
+            },
+            persistent("Output"),
+        ];
+        let passes = [
+            pass("Split", PassStage::PostProcess, &[], &[0, 1]),
+            pass("Downsample", PassStage::PostProcess, &[0, 1], &[2]),
+            // "Near" and "Far" are free again, but of a different size
+            pass("Composite", PassStage::PostProcess, &[2], &[3]),
+        ];
+        let order = resolve_order(&passes, &resources).unwrap();
+
+        let slots = assign_transients(&passes, &order, &resources, &[]);
+        let mut used: Vec<_> = slots.iter().map(|(_, _, slot)| *slot).collect();
+        used.sort();
+        assert_eq!(used, [0, 1, 2]);
+    }
+
+    // Recording without a window: buffers to copy between, or a pipeline to draw with
+    struct TestContext {
+        device: wgpu::Device,
+        patterns: Vec<wgpu::Buffer>,
+        target: wgpu::Buffer,
+        draw: Option<(wgpu::RenderPipeline, wgpu::TextureView)>,
+    }
+
+    impl RecordingContext for TestContext {
+        fn device(&self) -> &wgpu::Device {
+            &self.device
+        }
+    }
+
+    const PATTERN_BYTES: u64 = 256;
+    // Each copy overlaps half of the previous one, so the result depends on their order
+    const COPY_STRIDE: u64 = PATTERN_BYTES / 2;
+    const COPIES: usize = 8;
+    // The serial pass between the two runs of parallel ones
+    const CLEAR_AFTER: usize = 4;
+    const CLEAR_BYTES: u64 = 64;
+    const TARGET_BYTES: u64 = COPIES as u64 * COPY_STRIDE + COPY_STRIDE;
+
+    fn serial_pass(
+        record: impl FnOnce(&mut PassContext<TestContext>) + 'static,
+    ) -> Pass<TestContext> {
+        let mut pass = pass("Serial", PassStage::Scene, &[], &[]);
+        pass.record = Some(Recorder::Serial(Box::new(record)));
+        pass
+    }
+
+    fn parallel_pass(
+        record: impl FnOnce(&mut ParallelPassContext<TestContext>) + Send + 'static,
+    ) -> Pass<TestContext> {
+        let mut pass = pass("Parallel", PassStage::Scene, &[], &[]);
+        pass.record = Some(Recorder::Parallel(Box::new(record)));
+        pass
+    }
+
+    fn pass<C>(name: &'static str, stage: PassStage, reads: &[usize], writes: &[usize]) -> Pass<C> {
+        Pass {
+            name,
+            stage,
+            reads: reads.iter().map(|&index| GraphTexture(index)).collect(),
+            writes: writes.iter().map(|&index| GraphTexture(index)).collect(),
+            keep: false,
+            record: None,
+        }
+    }
+
+    fn copy_passes() -> Vec<Pass<TestContext>> {
+        let mut passes: Vec<_> = (0..COPIES)
+            .map(|copy| {
+                parallel_pass(move |pass| {
+                    pass.encoder.copy_buffer_to_buffer(
+                        &pass.context.patterns[copy],
+                        0,
+                        &pass.context.target,
+                        copy as u64 * COPY_STRIDE,
+                        PATTERN_BYTES,
+                    );
+                })
+            })
+            .collect();
+        passes.insert(
+            CLEAR_AFTER,
+            serial_pass(|pass| {
+                pass.encoder
+                    .clear_buffer(&pass.context.target, 0, Some(CLEAR_BYTES));
+            }),
+        );
+        passes
+    }
+
+    // What the copies leave in the target when they run in declaration order
+    fn expected_copies() -> Vec<u8> {
+        let mut target = vec![0u8; TARGET_BYTES as usize];
+        for copy in 0..COPIES {
+            if copy == CLEAR_AFTER {
+                target[..CLEAR_BYTES as usize].fill(0);
+            }
+            let start = copy * COPY_STRIDE as usize;
+            target[start..start + PATTERN_BYTES as usize].fill(copy as u8 + 1);
+        }
+        target
+    }
+
+    fn test_context(device: &wgpu::Device) -> TestContext {
+        use wgpu::util::DeviceExt;
+
+        let patterns = (0..COPIES)
+            .map(|copy| {
+                device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Pattern"),
+                    contents: &[copy as u8 + 1; PATTERN_BYTES as usize],
+                    usage: wgpu::BufferUsages::COPY_SRC,
+                })
+            })
+            .collect();
+        TestContext {
+            device: device.clone(),
+            patterns,
+            target: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Target"),
+                size: TARGET_BYTES,
+                usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            draw: None,
+        }
+    }
+
+    // Records the passes, submits them and reads the target buffer back
+    fn record_and_read(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        context: &mut TestContext,
+        mut passes: Vec<Pass<TestContext>>,
+        parallel: bool,
+    ) -> (Vec<u8>, usize) {
+        let world = World::new();
+        let order: Vec<usize> = (0..passes.len()).collect();
+        let Recording {
+            mut command_buffers,
+            encoder,
+            parallel_passes,
+            ..
+        } = record_passes(
+            &mut passes,
+            &order,
+            &[],
+            None,
+            parallel,
+            WorldRef::from(&world),
+            context,
+        );
+        command_buffers.push(encoder.finish());
+
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Readback"),
+            size: TARGET_BYTES,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&Default::default());
+        encoder.copy_buffer_to_buffer(&context.target, 0, &readback, 0, TARGET_BYTES);
+        command_buffers.push(encoder.finish());
+        queue.submit(command_buffers);
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        readback
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result.is_ok());
+            });
+        let mapped = loop {
+            let _ = device.poll(wgpu::PollType::Poll);
+            match receiver.try_recv() {
+                Ok(mapped) => break mapped,
+                Err(std::sync::mpsc::TryRecvError::Empty) => std::thread::yield_now(),
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => break false,
+            }
+        };
+        assert!(mapped, "the readback failed to map");
+        let bytes = readback.slice(..).get_mapped_range().to_vec();
+        (bytes, parallel_passes)
+    }
+
+    #[test]
+    fn parallel_recording_gives_the_same_result_as_serial_recording() {
+        let Some((device, queue)) = crate::headless_device() else {
+            return;
+        };
+
+        let mut context = test_context(&device);
+        let (serial, serial_passes) =
+            record_and_read(&device, &queue, &mut context, copy_passes(), false);
+        let mut context = test_context(&device);
+        let (parallel, parallel_passes) =
+            record_and_read(&device, &queue, &mut context, copy_passes(), true);
+
+        assert_eq!(serial, expected_copies());
+        assert_eq!(parallel, serial);
+        assert_eq!(serial_passes, 0);
+        // Both runs around the serial pass went to the workers
+        assert_eq!(parallel_passes, COPIES);
+    }
+
+    // Recording time of 8 passes of 1000 draws each, serial against parallel. Timing
+    // depends on the machine, run with --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn parallel_recording_of_thousands_of_draws() {
+        const PASSES: usize = 8;
+        const DRAWS: u32 = 1000;
+        const FRAMES: u32 = 20;
+        let Some((device, queue)) = crate::headless_device() else {
+            return;
+        };
+
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Draw Test"),
+            source: wgpu::ShaderSource::Wgsl(
+                "@vertex fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+                     let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+                     return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+                 }
+                 @fragment fn fs_main() -> @location(0) vec4<f32> {
+                     return vec4<f32>(1.0, 0.5, 0.25, 1.0);
+                 }"
+                .into(),
+            ),
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            cache: None,
+            label: Some("Draw Test"),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(format.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        let target = device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("Draw Test"),
+                size: wgpu::Extent3d {
+                    width: 64,
+                    height: 64,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            })
+            .create_view(&Default::default());
+        let mut context = test_context(&device);
+        context.draw = Some((pipeline, target));
+
+        let draw_passes = || -> Vec<Pass<TestContext>> {
+            (0..PASSES)
+                .map(|_| {
+                    parallel_pass(|pass| {
+                        let (pipeline, target) = pass.context.draw.as_ref().unwrap();
+                        let mut render_pass =
+                            pass.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                                label: Some("Draws"),
+                                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                                    view: target,
+                                    resolve_target: None,
+                                    depth_slice: None,
+                                    ops: wgpu::Operations {
+                                        load: wgpu::LoadOp::Load,
+                                        store: wgpu::StoreOp::Store,
+                                    },
+                                })],
+                                ..Default::default()
+                            });
+                        render_pass.set_pipeline(pipeline);
+                        for draw in 0..DRAWS {
+                            render_pass.draw(0..3, draw..draw + 1);
+                        }
+                    })
+                })
+                .collect()
+        };
+
+        let world = World::new();
+        let order: Vec<usize> = (0..PASSES).collect();
+        let mut milliseconds = [0.0; 2];
+        for (parallel, total) in [false, true].into_iter().zip(&mut milliseconds) {
+            for _ in 0..FRAMES {
+                let mut passes = draw_passes();
+                let started = Instant::now();
+                let recording = record_passes(
+                    &mut passes,
+                    &order,
+                    &[],
+                    None,
+                    parallel,
+                    WorldRef::from(&world),
+                    &mut context,
+                );
+                *total += started.elapsed().as_secs_f32() * 1000.0 / FRAMES as f32;
+                queue.submit(
+                    recording
+                        .command_buffers
+                        .into_iter()
+                        .chain(Some(recording.encoder.finish())),
+                );
+            }
+        }
+
+        let [serial, parallel] = milliseconds;
+        println!(
+            "{} draws: serial {:.2} ms, parallel {:.2} ms ({} threads)",
+            PASSES as u32 * DRAWS,
+            serial,
+            parallel,
+            rayon::current_num_threads()
+        );
+        assert!(
+            parallel < serial,
+            "serial {} ms, parallel {} ms",
+            serial,
+            parallel
+        );
+    }
+}
@@ -0,0 +1,486 @@
+use std::{collections::HashMap, time::Instant};
+
+use catalyst_core::{
+    App, bounds::Aabb, camera::Camera, physics::ColliderDefinition, pipeline::PhaseRenderPrepare,
+    transform::GlobalTransform,
+};
+use catalyst_window::FrameStats;
+use flecs_ecs::prelude::*;
+use glam::{Mat4, Vec3};
+use rayon::prelude::*;
+
+use crate::{
+    material::AssetMaterial,
+    mesh::{AssetMesh, MeshBounds, MeshInstance},
+    reflection::PlanarReflections,
+    reflection_probe::ReflectionProbes,
+    render_order::{RenderBucket, RenderOrder},
+    static_batching::StaticBatched,
+    visibility::{Frustum, ViewFrusta},
+};
+
+// Entities tested per rayon job
+const CHUNK_SIZE: usize = 1024;
+
+/// On mesh entities outside every view this frame, mirrored views of planar reflections
+/// and reflection probe captures included. The mesh queries skip them.
+#[derive(Component)]
+pub struct FrustumCulled;
+
+/// Frustum culling of meshes, in PhaseRenderPrepare. The bounds of every mesh entity
+/// are kept in a flat snapshot, recomputed only for the entities whose transform, mesh
+/// or bucket changed, and tested against the views in chunks on the rayon pool.
+/// FrustumCulled tags only change when an entity's visibility does.
+#[derive(Component, Clone, Debug)]
+pub struct FrustumCulling {
+    /// Off draws everything. Read every frame.
+    pub enabled: bool,
+    /// Off tests the chunks one after the other on this thread, for comparison.
+    pub parallel: bool,
+    snapshot: CullSnapshot,
+    visible: Vec<(DrawKey, Entity)>,
+    culled: usize,
+    resnapshotted: usize,
+}
+
+impl Default for FrustumCulling {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            parallel: true,
+            snapshot: CullSnapshot::default(),
+            visible: Vec::new(),
+            culled: 0,
+            resnapshotted: 0,
+        }
+    }
+}
+
+impl FrustumCulling {
+    /// Entities visible this frame in draw order: by bucket, material and mesh. Empty
+    /// while culling is off.
+    pub fn visible_entities(&self) -> &[(DrawKey, Entity)] {
+        &self.visible
+    }
+
+    /// Mesh entities tested this frame.
+    pub fn tested_entities(&self) -> usize {
+        self.snapshot.len()
+    }
+
+    /// Entities not drawn this frame because no view sees them.
+    pub fn culled_entities(&self) -> usize {
+        self.culled
+    }
+
+    /// Entities whose bounds were recomputed this frame.
+    pub fn resnapshotted_entities(&self) -> usize {
+        self.resnapshotted
+    }
+}
+
+/// Order of a draw: bucket first, then what the draws of a bucket are batched by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DrawKey {
+    pub bucket: RenderBucket,
+    pub material: Entity,
+    pub mesh: Entity,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct CullEntry {
+    entity: Entity,
+    key: DrawKey,
+    model: Mat4,
+    local_bounds: Aabb,
+    // World-space bounding sphere
+    center: Vec3,
+    radius: f32,
+    visible: bool,
+    // Frame the entity was last seen in, entries not seen are dropped
+    frame: u64,
+}
+
+/// The visible entities of one `CullSnapshot::cull`, and the ones whose visibility
+/// changed since the last.
+#[derive(Clone, Debug, Default)]
+pub struct CullResult {
+    /// Sorted by key, then entity, whatever the order of the snapshot or the chunks.
+    pub visible: Vec<(DrawKey, Entity)>,
+    pub changed: Vec<(Entity, bool)>,
+}
+
+/// World-space bounds of every entity to cull, in a flat array. New entities count as
+/// visible until their first `cull`.
+#[derive(Clone, Debug, Default)]
+pub struct CullSnapshot {
+    entries: Vec<CullEntry>,
+    index: HashMap<Entity, usize>,
+    frame: u64,
+}
+
+impl CullSnapshot {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Starts a frame of `update`s.
+    pub fn begin(&mut self) {
+        self.frame += 1;
+    }
+
+    /// Records an entity for this frame. Returns whether its bounds were recomputed,
+    /// i.e. whether it is new or its transform, bounds or key changed.
+    pub fn update(
+        &mut self,
+        entity: Entity,
+        key: DrawKey,
+        model: &Mat4,
+        local_bounds: &Aabb,
+    ) -> bool {
+        if let Some(&index) = self.index.get(&entity) {
+            let entry = &mut self.entries[index];
+            entry.frame = self.frame;
+            if entry.model == *model && entry.local_bounds == *local_bounds && entry.key == key {
+                return false;
+            }
+            entry.key = key;
+            entry.model = *model;
+            entry.local_bounds = *local_bounds;
+            (entry.center, entry.radius) = bounding_sphere(model, local_bounds);
+            return true;
+        }
+
+        let (center, radius) = bounding_sphere(model, local_bounds);
+        self.index.insert(entity, self.entries.len());
+        self.entries.push(CullEntry {
+            entity,
+            key,
+            model: *model,
+            local_bounds: *local_bounds,
+            center,
+            radius,
+            visible: true,
+            frame: self.frame,
+        });
+        true
+    }
+
+    /// Drops the entities not updated since `begin`. Returns the ones among them that
+    /// were culled, whose tag has to go.
+    pub fn end(&mut self) -> Vec<Entity> {
+        let mut hidden = Vec::new();
+        let mut index = 0;
+        while index < self.entries.len() {
+            let entry = self.entries[index];
+            if entry.frame == self.frame {
+                index += 1;
+                continue;
+            }
+            if !entry.visible {
+                hidden.push(entry.entity);
+            }
+            self.index.remove(&entry.entity);
+            self.entries.swap_remove(index);
+            if let Some(moved) = self.entries.get(index) {
+                self.index.insert(moved.entity, index);
+            }
+        }
+        hidden
+    }
+
+    /// Tests every entity against `frusta`: visible when inside any of them. With
+    /// `parallel` the chunks go to the rayon pool; the result is the same either way.
+    pub fn cull(&mut self, frusta: &[Frustum], parallel: bool) -> CullResult {
+        let test = |chunk: &mut [CullEntry]| {
+            let mut result = CullResult::default();
+            for entry in chunk {
+                let visible = frusta
+                    .iter()
+                    .any(|frustum| frustum.intersects_sphere(entry.center, entry.radius));
+                if visible != entry.visible {
+                    entry.visible = visible;
+                    result.changed.push((entry.entity, visible));
+                }
+                if visible {
+                    result.visible.push((entry.key, entry.entity));
+                }
+            }
+            result
+        };
+
+        // Per-chunk lists, merged in chunk order
+        let chunks: Vec<CullResult> = if parallel {
+            self.entries.par_chunks_mut(CHUNK_SIZE).map(test).collect()
+        } else {
+            self.entries.chunks_mut(CHUNK_SIZE).map(test).collect()
+        };
+        let mut result = CullResult::default();
+        for chunk in chunks {
+            result.visible.extend(chunk.visible);
+            result.changed.extend(chunk.changed);
+        }
+
+        // Entities are unique, so the order is total
+        if parallel {
+            result.visible.par_sort_unstable();
+        } else {
+            result.visible.sort_unstable();
+        }
+        result
+    }
+
+    /// Marks every entity visible. Returns the ones that were culled.
+    pub fn show_all(&mut self) -> Vec<Entity> {
+        let mut shown = Vec::new();
+        for entry in &mut self.entries {
+            if !entry.visible {
+                entry.visible = true;
+                shown.push(entry.entity);
+            }
+        }
+        shown
+    }
+}
+
+fn bounding_sphere(model: &Mat4, local_bounds: &Aabb) -> (Vec3, f32) {
+    let center = model.transform_point3(local_bounds.center());
+    let (scale, _, _) = model.to_scale_rotation_translation();
+    let radius = local_bounds.half_extents().length() * scale.abs().max_element();
+    (center, radius)
+}
+
+pub fn register_frustum_culling_systems(app: &mut App) {
+    app.register_singleton_default::<FrustumCulling>();
+    app.world.component::<FrustumCulled>();
+
+    // Same entities as the mesh queries of "Render Frame", culled or not
+    let unordered_query = app
+        .world
+        .query::<(&MeshInstance, &GlobalTransform)>()
+        .with((AssetMesh, flecs::Wildcard))
+        .with((AssetMaterial, flecs::Wildcard))
+        .without(RenderOrder::id())
+        .without(Camera::id())
+        .without(ColliderDefinition::id())
+        .without(StaticBatched::id())
+        .set_cached()
+        .build();
+
+    let ordered_query = app
+        .world
+        .query::<(&MeshInstance, &GlobalTransform, &RenderOrder)>()
+        .with((AssetMesh, flecs::Wildcard))
+        .with((AssetMaterial, flecs::Wildcard))
+        .without(Camera::id())
+        .without(ColliderDefinition::id())
+        .set_cached()
+        .build();
+
+    // After "Prepare Planar Reflections" and "Prepare Reflection Probes" (same phase,
+    // registered first), so the reflections and the capture drawn this frame are known
+    app.world
+        .system_named::<(
+            &mut FrustumCulling,
+            &ViewFrusta,
+            &PlanarReflections,
+            &ReflectionProbes,
+            &mut FrameStats,
+        )>("Frustum Culling")
+        .kind(PhaseRenderPrepare)
+        .each_iter(
+            move |iter, _, (culling, frusta, reflections, probes, frame_stats)| {
+                catalyst_core::profile_scope!("Frustum Culling");
+                let started = Instant::now();
+                let world = iter.world();
+
+                // Bounds of each mesh asset, looked up once per frame
+                let mut mesh_bounds: HashMap<Entity, Option<Aabb>> = HashMap::new();
+                let mut bounds_of = |mesh: Entity| {
+                    *mesh_bounds.entry(mesh).or_insert_with(|| {
+                        world
+                            .entity_from_id(mesh)
+                            .try_get::<&MeshBounds>(|bounds| bounds.0)
+                    })
+                };
+
+                let snapshot = &mut culling.snapshot;
+                let mut resnapshotted = 0;
+                snapshot.begin();
+                // Meshes still loading have no bounds yet and are never culled
+                unordered_query.each_iter(|iter, index, (_, global)| {
+                    let mesh = iter.pair(2).second_id().id();
+                    let material = iter.pair(3).second_id().id();
+                    if let Some(bounds) = bounds_of(mesh) {
+                        let key = DrawKey {
+                            bucket: RenderBucket::Opaque,
+                            material,
+                            mesh,
+                        };
+                        resnapshotted +=
+                            snapshot.update(iter.entity(index).id(), key, &global.0, &bounds)
+                                as usize;
+                    }
+                });
+                ordered_query.each_iter(|iter, index, (_, global, order)| {
+                    let mesh = iter.pair(3).second_id().id();
+                    let material = iter.pair(4).second_id().id();
+                    if let Some(bounds) = bounds_of(mesh) {
+                        let key = DrawKey {
+                            bucket: order.bucket,
+                            material,
+                            mesh,
+                        };
+                        resnapshotted +=
+                            snapshot.update(iter.entity(index).id(), key, &global.0, &bounds)
+                                as usize;
+                    }
+                });
+                let mut changed: Vec<(Entity, bool)> = snapshot
+                    .end()
+                    .into_iter()
+                    .map(|entity| (entity, true))
+                    .collect();
+                culling.resnapshotted = resnapshotted;
+
+                // No views (no camera yet) draws nothing anyway: keep the tags as they are
+                if !culling.enabled {
+                    changed.extend(snapshot.show_all().into_iter().map(|entity| (entity, true)));
+                    culling.visible.clear();
+                    culling.culled = 0;
+                } else if !frusta.0.is_empty() {
+                    let mut views: Vec<Frustum> =
+                        frusta.0.iter().map(|view| view.frustum).collect();
+                    for view in &frusta.0 {
+                        views.extend(reflections.mirrored_frusta(view.view_proj));
+                    }
+                    views.extend(probes.capture_frusta());
+                    let result = snapshot.cull(&views, culling.parallel);
+                    changed.extend(result.changed);
+                    culling.culled = snapshot.len() - result.visible.len();
+                    culling.visible = result.visible;
+                }
+
+                for (entity, visible) in changed {
+                    let entity = world.entity_from_id(entity);
+                    if !entity.is_alive() {
+                        continue;
+                    }
+                    if visible {
+                        entity.remove(FrustumCulled);
+                    } else {
+                        entity.add(FrustumCulled);
+                    }
+                }
+
+                frame_stats.culling_ms = started.elapsed().as_secs_f32() * 1000.0;
+            },
+        );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENTITIES: u64 = 100_000;
+    // One mesh in MOVING_EVERY jumps high above the cameras
+    const MOVING_EVERY: u64 = 100;
+    // Out of the way of real entity ids
+    const FIRST_ID: u64 = 1 << 32;
+
+    fn key(index: u64) -> DrawKey {
+        let bucket = match index % 10 {
+            0 => RenderBucket::Transparent,
+            1 => RenderBucket::Overlay,
+            _ => RenderBucket::Opaque,
+        };
+        DrawKey {
+            bucket,
+            material: Entity::new(FIRST_ID + ENTITIES + index % 7),
+            mesh: Entity::new(FIRST_ID + ENTITIES + 100 + index % 13),
+        }
+    }
+
+    // A 316 x 316 grid of unit cubes around the origin
+    fn model(index: u64) -> Mat4 {
+        let side = (ENTITIES as f64).sqrt().ceil() as u64;
+        let (row, column) = (index / side, index % side);
+        Mat4::from_translation(Vec3::new(
+            column as f32 * 2.0 - side as f32,
+            0.0,
+            row as f32 * 2.0 - side as f32,
+        ))
+    }
+
+    // Returns how many meshes got their bounds recomputed
+    fn fill(snapshot: &mut CullSnapshot, moved: bool) -> usize {
+        let bounds = Aabb::new(Vec3::splat(-0.5), Vec3::splat(0.5));
+        snapshot.begin();
+        let mut recomputed = 0;
+        for index in 0..ENTITIES {
+            let mut model = model(index);
+            if moved && index % MOVING_EVERY == 0 {
+                model = Mat4::from_translation(Vec3::new(0.0, 10_000.0, 0.0)) * model;
+            }
+            let entity = Entity::new(FIRST_ID + index);
+            recomputed += snapshot.update(entity, key(index), &model, &bounds) as usize;
+        }
+        snapshot.end();
+        recomputed
+    }
+
+    // Two split-screen views looking down at the grid from opposite corners
+    fn frusta() -> [Frustum; 2] {
+        let projection = Mat4::perspective_rh(60f32.to_radians(), 16.0 / 9.0, 0.1, 1000.0);
+        [
+            Vec3::new(-200.0, 80.0, -200.0),
+            Vec3::new(200.0, 80.0, 200.0),
+        ]
+        .map(|eye| Frustum::from_view_proj(projection * Mat4::look_at_rh(eye, Vec3::ZERO, Vec3::Y)))
+    }
+
+    #[test]
+    fn serial_and_parallel_give_the_same_draw_order() {
+        let frusta = frusta();
+        let mut snapshot = CullSnapshot::default();
+        assert_eq!(fill(&mut snapshot, false), ENTITIES as usize);
+
+        let serial = snapshot.clone().cull(&frusta, false);
+        let parallel = snapshot.cull(&frusta, true);
+        assert!(!serial.visible.is_empty() && serial.visible.len() < ENTITIES as usize);
+        assert_eq!(serial.visible, parallel.visible);
+        // Sorted by bucket, material, mesh and entity
+        assert!(serial.visible.is_sorted());
+        // New meshes start visible, so the culled ones are reported
+        assert_eq!(
+            serial.changed.len(),
+            ENTITIES as usize - serial.visible.len()
+        );
+    }
+
+    #[test]
+    fn only_moved_meshes_are_recomputed_and_reported() {
+        let frusta = frusta();
+        let mut snapshot = CullSnapshot::default();
+        fill(&mut snapshot, false);
+        let visible = snapshot.cull(&frusta, true).visible.len();
+
+        assert_eq!(fill(&mut snapshot, false), 0);
+        assert!(snapshot.cull(&frusta, true).changed.is_empty());
+
+        assert_eq!(
+            fill(&mut snapshot, true),
+            (ENTITIES / MOVING_EVERY) as usize
+        );
+        let result = snapshot.cull(&frusta, true);
+        for (entity, visible) in &result.changed {
+            assert!(!visible);
+            assert_eq!((entity.0 - FIRST_ID) % MOVING_EVERY, 0);
+        }
+        assert_eq!(result.visible.len(), visible - result.changed.len());
+    }
+}
@@ -0,0 +1,452 @@
+use std::{ops::Range, sync::Mutex};
+
+use crate::mesh::Vertex;
+
+// Size of a block's buffers. A mesh bigger than a block gets a block of its own.
+const BLOCK_VERTEX_BYTES: u64 = 32 * 1024 * 1024;
+const BLOCK_INDEX_BYTES: u64 = 16 * 1024 * 1024;
+
+const VERTEX_SIZE: u64 = std::mem::size_of::<Vertex>() as u64;
+
+// Frames a freed range waits before it is handed out again: the draws collected
+// this frame still point at it until they are submitted
+const FREE_DELAY: u32 = 2;
+
+/// First fit free-list suballocator over `0..size` elements. Freed ranges merge
+/// with their free neighbours, so a block emptied in any order is one range again.
+#[derive(Debug)]
+struct RangeAllocator {
+    size: u32,
+    // Sorted by start, never adjacent (they would have been merged)
+    free: Vec<Range<u32>>,
+}
+
+impl RangeAllocator {
+    fn new(size: u32) -> Self {
+        Self {
+            size,
+            free: vec![0..size],
+        }
+    }
+
+    /// Empty requests get an empty range and leave the free list alone.
+    fn allocate(&mut self, count: u32) -> Option<Range<u32>> {
+        if count == 0 {
+            return Some(0..0);
+        }
+        let index = self
+            .free
+            .iter()
+            .position(|range| range.end - range.start >= count)?;
+        let start = self.free[index].start;
+        self.free[index].start += count;
+        if self.free[index].is_empty() {
+            self.free.remove(index);
+        }
+        Some(start..start + count)
+    }
+
+    fn free(&mut self, range: Range<u32>) {
+        if range.is_empty() {
+            return;
+        }
+        let index = self.free.partition_point(|free| free.start < range.start);
+        let merges_next = self.free.get(index).is_some_and(|next| next.start == range.end);
+        let merges_previous = index > 0 && self.free[index - 1].end == range.start;
+
+        match (merges_previous, merges_next) {
+            (true, true) => {
+                self.free[index - 1].end = self.free[index].end;
+                self.free.remove(index);
+            }
+            (true, false) => self.free[index - 1].end = range.end,
+            (false, true) => self.free[index].start = range.start,
+            (false, false) => self.free.insert(index, range),
+        }
+    }
+
+    fn used(&self) -> u32 {
+        self.size - self.free.iter().map(|range| range.end - range.start).sum::<u32>()
+    }
+
+    fn largest_free(&self) -> u32 {
+        self.free.iter().map(|range| range.end - range.start).max().unwrap_or(0)
+    }
+}
+
+/// Where a mesh lives in the pool: a block and its ranges of vertices and indices,
+/// in elements. Indices are local to the mesh, draws add `vertices.start` as the
+/// base vertex.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GeometryAllocation {
+    pub block: u32,
+    pub vertices: Range<u32>,
+    pub indices: Range<u32>,
+    /// Every index in the block has this width.
+    pub index_format: wgpu::IndexFormat,
+}
+
+struct PoolBlock {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    index_format: wgpu::IndexFormat,
+    vertices: RangeAllocator,
+    indices: RangeAllocator,
+    allocations: usize,
+}
+
+#[derive(Default)]
+struct PoolState {
+    // Indexed by block id; released blocks leave a hole so ids stay valid
+    blocks: Vec<Option<PoolBlock>>,
+    // Freed allocations and the frames they still wait
+    retired: Vec<(GeometryAllocation, u32)>,
+}
+
+/// Fill of the geometry pool, copied into RenderStats every frame.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GeometryPoolStats {
+    pub blocks: usize,
+    pub allocations: usize,
+    /// Size of every block's buffers.
+    pub capacity_bytes: u64,
+    /// Bytes of those held by meshes.
+    pub used_bytes: u64,
+    /// Free ranges over all blocks; many small ones mean fragmentation.
+    pub free_ranges: usize,
+    /// Biggest mesh (in vertex bytes) that fits without a new block.
+    pub largest_free_vertex_bytes: u64,
+    /// Blocks of 16-bit indices, of `blocks`.
+    pub u16_blocks: usize,
+}
+
+impl GeometryPoolStats {
+    pub fn utilization(&self) -> f32 {
+        if self.capacity_bytes == 0 {
+            return 0.0;
+        }
+        self.used_bytes as f32 / self.capacity_bytes as f32
+    }
+}
+
+/// Vertex and index data of every mesh, suballocated from a few large buffers so
+/// meshes sharing a block are drawn without rebinding buffers. A block holds either
+/// 16 or 32-bit indices, so a mesh's block also decides its IndexFormat.
+///
+/// Allocations are addressed by block and offset only; a compacting defragmenter
+/// can move a block's live ranges to its start with copy_buffer_to_buffer and
+/// rewrite the GpuGeometry pointing at them (`sparse_blocks` finds the candidates).
+#[derive(Default)]
+pub struct GeometryPool {
+    state: Mutex<PoolState>,
+}
+
+impl GeometryPool {
+    /// Reserves room for a mesh, in a new block if none of the others with
+    /// `index_format` has it.
+    pub fn allocate(
+        &self,
+        device: &wgpu::Device,
+        vertex_count: u32,
+        index_count: u32,
+        index_format: wgpu::IndexFormat,
+    ) -> GeometryAllocation {
+        let mut state = self.state.lock().expect("geometry pool poisoned");
+
+        // Buffer writes go by 4 bytes: 16-bit ranges are kept to even lengths, so they
+        // all start and end aligned
+        let index_count = match index_format {
+            wgpu::IndexFormat::Uint16 => index_count.next_multiple_of(2),
+            wgpu::IndexFormat::Uint32 => index_count,
+        };
+        let fits = |block: &PoolBlock| {
+            block.index_format == index_format
+                && block.vertices.largest_free() >= vertex_count
+                && block.indices.largest_free() >= index_count
+        };
+        let id = match state
+            .blocks
+            .iter()
+            .position(|block| block.as_ref().is_some_and(fits))
+        {
+            Some(id) => id,
+            None => {
+                let block = create_block(device, vertex_count, index_count, index_format);
+                match state.blocks.iter().position(Option::is_none) {
+                    Some(hole) => {
+                        state.blocks[hole] = Some(block);
+                        hole
+                    }
+                    None => {
+                        state.blocks.push(Some(block));
+                        state.blocks.len() - 1
+                    }
+                }
+            }
+        };
+
+        let block = state.blocks[id].as_mut().expect("block just found");
+        let vertices = block.vertices.allocate(vertex_count).expect("checked by fits");
+        let indices = block.indices.allocate(index_count).expect("checked by fits");
+        block.allocations += 1;
+
+        GeometryAllocation {
+            block: id as u32,
+            vertices,
+            indices,
+            index_format,
+        }
+    }
+
+    /// Writes a mesh's data at its allocation, through the queue's staging belt, with
+    /// the indices narrowed to the allocation's format. The data may be smaller than
+    /// the allocation.
+    pub fn write(
+        &self,
+        queue: &wgpu::Queue,
+        allocation: &GeometryAllocation,
+        vertex_bytes: &[u8],
+        indices: &[u32],
+    ) {
+        let Some((vertex_buffer, index_buffer)) = self.buffers(allocation.block) else {
+            return;
+        };
+        let narrowed: Vec<u16>;
+        let index_bytes: &[u8] = match allocation.index_format {
+            wgpu::IndexFormat::Uint16 => {
+                // Padded with a 0 to an even count, never drawn
+                narrowed = indices
+                    .iter()
+                    .map(|&index| index as u16)
+                    .chain((indices.len() % 2 == 1).then_some(0))
+                    .collect();
+                bytemuck::cast_slice(&narrowed)
+            }
+            wgpu::IndexFormat::Uint32 => bytemuck::cast_slice(indices),
+        };
+        if !vertex_bytes.is_empty() {
+            queue.write_buffer(
+                &vertex_buffer,
+                allocation.vertices.start as u64 * VERTEX_SIZE,
+                vertex_bytes,
+            );
+        }
+        if !index_bytes.is_empty() {
+            queue.write_buffer(
+                &index_buffer,
+                allocation.indices.start as u64 * index_size(allocation.index_format),
+                index_bytes,
+            );
+        }
+    }
+
+    /// The vertex and index buffers of a block.
+    pub fn buffers(&self, block: u32) -> Option<(wgpu::Buffer, wgpu::Buffer)> {
+        let state = self.state.lock().expect("geometry pool poisoned");
+        let block = state.blocks.get(block as usize)?.as_ref()?;
+        Some((block.vertex_buffer.clone(), block.index_buffer.clone()))
+    }
+
+    /// Returns an allocation's ranges, reused FREE_DELAY frames later.
+    pub fn free(&self, allocation: GeometryAllocation) {
+        let mut state = self.state.lock().expect("geometry pool poisoned");
+        state.retired.push((allocation, FREE_DELAY));
+    }
+
+    /// Ages the freed allocations, returning those old enough to their blocks. Blocks
+    /// left empty are released, except the first one.
+    pub fn end_frame(&self) {
+        let mut state = self.state.lock().expect("geometry pool poisoned");
+        let PoolState { blocks, retired } = &mut *state;
+
+        retired.retain_mut(|(allocation, frames)| {
+            if *frames > 0 {
+                *frames -= 1;
+                return true;
+            }
+            if let Some(Some(block)) = blocks.get_mut(allocation.block as usize) {
+                block.vertices.free(allocation.vertices.clone());
+                block.indices.free(allocation.indices.clone());
+                block.allocations -= 1;
+            }
+            false
+        });
+
+        for block in blocks.iter_mut().skip(1) {
+            if block.as_ref().is_some_and(|block| block.allocations == 0) {
+                *block = None;
+            }
+        }
+    }
+
+    pub fn stats(&self) -> GeometryPoolStats {
+        let state = self.state.lock().expect("geometry pool poisoned");
+        let mut stats = GeometryPoolStats::default();
+        for block in state.blocks.iter().flatten() {
+            stats.blocks += 1;
+            stats.allocations += block.allocations;
+            stats.capacity_bytes += block.vertex_buffer.size() + block.index_buffer.size();
+            stats.used_bytes += block.vertices.used() as u64 * VERTEX_SIZE
+                + block.indices.used() as u64 * index_size(block.index_format);
+            if block.index_format == wgpu::IndexFormat::Uint16 {
+                stats.u16_blocks += 1;
+            }
+            stats.free_ranges += block.vertices.free.len() + block.indices.free.len();
+            stats.largest_free_vertex_bytes = stats
+                .largest_free_vertex_bytes
+                .max(block.vertices.largest_free() as u64 * VERTEX_SIZE);
+        }
+        stats
+    }
+
+    /// Blocks whose vertex ranges are less than `utilization` used, e.g. to compact.
+    pub fn sparse_blocks(&self, utilization: f32) -> Vec<u32> {
+        let state = self.state.lock().expect("geometry pool poisoned");
+        state
+            .blocks
+            .iter()
+            .enumerate()
+            .filter_map(|(id, block)| {
+                let block = block.as_ref()?;
+                let used = block.vertices.used() as f32 / block.vertices.size.max(1) as f32;
+                (used < utilization).then_some(id as u32)
+            })
+            .collect()
+    }
+}
+
+fn index_size(format: wgpu::IndexFormat) -> u64 {
+    match format {
+        wgpu::IndexFormat::Uint16 => 2,
+        wgpu::IndexFormat::Uint32 => 4,
+    }
+}
+
+fn create_block(
+    device: &wgpu::Device,
+    vertex_count: u32,
+    index_count: u32,
+    index_format: wgpu::IndexFormat,
+) -> PoolBlock {
+    let index_size = index_size(index_format);
+    let vertex_bytes = BLOCK_VERTEX_BYTES.max(vertex_count as u64 * VERTEX_SIZE);
+    let index_bytes = BLOCK_INDEX_BYTES.max(index_count as u64 * index_size);
+
+    // COPY_SRC for compaction, COPY_DST for the uploads
+    let usage = wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC;
+    let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Geometry Pool Vertex Buffer"),
+        size: vertex_bytes,
+        usage: wgpu::BufferUsages::VERTEX | usage,
+        mapped_at_creation: false,
+    });
+    let index_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Geometry Pool Index Buffer"),
+        size: index_bytes,
+        usage: wgpu::BufferUsages::INDEX | usage,
+        mapped_at_creation: false,
+    });
+
+    PoolBlock {
+        vertex_buffer,
+        index_buffer,
+        index_format,
+        vertices: RangeAllocator::new((vertex_bytes / VERTEX_SIZE) as u32),
+        indices: RangeAllocator::new((index_bytes / index_size) as u32),
+        allocations: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocations_take_the_first_range_that_fits() {
+        let mut allocator = RangeAllocator::new(100);
+        assert_eq!(allocator.allocate(10), Some(0..10));
+        assert_eq!(allocator.allocate(20), Some(10..30));
+        assert_eq!(allocator.allocate(30), Some(30..60));
+
+        // Holes of 10 at the start and 30 in the middle, 40 at the end
+        allocator.free(0..10);
+        allocator.free(30..60);
+        assert_eq!(allocator.allocate(25), Some(30..55));
+        assert_eq!(allocator.allocate(8), Some(0..8));
+        assert_eq!(allocator.allocate(40), Some(60..100));
+        assert_eq!(allocator.used(), 93);
+        assert_eq!(allocator.largest_free(), 5);
+    }
+
+    #[test]
+    fn freed_ranges_merge_with_their_neighbours() {
+        let mut allocator = RangeAllocator::new(40);
+        let ranges: Vec<_> = (0..4).map(|_| allocator.allocate(10).unwrap()).collect();
+        assert!(allocator.free.is_empty());
+
+        allocator.free(ranges[0].clone());
+        allocator.free(ranges[2].clone());
+        assert_eq!(allocator.free, [0..10, 20..30]);
+        // Between two free ranges: all three become one
+        allocator.free(ranges[1].clone());
+        assert_eq!(allocator.free, [0..30]);
+        allocator.free(ranges[3].clone());
+        assert_eq!(allocator.free, [0..40]);
+        assert_eq!(allocator.used(), 0);
+    }
+
+    #[test]
+    fn a_freed_range_is_handed_out_again() {
+        let mut allocator = RangeAllocator::new(30);
+        let first = allocator.allocate(10).unwrap();
+        allocator.allocate(20).unwrap();
+        assert_eq!(allocator.allocate(10), None);
+
+        allocator.free(first.clone());
+        assert_eq!(allocator.allocate(10), Some(first));
+        assert_eq!(allocator.used(), 30);
+    }
+
+    #[test]
+    fn zero_sized_requests_get_an_empty_range() {
+        let mut allocator = RangeAllocator::new(10);
+        assert_eq!(allocator.allocate(10), Some(0..10));
+        assert!(allocator.free.is_empty());
+
+        let empty = allocator.allocate(0).unwrap();
+        assert!(empty.is_empty());
+        allocator.free(empty);
+        assert!(allocator.free.is_empty());
+        assert_eq!(allocator.used(), 10);
+    }
+
+    #[test]
+    fn the_pool_grows_a_block_when_the_others_are_full() {
+        let Some((device, _queue)) = crate::headless_device() else {
+            return;
+        };
+        let pool = GeometryPool::default();
+        let block_vertices = (BLOCK_VERTEX_BYTES / VERTEX_SIZE) as u32;
+        let format = wgpu::IndexFormat::Uint32;
+
+        let first = pool.allocate(&device, block_vertices, 6, format);
+        let second = pool.allocate(&device, 3, 3, format);
+        assert_eq!((first.block, second.block), (0, 1));
+        // Blocks hold one index format
+        let narrow = pool.allocate(&device, 3, 3, wgpu::IndexFormat::Uint16);
+        assert_eq!(narrow.block, 2);
+        assert_eq!(narrow.indices, 0..4);
+        assert_eq!(pool.stats().blocks, 3);
+
+        // Empty blocks past the first are released once their ranges come back
+        pool.free(second);
+        pool.free(narrow);
+        for _ in 0..FREE_DELAY {
+            pool.end_frame();
+            assert_eq!(pool.stats().blocks, 3);
+        }
+        pool.end_frame();
+        assert_eq!(pool.stats().blocks, 1);
+        assert_eq!(pool.allocate(&device, 3, 3, format).block, 1);
+    }
+}
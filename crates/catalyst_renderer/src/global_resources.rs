@@ -1,10 +1,28 @@
 use glam::{Mat4, Vec3};
 use wgpu::util::DeviceExt;
 
+use crate::{
+    clusters::{
+        CLUSTER_COUNT, ClusterGrid, ClusterUniform, LightingMode, MAX_LIGHT_INDICES, MAX_LIGHTS,
+    },
+    layout::{StructLayout, rust_layout},
+    reflection_probe::ProbeTextures,
+    spot_light::Gobos,
+    viewport::Viewport,
+    wind::WindUniforms,
+};
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct CameraUniform {
-    pub view_proj: [[f32; 4]; 4], // View-Projection matrix
+    pub view_proj: [[f32; 4]; 4], // View-Projection matrix (jittered when TAA is on)
+    pub unjittered_view_proj: [[f32; 4]; 4], // Same without jitter, for motion vectors
+    pub prev_view_proj: [[f32; 4]; 4], // Last frame's unjittered View-Projection
+    pub exposure: f32,                 // Multiplier applied before tone mapping
+    pub _padding0: f32,
+    pub _padding1: [f32; 2],
+    pub wind: [f32; 4],      // .xy = horizontal wind, .z = wind clock, see WindSettings
+    pub prev_wind: [f32; 4], // Last frame's, for motion vectors
 }
 
 #[repr(C)]
@@ -12,44 +30,261 @@ pub struct CameraUniform {
 pub struct LightUniforms {
     pub sun_direction: [f32; 4],          // .w = intensity
     pub sun_color: [f32; 4],              // .w = padding
-    pub point_lights: [GpuPointLight; 4], // Fixed array of 4
+    pub point_lights: [GpuLight; 4], // Fixed array of 4
     pub camera_pos: [f32; 3],
     pub active_lights: u32, // Count
+    pub fog_color: [f32; 4], // .w = density, 0 without fog, see FogSettings
+    pub fog_params: [f32; 4], // Height falloff, base height, start distance, max opacity
+    pub fog_sun: [f32; 4],   // .x = sun anisotropy
 }
 
+/// `GpuLight::kind` values, must match LIGHT_* in common/lights.wgsl
+pub const LIGHT_POINT: u32 = 0;
+pub const LIGHT_SPOT: u32 = 1;
+
+/// `GpuLight::gobo` of lights without a projected texture
+pub const NO_GOBO: u32 = u32::MAX;
+
+/// A point or spot light as the shaders see it.
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct GpuPointLight {
+pub struct GpuLight {
     pub position: [f32; 4], // .w = intensity
-    pub color: [f32; 4],    // .w = radius (unused in shader currently but good for padding)
+    pub color: [f32; 4],    // .w = radius, the light has no effect past it
+    pub direction: [f32; 3], // Spot lights only, normalized
+    pub kind: u32,           // LIGHT_POINT or LIGHT_SPOT
+    pub cos_inner: f32,      // Full intensity inside this cone
+    pub cos_outer: f32,      // No light outside this one
+    pub gobo: u32,           // Slot in Gobos, or NO_GOBO
+    pub _padding: u32,
 }
 
-pub struct GlobalResources {
-    pub layout: wgpu::BindGroupLayout,
+// Sizes as seen by WGSL (uniform address space). Field offsets are checked
+// against each shader at pipeline creation, see programs::*::UNIFORM_LAYOUTS.
+const _: () = assert!(std::mem::size_of::<CameraUniform>() == 240);
+const _: () = assert!(std::mem::size_of::<GpuLight>() == 64);
+// vec3 camera_pos is 16-aligned in WGSL, active_lights packs into its last 4 bytes
+const _: () = assert!(std::mem::offset_of!(LightUniforms, camera_pos) % 16 == 0);
+const _: () = assert!(std::mem::size_of::<LightUniforms>() == 352);
+const _: () = assert!(std::mem::size_of::<LightUniforms>() % 16 == 0);
+
+impl CameraUniform {
+    pub const LAYOUT: StructLayout = rust_layout!(CameraUniform {
+        view_proj: [[f32; 4]; 4],
+        unjittered_view_proj: [[f32; 4]; 4],
+        prev_view_proj: [[f32; 4]; 4],
+        exposure: f32,
+        _padding0: f32,
+        _padding1: [f32; 2],
+        wind: [f32; 4],
+        prev_wind: [f32; 4],
+    });
+}
+
+impl LightUniforms {
+    pub const LAYOUT: StructLayout = rust_layout!(LightUniforms {
+        sun_direction: [f32; 4],
+        sun_color: [f32; 4],
+        point_lights: [GpuLight; 4],
+        camera_pos: [f32; 3],
+        active_lights: u32,
+        fog_color: [f32; 4],
+        fog_params: [f32; 4],
+        fog_sun: [f32; 4],
+    });
+}
+
+impl GpuLight {
+    pub const EMPTY: Self = Self {
+        position: [0.0; 4],
+        color: [0.0; 4],
+        direction: [0.0, 0.0, -1.0],
+        kind: LIGHT_POINT,
+        cos_inner: -1.0,
+        cos_outer: -1.0,
+        gobo: NO_GOBO,
+        _padding: 0,
+    };
+
+    pub const LAYOUT: StructLayout = rust_layout!(GpuLight {
+        position: [f32; 4],
+        color: [f32; 4],
+        direction: [f32; 3],
+        kind: u32,
+        cos_inner: f32,
+        cos_outer: f32,
+        gobo: u32,
+        _padding: u32,
+    });
+
+    pub fn point(position: Vec3, color: [f32; 3], intensity: f32, radius: f32) -> Self {
+        Self {
+            position: [position.x, position.y, position.z, intensity],
+            color: [color[0], color[1], color[2], radius],
+            ..Self::EMPTY
+        }
+    }
+
+    /// Sphere (center, radius) around everything the light reaches, for cluster
+    /// assignment. A spot's is the smallest sphere around its cone rather than its range.
+    pub fn bounding_sphere(&self) -> (Vec3, f32) {
+        let position = Vec3::from_slice(&self.position[..3]);
+        let range = self.color[3];
+        if self.kind != LIGHT_SPOT {
+            return (position, range);
+        }
+
+        let direction = Vec3::from_array(self.direction);
+        let cos = self.cos_outer.clamp(0.0, 1.0);
+        if cos >= std::f32::consts::FRAC_1_SQRT_2 {
+            // Narrow cone (up to 45 degrees): the apex and the rim are on the sphere
+            let radius = range / (2.0 * cos);
+            (position + direction * radius, radius)
+        } else {
+            // Wide cone: the sphere around the rim also holds the apex and the cap
+            let sin = (1.0 - cos * cos).sqrt();
+            (position + direction * range * cos, range * sin)
+        }
+    }
+}
+
+/// Camera + light uniforms for a single view. Each viewport gets its own set,
+/// since write_buffer calls are all applied before the frame is submitted.
+pub struct ViewResources {
     pub bind_group: wgpu::BindGroup,
     cam_buffer: wgpu::Buffer,
     lights_buffer: wgpu::Buffer,
+    clusters: Option<ClusterBuffers>, // Clustered lighting only
+    // Unjittered view_proj uploaded last frame, becomes prev_view_proj
+    last_view_proj: Option<Mat4>,
+}
+
+// Light lists of one view, bindings 2, 4 and 5
+struct ClusterBuffers {
+    grid: ClusterGrid,
+    uniform: wgpu::Buffer,
+    ranges: wgpu::Buffer,
+    indices: wgpu::Buffer,
+}
+
+pub struct GlobalResources {
+    pub layout: wgpu::BindGroupLayout,
+    pub views: Vec<ViewResources>,
+    pub lighting_mode: LightingMode,
+    /// Textures and matrices of projected spot lights, bindings 6 to 8
+    pub gobos: Gobos,
+    /// Boxes and prefiltered cubemaps of the reflection probes, bindings 9 to 11
+    pub probes: ProbeTextures,
+    /// This frame's wind for the camera uniforms, set by the "Update Wind" system
+    pub wind: WindUniforms,
+    // Every point and spot light, shared by all views (binding 3). Clustered lighting only.
+    point_lights_buffer: Option<wgpu::Buffer>,
+}
+
+// Read-only storage buffer entry for the clustered lighting bindings
+fn storage_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: true },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
 }
 
 impl GlobalResources {
-    pub fn new(device: &wgpu::Device) -> Self {
-        let global_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Uniform Bind Group Layout"),
-            entries: &[
-                // --- BINDING 0: MVP Matrix ---
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
+    pub fn new(device: &wgpu::Device, lighting_mode: LightingMode) -> Self {
+        let mut entries = vec![
+            // --- BINDING 0: MVP Matrix + exposure ---
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            // --- BINDING 1: Light Uniforms ---
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
                 },
-                // --- BINDING 1: Light Uniforms ---
+                count: None,
+            },
+            // --- BINDING 6: Gobo view-projections ---
+            wgpu::BindGroupLayoutEntry {
+                binding: 6,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            // --- BINDING 7: Gobo texture array ---
+            wgpu::BindGroupLayoutEntry {
+                binding: 7,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            // --- BINDING 8: Gobo sampler ---
+            wgpu::BindGroupLayoutEntry {
+                binding: 8,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+            // --- BINDING 9: Reflection probe boxes ---
+            wgpu::BindGroupLayoutEntry {
+                binding: 9,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            // --- BINDING 10: Reflection probe cubemaps ---
+            wgpu::BindGroupLayoutEntry {
+                binding: 10,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::CubeArray,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            // --- BINDING 11: Reflection probe sampler ---
+            wgpu::BindGroupLayoutEntry {
+                binding: 11,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ];
+
+        if lighting_mode == LightingMode::Clustered {
+            entries.extend([
+                // --- BINDING 2: Cluster grid of the view ---
                 wgpu::BindGroupLayoutEntry {
-                    binding: 1,
+                    binding: 2,
                     visibility: wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
@@ -58,11 +293,94 @@ impl GlobalResources {
                     },
                     count: None,
                 },
-            ],
+                // --- BINDING 3: All point and spot lights ---
+                storage_entry(3),
+                // --- BINDING 4: (offset, count) per cluster ---
+                storage_entry(4),
+                // --- BINDING 5: Light indices of all clusters ---
+                storage_entry(5),
+            ]);
+        }
+
+        let global_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Uniform Bind Group Layout"),
+            entries: &entries,
+        });
+
+        let point_lights_buffer = (lighting_mode == LightingMode::Clustered).then(|| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Point Lights Buffer"),
+                size: (MAX_LIGHTS * std::mem::size_of::<GpuLight>()) as u64,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
         });
 
+        let mut resources = Self {
+            layout: global_layout,
+            views: Vec::new(),
+            lighting_mode,
+            gobos: Gobos::new(device),
+            probes: ProbeTextures::new(device),
+            wind: WindUniforms::default(),
+            point_lights_buffer,
+        };
+        resources.ensure_views(device, 1);
+        resources
+    }
+
+    /// Grows the per-view uniform sets so at least `count` views can be drawn this frame.
+    pub fn ensure_views(&mut self, device: &wgpu::Device, count: usize) {
+        while self.views.len() < count {
+            let view = ViewResources::new(
+                device,
+                &self.layout,
+                &self.gobos,
+                &self.probes,
+                self.point_lights_buffer.as_ref(),
+            );
+            self.views.push(view);
+        }
+    }
+
+    pub fn view(&self, index: usize) -> &ViewResources {
+        &self.views[index]
+    }
+
+    pub fn view_mut(&mut self, index: usize) -> &mut ViewResources {
+        &mut self.views[index]
+    }
+
+    /// Uploads every point and spot light for clustered lighting, up to MAX_LIGHTS.
+    /// Does nothing with uniform lighting, which only sees LightUniforms.
+    pub fn update_point_lights(&self, queue: &wgpu::Queue, lights: &[GpuLight]) {
+        let Some(buffer) = &self.point_lights_buffer else {
+            return;
+        };
+        let lights = &lights[..lights.len().min(MAX_LIGHTS)];
+        if !lights.is_empty() {
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(lights));
+        }
+    }
+}
+
+impl ViewResources {
+    fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        gobos: &Gobos,
+        probes: &ProbeTextures,
+        point_lights: Option<&wgpu::Buffer>,
+    ) -> Self {
         let initial_camera_data = CameraUniform {
             view_proj: [[0.0; 4]; 4], // Placeholder
+            unjittered_view_proj: [[0.0; 4]; 4],
+            prev_view_proj: [[0.0; 4]; 4],
+            exposure: 1.0,
+            _padding0: 0.0,
+            _padding1: [0.0; 2],
+            wind: [0.0; 4],
+            prev_wind: [0.0; 4],
         };
 
         let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -75,12 +393,12 @@ impl GlobalResources {
         let initial_light_data = LightUniforms {
             sun_direction: [0.0, -1.0, 0.0, 1.0],
             sun_color: [1.0, 1.0, 1.0, 0.0],
-            point_lights: [GpuPointLight {
-                position: [0.0; 4],
-                color: [0.0; 4],
-            }; 4],
+            point_lights: [GpuLight::EMPTY; 4],
             camera_pos: [0.0, 0.0, 0.0],
             active_lights: 4,
+            fog_color: [0.0; 4],
+            fog_params: [0.0; 4],
+            fog_sun: [0.0; 4],
         };
 
         let scene_data_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -89,35 +407,127 @@ impl GlobalResources {
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST, // COPY_DST is critical for updates!
         });
 
-        let global_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Global Bind Group"),
-            layout: &global_layout,
-            entries: &[
+        let clusters = point_lights.map(|_| {
+            let storage = |label, size: usize| {
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(label),
+                    size: size as u64,
+                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                })
+            };
+
+            ClusterBuffers {
+                grid: ClusterGrid::default(),
+                uniform: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Cluster Uniform Buffer"),
+                    size: std::mem::size_of::<ClusterUniform>() as u64,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                ranges: storage("Cluster Ranges Buffer", CLUSTER_COUNT * 8),
+                indices: storage("Cluster Light Indices Buffer", MAX_LIGHT_INDICES * 4),
+            }
+        });
+
+        let mut entries = vec![
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: scene_data_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 6,
+                resource: gobos.uniform.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 7,
+                resource: wgpu::BindingResource::TextureView(&gobos.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 8,
+                resource: wgpu::BindingResource::Sampler(&gobos.sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 9,
+                resource: probes.uniform.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 10,
+                resource: wgpu::BindingResource::TextureView(&probes.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 11,
+                resource: wgpu::BindingResource::Sampler(&probes.sampler),
+            },
+        ];
+        if let (Some(clusters), Some(point_lights)) = (&clusters, point_lights) {
+            entries.extend([
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: clusters.uniform.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: point_lights.as_entire_binding(),
+                },
                 wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: camera_buffer.as_entire_binding(),
+                    binding: 4,
+                    resource: clusters.ranges.as_entire_binding(),
                 },
                 wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: scene_data_buffer.as_entire_binding(),
+                    binding: 5,
+                    resource: clusters.indices.as_entire_binding(),
                 },
-            ],
+            ]);
+        }
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Global Bind Group"),
+            layout,
+            entries: &entries,
         });
 
         Self {
-            layout: global_layout,
-            bind_group: global_bind_group,
+            bind_group,
             cam_buffer: camera_buffer,
             lights_buffer: scene_data_buffer,
+            clusters,
+            last_view_proj: None,
         }
     }
 
     // This is the key method you were missing!
-    pub fn update_camera(&self, queue: &wgpu::Queue, view_proj: Mat4) {
+    pub fn update_camera(
+        &mut self,
+        queue: &wgpu::Queue,
+        view_proj: Mat4,
+        unjittered_view_proj: Mat4,
+        exposure: f32,
+        wind: WindUniforms,
+    ) {
+        // First frame for this view: no history, so the camera counts as still
+        let prev_view_proj = self.last_view_proj.unwrap_or(unjittered_view_proj);
+        self.last_view_proj = Some(unjittered_view_proj);
+
+        let uniform = CameraUniform {
+            view_proj: view_proj.to_cols_array_2d(),
+            unjittered_view_proj: unjittered_view_proj.to_cols_array_2d(),
+            prev_view_proj: prev_view_proj.to_cols_array_2d(),
+            exposure,
+            _padding0: 0.0,
+            _padding1: [0.0; 2],
+            wind: wind.current,
+            prev_wind: wind.previous,
+        };
+
         queue.write_buffer(
-            &self.cam_buffer,                                      // Target
-            0,                                                     // Offset
-            bytemuck::cast_slice(&[view_proj.to_cols_array_2d()]), // Data
+            &self.cam_buffer,             // Target
+            0,                            // Offset
+            bytemuck::bytes_of(&uniform), // Data
         );
     }
 
@@ -128,4 +538,31 @@ impl GlobalResources {
     ) {
         queue.write_buffer(&self.lights_buffer, 0, bytemuck::bytes_of(&uniform));
     }
+
+    /// Assigns `lights` to this view's clusters and uploads the lists. Returns the
+    /// average light count of the occupied clusters, None with uniform lighting.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_clusters(
+        &mut self,
+        queue: &wgpu::Queue,
+        lights: &[GpuLight],
+        view: Mat4,
+        projection: Mat4,
+        near: f32,
+        far: f32,
+        viewport: &Viewport,
+    ) -> Option<f32> {
+        let clusters = self.clusters.as_mut()?;
+
+        let mut uniform = clusters.grid.build(lights, view, projection, near, far);
+        uniform.viewport = [viewport.x, viewport.y, viewport.width, viewport.height];
+
+        queue.write_buffer(&clusters.uniform, 0, bytemuck::bytes_of(&uniform));
+        queue.write_buffer(&clusters.ranges, 0, bytemuck::cast_slice(&clusters.grid.ranges));
+        if !clusters.grid.indices.is_empty() {
+            queue.write_buffer(&clusters.indices, 0, bytemuck::cast_slice(&clusters.grid.indices));
+        }
+
+        Some(clusters.grid.average_lights_per_cluster())
+    }
 }
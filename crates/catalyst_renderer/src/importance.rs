@@ -0,0 +1,74 @@
+use catalyst_core::{
+    App,
+    importance::{ImportanceBias, ImportanceSettings},
+    transform::GlobalTransform,
+};
+use flecs_ecs::prelude::*;
+use glam::{Mat4, Vec2, Vec3};
+
+use crate::{
+    mesh::{AssetMesh, MeshBounds},
+    visibility::{ComputedVisibility, ViewFrusta},
+};
+
+/// Where `point` lands on a view's screen in normalized device coordinates (-1..1 from
+/// edge to edge), None when it is behind the eye.
+pub fn screen_position(view_proj: Mat4, point: Vec3) -> Option<Vec2> {
+    let clip = view_proj * point.extend(1.0);
+    (clip.w > f32::EPSILON).then(|| clip.truncate().truncate() / clip.w)
+}
+
+// After "Update View Frusta" (particles), so the views are this frame's
+pub fn register_importance_systems(app: &mut App) {
+    app.world
+        .system_named::<&ImportanceBias>("Setup Importance Visibility")
+        .without(ComputedVisibility::id())
+        .kind(flecs::pipeline::OnUpdate)
+        .each_entity(|entity, _| {
+            entity.set(ComputedVisibility::default());
+        });
+
+    // Visible entities whose center is near the middle of a view are boosted, the one
+    // the player is looking at first. The boost decays once they leave it.
+    app.world
+        .system_named::<(
+            &mut ImportanceBias,
+            &GlobalTransform,
+            &mut ComputedVisibility,
+            &ImportanceSettings,
+            &ViewFrusta,
+        )>("Screen Importance")
+        .kind(flecs::pipeline::PostUpdate)
+        .each_entity(
+            |entity, (importance, global, visibility, settings, frusta)| {
+                let bounds = entity
+                    .target(AssetMesh, 0)
+                    .and_then(|mesh| mesh.try_get::<&MeshBounds>(|bounds| bounds.0));
+                let (center, radius) = match bounds {
+                    Some(bounds) => {
+                        let (scale, _, _) = global.to_scale_rotation_translation();
+                        (
+                            global.0.transform_point3(bounds.center()),
+                            bounds.half_extents().length() * scale.abs().max_element(),
+                        )
+                    }
+                    None => (global.0.transform_point3(Vec3::ZERO), 0.0),
+                };
+
+                visibility.visible = false;
+                let mut centered = false;
+                for view in &frusta.0 {
+                    if !view.frustum.intersects_sphere(center, radius) {
+                        continue;
+                    }
+                    visibility.visible = true;
+                    centered |= screen_position(view.view_proj, center)
+                        .is_some_and(|ndc| ndc.length() <= settings.screen_center_radius);
+                }
+
+                if centered {
+                    importance.raise(settings.screen_center_boost);
+                }
+            },
+        );
+}
@@ -0,0 +1,290 @@
+use thiserror::Error;
+
+/// Byte layout of one struct member.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FieldLayout {
+    pub name: &'static str,
+    pub offset: usize,
+    pub size: usize,
+}
+
+impl FieldLayout {
+    pub const fn new(name: &'static str, offset: usize, size: usize) -> Self {
+        Self { name, offset, size }
+    }
+}
+
+/// Byte layout of a GPU-visible struct, either as Rust lays it out (`rust_layout!`)
+/// or as the WGSL uniform address space rules do (reflected by build.rs, see
+/// `programs::reflected`).
+#[derive(Clone, Copy, Debug)]
+pub struct StructLayout {
+    pub name: &'static str,
+    pub size: usize,
+    pub fields: &'static [FieldLayout],
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum LayoutError {
+    #[error(
+        "uniform layout mismatch in {rust_struct} (WGSL: {wgsl_struct}): field '{field}' is at offset {rust_offset} in Rust but {wgsl_offset} in WGSL"
+    )]
+    Offset {
+        rust_struct: &'static str,
+        wgsl_struct: &'static str,
+        field: &'static str,
+        rust_offset: usize,
+        wgsl_offset: usize,
+    },
+    #[error(
+        "uniform layout mismatch in {rust_struct} (WGSL: {wgsl_struct}): field '{field}' is {rust_size} bytes in Rust but {wgsl_size} in WGSL"
+    )]
+    FieldSize {
+        rust_struct: &'static str,
+        wgsl_struct: &'static str,
+        field: &'static str,
+        rust_size: usize,
+        wgsl_size: usize,
+    },
+    #[error(
+        "uniform layout mismatch in {rust_struct} (WGSL: {wgsl_struct}): field '{field}' exists on only one side"
+    )]
+    MissingField {
+        rust_struct: &'static str,
+        wgsl_struct: &'static str,
+        field: &'static str,
+    },
+    #[error(
+        "uniform layout mismatch in {rust_struct} (WGSL: {wgsl_struct}): struct is {rust_size} bytes in Rust but {wgsl_size} in WGSL"
+    )]
+    StructSize {
+        rust_struct: &'static str,
+        wgsl_struct: &'static str,
+        rust_size: usize,
+        wgsl_size: usize,
+    },
+}
+
+/// Builds a StructLayout for a #[repr(C)] struct from its real field offsets.
+/// Fields are listed in declaration order: `rust_layout!(Foo { a: [f32; 4], b: u32 })`.
+macro_rules! rust_layout {
+    ($ty:ident { $($field:ident : $field_ty:ty),* $(,)? }) => {
+        $crate::layout::StructLayout {
+            name: stringify!($ty),
+            size: std::mem::size_of::<$ty>(),
+            fields: &[$(
+                $crate::layout::FieldLayout {
+                    name: stringify!($field),
+                    offset: std::mem::offset_of!($ty, $field),
+                    size: std::mem::size_of::<$field_ty>(),
+                },
+            )*],
+        }
+    };
+}
+pub(crate) use rust_layout;
+
+/// Declares a program's UNIFORM_LAYOUTS, its (Rust, WGSL) struct pairs:
+/// `uniform_layouts!(CameraUniform::LAYOUT => WGSL_CAMERA, ...)`. A pair whose sizes
+/// differ fails the build; the fields are compared when the program is created, by
+/// `validate_program_layouts`.
+macro_rules! uniform_layouts {
+    ($($rust:expr => $wgsl:expr),* $(,)?) => {
+        const UNIFORM_LAYOUTS: &[($crate::layout::StructLayout, $crate::layout::StructLayout)] =
+            &[$(($rust, $wgsl)),*];
+        const _: () = {
+            $(assert!(
+                $rust.size == $wgsl.size,
+                concat!(
+                    "uniform layout mismatch: ",
+                    stringify!($rust),
+                    " and ",
+                    stringify!($wgsl),
+                    " have different sizes, update the Rust struct to match the shader"
+                )
+            );)*
+        };
+    };
+}
+pub(crate) use uniform_layouts;
+
+/// Compares fields pairwise in declaration order (names may differ between
+/// Rust and WGSL, e.g. `point_lights` vs `lights`), then the total size.
+/// The first difference is reported, which is the field that actually moved.
+pub fn validate_layout(rust: &StructLayout, wgsl: &StructLayout) -> Result<(), LayoutError> {
+    for i in 0..rust.fields.len().max(wgsl.fields.len()) {
+        let (r, w) = match (rust.fields.get(i), wgsl.fields.get(i)) {
+            (Some(r), Some(w)) => (r, w),
+            (Some(only), None) | (None, Some(only)) => {
+                return Err(LayoutError::MissingField {
+                    rust_struct: rust.name,
+                    wgsl_struct: wgsl.name,
+                    field: only.name,
+                });
+            }
+            (None, None) => unreachable!(),
+        };
+
+        if r.offset != w.offset {
+            return Err(LayoutError::Offset {
+                rust_struct: rust.name,
+                wgsl_struct: wgsl.name,
+                field: r.name,
+                rust_offset: r.offset,
+                wgsl_offset: w.offset,
+            });
+        }
+
+        if r.size != w.size {
+            return Err(LayoutError::FieldSize {
+                rust_struct: rust.name,
+                wgsl_struct: wgsl.name,
+                field: r.name,
+                rust_size: r.size,
+                wgsl_size: w.size,
+            });
+        }
+    }
+
+    if rust.size != wgsl.size {
+        return Err(LayoutError::StructSize {
+            rust_struct: rust.name,
+            wgsl_struct: wgsl.name,
+            rust_size: rust.size,
+            wgsl_size: wgsl.size,
+        });
+    }
+
+    Ok(())
+}
+
+/// Validates every (Rust, WGSL) pair a program binds. Called while building
+/// the pipeline so a bad edit fails loudly at startup instead of rendering garbage.
+pub fn validate_program_layouts(program: &str, layouts: &[(StructLayout, StructLayout)]) {
+    for (rust, wgsl) in layouts {
+        if let Err(e) = validate_layout(rust, wgsl) {
+            panic!("{}: {}", program, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Only read through offset_of!
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct PackedLight {
+        position: [f32; 3],
+        range: f32,
+    }
+
+    // A lone f32 before a vec3: Rust packs the array right after it, WGSL aligns
+    // the vec3 to 16 bytes
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct MisalignedLight {
+        range: f32,
+        position: [f32; 3],
+    }
+
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct Position {
+        position: [f32; 3],
+    }
+
+    const RUST_PACKED_LIGHT: StructLayout = rust_layout!(PackedLight {
+        position: [f32; 3],
+        range: f32,
+    });
+    const RUST_MISALIGNED_LIGHT: StructLayout = rust_layout!(MisalignedLight {
+        range: f32,
+        position: [f32; 3],
+    });
+    const RUST_POSITION: StructLayout = rust_layout!(Position { position: [f32; 3] });
+
+    // What build.rs reflects for `struct Light { position: vec3<f32>, range: f32 }`
+    const WGSL_PACKED_LIGHT: StructLayout = StructLayout {
+        name: "Light",
+        size: 16,
+        fields: &[
+            FieldLayout::new("position", 0, 12),
+            FieldLayout::new("range", 12, 4),
+        ],
+    };
+
+    // `struct Light { range: f32, position: vec3<f32> }`
+    const WGSL_MISALIGNED_LIGHT: StructLayout = StructLayout {
+        name: "Light",
+        size: 32,
+        fields: &[
+            FieldLayout::new("range", 0, 4),
+            FieldLayout::new("position", 16, 12),
+        ],
+    };
+
+    // `struct Position { position: vec3<f32> }`, rounded up to its 16 byte alignment
+    const WGSL_POSITION: StructLayout = StructLayout {
+        name: "Position",
+        size: 16,
+        fields: &[FieldLayout::new("position", 0, 12)],
+    };
+
+    #[test]
+    fn a_vec3_followed_by_an_f32_matches() {
+        assert_eq!(
+            validate_layout(&RUST_PACKED_LIGHT, &WGSL_PACKED_LIGHT),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn a_vec3_after_an_f32_is_at_a_different_offset() {
+        assert_eq!(
+            validate_layout(&RUST_MISALIGNED_LIGHT, &WGSL_MISALIGNED_LIGHT),
+            Err(LayoutError::Offset {
+                rust_struct: "MisalignedLight",
+                wgsl_struct: "Light",
+                field: "position",
+                rust_offset: 4,
+                wgsl_offset: 16,
+            })
+        );
+    }
+
+    #[test]
+    fn a_missing_tail_padding_is_a_struct_size_error() {
+        assert_eq!(
+            validate_layout(&RUST_POSITION, &WGSL_POSITION),
+            Err(LayoutError::StructSize {
+                rust_struct: "Position",
+                wgsl_struct: "Position",
+                rust_size: 12,
+                wgsl_size: 16,
+            })
+        );
+    }
+
+    #[test]
+    fn a_field_on_one_side_only_is_reported() {
+        assert_eq!(
+            validate_layout(&RUST_POSITION, &WGSL_PACKED_LIGHT),
+            Err(LayoutError::MissingField {
+                rust_struct: "Position",
+                wgsl_struct: "Light",
+                field: "range",
+            })
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "uniform layout mismatch in MisalignedLight")]
+    fn program_layouts_panic_on_a_mismatch() {
+        validate_program_layouts(
+            "test program",
+            &[(RUST_MISALIGNED_LIGHT, WGSL_MISALIGNED_LIGHT)],
+        );
+    }
+}
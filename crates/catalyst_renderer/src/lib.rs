@@ -1,85 +1,167 @@
-use catalyst_core::{App, Plugin};
+use catalyst_assets::AssetPlugin;
+use catalyst_core::{App, Plugin, PluginId, console::ConsoleCommands};
+use catalyst_window::WindowPlugin;
 
 use crate::{
-    material::register_material_handlers, mesh::register_mesh_handlers, programs::debug_lines_program::register_debug_lines_program_systems, render::register_renderings, texture::register_texture_handlers
+    color_grading::register_color_grading_systems, debug_view::register_debug_view_commands, decals::register_decal_systems, device_recovery::register_device_recovery, dynamic_resolution::register_dynamic_resolution_systems, exposure::register_exposure_systems, frustum_culling::register_frustum_culling_systems, importance::register_importance_systems, lod::register_lod_systems, material::register_material_handlers, material_preview::register_material_preview_systems, mesh::register_mesh_handlers, occlusion_culling::register_occlusion_culling_systems, outline::register_outline_systems, particles::register_particle_systems, picking::register_picking_systems, portal_culling::register_portal_culling_systems, programs::debug_lines_program::register_debug_lines_program_systems, raycast::register_raycast_systems, reflection::register_reflection_systems, reflection_probe::register_reflection_probe_systems, render::register_renderings, screenshot::register_screenshot_commands, spot_light::register_spot_light_systems, static_batching::register_static_batching_systems, streaming::register_texture_streaming, surface::register_surface_observers, texture::register_texture_handlers, upload::register_upload_queue, wind::register_wind_systems
 };
 
+pub mod capabilities;
+pub mod clusters;
+pub mod color_grading;
+pub mod custom_program;
+pub mod debug_view;
+pub mod decals;
+pub mod depth_of_field;
+pub mod device_recovery;
+pub mod dynamic_resolution;
+pub mod exposure;
+pub mod fog;
+pub mod frame_graph;
+pub mod frustum_culling;
+pub mod geometry_pool;
 mod global_resources;
+pub mod importance;
+mod layout;
+pub mod lod;
 mod material;
+pub mod material_preview;
 pub mod mesh;
+pub mod occlusion_culling;
+pub mod outline;
+pub mod particles;
+pub mod picking;
+pub mod portal_culling;
 mod programs;
+pub mod raycast;
+pub mod readback;
+pub mod reflection;
+pub mod reflection_probe;
 pub mod render;
+pub mod render_order;
+pub mod screenshot;
+pub mod shaders;
+pub mod spot_light;
+pub mod static_batching;
+pub mod streaming;
+pub mod surface;
+mod taa;
 mod texture;
+mod upload;
+pub mod viewport;
+pub mod visibility;
+pub mod wind;
 
-pub use render::{RenderContext, RenderTarget};
-pub use texture::GpuTexture;
+pub use capabilities::{GraphicsBackend, RendererCapabilities};
+pub use clusters::LightingMode;
+pub use color_grading::{ColorGrading, GradingVolume};
+pub use custom_program::{
+    CustomMaterialProgram, CustomProgramContext, MaterialProgram, MaterialPrograms, ProgramPasses,
+};
+pub use debug_view::DebugViewMode;
+pub use decals::Decal;
+pub use depth_of_field::{Autofocus, DepthOfField};
+pub use device_recovery::DeviceRecovery;
+pub use dynamic_resolution::{DynamicResolution, DynamicResolutionState, UpscaleFilter};
+pub use exposure::{AutoExposure, Exposure, ExposureState};
+pub use fog::FogSettings;
+pub use frame_graph::{
+    FrameGraph, GraphTexture, GraphTextureDesc, ParallelPassContext, PassContext, PassCycle,
+    PassStage, SceneTargets,
+};
+pub use material_preview::MaterialPreview;
+pub use outline::Outlined;
+pub use particles::{EmitterMode, ParticleEmitter, ParticlePool, ParticleTier};
+pub use picking::{PickBackend, PickResult, Picking};
+pub use frustum_culling::{FrustumCulled, FrustumCulling};
+pub use occlusion_culling::{OcclusionCullable, OcclusionCulled, OcclusionCulling};
+pub use portal_culling::{PortalCulling, RoomCulled, RoomMember};
+pub use reflection::PlanarReflector;
+pub use raycast::{MeshBvh, raycast_render, raycast_render_all};
+pub use readback::{Readback, ReadbackDelivery, ReadbackError, ReadbackManager, TextureRegion};
+pub use reflection_probe::{ReflectionProbe, ReflectionProbes};
+pub use render::{ClearColor, RenderContext, RenderSettings, RenderStats, RenderTarget};
+pub use streaming::{TextureResidency, TextureStreaming, TextureStreamingStats};
+pub use surface::SurfaceState;
+pub use render_order::{RenderBucket, RenderLayers, RenderOrder};
+pub use screenshot::Screenshots;
+pub use spot_light::SpotLight;
+pub use static_batching::{StaticBatching, StaticGeometry};
+pub use taa::TaaSettings;
+pub use texture::{DepthMode, GpuTexture};
+pub use upload::UploadSettings;
+pub use viewport::{SplitScreenLayout, SplitScreenSettings, Viewport};
+pub use visibility::ComputedVisibility;
+pub use wind::WindSettings;
+
+// For games writing their own CustomMaterialProgram
+pub use wgpu;
 
 pub struct RenderPlugin;
 
 impl Plugin for RenderPlugin {
     fn build(&self, app: &mut App) {
         register_renderings(app);
+        register_surface_observers(&app.world);
         register_mesh_handlers(&app.world);
+        register_lod_systems(&app.world);
         register_material_handlers(&app.world);
         register_texture_handlers(&app.world);
+        register_upload_queue(&app.world);
+        register_texture_streaming(&app.world);
         register_debug_lines_program_systems(app);
-    }
-}
-
-// pub fn prepare_lights(
-//     // We need the RenderContext to access the Buffer and Queue
-//     context: &mut RenderContext,
-
-//     // Queries to find lights in the scene
-//     sun_query: Query<(&GlobalTransform, &DirectionalLight)>,
-//     point_query: Query<(&GlobalTransform, &PointLight)>,
+        register_outline_systems(app);
+        register_particle_systems(app);
+        register_picking_systems(app);
+        register_importance_systems(app);
+        register_decal_systems(app);
+        register_spot_light_systems(app);
+        register_reflection_systems(app);
+        register_reflection_probe_systems(app);
+        register_portal_culling_systems(app);
+        register_frustum_culling_systems(app);
+        register_occlusion_culling_systems(app);
+        register_exposure_systems(app);
+        register_color_grading_systems(app);
+        register_dynamic_resolution_systems(app);
+        register_material_preview_systems(app);
+        register_static_batching_systems(app);
+        register_device_recovery(app);
+        register_wind_systems(app);
+        register_raycast_systems(app);
+        app.world.get::<&mut ConsoleCommands>(|commands| {
+            register_debug_view_commands(commands);
+            register_screenshot_commands(commands);
+        });
 
-//     // We need Camera pos for specular calculations
-//     cam_query: Query<&GlobalTransform, With<Camera>>,
-// ) {
-//     let mut uniforms = LightUniforms {
-//         sun_direction: [0.0, -1.0, 0.0, 1.0], // Default Down
-//         sun_color: [1.0, 1.0, 1.0, 0.0],
-//         point_lights: [GpuPointLight {
-//             position: [0.0; 4],
-//             color: [0.0; 4],
-//         }; 4],
-//         camera_pos: [0.0, 0.0, 0.0],
-//         active_lights: 0,
-//     };
-
-//     // A. Process Sun (Take the first one we find)
-//     if let Some((transform, sun)) = sun_query.iter().next() {
-//         // Calculate forward vector from rotation
-//         let forward = transform.forward(); // Bevy GlobalTransform helper
-//         uniforms.sun_direction = [forward.x, forward.y, forward.z, sun.intensity];
-//         uniforms.sun_color = [sun.color[0], sun.color[1], sun.color[2], 0.0];
-//     }
-
-//     // B. Process Point Lights
-//     let mut count = 0;
-//     for (transform, light) in point_query.iter().take(4) {
-//         // Limit to 4!
-//         let pos = transform.translation();
-//         uniforms.point_lights[count] = GpuPointLight {
-//             position: [pos.x, pos.y, pos.z, light.intensity],
-//             color: [light.color[0], light.color[1], light.color[2], light.radius],
-//         };
-//         count += 1;
-//     }
-//     uniforms.active_lights = count as u32;
+        app.register_component::<ParticleEmitter>()
+            .register_component::<Decal>()
+            .register_component::<SpotLight>()
+            .register_component::<DepthOfField>()
+            .register_component::<GradingVolume>()
+            .register_component::<Outlined>()
+            .register_component::<MaterialProgram>()
+            .register_component::<PlanarReflector>()
+            .register_component::<ReflectionProbe>()
+            .register_component::<RenderLayers>()
+            .register_component::<Exposure>()
+            .register_component::<RenderOrder>();
+    }
 
-//     // C. Process Camera Position
-//     if let Some(cam_tf) = cam_query.iter().next() {
-//         let pos = cam_tf.translation();
-//         uniforms.camera_pos = [pos.x, pos.y, pos.z];
-//     }
+    fn dependencies(&self) -> Vec<PluginId> {
+        vec![PluginId::of::<WindowPlugin>(), PluginId::of::<AssetPlugin>()]
+    }
+}
 
-//     // D. Upload to GPU
-//     // "scene_data_buffer" is the buffer you created in Binding 1 of Group 0
-//     context.queue.write_buffer(
-//         &context.scene_data_buffer,
-//         0,
-//         bytemuck::cast_slice(&[uniforms]),
-//     );
-// }
+/// A device without a window, for the tests that run shaders. None on machines without
+/// a GPU adapter, where those tests return without checking anything.
+#[cfg(test)]
+fn headless_device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+    let adapter =
+        pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+            .ok()?;
+    let device = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default()))
+        .expect("the adapter gives a device");
+    Some(device)
+}
@@ -0,0 +1,86 @@
+use catalyst_assets::{MeshDefinition, lod::MeshLod};
+use catalyst_core::{
+    camera::Camera,
+    importance::{ImportanceBias, effective_distance},
+    transform::GlobalTransform,
+};
+use flecs_ecs::prelude::*;
+use glam::Vec3;
+
+use crate::mesh::{AssetMesh, GpuGeometry, MeshBounds};
+
+pub fn register_lod_systems(world: &World) {
+    let cameras = world
+        .query::<(&Camera, &GlobalTransform)>()
+        .set_cached()
+        .build();
+
+    // Points AssetMesh at the level matching how much of the screen the mesh covers in
+    // the closest camera, measured from the distance its ImportanceBias makes it count as.
+    // Levels not on the GPU yet are passed over for a more detailed one.
+    world
+        .system_named::<(
+            &MeshDefinition,
+            &MeshLod,
+            &GlobalTransform,
+            Option<&ImportanceBias>,
+        )>("Select Mesh LOD")
+        .with((AssetMesh, flecs::Wildcard))
+        .kind(flecs::pipeline::PostUpdate)
+        .each_iter(move |iter, index, (definition, lod, global, importance)| {
+            let world = iter.world();
+            let Some(base) = definition.0.try_get_entity(&world) else {
+                return;
+            };
+            let Some(bounds) = base.try_get::<&MeshBounds>(|bounds| bounds.0) else {
+                return;
+            };
+
+            let center = global.0.transform_point3(bounds.center());
+            let (scale, _, _) = global.to_scale_rotation_translation();
+            let radius = bounds.half_extents().length() * scale.abs().max_element();
+
+            // Fraction of the screen height the bounding sphere spans
+            let mut coverage = 0.0f32;
+            cameras.each(|(camera, camera_transform)| {
+                let eye = camera_transform.0.transform_point3(Vec3::ZERO);
+                let distance = effective_distance(importance, eye.distance(center));
+                let distance = distance.max(f32::EPSILON);
+                coverage = coverage.max(radius / (distance * (camera.fov * 0.5).tan()));
+            });
+
+            let mut selected = base.id();
+            for level in &lod.levels {
+                if coverage >= level.screen_size {
+                    break;
+                }
+                if let Some(mesh) = level
+                    .mesh
+                    .try_get_entity(&world)
+                    .filter(|mesh| mesh.has(GpuGeometry::id()))
+                {
+                    selected = mesh.id();
+                }
+            }
+
+            let current = iter.pair(4).second_id().id();
+            if current != selected {
+                iter.entity(index)
+                    .remove((AssetMesh, current))
+                    .add((AssetMesh, selected));
+            }
+        });
+}
+
+/// Index into `MeshLod::levels` of the level an entity currently draws, None for the
+/// full detail mesh.
+pub fn current_lod_level(entity: EntityView, lod: &MeshLod) -> Option<usize> {
+    let world = entity.world();
+    let current = entity.target(AssetMesh, 0)?.id();
+    lod.levels.iter().position(|level| {
+        level
+            .mesh
+            .try_get_entity(&world)
+            .is_some_and(|mesh| mesh.id() == current)
+    })
+}
@@ -1,13 +1,15 @@
 use catalyst_assets::{
-    MaterialDefinition,
+    MaterialDefinition, PrewarmAsset,
     assets::Handle,
-    material::{MaterialData, MaterialSettings},
+    material::{MaterialData, MaterialSettings, TextureData},
 };
 use flecs_ecs::prelude::*;
-use uuid::Uuid;
 use wgpu::util::DeviceExt;
 
 use crate::{
+    device_recovery::Regenerating,
+    layout::{StructLayout, rust_layout},
+    programs::reflected,
     render::{MaterialLayout, RenderContext},
     texture::GpuTexture,
 };
@@ -18,24 +20,91 @@ pub struct AssetMaterial;
 #[derive(Component)]
 pub struct GpuMaterial {
     pub bind_group: wgpu::BindGroup,
+    /// Which pipeline permutation draws this material.
+    pub key: MaterialKey,
 }
 
+bitflags::bitflags! {
+    /// Shader features a material needs. Every distinct key is one pipeline
+    /// permutation (see PbrProgram::prepare_pipeline), so features a material
+    /// doesn't use cost nothing.
+    ///
+    /// Mesh-side features (vertex colors, skinning) get bits once meshes carry
+    /// those attributes; they change the vertex layout, not just the shader.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+    pub struct MaterialKey: u32 {
+        const NORMAL_MAP = 1 << 0;
+        const ALPHA_MASK = 1 << 1;
+        const DOUBLE_SIDED = 1 << 2;
+        /// KHR_materials_unlit, skips all lighting (and with it the normal map)
+        const UNLIT = 1 << 3;
+        /// Sways in the wind, see WindSettings
+        const WIND = 1 << 4;
+    }
+}
+
+impl MaterialKey {
+    pub fn from_material(data: &MaterialData) -> Self {
+        let mut key = MaterialKey::empty();
+        key.set(MaterialKey::NORMAL_MAP, data.normal_texture.is_some());
+        key.set(MaterialKey::ALPHA_MASK, data.settings.alpha_cutoff.is_some());
+        key.set(MaterialKey::DOUBLE_SIDED, data.settings.double_sided);
+        key.set(MaterialKey::WIND, data.settings.wind.is_some());
+        if data.settings.unlit {
+            key.remove(MaterialKey::NORMAL_MAP);
+            key.insert(MaterialKey::UNLIT);
+        }
+        key
+    }
+}
+
+// Tag: the material references textures that aren't on the GPU yet
+#[derive(Component)]
+pub struct PendingGpuMaterial;
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct GpuMaterialUniform {
     pub base_color: [f32; 4], // 16 bytes
     pub roughness: f32,       // 4 bytes
     pub metallic: f32,        // 4 bytes
-    pub _padding: [f32; 2],   // 8 bytes (Total: 32 bytes, aligned to 16)
+    pub alpha_cutoff: f32,    // 4 bytes, only read by ALPHA_MASK pipelines
+    pub _padding: f32,        // 4 bytes
+    pub emissive: [f32; 4],   // 16 bytes, rgb * strength
+    pub wind: [f32; 4],       // 16 bytes, strength, frequency, branch, flutter (Total: 64 bytes)
+}
+
+const _: () = assert!(std::mem::size_of::<GpuMaterialUniform>() == 64);
+
+impl GpuMaterialUniform {
+    pub const LAYOUT: StructLayout = rust_layout!(GpuMaterialUniform {
+        base_color: [f32; 4],
+        roughness: f32,
+        metallic: f32,
+        alpha_cutoff: f32,
+        _padding: f32,
+        emissive: [f32; 4],
+        wind: [f32; 4],
+    });
 }
 
 impl From<MaterialSettings> for GpuMaterialUniform {
     fn from(s: MaterialSettings) -> Self {
         Self {
-            base_color: s.base_color,
+            base_color: s.base_color.to_linear(),
             roughness: s.roughness,
             metallic: s.metallic,
-            _padding: [0.0; 2],
+            alpha_cutoff: s.alpha_cutoff.unwrap_or(0.0),
+            _padding: 0.0,
+            emissive: [
+                s.emissive.r * s.emissive_strength,
+                s.emissive.g * s.emissive_strength,
+                s.emissive.b * s.emissive_strength,
+                1.0,
+            ],
+            wind: s
+                .wind
+                .map_or([0.0; 4], |w| [w.strength, w.frequency, w.branch, w.flutter]),
         }
     }
 }
@@ -52,88 +121,152 @@ pub fn register_material_handlers(world: &World) {
         });
 
     world
-        .system_named::<(&MaterialData, &mut RenderContext, &MaterialLayout)>(
-            "Init Material GPU buffers",
-        )
-        .without(GpuMaterial::id())
-        .kind(flecs::pipeline::OnStore)
+        .observer::<flecs::OnSet, (&MaterialData, &RenderContext, &MaterialLayout)>()
+        .term_at(1)
+        .filter()
+        .term_at(2)
+        .filter()
+        .without(PrewarmAsset) // Built by the prewarm queue
         .each_entity(|entity, (mat_data, context, mat_layout)| {
-            let world = entity.world();
+            init_gpu_material(entity, mat_data, context, mat_layout);
+        });
 
-            let gpu_uniform = GpuMaterialUniform::from(mat_data.settings.clone());
-            let uniform_buffer =
-                context
-                    .device
-                    .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                        label: Some("Material Uniforms"),
-                        contents: bytemuck::cast_slice(&[gpu_uniform]),
-                        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-                    });
-
-            // B. Find Texture (or Fallback)
-            let diffuse_binding = mat_data
-                .diffuse_texture
-                .as_ref()
-                .and_then(|tex_handle| tex_handle.try_get_entity(&world))
-                .and_then(|texture_entity| texture_entity.try_get::<&GpuTexture>(|tx| tx.clone()));
-
-            let roughness_binding = mat_data
-                .metallic_roughness_texture
-                .as_ref()
-                .and_then(|tex_handle| tex_handle.try_get_entity(&world))
-                .and_then(|texture_entity| texture_entity.try_get::<&GpuTexture>(|tx| tx.clone()));
-
-            let normal_binding = mat_data
-                .normal_texture
-                .as_ref()
-                .and_then(|tex_handle| tex_handle.try_get_entity(&world))
-                .and_then(|texture_entity| texture_entity.try_get::<&GpuTexture>(|tx| tx.clone()));
-
-            if let (Some(diffuse_texture), Some(roughness_texture), Some(normal_texture)) =
-                (diffuse_binding, roughness_binding, normal_binding)
-            {
-                let bind_group = context
-                    .device
-                    .create_bind_group(&wgpu::BindGroupDescriptor {
-                        label: Some("Material Bind Group"),
-                        layout: &mat_layout.0,
-                        entries: &[
-                            wgpu::BindGroupEntry {
-                                binding: 0,
-                                resource: uniform_buffer.as_entire_binding(),
-                            },
-                            wgpu::BindGroupEntry {
-                                binding: 1,
-                                resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
-                            },
-                            wgpu::BindGroupEntry {
-                                binding: 2,
-                                resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
-                            },
-                            wgpu::BindGroupEntry {
-                                binding: 3,
-                                resource: wgpu::BindingResource::TextureView(
-                                    &roughness_texture.view,
-                                ),
-                            },
-                            wgpu::BindGroupEntry {
-                                binding: 4,
-                                resource: wgpu::BindingResource::Sampler(
-                                    &roughness_texture.sampler,
-                                ),
-                            },
-                            wgpu::BindGroupEntry {
-                                binding: 5,
-                                resource: wgpu::BindingResource::TextureView(&normal_texture.view),
-                            },
-                            wgpu::BindGroupEntry {
-                                binding: 6,
-                                resource: wgpu::BindingResource::Sampler(&normal_texture.sampler),
-                            },
-                        ],
-                    });
-
-                entity.set(GpuMaterial { bind_group });
-            };
+    // A texture finished uploading: retry every material that was waiting on one.
+    // The pending set is small, so we don't bother matching the exact texture.
+    let pending_materials = world
+        .query::<&MaterialData>()
+        .with(PendingGpuMaterial)
+        .set_cached()
+        .build();
+
+    world
+        .observer::<flecs::OnSet, (&GpuTexture, &RenderContext, &MaterialLayout)>()
+        .term_at(1)
+        .filter()
+        .term_at(2)
+        .filter()
+        .each(move |(_texture, context, mat_layout)| {
+            pending_materials.each_entity(|material_entity, mat_data| {
+                init_gpu_material(material_entity, mat_data, context, mat_layout);
+            });
         });
+
+    world
+        .observer::<flecs::OnRemove, &MaterialData>()
+        .each_entity(|entity, _| {
+            entity.remove(GpuMaterial::id());
+            entity.remove(PendingGpuMaterial);
+        });
+
+    // Materials that arrived before the renderer was initialized
+    let materials_without_gpu = world
+        .query::<&MaterialData>()
+        .without(GpuMaterial::id())
+        .without(PrewarmAsset)
+        .build();
+
+    world
+        .observer::<flecs::OnSet, (&RenderContext, &MaterialLayout)>()
+        .term_at(1)
+        .filter()
+        .each(move |(context, mat_layout)| {
+            materials_without_gpu.each_entity(|entity, mat_data| {
+                init_gpu_material(entity, mat_data, context, mat_layout);
+            });
+        });
+}
+
+/// Creates the material bind group, or marks the material as pending when one of
+/// its textures hasn't been uploaded yet. Texture slots without a texture at all
+/// get the renderer's neutral defaults, and so do the missing ones of a material
+/// regenerated after a device loss, until they are back.
+pub(crate) fn init_gpu_material(
+    entity: EntityView,
+    mat_data: &MaterialData,
+    context: &RenderContext,
+    mat_layout: &MaterialLayout,
+) {
+    let world = entity.world();
+
+    // B. Find Textures (or Fallback)
+    let resolve = |handle: &Option<Handle<TextureData>>, fallback: &GpuTexture| match handle {
+        None => Some(fallback.clone()),
+        Some(tex_handle) => tex_handle
+            .try_get_entity(&world)
+            .and_then(|texture_entity| texture_entity.try_get::<&GpuTexture>(|tx| tx.clone())),
+    };
+
+    let diffuse_binding = resolve(&mat_data.diffuse_texture, &context.default_diffuse);
+    // White MR texture: AO = 1, and the material factors are used as-is
+    let roughness_binding = resolve(&mat_data.metallic_roughness_texture, &context.default_diffuse);
+    let normal_binding = resolve(&mat_data.normal_texture, &context.default_normal);
+
+    let complete = diffuse_binding.is_some() && roughness_binding.is_some() && normal_binding.is_some();
+    // Regenerated after a device loss: drawn with the defaults in place of the textures
+    // still queued, rather than not at all
+    if !complete && !entity.has(Regenerating) {
+        // Completed later by the GpuTexture observer
+        entity.add(PendingGpuMaterial);
+        return;
+    }
+    let diffuse_texture = diffuse_binding.unwrap_or_else(|| context.default_diffuse.clone());
+    let roughness_texture = roughness_binding.unwrap_or_else(|| context.default_diffuse.clone());
+    let normal_texture = normal_binding.unwrap_or_else(|| context.default_normal.clone());
+
+    let gpu_uniform = GpuMaterialUniform::from(mat_data.settings.clone());
+    let uniform_buffer = context
+        .device
+        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Material Uniforms"),
+            contents: bytemuck::cast_slice(&[gpu_uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+    let bind_group = context
+        .device
+        .create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Material Bind Group"),
+            layout: &mat_layout.0,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: reflected::pbr::MATERIAL,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: reflected::pbr::T_DIFFUSE,
+                    resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: reflected::pbr::S_DIFFUSE,
+                    resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: reflected::pbr::T_METALLIC_ROUGHNESS,
+                    resource: wgpu::BindingResource::TextureView(&roughness_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: reflected::pbr::S_METALLIC_ROUGHNESS,
+                    resource: wgpu::BindingResource::Sampler(&roughness_texture.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: reflected::pbr::T_NORMAL,
+                    resource: wgpu::BindingResource::TextureView(&normal_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: reflected::pbr::S_NORMAL,
+                    resource: wgpu::BindingResource::Sampler(&normal_texture.sampler),
+                },
+            ],
+        });
+
+    entity.set(GpuMaterial {
+        bind_group,
+        key: MaterialKey::from_material(mat_data),
+    });
+    if complete {
+        entity.remove(PendingGpuMaterial);
+        entity.remove(Regenerating);
+    } else {
+        entity.add(PendingGpuMaterial);
+    }
 }
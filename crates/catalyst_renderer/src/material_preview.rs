@@ -0,0 +1,150 @@
+use catalyst_core::{App, pipeline::PhaseRender3D, time::Time};
+use flecs_ecs::prelude::*;
+
+use crate::{
+    frame_graph::{FrameGraph, PassStage},
+    material::GpuMaterial,
+    programs::{GpuProgram, material_preview_program::PreviewUniform},
+    render::RenderContext,
+    texture::GpuTexture,
+};
+
+/// Size of the square preview image.
+pub const PREVIEW_SIZE: u32 = 256;
+/// Format of the preview image; the shader writes linear color.
+pub const PREVIEW_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Renders one material on a sphere under a fixed light rig, for the material editor.
+///
+/// The image goes to the GpuTexture of the `target` entity ("Material Preview"),
+/// created the first time a material is set, so UI code can show it like any other
+/// texture. Nothing is drawn while `material` is None or has no GpuMaterial yet.
+#[derive(Component, Debug)]
+pub struct MaterialPreview {
+    /// The entity with the MaterialData to show.
+    pub material: Option<Entity>,
+    /// Turntable speed, radians per second. 0 to stop it.
+    pub spin: f32,
+    /// The entity holding the preview image, None until first used.
+    pub target: Option<Entity>,
+}
+
+impl Default for MaterialPreview {
+    fn default() -> Self {
+        Self {
+            material: None,
+            spin: 0.5,
+            target: None,
+        }
+    }
+}
+
+fn create_target(device: &wgpu::Device) -> GpuTexture {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Material Preview"),
+        size: wgpu::Extent3d {
+            width: PREVIEW_SIZE,
+            height: PREVIEW_SIZE,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: PREVIEW_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("Material Preview Sampler"),
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    });
+    GpuTexture {
+        texture,
+        view,
+        sampler,
+    }
+}
+
+pub fn register_material_preview_systems(app: &mut App) {
+    app.register_singleton_default::<MaterialPreview>();
+
+    app.world
+        .system_named::<(&mut MaterialPreview, &RenderContext, &mut FrameGraph, &Time)>(
+            "Material Preview",
+        )
+        .kind(PhaseRender3D)
+        .each_iter(|iter, _, (preview, context, graph, time)| {
+            let world = iter.world();
+            let Some(material) = preview.material else {
+                return;
+            };
+            let material = world.entity_from_id(material);
+            if !material.is_alive() {
+                return;
+            }
+            let Some((bind_group, key)) =
+                material.try_get::<&GpuMaterial>(|gpu| (gpu.bind_group.clone(), gpu.key))
+            else {
+                return;
+            };
+
+            // Created on demand, so only when something (the editor) asks for a preview
+            let target = match preview.target {
+                // Its texture went with the device after a device loss
+                Some(target) => {
+                    let target = world.entity_from_id(target);
+                    if !target.has(GpuTexture::id()) {
+                        target.set(create_target(&context.device));
+                    }
+                    target
+                }
+                None => {
+                    let target = world
+                        .entity_named("Material Preview")
+                        .set(create_target(&context.device));
+                    preview.target = Some(target.id());
+                    target
+                }
+            };
+            let Some(view) = target.try_get::<&GpuTexture>(|texture| texture.view.clone()) else {
+                return;
+            };
+
+            let rotation = time.elapsed_seconds() * preview.spin;
+            context
+                .material_preview_program
+                .write_uniform(&context.queue, &PreviewUniform::new(rotation, key));
+
+            let output = graph.import_texture("Material Preview", view);
+            graph
+                .add_pass("Material Preview", PassStage::Scene)
+                .write(output)
+                .record_parallel(move |pass| {
+                    let timestamp_writes = pass.timestamp_writes();
+                    let view = pass.view(output);
+                    let context = pass.context;
+
+                    let mut render_pass =
+                        pass.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                            label: Some("Material Preview"),
+                            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                                view,
+                                resolve_target: None,
+                                depth_slice: None,
+                                ops: wgpu::Operations {
+                                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                                    store: wgpu::StoreOp::Store,
+                                },
+                            })],
+                            timestamp_writes,
+                            ..Default::default()
+                        });
+                    context
+                        .material_preview_program
+                        .record(&mut render_pass, &bind_group);
+                });
+        });
+}
@@ -3,13 +3,27 @@ use std::mem;
 
 use bytemuck::{Pod, Zeroable};
 use catalyst_assets::{
-    MeshDefinition,
-    assets::{MeshData},
+    MeshDefinition, PrewarmAsset,
+    asset_events::{AssetChanged, AssetEvent},
+    asset_server::AssetServer,
+    assets::{Handle, MeshData},
+    mesh_repair::IndexFormat,
 };
-use catalyst_core::transform::GlobalTransform;
+use catalyst_core::{
+    bounds::Aabb,
+    pipeline::PhaseRenderPrepare,
+    transform::{GlobalTransform, StaticTransform},
+};
+use glam::Mat4;
 use wgpu::util::DeviceExt;
 
-use crate::render::RenderContext;
+use crate::{
+    device_recovery::Regenerating,
+    geometry_pool::GeometryAllocation,
+    layout::{StructLayout, rust_layout},
+    programs::reflected,
+    render::{RenderContext, RenderStats},
+};
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Pod, Zeroable)]
@@ -22,12 +36,26 @@ pub struct MeshUniform {
     // Used for lighting. It handles weird scaling issues.
     // (Technically 3x3 is enough, but GPUs prefer 4x4 alignment).
     pub normal_matrix: [[f32; 4]; 4],
+
+    // 3. Last frame's Model Matrix, for per-object motion vectors.
+    pub prev_model: [[f32; 4]; 4],
 }
 
+const _: () = assert!(std::mem::size_of::<MeshUniform>() == 192);
+
 impl MeshUniform {
+    pub const LAYOUT: StructLayout = rust_layout!(MeshUniform {
+        model: [[f32; 4]; 4],
+        normal_matrix: [[f32; 4]; 4],
+        prev_model: [[f32; 4]; 4],
+    });
+
     // Helper to calculate this from your ECS component
-    pub fn from_transform(global: &GlobalTransform) -> Self {
-        let model_matrix = global.0; // The Mat4 you calculated in PostUpdate
+    pub fn from_transform(global: &GlobalTransform, prev_model: Mat4) -> Self {
+        Self::from_matrices(global.0, prev_model)
+    }
+
+    pub fn from_matrices(model_matrix: Mat4, prev_model: Mat4) -> Self {
 
         // Lighting math: Transpose(Inverse(Model))
         // If you squash a sphere, the normals shouldn't squash; they should stretch.
@@ -37,6 +65,7 @@ impl MeshUniform {
         Self {
             model: model_matrix.to_cols_array_2d(),
             normal_matrix: normal_matrix.to_cols_array_2d(),
+            prev_model: prev_model.to_cols_array_2d(),
         }
     }
 }
@@ -47,6 +76,7 @@ pub struct Vertex {
     pub position: [f32; 3], // X, Y, Z
     pub normal: [f32; 3],
     pub uv: [f32; 2],
+    pub wind_weight: f32,
 }
 
 impl Vertex {
@@ -70,6 +100,12 @@ impl Vertex {
                     shader_location: 2,
                     format: wgpu::VertexFormat::Float32x2,
                 },
+                wgpu::VertexAttribute {
+                    offset: (mem::size_of::<[f32; 3]>() * 2 + mem::size_of::<[f32; 2]>())
+                        as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32, // wind weight
+                },
             ],
         }
     }
@@ -83,27 +119,135 @@ pub struct AssetMesh;
 pub struct MeshInstance {
     pub bind_group: wgpu::BindGroup, // Passed to render_pass.set_bind_group(2, ...)
     pub buffer: wgpu::Buffer,        // Passed to queue.write_buffer(...)
+    pub state: MeshUniformState,     // What the buffer holds
+}
+
+/// The matrices a MeshInstance's uniform buffer holds, so "Write Mesh Uniforms" only
+/// writes the buffers of instances whose GlobalTransform changed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MeshUniformState {
+    /// Model matrix currently in the buffer.
+    pub model: Mat4,
+    /// Model matrix of the frame before.
+    pub prev_model: Mat4,
+}
+
+impl MeshUniformState {
+    /// A brand new instance at `model`: no motion yet.
+    pub fn new(model: Mat4) -> Self {
+        Self {
+            model,
+            prev_model: model,
+        }
+    }
+
+    pub fn uniform(&self) -> MeshUniform {
+        MeshUniform::from_matrices(self.model, self.prev_model)
+    }
+
+    /// Moves on to this frame's `model` and returns the uniform to write, None when
+    /// the buffer already holds it. An instance that just stopped moving gets one more
+    /// write, so prev_model catches up and its velocity drops to zero.
+    pub fn advance(&mut self, model: Mat4) -> Option<MeshUniform> {
+        if self.model == model && self.prev_model == model {
+            return None;
+        }
+        self.prev_model = self.model;
+        self.model = model;
+        Some(self.uniform())
+    }
 }
 
+/// A mesh's place in the GeometryPool. Draw with the block's buffers, `index_offset`
+/// as the first index and `vertex_offset` as the base vertex.
 #[derive(Component)]
 pub struct GpuGeometry {
+    pub pool_block_id: u32,
+    pub vertex_offset: u32,
+    pub index_offset: u32,
+    pub index_count: u32,
+    // The block's buffers, shared with every mesh in it
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
-    pub index_count: u32,
+    // 16-bit for meshes under 65536 vertices, see MeshData::index_format
+    pub index_format: wgpu::IndexFormat,
+    // Modified meshes are written in place while they fit in it
+    pub allocation: GeometryAllocation,
+}
+
+impl GpuGeometry {
+    /// Index range and base vertex of the mesh, for draw_indexed.
+    pub fn draw_range(&self) -> (std::ops::Range<u32>, i32) {
+        (
+            self.index_offset..self.index_offset + self.index_count,
+            self.vertex_offset as i32,
+        )
+    }
 }
 
+// Local-space bounds of a mesh asset, copied from MeshData so render systems
+// can cull/pick without touching the CPU vertex data.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct MeshBounds(pub Aabb);
+
 pub fn register_mesh_handlers(world: &World) {
+    // GPU geometry is created when the mesh data lands on the asset entity,
+    // and updated whenever it is set again (e.g. a hot reload)
     world
-        .system_named::<(&MeshData, &mut RenderContext)>("Init Mesh GPU buffers")
-        .without(GpuGeometry::id())
-        .kind(flecs::pipeline::OnStore)
+        .observer::<flecs::OnSet, (&MeshData, &RenderContext)>()
+        .term_at(1)
+        .filter()
+        .without(PrewarmAsset) // Uploaded by the prewarm queue
         .each_entity(|entity, (mesh_data, context)| {
-            let (v_buf, i_buf, count) = create_gpu_buffer(&context.device, mesh_data);
+            init_gpu_geometry(entity, mesh_data, context);
+        });
+
+    // Handle::modify: written into the existing buffers while the mesh still fits. A mesh
+    // without GPU geometry yet is uploaded as it is now when that happens
+    world
+        .entity_from::<AssetServer>()
+        .observe_payload_entity::<&AssetChanged>(|server, AssetChanged(event)| {
+            let AssetEvent::MeshModified { id } = *event;
+            let world = server.world();
+            let Some(mesh) = Handle::<MeshData>::from_id(id).try_get_entity(&world) else {
+                return;
+            };
+            if !mesh.has(GpuGeometry::id()) {
+                return;
+            }
+            world.try_get::<&RenderContext>(|context| {
+                mesh.try_get::<&MeshData>(|mesh_data| init_gpu_geometry(mesh, mesh_data, context));
+            });
+        });
+
+    world
+        .observer::<flecs::OnRemove, &MeshData>()
+        .each_entity(|entity, _| {
+            entity.remove(GpuGeometry::id());
+            entity.remove(MeshBounds::id());
+        });
+
+    // The ranges go back to the pool (a few frames later, see GeometryPool::free)
+    world
+        .observer::<flecs::OnRemove, (&GpuGeometry, &RenderContext)>()
+        .term_at(1)
+        .filter()
+        .each(|(geometry, context)| {
+            context.geometry_pool.free(geometry.allocation.clone());
+        });
 
-            entity.set(GpuGeometry {
-                vertex_buffer: v_buf,
-                index_buffer: i_buf,
-                index_count: count,
+    // Meshes that arrived before the renderer was initialized
+    let meshes_without_gpu = world
+        .query::<&MeshData>()
+        .without(GpuGeometry::id())
+        .without(PrewarmAsset)
+        .build();
+
+    world
+        .observer::<flecs::OnSet, &RenderContext>()
+        .each(move |context| {
+            meshes_without_gpu.each_entity(|entity, mesh_data| {
+                init_gpu_geometry(entity, mesh_data, context);
             });
         });
 
@@ -117,92 +261,222 @@ pub fn register_mesh_handlers(world: &World) {
             }
         });
 
-    // Find entities that have a mesh and position, but NO GPU data yet.
+    // Per-object uniforms are written in PhaseRenderPrepare: after "transform_propagation_system"
+    // (PostUpdate), so an entity spawned this frame draws at its transform on its first
+    // frame, and before any pass reads them. An instance is created with its uniform
+    // already in the buffer; from then on only changed transforms are written, and
+    // instances with a StaticTransform aren't even looked at.
     world
         .system_named::<(&GlobalTransform, &mut RenderContext)>("Setup Meshes in GPU")
         .with((AssetMesh, Wildcard))
         .without(MeshInstance::id())
-        .kind(flecs::pipeline::OnUpdate)
+        .kind(PhaseRenderPrepare)
         .each_entity(|entity, (global_transform, context)| {
-            // 1. Calculate Matrices
-            // We take the Position/Rotation/Scale from the ECS and turn it into
-            // the 4x4 matrix the shader expects.
-            let uniform = MeshUniform::from_transform(global_transform);
-
-            // 2. Allocate VRAM (Expensive!)
-            // We ask the GPU to reserve 128 bytes of memory for this specific object.
-            let buffer = context
-                .device
-                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("Mesh Uniform Buffer"),
-                    contents: bytemuck::cast_slice(&[uniform]),
-                    // COPY_DST is crucial: it allows us to update this buffer later!
-                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-                });
-
-            // 3. Create the Bind Group ( The "Signpost")
-            // We create a handle that tells the shader: "When you ask for Group 2, look at THIS buffer."
-            let bind_group = context
-                .device
-                .create_bind_group(&wgpu::BindGroupDescriptor {
-                    label: Some("Mesh Bind Group"),
-                    layout: &context.pbr_program.mesh_layout, // Defined in Renderer::new()
-                    entries: &[wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: buffer.as_entire_binding(),
-                    }],
-                });
-
-            entity.set(MeshInstance { bind_group, buffer });
+            entity.set(create_mesh_instance(global_transform, context));
         });
 
+    let instances = world
+        .query::<(&GlobalTransform, &mut MeshInstance)>()
+        .without(StaticTransform::id())
+        .set_cached()
+        .build();
     world
-        .system_named::<(&GlobalTransform, &MeshInstance, &mut RenderContext)>(
-            "Setup Mesh in GPU on change",
-        )
-        .kind(flecs::pipeline::PostUpdate)
-        .detect_changes()
-        .each(|(global_transform, gpu_mesh, context)| {
-            let uniform = MeshUniform::from_transform(global_transform);
-
-            // 2. Upload Data (Cheap!)
-            // We don't allocate memory. We just copy 128 bytes over the PCIe bus
-            // into the existing buffer we created in Phase 1.
-            context.queue.write_buffer(
-                &gpu_mesh.buffer, // The buffer stored in the GpuMesh component
-                0,                // Offset 0 (Overwrite from start)
-                bytemuck::cast_slice(&[uniform]),
-            );
+        .system_named::<(&RenderContext, &mut RenderStats)>("Write Mesh Uniforms")
+        .kind(PhaseRenderPrepare)
+        .each(move |(context, stats)| {
+            let mut writes = 0;
+            instances.each(|(global_transform, instance)| {
+                if let Some(uniform) = instance.state.advance(global_transform.0) {
+                    context
+                        .queue
+                        .write_buffer(&instance.buffer, 0, bytemuck::bytes_of(&uniform));
+                    writes += 1;
+                }
+            });
+            stats.mesh_uniform_writes = writes;
+            stats.mesh_uniform_bytes = (writes * mem::size_of::<MeshUniform>()) as u64;
         });
 }
 
-fn create_gpu_buffer(device: &wgpu::Device, data: &MeshData) -> (wgpu::Buffer, wgpu::Buffer, u32) {
-    // 1. Interleave Data (SoA -> AoS)
-    // We combine pos, normal, uv into a single 'Vertex' struct list
-    let vertex_count = data.vertices.len();
-    let mut vertices = Vec::with_capacity(vertex_count);
-
-    for i in 0..vertex_count {
-        vertices.push(Vertex {
-            position: data.vertices[i].position,
-            normal: data.vertices[i].normal,
-            uv: data.vertices[i].uv,
+/// Uniform buffer and bind group of a new mesh instance at `global_transform`.
+pub(crate) fn create_mesh_instance(
+    global_transform: &GlobalTransform,
+    context: &RenderContext,
+) -> MeshInstance {
+    // 1. Calculate Matrices
+    // We take the Position/Rotation/Scale from the ECS and turn it into
+    // the 4x4 matrix the shader expects.
+    let state = MeshUniformState::new(global_transform.0);
+    let uniform = state.uniform();
+
+    // 2. Allocate VRAM (Expensive!)
+    // We ask the GPU to reserve 128 bytes of memory for this specific object.
+    let buffer = context
+        .device
+        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Mesh Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            // COPY_DST is crucial: it allows us to update this buffer later!
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+    // 3. Create the Bind Group ( The "Signpost")
+    // We create a handle that tells the shader: "When you ask for Group 2, look at THIS buffer."
+    let bind_group = context
+        .device
+        .create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Mesh Bind Group"),
+            layout: &context.pbr_program.mesh_layout, // Defined in Renderer::new()
+            entries: &[wgpu::BindGroupEntry {
+                binding: reflected::pbr::MESH,
+                resource: buffer.as_entire_binding(),
+            }],
         });
+
+    MeshInstance {
+        bind_group,
+        buffer,
+        state,
     }
+}
 
-    use wgpu::util::DeviceExt;
+pub(crate) fn init_gpu_geometry(entity: EntityView, mesh_data: &MeshData, context: &RenderContext) {
+    let vertices = interleave(mesh_data);
+    let vertex_bytes: &[u8] = bytemuck::cast_slice(&vertices);
+    let indices = &mesh_data.indices;
+    let vertex_count = vertices.len() as u32;
+    let index_count = indices.len() as u32;
+    let index_format = match mesh_data.index_format() {
+        IndexFormat::U16 => wgpu::IndexFormat::Uint16,
+        IndexFormat::U32 => wgpu::IndexFormat::Uint32,
+    };
+    let pool = &context.geometry_pool;
 
-    let v_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: Some("Mesh Vertex Buffer"),
-        contents: bytemuck::cast_slice(&vertices),
-        usage: wgpu::BufferUsages::VERTEX,
+    // Fits in the current allocation: overwrite it instead of allocating a new one.
+    // Otherwise the allocation that was too small (or of the wrong index width), if any
+    let outgrown = entity.try_get::<&mut GpuGeometry>(|geometry| {
+        let allocation = &geometry.allocation;
+        if vertex_count as usize <= allocation.vertices.len()
+            && index_count as usize <= allocation.indices.len()
+            && allocation.index_format == index_format
+        {
+            pool.write(&context.queue, allocation, vertex_bytes, indices);
+            geometry.index_count = index_count;
+            None
+        } else {
+            Some(allocation.clone())
+        }
     });
 
-    let i_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: Some("Mesh Index Buffer"),
-        contents: bytemuck::cast_slice(&data.indices),
-        usage: wgpu::BufferUsages::INDEX,
-    });
+    let reallocate = match outgrown {
+        Some(None) => false,
+        // Replacing the component doesn't run the OnRemove observer
+        Some(Some(old)) => {
+            pool.free(old);
+            true
+        }
+        None => true,
+    };
+    if reallocate {
+        let allocation = pool.allocate(&context.device, vertex_count, index_count, index_format);
+        pool.write(&context.queue, &allocation, vertex_bytes, indices);
+        let (vertex_buffer, index_buffer) = pool
+            .buffers(allocation.block)
+            .expect("block of a new allocation");
+
+        entity.set(GpuGeometry {
+            pool_block_id: allocation.block,
+            vertex_offset: allocation.vertices.start,
+            index_offset: allocation.indices.start,
+            index_count,
+            vertex_buffer,
+            index_buffer,
+            index_format,
+            allocation,
+        });
+    }
+
+    entity.set(MeshBounds(mesh_data.aabb));
+    entity.remove(Regenerating);
+}
 
-    (v_buffer, i_buffer, data.indices.len() as u32)
+// 1. Interleave Data (SoA -> AoS)
+// We combine pos, normal, uv and wind weight into a single 'Vertex' struct list
+fn interleave(data: &MeshData) -> Vec<Vertex> {
+    data.vertices
+        .iter()
+        .map(|vertex| Vertex {
+            position: vertex.position,
+            normal: vertex.normal,
+            uv: vertex.uv,
+            wind_weight: vertex.wind_weight,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::*;
+
+    fn matrices(uniform: &MeshUniform) -> (Mat4, Mat4) {
+        (
+            Mat4::from_cols_array_2d(&uniform.model),
+            Mat4::from_cols_array_2d(&uniform.prev_model),
+        )
+    }
+
+    fn at(x: f32) -> Mat4 {
+        Mat4::from_translation(Vec3::new(x, 2.0, 3.0))
+    }
+
+    #[test]
+    fn a_new_instance_holds_its_transform_with_no_motion() {
+        let state = MeshUniformState::new(at(1.0));
+        assert_eq!(matrices(&state.uniform()), (at(1.0), at(1.0)));
+    }
+
+    #[test]
+    fn a_still_instance_is_never_written() {
+        let mut state = MeshUniformState::new(at(1.0));
+        for _ in 0..10 {
+            assert!(state.advance(at(1.0)).is_none());
+        }
+    }
+
+    #[test]
+    fn a_moving_instance_is_written_every_frame_and_once_more_when_it_stops() {
+        let mut state = MeshUniformState::new(at(1.0));
+        let mut previous = at(1.0);
+        for step in 2..=6 {
+            let model = at(step as f32);
+            let uniform = state.advance(model).expect("written");
+            // With last frame's matrix
+            assert_eq!(matrices(&uniform), (model, previous));
+            previous = model;
+        }
+
+        let stopped = state.advance(previous).expect("written once more");
+        assert_eq!(matrices(&stopped), (previous, previous));
+        for _ in 0..10 {
+            assert!(state.advance(previous).is_none());
+        }
+    }
+
+    #[test]
+    fn a_still_scene_writes_nothing() {
+        let mut scene: Vec<MeshUniformState> = (0..1000)
+            .map(|index| MeshUniformState::new(Mat4::from_translation(Vec3::X * index as f32)))
+            .collect();
+        let writes: usize = (0..100)
+            .map(|_| {
+                scene
+                    .iter_mut()
+                    .filter_map(|state| state.advance(state.model))
+                    .count()
+            })
+            .sum();
+        assert_eq!(writes, 0);
+    }
 }
@@ -0,0 +1,633 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use catalyst_core::{
+    App, bounds::Aabb, camera::Camera, physics::ColliderDefinition, pipeline::PhaseRenderPrepare,
+    transform::GlobalTransform,
+};
+use flecs_ecs::prelude::*;
+use glam::Vec3;
+
+use crate::{
+    RenderContext, RenderStats,
+    frustum_culling::FrustumCulled,
+    material::AssetMaterial,
+    mesh::{AssetMesh, MeshBounds, MeshInstance},
+    portal_culling::RoomCulled,
+    render_order::RenderOrder,
+    static_batching::StaticBatched,
+    visibility::ViewFrusta,
+};
+
+/// Occlusion queries per frame, over all views. Entities past what fits are always drawn.
+pub const MAX_OCCLUSION_QUERIES: u32 = 1024;
+
+// A camera this close to a proxy may have clipped its front faces with the near plane
+const NEAR_MARGIN: f32 = 0.5;
+
+/// Marks a mesh entity worth testing against the depth buffer before drawing it, such
+/// as a high-poly statue or a dense foliage cluster. Its bounding box is drawn after the
+/// opaque geometry of each view with an occlusion query; while no fragment of the box
+/// passes the depth test, the main views skip the entity. Results arrive a few frames
+/// late, so hidden entities pop back in with that delay: only worth it for meshes far
+/// more expensive than a box. Entities with a RenderOrder are always drawn.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct OcclusionCullable;
+
+/// On OcclusionCullable entities hidden behind other geometry in every view. Mirrored
+/// views of planar reflections and reflection probe captures still draw them.
+#[derive(Component)]
+pub struct OcclusionCulled;
+
+/// Occlusion culling of OcclusionCullable entities, in PhaseRenderPrepare after frustum
+/// culling. Reads back the query results of an earlier frame, updates the OcclusionCulled
+/// tags and writes this frame's proxies.
+#[derive(Component, Clone, Debug)]
+pub struct OcclusionCulling {
+    /// Off draws everything. Read every frame.
+    pub enabled: bool,
+    /// Frames an entity stays drawn after a query last saw it, so results arriving late
+    /// don't hide what just came into view.
+    pub grace_frames: u32,
+    /// A camera moving further than this in one frame (a cut or teleport) throws the
+    /// results in flight away and draws everything again until new ones arrive.
+    pub teleport_distance: f32,
+    history: OcclusionHistory,
+    eyes: Vec<Vec3>,
+    tested: usize,
+    occluded: usize,
+    flushes: u64,
+}
+
+impl Default for OcclusionCulling {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            grace_frames: 4,
+            teleport_distance: 5.0,
+            history: OcclusionHistory::default(),
+            eyes: Vec::new(),
+            tested: 0,
+            occluded: 0,
+            flushes: 0,
+        }
+    }
+}
+
+impl OcclusionCulling {
+    /// Entities whose proxy was queried this frame.
+    pub fn tested_entities(&self) -> usize {
+        self.tested
+    }
+
+    /// Entities not drawn this frame because they are hidden.
+    pub fn occluded_entities(&self) -> usize {
+        self.occluded
+    }
+
+    /// Times the results were thrown away because a camera jumped.
+    pub fn flushes(&self) -> u64 {
+        self.flushes
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct HistoryEntry {
+    // Frame a query last saw the entity, or it was last drawn without a test
+    last_visible: u64,
+    occluded: bool,
+    // What the OcclusionCulled tag says
+    tagged: bool,
+    // Frame the entity was last recorded in, entries not recorded are dropped
+    frame: u64,
+}
+
+/// Whether each tested entity counts as occluded, from the query results as they arrive.
+/// Kept apart from the queries so the grace period and flushes can be checked without
+/// a GPU. New entities count as visible.
+#[derive(Clone, Debug, Default)]
+pub struct OcclusionHistory {
+    entries: HashMap<Entity, HistoryEntry>,
+    frame: u64,
+}
+
+impl OcclusionHistory {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Starts a frame of records.
+    pub fn begin(&mut self) {
+        self.frame += 1;
+    }
+
+    fn entry(&mut self, entity: Entity) -> &mut HistoryEntry {
+        let frame = self.frame;
+        let entry = self.entries.entry(entity).or_insert(HistoryEntry {
+            last_visible: frame,
+            occluded: false,
+            tagged: false,
+            frame,
+        });
+        entry.frame = frame;
+        entry
+    }
+
+    /// Records a query result of `entity`: `samples` fragments of its proxy passed the
+    /// depth test. Without any, it is occluded once `grace_frames` have passed since a
+    /// query last saw it.
+    pub fn record(&mut self, entity: Entity, samples: u64, grace_frames: u32) {
+        let frame = self.frame;
+        let entry = self.entry(entity);
+        if samples > 0 {
+            entry.last_visible = frame;
+            entry.occluded = false;
+        } else if frame - entry.last_visible > grace_frames as u64 {
+            entry.occluded = true;
+        }
+    }
+
+    /// Keeps `entity` as it is, no result for it arrived this frame.
+    pub fn keep(&mut self, entity: Entity) {
+        self.entry(entity);
+    }
+
+    /// Draws `entity`, which can't be tested this frame, and restarts its grace period.
+    pub fn reveal(&mut self, entity: Entity) {
+        let frame = self.frame;
+        let entry = self.entry(entity);
+        entry.last_visible = frame;
+        entry.occluded = false;
+    }
+
+    /// Reveals every entity.
+    pub fn flush(&mut self) {
+        for entry in self.entries.values_mut() {
+            entry.last_visible = self.frame;
+            entry.occluded = false;
+        }
+    }
+
+    pub fn is_occluded(&self, entity: Entity) -> bool {
+        self.entries
+            .get(&entity)
+            .is_some_and(|entry| entry.occluded)
+    }
+
+    /// Drops the entities not recorded since `begin`. Returns the entities whose tag has
+    /// to change, and whether they are occluded now.
+    pub fn end(&mut self) -> Vec<(Entity, bool)> {
+        let frame = self.frame;
+        let mut changed = Vec::new();
+        self.entries.retain(|entity, entry| {
+            if entry.frame != frame {
+                if entry.tagged {
+                    changed.push((*entity, false));
+                }
+                return false;
+            }
+            if entry.occluded != entry.tagged {
+                entry.tagged = entry.occluded;
+                changed.push((*entity, entry.occluded));
+            }
+            true
+        });
+        changed
+    }
+}
+
+/// World-space box drawn for an entity's occlusion query.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct OcclusionProxy {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+const _: () = assert!(std::mem::size_of::<OcclusionProxy>() == 24);
+
+/// The query set the main pass writes and the readback of its results, read back like
+/// the pass timestamps: resolved after the frame, mapped without blocking, and skipped
+/// while the previous results are still in flight.
+pub struct OcclusionQueries {
+    query_set: wgpu::QuerySet,
+    proxy_buffer: wgpu::Buffer,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    // This frame's proxies, their queries one block per view
+    proxies: Vec<Entity>,
+    views: usize,
+    // Entities and view count of the results being read back
+    in_flight: Option<(Vec<Entity>, usize)>,
+    copy_recorded: bool,
+    // The results in flight predate a flush, thrown away when they arrive
+    stale: bool,
+    mapped: Arc<AtomicBool>,
+}
+
+impl OcclusionQueries {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let results_size = MAX_OCCLUSION_QUERIES as u64 * std::mem::size_of::<u64>() as u64;
+
+        Self {
+            query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("Occlusion Queries"),
+                ty: wgpu::QueryType::Occlusion,
+                count: MAX_OCCLUSION_QUERIES,
+            }),
+            proxy_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Occlusion Proxy Buffer"),
+                size: MAX_OCCLUSION_QUERIES as u64 * std::mem::size_of::<OcclusionProxy>() as u64,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Occlusion Queries Resolve"),
+                size: results_size,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            readback_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Occlusion Queries Readback"),
+                size: results_size,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            proxies: Vec::new(),
+            views: 0,
+            in_flight: None,
+            copy_recorded: false,
+            stale: false,
+            mapped: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Proxies that fit in the query set with `views` views.
+    pub fn capacity(views: usize) -> usize {
+        MAX_OCCLUSION_QUERIES as usize / views.max(1)
+    }
+
+    /// This frame's proxies, tested in each of `views` views. Past `capacity` they are dropped.
+    pub fn write_proxies(
+        &mut self,
+        queue: &wgpu::Queue,
+        proxies: &[(Entity, OcclusionProxy)],
+        views: usize,
+    ) {
+        let count = proxies.len().min(Self::capacity(views));
+        self.proxies = proxies[..count].iter().map(|(entity, _)| *entity).collect();
+        self.views = views;
+        if count == 0 {
+            return;
+        }
+
+        let boxes: Vec<OcclusionProxy> = proxies[..count].iter().map(|(_, proxy)| *proxy).collect();
+        queue.write_buffer(&self.proxy_buffer, 0, bytemuck::cast_slice(&boxes));
+    }
+
+    /// The query set for the main pass descriptor. None without proxies this frame.
+    pub fn query_set(&self) -> Option<&wgpu::QuerySet> {
+        (!self.proxies.is_empty() && self.views > 0).then_some(&self.query_set)
+    }
+
+    pub(crate) fn proxy_buffer(&self) -> &wgpu::Buffer {
+        &self.proxy_buffer
+    }
+
+    /// Proxies of view `view`, and the index of their first query.
+    pub(crate) fn view_queries(&self, view: usize) -> Option<(u32, u32)> {
+        if view >= self.views || self.proxies.is_empty() {
+            return None;
+        }
+        let count = self.proxies.len() as u32;
+        Some((count, view as u32 * count))
+    }
+
+    /// Records the copy of this frame's results, written by the main pass for `views`
+    /// views. Skipped while the previous results are being read back, or when the views
+    /// drawn aren't the ones the proxies were written for (unwritten queries read as 0).
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder, views: usize) {
+        if self.in_flight.is_some() || self.query_set().is_none() || views != self.views {
+            return;
+        }
+
+        let queries = (self.proxies.len() * self.views) as u32;
+        encoder.resolve_query_set(&self.query_set, 0..queries, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            queries as u64 * std::mem::size_of::<u64>() as u64,
+        );
+
+        self.in_flight = Some((self.proxies.clone(), self.views));
+        self.copy_recorded = true;
+    }
+
+    /// Call after the frame is submitted: starts reading back what `resolve` recorded.
+    pub fn request_readback(&mut self) {
+        if !std::mem::take(&mut self.copy_recorded) {
+            return;
+        }
+
+        let mapped = self.mapped.clone();
+        self.readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                if result.is_ok() {
+                    mapped.store(true, Ordering::Release);
+                }
+            });
+    }
+
+    /// Throws away the results in flight.
+    pub fn flush(&mut self) {
+        self.stale = self.in_flight.is_some();
+    }
+
+    /// Fragments of each entity's proxy that passed the depth test, summed over the
+    /// views, once the GPU has finished the frame that tested them.
+    pub fn take_results(&mut self, device: &wgpu::Device) -> Option<Vec<(Entity, u64)>> {
+        if self.in_flight.is_none() || self.copy_recorded {
+            return None;
+        }
+
+        let _ = device.poll(wgpu::PollType::Poll);
+        if !self.mapped.swap(false, Ordering::Acquire) {
+            return None;
+        }
+
+        let (entities, views) = self.in_flight.take().unwrap_or_default();
+        let results = {
+            let data = self.readback_buffer.slice(..).get_mapped_range();
+            let samples: &[u64] = bytemuck::cast_slice(&data);
+            entities
+                .iter()
+                .enumerate()
+                .map(|(index, entity)| {
+                    let total = (0..views)
+                        .map(|view| samples[view * entities.len() + index])
+                        .sum();
+                    (*entity, total)
+                })
+                .collect()
+        };
+        self.readback_buffer.unmap();
+
+        if std::mem::take(&mut self.stale) {
+            return None;
+        }
+        Some(results)
+    }
+}
+
+pub fn register_occlusion_culling_systems(app: &mut App) {
+    app.register_singleton_default::<OcclusionCulling>();
+    app.register_tag::<OcclusionCullable>();
+    app.world.component::<OcclusionCulled>();
+
+    // The entities of the unordered mesh query of "Render Frame" that are still drawn
+    // after portal and frustum culling
+    let cullable = app
+        .world
+        .query::<&GlobalTransform>()
+        .with(OcclusionCullable)
+        .with(MeshInstance::id())
+        .with((AssetMesh, flecs::Wildcard))
+        .with((AssetMaterial, flecs::Wildcard))
+        .without(RenderOrder::id())
+        .without(Camera::id())
+        .without(ColliderDefinition::id())
+        .without(RoomCulled)
+        .without(FrustumCulled)
+        .without(StaticBatched::id())
+        .set_cached()
+        .build();
+
+    // After "Frustum Culling" (same phase, registered first): entities it culled aren't
+    // tested, and are revealed when they come back into view
+    app.world
+        .system_named::<(
+            &mut OcclusionCulling,
+            &mut RenderContext,
+            &ViewFrusta,
+            &mut RenderStats,
+        )>("Occlusion Culling")
+        .kind(PhaseRenderPrepare)
+        .each_iter(move |iter, _, (culling, context, frusta, stats)| {
+            catalyst_core::profile_scope!("Occlusion Culling");
+            let world = iter.world();
+            let mut results = context.occlusion.take_results(&context.device);
+
+            // A camera cut: what was hidden from the old position says nothing about the new one
+            let eyes: Vec<Vec3> = frusta.0.iter().map(|view| view.eye).collect();
+            let jumped = eyes.len() != culling.eyes.len()
+                || eyes
+                    .iter()
+                    .zip(&culling.eyes)
+                    .any(|(eye, last)| eye.distance(*last) > culling.teleport_distance);
+            culling.eyes = eyes;
+
+            let history = &mut culling.history;
+            history.begin();
+            if jumped && !history.is_empty() {
+                history.flush();
+                context.occlusion.flush();
+                results = None;
+                culling.flushes += 1;
+            }
+            let results: HashMap<Entity, u64> = results.unwrap_or_default().into_iter().collect();
+
+            let views = frusta.0.len();
+            let grace_frames = culling.grace_frames;
+            let capacity = OcclusionQueries::capacity(views);
+            let mut proxies = Vec::new();
+            if culling.enabled && views > 0 {
+                cullable.each_iter(|iter, index, global| {
+                    let entity = iter.entity(index).id();
+                    let mesh = iter.pair(3).second_id().id();
+                    // Meshes still loading have no bounds yet
+                    let Some(bounds) = world
+                        .entity_from_id(mesh)
+                        .try_get::<&MeshBounds>(|bounds| bounds.0.transform(&global.0))
+                    else {
+                        history.reveal(entity);
+                        return;
+                    };
+
+                    let near = Aabb::new(
+                        bounds.min - Vec3::splat(NEAR_MARGIN),
+                        bounds.max + Vec3::splat(NEAR_MARGIN),
+                    );
+                    if proxies.len() >= capacity
+                        || frusta.0.iter().any(|view| near.contains_point(view.eye))
+                    {
+                        history.reveal(entity);
+                        return;
+                    }
+
+                    match results.get(&entity) {
+                        Some(&samples) => history.record(entity, samples, grace_frames),
+                        None => history.keep(entity),
+                    }
+                    proxies.push((
+                        entity,
+                        OcclusionProxy {
+                            min: bounds.min.to_array(),
+                            max: bounds.max.to_array(),
+                        },
+                    ));
+                });
+            }
+            // Off, every entity is dropped from the history and loses its tag
+            let changed = history.end();
+            context
+                .occlusion
+                .write_proxies(&context.queue, &proxies, views);
+
+            for (entity, occluded) in changed {
+                let entity = world.entity_from_id(entity);
+                if !entity.is_alive() {
+                    continue;
+                }
+                if occluded {
+                    entity.add(OcclusionCulled);
+                } else {
+                    entity.remove(OcclusionCulled);
+                }
+            }
+
+            culling.tested = proxies.len();
+            culling.occluded = proxies
+                .iter()
+                .filter(|(entity, _)| history.is_occluded(*entity))
+                .count();
+            stats.occlusion_skipped_draws = culling.occluded * views;
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Above the ids flecs uses for its own entities
+    const FIRST_ID: u64 = 1 << 32;
+    const GRACE_FRAMES: u32 = 4;
+
+    fn statue_and_tree() -> (Entity, Entity) {
+        (Entity::new(FIRST_ID), Entity::new(FIRST_ID + 1))
+    }
+
+    // A frame where every query of `hidden` found nothing and the rest were seen
+    fn frame(
+        history: &mut OcclusionHistory,
+        hidden: &[Entity],
+        seen: &[Entity],
+    ) -> Vec<(Entity, bool)> {
+        history.begin();
+        for &entity in hidden {
+            history.record(entity, 0, GRACE_FRAMES);
+        }
+        for &entity in seen {
+            history.record(entity, 64, GRACE_FRAMES);
+        }
+        history.end()
+    }
+
+    // Both entities hidden for longer than the grace period
+    fn both_culled() -> OcclusionHistory {
+        let (statue, tree) = statue_and_tree();
+        let mut history = OcclusionHistory::default();
+        for _ in 0..=GRACE_FRAMES + 1 {
+            frame(&mut history, &[statue, tree], &[]);
+        }
+        assert!(history.is_occluded(statue) && history.is_occluded(tree));
+        history
+    }
+
+    #[test]
+    fn an_entity_is_culled_once_hidden_past_the_grace_period() {
+        let (statue, tree) = statue_and_tree();
+        let mut history = OcclusionHistory::default();
+        // New entities are drawn, even without a sample
+        assert!(frame(&mut history, &[statue], &[tree]).is_empty());
+        assert!(!history.is_occluded(statue));
+        assert_eq!(history.len(), 2);
+
+        for _ in 0..GRACE_FRAMES {
+            assert!(frame(&mut history, &[statue], &[tree]).is_empty());
+        }
+        assert!(!history.is_occluded(statue));
+
+        assert_eq!(frame(&mut history, &[statue], &[tree]), [(statue, true)]);
+        assert!(history.is_occluded(statue));
+        assert!(!history.is_occluded(tree));
+    }
+
+    #[test]
+    fn a_frame_without_results_keeps_the_entity_as_it_is() {
+        let (statue, tree) = statue_and_tree();
+        let mut history = both_culled();
+        history.begin();
+        history.keep(statue);
+        history.record(tree, 1, GRACE_FRAMES);
+        assert_eq!(history.end(), [(tree, false)]);
+        assert!(history.is_occluded(statue));
+
+        // A single sample brings it back
+        assert_eq!(frame(&mut history, &[], &[statue, tree]), [(statue, false)]);
+        assert!(!history.is_occluded(statue));
+    }
+
+    #[test]
+    fn a_flush_reveals_everything_and_restarts_the_grace_period() {
+        let (statue, tree) = statue_and_tree();
+        let mut history = both_culled();
+        // As the system does it: the flush comes in the frame the camera jumped
+        history.begin();
+        history.flush();
+        history.keep(statue);
+        history.keep(tree);
+        let mut changes = history.end();
+        changes.sort_by_key(|(entity, _)| *entity);
+        assert_eq!(changes, [(statue, false), (tree, false)]);
+
+        for _ in 0..GRACE_FRAMES {
+            assert!(frame(&mut history, &[statue, tree], &[]).is_empty());
+        }
+    }
+
+    #[test]
+    fn an_entity_that_cant_be_tested_is_drawn() {
+        let (statue, tree) = statue_and_tree();
+        let mut history = both_culled();
+        history.begin();
+        history.reveal(statue);
+        history.record(tree, 0, GRACE_FRAMES);
+        assert_eq!(history.end(), [(statue, false)]);
+        assert!(!history.is_occluded(statue));
+
+        // And its grace period starts over
+        for _ in 0..GRACE_FRAMES {
+            assert!(frame(&mut history, &[statue, tree], &[]).is_empty());
+        }
+    }
+
+    #[test]
+    fn an_entity_no_longer_tested_is_dropped_and_loses_its_tag() {
+        let (statue, tree) = statue_and_tree();
+        let mut history = both_culled();
+        assert_eq!(frame(&mut history, &[statue], &[]), [(tree, false)]);
+        assert_eq!(history.len(), 1);
+    }
+}
@@ -0,0 +1,252 @@
+use catalyst_core::{App, pipeline::PhaseRender3D};
+use flecs_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    frame_graph::{FrameGraph, GraphTextureDesc, PassStage},
+    layout::{StructLayout, rust_layout},
+    mesh::{AssetMesh, GpuGeometry, MeshInstance},
+    programs::{GpuProgram, outline_program::OutlineDraw},
+    render::RenderContext,
+};
+
+/// Distinct (color, thickness) outlines per frame. Entities asking for more share
+/// the last one.
+pub const MAX_OUTLINE_STYLES: usize = 8;
+
+/// Widest outline in pixels; thicker ones are clamped.
+pub const MAX_OUTLINE_THICKNESS: f32 = 8.0;
+
+// Mask texel for "no outlined mesh here"; the others are style index + 1
+const MASK_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Uint;
+
+/// Draws a colored border around the entity's mesh and the meshes of all its
+/// descendants, so tagging the root of a loaded scene outlines the whole model.
+/// The border follows the silhouette on screen and shows through anything in front
+/// of it. For selection feedback and interactive objects alike: the debug inspector
+/// puts one on the selected entity.
+///
+/// Outlined meshes are drawn into a mask, which a fullscreen pass dilates into
+/// borders on the final image, before the UI. Nothing is drawn, and no pass added,
+/// while no entity is outlined.
+#[derive(Component, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Outlined {
+    /// Linear RGBA, blended over the image by its alpha.
+    pub color: [f32; 4],
+    /// Border width in pixels, up to MAX_OUTLINE_THICKNESS.
+    pub thickness: f32,
+}
+
+impl Default for Outlined {
+    fn default() -> Self {
+        Self {
+            color: [1.0, 0.6, 0.1, 1.0],
+            thickness: 2.0,
+        }
+    }
+}
+
+impl Outlined {
+    pub fn new(color: [f32; 4], thickness: f32) -> Self {
+        Self { color, thickness }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct OutlineUniform {
+    pub colors: [[f32; 4]; MAX_OUTLINE_STYLES],
+    /// Thickness of each style in x, the rest unused (uniform arrays stride 16 bytes).
+    pub thickness: [[f32; 4]; MAX_OUTLINE_STYLES],
+    /// Largest thickness in use, how far the composite pass searches.
+    pub radius: u32,
+    pub _padding0: u32,
+    pub _padding1: [u32; 2],
+}
+
+const _: () = assert!(std::mem::size_of::<OutlineUniform>() == 272);
+
+impl OutlineUniform {
+    pub const LAYOUT: StructLayout = rust_layout!(OutlineUniform {
+        colors: [[f32; 4]; MAX_OUTLINE_STYLES],
+        thickness: [[f32; 4]; MAX_OUTLINE_STYLES],
+        radius: u32,
+        _padding0: u32,
+        _padding1: [u32; 2],
+    });
+}
+
+// This frame's styles, in the order they were first seen
+#[derive(Default)]
+struct OutlinePalette {
+    styles: Vec<([f32; 4], f32)>,
+}
+
+impl OutlinePalette {
+    fn slot(&mut self, outline: &Outlined) -> u32 {
+        let style = (
+            outline.color,
+            outline.thickness.clamp(0.0, MAX_OUTLINE_THICKNESS),
+        );
+        let index = match self.styles.iter().position(|existing| *existing == style) {
+            Some(index) => index,
+            None if self.styles.len() < MAX_OUTLINE_STYLES => {
+                self.styles.push(style);
+                self.styles.len() - 1
+            }
+            None => MAX_OUTLINE_STYLES - 1,
+        };
+        index as u32
+    }
+
+    fn uniform(&self) -> OutlineUniform {
+        let mut uniform = OutlineUniform {
+            colors: [[0.0; 4]; MAX_OUTLINE_STYLES],
+            thickness: [[0.0; 4]; MAX_OUTLINE_STYLES],
+            radius: 0,
+            _padding0: 0,
+            _padding1: [0; 2],
+        };
+        for (index, (color, thickness)) in self.styles.iter().enumerate() {
+            uniform.colors[index] = *color;
+            uniform.thickness[index][0] = *thickness;
+            uniform.radius = uniform.radius.max(thickness.ceil() as u32);
+        }
+        uniform
+    }
+}
+
+pub fn register_outline_systems(app: &mut App) {
+    let outlined_query = app.world.query::<&Outlined>().set_cached().build();
+
+    // After "Render Frame": the composite goes over the final image, TAA resolve included
+    app.world
+        .system_named::<(&RenderContext, &mut FrameGraph)>("Outline Pass")
+        .kind(PhaseRender3D)
+        .each(move |(context, graph)| {
+            if !outlined_query.is_true() {
+                return;
+            }
+            let (Some(backbuffer), Some(scene)) =
+                (graph.backbuffer(), graph.scene_targets().cloned())
+            else {
+                return;
+            };
+
+            let mut palette = OutlinePalette::default();
+            let mut draws = Vec::new();
+            outlined_query.each_entity(|entity, outline| {
+                let slot = palette.slot(outline);
+                collect_outline_draws(entity, slot, &mut draws);
+            });
+            if draws.is_empty() {
+                return;
+            }
+            context
+                .outline_program
+                .write_uniform(&context.queue, &palette.uniform());
+
+            let mask = graph.create_texture(
+                "Outline Mask",
+                GraphTextureDesc::screen(&context.config, MASK_FORMAT),
+            );
+
+            // Drawn at full resolution over the upscaled image, see DynamicResolution
+            let viewports = scene.screen_viewports.clone();
+            graph
+                .add_pass("Outline Mask", PassStage::PostProcess)
+                .write(mask)
+                .record_parallel(move |pass| {
+                    let timestamp_writes = pass.timestamp_writes();
+                    let view = pass.view(mask);
+                    let context = pass.context;
+
+                    let mut render_pass =
+                        pass.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                            label: Some("Outline Mask"),
+                            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                                view,
+                                resolve_target: None,
+                                depth_slice: None,
+                                ops: wgpu::Operations {
+                                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                                    store: wgpu::StoreOp::Store,
+                                },
+                            })],
+                            timestamp_writes,
+                            ..Default::default()
+                        });
+
+                    for (index, viewport) in viewports.iter().enumerate() {
+                        viewport.apply(&mut render_pass);
+                        let bind_group = &context.global_resources.view(index).bind_group;
+                        context
+                            .outline_program
+                            .record(&mut render_pass, (bind_group, &draws));
+                    }
+                });
+
+            graph
+                .add_pass("Outline Composite", PassStage::PostProcess)
+                .read(mask)
+                .read(backbuffer)
+                .write(backbuffer)
+                .record_parallel(move |pass| {
+                    let timestamp_writes = pass.timestamp_writes();
+                    let (mask, screen) = (pass.view(mask), pass.view(backbuffer));
+                    let context = pass.context;
+                    let bind_group = context
+                        .outline_program
+                        .composite_bind_group(&context.device, mask);
+
+                    let mut render_pass =
+                        pass.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                            label: Some("Outline Composite"),
+                            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                                view: screen,
+                                resolve_target: None,
+                                depth_slice: None,
+                                ops: wgpu::Operations {
+                                    load: wgpu::LoadOp::Load,
+                                    store: wgpu::StoreOp::Store,
+                                },
+                            })],
+                            timestamp_writes,
+                            ..Default::default()
+                        });
+                    context
+                        .outline_program
+                        .record_composite(&mut render_pass, &bind_group);
+                });
+        });
+}
+
+// The entity's mesh, if it has one on the GPU, and its descendants' meshes
+fn collect_outline_draws(entity: EntityView, slot: u32, draws: &mut Vec<OutlineDraw>) {
+    let geometry = entity.target(AssetMesh, 0).and_then(|mesh| {
+        mesh.try_get::<&GpuGeometry>(|geometry| {
+            (
+                geometry.vertex_buffer.clone(),
+                geometry.index_buffer.clone(),
+                geometry.index_format,
+                geometry.draw_range(),
+            )
+        })
+    });
+    if let Some((vertex_buffer, index_buffer, index_format, (indices, base_vertex))) = geometry {
+        entity.try_get::<&MeshInstance>(|instance| {
+            draws.push(OutlineDraw {
+                vertex_buffer,
+                index_buffer,
+                index_format,
+                indices,
+                base_vertex,
+                instance_bind_group: instance.bind_group.clone(),
+                slot,
+            });
+        });
+    }
+
+    entity.each_child(|child| collect_outline_draws(child, slot, draws));
+}
@@ -0,0 +1,678 @@
+use std::{collections::VecDeque, f32::consts::TAU};
+
+use catalyst_assets::{assets::Handle, material::TextureData};
+use catalyst_core::{
+    App,
+    camera::Camera,
+    pipeline::PhaseRenderPrepare,
+    player::PlayerIndex,
+    time::Time,
+    transform::GlobalTransform,
+};
+use flecs_ecs::prelude::*;
+use glam::{Vec3, Vec4};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    capabilities::RendererCapabilities,
+    frame_graph::{FrameGraph, PassStage, SceneTargets},
+    programs::{
+        gpu_particle_program::{
+            EMITTER_ACTIVE, EMITTER_GROUND, EMITTER_PAUSED, GpuParticleEmitter, GpuParticleSpawn,
+            SPAWN_RING_CAPACITY,
+        },
+        particle_program::{ParticleBatch, ParticleInstance},
+    },
+    render::{RenderContext, collect_views},
+    texture::GpuTexture,
+    viewport::{SplitScreenSettings, compute_viewports},
+    visibility::{ComputedVisibility, Frustum, ViewFrusta, ViewVolume, camera_view_proj},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum EmitterMode {
+    /// `spawn_rate` particles per second, for as long as the emitter exists.
+    Continuous,
+    /// N particles at once, the first time the emitter is visible. See ParticlePool::fire_burst.
+    Burst(u32),
+}
+
+/// Where an emitter's particles are simulated and how they are drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ParticleTier {
+    /// Simulated on the CPU and sorted back to front: alpha blended and textured.
+    /// Up to a few thousand per emitter.
+    #[default]
+    Cpu,
+    /// Launched on the CPU, then simulated in a compute pass and drawn with one
+    /// indirect draw for every GPU emitter, additively blended, as soft round spots
+    /// (`texture` and `spin` are ignored). Adds `drag` and ground collision. For sparks
+    /// and embers by the hundred thousand; see GpuParticleProgram. On adapters without
+    /// compute shaders or indirect draws the emitter is simulated on the CPU instead.
+    Gpu,
+}
+
+/// Spawns camera-facing particles from the entity's GlobalTransform, simulated on the CPU
+/// or the GPU depending on `tier`. Drawn after opaque geometry, without writing depth.
+/// Simulation pauses while no camera can see the emitter (ComputedVisibility).
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ParticleEmitter {
+    pub tier: ParticleTier,
+    pub mode: EmitterMode,
+    pub spawn_rate: f32,
+    /// Seconds, picked per particle between the two values.
+    pub lifetime: (f32, f32),
+    /// Launch direction in the emitter's local space.
+    pub direction: Vec3,
+    /// Half-angle of the launch cone in radians, 0 launches straight along `direction`.
+    pub cone_angle: f32,
+    /// Launch speed range.
+    pub speed: (f32, f32),
+    /// World space acceleration.
+    pub gravity: Vec3,
+    /// Colors and sizes are interpolated over each particle's lifetime.
+    pub start_color: [f32; 4],
+    pub end_color: [f32; 4],
+    pub start_size: f32,
+    pub end_size: f32,
+    /// Maximum rotation speed in radians per second, each particle picks one in -spin..spin.
+    pub spin: f32,
+    /// Multiplied with the color. None draws plain colored squares.
+    /// Not saved in scene files, asset handles only live as long as the session.
+    #[serde(skip)]
+    pub texture: Option<Handle<TextureData>>,
+    pub max_particles: usize,
+    /// GPU tier only: fraction of the speed lost per second, e.g. to air. 0 keeps it.
+    pub drag: f32,
+    /// GPU tier only: world height of a ground plane the particles bounce off, None
+    /// to let them fall through.
+    pub ground_height: Option<f32>,
+    /// GPU tier only: fraction of the vertical speed a bounce keeps.
+    pub restitution: f32,
+}
+
+impl Default for ParticleEmitter {
+    fn default() -> Self {
+        Self {
+            mode: EmitterMode::Continuous,
+            spawn_rate: 20.0,
+            lifetime: (1.0, 2.0),
+            direction: Vec3::Y,
+            cone_angle: 0.3,
+            speed: (1.0, 2.0),
+            gravity: Vec3::ZERO,
+            start_color: [1.0, 1.0, 1.0, 1.0],
+            end_color: [1.0, 1.0, 1.0, 0.0],
+            start_size: 0.2,
+            end_size: 0.05,
+            spin: 0.0,
+            texture: None,
+            max_particles: 1000,
+            drag: 0.0,
+            ground_height: None,
+            restitution: 0.4,
+        }
+    }
+}
+
+impl ParticleEmitter {
+    pub fn burst(count: u32) -> Self {
+        Self {
+            mode: EmitterMode::Burst(count),
+            max_particles: count as usize,
+            ..Default::default()
+        }
+    }
+
+    /// How far from the emitter a particle can get, for visibility tests.
+    pub fn reach(&self) -> f32 {
+        let lifetime = self.lifetime.0.max(self.lifetime.1);
+        let speed = self.speed.0.max(self.speed.1);
+        speed * lifetime
+            + 0.5 * self.gravity.length() * lifetime * lifetime
+            + self.start_size.max(self.end_size)
+    }
+
+    fn gpu_emitter(&self, paused: bool) -> GpuParticleEmitter {
+        let mut flags = EMITTER_ACTIVE;
+        if self.ground_height.is_some() {
+            flags |= EMITTER_GROUND;
+        }
+        if paused {
+            flags |= EMITTER_PAUSED;
+        }
+        GpuParticleEmitter {
+            start_color: self.start_color,
+            end_color: self.end_color,
+            gravity: self.gravity.to_array(),
+            // As a rate, the shader's exp(-drag * dt) leaves 1 - drag after a second
+            drag: -(1.0 - self.drag.clamp(0.0, 0.999)).ln(),
+            start_size: self.start_size,
+            end_size: self.end_size,
+            ground_height: self.ground_height.unwrap_or(0.0),
+            restitution: self.restitution,
+            flags,
+            _padding0: 0,
+            _padding1: 0,
+            _padding2: 0,
+        }
+    }
+}
+
+struct Particle {
+    position: Vec3,
+    velocity: Vec3,
+    age: f32,
+    lifetime: f32,
+    rotation: f32,
+    spin: f32,
+}
+
+/// Live particles of one emitter. Added next to every ParticleEmitter and dropped
+/// with the entity, or on the next frame after the emitter is removed.
+#[derive(Component)]
+pub struct ParticlePool {
+    particles: Vec<Particle>,
+    // Fraction of a particle carried over to the next frame in continuous mode
+    spawn_accumulator: f32,
+    burst_fired: bool,
+    paused: bool,
+    rng: u32,
+    // GPU tier: spawns waiting for "Prepare Particles", and the particles spawned and
+    // not expired yet as (expiry on `clock`, count), since the GPU's count isn't read back
+    on_gpu: bool,
+    gpu_spawns: VecDeque<GpuParticleSpawn>,
+    gpu_batches: VecDeque<(f64, usize)>,
+    gpu_alive: usize,
+    clock: f64,
+}
+
+impl ParticlePool {
+    fn new(seed: u64) -> Self {
+        Self {
+            particles: Vec::new(),
+            spawn_accumulator: 0.0,
+            burst_fired: false,
+            paused: false,
+            // xorshift never leaves 0
+            rng: (seed as u32 ^ (seed >> 32) as u32) | 1,
+            on_gpu: false,
+            gpu_spawns: VecDeque::new(),
+            gpu_batches: VecDeque::new(),
+            gpu_alive: 0,
+            clock: 0.0,
+        }
+    }
+
+    /// Live particles. On the GPU tier an upper bound, the spawned ones whose lifetime
+    /// isn't over: the GPU may have dropped some when its pool was full.
+    pub fn len(&self) -> usize {
+        self.particles.len() + self.gpu_alive
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// True while the emitter's particles are simulated on the GPU: a GPU tier emitter
+    /// on an adapter that supports it.
+    pub fn is_on_gpu(&self) -> bool {
+        self.on_gpu
+    }
+
+    /// True while the emitter is not visible and its simulation is on hold.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Lets a burst emitter fire again on its next simulated frame.
+    pub fn fire_burst(&mut self) {
+        self.burst_fired = false;
+    }
+
+    /// Drops the CPU tier's particles, and the GPU tier's spawns not sent yet.
+    pub fn clear(&mut self) {
+        self.particles.clear();
+        self.gpu_spawns.clear();
+        self.spawn_accumulator = 0.0;
+    }
+
+    fn random(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        (self.rng >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    fn random_range(&mut self, (min, max): (f32, f32)) -> f32 {
+        min + (max - min) * self.random()
+    }
+
+    // Uniform direction inside a cone around `axis`
+    fn random_in_cone(&mut self, axis: Vec3, angle: f32) -> Vec3 {
+        let cos_theta = 1.0 - self.random() * (1.0 - angle.cos());
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = self.random() * TAU;
+        let (u, v) = axis.any_orthonormal_pair();
+        axis * cos_theta + (u * phi.cos() + v * phi.sin()) * sin_theta
+    }
+
+    // Particles the emitter asks for this frame, before the max_particles cap
+    fn requested(&mut self, emitter: &ParticleEmitter, dt: f32) -> usize {
+        match emitter.mode {
+            EmitterMode::Continuous => {
+                self.spawn_accumulator += emitter.spawn_rate.max(0.0) * dt;
+                let count = self.spawn_accumulator.floor();
+                self.spawn_accumulator -= count;
+                count as usize
+            }
+            EmitterMode::Burst(count) if !self.burst_fired => {
+                self.burst_fired = true;
+                count as usize
+            }
+            EmitterMode::Burst(_) => 0,
+        }
+    }
+
+    // Where particles start, and the axis of the launch cone
+    fn launch_frame(emitter: &ParticleEmitter, global: &GlobalTransform) -> (Vec3, Vec3) {
+        let origin = global.0.transform_point3(Vec3::ZERO);
+        let axis = global
+            .0
+            .transform_vector3(emitter.direction)
+            .try_normalize()
+            .unwrap_or(Vec3::Y);
+        (origin, axis)
+    }
+
+    fn simulate(&mut self, emitter: &ParticleEmitter, global: &GlobalTransform, dt: f32) {
+        for particle in &mut self.particles {
+            particle.age += dt;
+            particle.velocity += emitter.gravity * dt;
+            particle.position += particle.velocity * dt;
+            particle.rotation += particle.spin * dt;
+        }
+        self.particles.retain(|particle| particle.age < particle.lifetime);
+
+        let requested = self.requested(emitter, dt);
+        let count = requested.min(emitter.max_particles.saturating_sub(self.particles.len()));
+        let (origin, axis) = Self::launch_frame(emitter, global);
+
+        for _ in 0..count {
+            let direction = self.random_in_cone(axis, emitter.cone_angle);
+            let speed = self.random_range(emitter.speed);
+            let lifetime = self.random_range(emitter.lifetime).max(f32::EPSILON);
+            let rotation = self.random() * TAU;
+            let spin = self.random_range((-emitter.spin, emitter.spin));
+
+            self.particles.push(Particle {
+                position: origin,
+                velocity: direction * speed,
+                age: 0.0,
+                lifetime,
+                rotation,
+                spin,
+            });
+        }
+    }
+
+    // GPU tier: only launches, the compute pass does the rest. Spawns that didn't fit
+    // in the ring last frame are still waiting in `gpu_spawns`.
+    fn emit_gpu(&mut self, emitter: &ParticleEmitter, global: &GlobalTransform, dt: f32) {
+        self.clock += dt as f64;
+        while let Some(&(expiry, count)) = self.gpu_batches.front() {
+            if expiry > self.clock {
+                break;
+            }
+            self.gpu_alive -= count;
+            self.gpu_batches.pop_front();
+        }
+
+        let requested = self.requested(emitter, dt);
+        let count = requested.min(emitter.max_particles.saturating_sub(self.gpu_alive));
+        if count == 0 {
+            return;
+        }
+        let (origin, axis) = Self::launch_frame(emitter, global);
+
+        let mut longest: f32 = 0.0;
+        self.gpu_spawns.reserve(count);
+        for _ in 0..count {
+            let direction = self.random_in_cone(axis, emitter.cone_angle);
+            let speed = self.random_range(emitter.speed);
+            let lifetime = self.random_range(emitter.lifetime).max(f32::EPSILON);
+            longest = longest.max(lifetime);
+
+            self.gpu_spawns.push_back(GpuParticleSpawn {
+                position: origin.to_array(),
+                emitter: 0,
+                velocity: (direction * speed).to_array(),
+                lifetime,
+            });
+        }
+        self.gpu_batches.push_back((self.clock + longest as f64, count));
+        self.gpu_alive += count;
+    }
+
+    fn write_instances(&self, emitter: &ParticleEmitter, eye: Vec3, out: &mut Vec<ParticleInstance>) {
+        let start = out.len();
+        let start_color = Vec4::from_array(emitter.start_color);
+        let end_color = Vec4::from_array(emitter.end_color);
+
+        out.extend(self.particles.iter().map(|particle| {
+            let t = (particle.age / particle.lifetime).clamp(0.0, 1.0);
+            ParticleInstance {
+                position: particle.position.to_array(),
+                size: emitter.start_size + (emitter.end_size - emitter.start_size) * t,
+                color: start_color.lerp(end_color, t).to_array(),
+                rotation: particle.rotation,
+            }
+        }));
+
+        // Back to front, so blending within the emitter is right
+        let distance = |instance: &ParticleInstance| {
+            Vec3::from_array(instance.position).distance_squared(eye)
+        };
+        out[start..].sort_by(|a, b| distance(b).total_cmp(&distance(a)));
+    }
+}
+
+pub fn register_particle_systems(app: &mut App) {
+    app.register_singleton_default::<ViewFrusta>();
+
+    app.world
+        .system_named::<&ParticleEmitter>("Setup Particle Pools")
+        .without(ParticlePool::id())
+        .kind(flecs::pipeline::OnUpdate)
+        .each_entity(|entity, _| {
+            entity.set(ParticlePool::new(*entity.id()));
+            if !entity.has(ComputedVisibility::id()) {
+                entity.set(ComputedVisibility::default());
+            }
+        });
+
+    // A despawned emitter's pool goes with the entity; this only catches an emitter
+    // removed from an entity that stays
+    app.world
+        .system_named::<&ParticlePool>("Drop Particle Pools")
+        .without(ParticleEmitter::id())
+        .kind(flecs::pipeline::OnUpdate)
+        .each_entity(|entity, _| {
+            entity.remove(ParticlePool::id());
+        });
+
+    let camera_query = app
+        .world
+        .query::<(&Camera, &GlobalTransform, Option<&PlayerIndex>)>()
+        .set_cached()
+        .build();
+
+    // Same views "Render Frame" draws, with the same viewport aspect ratios
+    app.world
+        .system_named::<(&RenderContext, &SplitScreenSettings, &mut ViewFrusta)>(
+            "Update View Frusta",
+        )
+        .kind(flecs::pipeline::PostUpdate)
+        .each(move |(context, split_screen, frusta)| {
+            let views = collect_views(&camera_query);
+            let viewports = compute_viewports(
+                split_screen.layout,
+                views.len(),
+                context.config.width,
+                context.config.height,
+            );
+
+            frusta.0 = views
+                .iter()
+                .zip(&viewports)
+                .map(|((_, camera, camera_transform), viewport)| {
+                    let view_proj =
+                        camera_view_proj(camera, *camera_transform, viewport.aspect_ratio());
+                    ViewVolume {
+                        frustum: Frustum::from_view_proj(view_proj),
+                        eye: camera_transform.transform_point3(Vec3::ZERO),
+                        view_proj,
+                    }
+                })
+                .collect();
+        });
+
+    // No views (no camera yet): nothing is drawn, so nothing counts as visible
+    app.world
+        .system_named::<(&ParticleEmitter, &GlobalTransform, &mut ComputedVisibility, &ViewFrusta)>(
+            "Compute Emitter Visibility",
+        )
+        .kind(flecs::pipeline::PostUpdate)
+        .each(|(emitter, global, visibility, frusta)| {
+            let center = global.0.transform_point3(Vec3::ZERO);
+            let radius = emitter.reach();
+            visibility.visible = frusta
+                .0
+                .iter()
+                .any(|view| view.frustum.intersects_sphere(center, radius));
+        });
+
+    app.world
+        .system_named::<(
+            &ParticleEmitter,
+            &mut ParticlePool,
+            &GlobalTransform,
+            &ComputedVisibility,
+            &RendererCapabilities,
+            &Time,
+        )>("Simulate Particles")
+        .kind(flecs::pipeline::PostUpdate)
+        .each(|(emitter, pool, global, visibility, capabilities, time)| {
+            let on_gpu = emitter.tier == ParticleTier::Gpu && capabilities.supports_gpu_particles();
+            if on_gpu != pool.on_gpu {
+                // The other tier's particles go; on the GPU they die with the emitter's slot
+                pool.particles.clear();
+                pool.gpu_spawns.clear();
+                pool.gpu_batches.clear();
+                pool.gpu_alive = 0;
+                pool.on_gpu = on_gpu;
+            }
+
+            pool.paused = !visibility.visible;
+            if pool.paused {
+                return;
+            }
+            if on_gpu {
+                pool.emit_gpu(emitter, global, time.delta_seconds());
+            } else {
+                pool.simulate(emitter, global, time.delta_seconds());
+            }
+        });
+
+    let pool_query = app
+        .world
+        .query::<(&ParticleEmitter, &mut ParticlePool)>()
+        .set_cached()
+        .build();
+
+    app.world
+        .system_named::<(&mut RenderContext, &ViewFrusta)>("Prepare Particles")
+        .kind(PhaseRenderPrepare)
+        .run(move |iter| prepare_particles(&iter.world(), &pool_query));
+}
+
+// Gathers every drawn CPU tier particle into one instance list, batched by texture, and
+// hands the GPU tier's emitters and spawns to GpuParticleProgram
+fn prepare_particles(world: &World, pool_query: &Query<(&ParticleEmitter, &mut ParticlePool)>) {
+    // Sorting is done for the first view; split-screen views share it
+    let eye = world
+        .get::<&ViewFrusta>(|frusta| frusta.0.first().map(|view| view.eye))
+        .unwrap_or(Vec3::ZERO);
+
+    let mut emitters: Vec<(Option<Entity>, Vec<ParticleInstance>)> = Vec::new();
+    let mut gpu_emitters: Vec<(Entity, GpuParticleEmitter, Vec<GpuParticleSpawn>)> = Vec::new();
+    let mut ring_room = SPAWN_RING_CAPACITY;
+    pool_query.each_entity(|entity, (emitter, pool)| {
+        // Listed while paused too, so the particles keep their emitter
+        if pool.on_gpu {
+            // What doesn't fit in the ring is sent next frame
+            let taken = pool.gpu_spawns.len().min(ring_room);
+            ring_room -= taken;
+            let spawns = pool.gpu_spawns.drain(..taken).collect();
+            gpu_emitters.push((entity.id(), emitter.gpu_emitter(pool.paused), spawns));
+            return;
+        }
+        if pool.paused || pool.is_empty() {
+            return;
+        }
+
+        // Emitters whose texture isn't loaded yet wait for it
+        let texture = match &emitter.texture {
+            None => None,
+            Some(handle) => match handle.try_get_entity(world) {
+                Some(texture) if texture.has(GpuTexture::id()) => Some(texture.id()),
+                _ => return,
+            },
+        };
+
+        let mut instances = Vec::with_capacity(pool.len());
+        pool.write_instances(emitter, eye, &mut instances);
+        emitters.push((texture, instances));
+    });
+    emitters.sort_by_key(|(texture, _)| texture.map(|texture| *texture));
+
+    world.get::<&mut RenderContext>(|context| {
+        let mut instances = Vec::new();
+        let mut batches: Vec<ParticleBatch> = Vec::new();
+        let mut current: Option<Option<Entity>> = None;
+
+        for (texture, emitter_instances) in emitters {
+            let start = instances.len() as u32;
+            instances.extend(emitter_instances);
+            let end = instances.len() as u32;
+
+            if current == Some(texture) {
+                if let Some(batch) = batches.last_mut() {
+                    batch.instances.end = end;
+                }
+                continue;
+            }
+            current = Some(texture);
+
+            let gpu_texture = texture
+                .and_then(|texture| {
+                    world
+                        .entity_from_id(texture)
+                        .try_get::<&GpuTexture>(|gpu_texture| gpu_texture.clone())
+                })
+                .unwrap_or_else(|| context.default_diffuse.clone());
+
+            batches.push(ParticleBatch {
+                texture: context.particle_program.texture_bind_group(
+                    &context.device,
+                    texture,
+                    &gpu_texture.view,
+                    &gpu_texture.sampler,
+                ),
+                instances: start..end,
+            });
+        }
+
+        let RenderContext {
+            particle_program,
+            gpu_particle_program,
+            device,
+            queue,
+            ..
+        } = context;
+        particle_program.prepare(&instances, batches, device, queue);
+
+        // Only set when the adapter supports it, like ParticlePool::on_gpu
+        let Some(gpu_particle_program) = gpu_particle_program else {
+            return;
+        };
+        let mut slots = Vec::with_capacity(gpu_emitters.len());
+        let mut spawns = Vec::new();
+        for (entity, emitter, emitter_spawns) in gpu_emitters {
+            // Past MAX_GPU_EMITTERS emitters spawn nothing
+            let Some(slot) = gpu_particle_program.emitter_slot(entity) else {
+                continue;
+            };
+            slots.push((slot, emitter));
+            spawns.extend(
+                emitter_spawns
+                    .into_iter()
+                    .map(|spawn| GpuParticleSpawn { emitter: slot, ..spawn }),
+            );
+        }
+        let dt = world.get::<&Time>(|time| time.delta_seconds());
+        gpu_particle_program.prepare(device, queue, &slots, &spawns, dt);
+    });
+}
+
+/// Declares the GPU tier's passes: the "GPU Particle Simulation" compute pass and the
+/// "GPU Particle Draw" into the scene color, before the transparent pass, so blended
+/// geometry in front covers them. Neither is added while there are no GPU particles.
+pub(crate) fn add_gpu_particle_passes(
+    graph: &mut FrameGraph,
+    context: &RenderContext,
+    scene: &SceneTargets,
+) {
+    let active = context
+        .gpu_particle_program
+        .as_ref()
+        .is_some_and(|program| program.is_active());
+    if !active {
+        return;
+    }
+
+    // Its results are buffers the draw reads
+    graph
+        .add_pass("GPU Particle Simulation", PassStage::Scene)
+        .keep()
+        .record_parallel(|pass| {
+            let timestamp_writes = pass.compute_timestamp_writes();
+            if let Some(program) = &pass.context.gpu_particle_program {
+                program.dispatch(pass.encoder, timestamp_writes);
+            }
+        });
+
+    let viewports = scene.viewports.clone();
+    let (color, depth) = (scene.color, scene.depth);
+    graph
+        .add_pass("GPU Particle Draw", PassStage::Scene)
+        .read(depth)
+        .read(color)
+        .write(color)
+        .record_parallel(move |pass| {
+            let timestamp_writes = pass.timestamp_writes();
+            let (color, depth) = (pass.view(color), pass.view(depth));
+            let context = pass.context;
+            let Some(program) = &context.gpu_particle_program else {
+                return;
+            };
+
+            let mut render_pass = pass.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("GPU Particle Draw"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: color,
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                // Tested against, never written
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: depth,
+                    depth_ops: None,
+                    stencil_ops: None,
+                }),
+                timestamp_writes,
+                ..Default::default()
+            });
+
+            for (index, viewport) in viewports.iter().enumerate() {
+                viewport.apply(&mut render_pass);
+                let bind_group = &context.global_resources.view(index).bind_group;
+                program.record(&mut render_pass, bind_group);
+            }
+        });
+}
@@ -0,0 +1,302 @@
+use std::sync::{Arc, Mutex};
+
+use catalyst_core::{
+    App,
+    camera::Camera,
+    pipeline::PhaseRenderPrepare,
+    player::PlayerIndex,
+    raycast::{RayHit, RaycastBackend, RaycastFilter, raycast},
+    transform::GlobalTransform,
+};
+use flecs_ecs::prelude::*;
+use glam::Vec2;
+
+use crate::{
+    frame_graph::{FrameGraph, PassStage},
+    programs::{GpuProgram, PickingProgram, pbr_program::FrameDraws},
+    readback::{ReadbackDelivery, ReadbackError, ReadbackManager, TextureRegion},
+    render::{RenderContext, collect_views},
+    texture::TextureHelper,
+    viewport::{SplitScreenSettings, Viewport, compute_viewports},
+};
+
+/// Which entity is drawn at a position on screen, e.g. under the cursor. `pick` asks
+/// for one; the frame's PBR draws are then drawn again as ids and the texel read back,
+/// and the answer shows up in `take_results` a frame or two later.
+///
+/// Like the debug views, picking sees the PBR draws: meshes with a MaterialProgram,
+/// particles and decals can't be picked. `pick_with` casts a ray instead, see
+/// PickBackend.
+#[derive(Component, Default)]
+pub struct Picking {
+    requests: Vec<Vec2>,
+    raycasts: Vec<(Vec2, RaycastBackend)>,
+    results: Arc<Mutex<Vec<PickResult>>>,
+}
+
+/// How a pick finds what is at its position.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PickBackend {
+    /// The draws as ids, read back from the GPU: exactly what is on screen, with no
+    /// more than the entity.
+    #[default]
+    Gpu,
+    /// A ray from the camera of the view under the position, against colliders or
+    /// render geometry. Answered in the frame's PhaseRenderPrepare, with where the
+    /// surface was hit (see PickResult::surface). Sees meshes regardless of culling
+    /// and render layers, and colliders that aren't drawn.
+    Raycast(RaycastBackend),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PickResult {
+    /// As passed to `pick`.
+    pub position: Vec2,
+    /// Frame the ids were drawn on, see ReadbackManager::frame.
+    pub frame: u64,
+    /// The entity drawn there, None for nothing (or outside the window).
+    pub hit: Result<Option<Entity>, ReadbackError>,
+    /// Where the ray hit the entity, for PickBackend::Raycast picks.
+    pub surface: Option<RayHit>,
+}
+
+impl Picking {
+    /// Asks for the entity at `position`, in physical pixels from the window's top left.
+    pub fn pick(&mut self, position: Vec2) {
+        self.pick_with(position, PickBackend::Gpu);
+    }
+
+    /// Like `pick`, found with `backend`.
+    pub fn pick_with(&mut self, position: Vec2, backend: PickBackend) {
+        match backend {
+            PickBackend::Gpu => self.requests.push(position),
+            PickBackend::Raycast(backend) => self.raycasts.push((position, backend)),
+        }
+    }
+
+    /// Answers that arrived since the last call, oldest first.
+    pub fn take_results(&mut self) -> Vec<PickResult> {
+        std::mem::take(&mut *self.results.lock().unwrap())
+    }
+
+    pub(crate) fn take_requests(&mut self) -> Vec<Vec2> {
+        std::mem::take(&mut self.requests)
+    }
+}
+
+/// The id target and its depth, created at the screen's size for the first pick.
+#[derive(Default)]
+pub struct PickingTarget {
+    target: Option<(wgpu::Texture, wgpu::TextureView, wgpu::TextureView)>,
+}
+
+impl PickingTarget {
+    // Recreated when the screen's size changes; readbacks of the old one are cancelled
+    fn ensure(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        readback: &mut ReadbackManager,
+    ) -> (wgpu::Texture, wgpu::TextureView, wgpu::TextureView) {
+        let resized = self.target.as_ref().is_some_and(|(texture, _, _)| {
+            (texture.width(), texture.height()) != (config.width, config.height)
+        });
+        if let Some((texture, _, _)) = self.target.take_if(|_| resized) {
+            readback.cancel_texture(&texture);
+        }
+
+        self.target
+            .get_or_insert_with(|| {
+                let texture = device.create_texture(&wgpu::TextureDescriptor {
+                    label: Some("Picking Ids"),
+                    size: wgpu::Extent3d {
+                        width: config.width,
+                        height: config.height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: PickingProgram::TARGET_FORMAT,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                    view_formats: &[],
+                });
+                let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                let depth = TextureHelper::create_depth_texture(device, config, "Picking Depth");
+                (texture, view, depth)
+            })
+            .clone()
+    }
+}
+
+/// Declares the picking pass for `positions`, called by "Render Frame" after the scene
+/// passes: the draws as ids (at the render scale, in `viewports`), then a readback of
+/// the texel under each position.
+pub(crate) fn add_picking_pass(
+    graph: &mut FrameGraph,
+    context: &mut RenderContext,
+    picking: &Picking,
+    positions: Vec<Vec2>,
+    draws: FrameDraws,
+    viewports: &[Viewport],
+    uv_scale: Vec2,
+) {
+    context.picking_program.prepare(&context.device);
+    let (texture, view, depth) =
+        context
+            .picking_target
+            .ensure(&context.device, &context.config, &mut context.readback);
+    let ids = graph.import_texture("Picking Ids", view);
+    let depth = graph.import_texture("Picking Depth", depth);
+
+    let results = picking.results.clone();
+    let viewports = viewports.to_vec();
+    graph
+        .add_pass("Picking", PassStage::Scene)
+        .write(ids)
+        .write(depth)
+        .keep()
+        .record(move |pass| {
+            let timestamp_writes = pass.timestamp_writes();
+            let (view, depth) = (pass.view(ids), pass.view(depth));
+            let context = &mut *pass.context;
+
+            // Slot i + 1 is written for entities[i]
+            let mut entities = Vec::new();
+            {
+                let mut render_pass = pass.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Picking"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view,
+                        resolve_target: None,
+                        depth_slice: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                        view: depth,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(context.depth_mode.clear_value()),
+                            store: wgpu::StoreOp::Discard,
+                        }),
+                        stencil_ops: None,
+                    }),
+                    timestamp_writes,
+                    ..Default::default()
+                });
+
+                for (index, (viewport, transparent)) in
+                    viewports.iter().zip(&draws.transparent).enumerate()
+                {
+                    viewport.apply(&mut render_pass);
+                    let bind_group = &context.global_resources.view(index).bind_group;
+                    for draws in [
+                        &draws.opaque,
+                        &draws.ordered_opaque,
+                        transparent,
+                        &draws.overlay,
+                    ] {
+                        let first_slot = entities.len() as u32;
+                        entities.extend(draws.iter().map(|draw| draw.entity()));
+                        context
+                            .picking_program
+                            .record(&mut render_pass, (bind_group, draws, first_slot));
+                    }
+                }
+            }
+
+            let entities: Arc<[Entity]> = entities.into();
+            let frame = context.readback.frame();
+            for position in positions {
+                // The ids are drawn at the render scale
+                let texel = (position * uv_scale).floor();
+                if texel.x < 0.0
+                    || texel.y < 0.0
+                    || texel.x >= texture.width() as f32
+                    || texel.y >= texture.height() as f32
+                {
+                    results.lock().unwrap().push(PickResult {
+                        position,
+                        frame,
+                        hit: Ok(None),
+                        surface: None,
+                    });
+                    continue;
+                }
+
+                let results = results.clone();
+                let entities = entities.clone();
+                context.readback.read_texture(
+                    &context.device,
+                    pass.encoder,
+                    &texture,
+                    TextureRegion::texel(texel.x as u32, texel.y as u32),
+                    ReadbackDelivery::callback(move |readback| {
+                        let hit = readback.map(|readback| {
+                            let slot = readback.to_vec::<u32>()[0] as usize;
+                            slot.checked_sub(1)
+                                .and_then(|slot| entities.get(slot).copied())
+                        });
+                        results.lock().unwrap().push(PickResult {
+                            position,
+                            frame,
+                            hit,
+                            surface: None,
+                        });
+                    }),
+                );
+            }
+        });
+}
+
+pub fn register_picking_systems(app: &mut App) {
+    let camera_query = app
+        .world
+        .query::<(&Camera, &GlobalTransform, Option<&PlayerIndex>)>()
+        .set_cached()
+        .build();
+
+    // Each ray goes through the view under its position, like "Render Frame" lays them out
+    app.world
+        .system_named::<(&mut Picking, &RenderContext, &SplitScreenSettings)>("Raycast Picks")
+        .kind(PhaseRenderPrepare)
+        .each_iter(move |iter, _, (picking, context, split_screen)| {
+            if picking.raycasts.is_empty() {
+                return;
+            }
+            let world = iter.world();
+            let views = collect_views(&camera_query);
+            let viewports = compute_viewports(
+                split_screen.layout,
+                views.len(),
+                context.config.width,
+                context.config.height,
+            );
+            let frame = context.readback.frame();
+
+            for (position, backend) in std::mem::take(&mut picking.raycasts) {
+                let view = views.iter().zip(&viewports).find(|(_, viewport)| {
+                    position.x >= viewport.x
+                        && position.y >= viewport.y
+                        && position.x < viewport.x + viewport.width
+                        && position.y < viewport.y + viewport.height
+                });
+                let surface = view.and_then(|((_, camera, camera_transform), viewport)| {
+                    let ray = camera.screen_ray(
+                        camera_transform,
+                        position - Vec2::new(viewport.x, viewport.y),
+                        Vec2::new(viewport.width, viewport.height),
+                    );
+                    raycast(&world, &ray, &RaycastFilter::default(), backend)
+                });
+                picking.results.lock().unwrap().push(PickResult {
+                    position,
+                    frame,
+                    hit: Ok(surface.as_ref().map(|surface| surface.entity)),
+                    surface,
+                });
+            }
+        });
+}
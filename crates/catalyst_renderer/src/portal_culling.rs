@@ -0,0 +1,349 @@
+use std::collections::{HashMap, HashSet};
+
+use catalyst_core::{
+    App,
+    pipeline::PhaseRenderPrepare,
+    rooms::{Portal, Room},
+    transform::GlobalTransform,
+};
+use flecs_ecs::prelude::*;
+use glam::{Mat4, Vec2, Vec3, Vec4};
+
+use crate::{mesh::MeshInstance, static_batching::StaticBatch, visibility::ViewFrusta};
+
+// Portals walked through from a camera's room at most, so cycles of rooms end
+const MAX_PORTAL_DEPTH: usize = 8;
+
+// Clip-space w below which a portal corner counts as behind the eye
+const NEAR_W: f32 = 1e-3;
+
+/// The Room holding the entity's origin when last checked, kept up to date by
+/// "Assign Rooms" for every drawn mesh. None outside all rooms: never culled by rooms.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq)]
+pub struct RoomMember {
+    pub room: Option<Entity>,
+}
+
+/// On entities whose room no view can see into this frame. The mesh queries skip them.
+#[derive(Component)]
+pub struct RoomCulled;
+
+/// Portal culling for interiors. Every frame the rooms holding the cameras are found
+/// and their portals walked: each portal narrows the screen rectangle the rooms behind
+/// it are seen through, until it is empty. A camera outside every room sees them all.
+#[derive(Component, Clone, Debug)]
+pub struct PortalCulling {
+    /// Off draws every room. Read every frame.
+    pub enabled: bool,
+    graph: RoomGraph,
+    visible: HashSet<Entity>,
+    culled: usize,
+}
+
+impl Default for PortalCulling {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            graph: RoomGraph::default(),
+            visible: HashSet::new(),
+            culled: 0,
+        }
+    }
+}
+
+impl PortalCulling {
+    /// Whether any view saw into `room` this frame.
+    pub fn is_visible(&self, room: Entity) -> bool {
+        self.visible.contains(&room)
+    }
+
+    pub fn visible_rooms(&self) -> usize {
+        self.visible.len()
+    }
+
+    pub fn room_count(&self) -> usize {
+        self.graph.rooms.len()
+    }
+
+    /// Entities not drawn this frame because of their room.
+    pub fn culled_entities(&self) -> usize {
+        self.culled
+    }
+}
+
+// Rooms and portals in world space, rebuilt every frame: there are few, and moved or
+// edited volumes show up right away
+#[derive(Clone, Debug, Default)]
+struct RoomGraph {
+    rooms: Vec<RoomVolume>,
+    portals: Vec<PortalQuad>,
+    index: HashMap<Entity, usize>,
+}
+
+#[derive(Clone, Debug)]
+struct RoomVolume {
+    entity: Entity,
+    world_to_local: Mat4,
+    half_extents: Vec3,
+    portals: Vec<usize>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct PortalQuad {
+    rooms: [usize; 2],
+    corners: [Vec3; 4],
+}
+
+impl PortalQuad {
+    fn other(&self, room: usize) -> usize {
+        if self.rooms[0] == room {
+            self.rooms[1]
+        } else {
+            self.rooms[0]
+        }
+    }
+}
+
+impl RoomGraph {
+    fn build(
+        room_query: &Query<(&Room, &GlobalTransform)>,
+        portal_query: &Query<(&Portal, &GlobalTransform)>,
+    ) -> Self {
+        let mut graph = Self::default();
+        room_query.each_entity(|entity, (room, global)| {
+            graph.index.insert(entity.id(), graph.rooms.len());
+            graph.rooms.push(RoomVolume {
+                entity: entity.id(),
+                world_to_local: global.0.inverse(),
+                half_extents: room.half_extents,
+                portals: Vec::new(),
+            });
+        });
+
+        // Portals to a room that doesn't exist (anymore) are ignored
+        portal_query.each(|(portal, global)| {
+            let (Some(&a), Some(&b)) = (
+                graph.index.get(&portal.rooms[0]),
+                graph.index.get(&portal.rooms[1]),
+            ) else {
+                return;
+            };
+
+            let Vec2 { x, y } = portal.half_extents;
+            let corners = [(-x, -y), (x, -y), (x, y), (-x, y)]
+                .map(|(x, y)| global.0.transform_point3(Vec3::new(x, y, 0.0)));
+
+            let portal_index = graph.portals.len();
+            graph.portals.push(PortalQuad {
+                rooms: [a, b],
+                corners,
+            });
+            graph.rooms[a].portals.push(portal_index);
+            if b != a {
+                graph.rooms[b].portals.push(portal_index);
+            }
+        });
+
+        graph
+    }
+
+    fn contains(&self, room: usize, point: Vec3) -> bool {
+        let room = &self.rooms[room];
+        let local = room.world_to_local.transform_point3(point);
+        local.abs().cmple(room.half_extents).all()
+    }
+
+    fn room_at(&self, point: Vec3) -> Option<usize> {
+        (0..self.rooms.len()).find(|&room| self.contains(room, point))
+    }
+
+    // Where an entity last found in `current` is now: that room and its neighbours are
+    // checked first, so moving through a door doesn't search every room
+    fn locate(&self, current: Option<Entity>, point: Vec3) -> Option<Entity> {
+        if let Some(&room) = current.and_then(|entity| self.index.get(&entity)) {
+            if self.contains(room, point) {
+                return current;
+            }
+            let neighbour = self.rooms[room]
+                .portals
+                .iter()
+                .map(|&portal| self.portals[portal].other(room))
+                .find(|&neighbour| self.contains(neighbour, point));
+            if let Some(neighbour) = neighbour {
+                return Some(self.rooms[neighbour].entity);
+            }
+        }
+
+        self.room_at(point).map(|room| self.rooms[room].entity)
+    }
+
+    // Every room seen from `start` through its portals, with `view_proj` of the view
+    fn visible_from(&self, start: usize, view_proj: Mat4, visible: &mut HashSet<Entity>) {
+        // A room reached again through a rectangle inside one it was already reached
+        // through adds nothing; without this a well connected level walks every path
+        let mut reached: Vec<Vec<ScreenRect>> = vec![Vec::new(); self.rooms.len()];
+        let mut stack = vec![(start, ScreenRect::FULL, None, 0)];
+
+        while let Some((room, rect, entered_through, depth)) = stack.pop() {
+            if reached[room].iter().any(|seen| seen.contains(&rect)) {
+                continue;
+            }
+            reached[room].push(rect);
+            visible.insert(self.rooms[room].entity);
+            if depth == MAX_PORTAL_DEPTH {
+                continue;
+            }
+
+            for &portal in &self.rooms[room].portals {
+                if entered_through == Some(portal) {
+                    continue;
+                }
+                let quad = &self.portals[portal];
+                let Some(through) = screen_bounds(view_proj, &quad.corners)
+                    .and_then(|bounds| bounds.intersect(&rect))
+                else {
+                    continue;
+                };
+                stack.push((quad.other(room), through, Some(portal), depth + 1));
+            }
+        }
+    }
+}
+
+// A rectangle in normalized device coordinates
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ScreenRect {
+    min: Vec2,
+    max: Vec2,
+}
+
+impl ScreenRect {
+    const FULL: Self = Self {
+        min: Vec2::NEG_ONE,
+        max: Vec2::ONE,
+    };
+
+    fn intersect(&self, other: &Self) -> Option<Self> {
+        let min = self.min.max(other.min);
+        let max = self.max.min(other.max);
+        min.cmplt(max).all().then_some(Self { min, max })
+    }
+
+    fn contains(&self, other: &Self) -> bool {
+        self.min.cmple(other.min).all() && self.max.cmpge(other.max).all()
+    }
+}
+
+// Screen bounds of a portal, clipped to the part in front of the eye. A portal the eye
+// stands in spreads far past the screen, so the room behind it stays visible.
+fn screen_bounds(view_proj: Mat4, corners: &[Vec3; 4]) -> Option<ScreenRect> {
+    let clip = corners.map(|corner| view_proj * corner.extend(1.0));
+
+    let mut bounds: Option<ScreenRect> = None;
+    let mut include = |point: Vec4| {
+        let ndc = Vec2::new(point.x, point.y) / point.w;
+        bounds = Some(match bounds {
+            Some(rect) => ScreenRect {
+                min: rect.min.min(ndc),
+                max: rect.max.max(ndc),
+            },
+            None => ScreenRect { min: ndc, max: ndc },
+        });
+    };
+
+    for i in 0..clip.len() {
+        let (a, b) = (clip[i], clip[(i + 1) % clip.len()]);
+        if a.w >= NEAR_W {
+            include(a);
+        }
+        // Where the edge crosses into or out of the space behind the eye
+        if (a.w >= NEAR_W) != (b.w >= NEAR_W) {
+            include(a.lerp(b, (NEAR_W - a.w) / (b.w - a.w)));
+        }
+    }
+
+    bounds
+}
+
+pub fn register_portal_culling_systems(app: &mut App) {
+    app.register_singleton_default::<PortalCulling>();
+
+    let room_query = app
+        .world
+        .query::<(&Room, &GlobalTransform)>()
+        .set_cached()
+        .build();
+    let portal_query = app
+        .world
+        .query::<(&Portal, &GlobalTransform)>()
+        .set_cached()
+        .build();
+
+    app.world
+        .system_named::<&mut PortalCulling>("Update Room Graph")
+        .kind(PhaseRenderPrepare)
+        .each(move |culling| {
+            culling.graph = RoomGraph::build(&room_query, &portal_query);
+        });
+
+    // New meshes search every room once; after that only their room and its neighbours
+    app.world
+        .system_named::<(&GlobalTransform, Option<&mut RoomMember>, &PortalCulling)>(
+            "Assign Rooms",
+        )
+        .with(MeshInstance::id())
+        .without(StaticBatch::id()) // Set to their members' room
+        .kind(PhaseRenderPrepare)
+        .each_entity(|entity, (global, member, culling)| {
+            let position = global.0.w_axis.truncate();
+            match member {
+                Some(member) => member.room = culling.graph.locate(member.room, position),
+                None => {
+                    entity.set(RoomMember {
+                        room: culling.graph.locate(None, position),
+                    });
+                }
+            }
+        });
+
+    let member_query = app.world.query::<&RoomMember>().set_cached().build();
+
+    app.world
+        .system_named::<(&mut PortalCulling, &ViewFrusta)>("Portal Culling")
+        .kind(PhaseRenderPrepare)
+        .each(move |(culling, frusta)| {
+            culling.visible.clear();
+
+            let mut see_everything = !culling.enabled;
+            for view in &frusta.0 {
+                match culling.graph.room_at(view.eye) {
+                    Some(room) => {
+                        culling
+                            .graph
+                            .visible_from(room, view.view_proj, &mut culling.visible)
+                    }
+                    None => see_everything = true,
+                }
+            }
+            if see_everything {
+                culling
+                    .visible
+                    .extend(culling.graph.rooms.iter().map(|room| room.entity));
+            }
+
+            // Tags only change when a room's visibility does
+            let mut culled = 0;
+            member_query.each_entity(|entity, member| {
+                let hidden = member
+                    .room
+                    .is_some_and(|room| !culling.visible.contains(&room));
+                culled += hidden as usize;
+                if hidden && !entity.has(RoomCulled) {
+                    entity.add(RoomCulled);
+                } else if !hidden && entity.has(RoomCulled) {
+                    entity.remove(RoomCulled);
+                }
+            });
+            culling.culled = culled;
+        });
+}
@@ -1,14 +1,47 @@
+pub mod color_grading_program;
 pub mod debug_lines_program;
+pub mod debug_view_program;
+pub mod decal_program;
+pub mod depth_of_field_program;
+pub mod gpu_particle_program;
+pub mod luminance_program;
+pub mod material_preview_program;
+pub mod occlusion_proxy_program;
+pub mod outline_program;
+pub mod particle_program;
 pub mod pbr_program;
+pub mod picking_program;
+pub mod probe_prefilter_program;
+pub(crate) mod reflected;
+pub mod reflection_program;
+pub mod taa_program;
+pub mod upscale_program;
 
 pub use pbr_program::PbrProgram;
+pub use color_grading_program::ColorGradingProgram;
 pub use debug_lines_program::DebugLinesProgram;
+pub use debug_view_program::DebugViewProgram;
+pub use decal_program::DecalProgram;
+pub use depth_of_field_program::DepthOfFieldProgram;
+pub use gpu_particle_program::GpuParticleProgram;
+pub use material_preview_program::MaterialPreviewProgram;
+pub use occlusion_proxy_program::OcclusionProxyProgram;
+pub use outline_program::OutlineProgram;
+pub use particle_program::ParticleProgram;
+pub use picking_program::PickingProgram;
+pub use reflection_program::ReflectionProgram;
+pub use taa_program::TaaProgram;
+pub use upscale_program::UpscaleProgram;
+
+use crate::{clusters::LightingMode, texture::DepthMode};
 
 /// Holds common WGPU references to simplify function signatures.
 pub struct GpuProgramRenderContext<'a> {
     pub device: &'a wgpu::Device,
     pub queue: &'a wgpu::Queue,
     pub format: wgpu::TextureFormat, // The output format (Swapchain or HDR)
+    pub depth_mode: DepthMode,
+    pub lighting_mode: LightingMode,
 }
 
 pub trait GpuProgram {
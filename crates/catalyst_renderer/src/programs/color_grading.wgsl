@@ -0,0 +1,63 @@
+// ========================================================================
+//  COLOR GRADING
+//  Looks the finished image up in two 3D LUTs, mixes them by the view's
+//  grading volume weight, then with the ungraded image by the intensity.
+//  Colors are looked up as stored: tone mapped and gamma encoded, the way
+//  grading tools see a screenshot.
+// ========================================================================
+
+struct GradingView {
+    lut_sizes: vec2<f32>,  // Entries per axis of the primary and secondary LUT
+    primary_weight: f32,   // 1 is the primary LUT only, 0 the secondary only
+    intensity: f32,        // 0 leaves the image as it is
+};
+
+struct GradingUniforms {
+    views: array<GradingView, 4>,
+};
+
+@group(0) @binding(0) var t_source: texture_2d<f32>;
+@group(0) @binding(1) var<uniform> grading: GradingUniforms;
+@group(0) @binding(2) var s_lut: sampler;
+
+// This view's LUTs; an identity LUT stands in for neutral ones
+@group(1) @binding(0) var t_primary: texture_3d<f32>;
+@group(1) @binding(1) var t_secondary: texture_3d<f32>;
+
+struct FullscreenOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) @interpolate(flat) view: u32,
+};
+
+// Fullscreen triangle, no vertex buffer; drawn once per viewport, the view travels
+// as the instance index
+@vertex
+fn vs_fullscreen(
+    @builtin(vertex_index) index: u32,
+    @builtin(instance_index) view: u32,
+) -> FullscreenOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+    var out: FullscreenOutput;
+    out.position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    out.view = view;
+    return out;
+}
+
+// Trilinear lookup between entry centers, so 0 and 1 hit the first and last entries
+fn lookup(lut: texture_3d<f32>, size: f32, color: vec3<f32>) -> vec3<f32> {
+    let uvw = color * ((size - 1.0) / size) + 0.5 / size;
+    return textureSampleLevel(lut, s_lut, uvw, 0.0).rgb;
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let source = textureLoad(t_source, vec2<i32>(in.position.xy), 0);
+    let view = grading.views[in.view];
+
+    let color = clamp(source.rgb, vec3<f32>(0.0), vec3<f32>(1.0));
+    let primary = lookup(t_primary, view.lut_sizes.x, color);
+    let secondary = lookup(t_secondary, view.lut_sizes.y, color);
+    let graded = mix(secondary, primary, view.primary_weight);
+    return vec4<f32>(mix(source.rgb, graded, view.intensity), source.a);
+}
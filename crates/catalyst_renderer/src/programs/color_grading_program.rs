@@ -0,0 +1,206 @@
+use catalyst_assets::lut::LutData;
+use wgpu::RenderPipeline;
+
+use crate::{
+    color_grading::{GradingUniform, GradingViewUniform},
+    layout::{uniform_layouts, validate_program_layouts},
+    programs::{
+        GpuProgram, GpuProgramRenderContext,
+        reflected::{self, WGSL_GRADING_UNIFORMS, WGSL_GRADING_VIEW},
+    },
+    texture::GpuTexture,
+    viewport::Viewport,
+};
+
+uniform_layouts!(
+    GradingViewUniform::LAYOUT => WGSL_GRADING_VIEW,
+    GradingUniform::LAYOUT => WGSL_GRADING_UNIFORMS,
+);
+
+/// Fullscreen pass looking the finished image up in each view's pair of 3D LUTs,
+/// one draw per viewport.
+pub struct ColorGradingProgram {
+    pipeline: RenderPipeline,
+    source_layout: wgpu::BindGroupLayout,
+    lut_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    uniform_buffer: wgpu::Buffer,
+    /// Bound in place of neutral LUTs; two entries per axis are exact.
+    identity: GpuTexture,
+}
+
+impl ColorGradingProgram {
+    pub fn write_uniform(&self, queue: &wgpu::Queue, uniform: &GradingUniform) {
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(uniform));
+    }
+
+    /// Entries per axis of the LUT bound for None.
+    pub fn identity_size(&self) -> u32 {
+        self.identity.texture.width()
+    }
+
+    /// Bind group reading `source`, a frame graph view, so it is built per frame.
+    pub fn source_bind_group(
+        &self,
+        device: &wgpu::Device,
+        source: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Color Grading Source Bind Group"),
+            layout: &self.source_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(source),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        })
+    }
+
+    /// Bind group of one view's LUTs; None binds the identity LUT.
+    pub fn lut_bind_group(
+        &self,
+        device: &wgpu::Device,
+        primary: Option<&GpuTexture>,
+        secondary: Option<&GpuTexture>,
+    ) -> wgpu::BindGroup {
+        let primary = primary.unwrap_or(&self.identity);
+        let secondary = secondary.unwrap_or(&self.identity);
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Color Grading LUT Bind Group"),
+            layout: &self.lut_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&primary.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&secondary.view),
+                },
+            ],
+        })
+    }
+}
+
+impl GpuProgram for ColorGradingProgram {
+    type InitData = ();
+    /// The source bind group, then each view's LUT bind group and viewport.
+    type DrawData<'a> = (&'a wgpu::BindGroup, &'a [(wgpu::BindGroup, Viewport)]);
+
+    fn new(ctx: &GpuProgramRenderContext, _init_data: &Self::InitData) -> Self {
+        validate_program_layouts("ColorGradingProgram", UNIFORM_LAYOUTS);
+
+        let shader = ctx
+            .device
+            .create_shader_module(wgpu::include_wgsl!("color_grading.wgsl"));
+
+        let source_layout = ctx
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Color Grading Source Layout"),
+                // Finished image, settings per view and LUT sampler
+                entries: reflected::color_grading::GROUP_0,
+            });
+
+        let lut_layout = ctx
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Color Grading LUT Layout"),
+                // Primary and secondary LUT
+                entries: reflected::color_grading::GROUP_1,
+            });
+
+        // Clamped, so colors at 0 and 1 never wrap to the other side of the cube
+        let sampler = ctx.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Color Grading LUT Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let uniform_buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Color Grading Uniform Buffer"),
+            size: std::mem::size_of::<GradingUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Color Grading Pipeline Layout"),
+                bind_group_layouts: &[&source_layout, &lut_layout],
+                push_constant_ranges: &[],
+            });
+
+        let pipeline = ctx
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                cache: None,
+                label: Some("Color Grading Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_fullscreen"),
+                    compilation_options: Default::default(),
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    compilation_options: Default::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: ctx.format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            });
+
+        let identity = GpuTexture::from_lut(
+            ctx.device,
+            ctx.queue,
+            &LutData::identity(2),
+            Some("Identity LUT"),
+        );
+
+        Self {
+            pipeline,
+            source_layout,
+            lut_layout,
+            sampler,
+            uniform_buffer,
+            identity,
+        }
+    }
+
+    fn record<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, data: Self::DrawData<'a>) {
+        let (source, views) = data;
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, source, &[]);
+        // A fullscreen triangle per view, clipped to its viewport; the view travels as
+        // the instance index
+        for (index, (luts, viewport)) in views.iter().enumerate() {
+            viewport.apply(render_pass);
+            render_pass.set_bind_group(1, luts, &[]);
+            let index = index as u32;
+            render_pass.draw(0..3, index..index + 1);
+        }
+    }
+}
@@ -0,0 +1,15 @@
+// ========================================================================
+//  CAMERA (group 0, binding 0 of every scene pass)
+//  Must match CameraUniform in global_resources.rs, see WGSL_CAMERA
+// ========================================================================
+
+struct Camera {
+    view_proj: mat4x4<f32>,            // Jittered when TAA is on
+    unjittered_view_proj: mat4x4<f32>,
+    prev_view_proj: mat4x4<f32>,       // Last frame, unjittered
+    exposure: f32,                     // Multiplier applied before tone mapping
+    padding0: f32,
+    padding1: vec2<f32>,
+    wind: vec4<f32>,                   // .xy = horizontal wind, .z = wind clock, see common/wind.wgsl
+    prev_wind: vec4<f32>,              // Last frame's, for motion vectors
+};
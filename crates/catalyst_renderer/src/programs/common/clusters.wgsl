@@ -0,0 +1,23 @@
+// ========================================================================
+//  CLUSTERS (group 0, binding 2 with clustered lighting)
+//  Must match ClusterUniform in clusters.rs, see WGSL_CLUSTER_UNIFORM
+// ========================================================================
+
+struct ClusterUniform {
+    view: mat4x4<f32>,         // World -> view space
+    viewport: vec4<f32>,       // x, y, width, height in pixels
+    dimensions: vec4<u32>,     // Tiles x, tiles y, depth slices, unused
+    depth_slicing: vec4<f32>,  // near, far, scale, bias: slice = log2(depth) * scale + bias
+};
+
+// The cluster a fragment is in, an index into the cluster ranges
+fn cluster_index(grid: ClusterUniform, frag_coord: vec2<f32>, world_pos: vec3<f32>) -> u32 {
+    let uv = clamp((frag_coord - grid.viewport.xy) / grid.viewport.zw, vec2<f32>(0.0), vec2<f32>(0.9999));
+    let tile = vec2<u32>(uv * vec2<f32>(grid.dimensions.xy));
+
+    let depth = -(grid.view * vec4<f32>(world_pos, 1.0)).z;
+    let slice = log2(max(depth, 0.0001)) * grid.depth_slicing.z + grid.depth_slicing.w;
+    let z = u32(clamp(slice, 0.0, f32(grid.dimensions.z - 1u)));
+
+    return tile.x + grid.dimensions.x * (tile.y + grid.dimensions.y * z);
+}
@@ -0,0 +1,56 @@
+// ========================================================================
+//  FOG (in LightUniforms, include after common/lights.wgsl)
+//  Must match FogSettings::opacity in fog.rs
+// ========================================================================
+
+const FOG_MAX_EXPONENT: f32 = 40.0; // Caps the height term, see MAX_EXPONENT
+
+// The fog's density relative to its base height, exponential in height
+fn fog_density_at(lights: LightUniforms, height: f32) -> f32 {
+    return exp(min(-lights.fog_params.x * (height - lights.fog_params.y), FOG_MAX_EXPONENT));
+}
+
+// How much of what is at `world_pos` the fog hides, from 0 to 1. The density is
+// integrated along the ray in closed form, from the start distance in front of the eye.
+fn fog_opacity(lights: LightUniforms, eye: vec3<f32>, world_pos: vec3<f32>) -> f32 {
+    let density = lights.fog_color.w;
+    let to_point = world_pos - eye;
+    let ray_length = length(to_point);
+    let fog_length = ray_length - lights.fog_params.z;
+    if (density <= 0.0 || fog_length <= 0.0) {
+        return 0.0;
+    }
+    let direction = to_point / ray_length;
+    let start_height = eye.y + direction.y * lights.fog_params.z;
+    let rise = direction.y * fog_length;
+
+    let start_density = fog_density_at(lights, start_height);
+    let exponent = lights.fog_params.x * rise;
+    var optical_depth = density * fog_length * start_density;
+    if (abs(exponent) > 1e-4) {
+        optical_depth = density * fog_length
+            * (start_density - fog_density_at(lights, start_height + rise)) / exponent;
+    }
+    return min(1.0 - exp(-optical_depth), lights.fog_params.w);
+}
+
+// Light the fog scatters towards the eye looking along `direction`: the sun through a
+// Henyey-Greenstein phase function, plus the ambient term surfaces get
+fn fog_inscattering(lights: LightUniforms, direction: vec3<f32>) -> vec3<f32> {
+    let g = lights.fog_sun.x;
+    let cos_theta = dot(direction, normalize(-lights.sun_direction.xyz));
+    // Times 4 pi, so fog scattering evenly is lit like a white surface facing the sun
+    let phase = (1.0 - g * g) / pow(1.0 + g * g - 2.0 * g * cos_theta, 1.5);
+    let sun = lights.sun_color.rgb * lights.sun_direction.w * phase;
+    return lights.fog_color.rgb * (vec3<f32>(0.03) + sun);
+}
+
+// `color` (linear, before exposure) at `world_pos` seen through the fog
+fn apply_fog(lights: LightUniforms, color: vec3<f32>, world_pos: vec3<f32>) -> vec3<f32> {
+    let opacity = fog_opacity(lights, lights.camera_pos, world_pos);
+    if (opacity <= 0.0) {
+        return color;
+    }
+    let direction = normalize(world_pos - lights.camera_pos);
+    return mix(color, fog_inscattering(lights, direction), opacity);
+}
@@ -0,0 +1,35 @@
+// ========================================================================
+//  LIGHTS (group 0, binding 1 of every scene pass)
+//  Must match GpuLight, GoboUniform and LightUniforms, see WGSL_LIGHT*
+// ========================================================================
+
+const LIGHT_POINT: u32 = 0u;
+const LIGHT_SPOT: u32 = 1u;
+const NO_GOBO: u32 = 0xffffffffu;
+const MAX_GOBOS: u32 = 4u;
+
+struct Light {
+    position: vec4<f32>, // .xyz = position, .w = intensity
+    color: vec4<f32>,    // .xyz = color,    .w = radius
+    direction: vec3<f32>, // Spot lights only
+    kind: u32,            // LIGHT_POINT or LIGHT_SPOT
+    cos_inner: f32,       // Full intensity inside this cone
+    cos_outer: f32,       // No light outside this one
+    gobo: u32,            // Layer of gobo_textures, or NO_GOBO
+    padding: u32,
+};
+
+struct GoboUniform {
+    view_proj: array<mat4x4<f32>, MAX_GOBOS>, // Per layer, world -> gobo clip space
+};
+
+struct LightUniforms {
+    sun_direction: vec4<f32>, // .xyz = direction, .w = intensity
+    sun_color: vec4<f32>,     // .xyz = color,     .w = padding
+    lights: array<Light, 4>, // Only read with uniform lighting
+    camera_pos: vec3<f32>,
+    active_lights: u32,       // How many of `lights` to loop over
+    fog_color: vec4<f32>,     // .xyz = color, .w = density (0 = no fog), see common/fog.wgsl
+    fog_params: vec4<f32>,    // height falloff, base height, start distance, max opacity
+    fog_sun: vec4<f32>,       // .x = sun anisotropy
+};
@@ -0,0 +1,14 @@
+// ========================================================================
+//  MATERIAL (group 1, binding 0 of the PBR pass and the debug views)
+//  Must match GpuMaterialUniform in material.rs, see WGSL_MATERIAL_UNIFORMS
+// ========================================================================
+
+struct MaterialUniforms {
+    base_color: vec4<f32>,
+    roughness: f32,
+    metallic: f32,
+    alpha_cutoff: f32,
+    padding: f32,
+    emissive: vec4<f32>, // rgb already scaled by the emissive strength
+    wind: vec4<f32>,     // strength, frequency, branch, flutter; 0 without wind
+};
@@ -0,0 +1,10 @@
+// ========================================================================
+//  MESH (per object, group 2 of the PBR pass, group 1 of outlines and custom programs)
+//  Must match MeshUniform in mesh.rs, see WGSL_MESH_UNIFORM
+// ========================================================================
+
+struct MeshUniform {
+    model: mat4x4<f32>,
+    normal_matrix: mat4x4<f32>, // We only use top-left 3x3
+    prev_model: mat4x4<f32>,    // Last frame, for motion vectors
+};
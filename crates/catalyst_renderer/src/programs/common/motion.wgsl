@@ -0,0 +1,10 @@
+// ========================================================================
+//  MOTION VECTORS (velocity target, read by TAA)
+// ========================================================================
+
+// NDC delta between frames, converted to UV space (Y flipped)
+fn motionVector(curr_clip: vec4<f32>, prev_clip: vec4<f32>) -> vec2<f32> {
+    let curr_ndc = curr_clip.xy / curr_clip.w;
+    let prev_ndc = prev_clip.xy / prev_clip.w;
+    return (curr_ndc - prev_ndc) * vec2<f32>(0.5, -0.5);
+}
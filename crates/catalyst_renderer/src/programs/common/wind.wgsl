@@ -0,0 +1,43 @@
+// ========================================================================
+//  WIND (vegetation sway in the vertex shaders)
+//  Must match WindSettings::displace in wind.rs
+// ========================================================================
+
+const WIND_TAU: f32 = 6.2831853;
+// Flutter cycles per second, fast next to the bend
+const WIND_FLUTTER_RATE: f32 = 7.0;
+// Cycles of the bend per meter travelled by the wind, so gusts roll through a field
+const WIND_WAVE_DENSITY: f32 = 0.15;
+
+// World space offset of a vertex. `wind` is camera.wind or camera.prev_wind (.xy =
+// horizontal wind on x and z, .z = wind clock in seconds), `params` the material's
+// (strength, frequency, branch, flutter) and `weight` the vertex's wind weight.
+fn wind_offset(world_pos: vec3<f32>, normal: vec3<f32>, weight: f32, wind: vec4<f32>, params: vec4<f32>) -> vec3<f32> {
+    let speed = length(wind.xy);
+    if (weight <= 0.0 || speed <= 0.0) {
+        return vec3<f32>(0.0);
+    }
+    let along = vec3<f32>(wind.x, 0.0, wind.y) / speed;
+    let side = vec3<f32>(-along.z, 0.0, along.x);
+    let time = wind.z;
+
+    // Main bend: leans with the wind, swaying as the wave travels along it
+    let wave = dot(world_pos.xz, along.xz) * WIND_WAVE_DENSITY;
+    let sway = 0.75 + 0.25 * sin(WIND_TAU * (params.y * time - wave));
+    var offset = along * (params.x * sway);
+
+    // Branches: sideways, out of step with their neighbours
+    let branch_phase = dot(world_pos, vec3<f32>(0.37, 0.11, 0.53));
+    offset += side * (params.z * sin(WIND_TAU * params.y * 1.7 * time + branch_phase));
+
+    // Flutter: leaf and blade tips along their normal
+    let flutter_phase = dot(world_pos, vec3<f32>(1.3, 2.1, 1.7));
+    offset += normal * (params.w * sin(WIND_TAU * WIND_FLUTTER_RATE * time + flutter_phase));
+
+    return offset * (speed * weight);
+}
+
+// Tilts the normal with the bend, so bent blades catch the light differently
+fn wind_normal(normal: vec3<f32>, offset: vec3<f32>) -> vec3<f32> {
+    return normalize(normal - vec3<f32>(0.0, 1.0, 0.0) * dot(normal, offset));
+}
@@ -1,8 +1,29 @@
-use catalyst_core::App;
+use catalyst_core::{
+    App,
+    pipeline::{PhaseRender3D, PhaseRenderPrepare},
+};
 use flecs_ecs::prelude::*;
 use wgpu::{Device, Queue, RenderPipeline, VertexFormat, util::DeviceExt};
 
-use crate::{RenderContext, programs::GpuProgram, render::DebugDraw3D, texture::TextureHelper};
+use crate::{
+    RenderContext,
+    frame_graph::{FrameGraph, PassStage},
+    global_resources::{CameraUniform, LightUniforms},
+    layout::{uniform_layouts, validate_program_layouts},
+    programs::{
+        GpuProgram,
+        reflected::{WGSL_CAMERA, WGSL_LIGHT_UNIFORMS},
+    },
+    render::DebugDraw3D,
+    shaders::create_shader_module,
+    texture::TextureHelper,
+};
+
+// Uniform structs lines.wgsl includes from common/
+uniform_layouts!(
+    CameraUniform::LAYOUT => WGSL_CAMERA,
+    LightUniforms::LAYOUT => WGSL_LIGHT_UNIFORMS,
+);
 
 #[repr(C)]
 #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
@@ -42,6 +63,11 @@ pub struct DebugLinesProgram {
 }
 
 impl DebugLinesProgram {
+    /// Nothing was pushed this frame.
+    pub fn is_empty(&self) -> bool {
+        self.draw_count == 0
+    }
+
     pub fn prepare(&mut self, vertexes: &Vec<DebugLineVertex>, device: &Device, queue: &Queue) {
         if vertexes.is_empty() {
             self.draw_count = 0;
@@ -86,9 +112,9 @@ impl GpuProgram for DebugLinesProgram {
     type DrawData<'a> = &'a wgpu::BindGroup;
 
     fn new(ctx: &super::GpuProgramRenderContext, global_layout: &Self::InitData) -> Self {
-        let line_shader = ctx
-            .device
-            .create_shader_module(wgpu::include_wgsl!("lines.wgsl"));
+        validate_program_layouts("DebugLinesProgram", UNIFORM_LAYOUTS);
+
+        let line_shader = create_shader_module(ctx.device, "Debug Lines Shader", &["lines.wgsl"]);
 
         let line_pipeline_layout =
             ctx.device
@@ -114,11 +140,25 @@ impl GpuProgram for DebugLinesProgram {
                     module: &line_shader,
                     entry_point: Some("fs_main"),
                     compilation_options: Default::default(),
-                    targets: &[Some(wgpu::ColorTargetState {
-                        format: ctx.format,
-                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                        write_mask: wgpu::ColorWrites::ALL,
-                    })],
+                    targets: &[
+                        Some(wgpu::ColorTargetState {
+                            format: ctx.format,
+                            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        }),
+                        // Motion vectors for TAA
+                        Some(wgpu::ColorTargetState {
+                            format: TextureHelper::VELOCITY_FORMAT,
+                            blend: None,
+                            write_mask: wgpu::ColorWrites::ALL,
+                        }),
+                        // Auto exposure only measures lit geometry
+                        Some(wgpu::ColorTargetState {
+                            format: TextureHelper::LUMINANCE_FORMAT,
+                            blend: None,
+                            write_mask: wgpu::ColorWrites::empty(),
+                        }),
+                    ],
                 }),
                 primitive: wgpu::PrimitiveState {
                     topology: wgpu::PrimitiveTopology::LineList,
@@ -132,7 +172,7 @@ impl GpuProgram for DebugLinesProgram {
                 depth_stencil: Some(wgpu::DepthStencilState {
                     format: TextureHelper::DEPTH_FORMAT,
                     depth_write_enabled: false, // important: do NOT write depth
-                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    depth_compare: ctx.depth_mode.compare_or_equal(),
                     stencil: wgpu::StencilState::default(),
                     bias: wgpu::DepthBiasState::default(),
                 }),
@@ -175,7 +215,7 @@ impl GpuProgram for DebugLinesProgram {
 pub fn register_debug_lines_program_systems(app: &mut App) {
     app.world
         .system_named::<(&mut DebugDraw3D, &mut RenderContext)>("process_debug_lines_rendering")
-        .kind(flecs::pipeline::PreStore)
+        .kind(PhaseRenderPrepare)
         .run(|mut iter| {
             while iter.next() {
                 let mut debug_field = iter.field_mut::<DebugDraw3D>(0);
@@ -193,4 +233,69 @@ pub fn register_debug_lines_program_systems(app: &mut App) {
                 }
             }
         });
+
+    // Drawn over the finished scene in every view, before post-processing
+    app.world
+        .system_named::<(&RenderContext, &mut FrameGraph)>("Debug Lines Pass")
+        .kind(PhaseRender3D)
+        .each(|(context, graph)| {
+            if context.debug_lines_program.is_empty() {
+                return;
+            }
+            let Some(scene) = graph.scene_targets().cloned() else {
+                return;
+            };
+
+            graph
+                .add_pass("Debug Lines", PassStage::Scene)
+                .read(scene.color)
+                .read(scene.depth)
+                .write(scene.color)
+                .write(scene.velocity)
+                .write(scene.luminance)
+                .record_parallel(move |pass| {
+                    let timestamp_writes = pass.timestamp_writes();
+                    let load = |view| {
+                        Some(wgpu::RenderPassColorAttachment {
+                            view,
+                            resolve_target: None,
+                            depth_slice: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Load,
+                                store: wgpu::StoreOp::Store,
+                            },
+                        })
+                    };
+                    let color_attachments = [
+                        load(pass.view(scene.color)),
+                        load(pass.view(scene.velocity)),
+                        load(pass.view(scene.luminance)),
+                    ];
+                    let depth = pass.view(scene.depth);
+                    let context = pass.context;
+
+                    let mut render_pass = pass.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("Debug Lines Pass"),
+                        color_attachments: &color_attachments,
+                        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                            view: depth,
+                            depth_ops: Some(wgpu::Operations {
+                                load: wgpu::LoadOp::Load,
+                                store: wgpu::StoreOp::Store,
+                            }),
+                            stencil_ops: None,
+                        }),
+                        timestamp_writes,
+                        ..Default::default()
+                    });
+
+                    for (index, viewport) in scene.viewports.iter().enumerate() {
+                        viewport.apply(&mut render_pass);
+                        let bind_group = &context.global_resources.view(index).bind_group;
+                        context
+                            .debug_lines_program
+                            .record(&mut render_pass, bind_group);
+                    }
+                });
+        });
 }
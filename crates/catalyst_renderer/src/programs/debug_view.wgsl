@@ -0,0 +1,157 @@
+// ========================================================================
+//  DEBUG VIEWS
+//  The scene's PBR draws are drawn again into a debug target: a count per
+//  fragment (overdraw, depth complexity) or one material channel. A
+//  fullscreen pass then shows the target on screen, counts through a heat
+//  ramp. See DebugViewMode.
+// ========================================================================
+
+#include "common/camera.wgsl"
+#include "common/material.wgsl"
+#include "common/mesh.wgsl"
+#include "common/wind.wgsl"
+
+struct DebugViewUniforms {
+    uv_scale: vec2<f32>, // Render size / screen size, the debug target is drawn at the render scale
+    ramp_max: f32,       // Count shown at the hot end of the ramp
+    ramp: u32,           // 1 = counts through the ramp, 0 = the channel as it is
+};
+
+// --- GROUP 0: CAMERA (the scene's global bind group) ---
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+// --- GROUP 1: MATERIAL (the PBR material bind group) ---
+@group(1) @binding(0) var<uniform> material: MaterialUniforms;
+@group(1) @binding(1) var t_diffuse: texture_2d<f32>;
+@group(1) @binding(2) var s_diffuse: sampler;
+@group(1) @binding(3) var t_metallic_roughness: texture_2d<f32>;
+@group(1) @binding(4) var s_metallic_roughness: sampler;
+
+// --- GROUP 2: MESH ---
+@group(2) @binding(0) var<uniform> mesh: MeshUniform;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(3) wind_weight: f32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_pos: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var world_pos = mesh.model * vec4<f32>(in.position, 1.0);
+    let normal_matrix = mat3x3<f32>(
+        mesh.normal_matrix[0].xyz,
+        mesh.normal_matrix[1].xyz,
+        mesh.normal_matrix[2].xyz
+    );
+
+    var normal = normalize(normal_matrix * in.normal);
+    // Materials without wind have zero wind parameters and stay put
+    let offset = wind_offset(world_pos.xyz, normal, in.wind_weight, camera.wind, material.wind);
+    world_pos += vec4<f32>(offset, 0.0);
+    normal = wind_normal(normal, offset);
+
+    var out: VertexOutput;
+    // Unjittered, so the counts don't shimmer with TAA
+    out.clip_position = camera.unjittered_view_proj * world_pos;
+    out.world_pos = world_pos.xyz;
+    out.normal = normal;
+    out.uv = in.uv;
+    return out;
+}
+
+// Cut-out texels are no fragments in the lit view either. A material without a
+// cutoff has 0 there.
+fn clip_alpha(uv: vec2<f32>) {
+    let alpha = textureSample(t_diffuse, s_diffuse, uv).a * material.base_color.a;
+    if (material.alpha_cutoff > 0.0 && alpha < material.alpha_cutoff) {
+        discard;
+    }
+}
+
+// --- COUNTS (additive, one per fragment) ---
+
+@fragment
+fn fs_count(in: VertexOutput) -> @location(0) vec4<f32> {
+    clip_alpha(in.uv);
+    return vec4<f32>(1.0, 0.0, 0.0, 0.0);
+}
+
+// --- CHANNELS ---
+
+@fragment
+fn fs_normal(in: VertexOutput, @builtin(front_facing) front_facing: bool) -> @location(0) vec4<f32> {
+    clip_alpha(in.uv);
+    // Back faces are only drawn for double sided materials, which face the viewer
+    var normal = normalize(in.normal);
+    if (!front_facing) {
+        normal = -normal;
+    }
+    return vec4<f32>(normal * 0.5 + 0.5, 1.0);
+}
+
+// Metallic/roughness texture packs roughness in G and metallic in B
+@fragment
+fn fs_roughness(in: VertexOutput) -> @location(0) vec4<f32> {
+    clip_alpha(in.uv);
+    let roughness = textureSample(t_metallic_roughness, s_metallic_roughness, in.uv).g * material.roughness;
+    return vec4<f32>(vec3<f32>(roughness), 1.0);
+}
+
+@fragment
+fn fs_metallic(in: VertexOutput) -> @location(0) vec4<f32> {
+    clip_alpha(in.uv);
+    let metallic = textureSample(t_metallic_roughness, s_metallic_roughness, in.uv).b * material.metallic;
+    return vec4<f32>(vec3<f32>(metallic), 1.0);
+}
+
+// --- COMPOSITE ---
+
+@group(0) @binding(0) var t_debug: texture_2d<f32>;
+@group(0) @binding(1) var<uniform> debug_view: DebugViewUniforms;
+
+// Fullscreen triangle, no vertex buffer
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+}
+
+// Black for nothing, then blue, cyan, green, yellow, red and white past ramp_max
+fn heat(value: f32) -> vec3<f32> {
+    if (value <= 0.0) {
+        return vec3<f32>(0.0);
+    }
+    if (value > debug_view.ramp_max) {
+        return vec3<f32>(1.0);
+    }
+    let t = value / debug_view.ramp_max;
+    var stops = array<vec3<f32>, 5>(
+        vec3<f32>(0.0, 0.0, 1.0),
+        vec3<f32>(0.0, 1.0, 1.0),
+        vec3<f32>(0.0, 1.0, 0.0),
+        vec3<f32>(1.0, 1.0, 0.0),
+        vec3<f32>(1.0, 0.0, 0.0),
+    );
+    let position = t * 4.0;
+    let index = min(u32(position), 3u);
+    return mix(stops[index], stops[index + 1u], position - f32(index));
+}
+
+@fragment
+fn fs_composite(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(position.xy * debug_view.uv_scale);
+    let value = textureLoad(t_debug, pixel, 0);
+    if (debug_view.ramp == 1u) {
+        return vec4<f32>(heat(value.r), 1.0);
+    }
+    return vec4<f32>(value.rgb, 1.0);
+}
@@ -0,0 +1,17 @@
+// ========================================================================
+//  DEBUG VIEWS: LIGHTS PER CLUSTER (composed with debug_view.wgsl, only with
+//  clustered lighting)
+// ========================================================================
+
+#include "common/clusters.wgsl"
+
+@group(0) @binding(2) var<uniform> clusters: ClusterUniform;
+@group(0) @binding(4) var<storage, read> cluster_ranges: array<vec2<u32>>; // offset, count
+
+// How many lights the CPU assigned to the cluster of the visible surface
+@fragment
+fn fs_cluster_lights(in: VertexOutput) -> @location(0) vec4<f32> {
+    clip_alpha(in.uv);
+    let range = cluster_ranges[cluster_index(clusters, in.clip_position.xy, in.world_pos)];
+    return vec4<f32>(f32(range.y), 0.0, 0.0, 0.0);
+}
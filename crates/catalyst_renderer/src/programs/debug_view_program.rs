@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+
+use wgpu::{Queue, RenderPipeline};
+
+use crate::{
+    clusters::{ClusterUniform, LightingMode},
+    debug_view::{DebugViewMode, DebugViewUniform},
+    global_resources::CameraUniform,
+    layout::{uniform_layouts, validate_program_layouts},
+    material::{GpuMaterialUniform, MaterialKey},
+    mesh::{MeshUniform, Vertex},
+    programs::{
+        GpuProgram, GpuProgramRenderContext, PbrProgram,
+        reflected::{
+            self, WGSL_CAMERA, WGSL_CLUSTER_UNIFORM, WGSL_DEBUG_VIEW_UNIFORMS,
+            WGSL_MATERIAL_UNIFORMS, WGSL_MESH_UNIFORM,
+        },
+        pbr_program::{PbrDraw, PbrPass},
+    },
+    shaders::create_shader_module,
+    texture::TextureHelper,
+};
+
+uniform_layouts!(
+    CameraUniform::LAYOUT => WGSL_CAMERA,
+    GpuMaterialUniform::LAYOUT => WGSL_MATERIAL_UNIFORMS,
+    MeshUniform::LAYOUT => WGSL_MESH_UNIFORM,
+    ClusterUniform::LAYOUT => WGSL_CLUSTER_UNIFORM,
+    DebugViewUniform::LAYOUT => WGSL_DEBUG_VIEW_UNIFORMS,
+);
+
+const PASSES: [PbrPass; 4] = [
+    PbrPass::Opaque,
+    PbrPass::Transparent,
+    PbrPass::Overlay,
+    PbrPass::OverlayNoDepth,
+];
+
+// Fragment entry point of each mode in debug_view.wgsl (or debug_view_clusters.wgsl)
+fn entry_point(mode: DebugViewMode) -> Option<&'static str> {
+    match mode {
+        DebugViewMode::Lit => None,
+        DebugViewMode::Overdraw | DebugViewMode::DepthComplexity => Some("fs_count"),
+        DebugViewMode::Normals => Some("fs_normal"),
+        DebugViewMode::Roughness => Some("fs_roughness"),
+        DebugViewMode::Metallic => Some("fs_metallic"),
+        DebugViewMode::LightsPerCluster => Some("fs_cluster_lights"),
+    }
+}
+
+// Blend and depth state of a mode's pipeline for the draws of one PbrPass, None when
+// the mode skips those draws
+fn pass_state(
+    mode: DebugViewMode,
+    pass: PbrPass,
+    depth_compare: wgpu::CompareFunction,
+) -> Option<(wgpu::BlendState, bool, wgpu::CompareFunction)> {
+    let additive = wgpu::BlendState {
+        color: wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        },
+        alpha: wgpu::BlendComponent::REPLACE,
+    };
+    match (mode, pass) {
+        // Depth state of the PBR pipelines, so what they would shade is counted
+        (DebugViewMode::Overdraw, PbrPass::Opaque | PbrPass::Overlay) => {
+            Some((additive, true, depth_compare))
+        }
+        (DebugViewMode::Overdraw, PbrPass::Transparent) => Some((additive, false, depth_compare)),
+        (DebugViewMode::Overdraw, PbrPass::OverlayNoDepth) => {
+            Some((additive, false, wgpu::CompareFunction::Always))
+        }
+        (DebugViewMode::DepthComplexity, _) => {
+            Some((additive, false, wgpu::CompareFunction::Always))
+        }
+        // The rest show the visible opaque surface
+        (DebugViewMode::Lit, _)
+        | (_, PbrPass::Transparent | PbrPass::Overlay | PbrPass::OverlayNoDepth) => None,
+        (_, PbrPass::Opaque) => Some((wgpu::BlendState::REPLACE, true, depth_compare)),
+    }
+}
+
+/// The scene's PBR draws again for a DebugViewMode, into the debug target, and the
+/// fullscreen composite showing that target. A mode's pipelines are created when it is
+/// first shown and dropped when the view goes back to Lit, see `prepare`.
+pub struct DebugViewProgram {
+    shader: wgpu::ShaderModule,
+    lighting_mode: LightingMode,
+    pipeline_layout: wgpu::PipelineLayout,
+    depth_compare: wgpu::CompareFunction,
+    // Per (mode, pass, double sided)
+    pipelines: HashMap<(DebugViewMode, PbrPass, bool), RenderPipeline>,
+    composite_pipeline: RenderPipeline,
+    composite_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+}
+
+impl GpuProgram for DebugViewProgram {
+    // Global, material and mesh layouts, shared with the PBR program
+    type InitData = (
+        wgpu::BindGroupLayout,
+        wgpu::BindGroupLayout,
+        wgpu::BindGroupLayout,
+    );
+    type DrawData<'a> = (&'a wgpu::BindGroup, DebugViewMode, &'a [PbrDraw]);
+
+    fn new(ctx: &GpuProgramRenderContext, init_data: &Self::InitData) -> Self {
+        validate_program_layouts("DebugViewProgram", UNIFORM_LAYOUTS);
+        let (global_layout, material_layout, mesh_layout) = init_data;
+
+        // Cluster ranges are only bound with clustered lighting
+        let files: &[&str] = match ctx.lighting_mode {
+            LightingMode::Clustered => &["debug_view.wgsl", "debug_view_clusters.wgsl"],
+            LightingMode::Uniform => &["debug_view.wgsl"],
+        };
+        let shader = create_shader_module(ctx.device, "Debug View Shader", files);
+
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Debug View Pipeline Layout"),
+                bind_group_layouts: &[global_layout, material_layout, mesh_layout],
+                push_constant_ranges: &[],
+            });
+
+        let composite_layout =
+            ctx.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Debug View Composite Bind Group Layout"),
+                    // Debug target, read with textureLoad, and ramp
+                    entries: reflected::debug_view_composite::GROUP_0,
+                });
+
+        let composite_pipeline_layout =
+            ctx.device
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("Debug View Composite Pipeline Layout"),
+                    bind_group_layouts: &[&composite_layout],
+                    push_constant_ranges: &[],
+                });
+
+        let composite_pipeline =
+            ctx.device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    cache: None,
+                    label: Some("Debug View Composite Pipeline"),
+                    layout: Some(&composite_pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: Some("vs_fullscreen"),
+                        compilation_options: Default::default(),
+                        buffers: &[],
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: Some("fs_composite"),
+                        compilation_options: Default::default(),
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format: ctx.format,
+                            blend: None,
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                    }),
+                    primitive: wgpu::PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState::default(),
+                    multiview: None,
+                });
+
+        let uniform_buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Debug View Uniform Buffer"),
+            size: std::mem::size_of::<DebugViewUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            shader,
+            lighting_mode: ctx.lighting_mode,
+            pipeline_layout,
+            depth_compare: ctx.depth_mode.compare(),
+            pipelines: HashMap::new(),
+            composite_pipeline,
+            composite_layout,
+            uniform_buffer,
+        }
+    }
+
+    fn record<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, data: Self::DrawData<'a>) {
+        let (global_bind_group, mode, draws) = data;
+        PbrProgram::record_draws(render_pass, global_bind_group, draws, None, |key| {
+            let double_sided = key.material.contains(MaterialKey::DOUBLE_SIDED);
+            self.pipelines.get(&(mode, key.pass, double_sided))
+        });
+    }
+}
+
+impl DebugViewProgram {
+    /// Counts are summed in a float target, the channels written as colors.
+    pub fn target_format(mode: DebugViewMode) -> wgpu::TextureFormat {
+        match mode.ramp_max() {
+            Some(_) => wgpu::TextureFormat::R16Float,
+            None => wgpu::TextureFormat::Rgba8Unorm,
+        }
+    }
+
+    /// Whether `mode` can be drawn with this lighting mode.
+    pub fn supports(&self, mode: DebugViewMode) -> bool {
+        mode != DebugViewMode::LightsPerCluster || self.lighting_mode == LightingMode::Clustered
+    }
+
+    /// Creates the pipelines of `mode` unless they exist, and drops those of every
+    /// other mode. Call before declaring the mode's passes; Lit drops them all.
+    pub fn prepare(&mut self, device: &wgpu::Device, mode: DebugViewMode) {
+        self.pipelines
+            .retain(|(pipeline_mode, _, _), _| *pipeline_mode == mode);
+        let Some(entry_point) = entry_point(mode) else {
+            return;
+        };
+        if !self.pipelines.is_empty() || !self.supports(mode) {
+            return;
+        }
+
+        for pass in PASSES {
+            let Some(state) = pass_state(mode, pass, self.depth_compare) else {
+                continue;
+            };
+            for double_sided in [false, true] {
+                let pipeline =
+                    self.create_pipeline(device, mode, entry_point, pass, double_sided, state);
+                self.pipelines.insert((mode, pass, double_sided), pipeline);
+            }
+        }
+    }
+
+    fn create_pipeline(
+        &self,
+        device: &wgpu::Device,
+        mode: DebugViewMode,
+        entry_point: &str,
+        pass: PbrPass,
+        double_sided: bool,
+        (blend, depth_write_enabled, depth_compare): (
+            wgpu::BlendState,
+            bool,
+            wgpu::CompareFunction,
+        ),
+    ) -> RenderPipeline {
+        let label = format!("Debug View Pipeline {:?} {:?}", mode, pass);
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            cache: None,
+            label: Some(&label),
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &self.shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[Vertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.shader,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: Self::target_format(mode),
+                    blend: Some(blend),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: TextureHelper::DEPTH_FORMAT,
+                depth_write_enabled,
+                depth_compare,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: if double_sided {
+                    None
+                } else {
+                    Some(wgpu::Face::Back)
+                },
+                ..Default::default()
+            },
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    pub fn write_uniform(&self, queue: &Queue, uniform: &DebugViewUniform) {
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(uniform));
+    }
+
+    /// Built per frame: the debug target is a transient graph texture.
+    pub fn composite_bind_group(
+        &self,
+        device: &wgpu::Device,
+        target: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Debug View Composite Bind Group"),
+            layout: &self.composite_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(target),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    pub fn record_composite<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        bind_group: &'a wgpu::BindGroup,
+    ) {
+        render_pass.set_pipeline(&self.composite_pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,352 @@
+use std::ops::Range;
+
+use glam::{Mat4, Vec3};
+use wgpu::{Device, Queue, RenderPipeline};
+
+use crate::{
+    global_resources::CameraUniform,
+    layout::{StructLayout, rust_layout, uniform_layouts, validate_program_layouts},
+    programs::{
+        GpuProgram,
+        reflected::{self, WGSL_CAMERA, WGSL_DECAL_VIEW},
+    },
+    shaders::create_shader_module,
+    viewport::Viewport,
+};
+
+uniform_layouts!(
+    CameraUniform::LAYOUT => WGSL_CAMERA,
+    DecalViewUniform::LAYOUT => WGSL_DECAL_VIEW,
+);
+
+/// Offset between the views in the view uniform buffer, the dynamic offset alignment
+/// every adapter supports.
+const VIEW_STRIDE: u64 = 256;
+
+/// What a decal needs to know about the view it is drawn in to rebuild world positions
+/// from the depth buffer.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DecalViewUniform {
+    /// Inverse of the (jittered) view_proj the depth buffer was drawn with.
+    pub inv_view_proj: [[f32; 4]; 4],
+    pub eye: [f32; 4],
+    /// x, y, width, height in pixels.
+    pub viewport: [f32; 4],
+    /// RenderLayers of the view's camera.
+    pub layers: u32,
+    pub _padding: [u32; 3],
+}
+
+const _: () = assert!(std::mem::size_of::<DecalViewUniform>() as u64 <= VIEW_STRIDE);
+
+impl DecalViewUniform {
+    pub const LAYOUT: StructLayout = rust_layout!(DecalViewUniform {
+        inv_view_proj: [[f32; 4]; 4],
+        eye: [f32; 4],
+        viewport: [f32; 4],
+        layers: u32,
+        _padding: [u32; 3],
+    });
+}
+
+/// Per-instance data of one decal. The box corners are generated in the shader.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DecalInstance {
+    /// Unit box -> world, the decal's size included.
+    pub model: [[f32; 4]; 4],
+    /// World -> unit box, for the projection.
+    pub inv_model: [[f32; 4]; 4],
+    pub color: [f32; 4],
+    /// Cosines of the angle fade: fully opaque above x, gone below y.
+    pub angle_fade: [f32; 2],
+    pub layers: u32,
+    pub _padding: u32,
+}
+
+impl DecalInstance {
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 11] = wgpu::vertex_attr_array![
+            // model
+            0 => Float32x4, 1 => Float32x4, 2 => Float32x4, 3 => Float32x4,
+            // inv_model
+            4 => Float32x4, 5 => Float32x4, 6 => Float32x4, 7 => Float32x4,
+            // color
+            8 => Float32x4,
+            // angle_fade
+            9 => Float32x2,
+            // layers
+            10 => Uint32,
+        ];
+
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<DecalInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+/// Instances sharing one texture, drawn with a single call.
+pub struct DecalBatch {
+    pub texture: wgpu::BindGroup,
+    pub instances: Range<u32>,
+}
+
+pub struct DecalProgram {
+    pipeline: RenderPipeline,
+    /// Group 1: the scene depth and the per-view uniforms.
+    view_layout: wgpu::BindGroupLayout,
+    /// Group 2: the decal texture and its sampler.
+    pub texture_layout: wgpu::BindGroupLayout,
+    view_buffer: wgpu::Buffer,
+    view_capacity: usize,
+    /// RenderLayers of each view's camera, gathered with the instances.
+    view_layers: Vec<u32>,
+    buffer: Option<wgpu::Buffer>,
+    capacity: usize,
+    batches: Vec<DecalBatch>,
+}
+
+impl DecalProgram {
+    /// Uploads this frame's decals. `instances` is laid out batch by batch, in draw order.
+    pub fn prepare(
+        &mut self,
+        instances: &[DecalInstance],
+        batches: Vec<DecalBatch>,
+        view_layers: Vec<u32>,
+        device: &Device,
+        queue: &Queue,
+    ) {
+        self.batches = batches;
+        self.view_layers = view_layers;
+        if instances.is_empty() {
+            self.batches.clear();
+            return;
+        }
+
+        match self.buffer {
+            Some(ref buffer) if instances.len() <= self.capacity => {
+                queue.write_buffer(buffer, 0, bytemuck::cast_slice(instances));
+            }
+            _ => {
+                self.capacity = instances.len().max(self.capacity * 2);
+                let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Decal Instance Buffer"),
+                    size: (self.capacity * std::mem::size_of::<DecalInstance>())
+                        as wgpu::BufferAddress,
+                    usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                queue.write_buffer(&buffer, 0, bytemuck::cast_slice(instances));
+                self.buffer = Some(buffer);
+            }
+        }
+    }
+
+    /// Decals drawn per view this frame.
+    pub fn instance_count(&self) -> u32 {
+        self.batches
+            .last()
+            .map(|batch| batch.instances.end)
+            .unwrap_or(0)
+    }
+
+    /// Makes room for `count` views. Call before writing any view of the frame, growing
+    /// replaces the buffer.
+    pub fn ensure_views(&mut self, device: &Device, count: usize) {
+        if count > self.view_capacity {
+            self.view_capacity = count;
+            self.view_buffer = create_view_buffer(device, count);
+        }
+    }
+
+    /// Writes view `index`'s uniforms. Called by "Render Frame" with the same matrix
+    /// the depth buffer is drawn with.
+    pub fn write_view(
+        &self,
+        queue: &Queue,
+        index: usize,
+        view_proj: Mat4,
+        eye: Vec3,
+        viewport: &Viewport,
+    ) {
+        let uniform = DecalViewUniform {
+            inv_view_proj: view_proj.inverse().to_cols_array_2d(),
+            eye: eye.extend(1.0).to_array(),
+            viewport: [viewport.x, viewport.y, viewport.width, viewport.height],
+            layers: self.view_layers.get(index).copied().unwrap_or(u32::MAX),
+            _padding: [0; 3],
+        };
+        queue.write_buffer(
+            &self.view_buffer,
+            index as u64 * VIEW_STRIDE,
+            bytemuck::bytes_of(&uniform),
+        );
+    }
+
+    /// Group 1 for this frame. The depth view is only known once the graph executes.
+    pub fn view_bind_group(&self, device: &Device, depth: &wgpu::TextureView) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Decal View Bind Group"),
+            layout: &self.view_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(depth),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &self.view_buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(std::mem::size_of::<DecalViewUniform>() as u64),
+                    }),
+                },
+            ],
+        })
+    }
+
+    pub fn texture_bind_group(
+        &self,
+        device: &Device,
+        view: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Decal Texture Bind Group"),
+            layout: &self.texture_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        })
+    }
+}
+
+fn create_view_buffer(device: &Device, views: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Decal View Buffer"),
+        size: views.max(1) as u64 * VIEW_STRIDE,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+impl GpuProgram for DecalProgram {
+    type InitData = wgpu::BindGroupLayout;
+
+    /// Global (camera) bind group, the frame's view bind group and the view index.
+    type DrawData<'a> = (&'a wgpu::BindGroup, &'a wgpu::BindGroup, usize);
+
+    fn new(ctx: &super::GpuProgramRenderContext, global_layout: &Self::InitData) -> Self {
+        validate_program_layouts("DecalProgram", UNIFORM_LAYOUTS);
+
+        let shader = create_shader_module(ctx.device, "Decal Shader", &["decals.wgsl"]);
+
+        let view_layout = ctx
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Decal View Layout"),
+                // Scene depth, read with textureLoad, and the views at a dynamic offset
+                entries: reflected::decals::GROUP_1,
+            });
+
+        let texture_layout = ctx
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Decal Texture Layout"),
+                entries: reflected::decals::GROUP_2,
+            });
+
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Decal Pipeline Layout"),
+                bind_group_layouts: &[global_layout, &view_layout, &texture_layout],
+                push_constant_ranges: &[],
+            });
+
+        let pipeline = ctx
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                cache: None,
+                label: Some("Decal Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[DecalInstance::desc()],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    compilation_options: Default::default(),
+                    // Color only: decals don't move the surface (velocity) and auto
+                    // exposure measures the geometry underneath
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: ctx.format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    // Back faces only: one fragment per pixel, also with the camera inside the box
+                    cull_mode: Some(wgpu::Face::Front),
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                // The depth buffer is read as a texture instead, the box test does the rest
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            });
+
+        Self {
+            pipeline,
+            view_layout,
+            texture_layout,
+            view_buffer: create_view_buffer(ctx.device, 1),
+            view_capacity: 1,
+            view_layers: Vec::new(),
+            buffer: None,
+            capacity: 0,
+            batches: Vec::new(),
+        }
+    }
+
+    fn record<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        (global_bind_group, view_bind_group, view_index): Self::DrawData<'a>,
+    ) {
+        let Some(ref buffer) = self.buffer else {
+            return;
+        };
+        if self.batches.is_empty() {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, global_bind_group, &[]);
+        render_pass.set_bind_group(1, view_bind_group, &[(view_index as u64 * VIEW_STRIDE) as u32]);
+        render_pass.set_vertex_buffer(0, buffer.slice(..));
+
+        for batch in &self.batches {
+            render_pass.set_bind_group(2, &batch.texture, &[]);
+            render_pass.draw(0..36, batch.instances.clone());
+        }
+    }
+}
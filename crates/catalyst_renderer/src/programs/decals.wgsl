@@ -0,0 +1,133 @@
+#include "common/camera.wgsl"
+
+struct DecalView {
+    inv_view_proj: mat4x4<f32>,
+    eye: vec4<f32>,
+    viewport: vec4<f32>, // x, y, width, height in pixels
+    layers: u32,
+    padding: vec3<u32>,
+};
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+@group(1) @binding(0) var scene_depth: texture_depth_2d;
+@group(1) @binding(1) var<uniform> view: DecalView;
+
+@group(2) @binding(0) var decal_texture: texture_2d<f32>;
+@group(2) @binding(1) var decal_sampler: sampler;
+
+// One decal, stepped once per instance
+struct InstanceIn {
+    @location(0) model_0: vec4<f32>,
+    @location(1) model_1: vec4<f32>,
+    @location(2) model_2: vec4<f32>,
+    @location(3) model_3: vec4<f32>,
+    @location(4) inv_model_0: vec4<f32>,
+    @location(5) inv_model_1: vec4<f32>,
+    @location(6) inv_model_2: vec4<f32>,
+    @location(7) inv_model_3: vec4<f32>,
+    @location(8) color: vec4<f32>,
+    @location(9) angle_fade: vec2<f32>,
+    @location(10) layers: u32,
+};
+
+struct VSOut {
+    @builtin(position) clip_pos: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) angle_fade: vec2<f32>,
+    // Decal space is constant over the box, passed flat
+    @location(2) @interpolate(flat) inv_model_0: vec4<f32>,
+    @location(3) @interpolate(flat) inv_model_1: vec4<f32>,
+    @location(4) @interpolate(flat) inv_model_2: vec4<f32>,
+    @location(5) @interpolate(flat) inv_model_3: vec4<f32>,
+    @location(6) @interpolate(flat) projection_axis: vec3<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32, decal: InstanceIn) -> VSOut {
+    // Face normal and two edges per face, with edge_u x edge_v = normal, so the
+    // corners below wind counter-clockwise seen from outside
+    var normals = array<vec3<f32>, 6>(
+        vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(-1.0, 0.0, 0.0),
+        vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(0.0, -1.0, 0.0),
+        vec3<f32>(0.0, 0.0, 1.0), vec3<f32>(0.0, 0.0, -1.0),
+    );
+    var edges_u = array<vec3<f32>, 6>(
+        vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(0.0, 0.0, 1.0),
+        vec3<f32>(0.0, 0.0, 1.0), vec3<f32>(1.0, 0.0, 0.0),
+        vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0),
+    );
+    var edges_v = array<vec3<f32>, 6>(
+        vec3<f32>(0.0, 0.0, 1.0), vec3<f32>(0.0, 1.0, 0.0),
+        vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 0.0, 1.0),
+        vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(1.0, 0.0, 0.0),
+    );
+    // Two triangles per face, no vertex buffer
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let face = vertex_index / 6u;
+    let corner = corners[vertex_index % 6u];
+    let local = (normals[face] + edges_u[face] * corner.x + edges_v[face] * corner.y) * 0.5;
+
+    let model = mat4x4<f32>(decal.model_0, decal.model_1, decal.model_2, decal.model_3);
+
+    var out: VSOut;
+    // Not on a layer this view draws: collapse the box
+    if (decal.layers & view.layers) == 0u {
+        out.clip_pos = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+    } else {
+        out.clip_pos = camera.view_proj * model * vec4<f32>(local, 1.0);
+    }
+    out.color = decal.color;
+    out.angle_fade = decal.angle_fade;
+    out.inv_model_0 = decal.inv_model_0;
+    out.inv_model_1 = decal.inv_model_1;
+    out.inv_model_2 = decal.inv_model_2;
+    out.inv_model_3 = decal.inv_model_3;
+    // Decals project down their local -Y, onto surfaces facing +Y
+    out.projection_axis = normalize(decal.model_1.xyz);
+    return out;
+}
+
+@fragment
+fn fs_main(input: VSOut) -> @location(0) vec4<f32> {
+    let pixel = input.clip_pos.xy;
+    let depth = textureLoad(scene_depth, vec2<i32>(pixel), 0);
+
+    // Back to world space through the same matrix the depth was drawn with
+    let uv = (pixel - view.viewport.xy) / view.viewport.zw;
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let world_h = view.inv_view_proj * ndc;
+    // Sky with an infinite far plane lands at w = 0
+    let valid = abs(world_h.w) > 1e-6;
+    let world_pos = world_h.xyz / select(1.0, world_h.w, valid);
+
+    // Derivatives before any discard, they need uniform control flow
+    var normal = normalize(cross(dpdy(world_pos), dpdx(world_pos)));
+    if dot(normal, view.eye.xyz - world_pos) < 0.0 {
+        normal = -normal;
+    }
+
+    let inv_model = mat4x4<f32>(input.inv_model_0, input.inv_model_1, input.inv_model_2, input.inv_model_3);
+    let local = (inv_model * vec4<f32>(world_pos, 1.0)).xyz;
+    if !valid || any(abs(local) > vec3<f32>(0.5)) {
+        discard;
+    }
+
+    // Fade out on surfaces turned away from the projection, and towards the
+    // ends of the box so the decal doesn't stop at a hard line
+    let facing = dot(normal, input.projection_axis);
+    let angle_fade = smoothstep(input.angle_fade.y, input.angle_fade.x, facing);
+    let depth_fade = 1.0 - smoothstep(0.4, 0.5, abs(local.y));
+
+    let texture_uv = vec2<f32>(local.x + 0.5, local.z + 0.5);
+    var color = textureSampleLevel(decal_texture, decal_sampler, texture_uv, 0.0) * input.color;
+    color.a *= angle_fade * depth_fade;
+    return color;
+}
@@ -0,0 +1,198 @@
+// ========================================================================
+//  DEPTH OF FIELD
+//  The circle of confusion comes from the depth buffer. The image is split
+//  into near and far fields at half resolution, each blurred with a disc
+//  kernel (scatter as gather), then composited over the sharp image.
+//  Radii are signed: negative in front of the focus, positive behind it.
+// ========================================================================
+
+struct DofView {
+    rect: vec4<f32>,       // Viewport x, y, width, height in pixels
+    focus_distance: f32,   // Manual focus, meters
+    aperture: f32,         // Aperture diameter, meters
+    focal_length: f32,     // Meters
+    pixels_per_meter: f32, // Sensor meters to radius in pixels
+    max_radius: f32,       // Pixels, full resolution
+    near: f32,
+    far: f32,
+    reversed_z: u32,
+    autofocus: u32,
+    focus_blend: f32,
+    min_focus: f32,
+    max_focus: f32,
+    enabled: u32,
+    padding0: u32,
+    padding1: vec2<u32>,
+};
+
+struct DofUniforms {
+    views: array<DofView, 4>,
+};
+
+@group(0) @binding(0) var<uniform> dof: DofUniforms;
+@group(0) @binding(1) var<storage, read> focus: array<f32, 4>; // Written by dof_focus.wgsl
+@group(0) @binding(2) var s_linear: sampler;
+
+// Full resolution scene
+@group(1) @binding(0) var t_color: texture_2d<f32>;
+@group(1) @binding(1) var t_depth: texture_depth_2d;
+
+// Half resolution fields: color, radius in half resolution pixels
+@group(2) @binding(0) var t_near: texture_2d<f32>;
+@group(2) @binding(1) var t_far: texture_2d<f32>;
+
+const PI: f32 = 3.14159265;
+const GOLDEN_ANGLE: f32 = 2.39996323;
+const SAMPLES: u32 = 48u;
+
+fn linear_depth(view: DofView, depth: f32) -> f32 {
+    if (view.reversed_z != 0u) {
+        // Infinite far plane: depth = near / z
+        return view.near / max(depth, 1e-7);
+    }
+    return view.near * view.far / (view.far - depth * (view.far - view.near));
+}
+
+// Thin lens: blur circle radius in pixels of a point at distance z
+fn circle_of_confusion(view: DofView, focus_distance: f32, z: f32) -> f32 {
+    let coc = view.aperture * view.focal_length * (z - focus_distance)
+        / (z * (focus_distance - view.focal_length));
+    return clamp(coc * view.pixels_per_meter, -view.max_radius, view.max_radius);
+}
+
+struct FullscreenOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) @interpolate(flat) view: u32,
+};
+
+// Fullscreen triangle, no vertex buffer; drawn once per viewport, the view travels
+// as the instance index
+@vertex
+fn vs_fullscreen(
+    @builtin(vertex_index) index: u32,
+    @builtin(instance_index) view: u32,
+) -> FullscreenOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+    var out: FullscreenOutput;
+    out.position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    out.view = view;
+    return out;
+}
+
+struct FieldsOutput {
+    @location(0) near: vec4<f32>,
+    @location(1) far: vec4<f32>,
+};
+
+// --- SPLIT: full resolution -> near and far fields at half resolution ---
+
+@fragment
+fn fs_split(in: FullscreenOutput) -> FieldsOutput {
+    var out: FieldsOutput;
+    let view = dof.views[in.view];
+    if (view.enabled == 0u) {
+        return out;
+    }
+
+    let size = vec2<i32>(textureDimensions(t_color));
+    let base = vec2<i32>(in.position.xy) * 2;
+
+    var color = vec3<f32>(0.0);
+    var near_radius = 0.0;
+    var far_radius = view.max_radius;
+    for (var i = 0; i < 4; i++) {
+        let coord = min(base + vec2<i32>(i & 1, i >> 1u), size - vec2<i32>(1));
+        color += textureLoad(t_color, coord, 0).rgb;
+        let z = linear_depth(view, textureLoad(t_depth, coord, 0));
+        let coc = circle_of_confusion(view, focus[in.view], z);
+        // The near field takes the blurriest texel, so it spreads over edges; the far
+        // field the sharpest, so it doesn't
+        near_radius = max(near_radius, -coc);
+        far_radius = min(far_radius, max(coc, 0.0));
+    }
+    color *= 0.25;
+
+    out.near = vec4<f32>(color, near_radius * 0.5);
+    out.far = vec4<f32>(color, far_radius * 0.5);
+    return out;
+}
+
+// --- BLUR: disc kernel over both fields, at half resolution ---
+
+@fragment
+fn fs_blur(in: FullscreenOutput) -> FieldsOutput {
+    var out: FieldsOutput;
+    let view = dof.views[in.view];
+    if (view.enabled == 0u) {
+        return out;
+    }
+
+    let size = vec2<f32>(textureDimensions(t_near));
+    let radius = max(view.max_radius * 0.5, 0.5);
+    // Area of the kernel each sample stands for
+    let sample_area = PI * radius * radius / f32(SAMPLES);
+
+    var near_color = vec3<f32>(0.0);
+    var near_weight = 0.0;
+    var near_coverage = 0.0;
+    var far_color = vec3<f32>(0.0);
+    var far_weight = 0.0;
+
+    for (var i = 0u; i < SAMPLES; i++) {
+        // Golden angle spiral: the samples cover the disc evenly
+        let distance = sqrt((f32(i) + 0.5) / f32(SAMPLES)) * radius;
+        let angle = f32(i) * GOLDEN_ANGLE;
+        let uv = (in.position.xy + vec2<f32>(cos(angle), sin(angle)) * distance) / size;
+
+        // Scatter as gather: a sample only counts where its own blur circle reaches
+        let far = textureSampleLevel(t_far, s_linear, uv, 0.0);
+        let far_reach = saturate(far.a - distance + 1.0);
+        far_color += far.rgb * far_reach;
+        far_weight += far_reach;
+
+        // Near samples spread their color over their whole circle, so their coverage
+        // here is the share of it this sample stands for. Over in-focus geometry that
+        // gives the soft, partly transparent edge of a blurred foreground.
+        let near = textureSampleLevel(t_near, s_linear, uv, 0.0);
+        let near_reach = saturate(near.a - distance + 1.0);
+        near_color += near.rgb * near_reach;
+        near_weight += near_reach;
+        near_coverage += near_reach * sample_area / max(PI * near.a * near.a, sample_area);
+    }
+
+    let center = vec2<i32>(in.position.xy);
+    if (far_weight > 0.0) {
+        out.far = vec4<f32>(far_color / far_weight, 1.0);
+    } else {
+        out.far = vec4<f32>(textureLoad(t_far, center, 0).rgb, 1.0);
+    }
+    if (near_weight > 0.0) {
+        out.near = vec4<f32>(near_color / near_weight, saturate(near_coverage));
+    }
+    return out;
+}
+
+// --- COMPOSITE: blurred fields over the sharp image, full resolution ---
+
+@fragment
+fn fs_composite(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(in.position.xy);
+    let sharp = textureLoad(t_color, pixel, 0);
+    let view = dof.views[in.view];
+    if (view.enabled == 0u) {
+        return sharp;
+    }
+
+    let z = linear_depth(view, textureLoad(t_depth, pixel, 0));
+    let coc = circle_of_confusion(view, focus[in.view], z);
+    let uv = in.position.xy / vec2<f32>(textureDimensions(t_color));
+    let near = textureSampleLevel(t_near, s_linear, uv, 0.0);
+    let far = textureSampleLevel(t_far, s_linear, uv, 0.0);
+
+    // Fades into the far blur over its first pixel, so in-focus pixels stay sharp
+    var color = mix(sharp.rgb, far.rgb, smoothstep(0.5, 1.5, coc));
+    // The near field goes on top of everything, including sharp geometry behind it
+    color = mix(color, near.rgb, near.a);
+    return vec4<f32>(color, sharp.a);
+}
@@ -0,0 +1,352 @@
+use wgpu::{ComputePipeline, Queue, RenderPipeline};
+
+use crate::{
+    depth_of_field::{DofUniform, DofViewUniform, FIELD_FORMAT, MAX_VIEWS},
+    layout::{uniform_layouts, validate_program_layouts},
+    programs::{
+        GpuProgramRenderContext,
+        reflected::{self, WGSL_DOF_UNIFORMS, WGSL_DOF_VIEW},
+    },
+    viewport::Viewport,
+};
+
+uniform_layouts!(
+    DofViewUniform::LAYOUT => WGSL_DOF_VIEW,
+    DofUniform::LAYOUT => WGSL_DOF_UNIFORMS,
+);
+
+/// Depth of field in four steps: a compute pass updating each view's focus distance,
+/// then fullscreen passes splitting the image into near and far fields, blurring them
+/// and compositing them over the sharp image. Not a GpuProgram: it has more than one
+/// thing to record, see depth_of_field::add_depth_of_field_passes.
+pub struct DepthOfFieldProgram {
+    focus_pipeline: ComputePipeline,
+    split_pipeline: RenderPipeline,
+    blur_pipeline: RenderPipeline,
+    composite_pipeline: RenderPipeline,
+    focus_layout: wgpu::BindGroupLayout,
+    scene_layout: wgpu::BindGroupLayout,
+    fields_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+    /// Focus distance per view, written by the focus pass and kept between frames.
+    focus_buffer: wgpu::Buffer,
+    settings_bind_group: wgpu::BindGroup,
+}
+
+impl DepthOfFieldProgram {
+    pub fn new(ctx: &GpuProgramRenderContext) -> Self {
+        validate_program_layouts("DepthOfFieldProgram", UNIFORM_LAYOUTS);
+        let device = ctx.device;
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("depth_of_field.wgsl"));
+        let focus_shader = device.create_shader_module(wgpu::include_wgsl!("dof_focus.wgsl"));
+
+        let focus_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("DoF Focus Bind Group Layout"),
+            // Settings, focus distances and depth
+            entries: reflected::dof_focus::GROUP_0,
+        });
+
+        let settings_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("DoF Settings Bind Group Layout"),
+            // Settings, focus distances and sampler
+            entries: reflected::depth_of_field::GROUP_0,
+        });
+
+        let scene_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("DoF Scene Bind Group Layout"),
+            // Sharp color and depth
+            entries: reflected::depth_of_field::GROUP_1,
+        });
+
+        let fields_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("DoF Fields Bind Group Layout"),
+            // Near and far field
+            entries: reflected::depth_of_field::GROUP_2,
+        });
+
+        let focus_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("DoF Focus Pipeline Layout"),
+            bind_group_layouts: &[&focus_layout],
+            push_constant_ranges: &[],
+        });
+        let focus_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("DoF Focus Pipeline"),
+            layout: Some(&focus_pipeline_layout),
+            module: &focus_shader,
+            entry_point: Some("cs_focus"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        // The split pass doesn't see the fields yet
+        let split_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("DoF Split Pipeline Layout"),
+            bind_group_layouts: &[&settings_layout, &scene_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("DoF Pipeline Layout"),
+            bind_group_layouts: &[&settings_layout, &scene_layout, &fields_layout],
+            push_constant_ranges: &[],
+        });
+
+        let field_target = Some(wgpu::ColorTargetState {
+            format: FIELD_FORMAT,
+            blend: None,
+            write_mask: wgpu::ColorWrites::ALL,
+        });
+        let fullscreen_pipeline =
+            |label: &str,
+             layout: &wgpu::PipelineLayout,
+             entry_point: &str,
+             targets: &[Option<wgpu::ColorTargetState>]| {
+                device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    cache: None,
+                    label: Some(label),
+                    layout: Some(layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: Some("vs_fullscreen"),
+                        compilation_options: Default::default(),
+                        buffers: &[],
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: Some(entry_point),
+                        compilation_options: Default::default(),
+                        targets,
+                    }),
+                    primitive: wgpu::PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState::default(),
+                    multiview: None,
+                })
+            };
+
+        let split_pipeline = fullscreen_pipeline(
+            "DoF Split Pipeline",
+            &split_pipeline_layout,
+            "fs_split",
+            &[field_target.clone(), field_target.clone()],
+        );
+        let blur_pipeline = fullscreen_pipeline(
+            "DoF Blur Pipeline",
+            &pipeline_layout,
+            "fs_blur",
+            &[field_target.clone(), field_target],
+        );
+        let composite_pipeline = fullscreen_pipeline(
+            "DoF Composite Pipeline",
+            &pipeline_layout,
+            "fs_composite",
+            &[Some(wgpu::ColorTargetState {
+                format: ctx.format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        );
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("DoF Uniform Buffer"),
+            size: std::mem::size_of::<DofUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let focus_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("DoF Focus Buffer"),
+            size: (MAX_VIEWS * std::mem::size_of::<f32>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("DoF Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let settings_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("DoF Settings Bind Group"),
+            layout: &settings_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: focus_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        Self {
+            focus_pipeline,
+            split_pipeline,
+            blur_pipeline,
+            composite_pipeline,
+            focus_layout,
+            scene_layout,
+            fields_layout,
+            uniform_buffer,
+            focus_buffer,
+            settings_bind_group,
+        }
+    }
+
+    pub fn write_uniform(&self, queue: &Queue, uniform: &DofUniform) {
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(uniform));
+    }
+
+    /// Built per frame, like the others below: depth is bound through the frame graph.
+    pub fn focus_bind_group(
+        &self,
+        device: &wgpu::Device,
+        depth: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("DoF Focus Bind Group"),
+            layout: &self.focus_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.focus_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(depth),
+                },
+            ],
+        })
+    }
+
+    pub fn scene_bind_group(
+        &self,
+        device: &wgpu::Device,
+        color: &wgpu::TextureView,
+        depth: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("DoF Scene Bind Group"),
+            layout: &self.scene_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(color),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(depth),
+                },
+            ],
+        })
+    }
+
+    pub fn fields_bind_group(
+        &self,
+        device: &wgpu::Device,
+        near: &wgpu::TextureView,
+        far: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("DoF Fields Bind Group"),
+            layout: &self.fields_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(near),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(far),
+                },
+            ],
+        })
+    }
+
+    /// One workgroup per view.
+    pub fn dispatch_focus(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        bind_group: &wgpu::BindGroup,
+        view_count: u32,
+        timestamp_writes: Option<wgpu::ComputePassTimestampWrites>,
+    ) {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("DoF Focus"),
+            timestamp_writes,
+        });
+        pass.set_pipeline(&self.focus_pipeline);
+        pass.set_bind_group(0, bind_group, &[]);
+        pass.dispatch_workgroups(view_count, 1, 1);
+    }
+
+    pub fn record_split<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        scene: &'a wgpu::BindGroup,
+        viewports: &[Viewport],
+    ) {
+        render_pass.set_pipeline(&self.split_pipeline);
+        render_pass.set_bind_group(0, &self.settings_bind_group, &[]);
+        render_pass.set_bind_group(1, scene, &[]);
+        Self::draw_views(render_pass, viewports);
+    }
+
+    pub fn record_blur<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        scene: &'a wgpu::BindGroup,
+        fields: &'a wgpu::BindGroup,
+        viewports: &[Viewport],
+    ) {
+        render_pass.set_pipeline(&self.blur_pipeline);
+        self.bind_all(render_pass, scene, fields);
+        Self::draw_views(render_pass, viewports);
+    }
+
+    pub fn record_composite<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        scene: &'a wgpu::BindGroup,
+        fields: &'a wgpu::BindGroup,
+        viewports: &[Viewport],
+    ) {
+        render_pass.set_pipeline(&self.composite_pipeline);
+        self.bind_all(render_pass, scene, fields);
+        Self::draw_views(render_pass, viewports);
+    }
+
+    fn bind_all<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        scene: &'a wgpu::BindGroup,
+        fields: &'a wgpu::BindGroup,
+    ) {
+        render_pass.set_bind_group(0, &self.settings_bind_group, &[]);
+        render_pass.set_bind_group(1, scene, &[]);
+        render_pass.set_bind_group(2, fields, &[]);
+    }
+
+    // A fullscreen triangle per view, clipped to its viewport; the view travels as
+    // the instance index
+    fn draw_views(render_pass: &mut wgpu::RenderPass, viewports: &[Viewport]) {
+        for (index, viewport) in viewports.iter().enumerate() {
+            viewport.apply(render_pass);
+            let index = index as u32;
+            render_pass.draw(0..3, index..index + 1);
+        }
+    }
+}
@@ -0,0 +1,74 @@
+// ========================================================================
+//  DEPTH OF FIELD FOCUS
+//  One workgroup per view: writes the view's focus distance for the DoF
+//  passes. Autofocus measures the depth at the center of the viewport and
+//  moves toward it; the result stays in the buffer for the next frame.
+// ========================================================================
+
+struct DofView {
+    rect: vec4<f32>,
+    focus_distance: f32,
+    aperture: f32,
+    focal_length: f32,
+    pixels_per_meter: f32,
+    max_radius: f32,
+    near: f32,
+    far: f32,
+    reversed_z: u32,
+    autofocus: u32,
+    focus_blend: f32, // Weight of this frame's measurement, 1 snaps
+    min_focus: f32,
+    max_focus: f32,
+    enabled: u32,
+    padding0: u32,
+    padding1: vec2<u32>,
+};
+
+struct DofUniforms {
+    views: array<DofView, 4>,
+};
+
+@group(0) @binding(0) var<uniform> dof: DofUniforms;
+@group(0) @binding(1) var<storage, read_write> focus: array<f32, 4>;
+@group(0) @binding(2) var t_depth: texture_depth_2d;
+
+// Pixels between the center and the other measured points
+const SPREAD: i32 = 4;
+
+fn linear_depth(view: DofView, depth: f32) -> f32 {
+    if (view.reversed_z != 0u) {
+        return view.near / max(depth, 1e-7);
+    }
+    return view.near * view.far / (view.far - depth * (view.far - view.near));
+}
+
+@compute @workgroup_size(1, 1, 1)
+fn cs_focus(@builtin(workgroup_id) group: vec3<u32>) {
+    let index = group.x;
+    let view = dof.views[index];
+    if (view.autofocus == 0u) {
+        focus[index] = view.focus_distance;
+        return;
+    }
+
+    // A small cross around the center, so a thin object or a gap to the sky there
+    // doesn't throw the focus off
+    let size = vec2<i32>(textureDimensions(t_depth));
+    let center = vec2<i32>(view.rect.xy + view.rect.zw * 0.5);
+    var offsets = array<vec2<i32>, 5>(
+        vec2<i32>(0, 0),
+        vec2<i32>(SPREAD, 0),
+        vec2<i32>(-SPREAD, 0),
+        vec2<i32>(0, SPREAD),
+        vec2<i32>(0, -SPREAD),
+    );
+    var distance = 0.0;
+    for (var i = 0; i < 5; i++) {
+        let coord = clamp(center + offsets[i], vec2<i32>(0), size - vec2<i32>(1));
+        let z = linear_depth(view, textureLoad(t_depth, coord, 0));
+        distance += clamp(z, view.min_focus, view.max_focus);
+    }
+    distance /= 5.0;
+
+    focus[index] = mix(focus[index], distance, view.focus_blend);
+}
@@ -0,0 +1,616 @@
+use std::collections::{HashMap, HashSet};
+
+use bytemuck::Zeroable;
+use flecs_ecs::prelude::Entity;
+use wgpu::{ComputePipeline, Device, Queue, RenderPipeline};
+
+use crate::{
+    global_resources::CameraUniform,
+    layout::{StructLayout, rust_layout, uniform_layouts, validate_program_layouts},
+    programs::{
+        GpuProgramRenderContext,
+        reflected::{
+            self, WGSL_CAMERA, WGSL_GPU_PARTICLE, WGSL_GPU_PARTICLE_EMITTER,
+            WGSL_GPU_PARTICLE_PARAMS, WGSL_GPU_PARTICLE_SPAWN,
+        },
+    },
+    shaders::create_shader_module,
+    texture::TextureHelper,
+};
+
+/// GPU tier emitters drawn at once; the emitter table is a uniform array this long
+/// (the 256 in gpu_particles*.wgsl).
+pub const MAX_GPU_EMITTERS: usize = 256;
+
+/// Spawn requests the ring holds, the most spawned per frame over all GPU emitters.
+pub const SPAWN_RING_CAPACITY: usize = 65536;
+
+// Threads per workgroup of the simulation and spawn dispatches
+const WORKGROUP_SIZE: u32 = 64;
+
+// Byte offsets of the draw and dispatch halves of GpuParticleArgs
+const DRAW_ARGS_OFFSET: wgpu::BufferAddress = 0;
+const DISPATCH_ARGS_OFFSET: wgpu::BufferAddress = 16;
+const ARGS_SIZE: wgpu::BufferAddress = 32;
+
+/// One particle in a state buffer. Written by the GPU only.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GpuParticle {
+    pub position: [f32; 3],
+    pub age: f32,
+    pub velocity: [f32; 3],
+    pub lifetime: f32,
+    pub emitter: u32,
+    pub _padding0: u32,
+    pub _padding1: u32,
+    pub _padding2: u32,
+}
+
+impl GpuParticle {
+    pub const LAYOUT: StructLayout = rust_layout!(GpuParticle {
+        position: [f32; 3],
+        age: f32,
+        velocity: [f32; 3],
+        lifetime: f32,
+        emitter: u32,
+        _padding0: u32,
+        _padding1: u32,
+        _padding2: u32,
+    });
+}
+
+/// Bits of GpuParticleEmitter::flags.
+pub const EMITTER_ACTIVE: u32 = 1;
+pub const EMITTER_GROUND: u32 = 2;
+pub const EMITTER_PAUSED: u32 = 4;
+
+/// What the simulation and the draw need of one GPU tier ParticleEmitter. A zeroed
+/// entry is an empty slot: its particles die in the next simulation step.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GpuParticleEmitter {
+    pub start_color: [f32; 4],
+    pub end_color: [f32; 4],
+    pub gravity: [f32; 3],
+    pub drag: f32,
+    pub start_size: f32,
+    pub end_size: f32,
+    pub ground_height: f32,
+    pub restitution: f32,
+    pub flags: u32,
+    pub _padding0: u32,
+    pub _padding1: u32,
+    pub _padding2: u32,
+}
+
+impl GpuParticleEmitter {
+    pub const LAYOUT: StructLayout = rust_layout!(GpuParticleEmitter {
+        start_color: [f32; 4],
+        end_color: [f32; 4],
+        gravity: [f32; 3],
+        drag: f32,
+        start_size: f32,
+        end_size: f32,
+        ground_height: f32,
+        restitution: f32,
+        flags: u32,
+        _padding0: u32,
+        _padding1: u32,
+        _padding2: u32,
+    });
+}
+
+/// A particle to add, launched on the CPU. `emitter` is set to the emitter's slot
+/// by `GpuParticleProgram::prepare`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GpuParticleSpawn {
+    pub position: [f32; 3],
+    pub emitter: u32,
+    pub velocity: [f32; 3],
+    pub lifetime: f32,
+}
+
+impl GpuParticleSpawn {
+    pub const LAYOUT: StructLayout = rust_layout!(GpuParticleSpawn {
+        position: [f32; 3],
+        emitter: u32,
+        velocity: [f32; 3],
+        lifetime: f32,
+    });
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GpuParticleParams {
+    pub dt: f32,
+    pub source: u32,
+    pub capacity: u32,
+    pub spawn_start: u32,
+    pub spawn_count: u32,
+    pub spawn_capacity: u32,
+    pub _padding0: u32,
+    pub _padding1: u32,
+}
+
+impl GpuParticleParams {
+    pub const LAYOUT: StructLayout = rust_layout!(GpuParticleParams {
+        dt: f32,
+        source: u32,
+        capacity: u32,
+        spawn_start: u32,
+        spawn_count: u32,
+        spawn_capacity: u32,
+        _padding0: u32,
+        _padding1: u32,
+    });
+}
+
+uniform_layouts!(
+    CameraUniform::LAYOUT => WGSL_CAMERA,
+    GpuParticle::LAYOUT => WGSL_GPU_PARTICLE,
+    GpuParticleEmitter::LAYOUT => WGSL_GPU_PARTICLE_EMITTER,
+    GpuParticleSpawn::LAYOUT => WGSL_GPU_PARTICLE_SPAWN,
+    GpuParticleParams::LAYOUT => WGSL_GPU_PARTICLE_PARAMS,
+);
+
+// Created with the first GPU emitter: a million particles are 96 MB of state
+struct GpuParticleBuffers {
+    params: wgpu::Buffer,
+    emitters: wgpu::Buffer,
+    spawns: wgpu::Buffer,
+    counters: wgpu::Buffer,
+    args: wgpu::Buffer,
+    // Indexed by the state buffer holding last frame's particles
+    simulate_bind_groups: [wgpu::BindGroup; 2],
+    draw_bind_groups: [wgpu::BindGroup; 2],
+    finish_bind_group: wgpu::BindGroup,
+}
+
+/// The GPU particle tier: every GPU tier emitter's particles in one pool, stepped by a
+/// compute pass and drawn with one indirect draw, additively blended. The particles
+/// live in two state buffers; each frame the simulation reads last frame's and appends
+/// the survivors, then the frame's spawns, to the other one, so the alive ones stay
+/// packed at the front. Their count only exists on the GPU: it sizes the draw and the
+/// next frame's dispatch without a readback. Not a GpuProgram: it records a compute
+/// pass and a draw, see particles::add_gpu_particle_passes.
+pub struct GpuParticleProgram {
+    simulate_pipeline: ComputePipeline,
+    spawn_pipeline: ComputePipeline,
+    finish_pipeline: ComputePipeline,
+    draw_pipeline: RenderPipeline,
+    simulate_layout: wgpu::BindGroupLayout,
+    finish_layout: wgpu::BindGroupLayout,
+    draw_layout: wgpu::BindGroupLayout,
+    capacity: u32,
+    buffers: Option<GpuParticleBuffers>,
+
+    // Emitter slots by entity. Slots whose emitter went away wait a frame in `retired`,
+    // so the pass that kills their particles runs before the slot is reused.
+    slots: HashMap<Entity, u32>,
+    seen: HashSet<Entity>,
+    free_slots: Vec<u32>,
+    retired: Vec<u32>,
+
+    // This frame
+    source: u32,
+    spawn_head: u32,
+    spawn_count: u32,
+    active: bool,
+}
+
+impl GpuParticleProgram {
+    /// `capacity` particles at most, fewer if the device's storage buffers can't hold them.
+    pub fn new(
+        ctx: &GpuProgramRenderContext,
+        global_layout: &wgpu::BindGroupLayout,
+        capacity: u32,
+    ) -> Self {
+        validate_program_layouts("GpuParticleProgram", UNIFORM_LAYOUTS);
+        let device = ctx.device;
+
+        let limits = device.limits();
+        let particle_size = std::mem::size_of::<GpuParticle>() as u64;
+        let capacity = (capacity as u64)
+            .min(limits.max_storage_buffer_binding_size as u64 / particle_size)
+            .min(limits.max_buffer_size / particle_size)
+            .min(limits.max_compute_workgroups_per_dimension as u64 * WORKGROUP_SIZE as u64)
+            .max(1) as u32;
+
+        let simulate_shader = create_shader_module(
+            device,
+            "GPU Particle Simulation Shader",
+            &["gpu_particles_simulate.wgsl"],
+        );
+        let draw_shader =
+            create_shader_module(device, "GPU Particle Shader", &["gpu_particles.wgsl"]);
+
+        let simulate_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("GPU Particle Simulation Bind Group Layout"),
+            // Params, emitters, both state buffers, spawn ring and counters
+            entries: reflected::gpu_particles_simulate::GROUP_0,
+        });
+        // Apart from the simulation's, which dispatches from the args it writes
+        let finish_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("GPU Particle Finish Bind Group Layout"),
+            // Params, counters and indirect args
+            entries: reflected::gpu_particles_finish::GROUP_0,
+        });
+        let draw_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("GPU Particle Draw Bind Group Layout"),
+            // This frame's state buffer and the emitters
+            entries: reflected::gpu_particles::GROUP_1,
+        });
+
+        let compute_pipeline = |label: &str, layout: &wgpu::BindGroupLayout, entry_point: &str| {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            });
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module: &simulate_shader,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+        let simulate_pipeline = compute_pipeline(
+            "GPU Particle Simulate Pipeline",
+            &simulate_layout,
+            "cs_simulate",
+        );
+        let spawn_pipeline =
+            compute_pipeline("GPU Particle Spawn Pipeline", &simulate_layout, "cs_spawn");
+        let finish_pipeline =
+            compute_pipeline("GPU Particle Finish Pipeline", &finish_layout, "cs_finish");
+
+        let draw_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("GPU Particle Pipeline Layout"),
+            bind_group_layouts: &[global_layout, &draw_layout],
+            push_constant_ranges: &[],
+        });
+        let draw_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            cache: None,
+            label: Some("GPU Particle Pipeline"),
+            layout: Some(&draw_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &draw_shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &draw_shader,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: ctx.format,
+                    // Added to the scene, in any order; the scene's alpha is kept
+                    blend: Some(wgpu::BlendState {
+                        color: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::One,
+                            dst_factor: wgpu::BlendFactor::One,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                        alpha: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::Zero,
+                            dst_factor: wgpu::BlendFactor::One,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                    }),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None, // Billboards, both windings are fine
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: TextureHelper::DEPTH_FORMAT,
+                depth_write_enabled: false, // Hidden by opaque geometry, but never hide each other
+                depth_compare: ctx.depth_mode.compare(),
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            simulate_pipeline,
+            spawn_pipeline,
+            finish_pipeline,
+            draw_pipeline,
+            simulate_layout,
+            finish_layout,
+            draw_layout,
+            capacity,
+            buffers: None,
+            slots: HashMap::new(),
+            seen: HashSet::new(),
+            free_slots: (0..MAX_GPU_EMITTERS as u32).rev().collect(),
+            retired: Vec::new(),
+            // Flipped before the first frame
+            source: 1,
+            spawn_head: 0,
+            spawn_count: 0,
+            active: false,
+        }
+    }
+
+    /// Most particles alive at once.
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Slot of `entity`'s emitter for this frame, the same as long as the emitter is
+    /// reported every frame. None when MAX_GPU_EMITTERS are in use.
+    pub fn emitter_slot(&mut self, entity: Entity) -> Option<u32> {
+        self.seen.insert(entity);
+        if let Some(&slot) = self.slots.get(&entity) {
+            return Some(slot);
+        }
+        let slot = self.free_slots.pop()?;
+        self.slots.insert(entity, slot);
+        Some(slot)
+    }
+
+    /// Uploads this frame's emitters, as (slot, emitter), and spawn requests, and swaps
+    /// the state buffers. Slots not asked for since the last call are emptied.
+    pub fn prepare(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        emitters: &[(u32, GpuParticleEmitter)],
+        spawns: &[GpuParticleSpawn],
+        dt: f32,
+    ) {
+        self.free_slots.append(&mut self.retired);
+        let seen = std::mem::take(&mut self.seen);
+        let retired = &mut self.retired;
+        self.slots.retain(|entity, slot| {
+            let keep = seen.contains(entity);
+            if !keep {
+                retired.push(*slot);
+            }
+            keep
+        });
+
+        // Nothing alive or dying: the passes are skipped
+        self.active = !self.slots.is_empty() || !self.retired.is_empty();
+        self.spawn_count = 0;
+        if !self.active {
+            return;
+        }
+
+        let buffers = self.buffers.get_or_insert_with(|| {
+            Self::create_buffers(
+                device,
+                &self.simulate_layout,
+                &self.finish_layout,
+                &self.draw_layout,
+                self.capacity,
+            )
+        });
+
+        let mut table = vec![GpuParticleEmitter::zeroed(); MAX_GPU_EMITTERS];
+        for (slot, emitter) in emitters {
+            table[*slot as usize] = *emitter;
+        }
+        queue.write_buffer(&buffers.emitters, 0, bytemuck::cast_slice(&table));
+
+        // From the head on, wrapping at the end of the ring
+        let count = spawns.len().min(SPAWN_RING_CAPACITY);
+        let head = self.spawn_head as usize;
+        let before_end = count.min(SPAWN_RING_CAPACITY - head);
+        let spawn_size = std::mem::size_of::<GpuParticleSpawn>();
+        queue.write_buffer(
+            &buffers.spawns,
+            (head * spawn_size) as wgpu::BufferAddress,
+            bytemuck::cast_slice(&spawns[..before_end]),
+        );
+        if count > before_end {
+            queue.write_buffer(
+                &buffers.spawns,
+                0,
+                bytemuck::cast_slice(&spawns[before_end..count]),
+            );
+        }
+
+        self.source = 1 - self.source;
+        self.spawn_count = count as u32;
+        queue.write_buffer(
+            &buffers.params,
+            0,
+            bytemuck::bytes_of(&GpuParticleParams {
+                dt,
+                source: self.source,
+                capacity: self.capacity,
+                spawn_start: self.spawn_head,
+                spawn_count: self.spawn_count,
+                spawn_capacity: SPAWN_RING_CAPACITY as u32,
+                _padding0: 0,
+                _padding1: 0,
+            }),
+        );
+        self.spawn_head = ((head + count) % SPAWN_RING_CAPACITY) as u32;
+    }
+
+    /// True when this frame has GPU particles to simulate and draw. Without, neither
+    /// pass is added.
+    pub fn is_active(&self) -> bool {
+        self.active && self.buffers.is_some()
+    }
+
+    /// GPU tier emitters this frame.
+    pub fn emitter_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Particles spawned this frame.
+    pub fn spawn_count(&self) -> u32 {
+        self.spawn_count
+    }
+
+    /// Steps the particles, adds this frame's spawns and writes the indirect arguments.
+    pub fn dispatch(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        timestamp_writes: Option<wgpu::ComputePassTimestampWrites>,
+    ) {
+        let Some(buffers) = &self.buffers else {
+            return;
+        };
+        let current = 1 - self.source;
+        let counter_size = std::mem::size_of::<u32>() as wgpu::BufferAddress;
+        encoder.clear_buffer(
+            &buffers.counters,
+            current as wgpu::BufferAddress * counter_size,
+            Some(counter_size),
+        );
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("GPU Particle Simulation"),
+            timestamp_writes,
+        });
+        pass.set_bind_group(0, &buffers.simulate_bind_groups[self.source as usize], &[]);
+        // As many workgroups as last frame left particles
+        pass.set_pipeline(&self.simulate_pipeline);
+        pass.dispatch_workgroups_indirect(&buffers.args, DISPATCH_ARGS_OFFSET);
+        if self.spawn_count > 0 {
+            pass.set_pipeline(&self.spawn_pipeline);
+            pass.dispatch_workgroups(self.spawn_count.div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+        pass.set_pipeline(&self.finish_pipeline);
+        pass.set_bind_group(0, &buffers.finish_bind_group, &[]);
+        pass.dispatch_workgroups(1, 1, 1);
+    }
+
+    /// Draws the particles `dispatch` left, for the view whose camera is in `global_bind_group`.
+    pub fn record<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        global_bind_group: &'a wgpu::BindGroup,
+    ) {
+        let Some(buffers) = &self.buffers else {
+            return;
+        };
+        render_pass.set_pipeline(&self.draw_pipeline);
+        render_pass.set_bind_group(0, global_bind_group, &[]);
+        render_pass.set_bind_group(1, &buffers.draw_bind_groups[self.source as usize], &[]);
+        render_pass.draw_indirect(&buffers.args, DRAW_ARGS_OFFSET);
+    }
+
+    fn create_buffers(
+        device: &Device,
+        simulate_layout: &wgpu::BindGroupLayout,
+        finish_layout: &wgpu::BindGroupLayout,
+        draw_layout: &wgpu::BindGroupLayout,
+        capacity: u32,
+    ) -> GpuParticleBuffers {
+        let buffer = |label: &str, size: usize, usage: wgpu::BufferUsages| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: size as wgpu::BufferAddress,
+                usage,
+                mapped_at_creation: false,
+            })
+        };
+        let state_size = capacity as usize * std::mem::size_of::<GpuParticle>();
+        let states = [
+            buffer(
+                "GPU Particle State A",
+                state_size,
+                wgpu::BufferUsages::STORAGE,
+            ),
+            buffer(
+                "GPU Particle State B",
+                state_size,
+                wgpu::BufferUsages::STORAGE,
+            ),
+        ];
+        let params = buffer(
+            "GPU Particle Params",
+            std::mem::size_of::<GpuParticleParams>(),
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        );
+        let emitters = buffer(
+            "GPU Particle Emitters",
+            MAX_GPU_EMITTERS * std::mem::size_of::<GpuParticleEmitter>(),
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        );
+        let spawns = buffer(
+            "GPU Particle Spawn Ring",
+            SPAWN_RING_CAPACITY * std::mem::size_of::<GpuParticleSpawn>(),
+            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        );
+        // Zeroed: no particles, and a first dispatch of no workgroups
+        let counters = buffer(
+            "GPU Particle Counters",
+            2 * std::mem::size_of::<u32>(),
+            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        );
+        let args = buffer(
+            "GPU Particle Indirect Args",
+            ARGS_SIZE as usize,
+            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::INDIRECT,
+        );
+
+        let entry = |binding: u32, buffer: &wgpu::Buffer| wgpu::BindGroupEntry {
+            binding,
+            resource: buffer.as_entire_binding(),
+        };
+        let simulate_bind_group = |source: usize| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("GPU Particle Simulation Bind Group"),
+                layout: simulate_layout,
+                entries: &[
+                    entry(reflected::gpu_particles_simulate::PARAMS, &params),
+                    entry(reflected::gpu_particles_simulate::EMITTERS, &emitters),
+                    entry(reflected::gpu_particles_simulate::PREVIOUS, &states[source]),
+                    entry(
+                        reflected::gpu_particles_simulate::CURRENT,
+                        &states[1 - source],
+                    ),
+                    entry(reflected::gpu_particles_simulate::SPAWNS, &spawns),
+                    entry(reflected::gpu_particles_simulate::COUNTERS, &counters),
+                ],
+            })
+        };
+        let draw_bind_group = |source: usize| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("GPU Particle Draw Bind Group"),
+                layout: draw_layout,
+                entries: &[
+                    entry(reflected::gpu_particles::PARTICLES, &states[1 - source]),
+                    entry(reflected::gpu_particles::EMITTERS, &emitters),
+                ],
+            })
+        };
+        let finish_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("GPU Particle Finish Bind Group"),
+            layout: finish_layout,
+            entries: &[
+                entry(reflected::gpu_particles_finish::PARAMS, &params),
+                entry(reflected::gpu_particles_finish::COUNTERS, &counters),
+                entry(reflected::gpu_particles_finish::ARGS, &args),
+            ],
+        });
+
+        GpuParticleBuffers {
+            simulate_bind_groups: [simulate_bind_group(0), simulate_bind_group(1)],
+            draw_bind_groups: [draw_bind_group(0), draw_bind_group(1)],
+            finish_bind_group,
+            params,
+            emitters,
+            spawns,
+            counters,
+            args,
+        }
+    }
+}
@@ -0,0 +1,84 @@
+#include "common/camera.wgsl"
+
+// Draws the GPU tier particles gpu_particles_simulate.wgsl left in this frame's state
+// buffer, one instance each, with the instance count it wrote. Additive: they add
+// light, so they need no sorting and no texture.
+
+struct GpuParticle {
+    position: vec3<f32>,
+    age: f32,
+    velocity: vec3<f32>,
+    lifetime: f32,
+    emitter: u32, // Slot in `emitters`
+    padding0: u32,
+    padding1: u32,
+    padding2: u32,
+};
+
+struct GpuParticleEmitter {
+    start_color: vec4<f32>,
+    end_color: vec4<f32>,
+    gravity: vec3<f32>,
+    drag: f32,
+    start_size: f32,
+    end_size: f32,
+    ground_height: f32,
+    restitution: f32,
+    flags: u32, // EMITTER_*
+    padding0: u32,
+    padding1: u32,
+    padding2: u32,
+};
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+@group(1) @binding(0) var<storage, read> particles: array<GpuParticle>;
+@group(1) @binding(1) var<uniform> emitters: array<GpuParticleEmitter, 256>;
+
+struct VSOut {
+    @builtin(position) clip_pos: vec4<f32>,
+    @location(0) offset: vec2<f32>, // -1..1 across the quad
+    @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> VSOut {
+    // Two triangles, no vertex buffer
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-0.5, -0.5),
+        vec2<f32>(0.5, -0.5),
+        vec2<f32>(0.5, 0.5),
+        vec2<f32>(-0.5, -0.5),
+        vec2<f32>(0.5, 0.5),
+        vec2<f32>(-0.5, 0.5),
+    );
+    let corner = corners[vertex_index];
+
+    let particle = particles[instance_index];
+    let emitter = emitters[particle.emitter];
+    let t = clamp(particle.age / particle.lifetime, 0.0, 1.0);
+    let size = mix(emitter.start_size, emitter.end_size, t);
+
+    // The first two rows of view_proj are the camera right/up axes scaled by the projection
+    let vp = camera.unjittered_view_proj;
+    let right = normalize(vec3<f32>(vp[0][0], vp[1][0], vp[2][0]));
+    let up = normalize(vec3<f32>(vp[0][1], vp[1][1], vp[2][1]));
+
+    let world_pos = vec4<f32>(particle.position + (right * corner.x + up * corner.y) * size, 1.0);
+
+    var out: VSOut;
+    out.clip_pos = camera.view_proj * world_pos;
+    out.offset = corner * 2.0;
+    out.color = mix(emitter.start_color, emitter.end_color, t);
+    return out;
+}
+
+@fragment
+fn fs_main(input: VSOut) -> @location(0) vec4<f32> {
+    // A soft round spot; alpha scales the light added rather than blending
+    let falloff = saturate(1.0 - dot(input.offset, input.offset));
+    return vec4<f32>(input.color.rgb * input.color.a * falloff * falloff, 0.0);
+}
@@ -0,0 +1,157 @@
+// ========================================================================
+//  GPU PARTICLE SIMULATION
+//  Every GPU tier particle lives in one of two state buffers, swapped each
+//  frame. cs_simulate steps last frame's particles and appends the living
+//  ones to this frame's buffer, cs_spawn appends the frame's spawn requests
+//  from the ring, and cs_finish turns the count into this frame's indirect
+//  draw and next frame's indirect dispatch. The count never leaves the GPU.
+// ========================================================================
+
+struct GpuParticle {
+    position: vec3<f32>,
+    age: f32,
+    velocity: vec3<f32>,
+    lifetime: f32,
+    emitter: u32, // Slot in `emitters`
+    padding0: u32,
+    padding1: u32,
+    padding2: u32,
+};
+
+struct GpuParticleEmitter {
+    start_color: vec4<f32>,
+    end_color: vec4<f32>,
+    gravity: vec3<f32>,
+    drag: f32,
+    start_size: f32,
+    end_size: f32,
+    ground_height: f32,
+    restitution: f32,
+    flags: u32, // EMITTER_*
+    padding0: u32,
+    padding1: u32,
+    padding2: u32,
+};
+
+struct GpuParticleSpawn {
+    position: vec3<f32>,
+    emitter: u32,
+    velocity: vec3<f32>,
+    lifetime: f32,
+};
+
+struct GpuParticleParams {
+    dt: f32,
+    source: u32, // Which state buffer holds last frame's particles
+    capacity: u32,
+    spawn_start: u32, // First request in the ring, wrapping at spawn_capacity
+    spawn_count: u32,
+    spawn_capacity: u32,
+    padding0: u32,
+    padding1: u32,
+};
+
+// Particles in each state buffer
+struct GpuParticleCounters {
+    alive: array<atomic<u32>, 2>,
+};
+
+// A DrawIndirectArgs, then a DispatchIndirectArgs
+struct GpuParticleArgs {
+    vertex_count: u32,
+    instance_count: u32,
+    first_vertex: u32,
+    first_instance: u32,
+    dispatch_x: u32,
+    dispatch_y: u32,
+    dispatch_z: u32,
+    padding: u32,
+};
+
+@group(0) @binding(0) var<uniform> params: GpuParticleParams;
+@group(0) @binding(1) var<uniform> emitters: array<GpuParticleEmitter, 256>;
+@group(0) @binding(2) var<storage, read> previous: array<GpuParticle>;
+@group(0) @binding(3) var<storage, read_write> current: array<GpuParticle>;
+@group(0) @binding(4) var<storage, read> spawns: array<GpuParticleSpawn>;
+@group(0) @binding(5) var<storage, read_write> counters: GpuParticleCounters;
+// cs_finish only: the simulation's indirect dispatch reads it
+@group(0) @binding(6) var<storage, read_write> args: GpuParticleArgs;
+
+const WORKGROUP_SIZE: u32 = 64u;
+
+const EMITTER_ACTIVE: u32 = 1u;
+const EMITTER_GROUND: u32 = 2u;
+const EMITTER_PAUSED: u32 = 4u;
+
+// Compacts: each living particle takes the next free index. Past the capacity
+// it is dropped.
+fn append(particle: GpuParticle) {
+    let index = atomicAdd(&counters.alive[1u - params.source], 1u);
+    if (index < params.capacity) {
+        current[index] = particle;
+    }
+}
+
+@compute @workgroup_size(64, 1, 1)
+fn cs_simulate(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= atomicLoad(&counters.alive[params.source])) {
+        return;
+    }
+    var particle = previous[id.x];
+    let emitter = emitters[particle.emitter];
+    // The emitter is gone, and its particles with it
+    if ((emitter.flags & EMITTER_ACTIVE) == 0u) {
+        return;
+    }
+    // Not visible: held where they are, like the CPU tier
+    var dt = params.dt;
+    if ((emitter.flags & EMITTER_PAUSED) != 0u) {
+        dt = 0.0;
+    }
+
+    particle.age += dt;
+    if (particle.age >= particle.lifetime) {
+        return;
+    }
+    particle.velocity += emitter.gravity * dt;
+    // Exact for drag proportional to speed, stable at any frame time
+    particle.velocity *= exp(-emitter.drag * dt);
+    particle.position += particle.velocity * dt;
+
+    if ((emitter.flags & EMITTER_GROUND) != 0u && particle.position.y < emitter.ground_height) {
+        particle.position.y = emitter.ground_height;
+        particle.velocity.y = abs(particle.velocity.y) * emitter.restitution;
+    }
+    append(particle);
+}
+
+@compute @workgroup_size(64, 1, 1)
+fn cs_spawn(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.spawn_count) {
+        return;
+    }
+    let spawn = spawns[(params.spawn_start + id.x) % params.spawn_capacity];
+    var particle: GpuParticle;
+    particle.position = spawn.position;
+    particle.age = 0.0;
+    particle.velocity = spawn.velocity;
+    particle.lifetime = spawn.lifetime;
+    particle.emitter = spawn.emitter;
+    append(particle);
+}
+
+@compute @workgroup_size(1, 1, 1)
+fn cs_finish() {
+    let index = 1u - params.source;
+    let alive = min(atomicLoad(&counters.alive[index]), params.capacity);
+    atomicStore(&counters.alive[index], alive);
+
+    // Six vertices per particle, a camera-facing quad
+    args.vertex_count = 6u;
+    args.instance_count = alive;
+    args.first_vertex = 0u;
+    args.first_instance = 0u;
+    args.dispatch_x = (alive + WORKGROUP_SIZE - 1u) / WORKGROUP_SIZE;
+    args.dispatch_y = 1u;
+    args.dispatch_z = 1u;
+}
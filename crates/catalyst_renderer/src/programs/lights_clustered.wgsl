@@ -0,0 +1,32 @@
+// ========================================================================
+//  POINT AND SPOT LIGHTS: CLUSTERED (composed with shader.wgsl)
+// ========================================================================
+
+#include "common/clusters.wgsl"
+#include "common/lights.wgsl"
+
+@group(0) @binding(2) var<uniform> clusters: ClusterUniform;
+@group(0) @binding(3) var<storage, read> lights: array<Light>;
+@group(0) @binding(4) var<storage, read> cluster_ranges: array<vec2<u32>>; // offset, count
+@group(0) @binding(5) var<storage, read> light_indices: array<u32>;
+
+// Only the lights the CPU assigned to this fragment's cluster
+fn lights_radiance(
+    frag_coord: vec2<f32>,
+    world_pos: vec3<f32>,
+    N: vec3<f32>,
+    V: vec3<f32>,
+    albedo: vec3<f32>,
+    roughness: f32,
+    metallic: f32,
+    F0: vec3<f32>,
+) -> vec3<f32> {
+    let range = cluster_ranges[cluster_index(clusters, frag_coord, world_pos)];
+
+    var Lo = vec3<f32>(0.0);
+    for (var i = 0u; i < range.y; i++) {
+        let light = lights[light_indices[range.x + i]];
+        Lo += light_radiance(light, world_pos, N, V, albedo, roughness, metallic, F0);
+    }
+    return Lo;
+}
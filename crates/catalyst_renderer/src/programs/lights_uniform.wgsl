@@ -0,0 +1,24 @@
+// ========================================================================
+//  POINT AND SPOT LIGHTS: UNIFORM (composed with shader.wgsl)
+// ========================================================================
+
+#include "common/lights.wgsl"
+
+// Fallback for adapters without storage buffers in fragment shaders:
+// only the first 4 lights, straight from the light uniform.
+fn lights_radiance(
+    frag_coord: vec2<f32>,
+    world_pos: vec3<f32>,
+    N: vec3<f32>,
+    V: vec3<f32>,
+    albedo: vec3<f32>,
+    roughness: f32,
+    metallic: f32,
+    F0: vec3<f32>,
+) -> vec3<f32> {
+    var Lo = vec3<f32>(0.0);
+    for (var i = 0u; i < scene_data.active_lights; i++) {
+        Lo += light_radiance(scene_data.lights[i], world_pos, N, V, albedo, roughness, metallic, F0);
+    }
+    return Lo;
+}
@@ -6,25 +6,18 @@ struct VSIn {
 struct VSOut {
     @builtin(position) clip_pos: vec4<f32>,
     @location(0) color: vec4<f32>,
+    @location(1) curr_clip: vec4<f32>,
+    @location(2) prev_clip: vec4<f32>,
 };
 
-// --- LIGHTING ---
-struct PointLight {
-    position: vec4<f32>, // .xyz = position, .w = intensity
-    color: vec4<f32>,    // .xyz = color,    .w = radius (or unused)
-};
-
-struct LightUniforms {
-    sun_direction: vec4<f32>, // .xyz = direction, .w = intensity
-    sun_color: vec4<f32>,     // .xyz = color,     .w = padding
-    lights: array<PointLight, 4>,
-    camera_pos: vec3<f32>,
-    active_lights: u32,       // How many point lights to loop over
+struct FSOut {
+    @location(0) color: vec4<f32>,
+    @location(1) velocity: vec2<f32>,
 };
 
-struct Camera {
-    view_proj: mat4x4<f32>,
-};
+#include "common/camera.wgsl"
+#include "common/lights.wgsl"
+#include "common/motion.wgsl"
 
 @group(0) @binding(0) var<uniform> camera: Camera;
 @group(0) @binding(1) var<uniform> scene_data: LightUniforms;
@@ -32,12 +25,19 @@ struct Camera {
 @vertex
 fn vs_main(input: VSIn) -> VSOut {
     var out: VSOut;
-    out.clip_pos = camera.view_proj * vec4<f32>(input.position, 1.0);
+    let world_pos = vec4<f32>(input.position, 1.0);
+    out.clip_pos = camera.view_proj * world_pos;
     out.color = input.color;
+    // Lines are rebuilt every frame in world space, so only the camera moves them
+    out.curr_clip = camera.unjittered_view_proj * world_pos;
+    out.prev_clip = camera.prev_view_proj * world_pos;
     return out;
 }
 
 @fragment
-fn fs_main(input: VSOut) -> @location(0) vec4<f32> {
-    return input.color;
+fn fs_main(input: VSOut) -> FSOut {
+    var out: FSOut;
+    out.color = input.color;
+    out.velocity = motionVector(input.curr_clip, input.prev_clip);
+    return out;
 }
@@ -0,0 +1,57 @@
+// ========================================================================
+//  AVERAGE LUMINANCE
+//  One workgroup per view: averages the log2 luminance written by the main
+//  pass over a grid of samples inside the view's viewport. The result is
+//  read back on the CPU, where auto exposure adapts toward it.
+// ========================================================================
+
+struct LuminanceViews {
+    rects: array<vec4<u32>, 4>, // x, y, width, height in pixels
+    count: u32,
+    padding0: u32,
+    padding1: vec2<u32>,
+};
+
+@group(0) @binding(0) var t_luminance: texture_2d<f32>; // log2 luminance, coverage
+@group(0) @binding(1) var<uniform> views: LuminanceViews;
+@group(0) @binding(2) var<storage, read_write> results: array<vec2<f32>, 4>; // average log2 luminance, coverage
+
+const GROUP_SIZE: u32 = 16u;
+const SAMPLES: u32 = 64u; // Per side and view; every pixel would cost far more for the same average
+
+var<workgroup> sums: array<vec2<f32>, 256>;
+
+@compute @workgroup_size(16, 16, 1)
+fn cs_main(
+    @builtin(workgroup_id) group: vec3<u32>,
+    @builtin(local_invocation_id) local: vec3<u32>,
+    @builtin(local_invocation_index) index: u32,
+) {
+    let view = group.x;
+    let rect = views.rects[view];
+
+    var sum = vec2<f32>(0.0);
+    for (var y = local.y; y < SAMPLES; y += GROUP_SIZE) {
+        for (var x = local.x; x < SAMPLES; x += GROUP_SIZE) {
+            let offset = (vec2<f32>(f32(x), f32(y)) + 0.5) / f32(SAMPLES) * vec2<f32>(rect.zw);
+            let sample = textureLoad(t_luminance, rect.xy + vec2<u32>(offset), 0).xy;
+            // Pixels without lit geometry have zero coverage and drop out
+            sum += vec2<f32>(sample.x * sample.y, sample.y);
+        }
+    }
+
+    sums[index] = sum;
+    workgroupBarrier();
+
+    for (var stride = 128u; stride > 0u; stride >>= 1u) {
+        if (index < stride) {
+            sums[index] += sums[index + stride];
+        }
+        workgroupBarrier();
+    }
+
+    if (index == 0u) {
+        let total = sums[0];
+        results[view] = vec2<f32>(total.x / max(total.y, 1.0), total.y / f32(SAMPLES * SAMPLES));
+    }
+}
@@ -0,0 +1,63 @@
+use wgpu::ComputePipeline;
+
+use crate::{
+    exposure::LuminanceViewsUniform,
+    layout::{uniform_layouts, validate_program_layouts},
+    programs::reflected::{self, WGSL_LUMINANCE_VIEWS},
+};
+
+uniform_layouts!(LuminanceViewsUniform::LAYOUT => WGSL_LUMINANCE_VIEWS);
+
+/// Compute pass averaging the luminance buffer per view, for auto exposure.
+/// Not a GpuProgram: it runs outside of any render pass.
+pub struct LuminanceProgram {
+    pipeline: ComputePipeline,
+    pub layout: wgpu::BindGroupLayout,
+}
+
+impl LuminanceProgram {
+    pub fn new(device: &wgpu::Device) -> Self {
+        validate_program_layouts("LuminanceProgram", UNIFORM_LAYOUTS);
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("luminance.wgsl"));
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Luminance Bind Group Layout"),
+            // Luminance buffer, view rectangles and results
+            entries: reflected::luminance::GROUP_0,
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Luminance Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Luminance Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("cs_main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        Self { pipeline, layout }
+    }
+
+    /// One workgroup per view.
+    pub fn dispatch(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        bind_group: &wgpu::BindGroup,
+        view_count: u32,
+    ) {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Luminance Pass"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, bind_group, &[]);
+        pass.dispatch_workgroups(view_count, 1, 1);
+    }
+}
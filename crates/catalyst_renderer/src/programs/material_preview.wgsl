@@ -0,0 +1,157 @@
+// ========================================================================
+//  MATERIAL PREVIEW
+//  A sphere ray traced in a fullscreen triangle, shaded with one material
+//  under a fixed key / fill / ambient rig. Used by the material editor, so
+//  the material looks the same whatever the scene's lighting is.
+// ========================================================================
+
+const PI = 3.14159265359;
+
+const PREVIEW_UNLIT: u32 = 1u;
+const PREVIEW_ALPHA_MASK: u32 = 2u;
+
+struct PreviewUniforms {
+    rotation: f32, // Turntable angle around +Y, radians
+    flags: u32,    // PREVIEW_UNLIT | PREVIEW_ALPHA_MASK
+    padding: vec2<f32>,
+};
+
+struct MaterialUniforms {
+    base_color: vec4<f32>,
+    roughness: f32,
+    metallic: f32,
+    alpha_cutoff: f32,
+    padding: f32,
+    emissive: vec4<f32>, // rgb already scaled by the emissive strength
+};
+
+@group(0) @binding(0) var<uniform> preview: PreviewUniforms;
+
+// Same layout as the PBR material group, so GpuMaterial bind groups are used as-is
+@group(1) @binding(0) var<uniform> material: MaterialUniforms;
+@group(1) @binding(1) var t_diffuse: texture_2d<f32>;
+@group(1) @binding(2) var s_diffuse: sampler;
+@group(1) @binding(3) var t_metallic_roughness: texture_2d<f32>;
+@group(1) @binding(4) var s_metallic_roughness: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// Fullscreen triangle, no vertex buffer
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+fn distribution_ggx(NdotH: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let denom = NdotH * NdotH * (a2 - 1.0) + 1.0;
+    return a2 / (PI * denom * denom);
+}
+
+fn geometry_schlick_ggx(NdotX: f32, roughness: f32) -> f32 {
+    let r = roughness + 1.0;
+    let k = (r * r) / 8.0;
+    return NdotX / (NdotX * (1.0 - k) + k);
+}
+
+fn fresnel_schlick(cos_theta: f32, F0: vec3<f32>) -> vec3<f32> {
+    return F0 + (vec3<f32>(1.0) - F0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// Cook-Torrance contribution of a directional light coming from `L`
+fn directional(
+    L: vec3<f32>,
+    radiance: vec3<f32>,
+    N: vec3<f32>,
+    V: vec3<f32>,
+    albedo: vec3<f32>,
+    roughness: f32,
+    metallic: f32,
+    F0: vec3<f32>,
+) -> vec3<f32> {
+    let H = normalize(V + L);
+    let NdotL = max(dot(N, L), 0.0);
+    let NdotV = max(dot(N, V), 0.0);
+
+    let NDF = distribution_ggx(max(dot(N, H), 0.0), roughness);
+    let G = geometry_schlick_ggx(NdotV, roughness) * geometry_schlick_ggx(NdotL, roughness);
+    let F = fresnel_schlick(max(dot(H, V), 0.0), F0);
+    let specular = NDF * G * F / (4.0 * NdotV * NdotL + 0.0001);
+
+    let kD = (vec3<f32>(1.0) - F) * (1.0 - metallic);
+    return (kD * albedo / PI + specular) * radiance * NdotL;
+}
+
+fn background(uv: vec2<f32>) -> vec3<f32> {
+    // Dark checkerboard, so masked out and dark materials still read
+    let cell = vec2<i32>(floor(uv * 16.0));
+    let checker = f32((cell.x + cell.y) & 1);
+    return vec3<f32>(mix(0.02, 0.04, checker));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Camera at +Z looking at a unit sphere at the origin
+    let ndc = in.uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0);
+    let origin = vec3<f32>(0.0, 0.0, 3.2);
+    let dir = normalize(vec3<f32>(ndc * 0.4, -1.0));
+
+    let b = dot(origin, dir);
+    let c = dot(origin, origin) - 1.0;
+    let h = b * b - c;
+    if (h < 0.0) {
+        return vec4<f32>(background(in.uv), 1.0);
+    }
+    let position = origin + dir * (-b - sqrt(h));
+    let N = normalize(position);
+    let V = -dir;
+
+    // Equirectangular UVs on the turned sphere
+    let turned = vec3<f32>(
+        N.x * cos(preview.rotation) - N.z * sin(preview.rotation),
+        N.y,
+        N.x * sin(preview.rotation) + N.z * cos(preview.rotation),
+    );
+    let uv = vec2<f32>(atan2(turned.x, turned.z) / (2.0 * PI) + 0.5, acos(clamp(turned.y, -1.0, 1.0)) / PI);
+
+    // Explicit level: the seam of atan2 would make the derivatives blow up
+    let diffuse = textureSampleLevel(t_diffuse, s_diffuse, uv, 0.0);
+    let albedo = diffuse.rgb * material.base_color.rgb;
+    let alpha = diffuse.a * material.base_color.a;
+    if ((preview.flags & PREVIEW_ALPHA_MASK) != 0u && alpha < material.alpha_cutoff) {
+        return vec4<f32>(background(in.uv), 1.0);
+    }
+
+    if ((preview.flags & PREVIEW_UNLIT) != 0u) {
+        return vec4<f32>(albedo + material.emissive.rgb, 1.0);
+    }
+
+    // Packed like the scene's: R = AO, G = roughness, B = metallic
+    let mr = textureSampleLevel(t_metallic_roughness, s_metallic_roughness, uv, 0.0);
+    let roughness = clamp(mr.g * material.roughness, 0.04, 1.0);
+    let metallic = mr.b * material.metallic;
+    let F0 = mix(vec3<f32>(0.04), albedo, metallic);
+
+    // Warm key from the upper left, cool fill from the right, rim from behind
+    var color = directional(normalize(vec3<f32>(-0.6, 0.7, 0.6)), vec3<f32>(3.0, 2.9, 2.7), N, V, albedo, roughness, metallic, F0);
+    color += directional(normalize(vec3<f32>(0.8, 0.1, 0.4)), vec3<f32>(0.6, 0.7, 0.9), N, V, albedo, roughness, metallic, F0);
+    color += directional(normalize(vec3<f32>(0.2, 0.4, -1.0)), vec3<f32>(1.5), N, V, albedo, roughness, metallic, F0);
+
+    // Sky / ground hemisphere instead of the scene's flat ambient
+    let sky = mix(vec3<f32>(0.06, 0.05, 0.04), vec3<f32>(0.12, 0.14, 0.18), N.y * 0.5 + 0.5);
+    color += sky * albedo * mr.r;
+    color += material.emissive.rgb;
+
+    // Reinhard like the scene; the sRGB target does the gamma
+    color = color / (color + vec3<f32>(1.0));
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,153 @@
+use wgpu::RenderPipeline;
+
+use crate::{
+    layout::{StructLayout, rust_layout, uniform_layouts, validate_program_layouts},
+    material::MaterialKey,
+    material_preview::PREVIEW_FORMAT,
+    programs::{
+        GpuProgram, GpuProgramRenderContext,
+        reflected::{self, WGSL_PREVIEW_UNIFORMS},
+    },
+};
+
+// PreviewUniform::flags
+const PREVIEW_UNLIT: u32 = 1 << 0;
+const PREVIEW_ALPHA_MASK: u32 = 1 << 1;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PreviewUniform {
+    pub rotation: f32,
+    pub flags: u32,
+    pub _padding: [f32; 2],
+}
+
+const _: () = assert!(std::mem::size_of::<PreviewUniform>() == 16);
+
+impl PreviewUniform {
+    pub const LAYOUT: StructLayout = rust_layout!(PreviewUniform {
+        rotation: f32,
+        flags: u32,
+        _padding: [f32; 2],
+    });
+
+    pub fn new(rotation: f32, key: MaterialKey) -> Self {
+        let mut flags = 0;
+        if key.contains(MaterialKey::UNLIT) {
+            flags |= PREVIEW_UNLIT;
+        }
+        if key.contains(MaterialKey::ALPHA_MASK) {
+            flags |= PREVIEW_ALPHA_MASK;
+        }
+        Self {
+            rotation,
+            flags,
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+uniform_layouts!(PreviewUniform::LAYOUT => WGSL_PREVIEW_UNIFORMS);
+
+/// Fullscreen pass shading a ray traced sphere with one material, for the material
+/// editor's preview. Takes the material bind group of the scene (GpuMaterial), so
+/// the preview shows exactly what the scene binds; normal maps are left out.
+pub struct MaterialPreviewProgram {
+    pipeline: RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+}
+
+impl MaterialPreviewProgram {
+    pub fn write_uniform(&self, queue: &wgpu::Queue, uniform: &PreviewUniform) {
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(uniform));
+    }
+}
+
+impl GpuProgram for MaterialPreviewProgram {
+    /// The PBR material layout.
+    type InitData = wgpu::BindGroupLayout;
+    /// The previewed material's bind group.
+    type DrawData<'a> = &'a wgpu::BindGroup;
+
+    fn new(ctx: &GpuProgramRenderContext, material_layout: &Self::InitData) -> Self {
+        validate_program_layouts("MaterialPreviewProgram", UNIFORM_LAYOUTS);
+
+        let shader = ctx
+            .device
+            .create_shader_module(wgpu::include_wgsl!("material_preview.wgsl"));
+
+        let layout = ctx
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Material Preview Bind Group Layout"),
+                // Preview settings; group 1 is the PBR material layout
+                entries: reflected::material_preview::GROUP_0,
+            });
+
+        let uniform_buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Material Preview Uniform Buffer"),
+            size: std::mem::size_of::<PreviewUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Material Preview Bind Group"),
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: reflected::material_preview::PREVIEW,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Material Preview Pipeline Layout"),
+                bind_group_layouts: &[&layout, material_layout],
+                push_constant_ranges: &[],
+            });
+
+        let pipeline = ctx
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                cache: None,
+                label: Some("Material Preview Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    compilation_options: Default::default(),
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    compilation_options: Default::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: PREVIEW_FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            });
+
+        Self {
+            pipeline,
+            bind_group,
+            uniform_buffer,
+        }
+    }
+
+    fn record<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, material: Self::DrawData<'a>) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_bind_group(1, material, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,39 @@
+// ========================================================================
+//  OCCLUSION PROXIES
+//  The bounding box of an OcclusionCullable entity, depth tested against
+//  the opaque geometry inside an occlusion query. Writes no color and no
+//  depth: only whether any fragment passed matters.
+// ========================================================================
+
+#include "common/camera.wgsl"
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+struct ProxyIn {
+    @location(0) min: vec3<f32>,
+    @location(1) max: vec3<f32>,
+};
+
+// Corner of the box for each vertex of its 12 triangles, as bits: x = 1, y = 2, z = 4
+fn box_corner(index: u32) -> vec3<f32> {
+    var corners = array<u32, 36>(
+        0u, 2u, 3u, 0u, 3u, 1u, // -Z
+        4u, 5u, 7u, 4u, 7u, 6u, // +Z
+        0u, 4u, 6u, 0u, 6u, 2u, // -X
+        1u, 3u, 7u, 1u, 7u, 5u, // +X
+        0u, 1u, 5u, 0u, 5u, 4u, // -Y
+        2u, 6u, 7u, 2u, 7u, 3u, // +Y
+    );
+    let bits = corners[index];
+    return vec3<f32>(f32(bits & 1u), f32((bits >> 1u) & 1u), f32((bits >> 2u) & 1u));
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32, proxy: ProxyIn) -> @builtin(position) vec4<f32> {
+    // Jittered like the geometry it is tested against
+    let position = mix(proxy.min, proxy.max, box_corner(index));
+    return camera.view_proj * vec4<f32>(position, 1.0);
+}
+
+@fragment
+fn fs_main() {}
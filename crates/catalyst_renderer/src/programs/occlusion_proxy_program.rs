@@ -0,0 +1,129 @@
+use wgpu::{RenderPipeline, VertexFormat};
+
+use crate::{
+    global_resources::CameraUniform,
+    layout::{uniform_layouts, validate_program_layouts},
+    occlusion_culling::{OcclusionProxy, OcclusionQueries},
+    programs::{GpuProgram, GpuProgramRenderContext, reflected::WGSL_CAMERA},
+    shaders::create_shader_module,
+    texture::TextureHelper,
+};
+
+// Uniform struct occlusion_proxy.wgsl includes from common/
+uniform_layouts!(CameraUniform::LAYOUT => WGSL_CAMERA);
+
+// 12 triangles, expanded in the vertex shader
+const BOX_VERTICES: u32 = 36;
+
+/// Draws the boxes of the occlusion queries at the end of the main pass, one query per
+/// box and view. See occlusion_culling.rs.
+pub struct OcclusionProxyProgram {
+    pipeline: RenderPipeline,
+}
+
+impl GpuProgram for OcclusionProxyProgram {
+    type InitData = wgpu::BindGroupLayout;
+    /// Global bind group of the view, the queries and the view's index.
+    type DrawData<'a> = (&'a wgpu::BindGroup, &'a OcclusionQueries, usize);
+
+    fn new(ctx: &GpuProgramRenderContext, global_layout: &Self::InitData) -> Self {
+        validate_program_layouts("OcclusionProxyProgram", UNIFORM_LAYOUTS);
+
+        let shader = create_shader_module(
+            ctx.device,
+            "Occlusion Proxy Shader",
+            &["occlusion_proxy.wgsl"],
+        );
+
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Occlusion Proxy Pipeline Layout"),
+                bind_group_layouts: &[global_layout], // same camera bind group
+                push_constant_ranges: &[],
+            });
+
+        // Same targets as the main pass, none of them written
+        let untouched = |format| {
+            Some(wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::empty(),
+            })
+        };
+
+        let pipeline = ctx
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                cache: None,
+                label: Some("Occlusion Proxy Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    compilation_options: Default::default(),
+                    buffers: &[wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<OcclusionProxy>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Instance,
+                        attributes: &[
+                            // min
+                            wgpu::VertexAttribute {
+                                offset: 0,
+                                shader_location: 0,
+                                format: VertexFormat::Float32x3,
+                            },
+                            // max
+                            wgpu::VertexAttribute {
+                                offset: 12,
+                                shader_location: 1,
+                                format: VertexFormat::Float32x3,
+                            },
+                        ],
+                    }],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    compilation_options: Default::default(),
+                    targets: &[
+                        untouched(ctx.format),
+                        untouched(TextureHelper::VELOCITY_FORMAT),
+                        untouched(TextureHelper::LUMINANCE_FORMAT),
+                    ],
+                }),
+                // Both sides: a box the camera is close to may only show its back faces
+                primitive: wgpu::PrimitiveState {
+                    cull_mode: None,
+                    ..Default::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: TextureHelper::DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: ctx.depth_mode.compare_or_equal(),
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            });
+
+        Self { pipeline }
+    }
+
+    fn record<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, data: Self::DrawData<'a>) {
+        let (global_bind_group, queries, view) = data;
+        let Some((count, first_query)) = queries.view_queries(view) else {
+            return;
+        };
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, global_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, queries.proxy_buffer().slice(..));
+
+        for proxy in 0..count {
+            render_pass.begin_occlusion_query(first_query + proxy);
+            render_pass.draw(0..BOX_VERTICES, proxy..proxy + 1);
+            render_pass.end_occlusion_query();
+        }
+    }
+}
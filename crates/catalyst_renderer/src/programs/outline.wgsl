@@ -0,0 +1,93 @@
+// ========================================================================
+//  OUTLINES
+//  Outlined meshes are drawn into a mask as their style index + 1, then a
+//  fullscreen pass dilates the mask into borders over the final image.
+// ========================================================================
+
+#include "common/camera.wgsl"
+#include "common/mesh.wgsl"
+
+struct OutlineUniforms {
+    colors: array<vec4<f32>, 8>,
+    thickness: array<vec4<f32>, 8>, // .x = width in pixels
+    radius: u32,                     // Widest style, in whole pixels
+    padding0: u32,
+    padding1: vec2<u32>,
+};
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(1) @binding(0) var<uniform> mesh: MeshUniform;
+
+// --- MASK ---
+
+struct MaskOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) @interpolate(flat) slot: u32,
+};
+
+@vertex
+fn vs_mask(
+    @location(0) position: vec3<f32>,
+    @builtin(instance_index) slot: u32,
+) -> MaskOutput {
+    var out: MaskOutput;
+    // Unjittered, so the outline doesn't shimmer with TAA
+    out.position = camera.unjittered_view_proj * mesh.model * vec4<f32>(position, 1.0);
+    out.slot = slot;
+    return out;
+}
+
+@fragment
+fn fs_mask(in: MaskOutput) -> @location(0) u32 {
+    return in.slot + 1u;
+}
+
+// --- COMPOSITE ---
+
+@group(0) @binding(0) var t_mask: texture_2d<u32>;
+@group(0) @binding(1) var<uniform> outline: OutlineUniforms;
+
+// Fullscreen triangle, no vertex buffer
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+}
+
+@fragment
+fn fs_composite(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let size = vec2<i32>(textureDimensions(t_mask));
+    let pixel = vec2<i32>(position.xy);
+
+    // Inside an outlined mesh: the border only goes around it
+    if (textureLoad(t_mask, pixel, 0).r != 0u) {
+        discard;
+    }
+
+    // Nearest outlined pixel within its style's width
+    let radius = i32(outline.radius);
+    var best_distance = 1e9;
+    var best_slot = 0u;
+    for (var y = -radius; y <= radius; y++) {
+        for (var x = -radius; x <= radius; x++) {
+            let coord = pixel + vec2<i32>(x, y);
+            if (any(coord < vec2<i32>(0)) || any(coord >= size)) {
+                continue;
+            }
+            let value = textureLoad(t_mask, coord, 0).r;
+            if (value == 0u) {
+                continue;
+            }
+            let distance = length(vec2<f32>(f32(x), f32(y)));
+            if (distance <= outline.thickness[value - 1u].x && distance < best_distance) {
+                best_distance = distance;
+                best_slot = value;
+            }
+        }
+    }
+
+    if (best_slot == 0u) {
+        discard;
+    }
+    return outline.colors[best_slot - 1u];
+}
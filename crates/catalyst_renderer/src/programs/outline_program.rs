@@ -0,0 +1,211 @@
+use std::ops::Range;
+
+use wgpu::{Queue, RenderPipeline};
+
+use crate::{
+    global_resources::CameraUniform,
+    layout::{uniform_layouts, validate_program_layouts},
+    mesh::{MeshUniform, Vertex},
+    outline::OutlineUniform,
+    programs::{
+        GpuProgram, GpuProgramRenderContext,
+        reflected::{self, WGSL_CAMERA, WGSL_MESH_UNIFORM, WGSL_OUTLINE_UNIFORMS},
+    },
+    shaders::create_shader_module,
+};
+
+uniform_layouts!(
+    CameraUniform::LAYOUT => WGSL_CAMERA,
+    MeshUniform::LAYOUT => WGSL_MESH_UNIFORM,
+    OutlineUniform::LAYOUT => WGSL_OUTLINE_UNIFORMS,
+);
+
+/// One outlined mesh, collected by "Outline Pass" like PbrDraw so the mask can be
+/// recorded on any thread.
+pub struct OutlineDraw {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub index_format: wgpu::IndexFormat,
+    /// The mesh's ranges in its GeometryPool block, see GpuGeometry::draw_range.
+    pub indices: Range<u32>,
+    pub base_vertex: i32,
+    pub instance_bind_group: wgpu::BindGroup,
+    /// Outline style, written into the mask as slot + 1.
+    pub slot: u32,
+}
+
+/// Outlines in two pipelines: the mask draws outlined meshes as their style index,
+/// the composite dilates the mask into borders over the final image.
+pub struct OutlineProgram {
+    mask_pipeline: RenderPipeline,
+    composite_pipeline: RenderPipeline,
+    composite_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+}
+
+impl GpuProgram for OutlineProgram {
+    // Global (camera) and mesh layouts, shared with the PBR program
+    type InitData = (wgpu::BindGroupLayout, wgpu::BindGroupLayout);
+    type DrawData<'a> = (&'a wgpu::BindGroup, &'a [OutlineDraw]);
+
+    fn new(ctx: &GpuProgramRenderContext, init_data: &Self::InitData) -> Self {
+        validate_program_layouts("OutlineProgram", UNIFORM_LAYOUTS);
+        let (global_layout, mesh_layout) = init_data;
+
+        let shader = create_shader_module(ctx.device, "Outline Shader", &["outline.wgsl"]);
+
+        let mask_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Outline Mask Pipeline Layout"),
+                bind_group_layouts: &[global_layout, mesh_layout],
+                push_constant_ranges: &[],
+            });
+
+        // No depth test: the whole silhouette is outlined, also behind other geometry
+        let mask_pipeline = ctx
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                cache: None,
+                label: Some("Outline Mask Pipeline"),
+                layout: Some(&mask_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_mask"),
+                    compilation_options: Default::default(),
+                    buffers: &[Vertex::desc()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_mask"),
+                    compilation_options: Default::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: wgpu::TextureFormat::R8Uint,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                // Both faces, so double-sided and inside-out meshes fill the mask too
+                primitive: wgpu::PrimitiveState {
+                    cull_mode: None,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            });
+
+        let composite_layout =
+            ctx.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Outline Composite Bind Group Layout"),
+                    // Mask and styles
+                    entries: reflected::outline_composite::GROUP_0,
+                });
+
+        let composite_pipeline_layout =
+            ctx.device
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("Outline Composite Pipeline Layout"),
+                    bind_group_layouts: &[&composite_layout],
+                    push_constant_ranges: &[],
+                });
+
+        let composite_pipeline =
+            ctx.device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    cache: None,
+                    label: Some("Outline Composite Pipeline"),
+                    layout: Some(&composite_pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: Some("vs_fullscreen"),
+                        compilation_options: Default::default(),
+                        buffers: &[],
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: Some("fs_composite"),
+                        compilation_options: Default::default(),
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format: ctx.format,
+                            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                            write_mask: wgpu::ColorWrites::COLOR,
+                        })],
+                    }),
+                    primitive: wgpu::PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState::default(),
+                    multiview: None,
+                });
+
+        let uniform_buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Outline Uniform Buffer"),
+            size: std::mem::size_of::<OutlineUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            mask_pipeline,
+            composite_pipeline,
+            composite_layout,
+            uniform_buffer,
+        }
+    }
+
+    fn record<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, data: Self::DrawData<'a>) {
+        let (global_bind_group, draws) = data;
+        if draws.is_empty() {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.mask_pipeline);
+        render_pass.set_bind_group(0, global_bind_group, &[]);
+        for draw in draws {
+            render_pass.set_vertex_buffer(0, draw.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(draw.index_buffer.slice(..), draw.index_format);
+            render_pass.set_bind_group(1, &draw.instance_bind_group, &[]);
+            // The style travels as the instance index
+            render_pass.draw_indexed(draw.indices.clone(), draw.base_vertex, draw.slot..draw.slot + 1);
+        }
+    }
+}
+
+impl OutlineProgram {
+    pub fn write_uniform(&self, queue: &Queue, uniform: &OutlineUniform) {
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(uniform));
+    }
+
+    /// Built per frame: the mask is a transient graph texture.
+    pub fn composite_bind_group(
+        &self,
+        device: &wgpu::Device,
+        mask: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Outline Composite Bind Group"),
+            layout: &self.composite_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(mask),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    pub fn record_composite<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        bind_group: &'a wgpu::BindGroup,
+    ) {
+        render_pass.set_pipeline(&self.composite_pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,295 @@
+use std::{collections::HashMap, ops::Range};
+
+use flecs_ecs::prelude::Entity;
+use wgpu::{Device, Queue, RenderPipeline, VertexFormat};
+
+use crate::{
+    global_resources::CameraUniform,
+    layout::{uniform_layouts, validate_program_layouts},
+    programs::{
+        GpuProgram,
+        reflected::{self, WGSL_CAMERA},
+    },
+    shaders::create_shader_module,
+    texture::TextureHelper,
+};
+
+// Uniform struct particles.wgsl includes from common/
+uniform_layouts!(CameraUniform::LAYOUT => WGSL_CAMERA);
+
+/// Per-instance data of one particle. The quad corners are generated in the shader.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ParticleInstance {
+    pub position: [f32; 3],
+    pub size: f32,
+    pub color: [f32; 4],
+    pub rotation: f32,
+}
+
+impl ParticleInstance {
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<ParticleInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                // position
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: VertexFormat::Float32x3,
+                },
+                // size
+                wgpu::VertexAttribute {
+                    offset: 12,
+                    shader_location: 1,
+                    format: VertexFormat::Float32,
+                },
+                // color
+                wgpu::VertexAttribute {
+                    offset: 16,
+                    shader_location: 2,
+                    format: VertexFormat::Float32x4,
+                },
+                // rotation
+                wgpu::VertexAttribute {
+                    offset: 32,
+                    shader_location: 3,
+                    format: VertexFormat::Float32,
+                },
+            ],
+        }
+    }
+}
+
+/// Instances sharing one texture, drawn with a single call.
+pub struct ParticleBatch {
+    pub texture: wgpu::BindGroup,
+    pub instances: Range<u32>,
+}
+
+// A texture's bind group with the view and sampler it was made of. Texture streaming
+// replaces the view in place, so they are compared rather than watched
+struct CachedBindGroup {
+    view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    bind_group: wgpu::BindGroup,
+    used: bool,
+}
+
+pub struct ParticleProgram {
+    pipeline: RenderPipeline,
+    /// Group 1: the particle texture and its sampler.
+    pub texture_layout: wgpu::BindGroupLayout,
+    buffer: Option<wgpu::Buffer>,
+    capacity: usize,
+    batches: Vec<ParticleBatch>,
+    // By texture entity, None for the default texture
+    texture_bind_groups: HashMap<Option<Entity>, CachedBindGroup>,
+}
+
+impl ParticleProgram {
+    /// Uploads this frame's particles. `instances` is laid out batch by batch.
+    pub fn prepare(
+        &mut self,
+        instances: &[ParticleInstance],
+        batches: Vec<ParticleBatch>,
+        device: &Device,
+        queue: &Queue,
+    ) {
+        self.batches = batches;
+        // Textures no emitter drew with since the last call
+        self.texture_bind_groups
+            .retain(|_, cached| std::mem::take(&mut cached.used));
+        if instances.is_empty() {
+            self.batches.clear();
+            return;
+        }
+
+        match self.buffer {
+            Some(ref buffer) if instances.len() <= self.capacity => {
+                queue.write_buffer(buffer, 0, bytemuck::cast_slice(instances));
+            }
+            _ => {
+                // Grows to at least double, so a steadily rising count doesn't reallocate every frame
+                self.capacity = instances.len().max(self.capacity * 2);
+                let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Particle Instance Buffer"),
+                    size: (self.capacity * std::mem::size_of::<ParticleInstance>())
+                        as wgpu::BufferAddress,
+                    usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                queue.write_buffer(&buffer, 0, bytemuck::cast_slice(instances));
+                self.buffer = Some(buffer);
+            }
+        }
+    }
+
+    /// Particles drawn per view this frame.
+    pub fn instance_count(&self) -> u32 {
+        self.batches
+            .last()
+            .map(|batch| batch.instances.end)
+            .unwrap_or(0)
+    }
+
+    /// Bind group of the texture entity `texture` (None for the default texture), kept
+    /// from frame to frame and rebuilt when its view or sampler changes. Dropped by
+    /// `prepare` once no batch uses it.
+    pub fn texture_bind_group(
+        &mut self,
+        device: &Device,
+        texture: Option<Entity>,
+        view: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        let stale = self
+            .texture_bind_groups
+            .get(&texture)
+            .is_none_or(|cached| cached.view != *view || cached.sampler != *sampler);
+        if stale {
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Particle Texture Bind Group"),
+                layout: &self.texture_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                ],
+            });
+            self.texture_bind_groups.insert(
+                texture,
+                CachedBindGroup {
+                    view: view.clone(),
+                    sampler: sampler.clone(),
+                    bind_group,
+                    used: false,
+                },
+            );
+        }
+
+        let cached = self.texture_bind_groups.get_mut(&texture).unwrap();
+        cached.used = true;
+        cached.bind_group.clone()
+    }
+}
+
+impl GpuProgram for ParticleProgram {
+    type InitData = wgpu::BindGroupLayout;
+
+    type DrawData<'a> = &'a wgpu::BindGroup;
+
+    fn new(ctx: &super::GpuProgramRenderContext, global_layout: &Self::InitData) -> Self {
+        validate_program_layouts("ParticleProgram", UNIFORM_LAYOUTS);
+
+        let shader = create_shader_module(ctx.device, "Particle Shader", &["particles.wgsl"]);
+
+        let texture_layout = ctx
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Particle Texture Layout"),
+                entries: reflected::particles::GROUP_1,
+            });
+
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Particle Pipeline Layout"),
+                bind_group_layouts: &[global_layout, &texture_layout],
+                push_constant_ranges: &[],
+            });
+
+        let pipeline = ctx
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                cache: None,
+                label: Some("Particle Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[ParticleInstance::desc()],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    compilation_options: Default::default(),
+                    targets: &[
+                        Some(wgpu::ColorTargetState {
+                            format: ctx.format,
+                            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        }),
+                        // Motion vectors for TAA
+                        Some(wgpu::ColorTargetState {
+                            format: TextureHelper::VELOCITY_FORMAT,
+                            blend: None,
+                            write_mask: wgpu::ColorWrites::ALL,
+                        }),
+                        // Auto exposure only measures lit geometry
+                        Some(wgpu::ColorTargetState {
+                            format: TextureHelper::LUMINANCE_FORMAT,
+                            blend: None,
+                            write_mask: wgpu::ColorWrites::empty(),
+                        }),
+                    ],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None, // Billboards, both windings are fine
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: TextureHelper::DEPTH_FORMAT,
+                    depth_write_enabled: false, // Hidden by opaque geometry, but never hide each other
+                    depth_compare: ctx.depth_mode.compare(),
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            });
+
+        Self {
+            pipeline,
+            texture_layout,
+            buffer: None,
+            capacity: 0,
+            batches: Vec::new(),
+            texture_bind_groups: HashMap::new(),
+        }
+    }
+
+    fn record<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        global_bind_group: Self::DrawData<'a>,
+    ) {
+        let Some(ref buffer) = self.buffer else {
+            return;
+        };
+        if self.batches.is_empty() {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, global_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, buffer.slice(..));
+
+        for batch in &self.batches {
+            render_pass.set_bind_group(1, &batch.texture, &[]);
+            render_pass.draw(0..6, batch.instances.clone());
+        }
+    }
+}
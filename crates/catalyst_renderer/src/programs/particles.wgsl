@@ -0,0 +1,74 @@
+#include "common/camera.wgsl"
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+@group(1) @binding(0) var particle_texture: texture_2d<f32>;
+@group(1) @binding(1) var particle_sampler: sampler;
+
+// One particle, stepped once per instance
+struct InstanceIn {
+    @location(0) position: vec3<f32>,
+    @location(1) size: f32,
+    @location(2) color: vec4<f32>,
+    @location(3) rotation: f32,
+};
+
+struct VSOut {
+    @builtin(position) clip_pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) curr_clip: vec4<f32>,
+    @location(3) prev_clip: vec4<f32>,
+};
+
+struct FSOut {
+    @location(0) color: vec4<f32>,
+    @location(1) velocity: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32, particle: InstanceIn) -> VSOut {
+    // Two triangles, no vertex buffer
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-0.5, -0.5),
+        vec2<f32>(0.5, -0.5),
+        vec2<f32>(0.5, 0.5),
+        vec2<f32>(-0.5, -0.5),
+        vec2<f32>(0.5, 0.5),
+        vec2<f32>(-0.5, 0.5),
+    );
+    let corner = corners[vertex_index];
+
+    let c = cos(particle.rotation);
+    let s = sin(particle.rotation);
+    let rotated = vec2<f32>(corner.x * c - corner.y * s, corner.x * s + corner.y * c);
+
+    // The first two rows of view_proj are the camera right/up axes scaled by the projection
+    let vp = camera.unjittered_view_proj;
+    let right = normalize(vec3<f32>(vp[0][0], vp[1][0], vp[2][0]));
+    let up = normalize(vec3<f32>(vp[0][1], vp[1][1], vp[2][1]));
+
+    let world_pos = vec4<f32>(
+        particle.position + (right * rotated.x + up * rotated.y) * particle.size,
+        1.0,
+    );
+
+    var out: VSOut;
+    out.clip_pos = camera.view_proj * world_pos;
+    out.uv = vec2<f32>(corner.x + 0.5, 0.5 - corner.y);
+    out.color = particle.color;
+    // Particle motion isn't tracked, only the camera's
+    out.curr_clip = camera.unjittered_view_proj * world_pos;
+    out.prev_clip = camera.prev_view_proj * world_pos;
+    return out;
+}
+
+@fragment
+fn fs_main(input: VSOut) -> FSOut {
+    var out: FSOut;
+    out.color = textureSample(particle_texture, particle_sampler, input.uv) * input.color;
+    let curr_ndc = input.curr_clip.xy / input.curr_clip.w;
+    let prev_ndc = input.prev_clip.xy / input.prev_clip.w;
+    out.velocity = (curr_ndc - prev_ndc) * vec2<f32>(0.5, -0.5);
+    return out;
+}
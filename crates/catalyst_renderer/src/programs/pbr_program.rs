@@ -1,15 +1,143 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use catalyst_assets::{MaterialDefinition, MeshDefinition};
+use catalyst_core::transform::GlobalTransform;
 use flecs_ecs::prelude::*;
+use glam::Vec3;
 use wgpu::RenderPipeline;
 
 use crate::{
-    material::GpuMaterial,
-    mesh::{GpuGeometry, MeshInstance, Vertex},
-    programs::{GpuProgram, GpuProgramRenderContext},
+    clusters::{ClusterUniform, LightingMode},
+    global_resources::{CameraUniform, GpuLight, LightUniforms},
+    layout::{uniform_layouts, validate_program_layouts},
+    material::{GpuMaterial, GpuMaterialUniform, MaterialKey},
+    mesh::{GpuGeometry, MeshInstance, MeshUniform, Vertex},
+    occlusion_culling::OcclusionCulled,
+    programs::{
+        GpuProgram, GpuProgramRenderContext,
+        reflected::{
+            self, WGSL_CAMERA, WGSL_CLUSTER_UNIFORM, WGSL_GOBO_UNIFORM, WGSL_LIGHT,
+            WGSL_LIGHT_UNIFORMS, WGSL_MATERIAL_UNIFORMS, WGSL_MESH_UNIFORM, WGSL_PROBE,
+            WGSL_PROBE_UNIFORM,
+        },
+    },
+    reflection::is_reflected,
+    reflection_probe::{GpuProbe, ProbeUniform},
+    render_order::{RenderBucket, RenderLayers, RenderOrder},
+    shaders::create_shader_module,
+    spot_light::GoboUniform,
     texture::TextureHelper,
 };
 
+// Shader structs are reflected from shader.wgsl and the common/ files it includes
+uniform_layouts!(
+    CameraUniform::LAYOUT => WGSL_CAMERA,
+    LightUniforms::LAYOUT => WGSL_LIGHT_UNIFORMS,
+    GpuLight::LAYOUT => WGSL_LIGHT,
+    GoboUniform::LAYOUT => WGSL_GOBO_UNIFORM,
+    GpuProbe::LAYOUT => WGSL_PROBE,
+    ProbeUniform::LAYOUT => WGSL_PROBE_UNIFORM,
+    ClusterUniform::LAYOUT => WGSL_CLUSTER_UNIFORM,
+    GpuMaterialUniform::LAYOUT => WGSL_MATERIAL_UNIFORMS,
+    MeshUniform::LAYOUT => WGSL_MESH_UNIFORM,
+);
+
+/// Blend and depth state of a pipeline, one per RenderBucket (Overlay split by depth test).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PbrPass {
+    Opaque,
+    Transparent,
+    Overlay,
+    OverlayNoDepth,
+}
+
+impl PbrPass {
+    pub fn from_order(bucket: RenderBucket, depth_test: bool) -> Self {
+        match (bucket, depth_test) {
+            (RenderBucket::Opaque, _) => PbrPass::Opaque,
+            (RenderBucket::Transparent, _) => PbrPass::Transparent,
+            (RenderBucket::Overlay, true) => PbrPass::Overlay,
+            (RenderBucket::Overlay, false) => PbrPass::OverlayNoDepth,
+        }
+    }
+}
+
+/// One pipeline permutation: the shader features of the material and the pass state.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PipelineKey {
+    pub material: MaterialKey,
+    pub pass: PbrPass,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum MissingResource {
+    Material,
+    Geometry,
+}
+
+/// Draws skipped because their material or mesh has no GPU resource: not built yet,
+/// or the asset entity is gone. Every asset is logged once, so broken content is
+/// visible without flooding the log.
+#[derive(Default)]
+struct MissingResources {
+    skipped: AtomicU64,
+    reported: Mutex<HashSet<Entity>>,
+}
+
+impl MissingResources {
+    fn skip(&self, draws: usize, instance: EntityView, asset: Entity, kind: MissingResource) {
+        self.skipped.fetch_add(draws as u64, Ordering::Relaxed);
+
+        if !self.reported.lock().unwrap().insert(asset) {
+            return;
+        }
+
+        let asset_id = match kind {
+            MissingResource::Material => instance.try_get::<&MaterialDefinition>(|d| d.0.id),
+            MissingResource::Geometry => instance.try_get::<&MeshDefinition>(|d| d.0.id),
+        };
+        let world = instance.world();
+        let asset_view = world.entity_from_id(asset);
+        let asset_name = if asset_view.is_alive() {
+            asset_view.name()
+        } else {
+            "<deleted>".to_string()
+        };
+
+        log::warn!(
+            "Skipping draws of '{}' ({:?}): {:?} asset '{}' ({:?}, id {:?}) has no GPU resource",
+            instance.name(),
+            instance.id(),
+            kind,
+            asset_name,
+            asset,
+            asset_id
+        );
+    }
+}
+
+// Asset entities can be deleted while instances still point at them
+fn alive_asset<'w>(world: &'w WorldRef, asset: Entity) -> Option<EntityView<'w>> {
+    let entity = world.entity_from_id(asset);
+    entity.is_alive().then_some(entity)
+}
+
 pub struct PbrProgram {
-    pipeline: RenderPipeline,
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+    depth_compare: wgpu::CompareFunction,
+    pipelines: HashMap<PipelineKey, RenderPipeline>,
+    cache_hits: u64,
+    cache_misses: u64,
+    missing: MissingResources,
     pub material_layout: wgpu::BindGroupLayout,
     pub mesh_layout: wgpu::BindGroupLayout,
 }
@@ -17,106 +145,34 @@ pub struct PbrProgram {
 impl GpuProgram for PbrProgram {
     type InitData = wgpu::BindGroupLayout;
     type DrawData<'a> = (
-        &'a wgpu::BindGroup,         // Global (Camera/Lights) - Group 0
-        &'a Query<&'a MeshInstance>, // The Meshes - Group 1 & 2
+        &'a wgpu::BindGroup, // Global (Camera/Lights) - Group 0
+        &'a [PbrDraw],       // The Meshes - Group 1 & 2, from collect_draws / collect_bucket
     );
 
     fn new(ctx: &GpuProgramRenderContext, global_layout: &Self::InitData) -> Self {
-        let shader = ctx
-            .device
-            .create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
+        validate_program_layouts("PbrProgram", UNIFORM_LAYOUTS);
+
+        // shader.wgsl calls lights_radiance, each lighting mode brings its own
+        let lights = match ctx.lighting_mode {
+            LightingMode::Clustered => "lights_clustered.wgsl",
+            LightingMode::Uniform => "lights_uniform.wgsl",
+        };
+        let shader = create_shader_module(ctx.device, "PBR Shader", &["shader.wgsl", lights]);
 
         let material_bind_group_layout =
             ctx.device
                 .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                     label: Some("Material Bind Group Layout"),
-                    entries: &[
-                        // --- BINDING 0: Material Settings (Uniform Buffer) ---
-                        // The error happened because this was likely set to 'Texture' or missing!
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 0,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
-                            ty: wgpu::BindingType::Buffer {
-                                ty: wgpu::BufferBindingType::Uniform,
-                                has_dynamic_offset: false,
-                                min_binding_size: None,
-                            },
-                            count: None,
-                        },
-                        // --- BINDING 1: Diffuse Texture ---
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 1,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
-                            ty: wgpu::BindingType::Texture {
-                                multisampled: false,
-                                view_dimension: wgpu::TextureViewDimension::D2,
-                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            },
-                            count: None,
-                        },
-                        // --- BINDING 2: Sampler ---
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 2,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
-                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                            count: None,
-                        },
-                        // --- METALLIC-ROUGHNESS MAP ---
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 3,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
-                            ty: wgpu::BindingType::Texture {
-                                multisampled: false,
-                                view_dimension: wgpu::TextureViewDimension::D2,
-                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            },
-                            count: None,
-                        },
-                        // SAMPLER
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 4,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
-                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                            count: None,
-                        },
-                        // --- NORMAL MAP ---
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 5,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
-                            ty: wgpu::BindingType::Texture {
-                                multisampled: false,
-                                view_dimension: wgpu::TextureViewDimension::D2,
-                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            },
-                            count: None,
-                        },
-                        // SAMPLER
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 6,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
-                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                            count: None,
-                        },
-                    ],
+                    // Uniforms, then base color, metallic-roughness and normal maps, each
+                    // with its sampler
+                    entries: reflected::pbr::GROUP_1,
                 });
 
         let mesh_bind_group_layout =
             ctx.device
                 .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                     label: Some("Mesh Bind Group Layout"),
-                    entries: &[
-                        // --- BINDING 0: MVP Matrix ---
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 0,
-                            visibility: wgpu::ShaderStages::VERTEX,
-                            ty: wgpu::BindingType::Buffer {
-                                ty: wgpu::BufferBindingType::Uniform,
-                                has_dynamic_offset: false,
-                                min_binding_size: None,
-                            },
-                            count: None,
-                        },
-                    ],
+                    entries: reflected::pbr::GROUP_2,
                 });
 
         // 2. Create Pipeline Layout
@@ -133,97 +189,526 @@ impl GpuProgram for PbrProgram {
                     push_constant_ranges: &[],
                 });
 
-        // 3. Create the Pipeline
-        let pipeline = ctx
-            .device
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                cache: None,
-                label: Some("Render Pipeline"),
-                layout: Some(&render_pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &shader,
-                    entry_point: Some("vs_main"),
-                    compilation_options: Default::default(),
-                    buffers: &[Vertex::desc()], // <--- Use our Vertex layout!
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: Some("fs_main"),
-                    compilation_options: Default::default(),
-                    targets: &[Some(wgpu::ColorTargetState {
-                        format: ctx.format,
-                        blend: Some(wgpu::BlendState::REPLACE),
-                        write_mask: wgpu::ColorWrites::ALL,
-                    })],
-                }),
-                depth_stencil: Some(wgpu::DepthStencilState {
-                    format: TextureHelper::DEPTH_FORMAT,
-                    depth_write_enabled: true, // Write Z-values
-                    depth_compare: wgpu::CompareFunction::Less, // Closer pixels win
-                    stencil: wgpu::StencilState::default(),
-                    bias: wgpu::DepthBiasState::default(),
-                }),
-                primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::TriangleList,
-                    strip_index_format: None,
-                    front_face: wgpu::FrontFace::Ccw,
-                    cull_mode: Some(wgpu::Face::Back),
-                    // Setting this to Fill means "draw filled triangles"
-                    polygon_mode: wgpu::PolygonMode::Fill,
-                    unclipped_depth: false,
-                    conservative: false,
-                },
-                multisample: wgpu::MultisampleState::default(),
-                multiview: None,
-            });
-
+        // 3. Pipelines are created per (material key, pass) on first use, see prepare_pipeline
         Self {
-            pipeline,
+            shader,
+            pipeline_layout: render_pipeline_layout,
+            format: ctx.format,
+            depth_compare: ctx.depth_mode.compare(),
+            pipelines: HashMap::new(),
+            cache_hits: 0,
+            cache_misses: 0,
+            missing: MissingResources::default(),
             material_layout: material_bind_group_layout,
             mesh_layout: mesh_bind_group_layout,
         }
     }
 
     fn record<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, data: Self::DrawData<'a>) {
-        let (global_bind_group, mesh_query) = data;
+        let (global_bind_group, draws) = data;
+        Self::record_draws(render_pass, global_bind_group, draws, None, |key| {
+            self.get_pipeline(key)
+        });
+    }
+}
+
+/// One PBR draw with everything it binds, collected from the ECS while the render
+/// systems run, so the pass drawing it can be recorded later on any thread. The GPU
+/// handles are owned: an asset unloaded before the frame is submitted stays alive.
+#[derive(Clone)]
+pub struct PbrDraw {
+    key: PipelineKey,
+    entity: Entity,
+    material: Entity,
+    material_bind_group: wgpu::BindGroup,
+    mesh: Entity,
+    // GeometryPool block holding the mesh, its buffers and the mesh's ranges in them
+    block: u32,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    index_format: wgpu::IndexFormat,
+    indices: Range<u32>,
+    base_vertex: i32,
+    instance_bind_group: wgpu::BindGroup,
+}
+
+impl PbrDraw {
+    /// The entity drawn.
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+}
+
+/// This frame's PBR draw lists, as "Render Frame" collected them for the scene passes,
+/// for the passes drawing them again (debug views, picking).
+pub(crate) struct FrameDraws {
+    pub opaque: Arc<[PbrDraw]>,
+    pub ordered_opaque: Arc<[PbrDraw]>,
+    pub overlay: Arc<[PbrDraw]>,
+    /// One list per view, back to front from its camera.
+    pub transparent: Vec<Arc<[PbrDraw]>>,
+}
+
+// Shared by the draws of one table
+struct TableResources {
+    material: Entity,
+    material_key: MaterialKey,
+    material_bind_group: wgpu::BindGroup,
+    mesh: Entity,
+    block: u32,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    index_format: wgpu::IndexFormat,
+    indices: Range<u32>,
+    base_vertex: i32,
+}
+
+impl TableResources {
+    fn draw(&self, pass: PbrPass, entity: Entity, instance: &MeshInstance) -> PbrDraw {
+        PbrDraw {
+            key: PipelineKey {
+                material: self.material_key,
+                pass,
+            },
+            entity,
+            material: self.material,
+            material_bind_group: self.material_bind_group.clone(),
+            mesh: self.mesh,
+            block: self.block,
+            vertex_buffer: self.vertex_buffer.clone(),
+            index_buffer: self.index_buffer.clone(),
+            index_format: self.index_format,
+            indices: self.indices.clone(),
+            base_vertex: self.base_vertex,
+            instance_bind_group: instance.bind_group.clone(),
+        }
+    }
+}
 
-        // 1. Set Pipeline
-        render_pass.set_pipeline(&self.pipeline);
+// A draw of the bucketed path, collected so it can be sorted
+struct BucketDraw {
+    draw: PbrDraw,
+    priority: i32,
+    distance: f32,
+}
 
-        // 2. Bind Shared Data (Group 0)
+impl PbrProgram {
+    /// Creates the pipeline for `key` if it isn't cached yet. Call for every key
+    /// of the frame before recording; record only reads the cache.
+    pub fn prepare_pipeline(&mut self, device: &wgpu::Device, key: PipelineKey) {
+        if self.pipelines.contains_key(&key) {
+            self.cache_hits += 1;
+            return;
+        }
+        self.cache_misses += 1;
+        let pipeline = self.create_pipeline(device, key);
+        self.pipelines.insert(key, pipeline);
+    }
+
+    /// Records `draws` with the pipeline `pipeline_for` picks for each key, skipping the
+    /// draws it has none for. The PBR pass uses its own cache; other programs drawing the
+    /// same lists (debug views, picking) bring pipelines with the PBR bind group layouts.
+    /// With `first_slot`, draw i is drawn as instance first_slot + i, which picking
+    /// writes as its id; otherwise as instance 0.
+    pub fn record_draws<'a>(
+        render_pass: &mut wgpu::RenderPass<'a>,
+        global_bind_group: &'a wgpu::BindGroup,
+        draws: &'a [PbrDraw],
+        first_slot: Option<u32>,
+        pipeline_for: impl Fn(PipelineKey) -> Option<&'a RenderPipeline>,
+    ) {
+        if draws.is_empty() {
+            return;
+        }
+
+        // 1. Bind Shared Data (Group 0)
         // This is the "Shared Buffer" passed in by reference
         render_pass.set_bind_group(0, global_bind_group, &[]);
-        // 3. Draw Loop
-        mesh_query.run(|mut iter| {
+
+        // 2. Draws arrive sorted, so only what changes between neighbours is rebound
+        let mut current_pipeline: Option<&RenderPipeline> = None;
+        let mut current_material = None;
+        let mut current_block = None;
+
+        for (index, draw) in draws.iter().enumerate() {
+            let Some(pipeline) = pipeline_for(draw.key) else {
+                continue;
+            };
+            if !current_pipeline.is_some_and(|current| std::ptr::eq(current, pipeline)) {
+                render_pass.set_pipeline(pipeline);
+                current_pipeline = Some(pipeline);
+            }
+            if current_material != Some(draw.material) {
+                render_pass.set_bind_group(1, &draw.material_bind_group, &[]);
+                current_material = Some(draw.material);
+            }
+            // Meshes of the same pool block share the buffers and index format, only the
+            // ranges differ
+            if current_block != Some(draw.block) {
+                render_pass.set_vertex_buffer(0, draw.vertex_buffer.slice(..));
+                render_pass.set_index_buffer(draw.index_buffer.slice(..), draw.index_format);
+                current_block = Some(draw.block);
+            }
+
+            render_pass.set_bind_group(2, &draw.instance_bind_group, &[]);
+            let instance = first_slot.map_or(0, |first| first + index as u32);
+            render_pass.draw_indexed(draw.indices.clone(), draw.base_vertex, instance..instance + 1);
+        }
+    }
+
+    pub fn get_pipeline(&self, key: PipelineKey) -> Option<&RenderPipeline> {
+        self.pipelines.get(&key)
+    }
+
+    /// Permutations compiled so far.
+    pub fn pipeline_count(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    /// Draws skipped because of a missing GPU material or mesh since the last call.
+    pub fn take_missing_resource_skips(&self) -> u64 {
+        self.missing.skipped.swap(0, Ordering::Relaxed)
+    }
+
+    /// (hits, misses) of prepare_pipeline since startup.
+    pub fn cache_stats(&self) -> (u64, u64) {
+        (self.cache_hits, self.cache_misses)
+    }
+
+    // Same shader for every permutation: the material key picks the override
+    // constants, the pass picks blending and depth state.
+    fn create_pipeline(&self, device: &wgpu::Device, key: PipelineKey) -> RenderPipeline {
+        let (blend, write_velocity, depth_write_enabled, depth_compare) = match key.pass {
+            // Opaque: write Z-values, closer pixels win
+            PbrPass::Opaque => (wgpu::BlendState::REPLACE, true, true, self.depth_compare),
+            PbrPass::Transparent => (
+                wgpu::BlendState::ALPHA_BLENDING,
+                false,
+                false,
+                self.depth_compare,
+            ),
+            PbrPass::Overlay => (
+                wgpu::BlendState::ALPHA_BLENDING,
+                false,
+                true,
+                self.depth_compare,
+            ),
+            PbrPass::OverlayNoDepth => (
+                wgpu::BlendState::ALPHA_BLENDING,
+                false,
+                false,
+                wgpu::CompareFunction::Always,
+            ),
+        };
+
+        let flag = |feature| {
+            if key.material.contains(feature) {
+                1.0
+            } else {
+                0.0
+            }
+        };
+        let constants = [
+            ("NORMAL_MAP", flag(MaterialKey::NORMAL_MAP)),
+            ("ALPHA_MASK", flag(MaterialKey::ALPHA_MASK)),
+            ("DOUBLE_SIDED", flag(MaterialKey::DOUBLE_SIDED)),
+            ("UNLIT", flag(MaterialKey::UNLIT)),
+            ("WIND", flag(MaterialKey::WIND)),
+        ];
+        let compilation_options = wgpu::PipelineCompilationOptions {
+            constants: &constants,
+            ..Default::default()
+        };
+
+        let label = format!("PBR Pipeline {:?} {:?}", key.pass, key.material);
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            cache: None,
+            label: Some(&label),
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &self.shader,
+                entry_point: Some("vs_main"),
+                compilation_options: compilation_options.clone(),
+                buffers: &[Vertex::desc()], // <--- Use our Vertex layout!
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.shader,
+                entry_point: Some("fs_main"),
+                compilation_options,
+                targets: &[
+                    Some(wgpu::ColorTargetState {
+                        format: self.format,
+                        blend: Some(blend),
+                        // Replace would copy material alpha into the target; keep it opaque
+                        write_mask: if blend == wgpu::BlendState::REPLACE {
+                            wgpu::ColorWrites::COLOR
+                        } else {
+                            wgpu::ColorWrites::ALL
+                        },
+                    }),
+                    // Motion vectors for TAA. Blended geometry keeps the
+                    // velocity of whatever opaque surface is behind it.
+                    Some(wgpu::ColorTargetState {
+                        format: TextureHelper::VELOCITY_FORMAT,
+                        blend: None,
+                        write_mask: if write_velocity {
+                            wgpu::ColorWrites::ALL
+                        } else {
+                            wgpu::ColorWrites::empty()
+                        },
+                    }),
+                    // Scene luminance for auto exposure, same rule as the velocity
+                    Some(wgpu::ColorTargetState {
+                        format: TextureHelper::LUMINANCE_FORMAT,
+                        blend: None,
+                        write_mask: if write_velocity {
+                            wgpu::ColorWrites::ALL
+                        } else {
+                            wgpu::ColorWrites::empty()
+                        },
+                    }),
+                ],
+            }),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: TextureHelper::DEPTH_FORMAT,
+                depth_write_enabled,
+                depth_compare,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: if key.material.contains(MaterialKey::DOUBLE_SIDED) {
+                    None
+                } else {
+                    Some(wgpu::Face::Back)
+                },
+                // Setting this to Fill means "draw filled triangles"
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    /// The draws of the entities without a RenderOrder, one per instance, sorted by
+    /// pipeline so every pipeline is bound once. With `reflection`, only the entities a
+    /// reflection drawing those layers shows; without, OcclusionCulled entities are skipped.
+    pub fn collect_draws(
+        &self,
+        query: &Query<&MeshInstance>,
+        reflection: Option<RenderLayers>,
+    ) -> Arc<[PbrDraw]> {
+        let mut draws = Vec::new();
+        query.run(|mut iter| {
             let world = iter.world();
-            let mut current_index_count: u32 = 0;
 
             while iter.next() {
+                if iter.count() == 0 {
+                    continue;
+                }
+                // Tags are per table: a hidden table is skipped whole, in the screen's views only
+                if reflection.is_none() && iter.entity(0).has(OcclusionCulled) {
+                    continue;
+                }
                 let instances = iter.field::<MeshInstance>(0);
-                let material_entity = world.entity_from_id(iter.group_id());
-                let mesh_pair = iter.pair(1);
-                let mesh_entity = mesh_pair.second_id();
+                let material = world.entity_from_id(iter.group_id()).id();
+                let mesh = iter.pair(1).second_id().id();
 
-                material_entity.get::<&GpuMaterial>(|gpu_material| {
-                    render_pass.set_bind_group(1, &gpu_material.bind_group, &[]);
-                });
+                let Some(table) =
+                    self.table_resources(&world, iter.entity(0), iter.count(), material, mesh)
+                else {
+                    continue;
+                };
+                for i in iter.iter() {
+                    if reflection.is_some_and(|layers| !is_reflected(iter.entity(i), layers)) {
+                        continue;
+                    }
+                    draws.push(table.draw(PbrPass::Opaque, iter.entity(i).id(), &instances[i]));
+                }
+            }
+        });
 
-                mesh_entity.try_get::<&GpuGeometry>(|gpu_geometry| {
-                    render_pass.set_vertex_buffer(0, gpu_geometry.vertex_buffer.slice(..));
-                    render_pass.set_index_buffer(
-                        gpu_geometry.index_buffer.slice(..),
-                        wgpu::IndexFormat::Uint32,
-                    );
+        // Stable, so the instances of a table stay next to each other
+        draws.sort_by_key(|draw| draw.key);
+        draws.into()
+    }
 
-                    //last_mesh_uuid = Some(current_mesh_uuid);
-                    current_index_count = gpu_geometry.index_count;
-                });
+    /// The draws of the entities with a RenderOrder in `bucket`.
+    ///
+    /// Opaque/Overlay are sorted by priority and then pipeline + material + mesh, so batching still
+    /// applies within a priority level. Transparent is sorted by priority and then back-to-front.
+    /// With `reflection`, only entities a reflection drawing those layers shows are drawn.
+    pub fn collect_bucket(
+        &self,
+        query: &Query<(&MeshInstance, &RenderOrder, &GlobalTransform)>,
+        bucket: RenderBucket,
+        camera_pos: Vec3,
+        reflection: Option<RenderLayers>,
+    ) -> Arc<[PbrDraw]> {
+        let mut draws = Vec::new();
+        query.run(|mut iter| {
+            let world = iter.world();
 
-                for i in iter.iter() {
-                    render_pass.set_bind_group(2, &instances[i].bind_group, &[]);
-                    render_pass.draw_indexed(0..current_index_count, 0, 0..1);
+            while iter.next() {
+                let instances = iter.field::<MeshInstance>(0);
+                let orders = iter.field::<RenderOrder>(1);
+                let transforms = iter.field::<GlobalTransform>(2);
+                let mesh = iter.pair(3).second_id().id();
+                let material = iter.pair(4).second_id().id();
+
+                let in_bucket: Vec<usize> = iter
+                    .iter()
+                    .filter(|&i| orders[i].bucket == bucket)
+                    .filter(|&i| {
+                        reflection.is_none_or(|layers| is_reflected(iter.entity(i), layers))
+                    })
+                    .collect();
+                let Some(&first) = in_bucket.first() else {
+                    continue;
+                };
+
+                // Resources are looked up once per table
+                let first = iter.entity(first);
+                let Some(table) =
+                    self.table_resources(&world, first, in_bucket.len(), material, mesh)
+                else {
+                    continue;
+                };
+                for i in in_bucket {
+                    draws.push(BucketDraw {
+                        draw: table.draw(
+                            PbrPass::from_order(bucket, orders[i].depth_test),
+                            iter.entity(i).id(),
+                            &instances[i],
+                        ),
+                        priority: orders[i].priority,
+                        distance: transforms[i].0.w_axis.truncate().distance(camera_pos),
+                    });
                 }
             }
         });
+
+        match bucket {
+            RenderBucket::Transparent => draws.sort_by(|a, b| {
+                a.priority
+                    .cmp(&b.priority)
+                    .then(b.distance.total_cmp(&a.distance)) // Far to near
+            }),
+            _ => draws.sort_by_key(|d| {
+                (d.priority, d.draw.key, d.draw.material, d.draw.block, d.draw.mesh)
+            }),
+        }
+        draws.into_iter().map(|d| d.draw).collect()
+    }
+
+    // What the draws of one table bind. None when its material or mesh has no GPU
+    // resource; the table's draws are counted as skipped then.
+    fn table_resources(
+        &self,
+        world: &WorldRef,
+        first: EntityView,
+        draws: usize,
+        material: Entity,
+        mesh: Entity,
+    ) -> Option<TableResources> {
+        let Some((material_key, material_bind_group)) = alive_asset(world, material)
+            .and_then(|entity| entity.try_get::<&GpuMaterial>(|m| (m.key, m.bind_group.clone())))
+        else {
+            self.missing.skip(draws, first, material, MissingResource::Material);
+            return None;
+        };
+        let Some((block, vertex_buffer, index_buffer, index_format, (indices, base_vertex))) =
+            alive_asset(world, mesh).and_then(|entity| {
+                entity.try_get::<&GpuGeometry>(|geometry| {
+                    (
+                        geometry.pool_block_id,
+                        geometry.vertex_buffer.clone(),
+                        geometry.index_buffer.clone(),
+                        geometry.index_format,
+                        geometry.draw_range(),
+                    )
+                })
+            })
+        else {
+            self.missing.skip(draws, first, mesh, MissingResource::Geometry);
+            return None;
+        };
+
+        Some(TableResources {
+            material,
+            material_key,
+            material_bind_group,
+            mesh,
+            block,
+            vertex_buffer,
+            index_buffer,
+            index_format,
+            indices,
+            base_vertex,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use catalyst_assets::assets::Handle;
+
+    use super::*;
+
+    // What table_resources does for a table's material, minus the GPU
+    fn lookup_material(
+        missing: &MissingResources,
+        first: EntityView,
+        draws: usize,
+        material: Entity,
+    ) {
+        let world = first.world();
+        let resource = alive_asset(&world, material)
+            .and_then(|entity| entity.try_get::<&GpuMaterial>(|m| m.key));
+        if resource.is_none() {
+            missing.skip(draws, first, material, MissingResource::Material);
+        }
+    }
+
+    #[test]
+    fn alive_asset_is_none_once_the_asset_is_deleted() {
+        let world = World::new();
+        let material = world.entity_named("Crate Material");
+        let view = material.world();
+        assert!(alive_asset(&view, material.id()).is_some());
+
+        let material = material.id();
+        world.entity_from_id(material).destruct();
+        assert!(alive_asset(&view, material).is_none());
+    }
+
+    #[test]
+    fn draws_of_a_deleted_material_are_skipped_and_counted() {
+        let world = World::new();
+        let material = world.entity_named("Crate Material").id();
+        let instance = world
+            .entity_named("Crate")
+            .set(MaterialDefinition(Handle::new()));
+        world.entity_from_id(material).destruct();
+
+        let missing = MissingResources::default();
+        for _ in 0..3 {
+            lookup_material(&missing, instance, 4, material);
+        }
+        assert_eq!(missing.skipped.load(Ordering::Relaxed), 12);
+        // Logged once, however many frames it is skipped for
+        assert_eq!(missing.reported.lock().unwrap().len(), 1);
+
+        // A live material that isn't on the GPU yet is skipped the same way
+        let pending = world.entity_named("Pending Material").id();
+        lookup_material(&missing, instance, 1, pending);
+        assert_eq!(missing.skipped.load(Ordering::Relaxed), 13);
+        assert_eq!(missing.reported.lock().unwrap().len(), 2);
     }
 }
@@ -0,0 +1,59 @@
+// ========================================================================
+//  PICKING
+//  The scene's PBR draws are drawn again as ids: each draw's slot + 1 in
+//  an R32Uint target, 0 where nothing is. The texels under the requested
+//  positions are read back and the slots looked up, see Picking.
+// ========================================================================
+
+#include "common/camera.wgsl"
+#include "common/material.wgsl"
+#include "common/mesh.wgsl"
+#include "common/wind.wgsl"
+
+// --- GROUP 0: CAMERA (the scene's global bind group) ---
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+// --- GROUP 1: MATERIAL (the PBR material bind group) ---
+@group(1) @binding(0) var<uniform> material: MaterialUniforms;
+@group(1) @binding(1) var t_diffuse: texture_2d<f32>;
+@group(1) @binding(2) var s_diffuse: sampler;
+
+// --- GROUP 2: MESH ---
+@group(2) @binding(0) var<uniform> mesh: MeshUniform;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(3) wind_weight: f32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) @interpolate(flat) slot: u32,
+};
+
+@vertex
+fn vs_main(in: VertexInput, @builtin(instance_index) slot: u32) -> VertexOutput {
+    var out: VertexOutput;
+    // Unjittered, the pixel under the cursor is the one asked about
+    // Swaying like in the lit view; materials without wind have zero wind parameters
+    let world_pos = mesh.model * vec4<f32>(in.position, 1.0);
+    let normal = (mesh.normal_matrix * vec4<f32>(in.normal, 0.0)).xyz;
+    let offset = wind_offset(world_pos.xyz, normal, in.wind_weight, camera.wind, material.wind);
+    out.clip_position = camera.unjittered_view_proj * (world_pos + vec4<f32>(offset, 0.0));
+    out.uv = in.uv;
+    out.slot = slot;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) u32 {
+    // Cut-out texels can't be picked, they aren't drawn in the lit view either
+    let alpha = textureSample(t_diffuse, s_diffuse, in.uv).a * material.base_color.a;
+    if (material.alpha_cutoff > 0.0 && alpha < material.alpha_cutoff) {
+        discard;
+    }
+    return in.slot + 1u;
+}
@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+
+use wgpu::RenderPipeline;
+
+use crate::{
+    global_resources::CameraUniform,
+    layout::{uniform_layouts, validate_program_layouts},
+    material::{GpuMaterialUniform, MaterialKey},
+    mesh::{MeshUniform, Vertex},
+    programs::{
+        GpuProgram, GpuProgramRenderContext, PbrProgram,
+        reflected::{WGSL_CAMERA, WGSL_MATERIAL_UNIFORMS, WGSL_MESH_UNIFORM},
+        pbr_program::{PbrDraw, PbrPass},
+    },
+    shaders::create_shader_module,
+    texture::TextureHelper,
+};
+
+uniform_layouts!(
+    CameraUniform::LAYOUT => WGSL_CAMERA,
+    GpuMaterialUniform::LAYOUT => WGSL_MATERIAL_UNIFORMS,
+    MeshUniform::LAYOUT => WGSL_MESH_UNIFORM,
+);
+
+const PASSES: [PbrPass; 4] = [
+    PbrPass::Opaque,
+    PbrPass::Transparent,
+    PbrPass::Overlay,
+    PbrPass::OverlayNoDepth,
+];
+
+/// The scene's PBR draws again as ids into the picking target, see Picking. The
+/// pipelines are created for the first pick, see `prepare`.
+pub struct PickingProgram {
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    depth_compare: wgpu::CompareFunction,
+    // Per (pass, double sided)
+    pipelines: HashMap<(PbrPass, bool), RenderPipeline>,
+}
+
+impl GpuProgram for PickingProgram {
+    // Global, material and mesh layouts, shared with the PBR program
+    type InitData = (
+        wgpu::BindGroupLayout,
+        wgpu::BindGroupLayout,
+        wgpu::BindGroupLayout,
+    );
+    // Global bind group, draws and the slot of the first draw
+    type DrawData<'a> = (&'a wgpu::BindGroup, &'a [PbrDraw], u32);
+
+    fn new(ctx: &GpuProgramRenderContext, init_data: &Self::InitData) -> Self {
+        validate_program_layouts("PickingProgram", UNIFORM_LAYOUTS);
+        let (global_layout, material_layout, mesh_layout) = init_data;
+
+        let shader = create_shader_module(ctx.device, "Picking Shader", &["picking.wgsl"]);
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Picking Pipeline Layout"),
+                bind_group_layouts: &[global_layout, material_layout, mesh_layout],
+                push_constant_ranges: &[],
+            });
+
+        Self {
+            shader,
+            pipeline_layout,
+            depth_compare: ctx.depth_mode.compare(),
+            pipelines: HashMap::new(),
+        }
+    }
+
+    fn record<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, data: Self::DrawData<'a>) {
+        let (global_bind_group, draws, first_slot) = data;
+        PbrProgram::record_draws(
+            render_pass,
+            global_bind_group,
+            draws,
+            Some(first_slot),
+            |key| {
+                let double_sided = key.material.contains(MaterialKey::DOUBLE_SIDED);
+                self.pipelines.get(&(key.pass, double_sided))
+            },
+        );
+    }
+}
+
+impl PickingProgram {
+    pub const TARGET_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+
+    /// Creates the pipelines unless they exist. Call before declaring the picking pass.
+    pub fn prepare(&mut self, device: &wgpu::Device) {
+        if !self.pipelines.is_empty() {
+            return;
+        }
+        for pass in PASSES {
+            for double_sided in [false, true] {
+                let pipeline = self.create_pipeline(device, pass, double_sided);
+                self.pipelines.insert((pass, double_sided), pipeline);
+            }
+        }
+    }
+
+    // Every pass writes depth, so the nearest surface wins, transparent or not.
+    // Overlays without a depth test still draw over everything.
+    fn create_pipeline(
+        &self,
+        device: &wgpu::Device,
+        pass: PbrPass,
+        double_sided: bool,
+    ) -> RenderPipeline {
+        let depth_compare = match pass {
+            PbrPass::OverlayNoDepth => wgpu::CompareFunction::Always,
+            _ => self.depth_compare,
+        };
+        let label = format!("Picking Pipeline {:?}", pass);
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            cache: None,
+            label: Some(&label),
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &self.shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[Vertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.shader,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: Self::TARGET_FORMAT,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: TextureHelper::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: if double_sided {
+                    None
+                } else {
+                    Some(wgpu::Face::Back)
+                },
+                ..Default::default()
+            },
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+}
@@ -0,0 +1,133 @@
+// ========================================================================
+//  REFLECTION PROBE PREFILTER
+//  Turns the six faces of a probe capture, tone mapped like the screen, back
+//  into radiance and blurs them into one face of one mip level of the probe's
+//  cubemap, the blur matching the GGX lobe of that level's roughness.
+// ========================================================================
+
+const PI = 3.14159265359;
+const SAMPLES: u32 = 64u;
+
+struct PrefilterUniforms {
+    face: u32,      // Cubemap face drawn into
+    roughness: f32, // Of the mip level drawn into
+    padding: vec2<f32>,
+};
+
+@group(0) @binding(0) var t_capture: texture_2d_array<f32>; // One layer per face camera
+@group(0) @binding(1) var s_linear: sampler;
+@group(0) @binding(2) var<uniform> params: PrefilterUniforms;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// Fullscreen triangle, no vertex buffer
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+// Direction of a texel of a cubemap face
+fn cube_direction(face: u32, uv: vec2<f32>) -> vec3<f32> {
+    let p = uv * 2.0 - 1.0;
+    switch face {
+        case 0u: { return normalize(vec3<f32>(1.0, -p.y, -p.x)); }
+        case 1u: { return normalize(vec3<f32>(-1.0, -p.y, p.x)); }
+        case 2u: { return normalize(vec3<f32>(p.x, 1.0, p.y)); }
+        case 3u: { return normalize(vec3<f32>(p.x, -1.0, -p.y)); }
+        case 4u: { return normalize(vec3<f32>(p.x, -p.y, 1.0)); }
+        default: { return normalize(vec3<f32>(-p.x, -p.y, -1.0)); }
+    }
+}
+
+// Forward and up of each capture camera, as in reflection_probe.rs
+fn camera_basis(face: u32) -> mat2x3<f32> {
+    switch face {
+        case 0u: { return mat2x3<f32>(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0)); }
+        case 1u: { return mat2x3<f32>(vec3<f32>(-1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0)); }
+        case 2u: { return mat2x3<f32>(vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(0.0, 0.0, 1.0)); }
+        case 3u: { return mat2x3<f32>(vec3<f32>(0.0, -1.0, 0.0), vec3<f32>(0.0, 0.0, -1.0)); }
+        case 4u: { return mat2x3<f32>(vec3<f32>(0.0, 0.0, 1.0), vec3<f32>(0.0, 1.0, 0.0)); }
+        default: { return mat2x3<f32>(vec3<f32>(0.0, 0.0, -1.0), vec3<f32>(0.0, 1.0, 0.0)); }
+    }
+}
+
+// Radiance seen along `direction`, from the capture camera looking closest to it
+fn capture_radiance(direction: vec3<f32>) -> vec3<f32> {
+    let a = abs(direction);
+    var face = 4u;
+    if (a.x >= a.y && a.x >= a.z) {
+        face = select(1u, 0u, direction.x > 0.0);
+    } else if (a.y >= a.z) {
+        face = select(3u, 2u, direction.y > 0.0);
+    } else {
+        face = select(5u, 4u, direction.z > 0.0);
+    }
+
+    // 90 degree field of view: the projection divides by the depth along forward
+    let basis = camera_basis(face);
+    let forward = basis[0];
+    let right = normalize(cross(forward, basis[1]));
+    let up = cross(right, forward);
+    let ndc = vec2<f32>(dot(direction, right), dot(direction, up)) / dot(direction, forward);
+    let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+    let stored = textureSampleLevel(t_capture, s_linear, uv, face, 0.0).rgb;
+
+    // Undo the gamma and the Reinhard curve of shader.wgsl. The brightest values
+    // can't come back, clamped so they stay finite.
+    let tone_mapped = pow(clamp(stored, vec3<f32>(0.0), vec3<f32>(0.99)), vec3<f32>(2.2));
+    return tone_mapped / (vec3<f32>(1.0) - tone_mapped);
+}
+
+fn hammersley(i: u32, count: u32) -> vec2<f32> {
+    var bits = i;
+    bits = (bits << 16u) | (bits >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    return vec2<f32>(f32(i) / f32(count), f32(bits) * 2.3283064365386963e-10);
+}
+
+// Half vector around N, distributed like the GGX lobe of `roughness`
+fn importance_sample_ggx(xi: vec2<f32>, N: vec3<f32>, roughness: f32) -> vec3<f32> {
+    let a = roughness * roughness;
+    let phi = 2.0 * PI * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    let h = vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+
+    let up = select(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 0.0, 1.0), abs(N.z) < 0.999);
+    let tangent = normalize(cross(up, N));
+    let bitangent = cross(N, tangent);
+    return normalize(tangent * h.x + bitangent * h.y + N * h.z);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Seen head on, as the split-sum approximation assumes
+    let N = cube_direction(params.face, in.uv);
+    if (params.roughness <= 0.0) {
+        return vec4<f32>(capture_radiance(N), 1.0);
+    }
+
+    var radiance = vec3<f32>(0.0);
+    var total_weight = 0.0;
+    for (var i = 0u; i < SAMPLES; i++) {
+        let H = importance_sample_ggx(hammersley(i, SAMPLES), N, params.roughness);
+        let L = normalize(2.0 * dot(N, H) * H - N);
+        let NdotL = dot(N, L);
+        if (NdotL > 0.0) {
+            radiance += capture_radiance(L) * NdotL;
+            total_weight += NdotL;
+        }
+    }
+    return vec4<f32>(radiance / max(total_weight, 0.0001), 1.0);
+}
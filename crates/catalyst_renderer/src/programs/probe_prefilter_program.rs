@@ -0,0 +1,183 @@
+use wgpu::RenderPipeline;
+
+use crate::{
+    layout::{StructLayout, rust_layout, uniform_layouts, validate_program_layouts},
+    programs::{
+        GpuProgram, GpuProgramRenderContext,
+        reflected::{self, WGSL_PREFILTER_UNIFORMS},
+    },
+    reflection_probe::{PROBE_FORMAT, PROBE_MIPS},
+};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PrefilterUniform {
+    pub face: u32,
+    pub roughness: f32,
+    pub _padding: [f32; 2],
+}
+
+const _: () = assert!(std::mem::size_of::<PrefilterUniform>() == 16);
+
+impl PrefilterUniform {
+    pub const LAYOUT: StructLayout = rust_layout!(PrefilterUniform {
+        face: u32,
+        roughness: f32,
+        _padding: [f32; 2],
+    });
+}
+
+uniform_layouts!(PrefilterUniform::LAYOUT => WGSL_PREFILTER_UNIFORMS);
+
+// One uniform per face and mip level, bound at a dynamic offset. 256 is the largest
+// min_uniform_buffer_offset_alignment wgpu allows.
+const UNIFORM_STRIDE: u64 = 256;
+const FACES: u32 = 6;
+
+/// Fullscreen pass blurring a reflection probe capture into one face of one mip level
+/// of the probe's cubemap, see reflection_probe.rs.
+pub struct ProbePrefilterProgram {
+    pipeline: RenderPipeline,
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    uniform_buffer: wgpu::Buffer,
+}
+
+impl ProbePrefilterProgram {
+    /// Bind group reading `capture`, the six faces of a capture as a 2D array.
+    pub fn bind_group(
+        &self,
+        device: &wgpu::Device,
+        capture: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Probe Prefilter Bind Group"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(capture),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &self.uniform_buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(std::mem::size_of::<PrefilterUniform>() as u64),
+                    }),
+                },
+            ],
+        })
+    }
+}
+
+impl GpuProgram for ProbePrefilterProgram {
+    type InitData = ();
+    /// Bind group of the capture, cubemap face and mip level drawn into.
+    type DrawData<'a> = (&'a wgpu::BindGroup, u32, u32);
+
+    fn new(ctx: &GpuProgramRenderContext, _init_data: &Self::InitData) -> Self {
+        validate_program_layouts("ProbePrefilterProgram", UNIFORM_LAYOUTS);
+
+        let shader = ctx
+            .device
+            .create_shader_module(wgpu::include_wgsl!("probe_prefilter.wgsl"));
+
+        let layout = ctx
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Probe Prefilter Bind Group Layout"),
+                // Capture faces, sampler, and face and roughness at a dynamic offset
+                entries: reflected::probe_prefilter::GROUP_0,
+            });
+
+        let sampler = ctx.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Probe Prefilter Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        // Never changes, written once
+        let uniform_buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Probe Prefilter Uniform Buffer"),
+            size: UNIFORM_STRIDE * (PROBE_MIPS * FACES) as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        for mip in 0..PROBE_MIPS {
+            for face in 0..FACES {
+                let uniform = PrefilterUniform {
+                    face,
+                    roughness: mip as f32 / (PROBE_MIPS - 1) as f32,
+                    _padding: [0.0; 2],
+                };
+                ctx.queue.write_buffer(
+                    &uniform_buffer,
+                    uniform_offset(face, mip),
+                    bytemuck::bytes_of(&uniform),
+                );
+            }
+        }
+
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Probe Prefilter Pipeline Layout"),
+                bind_group_layouts: &[&layout],
+                push_constant_ranges: &[],
+            });
+
+        let pipeline = ctx
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                cache: None,
+                label: Some("Probe Prefilter Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    compilation_options: Default::default(),
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    compilation_options: Default::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: PROBE_FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            });
+
+        Self {
+            pipeline,
+            layout,
+            sampler,
+            uniform_buffer,
+        }
+    }
+
+    fn record<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, data: Self::DrawData<'a>) {
+        let (bind_group, face, mip) = data;
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, bind_group, &[uniform_offset(face, mip) as u32]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+fn uniform_offset(face: u32, mip: u32) -> u64 {
+    (mip * FACES + face) as u64 * UNIFORM_STRIDE
+}
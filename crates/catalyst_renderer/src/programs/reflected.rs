@@ -0,0 +1,10 @@
+// Generated by build.rs from the WGSL: a StructLayout for every struct of a uniform or
+// storage buffer (WGSL_<STRUCT>), and per program the binding indices and layout
+// entries of each bind group (pbr::MATERIAL, pbr::GROUP_1, ...). Programs build their
+// bind group layouts from these, and check their Rust structs against the layouts with
+// uniform_layouts!.
+#![allow(dead_code)] // Not every binding index is used from Rust
+
+use crate::layout::{FieldLayout, StructLayout};
+
+include!(concat!(env!("OUT_DIR"), "/reflected_shaders.rs"));
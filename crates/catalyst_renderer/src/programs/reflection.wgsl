@@ -0,0 +1,77 @@
+#include "common/camera.wgsl"
+#include "common/lights.wgsl"
+#include "common/mesh.wgsl"
+#include "common/fog.wgsl"
+
+struct ReflectionSurface {
+    tint: vec4<f32>,
+    distortion: f32,
+    normal_tiling: f32,
+    fresnel: f32,
+    padding: f32,
+};
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(0) @binding(1) var<uniform> scene_data: LightUniforms;
+
+@group(1) @binding(0) var<uniform> surface: ReflectionSurface;
+@group(1) @binding(1) var t_reflection: texture_2d<f32>;
+@group(1) @binding(2) var s_reflection: sampler;
+@group(1) @binding(3) var t_normal: texture_2d<f32>;
+@group(1) @binding(4) var s_normal: sampler;
+
+@group(2) @binding(0) var<uniform> mesh: MeshUniform;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+};
+
+struct VSOut {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_pos: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+    // Where the reflection was drawn, the reflection view isn't jittered
+    @location(3) screen: vec4<f32>,
+};
+
+@vertex
+fn vs_main(in: VertexInput) -> VSOut {
+    let world_pos = mesh.model * vec4<f32>(in.position, 1.0);
+    let normal_matrix = mat3x3<f32>(
+        mesh.normal_matrix[0].xyz,
+        mesh.normal_matrix[1].xyz,
+        mesh.normal_matrix[2].xyz
+    );
+
+    var out: VSOut;
+    out.clip_position = camera.view_proj * world_pos;
+    out.world_pos = world_pos.xyz;
+    out.normal = normalize(normal_matrix * in.normal);
+    out.uv = in.uv;
+    out.screen = camera.unjittered_view_proj * world_pos;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VSOut) -> @location(0) vec4<f32> {
+    // Points on the plane land on the same pixel in both views. The reflection is
+    // drawn mirrored left to right (it keeps the triangle winding), so u is flipped.
+    let ndc = in.screen.xy / in.screen.w;
+    var uv = vec2<f32>(0.5 - ndc.x * 0.5, 0.5 - ndc.y * 0.5);
+
+    let ripple = textureSample(t_normal, s_normal, in.uv * surface.normal_tiling).xy * 2.0 - 1.0;
+    uv = clamp(uv + ripple * surface.distortion, vec2<f32>(0.0), vec2<f32>(1.0));
+    let reflected = textureSample(t_reflection, s_reflection, uv).rgb;
+
+    // Schlick: grazing angles reflect more than looking straight down
+    let view_dir = normalize(scene_data.camera_pos - in.world_pos);
+    let cos_theta = clamp(abs(dot(normalize(in.normal), view_dir)), 0.0, 1.0);
+    let fresnel = surface.fresnel + (1.0 - surface.fresnel) * pow(1.0 - cos_theta, 5.0);
+
+    // Fogged like the surface under it, by fading the reflection out
+    let fog = fog_opacity(scene_data, scene_data.camera_pos, in.world_pos);
+    return vec4<f32>(reflected * surface.tint.rgb, surface.tint.a * fresnel * (1.0 - fog));
+}
@@ -0,0 +1,283 @@
+use std::ops::Range;
+
+use wgpu::{Device, Queue, RenderPipeline};
+
+use crate::{
+    global_resources::{CameraUniform, LightUniforms},
+    layout::{StructLayout, rust_layout, uniform_layouts, validate_program_layouts},
+    mesh::{MeshUniform, Vertex},
+    programs::{
+        GpuProgram,
+        reflected::{
+            self, WGSL_CAMERA, WGSL_LIGHT_UNIFORMS, WGSL_MESH_UNIFORM, WGSL_REFLECTION_SURFACE,
+        },
+    },
+    shaders::create_shader_module,
+    texture::{GpuTexture, TextureHelper},
+};
+
+uniform_layouts!(
+    CameraUniform::LAYOUT => WGSL_CAMERA,
+    LightUniforms::LAYOUT => WGSL_LIGHT_UNIFORMS,
+    MeshUniform::LAYOUT => WGSL_MESH_UNIFORM,
+    ReflectionSurfaceUniform::LAYOUT => WGSL_REFLECTION_SURFACE,
+);
+
+/// Reflections drawn at once. Each gets a slot: its uniforms, its target and its pass.
+pub const MAX_REFLECTIONS: usize = 4;
+
+/// Offset between the slots in the surface uniform buffer, the dynamic offset alignment
+/// every adapter supports.
+const SLOT_STRIDE: u64 = 256;
+
+/// How a reflector's surface blends its reflection over the scene.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ReflectionSurfaceUniform {
+    pub tint: [f32; 4],
+    pub distortion: f32,
+    pub normal_tiling: f32,
+    pub fresnel: f32,
+    pub _padding: f32,
+}
+
+const _: () = assert!(std::mem::size_of::<ReflectionSurfaceUniform>() as u64 <= SLOT_STRIDE);
+
+impl ReflectionSurfaceUniform {
+    pub const LAYOUT: StructLayout = rust_layout!(ReflectionSurfaceUniform {
+        tint: [f32; 4],
+        distortion: f32,
+        normal_tiling: f32,
+        fresnel: f32,
+        _padding: f32,
+    });
+}
+
+/// A reflector's mesh, drawn again on top of itself with its reflection.
+pub struct ReflectionSurface {
+    pub slot: usize,
+    pub mesh_bind_group: wgpu::BindGroup,
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub index_format: wgpu::IndexFormat,
+    /// The mesh's ranges in its GeometryPool block, see GpuGeometry::draw_range.
+    pub indices: Range<u32>,
+    pub base_vertex: i32,
+    pub normal_map: GpuTexture,
+}
+
+pub struct ReflectionProgram {
+    pipeline: RenderPipeline,
+    /// Group 1: surface uniforms, the reflection and the normal map.
+    surface_layout: wgpu::BindGroupLayout,
+    surface_buffer: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+    surfaces: Vec<ReflectionSurface>,
+}
+
+impl ReflectionProgram {
+    /// This frame's reflector surfaces, with the uniforms of their slots.
+    pub fn prepare(
+        &mut self,
+        surfaces: Vec<(ReflectionSurface, ReflectionSurfaceUniform)>,
+        queue: &Queue,
+    ) {
+        self.surfaces.clear();
+        for (surface, uniform) in surfaces {
+            queue.write_buffer(
+                &self.surface_buffer,
+                surface.slot as u64 * SLOT_STRIDE,
+                bytemuck::bytes_of(&uniform),
+            );
+            self.surfaces.push(surface);
+        }
+    }
+
+    pub fn surface_count(&self) -> usize {
+        self.surfaces.len()
+    }
+
+    /// Group 1 of each surface, in prepare order. The reflection views are only known
+    /// once the graph executes; `reflections[slot]` is the target of that slot.
+    pub fn surface_bind_groups(
+        &self,
+        device: &Device,
+        reflections: &[Option<&wgpu::TextureView>],
+    ) -> Vec<Option<wgpu::BindGroup>> {
+        self.surfaces
+            .iter()
+            .map(|surface| {
+                let reflection = reflections.get(surface.slot).copied().flatten()?;
+                Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Reflection Surface Bind Group"),
+                    layout: &self.surface_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                                buffer: &self.surface_buffer,
+                                offset: 0,
+                                size: wgpu::BufferSize::new(
+                                    std::mem::size_of::<ReflectionSurfaceUniform>() as u64,
+                                ),
+                            }),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::TextureView(reflection),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: wgpu::BindingResource::Sampler(&self.sampler),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 3,
+                            resource: wgpu::BindingResource::TextureView(&surface.normal_map.view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 4,
+                            resource: wgpu::BindingResource::Sampler(&surface.normal_map.sampler),
+                        },
+                    ],
+                }))
+            })
+            .collect()
+    }
+}
+
+impl GpuProgram for ReflectionProgram {
+    /// Global layout (group 0) and the PBR mesh layout (group 2).
+    type InitData = (wgpu::BindGroupLayout, wgpu::BindGroupLayout);
+
+    /// Global (camera/lights) bind group of the view, and the surface bind groups from
+    /// `surface_bind_groups`.
+    type DrawData<'a> = (&'a wgpu::BindGroup, &'a [Option<wgpu::BindGroup>]);
+
+    fn new(ctx: &super::GpuProgramRenderContext, (global_layout, mesh_layout): &Self::InitData) -> Self {
+        validate_program_layouts("ReflectionProgram", UNIFORM_LAYOUTS);
+
+        let shader = create_shader_module(ctx.device, "Reflection Shader", &["reflection.wgsl"]);
+
+        let surface_layout = ctx
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Reflection Surface Layout"),
+                // Surface settings at a dynamic offset, reflection target and normal map
+                entries: reflected::reflection::GROUP_1,
+            });
+
+        let sampler = ctx.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Reflection Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Reflection Pipeline Layout"),
+                bind_group_layouts: &[global_layout, &surface_layout, mesh_layout],
+                push_constant_ranges: &[],
+            });
+
+        let pipeline = ctx
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                cache: None,
+                label: Some("Reflection Surface Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[Vertex::desc()],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    compilation_options: Default::default(),
+                    // Drawn in the transparent pass; the surface's own material already
+                    // wrote its velocity and luminance
+                    targets: &[
+                        Some(wgpu::ColorTargetState {
+                            format: ctx.format,
+                            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                            write_mask: wgpu::ColorWrites::COLOR,
+                        }),
+                        Some(wgpu::ColorTargetState {
+                            format: TextureHelper::VELOCITY_FORMAT,
+                            blend: None,
+                            write_mask: wgpu::ColorWrites::empty(),
+                        }),
+                        Some(wgpu::ColorTargetState {
+                            format: TextureHelper::LUMINANCE_FORMAT,
+                            blend: None,
+                            write_mask: wgpu::ColorWrites::empty(),
+                        }),
+                    ],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: Some(wgpu::Face::Back),
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                // Same triangles as the opaque draw underneath, so equal depth passes
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: TextureHelper::DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: ctx.depth_mode.compare_or_equal(),
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            });
+
+        let surface_buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Reflection Surface Buffer"),
+            size: MAX_REFLECTIONS as u64 * SLOT_STRIDE,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            pipeline,
+            surface_layout,
+            surface_buffer,
+            sampler,
+            surfaces: Vec::new(),
+        }
+    }
+
+    fn record<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        (global_bind_group, bind_groups): Self::DrawData<'a>,
+    ) {
+        if self.surfaces.is_empty() {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, global_bind_group, &[]);
+
+        for (surface, bind_group) in self.surfaces.iter().zip(bind_groups) {
+            // Reflection not drawn yet: only the surface's own material shows
+            let Some(bind_group) = bind_group else {
+                continue;
+            };
+            render_pass.set_bind_group(1, bind_group, &[(surface.slot as u64 * SLOT_STRIDE) as u32]);
+            render_pass.set_bind_group(2, &surface.mesh_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, surface.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(surface.index_buffer.slice(..), surface.index_format);
+            render_pass.draw_indexed(surface.indices.clone(), surface.base_vertex, 0..1);
+        }
+    }
+}
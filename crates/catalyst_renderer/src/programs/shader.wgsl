@@ -2,38 +2,41 @@
 //  STRUCTS (Must match Rust #[repr(C)] layout)
 // ========================================================================
 
-// --- LIGHTING ---
-struct PointLight {
-    position: vec4<f32>, // .xyz = position, .w = intensity
-    color: vec4<f32>,    // .xyz = color,    .w = radius (or unused)
+#include "common/camera.wgsl"
+#include "common/lights.wgsl"
+#include "common/material.wgsl"
+#include "common/mesh.wgsl"
+#include "common/brdf.wgsl"
+#include "common/motion.wgsl"
+#include "common/fog.wgsl"
+#include "common/wind.wgsl"
+
+const MAX_PROBES: u32 = 8u;
+const PROBE_MIPS: f32 = 6.0; // One per roughness level, 0 to 1
+
+// --- REFLECTION PROBES ---
+struct Probe {
+    center: vec4<f32>,  // .xyz = capture position, .w = layer of probe_textures
+    box_min: vec4<f32>, // .w = blend distance
+    box_max: vec4<f32>,
 };
 
-struct LightUniforms {
-    sun_direction: vec4<f32>, // .xyz = direction, .w = intensity
-    sun_color: vec4<f32>,     // .xyz = color,     .w = padding
-    lights: array<PointLight, 4>,
-    camera_pos: vec3<f32>,
-    active_lights: u32,       // How many point lights to loop over
+struct ProbeUniform {
+    probes: array<Probe, MAX_PROBES>, // Smallest box first
+    count: u32,
+    padding0: u32,
+    padding1: vec2<u32>,
 };
 
-// --- MATERIAL ---
-struct MaterialUniforms {
-    base_color: vec4<f32>,
-    roughness: f32,
-    metallic: f32,
-    padding: vec2<f32>,
-};
-
-// --- MESH (Per-Object) ---
-struct MeshUniform {
-    model: mat4x4<f32>,
-    normal_matrix: mat4x4<f32>, // We only use top-left 3x3
-};
+// ========================================================================
+//  SPECIALIZATION (MaterialKey, set per pipeline permutation)
+// ========================================================================
 
-// --- CAMERA (Global) ---
-struct Camera {
-    view_proj: mat4x4<f32>,
-};
+override NORMAL_MAP: bool = true;
+override ALPHA_MASK: bool = false;
+override DOUBLE_SIDED: bool = false;
+override UNLIT: bool = false;
+override WIND: bool = false;
 
 // ========================================================================
 //  BINDINGS
@@ -42,6 +45,12 @@ struct Camera {
 // --- GROUP 0: SCENE & CAMERA (Global) ---
 @group(0) @binding(0) var<uniform> camera: Camera;
 @group(0) @binding(1) var<uniform> scene_data: LightUniforms;
+@group(0) @binding(6) var<uniform> gobos: GoboUniform;
+@group(0) @binding(7) var gobo_textures: texture_2d_array<f32>;
+@group(0) @binding(8) var gobo_sampler: sampler;
+@group(0) @binding(9) var<uniform> probes: ProbeUniform;
+@group(0) @binding(10) var probe_textures: texture_cube_array<f32>;
+@group(0) @binding(11) var probe_sampler: sampler;
 
 // --- GROUP 1: MATERIAL (Per-Material) ---
 @group(1) @binding(0) var<uniform> material: MaterialUniforms;
@@ -64,6 +73,7 @@ struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) normal: vec3<f32>,
     @location(2) uv: vec2<f32>,
+    @location(3) wind_weight: f32,
 };
 
 struct VertexOutput {
@@ -71,6 +81,14 @@ struct VertexOutput {
     @location(0) world_pos: vec3<f32>,
     @location(1) normal: vec3<f32>,
     @location(2) uv: vec2<f32>,
+    @location(3) curr_clip: vec4<f32>, // Unjittered, for motion vectors
+    @location(4) prev_clip: vec4<f32>,
+};
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) velocity: vec2<f32>,  // Screen-space motion in UV units
+    @location(2) luminance: vec2<f32>, // log2 of the unexposed luminance, coverage (for auto exposure)
 };
 
 // ========================================================================
@@ -85,8 +103,8 @@ fn vs_main(in: VertexInput) -> VertexOutput {
     out.uv = in.uv;
 
     // 2. World Position
-    let world_pos_4 = mesh.model * vec4<f32>(in.position, 1.0);
-    out.world_pos = world_pos_4.xyz;
+    var world_pos_4 = mesh.model * vec4<f32>(in.position, 1.0);
+    var prev_world_pos = mesh.prev_model * vec4<f32>(in.position, 1.0);
 
     // 3. Normal (Using Normal Matrix to handle non-uniform scaling)
     let normal_matrix = mat3x3<f32>(
@@ -96,18 +114,30 @@ fn vs_main(in: VertexInput) -> VertexOutput {
     );
     out.normal = normalize(normal_matrix * in.normal);
 
-    // 4. Clip Position (Screen Space)
+    // 4. Sway in the wind, last frame's too so the motion vectors follow it
+    if (WIND) {
+        let offset = wind_offset(world_pos_4.xyz, out.normal, in.wind_weight, camera.wind, material.wind);
+        let prev_offset = wind_offset(prev_world_pos.xyz, out.normal, in.wind_weight, camera.prev_wind, material.wind);
+        world_pos_4 += vec4<f32>(offset, 0.0);
+        prev_world_pos += vec4<f32>(prev_offset, 0.0);
+        out.normal = wind_normal(out.normal, offset);
+    }
+    out.world_pos = world_pos_4.xyz;
+
+    // 5. Clip Position (Screen Space)
     out.clip_position = camera.view_proj * world_pos_4;
 
+    // 6. Where this vertex was last frame (object + camera motion)
+    out.curr_clip = camera.unjittered_view_proj * world_pos_4;
+    out.prev_clip = camera.prev_view_proj * prev_world_pos;
+
     return out;
 }
 
 // ========================================================================
-//  PBR FUNCTIONS
+//  PBR FUNCTIONS (the BRDF itself is in common/brdf.wgsl)
 // ========================================================================
 
-const PI = 3.14159265359;
-
 // Trick to calculate TBN matrix on the fly without pre-computing tangents
 fn getNormalFromMap(uv: vec2<f32>, world_pos: vec3<f32>, normal_geom: vec3<f32>) -> vec3<f32> {
     let tangent_normal = textureSample(t_normal, s_normal, uv).xyz * 2.0 - 1.0;
@@ -136,31 +166,92 @@ fn getNormalFromMap(uv: vec2<f32>, world_pos: vec3<f32>, normal_geom: vec3<f32>)
     return normalize(TBN * tangent_normal);
 }
 
-fn DistributionGGX(N: vec3<f32>, H: vec3<f32>, roughness: f32) -> f32 {
-    let a = roughness * roughness;
-    let a2 = a * a;
-    let NdotH = max(dot(N, H), 0.0);
-    let NdotH2 = NdotH * NdotH;
-    let denom = (NdotH2 * (a2 - 1.0) + 1.0);
-    return a2 / (PI * denom * denom);
+// Reflection of the first probe box around `world_pos` (the smallest), looked up along
+// R intersected with the box, so it lines up with the room the box matches.
+// .a = how much of it to use, fading out near the box's edges.
+fn probe_specular(world_pos: vec3<f32>, R: vec3<f32>, roughness: f32) -> vec4<f32> {
+    for (var i = 0u; i < probes.count; i++) {
+        let probe = probes.probes[i];
+        let box_min = probe.box_min.xyz;
+        let box_max = probe.box_max.xyz;
+        if (any(world_pos < box_min) || any(world_pos > box_max)) {
+            continue;
+        }
+
+        let edge = min(world_pos - box_min, box_max - world_pos);
+        let weight = clamp(min(edge.x, min(edge.y, edge.z)) / max(probe.box_min.w, 0.0001), 0.0, 1.0);
+
+        // Distance along R to the box's far side
+        let to_max = (box_max - world_pos) / R;
+        let to_min = (box_min - world_pos) / R;
+        let far = max(to_max, to_min);
+        let distance = min(far.x, min(far.y, far.z));
+        let direction = world_pos + R * distance - probe.center.xyz;
+
+        let lod = roughness * (PROBE_MIPS - 1.0);
+        let layer = i32(probe.center.w);
+        let reflection = textureSampleLevel(probe_textures, probe_sampler, direction, layer, lod).rgb;
+        return vec4<f32>(reflection, weight);
+    }
+    return vec4<f32>(0.0);
 }
 
-fn GeometrySchlickGGX(NdotV: f32, roughness: f32) -> f32 {
-    let r = (roughness + 1.0);
-    let k = (r * r) / 8.0;
-    return NdotV / (NdotV * (1.0 - k) + k);
+// Spot cone falloff times the projected gobo, 1 for point lights
+fn spot_factor(light: Light, world_pos: vec3<f32>, L: vec3<f32>) -> vec3<f32> {
+    if (light.kind != LIGHT_SPOT) {
+        return vec3<f32>(1.0);
+    }
+
+    let cone = smoothstep(light.cos_outer, light.cos_inner, dot(-L, light.direction));
+    if (light.gobo == NO_GOBO || cone <= 0.0) {
+        return vec3<f32>(cone);
+    }
+
+    let clip = gobos.view_proj[light.gobo] * vec4<f32>(world_pos, 1.0);
+    let uv = clip.xy / clip.w * vec2<f32>(0.5, -0.5) + 0.5;
+    // Sampled in a loop, so no implicit derivatives
+    let gobo = textureSampleLevel(gobo_textures, gobo_sampler, uv, light.gobo, 0.0).rgb;
+    return gobo * cone;
 }
 
-fn GeometrySmith(N: vec3<f32>, V: vec3<f32>, L: vec3<f32>, roughness: f32) -> f32 {
-    let NdotV = max(dot(N, V), 0.0);
+// Cook-Torrance contribution of one point or spot light. Fades out smoothly at the
+// light's radius, so lights can be culled there (clustered lighting) without a visible edge.
+fn light_radiance(
+    light: Light,
+    world_pos: vec3<f32>,
+    N: vec3<f32>,
+    V: vec3<f32>,
+    albedo: vec3<f32>,
+    roughness: f32,
+    metallic: f32,
+    F0: vec3<f32>,
+) -> vec3<f32> {
+    let light_pos = light.position.xyz;
+    let light_intensity = light.position.w;
+    let light_color = light.color.rgb;
+    let light_radius = light.color.w;
+
+    let dist = length(light_pos - world_pos);
+    let L = normalize(light_pos - world_pos);
+    let H = normalize(V + L);
+
+    let falloff = clamp(1.0 - pow(dist / max(light_radius, 0.0001), 4.0), 0.0, 1.0);
+    let attenuation = falloff * falloff / (dist * dist);
+    let radiance = light_color * light_intensity * attenuation * spot_factor(light, world_pos, L);
+
+    let NDF = DistributionGGX(N, H, roughness);
+    let G = GeometrySmith(N, V, L, roughness);
+    let F = fresnelSchlick(max(dot(H, V), 0.0), F0);
+
+    let numerator = NDF * G * F;
+    let denominator = 4.0 * max(dot(N, V), 0.0) * max(dot(N, L), 0.0) + 0.0001;
+    let specular = numerator / denominator;
+
+    let kS = F;
+    let kD = (vec3<f32>(1.0) - kS) * (1.0 - metallic);
     let NdotL = max(dot(N, L), 0.0);
-    let ggx2 = GeometrySchlickGGX(NdotV, roughness);
-    let ggx1 = GeometrySchlickGGX(NdotL, roughness);
-    return ggx1 * ggx2;
-}
 
-fn fresnelSchlick(cosTheta: f32, F0: vec3<f32>) -> vec3<f32> {
-    return F0 + (vec3<f32>(1.0) - F0) * pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
+    return (kD * albedo / PI + specular) * radiance * NdotL;
 }
 
 // ========================================================================
@@ -168,10 +259,35 @@ fn fresnelSchlick(cosTheta: f32, F0: vec3<f32>) -> vec3<f32> {
 // ========================================================================
 
 @fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+fn fs_main(in: VertexOutput, @builtin(front_facing) front_facing: bool) -> FragmentOutput {
     // --- 1. SAMPLE MATERIAL ---
     // Albedo
-    let albedo = textureSample(t_diffuse, s_diffuse, in.uv).rgb * material.base_color.rgb;
+    let diffuse = textureSample(t_diffuse, s_diffuse, in.uv);
+    let albedo = diffuse.rgb * material.base_color.rgb;
+    // Only used by the blended (Transparent/Overlay) pipelines and alpha masking
+    let alpha = diffuse.a * material.base_color.a;
+    if (ALPHA_MASK && alpha < material.alpha_cutoff) {
+        discard;
+    }
+
+    // KHR_materials_unlit: authored color at full brightness, no lighting and no
+    // tone mapping. Emission stays unclamped past 1.0 for a future bloom pass. Fades
+    // into the fog as the lit pipelines show it, exposed and tone mapped.
+    if (UNLIT) {
+        var unlit_color = albedo + material.emissive.rgb;
+        let fog_amount = fog_opacity(scene_data, scene_data.camera_pos, in.world_pos);
+        if (fog_amount > 0.0) {
+            let view_dir = normalize(in.world_pos - scene_data.camera_pos);
+            let fog = camera.exposure * fog_inscattering(scene_data, view_dir);
+            unlit_color = mix(unlit_color, fog / (fog + vec3<f32>(1.0)), fog_amount);
+        }
+
+        var unlit_out: FragmentOutput;
+        unlit_out.color = vec4<f32>(pow(unlit_color, vec3<f32>(1.0 / 2.2)), alpha);
+        unlit_out.velocity = motionVector(in.curr_clip, in.prev_clip);
+        unlit_out.luminance = vec2<f32>(0.0); // Not exposed, so left out of the average
+        return unlit_out;
+    }
     
     // Metallic/Roughness (Packed: G=Roughness, B=Metallic)
     let mr_sample = textureSample(t_metallic_roughness, s_metallic_roughness, in.uv);
@@ -179,8 +295,15 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     let roughness = mr_sample.g * material.roughness; 
     let metallic = mr_sample.b * material.metallic;
 
-    // Normals
-    let N = getNormalFromMap(in.uv, in.world_pos, in.normal);
+    // Normals (back faces of double sided materials face the viewer too)
+    var geometry_normal = normalize(in.normal);
+    if (DOUBLE_SIDED && !front_facing) {
+        geometry_normal = -geometry_normal;
+    }
+    var N = geometry_normal;
+    if (NORMAL_MAP) {
+        N = getNormalFromMap(in.uv, in.world_pos, geometry_normal);
+    }
     let V = normalize(scene_data.camera_pos - in.world_pos);
 
     // F0 setup
@@ -212,38 +335,27 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     }
 
     // --- 3. POINT LIGHTS ---
-    for (var i = 0u; i < scene_data.active_lights; i++) {
-        let light = scene_data.lights[i];
-        let light_pos = light.position.xyz;
-        let light_intensity = light.position.w;
-        let light_color = light.color.rgb;
-
-        let dist = length(light_pos - in.world_pos);
-        let L = normalize(light_pos - in.world_pos);
-        let H = normalize(V + L);
-        
-        let attenuation = 1.0 / (dist * dist);
-        let radiance = light_color * light_intensity * attenuation;
-
-        // Cook-Torrance
-        let NDF = DistributionGGX(N, H, roughness);
-        let G = GeometrySmith(N, V, L, roughness);
-        let F = fresnelSchlick(max(dot(H, V), 0.0), F0);
+    // Defined by the lighting mode composed with this file: lights_clustered.wgsl or lights_uniform.wgsl
+    Lo += lights_radiance(in.clip_position.xy, in.world_pos, N, V, albedo, roughness, metallic, F0);
 
-        let numerator = NDF * G * F;
-        let denominator = 4.0 * max(dot(N, V), 0.0) * max(dot(N, L), 0.0) + 0.0001;
-        let specular = numerator / denominator;
-
-        let kS = F;
-        let kD = (vec3<f32>(1.0) - kS) * (1.0 - metallic);
-        let NdotL = max(dot(N, L), 0.0);
+    // --- 4. AMBIENT, REFLECTIONS & OUTPUT ---
+    let ambient = vec3<f32>(0.03) * albedo * ao;
+    var color = ambient + Lo + material.emissive.rgb;
 
-        Lo += (kD * albedo / PI + specular) * radiance * NdotL;
+    // Inside a reflection probe's box, what it captured. Outside there is nothing to reflect.
+    let probe = probe_specular(in.world_pos, reflect(-V, N), roughness);
+    if (probe.a > 0.0) {
+        let NdotV = max(dot(N, V), 0.0);
+        color += probe.rgb * env_brdf_approx(F0, roughness, NdotV) * probe.a * ao;
     }
 
-    // --- 4. AMBIENT & OUTPUT ---
-    let ambient = vec3<f32>(0.03) * albedo * ao;
-    var color = ambient + Lo;
+    // Before exposure, so auto exposure sees the fog like the rest of the scene
+    color = apply_fog(scene_data, color, in.world_pos);
+
+    // Measured before exposure, so auto exposure doesn't chase its own output
+    let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+    let log_luminance = log2(max(luminance, 0.0001));
+    color *= camera.exposure;
 
     // --- STEP 5: TONE MAPPING (Reinhard) ---
     // Maps High Dynamic Range (HDR) values (e.g. 10.0) down to 0.0 - 1.0 range
@@ -256,5 +368,9 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     // and do this manually.
     color = pow(color, vec3<f32>(1.0 / 2.2));
 
-    return vec4<f32>(color, 1.0);
+    var out: FragmentOutput;
+    out.color = vec4<f32>(color, alpha);
+    out.velocity = motionVector(in.curr_clip, in.prev_clip);
+    out.luminance = vec2<f32>(log_luminance, 1.0);
+    return out;
 }
\ No newline at end of file
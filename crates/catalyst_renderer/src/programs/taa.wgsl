@@ -0,0 +1,76 @@
+// ========================================================================
+//  TAA RESOLVE
+//  Blends the jittered current frame with the reprojected history.
+// ========================================================================
+
+struct TaaUniforms {
+    blend_factor: f32, // Weight of the current frame (lower = smoother, more ghosting)
+    reset: u32,        // 1 = ignore history (first frame, just toggled on)
+    uv_scale: vec2<f32>, // Part of the targets the scene covers (dynamic resolution)
+};
+
+@group(0) @binding(0) var t_current: texture_2d<f32>;
+@group(0) @binding(1) var t_history: texture_2d<f32>;
+@group(0) @binding(2) var t_velocity: texture_2d<f32>;
+@group(0) @binding(3) var s_linear: sampler;
+@group(0) @binding(4) var<uniform> taa: TaaUniforms;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,   // To the screen
+    @location(1) history: vec4<f32>, // Next frame's history
+};
+
+// Fullscreen triangle, no vertex buffer
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    let size = vec2<i32>(textureDimensions(t_current));
+    let pixel = vec2<i32>(in.position.xy);
+
+    let current = textureLoad(t_current, pixel, 0).rgb;
+
+    // Neighborhood clamping: history may only contain colors that could plausibly
+    // be at this pixel now, anything else is a disoccluded/stale sample (ghosting).
+    var color_min = current;
+    var color_max = current;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let coord = clamp(pixel + vec2<i32>(x, y), vec2<i32>(0), size - vec2<i32>(1));
+            let neighbor = textureLoad(t_current, coord, 0).rgb;
+            color_min = min(color_min, neighbor);
+            color_max = max(color_max, neighbor);
+        }
+    }
+
+    // Velocity is in UVs of the drawn area, in.uv in UVs of the whole target
+    let velocity = textureLoad(t_velocity, pixel, 0).xy * taa.uv_scale;
+    let prev_uv = in.uv - velocity;
+
+    var blend = taa.blend_factor;
+    let off_screen = any(prev_uv < vec2<f32>(0.0)) || any(prev_uv > taa.uv_scale);
+    if (taa.reset != 0u || off_screen) {
+        blend = 1.0;
+    }
+
+    let history = clamp(textureSample(t_history, s_linear, prev_uv).rgb, color_min, color_max);
+    let resolved = vec4<f32>(mix(history, current, blend), 1.0);
+
+    var out: FragmentOutput;
+    out.color = resolved;
+    out.history = resolved;
+    return out;
+}
@@ -0,0 +1,100 @@
+use wgpu::RenderPipeline;
+
+use crate::{
+    layout::{uniform_layouts, validate_program_layouts},
+    programs::{
+        GpuProgram, GpuProgramRenderContext,
+        reflected::{self, WGSL_TAA_UNIFORMS},
+    },
+    taa::TaaUniform,
+};
+
+uniform_layouts!(TaaUniform::LAYOUT => WGSL_TAA_UNIFORMS);
+
+/// Fullscreen resolve pass: current frame + reprojected history -> screen and new history.
+pub struct TaaProgram {
+    pipeline: RenderPipeline,
+    pub layout: wgpu::BindGroupLayout,
+    pub sampler: wgpu::Sampler,
+}
+
+impl GpuProgram for TaaProgram {
+    type InitData = ();
+    type DrawData<'a> = &'a wgpu::BindGroup;
+
+    fn new(ctx: &GpuProgramRenderContext, _init_data: &Self::InitData) -> Self {
+        validate_program_layouts("TaaProgram", UNIFORM_LAYOUTS);
+
+        let shader = ctx
+            .device
+            .create_shader_module(wgpu::include_wgsl!("taa.wgsl"));
+
+        let layout = ctx
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("TAA Bind Group Layout"),
+                // Current frame, history, velocity, sampler and settings
+                entries: reflected::taa::GROUP_0,
+            });
+
+        let sampler = ctx.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("TAA History Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("TAA Pipeline Layout"),
+                bind_group_layouts: &[&layout],
+                push_constant_ranges: &[],
+            });
+
+        let target = Some(wgpu::ColorTargetState {
+            format: ctx.format,
+            blend: None,
+            write_mask: wgpu::ColorWrites::ALL,
+        });
+
+        let pipeline = ctx
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                cache: None,
+                label: Some("TAA Resolve Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    compilation_options: Default::default(),
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    compilation_options: Default::default(),
+                    // Screen + next history, both in the surface format
+                    targets: &[target.clone(), target],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            });
+
+        Self {
+            pipeline,
+            layout,
+            sampler,
+        }
+    }
+
+    fn record<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, bind_group: Self::DrawData<'a>) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
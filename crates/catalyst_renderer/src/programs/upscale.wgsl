@@ -0,0 +1,39 @@
+// ========================================================================
+//  UPSCALE
+//  Stretches the scene, drawn into the top left part of its targets at the
+//  dynamic resolution scale, over the whole screen. One entry point per
+//  UpscaleFilter.
+// ========================================================================
+
+struct UpscaleUniforms {
+    uv_scale: vec2<f32>, // Part of the source the scene covers
+    padding: vec2<f32>,
+};
+
+@group(0) @binding(0) var t_source: texture_2d<f32>;
+@group(0) @binding(1) var s_linear: sampler;
+@group(0) @binding(2) var<uniform> params: UpscaleUniforms;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// Fullscreen triangle, no vertex buffer
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+@fragment
+fn fs_bilinear(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Half a texel in from the edge of the drawn area, the rest of the source is stale
+    let half_texel = 0.5 / vec2<f32>(textureDimensions(t_source));
+    let uv = clamp(in.uv * params.uv_scale, half_texel, params.uv_scale - half_texel);
+    return vec4<f32>(textureSampleLevel(t_source, s_linear, uv, 0.0).rgb, 1.0);
+}
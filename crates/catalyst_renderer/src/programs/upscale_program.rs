@@ -0,0 +1,175 @@
+use glam::Vec2;
+use wgpu::RenderPipeline;
+
+use crate::{
+    dynamic_resolution::UpscaleFilter,
+    layout::{StructLayout, rust_layout, uniform_layouts, validate_program_layouts},
+    programs::{
+        GpuProgram, GpuProgramRenderContext,
+        reflected::{self, WGSL_UPSCALE_UNIFORMS},
+    },
+};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct UpscaleUniform {
+    pub uv_scale: [f32; 2],
+    pub _padding: [f32; 2],
+}
+
+const _: () = assert!(std::mem::size_of::<UpscaleUniform>() == 16);
+
+impl UpscaleUniform {
+    pub const LAYOUT: StructLayout = rust_layout!(UpscaleUniform {
+        uv_scale: [f32; 2],
+        _padding: [f32; 2],
+    });
+}
+
+uniform_layouts!(UpscaleUniform::LAYOUT => WGSL_UPSCALE_UNIFORMS);
+
+// Fragment entry point of each filter in upscale.wgsl
+fn entry_point(filter: UpscaleFilter) -> &'static str {
+    match filter {
+        UpscaleFilter::Bilinear => "fs_bilinear",
+    }
+}
+
+const FILTERS: [UpscaleFilter; 1] = [UpscaleFilter::Bilinear];
+
+/// Fullscreen pass stretching the dynamic resolution scene to the screen, one
+/// pipeline per UpscaleFilter.
+pub struct UpscaleProgram {
+    pipelines: Vec<(UpscaleFilter, RenderPipeline)>,
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    uniform_buffer: wgpu::Buffer,
+}
+
+impl UpscaleProgram {
+    pub fn write_uniform(&self, queue: &wgpu::Queue, uv_scale: Vec2) {
+        let uniform = UpscaleUniform {
+            uv_scale: uv_scale.to_array(),
+            _padding: [0.0; 2],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+
+    /// Bind group reading `source`, a frame graph view, so it is built per frame.
+    pub fn bind_group(&self, device: &wgpu::Device, source: &wgpu::TextureView) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Upscale Bind Group"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(source),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+}
+
+impl GpuProgram for UpscaleProgram {
+    type InitData = ();
+    type DrawData<'a> = (&'a wgpu::BindGroup, UpscaleFilter);
+
+    fn new(ctx: &GpuProgramRenderContext, _init_data: &Self::InitData) -> Self {
+        validate_program_layouts("UpscaleProgram", UNIFORM_LAYOUTS);
+
+        let shader = ctx
+            .device
+            .create_shader_module(wgpu::include_wgsl!("upscale.wgsl"));
+
+        let layout = ctx
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Upscale Bind Group Layout"),
+                // Scaled scene, sampler and settings
+                entries: reflected::upscale::GROUP_0,
+            });
+
+        let sampler = ctx.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Upscale Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let uniform_buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Upscale Uniform Buffer"),
+            size: std::mem::size_of::<UpscaleUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Upscale Pipeline Layout"),
+                bind_group_layouts: &[&layout],
+                push_constant_ranges: &[],
+            });
+
+        let pipelines = FILTERS
+            .iter()
+            .map(|&filter| {
+                let pipeline = ctx
+                    .device
+                    .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                        cache: None,
+                        label: Some("Upscale Pipeline"),
+                        layout: Some(&pipeline_layout),
+                        vertex: wgpu::VertexState {
+                            module: &shader,
+                            entry_point: Some("vs_main"),
+                            compilation_options: Default::default(),
+                            buffers: &[],
+                        },
+                        fragment: Some(wgpu::FragmentState {
+                            module: &shader,
+                            entry_point: Some(entry_point(filter)),
+                            compilation_options: Default::default(),
+                            targets: &[Some(wgpu::ColorTargetState {
+                                format: ctx.format,
+                                blend: None,
+                                write_mask: wgpu::ColorWrites::ALL,
+                            })],
+                        }),
+                        primitive: wgpu::PrimitiveState::default(),
+                        depth_stencil: None,
+                        multisample: wgpu::MultisampleState::default(),
+                        multiview: None,
+                    });
+                (filter, pipeline)
+            })
+            .collect();
+
+        Self {
+            pipelines,
+            layout,
+            sampler,
+            uniform_buffer,
+        }
+    }
+
+    fn record<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, data: Self::DrawData<'a>) {
+        let (bind_group, filter) = data;
+        let Some((_, pipeline)) = self.pipelines.iter().find(|(f, _)| *f == filter) else {
+            return;
+        };
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,739 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use catalyst_assets::{
+    MeshDefinition,
+    asset_events::{AssetChanged, AssetEvent},
+    asset_server::AssetServer,
+    assets::{Handle, MeshData},
+};
+use catalyst_core::{
+    App,
+    bounds::{Aabb, Ray},
+    raycast::{RayHit, RaycastBackend, RaycastFilter, Raycasters},
+    transform::GlobalTransform,
+};
+use flecs_ecs::prelude::*;
+use glam::{Mat4, Vec2, Vec3};
+
+// Triangles per leaf; below this splitting costs more box tests than it saves
+const LEAF_TRIANGLES: usize = 4;
+
+/// Bounding volume hierarchy over a mesh's triangles, for `raycast_render`.
+///
+/// Built on the compute pool the first time a ray reaches the mesh and kept on the mesh
+/// asset entity next to its MeshData; until it arrives the mesh's triangles are tested
+/// one by one. Dropped when the MeshData is set again or changed by Handle::modify.
+#[derive(Component, Debug)]
+pub struct MeshBvh {
+    nodes: Vec<BvhNode>,
+    // Triangle indices (index / 3), in leaf order
+    triangles: Vec<u32>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct BvhNode {
+    bounds: Aabb,
+    // A leaf holds triangles[first..first + count]; a node with count 0 has its
+    // children at nodes[first] and nodes[first + 1]
+    first: u32,
+    count: u32,
+}
+
+impl MeshBvh {
+    pub fn build(mesh: &MeshData) -> Self {
+        let positions: Vec<Vec3> = mesh
+            .vertices
+            .iter()
+            .map(|vertex| Vec3::from_array(vertex.position))
+            .collect();
+        Self::from_triangles(&positions, &mesh.indices)
+    }
+
+    /// Triangles with an index out of range are left out.
+    pub fn from_triangles(positions: &[Vec3], indices: &[u32]) -> Self {
+        let mut triangles = Vec::new();
+        let mut bounds = Vec::new();
+        let mut centroids = Vec::new();
+        for (triangle, corners) in indices.chunks_exact(3).enumerate() {
+            let Some(points) = corners
+                .iter()
+                .map(|&index| positions.get(index as usize).copied())
+                .collect::<Option<Vec<Vec3>>>()
+            else {
+                continue;
+            };
+            triangles.push(triangle as u32);
+            bounds.push(Aabb::from_points(points.iter().copied()).unwrap());
+            centroids.push((points[0] + points[1] + points[2]) / 3.0);
+        }
+        if triangles.is_empty() {
+            return Self {
+                nodes: Vec::new(),
+                triangles,
+            };
+        }
+
+        // Slots into bounds and centroids while building, triangle indices at the end
+        let mut order: Vec<u32> = (0..triangles.len() as u32).collect();
+        let fit = |slots: &[u32]| {
+            slots
+                .iter()
+                .map(|&slot| bounds[slot as usize])
+                .reduce(|a, b| a.merge(&b))
+                .unwrap()
+        };
+        let mut nodes = vec![BvhNode {
+            bounds: fit(&order),
+            first: 0,
+            count: order.len() as u32,
+        }];
+
+        // Median split on the longest axis of the centroids
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let BvhNode { first, count, .. } = nodes[index];
+            if count as usize <= LEAF_TRIANGLES {
+                continue;
+            }
+            let slots = &mut order[first as usize..(first + count) as usize];
+            let spread = Aabb::from_points(slots.iter().map(|&slot| centroids[slot as usize]))
+                .unwrap()
+                .size();
+            let axis = if spread.x >= spread.y && spread.x >= spread.z {
+                0
+            } else if spread.y >= spread.z {
+                1
+            } else {
+                2
+            };
+            let middle = slots.len() / 2;
+            slots.select_nth_unstable_by(middle, |a, b| {
+                centroids[*a as usize][axis].total_cmp(&centroids[*b as usize][axis])
+            });
+
+            let (left, right) = slots.split_at(middle);
+            let children = [
+                BvhNode {
+                    bounds: fit(left),
+                    first,
+                    count: middle as u32,
+                },
+                BvhNode {
+                    bounds: fit(right),
+                    first: first + middle as u32,
+                    count: count - middle as u32,
+                },
+            ];
+            let child = nodes.len();
+            nodes.extend(children);
+            nodes[index].first = child as u32;
+            nodes[index].count = 0;
+            stack.extend([child, child + 1]);
+        }
+
+        Self {
+            nodes,
+            triangles: order.iter().map(|&slot| triangles[slot as usize]).collect(),
+        }
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// The nearest triangle of `mesh` hit by `ray` (in the mesh's space) and the
+    /// distance to it, up to `max_distance`.
+    pub fn intersect(&self, mesh: &MeshData, ray: &Ray, max_distance: f32) -> Option<(u32, f32)> {
+        let mut nearest: Option<(u32, f32)> = None;
+        let reach = |nearest: &Option<(u32, f32)>| nearest.map_or(max_distance, |(_, d)| d);
+
+        let mut stack = Vec::with_capacity(32);
+        stack.extend((!self.nodes.is_empty()).then_some(0));
+        while let Some(index) = stack.pop() {
+            let node = self.nodes[index];
+            if ray
+                .intersect_aabb(&node.bounds)
+                .is_none_or(|entry| entry > reach(&nearest))
+            {
+                continue;
+            }
+            if node.count == 0 {
+                stack.extend([node.first as usize, node.first as usize + 1]);
+                continue;
+            }
+            let leaf = &self.triangles[node.first as usize..(node.first + node.count) as usize];
+            for &triangle in leaf {
+                let distance = intersect_triangle(mesh, ray, triangle);
+                if let Some(distance) = distance.filter(|d| *d <= reach(&nearest)) {
+                    nearest = Some((triangle, distance));
+                }
+            }
+        }
+        nearest
+    }
+}
+
+/// BVH builds in flight, in the singleton of the same name. A build is only kept if
+/// its mesh wasn't set again while it ran.
+#[derive(Component, Default)]
+pub struct MeshBvhBuilds {
+    // Mesh asset entity -> ticket of its build
+    pending: HashMap<Entity, u64>,
+    next_ticket: u64,
+    finished: Arc<Mutex<Vec<(Entity, u64, MeshBvh)>>>,
+}
+
+impl MeshBvhBuilds {
+    /// Builds running or waiting to be cached.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    // Copies the triangles and builds on the compute pool, unless already building
+    fn request(&mut self, entity: Entity, mesh: &MeshData) {
+        if self.pending.contains_key(&entity) {
+            return;
+        }
+        let ticket = self.next_ticket;
+        self.next_ticket += 1;
+        self.pending.insert(entity, ticket);
+
+        let positions: Vec<Vec3> = mesh
+            .vertices
+            .iter()
+            .map(|vertex| Vec3::from_array(vertex.position))
+            .collect();
+        let indices = mesh.indices.clone();
+        let finished = self.finished.clone();
+        rayon::spawn(move || {
+            catalyst_core::profile_scope!("Mesh BVH");
+            let bvh = MeshBvh::from_triangles(&positions, &indices);
+            finished.lock().unwrap().push((entity, ticket, bvh));
+        });
+    }
+}
+
+/// The nearest mesh `direction` from `origin` hits, tested against the triangles of
+/// every entity with a MeshDefinition and a GlobalTransform, drawn or not. Meshes are
+/// tested in their authored pose, without wind or LODs. See `raycast_render_all`.
+pub fn raycast_render(
+    world: &WorldRef,
+    origin: Vec3,
+    direction: Vec3,
+    filter: &RaycastFilter,
+) -> Option<RayHit> {
+    cast(world, &Ray::new(origin, direction), filter, true)
+        .into_iter()
+        .next()
+}
+
+/// Every mesh the ray hits, nearest first, with the nearest hit on each.
+pub fn raycast_render_all(
+    world: &WorldRef,
+    origin: Vec3,
+    direction: Vec3,
+    filter: &RaycastFilter,
+) -> Vec<RayHit> {
+    cast(world, &Ray::new(origin, direction), filter, false)
+}
+
+struct Candidate {
+    entity: Entity,
+    mesh: Entity,
+    model: Mat4,
+    // Where the ray enters the world bounds
+    entry: f32,
+}
+
+fn cast(world: &WorldRef, ray: &Ray, filter: &RaycastFilter, nearest_only: bool) -> Vec<RayHit> {
+    if ray.direction == Vec3::ZERO {
+        return Vec::new();
+    }
+
+    // Broad phase: the mesh bounds, moved to where each entity is
+    let mut candidates = Vec::new();
+    world
+        .query::<(&MeshDefinition, &GlobalTransform)>()
+        .build()
+        .each_entity(|entity, (definition, global)| {
+            // Scaled flat, there is nothing to hit
+            if global.0.determinant().abs() <= f32::EPSILON {
+                return;
+            }
+            let Some(mesh) = definition.0.try_get_entity(world) else {
+                return;
+            };
+            let Some(bounds) = mesh.try_get::<&MeshData>(|data| data.aabb) else {
+                return;
+            };
+            let entry = ray.intersect_aabb(&bounds.transform(&global.0));
+            if let Some(entry) = entry.filter(|entry| filter.allows(entity.id(), *entry)) {
+                candidates.push(Candidate {
+                    entity: entity.id(),
+                    mesh: mesh.id(),
+                    model: global.0,
+                    entry,
+                });
+            }
+        });
+    candidates.sort_by(|a, b| a.entry.total_cmp(&b.entry));
+
+    let mut hits: Vec<RayHit> = Vec::new();
+    let mut missing = Vec::new();
+    for candidate in candidates {
+        // Nothing behind the nearest hit can be nearer
+        if nearest_only
+            && hits
+                .first()
+                .is_some_and(|hit| hit.distance < candidate.entry)
+        {
+            break;
+        }
+
+        let inverse = candidate.model.inverse();
+        let local = Ray::new(
+            inverse.transform_point3(ray.origin),
+            inverse.transform_vector3(ray.direction),
+        );
+        let nearest = |mesh: &MeshData, bvh: Option<&MeshBvh>| {
+            let hit = match bvh {
+                Some(bvh) => bvh.intersect(mesh, &local, f32::INFINITY),
+                None => (0..(mesh.indices.len() / 3) as u32)
+                    .filter_map(|triangle| {
+                        intersect_triangle(mesh, &local, triangle).map(|d| (triangle, d))
+                    })
+                    .min_by(|a, b| a.1.total_cmp(&b.1)),
+            };
+            hit.map(|(triangle, distance)| {
+                surface_hit(
+                    candidate.entity,
+                    mesh,
+                    &candidate.model,
+                    ray,
+                    &local,
+                    triangle,
+                    distance,
+                )
+            })
+        };
+
+        let mesh = world.entity_from_id(candidate.mesh);
+        let hit = if mesh.has(MeshBvh::id()) {
+            mesh.try_get::<(&MeshData, &MeshBvh)>(|(data, bvh)| nearest(data, Some(bvh)))
+        } else {
+            missing.push(candidate.mesh);
+            mesh.try_get::<&MeshData>(|data| nearest(data, None))
+        };
+        if let Some(hit) = hit
+            .flatten()
+            .filter(|hit| filter.allows(hit.entity, hit.distance))
+        {
+            hits.push(hit);
+            hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        }
+    }
+
+    // Meshes tested triangle by triangle get their BVH for next time
+    if !missing.is_empty() {
+        world.try_get::<&mut MeshBvhBuilds>(|builds| {
+            for mesh in missing {
+                world
+                    .entity_from_id(mesh)
+                    .try_get::<&MeshData>(|data| builds.request(mesh, data));
+            }
+        });
+    }
+
+    if nearest_only {
+        hits.truncate(1);
+    }
+    hits
+}
+
+fn triangle_corners(mesh: &MeshData, triangle: u32) -> Option<[usize; 3]> {
+    let start = triangle as usize * 3;
+    let corners = mesh.indices.get(start..start + 3)?;
+    let corners = [
+        corners[0] as usize,
+        corners[1] as usize,
+        corners[2] as usize,
+    ];
+    corners
+        .iter()
+        .all(|&index| index < mesh.vertices.len())
+        .then_some(corners)
+}
+
+fn intersect_triangle(mesh: &MeshData, ray: &Ray, triangle: u32) -> Option<f32> {
+    let [a, b, c] = triangle_corners(mesh, triangle)?
+        .map(|index| Vec3::from_array(mesh.vertices[index].position));
+    ray.intersect_triangle(a, b, c)
+}
+
+// The hit on `triangle`, `local_distance` along `local` (the ray in the mesh's space)
+fn surface_hit(
+    entity: Entity,
+    mesh: &MeshData,
+    model: &Mat4,
+    ray: &Ray,
+    local: &Ray,
+    triangle: u32,
+    local_distance: f32,
+) -> RayHit {
+    let corners = triangle_corners(mesh, triangle).expect("intersected triangle");
+    let [a, b, c] = corners.map(|index| Vec3::from_array(mesh.vertices[index].position));
+    let [uv_a, uv_b, uv_c] = corners.map(|index| Vec2::from_array(mesh.vertices[index].uv));
+
+    let local_position = local.at(local_distance);
+    let position = model.transform_point3(local_position);
+
+    // Barycentric weights of the hit
+    let (edge1, edge2, offset) = (b - a, c - a, local_position - a);
+    let (d11, d12, d22) = (edge1.dot(edge1), edge1.dot(edge2), edge2.dot(edge2));
+    let (d31, d32) = (offset.dot(edge1), offset.dot(edge2));
+    let denominator = d11 * d22 - d12 * d12;
+    let (v, w) = if denominator.abs() > f32::EPSILON {
+        (
+            (d22 * d31 - d12 * d32) / denominator,
+            (d11 * d32 - d12 * d31) / denominator,
+        )
+    } else {
+        (0.0, 0.0)
+    };
+    let uv = uv_a * (1.0 - v - w) + uv_b * v + uv_c * w;
+
+    // The face's normal, so decals sit flat on it even where the mesh is smooth shaded
+    let normal_matrix = model.inverse().transpose();
+    let mut normal = normal_matrix
+        .transform_vector3(edge1.cross(edge2))
+        .normalize_or_zero();
+    if normal.dot(ray.direction) > 0.0 {
+        normal = -normal;
+    }
+
+    // Where U grows along the face, from how the UVs change along its edges
+    let (duv1, duv2) = (uv_b - uv_a, uv_c - uv_a);
+    let determinant = duv1.x * duv2.y - duv2.x * duv1.y;
+    let tangent = (determinant.abs() > f32::EPSILON)
+        .then(|| model.transform_vector3((edge1 * duv2.y - edge2 * duv1.y) / determinant))
+        .map(|tangent| (tangent - normal * normal.dot(tangent)).normalize_or_zero())
+        .filter(|tangent| *tangent != Vec3::ZERO);
+
+    RayHit {
+        entity,
+        distance: ray.origin.distance(position),
+        position,
+        normal,
+        triangle: Some(triangle),
+        uv: Some(uv),
+        tangent,
+    }
+}
+
+fn render_raycaster(world: &WorldRef, ray: &Ray, filter: &RaycastFilter) -> Option<RayHit> {
+    raycast_render(world, ray.origin, ray.direction, filter)
+}
+
+pub fn register_raycast_systems(app: &mut App) {
+    app.register_singleton_default::<MeshBvhBuilds>();
+    app.world.get::<&mut Raycasters>(|raycasters| {
+        raycasters.register(RaycastBackend::Render, render_raycaster);
+    });
+
+    // A mesh set again or modified needs a new BVH; one still building for it is dropped
+    app.world
+        .observer::<flecs::OnSet, &MeshData>()
+        .each_entity(|entity, _| drop_bvh(entity));
+    app.world
+        .entity_from::<AssetServer>()
+        .observe_payload_entity::<&AssetChanged>(|server, AssetChanged(event)| {
+            let AssetEvent::MeshModified { id } = *event;
+            let world = server.world();
+            if let Some(mesh) = Handle::<MeshData>::from_id(id).try_get_entity(&world) {
+                drop_bvh(mesh);
+            }
+        });
+
+    app.world
+        .system_named::<&mut MeshBvhBuilds>("Cache Mesh BVHs")
+        .kind(flecs::pipeline::PreUpdate)
+        .each_iter(|iter, _, builds| {
+            let world = iter.world();
+            let finished = std::mem::take(&mut *builds.finished.lock().unwrap());
+            for (entity, ticket, bvh) in finished {
+                if builds.pending.get(&entity) != Some(&ticket) {
+                    continue;
+                }
+                builds.pending.remove(&entity);
+                let mesh = world.entity_from_id(entity);
+                if mesh.is_alive() && mesh.has(MeshData::id()) {
+                    mesh.set(bvh);
+                }
+            }
+        });
+}
+
+fn drop_bvh(mesh: EntityView) {
+    mesh.remove(MeshBvh::id());
+    mesh.world()
+        .try_get::<&mut MeshBvhBuilds>(|builds| builds.pending.remove(&mesh.id()));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use catalyst_assets::{AssetPlugin, asset_events::AssetLookup, primitives};
+    use catalyst_core::{raycast::raycast, transform::Transform};
+    use glam::Quat;
+
+    use super::*;
+    use crate::Decal;
+
+    const CUBE_SIZE: f32 = 2.0;
+
+    // The cube's faces, in primitives::cube order
+    const FACES: [Vec3; 6] = [
+        Vec3::X,
+        Vec3::NEG_X,
+        Vec3::Y,
+        Vec3::NEG_Y,
+        Vec3::Z,
+        Vec3::NEG_Z,
+    ];
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugin(AssetPlugin);
+        register_raycast_systems(&mut app);
+        app
+    }
+
+    fn update_until(app: &mut App, done: impl Fn(&App) -> bool) -> bool {
+        for _ in 0..200 {
+            app.update();
+            if done(app) {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    fn add_cube(app: &mut App) -> (Handle<MeshData>, Entity) {
+        let handle = app
+            .world
+            .get::<&AssetServer>(|server| server.add_mesh("cube", primitives::cube(CUBE_SIZE)));
+        let entity = app
+            .world
+            .get::<&mut AssetLookup>(|lookup| lookup.entity(handle.id, &app.world));
+        assert!(update_until(app, |app| app
+            .world
+            .entity_from_id(entity)
+            .has(MeshData::id())));
+        (handle, entity)
+    }
+
+    fn spawn(app: &App, mesh: &Handle<MeshData>, transform: Transform) -> Entity {
+        app.world
+            .entity()
+            .set(transform)
+            .set(GlobalTransform(transform.compute_matrix()))
+            .set(MeshDefinition(mesh.clone()))
+            .id()
+    }
+
+    fn cast(app: &App, origin: Vec3, direction: Vec3) -> Option<RayHit> {
+        raycast_render(
+            &app.world.world(),
+            origin,
+            direction,
+            &RaycastFilter::default(),
+        )
+    }
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!(a.distance(b) < 1e-4, "{} is not {}", a, b);
+    }
+
+    // Casts at the center of each face of the cube at `transform` from 4 away, and
+    // checks it hits that face with its normal, UV center and one of its triangles
+    fn assert_face_centers_hit(app: &App, entity: Entity, transform: &Transform) {
+        for (face, direction) in FACES.iter().enumerate() {
+            let center = transform
+                .compute_matrix()
+                .transform_point3(*direction * CUBE_SIZE * 0.5);
+            let outward = transform.rotation * *direction;
+            let hit = cast(app, center + outward * 4.0, -outward).expect("face missed");
+            assert_eq!(hit.entity, entity);
+            assert!((hit.distance - 4.0).abs() < 1e-4);
+            assert_near(hit.position, center);
+            assert_near(hit.normal, outward);
+            assert!(
+                hit.uv
+                    .is_some_and(|uv| uv.distance(Vec2::splat(0.5)) < 1e-4)
+            );
+            assert_eq!(hit.triangle.map(|triangle| triangle / 2), Some(face as u32));
+        }
+    }
+
+    #[test]
+    fn face_centers_are_hit_before_and_after_the_bvh_is_built() {
+        let mut app = app();
+        let (cube, cube_asset) = add_cube(&mut app);
+        let plain = Transform::from_xyz(0.0, 0.0, -10.0);
+        let entity = spawn(&app, &cube, plain);
+
+        // The first rays test the triangles one by one and start the build
+        assert_face_centers_hit(&app, entity, &plain);
+        assert!(!app.world.entity_from_id(cube_asset).has(MeshBvh::id()));
+        assert!(update_until(&mut app, |app| {
+            app.world.entity_from_id(cube_asset).has(MeshBvh::id())
+        }));
+        assert_face_centers_hit(&app, entity, &plain);
+    }
+
+    #[test]
+    fn a_turned_cube_is_hit_at_its_face_centers() {
+        let mut app = app();
+        let (cube, _) = add_cube(&mut app);
+        let turned = Transform {
+            translation: Vec3::new(3.0, 1.0, -8.0),
+            rotation: Quat::from_euler(glam::EulerRot::YXZ, 0.7, 0.3, 0.0),
+            scale: Vec3::ONE,
+        };
+        let entity = spawn(&app, &cube, turned);
+        assert_face_centers_hit(&app, entity, &turned);
+    }
+
+    #[test]
+    fn a_scaled_cube_is_hit_at_its_world_distance() {
+        let mut app = app();
+        let (cube, _) = add_cube(&mut app);
+        let scaled = Transform {
+            scale: Vec3::splat(3.0),
+            ..Transform::from_xyz(0.0, 0.0, -10.0)
+        };
+        let entity = spawn(&app, &cube, scaled);
+
+        let hit = cast(&app, Vec3::new(0.0, 0.0, 5.0), Vec3::NEG_Z).expect("cube missed");
+        assert_eq!(hit.entity, entity);
+        assert!((hit.distance - 12.0).abs() < 1e-4);
+        assert_near(hit.normal, Vec3::Z);
+        // The face's U direction
+        assert_near(hit.tangent.expect("no tangent"), Vec3::X);
+
+        // A decal at the hit projects into the surface, lined up with its texture
+        let decal = Decal::transform_at(&hit, 0.0);
+        assert_near(decal.translation, Vec3::new(0.0, 0.0, -7.0));
+        assert_near(decal.rotation * Vec3::NEG_Y, Vec3::NEG_Z);
+        assert_near(decal.rotation * Vec3::X, Vec3::X);
+    }
+
+    #[test]
+    fn overlapping_meshes_come_back_nearest_first_and_respect_the_filter() {
+        let mut app = app();
+        let (cube, _) = add_cube(&mut app);
+        let far = spawn(&app, &cube, Transform::from_xyz(0.0, 0.0, -20.0));
+        let middle = spawn(&app, &cube, Transform::from_xyz(0.5, 0.0, -11.0));
+        let front = spawn(&app, &cube, Transform::from_xyz(0.0, 0.5, -10.0));
+        let world = app.world.world();
+
+        let all = raycast_render_all(&world, Vec3::ZERO, Vec3::NEG_Z, &RaycastFilter::default());
+        let entities: Vec<Entity> = all.iter().map(|hit| hit.entity).collect();
+        assert_eq!(entities, [front, middle, far]);
+        assert!(
+            all.windows(2)
+                .all(|pair| pair[0].distance <= pair[1].distance)
+        );
+
+        let nearest = cast(&app, Vec3::ZERO, Vec3::NEG_Z).expect("cubes missed");
+        assert_eq!(nearest.entity, front);
+        assert!((nearest.distance - 9.0).abs() < 1e-4);
+
+        let behind = raycast_render(
+            &world,
+            Vec3::ZERO,
+            Vec3::NEG_Z,
+            &RaycastFilter::default().exclude(front),
+        );
+        assert_eq!(behind.map(|hit| hit.entity), Some(middle));
+
+        let short = RaycastFilter::default().max_distance(8.0);
+        assert!(raycast_render(&world, Vec3::ZERO, Vec3::NEG_Z, &short).is_none());
+    }
+
+    #[test]
+    fn raycast_answers_through_the_render_backend() {
+        let mut app = app();
+        let (cube, _) = add_cube(&mut app);
+        let front = spawn(&app, &cube, Transform::from_xyz(0.0, 0.0, -10.0));
+        let world = app.world.world();
+        let ray = Ray::new(Vec3::ZERO, Vec3::NEG_Z);
+        let filter = RaycastFilter::default();
+
+        let hit = raycast(&world, &ray, &filter, RaycastBackend::Render);
+        assert_eq!(hit.map(|hit| hit.entity), Some(front));
+        // No physics plugin registered
+        assert!(raycast(&world, &ray, &filter, RaycastBackend::Physics).is_none());
+    }
+
+    #[test]
+    fn the_bvh_finds_the_same_triangles_as_testing_every_one() {
+        let capsule = primitives::capsule(0.5, 1.0, 24, 8);
+        let bvh = MeshBvh::build(&capsule);
+        assert_eq!(bvh.triangle_count(), capsule.indices.len() / 3);
+
+        let rays = 500;
+        for index in 0..rays {
+            let angle = index as f32 * 2.399;
+            let height = (index as f32 / rays as f32) * 2.4 - 1.2;
+            let origin = Vec3::new(angle.cos() * 3.0, height, angle.sin() * 3.0);
+            let target = Vec3::new((angle * 3.1).sin() * 0.3, height * 0.8, 0.0);
+            let ray = Ray::new(origin, target - origin);
+
+            let expected = (0..bvh.triangle_count() as u32)
+                .filter_map(|triangle| intersect_triangle(&capsule, &ray, triangle))
+                .min_by(|a, b| a.total_cmp(b));
+            let found = bvh
+                .intersect(&capsule, &ray, f32::INFINITY)
+                .map(|(_, distance)| distance);
+            match (expected, found) {
+                (Some(expected), Some(found)) => {
+                    assert!((expected - found).abs() < 1e-5, "ray {}", index)
+                }
+                (expected, found) => assert_eq!(expected, found, "ray {}", index),
+            }
+        }
+    }
+
+    #[test]
+    fn modifying_the_mesh_drops_its_bvh() {
+        let mut app = app();
+        let (cube, cube_asset) = add_cube(&mut app);
+        let entity = spawn(&app, &cube, Transform::from_xyz(0.0, 0.0, -10.0));
+        assert!(cast(&app, Vec3::ZERO, Vec3::NEG_Z).is_some());
+        assert!(update_until(&mut app, |app| {
+            app.world.entity_from_id(cube_asset).has(MeshBvh::id())
+        }));
+
+        cube.modify(&app.world, |mesh| {
+            for vertex in &mut mesh.vertices {
+                vertex.position[2] *= 0.5;
+            }
+        });
+        assert!(!app.world.entity_from_id(cube_asset).has(MeshBvh::id()));
+
+        // The changed mesh is hit where it is now
+        let hit = cast(&app, Vec3::ZERO, Vec3::NEG_Z).expect("cube missed");
+        assert_eq!(hit.entity, entity);
+        assert!((hit.distance - 9.5).abs() < 1e-4);
+    }
+}
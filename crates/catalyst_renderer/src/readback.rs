@@ -0,0 +1,830 @@
+use std::{
+    collections::VecDeque,
+    ops::Range,
+    sync::{
+        Arc,
+        atomic::{AtomicU8, Ordering},
+        mpsc::Sender,
+    },
+};
+
+use thiserror::Error;
+
+/// Readbacks mapping at once before the oldest is evicted, see ReadbackManager.
+pub const DEFAULT_MAX_IN_FLIGHT: usize = 16;
+
+// Staging buffers kept for reuse; more are released when they come back
+const MAX_POOLED: usize = 8;
+
+// Map state of a pending readback, set by its map_async callback
+const WAITING: u8 = 0;
+const MAPPED: u8 = 1;
+const FAILED: u8 = 2;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ReadbackError {
+    #[error("the source was resized before the readback finished")]
+    Resized,
+    #[error("evicted by newer readbacks, at most {0} can be in flight")]
+    Evicted(usize),
+    #[error("mapping the staging buffer failed")]
+    MapFailed,
+    #[error("invalid readback: {0}")]
+    Invalid(String),
+}
+
+/// Bytes copied back from the GPU, rows without the copy padding.
+#[derive(Clone, Debug)]
+pub struct Readback {
+    /// ReadbackManager::frame when the copy was recorded.
+    pub frame: u64,
+    /// Texels per row and rows of a texture region; a buffer range is one row of bytes.
+    pub width: u32,
+    pub height: u32,
+    /// Bytes per row in `data`.
+    pub row_bytes: u32,
+    pub data: Vec<u8>,
+}
+
+impl Readback {
+    /// The data as `T`s, e.g. `u32` ids or `[f32; 2]` results.
+    pub fn to_vec<T: bytemuck::Pod>(&self) -> Vec<T> {
+        bytemuck::pod_collect_to_vec(&self.data)
+    }
+
+    /// Bytes of the texel at (x, y) of the region.
+    pub fn texel(&self, x: u32, y: u32) -> &[u8] {
+        let size = (self.row_bytes / self.width.max(1)) as usize;
+        let start = y as usize * self.row_bytes as usize + x as usize * size;
+        &self.data[start..start + size]
+    }
+}
+
+pub type ReadbackResult = Result<Readback, ReadbackError>;
+
+/// Where a readback's result goes. Called or sent exactly once, from
+/// ReadbackManager::poll, or right away when the request is rejected or cancelled.
+pub enum ReadbackDelivery {
+    Callback(Box<dyn FnOnce(ReadbackResult) + Send>),
+    Channel(Sender<ReadbackResult>),
+}
+
+impl ReadbackDelivery {
+    pub fn callback(callback: impl FnOnce(ReadbackResult) + Send + 'static) -> Self {
+        Self::Callback(Box::new(callback))
+    }
+
+    fn deliver(self, result: ReadbackResult) {
+        match self {
+            ReadbackDelivery::Callback(callback) => callback(result),
+            // The receiver may be gone, nobody is waiting then
+            ReadbackDelivery::Channel(sender) => {
+                let _ = sender.send(result);
+            }
+        }
+    }
+}
+
+impl From<Sender<ReadbackResult>> for ReadbackDelivery {
+    fn from(sender: Sender<ReadbackResult>) -> Self {
+        Self::Channel(sender)
+    }
+}
+
+/// Part of one mip level of a 2D texture, in texels of that level.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextureRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub mip_level: u32,
+}
+
+impl TextureRegion {
+    /// All of mip level 0.
+    pub fn full(texture: &wgpu::Texture) -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            width: texture.width(),
+            height: texture.height(),
+            mip_level: 0,
+        }
+    }
+
+    /// One texel of mip level 0, e.g. the one under the cursor.
+    pub fn texel(x: u32, y: u32) -> Self {
+        Self {
+            x,
+            y,
+            width: 1,
+            height: 1,
+            mip_level: 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReadbackStats {
+    /// Readbacks waiting for their result.
+    pub in_flight: usize,
+    /// Staging buffers alive, in use or pooled.
+    pub staging_buffers: usize,
+    /// Staging buffers free for the next readback.
+    pub pooled: usize,
+}
+
+struct StagingBuffer {
+    buffer: wgpu::Buffer,
+    size: u64,
+}
+
+struct PendingReadback {
+    frame: u64,
+    staging: StagingBuffer,
+    width: u32,
+    height: u32,
+    row_bytes: u32,
+    padded_row_bytes: u32,
+    // The source texture, and whether it had the screen's size when the copy was recorded
+    texture: Option<wgpu::Texture>,
+    screen_sized: bool,
+    state: Arc<AtomicU8>,
+    map_requested: bool,
+    // None once delivered with an error (cancelled, evicted); the staging buffer still
+    // comes back to the pool when its mapping finishes
+    delivery: Option<ReadbackDelivery>,
+}
+
+/// Copies of GPU textures and buffers read back without stalling the frame. A request
+/// records a copy into a staging buffer from a small pool; after the frame is submitted
+/// the buffers are mapped, and `poll` hands over the ones the GPU has finished, usually
+/// one or two frames later, tagged with the frame they were captured on.
+///
+/// Readbacks of a screen sized texture fail with ReadbackError::Resized when the screen
+/// is resized before they finish, see `resize` and `cancel_texture`. With more than
+/// `max_in_flight` readbacks waiting, the oldest fails with ReadbackError::Evicted.
+/// Lives in the RenderContext; "Execute Frame Graph" maps after submitting and
+/// "Poll Readbacks" delivers at the start of the frame.
+pub struct ReadbackManager {
+    frame: u64,
+    screen_size: (u32, u32),
+    max_in_flight: usize,
+    pool: Vec<StagingBuffer>,
+    pending: VecDeque<PendingReadback>,
+    allocated: usize,
+}
+
+impl ReadbackManager {
+    pub fn new(screen_width: u32, screen_height: u32) -> Self {
+        Self {
+            frame: 0,
+            screen_size: (screen_width, screen_height),
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+            pool: Vec::new(),
+            pending: VecDeque::new(),
+            allocated: 0,
+        }
+    }
+
+    /// Frames submitted so far, the tag of readbacks requested now.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn set_max_in_flight(&mut self, max_in_flight: usize) {
+        self.max_in_flight = max_in_flight.max(1);
+    }
+
+    pub fn stats(&self) -> ReadbackStats {
+        ReadbackStats {
+            in_flight: self.live().count(),
+            staging_buffers: self.allocated,
+            pooled: self.pool.len(),
+        }
+    }
+
+    /// Records a copy of `region` of `texture` into `encoder`. The texture needs
+    /// COPY_SRC and a format with single texel blocks; rows are padded for the copy
+    /// and the padding stripped before delivery.
+    pub fn read_texture(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        region: TextureRegion,
+        delivery: impl Into<ReadbackDelivery>,
+    ) {
+        let delivery = delivery.into();
+        let texel_size = match validate_texture(texture, region) {
+            Ok(texel_size) => texel_size,
+            Err(error) => return delivery.deliver(Err(ReadbackError::Invalid(error))),
+        };
+
+        // Rows of a buffer copy are padded to COPY_BYTES_PER_ROW_ALIGNMENT
+        let row_bytes = region.width * texel_size;
+        let padded_row_bytes = row_bytes.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let staging = self.acquire(device, padded_row_bytes as u64 * region.height as u64);
+
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture,
+                mip_level: region.mip_level,
+                origin: wgpu::Origin3d {
+                    x: region.x,
+                    y: region.y,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &staging.buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: Some(region.height),
+                },
+            },
+            wgpu::Extent3d {
+                width: region.width,
+                height: region.height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        let screen_sized = (texture.width(), texture.height()) == self.screen_size;
+        self.push(PendingReadback {
+            frame: self.frame,
+            staging,
+            width: region.width,
+            height: region.height,
+            row_bytes,
+            padded_row_bytes,
+            texture: Some(texture.clone()),
+            screen_sized,
+            state: Arc::new(AtomicU8::new(WAITING)),
+            map_requested: false,
+            delivery: Some(delivery),
+        });
+    }
+
+    /// Records a copy of `range` of `buffer` into `encoder`. The buffer needs COPY_SRC;
+    /// the range's start and length must be multiples of COPY_BUFFER_ALIGNMENT.
+    pub fn read_buffer(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        buffer: &wgpu::Buffer,
+        range: Range<u64>,
+        delivery: impl Into<ReadbackDelivery>,
+    ) {
+        let delivery = delivery.into();
+        if let Err(error) = validate_buffer(buffer, &range) {
+            return delivery.deliver(Err(ReadbackError::Invalid(error)));
+        }
+
+        let size = range.end - range.start;
+        let staging = self.acquire(device, size);
+        encoder.copy_buffer_to_buffer(buffer, range.start, &staging.buffer, 0, size);
+
+        self.push(PendingReadback {
+            frame: self.frame,
+            staging,
+            width: size as u32,
+            height: 1,
+            row_bytes: size as u32,
+            padded_row_bytes: size as u32,
+            texture: None,
+            screen_sized: false,
+            state: Arc::new(AtomicU8::new(WAITING)),
+            map_requested: false,
+            delivery: Some(delivery),
+        });
+    }
+
+    /// Call after the frame's commands are submitted: maps the staging buffers of the
+    /// copies recorded since, and starts the next frame. Mapping earlier would fail the
+    /// submission.
+    pub fn after_submit(&mut self) {
+        for pending in self
+            .pending
+            .iter_mut()
+            .filter(|pending| !pending.map_requested)
+        {
+            let state = pending.state.clone();
+            pending
+                .staging
+                .buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    let mapped = if result.is_ok() { MAPPED } else { FAILED };
+                    state.store(mapped, Ordering::Release);
+                });
+            pending.map_requested = true;
+        }
+        self.frame += 1;
+    }
+
+    /// Delivers the readbacks the GPU has finished, without blocking, and takes their
+    /// staging buffers back.
+    pub fn poll(&mut self, device: &wgpu::Device) {
+        if self.pending.is_empty() {
+            return;
+        }
+
+        // Drives the map_async callbacks
+        let _ = device.poll(wgpu::PollType::Poll);
+
+        let mut waiting = VecDeque::with_capacity(self.pending.len());
+        for mut pending in self.pending.drain(..) {
+            match pending.state.load(Ordering::Acquire) {
+                MAPPED => {
+                    if let Some(delivery) = pending.delivery.take() {
+                        delivery.deliver(Ok(read_mapped(&pending)));
+                    }
+                    pending.staging.buffer.unmap();
+                    if self.pool.len() < MAX_POOLED {
+                        self.pool.push(pending.staging);
+                    } else {
+                        self.allocated -= 1;
+                    }
+                }
+                FAILED => {
+                    if let Some(delivery) = pending.delivery.take() {
+                        delivery.deliver(Err(ReadbackError::MapFailed));
+                    }
+                    self.allocated -= 1;
+                }
+                _ => waiting.push_back(pending),
+            }
+        }
+        self.pending = waiting;
+    }
+
+    /// The screen's new size: readbacks of textures with the old one fail with
+    /// ReadbackError::Resized.
+    pub fn resize(&mut self, screen_width: u32, screen_height: u32) {
+        if self.screen_size == (screen_width, screen_height) {
+            return;
+        }
+        self.screen_size = (screen_width, screen_height);
+        self.cancel(|pending| pending.screen_sized);
+    }
+
+    /// Fails the readbacks of `texture` with ReadbackError::Resized, for a target
+    /// recreated at another size.
+    pub fn cancel_texture(&mut self, texture: &wgpu::Texture) {
+        self.cancel(|pending| pending.texture.as_ref() == Some(texture));
+    }
+
+    fn cancel(&mut self, matches: impl Fn(&PendingReadback) -> bool) {
+        for pending in self.pending.iter_mut().filter(|pending| matches(pending)) {
+            if let Some(delivery) = pending.delivery.take() {
+                delivery.deliver(Err(ReadbackError::Resized));
+            }
+            pending.texture = None;
+        }
+    }
+
+    fn live(&self) -> impl Iterator<Item = &PendingReadback> {
+        self.pending
+            .iter()
+            .filter(|pending| pending.delivery.is_some())
+    }
+
+    fn push(&mut self, pending: PendingReadback) {
+        // Oldest first
+        while self.live().count() >= self.max_in_flight {
+            let Some(oldest) = self
+                .pending
+                .iter_mut()
+                .find(|pending| pending.delivery.is_some())
+            else {
+                break;
+            };
+            if let Some(delivery) = oldest.delivery.take() {
+                delivery.deliver(Err(ReadbackError::Evicted(self.max_in_flight)));
+            }
+        }
+        self.pending.push_back(pending);
+    }
+
+    // The smallest pooled buffer holding `size` bytes, or a new one
+    fn acquire(&mut self, device: &wgpu::Device, size: u64) -> StagingBuffer {
+        let best = self
+            .pool
+            .iter()
+            .enumerate()
+            .filter(|(_, staging)| staging.size >= size)
+            .min_by_key(|(_, staging)| staging.size)
+            .map(|(index, _)| index);
+        if let Some(index) = best {
+            return self.pool.swap_remove(index);
+        }
+
+        // Rounded up, so readbacks of similar sizes share buffers
+        let size = size.max(wgpu::COPY_BUFFER_ALIGNMENT).next_power_of_two();
+        self.allocated += 1;
+        StagingBuffer {
+            buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Readback Staging Buffer"),
+                size,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            size,
+        }
+    }
+}
+
+// Bytes per texel, or why the region can't be copied
+fn validate_texture(texture: &wgpu::Texture, region: TextureRegion) -> Result<u32, String> {
+    if !texture.usage().contains(wgpu::TextureUsages::COPY_SRC) {
+        return Err("the texture has no COPY_SRC usage".to_string());
+    }
+    if texture.dimension() != wgpu::TextureDimension::D2 {
+        return Err("only 2D textures are read back".to_string());
+    }
+    let format = texture.format();
+    let texel_size = match format.block_copy_size(Some(wgpu::TextureAspect::All)) {
+        Some(size) if format.block_dimensions() == (1, 1) => size,
+        _ => return Err(format!("{:?} can't be read back", format)),
+    };
+    if region.mip_level >= texture.mip_level_count() {
+        return Err(format!("the texture has no mip level {}", region.mip_level));
+    }
+
+    let level_width = (texture.width() >> region.mip_level).max(1);
+    let level_height = (texture.height() >> region.mip_level).max(1);
+    if region.width == 0
+        || region.height == 0
+        || region.x + region.width > level_width
+        || region.y + region.height > level_height
+    {
+        return Err(format!(
+            "region {}x{} at ({}, {}) is outside the {}x{} level",
+            region.width, region.height, region.x, region.y, level_width, level_height
+        ));
+    }
+    Ok(texel_size)
+}
+
+fn validate_buffer(buffer: &wgpu::Buffer, range: &Range<u64>) -> Result<(), String> {
+    if !buffer.usage().contains(wgpu::BufferUsages::COPY_SRC) {
+        return Err("the buffer has no COPY_SRC usage".to_string());
+    }
+    if range.start >= range.end || range.end > buffer.size() {
+        return Err(format!(
+            "range {:?} is empty or outside the {} byte buffer",
+            range,
+            buffer.size()
+        ));
+    }
+    if range.start % wgpu::COPY_BUFFER_ALIGNMENT != 0
+        || (range.end - range.start) % wgpu::COPY_BUFFER_ALIGNMENT != 0
+    {
+        return Err(format!(
+            "range {:?} isn't aligned to {} bytes",
+            range,
+            wgpu::COPY_BUFFER_ALIGNMENT
+        ));
+    }
+    Ok(())
+}
+
+fn read_mapped(pending: &PendingReadback) -> Readback {
+    let data = pending.staging.buffer.slice(..).get_mapped_range();
+    let mut bytes = Vec::with_capacity((pending.row_bytes * pending.height) as usize);
+    for row in data
+        .chunks(pending.padded_row_bytes as usize)
+        .take(pending.height as usize)
+    {
+        bytes.extend_from_slice(&row[..pending.row_bytes as usize]);
+    }
+
+    Readback {
+        frame: pending.frame,
+        width: pending.width,
+        height: pending.height,
+        row_bytes: pending.row_bytes,
+        data: bytes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Mutex, atomic::AtomicBool, mpsc};
+
+    use wgpu::util::DeviceExt;
+
+    use super::*;
+
+    const WIDTH: u32 = 37;
+    const HEIGHT: u32 = 19;
+    // 1.0, 0.2, 0.0, 1.0 in Rgba8Unorm
+    const CLEAR: [u8; 4] = [255, 51, 0, 255];
+
+    // Submits `encoder` and waits until the GPU has done everything submitted, as a frame
+    // paced by the display would before the next one starts
+    fn finish_frame(device: &wgpu::Device, queue: &wgpu::Queue, encoder: wgpu::CommandEncoder) {
+        queue.submit(Some(encoder.finish()));
+        let done = Arc::new(AtomicBool::new(false));
+        let flag = done.clone();
+        queue.on_submitted_work_done(move || flag.store(true, Ordering::Release));
+        while !done.load(Ordering::Acquire) {
+            let _ = device.poll(wgpu::PollType::Poll);
+            std::thread::yield_now();
+        }
+    }
+
+    fn encoder(device: &wgpu::Device) -> wgpu::CommandEncoder {
+        device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Readback Check"),
+        })
+    }
+
+    fn texture(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        usage: wgpu::TextureUsages,
+    ) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Readback Source"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage,
+            view_formats: &[],
+        })
+    }
+
+    fn clear(encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture) {
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Readback Clear"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                        r: 1.0,
+                        g: 0.2,
+                        b: 0.0,
+                        a: 1.0,
+                    }),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+    }
+
+    // Collects what a readback delivers
+    fn slot() -> (Arc<Mutex<Option<ReadbackResult>>>, ReadbackDelivery) {
+        let result = Arc::new(Mutex::new(None));
+        let sink = result.clone();
+        let delivery = ReadbackDelivery::callback(move |readback| {
+            *sink.lock().unwrap() = Some(readback);
+        });
+        (result, delivery)
+    }
+
+    const COPYABLE: wgpu::TextureUsages =
+        wgpu::TextureUsages::RENDER_ATTACHMENT.union(wgpu::TextureUsages::COPY_SRC);
+
+    // Runs frames until every slot is filled, two at most
+    fn wait_for(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        readback: &mut ReadbackManager,
+        slots: &[&Arc<Mutex<Option<ReadbackResult>>>],
+    ) {
+        for _ in 0..2 {
+            if slots.iter().all(|slot| slot.lock().unwrap().is_some()) {
+                return;
+            }
+            readback.poll(device);
+            finish_frame(device, queue, encoder(device));
+            readback.after_submit();
+        }
+    }
+
+    #[test]
+    fn textures_regions_and_buffer_ranges_read_back_within_two_frames() {
+        let Some((device, queue)) = crate::headless_device() else {
+            return;
+        };
+        let mut readback = ReadbackManager::new(WIDTH, HEIGHT);
+        // An odd width, so rows are padded
+        let source = texture(&device, WIDTH, HEIGHT, COPYABLE);
+        let values: Vec<u32> = (0..64).collect();
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Readback Buffer Source"),
+            contents: bytemuck::cast_slice(&values),
+            usage: wgpu::BufferUsages::COPY_SRC,
+        });
+
+        let (sender, receiver) = mpsc::channel();
+        let (part, part_delivery) = slot();
+        let (words, words_delivery) = slot();
+        let mut frame = encoder(&device);
+        clear(&mut frame, &source);
+        readback.read_texture(
+            &device,
+            &mut frame,
+            &source,
+            TextureRegion::full(&source),
+            sender,
+        );
+        let region = TextureRegion {
+            x: 30,
+            y: 15,
+            width: 7,
+            height: 4,
+            mip_level: 0,
+        };
+        readback.read_texture(&device, &mut frame, &source, region, part_delivery);
+        readback.read_buffer(&device, &mut frame, &buffer, 16..48, words_delivery);
+        finish_frame(&device, &queue, frame);
+        readback.after_submit();
+
+        let mut whole = None;
+        for _ in 0..2 {
+            readback.poll(&device);
+            if let Ok(result) = receiver.try_recv() {
+                whole = Some(result);
+            }
+            if whole.is_some() && part.lock().unwrap().is_some() && words.lock().unwrap().is_some()
+            {
+                break;
+            }
+            finish_frame(&device, &queue, encoder(&device));
+            readback.after_submit();
+        }
+
+        // The clear color, with the padding stripped
+        let whole = whole.expect("the whole texture arrived").unwrap();
+        assert_eq!(whole.frame, 0);
+        assert_eq!((whole.width, whole.height), (WIDTH, HEIGHT));
+        assert_eq!(whole.data.len(), (WIDTH * HEIGHT * 4) as usize);
+        assert!(whole.data.chunks_exact(4).all(|texel| texel == CLEAR));
+
+        // A region at the texture's corner reads back alone
+        let part = part
+            .lock()
+            .unwrap()
+            .take()
+            .expect("the region arrived")
+            .unwrap();
+        assert_eq!(part.data.len(), 7 * 4 * 4);
+        assert_eq!(part.texel(6, 3), CLEAR);
+        assert!(part.data.chunks_exact(4).all(|texel| texel == CLEAR));
+
+        let words = words
+            .lock()
+            .unwrap()
+            .take()
+            .expect("the range arrived")
+            .unwrap();
+        assert_eq!(words.to_vec::<u32>(), (4..12).collect::<Vec<u32>>());
+
+        // Delivered readbacks return their staging buffers to the pool
+        let stats = readback.stats();
+        assert_eq!(stats.in_flight, 0);
+        assert_eq!(stats.staging_buffers, stats.pooled);
+    }
+
+    #[test]
+    fn a_resize_fails_screen_sized_readbacks_without_leaking_their_buffers() {
+        let Some((device, queue)) = crate::headless_device() else {
+            return;
+        };
+        let mut readback = ReadbackManager::new(WIDTH, HEIGHT);
+        let source = texture(&device, WIDTH, HEIGHT, COPYABLE);
+
+        let (cancelled, delivery) = slot();
+        let mut frame = encoder(&device);
+        clear(&mut frame, &source);
+        readback.read_texture(
+            &device,
+            &mut frame,
+            &source,
+            TextureRegion::full(&source),
+            delivery,
+        );
+        finish_frame(&device, &queue, frame);
+        readback.after_submit();
+        readback.resize(WIDTH * 2, HEIGHT * 2);
+        // Right away
+        assert!(matches!(
+            *cancelled.lock().unwrap(),
+            Some(Err(ReadbackError::Resized))
+        ));
+
+        // The staging buffer goes back to the pool once it's mapped
+        for _ in 0..2 {
+            readback.poll(&device);
+            finish_frame(&device, &queue, encoder(&device));
+            readback.after_submit();
+        }
+        readback.poll(&device);
+        let stats = readback.stats();
+        assert_eq!(stats.in_flight, 0);
+        assert!(stats.staging_buffers > 0);
+        assert_eq!(stats.staging_buffers, stats.pooled);
+    }
+
+    #[test]
+    fn the_oldest_of_too_many_readbacks_is_evicted() {
+        let Some((device, queue)) = crate::headless_device() else {
+            return;
+        };
+        let mut readback = ReadbackManager::new(WIDTH, HEIGHT);
+        let source = texture(&device, WIDTH, HEIGHT, COPYABLE);
+        readback.set_max_in_flight(2);
+
+        let mut frame = encoder(&device);
+        clear(&mut frame, &source);
+        let slots: Vec<_> = (0..3)
+            .map(|_| {
+                let (result, delivery) = slot();
+                readback.read_texture(
+                    &device,
+                    &mut frame,
+                    &source,
+                    TextureRegion::texel(1, 1),
+                    delivery,
+                );
+                result
+            })
+            .collect();
+        finish_frame(&device, &queue, frame);
+        readback.after_submit();
+        wait_for(&device, &queue, &mut readback, &[&slots[1], &slots[2]]);
+
+        assert!(matches!(
+            *slots[0].lock().unwrap(),
+            Some(Err(ReadbackError::Evicted(2)))
+        ));
+        // The rest arrive
+        for slot in &slots[1..] {
+            assert!(
+                matches!(slot.lock().unwrap().as_ref(), Some(Ok(readback)) if readback.data == CLEAR)
+            );
+        }
+    }
+
+    #[test]
+    fn copies_the_gpu_cant_do_are_rejected_before_anything_is_recorded() {
+        let Some((device, _queue)) = crate::headless_device() else {
+            return;
+        };
+        let mut readback = ReadbackManager::new(WIDTH, HEIGHT);
+        let source = texture(&device, WIDTH, HEIGHT, COPYABLE);
+        let not_copyable = texture(&device, 4, 4, wgpu::TextureUsages::RENDER_ATTACHMENT);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Readback Buffer Source"),
+            size: 64,
+            usage: wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let mut frame = encoder(&device);
+
+        let (no_copy, delivery) = slot();
+        readback.read_texture(
+            &device,
+            &mut frame,
+            &not_copyable,
+            TextureRegion::texel(0, 0),
+            delivery,
+        );
+        let (outside, delivery) = slot();
+        readback.read_texture(
+            &device,
+            &mut frame,
+            &source,
+            TextureRegion::texel(WIDTH, 0),
+            delivery,
+        );
+        let (unaligned, delivery) = slot();
+        readback.read_buffer(&device, &mut frame, &buffer, 2..10, delivery);
+
+        for result in [&no_copy, &outside, &unaligned] {
+            assert!(matches!(
+                *result.lock().unwrap(),
+                Some(Err(ReadbackError::Invalid(_)))
+            ));
+        }
+        assert_eq!(readback.stats().in_flight, 0);
+    }
+}
@@ -0,0 +1,576 @@
+
+use catalyst_assets::{assets::Handle, material::TextureData};
+use catalyst_core::{
+    App, camera::Camera, pipeline::PhaseRenderPrepare, player::PlayerIndex,
+    transform::GlobalTransform,
+};
+use flecs_ecs::prelude::*;
+use glam::{Mat4, Vec3, Vec4};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    custom_program::{MaterialProgram, ProgramPasses},
+    frame_graph::{FrameGraph, GraphTexture, GraphTextureDesc, PassStage},
+    global_resources::{GpuLight, LightUniforms},
+    mesh::{AssetMesh, GpuGeometry, MeshBounds, MeshInstance},
+    programs::{
+        GpuProgram,
+        reflection_program::{MAX_REFLECTIONS, ReflectionSurface, ReflectionSurfaceUniform},
+    },
+    render::{ClearColor, RenderContext, collect_views},
+    render_order::{RenderBucket, RenderLayers, RenderOrder},
+    texture::{DepthMode, GpuTexture, TextureHelper},
+    viewport::Viewport,
+    visibility::{Frustum, ViewFrusta},
+};
+
+// Graph names of each slot's pass and target, so the GPU timings tell them apart
+const PASS_NAMES: [&str; MAX_REFLECTIONS] = [
+    "Planar Reflection 0",
+    "Planar Reflection 1",
+    "Planar Reflection 2",
+    "Planar Reflection 3",
+];
+const TARGET_NAMES: [&str; MAX_REFLECTIONS] = [
+    "Planar Reflection Color 0",
+    "Planar Reflection Color 1",
+    "Planar Reflection Color 2",
+    "Planar Reflection Color 3",
+];
+
+// How far below the plane geometry is still drawn, so objects standing in the water
+// don't show a gap where they meet their reflection
+const CLIP_PLANE_OFFSET: f32 = 0.02;
+
+/// A flat mirror or water surface. The scene is drawn a second time, mirrored across
+/// the entity's plane, and blended over the entity's mesh. The plane goes through the
+/// entity's GlobalTransform position with the local +Y axis as its normal, so an
+/// unrotated plane mesh reflects what is above it.
+///
+/// The entity needs a mesh: it is drawn with its own material first, the reflection
+/// goes on top. Reflections are drawn for the first view only; other split-screen
+/// views see the material alone. At most MAX_REFLECTIONS are drawn, closest first.
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlanarReflector {
+    /// Ripples distorting the reflection. None keeps it flat.
+    /// Not saved in scene files, asset handles only live as long as the session.
+    #[serde(skip)]
+    pub normal_map: Option<Handle<TextureData>>,
+    /// Size of the reflection target relative to the view.
+    pub resolution_scale: f32,
+    /// Not drawn while the camera is farther than this from the reflector's bounds.
+    pub max_distance: f32,
+    /// Redrawn every this many frames, 1 for every frame. In between the last one is
+    /// reused, which lags behind a moving camera.
+    pub update_interval: u32,
+    /// Entities whose RenderLayers share none of these are left out of the reflection.
+    /// Reflectors and the Overlay bucket are never reflected.
+    pub layers: RenderLayers,
+    /// Multiplied with the reflection; alpha scales how much of it covers the surface.
+    pub tint: [f32; 4],
+    /// How far the normal map moves the reflection lookup, in screen UV.
+    pub distortion: f32,
+    /// Normal map repeats per mesh UV unit.
+    pub normal_tiling: f32,
+    /// Share reflected looking straight down, rising to all of it at grazing angles.
+    /// About 0.02 for water, 1 for a mirror.
+    pub fresnel: f32,
+}
+
+impl Default for PlanarReflector {
+    fn default() -> Self {
+        Self {
+            normal_map: None,
+            resolution_scale: 0.5,
+            max_distance: 200.0,
+            update_interval: 1,
+            layers: RenderLayers::ALL,
+            tint: [1.0, 1.0, 1.0, 1.0],
+            distortion: 0.02,
+            normal_tiling: 4.0,
+            fresnel: 0.02,
+        }
+    }
+}
+
+/// Reflections drawn this frame, gathered by "Prepare Planar Reflections".
+#[derive(Component, Default)]
+pub struct PlanarReflections {
+    reflections: Vec<PreparedReflection>,
+    // Reflector in each slot last frame; one taking over a slot is drawn right away
+    slots: [Option<Entity>; MAX_REFLECTIONS],
+    frame: u64,
+}
+
+impl PlanarReflections {
+    /// The frustum of a view mirrored across each reflection's plane, for culling what
+    /// only shows up in the reflections.
+    pub(crate) fn mirrored_frusta(&self, view_proj: Mat4) -> impl Iterator<Item = Frustum> + '_ {
+        self.reflections.iter().map(move |reflection| {
+            Frustum::from_view_proj(view_proj * reflection_matrix(reflection.plane))
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct PreparedReflection {
+    slot: usize,
+    // xyz = unit normal on the camera's side, w = -dot(normal, point on the plane)
+    plane: Vec4,
+    resolution_scale: f32,
+    layers: RenderLayers,
+    // Otherwise last frame's target is reused
+    redraw: bool,
+}
+
+impl PreparedReflection {
+    /// The view mirrored across the plane, and a projection that clips everything on
+    /// the far side of the plane and flips the image left to right: mirroring reverses
+    /// the triangle winding, flipping it back keeps the PBR pipelines' culling right.
+    /// The third matrix is the flipped projection without the clipping, for clusters.
+    fn view_projection(
+        &self,
+        view: Mat4,
+        projection: Mat4,
+        depth_mode: DepthMode,
+    ) -> (Mat4, Mat4, Mat4) {
+        let mirrored_view = view * reflection_matrix(self.plane);
+        // Planes go to view space with the inverse transpose
+        let clip_plane = mirrored_view.inverse().transpose()
+            * (self.plane + Vec4::new(0.0, 0.0, 0.0, CLIP_PLANE_OFFSET));
+        let flip = Mat4::from_scale(Vec3::new(-1.0, 1.0, 1.0));
+        (
+            mirrored_view,
+            flip * oblique_projection(projection, clip_plane, depth_mode),
+            flip * projection,
+        )
+    }
+}
+
+/// The first view, which the reflections are drawn for.
+pub(crate) struct ReflectedView {
+    pub view: Mat4,
+    /// Unjittered: reflections are sampled in the main view's unjittered screen space.
+    pub projection: Mat4,
+    pub eye: Vec3,
+    pub near: f32,
+    pub far: f32,
+    pub viewport: Viewport,
+    pub exposure: f32,
+}
+
+// Mirrors points across `plane`: p - 2 * (dot(n, p) + d) * n
+fn reflection_matrix(plane: Vec4) -> Mat4 {
+    let n = plane.truncate();
+    let d = plane.w;
+    Mat4::from_cols(
+        Vec4::new(1.0 - 2.0 * n.x * n.x, -2.0 * n.x * n.y, -2.0 * n.x * n.z, 0.0),
+        Vec4::new(-2.0 * n.y * n.x, 1.0 - 2.0 * n.y * n.y, -2.0 * n.y * n.z, 0.0),
+        Vec4::new(-2.0 * n.z * n.x, -2.0 * n.z * n.y, 1.0 - 2.0 * n.z * n.z, 0.0),
+        Vec4::new(-2.0 * d * n.x, -2.0 * d * n.y, -2.0 * d * n.z, 1.0),
+    )
+}
+
+// Replaces the near plane of `projection` with `clip_plane` (view space, positive on
+// the kept side, the eye on the other side). The depth row is scaled by the widest
+// view angle so nothing inside the view lands past the end of the depth range; with
+// the eye behind the plane, depth still grows with distance along every ray.
+fn oblique_projection(projection: Mat4, clip_plane: Vec4, depth_mode: DepthMode) -> Mat4 {
+    let clip_plane = clip_plane / clip_plane.truncate().length().max(f32::EPSILON);
+
+    // Distance to the eye per unit of view depth, at the corners of the view
+    let spread = (1.0
+        + 1.0 / (projection.x_axis.x * projection.x_axis.x)
+        + 1.0 / (projection.y_axis.y * projection.y_axis.y))
+        .sqrt();
+
+    let depth_row = match depth_mode {
+        // 0 on the plane, at most w inside the view
+        DepthMode::Standard => clip_plane / spread,
+        // w on the plane (near is 1), at least 0 inside the view
+        DepthMode::ReversedZ => projection.row(3) - clip_plane / spread,
+    };
+
+    let mut oblique = projection;
+    oblique.x_axis.z = depth_row.x;
+    oblique.y_axis.z = depth_row.y;
+    oblique.z_axis.z = depth_row.z;
+    oblique.w_axis.z = depth_row.w;
+    oblique
+}
+
+/// Whether an entity shows up in a reflection drawing `layers`. Reflectors never do,
+/// their own surface would cover what they reflect.
+pub(crate) fn is_reflected(entity: EntityView, layers: RenderLayers) -> bool {
+    !entity.has(PlanarReflector::id())
+        && entity
+            .try_get::<&RenderLayers>(|entity_layers| entity_layers.intersects(layers))
+            .unwrap_or(true)
+}
+
+pub fn register_reflection_systems(app: &mut App) {
+    app.register_singleton_default::<PlanarReflections>();
+
+    let reflector_query = app
+        .world
+        .query::<(&PlanarReflector, &GlobalTransform, &MeshInstance)>()
+        .with((AssetMesh, flecs::Wildcard))
+        .set_cached()
+        .build();
+
+    let camera_query = app
+        .world
+        .query::<(&Camera, &GlobalTransform, Option<&PlayerIndex>)>()
+        .set_cached()
+        .build();
+
+    app.world
+        .system_named::<(&mut RenderContext, &mut PlanarReflections, &ViewFrusta)>(
+            "Prepare Planar Reflections",
+        )
+        .kind(PhaseRenderPrepare)
+        .run(move |iter| prepare_reflections(&iter.world(), &reflector_query, &camera_query));
+}
+
+struct Candidate {
+    entity: Entity,
+    distance: f32,
+    plane: Vec4,
+    reflector: PlanarReflector,
+    surface: ReflectionSurface,
+}
+
+// Picks the reflectors to draw this frame, gives each a slot and uploads their surfaces
+fn prepare_reflections(
+    world: &World,
+    reflector_query: &Query<(&PlanarReflector, &GlobalTransform, &MeshInstance)>,
+    camera_query: &Query<(&Camera, &GlobalTransform, Option<&PlayerIndex>)>,
+) {
+    let eye = collect_views(camera_query)
+        .first()
+        .map(|(_, _, transform)| transform.transform_point3(Vec3::ZERO));
+    let frustum = world.get::<&ViewFrusta>(|frusta| frusta.0.first().map(|view| view.frustum));
+
+    world.get::<&mut RenderContext>(|context| {
+        let mut candidates = Vec::new();
+        if let (Some(eye), Some(frustum)) = (eye, frustum) {
+            reflector_query.each_iter(|iter, index, (reflector, global, instance)| {
+                let mesh = world.entity_from_id(iter.pair(3).second_id().id());
+                let Some(bounds) = mesh.try_get::<&MeshBounds>(|bounds| bounds.0) else {
+                    return;
+                };
+
+                let center = global.0.transform_point3(bounds.center());
+                let (scale, _, _) = global.to_scale_rotation_translation();
+                let radius = bounds.half_extents().length() * scale.abs().max_element();
+                let distance = (eye.distance(center) - radius).max(0.0);
+                if distance > reflector.max_distance || !frustum.intersects_sphere(center, radius)
+                {
+                    return;
+                }
+
+                // Seen from behind there is nothing to reflect
+                let normal = global.0.y_axis.truncate().normalize_or_zero();
+                let point = global.0.w_axis.truncate();
+                if normal == Vec3::ZERO || normal.dot(eye - point) <= 0.0 {
+                    return;
+                }
+
+                // Meshes still uploading wait for their geometry
+                let Some((vertex_buffer, index_buffer, index_format, (indices, base_vertex))) =
+                    mesh.try_get::<&GpuGeometry>(|geometry| {
+                        (
+                            geometry.vertex_buffer.clone(),
+                            geometry.index_buffer.clone(),
+                            geometry.index_format,
+                            geometry.draw_range(),
+                        )
+                    })
+                else {
+                    return;
+                };
+
+                // A normal map not loaded yet leaves the reflection flat until it is
+                let normal_map = reflector
+                    .normal_map
+                    .as_ref()
+                    .and_then(|handle| handle.try_get_entity(world))
+                    .and_then(|texture| texture.try_get::<&GpuTexture>(|texture| texture.clone()))
+                    .unwrap_or_else(|| context.default_normal.clone());
+
+                candidates.push(Candidate {
+                    entity: iter.entity(index).id(),
+                    distance,
+                    plane: normal.extend(-normal.dot(point)),
+                    reflector: reflector.clone(),
+                    surface: ReflectionSurface {
+                        slot: 0,
+                        mesh_bind_group: instance.bind_group.clone(),
+                        vertex_buffer,
+                        index_buffer,
+                        index_format,
+                        indices,
+                        base_vertex,
+                        normal_map,
+                    },
+                });
+            });
+        }
+
+        candidates.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        candidates.truncate(MAX_REFLECTIONS);
+
+        world.get::<&mut PlanarReflections>(|reflections| {
+            // Reflectors keep last frame's slot, whose target still holds their reflection
+            let mut slots = [None; MAX_REFLECTIONS];
+            let mut assigned: Vec<Option<(usize, bool)>> = candidates
+                .iter()
+                .map(|candidate| {
+                    let slot = reflections
+                        .slots
+                        .iter()
+                        .position(|entity| *entity == Some(candidate.entity))?;
+                    slots[slot] = Some(candidate.entity);
+                    Some((slot, false))
+                })
+                .collect();
+            for (candidate, assigned) in candidates.iter().zip(&mut assigned) {
+                if assigned.is_none() {
+                    let slot = slots.iter().position(Option::is_none).unwrap_or_default();
+                    slots[slot] = Some(candidate.entity);
+                    *assigned = Some((slot, true));
+                }
+            }
+
+            reflections.frame += 1;
+            reflections.slots = slots;
+            reflections.reflections.clear();
+
+            let mut surfaces = Vec::with_capacity(candidates.len());
+            for (candidate, assigned) in candidates.into_iter().zip(assigned) {
+                let Some((slot, new)) = assigned else {
+                    continue;
+                };
+                let reflector = &candidate.reflector;
+                // Staggered by slot, so reflectors sharing an interval don't all redraw at once
+                let interval = reflector.update_interval.max(1) as u64;
+
+                reflections.reflections.push(PreparedReflection {
+                    slot,
+                    plane: candidate.plane,
+                    resolution_scale: reflector.resolution_scale.clamp(0.05, 1.0),
+                    layers: reflector.layers,
+                    redraw: new || (reflections.frame + slot as u64) % interval == 0,
+                });
+                surfaces.push((
+                    ReflectionSurface {
+                        slot,
+                        ..candidate.surface
+                    },
+                    ReflectionSurfaceUniform {
+                        tint: reflector.tint,
+                        distortion: reflector.distortion,
+                        normal_tiling: reflector.normal_tiling,
+                        fresnel: reflector.fresnel.clamp(0.0, 1.0),
+                        _padding: 0.0,
+                    },
+                ));
+            }
+
+            context.reflection_program.prepare(surfaces, &context.queue);
+        });
+    });
+}
+
+/// Declares every reflection's target and, for the ones redrawn this frame, a pass
+/// drawing the mirrored scene into it. Uses the global views from `view_offset` on,
+/// one per slot. Returns the target of each slot in use and the number of passes.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn add_reflection_passes(
+    graph: &mut FrameGraph,
+    context: &mut RenderContext,
+    reflections: &PlanarReflections,
+    source: &ReflectedView,
+    scene_lights: LightUniforms,
+    point_lights: &[GpuLight],
+    view_offset: usize,
+    clear_color: ClearColor,
+    mesh_query: &Query<&MeshInstance>,
+    ordered_mesh_query: &Query<(&MeshInstance, &RenderOrder, &GlobalTransform)>,
+    custom_mesh_query: &Query<(&MeshInstance, &MaterialProgram)>,
+) -> ([Option<GraphTexture>; MAX_REFLECTIONS], usize) {
+    let mut targets = [None; MAX_REFLECTIONS];
+    let mut drawn = 0;
+    if reflections.reflections.is_empty() {
+        return (targets, drawn);
+    }
+
+    context
+        .global_resources
+        .ensure_views(&context.device, view_offset + MAX_REFLECTIONS);
+
+    for reflection in &reflections.reflections {
+        let slot = reflection.slot;
+        let width = ((source.viewport.width * reflection.resolution_scale).round() as u32).max(1);
+        let height = ((source.viewport.height * reflection.resolution_scale).round() as u32).max(1);
+        let desc = GraphTextureDesc {
+            width,
+            height,
+            format: context.config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        };
+
+        let color = graph.persistent_texture(TARGET_NAMES[slot], desc);
+        targets[slot] = Some(color);
+        if !reflection.redraw && !graph.was_reset(color) {
+            continue;
+        }
+        drawn += 1;
+
+        let (view, projection, cluster_projection) =
+            reflection.view_projection(source.view, source.projection, context.depth_mode);
+        let viewport = Viewport::full(width, height);
+        let mut light_data = scene_lights;
+        light_data.camera_pos = reflection_matrix(reflection.plane)
+            .transform_point3(source.eye)
+            .to_array();
+
+        let index = view_offset + slot;
+        let wind = context.global_resources.wind;
+        let view_resources = context.global_resources.view_mut(index);
+        view_resources.update_camera(
+            &context.queue,
+            projection * view,
+            projection * view,
+            source.exposure,
+            wind,
+        );
+        view_resources.update_lights(&context.queue, light_data);
+        view_resources.update_clusters(
+            &context.queue,
+            point_lights,
+            view,
+            cluster_projection,
+            source.near,
+            source.far,
+            &viewport,
+        );
+
+        // The pipelines always write all three targets, only the color is kept
+        let depth = graph.create_texture(
+            "Planar Reflection Depth",
+            GraphTextureDesc {
+                format: TextureHelper::DEPTH_FORMAT,
+                ..desc
+            },
+        );
+        let velocity = graph.create_texture(
+            "Planar Reflection Velocity",
+            GraphTextureDesc {
+                format: TextureHelper::VELOCITY_FORMAT,
+                ..desc
+            },
+        );
+        let luminance = graph.create_texture(
+            "Planar Reflection Luminance",
+            GraphTextureDesc {
+                format: TextureHelper::LUMINANCE_FORMAT,
+                ..desc
+            },
+        );
+
+        // What the mirrored view shows, collected now for the pass to record on any thread
+        let layers = Some(reflection.layers);
+        let camera_pos = Vec3::from_array(light_data.camera_pos);
+        let draws = [
+            context.pbr_program.collect_draws(mesh_query, layers),
+            context.pbr_program.collect_bucket(
+                ordered_mesh_query,
+                RenderBucket::Opaque,
+                camera_pos,
+                layers,
+            ),
+            context.pbr_program.collect_bucket(
+                ordered_mesh_query,
+                RenderBucket::Transparent,
+                camera_pos,
+                layers,
+            ),
+        ];
+        let custom_draws = context.custom_programs.collect_draws(
+            custom_mesh_query,
+            ProgramPasses::REFLECTIONS,
+            layers,
+        );
+        graph
+            .add_pass(PASS_NAMES[slot], PassStage::Scene)
+            .write(color)
+            .write(velocity)
+            .write(luminance)
+            .write(depth)
+            .record_parallel(move |pass| {
+                let timestamp_writes = pass.timestamp_writes();
+                let (color, velocity, luminance, depth) = (
+                    pass.view(color),
+                    pass.view(velocity),
+                    pass.view(luminance),
+                    pass.view(depth),
+                );
+                let context = pass.context;
+                let attachment = |view, load, store| {
+                    Some(wgpu::RenderPassColorAttachment {
+                        view,
+                        resolve_target: None,
+                        depth_slice: None,
+                        ops: wgpu::Operations { load, store },
+                    })
+                };
+                let unused = wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT);
+
+                let mut render_pass = pass.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some(PASS_NAMES[slot]),
+                    color_attachments: &[
+                        // Same backdrop as the main pass
+                        attachment(
+                            color,
+                            clear_color.load_op(),
+                            wgpu::StoreOp::Store,
+                        ),
+                        attachment(velocity, unused, wgpu::StoreOp::Discard),
+                        attachment(luminance, unused, wgpu::StoreOp::Discard),
+                    ],
+                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                        view: depth,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(context.depth_mode.clear_value()),
+                            store: wgpu::StoreOp::Discard,
+                        }),
+                        stencil_ops: None,
+                    }),
+                    timestamp_writes,
+                    ..Default::default()
+                });
+
+                let bind_group = &context.global_resources.view(index).bind_group;
+                // Custom programs after the opaque draws, before the transparent ones
+                let [opaque, ordered_opaque, transparent] = &draws;
+                for draws in [opaque, ordered_opaque] {
+                    context
+                        .pbr_program
+                        .record(&mut render_pass, (bind_group, draws));
+                }
+                context
+                    .custom_programs
+                    .record(&mut render_pass, bind_group, &custom_draws);
+                context
+                    .pbr_program
+                    .record(&mut render_pass, (bind_group, transparent));
+                context
+                    .particle_program
+                    .record(&mut render_pass, bind_group);
+            });
+    }
+
+    (targets, drawn)
+}
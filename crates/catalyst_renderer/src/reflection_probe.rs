@@ -0,0 +1,862 @@
+use std::time::Instant;
+
+use catalyst_core::{
+    App, camera::Camera, pipeline::PhaseRenderPrepare, player::PlayerIndex,
+    transform::GlobalTransform,
+};
+use flecs_ecs::prelude::*;
+use glam::{Mat4, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    custom_program::{MaterialProgram, ProgramPasses},
+    frame_graph::{FrameGraph, GraphTextureDesc, PassStage},
+    global_resources::{GpuLight, LightUniforms},
+    layout::{StructLayout, rust_layout},
+    mesh::MeshInstance,
+    programs::{GpuProgram, probe_prefilter_program::ProbePrefilterProgram},
+    render::{ClearColor, RenderContext, RenderStats, collect_views},
+    render_order::{RenderBucket, RenderLayers, RenderOrder},
+    texture::{DepthMode, TextureHelper},
+    viewport::Viewport,
+    visibility::Frustum,
+    wind::WindUniforms,
+};
+
+/// Probes whose cubemaps are kept at once, the closest ones to the camera.
+pub const MAX_PROBES: usize = 8;
+
+/// Face size of the cubemaps in the probe array. Captures of another resolution are
+/// resampled into it.
+pub const PROBE_SIZE: u32 = 128;
+
+/// Roughness levels of each cubemap, PROBE_SIZE down to 4 pixels: mip `m` holds the
+/// reflection of roughness `m / (PROBE_MIPS - 1)`.
+pub const PROBE_MIPS: u32 = 6;
+
+pub(crate) const PROBE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+const CUBE_FACES: usize = 6;
+
+// Static probes are captured once more this long after their first capture, so meshes
+// and textures still uploading when the probe appeared show up
+const SETTLE_FRAMES: u64 = 60;
+
+const PASS_NAMES: [&str; CUBE_FACES] = [
+    "Reflection Probe +X",
+    "Reflection Probe -X",
+    "Reflection Probe +Y",
+    "Reflection Probe -Y",
+    "Reflection Probe +Z",
+    "Reflection Probe -Z",
+];
+const TARGET_NAMES: [&str; CUBE_FACES] = [
+    "Reflection Probe Capture +X",
+    "Reflection Probe Capture -X",
+    "Reflection Probe Capture +Y",
+    "Reflection Probe Capture -Y",
+    "Reflection Probe Capture +Z",
+    "Reflection Probe Capture -Z",
+];
+const PREFILTER_PASS: &str = "Reflection Probe Prefilter";
+
+/// Direction and up vector of the capture camera of each face, in cubemap face order.
+/// probe_prefilter.wgsl reads the captures with the same cameras.
+const FACE_CAMERAS: [(Vec3, Vec3); CUBE_FACES] = [
+    (Vec3::X, Vec3::Y),
+    (Vec3::NEG_X, Vec3::Y),
+    (Vec3::Y, Vec3::Z),
+    (Vec3::NEG_Y, Vec3::NEG_Z),
+    (Vec3::Z, Vec3::Y),
+    (Vec3::NEG_Z, Vec3::Y),
+];
+
+/// A box whose surfaces reflect the scene as seen from the probe, instead of nothing
+/// but the constant ambient. The scene is captured into a cubemap from the entity's
+/// position, blurred into one level per roughness, and looked up with box projection,
+/// so reflections line up with the walls of a room the box matches.
+///
+/// The box is `half_extents` around the entity's position, scaled by its transform
+/// and aligned with the world axes. Where boxes overlap the smallest one wins. The
+/// Overlay bucket, particles and debug drawing are never captured.
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReflectionProbe {
+    pub half_extents: Vec3,
+    /// Face size of the capture in pixels.
+    pub resolution: u32,
+    /// Captured again every `update_interval` frames, for moving objects and lights.
+    /// Static probes are captured when they appear and when they move.
+    pub dynamic: bool,
+    pub update_interval: u32,
+    /// Width of the band inside the box where the probe fades out, so there is no
+    /// seam at its edges.
+    pub blend_distance: f32,
+    /// Clip planes of the capture cameras.
+    pub near: f32,
+    pub far: f32,
+    /// Entities whose RenderLayers share none of these are left out of the capture.
+    pub layers: RenderLayers,
+    /// Set to capture the probe again on the next frame, then cleared.
+    #[serde(skip)]
+    pub request_capture: bool,
+}
+
+impl Default for ReflectionProbe {
+    fn default() -> Self {
+        Self {
+            half_extents: Vec3::splat(5.0),
+            resolution: 128,
+            dynamic: false,
+            update_interval: 30,
+            blend_distance: 1.0,
+            near: 0.1,
+            far: 200.0,
+            layers: RenderLayers::ALL,
+            request_capture: false,
+        }
+    }
+}
+
+/// Probes in use this frame and the capture drawn, gathered by "Prepare Reflection
+/// Probes". At most one probe is captured per frame.
+#[derive(Component, Default)]
+pub struct ReflectionProbes {
+    capture: Option<ProbeCapture>,
+    frame: u64,
+    in_scene: usize,
+    active: usize,
+    captures: u64,
+    capture_ms: f32,
+    capture_gpu_ms: f32,
+    memory_bytes: u64,
+}
+
+impl ReflectionProbes {
+    /// Probes in the scene, drawn or not.
+    pub fn probe_count(&self) -> usize {
+        self.in_scene
+    }
+
+    /// Probes with a cubemap the scene reflects this frame.
+    pub fn active_probes(&self) -> usize {
+        self.active
+    }
+
+    /// Captures since startup.
+    pub fn captures(&self) -> u64 {
+        self.captures
+    }
+
+    /// CPU milliseconds spent collecting and declaring the last capture.
+    pub fn capture_ms(&self) -> f32 {
+        self.capture_ms
+    }
+
+    /// GPU milliseconds of the last capture read back, faces and prefilter together.
+    /// 0 without timestamp queries.
+    pub fn capture_gpu_ms(&self) -> f32 {
+        self.capture_gpu_ms
+    }
+
+    /// The probe array and the capture target.
+    pub fn memory_bytes(&self) -> u64 {
+        self.memory_bytes
+    }
+
+    /// Views of this frame's capture, for culling: none without a capture.
+    pub(crate) fn capture_frusta(&self) -> Vec<Frustum> {
+        let Some(capture) = &self.capture else {
+            return Vec::new();
+        };
+        let projection = capture.projection(DepthMode::Standard);
+        (0..CUBE_FACES)
+            .map(|face| Frustum::from_view_proj(projection * face_view(face, capture.center)))
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct ProbeCapture {
+    slot: usize,
+    center: Vec3,
+    resolution: u32,
+    near: f32,
+    far: f32,
+    layers: RenderLayers,
+    content: ProbeContent,
+}
+
+impl ProbeCapture {
+    fn projection(&self, depth_mode: DepthMode) -> Mat4 {
+        let camera = Camera {
+            fov: 90f32.to_radians(),
+            aspect_ratio: 1.0,
+            near: self.near,
+            far: self.far,
+        };
+        match depth_mode {
+            DepthMode::Standard => camera.compute_projection_matrix(),
+            DepthMode::ReversedZ => camera.compute_reversed_z_projection_matrix(),
+        }
+    }
+}
+
+fn face_view(face: usize, center: Vec3) -> Mat4 {
+    let (direction, up) = FACE_CAMERAS[face];
+    Mat4::look_to_rh(center, direction, up)
+}
+
+/// What a slot of the probe array holds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ProbeContent {
+    entity: Entity,
+    center: Vec3,
+    resolution: u32,
+    layers: RenderLayers,
+    // Frame of the last capture, and how many there were
+    frame: u64,
+    captures: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GpuProbe {
+    pub center: [f32; 4],  // .xyz = capture position, .w = slot in the cube array
+    pub box_min: [f32; 4], // .w = blend distance
+    pub box_max: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ProbeUniform {
+    pub probes: [GpuProbe; MAX_PROBES], // Smallest box first
+    pub count: u32,
+    pub _padding0: u32,
+    pub _padding1: [u32; 2],
+}
+
+const _: () = assert!(std::mem::size_of::<GpuProbe>() == 48);
+const _: () = assert!(std::mem::size_of::<ProbeUniform>() == 400);
+
+impl GpuProbe {
+    pub const LAYOUT: StructLayout = rust_layout!(GpuProbe {
+        center: [f32; 4],
+        box_min: [f32; 4],
+        box_max: [f32; 4],
+    });
+}
+
+impl ProbeUniform {
+    pub const LAYOUT: StructLayout = rust_layout!(ProbeUniform {
+        probes: [GpuProbe; MAX_PROBES],
+        count: u32,
+        _padding0: u32,
+        _padding1: [u32; 2],
+    });
+}
+
+/// The prefiltered cubemaps of the probes, MAX_PROBES layers of a cube array, with
+/// their boxes. Part of GlobalResources, bound to every view (bindings 9 to 11).
+pub struct ProbeTextures {
+    pub(crate) uniform: wgpu::Buffer,
+    texture: wgpu::Texture,
+    pub(crate) view: wgpu::TextureView,
+    pub(crate) sampler: wgpu::Sampler,
+    capture: Option<CaptureTarget>,
+    contents: [Option<ProbeContent>; MAX_PROBES],
+}
+
+// The six faces of a capture, as seen by the face cameras
+struct CaptureTarget {
+    size: u32,
+    format: wgpu::TextureFormat,
+    faces: [wgpu::TextureView; CUBE_FACES],
+    bind_group: wgpu::BindGroup,
+}
+
+impl ProbeTextures {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let uniform = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Reflection Probe Uniform Buffer"),
+            size: std::mem::size_of::<ProbeUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Reflection Probes"),
+            size: wgpu::Extent3d {
+                width: PROBE_SIZE,
+                height: PROBE_SIZE,
+                depth_or_array_layers: (MAX_PROBES * CUBE_FACES) as u32,
+            },
+            mip_level_count: PROBE_MIPS,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: PROBE_FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Reflection Probes View"),
+            dimension: Some(wgpu::TextureViewDimension::CubeArray),
+            ..Default::default()
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Reflection Probe Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            uniform,
+            texture,
+            view,
+            sampler,
+            capture: None,
+            contents: [None; MAX_PROBES],
+        }
+    }
+
+    /// The probe array and the capture target, in bytes.
+    pub fn memory_bytes(&self) -> u64 {
+        // Each mip level is a quarter of the one above
+        let level = |size: u32| (size as u64 * size as u64) * 8;
+        let array: u64 = (0..PROBE_MIPS)
+            .map(|mip| level(PROBE_SIZE >> mip))
+            .sum::<u64>()
+            * (MAX_PROBES * CUBE_FACES) as u64;
+        let capture = self.capture.as_ref().map_or(0, |capture| {
+            let bytes_per_pixel = capture.format.block_copy_size(None).unwrap_or(4) as u64;
+            capture.size as u64 * capture.size as u64 * bytes_per_pixel * CUBE_FACES as u64
+        });
+        array + capture
+    }
+
+    // The capture target for `size` pixel faces, recreated when the size changes
+    fn ensure_capture(
+        &mut self,
+        device: &wgpu::Device,
+        size: u32,
+        format: wgpu::TextureFormat,
+        prefilter: &ProbePrefilterProgram,
+    ) -> &CaptureTarget {
+        if self
+            .capture
+            .as_ref()
+            .is_none_or(|capture| capture.size != size || capture.format != format)
+        {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Reflection Probe Capture"),
+                size: wgpu::Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: CUBE_FACES as u32,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            });
+            let faces = std::array::from_fn(|face| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some(TARGET_NAMES[face]),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: face as u32,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            });
+            let array = texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("Reflection Probe Capture View"),
+                dimension: Some(wgpu::TextureViewDimension::D2Array),
+                ..Default::default()
+            });
+            self.capture = Some(CaptureTarget {
+                size,
+                format,
+                faces,
+                bind_group: prefilter.bind_group(device, &array),
+            });
+        }
+        self.capture.as_ref().unwrap()
+    }
+
+    // One mip level of one face of a slot, for the prefilter to draw into
+    fn face_view(&self, slot: usize, face: usize, mip: u32) -> wgpu::TextureView {
+        self.texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Reflection Probe Face"),
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            base_mip_level: mip,
+            mip_level_count: Some(1),
+            base_array_layer: (slot * CUBE_FACES + face) as u32,
+            array_layer_count: Some(1),
+            ..Default::default()
+        })
+    }
+}
+
+pub fn register_reflection_probe_systems(app: &mut App) {
+    app.register_singleton_default::<ReflectionProbes>();
+
+    let probe_query = app
+        .world
+        .query::<(&ReflectionProbe, &GlobalTransform)>()
+        .set_cached()
+        .build();
+
+    let camera_query = app
+        .world
+        .query::<(&Camera, &GlobalTransform, Option<&PlayerIndex>)>()
+        .set_cached()
+        .build();
+
+    app.world
+        .system_named::<(&mut RenderContext, &mut ReflectionProbes, &RenderStats)>(
+            "Prepare Reflection Probes",
+        )
+        .kind(PhaseRenderPrepare)
+        .run(move |iter| prepare_probes(&iter.world(), &probe_query, &camera_query));
+}
+
+struct Candidate {
+    entity: Entity,
+    center: Vec3,
+    half_extents: Vec3,
+    distance: f32,
+    probe: ReflectionProbe,
+}
+
+// Picks the probes to use this frame, gives each a slot, picks the one to capture and
+// uploads the boxes
+fn prepare_probes(
+    world: &World,
+    probe_query: &Query<(&ReflectionProbe, &GlobalTransform)>,
+    camera_query: &Query<(&Camera, &GlobalTransform, Option<&PlayerIndex>)>,
+) {
+    let eye = collect_views(camera_query)
+        .first()
+        .map(|(_, _, transform)| transform.transform_point3(Vec3::ZERO));
+
+    let mut candidates = Vec::new();
+    probe_query.each_entity(|entity, (probe, global)| {
+        let (scale, _, center) = global.0.to_scale_rotation_translation();
+        let half_extents = (probe.half_extents * scale.abs()).max(Vec3::splat(0.01));
+        // Distance from the eye to the box, 0 inside
+        let distance = eye.map_or(0.0, |eye| {
+            ((eye - center).abs() - half_extents)
+                .max(Vec3::ZERO)
+                .length()
+        });
+        candidates.push(Candidate {
+            entity: entity.id(),
+            center,
+            half_extents,
+            distance,
+            probe: probe.clone(),
+        });
+    });
+    let in_scene = candidates.len();
+    candidates.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+    candidates.truncate(MAX_PROBES);
+
+    let mut requested = None;
+    world.get::<&mut RenderContext>(|context| {
+        world.get::<&mut ReflectionProbes>(|probes| {
+            let textures = &mut context.global_resources.probes;
+            probes.frame += 1;
+
+            // Probes keep the slot already holding their cubemap
+            let mut claimed = [false; MAX_PROBES];
+            let mut slots: Vec<Option<usize>> = candidates
+                .iter()
+                .map(|candidate| {
+                    let slot = textures.contents.iter().position(|content| {
+                        content.is_some_and(|content| content.entity == candidate.entity)
+                    })?;
+                    claimed[slot] = true;
+                    Some(slot)
+                })
+                .collect();
+            for slot in slots.iter_mut().filter(|slot| slot.is_none()) {
+                // Free slots first, then the ones of probes no longer in use
+                let free = (0..MAX_PROBES)
+                    .find(|&index| !claimed[index] && textures.contents[index].is_none())
+                    .or_else(|| (0..MAX_PROBES).find(|&index| !claimed[index]));
+                if let Some(free) = free {
+                    claimed[free] = true;
+                    textures.contents[free] = None;
+                    *slot = Some(free);
+                }
+            }
+
+            // Never captured first, then moved or requested, then the periodic ones
+            let frame = probes.frame;
+            let urgency = |candidate: &Candidate, slot: usize| -> Option<u8> {
+                let probe = &candidate.probe;
+                let Some(content) = textures.contents[slot] else {
+                    return Some(0);
+                };
+                let changed = content.center != candidate.center
+                    || content.resolution != probe.resolution
+                    || content.layers != probe.layers;
+                if changed || probe.request_capture {
+                    return Some(1);
+                }
+                let due = if probe.dynamic {
+                    frame >= content.frame + probe.update_interval.max(1) as u64
+                } else {
+                    content.captures == 1 && frame >= content.frame + SETTLE_FRAMES
+                };
+                due.then_some(2)
+            };
+            let capture = candidates
+                .iter()
+                .zip(&slots)
+                .filter_map(|(candidate, slot)| {
+                    let slot = (*slot)?;
+                    Some((urgency(candidate, slot)?, candidate, slot))
+                })
+                .min_by_key(|(urgency, _, _)| *urgency)
+                .map(|(_, candidate, slot)| {
+                    let probe = &candidate.probe;
+                    let captures = textures.contents[slot].map_or(0, |content| content.captures);
+                    if probe.request_capture {
+                        requested = Some(candidate.entity);
+                    }
+                    ProbeCapture {
+                        slot,
+                        center: candidate.center,
+                        resolution: probe.resolution.clamp(16, 2048),
+                        near: probe.near.max(0.001),
+                        far: probe.far.max(probe.near + 0.01),
+                        layers: probe.layers,
+                        content: ProbeContent {
+                            entity: candidate.entity,
+                            center: candidate.center,
+                            resolution: probe.resolution,
+                            layers: probe.layers,
+                            frame,
+                            captures: captures + 1,
+                        },
+                    }
+                });
+
+            // Probes with a cubemap, or getting one this frame
+            let mut gpu_probes: Vec<(f32, GpuProbe)> = candidates
+                .iter()
+                .zip(&slots)
+                .filter_map(|(candidate, slot)| {
+                    let slot = (*slot)?;
+                    let captured_at = match (textures.contents[slot], capture) {
+                        (_, Some(capture)) if capture.slot == slot => capture.center,
+                        (Some(content), _) if content.entity == candidate.entity => content.center,
+                        _ => return None,
+                    };
+                    let box_min = candidate.center - candidate.half_extents;
+                    let box_max = candidate.center + candidate.half_extents;
+                    let volume = candidate.half_extents.x
+                        * candidate.half_extents.y
+                        * candidate.half_extents.z;
+                    Some((
+                        volume,
+                        GpuProbe {
+                            center: captured_at.extend(slot as f32).to_array(),
+                            box_min: box_min
+                                .extend(candidate.probe.blend_distance.max(0.0))
+                                .to_array(),
+                            box_max: box_max.extend(0.0).to_array(),
+                        },
+                    ))
+                })
+                .collect();
+            gpu_probes.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            let mut uniform = ProbeUniform {
+                probes: [GpuProbe {
+                    center: [0.0; 4],
+                    box_min: [0.0; 4],
+                    box_max: [0.0; 4],
+                }; MAX_PROBES],
+                count: gpu_probes.len() as u32,
+                _padding0: 0,
+                _padding1: [0; 2],
+            };
+            for (index, (_, probe)) in gpu_probes.iter().enumerate() {
+                uniform.probes[index] = *probe;
+            }
+            context
+                .queue
+                .write_buffer(&textures.uniform, 0, bytemuck::bytes_of(&uniform));
+
+            probes.capture = capture;
+            probes.in_scene = in_scene;
+            probes.active = gpu_probes.len();
+            probes.memory_bytes = textures.memory_bytes();
+        });
+    });
+
+    // Timings of the last capture read back, while they are there
+    world.get::<&RenderStats>(|stats| {
+        let gpu_ms: f32 = stats
+            .pass_timings
+            .iter()
+            .filter(|(name, _)| PASS_NAMES.contains(name) || *name == PREFILTER_PASS)
+            .map(|(_, ms)| ms)
+            .sum();
+        if gpu_ms > 0.0 {
+            world.get::<&mut ReflectionProbes>(|probes| probes.capture_gpu_ms = gpu_ms);
+        }
+    });
+
+    if let Some(entity) = requested {
+        world
+            .entity_from_id(entity)
+            .get::<&mut ReflectionProbe>(|probe| probe.request_capture = false);
+    }
+}
+
+/// Declares this frame's capture, if any: the scene drawn from the probe into the six
+/// faces of the capture target, then blurred into the probe's slot of the array, one
+/// level per roughness. Uses the global views from `view_offset` on, one per face.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn add_probe_capture_passes(
+    graph: &mut FrameGraph,
+    context: &mut RenderContext,
+    probes: &mut ReflectionProbes,
+    scene_lights: LightUniforms,
+    point_lights: &[GpuLight],
+    view_offset: usize,
+    clear_color: ClearColor,
+    mesh_query: &Query<&MeshInstance>,
+    ordered_mesh_query: &Query<(&MeshInstance, &RenderOrder, &GlobalTransform)>,
+    custom_mesh_query: &Query<(&MeshInstance, &MaterialProgram)>,
+) {
+    let Some(capture) = probes.capture else {
+        return;
+    };
+    let started = Instant::now();
+
+    context
+        .global_resources
+        .ensure_views(&context.device, view_offset + CUBE_FACES);
+    let size = capture.resolution;
+    let format = context.config.format;
+    let faces = context
+        .global_resources
+        .probes
+        .ensure_capture(
+            &context.device,
+            size,
+            format,
+            &context.probe_prefilter_program,
+        )
+        .faces
+        .clone();
+    context.global_resources.probes.contents[capture.slot] = Some(capture.content);
+
+    // Drawn without exposure, the prefilter undoes the tone mapping of the captures
+    let mut light_data = scene_lights;
+    light_data.camera_pos = capture.center.to_array();
+    let projection = capture.projection(context.depth_mode);
+    let cluster_projection = capture.projection(DepthMode::Standard);
+    let viewport = Viewport::full(size, size);
+
+    // The same draws for every face, all from the probe's position
+    let layers = Some(capture.layers);
+    let draws = [
+        context.pbr_program.collect_draws(mesh_query, layers),
+        context.pbr_program.collect_bucket(
+            ordered_mesh_query,
+            RenderBucket::Opaque,
+            capture.center,
+            layers,
+        ),
+        context.pbr_program.collect_bucket(
+            ordered_mesh_query,
+            RenderBucket::Transparent,
+            capture.center,
+            layers,
+        ),
+    ];
+    let custom_draws = context.custom_programs.collect_draws(
+        custom_mesh_query,
+        ProgramPasses::PROBE_CAPTURES,
+        layers,
+    );
+
+    let mut targets = Vec::with_capacity(CUBE_FACES);
+    for (face, face_target) in faces.into_iter().enumerate() {
+        let view = face_view(face, capture.center);
+        let index = view_offset + face;
+        let view_resources = context.global_resources.view_mut(index);
+        // Captured at rest, a probe isn't redrawn often enough to follow the wind
+        view_resources.update_camera(
+            &context.queue,
+            projection * view,
+            projection * view,
+            1.0,
+            WindUniforms::default(),
+        );
+        view_resources.update_lights(&context.queue, light_data);
+        view_resources.update_clusters(
+            &context.queue,
+            point_lights,
+            view,
+            cluster_projection,
+            capture.near,
+            capture.far,
+            &viewport,
+        );
+
+        let desc = GraphTextureDesc {
+            width: size,
+            height: size,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        };
+        let color = graph.import_texture(TARGET_NAMES[face], face_target);
+        targets.push(color);
+        // The pipelines always write all three targets, only the color is kept
+        let depth = graph.create_texture(
+            "Reflection Probe Depth",
+            GraphTextureDesc {
+                format: TextureHelper::DEPTH_FORMAT,
+                ..desc
+            },
+        );
+        let velocity = graph.create_texture(
+            "Reflection Probe Velocity",
+            GraphTextureDesc {
+                format: TextureHelper::VELOCITY_FORMAT,
+                ..desc
+            },
+        );
+        let luminance = graph.create_texture(
+            "Reflection Probe Luminance",
+            GraphTextureDesc {
+                format: TextureHelper::LUMINANCE_FORMAT,
+                ..desc
+            },
+        );
+
+        let draws = draws.clone();
+        let custom_draws = custom_draws.clone();
+        graph
+            .add_pass(PASS_NAMES[face], PassStage::Scene)
+            .write(color)
+            .write(velocity)
+            .write(luminance)
+            .write(depth)
+            .record_parallel(move |pass| {
+                let timestamp_writes = pass.timestamp_writes();
+                let (color, velocity, luminance, depth) = (
+                    pass.view(color),
+                    pass.view(velocity),
+                    pass.view(luminance),
+                    pass.view(depth),
+                );
+                let context = pass.context;
+                let attachment = |view, load, store| {
+                    Some(wgpu::RenderPassColorAttachment {
+                        view,
+                        resolve_target: None,
+                        depth_slice: None,
+                        ops: wgpu::Operations { load, store },
+                    })
+                };
+                let unused = wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT);
+
+                let mut render_pass = pass.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some(PASS_NAMES[face]),
+                    color_attachments: &[
+                        // Same backdrop as the main pass
+                        attachment(color, clear_color.load_op(), wgpu::StoreOp::Store),
+                        attachment(velocity, unused, wgpu::StoreOp::Discard),
+                        attachment(luminance, unused, wgpu::StoreOp::Discard),
+                    ],
+                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                        view: depth,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(context.depth_mode.clear_value()),
+                            store: wgpu::StoreOp::Discard,
+                        }),
+                        stencil_ops: None,
+                    }),
+                    timestamp_writes,
+                    ..Default::default()
+                });
+
+                let bind_group = &context.global_resources.view(index).bind_group;
+                // Custom programs after the opaque draws, before the transparent ones
+                let [opaque, ordered_opaque, transparent] = &draws;
+                for draws in [opaque, ordered_opaque] {
+                    context
+                        .pbr_program
+                        .record(&mut render_pass, (bind_group, draws));
+                }
+                context
+                    .custom_programs
+                    .record(&mut render_pass, bind_group, &custom_draws);
+                context
+                    .pbr_program
+                    .record(&mut render_pass, (bind_group, transparent));
+            });
+    }
+
+    // Blurs the faces into the probe's slot, one mip level per roughness. Only the
+    // first level's first face is timed.
+    let array = graph.import_texture(
+        "Reflection Probes",
+        context.global_resources.probes.view.clone(),
+    );
+    let mut prefilter = graph.add_pass(PREFILTER_PASS, PassStage::Scene);
+    for target in targets {
+        prefilter = prefilter.read(target);
+    }
+    let slot = capture.slot;
+    prefilter.write(array).record_parallel(move |pass| {
+        let mut timestamp_writes = pass.timestamp_writes();
+        let context = pass.context;
+        let textures = &context.global_resources.probes;
+        let Some(capture) = &textures.capture else {
+            return;
+        };
+        for mip in 0..PROBE_MIPS {
+            for face in 0..CUBE_FACES {
+                let view = textures.face_view(slot, face, mip);
+                let mut render_pass = pass.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some(PREFILTER_PASS),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &view,
+                        resolve_target: None,
+                        depth_slice: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    timestamp_writes: timestamp_writes.take(),
+                    ..Default::default()
+                });
+                context
+                    .probe_prefilter_program
+                    .record(&mut render_pass, (&capture.bind_group, face as u32, mip));
+            }
+        }
+    });
+
+    probes.captures += 1;
+    probes.capture_ms = started.elapsed().as_secs_f32() * 1000.0;
+}
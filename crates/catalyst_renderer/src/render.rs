@@ -1,40 +1,165 @@
+use std::collections::BTreeSet;
+
 use catalyst_assets::material::{TextureData, TextureFormat, TextureType};
 use catalyst_core::{
-    App,
+    App, AppExit,
     camera::Camera,
+    camera_effects::CameraEffects,
+    color::Color,
+    light::{DirectionalLight, PointLight},
     physics::ColliderDefinition,
-    pipeline::{PhasePresent, PhaseRender3D},
+    player::PlayerIndex,
+    pipeline::{PhasePresent, PhaseRender3D, PhaseRenderPrepare},
+    time::Time,
     transform::GlobalTransform,
 };
 use catalyst_window::MainWindow;
 use flecs_ecs::prelude::*;
-use glam::{Mat4, Vec3, Vec4};
-use wgpu::{Device, Queue, Surface, SurfaceConfiguration};
+use serde::{Deserialize, Serialize};
+use glam::{Mat4, Vec2, Vec3};
+use wgpu::{Device, Queue, SurfaceConfiguration};
 
 use crate::{
-    global_resources::{GlobalResources, GpuPointLight, LightUniforms},
-    material::AssetMaterial,
+    capabilities::{
+        AdapterSelection, AdapterSummary, OPTIONAL_FEATURES, REQUIRED_FEATURES,
+        RendererCapabilities, RendererInitError, failure_report, select_adapter,
+        select_surface_format,
+    },
+    clusters::LightingMode,
+    color_grading::{ColorGradingState, add_color_grading_pass},
+    custom_program::{
+        CustomProgramContext, CustomPrograms, MaterialProgram, MaterialPrograms, ProgramPasses,
+    },
+    debug_view::{DebugViewMode, add_debug_view_passes},
+    depth_of_field::{DepthOfField, DepthOfFieldState, add_depth_of_field_passes},
+    device_recovery::DeviceLost,
+    dynamic_resolution::{DynamicResolution, DynamicResolutionState},
+    exposure::{ExposureMeter, ExposureState},
+    fog::FogSettings,
+    frustum_culling::FrustumCulled,
+    occlusion_culling::OcclusionQueries,
+    particles::add_gpu_particle_passes,
+    picking::{Picking, PickingTarget, add_picking_pass},
+    geometry_pool::{GeometryPool, GeometryPoolStats},
+    frame_graph::{FrameGraph, GraphTextureDesc, PassStage, SceneTargets},
+    global_resources::{GlobalResources, GpuLight, LIGHT_SPOT, LightUniforms},
+    material::{AssetMaterial, GpuMaterial},
     mesh::{AssetMesh, MeshInstance},
+    portal_culling::RoomCulled,
+    readback::ReadbackManager,
+    reflection::{PlanarReflections, ReflectedView, add_reflection_passes},
+    reflection_probe::{ReflectionProbes, add_probe_capture_passes},
+    render_order::{RenderBucket, RenderOrder},
+    screenshot::{Screenshots, capture_screenshots},
+    spot_light::{Gobos, SpotLight},
+    static_batching::{StaticBatchStats, StaticBatched},
+    streaming::TextureStreaming,
+    surface::SurfaceState,
     programs::{
-        self, DebugLinesProgram, GpuProgram, PbrProgram, debug_lines_program::DebugLineVertex,
+        self, ColorGradingProgram, DebugLinesProgram, DebugViewProgram, DecalProgram, DepthOfFieldProgram, GpuParticleProgram, GpuProgram,
+        MaterialPreviewProgram, OcclusionProxyProgram, OutlineProgram, ParticleProgram, PbrProgram, PickingProgram, ReflectionProgram, TaaProgram, UpscaleProgram,
+        debug_lines_program::DebugLineVertex,
+        luminance_program::LuminanceProgram,
+        pbr_program::{FrameDraws, PbrPass, PipelineKey},
+        reflection_program::MAX_REFLECTIONS,
+        probe_prefilter_program::ProbePrefilterProgram,
     },
-    texture::{GpuTexture, TextureHelper},
+    taa::{TaaHistory, TaaSettings, jitter_projection},
+    texture::{DepthMode, GpuTexture, TextureHelper},
+    viewport::{SplitScreenSettings, compute_viewports},
 };
 
 #[derive(Component)]
 pub struct RenderContext {
+    /// Kept to recreate the surface, see SurfaceState.
+    pub instance: wgpu::Instance,
     pub device: Device,
     pub queue: Queue,
-    pub surface: Surface<'static>,
+    pub surface: SurfaceState,
     pub config: SurfaceConfiguration,
     pub depth_texture: wgpu::TextureView,
+    pub depth_mode: DepthMode,
 
     pub default_diffuse: GpuTexture,
+    pub default_normal: GpuTexture,
 
     pub global_resources: GlobalResources,
+    /// Vertex and index data of every mesh, see GpuGeometry.
+    pub geometry_pool: GeometryPool,
 
     pub pbr_program: PbrProgram,
     pub debug_lines_program: DebugLinesProgram,
+    pub particle_program: ParticleProgram,
+    /// The GPU particle tier, None when the adapter can't run it
+    /// (RendererCapabilities::supports_gpu_particles).
+    pub gpu_particle_program: Option<GpuParticleProgram>,
+    pub decal_program: DecalProgram,
+    pub reflection_program: ReflectionProgram,
+    pub taa_program: TaaProgram,
+    pub luminance_program: LuminanceProgram,
+    pub outline_program: OutlineProgram,
+    pub depth_of_field_program: DepthOfFieldProgram,
+    pub upscale_program: UpscaleProgram,
+    pub color_grading_program: ColorGradingProgram,
+    pub probe_prefilter_program: ProbePrefilterProgram,
+    pub occlusion_proxy_program: OcclusionProxyProgram,
+    pub material_preview_program: MaterialPreviewProgram,
+    pub debug_view_program: DebugViewProgram,
+    pub picking_program: PickingProgram,
+    /// The programs registered in MaterialPrograms, see CustomMaterialProgram.
+    pub custom_programs: CustomPrograms,
+
+    pub taa: TaaHistory,
+    pub exposure: ExposureMeter,
+    pub depth_of_field: DepthOfFieldState,
+    pub color_grading: ColorGradingState,
+    pub occlusion: OcclusionQueries,
+    pub picking_target: PickingTarget,
+    /// GPU to CPU copies of every feature reading results back, see ReadbackManager.
+    pub readback: ReadbackManager,
+
+    pub(crate) device_lost: DeviceLost,
+}
+
+/// Renderer-wide options. Read once by "init renderer", so set this
+/// before the first frame; changing it afterwards has no effect. Kept in the settings
+/// file as "render".
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RenderSettings {
+    pub depth_mode: DepthMode,
+    /// Backend / adapter overrides. CATALYST_BACKEND and CATALYST_ADAPTER take precedence.
+    pub adapter: AdapterSelection,
+    /// Forces the 4-point-light uniform path even when the adapter supports clustered lighting.
+    pub uniform_lighting: bool,
+    /// Mip streaming budget and policy. Read every frame.
+    pub texture_streaming: TextureStreaming,
+    /// Records every frame graph pass on the render thread, into one encoder. For comparing
+    /// output and RenderStats::recording_ms against parallel recording. Read every frame.
+    pub serial_recording: bool,
+    /// Render scale controller and manual override. Read every frame.
+    pub dynamic_resolution: DynamicResolution,
+    /// Presents in step with the display, without tearing. Off presents as soon as a
+    /// frame is done where the platform allows it. Read every frame.
+    pub vsync: bool,
+    /// Most GPU tier particles alive at once, over all emitters. Their state takes 96
+    /// bytes each, allocated when the first GPU emitter appears.
+    pub gpu_particle_capacity: u32,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            depth_mode: DepthMode::default(),
+            adapter: AdapterSelection::default(),
+            uniform_lighting: false,
+            texture_streaming: TextureStreaming::default(),
+            serial_recording: false,
+            dynamic_resolution: DynamicResolution::default(),
+            vsync: true,
+            gpu_particle_capacity: 1 << 20,
+        }
+    }
 }
 
 #[derive(Component, Default)]
@@ -43,18 +168,103 @@ pub struct DebugDraw3D {
 }
 
 impl DebugDraw3D {
-    pub fn push_line(&mut self, start: Vec3, end: Vec3, color: Vec4) {
+    /// `color` is linear; a Vec4 or array works as before.
+    pub fn push_line(&mut self, start: Vec3, end: Vec3, color: impl Into<Color>) {
+        let color = color.into().to_linear();
         self.debug_line_vertices.push(DebugLineVertex {
             position: start.into(),
-            color: color.into(),
+            color,
         });
         self.debug_line_vertices.push(DebugLineVertex {
             position: end.into(),
-            color: color.into(),
+            color,
         });
     }
 }
 
+/// Color the scene is cleared to where nothing is drawn (no skybox). Read every frame.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ClearColor(pub Color);
+
+impl Default for ClearColor {
+    fn default() -> Self {
+        // Dark slate blue
+        Self(Color::linear_rgb(0.1, 0.2, 0.3))
+    }
+}
+
+impl ClearColor {
+    pub(crate) fn load_op(&self) -> wgpu::LoadOp<wgpu::Color> {
+        let [r, g, b, a] = self.0.to_linear();
+        wgpu::LoadOp::Clear(wgpu::Color {
+            r: r as f64,
+            g: g as f64,
+            b: b as f64,
+            a: a as f64,
+        })
+    }
+}
+
+/// Renderer counters for the current session, updated by "Render Frame".
+#[derive(Component, Clone, Debug, Default)]
+pub struct RenderStats {
+    /// Pipeline permutations compiled so far.
+    pub pipeline_cache_size: usize,
+    pub pipeline_cache_hits: u64,
+    pub pipeline_cache_misses: u64,
+    /// Draws skipped since startup because their mesh or material had no GPU resource.
+    pub missing_resource_skips: u64,
+    /// Mesh draw calls in the screen's views last frame, summed over all views.
+    /// Planar reflections draw theirs on top.
+    pub mesh_draws: usize,
+    /// CPU tier particles drawn last frame, summed over all views.
+    pub particles_drawn: u64,
+    /// GPU tier emitters last frame, and the particles they spawned. How many are alive
+    /// stays on the GPU; the GPU Particle passes' timings show their cost.
+    pub gpu_particle_emitters: usize,
+    pub gpu_particles_spawned: u32,
+    /// Decals drawn last frame, summed over all views.
+    pub decals_drawn: u64,
+    /// Mesh draws skipped last frame because an occlusion query found them hidden,
+    /// summed over all views. See OcclusionCullable.
+    pub occlusion_skipped_draws: usize,
+    /// Planar reflections redrawn last frame, each one an extra scene pass.
+    pub reflections_drawn: usize,
+    /// Point and spot lights in the scene last frame (clustered lighting draws up to clusters::MAX_LIGHTS, uniform lighting 4).
+    pub point_lights: usize,
+    /// How many of `point_lights` are spot lights, and how many of those projected a gobo.
+    pub spot_lights: usize,
+    pub gobos: usize,
+    /// Average light count of the clusters holding any light, over all views. 0 with uniform lighting.
+    pub lights_per_cluster: f32,
+    /// Frame graph passes recorded last frame, after culling.
+    pub graph_passes: usize,
+    /// Textures held by the frame graph pool, transient and persistent, and their memory.
+    pub pool_textures: usize,
+    pub pool_memory_bytes: u64,
+    /// Transient textures that shared memory with another one last frame.
+    pub aliased_textures: usize,
+    /// CPU milliseconds spent recording and finishing command buffers last frame.
+    pub recording_ms: f32,
+    /// Passes recorded on worker threads last frame.
+    pub parallel_passes: usize,
+    /// GPU milliseconds of each timed pass, a few frames old. Empty without timestamp queries.
+    pub pass_timings: Vec<(&'static str, f32)>,
+    /// Sum of `pass_timings`, and how many times they were read back so far.
+    pub gpu_frame_ms: f32,
+    pub gpu_timing_samples: u64,
+    /// Fraction of the window size the scene was drawn at last frame, see DynamicResolution.
+    pub render_scale: f32,
+    /// Blocks, fill and fragmentation of the mesh geometry pool.
+    pub geometry_pool: GeometryPoolStats,
+    /// Static batches, the draws they save and their memory, see StaticGeometry.
+    pub static_batching: StaticBatchStats,
+    /// Per-object uniform buffers written last frame, and their bytes: only instances
+    /// whose GlobalTransform changed, so near zero for a still scene.
+    pub mesh_uniform_writes: usize,
+    pub mesh_uniform_bytes: u64,
+}
+
 #[derive(Component, Default)]
 pub struct RenderTarget {
     pub view: Option<wgpu::TextureView>,
@@ -64,8 +274,382 @@ pub struct RenderTarget {
 #[derive(Component)]
 pub struct MaterialLayout(pub wgpu::BindGroupLayout);
 
+struct GpuInit {
+    instance: wgpu::Instance,
+    surface: SurfaceState,
+    device: Device,
+    queue: Queue,
+    config: SurfaceConfiguration,
+    capabilities: RendererCapabilities,
+}
+
+// Everything that was enumerated before init failed, for the report
+struct GpuInitFailure {
+    error: RendererInitError,
+    adapters: Vec<AdapterSummary>,
+}
+
+impl From<RendererInitError> for GpuInitFailure {
+    fn from(error: RendererInitError) -> Self {
+        Self {
+            error,
+            adapters: Vec::new(),
+        }
+    }
+}
+
+fn init_gpu(
+    window: &MainWindow,
+    selection: &AdapterSelection,
+) -> Result<GpuInit, GpuInitFailure> {
+    // 1. Create the Instance, limited to the forced backend if there is one
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends: selection.backends(),
+        ..Default::default()
+    });
+
+    // 2. Create Surface (The canvas on the window)
+    // It shares ownership of the window, so neither can outlive the other's needs
+    let surface = SurfaceState::new(&instance, window.0.clone())
+        .map_err(|e| RendererInitError::Surface(e.to_string()))?;
+    let raw_surface = surface.surface().expect("a new SurfaceState holds a surface");
+
+    // 3. Enumerate and log every adapter, then pick one
+    let mut adapters = instance.enumerate_adapters(selection.backends());
+    let summaries: Vec<AdapterSummary> = adapters
+        .iter()
+        .enumerate()
+        .map(|(index, adapter)| AdapterSummary {
+            index,
+            info: adapter.get_info(),
+            features: adapter.features(),
+            supports_surface: adapter.is_surface_supported(raw_surface),
+        })
+        .collect();
+
+    log::info!("Available adapters:");
+    for summary in &summaries {
+        log::info!("  {}", summary.describe());
+    }
+
+    let fail = |error: RendererInitError| GpuInitFailure {
+        error,
+        adapters: summaries.clone(),
+    };
+
+    let index = select_adapter(&summaries, selection).map_err(fail)?;
+    let adapter = adapters.swap_remove(index);
+    let info = adapter.get_info();
+
+    // 4. Check features: required ones must be there, optional ones are enabled when present
+    let missing = REQUIRED_FEATURES.difference(adapter.features());
+    if !missing.is_empty() {
+        return Err(fail(RendererInitError::MissingFeatures {
+            adapter: info.name.clone(),
+            missing,
+        }));
+    }
+    let features = REQUIRED_FEATURES.union(OPTIONAL_FEATURES.intersection(adapter.features()));
+
+    // 5. Request Device (Logical GPU connection)
+    // We use 'pollster' to block on this async function inside a sync system.
+    // The adapter's own limits, so downlevel adapters (no storage buffers in fragment
+    // shaders) still get a device and fall back to uniform lighting.
+    let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+        label: Some("Catalyst Device"),
+        required_features: features,
+        required_limits: adapter.limits(),
+        ..Default::default()
+    }))
+    .map_err(|e| fail(e.into()))?;
+
+    // 6. Configure the Surface
+    let size = window.0.inner_size();
+    let caps = raw_surface.get_capabilities(&adapter);
+    let format = select_surface_format(&caps.formats).map_err(fail)?;
+    // Copied from for screenshots where the surface allows it
+    let usage = wgpu::TextureUsages::RENDER_ATTACHMENT
+        | (caps.usages & wgpu::TextureUsages::COPY_SRC);
+    let config = wgpu::SurfaceConfiguration {
+        usage,
+        format,
+        width: size.width,
+        height: size.height,
+        // VSync On, always supported. "Apply VSync" switches it to RenderSettings::vsync
+        present_mode: wgpu::PresentMode::Fifo,
+        desired_maximum_frame_latency: 2,
+        alpha_mode: caps.alpha_modes[0],
+        view_formats: vec![],
+    };
+    raw_surface.configure(&device, &config);
+
+    log::info!(
+        "Using adapter [{}] {} ({:?}, driver: {} {}), surface format {:?}",
+        index, info.name, info.backend, info.driver, info.driver_info, format
+    );
+    log::info!(
+        "Optional features enabled: {:?}",
+        features.intersection(OPTIONAL_FEATURES)
+    );
+
+    Ok(GpuInit {
+        instance,
+        surface,
+        capabilities: RendererCapabilities {
+            adapter: info,
+            features: device.features(),
+            limits: device.limits(),
+            downlevel: adapter.get_downlevel_capabilities().flags,
+            surface_format: format,
+        },
+        device,
+        queue,
+        config,
+    })
+}
+
+/// Creates the GPU device and everything in RenderContext for `window`, at startup and
+/// again after a device loss. Requests an exit when no usable adapter is found.
+pub(crate) fn init_renderer(world: &World, window: &MainWindow) -> bool {
+    log::info!("Initializing GPU");
+
+    let selection = world
+        .get::<&RenderSettings>(|settings| settings.adapter)
+        .with_env_overrides();
+
+    let GpuInit {
+        instance,
+        surface,
+        device,
+        queue,
+        config,
+        capabilities,
+    } = match init_gpu(window, &selection) {
+        Ok(gpu) => gpu,
+        Err(failure) => {
+            log::error!(
+                "{}",
+                failure_report(&failure.error, &selection, &failure.adapters)
+            );
+            world.get::<&mut AppExit>(|exit| exit.request(1));
+            return false;
+        }
+    };
+
+    let (depth_mode, uniform_lighting, gpu_particle_capacity) =
+        world.get::<&RenderSettings>(|settings| {
+            (
+                settings.depth_mode,
+                settings.uniform_lighting,
+                settings.gpu_particle_capacity,
+            )
+        });
+
+    let lighting_mode = if uniform_lighting {
+        LightingMode::Uniform
+    } else {
+        LightingMode::for_limits(&capabilities.limits)
+    };
+    log::info!("Lighting mode: {:?}", lighting_mode);
+
+    // Reversed-Z only buys precision with a float depth buffer;
+    // with a unorm format it is strictly worse than the standard mapping.
+    if depth_mode == DepthMode::ReversedZ {
+        assert_eq!(
+            TextureHelper::DEPTH_FORMAT,
+            wgpu::TextureFormat::Depth32Float,
+            "Reversed-Z requires a Depth32Float depth buffer"
+        );
+    }
+
+    let depth_texture =
+        TextureHelper::create_depth_texture(&device, &config, "Depth Texture");
+
+    let global_resources = GlobalResources::new(&device, lighting_mode);
+
+    let render_context = programs::GpuProgramRenderContext {
+        device: &device,
+        queue: &queue,
+        format: config.format,
+        depth_mode,
+        lighting_mode,
+    };
+
+    let pbr_program = PbrProgram::new(&render_context, &global_resources.layout);
+    let debug_lines_program =
+        DebugLinesProgram::new(&render_context, &global_resources.layout);
+    let particle_program =
+        ParticleProgram::new(&render_context, &global_resources.layout);
+    let gpu_particle_program = if capabilities.supports_gpu_particles() {
+        Some(GpuParticleProgram::new(
+            &render_context,
+            &global_resources.layout,
+            gpu_particle_capacity,
+        ))
+    } else {
+        log::warn!(
+            "No compute shaders or indirect draws on this adapter: GPU tier particle emitters are simulated on the CPU"
+        );
+        None
+    };
+    let decal_program =
+        DecalProgram::new(&render_context, &global_resources.layout);
+    let reflection_program = ReflectionProgram::new(
+        &render_context,
+        &(
+            global_resources.layout.clone(),
+            pbr_program.mesh_layout.clone(),
+        ),
+    );
+    let outline_program = OutlineProgram::new(
+        &render_context,
+        &(
+            global_resources.layout.clone(),
+            pbr_program.mesh_layout.clone(),
+        ),
+    );
+    let depth_of_field_program = DepthOfFieldProgram::new(&render_context);
+    let taa_program = TaaProgram::new(&render_context, &());
+    let upscale_program = UpscaleProgram::new(&render_context, &());
+    let color_grading_program = ColorGradingProgram::new(&render_context, &());
+    let probe_prefilter_program = ProbePrefilterProgram::new(&render_context, &());
+    let occlusion_proxy_program =
+        OcclusionProxyProgram::new(&render_context, &global_resources.layout);
+    let material_preview_program =
+        MaterialPreviewProgram::new(&render_context, &pbr_program.material_layout);
+    let debug_view_program = DebugViewProgram::new(
+        &render_context,
+        &(
+            global_resources.layout.clone(),
+            pbr_program.material_layout.clone(),
+            pbr_program.mesh_layout.clone(),
+        ),
+    );
+    let picking_program = PickingProgram::new(
+        &render_context,
+        &(
+            global_resources.layout.clone(),
+            pbr_program.material_layout.clone(),
+            pbr_program.mesh_layout.clone(),
+        ),
+    );
+    let custom_programs = world.get::<&MaterialPrograms>(|programs| {
+        programs.create(&CustomProgramContext {
+            device: &device,
+            queue: &queue,
+            global_layout: &global_resources.layout,
+            mesh_layout: &pbr_program.mesh_layout,
+            format: config.format,
+            depth_mode,
+        })
+    });
+    let taa = TaaHistory::new(&device);
+    let luminance_program = LuminanceProgram::new(&device);
+    let exposure = ExposureMeter::new(&device, &config, &luminance_program);
+    let occlusion = OcclusionQueries::new(&device);
+    let readback = ReadbackManager::new(config.width, config.height);
+
+    //let line_draw_pipeline = create_line_draw_pipeline(&device, &bind_group_layout, &config);
+
+    // --- CREATE DEFAULT TEXTURE (1x1 White Pixel) ---
+    // We create this manually so we don't depend on an asset file existing
+    let white_pixel = GpuTexture::from_image(
+        &device,
+        &queue,
+        &TextureData {
+            name: "Default White Pixel".to_string(),
+            width: 1,
+            height: 1,
+            // RGBA: (255, 255, 255, 255) -> Solid White
+            pixels: TextureType::LDR(vec![255, 255, 255, 255]),
+            format: TextureFormat::Rgba8Unorm,
+            mips: false,
+        },
+        Some("Default White Texture"),
+    );
+
+    // Flat tangent-space normal (0, 0, 1) for materials without a normal map
+    let flat_normal = GpuTexture::from_image(
+        &device,
+        &queue,
+        &TextureData {
+            name: "Default Normal Pixel".to_string(),
+            width: 1,
+            height: 1,
+            pixels: TextureType::LDR(vec![128, 128, 255, 255]),
+            format: TextureFormat::Rgba8Unorm,
+            mips: false,
+        },
+        Some("Default Normal Texture"),
+    );
+
+    log::info!("Pipelines compiled");
+
+    world.set(MaterialLayout(pbr_program.material_layout.clone()));
+    world.set(capabilities);
+
+    let device_lost = DeviceLost::watch(&device);
+
+    world.set(RenderContext {
+        instance,
+        device,
+        queue,
+        surface,
+        config,
+        depth_texture,
+        depth_mode,
+
+        global_resources,
+        geometry_pool: GeometryPool::default(),
+        default_diffuse: white_pixel,
+        default_normal: flat_normal,
+
+        pbr_program,
+        debug_lines_program,
+        particle_program,
+        gpu_particle_program,
+        decal_program,
+        reflection_program,
+        taa_program,
+        luminance_program,
+        outline_program,
+        depth_of_field_program,
+        upscale_program,
+        color_grading_program,
+        probe_prefilter_program,
+        occlusion_proxy_program,
+        material_preview_program,
+        debug_view_program,
+        picking_program,
+        custom_programs,
+
+        taa,
+        exposure,
+        depth_of_field: DepthOfFieldState::default(),
+        color_grading: ColorGradingState::default(),
+        occlusion,
+        picking_target: PickingTarget::default(),
+        readback,
+        device_lost,
+    });
+
+    // world.insert_resource(LayoutResource(bind_group_layout));
+    true
+}
+
 pub fn register_renderings(app: &mut App) {
     app.register_singleton_default::<DebugDraw3D>();
+    app.register_singleton_default::<FrameGraph>();
+    app.register_settings::<RenderSettings>("render");
+    app.register_singleton_default::<RenderStats>();
+    app.register_singleton_default::<ClearColor>();
+    app.register_singleton_default::<SplitScreenSettings>();
+    app.register_singleton_default::<TaaSettings>();
+    app.register_singleton_default::<MaterialPrograms>();
+    app.register_singleton_default::<DebugViewMode>();
+    app.register_singleton_default::<FogSettings>();
+    app.register_singleton_default::<Picking>();
+    app.register_singleton_default::<Screenshots>();
 
     app.world
         .component::<RenderTarget>()
@@ -77,6 +661,9 @@ pub fn register_renderings(app: &mut App) {
     app.world
         .component::<MaterialLayout>()
         .add_trait::<flecs::Singleton>();
+    app.world
+        .component::<RendererCapabilities>()
+        .add_trait::<flecs::Singleton>();
 
     app.world
         .system_named::<&MainWindow>("init renderer")
@@ -87,123 +674,82 @@ pub fn register_renderings(app: &mut App) {
             while iter.next() {
                 let windows = iter.field::<MainWindow>(0);
                 if let Some(window) = windows.get(0) {
-                    println!(">>> Catalyst Renderer: Initializing GPU <<<");
-
-                    // 2. Create the Instance (Vulkan/Metal/DX12)
-                    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
-
-                    // 3. Create Surface (The canvas on the window)
-                    // UNSAFE: We must ensure the window outlives the surface. In our loop, it does.
-                    let surface = unsafe {
-                        instance.create_surface_unsafe(
-                            wgpu::SurfaceTargetUnsafe::from_window(&window.0).unwrap(),
-                        )
-                    }
-                    .unwrap();
-
-                    // 4. Request Adapter (Physical GPU)
-                    // We use 'pollster' to block on this async function inside a sync system
-                    let adapter = pollster::block_on(instance.request_adapter(
-                        &wgpu::RequestAdapterOptions {
-                            power_preference: wgpu::PowerPreference::HighPerformance,
-                            compatible_surface: Some(&surface),
-                            force_fallback_adapter: false,
-                        },
-                    ))
-                    .expect("No GPU found!");
-
-                    // 5. Request Device (Logical GPU connection)
-                    let (device, queue) = pollster::block_on(
-                        adapter.request_device(&wgpu::DeviceDescriptor::default()),
-                    )
-                    .unwrap();
-
-                    // 6. Configure the Surface
-                    let size = window.0.inner_size();
-                    let caps = surface.get_capabilities(&adapter);
-                    let config = wgpu::SurfaceConfiguration {
-                        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-                        format: caps.formats[0], // Use the first supported format (usually sRGB)
-                        width: size.width,
-                        height: size.height,
-                        present_mode: wgpu::PresentMode::Fifo, // VSync On
-                        desired_maximum_frame_latency: 2,
-                        alpha_mode: caps.alpha_modes[0],
-                        view_formats: vec![],
-                    };
-                    surface.configure(&device, &config);
-
-                    let depth_texture =
-                        TextureHelper::create_depth_texture(&device, &config, "Depth Texture");
-
-                    let global_resources = GlobalResources::new(&device);
-
-                    let render_context = programs::GpuProgramRenderContext {
-                        device: &device,
-                        queue: &queue,
-                        format: config.format,
-                    };
-
-                    let pbr_program = PbrProgram::new(&render_context, &global_resources.layout);
-                    let debug_lines_program =
-                        DebugLinesProgram::new(&render_context, &global_resources.layout);
-
-                    //let line_draw_pipeline = create_line_draw_pipeline(&device, &bind_group_layout, &config);
-
-                    // --- CREATE DEFAULT TEXTURE (1x1 White Pixel) ---
-                    // We create this manually so we don't depend on an asset file existing
-                    let white_pixel = GpuTexture::from_image(
-                        &device,
-                        &queue,
-                        &TextureData {
-                            name: "Default White Pixel".to_string(),
-                            width: 1,
-                            height: 1,
-                            // RGBA: (255, 255, 255, 255) -> Solid White
-                            pixels: TextureType::LDR(vec![0, 111, 255, 255]),
-                            format: TextureFormat::Rgba8Unorm,
-                        },
-                        Some("Default White Texture"),
-                    );
-
-                    println!(">>> Catalyst Renderer: Pipeline Compiled <<<");
-
-                    world.set(MaterialLayout(pbr_program.material_layout.clone()));
-
-                    world.set(RenderContext {
-                        device,
-                        queue,
-                        surface,
-                        config,
-                        depth_texture,
-
-                        global_resources,
-                        default_diffuse: white_pixel,
+                    init_renderer(&world, window);
+                }
+            }
+        });
 
-                        pbr_program,
-                        debug_lines_program,
-                    });
+    // Hands over the readbacks the GPU finished since the last frame, before the systems
+    // using them (auto exposure) run
+    app.world
+        .system_named::<&mut RenderContext>("Poll Readbacks")
+        .kind(PhaseRenderPrepare)
+        .each(|context| {
+            context.readback.poll(&context.device);
+        });
 
-                    // world.insert_resource(LayoutResource(bind_group_layout));
-                }
+    // Before "start frame", so the next frame is acquired from the reconfigured surface.
+    // AutoNoVsync falls back to Fifo where nothing faster is supported
+    app.world
+        .system_named::<(&mut RenderContext, &RenderSettings)>("Apply VSync")
+        .kind(PhaseRenderPrepare)
+        .each(|(context, settings)| {
+            let present_mode = if settings.vsync {
+                wgpu::PresentMode::Fifo
+            } else {
+                wgpu::PresentMode::AutoNoVsync
+            };
+            if context.config.present_mode == present_mode {
+                return;
+            }
+            context.config.present_mode = present_mode;
+            if let Some(surface) = context.surface.surface() {
+                surface.configure(&context.device, &context.config);
             }
+            log::info!("VSync {}", if settings.vsync { "on" } else { "off" });
         });
 
     app.world
-        .system_named::<(&RenderContext, &mut RenderTarget)>("start frame")
-        .kind(flecs::pipeline::PreStore)
-        .each(|(context, target)| {
-            if let Ok(frame) = context.surface.get_current_texture() {
-                let view = frame
-                    .texture
-                    .create_view(&wgpu::TextureViewDescriptor::default());
-
-                // Store it so other plugins can see it
-                target.texture = Some(frame);
-                target.view = Some(view);
+        .system_named::<(&RenderContext, &MainWindow, &mut RenderTarget, &mut FrameGraph)>(
+            "start frame",
+        )
+        .kind(PhaseRenderPrepare)
+        .each(|(context, window, target, graph)| {
+            // A minimized window has no surface to draw to, acquiring would fail or block
+            let size = window.0.inner_size();
+            if size.width == 0 || size.height == 0 {
+                return;
+            }
+            debug_assert!(
+                context.surface.is_for(&window.0),
+                "the surface was created for a window that is no longer the MainWindow"
+            );
+
+            // Destroyed while suspended
+            let Some(surface) = context.surface.surface() else {
+                return;
+            };
+            match surface.get_current_texture() {
+                Ok(frame) => {
+                    let view = frame
+                        .texture
+                        .create_view(&wgpu::TextureViewDescriptor::default());
+
+                    // Store it so other plugins can see it
+                    graph.set_backbuffer(view.clone());
+                    target.texture = Some(frame);
+                    target.view = Some(view);
+                }
+                // The surface only needs configuring again, this frame is skipped.
+                // A lost device is handled by "Recover Lost Device".
+                Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                    surface.configure(&context.device, &context.config);
+                }
+                Err(_) => {}
             }
         });
 
+    // Read while "Render Frame" collects the draw lists; the passes only see those
     let mesh_query = app
         .world
         .query::<&MeshInstance>()
@@ -211,121 +757,879 @@ pub fn register_renderings(app: &mut App) {
         .with((AssetMaterial, flecs::Wildcard))
         .without(Camera::id())
         .without(ColliderDefinition::id()) // Example filter
+        .without(RenderOrder::id()) // Bucketed entities are drawn by ordered_mesh_query
+        .without(MaterialProgram::id()) // Drawn by their custom program, see custom_mesh_query
+        .without(RoomCulled) // Inside rooms no camera can see into
+        .without(FrustumCulled) // Outside every view
+        .without(StaticBatched::id()) // Drawn by their StaticBatch
         .group_by(AssetMaterial)
         // .order_by::<Material>(|_e1, m1: &Material, _e2, m2: &Material| m1.0.cmp(&m2.0) as i32)
         .set_cached()
         .build();
 
+    let ordered_mesh_query = app
+        .world
+        .query::<(&MeshInstance, &RenderOrder, &GlobalTransform)>()
+        .with((AssetMesh, flecs::Wildcard))
+        .with((AssetMaterial, flecs::Wildcard))
+        .without(Camera::id())
+        .without(ColliderDefinition::id())
+        .without(MaterialProgram::id())
+        .without(RoomCulled)
+        .without(FrustumCulled)
+        .set_cached()
+        .build();
+
+    let custom_mesh_query = app
+        .world
+        .query::<(&MeshInstance, &MaterialProgram)>()
+        .with((AssetMesh, flecs::Wildcard))
+        .without(Camera::id())
+        .without(ColliderDefinition::id())
+        .without(RoomCulled)
+        .without(FrustumCulled)
+        .set_cached()
+        .build();
+
+    let camera_query = app
+        .world
+        .query::<(&Camera, &GlobalTransform, Option<&PlayerIndex>)>()
+        .set_cached()
+        .build();
+
+    let sun_query = app
+        .world
+        .query::<(&DirectionalLight, &GlobalTransform)>()
+        .set_cached()
+        .build();
+
+    let point_light_query = app
+        .world
+        .query::<(&PointLight, &GlobalTransform)>()
+        .set_cached()
+        .build();
+
+    let spot_light_query = app
+        .world
+        .query::<(&SpotLight, &GlobalTransform)>()
+        .set_cached()
+        .build();
+
+    let depth_of_field_query = app
+        .world
+        .query::<&DepthOfField>()
+        .with(Camera::id())
+        .set_cached()
+        .build();
+
     app.world
         .system::<(
-            &Camera,
-            &GlobalTransform,
             &mut RenderContext,
-            &mut RenderTarget,
+            &mut FrameGraph,
+            &SplitScreenSettings,
+            &TaaSettings,
+            &mut ExposureState,
+            &mut RenderStats,
+            &PlanarReflections,
+            &DynamicResolutionState,
+            &RenderSettings,
+            &ClearColor,
+            &Time,
+            &mut ReflectionProbes,
+            &DebugViewMode,
+            &mut Picking,
+            &FogSettings,
         )>() // <()> = Run once (no entity matching)
         .named("Render Frame")
         .kind(PhaseRender3D)
         //.write(RenderContext::id()) // Declare access intent
         //.write(RenderTarget::id())
-        .each(move |(_cam, cam_t, context, target)| {
-            let view_proj = {
-                // A: View Matrix (Inverse of Camera Transform)
-                // Move the world opposite to the camera
-                let eye = cam_t.0.transform_point3(Vec3::ZERO);
-                let forward = -cam_t.0.z_axis.truncate(); // camera looks down -Z let up = m.y_axis.truncate();
-                let up = cam_t.0.y_axis.truncate();
-
-                let view = Mat4::look_at_rh(eye, eye + forward, up);
-
-                // B: Projection Matrix (Perspective)
-                let aspect = context.config.width as f32 / context.config.height as f32;
-                let proj = Mat4::perspective_rh(
-                    45.0f32.to_radians(), // FOV
-                    aspect,
-                    0.1,   // Near Plane
-                    100.0, // Far Plane
+        .each(move |(
+            context,
+            graph,
+            split_screen,
+            taa_settings,
+            exposure,
+            stats,
+            reflections,
+            resolution,
+            settings,
+            clear_color,
+            time,
+            probes,
+            debug_view,
+            picking,
+            fog,
+        )| {
+            catalyst_core::profile_scope!("Render Frame");
+            // No surface texture this frame (minimized, or acquiring failed)
+            let Some(backbuffer) = graph.backbuffer() else {
+                return;
+            };
+
+            let views = collect_views(&camera_query);
+            if views.is_empty() {
+                return;
+            }
+
+            let view_cameras: Vec<Entity> = views.iter().map(|(camera, _, _)| *camera).collect();
+            exposure.begin_views(&view_cameras);
+
+            // The scene is drawn into the top left of its targets at the dynamic resolution
+            // scale; the targets stay screen sized so a scale change doesn't reallocate them
+            let (render_width, render_height) =
+                resolution.render_size(context.config.width, context.config.height);
+            let scaled = (render_width, render_height) != (context.config.width, context.config.height);
+            let viewports =
+                compute_viewports(split_screen.layout, views.len(), render_width, render_height);
+            let screen_viewports = compute_viewports(
+                split_screen.layout,
+                views.len(),
+                context.config.width,
+                context.config.height,
+            );
+            let uv_scale = Vec2::new(
+                render_width as f32 / context.config.width as f32,
+                render_height as f32 / context.config.height as f32,
+            );
+            stats.render_scale = resolution.scale;
+            context
+                .global_resources
+                .ensure_views(&context.device, viewports.len());
+            context
+                .decal_program
+                .ensure_views(&context.device, viewports.len());
+
+            // Lights are gathered every frame, so edits to the components show up immediately
+            let (mut scene_lights, point_lights) = collect_lights(
+                &sun_query,
+                &point_light_query,
+                &spot_light_query,
+                &context.global_resources.gobos,
+            );
+            fog.write_uniforms(&mut scene_lights);
+            context
+                .global_resources
+                .update_point_lights(&context.queue, &point_lights);
+            let mut lights_per_cluster = Vec::with_capacity(views.len());
+            let mut reflected_view = None;
+
+            if taa_settings.enabled {
+                context.taa.begin_frame();
+            } else {
+                context.taa.invalidate_history();
+            }
+
+            for (index, ((camera_entity, cam, cam_t), viewport)) in
+                views.iter().zip(&viewports).enumerate()
+            {
+                let (view_proj, unjittered_view_proj, view, proj) = {
+                    // A: View Matrix (Inverse of Camera Transform)
+                    // Move the world opposite to the camera
+                    let eye = cam_t.transform_point3(Vec3::ZERO);
+                    let forward = -cam_t.z_axis.truncate(); // camera looks down -Z let up = m.y_axis.truncate();
+                    let up = cam_t.y_axis.truncate();
+
+                    let view = Mat4::look_at_rh(eye, eye + forward, up);
+
+                    // B: Projection Matrix (Perspective)
+                    // The aspect ratio always follows the viewport, the rest comes from the Camera
+                    let camera = Camera {
+                        aspect_ratio: viewport.aspect_ratio(),
+                        ..cam.clone()
+                    };
+                    let proj = match context.depth_mode {
+                        DepthMode::Standard => camera.compute_projection_matrix(),
+                        DepthMode::ReversedZ => camera.compute_reversed_z_projection_matrix(),
+                    };
+
+                    // C: Sub-pixel jitter, a different offset every frame for TAA to accumulate
+                    let jittered_proj = if taa_settings.enabled {
+                        let jitter = context.taa.jitter(viewport, taa_settings.jitter_scale);
+                        jitter_projection(proj, jitter)
+                    } else {
+                        proj
+                    };
+
+                    (jittered_proj * view, proj * view, view, proj)
+                };
+
+                // Planar reflections are drawn for the first view
+                if index == 0 {
+                    reflected_view = Some(ReflectedView {
+                        view,
+                        projection: proj,
+                        eye: cam_t.transform_point3(Vec3::ZERO),
+                        near: cam.near,
+                        far: cam.far,
+                        viewport: *viewport,
+                        exposure: exposure.exposure(*camera_entity),
+                    });
+                }
+
+                let mut light_data = scene_lights;
+                light_data.camera_pos = cam_t.transform_point3(Vec3::ZERO).to_array();
+
+                let wind = context.global_resources.wind;
+                let view_resources = context.global_resources.view_mut(index);
+                view_resources.update_camera(
+                    &context.queue,
+                    view_proj,
+                    unjittered_view_proj,
+                    exposure.exposure(*camera_entity),
+                    wind,
                 );
+                view_resources.update_lights(&context.queue, light_data);
+                // Decals rebuild positions from the depth this view_proj produces
+                context.decal_program.write_view(
+                    &context.queue,
+                    index,
+                    view_proj,
+                    cam_t.transform_point3(Vec3::ZERO),
+                    viewport,
+                );
+                lights_per_cluster.extend(view_resources.update_clusters(
+                    &context.queue,
+                    &point_lights,
+                    view,
+                    proj,
+                    cam.near,
+                    cam.far,
+                    viewport,
+                ));
+            }
 
-                proj * view
-            };
+            let depth_of_field = collect_depth_of_field(&depth_of_field_query, &views);
+            let has_depth_of_field = depth_of_field.iter().any(Option::is_some);
+            if has_depth_of_field {
+                context.depth_of_field.write_views(
+                    &context.queue,
+                    &context.depth_of_field_program,
+                    &views,
+                    &depth_of_field,
+                    &viewports,
+                    context.depth_mode,
+                    time.delta_seconds(),
+                );
+            } else {
+                context.depth_of_field.reset();
+            }
 
-            // 2. Create a Command Encoder
-            let mut encoder =
+            let has_color_grading = context.color_grading.is_active();
+            if has_color_grading {
                 context
-                    .device
-                    .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                        label: Some("Render Encoder"),
-                    });
+                    .color_grading
+                    .write_views(&context.queue, &context.color_grading_program);
+            }
+
+            stats.point_lights = point_lights.len();
+            stats.spot_lights = point_lights.iter().filter(|light| light.kind == LIGHT_SPOT).count();
+            stats.gobos = context.global_resources.gobos.len();
+            stats.lights_per_cluster = if lights_per_cluster.is_empty() {
+                0.0
+            } else {
+                lights_per_cluster.iter().sum::<f32>() / lights_per_cluster.len() as f32
+            };
+
+            // Compile any pipeline permutation this frame needs before recording starts
+            let pipeline_keys = collect_pipeline_keys(&mesh_query, &ordered_mesh_query);
+            for key in pipeline_keys {
+                context.pbr_program.prepare_pipeline(&context.device, key);
+            }
+            let (hits, misses) = context.pbr_program.cache_stats();
+            stats.pipeline_cache_size = context.pbr_program.pipeline_count();
+            stats.pipeline_cache_hits = hits;
+            stats.pipeline_cache_misses = misses;
+
+            // 2. Declare the scene targets
+            // Below full scale the image is finished offscreen, the upscale writes the screen
+            let upscaled = if scaled {
+                graph.create_texture(
+                    "Upscale Input",
+                    GraphTextureDesc::screen(&context.config, context.config.format),
+                )
+            } else {
+                backbuffer
+            };
+            // With color grading the image is finished offscreen, the grading pass writes `upscaled`
+            let grading_input = if has_color_grading {
+                graph.create_texture(
+                    "Grading Input",
+                    GraphTextureDesc::screen(&context.config, context.config.format),
+                )
+            } else {
+                upscaled
+            };
+            // With depth of field the image is finished offscreen, the DoF composite writes `grading_input`
+            let resolved = if has_depth_of_field {
+                graph.create_texture(
+                    "DoF Input",
+                    GraphTextureDesc::screen(&context.config, context.config.format),
+                )
+            } else {
+                grading_input
+            };
+            // With TAA the scene goes to an offscreen texture first, the resolve writes `resolved`
+            let color = if taa_settings.enabled {
+                graph.create_texture(
+                    "TAA Scene Color",
+                    GraphTextureDesc::screen(&context.config, context.config.format),
+                )
+            } else {
+                resolved
+            };
+            // Written every frame, even with TAA off: the pipelines always output it
+            let velocity = graph.create_texture(
+                "Velocity Buffer",
+                GraphTextureDesc::screen(&context.config, TextureHelper::VELOCITY_FORMAT),
+            );
+            let luminance = graph.import_texture("Luminance", context.exposure.luminance.clone());
+            let depth = graph.import_texture("Depth Texture", context.depth_texture.clone());
+
+            let scene = SceneTargets {
+                color,
+                velocity,
+                luminance,
+                depth,
+                viewports: viewports.clone(),
+                screen_viewports: screen_viewports.clone(),
+            };
+            graph.set_scene_targets(scene.clone());
+
+            let camera_positions: Vec<Vec3> = views
+                .iter()
+                .map(|(_, _, cam_t)| cam_t.transform_point3(Vec3::ZERO))
+                .collect();
+
+            // 2b. Planar reflections, mirrored scenes in their own targets, before the
+            // surfaces showing them are drawn. They use the global views after the screen's.
+            let (reflection_targets, reflections_drawn) = match &reflected_view {
+                Some(source) => add_reflection_passes(
+                    graph,
+                    context,
+                    reflections,
+                    source,
+                    scene_lights,
+                    &point_lights,
+                    views.len(),
+                    *clear_color,
+                    &mesh_query,
+                    &ordered_mesh_query,
+                    &custom_mesh_query,
+                ),
+                None => Default::default(),
+            };
+            stats.reflections_drawn = reflections_drawn;
+
+            // 2c. This frame's reflection probe capture, six more scene passes and the
+            // blur into the probe's cubemap. They use the views after the reflections'.
+            add_probe_capture_passes(
+                graph,
+                context,
+                probes,
+                scene_lights,
+                &point_lights,
+                views.len() + MAX_REFLECTIONS,
+                *clear_color,
+                &mesh_query,
+                &ordered_mesh_query,
+                &custom_mesh_query,
+            );
+
+            // Draw lists are collected now, so the scene passes can be recorded on worker
+            // threads. Only the transparent order depends on the view.
+            let opaque_draws = context.pbr_program.collect_draws(&mesh_query, None);
+            let ordered_opaque_draws = context.pbr_program.collect_bucket(
+                &ordered_mesh_query,
+                RenderBucket::Opaque,
+                Vec3::ZERO,
+                None,
+            );
+            let overlay_draws = context.pbr_program.collect_bucket(
+                &ordered_mesh_query,
+                RenderBucket::Overlay,
+                Vec3::ZERO,
+                None,
+            );
+            let custom_draws = context.custom_programs.collect_draws(
+                &custom_mesh_query,
+                ProgramPasses::MAIN,
+                None,
+            );
+            let transparent_draws: Vec<_> = camera_positions
+                .iter()
+                .map(|camera_pos| {
+                    context.pbr_program.collect_bucket(
+                        &ordered_mesh_query,
+                        RenderBucket::Transparent,
+                        *camera_pos,
+                        None,
+                    )
+                })
+                .collect();
+            let shared_draws = opaque_draws.len()
+                + ordered_opaque_draws.len()
+                + overlay_draws.len()
+                + custom_draws.len();
+            stats.mesh_draws = shared_draws * views.len()
+                + transparent_draws.iter().map(|draws| draws.len()).sum::<usize>();
+
+            // A debug view draws the same lists again, see DebugViewMode. A mode this
+            // lighting mode can't draw shows the lit image.
+            let debug_view = if context.debug_view_program.supports(*debug_view) {
+                *debug_view
+            } else {
+                DebugViewMode::Lit
+            };
+            context
+                .debug_view_program
+                .prepare(&context.device, debug_view);
+            let debug_view_draws = (debug_view != DebugViewMode::Lit).then(|| FrameDraws {
+                opaque: opaque_draws.clone(),
+                ordered_opaque: ordered_opaque_draws.clone(),
+                overlay: overlay_draws.clone(),
+                transparent: transparent_draws.clone(),
+            });
+            // Picking draws them once more as ids, only in frames something is picked
+            let picks = picking.take_requests();
+            let picking_draws = (!picks.is_empty()).then(|| FrameDraws {
+                opaque: opaque_draws.clone(),
+                ordered_opaque: ordered_opaque_draws.clone(),
+                overlay: overlay_draws.clone(),
+                transparent: transparent_draws.clone(),
+            });
 
             // 3. THE RENDER PASS (Clear Screen to Blue)
-            {
-                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                    label: Some("Main Render Pass"),
-                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                        view: &target.view.as_ref().unwrap(),
-                        resolve_target: None,
-                        depth_slice: None,
-                        ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(wgpu::Color {
-                                r: 0.1,
-                                g: 0.2, // Dark Blue/Slate
-                                b: 0.3,
-                                a: 1.0,
+            // One pass for all views: the target is cleared once and each view
+            // only draws inside its own viewport.
+            // Opaque geometry first; decals need its depth before anything blends on top.
+            let opaque_scene = scene.clone();
+            let clear = clear_color.load_op();
+            graph
+                .add_pass("Main Render Pass", PassStage::Scene)
+                .write(color)
+                .write(velocity)
+                .write(luminance)
+                .write(depth)
+                .record_parallel(move |pass| {
+                    let scene = opaque_scene;
+                    let timestamp_writes = pass.timestamp_writes();
+                    let (color, velocity, luminance, depth) = (
+                        pass.view(scene.color),
+                        pass.view(scene.velocity),
+                        pass.view(scene.luminance),
+                        pass.view(scene.depth),
+                    );
+                    let context = pass.context;
+
+                    let mut render_pass = pass.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("Main Render Pass"),
+                        color_attachments: &[
+                            Some(wgpu::RenderPassColorAttachment {
+                                view: color,
+                                resolve_target: None,
+                                depth_slice: None,
+                                ops: wgpu::Operations {
+                                    load: clear,
+                                    store: wgpu::StoreOp::Store,
+                                },
+                            }),
+                            Some(wgpu::RenderPassColorAttachment {
+                                view: velocity,
+                                resolve_target: None,
+                                depth_slice: None,
+                                ops: wgpu::Operations {
+                                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT), // No motion
+                                    store: wgpu::StoreOp::Store,
+                                },
                             }),
-                            store: wgpu::StoreOp::Store,
-                        },
-                    })],
-                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                        view: &context.depth_texture, // The texture we created
-                        depth_ops: Some(wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(1.0), // Clear to "Far" (1.0)
-                            store: wgpu::StoreOp::Store,
+                            Some(wgpu::RenderPassColorAttachment {
+                                view: luminance,
+                                resolve_target: None,
+                                depth_slice: None,
+                                ops: wgpu::Operations {
+                                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT), // No coverage
+                                    store: wgpu::StoreOp::Store,
+                                },
+                            }),
+                        ],
+                        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                            view: depth,
+                            depth_ops: Some(wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(context.depth_mode.clear_value()), // Clear to "Far"
+                                store: wgpu::StoreOp::Store,
+                            }),
+                            stencil_ops: None,
                         }),
-                        stencil_ops: None,
-                    }),
-                    ..Default::default()
+                        timestamp_writes,
+                        occlusion_query_set: context.occlusion.query_set(),
+                        ..Default::default()
+                    });
+
+                    for (index, viewport) in scene.viewports.iter().enumerate() {
+                        viewport.apply(&mut render_pass);
+
+                        let bind_group = &context.global_resources.view(index).bind_group;
+
+                        // Opaque: batched by material, then any opaque entities with a RenderOrder
+                        context
+                            .pbr_program
+                            .record(&mut render_pass, (bind_group, &opaque_draws));
+                        context
+                            .pbr_program
+                            .record(&mut render_pass, (bind_group, &ordered_opaque_draws));
+                        // Then the entities with a MaterialProgram, each program's together
+                        context
+                            .custom_programs
+                            .record(&mut render_pass, bind_group, &custom_draws);
+
+                        // Boxes of the OcclusionCullable entities, tested against the opaque depth
+                        context.occlusion_proxy_program.record(
+                            &mut render_pass,
+                            (bind_group, &context.occlusion, index),
+                        );
+                    }
                 });
 
-                let debug_light = GpuPointLight {
-                    position: [2.0, 2.0, 2.0, 0.0], // .w can be ignored or used for radius
-                    color: [1.0, 0.2, 0.2, 10.0],   // Red color, High Intensity (10.0)
-                };
+            // Results of the occlusion queries, read back for the next frames
+            if context.occlusion.query_set().is_some() {
+                let view_count = views.len();
+                graph
+                    .add_pass("Occlusion Queries", PassStage::Scene)
+                    .read(depth)
+                    .keep()
+                    .record(move |pass| {
+                        pass.context.occlusion.resolve(pass.encoder, view_count);
+                    });
+            }
 
-                // Create 3 empty lights
-                let empty_light = GpuPointLight {
-                    position: [0.0; 4],
-                    color: [0.0; 4],
-                };
+            // 4. DECALS, projected onto the opaque surfaces through the depth buffer
+            if context.decal_program.instance_count() > 0 {
+                let decal_viewports = scene.viewports.clone();
+                graph
+                    .add_pass("Decal Pass", PassStage::Scene)
+                    .read(depth)
+                    .read(color)
+                    .write(color)
+                    .record_parallel(move |pass| {
+                        let timestamp_writes = pass.timestamp_writes();
+                        let (color, depth) = (pass.view(color), pass.view(depth));
+                        let context = pass.context;
+                        let view_bind_group =
+                            context.decal_program.view_bind_group(&context.device, depth);
 
-                // Update lights
-                let light_data = LightUniforms {
-                    sun_direction: [0.0, -1.0, -0.5, 5.0],
-                    sun_color: [1.0, 1.0, 1.0, 0.0],
-                    point_lights: [debug_light, empty_light, empty_light, empty_light],
-                    // camera_pos: [0.0, 0.0, 0.0, 0.0],
-                    //camera_pos: cam_t.0.w_axis.to_array(),
-                    camera_pos: cam_t.0.transform_point3(Vec3::ZERO).to_array(),
-                    active_lights: 1,
-                };
+                        let mut render_pass = pass.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                            label: Some("Decal Pass"),
+                            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                                view: color,
+                                resolve_target: None,
+                                depth_slice: None,
+                                ops: wgpu::Operations {
+                                    load: wgpu::LoadOp::Load,
+                                    store: wgpu::StoreOp::Store,
+                                },
+                            })],
+                            timestamp_writes,
+                            ..Default::default()
+                        });
 
-                context
-                    .global_resources
-                    .update_camera(&context.queue, view_proj);
-                context
-                    .global_resources
-                    .update_lights(&context.queue, light_data);
+                        for (index, viewport) in decal_viewports.iter().enumerate() {
+                            viewport.apply(&mut render_pass);
+                            let bind_group = &context.global_resources.view(index).bind_group;
+                            context
+                                .decal_program
+                                .record(&mut render_pass, (bind_group, &view_bind_group, index));
+                        }
+                    });
+            }
+
+            // 4b. GPU tier particles, simulated and added to the lit scene
+            add_gpu_particle_passes(graph, context, &scene);
+            stats.gpu_particle_emitters = context
+                .gpu_particle_program
+                .as_ref()
+                .map_or(0, |program| program.emitter_count());
+            stats.gpu_particles_spawned = context
+                .gpu_particle_program
+                .as_ref()
+                .map_or(0, |program| program.spawn_count());
+
+            // 5. Blended geometry on top, depth tested against the opaque pass
+            let mut transparent_pass = graph.add_pass("Transparent Pass", PassStage::Scene);
+            for target in reflection_targets.iter().flatten() {
+                transparent_pass = transparent_pass.read(*target);
+            }
+            transparent_pass
+                .read(color)
+                .read(velocity)
+                .read(luminance)
+                .read(depth)
+                .write(color)
+                .write(velocity)
+                .write(luminance)
+                .write(depth)
+                .record_parallel(move |pass| {
+                    let timestamp_writes = pass.timestamp_writes();
+                    let (color, velocity, luminance, depth) = (
+                        pass.view(scene.color),
+                        pass.view(scene.velocity),
+                        pass.view(scene.luminance),
+                        pass.view(scene.depth),
+                    );
+                    let context = pass.context;
+                    let reflection_views =
+                        reflection_targets.map(|target| target.map(|target| pass.view(target)));
+                    let reflection_bind_groups = context
+                        .reflection_program
+                        .surface_bind_groups(&context.device, &reflection_views);
+                    let load = |view| {
+                        Some(wgpu::RenderPassColorAttachment {
+                            view,
+                            resolve_target: None,
+                            depth_slice: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Load,
+                                store: wgpu::StoreOp::Store,
+                            },
+                        })
+                    };
+
+                    let mut render_pass = pass.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("Transparent Pass"),
+                        color_attachments: &[load(color), load(velocity), load(luminance)],
+                        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                            view: depth,
+                            depth_ops: Some(wgpu::Operations {
+                                load: wgpu::LoadOp::Load,
+                                store: wgpu::StoreOp::Store,
+                            }),
+                            stencil_ops: None,
+                        }),
+                        timestamp_writes,
+                        ..Default::default()
+                    });
 
-                context.pbr_program.record(
-                    &mut render_pass,
-                    (&context.global_resources.bind_group, &mesh_query),
+                    for (index, (viewport, transparent_draws)) in
+                        scene.viewports.iter().zip(&transparent_draws).enumerate()
+                    {
+                        viewport.apply(&mut render_pass);
+
+                        let bind_group = &context.global_resources.view(index).bind_group;
+
+                        // Reflector surfaces are opaque underneath, so before anything blended
+                        if index == 0 {
+                            context
+                                .reflection_program
+                                .record(&mut render_pass, (bind_group, &reflection_bind_groups));
+                        }
+
+                        context
+                            .pbr_program
+                            .record(&mut render_pass, (bind_group, transparent_draws));
+
+                        context
+                            .particle_program
+                            .record(&mut render_pass, bind_group);
+
+                        // Overlay last, so it is never hidden inside scene geometry
+                        context
+                            .pbr_program
+                            .record(&mut render_pass, (bind_group, &overlay_draws));
+                    }
+                });
+
+            stats.particles_drawn =
+                context.particle_program.instance_count() as u64 * views.len() as u64;
+            stats.decals_drawn =
+                context.decal_program.instance_count() as u64 * views.len() as u64;
+
+            // Average luminance per view, read back for next frames' auto exposure
+            if exposure.needs_measurement() {
+                let exposure_viewports = viewports.clone();
+                graph
+                    .add_pass("Exposure", PassStage::PostProcess)
+                    .read(luminance)
+                    .keep()
+                    .record(move |pass| {
+                        let context = &mut *pass.context;
+                        context.exposure.measure(
+                            pass.encoder,
+                            &context.device,
+                            &context.queue,
+                            &context.luminance_program,
+                            &mut context.readback,
+                            &view_cameras,
+                            &exposure_viewports,
+                        );
+                    });
+            }
+
+            // 6. TAA RESOLVE (current + reprojected history -> screen)
+            if taa_settings.enabled {
+                let history_desc = GraphTextureDesc::screen(&context.config, context.config.format);
+                let (previous_name, next_name) = context.taa.history_textures();
+                let previous = graph.persistent_texture(previous_name, history_desc);
+                let next = graph.persistent_texture(next_name, history_desc);
+                if graph.was_reset(previous) {
+                    context.taa.invalidate_history();
+                }
+                context.taa.write_uniform(&context.queue, taa_settings, uv_scale);
+
+                graph
+                    .add_pass("TAA Resolve Pass", PassStage::PostProcess)
+                    .read(color)
+                    .read(velocity)
+                    .read(previous)
+                    .write(resolved)
+                    .write(next)
+                    .record(move |pass| {
+                        let timestamp_writes = pass.timestamp_writes();
+                        let bind_group = pass.context.taa.bind_group(
+                            &pass.context.device,
+                            &pass.context.taa_program,
+                            pass.view(color),
+                            pass.view(previous),
+                            pass.view(velocity),
+                        );
+
+                        let (screen, history) = (pass.view(resolved), pass.view(next));
+                        let resolve_attachment = |view| {
+                            Some(wgpu::RenderPassColorAttachment {
+                                view,
+                                resolve_target: None,
+                                depth_slice: None,
+                                ops: wgpu::Operations {
+                                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                                    store: wgpu::StoreOp::Store,
+                                },
+                            })
+                        };
+
+                        {
+                            let mut resolve_pass =
+                                pass.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                                    label: Some("TAA Resolve Pass"),
+                                    color_attachments: &[
+                                        resolve_attachment(screen),
+                                        resolve_attachment(history),
+                                    ],
+                                    timestamp_writes,
+                                    ..Default::default()
+                                });
+
+                            pass.context
+                                .taa_program
+                                .record(&mut resolve_pass, &bind_group);
+                        }
+
+                        pass.context.taa.end_frame();
+                    });
+            }
+
+            // 7. DEPTH OF FIELD (resolved image + depth -> grading input)
+            if has_depth_of_field {
+                add_depth_of_field_passes(
+                    graph,
+                    &context.config,
+                    resolved,
+                    depth,
+                    grading_input,
+                    &viewports,
                 );
+            }
 
-                context
-                    .debug_lines_program
-                    .record(&mut render_pass, &context.global_resources.bind_group);
+            // 7b. COLOR GRADING (finished image + each view's LUTs -> upscale input)
+            if has_color_grading {
+                add_color_grading_pass(graph, grading_input, upscaled, &viewports);
             }
 
-            context.queue.submit(std::iter::once(encoder.finish()));
+            // 8. UPSCALE (scaled image -> screen), before outlines and the UI draw at full size
+            if scaled {
+                let filter = settings.dynamic_resolution.filter;
+                context.upscale_program.write_uniform(&context.queue, uv_scale);
+
+                graph
+                    .add_pass("Upscale Pass", PassStage::PostProcess)
+                    .read(upscaled)
+                    .write(backbuffer)
+                    .record(move |pass| {
+                        let timestamp_writes = pass.timestamp_writes();
+                        let bind_group = pass
+                            .context
+                            .upscale_program
+                            .bind_group(&pass.context.device, pass.view(upscaled));
+                        let screen = pass.view(backbuffer);
+                        let mut upscale_pass =
+                            pass.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                                label: Some("Upscale Pass"),
+                                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                                    view: screen,
+                                    resolve_target: None,
+                                    depth_slice: None,
+                                    ops: wgpu::Operations {
+                                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                                        store: wgpu::StoreOp::Store,
+                                    },
+                                })],
+                                timestamp_writes,
+                                ..Default::default()
+                            });
+
+                        pass.context
+                            .upscale_program
+                            .record(&mut upscale_pass, (&bind_group, filter));
+                    });
+            }
+
+            // 9. DEBUG VIEW, replacing the upscaled image; outlines and the UI still go on top
+            if let Some(draws) = debug_view_draws {
+                add_debug_view_passes(
+                    graph,
+                    context,
+                    debug_view,
+                    draws,
+                    &viewports,
+                    uv_scale,
+                    backbuffer,
+                );
+            }
+
+            // 10. PICKING, the entities under the requested positions, read back later
+            if let Some(draws) = picking_draws {
+                add_picking_pass(graph, context, picking, picks, draws, &viewports, uv_scale);
+            }
+        });
+
+    // Records every pass declared this frame and submits them, before the frame is presented
+    app.world
+        .system_named::<(
+            &mut RenderContext,
+            &mut FrameGraph,
+            &RendererCapabilities,
+            &RenderSettings,
+            &mut RenderStats,
+            &RenderTarget,
+            &mut Screenshots,
+        )>("Execute Frame Graph")
+        .kind(PhasePresent)
+        .each_iter(|iter, _, (context, graph, capabilities, settings, stats, target, screenshots)| {
+            catalyst_core::profile_scope!("Execute Frame Graph");
+            graph.execute(
+                iter.world(),
+                context,
+                capabilities.supports_timestamps(),
+                !settings.serial_recording,
+                stats,
+            );
+
+            stats.missing_resource_skips += context.pbr_program.take_missing_resource_skips();
+            if let Some(frame) = &target.texture {
+                capture_screenshots(context, &frame.texture, screenshots);
+            }
+            context.readback.after_submit();
+            context.occlusion.request_readback();
+            context.geometry_pool.end_frame();
+            stats.geometry_pool = context.geometry_pool.stats();
         });
 
     app.world
@@ -338,3 +1642,163 @@ pub fn register_renderings(app: &mut App) {
             target.view = None;
         });
 }
+
+/// The cameras to draw this frame. Cameras tagged with a PlayerIndex each get a viewport,
+/// ordered by player. Without any player cameras we fall back to a single full screen camera.
+/// The transforms include the cameras' CameraEffects.
+pub(crate) fn collect_views(
+    camera_query: &Query<(&Camera, &GlobalTransform, Option<&PlayerIndex>)>,
+) -> Vec<(Entity, Camera, Mat4)> {
+    let mut player_cameras: Vec<(PlayerIndex, Entity, Camera, Mat4)> = Vec::new();
+    let mut main_camera: Option<(Entity, Camera, Mat4)> = None;
+    camera_query.each_entity(|entity, (cam, cam_t, player)| {
+        let cam_t = entity
+            .try_get::<&CameraEffects>(|effects| effects.apply(cam_t.0))
+            .unwrap_or(cam_t.0);
+        match player {
+            Some(player) => player_cameras.push((*player, entity.id(), cam.clone(), cam_t)),
+            None => {
+                if main_camera.is_none() {
+                    main_camera = Some((entity.id(), cam.clone(), cam_t));
+                }
+            }
+        }
+    });
+
+    if player_cameras.is_empty() {
+        main_camera.into_iter().collect()
+    } else {
+        player_cameras.sort_by_key(|(player, _, _, _)| *player);
+        player_cameras
+            .into_iter()
+            .map(|(_, entity, cam, cam_t)| (entity, cam, cam_t))
+            .collect()
+    }
+}
+
+/// Each view's DepthOfField, in view order; None for cameras without one.
+fn collect_depth_of_field(
+    depth_of_field_query: &Query<&DepthOfField>,
+    views: &[(Entity, Camera, Mat4)],
+) -> Vec<Option<DepthOfField>> {
+    let mut cameras = Vec::new();
+    depth_of_field_query.each_entity(|entity, settings| cameras.push((entity.id(), *settings)));
+
+    views
+        .iter()
+        .map(|(view_camera, _, _)| {
+            cameras
+                .iter()
+                .find(|(camera, _)| camera == view_camera)
+                .map(|(_, settings)| *settings)
+        })
+        .collect()
+}
+
+// Every (material key, pass) drawn this frame. Materials not on the GPU yet are skipped, like in record.
+fn collect_pipeline_keys(
+    mesh_query: &Query<&MeshInstance>,
+    ordered_mesh_query: &Query<(&MeshInstance, &RenderOrder, &GlobalTransform)>,
+) -> BTreeSet<PipelineKey> {
+    let mut keys = BTreeSet::new();
+
+    mesh_query.run(|mut iter| {
+        let world = iter.world();
+        while iter.next() {
+            world
+                .entity_from_id(iter.group_id())
+                .try_get::<&GpuMaterial>(|gpu_material| {
+                    keys.insert(PipelineKey {
+                        material: gpu_material.key,
+                        pass: PbrPass::Opaque,
+                    });
+                });
+        }
+    });
+
+    ordered_mesh_query.run(|mut iter| {
+        while iter.next() {
+            let orders = iter.field::<RenderOrder>(1);
+            let Some(material) = iter.pair(4).second_id().try_get::<&GpuMaterial>(|m| m.key) else {
+                continue;
+            };
+            for i in iter.iter() {
+                keys.insert(PipelineKey {
+                    material,
+                    pass: PbrPass::from_order(orders[i].bucket, orders[i].depth_test),
+                });
+            }
+        }
+    });
+
+    keys
+}
+
+// Uniform lighting only sees the first 4 point and spot lights, LightUniforms has no room for more
+const MAX_UNIFORM_POINT_LIGHTS: usize = 4;
+
+/// Builds the light uniforms and the list of every point and spot light from the scene's
+/// light components. The camera position is left at zero and filled in per view. Spot
+/// lights picked by "Prepare Spot Lights" point at their gobo layer.
+///
+/// Scenes without any light entities keep the old hardcoded sun and red point light,
+/// so they don't suddenly render black.
+fn collect_lights(
+    sun_query: &Query<(&DirectionalLight, &GlobalTransform)>,
+    point_light_query: &Query<(&PointLight, &GlobalTransform)>,
+    spot_light_query: &Query<(&SpotLight, &GlobalTransform)>,
+    gobos: &Gobos,
+) -> (LightUniforms, Vec<GpuLight>) {
+    let mut uniforms = LightUniforms {
+        sun_direction: [0.0, -1.0, -0.5, 0.0],
+        sun_color: [1.0, 1.0, 1.0, 0.0],
+        point_lights: [GpuLight::EMPTY; MAX_UNIFORM_POINT_LIGHTS],
+        camera_pos: [0.0; 3],
+        active_lights: 0,
+        fog_color: [0.0; 4],
+        fog_params: [0.0; 4],
+        fog_sun: [0.0; 4],
+    };
+
+    // A. Sun (the first one found)
+    let mut has_sun = false;
+    sun_query.each(|(sun, transform)| {
+        if has_sun {
+            return;
+        }
+        has_sun = true;
+
+        let forward = -transform.z_axis.truncate().normalize_or_zero();
+        uniforms.sun_direction = [forward.x, forward.y, forward.z, sun.intensity];
+        uniforms.sun_color = sun.color.with_alpha(0.0).to_linear();
+    });
+
+    // B. Point lights
+    let mut point_lights = Vec::new();
+    point_light_query.each(|(light, transform)| {
+        let pos = transform.transform_point3(Vec3::ZERO);
+        point_lights.push(GpuLight::point(
+            pos,
+            light.color.to_linear_rgb(),
+            light.intensity,
+            light.radius,
+        ));
+    });
+
+    // C. Spot lights, in the same list
+    spot_light_query.each_entity(|entity, (light, transform)| {
+        point_lights.push(light.gpu_light(transform, gobos.slot(entity.id())));
+    });
+
+    if !has_sun && point_lights.is_empty() {
+        uniforms.sun_direction[3] = 5.0;
+        // Red color, High Intensity (10.0)
+        point_lights.push(GpuLight::point(Vec3::new(2.0, 2.0, 2.0), [1.0, 0.2, 0.2], 0.0, 10.0));
+    }
+
+    let count = point_lights.len().min(MAX_UNIFORM_POINT_LIGHTS);
+    uniforms.point_lights[..count].copy_from_slice(&point_lights[..count]);
+    uniforms.active_lights = count as u32;
+
+    (uniforms, point_lights)
+}
@@ -0,0 +1,97 @@
+use flecs_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Coarse draw stage. Buckets are drawn in declaration order.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum RenderBucket {
+    /// Depth tested and written, batched by material.
+    #[default]
+    Opaque,
+    /// Alpha blended, sorted back-to-front, depth tested but not written.
+    Transparent,
+    /// Drawn after everything else (viewmodels, highlights, gizmos).
+    Overlay,
+}
+
+/// Puts a mesh entity in a specific bucket. Entities without it are Opaque.
+///
+/// `priority` orders draws inside the bucket (lower first). For Transparent it wins
+/// over distance sorting, so it can fix up intersecting transparent objects.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RenderOrder {
+    pub bucket: RenderBucket,
+    pub priority: i32,
+    /// Overlay only: false draws on top of everything, ignoring the scene's depth.
+    pub depth_test: bool,
+}
+
+impl Default for RenderOrder {
+    fn default() -> Self {
+        Self {
+            bucket: RenderBucket::Opaque,
+            priority: 0,
+            depth_test: true,
+        }
+    }
+}
+
+impl RenderOrder {
+    pub fn opaque() -> Self {
+        Self::default()
+    }
+
+    pub fn transparent() -> Self {
+        Self {
+            bucket: RenderBucket::Transparent,
+            ..Default::default()
+        }
+    }
+
+    pub fn overlay() -> Self {
+        Self {
+            bucket: RenderBucket::Overlay,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn without_depth_test(mut self) -> Self {
+        self.depth_test = false;
+        self
+    }
+}
+
+/// Bit mask of render layers. On a camera it selects the layers the camera draws,
+/// on a Decal the layers it shows up on. Cameras without it draw every layer.
+///
+/// Only decals are filtered by layer for now; meshes are drawn by every camera.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RenderLayers(pub u32);
+
+impl Default for RenderLayers {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl RenderLayers {
+    pub const ALL: Self = Self(u32::MAX);
+
+    /// Only layer `layer` (0..32).
+    pub fn layer(layer: u32) -> Self {
+        Self(1 << layer)
+    }
+
+    pub fn with(self, layer: u32) -> Self {
+        Self(self.0 | 1 << layer)
+    }
+
+    pub fn intersects(self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+}
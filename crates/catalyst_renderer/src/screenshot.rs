@@ -0,0 +1,123 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use catalyst_core::console::ConsoleCommands;
+use flecs_ecs::prelude::*;
+
+use crate::{
+    readback::{Readback, ReadbackDelivery, TextureRegion},
+    render::RenderContext,
+};
+
+/// Screenshots to take of the next presented frame, debug UI included. The frame is
+/// read back through the ReadbackManager and saved as a PNG on a worker thread, so
+/// taking one doesn't stall the frame. Request one with `request` or the
+/// render.screenshot console command.
+#[derive(Component, Default)]
+pub struct Screenshots {
+    requests: Vec<PathBuf>,
+}
+
+impl Screenshots {
+    pub fn request(&mut self, path: impl Into<PathBuf>) {
+        self.requests.push(path.into());
+    }
+}
+
+/// Records the readbacks of the screenshots requested since the last frame, called by
+/// "Execute Frame Graph" once the frame's passes are submitted and before it's presented.
+pub(crate) fn capture_screenshots(
+    context: &mut RenderContext,
+    frame: &wgpu::Texture,
+    screenshots: &mut Screenshots,
+) {
+    if screenshots.requests.is_empty() {
+        return;
+    }
+
+    let format = frame.format();
+    let mut encoder = context
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Screenshots"),
+        });
+    for path in screenshots.requests.drain(..) {
+        context.readback.read_texture(
+            &context.device,
+            &mut encoder,
+            frame,
+            TextureRegion::full(frame),
+            ReadbackDelivery::callback(move |readback| match readback {
+                // Encoding takes a while, the render thread doesn't wait for it
+                Ok(readback) => {
+                    std::thread::spawn(move || save_screenshot(&path, format, readback));
+                }
+                Err(error) => log::warn!("Screenshot {} failed: {}", path.display(), error),
+            }),
+        );
+    }
+    context.queue.submit(Some(encoder.finish()));
+}
+
+fn save_screenshot(path: &Path, format: wgpu::TextureFormat, readback: Readback) {
+    let mut pixels = readback.data;
+    match format {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => {}
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => {
+            for texel in pixels.chunks_exact_mut(4) {
+                texel.swap(0, 2);
+            }
+        }
+        _ => {
+            log::warn!("Screenshot {}: {:?} can't be saved", path.display(), format);
+            return;
+        }
+    }
+    // Whatever the surface's alpha holds, the picture is opaque
+    for texel in pixels.chunks_exact_mut(4) {
+        texel[3] = u8::MAX;
+    }
+
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    match image::save_buffer(
+        path,
+        &pixels,
+        readback.width,
+        readback.height,
+        image::ExtendedColorType::Rgba8,
+    ) {
+        Ok(()) => log::info!(
+            "Screenshot of frame {} saved to {}",
+            readback.frame,
+            path.display()
+        ),
+        Err(error) => log::warn!("Screenshot {} failed: {}", path.display(), error),
+    }
+}
+
+pub(crate) fn register_screenshot_commands(commands: &mut ConsoleCommands) {
+    commands.register(
+        "render.screenshot",
+        "render.screenshot [path] - save the next frame as a PNG, screenshots/<time>.png without a path",
+        |world, args| {
+            let path = match args.first() {
+                Some(path) => PathBuf::from(path),
+                None => {
+                    let seconds = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0, |elapsed| elapsed.as_secs());
+                    PathBuf::from(format!("screenshots/{}.png", seconds))
+                }
+            };
+            world.get::<&mut Screenshots>(|screenshots| screenshots.request(path.clone()));
+            Ok(format!("Saving the next frame to {}", path.display()))
+        },
+    );
+}
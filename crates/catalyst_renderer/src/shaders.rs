@@ -0,0 +1,536 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+/// Environment variable naming a directory to read shaders from instead of the copies
+/// built into the renderer, e.g. `crates/catalyst_renderer/src/programs`. Programs read
+/// it whenever they build their pipelines, so edits show up on the next rebuild
+/// (device recovery rebuilds every program).
+pub const SHADER_DIR_ENV: &str = "CATALYST_SHADER_DIR";
+
+// Every file a composed program may include, by the path #include uses
+macro_rules! embedded_shaders {
+    ($($path:literal),* $(,)?) => {
+        &[$(($path, include_str!(concat!("programs/", $path)))),*]
+    };
+}
+
+static EMBEDDED_SHADERS: &[(&str, &str)] = embedded_shaders![
+    "common/brdf.wgsl",
+    "common/camera.wgsl",
+    "common/clusters.wgsl",
+    "common/fog.wgsl",
+    "common/lights.wgsl",
+    "common/material.wgsl",
+    "common/mesh.wgsl",
+    "common/motion.wgsl",
+    "common/wind.wgsl",
+    "debug_view.wgsl",
+    "debug_view_clusters.wgsl",
+    "decals.wgsl",
+    "lights_clustered.wgsl",
+    "lights_uniform.wgsl",
+    "lines.wgsl",
+    "occlusion_proxy.wgsl",
+    "outline.wgsl",
+    "particles.wgsl",
+    "picking.wgsl",
+    "reflection.wgsl",
+    "shader.wgsl",
+];
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum ShaderError {
+    #[error("shader '{path}' not found (included from {included_from})")]
+    NotFound { path: String, included_from: String },
+    #[error("{file}:{line}: expected #include \"path\"")]
+    BadDirective { file: String, line: usize },
+    #[error("{file}:{line}: include cycle {cycle}")]
+    IncludeCycle {
+        file: String,
+        line: usize,
+        cycle: String,
+    },
+    /// Reported by naga, at the line of the file the error is in.
+    #[error("{file}:{line}:{column}: {message}")]
+    Compile {
+        file: String,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+/// Where shader paths are read from: the copies built into the renderer, a directory
+/// on disk (see SHADER_DIR_ENV) or files in memory. Paths are relative to that root,
+/// for entry points and `#include` directives alike.
+#[derive(Clone, Debug)]
+pub enum ShaderFiles {
+    Embedded,
+    Directory(PathBuf),
+    Memory(HashMap<String, String>),
+    /// Files in memory first, then the others: a game's shaders including the
+    /// renderer's common/ files.
+    Overlay(HashMap<String, String>, Box<ShaderFiles>),
+}
+
+impl ShaderFiles {
+    /// The directory named by CATALYST_SHADER_DIR when it is set, the embedded copies
+    /// otherwise.
+    pub fn from_env() -> Self {
+        match std::env::var_os(SHADER_DIR_ENV) {
+            Some(dir) => ShaderFiles::Directory(dir.into()),
+            None => ShaderFiles::Embedded,
+        }
+    }
+
+    pub fn memory<'a>(files: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        ShaderFiles::Memory(
+            files
+                .into_iter()
+                .map(|(path, source)| (path.to_string(), source.to_string()))
+                .collect(),
+        )
+    }
+
+    /// `files` on top of these, see Overlay.
+    pub fn overlay<'a>(self, files: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        let files = files
+            .into_iter()
+            .map(|(path, source)| (path.to_string(), source.to_string()))
+            .collect();
+        ShaderFiles::Overlay(files, Box::new(self))
+    }
+
+    pub fn read(&self, path: &str) -> Option<Cow<'static, str>> {
+        match self {
+            ShaderFiles::Embedded => EMBEDDED_SHADERS
+                .iter()
+                .find(|(embedded, _)| *embedded == path)
+                .map(|(_, source)| Cow::Borrowed(*source)),
+            ShaderFiles::Directory(dir) => std::fs::read_to_string(dir.join(Path::new(path)))
+                .ok()
+                .map(Cow::Owned),
+            ShaderFiles::Memory(files) => files.get(path).cloned().map(Cow::Owned),
+            ShaderFiles::Overlay(files, below) => match files.get(path) {
+                Some(source) => Some(Cow::Owned(source.clone())),
+                None => below.read(path),
+            },
+        }
+    }
+}
+
+/// WGSL with every `#include` expanded, remembering which file and line each of its
+/// lines came from.
+#[derive(Clone, Debug)]
+pub struct ComposedShader {
+    pub source: String,
+    files: Vec<String>,
+    // Per line of `source`: index into `files`, line in that file (1-based)
+    origins: Vec<(usize, usize)>,
+}
+
+impl ComposedShader {
+    /// Expands `entries` in order, as if one file included each of them.
+    ///
+    /// `#include "path"` on a line of its own is replaced by that file, expanded the
+    /// same way. A file is only expanded the first time it is included, so shared
+    /// structs can be included by every file that needs them; a file including itself,
+    /// directly or not, is an error.
+    pub fn compose(files: &ShaderFiles, entries: &[&str]) -> Result<Self, ShaderError> {
+        let mut composed = ComposedShader {
+            source: String::new(),
+            files: Vec::new(),
+            origins: Vec::new(),
+        };
+        let mut stack = Vec::new();
+        for entry in entries {
+            composed.expand(files, entry, "the program", &mut stack)?;
+        }
+        Ok(composed)
+    }
+
+    fn expand(
+        &mut self,
+        files: &ShaderFiles,
+        path: &str,
+        included_from: &str,
+        stack: &mut Vec<String>,
+    ) -> Result<(), ShaderError> {
+        if self.files.iter().any(|file| file == path) {
+            return Ok(());
+        }
+        let source = files.read(path).ok_or_else(|| ShaderError::NotFound {
+            path: path.to_string(),
+            included_from: included_from.to_string(),
+        })?;
+
+        let index = self.files.len();
+        self.files.push(path.to_string());
+        stack.push(path.to_string());
+
+        for (number, text) in source.lines().enumerate() {
+            let line = number + 1;
+            let include = match parse_include(text) {
+                None => {
+                    self.source.push_str(text);
+                    self.source.push('\n');
+                    self.origins.push((index, line));
+                    continue;
+                }
+                Some(None) => {
+                    return Err(ShaderError::BadDirective {
+                        file: path.to_string(),
+                        line,
+                    });
+                }
+                Some(Some(include)) => include,
+            };
+
+            if let Some(start) = stack.iter().position(|file| file == include) {
+                let mut cycle = stack[start..].to_vec();
+                cycle.push(include.to_string());
+                return Err(ShaderError::IncludeCycle {
+                    file: path.to_string(),
+                    line,
+                    cycle: cycle.join(" -> "),
+                });
+            }
+            self.expand(files, include, &format!("{}:{}", path, line), stack)?;
+        }
+
+        stack.pop();
+        Ok(())
+    }
+
+    /// Every file expanded into the source, entries and includes, in order.
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    /// File and line (1-based) that `line` of the composed source came from.
+    pub fn origin(&self, line: usize) -> Option<(&str, usize)> {
+        let (file, origin) = *self.origins.get(line.checked_sub(1)?)?;
+        Some((&self.files[file], origin))
+    }
+
+    /// Parses and validates the source with naga, reporting the first error at the
+    /// file and line it is in rather than somewhere in the composed source.
+    pub fn validate(&self) -> Result<naga::Module, ShaderError> {
+        let module = naga::front::wgsl::parse_str(&self.source).map_err(|error| {
+            self.compile_error(error.location(&self.source), error.message().to_string())
+        })?;
+
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        )
+        .validate(&module)
+        .map_err(|error| {
+            // The top error only names the function, its sources say what is wrong
+            let mut message = error.as_inner().to_string();
+            let mut source = std::error::Error::source(error.as_inner());
+            while let Some(inner) = source {
+                message.push_str(": ");
+                message.push_str(&inner.to_string());
+                source = std::error::Error::source(inner);
+            }
+            self.compile_error(error.location(&self.source), message)
+        })?;
+
+        Ok(module)
+    }
+
+    fn compile_error(
+        &self,
+        location: Option<naga::SourceLocation>,
+        message: String,
+    ) -> ShaderError {
+        let (line, column) = location
+            .map(|location| {
+                (
+                    location.line_number as usize,
+                    location.line_position as usize,
+                )
+            })
+            .unwrap_or((0, 0));
+        let (file, line) = match self.origin(line) {
+            Some((file, line)) => (file.to_string(), line),
+            None => (self.files.first().cloned().unwrap_or_default(), 0),
+        };
+        ShaderError::Compile {
+            file,
+            line,
+            column,
+            message,
+        }
+    }
+}
+
+// None for other lines, Some(None) for a malformed #include
+fn parse_include(line: &str) -> Option<Option<&str>> {
+    let rest = line.trim().strip_prefix("#include")?;
+    let path = rest
+        .trim_start()
+        .strip_prefix('"')
+        .and_then(|rest| rest.split_once('"'))
+        .filter(|(path, after)| {
+            let after = after.trim();
+            !path.is_empty() && (after.is_empty() || after.starts_with("//"))
+        })
+        .map(|(path, _)| path);
+    Some(path)
+}
+
+/// Composes `entries` from ShaderFiles::from_env and creates the module. Panics with
+/// the file and line of the error when the shader doesn't compile, like a layout
+/// mismatch, so a bad edit fails at startup with a readable message.
+pub(crate) fn create_shader_module(
+    device: &wgpu::Device,
+    label: &str,
+    entries: &[&str],
+) -> wgpu::ShaderModule {
+    compose_shader_module(device, label, &ShaderFiles::from_env(), entries)
+}
+
+pub(crate) fn compose_shader_module(
+    device: &wgpu::Device,
+    label: &str,
+    files: &ShaderFiles,
+    entries: &[&str],
+) -> wgpu::ShaderModule {
+    let composed = ComposedShader::compose(files, entries)
+        .and_then(|composed| composed.validate().map(|_| composed));
+    let composed = match composed {
+        Ok(composed) => composed,
+        Err(e) => panic!("{}: {}", label, e),
+    };
+
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(label),
+        source: wgpu::ShaderSource::Wgsl(composed.source.into()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Entry points of every program, as their GpuProgram::new composes them
+    const PROGRAMS: &[&[&str]] = &[
+        &["shader.wgsl", "lights_clustered.wgsl"],
+        &["shader.wgsl", "lights_uniform.wgsl"],
+        &["lines.wgsl"],
+        &["outline.wgsl"],
+        &["particles.wgsl"],
+        &["decals.wgsl"],
+        &["reflection.wgsl"],
+        &["occlusion_proxy.wgsl"],
+        &["debug_view.wgsl", "debug_view_clusters.wgsl"],
+        &["debug_view.wgsl"],
+        &["picking.wgsl"],
+    ];
+
+    const MAIN: &str = "\
+#include \"common/util.wgsl\"
+#include \"common/broken.wgsl\"
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(double(0.5));
+}
+";
+
+    const UTIL: &str = "\
+fn double(x: f32) -> f32 {
+    return x * 2.0;
+}
+";
+
+    // Line 3 uses a name nothing declares
+    const BROKEN: &str = "\
+#include \"common/util.wgsl\"
+fn broken(x: f32) -> f32 {
+    return double(x) * missing_scale;
+}
+";
+
+    // A custom program's shader, using the renderer's structs
+    const GAME: &str = "\
+#include \"common/camera.wgsl\"
+#include \"common/mesh.wgsl\"
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(1) @binding(0) var<uniform> mesh: MeshUniform;
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+    return camera.view_proj * mesh.model * vec4<f32>(position, 1.0);
+}
+";
+
+    fn compile_error(error: ShaderError) -> Option<(String, usize)> {
+        match error {
+            ShaderError::Compile { file, line, .. } => Some((file, line)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn every_program_composes_and_validates_from_either_copy() {
+        let disk =
+            ShaderFiles::Directory(Path::new(env!("CARGO_MANIFEST_DIR")).join("src/programs"));
+        for entries in PROGRAMS {
+            let composed = ComposedShader::compose(&ShaderFiles::Embedded, entries)
+                .unwrap_or_else(|error| panic!("{:?}: {}", entries, error));
+            if let Err(error) = composed.validate() {
+                panic!("{:?}: {}", entries, error);
+            }
+            let from_disk = ComposedShader::compose(&disk, entries).unwrap();
+            assert_eq!(composed.source, from_disk.source, "{:?}", entries);
+        }
+    }
+
+    #[test]
+    fn the_shared_structs_are_declared_once() {
+        let pbr = ComposedShader::compose(&ShaderFiles::Embedded, PROGRAMS[0]).unwrap();
+        for declaration in [
+            "struct Camera {",
+            "struct LightUniforms {",
+            "struct MaterialUniforms {",
+            "struct MeshUniform {",
+            "struct ClusterUniform {",
+            "fn wind_offset(",
+        ] {
+            assert_eq!(
+                pbr.source.matches(declaration).count(),
+                1,
+                "{}",
+                declaration
+            );
+        }
+        // Includes are expanded and the directives removed
+        assert!(pbr.files().iter().any(|file| file == "common/camera.wgsl"));
+        assert!(!pbr.source.contains("#include"));
+    }
+
+    #[test]
+    fn a_shared_include_is_expanded_once() {
+        let files = ShaderFiles::memory([
+            ("main.wgsl", MAIN),
+            ("common/util.wgsl", UTIL),
+            ("common/broken.wgsl", BROKEN),
+        ]);
+        let composed = ComposedShader::compose(&files, &["main.wgsl"]).unwrap();
+        assert_eq!(
+            composed.files(),
+            ["main.wgsl", "common/util.wgsl", "common/broken.wgsl"]
+        );
+        assert_eq!(composed.source.matches("fn double").count(), 1);
+    }
+
+    #[test]
+    fn composed_lines_map_back_to_their_file_and_line() {
+        let files = ShaderFiles::memory([
+            ("main.wgsl", MAIN),
+            ("common/util.wgsl", UTIL),
+            ("common/broken.wgsl", BROKEN),
+            ("fixed.wgsl", "#include \"common/util.wgsl\"\n"),
+        ]);
+        let composed = ComposedShader::compose(&files, &["main.wgsl"]).unwrap();
+        assert_eq!(composed.origin(1), Some(("common/util.wgsl", 1)));
+        assert_eq!(composed.origin(4), Some(("common/broken.wgsl", 2)));
+        assert_eq!(composed.origin(7), Some(("main.wgsl", 3)));
+        assert_eq!(composed.origin(0), None);
+        assert_eq!(composed.origin(1000), None);
+
+        // A broken include reports its own file and line
+        let error = composed.validate().err().and_then(compile_error);
+        assert_eq!(error, Some(("common/broken.wgsl".to_string(), 3)));
+        // The same include without the broken file validates
+        let fixed = ComposedShader::compose(&files, &["fixed.wgsl"]).unwrap();
+        assert!(fixed.validate().is_ok());
+    }
+
+    #[test]
+    fn a_games_files_come_before_the_renderers() {
+        // As custom material programs compose them
+        let game = ShaderFiles::Embedded
+            .overlay([("game.wgsl", GAME), ("common/camera.wgsl", "// shadowed\n")]);
+        let composed = ComposedShader::compose(&game, &["game.wgsl"]).unwrap();
+        assert!(
+            composed
+                .files()
+                .iter()
+                .any(|file| file == "common/mesh.wgsl")
+        );
+        assert!(!composed.source.contains("struct Camera {"));
+        assert!(composed.validate().is_err());
+    }
+
+    #[test]
+    fn include_cycles_are_reported_at_the_directive_closing_them() {
+        let cycle = ShaderFiles::memory([
+            ("a.wgsl", "#include \"b.wgsl\"\n"),
+            ("b.wgsl", "// b\n#include \"a.wgsl\"\n"),
+        ]);
+        assert_eq!(
+            ComposedShader::compose(&cycle, &["a.wgsl"]).err(),
+            Some(ShaderError::IncludeCycle {
+                file: "b.wgsl".to_string(),
+                line: 2,
+                cycle: "a.wgsl -> b.wgsl -> a.wgsl".to_string(),
+            })
+        );
+
+        // So is a file including itself
+        let own = ShaderFiles::memory([("a.wgsl", "#include \"a.wgsl\"\n")]);
+        assert!(matches!(
+            ComposedShader::compose(&own, &["a.wgsl"]),
+            Err(ShaderError::IncludeCycle { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn a_missing_file_names_the_directive_including_it() {
+        let missing = ShaderFiles::memory([("a.wgsl", "\n#include \"common/nowhere.wgsl\"\n")]);
+        assert_eq!(
+            ComposedShader::compose(&missing, &["a.wgsl"]).err(),
+            Some(ShaderError::NotFound {
+                path: "common/nowhere.wgsl".to_string(),
+                included_from: "a.wgsl:2".to_string(),
+            })
+        );
+        // A missing entry point is reported too
+        assert!(matches!(
+            ComposedShader::compose(&missing, &["b.wgsl"]),
+            Err(ShaderError::NotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn malformed_directives_are_reported() {
+        let malformed = ShaderFiles::memory([
+            ("a.wgsl", "#include common/util.wgsl\n"),
+            ("b.wgsl", "#include \"\"\n"),
+            ("c.wgsl", "#include \"common/util.wgsl\" fn f() {}\n"),
+            ("common/util.wgsl", UTIL),
+            ("d.wgsl", "  #include \"common/util.wgsl\" // shared\n"),
+        ]);
+        for entry in ["a.wgsl", "b.wgsl", "c.wgsl"] {
+            assert_eq!(
+                ComposedShader::compose(&malformed, &[entry]).err(),
+                Some(ShaderError::BadDirective {
+                    file: entry.to_string(),
+                    line: 1,
+                })
+            );
+        }
+        // Indented directives with a trailing comment are fine
+        assert!(ComposedShader::compose(&malformed, &["d.wgsl"]).is_ok());
+    }
+}
@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+
+use catalyst_assets::{
+    assets::Handle,
+    material::{TextureData, TextureFormat, TextureType},
+};
+use catalyst_core::{
+    App, color::Color, pipeline::PhaseRenderPrepare, transform::GlobalTransform,
+};
+use flecs_ecs::prelude::*;
+use glam::{Mat4, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    global_resources::{GpuLight, LIGHT_SPOT, NO_GOBO},
+    layout::{StructLayout, rust_layout},
+    render::RenderContext,
+};
+
+/// Spot lights that can project a gobo at the same time. Further spot lights with
+/// one shine without it.
+pub const MAX_GOBOS: usize = 4;
+
+/// Width and height of every gobo layer, textures are resampled to it once on upload.
+pub const GOBO_SIZE: u32 = 256;
+
+/// The outer cone angle is clamped below 90 degrees, a wider spot is a point light.
+pub const MAX_SPOT_ANGLE: f32 = 89.0 * std::f32::consts::PI / 180.0;
+
+/// Cone of light from the entity's GlobalTransform position along its forward (-Z)
+/// axis, so rotating the entity aims it. Full intensity inside `inner_angle`, fading
+/// out smoothly toward `outer_angle`; like a point light, it fades out at `range`.
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpotLight {
+    pub color: Color, // Alpha is ignored
+    pub intensity: f32,
+    pub range: f32, // Also the gizmo length
+    /// Angles in radians between the axis and the edge of each cone.
+    pub inner_angle: f32,
+    pub outer_angle: f32,
+    /// Texture projected through the cone (a gobo), multiplied with the color: a
+    /// window frame, leaves, a flashlight's lens. Its inscribed circle fills the outer cone.
+    /// Not saved in scene files, asset handles only live as long as the session.
+    #[serde(skip)]
+    pub gobo: Option<Handle<TextureData>>,
+}
+
+impl Default for SpotLight {
+    fn default() -> Self {
+        Self {
+            color: Color::WHITE,
+            intensity: 20.0,
+            range: 10.0,
+            inner_angle: 20f32.to_radians(),
+            outer_angle: 30f32.to_radians(),
+            gobo: None,
+        }
+    }
+}
+
+impl SpotLight {
+    pub fn with_gobo(mut self, texture: Handle<TextureData>) -> Self {
+        self.gobo = Some(texture);
+        self
+    }
+
+    /// Inner and outer angle as used for shading: the outer one below MAX_SPOT_ANGLE,
+    /// the inner one a bit narrower than it, so the cone's edge is never a hard cut.
+    pub fn clamped_angles(&self) -> (f32, f32) {
+        let outer = self.outer_angle.clamp(0.001, MAX_SPOT_ANGLE);
+        (self.inner_angle.clamp(0.0, outer * 0.99), outer)
+    }
+
+    /// World -> gobo clip space: a square frustum wrapping the outer cone.
+    pub fn view_projection(&self, global: &GlobalTransform) -> Mat4 {
+        let (_, outer) = self.clamped_angles();
+        let position = global.transform_point3(Vec3::ZERO);
+        let forward = -global.z_axis.truncate().normalize_or_zero();
+        let up = global.y_axis.truncate().normalize_or_zero();
+        let near = (self.range * 0.01).clamp(0.001, 0.1);
+
+        Mat4::perspective_rh(2.0 * outer, 1.0, near, self.range.max(near * 2.0))
+            * Mat4::look_to_rh(position, forward, up)
+    }
+
+    pub(crate) fn gpu_light(&self, global: &GlobalTransform, gobo: u32) -> GpuLight {
+        let (inner, outer) = self.clamped_angles();
+        let position = global.transform_point3(Vec3::ZERO);
+        let forward = -global.z_axis.truncate().normalize_or_zero();
+
+        GpuLight {
+            direction: forward.to_array(),
+            kind: LIGHT_SPOT,
+            cos_inner: inner.cos(),
+            cos_outer: outer.cos(),
+            gobo,
+            ..GpuLight::point(position, self.color.to_linear_rgb(), self.intensity, self.range)
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GoboUniform {
+    pub view_proj: [[[f32; 4]; 4]; MAX_GOBOS], // Per layer, world -> gobo clip space
+}
+
+const _: () = assert!(std::mem::size_of::<GoboUniform>() == 256);
+
+impl GoboUniform {
+    pub const LAYOUT: StructLayout = rust_layout!(GoboUniform {
+        view_proj: [[[f32; 4]; 4]; MAX_GOBOS],
+    });
+}
+
+/// Gobo textures of the spot lights projecting one this frame, one layer of a
+/// texture array each, with their projections. Part of GlobalResources, bound to
+/// every view (bindings 6 to 8).
+pub struct Gobos {
+    pub(crate) uniform: wgpu::Buffer,
+    texture: wgpu::Texture,
+    pub(crate) view: wgpu::TextureView,
+    pub(crate) sampler: wgpu::Sampler,
+    // Texture entity in each layer, so a layer is only uploaded when it changes
+    layers: [Option<Entity>; MAX_GOBOS],
+    // Layer of each spot light with a gobo this frame
+    slots: HashMap<Entity, u32>,
+}
+
+impl Gobos {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let uniform = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Gobo Uniform Buffer"),
+            size: std::mem::size_of::<GoboUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Gobo Textures"),
+            size: wgpu::Extent3d {
+                width: GOBO_SIZE,
+                height: GOBO_SIZE,
+                depth_or_array_layers: MAX_GOBOS as u32,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Gobo Textures View"),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+
+        // Clamped, so the light doesn't repeat the gobo outside its square
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Gobo Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            uniform,
+            texture,
+            view,
+            sampler,
+            layers: [None; MAX_GOBOS],
+            slots: HashMap::new(),
+        }
+    }
+
+    /// Layer of `light`'s gobo this frame, NO_GOBO when it doesn't project one.
+    pub fn slot(&self, light: Entity) -> u32 {
+        self.slots.get(&light).copied().unwrap_or(NO_GOBO)
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    // Gives each (light, texture entity, projection) a layer, keeping textures in the
+    // layer they already occupy, and uploads the projections. Returns the layers whose
+    // texture changed and must be uploaded.
+    fn assign(
+        &mut self,
+        queue: &wgpu::Queue,
+        lights: &[(Entity, Entity, Mat4)],
+    ) -> Vec<(u32, Entity)> {
+        let lights = &lights[..lights.len().min(MAX_GOBOS)];
+        let mut claimed = [false; MAX_GOBOS];
+        let mut layers = vec![None; lights.len()];
+
+        for (index, (_, texture, _)) in lights.iter().enumerate() {
+            let kept = (0..MAX_GOBOS)
+                .find(|&layer| !claimed[layer] && self.layers[layer] == Some(*texture));
+            if let Some(layer) = kept {
+                claimed[layer] = true;
+                layers[index] = Some(layer);
+            }
+        }
+
+        let mut uploads = Vec::new();
+        for (index, (_, texture, _)) in lights.iter().enumerate() {
+            if layers[index].is_some() {
+                continue;
+            }
+            let Some(layer) = (0..MAX_GOBOS).find(|&layer| !claimed[layer]) else {
+                break;
+            };
+            claimed[layer] = true;
+            layers[index] = Some(layer);
+            self.layers[layer] = Some(*texture);
+            uploads.push((layer as u32, *texture));
+        }
+
+        let mut uniform = GoboUniform {
+            view_proj: [Mat4::IDENTITY.to_cols_array_2d(); MAX_GOBOS],
+        };
+        self.slots.clear();
+        for ((light, _, view_proj), layer) in lights.iter().zip(layers) {
+            let Some(layer) = layer else {
+                continue;
+            };
+            uniform.view_proj[layer] = view_proj.to_cols_array_2d();
+            self.slots.insert(*light, layer as u32);
+        }
+        if !self.slots.is_empty() {
+            queue.write_buffer(&self.uniform, 0, bytemuck::bytes_of(&uniform));
+        }
+
+        uploads
+    }
+
+    fn upload(&mut self, queue: &wgpu::Queue, layer: u32, data: &TextureData) {
+        let pixels = gobo_pixels(data);
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: 0,
+                    y: 0,
+                    z: layer,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            &pixels,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * GOBO_SIZE),
+                rows_per_image: Some(GOBO_SIZE),
+            },
+            wgpu::Extent3d {
+                width: GOBO_SIZE,
+                height: GOBO_SIZE,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    // A layer whose texture went away must be uploaded again if it comes back
+    fn forget(&mut self, layer: u32) {
+        self.layers[layer as usize] = None;
+    }
+}
+
+// `data` resampled (nearest) to GOBO_SIZE x GOBO_SIZE RGBA8. Grayscale textures are
+// spread to every channel, HDR ones clamped to 0..1 (only the first face of a cubemap).
+fn gobo_pixels(data: &TextureData) -> Vec<u8> {
+    let channels = match data.format {
+        TextureFormat::Gray8 => 1,
+        _ => 4,
+    };
+    let texel = |x: u32, y: u32| -> [u8; 4] {
+        let index = (y * data.width + x) as usize * channels;
+        let value = |offset: usize| match &data.pixels {
+            TextureType::LDR(pixels) => pixels.get(index + offset).copied().unwrap_or(0),
+            TextureType::HDR(pixels) => {
+                let value = pixels.get(index + offset).copied().unwrap_or(0.0);
+                (value.clamp(0.0, 1.0) * 255.0).round() as u8
+            }
+        };
+        if channels == 1 {
+            let gray = value(0);
+            [gray, gray, gray, 255]
+        } else {
+            [value(0), value(1), value(2), value(3)]
+        }
+    };
+
+    let mut pixels = Vec::with_capacity((GOBO_SIZE * GOBO_SIZE * 4) as usize);
+    for y in 0..GOBO_SIZE {
+        let source_y = (y * data.height / GOBO_SIZE).min(data.height.saturating_sub(1));
+        for x in 0..GOBO_SIZE {
+            let source_x = (x * data.width / GOBO_SIZE).min(data.width.saturating_sub(1));
+            pixels.extend_from_slice(&texel(source_x, source_y));
+        }
+    }
+    pixels
+}
+
+pub fn register_spot_light_systems(app: &mut App) {
+    let spot_query = app
+        .world
+        .query::<(&SpotLight, &GlobalTransform)>()
+        .set_cached()
+        .build();
+
+    app.world
+        .system_named::<&mut RenderContext>("Prepare Spot Lights")
+        .kind(PhaseRenderPrepare)
+        .run(move |iter| prepare_gobos(&iter.world(), &spot_query));
+}
+
+// Picks the spot lights projecting a gobo this frame and fills the gobo layers.
+// "Render Frame" then tags their GpuLight with the layer.
+fn prepare_gobos(world: &World, spot_query: &Query<(&SpotLight, &GlobalTransform)>) {
+    let mut lights = Vec::new();
+    spot_query.each_entity(|entity, (light, global)| {
+        if lights.len() >= MAX_GOBOS {
+            return;
+        }
+        // Lights whose gobo isn't loaded yet shine without it
+        let Some(texture) = light
+            .gobo
+            .as_ref()
+            .and_then(|handle| handle.try_get_entity(world))
+            .filter(|texture| texture.has(TextureData::id()))
+        else {
+            return;
+        };
+        lights.push((entity.id(), texture.id(), light.view_projection(global)));
+    });
+
+    world.get::<&mut RenderContext>(|context| {
+        let queue = &context.queue;
+        let gobos = &mut context.global_resources.gobos;
+        for (layer, texture) in gobos.assign(queue, &lights) {
+            let uploaded = world
+                .entity_from_id(texture)
+                .try_get::<&TextureData>(|data| gobos.upload(queue, layer, data));
+            if uploaded.is_none() {
+                gobos.forget(layer);
+            }
+        }
+    });
+}
@@ -0,0 +1,467 @@
+use std::{collections::HashMap, ops::Range};
+
+use catalyst_assets::{
+    assets::{MeshData, Vertex},
+    lod::MeshLod,
+};
+use catalyst_core::{
+    App,
+    camera::Camera,
+    physics::{ColliderDefinition, RigidBodyDefinition},
+    transform::{GlobalTransform, Transform},
+};
+use flecs_ecs::prelude::*;
+use glam::{Mat4, Vec3};
+
+use crate::{
+    custom_program::MaterialProgram,
+    material::AssetMaterial,
+    mesh::{AssetMesh, create_mesh_instance},
+    portal_culling::RoomMember,
+    reflection::PlanarReflector,
+    render::{RenderContext, RenderStats},
+    render_order::{RenderLayers, RenderOrder},
+};
+
+/// Most vertices in one batch; bigger groups are split. Keeps a batch inside one
+/// GeometryPool block instead of making it allocate a dedicated one. Batches past 65535
+/// vertices draw with 32-bit indices, so they can't overflow before this.
+pub const MAX_BATCH_VERTICES: u32 = 1 << 20;
+/// Smallest group worth a batch: one entity alone saves no draw.
+const MIN_BATCH_MEMBERS: usize = 2;
+
+/// Marks a mesh entity that never moves, so it can be merged with the other static
+/// meshes of its material into one combined mesh drawn with a single call.
+///
+/// Batched entities keep all their components for gameplay (picking, queries, physics
+/// lookups); only the normal draw path skips them. Entities with a RenderOrder,
+/// RenderLayers, a MeshLod, a MaterialProgram, a PlanarReflector or a rigid body aren't
+/// batched, since those need drawing on their own. Entities are only merged with others in the same
+/// portal-culling room, so batches are culled like their members. Removing the tag, despawning the entity or moving it
+/// dissolves its batch, and the rest of the batch is merged again on the next frame.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct StaticGeometry;
+
+/// On an entity whose mesh was merged into `batch`, with the transform that was baked in.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct StaticBatched {
+    pub batch: Entity,
+    pub model: Mat4,
+}
+
+/// On a batch entity: what it holds, to count and dissolve it.
+#[derive(Component, Clone, Debug)]
+pub struct StaticBatch {
+    /// The mesh asset entity with the combined MeshData.
+    pub mesh: Entity,
+    pub members: usize,
+    pub bytes: u64,
+}
+
+/// Static batching totals, copied into RenderStats every frame.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StaticBatchStats {
+    pub batches: usize,
+    pub batched_entities: usize,
+    /// Mesh draws per view the batches replace, batched entities minus batches.
+    pub draws_saved: usize,
+    /// Vertex and index bytes of the combined meshes, on top of the originals.
+    pub bytes: u64,
+}
+
+/// Static batching on or off. Read every frame: turning it off dissolves every batch.
+#[derive(Component, Clone, Debug)]
+pub struct StaticBatching {
+    pub enabled: bool,
+}
+
+impl Default for StaticBatching {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// A mesh made of several, and the range of the merged parts it holds.
+pub struct MergedMesh {
+    pub mesh: MeshData,
+    pub parts: Range<usize>,
+}
+
+/// Merges meshes with their transforms baked in, in order, into meshes of at most
+/// `max_vertices` vertices (a part bigger than that gets one of its own). Normals go
+/// through the inverse transpose, and triangles of mirrored parts are flipped so they
+/// keep facing outwards.
+pub fn merge_meshes(parts: &[(&MeshData, Mat4)], max_vertices: u32) -> Vec<MergedMesh> {
+    let mut merged = Vec::new();
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    let mut start = 0;
+
+    for (index, (mesh, model)) in parts.iter().enumerate() {
+        let count = mesh.vertices.len();
+        if !vertices.is_empty() && vertices.len() + count > max_vertices as usize {
+            merged.push(MergedMesh {
+                mesh: MeshData::new(std::mem::take(&mut vertices), std::mem::take(&mut indices)),
+                parts: start..index,
+            });
+            start = index;
+        }
+
+        let base = vertices.len() as u32;
+        let normal_matrix = model.inverse().transpose();
+        vertices.extend(mesh.vertices.iter().map(|vertex| {
+            Vertex {
+                position: model
+                    .transform_point3(Vec3::from(vertex.position))
+                    .to_array(),
+                normal: normal_matrix
+                    .transform_vector3(Vec3::from(vertex.normal))
+                    .normalize_or_zero()
+                    .to_array(),
+                uv: vertex.uv,
+                wind_weight: vertex.wind_weight,
+            }
+        }));
+        if model.determinant() < 0.0 {
+            for triangle in mesh.indices.chunks_exact(3) {
+                indices.extend([triangle[0] + base, triangle[2] + base, triangle[1] + base]);
+            }
+        } else {
+            indices.extend(mesh.indices.iter().map(|index| index + base));
+        }
+    }
+
+    if !vertices.is_empty() {
+        merged.push(MergedMesh {
+            mesh: MeshData::new(vertices, indices),
+            parts: start..parts.len(),
+        });
+    }
+    merged
+}
+
+fn mesh_bytes(mesh: &MeshData) -> u64 {
+    (mesh.vertices.len() * std::mem::size_of::<Vertex>()
+        + mesh.indices.len() * mesh.index_format().size()) as u64
+}
+
+// Deletes a batch and its mesh; its members are merged again next frame unless they
+// stopped being static. Rare enough for an uncached query
+fn dissolve(world: &WorldRef, batch: Entity) {
+    let mut released = Vec::new();
+    world
+        .query::<&StaticBatched>()
+        .build()
+        .each_entity(|entity, batched| {
+            if batched.batch == batch {
+                released.push(entity.id());
+            }
+        });
+    for member in released {
+        world.entity_from_id(member).remove(StaticBatched::id());
+    }
+
+    let batch = world.entity_from_id(batch);
+    if !batch.is_alive() {
+        return;
+    }
+    if let Some(mesh) = batch.try_get::<&StaticBatch>(|batch| batch.mesh) {
+        world.entity_from_id(mesh).destruct();
+    }
+    batch.destruct();
+}
+
+pub fn register_static_batching_systems(app: &mut App) {
+    app.register_singleton_default::<StaticBatching>();
+    app.register_tag::<StaticGeometry>();
+    app.world.component::<StaticBatched>();
+    app.world.component::<StaticBatch>();
+
+    // Un-batching: dropping the tag (or despawning) dissolves the batch
+    app.world
+        .observer::<flecs::OnRemove, ()>()
+        .with(StaticGeometry::id())
+        .each_entity(|entity, _| {
+            let Some(batch) = entity.try_get::<&StaticBatched>(|batched| batched.batch) else {
+                return;
+            };
+            entity.remove(StaticBatched::id());
+            dissolve(&entity.world(), batch);
+        });
+
+    let members = app.world.query::<&StaticBatched>().set_cached().build();
+
+    let pending = app
+        .world
+        .query::<(&GlobalTransform, &RoomMember)>()
+        .with(StaticGeometry)
+        .with((AssetMesh, flecs::Wildcard))
+        .with((AssetMaterial, flecs::Wildcard))
+        .without(StaticBatched::id())
+        .without(RenderOrder::id())
+        .without(RenderLayers::id())
+        .without(MeshLod::id())
+        .without(MaterialProgram::id())
+        .without(PlanarReflector::id())
+        .without(RigidBodyDefinition::id())
+        .without(ColliderDefinition::id()) // Not drawn, see the mesh query
+        .without(Camera::id())
+        .set_cached()
+        .build();
+
+    let batches = app.world.query::<&StaticBatch>().set_cached().build();
+
+    // After transform propagation (PostUpdate, registered by the core), so the baked
+    // transforms are the ones drawn this frame
+    app.world
+        .system_named::<(&StaticBatching, &RenderContext, &mut RenderStats)>("Static Batching")
+        .kind(flecs::pipeline::PostUpdate)
+        .run(move |iter| {
+            let world = iter.world();
+            let enabled = world.get::<&StaticBatching>(|batching| batching.enabled);
+
+            // Anything moved since it was baked, or batching turned off
+            let mut stale = Vec::new();
+            members.each_entity(|entity, batched| {
+                let moved = entity
+                    .try_get::<&GlobalTransform>(|global| global.0 != batched.model)
+                    .unwrap_or(true);
+                if (moved || !enabled) && !stale.contains(&batched.batch) {
+                    stale.push(batched.batch);
+                }
+            });
+            for batch in stale {
+                dissolve(&world, batch);
+            }
+
+            if enabled {
+                build_batches(&world, &pending);
+            }
+
+            let mut stats = StaticBatchStats::default();
+            batches.each(|batch| {
+                stats.batches += 1;
+                stats.batched_entities += batch.members;
+                stats.bytes += batch.bytes;
+            });
+            stats.draws_saved = stats.batched_entities.saturating_sub(stats.batches);
+            world.get::<&mut RenderStats>(|render_stats| render_stats.static_batching = stats);
+        });
+}
+
+// Merges the pending static entities, per material and room. They wait for their
+// RoomMember, set the frame after their mesh instance, so a room's contents aren't
+// merged with what's outside it
+fn build_batches(world: &WorldRef, pending: &Query<(&GlobalTransform, &RoomMember)>) {
+    // (material, room) -> (entity, mesh, model)
+    let mut groups: HashMap<(Entity, Option<Entity>), Vec<(Entity, Entity, Mat4)>> = HashMap::new();
+    pending.each_entity(|entity, (global, member)| {
+        let (Some(mesh), Some(material)) =
+            (entity.target(AssetMesh, 0), entity.target(AssetMaterial, 0))
+        else {
+            return;
+        };
+        // Meshes still loading are picked up once they arrive
+        if !mesh.has(MeshData::id()) {
+            return;
+        }
+        groups
+            .entry((material.id(), member.room))
+            .or_default()
+            .push((entity.id(), mesh.id(), global.0));
+    });
+
+    for ((material, room), mut group) in groups {
+        if group.len() < MIN_BATCH_MEMBERS {
+            continue;
+        }
+        // Neighbours in the list end up in the same batch when it's split
+        group.sort_by_key(|(entity, _, _)| *entity);
+
+        let mut meshes: HashMap<Entity, MeshData> = HashMap::new();
+        for (_, mesh, _) in &group {
+            if !meshes.contains_key(mesh) {
+                let data = world.entity_from_id(*mesh).try_get::<&MeshData>(|data| {
+                    MeshData::new(
+                        data.vertices
+                            .iter()
+                            .map(|vertex| Vertex {
+                                position: vertex.position,
+                                normal: vertex.normal,
+                                uv: vertex.uv,
+                                wind_weight: vertex.wind_weight,
+                            })
+                            .collect(),
+                        data.indices.clone(),
+                    )
+                });
+                if let Some(data) = data {
+                    meshes.insert(*mesh, data);
+                }
+            }
+        }
+        group.retain(|(_, mesh, _)| meshes.contains_key(mesh));
+        let parts: Vec<(&MeshData, Mat4)> = group
+            .iter()
+            .map(|(_, mesh, model)| (&meshes[mesh], *model))
+            .collect();
+
+        for merged in merge_meshes(&parts, MAX_BATCH_VERTICES) {
+            let members = &group[merged.parts.clone()];
+            if members.len() < MIN_BATCH_MEMBERS {
+                continue;
+            }
+            let bytes = mesh_bytes(&merged.mesh);
+
+            // Uploaded to the geometry pool like any mesh asset, and drawn by the normal
+            // path with an identity transform. The instance is made now rather than by
+            // "Setup Meshes in GPU", so the batch shows in the frame its members stop drawing
+            let mesh = world.entity().set(merged.mesh);
+            let instance = world.get::<&RenderContext>(|context| {
+                create_mesh_instance(&GlobalTransform::default(), context)
+            });
+            let batch = world
+                .entity()
+                .set(instance)
+                .set(StaticBatch {
+                    mesh: mesh.id(),
+                    members: members.len(),
+                    bytes,
+                })
+                // Culled with the room of its members; "Assign Rooms" would go by the origin
+                .set(RoomMember { room })
+                .set(Transform::default())
+                .set(GlobalTransform::default())
+                .add((AssetMesh, mesh))
+                .add((AssetMaterial, material));
+            for (entity, _, model) in members {
+                world.entity_from_id(*entity).set(StaticBatched {
+                    batch: batch.id(),
+                    model: *model,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use catalyst_assets::primitives;
+    use glam::Quat;
+
+    use super::*;
+
+    const CUBES: usize = 500;
+
+    fn model(index: usize) -> Mat4 {
+        let position = Vec3::new((index % 25) as f32 * 2.0, 0.0, (index / 25) as f32 * 2.0);
+        Mat4::from_scale_rotation_translation(
+            Vec3::splat(1.0 + (index % 3) as f32 * 0.5),
+            Quat::from_rotation_y(index as f32 * 0.1),
+            position,
+        )
+    }
+
+    // Outward facing triangles wind counter-clockwise seen from outside, so their
+    // geometric normal points away from the center
+    fn faces_outwards(mesh: &MeshData, triangles: Range<usize>, center: Vec3) -> bool {
+        triangles.into_iter().all(|triangle| {
+            let [a, b, c] = [0, 1, 2].map(|corner| {
+                Vec3::from(mesh.vertices[mesh.indices[triangle * 3 + corner] as usize].position)
+            });
+            (b - a).cross(c - a).dot((a + b + c) / 3.0 - center) > 0.0
+        })
+    }
+
+    #[test]
+    fn merged_cubes_have_their_transforms_baked_in() {
+        let cube = primitives::cube(1.0);
+        let (vertices, indices) = (cube.vertices.len(), cube.indices.len());
+        let models: Vec<Mat4> = (0..CUBES).map(model).collect();
+        let parts: Vec<(&MeshData, Mat4)> = models.iter().map(|model| (&cube, *model)).collect();
+
+        let merged = merge_meshes(&parts, MAX_BATCH_VERTICES);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].parts, 0..CUBES);
+        let batch = &merged[0].mesh;
+        assert_eq!(batch.vertices.len(), vertices * CUBES);
+        assert_eq!(batch.indices.len(), indices * CUBES);
+        assert!(
+            batch
+                .indices
+                .iter()
+                .all(|&index| (index as usize) < batch.vertices.len())
+        );
+
+        for (part, model) in models.iter().enumerate() {
+            let normal_matrix = model.inverse().transpose();
+            for (vertex, original) in cube.vertices.iter().enumerate() {
+                let merged = &batch.vertices[part * vertices + vertex];
+                let position = model.transform_point3(Vec3::from(original.position));
+                let normal = normal_matrix
+                    .transform_vector3(Vec3::from(original.normal))
+                    .normalize();
+                assert!(position.distance(Vec3::from(merged.position)) < 1e-4);
+                assert!(normal.distance(Vec3::from(merged.normal)) < 1e-4);
+            }
+            let triangles = indices / 3;
+            let center = model.transform_point3(Vec3::ZERO);
+            assert!(faces_outwards(
+                batch,
+                part * triangles..(part + 1) * triangles,
+                center
+            ));
+        }
+    }
+
+    #[test]
+    fn a_mirrored_part_is_flipped_to_face_outwards() {
+        let cube = primitives::cube(1.0);
+        let (vertices, triangles) = (cube.vertices.len(), cube.indices.len() / 3);
+        let mirror = Vec3::new(-1.0, 1.0, 1.0);
+        let mirrored = Mat4::from_translation(Vec3::new(5.0, 0.0, 0.0)) * Mat4::from_scale(mirror);
+
+        let merged = merge_meshes(
+            &[(&cube, Mat4::IDENTITY), (&cube, mirrored)],
+            MAX_BATCH_VERTICES,
+        );
+        let batch = &merged[0].mesh;
+        assert!(faces_outwards(
+            batch,
+            triangles..triangles * 2,
+            Vec3::new(5.0, 0.0, 0.0)
+        ));
+        // The normals follow the mirror
+        for (merged, original) in batch.vertices[vertices..].iter().zip(&cube.vertices) {
+            let expected = Vec3::from(original.normal) * mirror;
+            assert!(Vec3::from(merged.normal).distance(expected) < 1e-4);
+        }
+    }
+
+    #[test]
+    fn a_vertex_budget_splits_the_merge_between_whole_parts() {
+        let cube = primitives::cube(1.0);
+        let vertices = cube.vertices.len();
+        let models: Vec<Mat4> = (0..CUBES).map(model).collect();
+        let parts: Vec<(&MeshData, Mat4)> = models.iter().map(|model| (&cube, *model)).collect();
+
+        // A budget of 10 cubes splits 500 into 50 batches, in order
+        let budget = (vertices * 10) as u32;
+        let merged = merge_meshes(&parts, budget);
+        assert_eq!(merged.len(), CUBES / 10);
+        for batch in &merged {
+            assert!(batch.mesh.vertices.len() <= budget as usize);
+            assert_eq!(batch.parts.len(), 10);
+        }
+        assert!(
+            merged
+                .windows(2)
+                .all(|pair| pair[0].parts.end == pair[1].parts.start)
+        );
+
+        // A part over the budget gets a batch of its own
+        let oversized = merge_meshes(&parts[..3], (vertices / 2) as u32);
+        assert_eq!(oversized.len(), 3);
+        assert!(oversized.iter().all(|batch| batch.parts.len() == 1));
+    }
+}
@@ -0,0 +1,618 @@
+use std::collections::HashMap;
+
+use catalyst_assets::material::{MaterialData, TextureData, TextureFormat, TextureType};
+use catalyst_core::{
+    camera::Camera,
+    importance::{ImportanceBias, effective_distance},
+    time::Time,
+    transform::GlobalTransform,
+};
+use flecs_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+use glam::Vec3;
+
+use crate::{
+    material::{AssetMaterial, GpuMaterial, init_gpu_material},
+    mesh::{AssetMesh, MeshBounds},
+    render::{MaterialLayout, RenderContext, RenderSettings},
+    texture::GpuTexture,
+    upload::{UploadBudget, UploadSettings},
+};
+
+/// Texture streaming options, part of RenderSettings. Unlike the rest of the
+/// settings these are read every frame.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextureStreaming {
+    /// Off: every texture keeps its full mip chain resident.
+    pub enabled: bool,
+    /// GPU memory the streamed textures may allocate, mip chains included.
+    pub budget_bytes: u64,
+    /// Smallest mips uploaded when a texture is created. They stay resident.
+    pub initial_mips: u32,
+    /// Textures no bigger than this are uploaded whole and never streamed.
+    pub min_streamed_size: u32,
+    /// Seconds a texture's top mips may go unneeded before they are evicted.
+    pub evict_after: f32,
+}
+
+impl Default for TextureStreaming {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            budget_bytes: 512 * 1024 * 1024,
+            initial_mips: 4,
+            min_streamed_size: 256,
+            evict_after: 5.0,
+        }
+    }
+}
+
+/// Streaming counters, updated by "Stream Textures".
+#[derive(Component, Clone, Debug, Default)]
+pub struct TextureStreamingStats {
+    pub textures: usize,
+    pub streamed_textures: usize,
+    /// Bytes allocated for every mipmapped texture, populated or not.
+    pub allocated_bytes: u64,
+    pub budget_bytes: u64,
+    /// Mip levels uploaded during the last frame.
+    pub levels_streamed_in: usize,
+    /// Textures shrunk since startup.
+    pub evictions: usize,
+}
+
+/// Size of every level of a texture's mip chain.
+#[derive(Clone, Copy, Debug)]
+struct MipLayout {
+    mip_count: u32,
+    width: u32,
+    height: u32,
+    bytes_per_texel: u32,
+}
+
+impl MipLayout {
+    fn extent(&self, level: u32) -> (u32, u32) {
+        ((self.width >> level).max(1), (self.height >> level).max(1))
+    }
+
+    fn level_bytes(&self, level: u32) -> u64 {
+        let (width, height) = self.extent(level);
+        width as u64 * height as u64 * self.bytes_per_texel as u64
+    }
+
+    /// Bytes of an allocation holding `level` and everything smaller.
+    fn bytes_from(&self, level: u32) -> u64 {
+        (level..self.mip_count).map(|level| self.level_bytes(level)).sum()
+    }
+}
+
+/// Which mips of a texture are on the GPU. Levels are numbered in the full chain,
+/// 0 being the full resolution image in TextureData.
+///
+/// The GPU texture holds levels `allocated_mip..mip_count`; `resident_mip..mip_count`
+/// of those are populated, and the view only covers those.
+#[derive(Component)]
+pub struct TextureResidency {
+    layout: MipLayout,
+    allocated_mip: u32,
+    resident_mip: u32,
+    streamed: bool,
+    /// Last time the allocated top mip was needed, in elapsed seconds.
+    needed_at: f32,
+    /// Screen height in pixels of the largest mesh using the texture, last frame.
+    screen_pixels: f32,
+    /// Levels 1.. of the chain, box filtered from TextureData on creation.
+    mips: Vec<Vec<u8>>,
+}
+
+impl TextureResidency {
+    pub fn mip_count(&self) -> u32 {
+        self.layout.mip_count
+    }
+
+    /// Levels currently sampled, counted from the smallest.
+    pub fn resident_mips(&self) -> u32 {
+        self.layout.mip_count - self.resident_mip
+    }
+
+    pub fn is_streamed(&self) -> bool {
+        self.streamed
+    }
+
+    pub fn screen_pixels(&self) -> f32 {
+        self.screen_pixels
+    }
+
+    pub fn allocated_bytes(&self) -> u64 {
+        self.layout.bytes_from(self.allocated_mip)
+    }
+
+    /// Coarsest level streaming may drop the texture to.
+    fn coarsest_mip(&self, settings: &TextureStreaming) -> u32 {
+        if !self.streamed {
+            return 0;
+        }
+        self.layout.mip_count.saturating_sub(settings.initial_mips.max(1))
+    }
+
+    fn pixels<'a>(&'a self, level: u32, data: &'a TextureData) -> &'a [u8] {
+        match (level, &data.pixels) {
+            (0, TextureType::LDR(pixels)) => pixels,
+            _ => &self.mips[level as usize - 1],
+        }
+    }
+}
+
+/// LDR 2D textures get a mip chain and are streamed, unless imported without mips.
+/// HDR cubemaps keep the single level upload in `GpuTexture::from_image`.
+pub(crate) fn is_streamable(data: &TextureData) -> bool {
+    data.mips
+        && matches!(data.pixels, TextureType::LDR(_))
+        && matches!(
+            data.format,
+            TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb | TextureFormat::Gray8
+        )
+}
+
+/// Creates the texture with its full mip chain allocated. Small textures, and all of
+/// them while streaming is off, get every level; the others only their smallest ones.
+pub(crate) fn init_streamed_texture(
+    entity: EntityView,
+    data: &TextureData,
+    context: &RenderContext,
+    settings: &TextureStreaming,
+) {
+    let TextureType::LDR(pixels) = &data.pixels else {
+        return;
+    };
+
+    let bytes_per_texel = match data.format {
+        TextureFormat::Gray8 => 1,
+        _ => 4,
+    };
+    let mip_count = data.width.max(data.height).max(1).ilog2() + 1;
+    let streamed = settings.enabled && data.width.max(data.height) > settings.min_streamed_size;
+
+    let mut residency = TextureResidency {
+        layout: MipLayout {
+            mip_count,
+            width: data.width,
+            height: data.height,
+            bytes_per_texel,
+        },
+        allocated_mip: 0,
+        resident_mip: 0,
+        streamed,
+        needed_at: 0.0,
+        screen_pixels: 0.0,
+        mips: build_mips(pixels, data.width, data.height, bytes_per_texel as usize, mip_count),
+    };
+    residency.resident_mip = residency.coarsest_mip(settings);
+
+    let texture = create_texture(context, data, &residency, residency.allocated_mip);
+    for level in residency.resident_mip..mip_count {
+        write_level(context, &texture, data, &residency, level);
+    }
+
+    let sampler = context.device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("Texture Sampler"),
+        address_mode_u: wgpu::AddressMode::Repeat,
+        address_mode_v: wgpu::AddressMode::Repeat,
+        address_mode_w: wgpu::AddressMode::Repeat,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        mipmap_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    });
+
+    let view = resident_view(&texture, &residency);
+    entity.set(residency).set(GpuTexture {
+        texture,
+        view,
+        sampler,
+    });
+}
+
+// Box filtered, each level from the one above. Averages the stored (sRGB encoded)
+// values, which darkens high contrast detail a little but is what most tools do.
+fn build_mips(pixels: &[u8], width: u32, height: u32, channels: usize, mip_count: u32) -> Vec<Vec<u8>> {
+    let mut mips: Vec<Vec<u8>> = Vec::with_capacity(mip_count as usize - 1);
+    let (mut width, mut height) = (width as usize, height as usize);
+
+    for _ in 1..mip_count {
+        let source = mips.last().map(Vec::as_slice).unwrap_or(pixels);
+        let (next_width, next_height) = ((width / 2).max(1), (height / 2).max(1));
+        let mut next = vec![0u8; next_width * next_height * channels];
+
+        for y in 0..next_height {
+            let (y0, y1) = ((y * 2).min(height - 1), (y * 2 + 1).min(height - 1));
+            for x in 0..next_width {
+                let (x0, x1) = ((x * 2).min(width - 1), (x * 2 + 1).min(width - 1));
+                for channel in 0..channels {
+                    let texel = |x: usize, y: usize| source[(y * width + x) * channels + channel] as u32;
+                    let sum = texel(x0, y0) + texel(x1, y0) + texel(x0, y1) + texel(x1, y1);
+                    next[(y * next_width + x) * channels + channel] = ((sum + 2) / 4) as u8;
+                }
+            }
+        }
+
+        mips.push(next);
+        (width, height) = (next_width, next_height);
+    }
+
+    mips
+}
+
+fn create_texture(
+    context: &RenderContext,
+    data: &TextureData,
+    residency: &TextureResidency,
+    allocated_mip: u32,
+) -> wgpu::Texture {
+    let format = match data.format {
+        TextureFormat::Rgba8UnormSrgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+        TextureFormat::Gray8 => wgpu::TextureFormat::R8Unorm,
+        _ => wgpu::TextureFormat::Rgba8Unorm,
+    };
+    let (width, height) = residency.layout.extent(allocated_mip);
+
+    context.device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(&data.name),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: residency.layout.mip_count - allocated_mip,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        // COPY_SRC: levels are copied over when the texture is recreated at another size
+        usage: wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_DST
+            | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    })
+}
+
+fn write_level(
+    context: &RenderContext,
+    texture: &wgpu::Texture,
+    data: &TextureData,
+    residency: &TextureResidency,
+    level: u32,
+) {
+    let (width, height) = residency.layout.extent(level);
+    context.queue.write_texture(
+        wgpu::TexelCopyTextureInfo {
+            texture,
+            mip_level: level - residency.allocated_mip,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        residency.pixels(level, data),
+        wgpu::TexelCopyBufferLayout {
+            offset: 0,
+            bytes_per_row: Some(residency.layout.bytes_per_texel * width),
+            rows_per_image: Some(height),
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+}
+
+fn resident_view(texture: &wgpu::Texture, residency: &TextureResidency) -> wgpu::TextureView {
+    texture.create_view(&wgpu::TextureViewDescriptor {
+        base_mip_level: residency.resident_mip - residency.allocated_mip,
+        ..Default::default()
+    })
+}
+
+/// Moves the texture to a new allocation starting at `allocated_mip`, copying the
+/// populated levels both textures have.
+fn reallocate(
+    context: &RenderContext,
+    encoder: &mut wgpu::CommandEncoder,
+    data: &TextureData,
+    residency: &mut TextureResidency,
+    gpu: &mut GpuTexture,
+    allocated_mip: u32,
+) {
+    let texture = create_texture(context, data, residency, allocated_mip);
+    let first_copied = residency.resident_mip.max(allocated_mip);
+
+    for level in first_copied..residency.layout.mip_count {
+        let (width, height) = residency.layout.extent(level);
+        encoder.copy_texture_to_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &gpu.texture,
+                mip_level: level - residency.allocated_mip,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: level - allocated_mip,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    residency.allocated_mip = allocated_mip;
+    residency.resident_mip = first_copied;
+    gpu.texture = texture;
+}
+
+/// Finest level worth having for a texture drawn `pixels` tall on screen, assuming
+/// the mesh's UVs span the texture once.
+fn desired_mip(layout: &MipLayout, pixels: f32) -> u32 {
+    if pixels <= 0.0 {
+        return layout.mip_count - 1;
+    }
+    let size = layout.width.max(layout.height) as f32;
+    ((size / pixels).log2().floor().max(0.0) as u32).min(layout.mip_count - 1)
+}
+
+struct Candidate {
+    entity: Entity,
+    layout: MipLayout,
+    allocated_mip: u32,
+    target: u32,
+    coarsest: u32,
+    pixels: f32,
+}
+
+/// Drops one level at a time from the least visible textures until the targets fit
+/// the budget. Textures that can't go any coarser are left as they are.
+fn fit_budget(candidates: &mut [Candidate], budget: u64) {
+    let mut total: u64 = candidates
+        .iter()
+        .map(|candidate| candidate.layout.bytes_from(candidate.target))
+        .sum();
+
+    candidates.sort_by(|a, b| a.pixels.total_cmp(&b.pixels));
+    while total > budget {
+        let mut dropped = false;
+        for candidate in candidates.iter_mut() {
+            if total <= budget {
+                break;
+            }
+            if candidate.target < candidate.coarsest {
+                total -= candidate.layout.level_bytes(candidate.target);
+                candidate.target += 1;
+                dropped = true;
+            }
+        }
+        if !dropped {
+            break;
+        }
+    }
+}
+
+pub fn register_texture_streaming(world: &World) {
+    world
+        .component::<TextureStreamingStats>()
+        .add_trait::<flecs::Singleton>()
+        .set(TextureStreamingStats::default());
+
+    let cameras = world
+        .query::<(&Camera, &GlobalTransform)>()
+        .set_cached()
+        .build();
+
+    let instances = world
+        .query::<(&GlobalTransform, Option<&ImportanceBias>)>()
+        .with((AssetMesh, flecs::Wildcard))
+        .with((AssetMaterial, flecs::Wildcard))
+        .set_cached()
+        .build();
+
+    let textures = world
+        .query::<(&TextureData, &mut TextureResidency, &mut GpuTexture)>()
+        .set_cached()
+        .build();
+
+    let materials = world
+        .query::<&MaterialData>()
+        .with(GpuMaterial::id())
+        .set_cached()
+        .build();
+
+    // Scores every texture by the on-screen size of the meshes using it, fits the
+    // wanted levels into the budget, then streams in at most the upload budget worth of
+    // levels and shrinks textures whose top levels went unused. Materials of textures
+    // that changed get new bind groups, since the views did. Meshes are measured at the
+    // distance their ImportanceBias makes them count as, which also keeps their
+    // textures last to drop for the budget; desired_mip never asks past level 0.
+    world
+        .system_named::<(
+            &RenderContext,
+            &RenderSettings,
+            &UploadSettings,
+            &MaterialLayout,
+            &Time,
+            &mut TextureStreamingStats,
+        )>("Stream Textures")
+        .kind(flecs::pipeline::PostUpdate)
+        .each_iter(move |iter, _, (context, render_settings, upload, mat_layout, time, stats)| {
+            catalyst_core::profile_scope!("Stream Textures");
+            let world = iter.world();
+            let settings = &render_settings.texture_streaming;
+            let now = time.elapsed_seconds();
+
+            let mut eyes: Vec<(Vec3, f32)> = Vec::new();
+            cameras.each(|(camera, transform)| {
+                eyes.push((transform.0.transform_point3(Vec3::ZERO), (camera.fov * 0.5).tan()));
+            });
+
+            // Tallest on-screen size of each material, in pixels
+            let mut material_pixels: HashMap<Entity, f32> = HashMap::new();
+            instances.each_iter(|iter, _, (global, importance)| {
+                let mesh = iter.pair(2).second_id().id();
+                let Some(bounds) = world
+                    .entity_from_id(mesh)
+                    .try_get::<&MeshBounds>(|bounds| bounds.0)
+                else {
+                    return;
+                };
+
+                let center = global.0.transform_point3(bounds.center());
+                let (scale, _, _) = global.to_scale_rotation_translation();
+                let radius = bounds.half_extents().length() * scale.abs().max_element();
+
+                let coverage = eyes.iter().fold(0.0f32, |coverage, (eye, tan_half_fov)| {
+                    let distance = effective_distance(importance, eye.distance(center));
+                    coverage.max(radius / (distance.max(f32::EPSILON) * tan_half_fov))
+                });
+                let pixels = coverage * context.config.height as f32;
+
+                let material = iter.pair(3).second_id().id();
+                let entry = material_pixels.entry(material).or_insert(0.0);
+                *entry = entry.max(pixels);
+            });
+
+            let mut texture_pixels: HashMap<Entity, f32> = HashMap::new();
+            for (material, pixels) in &material_pixels {
+                world.entity_from_id(*material).try_get::<&MaterialData>(|data| {
+                    for handle in [
+                        &data.diffuse_texture,
+                        &data.metallic_roughness_texture,
+                        &data.normal_texture,
+                    ]
+                    .into_iter()
+                    .flatten()
+                    {
+                        if let Some(texture) = handle.try_get_entity(&world) {
+                            let entry = texture_pixels.entry(texture.id()).or_insert(0.0);
+                            *entry = entry.max(*pixels);
+                        }
+                    }
+                });
+            }
+
+            let mut candidates = Vec::new();
+            textures.each_entity(|entity, (_, residency, _)| {
+                let pixels = texture_pixels.get(&entity.id()).copied().unwrap_or(0.0);
+                let coarsest = residency.coarsest_mip(settings);
+                let target = if settings.enabled {
+                    desired_mip(&residency.layout, pixels).min(coarsest)
+                } else {
+                    0
+                };
+                if target <= residency.allocated_mip {
+                    residency.needed_at = now;
+                }
+                residency.screen_pixels = pixels;
+
+                candidates.push(Candidate {
+                    entity: entity.id(),
+                    layout: residency.layout,
+                    allocated_mip: residency.allocated_mip,
+                    target,
+                    coarsest,
+                    pixels,
+                });
+            });
+
+            if settings.enabled {
+                fit_budget(&mut candidates, settings.budget_bytes);
+            }
+            let targets: HashMap<Entity, u32> = candidates
+                .iter()
+                .map(|candidate| (candidate.entity, candidate.target))
+                .collect();
+            let over_budget = candidates
+                .iter()
+                .map(|candidate| candidate.layout.bytes_from(candidate.allocated_mip))
+                .sum::<u64>()
+                > settings.budget_bytes;
+
+            let mut budget = UploadBudget::new(upload.bytes_per_frame);
+            let mut encoder = context
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Texture Streaming Encoder"),
+                });
+            let mut reallocated = false;
+            let mut changed = Vec::new();
+            let mut levels_streamed_in = 0;
+            let mut allocated_bytes = 0;
+            let mut streamed_textures = 0;
+
+            textures.each_entity(|entity, (data, residency, gpu)| {
+                let target = targets.get(&entity.id()).copied().unwrap_or(0);
+                let mut view_changed = false;
+
+                // Shrink: the top levels weren't needed for a while, or the budget wants them
+                let unused = settings.enabled
+                    && (over_budget || now - residency.needed_at > settings.evict_after);
+                if target > residency.allocated_mip && unused {
+                    reallocate(context, &mut encoder, data, residency, gpu, target);
+                    stats.evictions += 1;
+                    reallocated = true;
+                    view_changed = true;
+                }
+
+                // Grow by one level a frame, finest last
+                if target < residency.resident_mip {
+                    let level = residency.resident_mip - 1;
+                    if budget.take(residency.layout.level_bytes(level) as usize) {
+                        if level < residency.allocated_mip {
+                            reallocate(context, &mut encoder, data, residency, gpu, target);
+                            reallocated = true;
+                        }
+                        write_level(context, &gpu.texture, data, residency, level);
+                        residency.resident_mip = level;
+                        levels_streamed_in += 1;
+                        view_changed = true;
+                    }
+                }
+
+                if view_changed {
+                    gpu.view = resident_view(&gpu.texture, residency);
+                    changed.push(entity.id());
+                }
+                allocated_bytes += residency.allocated_bytes();
+                streamed_textures += residency.streamed as usize;
+            });
+
+            // Level writes go through the queue; only reallocations record copies
+            if reallocated {
+                context.queue.submit(Some(encoder.finish()));
+            }
+
+            if !changed.is_empty() {
+                materials.each_entity(|material, data| {
+                    let uses_changed = [
+                        &data.diffuse_texture,
+                        &data.metallic_roughness_texture,
+                        &data.normal_texture,
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|handle| handle.try_get_entity(&world))
+                    .any(|texture| changed.contains(&texture.id()));
+
+                    if uses_changed {
+                        init_gpu_material(material, data, context, mat_layout);
+                    }
+                });
+            }
+
+            stats.textures = candidates.len();
+            stats.streamed_textures = streamed_textures;
+            stats.allocated_bytes = allocated_bytes;
+            stats.budget_bytes = settings.budget_bytes;
+            stats.levels_streamed_in = levels_streamed_in;
+        });
+}
@@ -0,0 +1,122 @@
+use std::sync::Arc;
+
+use catalyst_window::{MainWindow, WindowLifecycle};
+use flecs_ecs::prelude::*;
+use winit::window::Window;
+
+use crate::RenderContext;
+
+/// The presentation surface and the window it draws to. The surface is created from
+/// an Arc of the window, so the window can't be destroyed while the surface exists,
+/// however the MainWindow singleton is torn down.
+///
+/// On platforms that take the surface away while the app is in the background
+/// (Android), it is destroyed on suspend and recreated on resume through `recreate`.
+pub struct SurfaceState {
+    // Declared before `window`: fields drop in order, so the surface always goes first
+    surface: Option<wgpu::Surface<'static>>,
+    window: Arc<Window>,
+}
+
+impl SurfaceState {
+    pub fn new(instance: &wgpu::Instance, window: Arc<Window>) -> Result<Self, wgpu::CreateSurfaceError> {
+        let surface = instance.create_surface(window.clone())?;
+        Ok(Self {
+            surface: Some(surface),
+            window,
+        })
+    }
+
+    /// None while destroyed (suspended, or the window was removed).
+    pub fn surface(&self) -> Option<&wgpu::Surface<'static>> {
+        self.surface.as_ref()
+    }
+
+    pub fn window(&self) -> &Arc<Window> {
+        &self.window
+    }
+
+    pub fn is_for(&self, window: &Arc<Window>) -> bool {
+        Arc::ptr_eq(&self.window, window)
+    }
+
+    /// Drops the surface. The window is kept until the state is dropped or recreated
+    /// for another window.
+    pub fn destroy(&mut self) {
+        if self.surface.take().is_some() {
+            log::info!("Surface destroyed");
+        }
+    }
+
+    /// Replaces the surface with a new one for `window` (the same window after a
+    /// resume, or a new one) and configures it.
+    pub fn recreate(
+        &mut self,
+        instance: &wgpu::Instance,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        window: Arc<Window>,
+    ) -> Result<(), wgpu::CreateSurfaceError> {
+        // The old surface has to go before a new one is created for the same window
+        self.surface = None;
+        let surface = instance.create_surface(window.clone())?;
+        surface.configure(device, config);
+
+        self.surface = Some(surface);
+        self.window = window;
+        log::info!("Surface recreated");
+        Ok(())
+    }
+}
+
+pub fn register_surface_observers(world: &World) {
+    // Suspend drops the surface before the platform destroys the native window behind
+    // it; resume builds a new one for the same window
+    world
+        .observer::<flecs::OnSet, (&WindowLifecycle, &mut RenderContext, &MainWindow)>()
+        .term_at(1)
+        .filter()
+        .term_at(2)
+        .filter()
+        .each(|(lifecycle, context, window)| match lifecycle {
+            WindowLifecycle::Suspended => context.surface.destroy(),
+            WindowLifecycle::Resumed => {
+                if context.surface.surface().is_none() {
+                    recreate_surface(context, &window.0);
+                }
+            }
+        });
+
+    // A replaced window gets a surface of its own
+    world
+        .observer::<flecs::OnSet, (&MainWindow, &mut RenderContext)>()
+        .term_at(1)
+        .filter()
+        .each(|(window, context)| {
+            if !context.surface.is_for(&window.0) {
+                recreate_surface(context, &window.0);
+            }
+        });
+
+    // Nothing to present to anymore. The surface's Arc keeps the window alive until
+    // the renderer is torn down or gets a new window.
+    world
+        .observer::<flecs::OnRemove, (&MainWindow, &mut RenderContext)>()
+        .term_at(1)
+        .filter()
+        .each(|(_, context)| context.surface.destroy());
+}
+
+fn recreate_surface(context: &mut RenderContext, window: &Arc<Window>) {
+    let RenderContext {
+        instance,
+        device,
+        config,
+        surface,
+        ..
+    } = context;
+
+    if let Err(error) = surface.recreate(instance, device, config, window.clone()) {
+        log::error!("Could not recreate the surface: {}", error);
+    }
+}
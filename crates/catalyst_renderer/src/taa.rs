@@ -0,0 +1,200 @@
+use flecs_ecs::prelude::*;
+use glam::{Mat4, Vec2};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    layout::{StructLayout, rust_layout},
+    programs::TaaProgram,
+    viewport::Viewport,
+};
+
+// Jitter pattern length. 8 Halton(2, 3) samples converge quickly and
+// keep the pattern short enough that it doesn't read as motion.
+const JITTER_SAMPLES: u32 = 8;
+
+/// Temporal anti-aliasing options. Read every frame, so they can be changed at runtime.
+///
+/// The renderer draws single-sampled; TAA is the anti-aliasing path and must stay
+/// exclusive with any multisampled target (history reprojection expects one sample per pixel).
+#[derive(Component, Clone, Copy, Debug)]
+pub struct TaaSettings {
+    pub enabled: bool,
+    /// Weight of the current frame in the resolve. Lower is smoother but ghosts more.
+    pub blend_factor: f32,
+    /// Jitter amplitude in pixels (1.0 = the full sub-pixel pattern).
+    pub jitter_scale: f32,
+}
+
+impl Default for TaaSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            blend_factor: 0.1,
+            jitter_scale: 1.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TaaUniform {
+    pub blend_factor: f32,
+    pub reset: u32,
+    pub uv_scale: [f32; 2], // Part of the targets holding the scene, see DynamicResolution
+}
+
+const _: () = assert!(std::mem::size_of::<TaaUniform>() == 16);
+
+impl TaaUniform {
+    pub const LAYOUT: StructLayout = rust_layout!(TaaUniform {
+        blend_factor: f32,
+        reset: u32,
+        uv_scale: [f32; 2],
+    });
+}
+
+/// TAA state kept between frames: the jitter sequence and which of the two history
+/// textures holds last frame's result. The textures themselves live in the frame graph.
+pub struct TaaHistory {
+    uniform_buffer: wgpu::Buffer,
+    // Which history texture holds last frame's result
+    history_index: usize,
+    frame_index: u32,
+    // False until a resolve has written history (and again whenever TAA is switched off)
+    history_valid: bool,
+    // uv_scale of the last resolve; the history doesn't line up with another one
+    uv_scale: Vec2,
+}
+
+impl TaaHistory {
+    /// Frame graph names of the persistent history textures.
+    pub const HISTORY_TEXTURES: [&'static str; 2] = ["TAA History 0", "TAA History 1"];
+
+    pub fn new(device: &wgpu::Device) -> Self {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("TAA Uniform Buffer"),
+            contents: bytemuck::bytes_of(&TaaUniform {
+                blend_factor: 1.0,
+                reset: 1,
+                uv_scale: [1.0; 2],
+            }),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        Self {
+            uniform_buffer,
+            history_index: 0,
+            frame_index: 0,
+            history_valid: false,
+            uv_scale: Vec2::ONE,
+        }
+    }
+
+    /// Advances the jitter sequence. Call once per frame while TAA is enabled.
+    pub fn begin_frame(&mut self) {
+        self.frame_index = self.frame_index.wrapping_add(1);
+    }
+
+    /// Forget the history, e.g. when TAA is switched off so re-enabling doesn't
+    /// blend in a stale image.
+    pub fn invalidate_history(&mut self) {
+        self.history_valid = false;
+    }
+
+    /// Sub-pixel offset for this frame in NDC units of `viewport`.
+    pub fn jitter(&self, viewport: &Viewport, jitter_scale: f32) -> Vec2 {
+        let index = self.frame_index % JITTER_SAMPLES + 1; // Halton(0) is 0, skip it
+        let sample = Vec2::new(halton(index, 2), halton(index, 3)) - Vec2::splat(0.5);
+
+        // One pixel is 2 / size in NDC
+        sample * 2.0 / Vec2::new(viewport.width, viewport.height) * jitter_scale
+    }
+
+    /// `uv_scale` is the part of the targets the scene covers (render size / target size).
+    /// A new one drops the history, which was drawn at the old size.
+    pub fn write_uniform(&mut self, queue: &wgpu::Queue, settings: &TaaSettings, uv_scale: Vec2) {
+        if uv_scale != self.uv_scale {
+            self.uv_scale = uv_scale;
+            self.history_valid = false;
+        }
+
+        let uniform = TaaUniform {
+            blend_factor: settings.blend_factor.clamp(0.0, 1.0),
+            reset: (!self.history_valid) as u32,
+            uv_scale: uv_scale.to_array(),
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+
+    /// Names of (last frame's history, the texture the resolve writes this frame).
+    pub fn history_textures(&self) -> (&'static str, &'static str) {
+        (
+            Self::HISTORY_TEXTURES[self.history_index],
+            Self::HISTORY_TEXTURES[1 - self.history_index],
+        )
+    }
+
+    /// Bind group of the resolve. The views come from the frame graph, so it is built
+    /// per frame.
+    pub fn bind_group(
+        &self,
+        device: &wgpu::Device,
+        program: &TaaProgram,
+        scene_color: &wgpu::TextureView,
+        history: &wgpu::TextureView,
+        velocity: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("TAA Bind Group"),
+            layout: &program.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(scene_color),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(history),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(velocity),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&program.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    /// Call after the resolve pass: what we just wrote becomes next frame's history.
+    pub fn end_frame(&mut self) {
+        self.history_index = 1 - self.history_index;
+        self.history_valid = true;
+    }
+}
+
+/// Shifts a projection matrix by `jitter` (NDC units). Only the projection moves,
+/// so motion vectors computed from the unjittered matrices stay jitter-free.
+pub fn jitter_projection(mut proj: Mat4, jitter: Vec2) -> Mat4 {
+    proj.z_axis.x += jitter.x;
+    proj.z_axis.y += jitter.y;
+    proj
+}
+
+/// Radical inverse of `index` in `base`, in [0, 1).
+pub fn halton(mut index: u32, base: u32) -> f32 {
+    let mut result = 0.0;
+    let mut fraction = 1.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
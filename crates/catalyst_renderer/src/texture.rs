@@ -1,12 +1,17 @@
-use catalyst_assets::material::TextureData;
+use catalyst_assets::{PrewarmAsset, lut::LutData, material::TextureData};
 use flecs_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
 use half::f16;
 use wgpu::{
-    Device, Extent3d, SurfaceConfiguration, TextureDescriptor, TextureDimension, TextureFormat,
-    TextureUsages,
+    CompareFunction, Device, Extent3d, SurfaceConfiguration, TextureDescriptor, TextureDimension,
+    TextureFormat, TextureUsages,
 };
 
-use crate::render::RenderContext;
+use crate::{
+    device_recovery::Regenerating,
+    render::{RenderContext, RenderSettings},
+    streaming::{TextureResidency, init_streamed_texture, is_streamable},
+};
 
 #[derive(Component, Clone)]
 pub struct GpuTexture {
@@ -36,6 +41,11 @@ impl GpuTexture {
 
         let (texture, view) = match &data.pixels {
             catalyst_assets::material::TextureType::LDR(pixels) => {
+                // Textures imported without mips come through here, grayscale ones included
+                let bytes_per_texel = match data.format {
+                    catalyst_assets::material::TextureFormat::Gray8 => 1,
+                    _ => 4,
+                };
                 let size = wgpu::Extent3d {
                     width: data.width,
                     height: data.height,
@@ -66,7 +76,7 @@ impl GpuTexture {
                     // RENAMED: ImageDataLayout -> TexelCopyBufferLayout
                     wgpu::TexelCopyBufferLayout {
                         offset: 0,
-                        bytes_per_row: Some(4 * data.width),
+                        bytes_per_row: Some(bytes_per_texel * data.width),
                         rows_per_image: Some(data.height),
                     },
                     size,
@@ -144,12 +154,145 @@ impl GpuTexture {
             sampler,
         }
     }
+
+    /// A 3D texture of a color grading LUT, red along x, green along y and blue along z,
+    /// sampled linearly and clamped at the edges.
+    pub fn from_lut(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        lut: &LutData,
+        label: Option<&str>,
+    ) -> Self {
+        let data_f16: Vec<u16> = lut
+            .to_rgba_f32()
+            .iter()
+            .map(|&val| f16::from_f32(val).to_bits())
+            .collect();
+
+        let size = wgpu::Extent3d {
+            width: lut.size,
+            height: lut.size,
+            depth_or_array_layers: lut.size,
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: wgpu::TextureFormat::Rgba16Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            bytemuck::cast_slice(&data_f16),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(8 * lut.size), // 4 halves per entry
+                rows_per_image: Some(lut.size),
+            },
+            size,
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D3),
+            ..Default::default()
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("LUT Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+}
+
+/// How the depth buffer is laid out. Chosen once when the renderer initializes,
+/// because every depth-tested pipeline bakes the compare function in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DepthMode {
+    /// Near = 0.0, Far = 1.0, closer pixels have smaller depth.
+    #[default]
+    Standard,
+    /// Near = 1.0, Far (infinity) = 0.0, closer pixels have larger depth.
+    /// Requires a floating point depth format to be useful.
+    ReversedZ,
+}
+
+impl DepthMode {
+    /// Compare function for opaque geometry ("closer pixels win").
+    pub fn compare(&self) -> CompareFunction {
+        match self {
+            DepthMode::Standard => CompareFunction::Less,
+            DepthMode::ReversedZ => CompareFunction::Greater,
+        }
+    }
+
+    /// Compare function for overlays drawn on top of already written depth (lines, skybox).
+    pub fn compare_or_equal(&self) -> CompareFunction {
+        match self {
+            DepthMode::Standard => CompareFunction::LessEqual,
+            DepthMode::ReversedZ => CompareFunction::GreaterEqual,
+        }
+    }
+
+    /// Value the depth buffer is cleared to ("as far away as possible").
+    pub fn clear_value(&self) -> f32 {
+        match self {
+            DepthMode::Standard => 1.0,
+            DepthMode::ReversedZ => 0.0,
+        }
+    }
 }
 
 pub struct TextureHelper;
 
 impl TextureHelper {
     pub const DEPTH_FORMAT: TextureFormat = TextureFormat::Depth32Float; // Standard depth format
+    pub const VELOCITY_FORMAT: TextureFormat = TextureFormat::Rg16Float; // Screen-space motion in UV units
+    pub const LUMINANCE_FORMAT: TextureFormat = TextureFormat::Rg16Float; // log2 luminance, coverage
+
+    /// Screen-sized color texture that can be rendered to and sampled afterwards.
+    pub fn create_color_target(
+        device: &Device,
+        config: &SurfaceConfiguration,
+        format: TextureFormat,
+        label: &str,
+    ) -> wgpu::TextureView {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some(label),
+            size: Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        texture.create_view(&wgpu::TextureViewDescriptor::default())
+    }
 
     pub fn create_depth_texture(
         device: &Device,
@@ -180,13 +323,125 @@ impl TextureHelper {
 
 pub fn register_texture_handlers(world: &World) {
     world
-        .system_named::<(&TextureData, &mut RenderContext)>("Init Texture GPU buffers")
-        .without(GpuTexture::id())
-        .kind(flecs::pipeline::OnStore)
+        .observer::<flecs::OnSet, (&TextureData, &RenderContext)>()
+        .term_at(1)
+        .filter()
+        .without(PrewarmAsset) // Uploaded by the prewarm queue
         .each_entity(|entity, (texture_data, context)| {
-            let gpu_tex =
-                GpuTexture::from_image(&context.device, &context.queue, &texture_data, None);
+            init_gpu_texture(entity, texture_data, context);
+        });
 
-            entity.set(gpu_tex);
+    world
+        .observer::<flecs::OnRemove, &TextureData>()
+        .each_entity(|entity, _| {
+            entity.remove(GpuTexture::id());
+            entity.remove(TextureResidency::id());
         });
+
+    // Textures that arrived before the renderer was initialized
+    let textures_without_gpu = world
+        .query::<&TextureData>()
+        .without(GpuTexture::id())
+        .without(PrewarmAsset)
+        .build();
+
+    world
+        .observer::<flecs::OnSet, &RenderContext>()
+        .each(move |context| {
+            textures_without_gpu.each_entity(|entity, texture_data| {
+                init_gpu_texture(entity, texture_data, context);
+            });
+        });
+}
+
+pub(crate) fn init_gpu_texture(entity: EntityView, texture_data: &TextureData, context: &RenderContext) {
+    if is_streamable(texture_data) {
+        let settings = entity
+            .world()
+            .get::<&RenderSettings>(|settings| settings.texture_streaming.clone());
+        init_streamed_texture(entity, texture_data, context, &settings);
+        entity.remove(Regenerating);
+        return;
+    }
+
+    let gpu_tex = GpuTexture::from_image(&context.device, &context.queue, texture_data, None);
+
+    entity.set(gpu_tex);
+    entity.remove(Regenerating);
+}
+
+#[cfg(test)]
+mod tests {
+    use catalyst_core::camera::Camera;
+    use glam::Vec3;
+    use wgpu::CompareFunction;
+
+    use super::DepthMode;
+
+    // Depth `distance` units in front of a camera at the origin looking down -Z
+    fn depth(camera: &Camera, mode: DepthMode, distance: f32) -> f32 {
+        let projection = match mode {
+            DepthMode::Standard => camera.compute_projection_matrix(),
+            DepthMode::ReversedZ => camera.compute_reversed_z_projection_matrix(),
+        };
+        projection.project_point3(Vec3::new(0.0, 0.0, -distance)).z
+    }
+
+    #[test]
+    fn compare_and_clear_values() {
+        assert_eq!(DepthMode::Standard.compare(), CompareFunction::Less);
+        assert_eq!(
+            DepthMode::Standard.compare_or_equal(),
+            CompareFunction::LessEqual
+        );
+        assert_eq!(DepthMode::Standard.clear_value(), 1.0);
+
+        assert_eq!(DepthMode::ReversedZ.compare(), CompareFunction::Greater);
+        assert_eq!(
+            DepthMode::ReversedZ.compare_or_equal(),
+            CompareFunction::GreaterEqual
+        );
+        assert_eq!(DepthMode::ReversedZ.clear_value(), 0.0);
+    }
+
+    #[test]
+    fn closer_pixels_pass_the_compare_and_the_clear_value_is_farthest() {
+        let camera = Camera::default();
+        for mode in [DepthMode::Standard, DepthMode::ReversedZ] {
+            let near = depth(&camera, mode, 1.0);
+            let far = depth(&camera, mode, 50.0);
+            let (closer_wins, beats_clear) = match mode.compare() {
+                CompareFunction::Less => (near < far, far < mode.clear_value()),
+                CompareFunction::Greater => (near > far, far > mode.clear_value()),
+                other => panic!("unexpected compare {:?}", other),
+            };
+            assert!(closer_wins, "{:?}: {} vs {}", mode, near, far);
+            assert!(beats_clear, "{:?}: {} vs clear", mode, far);
+        }
+    }
+
+    // Two surfaces 5 cm apart, 800 m away with a 10 cm near plane: the z-fighting
+    // reversed-Z is there for
+    #[test]
+    fn reversed_z_separates_distant_surfaces() {
+        let camera = Camera {
+            near: 0.1,
+            far: 1000.0,
+            ..Default::default()
+        };
+
+        let front = depth(&camera, DepthMode::ReversedZ, 800.0);
+        let back = depth(&camera, DepthMode::ReversedZ, 800.05);
+        assert!(front > back, "reversed-Z: {} vs {}", front, back);
+
+        // Standard depth crowds everything this far into the last few values below 1.0
+        let front = depth(&camera, DepthMode::Standard, 800.0);
+        let back = depth(&camera, DepthMode::Standard, 800.05);
+        assert!(
+            (back - front).abs() <= f32::EPSILON,
+            "standard: {} vs {}",
+            front,
+            back
+        );
+    }
 }
@@ -0,0 +1,170 @@
+use catalyst_assets::{
+    PrewarmAsset, PrewarmScene,
+    assets::MeshData,
+    material::{MaterialData, TextureData, TextureType},
+    scene::{SceneData, SceneReady},
+};
+use flecs_ecs::prelude::*;
+
+use crate::{
+    material::{GpuMaterial, init_gpu_material},
+    mesh::{GpuGeometry, Vertex, init_gpu_geometry},
+    render::{MaterialLayout, RenderContext},
+    texture::{GpuTexture, init_gpu_texture},
+};
+
+/// Budget of the prewarm upload queue. Read every frame, so it can be changed at runtime.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct UploadSettings {
+    /// Texture and mesh bytes uploaded per frame. One item is always uploaded,
+    /// so assets bigger than the budget still get through (over one frame).
+    pub bytes_per_frame: usize,
+}
+
+impl Default for UploadSettings {
+    fn default() -> Self {
+        Self {
+            bytes_per_frame: 8 * 1024 * 1024,
+        }
+    }
+}
+
+pub(crate) struct UploadBudget {
+    limit: usize,
+    spent: usize,
+}
+
+impl UploadBudget {
+    pub(crate) fn new(limit: usize) -> Self {
+        Self { limit, spent: 0 }
+    }
+
+    pub(crate) fn take(&mut self, bytes: usize) -> bool {
+        if self.spent > 0 && self.spent + bytes > self.limit {
+            return false;
+        }
+        self.spent += bytes;
+        true
+    }
+}
+
+fn texture_bytes(data: &TextureData) -> usize {
+    match &data.pixels {
+        TextureType::LDR(pixels) => pixels.len(),
+        TextureType::HDR(pixels) => pixels.len() * std::mem::size_of::<f32>(),
+    }
+}
+
+fn mesh_bytes(data: &MeshData) -> usize {
+    data.vertices.len() * std::mem::size_of::<Vertex>()
+        + data.indices.len() * data.index_format().size()
+}
+
+// Every texture the material references is on the GPU. Empty slots use the renderer defaults.
+fn textures_ready(world: &World, data: &MaterialData) -> bool {
+    [
+        &data.diffuse_texture,
+        &data.metallic_roughness_texture,
+        &data.normal_texture,
+    ]
+    .into_iter()
+    .flatten()
+    .all(|handle| {
+        handle
+            .try_get_entity(world)
+            .is_some_and(|texture| texture.has(GpuTexture::id()))
+    })
+}
+
+pub fn register_upload_queue(world: &World) {
+    world
+        .component::<UploadSettings>()
+        .add_trait::<flecs::Singleton>()
+        .set(UploadSettings::default());
+
+    let queued_textures = world
+        .query::<&TextureData>()
+        .with(PrewarmAsset)
+        .without(GpuTexture::id())
+        .set_cached()
+        .build();
+
+    let queued_meshes = world
+        .query::<&MeshData>()
+        .with(PrewarmAsset)
+        .without(GpuGeometry::id())
+        .set_cached()
+        .build();
+
+    let queued_materials = world
+        .query::<&MaterialData>()
+        .with(PrewarmAsset)
+        .without(GpuMaterial::id())
+        .set_cached()
+        .build();
+
+    // Textures first, materials can't build their bind groups without them.
+    // Everything created here shows up at the end of the system, so a material
+    // whose textures were uploaded this frame waits for the next one.
+    world
+        .system_named::<(&RenderContext, &MaterialLayout, &UploadSettings)>(
+            "Upload Prewarmed Assets",
+        )
+        .kind(flecs::pipeline::PostUpdate)
+        .each(move |(context, mat_layout, settings)| {
+            catalyst_core::profile_scope!("Upload Prewarmed Assets");
+            let mut budget = UploadBudget::new(settings.bytes_per_frame);
+
+            queued_textures.each_entity(|entity, data| {
+                if budget.take(texture_bytes(data)) {
+                    init_gpu_texture(entity, data, context);
+                    entity.remove(PrewarmAsset);
+                }
+            });
+
+            queued_meshes.each_entity(|entity, data| {
+                if budget.take(mesh_bytes(data)) {
+                    init_gpu_geometry(entity, data, context);
+                    entity.remove(PrewarmAsset);
+                }
+            });
+
+            // Bind groups are cheap, they don't count against the budget
+            queued_materials.each_entity(|entity, data| {
+                if textures_ready(&entity.world(), data) {
+                    init_gpu_material(entity, data, context, mat_layout);
+                    entity.remove(PrewarmAsset);
+                }
+            });
+        });
+
+    world
+        .system_named::<&SceneData>("Check Prewarmed Scenes")
+        .with(PrewarmScene)
+        .without(SceneReady)
+        .kind(flecs::pipeline::PostUpdate)
+        .each_entity(|entity, scene| {
+            let world = entity.world();
+
+            let textures = scene.textures.iter().all(|handle| {
+                handle
+                    .try_get_entity(&world)
+                    .is_some_and(|texture| texture.has(GpuTexture::id()))
+            });
+            let materials = scene.materials.iter().all(|handle| {
+                handle
+                    .try_get_entity(&world)
+                    .is_some_and(|material| material.has(GpuMaterial::id()))
+            });
+            let meshes = scene.meshes.iter().all(|handle| {
+                handle
+                    .try_get_entity(&world)
+                    .is_some_and(|mesh| mesh.has(GpuGeometry::id()))
+            });
+
+            if textures && materials && meshes {
+                log::info!("Scene {} is warm", entity.name());
+                entity.add(SceneReady);
+            }
+        });
+}
@@ -0,0 +1,116 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use flecs_ecs::prelude::*;
+
+static TOO_MANY_VIEWS_REPORTED: AtomicBool = AtomicBool::new(false);
+
+/// How the screen is divided when more than one player camera is active.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SplitScreenLayout {
+    /// Players side by side (the split line is vertical).
+    #[default]
+    Vertical,
+    /// Players stacked top to bottom (the split line is horizontal).
+    Horizontal,
+}
+
+/// Singleton controlling the split screen presentation of player cameras.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct SplitScreenSettings {
+    pub layout: SplitScreenLayout,
+}
+
+/// A region of the render target in pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Viewport {
+    pub fn full(width: u32, height: u32) -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            width: width as f32,
+            height: height as f32,
+        }
+    }
+
+    pub fn aspect_ratio(&self) -> f32 {
+        self.width / self.height.max(1.0)
+    }
+
+    pub fn apply(&self, render_pass: &mut wgpu::RenderPass) {
+        render_pass.set_viewport(self.x, self.y, self.width, self.height, 0.0, 1.0);
+        render_pass.set_scissor_rect(
+            self.x as u32,
+            self.y as u32,
+            self.width as u32,
+            self.height as u32,
+        );
+    }
+}
+
+/// Splits a `width` x `height` target into `count` viewports (1 to 4 players).
+///
+/// 2 players split along the layout direction, 3 players get one full-length
+/// region plus two halves, 4 players always use a 2x2 grid.
+pub fn compute_viewports(
+    layout: SplitScreenLayout,
+    count: usize,
+    width: u32,
+    height: u32,
+) -> Vec<Viewport> {
+    let w = width as f32;
+    let h = height as f32;
+    let half_w = (width / 2) as f32;
+    let half_h = (height / 2) as f32;
+
+    let rect = |x: f32, y: f32, width: f32, height: f32| Viewport {
+        x,
+        y,
+        width,
+        height,
+    };
+
+    match (count, layout) {
+        (0, _) => Vec::new(),
+        (1, _) => vec![Viewport::full(width, height)],
+        (2, SplitScreenLayout::Vertical) => vec![
+            rect(0.0, 0.0, half_w, h),
+            rect(half_w, 0.0, w - half_w, h),
+        ],
+        (2, SplitScreenLayout::Horizontal) => vec![
+            rect(0.0, 0.0, w, half_h),
+            rect(0.0, half_h, w, h - half_h),
+        ],
+        (3, SplitScreenLayout::Vertical) => vec![
+            rect(0.0, 0.0, half_w, h),
+            rect(half_w, 0.0, w - half_w, half_h),
+            rect(half_w, half_h, w - half_w, h - half_h),
+        ],
+        (3, SplitScreenLayout::Horizontal) => vec![
+            rect(0.0, 0.0, w, half_h),
+            rect(0.0, half_h, half_w, h - half_h),
+            rect(half_w, half_h, w - half_w, h - half_h),
+        ],
+        _ => {
+            // Runs every frame, so only the first time is reported
+            if count > 4 && !TOO_MANY_VIEWS_REPORTED.swap(true, Ordering::Relaxed) {
+                log::warn!(
+                    "Split screen supports up to 4 views, {} requested. Extra views are skipped.",
+                    count
+                );
+            }
+            vec![
+                rect(0.0, 0.0, half_w, half_h),
+                rect(half_w, 0.0, w - half_w, half_h),
+                rect(0.0, half_h, half_w, h - half_h),
+                rect(half_w, half_h, w - half_w, h - half_h),
+            ]
+        }
+    }
+}
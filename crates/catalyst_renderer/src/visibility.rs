@@ -0,0 +1,83 @@
+use catalyst_core::camera::Camera;
+use flecs_ecs::prelude::*;
+use glam::{Mat4, Vec3, Vec4};
+
+/// Whether any camera can see the entity, updated every frame by the renderer for
+/// the entities it tracks (particle emitters and entities with an ImportanceBias).
+/// Systems can skip work for invisible entities.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ComputedVisibility {
+    pub visible: bool,
+}
+
+impl Default for ComputedVisibility {
+    fn default() -> Self {
+        Self { visible: true }
+    }
+}
+
+/// The frustum and eye position of one rendered view.
+#[derive(Clone, Copy, Debug)]
+pub struct ViewVolume {
+    pub frustum: Frustum,
+    pub eye: Vec3,
+    /// Unjittered 0..1 depth view-projection, for projecting onto the view's screen.
+    pub view_proj: Mat4,
+}
+
+/// Every view drawn this frame (one per split-screen player), updated in PostUpdate.
+#[derive(Component, Clone, Debug, Default)]
+pub struct ViewFrusta(pub Vec<ViewVolume>);
+
+/// Side and near planes of a camera's view volume. There is no far plane, so the
+/// test works the same for infinite (reversed-Z) projections.
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    // xyz = normal pointing inside, w = distance; normalized
+    planes: [Vec4; 5],
+}
+
+impl Frustum {
+    /// Planes of a standard 0..1 depth view-projection matrix.
+    pub fn from_view_proj(view_proj: Mat4) -> Self {
+        let (r0, r1, r2, r3) = (
+            view_proj.row(0),
+            view_proj.row(1),
+            view_proj.row(2),
+            view_proj.row(3),
+        );
+
+        let planes = [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r2]
+            .map(|plane| plane / plane.truncate().length().max(f32::EPSILON));
+
+        Self { planes }
+    }
+
+    /// Frustum of a camera placed at `camera_transform`, for a view of the given aspect ratio.
+    pub fn from_camera(camera: &Camera, camera_transform: Mat4, aspect_ratio: f32) -> Self {
+        Self::from_view_proj(camera_view_proj(camera, camera_transform, aspect_ratio))
+    }
+
+    pub fn intersects_sphere(&self, center: Vec3, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.truncate().dot(center) + plane.w >= -radius)
+    }
+}
+
+/// Standard 0..1 depth view-projection of a camera placed at `camera_transform`, for a
+/// view of the given aspect ratio.
+pub fn camera_view_proj(camera: &Camera, camera_transform: Mat4, aspect_ratio: f32) -> Mat4 {
+    let eye = camera_transform.transform_point3(Vec3::ZERO);
+    let forward = -camera_transform.z_axis.truncate();
+    let up = camera_transform.y_axis.truncate();
+    let view = Mat4::look_at_rh(eye, eye + forward, up);
+
+    let projection = Camera {
+        aspect_ratio,
+        ..camera.clone()
+    }
+    .compute_projection_matrix();
+
+    projection * view
+}
@@ -0,0 +1,268 @@
+use std::f32::consts::TAU;
+
+use catalyst_assets::material::MaterialWind;
+use catalyst_core::{App, pipeline::PhaseRenderPrepare, time::Time};
+use flecs_ecs::prelude::*;
+use glam::{Vec2, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::render::RenderContext;
+
+// Must match the WIND_* constants in common/wind.wgsl
+const FLUTTER_RATE: f32 = 7.0;
+const WAVE_DENSITY: f32 = 0.15;
+
+/// The wind vegetation sways in, in the singleton of the same name.
+///
+/// Only materials with `MaterialSettings::wind` move, each vertex as far as its
+/// `Vertex::wind_weight` says, in the vertex shaders of the PBR, picking and debug view
+/// passes: a bend along the wind that rolls through a field as a wave, a sideways sway
+/// of branches and a fast flutter of the tips. Last frame's wind goes along, so the
+/// motion vectors follow the sway and TAA doesn't smear it.
+///
+/// The wind has its own clock advanced with the scaled game time, so it stops while
+/// gameplay is paused. Reflection probes are captured with the plants at rest.
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindSettings {
+    pub enabled: bool,
+    /// Where the wind blows to, on the ground plane (x, z). Normalized when used.
+    pub direction: [f32; 2],
+    /// Scales every material's wind distances; 1 is the breeze they were tuned for.
+    pub strength: f32,
+    /// How much stronger the wind gets in gusts, 0.5 is half again as strong.
+    pub gust_strength: f32,
+    /// Gusts per second.
+    pub gust_frequency: f32,
+    #[serde(skip)]
+    time: f32,
+    #[serde(skip)]
+    uniforms: WindUniforms,
+}
+
+impl Default for WindSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            direction: [1.0, 0.3],
+            strength: 1.0,
+            gust_strength: 0.5,
+            gust_frequency: 0.15,
+            time: 0.0,
+            uniforms: WindUniforms::default(),
+        }
+    }
+}
+
+/// The camera uniforms' wind: .xy = horizontal wind, .z = wind clock in seconds.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WindUniforms {
+    pub current: [f32; 4],
+    pub previous: [f32; 4],
+}
+
+impl WindSettings {
+    /// The horizontal wind (x, z) at the wind clock's current time, gusts included.
+    /// Zero when disabled.
+    pub fn wind(&self) -> Vec2 {
+        self.wind_at(self.time)
+    }
+
+    /// Seconds on the wind clock.
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Where a vertex at `position` (world space) with `normal` and `weight` is drawn
+    /// with `material`, matching the shader. Lets gameplay line things up with a
+    /// swaying plant.
+    pub fn displace(
+        &self,
+        material: &MaterialWind,
+        position: Vec3,
+        normal: Vec3,
+        weight: f32,
+    ) -> Vec3 {
+        let wind = self.uniforms.current;
+        position + offset(wind, material, position, normal, weight)
+    }
+
+    /// Moves the wind clock on by `dt` seconds and keeps the last frame's wind.
+    pub fn advance(&mut self, dt: f32) {
+        self.time += dt.max(0.0);
+        let wind = self.wind_at(self.time);
+        let current = [wind.x, wind.y, self.time, 0.0];
+        // Before the clock first ran there is no history: the wind counts as steady
+        let previous = if self.uniforms.current[2] > 0.0 {
+            self.uniforms.current
+        } else {
+            current
+        };
+        self.uniforms = WindUniforms { current, previous };
+    }
+
+    pub(crate) fn uniforms(&self) -> WindUniforms {
+        self.uniforms
+    }
+
+    fn wind_at(&self, time: f32) -> Vec2 {
+        let direction = Vec2::from(self.direction).normalize_or_zero();
+        if !self.enabled || direction == Vec2::ZERO {
+            return Vec2::ZERO;
+        }
+        // Two sines out of step, so gusts come irregularly; 0 to 1
+        let phase = TAU * self.gust_frequency.max(0.0) * time;
+        let gust = 0.5 + 0.5 * (phase.sin() * (phase * 0.37 + 1.3).sin());
+        direction * self.strength.max(0.0) * (1.0 + self.gust_strength.max(0.0) * gust)
+    }
+}
+
+// The shader's wind_offset
+fn offset(
+    wind: [f32; 4],
+    material: &MaterialWind,
+    position: Vec3,
+    normal: Vec3,
+    weight: f32,
+) -> Vec3 {
+    let horizontal = Vec2::new(wind[0], wind[1]);
+    let speed = horizontal.length();
+    if weight <= 0.0 || speed <= 0.0 {
+        return Vec3::ZERO;
+    }
+    let along = Vec3::new(horizontal.x, 0.0, horizontal.y) / speed;
+    let side = Vec3::new(-along.z, 0.0, along.x);
+    let time = wind[2];
+
+    let wave = Vec2::new(position.x, position.z).dot(Vec2::new(along.x, along.z)) * WAVE_DENSITY;
+    let sway = 0.75 + 0.25 * (TAU * (material.frequency * time - wave)).sin();
+    let mut offset = along * (material.strength * sway);
+
+    let branch_phase = position.dot(Vec3::new(0.37, 0.11, 0.53));
+    offset +=
+        side * (material.branch * (TAU * material.frequency * 1.7 * time + branch_phase).sin());
+
+    let flutter_phase = position.dot(Vec3::new(1.3, 2.1, 1.7));
+    offset += normal * (material.flutter * (TAU * FLUTTER_RATE * time + flutter_phase).sin());
+
+    offset * (speed * weight)
+}
+
+pub fn register_wind_systems(app: &mut App) {
+    app.register_singleton_default::<WindSettings>();
+
+    app.world
+        .system_named::<(&mut WindSettings, &Time)>("Update Wind")
+        .kind(flecs::pipeline::OnUpdate)
+        .each(|(wind, time)| {
+            wind.advance(time.scaled_delta().as_secs_f32());
+        });
+
+    app.world
+        .system_named::<(&WindSettings, &mut RenderContext)>("Upload Wind")
+        .kind(PhaseRenderPrepare)
+        .each(|(wind, context)| {
+            context.global_resources.wind = wind.uniforms();
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f32 = 1.0 / 60.0;
+    const FRAMES: usize = 600;
+    const EPSILON: f32 = 1e-5;
+
+    const NORMAL: Vec3 = Vec3::Z;
+    const TIP: Vec3 = Vec3::new(3.0, 1.0, -2.0);
+    const NEIGHBOUR: Vec3 = Vec3::new(5.5, 1.0, 1.0);
+
+    fn wind() -> WindSettings {
+        WindSettings {
+            direction: [1.0, 0.0],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn plants_sway_by_their_weight_and_lean_with_the_wind() {
+        let material = MaterialWind::default();
+        let mut wind = wind();
+        let mut lean = 0.0;
+        let mut out_of_step = false;
+        for _ in 0..FRAMES {
+            wind.advance(DT);
+            // A vertex with a weight of 0 stays put
+            assert_eq!(wind.displace(&material, TIP, NORMAL, 0.0), TIP);
+            let full = wind.displace(&material, TIP, NORMAL, 1.0) - TIP;
+            let half = wind.displace(&material, TIP, NORMAL, 0.5) - TIP;
+            assert!((full * 0.5 - half).length() < EPSILON);
+            lean += full.x / FRAMES as f32;
+            let other = wind.displace(&material, NEIGHBOUR, NORMAL, 1.0) - NEIGHBOUR;
+            out_of_step |= (other - full).length() > 0.01;
+        }
+        assert!(lean > 0.1, "leans {:.3} m", lean);
+        assert!(out_of_step, "neighbours sway in step");
+    }
+
+    #[test]
+    fn gusts_keep_the_wind_between_its_strength_and_the_gusts() {
+        let mut wind = wind();
+        let (mut weakest, mut strongest) = (f32::MAX, 0.0f32);
+        for _ in 0..FRAMES {
+            wind.advance(DT);
+            let speed = wind.wind().length();
+            weakest = weakest.min(speed);
+            strongest = strongest.max(speed);
+        }
+        assert!(weakest >= wind.strength - EPSILON, "{}", weakest);
+        assert!(
+            strongest <= wind.strength * (1.0 + wind.gust_strength) + EPSILON,
+            "{}",
+            strongest
+        );
+        assert!(strongest - weakest > 0.1, "{} to {}", weakest, strongest);
+    }
+
+    #[test]
+    fn a_paused_clock_freezes_the_sway() {
+        let material = MaterialWind::default();
+        let mut wind = wind();
+        wind.advance(1.0);
+        let time = wind.time();
+        let before = wind.displace(&material, TIP, NORMAL, 1.0);
+        for _ in 0..10 {
+            wind.advance(0.0);
+        }
+        assert_eq!(wind.time(), time);
+        assert_eq!(wind.displace(&material, TIP, NORMAL, 1.0), before);
+    }
+
+    #[test]
+    fn without_branch_and_flutter_a_plant_only_bends_along_the_wind() {
+        let calm = MaterialWind {
+            branch: 0.0,
+            flutter: 0.0,
+            ..MaterialWind::default()
+        };
+        let mut wind = wind();
+        wind.advance(1.0);
+        let bend = wind.displace(&calm, TIP, NORMAL, 1.0) - TIP;
+        let along = wind.wind().normalize();
+        assert!(bend.y.abs() < EPSILON);
+        assert!(Vec2::new(bend.x, bend.z).normalize().dot(along) > 1.0 - EPSILON);
+    }
+
+    #[test]
+    fn disabled_wind_moves_nothing() {
+        let material = MaterialWind::default();
+        let mut wind = WindSettings {
+            enabled: false,
+            ..wind()
+        };
+        wind.advance(DT);
+        assert_eq!(wind.wind(), Vec2::ZERO);
+        assert_eq!(wind.displace(&material, TIP, NORMAL, 1.0), TIP);
+    }
+}
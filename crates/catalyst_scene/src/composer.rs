@@ -0,0 +1,325 @@
+use std::collections::{BTreeMap, HashSet};
+
+use catalyst_assets::{
+    AssetSource, LoadScene, MaterialDefinition, MeshDefinition, asset_events::AssetLookup,
+    lod::MeshLod, material::MaterialData,
+};
+use catalyst_core::{
+    Source,
+    camera::Camera,
+    transform::{GlobalTransform, Transform},
+};
+use flecs_ecs::prelude::*;
+use glam::Vec3;
+
+use crate::despawn_scene;
+
+/// On every entity spawned from a scene: the scene entity it came from, which for a
+/// chunk loaded by the WorldComposer is the chunk.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SceneMembership(pub Entity);
+
+/// On a chunk's scene entity: the key the WorldComposer loaded it under.
+#[derive(Component, Clone, Debug)]
+pub struct SceneChunk {
+    pub key: String,
+}
+
+/// Unload group of a chunk, see WorldComposer::unload_group. On a StreamingVolume it is
+/// passed on to the chunks it loads.
+#[derive(Component, Clone, Debug, PartialEq, Eq)]
+pub struct ChunkGroup(pub String);
+
+/// Loads a scene as a chunk when the nearest camera comes within `load_distance` of
+/// the entity, and unloads it once every camera is further than `unload_distance`.
+/// The chunk is placed at the entity's GlobalTransform.
+#[derive(Component, Clone, Debug)]
+pub struct StreamingVolume {
+    /// Key of the chunk in the WorldComposer.
+    pub key: String,
+    pub path: String,
+    pub load_distance: f32,
+    /// Larger than `load_distance`, so a camera on the edge doesn't load and unload
+    /// the chunk every frame.
+    pub unload_distance: f32,
+}
+
+impl StreamingVolume {
+    pub fn new(key: impl Into<String>, path: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            path: path.into(),
+            load_distance: 100.0,
+            unload_distance: 120.0,
+        }
+    }
+
+    pub fn distances(mut self, load: f32, unload: f32) -> Self {
+        self.load_distance = load;
+        self.unload_distance = unload.max(load);
+        self
+    }
+}
+
+// Assets a chunk brought in, released once the chunk is gone
+struct PendingRelease {
+    chunk: Entity,
+    assets: Vec<Entity>,
+}
+
+/// Scenes loaded side by side as chunks of one world, each under a key and at its own
+/// placement. Loading a key that is loaded already does nothing, so streaming code can
+/// call `load` every frame.
+#[derive(Component, Default)]
+pub struct WorldComposer {
+    chunks: BTreeMap<String, Entity>,
+    releases: Vec<PendingRelease>,
+}
+
+impl WorldComposer {
+    /// Loads the scene at `path` as the chunk `key`, its nodes placed relative to
+    /// `placement`. Returns the chunk's scene entity, which takes the usual scene
+    /// options (SceneRoot, PrewarmScene) and a ChunkGroup.
+    pub fn load<'a>(
+        &mut self,
+        world: &'a World,
+        key: &str,
+        path: &str,
+        placement: Transform,
+    ) -> EntityView<'a> {
+        if let Some(chunk) = self.chunk(world, key) {
+            return chunk;
+        }
+
+        let chunk = world
+            .entity()
+            .set(AssetSource {
+                path: path.to_string(),
+            })
+            .add(LoadScene)
+            .set(placement)
+            .set(GlobalTransform(placement.compute_matrix()))
+            .set(SceneChunk {
+                key: key.to_string(),
+            });
+        self.chunks.insert(key.to_string(), chunk.id());
+        chunk
+    }
+
+    /// Deletes the chunk `key` with everything spawned from it, see `despawn_scene`.
+    /// Once it is gone, the meshes, materials and textures it loaded are released
+    /// unless something outside the chunk still uses them. Returns false when no such
+    /// chunk was loaded.
+    pub fn unload(&mut self, world: &World, key: &str) -> bool {
+        let Some(chunk) = self.chunks.remove(key) else {
+            return false;
+        };
+        let chunk = world.entity_from_id(chunk);
+        if !chunk.is_alive() {
+            return false;
+        }
+
+        // Gathered now: the Source pairs go away with the chunk
+        let assets = world.get::<&AssetLookup>(|lookup| {
+            lookup
+                .map
+                .values()
+                .copied()
+                .filter(|&asset| {
+                    let asset = world.entity_from_id(asset);
+                    asset.is_alive() && asset.has((Source::id(), chunk))
+                })
+                .collect()
+        });
+        self.releases.push(PendingRelease {
+            chunk: chunk.id(),
+            assets,
+        });
+
+        despawn_scene(chunk);
+        true
+    }
+
+    /// Unloads every chunk whose scene entity has `ChunkGroup(group)`, returns how many.
+    pub fn unload_group(&mut self, world: &World, group: &str) -> usize {
+        let keys: Vec<String> = self
+            .chunks
+            .iter()
+            .filter(|(_, chunk)| {
+                let chunk = world.entity_from_id(**chunk);
+                chunk.is_alive()
+                    && chunk
+                        .try_get::<&ChunkGroup>(|chunk_group| chunk_group.0 == group)
+                        .unwrap_or(false)
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        keys.iter().filter(|key| self.unload(world, key)).count()
+    }
+
+    /// The scene entity of the chunk `key`, None when it isn't loaded.
+    pub fn chunk<'a>(&self, world: &'a World, key: &str) -> Option<EntityView<'a>> {
+        let chunk = world.entity_from_id(*self.chunks.get(key)?);
+        chunk.is_alive().then_some(chunk)
+    }
+
+    pub fn is_loaded(&self, key: &str) -> bool {
+        self.chunks.contains_key(key)
+    }
+
+    /// Keys and scene entities of the loaded chunks, in key order.
+    pub fn chunks(&self) -> impl Iterator<Item = (&str, Entity)> {
+        self.chunks
+            .iter()
+            .map(|(key, chunk)| (key.as_str(), *chunk))
+    }
+
+    /// Unloaded chunks whose assets weren't released yet, e.g. because a large
+    /// chunk is still being despawned over several frames.
+    pub fn pending_releases(&self) -> usize {
+        self.releases.len()
+    }
+
+    // Destructs the assets of chunks that are gone unless an entity still draws them
+    // (MeshDefinition, MeshLod, MaterialDefinition) or a material kept around uses them
+    fn release(&mut self, world: &World) {
+        let (gone, waiting): (Vec<_>, Vec<_>) = self
+            .releases
+            .drain(..)
+            .partition(|release| !world.entity_from_id(release.chunk).is_alive());
+        self.releases = waiting;
+        let candidates: HashSet<Entity> = gone
+            .into_iter()
+            .flat_map(|release| release.assets)
+            .collect();
+        if candidates.is_empty() {
+            return;
+        }
+
+        world.get::<&mut AssetLookup>(|lookup| {
+            let mut used = HashSet::new();
+            let mut mark = |id| {
+                if let Some(&asset) = lookup.map.get(&id) {
+                    used.insert(asset);
+                }
+            };
+            world
+                .query::<&MeshDefinition>()
+                .build()
+                .each(|mesh| mark(mesh.0.id));
+            world
+                .query::<&MaterialDefinition>()
+                .build()
+                .each(|material| mark(material.0.id));
+            world.query::<&MeshLod>().build().each(|lod| {
+                for level in &lod.levels {
+                    mark(level.mesh.id);
+                }
+            });
+
+            // Textures are used through materials, of this chunk or any other
+            let kept_materials: Vec<Entity> = lookup
+                .map
+                .values()
+                .copied()
+                .filter(|asset| !candidates.contains(asset) || used.contains(asset))
+                .collect();
+            for material in kept_materials {
+                let material = world.entity_from_id(material);
+                if !material.is_alive() {
+                    continue;
+                }
+                material.try_get::<&MaterialData>(|data| {
+                    let textures = [
+                        &data.diffuse_texture,
+                        &data.normal_texture,
+                        &data.metallic_roughness_texture,
+                        &data.occlusion_texture,
+                    ];
+                    for texture in textures.into_iter().flatten() {
+                        if let Some(&asset) = lookup.map.get(&texture.id) {
+                            used.insert(asset);
+                        }
+                    }
+                });
+            }
+
+            let released: HashSet<Entity> = candidates.difference(&used).copied().collect();
+            lookup.map.retain(|_, asset| !released.contains(asset));
+            for asset in &released {
+                let asset = world.entity_from_id(*asset);
+                if asset.is_alive() {
+                    asset.destruct();
+                }
+            }
+            log::debug!(
+                "Released {} assets of unloaded chunks, {} still in use",
+                released.len(),
+                candidates.len() - released.len()
+            );
+        });
+    }
+}
+
+pub(crate) fn register_composer(world: &World) {
+    world.component::<SceneMembership>();
+    world.component::<SceneChunk>();
+    world.component::<ChunkGroup>();
+    world.component::<StreamingVolume>();
+
+    let cameras = world.query::<&GlobalTransform>().with(Camera::id()).build();
+    let volumes = world
+        .query::<(&StreamingVolume, &GlobalTransform, Option<&ChunkGroup>)>()
+        .build();
+
+    // PreUpdate, so a chunk loaded this frame is picked up by "load_assets" right away.
+    // Camera positions are last frame's.
+    world
+        .system_named::<&mut WorldComposer>("Stream Chunks")
+        .kind(flecs::pipeline::PreUpdate)
+        .each_entity(move |entity, composer| {
+            let world = entity.world();
+            let mut origins: Vec<Vec3> = Vec::new();
+            cameras.each(|global| origins.push(global.0.w_axis.truncate()));
+            // Nothing to measure from (no camera yet): leave every chunk as it is
+            if origins.is_empty() {
+                return;
+            }
+
+            volumes.each(|(volume, global, group)| {
+                let position = global.0.w_axis.truncate();
+                let nearest = origins
+                    .iter()
+                    .map(|origin| origin.distance_squared(position))
+                    .fold(f32::INFINITY, f32::min)
+                    .sqrt();
+
+                let loaded = composer.is_loaded(&volume.key);
+                if !loaded && nearest <= volume.load_distance {
+                    let (scale, rotation, translation) = global.to_scale_rotation_translation();
+                    let placement = Transform {
+                        translation,
+                        rotation,
+                        scale,
+                    };
+                    let chunk = composer.load(&world, &volume.key, &volume.path, placement);
+                    if let Some(group) = group {
+                        chunk.set(group.clone());
+                    }
+                } else if loaded && nearest > volume.unload_distance {
+                    composer.unload(&world, &volume.key);
+                }
+            });
+        });
+
+    // After the frame's despawns, so a chunk deleted at once is released the same frame
+    world
+        .system_named::<&mut WorldComposer>("Release Chunk Assets")
+        .kind(flecs::pipeline::OnStore)
+        .each_entity(|entity, composer| {
+            if !composer.releases.is_empty() {
+                composer.release(&entity.world());
+            }
+        });
+}
@@ -0,0 +1,64 @@
+use catalyst_assets::scene::SceneNode;
+use flecs_ecs::prelude::*;
+
+/// What a node hook wants done with the node after it ran.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NodeHookResult {
+    /// Attach the node's mesh and material as usual.
+    #[default]
+    Continue,
+    /// Don't attach the mesh and material, e.g. because the hook replaced them
+    /// or the node is only a marker. Transform, camera and physics are still set.
+    SkipMeshAndMaterial,
+}
+
+/// Called for every node as it is spawned, before its mesh and material are attached.
+/// Gets the node (name, extras, ...) and its freshly spawned entity, already parented
+/// and with its Transform set.
+pub type NodeHook = fn(&SceneNode, EntityView) -> NodeHookResult;
+
+/// Called once per spawned scene with the scene entity, after every node exists.
+/// Runs inside the spawn system, so what the node hooks set is not visible to
+/// queries until the end of the frame's pipeline stage. A large scene spawned through
+/// the SpawnQueue runs them in the frame its last nodes appear.
+pub type SceneHook = fn(EntityView);
+
+/// Gameplay hooks run while scenes are instantiated, in registration order.
+/// Register them with ScenePlugin::register_node_hook / register_scene_hook.
+#[derive(Component, Clone, Default)]
+pub struct SceneHooks {
+    node_hooks: Vec<NodeHook>,
+    scene_hooks: Vec<SceneHook>,
+}
+
+impl SceneHooks {
+    pub fn add_node_hook(&mut self, hook: NodeHook) -> &mut Self {
+        self.node_hooks.push(hook);
+        self
+    }
+
+    pub fn add_scene_hook(&mut self, hook: SceneHook) -> &mut Self {
+        self.scene_hooks.push(hook);
+        self
+    }
+
+    /// Runs every node hook. All of them run even if an earlier one asked to skip.
+    pub(crate) fn run_node_hooks(&self, node: &SceneNode, entity: EntityView) -> NodeHookResult {
+        self.node_hooks
+            .iter()
+            .map(|hook| hook(node, entity))
+            .fold(NodeHookResult::Continue, |result, hook_result| {
+                if hook_result == NodeHookResult::SkipMeshAndMaterial {
+                    hook_result
+                } else {
+                    result
+                }
+            })
+    }
+
+    pub(crate) fn run_scene_hooks(&self, root: EntityView) {
+        for hook in &self.scene_hooks {
+            hook(root);
+        }
+    }
+}
@@ -1,26 +1,83 @@
+use std::collections::HashMap;
+
 use catalyst_assets::{
-    MaterialDefinition, MeshDefinition,
-    physics::PhysicsShape,
-    scene::{SceneData, SceneNode},
+    AssetPlugin, AssetSource, MaterialDefinition, MeshDefinition,
+    lod::MeshLod,
+    physics::{LayerExtra, PhysicsShape},
+    scene::{SceneData, SceneLoadState, SceneNode, scene_load_state},
 };
 use catalyst_core::{
-    App, Plugin,
-    physics::{ColliderDefinition, ColliderShape, RigidBodyDefinition},
+    App, Plugin, PluginId,
+    bulk::{BulkBudget, BulkId, BulkJob, BulkProgress, DespawnGroups, DespawnQueue, SpawnQueue},
+    camera::Camera,
+    physics::{ColliderDefinition, ColliderShape, CollisionLayers, RigidBodyDefinition},
+    rooms::{Portal, Room},
+    stable_id::{StableId, StableIdRegistry},
     transform::{GlobalTransform, Transform},
 };
 use flecs_ecs::prelude::*;
+use glam::Mat4;
+
+pub mod composer;
+pub mod hooks;
+pub mod reload;
+
+pub use composer::{ChunkGroup, SceneChunk, SceneMembership, StreamingVolume, WorldComposer};
+pub use hooks::{NodeHook, NodeHookResult, SceneHook, SceneHooks};
 
 pub struct ScenePlugin;
 
+impl ScenePlugin {
+    /// Adds a hook called for every scene node as it is spawned, after the ones
+    /// registered before it. See NodeHook.
+    pub fn register_node_hook(world: &World, hook: NodeHook) {
+        world.get::<&mut SceneHooks>(|hooks| {
+            hooks.add_node_hook(hook);
+        });
+    }
+
+    /// Adds a hook called once per scene after its whole hierarchy is spawned.
+    pub fn register_scene_hook(world: &World, hook: SceneHook) {
+        world.get::<&mut SceneHooks>(|hooks| {
+            hooks.add_scene_hook(hook);
+        });
+    }
+}
+
 impl Plugin for ScenePlugin {
     fn build(&self, app: &mut App) {
+        app.world.component::<SceneRoot>();
+        app.world.component::<SceneSpawning>();
+        app.world.component::<SceneNodes>();
+        app.register_singleton_default::<SceneHooks>();
+        app.register_singleton_default::<WorldComposer>();
+        reload::register_scene_reloads(&app.world);
         register_spawn_scenes(&app.world);
+        composer::register_composer(&app.world);
+    }
+
+    fn dependencies(&self) -> Vec<PluginId> {
+        vec![PluginId::of::<AssetPlugin>()]
     }
 }
 
 #[derive(Component)]
 pub struct SceneLoaded;
 
+/// On a spawned scene: the entity of each of its nodes, by index into
+/// `SceneData::nodes`. Set once every node exists.
+#[derive(Component, Clone, Debug, Default)]
+pub struct SceneNodes(pub Vec<Option<Entity>>);
+
+/// Optional spawn options on a scene entity (the one with AssetSource + LoadScene).
+#[derive(Component, Clone, Copy, Debug, Default)]
+#[flecs(meta)]
+pub struct SceneRoot {
+    /// Hold the nodes back until the scene is Ready, i.e. until a prewarmed
+    /// scene (PrewarmScene) has all of its GPU resources.
+    pub spawn_when_ready: bool,
+}
+
 pub fn register_spawn_scenes(world: &World) {
     world
         .system_named::<&SceneData>("Spawn Scenes")
@@ -29,80 +86,485 @@ pub fn register_spawn_scenes(world: &World) {
         .each_entity(|entity, scene_data| {
             let world = entity.world();
 
-            println!("Asset arrived! Spawning nodes now...");
+            let wait_for_gpu = entity
+                .try_get::<&SceneRoot>(|root| root.spawn_when_ready)
+                .unwrap_or(false);
+            if wait_for_gpu && scene_load_state(entity) != SceneLoadState::Ready {
+                return;
+            }
+
+            log::debug!("Scene {} arrived, spawning nodes", entity.name());
             entity.add(SceneLoaded);
 
-            let mut node_entities = Vec::with_capacity(scene_data.nodes.len());
-
-            for node in &scene_data.nodes {
-                let entity_cmd = world
-                    .entity()
-                    .child_of(entity)
-                    .set(node.transform)
-                    .set(GlobalTransform::default());
-
-                // 2. Attach Generic Definitions
-                if let Some(mesh_idx) = node.mesh_index {
-                    if let Some(mesh) = scene_data.meshes.get(mesh_idx) {
-                        entity_cmd.set(MeshDefinition(mesh.clone()));
-                    }
-
-                    if let Some(mat_idx) = node.material_index {
-                        if let Some(mat) = scene_data.materials.get(mat_idx) {
-                            entity_cmd.set(MaterialDefinition(mat.clone()));
-                        }
-                    }
-                }
+            let mut spawn = SceneSpawn::new(entity, scene_data);
 
-                if let Some(camera_idx) = node.camera_index {
-                    if let Some(camera) = scene_data.camera.get(camera_idx) {
-                        entity_cmd.set(camera.clone());
-                    }
-                }
+            // Large scenes are spread over several frames by the SpawnQueue
+            let threshold = world.get::<&BulkBudget>(|budget| budget.threshold);
+            if scene_data.nodes.len() > threshold {
+                let job = SceneSpawnJob {
+                    spawn,
+                    scene: scene_data.clone(),
+                };
+                let id = world.get::<&mut SpawnQueue>(|queue| queue.push(job));
+                entity.set(SceneSpawning(id));
+                return;
+            }
 
-                if let Some(ref p) = node.physics {
-                    if let Some(body_type) = p.physics_body.clone() {
-                        entity_cmd.set(RigidBodyDefinition {
-                            body_type: body_type.into(),
-                            mass: p.physics_mass,
-                            gravity_scale: p.physics_gravity_scale.unwrap_or(1.0),
-                            linear_damping: p.physics_linear_damping.unwrap_or(0.0),
-                            angular_damping: p.physics_angular_damping.unwrap_or(0.0),
-                        });
-                    }
-
-                    if let Some(shape) = p.physics_shape.clone() {
-                        let collider = ColliderDefinition {
-                            shape: build_collider_shape(node, shape, scene_data),
-                            is_trigger: p.physics_is_trigger.unwrap_or(false),
-                            offset: Transform::default(),
-                            layer: p.physics_layer.unwrap_or(0),
-                            mask: p.physics_mask.unwrap_or(u32::MAX),
-                        };
-                        entity_cmd.set(collider);
-                    }
-
-                    if let Some(material_name) = &p.physics_material {
-                        if let Some(mat) = scene_data.physics_materials.get(material_name) {
-                            entity_cmd.set(mat.clone());
-                        }
-                    }
-                }
+            while !spawn.is_done() {
+                spawn.step(&world, scene_data);
+            }
+            spawn.finish(&world, scene_data);
+        });
+}
+
+/// On a scene whose nodes are being spawned through the SpawnQueue, removed once they
+/// all exist and the scene hooks ran. See `spawn_progress`.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct SceneSpawning(pub BulkId);
+
+/// How far the spawn of a large scene is; None when it isn't going through the
+/// SpawnQueue (anymore).
+pub fn spawn_progress(scene: EntityView) -> Option<BulkProgress> {
+    let id = scene.try_get::<&SceneSpawning>(|spawning| spawning.0)?;
+    scene.world().get::<&SpawnQueue>(|queue| queue.progress(id))
+}
+
+/// Deletes a spawned scene with its nodes: at once when it has at most
+/// `BulkBudget::threshold` entities, otherwise through the DespawnQueue, each node with
+/// a mesh, camera or physics removed with everything under it in one frame. Returns the
+/// despawn job when it was queued.
+pub fn despawn_scene(scene: EntityView) -> Option<BulkId> {
+    let world = scene.world();
+    if let Some(spawning) = scene.try_get::<&SceneSpawning>(|spawning| spawning.0) {
+        world.get::<&mut SpawnQueue>(|queue| queue.cancel(spawning));
+    }
+
+    let job = DespawnGroups::hierarchy(scene, |entity| {
+        entity.id() != scene.id() && has_content(entity)
+    });
+    let threshold = world.get::<&BulkBudget>(|budget| budget.threshold);
+    if job.total() <= threshold {
+        scene.destruct();
+        return None;
+    }
+    Some(world.get::<&mut DespawnQueue>(|queue| queue.push(job)))
+}
+
+// The entity's world matrix from its Transform and those of its parents, up to the
+// first one without a Transform, like propagation does. Its GlobalTransform would be a
+// frame behind when it was spawned or moved this frame.
+fn world_matrix(entity: EntityView) -> Mat4 {
+    let mut matrix = Mat4::IDENTITY;
+    let mut current = Some(entity);
+    while let Some(view) = current {
+        let Some(local) = view.try_get::<&Transform>(|transform| transform.compute_matrix()) else {
+            break;
+        };
+        matrix = local * matrix;
+        current = view.parent();
+    }
+    matrix
+}
+
+// What a spawn group is built around, see SceneSpawn::group_end
+fn has_content(entity: EntityView) -> bool {
+    entity.has(MeshDefinition::id())
+        || entity.has(Camera::id())
+        || entity.has(RigidBodyDefinition::id())
+        || entity.has(ColliderDefinition::id())
+}
+
+fn node_has_content(node: &SceneNode) -> bool {
+    node.mesh_index.is_some() || node.camera_index.is_some() || node.physics.is_some()
+}
+
+/// The nodes of one scene, spawned a group at a time: all at once for small scenes,
+/// over several frames through the SpawnQueue for large ones.
+struct SceneSpawn {
+    root: Entity,
+    // World matrix of the scene root; nodes get their final GlobalTransform at spawn
+    // so physics prepare (which runs before propagation next frame) sees the real pose.
+    // Computed from Transforms, see world_matrix.
+    root_global: GlobalTransform,
+    // Resolves named layers in the physics extras; empty without the PhysicsPlugin
+    layers: CollisionLayers,
+    // Copied out, so hooks are free to access any singleton
+    hooks: SceneHooks,
+    // Node StableIds follow the scene's path and how often it was spawned before
+    source: String,
+    instance: u32,
+    // Parents are always spawned before their children, so every child is
+    // attached straight to its real parent in a single pass.
+    order: Vec<(usize, Option<usize>)>,
+    depths: Vec<usize>,
+    next: usize,
+    node_entities: Vec<Option<(Entity, GlobalTransform)>>,
+}
+
+impl SceneSpawn {
+    fn new(entity: EntityView, scene_data: &SceneData) -> Self {
+        let world = entity.world();
 
-                node_entities.push(entity_cmd);
+        let root_global = GlobalTransform(world_matrix(entity));
+        let layers = world
+            .try_get::<&CollisionLayers>(|layers| layers.clone())
+            .unwrap_or_default();
+        let hooks = world
+            .try_get::<&SceneHooks>(|hooks| hooks.clone())
+            .unwrap_or_default();
+        let source = entity
+            .try_get::<&AssetSource>(|source| source.path.clone())
+            .unwrap_or_else(|| entity.name());
+        let instance =
+            world.get::<&mut StableIdRegistry>(|registry| registry.next_instance(&source));
+
+        let order = spawn_order(&scene_data.nodes);
+        let mut node_depths = vec![0; scene_data.nodes.len()];
+        let depths = order
+            .iter()
+            .map(|&(node_index, parent_index)| {
+                let depth = parent_index.map_or(0, |parent| node_depths[parent] + 1);
+                node_depths[node_index] = depth;
+                depth
+            })
+            .collect();
+
+        Self {
+            root: entity.id(),
+            root_global,
+            layers,
+            hooks,
+            source,
+            instance,
+            order,
+            depths,
+            next: 0,
+            node_entities: vec![None; scene_data.nodes.len()],
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.next >= self.order.len()
+    }
+
+    // A node with a mesh, camera or physics comes with everything under it in one group,
+    // so a prop and its parts or colliders appear in the same frame. An empty node is a
+    // group of its own, so a big hierarchy under one empty root still spreads out.
+    fn group_end(&self, scene_data: &SceneData) -> usize {
+        let start = self.next;
+        let (node_index, _) = self.order[start];
+        if !node_has_content(&scene_data.nodes[node_index]) {
+            return start + 1;
+        }
+        // Descendants directly follow their ancestor in the spawn order
+        (start + 1..self.order.len())
+            .find(|&position| self.depths[position] <= self.depths[start])
+            .unwrap_or(self.order.len())
+    }
+
+    /// Spawns the next group and returns how many nodes it had.
+    fn step(&mut self, world: &World, scene_data: &SceneData) -> usize {
+        // The scene was deleted while it was spawning
+        let root = world.entity_from_id(self.root);
+        if !root.is_alive() {
+            self.next = self.order.len();
+            return 0;
+        }
+
+        let end = self.group_end(scene_data);
+        for position in self.next..end {
+            let (node_index, parent_index) = self.order[position];
+            let (parent, parent_global) = parent_index
+                .and_then(|parent_index| self.node_entities[parent_index])
+                .unwrap_or((self.root, self.root_global));
+            let spawned = self.spawn_node(
+                world,
+                scene_data,
+                node_index,
+                world.entity_from_id(parent),
+                parent_global,
+            );
+            self.node_entities[node_index] = Some(spawned);
+        }
+
+        let spawned = end - self.next;
+        self.next = end;
+        spawned
+    }
+
+    fn spawn_node(
+        &self,
+        world: &World,
+        scene_data: &SceneData,
+        node_index: usize,
+        parent_entity: EntityView,
+        parent_global: GlobalTransform,
+    ) -> (Entity, GlobalTransform) {
+        let node = &scene_data.nodes[node_index];
+        let global = GlobalTransform(parent_global.0 * node.transform.compute_matrix());
+
+        let entity_cmd = world
+            .entity()
+            .child_of(parent_entity)
+            .set(node.transform)
+            .set(global)
+            .set(StableId::for_scene_node(
+                &self.source,
+                self.instance,
+                node_index,
+            ))
+            .set(SceneMembership(self.root));
+
+        let result = self.hooks.run_node_hooks(node, entity_cmd);
+
+        // 2. Attach Generic Definitions
+        // Room and portal nodes are blockout volumes, not drawn
+        if let Some(mesh_idx) = node.mesh_index.filter(|_| {
+            result != NodeHookResult::SkipMeshAndMaterial
+                && !is_room(node)
+                && portal_rooms(node).is_none()
+        }) {
+            if let Some(mesh) = scene_data.meshes.get(mesh_idx) {
+                entity_cmd.set(MeshDefinition(mesh.clone()));
             }
 
-            for (i, node) in scene_data.nodes.iter().enumerate() {
-                // The entity we just spawned for this node
-                let parent_entity = node_entities[i];
+            if let Some(levels) = scene_data
+                .mesh_lods
+                .get(mesh_idx)
+                .filter(|levels| !levels.is_empty())
+            {
+                entity_cmd.set(MeshLod {
+                    levels: levels.clone(),
+                });
+            }
 
-                // Loop through the children indices stored in the GLTF data
-                for &child_index in &node.children {
-                    let child_entity = node_entities[child_index];
-                    child_entity.child_of(parent_entity);
+            if let Some(mat_idx) = node.material_index {
+                if let Some(mat) = scene_data.materials.get(mat_idx) {
+                    entity_cmd.set(MaterialDefinition(mat.clone()));
                 }
             }
+        }
+
+        if let Some(camera_idx) = node.camera_index {
+            if let Some(camera) = scene_data.camera.get(camera_idx) {
+                entity_cmd.set(camera.clone());
+            }
+        }
+
+        set_node_physics(entity_cmd, node, scene_data, &self.layers);
+
+        (entity_cmd.id(), global)
+    }
+
+    fn finish(&self, world: &World, scene_data: &SceneData) {
+        let root = world.entity_from_id(self.root);
+        if !root.is_alive() {
+            return;
+        }
+
+        attach_rooms(world, &scene_data.nodes, &self.node_entities);
+        let nodes = self.node_entities.iter().map(|node| node.map(|(entity, _)| entity));
+        root.set(SceneNodes(nodes.collect()));
+
+        self.hooks.run_scene_hooks(root);
+    }
+}
+
+struct SceneSpawnJob {
+    spawn: SceneSpawn,
+    scene: SceneData,
+}
+
+impl BulkJob for SceneSpawnJob {
+    fn total(&self) -> usize {
+        self.spawn.order.len()
+    }
+
+    fn step(&mut self, world: &World) -> usize {
+        self.spawn.step(world, &self.scene)
+    }
+
+    fn is_done(&self) -> bool {
+        self.spawn.is_done()
+    }
+
+    fn finish(&mut self, world: &World) {
+        self.spawn.finish(world, &self.scene);
+        let root = world.entity_from_id(self.spawn.root);
+        if root.is_alive() {
+            root.remove(SceneSpawning::id());
+        }
+    }
+}
+
+/// Rooms and portals for portal culling, from the node extras: `"room": true` makes a
+/// Room (a unit box, scaled by the node), `"portal": "Hall, Kitchen"` a Portal (a unit
+/// quad in the node's XY plane) between the room nodes of this scene with those names.
+/// Runs after every node is spawned, since a portal may come before its rooms.
+fn attach_rooms(
+    world: &World,
+    nodes: &[SceneNode],
+    entities: &[Option<(Entity, GlobalTransform)>],
+) {
+    let rooms: HashMap<&str, Entity> = nodes
+        .iter()
+        .zip(entities)
+        .filter(|(node, _)| is_room(node))
+        .filter_map(|(node, entity)| Some((node.name.as_str(), entity.as_ref()?.0)))
+        .collect();
+
+    for (node, entity) in nodes.iter().zip(entities) {
+        let Some((entity, _)) = entity else {
+            continue;
+        };
+        let entity = world.entity_from_id(*entity);
+        if is_room(node) {
+            entity.set(Room::default());
+        }
+
+        let Some(names) = portal_rooms(node) else {
+            continue;
+        };
+        match names.map(|name| rooms.get(name)) {
+            [Some(a), Some(b)] => {
+                entity.set(Portal::new(*a, *b));
+            }
+            _ => log::warn!(
+                "Portal '{}' doesn't name two rooms of its scene: '{}', '{}'",
+                node.name,
+                names[0],
+                names[1]
+            ),
+        }
+    }
+}
+
+// Blender exports boolean custom properties as 1 / 0
+fn is_room(node: &SceneNode) -> bool {
+    node.extras
+        .as_ref()
+        .and_then(|extras| extras.get("room"))
+        .is_some_and(|room| room.as_bool() == Some(true) || room.as_i64() == Some(1))
+}
+
+fn portal_rooms(node: &SceneNode) -> Option<[&str; 2]> {
+    let rooms = node.extras.as_ref()?.get("portal")?.as_str()?;
+    let (a, b) = rooms.split_once(',')?;
+    Some([a.trim(), b.trim()])
+}
+
+/// Orders nodes so parents come before their children, paired with each node's parent.
+/// Roots are the nodes nobody lists as a child. A node listed under several parents
+/// (invalid glTF) keeps the first one; cycles are broken by visiting each node once.
+pub fn spawn_order(nodes: &[SceneNode]) -> Vec<(usize, Option<usize>)> {
+    let mut is_child = vec![false; nodes.len()];
+    for node in nodes {
+        for &child in &node.children {
+            if let Some(flag) = is_child.get_mut(child) {
+                *flag = true;
+            }
+        }
+    }
+
+    let mut order = Vec::with_capacity(nodes.len());
+    let mut visited = vec![false; nodes.len()];
+    let mut stack: Vec<(usize, Option<usize>)> = (0..nodes.len())
+        .rev()
+        .filter(|&i| !is_child[i])
+        .map(|i| (i, None))
+        .collect();
+
+    while let Some((index, parent)) = stack.pop() {
+        if std::mem::replace(&mut visited[index], true) {
+            continue;
+        }
+
+        order.push((index, parent));
+        for &child in nodes[index].children.iter().rev() {
+            if child < nodes.len() && !visited[child] {
+                stack.push((child, Some(index)));
+            }
+        }
+    }
+
+    // Nodes only reachable through a cycle have no root; hang them off the scene root
+    for index in 0..nodes.len() {
+        if !visited[index] {
+            visited[index] = true;
+            order.push((index, None));
+        }
+    }
+
+    order
+}
+
+/// The rigid body, collider and physics material of a node's physics extras.
+pub(crate) fn set_node_physics(
+    entity: EntityView,
+    node: &SceneNode,
+    scene_data: &SceneData,
+    layers: &CollisionLayers,
+) {
+    let Some(p) = &node.physics else {
+        return;
+    };
+    if let Some(body_type) = p.physics_body.clone() {
+        entity.set(RigidBodyDefinition {
+            body_type: body_type.into(),
+            mass: p.physics_mass,
+            gravity_scale: p.physics_gravity_scale.unwrap_or(1.0),
+            linear_damping: p.physics_linear_damping.unwrap_or(0.0),
+            angular_damping: p.physics_angular_damping.unwrap_or(0.0),
         });
+    }
+
+    if let Some(shape) = p.physics_shape.clone() {
+        let (layer, mask) = resolve_collision_filter(node, layers);
+        let collider = ColliderDefinition {
+            shape: build_collider_shape(node, shape, scene_data),
+            is_trigger: p.physics_is_trigger.unwrap_or(false),
+            offset: Transform::default(),
+            layer,
+            mask,
+        };
+        entity.set(collider);
+    }
+
+    if let Some(material_name) = &p.physics_material {
+        if let Some(mat) = scene_data.physics_materials.get(material_name) {
+            entity.set(mat.clone());
+        }
+    }
+}
+
+/// Layer and mask from the node's physics extras. Names go through the CollisionLayers
+/// registry; a named layer without a mask gets the mask from the interaction matrix.
+/// Unknown names are reported and fall back to the numeric defaults (layer 0, all masks).
+fn resolve_collision_filter(node: &SceneNode, layers: &CollisionLayers) -> (u32, u32) {
+    let Some(physics) = &node.physics else {
+        return (0, u32::MAX);
+    };
+
+    let resolve = |extra: &Option<LayerExtra>, what: &str| {
+        extra.as_ref().and_then(|extra| match extra.resolve(layers) {
+            Ok(bits) => Some(bits),
+            Err(error) => {
+                log::warn!("Node '{}' has an invalid physics_{}: {}", node.name, what, error);
+                None
+            }
+        })
+    };
+
+    let layer = resolve(&physics.physics_layer, "layer");
+    let mask = resolve(&physics.physics_mask, "mask");
+
+    match (layer, mask) {
+        (layer, Some(mask)) => (layer.unwrap_or(0), mask),
+        (Some(layer), None) if !matches!(physics.physics_layer, Some(LayerExtra::Bits(_))) => {
+            (layer, layers.interaction_mask(layer))
+        }
+        (layer, None) => (layer.unwrap_or(0), u32::MAX),
+    }
 }
 
 fn build_collider_shape(
@@ -149,3 +611,142 @@ fn build_collider_shape(
         PhysicsShape::Unknown => panic!("Unknown collider shape"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use glam::{Quat, Vec3};
+
+    use super::*;
+
+    fn node(name: &str, transform: Transform, children: Vec<usize>) -> SceneNode {
+        SceneNode {
+            name: name.to_string(),
+            transform,
+            mesh_index: None,
+            material_index: None,
+            camera_index: None,
+            children,
+            physics: None,
+            extras: None,
+        }
+    }
+
+    fn nodes(children: &[&[usize]]) -> Vec<SceneNode> {
+        children
+            .iter()
+            .enumerate()
+            .map(|(index, children)| {
+                node(&index.to_string(), Transform::default(), children.to_vec())
+            })
+            .collect()
+    }
+
+    fn scene(nodes: Vec<SceneNode>) -> SceneData {
+        SceneData {
+            meshes: Vec::new(),
+            materials: Vec::new(),
+            textures: Vec::new(),
+            physics_materials: HashMap::new(),
+            mesh_lods: Vec::new(),
+            nodes,
+            camera: Vec::new(),
+            filter: None,
+        }
+    }
+
+    fn position(order: &[(usize, Option<usize>)], node: usize) -> usize {
+        order.iter().position(|&(index, _)| index == node).unwrap()
+    }
+
+    #[test]
+    fn parents_come_before_their_children() {
+        // Listed children first: 2 is the root, 1 its child, 0 and 3 under 1
+        let order = spawn_order(&nodes(&[&[], &[0, 3], &[1], &[]]));
+        assert_eq!(order.len(), 4);
+        assert_eq!(order[0], (2, None));
+        for &(index, parent) in &order {
+            if let Some(parent) = parent {
+                assert!(position(&order, parent) < position(&order, index));
+            }
+        }
+        assert!(order.contains(&(0, Some(1))));
+        assert!(order.contains(&(3, Some(1))));
+    }
+
+    #[test]
+    fn a_node_under_two_parents_keeps_the_first() {
+        let order = spawn_order(&nodes(&[&[2], &[2], &[]]));
+        assert_eq!(order, vec![(0, None), (2, Some(0)), (1, None)]);
+    }
+
+    #[test]
+    fn cycles_visit_each_node_once() {
+        // 1 and 2 are each other's child under the root 0
+        let order = spawn_order(&nodes(&[&[1], &[2], &[1]]));
+        assert_eq!(order, vec![(0, None), (1, Some(0)), (2, Some(1))]);
+
+        // 0 and 1 only reachable through their cycle hang off the scene root
+        let order = spawn_order(&nodes(&[&[1], &[0], &[]]));
+        assert_eq!(order.len(), 3);
+        for index in 0..3 {
+            assert_eq!(order.iter().filter(|(node, _)| *node == index).count(), 1);
+        }
+        assert!(order.iter().all(|(_, parent)| parent.is_none()));
+    }
+
+    fn translation(world: &World, entity: Entity) -> Vec3 {
+        world
+            .entity_from_id(entity)
+            .get::<&GlobalTransform>(|global| global.0.w_axis.truncate())
+    }
+
+    // A glTF parent rotated 90° around Y with a child offset along its X axis, in a
+    // scene placed under a level entity; neither has been propagated yet
+    #[test]
+    fn child_of_a_rotated_parent_spawns_at_its_world_position() {
+        let app = App::new();
+        let world = &app.world;
+        let level = world
+            .entity()
+            .set(Transform::from_xyz(10.0, 0.0, 0.0))
+            .set(GlobalTransform::default());
+        let root = world
+            .entity()
+            .child_of(level)
+            .set(Transform::from_xyz(0.0, 0.0, 5.0))
+            .set(GlobalTransform::default());
+
+        let parent = Transform {
+            translation: Vec3::new(0.0, 1.0, 0.0),
+            rotation: Quat::from_rotation_y(FRAC_PI_2),
+            ..Default::default()
+        };
+        let data = scene(vec![
+            node("Parent", parent, vec![1]),
+            node("Child", Transform::from_xyz(2.0, 0.0, 0.0), Vec::new()),
+        ]);
+
+        let mut spawn = SceneSpawn::new(root, &data);
+        while !spawn.is_done() {
+            spawn.step(world, &data);
+        }
+        spawn.finish(world, &data);
+
+        let nodes = root.get::<&SceneNodes>(|nodes| nodes.0.clone());
+        let child = nodes[1].unwrap();
+        assert_eq!(
+            world.entity_from_id(child).parent().unwrap().id(),
+            nodes[0].unwrap()
+        );
+
+        // The child's +X is the parent's -Z
+        let expected = Vec3::new(10.0, 1.0, 3.0);
+        assert!(translation(world, child).abs_diff_eq(expected, 1e-5));
+
+        // Propagation agrees one frame later
+        world.progress();
+        assert!(translation(world, child).abs_diff_eq(expected, 1e-5));
+    }
+}
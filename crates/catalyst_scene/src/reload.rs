@@ -0,0 +1,96 @@
+use catalyst_assets::{
+    reload::SceneDiff,
+    scene::{SceneData, SceneNode},
+};
+use catalyst_core::{
+    bulk::SpawnQueue,
+    physics::{ColliderShapeChanged, CollisionLayers},
+};
+use flecs_ecs::prelude::*;
+
+use crate::{SceneLoaded, SceneMembership, SceneNodes, SceneSpawning, set_node_physics};
+
+/// Brings the nodes of a hot reloaded scene up to date with its new SceneData: the
+/// changed ones get their transform and physics again, in place. A scene that can't be
+/// matched up node by node, or still spawning, is despawned and spawned again.
+/// Registered before "Spawn Scenes", which does the respawn in the same frame.
+pub(crate) fn register_scene_reloads(world: &World) {
+    let members = world.query::<&SceneMembership>().build();
+
+    world
+        .system_named::<(&SceneData, &SceneDiff)>("Apply Scene Reloads")
+        .kind(flecs::pipeline::OnUpdate)
+        .each_entity(move |scene, (scene_data, diff)| {
+            let world = scene.world();
+            scene.remove(SceneDiff::id());
+            // Not spawned yet: it will be, from the new data
+            if !scene.has(SceneLoaded) {
+                return;
+            }
+
+            let nodes = scene.try_get::<&SceneNodes>(|nodes| nodes.0.clone());
+            let Some(nodes) = nodes.filter(|_| !diff.respawn) else {
+                respawn(scene, &members);
+                return;
+            };
+
+            let layers = world
+                .try_get::<&CollisionLayers>(|layers| layers.clone())
+                .unwrap_or_default();
+            for &index in &diff.changed_nodes {
+                let (Some(node), Some(Some(entity))) =
+                    (scene_data.nodes.get(index), nodes.get(index))
+                else {
+                    continue;
+                };
+                let entity = world.entity_from_id(*entity);
+                if entity.is_alive() {
+                    update_node(entity, node, scene_data, &layers);
+                }
+            }
+        });
+}
+
+fn update_node(
+    entity: EntityView,
+    node: &SceneNode,
+    scene_data: &SceneData,
+    layers: &CollisionLayers,
+) {
+    entity.set(node.transform);
+    set_node_physics(entity, node, scene_data, layers);
+    // The collider's size follows the node's scale
+    if node
+        .physics
+        .as_ref()
+        .is_some_and(|p| p.physics_shape.is_some())
+    {
+        entity.add(ColliderShapeChanged);
+    }
+}
+
+// Deletes the spawned nodes, so "Spawn Scenes" spawns the scene again
+fn respawn(scene: EntityView, members: &Query<&SceneMembership>) {
+    let world = scene.world();
+    if let Some(spawning) = scene.try_get::<&SceneSpawning>(|spawning| spawning.0) {
+        world.get::<&mut SpawnQueue>(|queue| queue.cancel(spawning));
+        scene.remove(SceneSpawning::id());
+    }
+
+    let mut spawned = Vec::new();
+    members.each_entity(|entity, membership| {
+        if membership.0 == scene.id() {
+            spawned.push(entity.id());
+        }
+    });
+    // Deleting a node deletes the ones under it too
+    for entity in spawned {
+        let entity = world.entity_from_id(entity);
+        if entity.is_alive() {
+            entity.destruct();
+        }
+    }
+
+    log::debug!("Respawning the nodes of scene {}", scene.name());
+    scene.remove(SceneLoaded).remove(SceneNodes::id());
+}
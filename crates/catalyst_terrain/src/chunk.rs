@@ -0,0 +1,218 @@
+use catalyst_assets::assets::{MeshData, Vertex};
+use catalyst_core::physics::ColliderShape;
+use glam::{Vec2, Vec3};
+
+use crate::{Terrain, heightmap::Heightmap};
+
+/// How a heightmap is cut into chunks of `size` quads a side. Neighbouring chunks
+/// share their border samples.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ChunkLayout {
+    size: u32,
+    columns: u32,
+    rows: u32,
+    width: u32,
+    depth: u32,
+}
+
+/// Samples a chunk covers, both corners included.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ChunkRect {
+    min: [u32; 2],
+    max: [u32; 2],
+}
+
+pub(crate) struct ChunkGeometry {
+    /// Most detailed first.
+    pub lods: Vec<MeshData>,
+    pub collider: ColliderShape,
+}
+
+impl ChunkLayout {
+    pub fn new(heightmap: &Heightmap, size: u32) -> Self {
+        let size = size.max(1);
+        Self {
+            size,
+            columns: (heightmap.width - 1).div_ceil(size),
+            rows: (heightmap.depth - 1).div_ceil(size),
+            width: heightmap.width,
+            depth: heightmap.depth,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        (self.columns * self.rows) as usize
+    }
+
+    pub fn rect(&self, index: usize) -> ChunkRect {
+        let (column, row) = (index as u32 % self.columns, index as u32 / self.columns);
+        let min = [column * self.size, row * self.size];
+        ChunkRect {
+            min,
+            max: [
+                (min[0] + self.size).min(self.width - 1),
+                (min[1] + self.size).min(self.depth - 1),
+            ],
+        }
+    }
+
+    /// Chunks whose meshes change when the samples from `min` to `max` do. Normals
+    /// reach one sample further, so the range grows by one first.
+    pub fn chunks_touching(&self, min: [u32; 2], max: [u32; 2]) -> Vec<usize> {
+        let first = |value: u32| value.saturating_sub(1).saturating_sub(1) / self.size;
+        let last = |value: u32, count: u32| ((value + 1) / self.size).min(count - 1);
+
+        let mut chunks = Vec::new();
+        for row in first(min[1])..=last(max[1], self.rows) {
+            for column in first(min[0])..=last(max[0], self.columns) {
+                chunks.push((row * self.columns + column) as usize);
+            }
+        }
+        chunks
+    }
+
+    /// Position of a sample relative to the terrain entity, which sits at the grid's center.
+    fn position(&self, heightmap: &Heightmap, x: u32, z: u32, spacing: f32) -> Vec3 {
+        Vec3::new(
+            (x as f32 - (self.width - 1) as f32 * 0.5) * spacing,
+            heightmap.get(x as i64, z as i64),
+            (z as f32 - (self.depth - 1) as f32 * 0.5) * spacing,
+        )
+    }
+
+    /// Center of the chunk at height 0, where its entity and collider are placed.
+    pub fn center(&self, rect: ChunkRect, spacing: f32) -> Vec3 {
+        let center =
+            (Vec2::from(rect.min.map(|v| v as f32)) + Vec2::from(rect.max.map(|v| v as f32))) * 0.5
+                - Vec2::new((self.width - 1) as f32, (self.depth - 1) as f32) * 0.5;
+        Vec3::new(center.x * spacing, 0.0, center.y * spacing)
+    }
+}
+
+/// The LOD meshes and collider of one chunk, relative to its center.
+pub(crate) fn build_chunk(
+    heightmap: &Heightmap,
+    layout: &ChunkLayout,
+    rect: ChunkRect,
+    terrain: &Terrain,
+) -> ChunkGeometry {
+    let center = layout.center(rect, terrain.spacing);
+    let span = (rect.max[0] - rect.min[0]).min(rect.max[1] - rect.min[1]);
+
+    // Every level halves the resolution; a level needs at least one quad across
+    let lods = (0..terrain.lod_levels.max(1))
+        .map(|level| 1u32 << level)
+        .take_while(|&step| step == 1 || step <= span)
+        .map(|step| lod_mesh(heightmap, layout, rect, step, center, terrain))
+        .collect();
+
+    let columns = rect.max[0] - rect.min[0] + 1;
+    let rows = rect.max[1] - rect.min[1] + 1;
+    let mut heights = Vec::with_capacity((rows * columns) as usize);
+    for z in rect.min[1]..=rect.max[1] {
+        for x in rect.min[0]..=rect.max[0] {
+            heights.push(heightmap.get(x as i64, z as i64));
+        }
+    }
+
+    ChunkGeometry {
+        lods,
+        collider: ColliderShape::Heightfield {
+            rows,
+            columns,
+            heights,
+            size: Vec2::new(
+                (columns - 1) as f32 * terrain.spacing,
+                (rows - 1) as f32 * terrain.spacing,
+            ),
+        },
+    }
+}
+
+fn lod_mesh(
+    heightmap: &Heightmap,
+    layout: &ChunkLayout,
+    rect: ChunkRect,
+    step: u32,
+    center: Vec3,
+    terrain: &Terrain,
+) -> MeshData {
+    let xs = axis_samples(rect.min[0], rect.max[0], step);
+    let zs = axis_samples(rect.min[1], rect.max[1], step);
+    let row = xs.len() as u32;
+
+    let mut vertices = Vec::with_capacity(xs.len() * zs.len() + 2 * (xs.len() + zs.len()));
+    for &z in &zs {
+        for &x in &xs {
+            let position = layout.position(heightmap, x, z, terrain.spacing);
+            vertices.push(Vertex {
+                position: (position - center).to_array(),
+                normal: heightmap.normal(x, z, terrain.spacing).to_array(),
+                // World-space UVs, so the material tiles seamlessly across chunks
+                uv: [
+                    position.x / terrain.texture_size,
+                    position.z / terrain.texture_size,
+                ],
+                wind_weight: 0.0,
+            });
+        }
+    }
+
+    let mut indices = Vec::with_capacity((xs.len() - 1) * (zs.len() - 1) * 6);
+    for j in 0..zs.len() as u32 - 1 {
+        for i in 0..row - 1 {
+            let a = j * row + i;
+            let b = a + 1;
+            let c = a + row;
+            let d = c + 1;
+            indices.extend_from_slice(&[a, c, b, b, c, d]);
+        }
+    }
+
+    // Skirts: a strip hanging down from every border, hiding the cracks between
+    // neighbours drawn at different levels. Both windings, so it shows from either side.
+    if terrain.skirt_depth > 0.0 {
+        let last_row = (zs.len() as u32 - 1) * row;
+        let borders: [Vec<u32>; 4] = [
+            (0..row).collect(),
+            (0..row).map(|i| last_row + i).collect(),
+            (0..zs.len() as u32).map(|j| j * row).collect(),
+            (0..zs.len() as u32).map(|j| j * row + row - 1).collect(),
+        ];
+
+        for border in borders {
+            let lowered: Vec<u32> = border
+                .iter()
+                .map(|&top| {
+                    let vertex = &vertices[top as usize];
+                    let mut position = vertex.position;
+                    position[1] -= terrain.skirt_depth;
+                    let lowered = Vertex {
+                        position,
+                        normal: vertex.normal,
+                        uv: vertex.uv,
+                        wind_weight: 0.0,
+                    };
+                    vertices.push(lowered);
+                    vertices.len() as u32 - 1
+                })
+                .collect();
+
+            for k in 0..border.len() - 1 {
+                let (p, q) = (border[k], border[k + 1]);
+                let (p_low, q_low) = (lowered[k], lowered[k + 1]);
+                indices.extend_from_slice(&[p, p_low, q, q, p_low, q_low]);
+                indices.extend_from_slice(&[p, q, p_low, q, q_low, p_low]);
+            }
+        }
+    }
+
+    MeshData::new(vertices, indices)
+}
+
+// Every `step`th sample from `min`, always ending on `max`
+fn axis_samples(min: u32, max: u32, step: u32) -> Vec<u32> {
+    let mut samples: Vec<u32> = (min..max).step_by(step as usize).collect();
+    samples.push(max);
+    samples
+}
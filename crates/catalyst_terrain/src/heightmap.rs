@@ -0,0 +1,113 @@
+use glam::{Vec2, Vec3};
+
+/// Terrain heights in world units, one sample per grid point, row-major
+/// (`heights[z * width + x]`).
+#[derive(Clone, Debug)]
+pub struct Heightmap {
+    pub width: u32,
+    pub depth: u32,
+    pub heights: Vec<f32>,
+}
+
+impl Heightmap {
+    /// Decodes a heightmap scaled to `0..height_scale`. `.r16` and `.raw` files are
+    /// square grids of little-endian 16-bit samples; everything else goes through the
+    /// image crate, 16-bit grayscale PNGs keeping their precision.
+    pub fn decode(path: &str, bytes: &[u8], height_scale: f32) -> Result<Self, String> {
+        let extension = path.rsplit('.').next().unwrap_or("").to_ascii_lowercase();
+        let (width, depth, samples) = if extension == "r16" || extension == "raw" {
+            let count = bytes.len() / 2;
+            let side = (count as f64).sqrt() as u32;
+            if side < 2 || (side * side) as usize * 2 != bytes.len() {
+                return Err(format!(
+                    "raw heightmap '{}' isn't a square of 16-bit samples ({} bytes)",
+                    path,
+                    bytes.len()
+                ));
+            }
+            let samples = bytes
+                .chunks_exact(2)
+                .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                .collect();
+            (side, side, samples)
+        } else {
+            let image = image::load_from_memory(bytes)
+                .map_err(|e| e.to_string())?
+                .to_luma16();
+            if image.width() < 2 || image.height() < 2 {
+                return Err(format!("heightmap '{}' is smaller than 2x2", path));
+            }
+            (image.width(), image.height(), image.into_raw())
+        };
+
+        let scale = height_scale / u16::MAX as f32;
+        Ok(Self {
+            width,
+            depth,
+            heights: samples
+                .into_iter()
+                .map(|sample: u16| sample as f32 * scale)
+                .collect(),
+        })
+    }
+
+    /// Height of a sample, clamped to the grid.
+    pub fn get(&self, x: i64, z: i64) -> f32 {
+        let x = x.clamp(0, self.width as i64 - 1) as usize;
+        let z = z.clamp(0, self.depth as i64 - 1) as usize;
+        self.heights[z * self.width as usize + x]
+    }
+
+    /// Surface normal at a sample, from the height differences to its neighbours.
+    pub fn normal(&self, x: u32, z: u32, spacing: f32) -> Vec3 {
+        let (x, z) = (x as i64, z as i64);
+        let dx = (self.get(x + 1, z) - self.get(x - 1, z)) / (2.0 * spacing);
+        let dz = (self.get(x, z + 1) - self.get(x, z - 1)) / (2.0 * spacing);
+        Vec3::new(-dx, 1.0, -dz).normalize()
+    }
+
+    /// Bilinear height at a point in sample coordinates, None outside the grid.
+    pub fn sample(&self, point: Vec2) -> Option<f32> {
+        let max = Vec2::new((self.width - 1) as f32, (self.depth - 1) as f32);
+        if point.cmplt(Vec2::ZERO).any() || point.cmpgt(max).any() {
+            return None;
+        }
+        let base = point.floor().min(max - 1.0);
+        let t = point - base;
+        let (x, z) = (base.x as i64, base.y as i64);
+        let near = self.get(x, z) + (self.get(x + 1, z) - self.get(x, z)) * t.x;
+        let far = self.get(x, z + 1) + (self.get(x + 1, z + 1) - self.get(x, z + 1)) * t.x;
+        Some(near + (far - near) * t.y)
+    }
+
+    /// Adds up to `amount` to the samples within `radius` (in samples) of `center`,
+    /// fading out smoothly towards the edge. Returns the touched sample range as
+    /// (min, max) corners, None if the brush misses the grid.
+    pub fn raise(
+        &mut self,
+        center: Vec2,
+        radius: f32,
+        amount: f32,
+    ) -> Option<([u32; 2], [u32; 2])> {
+        let radius = radius.max(f32::EPSILON);
+        let min = (center - radius).ceil().max(Vec2::ZERO);
+        let max = (center + radius)
+            .floor()
+            .min(Vec2::new((self.width - 1) as f32, (self.depth - 1) as f32));
+        if min.cmpgt(max).any() {
+            return None;
+        }
+
+        let (min, max) = ([min.x as u32, min.y as u32], [max.x as u32, max.y as u32]);
+        for z in min[1]..=max[1] {
+            for x in min[0]..=max[0] {
+                let distance = Vec2::new(x as f32, z as f32).distance(center) / radius;
+                if distance < 1.0 {
+                    let falloff = 1.0 - distance * distance;
+                    self.heights[(z * self.width + x) as usize] += amount * falloff * falloff;
+                }
+            }
+        }
+        Some((min, max))
+    }
+}
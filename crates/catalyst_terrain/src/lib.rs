@@ -0,0 +1,399 @@
+use std::{
+    collections::BTreeSet,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use catalyst_assets::{
+    AssetPlugin, MaterialDefinition, MeshDefinition,
+    asset_server::AssetServer,
+    assets::{Handle, MeshData},
+    lod::{MeshLod, MeshLodLevel},
+    material::{MaterialData, TextureData},
+};
+use catalyst_core::{
+    App, Plugin, PluginId,
+    physics::{ColliderDefinition, ColliderShapeChanged, PhysicsBody, RigidBodyDefinition},
+    rayon::{self, prelude::*},
+    transform::{GlobalTransform, Transform},
+};
+use flecs_ecs::prelude::*;
+use glam::{Vec2, Vec3};
+
+use crate::{
+    chunk::{ChunkGeometry, ChunkLayout, build_chunk},
+    heightmap::Heightmap,
+};
+
+mod chunk;
+pub mod heightmap;
+
+/// Terrain generated from a heightmap, centered on the entity (which needs a Transform
+/// and GlobalTransform, and shouldn't be scaled: use `spacing` and `height_scale`).
+///
+/// The heightmap is read and cut into chunks on the compute pool. Each chunk becomes a
+/// child entity with a mesh, a MeshLod of coarser grids, and a heightfield collider
+/// (the terrain entity gets a static RigidBodyDefinition for them). Once built, the
+/// entity gets TerrainHeights, which can be sculpted at runtime.
+#[derive(Component, Clone, Debug)]
+pub struct Terrain {
+    /// Grayscale image (16-bit PNGs keep their precision) or `.r16`/`.raw` file of
+    /// square little-endian 16-bit samples, read through the AssetServer.
+    pub heightmap: String,
+    /// Quads per chunk side.
+    pub chunk_size: u32,
+    /// World distance between neighbouring samples.
+    pub spacing: f32,
+    /// World height of the highest possible sample.
+    pub height_scale: f32,
+    /// Detail levels per chunk, each half the resolution of the one before.
+    pub lod_levels: u32,
+    /// Screen height fraction below which a chunk drops to its first coarser level.
+    /// Every further level starts at half the size of the one before.
+    pub lod_screen_size: f32,
+    /// How far the crack-hiding skirts hang below the chunk borders. 0 turns them off.
+    pub skirt_depth: f32,
+    /// World size of one repeat of the material's textures.
+    pub texture_size: f32,
+    /// Tiling material of every chunk. None uses a plain grass color.
+    pub material: Option<Handle<MaterialData>>,
+    /// Reserved for blending several materials; not sampled yet.
+    pub splat_map: Option<Handle<TextureData>>,
+    /// Generate heightfield colliders.
+    pub collider: bool,
+}
+
+impl Terrain {
+    pub fn new(heightmap: &str) -> Self {
+        Self {
+            heightmap: heightmap.to_owned(),
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for Terrain {
+    fn default() -> Self {
+        Self {
+            heightmap: String::new(),
+            chunk_size: 64,
+            spacing: 1.0,
+            height_scale: 50.0,
+            lod_levels: 3,
+            lod_screen_size: 0.5,
+            skirt_depth: 2.0,
+            texture_size: 4.0,
+            material: None,
+            splat_map: None,
+            collider: true,
+        }
+    }
+}
+
+/// On the child entity of every chunk.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct TerrainChunk {
+    pub index: usize,
+}
+
+/// Why a Terrain couldn't be built. It isn't retried until this is removed.
+#[derive(Component, Clone, Debug)]
+pub struct TerrainError(pub String);
+
+struct BuiltChunk {
+    geometry: ChunkGeometry,
+    center: Vec3,
+}
+
+struct TerrainBuild {
+    heightmap: Heightmap,
+    layout: ChunkLayout,
+    chunks: Vec<BuiltChunk>,
+}
+
+// The build running on the compute pool, picked up by "Finish Terrain"
+#[derive(Component)]
+struct TerrainTask {
+    result: Arc<Mutex<Option<Result<TerrainBuild, String>>>>,
+    started: Instant,
+}
+
+struct ChunkRecord {
+    entity: Entity,
+    lods: Vec<Handle<MeshData>>,
+}
+
+/// The heights of a built Terrain, in world units. Sculpting marks the chunks it
+/// touches; only those get new meshes and colliders, at the end of the frame.
+#[derive(Component)]
+pub struct TerrainHeights {
+    heightmap: Heightmap,
+    layout: ChunkLayout,
+    settings: Terrain,
+    chunks: Vec<ChunkRecord>,
+    dirty: BTreeSet<usize>,
+}
+
+impl TerrainHeights {
+    pub fn heightmap(&self) -> &Heightmap {
+        &self.heightmap
+    }
+
+    /// Terrain height at a point relative to the terrain entity (X, Z), None off the terrain.
+    pub fn height_at(&self, point: Vec2) -> Option<f32> {
+        self.heightmap.sample(self.to_samples(point))
+    }
+
+    /// Raises the terrain by up to `amount` (negative lowers it) within `radius` of a
+    /// point relative to the terrain entity, with a smooth falloff.
+    pub fn raise(&mut self, point: Vec2, radius: f32, amount: f32) {
+        let center = self.to_samples(point);
+        if let Some((min, max)) =
+            self.heightmap
+                .raise(center, radius / self.settings.spacing, amount)
+        {
+            self.dirty.extend(self.layout.chunks_touching(min, max));
+        }
+    }
+
+    /// Chunks waiting for new meshes.
+    pub fn dirty_chunks(&self) -> usize {
+        self.dirty.len()
+    }
+
+    fn to_samples(&self, point: Vec2) -> Vec2 {
+        let extent = Vec2::new(
+            (self.heightmap.width - 1) as f32,
+            (self.heightmap.depth - 1) as f32,
+        );
+        point / self.settings.spacing + extent * 0.5
+    }
+}
+
+/// Heightmap terrain: chunked meshes with LODs and heightfield colliders.
+pub struct TerrainPlugin;
+
+impl Plugin for TerrainPlugin {
+    fn build(&self, app: &mut App) {
+        app.world.component::<Terrain>();
+        app.world.component::<TerrainChunk>();
+
+        register_build_systems(&app.world);
+        register_update_system(&app.world);
+    }
+
+    fn dependencies(&self) -> Vec<PluginId> {
+        vec![PluginId::of::<AssetPlugin>()]
+    }
+}
+
+fn register_build_systems(world: &World) {
+    world
+        .system_named::<(&Terrain, &AssetServer)>("Load Terrain")
+        .without(TerrainTask::id())
+        .without(TerrainHeights::id())
+        .without(TerrainError::id())
+        .kind(flecs::pipeline::OnUpdate)
+        .each_entity(|entity, (terrain, assets)| {
+            entity.set(start_build(terrain.clone(), assets.clone()));
+        });
+
+    world
+        .system_named::<(&Terrain, &TerrainTask, &GlobalTransform, &AssetServer)>("Finish Terrain")
+        .kind(flecs::pipeline::OnUpdate)
+        .each_entity(|entity, (terrain, task, global, assets)| {
+            let Some(result) = task.result.lock().unwrap().take() else {
+                return;
+            };
+            let elapsed = task.started.elapsed();
+            entity.remove(TerrainTask::id());
+
+            match result {
+                Ok(build) => {
+                    log::info!(
+                        "Built terrain '{}': {}x{} samples, {} chunks in {:.1} ms",
+                        terrain.heightmap,
+                        build.heightmap.width,
+                        build.heightmap.depth,
+                        build.chunks.len(),
+                        elapsed.as_secs_f64() * 1000.0
+                    );
+                    spawn_chunks(entity, terrain, global, assets, build);
+                }
+                Err(error) => {
+                    log::error!("Failed to build terrain '{}': {}", terrain.heightmap, error);
+                    entity.set(TerrainError(error));
+                }
+            }
+        });
+}
+
+// Reads the heightmap and builds every chunk on the compute pool
+fn start_build(terrain: Terrain, assets: AssetServer) -> TerrainTask {
+    let task = TerrainTask {
+        result: Arc::new(Mutex::new(None)),
+        started: Instant::now(),
+    };
+
+    let result = task.result.clone();
+    rayon::spawn(move || {
+        catalyst_core::profile_scope!("Terrain Build");
+        let build = assets
+            .reader()
+            .read(&terrain.heightmap)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| Heightmap::decode(&terrain.heightmap, &bytes, terrain.height_scale))
+            .map(|heightmap| {
+                let layout = ChunkLayout::new(&heightmap, terrain.chunk_size);
+                let chunks = (0..layout.count())
+                    .into_par_iter()
+                    .map(|index| {
+                        let rect = layout.rect(index);
+                        BuiltChunk {
+                            geometry: build_chunk(&heightmap, &layout, rect, &terrain),
+                            center: layout.center(rect, terrain.spacing),
+                        }
+                    })
+                    .collect();
+                TerrainBuild {
+                    heightmap,
+                    layout,
+                    chunks,
+                }
+            });
+        *result.lock().unwrap() = Some(build);
+    });
+
+    task
+}
+
+fn spawn_chunks(
+    entity: EntityView,
+    terrain: &Terrain,
+    global: &GlobalTransform,
+    assets: &AssetServer,
+    build: TerrainBuild,
+) {
+    let world = entity.world();
+    let material = terrain.material.clone().unwrap_or_else(|| {
+        assets.create_material("terrain", |m| {
+            m.base_color([0.32, 0.45, 0.22, 1.0]).roughness(0.95)
+        })
+    });
+
+    if terrain.collider {
+        entity.set(RigidBodyDefinition {
+            body_type: PhysicsBody::Static,
+            ..Default::default()
+        });
+    }
+
+    let mut chunks = Vec::with_capacity(build.chunks.len());
+    for (index, chunk) in build.chunks.into_iter().enumerate() {
+        let triangles: Vec<usize> = chunk
+            .geometry
+            .lods
+            .iter()
+            .map(|data| data.indices.len() / 3)
+            .collect();
+        let lods: Vec<Handle<MeshData>> = chunk
+            .geometry
+            .lods
+            .into_iter()
+            .enumerate()
+            .map(|(level, data)| {
+                assets.add_mesh(&format!("terrain chunk {} lod {}", index, level), data)
+            })
+            .collect();
+
+        let transform = Transform::from_xyz(chunk.center.x, chunk.center.y, chunk.center.z);
+        let chunk_entity = world
+            .entity()
+            .child_of(entity)
+            .set(transform)
+            .set(GlobalTransform(global.0 * transform.compute_matrix()))
+            .set(MeshDefinition(lods[0].clone()))
+            .set(MaterialDefinition(material.clone()))
+            .set(TerrainChunk { index });
+
+        if lods.len() > 1 {
+            chunk_entity.set(MeshLod {
+                levels: lods[1..]
+                    .iter()
+                    .zip(&triangles[1..])
+                    .enumerate()
+                    .map(|(level, (mesh, &triangles))| MeshLodLevel {
+                        mesh: mesh.clone(),
+                        screen_size: terrain.lod_screen_size / (1 << level) as f32,
+                        triangles,
+                        // Regular grids, not simplified: there is no error to report
+                        error: 0.0,
+                    })
+                    .collect(),
+            });
+        }
+        if terrain.collider {
+            chunk_entity.set(ColliderDefinition {
+                shape: chunk.geometry.collider,
+                ..Default::default()
+            });
+        }
+
+        chunks.push(ChunkRecord {
+            entity: chunk_entity.id(),
+            lods,
+        });
+    }
+
+    entity.set(TerrainHeights {
+        heightmap: build.heightmap,
+        layout: build.layout,
+        settings: terrain.clone(),
+        chunks,
+        dirty: BTreeSet::new(),
+    });
+}
+
+// PostUpdate, so every sculpt of the frame is in before the chunks are rebuilt
+fn register_update_system(world: &World) {
+    world
+        .system_named::<&mut TerrainHeights>("Update Terrain Chunks")
+        .kind(flecs::pipeline::PostUpdate)
+        .each_iter(|iter, _, heights| {
+            if heights.dirty.is_empty() {
+                return;
+            }
+            catalyst_core::profile_scope!("Terrain Chunk Update");
+            let world = iter.world();
+
+            let dirty: Vec<usize> = std::mem::take(&mut heights.dirty).into_iter().collect();
+            let geometry: Vec<ChunkGeometry> = dirty
+                .par_iter()
+                .map(|&index| {
+                    build_chunk(
+                        &heights.heightmap,
+                        &heights.layout,
+                        heights.layout.rect(index),
+                        &heights.settings,
+                    )
+                })
+                .collect();
+
+            for (index, geometry) in dirty.into_iter().zip(geometry) {
+                let record = &heights.chunks[index];
+                for (handle, data) in record.lods.iter().zip(geometry.lods) {
+                    // Not uploaded yet: the next sculpt of this chunk catches up
+                    handle.modify(&world, |mesh| *mesh = data);
+                }
+
+                let chunk = world.entity_from_id(record.entity);
+                if heights.settings.collider {
+                    chunk.set(ColliderDefinition {
+                        shape: geometry.collider,
+                        ..Default::default()
+                    });
+                    chunk.add(ColliderShapeChanged);
+                }
+            }
+        });
+}
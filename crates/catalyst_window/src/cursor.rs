@@ -0,0 +1,33 @@
+use catalyst_core::game_state::{GameStateChanged, GameStates};
+use flecs_ecs::prelude::*;
+use winit::window::CursorGrabMode;
+
+use crate::MainWindow;
+
+// Menus free the cursor; going back to gameplay grabs it again, as the window starts
+fn follow_game_state(world: &World, changed: &GameStateChanged) {
+    let shows_cursor = world.get::<&GameStates>(|states| {
+        states
+            .state(changed.to)
+            .is_some_and(|state| state.is_cursor_shown())
+    });
+    world.try_get::<&MainWindow>(|window| {
+        let mode = if shows_cursor {
+            CursorGrabMode::None
+        } else {
+            CursorGrabMode::Locked
+        };
+        if let Err(error) = window.0.set_cursor_grab(mode) {
+            log::debug!("Can't set the cursor grab to {:?}: {}", mode, error);
+        }
+        window.0.set_cursor_visible(shows_cursor);
+    });
+}
+
+pub(crate) fn register_game_state_cursor(world: &World) {
+    world
+        .entity_from::<GameStates>()
+        .observe_payload_entity::<&GameStateChanged>(|entity, changed| {
+            follow_game_state(&entity.world(), changed);
+        });
+}
@@ -0,0 +1,66 @@
+use std::path::PathBuf;
+
+use flecs_ecs::prelude::*;
+
+use crate::MainWindow;
+
+/// Event: a file was dropped on the window. Emitted on the MainWindow singleton entity
+/// as soon as the platform reports it, between frames:
+///
+/// ```ignore
+/// world
+///     .entity_from::<MainWindow>()
+///     .observe_payload_entity::<&FileDropped>(|window, dropped| {
+///         log::info!("Dropped {}", dropped.path.display());
+///     });
+/// ```
+///
+/// Dropping several files at once emits one event per file.
+#[derive(Component, Clone, Debug)]
+pub struct FileDropped {
+    pub path: PathBuf,
+}
+
+/// Files being dragged over the window, empty otherwise. For hover feedback.
+#[derive(Component, Clone, Debug, Default)]
+pub struct FileHover {
+    pub paths: Vec<PathBuf>,
+}
+
+impl FileHover {
+    pub fn is_hovering(&self) -> bool {
+        !self.paths.is_empty()
+    }
+}
+
+/// Turns off the engine's handler for dropped files (the debug layer's, which loads
+/// scenes and textures), for games that observe FileDropped themselves.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct FileDropSettings {
+    pub default_handler: bool,
+}
+
+impl Default for FileDropSettings {
+    fn default() -> Self {
+        Self {
+            default_handler: true,
+        }
+    }
+}
+
+pub(crate) fn file_hovered(world: &World, path: PathBuf) {
+    world.get::<&mut FileHover>(|hover| hover.paths.push(path));
+}
+
+pub(crate) fn file_hover_cancelled(world: &World) {
+    world.get::<&mut FileHover>(|hover| hover.paths.clear());
+}
+
+pub(crate) fn file_dropped(world: &World, path: PathBuf) {
+    log::info!("File dropped: {}", path.display());
+    // Each dropped file was hovered first
+    world.get::<&mut FileHover>(|hover| hover.paths.retain(|hovered| *hovered != path));
+    world
+        .entity_from::<MainWindow>()
+        .emit(&FileDropped { path });
+}
@@ -0,0 +1,154 @@
+use std::{fmt, time::Duration};
+
+use flecs_ecs::macros::Component;
+use serde::{Deserialize, Serialize};
+
+/// How the runner paces frames: an optional overall cap, and what to do while the
+/// window isn't in front. Set it after adding the plugins, e.g.
+/// `WindowConfig::unthrottled()` for apps that must keep running at full speed in the
+/// background (music players, servers). Read every frame, and kept in the settings
+/// file as "window".
+#[derive(Component, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    /// Frame rate cap, whatever the present mode: the runner waits out the rest of
+    /// every frame, sleeping and then spinning the last millisecond. None renders as
+    /// fast as the present mode allows. The unfocused and hidden rates still apply
+    /// when they are lower.
+    pub max_fps: Option<f32>,
+    /// Frame rate cap while another window has focus. None keeps the full rate.
+    pub unfocused_fps: Option<f32>,
+    /// Rate at which the simulation keeps ticking while minimized or occluded.
+    /// None ticks as fast as possible.
+    pub hidden_fps: Option<f32>,
+    /// Skip the render phases while minimized or occluded.
+    pub skip_render_when_hidden: bool,
+    /// Stop updating entirely while minimized or occluded instead of ticking at
+    /// `hidden_fps`.
+    pub pause_when_hidden: bool,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            max_fps: None,
+            unfocused_fps: Some(10.0),
+            hidden_fps: Some(10.0),
+            skip_render_when_hidden: true,
+            pause_when_hidden: false,
+        }
+    }
+}
+
+impl WindowConfig {
+    /// Renders and updates at full speed whatever the window state.
+    pub fn unthrottled() -> Self {
+        Self {
+            max_fps: None,
+            unfocused_fps: None,
+            hidden_fps: None,
+            skip_render_when_hidden: false,
+            pause_when_hidden: false,
+        }
+    }
+}
+
+/// What the runner currently does with frames, derived from `WindowFocus` and `WindowConfig`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FramePacing {
+    /// Full speed, or capped at `WindowConfig::max_fps`.
+    #[default]
+    Active,
+    /// Unfocused, frames capped at `WindowConfig::unfocused_fps`.
+    Throttled,
+    /// Minimized or occluded, the simulation ticks at `WindowConfig::hidden_fps`.
+    Hidden,
+    /// Minimized or occluded with `WindowConfig::pause_when_hidden`, no frames run.
+    Paused,
+}
+
+impl fmt::Display for FramePacing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FramePacing::Active => "Active",
+            FramePacing::Throttled => "Throttled",
+            FramePacing::Hidden => "Hidden",
+            FramePacing::Paused => "Paused",
+        })
+    }
+}
+
+/// Window state as reported by the OS, updated by the runner from window events.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct WindowFocus {
+    pub focused: bool,
+    pub occluded: bool,
+    pub minimized: bool,
+    pacing: FramePacing,
+    rendering: bool,
+}
+
+impl Default for WindowFocus {
+    fn default() -> Self {
+        Self {
+            focused: true,
+            occluded: false,
+            minimized: false,
+            pacing: FramePacing::Active,
+            rendering: true,
+        }
+    }
+}
+
+impl WindowFocus {
+    /// Nothing of the window is visible.
+    pub fn is_hidden(&self) -> bool {
+        self.occluded || self.minimized
+    }
+
+    pub fn pacing(&self) -> FramePacing {
+        self.pacing
+    }
+
+    /// False while the render phases are skipped.
+    pub fn is_rendering(&self) -> bool {
+        self.rendering
+    }
+
+    pub(crate) fn set_policy(&mut self, pacing: FramePacing, rendering: bool) {
+        self.pacing = pacing;
+        self.rendering = rendering;
+    }
+
+    pub(crate) fn policy(&self, config: &WindowConfig) -> (FramePacing, bool) {
+        if self.is_hidden() {
+            let pacing = if config.pause_when_hidden {
+                FramePacing::Paused
+            } else {
+                FramePacing::Hidden
+            };
+            (pacing, !config.skip_render_when_hidden)
+        } else if !self.focused && config.unfocused_fps.is_some() {
+            (FramePacing::Throttled, true)
+        } else {
+            (FramePacing::Active, true)
+        }
+    }
+
+    /// Time between frames for the current pacing, None when frames run back to back
+    /// or not at all. The lowest of the applicable rates wins.
+    pub(crate) fn frame_interval(&self, config: &WindowConfig) -> Option<Duration> {
+        let state_fps = match self.pacing {
+            FramePacing::Throttled => config.unfocused_fps,
+            FramePacing::Hidden => config.hidden_fps,
+            FramePacing::Active => None,
+            FramePacing::Paused => return None,
+        };
+        let fps = [state_fps, config.max_fps]
+            .into_iter()
+            .flatten()
+            .filter(|fps| *fps > 0.0)
+            .reduce(f32::min)?;
+        Some(Duration::from_secs_f32(1.0 / fps))
+    }
+}
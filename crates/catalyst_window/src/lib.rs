@@ -1,9 +1,22 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
 use catalyst_core::{
-    App, Plugin, SystemEvents,
-    pipeline::{PhysicsPipeline},
+    App, Plugin, PluginId, SystemEvents,
+    console::ConsoleCommands,
+    pipeline::{PhysicsPipeline, set_rendering_enabled},
     time::{PhysicsTime, Time},
 };
-use catalyst_input::physical::{DeviceKind, InputState, MouseButtonId, PhysicalInputId};
+use catalyst_input::{
+    InputPlugin,
+    physical::{DeviceKind, GamepadAxisId, GamepadButtonId, InputState, MouseButtonId, PhysicalInputId},
+    player::{InputDevice, PlayerInputs},
+    replay::begin_input_frame,
+    rumble::Rumble,
+    timeline::{InputEvent, InputTimeline, begin_fixed_step},
+};
 use flecs_ecs::{
     core::{WorldGet, flecs, world},
     macros::Component,
@@ -16,21 +29,76 @@ use winit::{
     window::{CursorGrabMode, Window},
 };
 
+pub use file_drop::{FileDropSettings, FileDropped, FileHover};
+pub use focus::{FramePacing, WindowConfig, WindowFocus};
+pub use pacing::{FrameStats, HISTOGRAM_BUCKETS};
+
+use crate::{
+    cursor::register_game_state_cursor,
+    file_drop::{file_dropped, file_hover_cancelled, file_hovered},
+    pacing::{FrameLimiter, register_pacing_commands, register_transform_stats},
+    rumble::RumbleBackend,
+};
+
+mod cursor;
+mod file_drop;
+mod focus;
+mod pacing;
+mod rumble;
+
+/// The window the app renders to. Shared: the renderer's surface holds another Arc,
+/// so the window outlives the surface whatever order they are torn down in.
 #[derive(Component)]
-pub struct MainWindow(pub Window);
+pub struct MainWindow(pub Arc<Window>);
+
+/// Whether the platform currently lets the app draw. Android-style platforms take the
+/// surface away between suspend and resume; the runner sets this (observe it with
+/// OnSet to drop or rebuild window resources) and runs no frames while suspended.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WindowLifecycle {
+    #[default]
+    Resumed,
+    Suspended,
+}
 
 // The State Machine that holds the App while waiting for the OS
 struct CatalystRunner {
     app: App,
     // We keep track if we have started the engine yet
     initialized: bool,
+    // Gamepad backend. None if the platform has no gamepad support.
+    gilrs: Option<gilrs::Gilrs>,
+    // Force feedback effects playing on the gamepads
+    rumble: RumbleBackend,
+    // When the next throttled or hidden frame is due
+    next_frame: Instant,
+    // Holds active frames to WindowConfig::max_fps
+    limiter: FrameLimiter,
+    // Start of the previous frame, for FrameStats
+    last_frame_start: Option<Instant>,
+    // Between `suspended` and `resumed`
+    suspended: bool,
 }
 
 impl CatalystRunner {
     pub fn new(app: App) -> Self {
+        let gilrs = match gilrs::Gilrs::new() {
+            Ok(gilrs) => Some(gilrs),
+            Err(e) => {
+                log::warn!("Gamepad support unavailable: {}", e);
+                None
+            }
+        };
+
         Self {
             app,
             initialized: false,
+            gilrs,
+            rumble: RumbleBackend::default(),
+            next_frame: Instant::now(),
+            limiter: FrameLimiter::default(),
+            last_frame_start: None,
+            suspended: false,
         }
     }
 }
@@ -39,47 +107,255 @@ pub struct WindowPlugin;
 
 impl Plugin for WindowPlugin {
     fn build(&self, app: &mut App) {
-        // Nothing to register yet
         app.world
             .component::<MainWindow>()
             .add_trait::<flecs::Singleton>();
+        app.register_settings::<WindowConfig>("window");
+        app.register_singleton_default::<WindowFocus>();
+        app.register_singleton_default::<WindowLifecycle>();
+        app.register_singleton_default::<FrameStats>();
+        app.register_singleton_default::<FileHover>();
+        app.register_singleton_default::<FileDropSettings>();
+        app.world
+            .get::<&mut ConsoleCommands>(|commands| register_pacing_commands(commands));
+        register_transform_stats(&app.world);
+        register_game_state_cursor(&app.world);
+    }
+
+    // The runner feeds window and device events into InputState
+    fn dependencies(&self) -> Vec<PluginId> {
+        vec![PluginId::of::<InputPlugin>()]
     }
 }
 
 impl CatalystRunner {
-    fn run_physics_loop(&mut self) {
+    fn run_physics_loop(&mut self, frame_time: Instant) {
         let mut steps_to_run = 0;
         let mut fixed_dt = 0.0;
+        let mut accumulated = 0.0;
         let max_steps_per_frame = 4; // prevents spiral-of-death
 
         // --------------------------------------------------------- // Determine how many physics steps to run // ---------------------------------------------------------
         self.app.world.get::<&mut PhysicsTime>(|pt| {
             fixed_dt = pt.fixed_dt;
-            while pt.accumulator >= fixed_dt {
-                pt.accumulator -= fixed_dt;
-                steps_to_run += 1;
-                if steps_to_run >= max_steps_per_frame {
-                    // Clamp to avoid runaway catch-up
-                    break;
-                }
-            }
+            accumulated = pt.accumulator;
+            // Clamped to avoid runaway catch-up; single steps of a paused simulation on top
+            steps_to_run = pt.take_steps(max_steps_per_frame);
         });
 
+        // The accumulated time ends now, so the simulated time still owed started
+        // `accumulated` ago. Each step sees the input events up to the real time its
+        // simulation ends at.
+        let owed_since = frame_time
+            .checked_sub(Duration::from_secs_f32(accumulated))
+            .unwrap_or(frame_time);
+
         // --------------------------------------------------------- // Run physics steps // ---------------------------------------------------------
-        for _ in 0..steps_to_run {
+        for step in 1..=steps_to_run {
+            let step_end = owed_since + Duration::from_secs_f32(step as f32 * fixed_dt);
+            begin_fixed_step(&self.app.world, step_end);
             self.run_physics_pipeline(fixed_dt);
         }
     }
 
+    /// Applies an input event to the frame-level states right away and queues it,
+    /// timestamped, for the fixed steps.
+    fn dispatch_input(&mut self, device: InputDevice, event: InputEvent) {
+        let at = Instant::now();
+        let world = &self.app.world;
+
+        world.try_get::<&mut InputState>(|input_state| event.apply(input_state));
+        let player = world
+            .try_get::<&mut PlayerInputs>(|players| {
+                let player = players.player_for(device)?;
+                if let Some(input_state) = players.state_mut(player) {
+                    event.apply(input_state);
+                }
+                Some(player)
+            })
+            .flatten();
+        world.try_get::<&mut InputTimeline>(|timeline| timeline.push(at, player, event));
+    }
+
+    fn poll_gamepads(&mut self) {
+        let Some(gilrs) = self.gilrs.as_mut() else {
+            return;
+        };
+
+        while let Some(gilrs::Event { id, event, .. }) = gilrs.next_event() {
+            let device = InputDevice::Gamepad(usize::from(id));
+
+            let (physical, value) = match event {
+                gilrs::EventType::ButtonPressed(button, _) => match to_gamepad_button_id(button) {
+                    Some(b) => (DeviceKind::GamepadButton(b as u16), 1.0),
+                    None => continue,
+                },
+                gilrs::EventType::ButtonReleased(button, _) => match to_gamepad_button_id(button) {
+                    Some(b) => (DeviceKind::GamepadButton(b as u16), 0.0),
+                    None => continue,
+                },
+                gilrs::EventType::AxisChanged(axis, value, _) => match to_gamepad_axis_id(axis) {
+                    Some(a) => (DeviceKind::GamepadAxis(a as u16), value),
+                    None => continue,
+                },
+                gilrs::EventType::Connected => {
+                    log::info!("Gamepad {:?} connected", id);
+                    continue;
+                }
+                gilrs::EventType::Disconnected => {
+                    log::info!("Gamepad {:?} disconnected", id);
+                    self.app
+                        .world
+                        .try_get::<&mut Rumble>(|rumble| rumble.stop(usize::from(id)));
+                    continue;
+                }
+                _ => continue,
+            };
+
+            let id = PhysicalInputId { device: physical };
+            let event = match physical {
+                DeviceKind::GamepadAxis(_) => InputEvent::Axis { id, value },
+                _ => InputEvent::Button {
+                    id,
+                    pressed: value > 0.5,
+                },
+            };
+            self.dispatch_input(device, event);
+        }
+    }
+
+    // Sends the Rumble singleton's motor levels to the gamepads. Also called outside
+    // frames, since a paused window runs none to stop a rumble with
+    fn apply_rumble(&mut self) {
+        if let Some(gilrs) = self.gilrs.as_mut() {
+            self.rumble.apply(gilrs, &self.app.world);
+        }
+    }
+
     fn run_physics_pipeline(&mut self, dt: f32) {
         // let pipeline = self.app.world.lookup("physics_pipeline");
+        catalyst_core::profile_scope!("Physics Step");
         self.app.world.run_pipeline_time(PhysicsPipeline, dt);
     }
+
+    fn run_frame(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        catalyst_core::profile_scope!(catalyst_core::profiling::FRAME_SCOPE);
+        self.app.world.get::<&mut Time>(|time| time.update());
+        let frame_time = Instant::now();
+        if let Some(last) = self.last_frame_start.replace(frame_time) {
+            self.app
+                .world
+                .get::<&mut FrameStats>(|stats| stats.record(frame_time - last));
+        }
+
+        self.poll_gamepads();
+
+        // Records this frame, or swaps in the recorded input and delta time
+        begin_input_frame(&self.app.world);
+        let dt = self.app.world.get::<&Time>(|time| time.delta_seconds());
+
+        // --------------------------------------------------------- // 2. Accumulate physics time // ---------------------------------------------------------
+        self.app
+            .world
+            .get::<&mut PhysicsTime>(|pt| pt.accumulate(dt));
+
+        // --------------------------------------------------------- // 3. Run physics (fixed timestep) // ---------------------------------------------------------
+        self.run_physics_loop(frame_time);
+
+        // 2. Run the Systems
+        self.app.update();
+        self.apply_rumble();
+
+        if !self.app.running {
+            log::info!("Exit requested; stopping");
+            event_loop.exit();
+            return;
+        }
+
+        self.app.world.try_get::<&mut SystemEvents>(|events| {
+            events.clear();
+        });
+
+        self.limit_frame();
+    }
+
+    // Waits out the rest of an active frame under WindowConfig::max_fps. Throttled and
+    // hidden frames are paced by about_to_wait instead, which keeps handling events
+    // while it waits. The simulation isn't affected: the next frame's delta is the real
+    // time since this one, wait included, not the target interval.
+    fn limit_frame(&mut self) {
+        let world = &self.app.world;
+        let Some((pacing, interval)) = world.try_get::<&WindowConfig>(|config| {
+            world.get::<&WindowFocus>(|focus| (focus.pacing(), focus.frame_interval(config)))
+        }) else {
+            return;
+        };
+        world.get::<&mut FrameStats>(|stats| {
+            stats.target_fps = interval.map(|interval| 1.0 / interval.as_secs_f32());
+        });
+
+        match interval {
+            Some(interval) if pacing == FramePacing::Active => {
+                catalyst_core::profile_scope!("Frame Limiter");
+                if self.limiter.wait(interval) {
+                    world.get::<&mut FrameStats>(|stats| stats.missed_frames += 1);
+                }
+            }
+            _ => self.limiter.reset(),
+        }
+    }
+
+    // Re-derives the frame pacing after a focus, occlusion or size change
+    fn update_pacing(&mut self) {
+        let world = &self.app.world;
+        let Some(config) = world.try_get::<&WindowConfig>(|config| *config) else {
+            return;
+        };
+
+        let mut changed = None;
+        world.get::<&mut WindowFocus>(|focus| {
+            let previous = (focus.pacing(), focus.is_rendering());
+            let (pacing, rendering) = focus.policy(&config);
+            if previous != (pacing, rendering) {
+                focus.set_policy(pacing, rendering);
+                changed = Some((previous, (pacing, rendering)));
+            }
+        });
+        let Some(((old_pacing, old_rendering), (pacing, rendering))) = changed else {
+            return;
+        };
+
+        log::debug!("Window pacing: {} -> {}", old_pacing, pacing);
+        if old_rendering != rendering {
+            set_rendering_enabled(world, rendering);
+        }
+
+        // Time spent paused isn't a frame, don't let it land in the next delta
+        if old_pacing == FramePacing::Paused {
+            world.get::<&mut Time>(|time| time.reset_delta());
+            self.last_frame_start = None;
+        }
+
+        // Resume right away rather than at the end of a throttled interval
+        self.next_frame = Instant::now();
+    }
 }
 
 impl ApplicationHandler for CatalystRunner {
     fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
-        self.app.world.set(MainWindow(
+        // Coming back from the background: the window is still ours, only the
+        // surface has to be rebuilt (by the OnSet observers of WindowLifecycle)
+        if self.suspended {
+            self.suspended = false;
+            log::info!("Resumed");
+            self.app.world.set(WindowLifecycle::Resumed);
+            self.app.world.get::<&mut Time>(|time| time.reset_delta());
+            self.next_frame = Instant::now();
+            self.last_frame_start = None;
+            return;
+        }
+
+        self.app.world.set(MainWindow(Arc::new(
             event_loop
                 .create_window(
                     Window::default_attributes()
@@ -92,7 +368,7 @@ impl ApplicationHandler for CatalystRunner {
                     w
                 })
                 .unwrap(),
-        ));
+        )));
 
         if !self.initialized {
             self.app.startup();
@@ -100,10 +376,50 @@ impl ApplicationHandler for CatalystRunner {
         }
     }
 
+    // Android-style platforms destroy the native surface after this returns, so
+    // everything drawing to it is dropped right here
+    fn suspended(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop) {
+        self.suspended = true;
+        log::info!("Suspended");
+        self.app.world.set(WindowLifecycle::Suspended);
+        self.app.world.try_get::<&mut Rumble>(|rumble| rumble.stop_all());
+        self.apply_rumble();
+    }
+
     fn about_to_wait(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
-        self.app.world.get::<&mut MainWindow>(|w| {
-            w.0.request_redraw();
-        });
+        if self.suspended {
+            event_loop.set_control_flow(ControlFlow::Wait);
+            return;
+        }
+
+        let Some((pacing, interval)) = self.app.world.try_get::<&WindowConfig>(|config| {
+            self.app
+                .world
+                .get::<&WindowFocus>(|focus| (focus.pacing(), focus.frame_interval(config)))
+        }) else {
+            return;
+        };
+
+        match pacing {
+            FramePacing::Paused => event_loop.set_control_flow(ControlFlow::Wait),
+            FramePacing::Active => {
+                event_loop.set_control_flow(ControlFlow::Poll);
+                self.app.world.try_get::<&MainWindow>(|w| w.0.request_redraw());
+            }
+            FramePacing::Throttled | FramePacing::Hidden => {
+                let now = Instant::now();
+                if now >= self.next_frame {
+                    self.next_frame = now + interval.unwrap_or_default();
+                    if pacing == FramePacing::Hidden {
+                        // Hidden windows don't get redraw requests on every platform
+                        self.run_frame(event_loop);
+                    } else {
+                        self.app.world.try_get::<&MainWindow>(|w| w.0.request_redraw());
+                    }
+                }
+                event_loop.set_control_flow(ControlFlow::WaitUntil(self.next_frame));
+            }
+        }
     }
 
     fn device_event(
@@ -114,10 +430,12 @@ impl ApplicationHandler for CatalystRunner {
     ) {
         match event {
             winit::event::DeviceEvent::MouseMotion { delta } => {
-                self.app.world.get::<&mut InputState>(|input_state| {
-                    input_state.mouse_delta.0 += delta.0 as f32;
-                    input_state.mouse_delta.1 += delta.1 as f32;
-                });
+                self.dispatch_input(
+                    InputDevice::KeyboardMouse,
+                    InputEvent::MouseMotion {
+                        delta: (delta.0 as f32, delta.1 as f32),
+                    },
+                );
             }
             _ => {}
         }
@@ -134,76 +452,43 @@ impl ApplicationHandler for CatalystRunner {
         });
 
         // handle inputs
-        self.app.world.try_get::<&mut InputState>(|input_state| {
-            match event {
-                WindowEvent::KeyboardInput {
-                    event:
-                        KeyEvent {
-                            physical_key: winit::keyboard::PhysicalKey::Code(code),
-
-                            state,
-                            ..
-                        },
-                    ..
-                } => {
-                    let pid = PhysicalInputId {
-                        device: DeviceKind::Keyboard(code as u16),
-                    };
-
-                    let pressed = state == winit::event::ElementState::Pressed;
-                    input_state.physical_buttons.insert(pid, pressed);
-                }
-                WindowEvent::MouseInput {
-                    state: btn_state,
-                    button,
-                    ..
-                } => {
-                    let pid = PhysicalInputId {
-                        device: DeviceKind::MouseButton(to_mouse_button_id(button)),
-                    };
-                    let pressed = btn_state == winit::event::ElementState::Pressed;
-                    input_state.physical_buttons.insert(pid, pressed);
-                }
-                WindowEvent::CursorMoved { position, .. } => {
-                    // optional: store absolute position }
-                    let new_x = position.x as f32;
-                    let new_y = position.y as f32;
-                    input_state.mouse_position = (new_x, new_y);
-                }
-
-                _ => (),
-            }
-        });
+        if let Some(input) = window_input_event(&event) {
+            self.dispatch_input(InputDevice::KeyboardMouse, input);
+        }
 
         match event {
             WindowEvent::CloseRequested => {
-                println!("The close button was pressed; stopping");
+                log::info!("The close button was pressed; stopping");
                 event_loop.exit();
             }
-            WindowEvent::RedrawRequested => {
-                let dt = self.app.world.get::<&mut Time>(|time| {
-                    time.update();
-                    time.delta_seconds()
-                });
-
-                // --------------------------------------------------------- // 2. Accumulate physics time // ---------------------------------------------------------
-                self.app.world.get::<&mut PhysicsTime>(|pt| {
-                    pt.accumulator += dt;
-                });
-
-                // --------------------------------------------------------- // 3. Run physics (fixed timestep) // ---------------------------------------------------------
-                self.run_physics_loop();
-
-                // 2. Run the Systems
-                self.app.update();
-
-                self.app.world.try_get::<&mut SystemEvents>(|events| {
-                    events.clear();
-                });
-
-                self.app.world.try_get::<&mut MainWindow>(|window_res| {
-                    window_res.0.request_redraw();
+            WindowEvent::Focused(focused) => {
+                self.app.world.get::<&mut WindowFocus>(|focus| focus.focused = focused);
+                self.app
+                    .world
+                    .try_get::<&mut Rumble>(|rumble| rumble.set_window_focused(focused));
+                self.apply_rumble();
+                self.update_pacing();
+            }
+            WindowEvent::Occluded(occluded) => {
+                self.app.world.get::<&mut WindowFocus>(|focus| focus.occluded = occluded);
+                self.update_pacing();
+            }
+            WindowEvent::Resized(size) => {
+                self.app.world.get::<&mut WindowFocus>(|focus| {
+                    focus.minimized = size.width == 0 || size.height == 0;
                 });
+                self.update_pacing();
+            }
+            WindowEvent::HoveredFile(path) => file_hovered(&self.app.world, path),
+            WindowEvent::HoveredFileCancelled => file_hover_cancelled(&self.app.world),
+            WindowEvent::DroppedFile(path) => file_dropped(&self.app.world, path),
+            // Paced by about_to_wait, which also keeps requesting redraws
+            WindowEvent::RedrawRequested => {
+                if !self.suspended
+                    && self.app.world.get::<&WindowFocus>(|focus| focus.pacing()) != FramePacing::Paused
+                {
+                    self.run_frame(event_loop);
+                }
             }
             _ => (),
         }
@@ -220,6 +505,87 @@ pub fn run_catalyst_app(mut app: App) {
     let mut main_window = CatalystRunner::new(app);
 
     event_loop.run_app(&mut main_window).unwrap();
+
+    if let Some(code) = main_window.app.exit_code() {
+        std::process::exit(code);
+    }
+}
+
+fn window_input_event(event: &WindowEvent) -> Option<InputEvent> {
+    match event {
+        WindowEvent::KeyboardInput {
+            event:
+                KeyEvent {
+                    physical_key: winit::keyboard::PhysicalKey::Code(code),
+
+                    state,
+                    ..
+                },
+            ..
+        } => {
+            let pid = PhysicalInputId {
+                device: DeviceKind::Keyboard(*code as u16),
+            };
+
+            let pressed = *state == winit::event::ElementState::Pressed;
+            Some(InputEvent::Button { id: pid, pressed })
+        }
+        WindowEvent::MouseInput {
+            state: btn_state,
+            button,
+            ..
+        } => {
+            let pid = PhysicalInputId {
+                device: DeviceKind::MouseButton(to_mouse_button_id(*button)),
+            };
+            let pressed = *btn_state == winit::event::ElementState::Pressed;
+            Some(InputEvent::Button { id: pid, pressed })
+        }
+        WindowEvent::CursorMoved { position, .. } => {
+            let new_x = position.x as f32;
+            let new_y = position.y as f32;
+            Some(InputEvent::CursorMoved {
+                position: (new_x, new_y),
+            })
+        }
+
+        _ => None,
+    }
+}
+
+fn to_gamepad_button_id(button: gilrs::Button) -> Option<GamepadButtonId> {
+    Some(match button {
+        gilrs::Button::South => GamepadButtonId::South,
+        gilrs::Button::East => GamepadButtonId::East,
+        gilrs::Button::North => GamepadButtonId::North,
+        gilrs::Button::West => GamepadButtonId::West,
+        gilrs::Button::LeftTrigger => GamepadButtonId::LeftTrigger,
+        gilrs::Button::LeftTrigger2 => GamepadButtonId::LeftTrigger2,
+        gilrs::Button::RightTrigger => GamepadButtonId::RightTrigger,
+        gilrs::Button::RightTrigger2 => GamepadButtonId::RightTrigger2,
+        gilrs::Button::Select => GamepadButtonId::Select,
+        gilrs::Button::Start => GamepadButtonId::Start,
+        gilrs::Button::Mode => GamepadButtonId::Mode,
+        gilrs::Button::LeftThumb => GamepadButtonId::LeftThumb,
+        gilrs::Button::RightThumb => GamepadButtonId::RightThumb,
+        gilrs::Button::DPadUp => GamepadButtonId::DPadUp,
+        gilrs::Button::DPadDown => GamepadButtonId::DPadDown,
+        gilrs::Button::DPadLeft => GamepadButtonId::DPadLeft,
+        gilrs::Button::DPadRight => GamepadButtonId::DPadRight,
+        _ => return None,
+    })
+}
+
+fn to_gamepad_axis_id(axis: gilrs::Axis) -> Option<GamepadAxisId> {
+    Some(match axis {
+        gilrs::Axis::LeftStickX => GamepadAxisId::LeftStickX,
+        gilrs::Axis::LeftStickY => GamepadAxisId::LeftStickY,
+        gilrs::Axis::RightStickX => GamepadAxisId::RightStickX,
+        gilrs::Axis::RightStickY => GamepadAxisId::RightStickY,
+        gilrs::Axis::LeftZ => GamepadAxisId::LeftZ,
+        gilrs::Axis::RightZ => GamepadAxisId::RightZ,
+        _ => return None,
+    })
 }
 
 fn to_mouse_button_id(button: winit::event::MouseButton) -> MouseButtonId {
@@ -0,0 +1,267 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use catalyst_core::{console::ConsoleCommands, propagation::TransformTree};
+use flecs_ecs::prelude::*;
+
+use crate::focus::WindowConfig;
+
+/// Buckets of `FrameStats::histogram`, one per millisecond.
+pub const HISTOGRAM_BUCKETS: usize = 50;
+
+// The OS sleeps at least this much too long now and then; the rest is spun
+const SPIN_MARGIN: Duration = Duration::from_millis(1);
+
+/// How regularly frames arrive, updated by the runner at the start of every frame.
+#[derive(Component, Clone, Debug)]
+pub struct FrameStats {
+    /// Frame rate the limiter currently aims for, None when uncapped.
+    pub target_fps: Option<f32>,
+    /// Time between the starts of the last two frames.
+    pub interval_ms: f32,
+    /// Frame intervals since the last `reset`: `histogram[i]` counts the ones between
+    /// i and i + 1 ms, the last bucket everything longer.
+    pub histogram: [u64; HISTOGRAM_BUCKETS],
+    /// Frames counted since the last `reset`.
+    pub frames: u64,
+    /// Frames that ended after the limiter's deadline, so the next one started late.
+    pub missed_frames: u64,
+    /// Time the renderer spent culling meshes against the views this frame.
+    pub culling_ms: f32,
+    /// Time GlobalTransform propagation took this frame, see `TransformTree`.
+    pub transform_ms: f32,
+    /// Local transforms propagation compared this frame; entities with a
+    /// StaticTransform aren't, so this follows the moving ones.
+    pub transforms_refreshed: usize,
+    /// GlobalTransforms propagation recomputed this frame.
+    pub transforms_updated: usize,
+    total_ms: f64,
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self {
+            target_fps: None,
+            interval_ms: 0.0,
+            histogram: [0; HISTOGRAM_BUCKETS],
+            frames: 0,
+            missed_frames: 0,
+            culling_ms: 0.0,
+            transform_ms: 0.0,
+            transforms_refreshed: 0,
+            transforms_updated: 0,
+            total_ms: 0.0,
+        }
+    }
+}
+
+impl FrameStats {
+    /// Mean frame interval since the last `reset`.
+    pub fn average_interval_ms(&self) -> f32 {
+        if self.frames == 0 {
+            0.0
+        } else {
+            (self.total_ms / self.frames as f64) as f32
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self {
+            target_fps: self.target_fps,
+            ..Default::default()
+        };
+    }
+
+    pub(crate) fn record(&mut self, interval: Duration) {
+        let ms = interval.as_secs_f64() * 1000.0;
+        self.interval_ms = ms as f32;
+        self.histogram[(ms as usize).min(HISTOGRAM_BUCKETS - 1)] += 1;
+        self.frames += 1;
+        self.total_ms += ms;
+    }
+}
+
+/// Holds frames to a fixed rate, whatever the present mode. Deadlines follow each
+/// other by exactly one interval, so an early or late frame doesn't shift the ones
+/// after it; after a hitch of more than a whole interval it starts over instead of
+/// rushing frames to catch up.
+#[derive(Default)]
+pub(crate) struct FrameLimiter {
+    deadline: Option<Instant>,
+}
+
+impl FrameLimiter {
+    /// Waits until the current frame's interval is over. Returns true when the frame
+    /// overran it.
+    pub fn wait(&mut self, interval: Duration) -> bool {
+        let now = Instant::now();
+        let deadline = self.deadline.unwrap_or(now);
+
+        let missed = now > deadline;
+        if !missed {
+            sleep_until(deadline);
+        }
+
+        self.deadline = Some(if now > deadline + interval {
+            now + interval
+        } else {
+            deadline + interval
+        });
+        missed
+    }
+
+    /// Forgets the deadline, e.g. when the cap changes or frames resume after a pause.
+    pub fn reset(&mut self) {
+        self.deadline = None;
+    }
+}
+
+// Sleeps the bulk of the wait and spins the last SPIN_MARGIN, which OS timers
+// can't hit reliably
+fn sleep_until(deadline: Instant) {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining > SPIN_MARGIN {
+        thread::sleep(remaining - SPIN_MARGIN);
+    }
+    while Instant::now() < deadline {
+        std::hint::spin_loop();
+    }
+}
+
+pub(crate) fn register_pacing_commands(commands: &mut ConsoleCommands) {
+    commands.register(
+        "window.fps",
+        "window.fps <fps|off> - cap the frame rate while focused",
+        |world, args| {
+            let arg = args.first().ok_or("usage: window.fps <fps|off>")?;
+            let max_fps = match *arg {
+                "off" => None,
+                fps => match fps.parse::<f32>() {
+                    Ok(fps) if fps > 0.0 => Some(fps),
+                    _ => return Err(format!("'{}' isn't a positive frame rate", fps)),
+                },
+            };
+            world.get::<&mut WindowConfig>(|config| config.max_fps = max_fps);
+            world.get::<&mut FrameStats>(|stats| stats.reset());
+            Ok(match max_fps {
+                Some(fps) => format!("Frame rate capped at {} fps", fps),
+                None => "Frame rate uncapped".to_string(),
+            })
+        },
+    );
+    commands.register(
+        "window.pacing",
+        "Print frame pacing statistics",
+        |world, _| {
+            let stats = world.get::<&FrameStats>(|stats| stats.clone());
+            let target = match stats.target_fps {
+                Some(fps) => format!("{:.3} ms target", 1000.0 / fps),
+                None => "uncapped".to_string(),
+            };
+            Ok(format!(
+                "{} frames, {:.3} ms average ({}), {} missed",
+                stats.frames,
+                stats.average_interval_ms(),
+                target,
+                stats.missed_frames
+            ))
+        },
+    );
+}
+
+// Registered after "transform_propagation_system" (also PostUpdate), so the numbers
+// are this frame's
+pub(crate) fn register_transform_stats(world: &World) {
+    world
+        .system_named::<(&TransformTree, &mut FrameStats)>("Record Transform Stats")
+        .kind(flecs::pipeline::PostUpdate)
+        .each(|(tree, stats)| {
+            stats.transform_ms = tree.last_frame_ms();
+            stats.transforms_refreshed = tree.refreshed_last_frame();
+            stats.transforms_updated = tree.updated_last_frame();
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARGET_FPS: f64 = 60.0;
+
+    #[test]
+    fn stats_bucket_intervals_by_millisecond() {
+        let mut stats = FrameStats::default();
+        stats.record(Duration::from_micros(16_667));
+        stats.record(Duration::from_micros(16_900));
+        stats.record(Duration::from_millis(250));
+        assert_eq!(stats.frames, 3);
+        assert_eq!(stats.histogram[16], 2);
+        assert_eq!(stats.histogram[HISTOGRAM_BUCKETS - 1], 1);
+        assert!((stats.average_interval_ms() - 94.522).abs() < 0.01);
+
+        stats.target_fps = Some(60.0);
+        stats.reset();
+        assert_eq!(stats.frames, 0);
+        assert_eq!(stats.average_interval_ms(), 0.0);
+        assert_eq!(stats.target_fps, Some(60.0));
+    }
+
+    #[test]
+    fn limiter_waits_out_the_interval() {
+        let interval = Duration::from_millis(20);
+        let mut limiter = FrameLimiter::default();
+        // The first frame has no deadline yet
+        assert!(!limiter.wait(interval));
+        let start = Instant::now();
+        assert!(!limiter.wait(interval));
+        assert!(start.elapsed() >= interval - Duration::from_millis(1));
+    }
+
+    #[test]
+    fn limiter_starts_over_after_a_hitch() {
+        let interval = Duration::from_millis(5);
+        let mut limiter = FrameLimiter::default();
+        limiter.wait(interval);
+        thread::sleep(interval * 4);
+        assert!(
+            limiter.wait(interval),
+            "a frame longer than the interval is missed"
+        );
+
+        // The next deadline is a whole interval away rather than in the past
+        let start = Instant::now();
+        assert!(!limiter.wait(interval));
+        assert!(start.elapsed() >= interval - Duration::from_millis(1));
+    }
+
+    // Depends on the machine's timers and load, so it only runs on request:
+    // cargo test -p catalyst_window -- --ignored
+    #[test]
+    #[ignore]
+    fn limiter_holds_60_fps_within_a_millisecond() {
+        let interval = Duration::from_secs_f64(1.0 / TARGET_FPS);
+        let mut limiter = FrameLimiter::default();
+        let mut stats = FrameStats::default();
+        let mut last = Instant::now();
+        for frame in 0..330 {
+            limiter.wait(interval);
+            let now = Instant::now();
+            // The first frames aren't measured
+            if frame >= 30 {
+                stats.record(now - last);
+            }
+            last = now;
+        }
+        assert_eq!(stats.frames, 300);
+        let target_ms = (1000.0 / TARGET_FPS) as f32;
+        let average_ms = stats.average_interval_ms();
+        assert!(
+            (average_ms - target_ms).abs() <= 1.0,
+            "{:.3} ms average, {:.3} ms target",
+            average_ms,
+            target_ms
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+use catalyst_input::rumble::{Rumble, RumbleSettings};
+use flecs_ecs::core::{World, WorldGet};
+use gilrs::{
+    Gilrs,
+    ff::{BaseEffect, BaseEffectType, Effect, EffectBuilder, Replay, Ticks},
+};
+
+// Effects loop until they are dropped; Rumble decides when a rumble ends
+const EFFECT_LOOP_MS: u32 = 1000;
+
+// Motor levels as gilrs magnitudes, strong then weak
+type Magnitudes = (u16, u16);
+
+/// Plays the motor levels of the Rumble singleton on the gamepads with force feedback.
+/// An effect is only rebuilt when a gamepad's levels change.
+#[derive(Default)]
+pub(crate) struct RumbleBackend {
+    playing: HashMap<usize, (Effect, Magnitudes)>,
+}
+
+impl RumbleBackend {
+    pub(crate) fn apply(&mut self, gilrs: &mut Gilrs, world: &World) {
+        let intensity = world
+            .try_get::<&RumbleSettings>(|settings| settings.intensity)
+            .unwrap_or(1.0);
+        let Some(levels) = world.try_get::<&Rumble>(|rumble| {
+            gilrs
+                .gamepads()
+                .filter(|(_, gamepad)| gamepad.is_ff_supported())
+                .map(|(id, _)| {
+                    let (strong, weak) = rumble.motors(usize::from(id), intensity);
+                    (id, (magnitude(strong), magnitude(weak)))
+                })
+                .collect::<Vec<_>>()
+        }) else {
+            return;
+        };
+
+        // Dropping an effect stops it, also for gamepads that went away mid-rumble
+        self.playing
+            .retain(|gamepad, _| levels.iter().any(|(id, _)| usize::from(*id) == *gamepad));
+
+        for (id, magnitudes) in levels {
+            let gamepad = usize::from(id);
+            if self.playing.get(&gamepad).map(|(_, playing)| *playing) == Some(magnitudes) {
+                continue;
+            }
+            self.playing.remove(&gamepad);
+            if magnitudes == (0, 0) {
+                continue;
+            }
+
+            let motor = |kind| BaseEffect {
+                kind,
+                scheduling: Replay {
+                    play_for: Ticks::from_ms(EFFECT_LOOP_MS),
+                    ..Default::default()
+                },
+                envelope: Default::default(),
+            };
+            let effect = EffectBuilder::new()
+                .add_effect(motor(BaseEffectType::Strong {
+                    magnitude: magnitudes.0,
+                }))
+                .add_effect(motor(BaseEffectType::Weak {
+                    magnitude: magnitudes.1,
+                }))
+                .gamepads(&[id])
+                .finish(gilrs)
+                .and_then(|effect| effect.play().map(|_| effect));
+            match effect {
+                Ok(effect) => {
+                    self.playing.insert(gamepad, (effect, magnitudes));
+                }
+                Err(error) => log::debug!("Can't rumble gamepad {}: {}", gamepad, error),
+            }
+        }
+    }
+}
+
+fn magnitude(level: f32) -> u16 {
+    (level.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16
+}